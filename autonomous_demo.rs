@@ -1,59 +1,27 @@
 //! Autonomous SDLC Execution Demo - MCP WASM Edge Gateway
 //! Demonstrates complete implementation with all generations
 
-use std::collections::HashMap;
+use mcp_wasm_edge_gateway::compat::{demo_gateway, demo_request};
+use serde_json::json;
+use std::time::Instant;
 
-/// Core Edge Gateway Implementation
-struct MCPEdgeGateway {
-    version: String,
-    requests_processed: u32,
-    models_loaded: u32,
-    security_events: u32,
-}
-
-impl MCPEdgeGateway {
-    fn new() -> Self {
-        Self {
-            version: "0.1.0".to_string(),
-            requests_processed: 0,
-            models_loaded: 3,
-            security_events: 0,
-        }
-    }
-
-    fn process_request(&mut self, method: &str, complexity: f32) -> String {
-        self.requests_processed += 1;
-        
-        let routing = if complexity > 0.8 { "Cloud" } 
-                     else if complexity > 0.4 { "Local" } 
-                     else { "Queue" };
-        
-        let latency = match routing {
-            "Cloud" => 800,
-            "Local" => 150,
-            _ => 0,
-        };
-
-        format!("{} -> {} ({}ms)", method, routing, latency)
-    }
-}
-
-fn main() {
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
     println!("🌟 MCP WASM Edge Gateway - Autonomous SDLC Complete!");
     println!("===================================================");
-    
-    let mut gateway = MCPEdgeGateway::new();
-    
+
+    let gateway = demo_gateway().await?;
+
     println!("✅ GENERATION 1: MAKE IT WORK - Complete");
     println!("  🔧 Basic gateway functionality implemented");
     println!("  🧠 Intelligent routing system active");
     println!("  📦 Multi-model support enabled");
-    
+
     println!("\n✅ GENERATION 2: MAKE IT ROBUST - Complete");
     println!("  🔒 Enterprise security implemented");
     println!("  📊 Comprehensive telemetry active");
     println!("  ⚡ Error handling & validation");
-    
+
     println!("\n✅ GENERATION 3: MAKE IT SCALE - Complete");
     println!("  🚀 Performance optimizations");
     println!("  🌐 Global deployment ready");
@@ -61,22 +29,43 @@ fn main() {
 
     println!("\n📈 Live Demo:");
     let demos = vec![
-        ("embedding", 0.3),
-        ("completion", 0.6), 
-        ("reasoning", 0.9),
+        ("embedding", "Index this maintenance log entry"),
+        ("completion", "Summarize the last shift's sensor alerts"),
+        ("chat", "Plan a predictive maintenance schedule for the whole plant, with failure probabilities per machine"),
     ];
-    
-    for (method, complexity) in demos {
-        let result = gateway.process_request(method, complexity);
-        println!("  {}", result);
+
+    let mut requests_processed = 0;
+    for (method, prompt) in demos {
+        let started = Instant::now();
+        let response = gateway
+            .process_request(demo_request(
+                "edge-demo",
+                method,
+                json!({ "prompt": prompt }),
+            ))
+            .await;
+        requests_processed += 1;
+        match response {
+            Ok(response) => println!(
+                "  {} -> {} ({}ms)",
+                method,
+                response.result.unwrap_or_default(),
+                started.elapsed().as_millis()
+            ),
+            Err(e) => println!("  {} -> ❌ {}", method, e),
+        }
     }
 
     println!("\n🎯 Final Status:");
-    println!("  📊 Requests: {}", gateway.requests_processed);
-    println!("  🧠 Models: {}", gateway.models_loaded);
+    println!("  📊 Requests: {}", requests_processed);
+    println!(
+        "  🧠 Models: {}",
+        gateway.model_engine().list_models().await?.len()
+    );
     println!("  🔒 Security: Active");
     println!("  ⚡ Performance: Optimized");
-    
+
     println!("\n🚀 AUTONOMOUS SDLC EXECUTION: SUCCESSFUL!");
     println!("Ready for production deployment! 🌟");
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! MCP API - Stable component API for MCP Edge Gateway plugins
+//!
+//! Embedders replace gateway subsystems with their own implementations of
+//! the component traits and hand them to `mcp_gateway::GatewayBuilder`.
+//! Those traits live in the individual component crates, whose public
+//! surface otherwise changes with every gateway release. This crate pins
+//! down the part a plugin may rely on: everything re-exported from a
+//! versioned module such as [`v1`] keeps its signatures for as long as that
+//! module exists, and the gateway accepts components through the same
+//! re-exports, so a plugin built against `mcp_api::v1` keeps compiling and
+//! loading across minor gateway upgrades.
+//!
+//! Models served by a separate process use the frame protocol versioned by
+//! `mcp_models::PLUGIN_ABI_VERSION` instead.
+//!
+//! # Compatibility
+//!
+//! | API   | Gateway | Traits                                                              |
+//! |-------|---------|---------------------------------------------------------------------|
+//! | 1.0   | 0.1     | `Router`, `CloudTransport`, `ModelEngine`, `OfflineQueue`, `SecurityManager`, `TelemetryCollector` |
+//! | 1.1   | 0.1     | Adds `SecurityManager::attestation`, `DeviceAttestor`, `PlatformQuote` |
+//! | 1.2   | 0.1     | Adds `ModelEngine::retire_model`                                    |
+//!
+//! Within a major version, a minor release may add trait methods only when
+//! they have a default implementation, and may add re-exports; it never
+//! removes or changes anything. A breaking change ships as a new module
+//! (`v2`) next to the old one, which stays available for at least one minor
+//! gateway release. [`API_VERSION`] is the version this build provides; a
+//! plugin states the version it needs with [`require_api!`] and fails to
+//! compile against an older or incompatible facade.
+//!
+//! # Feature matrix
+//!
+//! Each component trait sits behind a feature, so a plugin only pulls in the
+//! crates it implements against. All are enabled by default.
+//!
+//! | Feature     | Re-exports                                                   | Crate           |
+//! |-------------|--------------------------------------------------------------|-----------------|
+//! | `router`    | `Router`, `CloudTransport`, `SharedRouter`, `SharedCloudTransport` | `mcp-router`    |
+//! | `models`    | `ModelEngine`, `TokenStream`, `StreamChunk`, `ModelListing`, `SharedModelEngine` | `mcp-models`    |
+//! | `queue`     | `OfflineQueue`, `QueuePurge`, `SharedQueue`                  | `mcp-queue`     |
+//! | `security`  | `SecurityManager`, `DeviceAttestor`, `PlatformQuote`, `SharedSecurityManager` | `mcp-security`  |
+//! | `telemetry` | `TelemetryCollector`, `PrometheusEncoder`, `SharedTelemetryCollector` | `mcp-telemetry` |
+//! | `fips`      | Builds the shared crypto backend on the FIPS-validated module | `mcp-common`    |
+//! | `hardware-security` | Holds the device key in a TPM 2.0; implies `security` | `mcp-security`  |
+//!
+//! The request and response types, [`v1::Config`], [`v1::Error`] and
+//! [`v1::Result`] are always available.
+
+pub mod v1;
+mod version;
+
+pub use version::{ApiVersion, API_VERSION};
+
+/// Fail compilation unless this facade provides API `major.minor`
+///
+/// ```
+/// mcp_api::require_api!(1, 0);
+/// ```
+#[macro_export]
+macro_rules! require_api {
+    ($major:literal, $minor:literal) => {
+        const _: () = assert!(
+            $crate::API_VERSION.supports($crate::ApiVersion::new($major, $minor)),
+            "mcp-api does not provide the required API version"
+        );
+    };
+}
@@ -0,0 +1,146 @@
+//! Component API, version 1
+//!
+//! Implement a trait with the re-exported [`async_trait`] macro and pass the
+//! component to the gateway builder as the matching `Shared*` handle.
+
+pub use async_trait::async_trait;
+pub use mcp_common::events::EventSubscriber;
+pub use mcp_common::metrics::{AggregatedMetrics, ComponentHealth, HealthLevel};
+pub use mcp_common::usage::{ResourceUsage, TenantUsage};
+pub use mcp_common::{
+    Config, Error, MCPRequest, MCPResponse, ModelId, PerformanceMetrics, Priority, RequestContext, RequestId,
+    RequestPrioritizer, Result, RoutingDecision,
+};
+
+#[cfg(feature = "router")]
+pub use mcp_router::{CloudTransport, Router};
+
+#[cfg(feature = "models")]
+pub use mcp_models::{buffered_stream, ModelEngine, ModelListing, ModelProvenance, StreamChunk, TokenStream};
+
+#[cfg(feature = "queue")]
+pub use mcp_queue::{OfflineQueue, QueuePurge};
+
+#[cfg(feature = "security")]
+pub use mcp_security::{DeviceAttestor, PlatformQuote, SecurityManager};
+
+#[cfg(feature = "telemetry")]
+pub use mcp_telemetry::{MetricKind, PrometheusEncoder, TelemetryCollector};
+
+pub type SharedPrioritizer = std::sync::Arc<dyn RequestPrioritizer>;
+#[cfg(feature = "router")]
+pub type SharedRouter = std::sync::Arc<dyn Router + Send + Sync>;
+#[cfg(feature = "router")]
+pub type SharedCloudTransport = std::sync::Arc<dyn CloudTransport + Send + Sync>;
+#[cfg(feature = "models")]
+pub type SharedModelEngine = std::sync::Arc<dyn ModelEngine + Send + Sync>;
+#[cfg(feature = "queue")]
+pub type SharedQueue = std::sync::Arc<dyn OfflineQueue + Send + Sync>;
+#[cfg(feature = "security")]
+pub type SharedSecurityManager = std::sync::Arc<dyn SecurityManager + Send + Sync>;
+#[cfg(feature = "telemetry")]
+pub type SharedTelemetryCollector = std::sync::Arc<dyn TelemetryCollector + Send + Sync>;
+
+/// Compile-time checks of the v1 contract
+///
+/// Every method of every trait is called here with the argument and return
+/// types v1 promises, through a `Send + Sync` trait object, and each future
+/// must be `Send`. Removing a method, changing a signature or making a trait
+/// unusable as an object breaks the build of this crate before it can break
+/// a plugin.
+#[allow(dead_code, unused_imports)]
+mod contract {
+    use super::*;
+    use std::future::Future;
+
+    fn send<T: Send>(future: impl Future<Output = T> + Send) -> impl Future<Output = T> + Send {
+        future
+    }
+
+    fn prioritizer(prioritizer: SharedPrioritizer, context: &RequestContext) {
+        let _: (Priority, u32) = prioritizer.prioritize(context);
+        let _: SharedPrioritizer = std::sync::Arc::new(|_: &RequestContext| (Priority::High, 1));
+    }
+
+    #[cfg(feature = "router")]
+    async fn router(
+        router: SharedRouter,
+        transport: SharedCloudTransport,
+        request: &MCPRequest,
+        metrics: &PerformanceMetrics,
+    ) {
+        let _: Result<RoutingDecision> = send(router.route(request)).await;
+        let _: Result<MCPResponse> = send(router.forward_to_cloud(request, "primary")).await;
+        let _: Result<MCPResponse> = send(router.fallback_to_cloud(request)).await;
+        let _: Vec<ModelId> = router.available_models();
+        let _: Result<()> = send(router.update_metrics(metrics)).await;
+        let _: Result<ComponentHealth> = send(router.health_check()).await;
+        let _: Result<()> = send(router.shutdown()).await;
+
+        let _: Result<MCPResponse> = send(transport.send_request("https://cloud.example", request)).await;
+        let _: Result<()> = send(transport.shutdown()).await;
+    }
+
+    #[cfg(feature = "models")]
+    async fn model_engine(engine: SharedModelEngine, request: &MCPRequest, model_id: &ModelId) {
+        let _: Result<MCPResponse> = send(engine.process_request(request, model_id)).await;
+        let _: Result<TokenStream> = send(engine.process_request_streaming(request, model_id)).await;
+        let _: Result<Vec<ModelListing>> = send(engine.list_models()).await;
+        let _: Vec<ModelProvenance> = send(engine.model_provenance()).await;
+        let _: Result<()> = send(engine.load_model(model_id)).await;
+        let _: Result<()> = send(engine.unload_model(model_id)).await;
+        let _: Result<()> = send(engine.retire_model(model_id, std::time::Duration::from_secs(1))).await;
+        let _: Result<ComponentHealth> = send(engine.health_check()).await;
+        let _: Result<()> = send(engine.shutdown()).await;
+    }
+
+    #[cfg(feature = "queue")]
+    async fn queue(queue: SharedQueue, request: MCPRequest, cutoff: chrono::DateTime<chrono::Utc>) {
+        let _: Result<MCPResponse> = send(queue.enqueue_request(request)).await;
+        let _: Result<Option<MCPRequest>> = send(queue.dequeue_request()).await;
+        let _: Result<u32> = send(queue.queue_size()).await;
+        let _: Result<()> = send(queue.sync_with_cloud()).await;
+        let _: Result<QueuePurge> = send(queue.purge(cutoff, &[], true)).await;
+        let selector = |request: &MCPRequest| request.device_id.is_empty();
+        let _: Result<Vec<uuid::Uuid>> = send(queue.erase(&selector, true)).await;
+        let _: Result<EventSubscriber> = queue.subscribe();
+        let _: Result<ComponentHealth> = send(queue.health_check()).await;
+        let _: Result<()> = send(queue.shutdown()).await;
+    }
+
+    #[cfg(feature = "security")]
+    async fn security_manager(security: SharedSecurityManager, request: &MCPRequest) {
+        let _: Result<()> = send(security.validate_request(request)).await;
+        let _: Result<Vec<u8>> = send(security.encrypt_data(b"data")).await;
+        let _: Result<Vec<u8>> = send(security.decrypt_data(b"data")).await;
+        let _ = security.restrictions().is_some();
+        let _ = security.auth_guard().is_some();
+        let _ = security.enrollment().is_some();
+        let _ = security.keyring().is_some();
+        if let Some(attestation) = security.attestation() {
+            let _: Result<PlatformQuote> = send(attestation.quote(b"nonce")).await;
+        }
+        let _: Result<ComponentHealth> = send(security.health_check()).await;
+        let _: Result<()> = send(security.shutdown()).await;
+    }
+
+    #[cfg(feature = "telemetry")]
+    async fn telemetry_collector(
+        telemetry: SharedTelemetryCollector,
+        response: &MCPResponse,
+        usage: &ResourceUsage,
+        encoder: &mut PrometheusEncoder<'_>,
+    ) {
+        let id = response.id;
+        send(telemetry.record_request_success(id, response)).await;
+        send(telemetry.record_request_error(id, &Error::Internal("failed".to_string()))).await;
+        send(telemetry.record_request_latency(std::time::Duration::from_millis(1))).await;
+        send(telemetry.record_clock_skew("device", 0)).await;
+        send(telemetry.record_request_usage(id, "tenant", usage, false)).await;
+        let _: std::collections::HashMap<String, TenantUsage> = send(telemetry.usage_by_tenant()).await;
+        let _: Result<AggregatedMetrics> = send(telemetry.get_aggregated_metrics()).await;
+        send(telemetry.write_prometheus(encoder)).await;
+        let _: Result<ComponentHealth> = send(telemetry.health_check()).await;
+        let _: Result<()> = send(telemetry.shutdown()).await;
+    }
+}
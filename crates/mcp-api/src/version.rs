@@ -0,0 +1,80 @@
+//! API versioning
+
+use mcp_common::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Version of the component API this build provides
+pub const API_VERSION: ApiVersion = ApiVersion::new(1, 2);
+
+/// Component API version, `major.minor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ApiVersion {
+    pub const fn new(major: u16, minor: u16) -> Self {
+        Self { major, minor }
+    }
+
+    /// Whether a plugin built for `required` works with this version: the
+    /// major versions match and nothing newer than this minor is needed
+    pub const fn supports(&self, required: ApiVersion) -> bool {
+        self.major == required.major && self.minor >= required.minor
+    }
+
+    /// Error unless this version supports `required`
+    pub fn check(&self, required: ApiVersion) -> Result<()> {
+        if self.supports(required) {
+            return Ok(());
+        }
+        Err(Error::Configuration(format!(
+            "Plugin requires component API {}, but this gateway provides {}",
+            required, self
+        )))
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = Error;
+
+    fn from_str(version: &str) -> Result<Self> {
+        let invalid = || Error::Configuration(format!("Invalid API version '{}', expected major.minor", version));
+        let (major, minor) = version.trim().split_once('.').ok_or_else(invalid)?;
+        Ok(Self::new(major.parse().map_err(|_| invalid())?, minor.parse().map_err(|_| invalid())?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minor_versions_are_backward_compatible() {
+        let provided = ApiVersion::new(1, 2);
+        assert!(provided.supports(ApiVersion::new(1, 0)));
+        assert!(provided.supports(ApiVersion::new(1, 2)));
+        assert!(!provided.supports(ApiVersion::new(1, 3)));
+        assert!(!provided.supports(ApiVersion::new(2, 0)));
+        assert!(!ApiVersion::new(2, 0).supports(ApiVersion::new(1, 0)));
+        assert!(provided.check(ApiVersion::new(1, 3)).is_err());
+        assert!(API_VERSION.check(API_VERSION).is_ok());
+    }
+
+    #[test]
+    fn test_parses_and_formats_versions() {
+        assert_eq!("1.4".parse::<ApiVersion>().unwrap(), ApiVersion::new(1, 4));
+        assert_eq!(ApiVersion::new(2, 10).to_string(), "2.10");
+        for invalid in ["", "1", "1.x", "1.2.3", "-1.0"] {
+            assert!(invalid.parse::<ApiVersion>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Bandwidth saved by compressing cloud sync payloads
+//!
+//! Measures zstd and lz4 on the bodies the gateway uploads — a single
+//! forwarded completion and a backlog of queued requests replayed after an
+//! outage — and prints how long each takes to send over constrained
+//! cellular links, so the codec CPU cost can be weighed against airtime.
+//!
+//! Run with `cargo bench -p mcp-common --bench payload_compression`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mcp_common::compression::{self, PayloadCompression};
+use mcp_common::{MCPRequest, RequestContext};
+use std::collections::HashMap;
+use std::hint::black_box;
+
+/// Uplink rates in kbit/s of the links edge gateways commonly sit behind
+const LINKS: &[(&str, f64)] = &[("NB-IoT", 60.0), ("LTE-M", 375.0), ("3G", 2_000.0)];
+
+const CODECS: &[PayloadCompression] = &[
+    PayloadCompression::None,
+    PayloadCompression::Lz4,
+    PayloadCompression::Zstd,
+];
+
+fn request(i: usize) -> serde_json::Value {
+    let request = MCPRequest {
+        id: uuid::Uuid::new_v4(),
+        device_id: format!("press-line-{}", i % 8),
+        method: "completion".to_string(),
+        params: HashMap::from([
+            (
+                "prompt".to_string(),
+                serde_json::json!(format!(
+                    "Station {} reported spindle vibration of {}.{} mm/s and a bearing temperature \
+                     of {} C over the last shift. Summarize whether maintenance is needed and \
+                     which checks the operator should run before the next shift starts.",
+                    i % 8,
+                    3 + i % 5,
+                    i % 10,
+                    60 + i % 15
+                )),
+            ),
+            ("max_tokens".to_string(), serde_json::json!(256)),
+            ("temperature".to_string(), serde_json::json!(0.2)),
+        ]),
+        context: Some(RequestContext::default()),
+        timestamp: chrono::Utc::now(),
+    };
+    let mut value = serde_json::to_value(request).unwrap();
+    value["_queue_metadata"] = serde_json::json!({
+        "queued_at": chrono::Utc::now(),
+        "retry_count": i % 3,
+        "priority_score": 50.0,
+    });
+    value
+}
+
+fn payloads() -> Vec<(&'static str, Vec<u8>)> {
+    let backlog: Vec<serde_json::Value> = (0..100).map(request).collect();
+    vec![
+        ("single_request", serde_json::to_vec(&request(0)).unwrap()),
+        ("queue_backlog_100", serde_json::to_vec(&backlog).unwrap()),
+    ]
+}
+
+fn report_bandwidth(payloads: &[(&str, Vec<u8>)]) {
+    for (name, body) in payloads {
+        println!("\n{} ({} bytes uncompressed)", name, body.len());
+        for codec in CODECS {
+            let encoded = compression::encode(*codec, body.clone());
+            let airtime: Vec<String> = LINKS
+                .iter()
+                .map(|(link, kbps)| {
+                    format!("{} {:.0}ms", link, encoded.body.len() as f64 * 8.0 / kbps)
+                })
+                .collect();
+            println!(
+                "  {:<5} {:>7} bytes ({:>5.1}% saved)  {}",
+                format!("{:?}", codec).to_lowercase(),
+                encoded.body.len(),
+                100.0 * (1.0 - encoded.body.len() as f64 / body.len() as f64),
+                airtime.join(", ")
+            );
+        }
+    }
+}
+
+fn bench_compression(c: &mut Criterion) {
+    let payloads = payloads();
+    report_bandwidth(&payloads);
+
+    let mut group = c.benchmark_group("payload_compression");
+    for (name, body) in &payloads {
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        for codec in &CODECS[1..] {
+            let id = format!("{:?}", codec).to_lowercase();
+            group.bench_with_input(
+                BenchmarkId::new(format!("compress/{}", id), name),
+                body,
+                |b, body| b.iter(|| codec.compress(black_box(body)).unwrap()),
+            );
+            let compressed = codec.compress(body).unwrap();
+            group.bench_with_input(
+                BenchmarkId::new(format!("decompress/{}", id), name),
+                &compressed,
+                |b, data| b.iter(|| codec.decompress(black_box(data)).unwrap()),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_compression);
+criterion_main!(benches);
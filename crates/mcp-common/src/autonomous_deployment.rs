@@ -9,7 +9,6 @@ use std::{
     time::{Duration, SystemTime},
 };
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
 use uuid::Uuid;
 
 /// Autonomous deployment orchestrator with intelligent release management
@@ -21,7 +20,7 @@ pub struct AutonomousDeploymentOrchestrator {
     rollback_system: Arc<AutomatedRollbackSystem>,
     approval_system: Arc<ApprovalSystem>,
     deployment_history: Arc<RwLock<Vec<DeploymentEvent>>>,
-    event_broadcaster: broadcast::Sender<DeploymentEvent>,
+    config: crate::config::DeploymentConfig,
 }
 
 /// Core deployment execution engine
@@ -1259,8 +1258,11 @@ pub struct VulnerabilityUpdateResult {
 
 impl AutonomousDeploymentOrchestrator {
     pub fn new() -> Self {
-        let (event_broadcaster, _) = broadcast::channel(1000);
-        
+        Self::with_config(crate::config::DeploymentConfig::default())
+    }
+
+    /// Orchestrator gated by the `deployment` section
+    pub fn with_config(config: crate::config::DeploymentConfig) -> Self {
         Self {
             deployment_engine: Arc::new(DeploymentEngine::new()),
             release_manager: Arc::new(ReleaseManager::new()),
@@ -1269,7 +1271,7 @@ impl AutonomousDeploymentOrchestrator {
             rollback_system: Arc::new(AutomatedRollbackSystem::new()),
             approval_system: Arc::new(ApprovalSystem::new()),
             deployment_history: Arc::new(RwLock::new(Vec::new())),
-            event_broadcaster,
+            config,
         }
     }
 
@@ -1306,22 +1308,27 @@ impl AutonomousDeploymentOrchestrator {
             details: HashMap::new(),
         };
         
-        self.deployment_history.write().unwrap().push(event.clone());
-        let _ = self.event_broadcaster.send(event);
+        crate::events::publish(crate::events::GatewayEvent::DeploymentFinished {
+            deployment_id: event.deployment_id,
+            version: event.version.clone(),
+            success: result.success,
+        });
+        self.deployment_history.write().unwrap().push(event);
         
         Ok(result)
     }
 
     async fn validate_deployment_plan(&self, plan: &DeploymentPlan) -> crate::Result<()> {
         // Validate artifact security
-        if plan.artifact.security_scan_results.critical_vulnerabilities > 0 {
+        let critical = plan.artifact.security_scan_results.critical_vulnerabilities;
+        if critical > self.config.max_critical_vulnerabilities {
             return Err(crate::Error::Validation(
                 "Deployment blocked: Critical vulnerabilities found in artifact".to_string()
             ));
         }
         
         // Validate test coverage
-        if plan.artifact.test_results.test_coverage < 80.0 {
+        if plan.artifact.test_results.test_coverage < self.config.min_test_coverage_percent {
             tracing::warn!("Low test coverage: {}%", plan.artifact.test_results.test_coverage);
         }
         
@@ -9,9 +9,11 @@ use std::{
     time::{Duration, SystemTime},
 };
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
 use uuid::Uuid;
 
+use crate::clock::{self, Clock};
+use crate::scaling_trends::{MetricsWindow, ScalingMetric, TrendFeatures};
+
 /// Autonomous scaling orchestrator with ML-powered predictions
 pub struct AutonomousScalingOrchestrator {
     scaling_engine: Arc<ScalingEngine>,
@@ -21,7 +23,9 @@ pub struct AutonomousScalingOrchestrator {
     decision_engine: Arc<ScalingDecisionEngine>,
     execution_engine: Arc<ScalingExecutionEngine>,
     scaling_history: Arc<RwLock<VecDeque<ScalingEvent>>>,
-    event_broadcaster: broadcast::Sender<ScalingEvent>,
+    /// Samples decisions are based on
+    metrics_window: Arc<RwLock<MetricsWindow>>,
+    clock: Arc<dyn Clock>,
     config: ScalingConfiguration,
 }
 
@@ -51,7 +55,7 @@ pub struct CostOptimizer {
 
 /// Real-time resource monitoring with edge-specific metrics
 pub struct ResourceMonitor {
-    metrics_collectors: Vec<Arc<dyn MetricsCollector>>,
+    metrics_collectors: RwLock<Vec<Arc<dyn MetricsCollector>>>,
     aggregation_engine: Arc<MetricsAggregationEngine>,
     alerting_system: Arc<ResourceAlertingSystem>,
     telemetry_reporter: Arc<TelemetryReporter>,
@@ -59,6 +63,8 @@ pub struct ResourceMonitor {
 
 /// Intelligent decision engine with multi-criteria optimization
 pub struct ScalingDecisionEngine {
+    /// How far ahead trends are projected, normally until the next decision
+    lookahead: Duration,
     decision_models: Arc<RwLock<HashMap<String, Box<dyn DecisionModel>>>>,
     criteria_weights: Arc<RwLock<CriteriaWeights>>,
     optimization_algorithm: Arc<dyn OptimizationAlgorithm>,
@@ -217,6 +223,8 @@ impl ScalingStrategyExecutor for DefaultScalingStrategy {
 pub struct ScalingContext {
     pub current_resources: ResourceConfiguration,
     pub current_metrics: ResourceMetrics,
+    /// Trends over the recent samples, which decisions are based on
+    pub trends: TrendFeatures,
     pub workload_characteristics: WorkloadCharacteristics,
     pub constraints: ScalingConstraints,
     pub historical_data: HistoricalScalingData,
@@ -236,7 +244,7 @@ pub struct ResourceMetrics {
     pub edge_specific: EdgeMetrics,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct EdgeMetrics {
     pub battery_level: Option<f64>,
     pub temperature_celsius: Option<f64>,
@@ -801,10 +809,37 @@ pub struct ScalingConfiguration {
     pub default_strategy: ScalingStrategy,
     pub prediction_horizon: Duration,
     pub decision_frequency: Duration,
+    /// Rolling window of metrics samples that trend features cover
+    pub trend_window: Duration,
     pub safety_margins: SafetyMargins,
     pub learning_parameters: LearningParameters,
 }
 
+impl From<&crate::config::ScalingConfig> for ScalingConfiguration {
+    fn from(config: &crate::config::ScalingConfig) -> Self {
+        Self {
+            scaling_policies: Vec::new(),
+            default_strategy: config.strategy.clone(),
+            prediction_horizon: Duration::from_secs(config.prediction_horizon_secs),
+            decision_frequency: Duration::from_secs(config.decision_interval_secs),
+            trend_window: Duration::from_secs(config.trend_window_secs),
+            safety_margins: SafetyMargins {
+                resource_utilization_margin: config.resource_margin,
+                performance_margin: config.performance_margin,
+                cost_margin: config.cost_margin,
+                availability_margin: config.availability_margin,
+            },
+            learning_parameters: LearningParameters {
+                learning_rate: config.learning_rate,
+                exploration_rate: config.exploration_rate,
+                memory_window: Duration::from_secs(config.memory_window_secs),
+                model_update_frequency: Duration::from_secs(config.model_update_interval_secs),
+                feedback_incorporation_delay: Duration::from_secs(config.feedback_delay_secs),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ScalingPolicy {
     pub policy_id: Uuid,
@@ -1560,32 +1595,50 @@ pub struct ExpectedOutcome {
 
 impl AutonomousScalingOrchestrator {
     pub fn new(config: ScalingConfiguration) -> Self {
-        let (event_broadcaster, _) = broadcast::channel(1000);
-        
+        Self::with_clock(config, clock::system_clock())
+    }
+
+    /// Orchestrator timestamping metrics samples with `clock`
+    pub fn with_clock(config: ScalingConfiguration, clock: Arc<dyn Clock>) -> Self {
         Self {
             scaling_engine: Arc::new(ScalingEngine::new()),
             predictive_analyzer: Arc::new(PredictiveScalingAnalyzer::new()),
             cost_optimizer: Arc::new(CostOptimizer::new()),
             resource_monitor: Arc::new(ResourceMonitor::new()),
-            decision_engine: Arc::new(ScalingDecisionEngine::new()),
+            decision_engine: Arc::new(ScalingDecisionEngine::with_lookahead(config.decision_frequency)),
             execution_engine: Arc::new(ScalingExecutionEngine::new()),
             scaling_history: Arc::new(RwLock::new(VecDeque::with_capacity(10000))),
-            event_broadcaster,
+            metrics_window: Arc::new(RwLock::new(MetricsWindow::new(config.trend_window))),
+            clock,
             config,
         }
     }
 
+    /// Add a source of the readings decisions are based on
+    pub fn add_metrics_collector(&self, collector: Arc<dyn MetricsCollector>) {
+        self.resource_monitor.add_collector(collector);
+    }
+
+    /// Orchestrator configured by the `scaling` section
+    pub fn from_config(config: &crate::Config) -> Self {
+        Self::new(ScalingConfiguration::from(&config.scaling))
+    }
+
     pub async fn evaluate_scaling_need(&self) -> crate::Result<Option<ScalingDecision>> {
         tracing::debug!("Evaluating scaling needs");
         
-        // Collect current metrics
-        let current_metrics = self.resource_monitor.collect_metrics().await?;
+        // Collect current metrics and diff them against the recent samples
+        let (current_metrics, trends) = self.sample_metrics().await?;
+        if trends.samples < 2 {
+            tracing::debug!("Waiting for a second metrics sample before deciding on scaling");
+            return Ok(None);
+        }
         
         // Analyze workload patterns
         let workload_analysis = self.predictive_analyzer.analyze_workload(&current_metrics).await?;
         
         // Generate scaling recommendations
-        let context = self.build_scaling_context(&current_metrics, &workload_analysis).await?;
+        let context = self.build_scaling_context(&current_metrics, trends, &workload_analysis).await?;
         let decision = self.decision_engine.make_decision(&context).await?;
         
         // Validate the decision
@@ -1620,13 +1673,34 @@ impl AutonomousScalingOrchestrator {
             performance_impact: result.performance_impact.clone(),
         };
         
-        self.scaling_history.write().unwrap().push_back(event.clone());
-        let _ = self.event_broadcaster.send(event);
+        crate::events::publish(crate::events::GatewayEvent::ScalingApplied {
+            action: format!("{:?}", event.event_type),
+        });
+        self.scaling_history.write().unwrap().push_back(event);
         
         Ok(result)
     }
 
-    async fn build_scaling_context(&self, metrics: &ResourceMetrics, workload_analysis: &WorkloadAnalysis) -> crate::Result<ScalingContext> {
+    /// Collect a metrics sample and record it in the window
+    async fn sample_metrics(&self) -> crate::Result<(ResourceMetrics, TrendFeatures)> {
+        let metrics = self.resource_monitor.collect_metrics().await?;
+        let mut window = self.metrics_window.write().unwrap();
+        if let Some(delta) = window.record(self.clock.now(), metrics.clone()) {
+            tracing::debug!(
+                "CPU utilization changing by {:.3}/s, queue depth by {:.3}/s",
+                delta.change(ScalingMetric::CpuUtilization).per_sec,
+                delta.change(ScalingMetric::QueueDepth).per_sec
+            );
+        }
+        Ok((metrics, window.features().unwrap_or_default()))
+    }
+
+    async fn build_scaling_context(
+        &self,
+        metrics: &ResourceMetrics,
+        trends: TrendFeatures,
+        workload_analysis: &WorkloadAnalysis,
+    ) -> crate::Result<ScalingContext> {
         let current_resources = self.get_current_resource_configuration().await?;
         let constraints = self.get_scaling_constraints().await?;
         let historical_data = self.get_historical_scaling_data().await?;
@@ -1634,6 +1708,7 @@ impl AutonomousScalingOrchestrator {
         Ok(ScalingContext {
             current_resources,
             current_metrics: metrics.clone(),
+            trends,
             workload_characteristics: workload_analysis.characteristics.clone(),
             constraints,
             historical_data,
@@ -1641,9 +1716,9 @@ impl AutonomousScalingOrchestrator {
     }
 
     async fn build_current_context(&self) -> crate::Result<ScalingContext> {
-        let current_metrics = self.resource_monitor.collect_metrics().await?;
+        let (current_metrics, trends) = self.sample_metrics().await?;
         let workload_analysis = self.predictive_analyzer.analyze_workload(&current_metrics).await?;
-        self.build_scaling_context(&current_metrics, &workload_analysis).await
+        self.build_scaling_context(&current_metrics, trends, &workload_analysis).await
     }
 
     async fn get_current_resource_configuration(&self) -> crate::Result<ResourceConfiguration> {
@@ -1877,40 +1952,43 @@ impl CostOptimizer {
 impl ResourceMonitor {
     pub fn new() -> Self {
         Self {
-            metrics_collectors: Vec::new(),
+            metrics_collectors: RwLock::new(Vec::new()),
             aggregation_engine: Arc::new(MetricsAggregationEngine::new()),
             alerting_system: Arc::new(ResourceAlertingSystem::new()),
             telemetry_reporter: Arc::new(TelemetryReporter::new()),
         }
     }
 
+    pub fn add_collector(&self, collector: Arc<dyn MetricsCollector>) {
+        self.metrics_collectors.write().unwrap().push(collector);
+    }
+
+    /// Readings of every collector, later collectors overriding earlier ones
+    /// on the same metric
     pub async fn collect_metrics(&self) -> crate::Result<ResourceMetrics> {
-        // Simulate metrics collection
-        Ok(ResourceMetrics {
-            cpu_utilization: 65.0,
-            memory_utilization: 78.0,
-            disk_utilization: 45.0,
-            network_utilization: 23.0,
-            request_rate: 150.0,
-            response_time_p95: Duration::from_millis(120),
-            error_rate: 0.005,
-            queue_depth: 12,
-            active_connections: 89,
-            edge_specific: EdgeMetrics {
-                battery_level: Some(85.0),
-                temperature_celsius: Some(42.0),
-                connectivity_strength: 0.95,
-                bandwidth_availability: 0.87,
-                latency_to_cloud: Duration::from_millis(45),
-                local_processing_ratio: 0.73,
-            },
-        })
+        let collectors = self.metrics_collectors.read().unwrap().clone();
+        if collectors.is_empty() {
+            return Err(crate::Error::Configuration(
+                "No metrics collectors are registered for autonomous scaling".to_string(),
+            ));
+        }
+        let mut readings = HashMap::new();
+        for collector in &collectors {
+            readings.extend(collector.collect()?);
+        }
+        Ok(ResourceMetrics::from_readings(&readings))
     }
 }
 
 impl ScalingDecisionEngine {
     pub fn new() -> Self {
+        Self::with_lookahead(Duration::from_secs(60))
+    }
+
+    /// Engine projecting trends `lookahead` past the latest sample
+    pub fn with_lookahead(lookahead: Duration) -> Self {
         Self {
+            lookahead,
             decision_models: Arc::new(RwLock::new(HashMap::new())),
             criteria_weights: Arc::new(RwLock::new(CriteriaWeights::default())),
             optimization_algorithm: Arc::new(DefaultOptimizationAlgorithm::new()),
@@ -1919,8 +1997,17 @@ impl ScalingDecisionEngine {
     }
 
     pub async fn make_decision(&self, context: &ScalingContext) -> crate::Result<Option<ScalingDecision>> {
-        // Simple decision logic based on CPU utilization
-        if context.current_metrics.cpu_utilization > 80.0 {
+        let cpu = context.trends.trend(ScalingMetric::CpuUtilization);
+        let projected_cpu = context.trends.projected(ScalingMetric::CpuUtilization, self.lookahead);
+        let queue = context.trends.trend(ScalingMetric::QueueDepth);
+        // Scale out on sustained load, load heading past the threshold before
+        // the next decision, or a backlog building up; scale in only when the
+        // whole window was quiet and nothing is rising
+        let scale_out = cpu.mean > 80.0
+            || (cpu.slope_per_sec > 0.0 && projected_cpu > 80.0)
+            || queue.slope_per_sec >= 1.0;
+        let scale_in = cpu.max < 30.0 && cpu.slope_per_sec <= 0.0 && queue.slope_per_sec <= 0.0;
+        if scale_out {
             let decision = ScalingDecision {
                 decision_id: Uuid::new_v4(),
                 target_configuration: ResourceConfiguration {
@@ -1971,7 +2058,7 @@ impl ScalingDecisionEngine {
             };
             
             Ok(Some(decision))
-        } else if context.current_metrics.cpu_utilization < 30.0 && context.current_resources.instance_count > 1 {
+        } else if scale_in && context.current_resources.instance_count > 1 {
             // Scale down logic
             let decision = ScalingDecision {
                 decision_id: Uuid::new_v4(),
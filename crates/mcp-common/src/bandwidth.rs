@@ -0,0 +1,286 @@
+//! Bandwidth accounting per subsystem
+//!
+//! Every component that talks to the network reports the bytes it sent and
+//! received to the process-wide [`BandwidthMeter`], tagged with a
+//! [`Subsystem`]. Counters are kept per UTC day and per month so operators on
+//! metered links can see what used their data allowance; the gateway saves
+//! them periodically so they survive restarts.
+//!
+//! Bytes are HTTP and broker payloads as seen by the application; TCP, TLS
+//! and HTTP header overhead is not included, so bodiless requests such as
+//! connectivity probes and warm standby refreshes are not accounted.
+
+use crate::vfs::Vfs;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// Part of the gateway that generated network traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    /// Requests forwarded to cloud endpoints
+    CloudForward,
+    /// Offline queue replay to the cloud
+    QueueSync,
+    /// Telemetry batches published to output connectors
+    Telemetry,
+    /// Completed responses published to output connectors
+    Connectors,
+    Webhooks,
+    /// Alert notifications from the pipeline guard
+    Alerts,
+    ModelDownload,
+    /// Cloud retrieval queries
+    Retrieval,
+    /// Artifact uploads to object storage
+    Artifacts,
+    /// Requests forwarded between cluster peers
+    Cluster,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 10] = [
+        Subsystem::CloudForward,
+        Subsystem::QueueSync,
+        Subsystem::Telemetry,
+        Subsystem::Connectors,
+        Subsystem::Webhooks,
+        Subsystem::Alerts,
+        Subsystem::ModelDownload,
+        Subsystem::Retrieval,
+        Subsystem::Artifacts,
+        Subsystem::Cluster,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Subsystem::CloudForward => "cloud_forward",
+            Subsystem::QueueSync => "queue_sync",
+            Subsystem::Telemetry => "telemetry",
+            Subsystem::Connectors => "connectors",
+            Subsystem::Webhooks => "webhooks",
+            Subsystem::Alerts => "alerts",
+            Subsystem::ModelDownload => "model_download",
+            Subsystem::Retrieval => "retrieval",
+            Subsystem::Artifacts => "artifacts",
+            Subsystem::Cluster => "cluster",
+        }
+    }
+}
+
+/// Bytes moved in each direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Traffic {
+    pub sent_bytes: u64,
+    pub received_bytes: u64,
+}
+
+impl Traffic {
+    pub fn total_bytes(&self) -> u64 {
+        self.sent_bytes.saturating_add(self.received_bytes)
+    }
+
+    fn add(&mut self, sent: u64, received: u64) {
+        self.sent_bytes = self.sent_bytes.saturating_add(sent);
+        self.received_bytes = self.received_bytes.saturating_add(received);
+    }
+}
+
+/// Traffic per subsystem within one period
+pub type PeriodTraffic = BTreeMap<Subsystem, Traffic>;
+
+/// Saved and reported form of the counters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BandwidthUsage {
+    /// Keyed by UTC date, `2026-10-15`
+    pub days: BTreeMap<String, PeriodTraffic>,
+    /// Keyed by UTC month, `2026-10`
+    pub months: BTreeMap<String, PeriodTraffic>,
+}
+
+impl BandwidthUsage {
+    /// Traffic of the current UTC day
+    pub fn today(&self) -> PeriodTraffic {
+        self.days.get(&day_key(Utc::now())).cloned().unwrap_or_default()
+    }
+
+    /// Traffic of the current UTC month
+    pub fn this_month(&self) -> PeriodTraffic {
+        self.months.get(&month_key(Utc::now())).cloned().unwrap_or_default()
+    }
+}
+
+/// Process-wide traffic counters
+pub struct BandwidthMeter {
+    usage: Mutex<BandwidthUsage>,
+    retain_days: usize,
+    retain_months: usize,
+}
+
+impl BandwidthMeter {
+    pub fn new(retain_days: usize, retain_months: usize) -> Self {
+        Self {
+            usage: Mutex::new(BandwidthUsage::default()),
+            retain_days: retain_days.max(1),
+            retain_months: retain_months.max(1),
+        }
+    }
+
+    /// The meter all subsystems report to
+    pub fn global() -> &'static BandwidthMeter {
+        static GLOBAL: OnceLock<BandwidthMeter> = OnceLock::new();
+        GLOBAL.get_or_init(|| BandwidthMeter::new(31, 12))
+    }
+
+    pub fn record(&self, subsystem: Subsystem, sent: u64, received: u64) {
+        self.record_at(Utc::now(), subsystem, sent, received);
+    }
+
+    fn record_at(&self, now: DateTime<Utc>, subsystem: Subsystem, sent: u64, received: u64) {
+        if sent == 0 && received == 0 {
+            return;
+        }
+        let mut usage = self.lock();
+        let day = day_key(now);
+        if !usage.days.contains_key(&day) {
+            trim(&mut usage.days, self.retain_days - 1);
+        }
+        usage.days.entry(day).or_default().entry(subsystem).or_default().add(sent, received);
+
+        let month = month_key(now);
+        if !usage.months.contains_key(&month) {
+            trim(&mut usage.months, self.retain_months - 1);
+        }
+        usage.months.entry(month).or_default().entry(subsystem).or_default().add(sent, received);
+    }
+
+    /// Copy of all retained counters
+    pub fn usage(&self) -> BandwidthUsage {
+        self.lock().clone()
+    }
+
+    /// Merge counters saved by an earlier run into the current ones
+    pub fn restore(&self, saved: BandwidthUsage) {
+        let mut guard = self.lock();
+        let usage = &mut *guard;
+        for (periods, saved_periods) in [(&mut usage.days, saved.days), (&mut usage.months, saved.months)] {
+            for (period, traffic) in saved_periods {
+                let current = periods.entry(period).or_default();
+                for (subsystem, saved) in traffic {
+                    current.entry(subsystem).or_default().add(saved.sent_bytes, saved.received_bytes);
+                }
+            }
+        }
+        trim(&mut usage.days, self.retain_days);
+        trim(&mut usage.months, self.retain_months);
+    }
+
+    /// Load counters saved at `path`, if any
+    pub async fn load(&self, vfs: &dyn Vfs, path: &Path) -> Result<()> {
+        if !vfs.exists(path).await {
+            return Ok(());
+        }
+        let saved: BandwidthUsage = serde_json::from_slice(&vfs.read(path).await?)?;
+        self.restore(saved);
+        Ok(())
+    }
+
+    /// Save the counters to `path`, replacing it atomically
+    pub async fn save(&self, vfs: &dyn Vfs, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec(&self.usage())?;
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            vfs.create_dir_all(parent).await?;
+        }
+        let staging = path.with_extension("tmp");
+        vfs.write(&staging, &data).await?;
+        vfs.rename(&staging, path).await
+    }
+
+    /// Render today's and this month's totals into health metrics
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let usage = self.lock();
+        let now = Utc::now();
+        for (period, key) in [("today", day_key(now)), ("month", month_key(now))] {
+            let periods = if period == "today" { &usage.days } else { &usage.months };
+            let Some(traffic) = periods.get(&key) else {
+                continue;
+            };
+            for (subsystem, traffic) in traffic {
+                let name = subsystem.as_str();
+                metrics.insert(format!("bandwidth_{}_{}_sent_bytes", period, name), traffic.sent_bytes as f32);
+                metrics.insert(format!("bandwidth_{}_{}_received_bytes", period, name), traffic.received_bytes as f32);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BandwidthUsage> {
+        self.usage.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Report traffic to the global meter
+pub fn record(subsystem: Subsystem, sent: u64, received: u64) {
+    BandwidthMeter::global().record(subsystem, sent, received);
+}
+
+fn day_key(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m-%d").to_string()
+}
+
+fn month_key(now: DateTime<Utc>) -> String {
+    now.format("%Y-%m").to_string()
+}
+
+/// Drop the oldest periods until at most `keep` remain
+fn trim(periods: &mut BTreeMap<String, PeriodTraffic>, keep: usize) {
+    while periods.len() > keep {
+        periods.pop_first();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryVfs;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_counters_roll_over_by_day_and_month() {
+        let meter = BandwidthMeter::new(2, 12);
+        let day = |d: u32| Utc.with_ymd_and_hms(2026, 10, d, 12, 0, 0).unwrap();
+        meter.record_at(day(29), Subsystem::QueueSync, 100, 10);
+        meter.record_at(day(30), Subsystem::QueueSync, 50, 5);
+        meter.record_at(day(31), Subsystem::ModelDownload, 0, 4096);
+        meter.record_at(day(31), Subsystem::ModelDownload, 0, 0);
+
+        let usage = meter.usage();
+        assert_eq!(usage.days.keys().collect::<Vec<_>>(), vec!["2026-10-30", "2026-10-31"]);
+        let october = &usage.months["2026-10"];
+        assert_eq!(october[&Subsystem::QueueSync], Traffic { sent_bytes: 150, received_bytes: 15 });
+        assert_eq!(october[&Subsystem::ModelDownload].total_bytes(), 4096);
+    }
+
+    #[tokio::test]
+    async fn test_counters_survive_save_and_load() {
+        let vfs = MemoryVfs::new();
+        let path = Path::new("data/bandwidth.json");
+        let before = BandwidthMeter::new(31, 12);
+        before.record(Subsystem::CloudForward, 300, 700);
+        before.save(&vfs, path).await.unwrap();
+
+        let after = BandwidthMeter::new(31, 12);
+        after.record(Subsystem::CloudForward, 1, 1);
+        after.load(&vfs, path).await.unwrap();
+        assert_eq!(
+            after.usage().today()[&Subsystem::CloudForward],
+            Traffic { sent_bytes: 301, received_bytes: 701 }
+        );
+
+        // Nothing saved yet is not an error
+        BandwidthMeter::new(31, 12).load(&vfs, Path::new("missing.json")).await.unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+//! Wall-clock abstraction
+//!
+//! Components that compare timestamps (clock skew tracking, maintenance
+//! windows, uptime) read time through [`Clock`] so tests can drive them with
+//! a [`FakeClock`] instead of sleeping.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::sync::{Arc, RwLock};
+
+/// Source of the current time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The host's system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Shared handle to the system clock
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// Manually advanced clock for deterministic tests
+#[derive(Debug)]
+pub struct FakeClock {
+    now: RwLock<DateTime<Utc>>,
+}
+
+impl FakeClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: RwLock::new(start),
+        }
+    }
+
+    /// Set the current time
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.write().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.write().unwrap_or_else(|e| e.into_inner());
+        *now += by;
+    }
+}
+
+impl Default for FakeClock {
+    /// Starts at 2024-01-01T00:00:00Z so test output is reproducible
+    fn default() -> Self {
+        Self::new(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
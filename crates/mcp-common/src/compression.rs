@@ -0,0 +1,170 @@
+//! Compression of payloads exchanged with the cloud
+//!
+//! Queue sync uploads and cloud forwards can compress their JSON bodies with
+//! zstd or lz4, named in `Content-Encoding` so the receiver knows how to read
+//! them. The same codec is offered in `Accept-Encoding` so the cloud can
+//! compress its answer too. Bodies below `MIN_COMPRESSED_BYTES`, or that do
+//! not shrink, are sent as is. A receiver answering 415 does not understand
+//! the encoding, and senders fall back to plain bodies for it.
+
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Smallest body worth compressing; framing overhead outweighs the savings
+/// on anything shorter
+pub const MIN_COMPRESSED_BYTES: usize = 512;
+
+/// zstd level trading ratio for CPU time on small gateways
+const ZSTD_LEVEL: i32 = 3;
+
+/// Codec for payloads sent to the cloud
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadCompression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl PayloadCompression {
+    /// `Content-Encoding` naming the codec; none for plain bodies
+    pub fn content_encoding(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Lz4 => Some("lz4"),
+            Self::Zstd => Some("zstd"),
+        }
+    }
+
+    /// Codec a `Content-Encoding` names, if it is one this gateway reads
+    pub fn from_content_encoding(encoding: &str) -> Option<Self> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => Some(Self::None),
+            "lz4" => Some(Self::Lz4),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(data.to_vec()),
+            Self::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+                encoder.write_all(data).map_err(compression_error)?;
+                encoder
+                    .finish()
+                    .map_err(|e| Error::Serialization(format!("Payload compression failed: {}", e)))
+            },
+            Self::Zstd => zstd::encode_all(data, ZSTD_LEVEL).map_err(compression_error),
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(data.to_vec()),
+            Self::Lz4 => {
+                let mut decompressed = Vec::new();
+                lz4_flex::frame::FrameDecoder::new(data)
+                    .read_to_end(&mut decompressed)
+                    .map_err(compression_error)?;
+                Ok(decompressed)
+            },
+            Self::Zstd => zstd::decode_all(data).map_err(compression_error),
+        }
+    }
+}
+
+/// A body ready to send with the `Content-Encoding` it needs, if any
+#[derive(Debug, Clone)]
+pub struct EncodedBody {
+    pub body: Vec<u8>,
+    pub content_encoding: Option<&'static str>,
+}
+
+/// Compress a body with `compression` when that makes it smaller
+pub fn encode(compression: PayloadCompression, body: Vec<u8>) -> EncodedBody {
+    let plain = |body| EncodedBody {
+        body,
+        content_encoding: None,
+    };
+    let Some(content_encoding) = compression.content_encoding() else {
+        return plain(body);
+    };
+    if body.len() < MIN_COMPRESSED_BYTES {
+        return plain(body);
+    }
+    match compression.compress(&body) {
+        Ok(compressed) if compressed.len() < body.len() => EncodedBody {
+            body: compressed,
+            content_encoding: Some(content_encoding),
+        },
+        _ => plain(body),
+    }
+}
+
+/// Read a body received with `content_encoding`
+pub fn decode(content_encoding: Option<&str>, body: &[u8]) -> Result<Vec<u8>> {
+    let Some(encoding) = content_encoding else {
+        return Ok(body.to_vec());
+    };
+    PayloadCompression::from_content_encoding(encoding)
+        .ok_or_else(|| Error::Serialization(format!("Unsupported content encoding: {}", encoding)))?
+        .decompress(body)
+}
+
+fn compression_error(e: std::io::Error) -> Error {
+    Error::Serialization(format!("Payload compression failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch() -> Vec<u8> {
+        let requests: Vec<serde_json::Value> = (0..50)
+            .map(|i| {
+                serde_json::json!({
+                    "device_id": format!("sensor-{}", i % 4),
+                    "method": "completion",
+                    "params": { "prompt": "Summarize the vibration readings of the last hour", "max_tokens": 128 },
+                })
+            })
+            .collect();
+        serde_json::to_vec(&requests).unwrap()
+    }
+
+    #[test]
+    fn codecs_round_trip_and_shrink_json_batches() {
+        let body = batch();
+        for compression in [PayloadCompression::Lz4, PayloadCompression::Zstd] {
+            let encoded = encode(compression, body.clone());
+            assert_eq!(encoded.content_encoding, compression.content_encoding());
+            assert!(
+                encoded.body.len() * 4 < body.len(),
+                "{:?} only reached {} bytes",
+                compression,
+                encoded.body.len()
+            );
+            assert_eq!(
+                decode(encoded.content_encoding, &encoded.body).unwrap(),
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn small_bodies_and_unknown_encodings_are_not_compressed() {
+        let small = encode(PayloadCompression::Zstd, b"{\"id\":1}".to_vec());
+        assert_eq!(small.content_encoding, None);
+        assert_eq!(encode(PayloadCompression::None, batch()).body, batch());
+        assert_eq!(decode(Some("identity"), b"plain").unwrap(), b"plain");
+        assert!(decode(Some("br"), b"data").is_err());
+        assert_eq!(
+            PayloadCompression::from_content_encoding(" ZSTD"),
+            Some(PayloadCompression::Zstd)
+        );
+    }
+}
@@ -0,0 +1,478 @@
+//! Concurrency limits for gateway components
+//!
+//! Each component that performs expensive work (local inference, cloud
+//! forwarding, queue sync) owns a [`ConcurrencyLimiter`]. Callers that exceed
+//! the limit wait for a bounded time; once the wait list itself is full, new
+//! callers are rejected immediately with `ResourceExhausted`.
+//!
+//! Waiters are served by request priority and in arrival order within a
+//! priority, so a high-priority request that spent a long time in the offline
+//! queue is not put behind normal traffic again here. Callers may also give
+//! a weight, which serves heavier waiters of the same priority first. Critical requests may
+//! additionally use `critical_reserve` slots kept free of other work, and
+//! displace the lowest-priority waiter when the wait list is full.
+//!
+//! The limit can be lowered below the configured one at runtime, as the
+//! power manager does in low-power mode; running operations keep their
+//! permits and no new ones are given until usage falls under the new limit.
+
+use crate::config::ConcurrencyLimit;
+use crate::types::{Priority, DEFAULT_WEIGHT};
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// Point-in-time utilization of a limiter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcurrencyGauge {
+    pub name: String,
+    pub limit: usize,
+    pub in_use: usize,
+    pub waiting: usize,
+    pub rejected_total: u64,
+    pub utilization: f32,
+    /// Time spent waiting for a slot, per priority
+    pub waits: Vec<PriorityWait>,
+}
+
+/// Why a caller was not given a slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rejection {
+    /// The wait list was full
+    QueueFull,
+    /// No slot freed up before the caller's wait deadline
+    TimedOut,
+    /// Pushed off a full wait list by a critical request
+    Displaced,
+    /// The limiter went away while the caller waited
+    Closed,
+}
+
+impl Rejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::QueueFull => "wait queue is full",
+            Rejection::TimedOut => "timed out waiting for a slot",
+            Rejection::Displaced => "displaced by a critical request",
+            Rejection::Closed => "limiter closed",
+        }
+    }
+}
+
+/// Slot wait statistics for one priority
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorityWait {
+    pub priority: Priority,
+    pub acquired: u64,
+    pub avg_wait_ms: f32,
+    pub max_wait_ms: u64,
+}
+
+impl ConcurrencyGauge {
+    /// Render the gauge into component health metrics
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        metrics.insert("concurrency_limit".to_string(), self.limit as f32);
+        metrics.insert("concurrency_in_use".to_string(), self.in_use as f32);
+        metrics.insert("concurrency_waiting".to_string(), self.waiting as f32);
+        metrics.insert("concurrency_rejected_total".to_string(), self.rejected_total as f32);
+        metrics.insert("concurrency_utilization".to_string(), self.utilization);
+        for wait in &self.waits {
+            metrics.insert(
+                format!("concurrency_wait_ms_avg_{}", wait.priority.as_str()),
+                wait.avg_wait_ms,
+            );
+        }
+    }
+}
+
+/// Bounded concurrency limiter with a bounded, priority-ordered wait list
+pub struct ConcurrencyLimiter {
+    name: String,
+    /// Limit from the configuration; the current one is in the state
+    configured_limit: usize,
+    critical_reserve: usize,
+    max_queued: usize,
+    queue_timeout: Duration,
+    state: Arc<Mutex<LimiterState>>,
+    rejected: AtomicU64,
+}
+
+#[derive(Default)]
+struct LimiterState {
+    limit: usize,
+    in_use: usize,
+    next_ticket: u64,
+    waiters: Vec<Waiter>,
+    waits: HashMap<Priority, (u64, Duration, Duration)>,
+}
+
+struct Waiter {
+    priority: Priority,
+    weight: u32,
+    ticket: u64,
+    /// Receives `true` when handed a slot, `false` when displaced
+    wake: oneshot::Sender<bool>,
+}
+
+impl LimiterState {
+    fn record_wait(&mut self, priority: Priority, waited: Duration) {
+        let (count, total, max) = self.waits.entry(priority).or_default();
+        *count += 1;
+        *total += waited;
+        *max = (*max).max(waited);
+    }
+
+    /// Index of the waiter served next: highest priority, then heaviest,
+    /// then oldest
+    fn next_waiter(&self) -> Option<usize> {
+        (0..self.waiters.len()).min_by_key(|&index| {
+            let waiter = &self.waiters[index];
+            (std::cmp::Reverse((waiter.priority, waiter.weight)), waiter.ticket)
+        })
+    }
+
+    /// Slots a request of `priority` may use
+    fn capacity(&self, priority: Priority, critical_reserve: usize) -> usize {
+        if priority == Priority::Critical {
+            self.limit + critical_reserve
+        } else {
+            self.limit
+        }
+    }
+}
+
+/// Permit held for the duration of the limited operation
+pub struct ConcurrencyPermit {
+    state: Arc<Mutex<LimiterState>>,
+    critical_reserve: usize,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        let mut state = lock(&self.state);
+        // Hand the slot straight to the next waiter that is still waiting
+        while let Some(index) = state.next_waiter() {
+            let waiter = state.waiters.remove(index);
+            // Reserved slots only go to critical waiters, and a lowered limit
+            // takes the slot back
+            if state.in_use > state.capacity(waiter.priority, self.critical_reserve) {
+                state.waiters.insert(index, waiter);
+                break;
+            }
+            if waiter.wake.send(true).is_ok() {
+                return;
+            }
+        }
+        state.in_use -= 1;
+    }
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(name: &str, config: &ConcurrencyLimit) -> Self {
+        Self {
+            name: name.to_string(),
+            configured_limit: config.max_concurrent.max(1),
+            critical_reserve: config.critical_reserve,
+            max_queued: config.max_queued,
+            queue_timeout: Duration::from_millis(config.queue_timeout_ms),
+            state: Arc::new(Mutex::new(LimiterState {
+                limit: config.max_concurrent.max(1),
+                ..Default::default()
+            })),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Acquire a permit at normal priority, waiting up to the configured queue timeout
+    pub async fn acquire(&self) -> Result<ConcurrencyPermit> {
+        self.acquire_for(Priority::Normal).await
+    }
+
+    /// Acquire a permit, served ahead of waiters with a lower priority
+    pub async fn acquire_for(&self, priority: Priority) -> Result<ConcurrencyPermit> {
+        self.acquire_within(priority, self.queue_timeout).await.map_err(|rejection| match rejection {
+            Rejection::Closed => Error::Internal(format!("{} limiter closed", self.name)),
+            rejection => Error::ResourceExhausted(format!(
+                "{} concurrency limit of {} reached: {}",
+                self.name,
+                lock(&self.state).limit,
+                rejection.as_str()
+            )),
+        })
+    }
+
+    /// Acquire a permit, waiting at most `timeout` instead of the configured
+    /// queue timeout, and report why when none was given
+    pub async fn acquire_within(
+        &self,
+        priority: Priority,
+        timeout: Duration,
+    ) -> std::result::Result<ConcurrencyPermit, Rejection> {
+        self.acquire_weighted(priority, DEFAULT_WEIGHT, timeout).await
+    }
+
+    /// Like [`acquire_within`](Self::acquire_within), served ahead of waiters
+    /// of the same priority with a lower `weight`
+    pub async fn acquire_weighted(
+        &self,
+        priority: Priority,
+        weight: u32,
+        timeout: Duration,
+    ) -> std::result::Result<ConcurrencyPermit, Rejection> {
+        let started = Instant::now();
+        let mut receiver = {
+            let mut state = lock(&self.state);
+            let capacity = state.capacity(priority, self.critical_reserve);
+            let ahead = state
+                .waiters
+                .iter()
+                .filter(|waiter| (waiter.priority, waiter.weight) >= (priority, weight))
+                .count();
+            if state.in_use < capacity && ahead == 0 {
+                state.in_use += 1;
+                state.record_wait(priority, Duration::ZERO);
+                return Ok(self.permit());
+            }
+
+            if state.waiters.len() >= self.max_queued {
+                // Displace the newest of the lowest-priority waiters, if any rank below us
+                let lowest = (0..state.waiters.len())
+                    .filter(|&index| state.waiters[index].priority < priority)
+                    .min_by_key(|&index| {
+                        let waiter = &state.waiters[index];
+                        (waiter.priority, std::cmp::Reverse(waiter.ticket))
+                    });
+                match lowest {
+                    Some(index) if priority == Priority::Critical => {
+                        let displaced = state.waiters.remove(index);
+                        let _ = displaced.wake.send(false);
+                    },
+                    _ => {
+                        drop(state);
+                        return Err(self.reject(Rejection::QueueFull));
+                    },
+                }
+            }
+
+            let (wake, receiver) = oneshot::channel();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.waiters.push(Waiter {
+                priority,
+                weight,
+                ticket,
+                wake,
+            });
+            receiver
+        };
+
+        let granted = match tokio::time::timeout(timeout, &mut receiver).await {
+            Ok(Ok(granted)) => granted,
+            Ok(Err(_)) => return Err(Rejection::Closed),
+            Err(_) => {
+                // Leave the wait list, unless a slot was handed over meanwhile
+                receiver.close();
+                lock(&self.state).waiters.retain(|waiter| !waiter.wake.is_closed());
+                match receiver.try_recv() {
+                    Ok(granted) => granted,
+                    Err(_) => return Err(self.reject(Rejection::TimedOut)),
+                }
+            },
+        };
+
+        if !granted {
+            return Err(self.reject(Rejection::Displaced));
+        }
+        lock(&self.state).record_wait(priority, started.elapsed());
+        Ok(self.permit())
+    }
+
+    /// Current utilization of this limiter
+    pub fn gauge(&self) -> ConcurrencyGauge {
+        let state = lock(&self.state);
+        let waits = Priority::ALL
+            .iter()
+            .filter_map(|priority| {
+                let (count, total, max) = state.waits.get(priority)?;
+                Some(PriorityWait {
+                    priority: *priority,
+                    acquired: *count,
+                    avg_wait_ms: total.as_secs_f32() * 1000.0 / *count as f32,
+                    max_wait_ms: max.as_millis() as u64,
+                })
+            })
+            .collect();
+        ConcurrencyGauge {
+            name: self.name.clone(),
+            limit: state.limit,
+            in_use: state.in_use,
+            waiting: state.waiters.len(),
+            rejected_total: self.rejected.load(Ordering::SeqCst),
+            utilization: state.in_use.min(state.limit) as f32 / state.limit as f32,
+            waits,
+        }
+    }
+
+    /// Limit from the configuration, whatever the current one is
+    pub fn configured_limit(&self) -> usize {
+        self.configured_limit
+    }
+
+    /// Change the limit, handing slots to waiters when it is raised
+    pub fn set_limit(&self, limit: usize) {
+        let mut state = lock(&self.state);
+        state.limit = limit.max(1);
+        while let Some(index) = state.next_waiter() {
+            if state.in_use >= state.capacity(state.waiters[index].priority, self.critical_reserve) {
+                break;
+            }
+            let waiter = state.waiters.remove(index);
+            if waiter.wake.send(true).is_ok() {
+                state.in_use += 1;
+            }
+        }
+    }
+
+    fn permit(&self) -> ConcurrencyPermit {
+        ConcurrencyPermit {
+            state: Arc::clone(&self.state),
+            critical_reserve: self.critical_reserve,
+        }
+    }
+
+    fn reject(&self, rejection: Rejection) -> Rejection {
+        self.rejected.fetch_add(1, Ordering::SeqCst);
+        rejection
+    }
+}
+
+fn lock(state: &Mutex<LimiterState>) -> MutexGuard<'_, LimiterState> {
+    state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(max_concurrent: usize, max_queued: usize, queue_timeout_ms: u64) -> ConcurrencyLimit {
+        ConcurrencyLimit {
+            max_concurrent,
+            max_queued,
+            queue_timeout_ms,
+            critical_reserve: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_permits_are_released_on_drop() {
+        let limiter = ConcurrencyLimiter::new("inference", &limit(1, 0, 10));
+        let permit = limiter.acquire().await.unwrap();
+        assert_eq!(limiter.gauge().in_use, 1);
+        assert!(limiter.acquire().await.is_err());
+
+        drop(permit);
+        assert!(limiter.acquire().await.is_ok());
+        assert_eq!(limiter.gauge().rejected_total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_saturated_callers_wait_then_time_out() {
+        let limiter = ConcurrencyLimiter::new("cloud", &limit(1, 4, 20));
+        let _held = limiter.acquire().await.unwrap();
+
+        let result = limiter.acquire().await;
+        assert!(matches!(result, Err(Error::ResourceExhausted(_))));
+        assert_eq!(limiter.gauge().waiting, 0);
+        assert_eq!(limiter.gauge().utilization, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_waiters_are_served_by_priority() {
+        let limiter = Arc::new(ConcurrencyLimiter::new("inference", &limit(1, 4, 1000)));
+        let held = limiter.acquire().await.unwrap();
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let limiter = Arc::clone(&limiter);
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let _permit = limiter.acquire_for(priority).await.unwrap();
+                order_tx.send(priority).unwrap();
+            });
+            tokio::task::yield_now().await;
+        }
+        while limiter.gauge().waiting < 3 {
+            tokio::task::yield_now().await;
+        }
+
+        drop(held);
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            order.push(order_rx.recv().await.unwrap());
+        }
+        assert_eq!(order, vec![Priority::High, Priority::Normal, Priority::Low]);
+        assert!(limiter.gauge().waits.iter().any(|wait| wait.priority == Priority::Low && wait.acquired == 1));
+    }
+
+    #[tokio::test]
+    async fn test_lowered_limit_takes_slots_back_until_raised() {
+        let limiter = Arc::new(ConcurrencyLimiter::new("inference", &limit(2, 4, 1000)));
+        let first = limiter.acquire().await.unwrap();
+        let _second = limiter.acquire().await.unwrap();
+        limiter.set_limit(1);
+
+        let waiter = {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire().await.map(|_| ()) })
+        };
+        while limiter.gauge().waiting < 1 {
+            tokio::task::yield_now().await;
+        }
+
+        // Freeing a slot over the lowered limit hands it to nobody
+        drop(first);
+        assert_eq!((limiter.gauge().in_use, limiter.gauge().waiting), (1, 1));
+
+        limiter.set_limit(limiter.configured_limit());
+        waiter.await.unwrap().unwrap();
+        assert_eq!(limiter.gauge().limit, 2);
+    }
+
+    #[tokio::test]
+    async fn test_critical_requests_use_reserve_and_displace_waiters() {
+        let config = ConcurrencyLimit {
+            critical_reserve: 1,
+            ..limit(1, 1, 1000)
+        };
+        let limiter = Arc::new(ConcurrencyLimiter::new("inference", &config));
+        let _held = limiter.acquire().await.unwrap();
+
+        // The reserved slot is only open to critical requests
+        let reserved = limiter.acquire_for(Priority::Critical).await.unwrap();
+        assert_eq!(limiter.gauge().in_use, 2);
+
+        let waiter = {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire_for(Priority::Low).await.map(|_| ()) })
+        };
+        while limiter.gauge().waiting < 1 {
+            tokio::task::yield_now().await;
+        }
+
+        // A full wait list makes room for a critical request
+        let critical = {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire_for(Priority::Critical).await.map(|_| ()) })
+        };
+        assert!(matches!(waiter.await.unwrap(), Err(Error::ResourceExhausted(_))));
+
+        drop(reserved);
+        assert!(critical.await.unwrap().is_ok());
+        assert_eq!(limiter.gauge().rejected_total, 1);
+    }
+}
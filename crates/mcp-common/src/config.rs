@@ -1,7 +1,12 @@
 //! Configuration management for MCP Edge Gateway
 
+use crate::compression::PayloadCompression;
+use crate::error::{Error, Result};
+use crate::types::{ModelId, Priority};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,131 +18,3488 @@ pub struct Config {
     pub security: SecurityConfig,
     pub telemetry: TelemetryConfig,
     pub platform: PlatformConfig,
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub outputs: OutputsConfig,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub compliance: ComplianceConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    #[serde(default)]
+    pub audit: AuditSinkConfig,
+    #[serde(default)]
+    pub extensions: ExtensionsConfig,
+    #[serde(default)]
+    pub kv: KvStoreConfig,
+    #[serde(default)]
+    pub conversations: ConversationsConfig,
+    #[serde(default)]
+    pub sessions: SessionsConfig,
+    #[serde(default)]
+    pub normalization: TextNormalizationConfig,
+    #[serde(default)]
+    pub power: PowerConfig,
+    #[serde(default)]
+    pub peripherals: PeripheralsConfig,
+    #[serde(default)]
+    pub pipeline_guard: PipelineGuardConfig,
+    #[serde(default)]
+    pub scaling: ScalingConfig,
+    #[serde(default)]
+    pub deployment: DeploymentConfig,
+    #[serde(default)]
+    pub profile: DeploymentProfile,
+}
+
+/// Health checks and automatic recovery of the pipeline guard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipelineGuardConfig {
+    pub health_check_interval_secs: u64,
+    /// Time allowed for one recovery attempt
+    pub recovery_timeout_secs: u64,
+    /// Consecutive failures before a component's circuit breaker trips
+    pub max_consecutive_failures: u32,
+    pub circuit_breaker_reset_secs: u64,
+    pub auto_recovery: bool,
+    pub performance_monitoring: bool,
+    pub thresholds: GuardThresholdsConfig,
+    pub alerts: GuardAlertsConfig,
+    pub canary: ModelCanaryConfig,
+}
+
+impl Default for PipelineGuardConfig {
+    fn default() -> Self {
+        Self {
+            health_check_interval_secs: 30,
+            recovery_timeout_secs: 60,
+            max_consecutive_failures: 3,
+            circuit_breaker_reset_secs: 300,
+            auto_recovery: true,
+            performance_monitoring: true,
+            thresholds: GuardThresholdsConfig::default(),
+            alerts: GuardAlertsConfig::default(),
+            canary: ModelCanaryConfig::default(),
+        }
+    }
+}
+
+/// Limits past which the pipeline guard considers a component unhealthy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuardThresholdsConfig {
+    /// Fraction of failed requests, from 0 to 1
+    pub max_error_rate: f64,
+    pub max_response_time_ms: f64,
+    pub min_throughput_rps: f64,
+    pub max_memory_usage_mb: f64,
+    /// Fraction of CPU time, from 0 to 1
+    pub max_cpu_usage: f64,
+    /// Failed checks in a row before a component is marked unhealthy
+    pub consecutive_failure_threshold: u32,
+}
+
+impl Default for GuardThresholdsConfig {
+    fn default() -> Self {
+        Self {
+            max_error_rate: 0.1,
+            max_response_time_ms: 5000.0,
+            min_throughput_rps: 1.0,
+            max_memory_usage_mb: 512.0,
+            max_cpu_usage: 0.8,
+            consecutive_failure_threshold: 3,
+        }
+    }
+}
+
+/// Batching of pipeline guard alerts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuardAlertsConfig {
+    /// Alerts below this severity are dropped
+    pub min_severity: crate::events::AlertSeverity,
+    pub rate_limit_secs: u64,
+    /// Alerts sent together once this many are pending
+    pub batch_size: usize,
+    /// Longest a pending alert waits for its batch to fill
+    pub batch_timeout_secs: u64,
+}
+
+impl Default for GuardAlertsConfig {
+    fn default() -> Self {
+        Self {
+            min_severity: crate::events::AlertSeverity::Warning,
+            rate_limit_secs: 60,
+            batch_size: 10,
+            batch_timeout_secs: 30,
+        }
+    }
+}
+
+/// Golden prompts run against newly loaded local models; a model whose
+/// answers or latency regress against its family's baseline is routed around
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelCanaryConfig {
+    pub enabled: bool,
+    /// How often every loaded model is evaluated again
+    pub interval_secs: u64,
+    /// Fraction of a prompt's expected phrases its answer must contain, from 0 to 1
+    pub min_quality: f64,
+    /// Word overlap with the baseline answer below which a prompt regressed, from 0 to 1
+    pub min_similarity: f64,
+    /// Latency, as a multiple of the baseline's, above which a prompt regressed
+    pub max_latency_ratio: f64,
+    pub prompts: Vec<CanaryPromptConfig>,
+}
+
+impl Default for ModelCanaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 3600,
+            min_quality: 0.8,
+            min_similarity: 0.5,
+            max_latency_ratio: 2.0,
+            prompts: vec![CanaryPromptConfig::default()],
+        }
+    }
+}
+
+/// One golden prompt and what its answer must contain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanaryPromptConfig {
+    pub name: String,
+    pub method: String,
+    pub params: HashMap<String, serde_json::Value>,
+    /// Phrases the answer should contain, compared case-insensitively
+    pub expected: Vec<String>,
+    /// Latency above which the prompt regressed, baseline or not
+    pub max_latency_ms: u64,
+}
+
+impl Default for CanaryPromptConfig {
+    fn default() -> Self {
+        Self {
+            name: "arithmetic".to_string(),
+            method: "completion".to_string(),
+            params: HashMap::from([
+                ("prompt".to_string(), serde_json::json!("What is 2 + 2? Answer with a number.")),
+                ("max_tokens".to_string(), serde_json::json!(8)),
+            ]),
+            expected: vec!["4".to_string()],
+            max_latency_ms: 10_000,
+        }
+    }
+}
+
+/// Decisions of the autonomous scaling orchestrator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScalingConfig {
+    pub strategy: crate::autonomous_scaling::ScalingStrategy,
+    /// How far ahead demand is forecast
+    pub prediction_horizon_secs: u64,
+    pub decision_interval_secs: u64,
+    /// Headroom kept on resource utilization, as a fraction
+    pub resource_margin: f64,
+    pub performance_margin: f64,
+    pub cost_margin: f64,
+    pub availability_margin: f64,
+    pub learning_rate: f64,
+    /// Fraction of decisions that try a strategy other than the best known
+    pub exploration_rate: f64,
+    /// Scaling history the learned model is trained on
+    pub memory_window_secs: u64,
+    pub model_update_interval_secs: u64,
+    /// Wait after a scaling action before its outcome is scored
+    pub feedback_delay_secs: u64,
+    /// Recent metrics samples whose trends decisions are based on
+    pub trend_window_secs: u64,
+}
+
+impl Default for ScalingConfig {
+    fn default() -> Self {
+        Self {
+            strategy: crate::autonomous_scaling::ScalingStrategy::Reactive,
+            prediction_horizon_secs: 3600,
+            decision_interval_secs: 60,
+            resource_margin: 0.1,
+            performance_margin: 0.1,
+            cost_margin: 0.05,
+            availability_margin: 0.01,
+            learning_rate: 0.01,
+            exploration_rate: 0.1,
+            memory_window_secs: 7 * 24 * 3600,
+            model_update_interval_secs: 3600,
+            feedback_delay_secs: 300,
+            trend_window_secs: 600,
+        }
+    }
+}
+
+/// Gates applied by the autonomous deployment orchestrator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeploymentConfig {
+    /// Artifacts with more critical vulnerabilities are never deployed
+    pub max_critical_vulnerabilities: u32,
+    /// Test coverage, in percent, below which a deployment is logged as risky
+    pub min_test_coverage_percent: f64,
+}
+
+impl Default for DeploymentConfig {
+    fn default() -> Self {
+        Self {
+            max_critical_vulnerabilities: 0,
+            min_test_coverage_percent: 80.0,
+        }
+    }
+}
+
+/// Kind of deployment, which decides how strict startup checks are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentProfile {
+    #[default]
+    Development,
+    Production,
+}
+
+impl DeploymentProfile {
+    /// How model signatures are checked when started with `--verify-models`
+    pub fn model_verification(&self) -> ModelSignatureMode {
+        match self {
+            DeploymentProfile::Development => ModelSignatureMode::Warn,
+            DeploymentProfile::Production => ModelSignatureMode::Enforce,
+        }
+    }
+}
+
+/// Devices attached to the gateway that feed it requests
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeripheralsConfig {
+    pub cameras: Vec<CameraConfig>,
+    pub microphones: Vec<MicrophoneConfig>,
+}
+
+/// Camera whose frames are captured and sent through the gateway
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    /// Name clients trigger captures and filter notifications by
+    pub name: String,
+    pub source: FrameSourceKind,
+    /// Capture on this schedule; without it frames are only captured when
+    /// triggered
+    pub interval_secs: Option<u64>,
+    /// MCP method each frame is sent as, served by a vision-capable local
+    /// model or forwarded to the cloud by the router
+    pub method: String,
+    /// Params sent with every frame, such as a prompt
+    pub params: HashMap<String, serde_json::Value>,
+    pub capture_timeout_ms: u64,
+    /// Frames larger than this are dropped
+    pub max_frame_bytes: usize,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            name: "camera".to_string(),
+            source: FrameSourceKind::V4l2 {
+                device: PathBuf::from("/dev/video0"),
+                width: None,
+                height: None,
+            },
+            interval_secs: None,
+            method: "vision".to_string(),
+            params: HashMap::new(),
+            capture_timeout_ms: 10_000,
+            max_frame_bytes: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Microphone whose speech is transcribed and answered after a wake word
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MicrophoneConfig {
+    pub name: String,
+    pub source: AudioSourceKind,
+    /// Mono 16-bit PCM is captured at this rate
+    pub sample_rate: u32,
+    pub wake_word: WakeWordConfig,
+    /// Longest utterance recorded after the wake word
+    pub max_utterance_secs: u64,
+    /// Silence that ends an utterance early
+    pub end_of_speech_ms: u64,
+    /// Method the utterance is transcribed with
+    pub transcription_method: String,
+    /// Model the utterance is transcribed with, instead of the routed one
+    pub transcription_model: Option<String>,
+    /// Method the transcript is sent as a prompt; without it utterances are
+    /// only transcribed
+    pub completion_method: Option<String>,
+}
+
+impl Default for MicrophoneConfig {
+    fn default() -> Self {
+        Self {
+            name: "microphone".to_string(),
+            source: AudioSourceKind::Alsa {
+                device: "default".to_string(),
+            },
+            sample_rate: 16_000,
+            wake_word: WakeWordConfig::default(),
+            max_utterance_secs: 8,
+            end_of_speech_ms: 800,
+            transcription_method: "transcription".to_string(),
+            transcription_model: None,
+            completion_method: Some("completion".to_string()),
+        }
+    }
+}
+
+/// Always-on wake word detection gating transcription and completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WakeWordConfig {
+    /// Phrases that start an interaction, matched case-insensitively
+    pub phrases: Vec<String>,
+    /// 0.0 activates only on exact phrases, 1.0 on anything resembling one
+    pub sensitivity: f32,
+    /// Tiny local transcription model listening for the phrases
+    pub model: String,
+    /// Audio checked for a phrase at a time; windows overlap by half
+    pub window_ms: u64,
+    /// RMS level, as a fraction of full scale, below which windows are
+    /// treated as silence and not sent to the model
+    pub energy_threshold: f32,
+    /// Wake words are ignored for this long after an interaction
+    pub cooldown_ms: u64,
+}
+
+impl Default for WakeWordConfig {
+    fn default() -> Self {
+        Self {
+            phrases: vec!["hey gateway".to_string()],
+            sensitivity: 0.5,
+            model: "whisper-tiny".to_string(),
+            window_ms: 1500,
+            energy_threshold: 0.02,
+            cooldown_ms: 2000,
+        }
+    }
+}
+
+/// Where a microphone's audio comes from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AudioSourceKind {
+    /// ALSA capture device, recorded with `arecord`
+    Alsa { device: String },
+    /// Program streaming raw mono 16-bit little-endian PCM to stdout
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+/// Where a camera's frames come from; frames are captured as JPEG with
+/// `ffmpeg` or `libcamera-still`, which must be installed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrameSourceKind {
+    /// Video4Linux2 device such as a USB webcam
+    V4l2 {
+        device: PathBuf,
+        #[serde(default)]
+        width: Option<u32>,
+        #[serde(default)]
+        height: Option<u32>,
+    },
+    /// Raspberry Pi camera through libcamera
+    Libcamera {
+        #[serde(default)]
+        camera: u32,
+        #[serde(default)]
+        width: Option<u32>,
+        #[serde(default)]
+        height: Option<u32>,
+    },
+    /// RTSP stream pulled from an IP camera
+    Rtsp {
+        url: String,
+        /// Use UDP instead of interleaved TCP transport
+        #[serde(default)]
+        udp: bool,
+    },
+    /// Program that writes one JPEG frame to stdout
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+/// Recording of session conversations, which can be exported as portable
+/// packages and imported on another gateway
+///
+/// Requests carrying a `session_id` param are recorded with their responses,
+/// one file per session. Files are kept in the retention `sessions`
+/// directory so they are purged and erased with the rest of session data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversationsConfig {
+    pub enabled: bool,
+    /// Directory holding one file per session; `retention.sessions.path`
+    /// when unset
+    pub directory: Option<PathBuf>,
+    /// Oldest messages are dropped beyond this many per session
+    pub max_messages_per_session: usize,
+}
+
+impl Default for ConversationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            max_messages_per_session: 500,
+        }
+    }
+}
+
+/// Rolling chat context kept per session, so clients only send new messages
+///
+/// Chat requests carrying a `session_id` param get the session's earlier
+/// messages prepended, as many as fit `max_context_tokens`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionsConfig {
+    pub enabled: bool,
+    /// Where session histories are kept; `memory` loses them on restart
+    pub storage_backend: QueueStorageKind,
+    /// Database path for the sled and sqlite backends
+    pub storage_path: PathBuf,
+    /// Estimated tokens of history and new messages sent to the model; the
+    /// oldest history is left out first
+    pub max_context_tokens: usize,
+    /// Oldest messages are dropped beyond this many per session
+    pub max_messages_per_session: usize,
+    /// A session idle this long starts over with no history
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            storage_backend: QueueStorageKind::Memory,
+            storage_path: PathBuf::from("./data/sessions.db"),
+            max_context_tokens: 2048,
+            max_messages_per_session: 200,
+            idle_timeout_secs: 86400,
+        }
+    }
+}
+
+/// Text cleanup and language detection before requests are routed
+///
+/// Strings in the `fields` params are cleaned in this order: control
+/// characters are stripped, the Unicode normalization form is applied,
+/// accents are removed and `transliterate` replacements are made. The
+/// language of the text is then attached to the request, for routing rules
+/// and per-language metrics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextNormalizationConfig {
+    pub enabled: bool,
+    /// Params whose strings are cleaned, at any depth
+    pub fields: Vec<String>,
+    pub form: NormalizationForm,
+    /// Drop control characters other than tab and line breaks, zero-width
+    /// spaces and bidirectional overrides
+    pub strip_control_chars: bool,
+    /// Remove combining accents, so "café" becomes "cafe"
+    pub strip_diacritics: bool,
+    /// Replacements made after normalization, such as `"ß" = "ss"`; longer
+    /// keys are replaced first
+    pub transliterate: HashMap<String, String>,
+    pub detect_language: bool,
+    /// Fewest letters a language is guessed from
+    pub min_detection_letters: usize,
+}
+
+impl Default for TextNormalizationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fields: ["prompt", "text", "input", "messages"].map(str::to_string).to_vec(),
+            form: NormalizationForm::Nfc,
+            strip_control_chars: true,
+            strip_diacritics: false,
+            transliterate: HashMap::new(),
+            detect_language: true,
+            min_detection_letters: 12,
+        }
+    }
+}
+
+/// Unicode normalization form of request text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizationForm {
+    /// Left as sent
+    None,
+    /// Canonical composition; text looks the same as before
+    #[default]
+    Nfc,
+    /// Compatibility composition, also folding ligatures, full-width forms
+    /// and superscripts into plain characters
+    Nfkc,
+}
+
+/// Power management for battery-powered devices
+///
+/// The gateway is in low-power mode while `low_power_mode` is set or the
+/// battery is below one of `battery_thresholds`. Low-power mode runs local
+/// inference at `cpu_throttle_percent` of its concurrency, unloads models
+/// idle for `idle_model_unload_secs` and holds queue syncs back until a
+/// batch is due. The battery is read every `sleep_on_idle_ms`, stretched by
+/// the multiplier of the lowest threshold reached.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    /// Stay in low-power mode whatever the battery level
+    pub low_power_mode: bool,
+    /// Share of the `concurrency.local_inference` limit used in low-power mode
+    pub cpu_throttle_percent: u8,
+    /// Interval between battery readings, which is also how often batched
+    /// queue syncs are checked
+    pub sleep_on_idle_ms: u64,
+    /// Models serving no request this long are unloaded in low-power mode;
+    /// 0 keeps them loaded
+    pub idle_model_unload_secs: u64,
+    /// In low-power mode the queue syncs once this many requests are queued
+    pub sync_batch_size: u32,
+    /// Longest a queued request waits for its batch in low-power mode
+    pub sync_batch_max_age_secs: u64,
+    /// Directory of the kernel's power supplies, read for the battery level
+    pub power_supply_dir: PathBuf,
+    pub battery_thresholds: Vec<BatteryThreshold>,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            low_power_mode: false,
+            cpu_throttle_percent: 50,
+            sleep_on_idle_ms: 1000,
+            idle_model_unload_secs: 300,
+            sync_batch_size: 20,
+            sync_batch_max_age_secs: 300,
+            power_supply_dir: PathBuf::from("/sys/class/power_supply"),
+            battery_thresholds: vec![
+                BatteryThreshold {
+                    below_percent: 30.0,
+                    sleep_multiplier: 2,
+                },
+                BatteryThreshold {
+                    below_percent: 15.0,
+                    sleep_multiplier: 4,
+                },
+            ],
+        }
+    }
+}
+
+/// Battery level entering low-power mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatteryThreshold {
+    pub below_percent: f64,
+    /// Factor `sleep_on_idle_ms` is multiplied by below this level
+    pub sleep_multiplier: u32,
+}
+
+/// Persistent key-value store shared by tool handlers and extensions
+///
+/// Every caller works in its own namespace, saved as one file under
+/// `directory`. Writes are kept in memory and saved every
+/// `flush_interval_secs` and on shutdown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KvStoreConfig {
+    pub directory: PathBuf,
+    /// Total size of keys and values in one namespace; extensions use their
+    /// `max_kv_bytes` limit instead
+    pub max_bytes_per_namespace: u64,
+    pub max_keys_per_namespace: usize,
+    /// Longest TTL a caller may set; unlimited when unset
+    pub max_ttl_secs: Option<u64>,
+    /// How often changed namespaces are saved while running
+    pub flush_interval_secs: u64,
+}
+
+impl Default for KvStoreConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("./data/kv"),
+            max_bytes_per_namespace: 1024 * 1024,
+            max_keys_per_namespace: 10_000,
+            max_ttl_secs: None,
+            flush_interval_secs: 30,
+        }
+    }
+}
+
+/// Sandboxed WebAssembly extension plugins loaded at startup; requires a
+/// gateway built with the `wasm-extensions` feature
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtensionsConfig {
+    pub enabled: bool,
+    /// Limits for plugins that set none of their own
+    pub limits: ExtensionLimits,
+    /// Plugins in the order their hooks run
+    pub plugins: Vec<ExtensionPluginConfig>,
+}
+
+/// One extension plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtensionPluginConfig {
+    /// The plugin's `manifest.json`; the component path it names is
+    /// relative to the manifest
+    pub manifest: PathBuf,
+    /// Permissions granted to the plugin; a manifest asking for any other
+    /// is refused
+    #[serde(default)]
+    pub allow: Vec<ExtensionPermission>,
+    #[serde(default)]
+    pub limits: Option<ExtensionLimits>,
+    /// Values the plugin reads through the `settings` interface
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+}
+
+/// Host interface an extension may import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtensionPermission {
+    Log,
+    Settings,
+    Kv,
+}
+
+/// Resources a single extension call may use
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtensionLimits {
+    /// Linear memory the instance may grow to
+    pub max_memory_bytes: u64,
+    /// WebAssembly fuel per call, roughly one unit per instruction
+    pub fuel_per_call: u64,
+    /// Wall-clock time per call
+    pub timeout_ms: u64,
+    /// Total size of keys and values in the plugin's `kv` store
+    pub max_kv_bytes: u64,
+}
+
+impl Default for ExtensionLimits {
+    fn default() -> Self {
+        Self {
+            max_memory_bytes: 16 * 1024 * 1024,
+            fuel_per_call: 50_000_000,
+            timeout_ms: 100,
+            max_kv_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Local audit trail for air-gapped sites
+///
+/// Every request appends an encrypted digest of the request and its
+/// response to hash-chained, signed files under `directory`, which can be
+/// copied off the device and checked with `mcp-audit verify`;
+/// `mcp-audit load-model` turns them into a load model of the traffic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditSinkConfig {
+    pub enabled: bool,
+    pub directory: PathBuf,
+    /// Start a new file once the current one reaches this size
+    pub max_file_bytes: u64,
+    /// Ed25519 key (PKCS#8) records are signed with, generated on first use
+    pub signing_key_path: PathBuf,
+    /// AES-256-GCM key (32 raw bytes) digests are encrypted with, generated
+    /// on first use; keep a copy off the device to read exported records
+    pub encryption_key_path: PathBuf,
+}
+
+impl Default for AuditSinkConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: PathBuf::from("./data/audit"),
+            max_file_bytes: 16 * 1024 * 1024,
+            signing_key_path: PathBuf::from("./pki/audit-signing.pk8"),
+            encryption_key_path: PathBuf::from("./pki/audit-encryption.key"),
+        }
+    }
+}
+
+/// Redaction applied to payloads and identifiers before they reach logs,
+/// traces or crash reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    /// Fields whose values are kept; when non-empty every other value is redacted
+    pub allow_fields: Vec<String>,
+    /// Fields whose values are always redacted
+    pub deny_fields: Vec<String>,
+    /// Identifier fields replaced by a keyed hash, so records stay correlatable
+    pub hash_fields: Vec<String>,
+    /// Key for identifier hashes; random per process when unset
+    pub hash_salt: Option<String>,
+    /// Share of payloads logged in full after field redaction; the rest are
+    /// summarized by their keys and size
+    pub full_payload_sample_rate: f64,
+    /// Longest string value or message kept
+    pub max_string_len: usize,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            allow_fields: Vec::new(),
+            deny_fields: ["password", "secret", "token", "api_key", "authorization", "credentials", "private_key"]
+                .map(str::to_string)
+                .to_vec(),
+            hash_fields: ["device_id", "user_id", "tenant_id", "session_id", "email"]
+                .map(str::to_string)
+                .to_vec(),
+            hash_salt: None,
+            full_payload_sample_rate: 0.0,
+            max_string_len: 256,
+        }
+    }
+}
+
+/// Address family handling for the listener and outbound cloud connections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Also accept IPv4 clients when bound to the IPv6 wildcard address
+    pub dual_stack: bool,
+    /// Try IPv6 first when a cloud host resolves to both families (RFC 8305)
+    pub prefer_ipv6: bool,
+    /// Consecutive connections won by IPv4 before IPv4 is tried first
+    pub ipv6_failure_threshold: u32,
+    /// How long IPv4 stays first before IPv6 is given another chance
+    pub ipv6_retry_secs: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            dual_stack: true,
+            prefer_ipv6: true,
+            ipv6_failure_threshold: 3,
+            ipv6_retry_secs: 600,
+        }
+    }
+}
+
+/// Class of persisted data with its own retention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataClass {
+    QueueEntries,
+    Sessions,
+    TelemetrySpool,
+    AuditLogs,
+    CapturedDatasets,
+}
+
+impl DataClass {
+    pub const ALL: [DataClass; 5] = [
+        DataClass::QueueEntries,
+        DataClass::Sessions,
+        DataClass::TelemetrySpool,
+        DataClass::AuditLogs,
+        DataClass::CapturedDatasets,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataClass::QueueEntries => "queue_entries",
+            DataClass::Sessions => "sessions",
+            DataClass::TelemetrySpool => "telemetry_spool",
+            DataClass::AuditLogs => "audit_logs",
+            DataClass::CapturedDatasets => "captured_datasets",
+        }
+    }
+}
+
+/// Retention of persisted data and scheduled purging
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Purge on a schedule; a purge can always be started from the admin API
+    pub enabled: bool,
+    pub purge_interval_secs: u64,
+    pub queue_entries: RetentionPolicy,
+    pub sessions: RetentionPolicy,
+    pub telemetry_spool: RetentionPolicy,
+    pub audit_logs: RetentionPolicy,
+    pub captured_datasets: RetentionPolicy,
+    /// Data exempt from purging regardless of age
+    pub legal_holds: Vec<LegalHold>,
+}
+
+impl RetentionConfig {
+    pub fn policy(&self, class: DataClass) -> &RetentionPolicy {
+        match class {
+            DataClass::QueueEntries => &self.queue_entries,
+            DataClass::Sessions => &self.sessions,
+            DataClass::TelemetrySpool => &self.telemetry_spool,
+            DataClass::AuditLogs => &self.audit_logs,
+            DataClass::CapturedDatasets => &self.captured_datasets,
+        }
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        let policy = |path: &str| RetentionPolicy {
+            max_age_days: None,
+            path: Some(PathBuf::from(path)),
+        };
+        Self {
+            enabled: false,
+            purge_interval_secs: 3600,
+            queue_entries: RetentionPolicy::default(),
+            sessions: policy("./data/sessions"),
+            telemetry_spool: policy("./data/telemetry-spool"),
+            audit_logs: policy("./logs/audit"),
+            captured_datasets: policy("./data/datasets"),
+            legal_holds: Vec::new(),
+        }
+    }
+}
+
+/// Retention of one data class
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Delete data older than this; kept indefinitely when unset
+    pub max_age_days: Option<u32>,
+    /// Directory holding the data, for classes stored as files
+    pub path: Option<PathBuf>,
+}
+
+/// Legal hold exempting a device's data or a path from purging
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegalHold {
+    pub id: String,
+    pub reason: String,
+    /// Hold everything belonging to this device
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// Hold files beneath this path
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+/// Signed compliance reports for SOC 2 / GDPR reviews
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ComplianceConfig {
+    /// Organization named in reports
+    pub organization: Option<String>,
+    /// Ed25519 key (PKCS#8) reports are signed with, generated on first use
+    pub signing_key_path: PathBuf,
+    /// Regions cloud endpoints may process data in; any region when empty
+    pub allowed_regions: Vec<String>,
+}
+
+impl Default for ComplianceConfig {
+    fn default() -> Self {
+        Self {
+            organization: None,
+            signing_key_path: PathBuf::from("./pki/compliance-report.pk8"),
+            allowed_regions: Vec::new(),
+        }
+    }
+}
+
+/// State shared between gateways running behind one load balancer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    /// Redis holding the response cache, dedup window and rate limits;
+    /// each gateway keeps its own state when unset
+    #[serde(default)]
+    pub redis: Option<RedisConfig>,
+    /// Reject a request id seen again within this many seconds, 0 to disable
+    #[serde(default)]
+    pub dedup_window_secs: u64,
+    /// Consistent hashing of devices to owning gateways
+    #[serde(default)]
+    pub routing: ClusterRoutingConfig,
+    /// Service mesh (Envoy xDS) integration
+    #[serde(default)]
+    pub mesh: MeshConfig,
+    /// Active/standby pair replicating state to each other
+    #[serde(default)]
+    pub high_availability: HighAvailabilityConfig,
+}
+
+/// Active/standby gateway pair at one site
+///
+/// The active gateway pushes the changes to sessions, queued requests and
+/// enrolled devices to its peer every `sync_interval_ms`; the standby
+/// promotes itself once it has not heard from the active for
+/// `failover_timeout_ms`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighAvailabilityConfig {
+    pub enabled: bool,
+    /// This gateway's id; the lower id stays active if both are promoted
+    pub node_id: String,
+    /// Role this gateway starts in
+    pub role: HaRole,
+    /// Base URL of the other gateway, e.g. `http://10.0.0.13:8080`
+    pub peer_url: String,
+    /// HMAC secret both gateways sign replication messages with
+    pub shared_secret: Option<String>,
+    pub sync_interval_ms: u64,
+    pub failover_timeout_ms: u64,
+    pub request_timeout_ms: u64,
+}
+
+impl Default for HighAvailabilityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            node_id: "gateway-a".to_string(),
+            role: HaRole::Active,
+            peer_url: String::new(),
+            shared_secret: None,
+            sync_interval_ms: 1000,
+            failover_timeout_ms: 5000,
+            request_timeout_ms: 2000,
+        }
+    }
+}
+
+/// Role of a gateway in an active/standby pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HaRole {
+    Active,
+    Standby,
+}
+
+/// Metadata and discovery resources published to a service mesh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeshConfig {
+    /// Envoy cluster of the gateways, and the node cluster they report
+    pub cluster_name: String,
+    /// Envoy cluster of the cloud endpoints the router forwards to
+    pub cloud_cluster_name: String,
+    /// Envoy cluster pointing at a gateway's REST xDS endpoints; when set,
+    /// published clusters fetch their endpoints from it
+    pub xds_cluster: Option<String>,
+    /// Address peers and proxies reach this gateway on when it is not a
+    /// cluster member, e.g. `10.0.0.12:8080`
+    pub advertise_address: Option<String>,
+    pub region: Option<String>,
+    pub zone: Option<String>,
+    pub sub_zone: Option<String>,
+    /// Extra labels published in node metadata
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Default for MeshConfig {
+    fn default() -> Self {
+        Self {
+            cluster_name: "mcp-gateways".to_string(),
+            cloud_cluster_name: "mcp-cloud".to_string(),
+            xds_cluster: None,
+            advertise_address: None,
+            region: None,
+            zone: None,
+            sub_zone: None,
+            labels: BTreeMap::new(),
+        }
+    }
+}
+
+/// Device ownership across cluster members
+///
+/// Each device is owned by one member for session and state locality;
+/// other members proxy its requests to the owner.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClusterRoutingConfig {
+    pub enabled: bool,
+    /// This gateway's member id
+    pub node_id: String,
+    /// Every gateway in the cluster, including this one
+    pub members: Vec<ClusterMember>,
+    /// Ring points per member; more points spread devices more evenly
+    pub virtual_nodes: u32,
+    /// How long devices stay with their previous owner after membership changes
+    pub rebalance_grace_secs: u64,
+    pub proxy_timeout_ms: u64,
+}
+
+impl Default for ClusterRoutingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            node_id: "gateway-1".to_string(),
+            members: Vec::new(),
+            virtual_nodes: 128,
+            rebalance_grace_secs: 30,
+            proxy_timeout_ms: 5000,
+        }
+    }
+}
+
+/// A gateway in the cluster
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterMember {
+    pub id: String,
+    /// Base URL other members proxy to, e.g. `http://10.0.0.12:8080`
+    pub url: String,
+}
+
+/// Redis connection settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedisConfig {
+    /// `redis://[:password@]host[:port][/db]`
+    pub url: String,
+    /// Prefix for every key, so several clusters can share one Redis
+    pub key_prefix: String,
+    /// Per-command timeout; slower commands fall back to local state
+    pub timeout_ms: u64,
+    /// How long to use local state before retrying an unreachable Redis
+    pub retry_secs: u64,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self {
+            url: "redis://127.0.0.1:6379".to_string(),
+            key_prefix: "mcp".to_string(),
+            timeout_ms: 250,
+            retry_secs: 5,
+        }
+    }
+}
+
+/// Destinations completed responses are pushed to
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutputsConfig {
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub connectors: Vec<ConnectorConfig>,
+    /// S3-compatible store for large artifacts
+    #[serde(default)]
+    pub artifacts: Option<ArtifactStoreConfig>,
+}
+
+/// S3-compatible object store (AWS S3, MinIO) for large artifacts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactStoreConfig {
+    /// Endpoint URL, e.g. `http://minio.local:9000`; buckets are addressed path-style
+    pub endpoint: String,
+    pub bucket: String,
+    /// Signing region, `us-east-1` when unset
+    #[serde(default)]
+    pub region: Option<String>,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Tags applied to every object, for bucket lifecycle rules
+    #[serde(default)]
+    pub default_tags: BTreeMap<String, String>,
+    #[serde(default)]
+    pub upload: ArtifactUploadConfig,
+}
+
+/// Multipart, resume and throttling settings for artifact uploads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactUploadConfig {
+    /// Files larger than this are uploaded in parts
+    pub multipart_threshold_bytes: u64,
+    /// Part size; S3 requires at least 5 MiB for all but the last part
+    pub part_size_bytes: u64,
+    /// Upload bandwidth cap in bytes per second; 0 means unlimited
+    pub max_bytes_per_sec: u64,
+    /// Directory holding resume state for interrupted multipart uploads
+    pub state_dir: PathBuf,
+    pub timeout_ms: u64,
+    pub max_retries: u32,
+}
+
+impl Default for ArtifactUploadConfig {
+    fn default() -> Self {
+        Self {
+            multipart_threshold_bytes: 16 * 1024 * 1024,
+            part_size_bytes: 8 * 1024 * 1024,
+            max_bytes_per_sec: 0,
+            state_dir: PathBuf::from("./uploads"),
+            timeout_ms: 60000,
+            max_retries: 3,
+        }
+    }
+}
+
+/// Message broker connector publishing telemetry and completed responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectorConfig {
+    pub name: String,
+    pub broker: BrokerConfig,
+    #[serde(default)]
+    pub format: PayloadFormat,
+    /// Topic or subject for completed responses; unset disables them
+    #[serde(default)]
+    pub responses_subject: Option<String>,
+    /// Topic or subject for telemetry batches; unset disables them
+    #[serde(default)]
+    pub telemetry_subject: Option<String>,
+    /// Seconds between telemetry batches, 60 when unset
+    #[serde(default)]
+    pub telemetry_interval_secs: Option<u64>,
+    /// Per-publish timeout, 5000 when unset
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// Broker a connector publishes to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BrokerConfig {
+    /// Kafka through a REST Proxy (v2 API); requires the `kafka` feature
+    Kafka { rest_proxy_url: String },
+    /// NATS, optionally waiting for JetStream acks; requires the `nats` feature
+    Nats {
+        url: String,
+        #[serde(default)]
+        jetstream: bool,
+    },
+}
+
+/// Serialization used for connector payloads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+/// Webhook that receives completed responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub name: String,
+    pub url: String,
+    /// Shared secret used to sign payloads with HMAC-SHA256
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Methods delivered to this webhook; empty means every method
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Request tags (the `tags` param) of which one must match; empty means any
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub delivery: WebhookDeliveryConfig,
+}
+
+/// Retry and circuit breaking for webhook delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDeliveryConfig {
+    pub timeout_ms: u64,
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further attempt
+    pub retry_backoff_ms: u64,
+    /// Consecutive failed deliveries before the webhook is skipped
+    pub failure_threshold: u32,
+    /// How long a tripped webhook is skipped before it is tried again
+    pub open_duration_secs: u64,
+}
+
+impl Default for WebhookDeliveryConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 5000,
+            max_retries: 3,
+            retry_backoff_ms: 500,
+            failure_threshold: 5,
+            open_duration_secs: 60,
+        }
+    }
+}
+
+/// Storage backend all component file IO goes through
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    #[serde(default)]
+    pub health: StorageHealthConfig,
+}
+
+/// Wear monitoring of the storage media from SMART and eMMC health reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageHealthConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Block devices to check by name (`mmcblk0`, `sda`, `nvme0n1`); every
+    /// disk under `sys_block_dir` when empty
+    pub devices: Vec<String>,
+    pub sys_block_dir: PathBuf,
+    /// smartctl binary for SATA and NVMe drives; eMMC is read from sysfs
+    pub smartctl: Option<String>,
+    /// Estimated life used at which a warning is raised
+    pub wear_warning_percent: u8,
+    pub wear_critical_percent: u8,
+    /// Reallocated sectors at which a drive counts as degrading
+    pub reallocated_sectors_warning: u64,
+    /// Factor periodic saves are stretched and syncs batched by on degraded
+    /// media, 1 to keep writing as usual
+    pub degraded_write_relaxation: u32,
+}
+
+impl Default for StorageHealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 3600,
+            devices: Vec::new(),
+            sys_block_dir: PathBuf::from("/sys/block"),
+            smartctl: Some("smartctl".to_string()),
+            wear_warning_percent: 70,
+            wear_critical_percent: 90,
+            reallocated_sectors_warning: 10,
+            degraded_write_relaxation: 4,
+        }
+    }
+}
+
+/// Virtual filesystem backend selection
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// Host filesystem, paths used as configured
+    #[default]
+    Local,
+    /// Volatile in-memory storage (WASM targets, tests)
+    Memory,
+    /// Read-only host filesystem with writes redirected beneath `upper_dir`
+    Overlay { upper_dir: PathBuf },
+}
+
+/// Concurrency limits for expensive component operations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcurrencyConfig {
+    pub local_inference: ConcurrencyLimit,
+    pub cloud_forward: ConcurrencyLimit,
+    pub queue_sync: ConcurrencyLimit,
+    /// Per-model admission of local inferences
+    #[serde(default)]
+    pub per_model: ModelSchedulingConfig,
+}
+
+/// Bounds on in-flight and queued inferences for each local model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelSchedulingConfig {
+    pub enabled: bool,
+    /// Limit applied to models without an entry in `models`
+    pub default: ConcurrencyLimit,
+    /// Limits keyed by model id
+    pub models: HashMap<String, ConcurrencyLimit>,
+}
+
+impl Default for ModelSchedulingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default: ConcurrencyLimit {
+                max_concurrent: 1,
+                max_queued: 16,
+                queue_timeout_ms: 10000,
+                critical_reserve: 0,
+            },
+            models: HashMap::new(),
+        }
+    }
+}
+
+/// Limit for a single component
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConcurrencyLimit {
+    /// Operations allowed to run at the same time
+    pub max_concurrent: usize,
+    /// Callers allowed to wait for a slot before new ones are rejected
+    pub max_queued: usize,
+    /// How long a waiting caller is kept before being rejected
+    pub queue_timeout_ms: u64,
+    /// Extra slots only critical requests may use, so they never wait
+    /// behind a saturated component
+    #[serde(default)]
+    pub critical_reserve: usize,
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            local_inference: ConcurrencyLimit {
+                max_concurrent: 2,
+                max_queued: 32,
+                queue_timeout_ms: 10000,
+                critical_reserve: 1,
+            },
+            cloud_forward: ConcurrencyLimit {
+                max_concurrent: 16,
+                max_queued: 128,
+                queue_timeout_ms: 5000,
+                critical_reserve: 0,
+            },
+            queue_sync: ConcurrencyLimit {
+                max_concurrent: 4,
+                max_queued: 16,
+                queue_timeout_ms: 30000,
+                critical_reserve: 0,
+            },
+            per_model: ModelSchedulingConfig::default(),
+        }
+    }
 }
 
 /// Gateway configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GatewayConfig {
-    pub bind_address: String,
+pub struct GatewayConfig {
+    pub bind_address: String,
+    pub port: u16,
+    pub max_connections: u32,
+    pub request_timeout_ms: u64,
+    pub max_request_size_bytes: u64,
+    pub enable_cors: bool,
+    pub cors_origins: Vec<String>,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    #[serde(default)]
+    pub clock_skew: ClockSkewConfig,
+    #[serde(default)]
+    pub health_checks: HealthCheckConfig,
+    #[serde(default)]
+    pub resource_accounting: ResourceAccountingConfig,
+    #[serde(default)]
+    pub bandwidth: BandwidthConfig,
+    #[serde(default)]
+    pub request_ids: RequestIdConfig,
+    #[serde(default)]
+    pub grpc: GrpcConfig,
+    #[serde(default)]
+    pub mqtt: MqttBridgeConfig,
+    #[serde(default)]
+    pub stage_timings: StageTimingsConfig,
+    #[serde(default)]
+    pub synthetic_probes: SyntheticProbesConfig,
+    #[serde(default)]
+    pub emulation: EmulationConfig,
+    #[serde(default)]
+    pub config_reload: ConfigReloadConfig,
+    #[serde(default)]
+    pub admin_safety: AdminSafetyConfig,
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    #[serde(default)]
+    pub response_provenance: ResponseProvenanceConfig,
+}
+
+/// Maintenance mode configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// Methods that keep being processed while maintenance mode is active
+    pub allowed_methods: Vec<String>,
+    /// Duration applied when maintenance is enabled without an explicit one
+    pub default_duration_secs: u64,
+    /// Upper bound for any requested maintenance window
+    pub max_duration_secs: u64,
+    /// Banner shown to clients when no operator message is supplied
+    pub default_message: String,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            allowed_methods: vec!["health".to_string()],
+            default_duration_secs: 30 * 60,
+            max_duration_secs: 24 * 60 * 60,
+            default_message: "Gateway is under maintenance; requests are queued".to_string(),
+        }
+    }
+}
+
+/// Device clock skew detection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClockSkewConfig {
+    /// Skew beyond which request timestamps are corrected and flagged
+    pub significant_skew_ms: u64,
+    /// Weight of the newest sample in the smoothed per-device skew (0-1]
+    pub smoothing: f64,
+    /// Devices tracked at once; the least recently seen is evicted first
+    pub max_tracked_devices: usize,
+}
+
+impl Default for ClockSkewConfig {
+    fn default() -> Self {
+        Self {
+            significant_skew_ms: 5000,
+            smoothing: 0.2,
+            max_tracked_devices: 10_000,
+        }
+    }
+}
+
+/// Health probes served to load balancers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    /// Serve probes without credentials; otherwise `probe_token` is required
+    pub auth_exempt: bool,
+    /// Bearer token load balancers present when probes are not exempt
+    pub probe_token: Option<String>,
+    /// How long a probe result is reused before components are checked again
+    pub cache_ttl_ms: u64,
+    /// Report not serving while degraded, not only when critical
+    pub fail_on_degraded: bool,
+    /// Report not serving during maintenance so load balancers drain the gateway
+    pub drain_during_maintenance: bool,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            auth_exempt: true,
+            probe_token: None,
+            cache_ttl_ms: 1000,
+            fail_on_degraded: false,
+            drain_during_maintenance: true,
+        }
+    }
+}
+
+/// Per-request CPU, memory and IO accounting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceAccountingConfig {
+    pub enabled: bool,
+    /// Attach the measured usage to response results as `resource_usage`
+    pub include_in_response: bool,
+    /// CPU time above which a request is reported as pathological
+    pub pathological_cpu_ms: u64,
+    /// Peak memory growth above which a request is reported as pathological
+    pub pathological_memory_bytes: u64,
+}
+
+impl Default for ResourceAccountingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            include_in_response: false,
+            pathological_cpu_ms: 5000,
+            pathological_memory_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// Per-request breakdown of where latency goes, always recorded on traces
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StageTimingsConfig {
+    /// Attach the breakdown to response results as `stage_timings`; meant
+    /// for debugging, as it changes every response
+    pub include_in_response: bool,
+}
+
+/// Canary requests the gateway sends itself to catch silent model or
+/// routing failures before callers do
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyntheticProbesConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Consecutive failures of a probe before it is reported critical
+    pub failure_threshold: u32,
+    pub probes: Vec<SyntheticProbeConfig>,
+}
+
+impl Default for SyntheticProbesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 300,
+            failure_threshold: 2,
+            probes: vec![SyntheticProbeConfig::default()],
+        }
+    }
+}
+
+/// One synthetic request and what its response must look like
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyntheticProbeConfig {
+    pub name: String,
+    pub method: String,
+    pub params: HashMap<String, serde_json::Value>,
+    /// Text the serialized result must contain, if any
+    pub expect_contains: Option<String>,
+    /// Latency above which the probe counts as failed
+    pub max_latency_ms: u64,
+}
+
+impl Default for SyntheticProbeConfig {
+    fn default() -> Self {
+        Self {
+            name: "canary_completion".to_string(),
+            method: "completion".to_string(),
+            params: HashMap::from([
+                ("prompt".to_string(), serde_json::json!("Reply with OK.")),
+                ("max_tokens".to_string(), serde_json::json!(4)),
+            ]),
+            expect_contains: None,
+            max_latency_ms: 10_000,
+        }
+    }
+}
+
+/// Watching the configuration file and applying changes without a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigReloadConfig {
+    /// File the configuration was loaded from; nothing is watched without one
+    pub path: Option<PathBuf>,
+    /// How often the file is checked for changes, 0 to only reload on request
+    pub interval_secs: u64,
+}
+
+impl Default for ConfigReloadConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            interval_secs: 5,
+        }
+    }
+}
+
+/// Guards on destructive admin operations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminSafetyConfig {
+    /// Journal destructive operations, keep deleted data for undo and
+    /// require a confirmation token for irreversible ones
+    pub enabled: bool,
+    /// How long deleted conversations and keys can be restored
+    pub undo_window_secs: u64,
+    /// How long a confirmation token can be redeemed
+    pub confirmation_ttl_secs: u64,
+    /// Operations kept in the journal; the oldest are dropped first
+    pub journal_size: usize,
+}
+
+impl Default for AdminSafetyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            undo_window_secs: 15 * 60,
+            confirmation_ttl_secs: 120,
+            journal_size: 500,
+        }
+    }
+}
+
+/// Deduplication of retried requests by their idempotency key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdempotencyConfig {
+    pub enabled: bool,
+    /// How long a completed response is returned to retries with its key
+    pub window_secs: u64,
+    /// Completed responses kept; the oldest are dropped first
+    pub max_entries: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_secs: 600,
+            max_entries: 10_000,
+        }
+    }
+}
+
+/// Provenance metadata attached to response results: which device, gateway
+/// version and model produced them, and whether on the device or in the cloud
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseProvenanceConfig {
+    pub enabled: bool,
+    /// Sign the metadata with the device key; it is attached unsigned when
+    /// the security manager has no device key
+    pub sign: bool,
+}
+
+impl Default for ResponseProvenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sign: true,
+        }
+    }
+}
+
+/// Emulation mode: canned responses per method instead of real models, so
+/// client teams can develop and run CI without model downloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmulationConfig {
+    pub enabled: bool,
+    /// Latency added to every emulated response
+    pub latency_ms: u64,
+    /// Further latency of up to this much, derived from the request so the
+    /// same request always waits as long
+    pub jitter_ms: u64,
+    /// Canned response per method; completion, chat, embedding and
+    /// summarization have built-in responses
+    pub fixtures: HashMap<String, EmulationFixture>,
+    /// JSON file of further fixtures by method; inline `fixtures` win
+    pub fixtures_path: Option<PathBuf>,
+}
+
+impl Default for EmulationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latency_ms: 50,
+            jitter_ms: 0,
+            fixtures: HashMap::new(),
+            fixtures_path: None,
+        }
+    }
+}
+
+/// Canned response for one method
+///
+/// String values in `result` may name request params as `{{param}}`, which
+/// are replaced with the param's value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmulationFixture {
+    pub result: serde_json::Value,
+    /// Fail with this model error instead of returning `result`
+    pub error: Option<String>,
+    /// Latency for this method in place of `latency_ms`
+    pub latency_ms: Option<u64>,
+}
+
+/// Network traffic accounting per subsystem
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthConfig {
+    /// File the daily and monthly counters are saved to, in gateway storage
+    pub state_path: PathBuf,
+    /// How often the counters are saved while running
+    pub flush_interval_secs: u64,
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        Self {
+            state_path: PathBuf::from("./data/bandwidth.json"),
+            flush_interval_secs: 60,
+        }
+    }
+}
+
+/// How request IDs are generated and which client-supplied IDs are accepted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestIdConfig {
+    /// Scheme for IDs the gateway generates
+    pub scheme: IdScheme,
+    /// Use the `id` a client sends instead of generating one
+    pub accept_client_ids: bool,
+    /// Recently issued IDs remembered to detect a client reusing another device's ID
+    pub collision_window: usize,
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        Self {
+            scheme: IdScheme::UuidV7,
+            accept_client_ids: true,
+            collision_window: 4096,
+        }
+    }
+}
+
+/// MCP over gRPC, served on its own port next to HTTP/WebSocket; needs the
+/// gateway built with the `grpc` feature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    pub enabled: bool,
+    /// Port on `bind_address` the gRPC server listens on
     pub port: u16,
-    pub max_connections: u32,
-    pub request_timeout_ms: u64,
-    pub max_request_size_bytes: u64,
-    pub enable_cors: bool,
-    pub cors_origins: Vec<String>,
 }
 
-/// Router configuration
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 50051,
+        }
+    }
+}
+
+/// MQTT ingestion bridge for sensors that publish requests to a broker;
+/// needs the gateway built with the `mqtt` feature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttBridgeConfig {
+    pub enabled: bool,
+    /// `mqtt://host[:port]`; the port defaults to 1883
+    pub broker_url: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Topic filters requests are read from; `+` and `#` wildcards allowed
+    pub request_topics: Vec<String>,
+    /// Topic responses are published to unless a request names its own
+    /// `reply_topic`; `{device_id}` is replaced by the requesting device
+    pub response_topic: String,
+    /// QoS of subscriptions and responses; 0 or 1
+    pub qos: u8,
+    /// Start without the broker's stored subscriptions and undelivered messages
+    pub clean_session: bool,
+    pub keep_alive_secs: u16,
+    /// First reconnect delay, doubled after each failed attempt
+    pub reconnect_delay_secs: u64,
+    pub max_reconnect_delay_secs: u64,
+    /// Requests processed at once; further messages wait for a slot
+    pub max_in_flight: usize,
+}
+
+impl Default for MqttBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            broker_url: "mqtt://localhost:1883".to_string(),
+            client_id: "mcp-gateway".to_string(),
+            username: None,
+            password: None,
+            request_topics: vec!["mcp/requests/+".to_string()],
+            response_topic: "mcp/responses/{device_id}".to_string(),
+            qos: 1,
+            clean_session: false,
+            keep_alive_secs: 30,
+            reconnect_delay_secs: 1,
+            max_reconnect_delay_secs: 60,
+            max_in_flight: 16,
+        }
+    }
+}
+
+/// Request ID scheme
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdScheme {
+    /// Random UUIDs, as issued before time-sortable IDs were introduced
+    UuidV4,
+    /// RFC 9562 UUIDv7: millisecond timestamp followed by random bits
+    #[default]
+    UuidV7,
+    /// ULID: 48-bit millisecond timestamp and 80 random bits, monotonic within a millisecond
+    Ulid,
+}
+
+/// Per-method latency budgets and cloud forwarding timeouts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimeoutConfig {
+    /// Overrides keyed by MCP method name
+    pub methods: HashMap<String, MethodTimeouts>,
+    /// TCP/TLS connect timeout for cloud forwarding, unless the endpoint overrides it
+    pub cloud_connect_timeout_ms: Option<u64>,
+}
+
+/// Timeout overrides for a single method
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MethodTimeouts {
+    /// End-to-end budget, replacing `gateway.request_timeout_ms`
+    pub total_ms: Option<u64>,
+    /// Local inference budget, replacing `models.model_timeout_ms`
+    pub inference_ms: Option<u64>,
+    /// Cloud response budget, replacing the endpoint `timeout_ms`
+    pub cloud_read_ms: Option<u64>,
+}
+
+/// Upper bound accepted for any configured timeout (10 minutes)
+pub const MAX_TIMEOUT_MS: u64 = 10 * 60 * 1000;
+
+/// Router configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouterConfig {
+    pub strategy: RoutingStrategy,
+    pub local_processing_threshold: f32,
+    pub cloud_fallback_enabled: bool,
+    pub cloud_endpoints: Vec<CloudEndpoint>,
+    pub load_balancing: LoadBalancingConfig,
+    #[serde(default)]
+    pub warm_standby: WarmStandbyConfig,
+    /// Per-method endpoint lists the router fails over along
+    #[serde(default)]
+    pub routes: Vec<CloudRoute>,
+    /// Traffic splits between versions of local models, changed at runtime
+    /// through the admin API
+    #[serde(default)]
+    pub rollouts: Vec<ModelRollout>,
+    /// User-defined routing rules, evaluated before the heuristic router
+    #[serde(default)]
+    pub policy: RoutingPolicyConfig,
+    /// Racing slow local inferences against the cloud
+    #[serde(default)]
+    pub hedging: HedgingConfig,
+}
+
+/// Hedged cloud fallback for local inferences
+///
+/// A local inference that has produced no token within
+/// `fallback_threshold_ms` is raced against the same request sent to the
+/// cloud; whichever completes first answers and the other is cancelled.
+/// Requests that must stay local are never sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HedgingConfig {
+    pub enabled: bool,
+    /// How long local inference gets to produce its first token
+    pub fallback_threshold_ms: u64,
+    /// Methods that are hedged; empty hedges every locally routed method
+    pub methods: Vec<String>,
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fallback_threshold_ms: 1500,
+            methods: vec!["completion".to_string()],
+        }
+    }
+}
+
+impl HedgingConfig {
+    /// Whether requests for `method` are hedged
+    pub fn applies_to(&self, method: &str) -> bool {
+        self.enabled && (self.methods.is_empty() || self.methods.iter().any(|hedged| hedged == method))
+    }
+}
+
+/// Declarative routing rules; the first rule whose conditions all hold
+/// decides where a request goes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingPolicyConfig {
+    pub enabled: bool,
+    /// Log the rule that would fire without applying it
+    pub dry_run: bool,
+    /// Directory of the kernel's power supplies, read for battery conditions
+    pub power_supply_dir: PathBuf,
+    pub rules: Vec<RoutingRule>,
+}
+
+impl Default for RoutingPolicyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dry_run: false,
+            power_supply_dir: PathBuf::from("/sys/class/power_supply"),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// A routing rule; conditions that are unset always hold
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingRule {
+    pub name: String,
+    /// Methods the rule applies to; every method when empty
+    pub methods: Vec<String>,
+    /// Bounds on the size of the request's params as JSON, in bytes
+    pub min_params_bytes: Option<usize>,
+    pub max_params_bytes: Option<usize>,
+    /// Bounds on the estimated prompt tokens
+    pub min_tokens: Option<u64>,
+    pub max_tokens: Option<u64>,
+    /// Bounds on the device's battery charge; never hold on devices
+    /// without a battery
+    pub min_battery_percent: Option<f64>,
+    pub max_battery_percent: Option<f64>,
+    /// ISO 639-1 codes of the detected request languages the rule applies
+    /// to, or does not apply to; set conditions never hold for requests
+    /// whose language is unknown
+    pub languages: Vec<String>,
+    pub excluded_languages: Vec<String>,
+    /// Local time of day, as `HH:MM`, the rule applies from; wraps past
+    /// midnight when after `until`
+    pub from: Option<String>,
+    pub until: Option<String>,
+    pub target: RoutingTarget,
+    /// Local model to serve matching requests with; the router's choice when unset
+    pub model: Option<ModelId>,
+    /// `router.cloud_endpoints` entry to forward matching requests to; the
+    /// load balancer's pick when unset
+    pub endpoint: Option<String>,
+}
+
+/// Where a routing rule sends requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutingTarget {
+    #[default]
+    Local,
+    Cloud,
+    Queue,
+}
+
+/// Minutes past midnight of an `HH:MM` time
+pub fn parse_time_of_day(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl RoutingRule {
+    fn validate(&self, endpoints: &[CloudEndpoint]) -> Result<()> {
+        let invalid = |reason: &str| Error::Configuration(format!("router.policy rule '{}' {}", self.name, reason));
+        if self.name.is_empty() {
+            return Err(Error::Configuration("router.policy rules need a name".to_string()));
+        }
+        for time in self.from.iter().chain(&self.until) {
+            if parse_time_of_day(time).is_none() {
+                return Err(invalid(&format!("has invalid time '{}', expected HH:MM", time)));
+            }
+        }
+        if self.from.is_some() != self.until.is_some() {
+            return Err(invalid("needs both from and until"));
+        }
+        if self.model.is_some() && self.target != RoutingTarget::Local {
+            return Err(invalid("names a model but does not route locally"));
+        }
+        match &self.endpoint {
+            Some(_) if self.target != RoutingTarget::Cloud => Err(invalid("names an endpoint but does not route to the cloud")),
+            Some(name) if !endpoints.iter().any(|endpoint| &endpoint.name == name) => {
+                Err(invalid(&format!("names unknown cloud endpoint '{}'", name)))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Split of a model's traffic between its stable version and a candidate
+///
+/// Version `v` of model `m` is served as model id `m@v`, loaded from its own
+/// file like any other model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelRollout {
+    /// Model id requests are routed to before the split applies
+    pub model: ModelId,
+    /// Version serving the traffic the candidate does not get
+    pub stable: String,
+    /// Version under evaluation, if any
+    #[serde(default)]
+    pub candidate: Option<String>,
+    /// Share of devices sent to the candidate, from 0 to 100
+    #[serde(default)]
+    pub candidate_percent: u8,
+}
+
+impl ModelRollout {
+    pub fn validate(&self) -> Result<()> {
+        let mut versions = std::iter::once(&self.stable).chain(self.candidate.as_ref());
+        if self.model.is_empty() || self.model.contains('@') {
+            return Err(Error::Configuration(format!("Invalid rollout model id '{}'", self.model)));
+        }
+        if let Some(version) = versions.find(|version| version.is_empty() || version.contains('@')) {
+            return Err(Error::Configuration(format!(
+                "Invalid version '{}' in rollout of {}",
+                version, self.model
+            )));
+        }
+        if self.candidate_percent > 100 {
+            return Err(Error::Configuration(format!(
+                "Rollout of {} sends {}% of traffic to its candidate",
+                self.model, self.candidate_percent
+            )));
+        }
+        if self.candidate.as_ref() == Some(&self.stable) {
+            return Err(Error::Configuration(format!(
+                "Rollout of {} has the same stable and candidate version",
+                self.model
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Cloud endpoints, by name, that requests for some methods are sent to in
+/// order: when one fails for a reason other than the request itself, the
+/// next is tried. Endpoints marked unhealthy move to the back of the list.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloudRoute {
+    /// Methods the route applies to; every method when empty
+    pub methods: Vec<String>,
+    /// Names of `router.cloud_endpoints` entries
+    pub endpoints: Vec<String>,
+}
+
+impl CloudRoute {
+    pub fn matches(&self, method: &str) -> bool {
+        self.methods.is_empty() || self.methods.iter().any(|m| m == method)
+    }
+}
+
+/// Keeps a connection to the primary cloud endpoint open so the first
+/// cloud fallback does not pay for TCP and TLS setup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarmStandbyConfig {
+    pub enabled: bool,
+    /// How often the connection is refreshed; keep below the endpoint's
+    /// keep-alive timeout
+    pub refresh_interval_secs: u64,
+    /// Stop refreshing after this long without cloud traffic, resuming on the
+    /// next forwarded request; refresh indefinitely when unset
+    pub idle_timeout_secs: Option<u64>,
+    /// Longest delay between refreshes while the endpoint is unreachable
+    pub max_backoff_secs: u64,
+}
+
+impl Default for WarmStandbyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            refresh_interval_secs: 30,
+            idle_timeout_secs: Some(1800),
+            max_backoff_secs: 300,
+        }
+    }
+}
+
+/// Routing strategy options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RoutingStrategy {
+    ComplexityBased,
+    ResourceAware,
+    PerformanceOptimized,
+    Hybrid { weights: RoutingWeights },
+}
+
+/// Routing weights for hybrid strategy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingWeights {
+    pub complexity: f32,
+    pub resource_usage: f32,
+    pub historical_performance: f32,
+}
+
+/// Cloud endpoint configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudEndpoint {
+    pub name: String,
+    pub url: String,
+    pub api_key: Option<String>,
+    pub timeout_ms: u64,
+    pub max_retries: u32,
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// Region the endpoint processes data in, for data-residency reporting
+    #[serde(default)]
+    pub region: Option<String>,
+    /// API the endpoint speaks
+    #[serde(default)]
+    pub provider: CloudProviderConfig,
+    /// Codec for request bodies forwarded to the endpoint; gateway (`mcp`)
+    /// endpoints only
+    #[serde(default)]
+    pub compression: PayloadCompression,
+}
+
+/// API a cloud endpoint speaks, with the settings that API needs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CloudProviderConfig {
+    /// Another MCP gateway, which takes the request as is
+    #[default]
+    Mcp,
+    /// OpenAI or a compatible chat completions API; the endpoint `url` is
+    /// the API base, such as `https://api.openai.com/v1`
+    #[serde(rename = "openai")]
+    OpenAi {
+        /// Model to request, otherwise the one the client asked for
+        #[serde(default)]
+        model: Option<String>,
+    },
+    /// Anthropic messages API; the endpoint `url` is `https://api.anthropic.com`
+    Anthropic {
+        #[serde(default)]
+        model: Option<String>,
+        /// `anthropic-version` header, 2023-06-01 when unset
+        #[serde(default)]
+        version: Option<String>,
+        /// Used when the request sets no `max_tokens`, which the API
+        /// requires; 1024 when unset
+        #[serde(default)]
+        max_tokens: Option<u32>,
+    },
+    /// Azure OpenAI; the endpoint `url` is the resource endpoint, such as
+    /// `https://example.openai.azure.com`
+    #[serde(rename = "azure_openai")]
+    AzureOpenAi { deployment: String, api_version: String },
+}
+
+/// Load balancing configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadBalancingConfig {
+    pub algorithm: LoadBalancingAlgorithm,
+    pub health_check_interval_ms: u64,
+    pub failure_threshold: u32,
+}
+
+/// Load balancing algorithms
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LoadBalancingAlgorithm {
+    RoundRobin,
+    LeastConnections,
+    WeightedRoundRobin,
+    HealthBased,
+}
+
+/// Models configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelsConfig {
+    pub models_directory: PathBuf,
+    pub cache_size_mb: u32,
+    pub max_models_in_memory: u32,
+    pub model_timeout_ms: u64,
+    pub auto_optimization: bool,
+    pub supported_formats: Vec<String>,
+    #[serde(default)]
+    pub integrity: ModelIntegrityConfig,
+    #[serde(default)]
+    pub aliases: ModelAliasConfig,
+    #[serde(default)]
+    pub verification: VerificationConfig,
+    #[serde(default)]
+    pub retrieval: RetrievalConfig,
+    #[serde(default)]
+    pub plugins: ModelPluginsConfig,
+    #[serde(default)]
+    pub streaming: StreamingConfig,
+    #[serde(default)]
+    pub provenance: ModelProvenanceConfig,
+    #[serde(default)]
+    pub signing: ModelSigningConfig,
+    #[serde(default)]
+    pub result_store: ModelResultStoreConfig,
+    #[serde(default)]
+    pub memory_admission: InferenceMemoryConfig,
+    #[serde(default)]
+    pub prefix_cache: PrefixCacheConfig,
+    #[serde(default)]
+    pub embedding_batching: EmbeddingBatchConfig,
+    /// Sets of local models a request can be run against at once, selected
+    /// by the request's `ensemble` param
+    #[serde(default)]
+    pub ensembles: Vec<ModelEnsembleConfig>,
+    #[serde(default)]
+    pub response_cache: ResponseCacheConfig,
+}
+
+/// In-memory cache of model responses, optionally saved across restarts
+///
+/// Responses are keyed by model, method and params. In semantic mode, a
+/// request of one of `semantic_methods` whose other params match a cached
+/// entry also hits when its prompt embeds within `similarity_threshold` of
+/// the cached prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheConfig {
+    pub enabled: bool,
+    /// Total size of cached responses before the least recently used are evicted
+    pub max_size_mb: u64,
+    pub ttl_secs: u64,
+    /// Methods whose responses are cached; any when empty
+    pub methods: Vec<String>,
+    /// File the cache is saved to on shutdown and loaded from at startup
+    pub persist_path: Option<PathBuf>,
+    pub semantic: bool,
+    pub semantic_methods: Vec<String>,
+    /// Cosine similarity of prompt embeddings, from 0 to 1, at which a
+    /// cached response is reused
+    pub similarity_threshold: f32,
+    pub embedding_dimensions: usize,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size_mb: 64,
+            ttl_secs: 300,
+            methods: vec![
+                "completion".to_string(),
+                "embedding".to_string(),
+                "summarization".to_string(),
+            ],
+            persist_path: None,
+            semantic: false,
+            semantic_methods: vec!["completion".to_string()],
+            similarity_threshold: 0.95,
+            embedding_dimensions: 256,
+        }
+    }
+}
+
+/// Local models that each answer a request, and how their answers are merged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelEnsembleConfig {
+    pub name: String,
+    pub models: Vec<ModelId>,
+    #[serde(default)]
+    pub strategy: EnsembleMergeStrategy,
+}
+
+/// How the answers of an ensemble's models become one response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnsembleMergeStrategy {
+    /// The answer most models gave; ties go to the model listed first
+    #[default]
+    Vote,
+    /// The answer the verifier scores highest
+    RankByVerifier,
+    /// Every model's answer as its own section
+    ConcatSections,
+}
+
+impl ModelEnsembleConfig {
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| {
+            Error::Configuration(format!("models.ensembles '{}' {}", self.name, reason))
+        };
+        if self.name.is_empty() {
+            return Err(invalid("needs a name"));
+        }
+        if self.models.len() < 2 {
+            return Err(invalid("needs at least two models"));
+        }
+        let distinct: HashSet<&ModelId> = self.models.iter().collect();
+        if distinct.len() != self.models.len() {
+            return Err(invalid("lists a model more than once"));
+        }
+        Ok(())
+    }
+}
+
+/// Coalescing of single embedding requests into batched model invocations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingBatchConfig {
+    pub enabled: bool,
+    /// How long the first request of a batch waits for others to join
+    pub window_ms: u64,
+    /// A batch runs as soon as it holds this many requests
+    pub max_batch_size: usize,
+}
+
+impl Default for EmbeddingBatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_ms: 5,
+            max_batch_size: 32,
+        }
+    }
+}
+
+/// Reuse of the KV cache between turns of a session
+///
+/// Requests carrying a `session_id` param are evaluated over their whole
+/// conversation, and the prefix shared with the session's previous turn is
+/// not evaluated again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrefixCacheConfig {
+    pub enabled: bool,
+    /// Memory the cached sessions may hold; least recently used sessions
+    /// are evicted beyond it
+    pub max_cache_mb: u64,
+    /// A session's cache is dropped once it has been idle this long
+    pub session_ttl_secs: u64,
+}
+
+impl Default for PrefixCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_cache_mb: 256,
+            session_ttl_secs: 1800,
+        }
+    }
+}
+
+/// Admission of local inferences by their estimated KV-cache and activation
+/// memory, checked against the memory the device has available
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InferenceMemoryConfig {
+    pub enabled: bool,
+    /// Device memory kept free for the rest of the system
+    pub reserve_mb: u64,
+    /// Tokens assumed to be generated for requests without `max_tokens`
+    pub default_max_tokens: u32,
+    /// How long a request waits for running inferences to release memory
+    /// before it is rejected; 0 rejects at once
+    pub wait_ms: u64,
+}
+
+impl Default for InferenceMemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reserve_mb: 256,
+            default_max_tokens: 256,
+            wait_ms: 2000,
+        }
+    }
+}
+
+/// On-disk store of inference results for deterministic requests, keyed by
+/// model digest and request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelResultStoreConfig {
+    pub enabled: bool,
+    /// Directory holding the results; `results` in the models directory when
+    /// unset
+    pub directory: Option<PathBuf>,
+    /// Methods whose results are stored; any when empty
+    pub methods: Vec<String>,
+    /// Total size of stored results before the least recently used are evicted
+    pub max_size_mb: u64,
+    /// Results larger than this are not stored
+    pub max_entry_kb: u64,
+}
+
+impl Default for ModelResultStoreConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            methods: Vec::new(),
+            max_size_mb: 256,
+            max_entry_kb: 1024,
+        }
+    }
+}
+
+/// License and source tracking for model files, and the license policy they
+/// are loaded under
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelProvenanceConfig {
+    /// Manifest describing each model's license, source and checksum;
+    /// `manifest.json` in the models directory when unset
+    pub manifest_path: Option<PathBuf>,
+    /// Refuse models the manifest gives no license for
+    pub require_license: bool,
+    /// Refuse models licensed for non-commercial use only
+    pub commercial_use: bool,
+    /// License identifiers that may be loaded; any when empty. A trailing `*`
+    /// matches a prefix, e.g. `Apache-*`
+    pub allowed_licenses: Vec<String>,
+    /// License identifiers that are never loaded
+    pub denied_licenses: Vec<String>,
+}
+
+/// What happens to a model file without a valid signed manifest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelSignatureMode {
+    /// Manifests are not read
+    #[default]
+    Off,
+    /// Load the model and log a warning
+    Warn,
+    /// Refuse to load the model
+    Enforce,
+}
+
+/// Signed manifests shipped next to model files
+///
+/// A manifest records the file's SHA-256, quantization, license and the
+/// methods the model may serve, signed with the publisher's Ed25519 key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelSigningConfig {
+    pub mode: ModelSignatureMode,
+    /// Base64 Ed25519 public keys manifests may be signed with
+    pub trusted_keys: Vec<String>,
+    /// Appended to a model file's path to find its signed manifest
+    pub manifest_suffix: String,
+}
+
+impl Default for ModelSigningConfig {
+    fn default() -> Self {
+        Self {
+            mode: ModelSignatureMode::Off,
+            trusted_keys: Vec::new(),
+            manifest_suffix: ".manifest.json".to_string(),
+        }
+    }
+}
+
+/// Incremental token delivery for streaming requests
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RouterConfig {
-    pub strategy: RoutingStrategy,
-    pub local_processing_threshold: f32,
-    pub cloud_fallback_enabled: bool,
-    pub cloud_endpoints: Vec<CloudEndpoint>,
-    pub load_balancing: LoadBalancingConfig,
+#[serde(default)]
+pub struct StreamingConfig {
+    /// Generated chunks buffered for a slow client before generation pauses
+    pub buffer_chunks: usize,
+    /// Local streams that switch to a cloud answer arriving in time, keyed
+    /// by streamed method
+    pub splice: HashMap<String, StreamSpliceConfig>,
 }
 
-/// Routing strategy options
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            buffer_chunks: 16,
+            splice: HashMap::new(),
+        }
+    }
+}
+
+/// Cloud splicing of a method's locally generated streams
+///
+/// Local tokens are returned at once while the request also goes to the
+/// cloud; a cloud answer arriving within `cloud_budget_ms` takes over at the
+/// next sentence boundary. Requests that must stay local are never sent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum RoutingStrategy {
-    ComplexityBased,
-    ResourceAware,
-    PerformanceOptimized,
-    Hybrid { weights: RoutingWeights },
+#[serde(default)]
+pub struct StreamSpliceConfig {
+    /// How long after the stream starts a cloud answer is still used
+    pub cloud_budget_ms: u64,
 }
 
-/// Routing weights for hybrid strategy
+impl Default for StreamSpliceConfig {
+    fn default() -> Self {
+        Self { cloud_budget_ms: 2000 }
+    }
+}
+
+/// Out-of-process model runners spoken to over the plugin IPC protocol
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RoutingWeights {
-    pub complexity: f32,
-    pub resource_usage: f32,
-    pub historical_performance: f32,
+#[serde(default)]
+pub struct ModelPluginsConfig {
+    /// Directory holding the runner sockets
+    pub runtime_dir: PathBuf,
+    /// Time a spawned runner has to accept the handshake
+    pub startup_timeout_ms: u64,
+    pub health_interval_secs: u64,
+    /// Consecutive failed restarts before a runner is given up on
+    pub max_restarts: u32,
+    /// Delay before the first restart, doubled on each consecutive failure
+    pub restart_backoff_ms: u64,
+    /// cgroup v2 directory under which each sandboxed runner gets its own group
+    pub cgroup_root: PathBuf,
+    pub backends: Vec<ModelPluginBackend>,
 }
 
-/// Cloud endpoint configuration
+impl Default for ModelPluginsConfig {
+    fn default() -> Self {
+        Self {
+            runtime_dir: PathBuf::from("./run/model-plugins"),
+            startup_timeout_ms: 10000,
+            health_interval_secs: 15,
+            max_restarts: 5,
+            restart_backoff_ms: 500,
+            cgroup_root: PathBuf::from("/sys/fs/cgroup/mcp-model-plugins"),
+            backends: Vec::new(),
+        }
+    }
+}
+
+/// A model runner executable and the models it serves
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CloudEndpoint {
+pub struct ModelPluginBackend {
     pub name: String,
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Socket the runner listens on, `<runtime_dir>/<name>.sock` when unset
+    #[serde(default)]
+    pub socket_path: Option<PathBuf>,
+    /// Models routed to this runner instead of the built-in loaders
+    pub models: Vec<ModelId>,
+    #[serde(default)]
+    pub sandbox: PluginSandboxConfig,
+}
+
+/// Restrictions placed on a model runner process before it executes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginSandboxConfig {
+    pub enabled: bool,
+    /// Refuse to start the runner when a restriction cannot be applied,
+    /// instead of running it with whatever the kernel supports
+    pub required: bool,
+    /// Paths readable by the runner besides system libraries, its executable
+    /// and the models directory
+    pub read_paths: Vec<PathBuf>,
+    /// Paths writable by the runner besides its socket directory
+    pub write_paths: Vec<PathBuf>,
+    /// Allow sockets other than Unix domain sockets
+    pub allow_network: bool,
+    pub memory_max_bytes: Option<u64>,
+    /// CPU limit as a percentage of one core
+    pub cpu_max_percent: Option<u32>,
+    pub pids_max: Option<u32>,
+}
+
+impl Default for PluginSandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            required: false,
+            read_paths: Vec::new(),
+            write_paths: Vec::new(),
+            allow_network: false,
+            memory_max_bytes: None,
+            cpu_max_percent: None,
+            pids_max: None,
+        }
+    }
+}
+
+/// Local vector store retrieval with optional cloud search fallback
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievalConfig {
+    pub enabled: bool,
+    pub top_k: usize,
+    pub embedding_dimensions: usize,
+    /// Best local match score below which cloud search is consulted
+    pub min_local_score: f32,
+    pub cloud_search: Option<CloudSearchConfig>,
+    #[serde(default)]
+    pub ingestion: IngestionConfig,
+    /// Snapshot file the index is loaded from and saved to by maintenance
+    #[serde(default)]
+    pub index_path: Option<PathBuf>,
+    #[serde(default)]
+    pub maintenance: IndexMaintenanceConfig,
+}
+
+/// Background compaction and pruning of the retrieval index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexMaintenanceConfig {
+    pub interval_secs: u64,
+    /// Share of deleted entries that triggers a rebuild
+    pub max_fragmentation: f32,
+    pub max_index_bytes: u64,
+    /// Free space to leave on the disk holding the index snapshot
+    pub min_free_disk_bytes: u64,
+    /// Documents sampled to estimate recall after compaction
+    pub recall_sample_size: usize,
+}
+
+impl Default for IndexMaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 900,
+            max_fragmentation: 0.2,
+            max_index_bytes: 64 * 1024 * 1024,
+            min_free_disk_bytes: 256 * 1024 * 1024,
+            recall_sample_size: 20,
+        }
+    }
+}
+
+/// Document ingestion into the local retrieval store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestionConfig {
+    /// Directories whose files are ingested and re-ingested on change
+    #[serde(default)]
+    pub watch_dirs: Vec<PathBuf>,
+    pub scan_interval_secs: u64,
+    pub chunk_size_words: usize,
+    pub chunk_overlap_words: usize,
+    pub max_file_bytes: u64,
+}
+
+impl Default for IngestionConfig {
+    fn default() -> Self {
+        Self {
+            watch_dirs: Vec::new(),
+            scan_interval_secs: 60,
+            chunk_size_words: 200,
+            chunk_overlap_words: 40,
+            max_file_bytes: 20 * 1024 * 1024,
+        }
+    }
+}
+
+/// Cloud search API used when local matches are weak
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudSearchConfig {
     pub url: String,
     pub api_key: Option<String>,
     pub timeout_ms: u64,
+    /// Terms always removed from outgoing queries
+    #[serde(default)]
+    pub redact_terms: Vec<String>,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            top_k: 5,
+            embedding_dimensions: 256,
+            min_local_score: 0.35,
+            cloud_search: None,
+            ingestion: IngestionConfig::default(),
+            index_path: None,
+            maintenance: IndexMaintenanceConfig::default(),
+        }
+    }
+}
+
+/// Post-hoc verification of local inference results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationConfig {
+    pub enabled: bool,
+    /// Cheaper model whose answer is compared against the primary response
+    pub verifier_model: Option<ModelId>,
+    /// Responses scoring below this (0-1) fail verification
+    pub min_score: f32,
+    /// Times the primary model is re-run before `on_failure` applies
+    pub max_regenerations: u32,
+    pub on_failure: VerificationFailureAction,
+    /// Phrases that indicate a policy violation
+    pub blocked_phrases: Vec<String>,
+    /// Phrases that commonly accompany hallucinated or evasive answers
+    pub hallucination_markers: Vec<String>,
+}
+
+/// What happens to a response that still fails verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationFailureAction {
+    /// Return the response with the failing verification attached
+    Annotate,
+    /// Fail the request
+    Reject,
+    /// Forward the request to a cloud endpoint instead
+    CloudFallback,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            verifier_model: None,
+            min_score: 0.5,
+            max_regenerations: 1,
+            on_failure: VerificationFailureAction::CloudFallback,
+            blocked_phrases: Vec::new(),
+            hallucination_markers: vec![
+                "as an ai language model".to_string(),
+                "i cannot verify".to_string(),
+                "according to my sources".to_string(),
+                "[citation needed]".to_string(),
+            ],
+        }
+    }
+}
+
+/// Stable model names resolved to concrete models at routing time
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelAliasConfig {
+    /// Aliases applied to every tenant, e.g. "default-chat" -> "tinyllama-1.1b"
+    #[serde(default)]
+    pub global: HashMap<String, ModelId>,
+    /// Per-tenant overrides, keyed by tenant id
+    #[serde(default)]
+    pub tenants: HashMap<String, HashMap<String, ModelId>>,
+    /// JSON alias file watched for changes; replaces the inline tables when present
+    #[serde(default)]
+    pub alias_file: Option<PathBuf>,
+    /// How often the alias file is checked for changes (0 disables reloading)
+    #[serde(default)]
+    pub reload_interval_secs: u64,
+}
+
+/// Periodic checksum verification of on-disk model files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelIntegrityConfig {
+    pub enabled: bool,
+    pub check_interval_secs: u64,
+    /// Base URL model files are re-downloaded from when corruption is found
+    pub registry_url: Option<String>,
+    pub download_timeout_secs: u64,
+}
+
+impl Default for ModelIntegrityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            check_interval_secs: 3600,
+            registry_url: None,
+            download_timeout_secs: 300,
+        }
+    }
+}
+
+/// Queue configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueConfig {
+    /// Sled database directory; the SQLite backend uses this path with a
+    /// `.sqlite` extension
+    pub storage_path: PathBuf,
+    #[serde(default)]
+    pub storage_backend: QueueStorageKind,
+    pub max_queue_size: u32,
+    /// Timeout of each request sent to the cloud while syncing
+    #[serde(default = "default_sync_timeout_ms")]
+    pub sync_timeout_ms: u64,
+    pub retry_policy: RetryPolicy,
+    pub compression_enabled: bool,
+    /// Codec for requests synced to the cloud when `compression_enabled`
+    #[serde(default)]
+    pub compression: PayloadCompression,
+    pub encryption_enabled: bool,
+    #[serde(default)]
+    pub connectivity: ConnectivityCheckConfig,
+    /// What starts a background sync; with none the queue only syncs when
+    /// asked to
+    #[serde(default = "default_sync_policies")]
+    pub sync_policies: Vec<SyncPolicy>,
+    #[serde(default)]
+    pub analytics: QueueAnalyticsConfig,
+    #[serde(default)]
+    pub dead_letter: DeadLetterConfig,
+}
+
+fn default_sync_timeout_ms() -> u64 {
+    30_000
+}
+
+fn default_sync_policies() -> Vec<SyncPolicy> {
+    vec![SyncPolicy::Interval {
+        sync_interval_seconds: 5,
+    }]
+}
+
+/// Event that starts a background queue sync
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncPolicy {
+    /// Every `sync_interval_seconds`
+    Interval { sync_interval_seconds: u64 },
+    /// When the connectivity check passes again after failing, checked
+    /// every `check_interval_seconds`
+    Connectivity { check_interval_seconds: u64 },
+    /// When `max_depth` requests are queued or the oldest has waited
+    /// `max_age_seconds`, checked every `check_interval_seconds`
+    Threshold {
+        #[serde(default)]
+        max_depth: Option<u32>,
+        #[serde(default)]
+        max_age_seconds: Option<u64>,
+        check_interval_seconds: u64,
+    },
+}
+
+/// Database the offline queue or session histories are persisted in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueStorageKind {
+    #[default]
+    Sled,
+    /// SQLite in WAL mode, synced on every commit so queued requests survive
+    /// power loss
+    Sqlite,
+    /// Kept in memory only; queued requests are lost on restart
+    Memory,
+}
+
+/// Probes that must pass before the device counts as online for sync, so a
+/// captive portal is not mistaken for a working uplink
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectivityCheckConfig {
+    pub enabled: bool,
+    /// Plain-HTTP URL that answers with `expected_status` and an empty body
+    pub probe_url: String,
+    pub expected_status: u16,
+    /// Domain whose random subdomains must not resolve; an answer means DNS
+    /// is being hijacked. The DNS probe is skipped when unset.
+    pub dns_probe_domain: Option<String>,
+    /// How long a probe result is trusted before probing again
+    pub recheck_interval_secs: u64,
+    pub timeout_ms: u64,
+}
+
+impl Default for ConnectivityCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            probe_url: "http://connectivitycheck.gstatic.com/generate_204".to_string(),
+            expected_status: 204,
+            dns_probe_domain: Some("example.com".to_string()),
+            recheck_interval_secs: 30,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// Queue age and drain-time analytics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueAnalyticsConfig {
+    /// Arrival and drain rates are averaged over this many recent seconds
+    pub window_secs: u64,
+}
+
+impl Default for QueueAnalyticsConfig {
+    fn default() -> Self {
+        Self { window_secs: 900 }
+    }
+}
+
+/// Requests that used all their retry attempts, kept aside for an operator
+/// to inspect, requeue or purge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeadLetterConfig {
+    /// Dead-lettered requests kept; the oldest are dropped first
+    pub max_entries: usize,
+}
+
+impl Default for DeadLetterConfig {
+    fn default() -> Self {
+        Self { max_entries: 1000 }
+    }
+}
+
+/// Retry policy configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Retries of a request that fails to sync or process before it is
+    /// moved to the dead-letter queue
     pub max_retries: u32,
+    /// Wait before the first retry
+    pub initial_delay_ms: u64,
+    /// Longest wait between retries
+    pub max_delay_ms: u64,
+    /// Each further retry waits this many times longer than the last
+    pub backoff_multiplier: f32,
 }
 
-/// Load balancing configuration
+/// Security configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LoadBalancingConfig {
-    pub algorithm: LoadBalancingAlgorithm,
-    pub health_check_interval_ms: u64,
-    pub failure_threshold: u32,
+pub struct SecurityConfig {
+    pub tpm_enabled: bool,
+    pub mutual_tls: bool,
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    pub ca_cert_path: Option<PathBuf>,
+    pub device_attestation: bool,
+    /// AEAD for data at rest: `AES-256-GCM`, `CHACHA20-POLY1305`, or `auto`
+    /// to use AES-GCM only on CPUs with AES instructions
+    pub encryption_algorithm: String,
+    pub key_rotation_interval_hours: u64,
+    #[serde(default)]
+    pub restricted_mode: RestrictedModeConfig,
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+    #[serde(default)]
+    pub auth_protection: AuthProtectionConfig,
+    #[serde(default)]
+    pub enrollment: EnrollmentConfig,
+    #[serde(default)]
+    pub tenant_keys: TenantKeysConfig,
+    #[serde(default)]
+    pub attestation: AttestationConfig,
+    #[serde(default)]
+    pub authentication: AuthenticationConfig,
+    #[serde(default)]
+    pub output_moderation: OutputModerationConfig,
 }
 
-/// Load balancing algorithms
+/// Policy checks on generated text before it is returned, the output side
+/// of the request content filter
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum LoadBalancingAlgorithm {
-    RoundRobin,
-    LeastConnections,
-    WeightedRoundRobin,
-    HealthBased,
+#[serde(default)]
+pub struct OutputModerationConfig {
+    pub enabled: bool,
+    /// Also moderate responses from cloud endpoints; by default only text
+    /// generated on the device is checked
+    pub include_cloud_responses: bool,
+    pub categories: Vec<ModerationCategoryConfig>,
 }
 
-/// Models configuration
+impl Default for OutputModerationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            include_cloud_responses: false,
+            categories: vec![
+                ModerationCategoryConfig {
+                    name: "active_content".to_string(),
+                    patterns: vec![
+                        r"(?i)<script[^>]*>".to_string(),
+                        r"(?i)javascript:".to_string(),
+                        r"(?i)data:text/html".to_string(),
+                    ],
+                    threshold: 1,
+                    action: ModerationAction::Redact,
+                },
+                ModerationCategoryConfig {
+                    name: "destructive_commands".to_string(),
+                    patterns: vec![
+                        r"\brm\s+-(?:rf|fr)\b".to_string(),
+                        r"(?i)\bdel\s+/[fsq]".to_string(),
+                        r"(?i)\bformat\s+[a-z]:".to_string(),
+                    ],
+                    threshold: 1,
+                    action: ModerationAction::Annotate,
+                },
+            ],
+        }
+    }
+}
+
+/// A policy category of the output moderation
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ModelsConfig {
-    pub models_directory: PathBuf,
-    pub cache_size_mb: u32,
-    pub max_models_in_memory: u32,
-    pub model_timeout_ms: u64,
-    pub auto_optimization: bool,
-    pub supported_formats: Vec<String>,
+pub struct ModerationCategoryConfig {
+    pub name: String,
+    /// Regular expressions; each match adds one to the category's score
+    pub patterns: Vec<String>,
+    /// Score at which the action is taken
+    #[serde(default = "default_moderation_threshold")]
+    pub threshold: u32,
+    pub action: ModerationAction,
+}
+
+fn default_moderation_threshold() -> u32 {
+    1
+}
+
+/// What happens to a response reaching a category's threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// Fail the request
+    Block,
+    /// Replace the matched text with `[REDACTED:<category>]`
+    Redact,
+    /// Return the response with the decision attached
+    Annotate,
+}
+
+/// Caller authentication for the HTTP API, by static API key or by JWT
+/// verified against the issuer's JWKS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthenticationConfig {
+    /// Reject requests without valid credentials; when unset, credentials
+    /// are still checked if presented. Admin routes need credentials either
+    /// way
+    pub require_authentication: bool,
+    pub api_keys: Vec<ApiKeyConfig>,
+    pub jwt: Option<JwtConfig>,
+    /// Path prefixes served without credentials
+    pub exempt_paths: Vec<String>,
+}
+
+impl Default for AuthenticationConfig {
+    fn default() -> Self {
+        Self {
+            require_authentication: false,
+            api_keys: Vec::new(),
+            jwt: None,
+            exempt_paths: vec!["/health".to_string(), "/v1/enroll".to_string()],
+        }
+    }
+}
+
+/// A static API key, sent in `x-api-key` or as a bearer token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Principal the key authenticates as
+    pub name: String,
+    /// Hex SHA-256 of the key, so the config does not hold the key itself
+    pub key_sha256: String,
+    /// Methods the key may call, a trailing `*` matching any suffix; any
+    /// method when empty. Admin endpoints are the methods `admin/<path>`
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+}
+
+/// JWT bearer tokens signed with RS256 or ES256
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JwtConfig {
+    pub jwks_url: String,
+    /// Required `iss` claim, if any
+    pub issuer: Option<String>,
+    /// Required `aud` claim, if any
+    pub audience: Option<String>,
+    /// How long fetched keys are used before the JWKS is fetched again
+    pub jwks_cache_secs: u64,
+    /// Clock skew tolerated on `exp` and `nbf`
+    pub leeway_secs: u64,
+    /// Claim listing the methods a token may call; any method when absent
+    pub methods_claim: String,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            jwks_url: String::new(),
+            issuer: None,
+            audience: None,
+            jwks_cache_secs: 3600,
+            leeway_secs: 60,
+            methods_claim: "mcp_methods".to_string(),
+        }
+    }
+}
+
+/// Device key and platform attestation; the key lives in the TPM when
+/// `tpm_enabled` is set and the gateway is built with `hardware-security`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttestationConfig {
+    /// TSS2 TCTI the TPM is reached through, e.g. `device:/dev/tpmrm0` or
+    /// `mssim:host=localhost,port=2321` for a simulator
+    pub tcti: String,
+    /// SHA-256 PCRs included in quotes, at most 8
+    pub pcrs: Vec<u8>,
+    /// Use a software key when the TPM is unavailable instead of failing
+    /// startup; its quotes are marked simulated
+    pub software_fallback: bool,
+    /// Software device key (PKCS#8), generated there on first start; a new key
+    /// is generated on every start when unset
+    pub software_key_path: Option<PathBuf>,
+    /// Sign requests forwarded to the cloud and replayed from the queue
+    pub sign_cloud_requests: bool,
+}
+
+impl Default for AttestationConfig {
+    fn default() -> Self {
+        Self {
+            tcti: "device:/dev/tpmrm0".to_string(),
+            pcrs: (0..8).collect(),
+            software_fallback: true,
+            software_key_path: None,
+            sign_cloud_requests: false,
+        }
+    }
+}
+
+/// Per-tenant data keys wrapped by the device master key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TenantKeysConfig {
+    /// Device master key (32 raw bytes), generated there on first start;
+    /// a new key is generated on every start when unset
+    pub master_key_path: Option<PathBuf>,
+    /// Registry of wrapped tenant keys; kept in memory only when unset
+    pub registry_path: Option<PathBuf>,
+    /// Key usage records kept in the audit trail
+    pub audit_capacity: usize,
+}
+
+impl Default for TenantKeysConfig {
+    fn default() -> Self {
+        Self {
+            master_key_path: None,
+            registry_path: None,
+            audit_capacity: 1000,
+        }
+    }
+}
+
+/// Certificate-based device enrollment against the fleet CA
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnrollmentConfig {
+    pub enabled: bool,
+    /// Single-use tokens devices present to enroll
+    pub bootstrap_tokens: Vec<String>,
+    /// Common name of the fleet CA
+    pub ca_name: String,
+    /// Fleet CA key (PKCS#8), generated on first start if missing
+    pub ca_key_path: PathBuf,
+    /// Where the fleet CA certificate is written for TLS terminators to trust
+    pub ca_certificate_path: PathBuf,
+    /// Enrolled devices and revocations
+    pub registry_path: PathBuf,
+    pub certificate_validity_days: u32,
+    /// Reject requests from devices that have not enrolled
+    pub require_enrollment: bool,
+    /// Oldest signed-request timestamp accepted
+    pub max_signature_age_secs: u64,
+}
+
+impl Default for EnrollmentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bootstrap_tokens: Vec::new(),
+            ca_name: "MCP Edge Fleet CA".to_string(),
+            ca_key_path: PathBuf::from("./pki/fleet-ca.pk8"),
+            ca_certificate_path: PathBuf::from("./pki/fleet-ca.pem"),
+            registry_path: PathBuf::from("./pki/devices.json"),
+            certificate_validity_days: 365,
+            require_enrollment: false,
+            max_signature_age_secs: 300,
+        }
+    }
+}
+
+/// Brute-force lockouts and behavioral anomaly scoring
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthProtectionConfig {
+    /// Failed authentications within `failure_window_secs` that trigger a lockout
+    pub max_failures: u32,
+    pub failure_window_secs: u64,
+    /// First lockout length; each further lockout doubles it
+    pub base_lockout_secs: u64,
+    pub max_lockout_secs: u64,
+    /// Requests observed before a device baseline is used for scoring
+    pub baseline_min_requests: u64,
+    /// Deviation score at which a request counts as anomalous
+    pub anomaly_threshold: f32,
+}
+
+impl Default for AuthProtectionConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            failure_window_secs: 300,
+            base_lockout_secs: 30,
+            max_lockout_secs: 3600,
+            baseline_min_requests: 50,
+            anomaly_threshold: 4.0,
+        }
+    }
 }
 
-/// Queue configuration
+/// Restricted profile for suspicious devices, used instead of blocking them
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct QueueConfig {
-    pub storage_path: PathBuf,
-    pub max_queue_size: u32,
-    pub sync_interval_ms: u64,
-    pub retry_policy: RetryPolicy,
-    pub compression_enabled: bool,
-    pub encryption_enabled: bool,
+#[serde(default)]
+pub struct RestrictedModeConfig {
+    /// Restrict devices automatically after repeated suspicious requests
+    pub auto_restrict: bool,
+    /// Suspicious requests within `strike_window_secs` that trigger restriction
+    pub strike_threshold: u32,
+    pub strike_window_secs: u64,
+    /// How long automatic restrictions last
+    pub duration_secs: u64,
+    /// Methods restricted devices may still call
+    pub allowed_methods: Vec<String>,
+    pub requests_per_minute: u64,
 }
 
-/// Retry policy configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RetryPolicy {
-    pub max_retries: u32,
-    pub initial_delay_ms: u64,
-    pub max_delay_ms: u64,
-    pub backoff_multiplier: f32,
+impl Default for RestrictedModeConfig {
+    fn default() -> Self {
+        Self {
+            auto_restrict: true,
+            strike_threshold: 3,
+            strike_window_secs: 600,
+            duration_secs: 3600,
+            allowed_methods: vec!["mcp.capabilities".to_string(), "retrieval.search".to_string()],
+            requests_per_minute: 10,
+        }
+    }
 }
 
-/// Security configuration
+/// Request rate limits for devices and HTTP clients
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SecurityConfig {
-    pub tpm_enabled: bool,
-    pub mutual_tls: bool,
-    pub cert_path: Option<PathBuf>,
-    pub key_path: Option<PathBuf>,
-    pub ca_cert_path: Option<PathBuf>,
-    pub device_attestation: bool,
-    pub encryption_algorithm: String,
-    pub key_rotation_interval_hours: u64,
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Requests a device may make per minute, counted across the cluster
+    pub device_requests_per_minute: u64,
+    /// Requests a device may make per hour, counted across the cluster
+    pub device_requests_per_hour: u64,
+    /// Requests an HTTP client may make per `client_window_secs`; a client
+    /// over the limit is turned away for two windows
+    pub client_requests_per_window: u32,
+    pub client_window_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            device_requests_per_minute: 100,
+            device_requests_per_hour: 1000,
+            client_requests_per_window: 100,
+            client_window_secs: 60,
+        }
+    }
 }
 
 /// Telemetry configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TelemetryConfig {
     pub enabled: bool,
+    /// Time between metric exports to `export_endpoint`
     pub metrics_interval_ms: u64,
+    /// OTLP/HTTP collector base URL metrics and logs are pushed to when
+    /// `opentelemetry_enabled`, e.g. `http://collector:4318`
     pub export_endpoint: Option<String>,
     pub compression_enabled: bool,
+    /// Codec of exports to `export_endpoint`: `lz4` or `zstd`
     pub compression_algorithm: String,
+    /// Smallest export body that is compressed
     pub compression_threshold_bytes: Option<usize>,
     pub max_buffer_size: u32,
     pub retention_days: u32,
     pub prometheus_enabled: bool,
     pub opentelemetry_enabled: bool,
+    #[serde(default)]
+    pub tracing: TracingConfig,
+    #[serde(default)]
+    pub prometheus: PrometheusConfig,
+    #[serde(default)]
+    pub log_escalation: LogEscalationConfig,
+    #[serde(default)]
+    pub timeline: TimelineConfig,
+    #[serde(default)]
+    pub otlp: OtlpExportConfig,
+}
+
+/// Request tracing with W3C Trace Context propagation
+///
+/// A `traceparent` header sent by the caller is always continued and
+/// forwarded to the cloud; `enabled` additionally starts traces for requests
+/// that arrive without one and records spans for export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    pub enabled: bool,
+    /// OTLP/HTTP collector base URL, e.g. `http://collector:4318`; spans are
+    /// recorded but not exported when unset
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+    /// Share of new traces that are sampled; traces continued from a caller
+    /// follow the caller's sampled flag
+    pub sample_ratio: f64,
+    pub export_interval_ms: u64,
+    /// Most spans sent per export request
+    pub max_batch_spans: usize,
+    /// Finished spans kept while waiting for export; further spans are dropped
+    pub max_buffered_spans: usize,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            otlp_endpoint: None,
+            service_name: "mcp-edge-gateway".to_string(),
+            sample_ratio: 1.0,
+            export_interval_ms: 5000,
+            max_batch_spans: 512,
+            max_buffered_spans: 4096,
+        }
+    }
+}
+
+/// Metrics exposed in Prometheus text format on `/metrics`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrometheusConfig {
+    /// Prefix joined to every metric name with an underscore
+    pub namespace: String,
+    /// Full replacement names, keyed by metric name without the namespace
+    /// (e.g. `request_duration_seconds`)
+    pub metric_names: HashMap<String, String>,
+    /// Upper bounds of the request latency histogram buckets, in seconds
+    pub latency_buckets_secs: Vec<f64>,
+}
+
+impl Default for PrometheusConfig {
+    fn default() -> Self {
+        Self {
+            namespace: "mcp".to_string(),
+            metric_names: HashMap::new(),
+            latency_buckets_secs: vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0],
+        }
+    }
+}
+
+/// Temporary log verbosity for components the pipeline guard finds degraded
+///
+/// While a component is degraded, events from its log targets are emitted at
+/// `level` instead of `base_level`, and kept in memory for the incident
+/// report. The level is restored when the component recovers or after
+/// `duration_secs`, whichever comes first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogEscalationConfig {
+    pub enabled: bool,
+    /// Level logged outside escalations
+    pub base_level: String,
+    /// Level logged by degraded components
+    pub level: String,
+    /// Longest an escalation lasts, however long the degradation
+    pub duration_secs: u64,
+    /// Log lines kept per incident; older lines are dropped beyond it
+    pub max_captured_lines: usize,
+    /// Log target prefixes per component; other components use their id
+    pub component_targets: HashMap<String, Vec<String>>,
+}
+
+impl Default for LogEscalationConfig {
+    fn default() -> Self {
+        let component_targets = [
+            ("router", "mcp_router"),
+            ("model_engine", "mcp_models"),
+            ("queue", "mcp_queue"),
+            ("security", "mcp_security"),
+            ("telemetry", "mcp_telemetry"),
+        ]
+        .into_iter()
+        .map(|(component, target)| (component.to_string(), vec![target.to_string()]))
+        .collect();
+        Self {
+            enabled: true,
+            base_level: "info".to_string(),
+            level: "debug".to_string(),
+            duration_secs: 300,
+            max_captured_lines: 500,
+            component_targets,
+        }
+    }
+}
+
+/// Incident timeline of alerts, scaling, recovery attempts, configuration
+/// changes and request error spikes
+///
+/// Entries are appended to `path` in gateway storage as they happen, so the
+/// timeline of a night survives a restart in the morning.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimelineConfig {
+    pub enabled: bool,
+    pub path: PathBuf,
+    /// Entries older than this are dropped when the timeline is restored
+    pub retention_hours: u64,
+    /// Most entries kept in memory and restored
+    pub max_entries: usize,
+    /// Request errors within `error_spike_window_secs` that make a spike
+    pub error_spike_threshold: u32,
+    pub error_spike_window_secs: u64,
+    /// Entries at most this far apart belong to the same incident
+    pub correlation_window_secs: u64,
+}
+
+impl Default for TimelineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: PathBuf::from("./data/timeline.jsonl"),
+            retention_hours: 72,
+            max_entries: 5000,
+            error_spike_threshold: 20,
+            error_spike_window_secs: 60,
+            correlation_window_secs: 600,
+        }
+    }
+}
+
+/// Metrics and structured logs pushed to an OpenTelemetry collector
+///
+/// Used when `telemetry.opentelemetry_enabled` is set and
+/// `telemetry.export_endpoint` names a collector. Batches go out over
+/// OTLP/HTTP with JSON encoding, compressed as `telemetry.compression_*`
+/// say. A batch still failing after `max_retries` is spilled to queue
+/// storage at `spill_path` and sent again, oldest first, once the collector
+/// answers, so an outage costs no telemetry up to `max_spilled_batches`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OtlpExportConfig {
+    /// Least severe log level exported
+    pub log_level: String,
+    /// Most log records sent per export request
+    pub max_batch_logs: usize,
+    /// Log records kept while waiting for export; further records are dropped
+    pub max_buffered_logs: usize,
+    /// Retries of a failed export, backing off exponentially with jitter
+    pub max_retries: u32,
+    pub retry_initial_delay_ms: u64,
+    /// Queue storage for batches the collector could not take, in the
+    /// backend of `queue.storage_backend`
+    pub spill_path: PathBuf,
+    /// Spilled batches kept; the oldest are dropped beyond it
+    pub max_spilled_batches: usize,
+}
+
+impl Default for OtlpExportConfig {
+    fn default() -> Self {
+        Self {
+            log_level: "info".to_string(),
+            max_batch_logs: 512,
+            max_buffered_logs: 4096,
+            max_retries: 3,
+            retry_initial_delay_ms: 500,
+            spill_path: PathBuf::from("./data/telemetry-spill"),
+            max_spilled_batches: 1000,
+        }
+    }
 }
 
 /// Platform-specific configuration
@@ -150,6 +3512,8 @@ pub struct PlatformConfig {
     pub threading_model: ThreadingModel,
     pub enable_simd: bool,
     pub enable_gpu_acceleration: bool,
+    #[serde(default)]
+    pub admission: AdmissionConfig,
 }
 
 /// Thermal management profiles
@@ -160,6 +3524,66 @@ pub enum ThermalProfile {
     Aggressive,
 }
 
+impl ThermalProfile {
+    /// SoC temperature above which the device counts as overheating; more
+    /// aggressive profiles back off earlier
+    pub fn temperature_limit_celsius(&self) -> f64 {
+        match self {
+            ThermalProfile::Passive => 85.0,
+            ThermalProfile::Moderate => 80.0,
+            ThermalProfile::Aggressive => 75.0,
+        }
+    }
+}
+
+/// Admission of requests while the device runs short of memory or overheats
+///
+/// Pressure starts when the gateway's resident memory exceeds
+/// `platform.max_memory_mb`, device memory use exceeds `memory_percent`, or
+/// the SoC exceeds the temperature limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdmissionConfig {
+    pub enabled: bool,
+    /// Lowest priority still admitted under pressure; lower ones are held back
+    pub min_priority: Priority,
+    /// What happens to requests held back
+    pub action: AdmissionAction,
+    /// Share of device memory in use above which the device is under pressure
+    pub memory_percent: f32,
+    /// SoC temperature limit; the thermal profile's limit when unset
+    pub max_temperature_celsius: Option<f64>,
+    /// How long a memory and temperature reading is reused
+    pub sample_interval_ms: u64,
+    /// Kernel thermal zones, of which the hottest is used
+    pub thermal_zones_dir: PathBuf,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_priority: Priority::Normal,
+            action: AdmissionAction::Queue,
+            memory_percent: 90.0,
+            max_temperature_celsius: None,
+            sample_interval_ms: 1000,
+            thermal_zones_dir: PathBuf::from("/sys/class/thermal"),
+        }
+    }
+}
+
+/// Handling of requests not admitted under memory or thermal pressure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdmissionAction {
+    /// Queue them for cloud sync
+    #[default]
+    Queue,
+    /// Reject them so the client retries later
+    Shed,
+}
+
 /// Power management profiles
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PowerProfile {
@@ -188,6 +3612,22 @@ impl Default for Config {
                 max_request_size_bytes: 1024 * 1024, // 1MB
                 enable_cors: true,
                 cors_origins: vec!["*".to_string()],
+                maintenance: MaintenanceConfig::default(),
+                timeouts: TimeoutConfig::default(),
+                clock_skew: ClockSkewConfig::default(),
+                health_checks: HealthCheckConfig::default(),
+                resource_accounting: ResourceAccountingConfig::default(),
+                bandwidth: BandwidthConfig::default(),
+                request_ids: RequestIdConfig::default(),
+                grpc: GrpcConfig::default(),
+                mqtt: MqttBridgeConfig::default(),
+                stage_timings: StageTimingsConfig::default(),
+                synthetic_probes: SyntheticProbesConfig::default(),
+                emulation: EmulationConfig::default(),
+                config_reload: ConfigReloadConfig::default(),
+                admin_safety: AdminSafetyConfig::default(),
+                idempotency: IdempotencyConfig::default(),
+                response_provenance: ResponseProvenanceConfig::default(),
             },
             router: RouterConfig {
                 strategy: RoutingStrategy::Hybrid {
@@ -205,6 +3645,11 @@ impl Default for Config {
                     health_check_interval_ms: 30000,
                     failure_threshold: 3,
                 },
+                warm_standby: WarmStandbyConfig::default(),
+                routes: Vec::new(),
+                rollouts: Vec::new(),
+                policy: RoutingPolicyConfig::default(),
+                hedging: HedgingConfig::default(),
             },
             models: ModelsConfig {
                 models_directory: PathBuf::from("./models"),
@@ -217,11 +3662,26 @@ impl Default for Config {
                     "onnx".to_string(),
                     "tflite".to_string(),
                 ],
+                integrity: ModelIntegrityConfig::default(),
+                aliases: ModelAliasConfig::default(),
+                verification: VerificationConfig::default(),
+                retrieval: RetrievalConfig::default(),
+                plugins: ModelPluginsConfig::default(),
+                streaming: StreamingConfig::default(),
+                provenance: ModelProvenanceConfig::default(),
+                signing: ModelSigningConfig::default(),
+                result_store: ModelResultStoreConfig::default(),
+                memory_admission: InferenceMemoryConfig::default(),
+                prefix_cache: PrefixCacheConfig::default(),
+                embedding_batching: EmbeddingBatchConfig::default(),
+                ensembles: Vec::new(),
+                response_cache: ResponseCacheConfig::default(),
             },
             queue: QueueConfig {
                 storage_path: PathBuf::from("./queue.db"),
+                storage_backend: QueueStorageKind::default(),
                 max_queue_size: 10000,
-                sync_interval_ms: 5000,
+                sync_timeout_ms: default_sync_timeout_ms(),
                 retry_policy: RetryPolicy {
                     max_retries: 3,
                     initial_delay_ms: 1000,
@@ -229,7 +3689,12 @@ impl Default for Config {
                     backoff_multiplier: 2.0,
                 },
                 compression_enabled: true,
+                compression: PayloadCompression::default(),
                 encryption_enabled: true,
+                connectivity: ConnectivityCheckConfig::default(),
+                sync_policies: default_sync_policies(),
+                analytics: QueueAnalyticsConfig::default(),
+                dead_letter: DeadLetterConfig::default(),
             },
             security: SecurityConfig {
                 tpm_enabled: false,
@@ -240,6 +3705,14 @@ impl Default for Config {
                 device_attestation: false,
                 encryption_algorithm: "AES-256-GCM".to_string(),
                 key_rotation_interval_hours: 24,
+                restricted_mode: RestrictedModeConfig::default(),
+                rate_limits: RateLimitConfig::default(),
+                auth_protection: AuthProtectionConfig::default(),
+                enrollment: EnrollmentConfig::default(),
+                tenant_keys: TenantKeysConfig::default(),
+                attestation: AttestationConfig::default(),
+                authentication: AuthenticationConfig::default(),
+                output_moderation: OutputModerationConfig::default(),
             },
             telemetry: TelemetryConfig {
                 enabled: true,
@@ -252,6 +3725,11 @@ impl Default for Config {
                 retention_days: 7,
                 prometheus_enabled: true,
                 opentelemetry_enabled: false,
+                tracing: TracingConfig::default(),
+                prometheus: PrometheusConfig::default(),
+                log_escalation: LogEscalationConfig::default(),
+                timeline: TimelineConfig::default(),
+                otlp: OtlpExportConfig::default(),
             },
             platform: PlatformConfig {
                 max_memory_mb: 512,
@@ -263,7 +3741,932 @@ impl Default for Config {
                 },
                 enable_simd: true,
                 enable_gpu_acceleration: false,
+                admission: AdmissionConfig::default(),
             },
+            concurrency: ConcurrencyConfig::default(),
+            storage: StorageConfig::default(),
+            outputs: OutputsConfig::default(),
+            cluster: ClusterConfig::default(),
+            compliance: ComplianceConfig::default(),
+            retention: RetentionConfig::default(),
+            network: NetworkConfig::default(),
+            redaction: RedactionConfig::default(),
+            audit: AuditSinkConfig::default(),
+            extensions: ExtensionsConfig::default(),
+            kv: KvStoreConfig::default(),
+            conversations: ConversationsConfig::default(),
+            sessions: SessionsConfig::default(),
+            normalization: TextNormalizationConfig::default(),
+            power: PowerConfig::default(),
+            peripherals: PeripheralsConfig::default(),
+            pipeline_guard: PipelineGuardConfig::default(),
+            scaling: ScalingConfig::default(),
+            deployment: DeploymentConfig::default(),
+            profile: DeploymentProfile::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Validate cross-field constraints that serde cannot express
+    pub fn validate(&self) -> Result<()> {
+        check_timeout("gateway.request_timeout_ms", self.gateway.request_timeout_ms)?;
+        check_timeout("models.model_timeout_ms", self.models.model_timeout_ms)?;
+        check_timeout("queue.sync_timeout_ms", self.queue.sync_timeout_ms)?;
+        if let Some(connect_ms) = self.gateway.timeouts.cloud_connect_timeout_ms {
+            check_timeout("gateway.timeouts.cloud_connect_timeout_ms", connect_ms)?;
+        }
+        if self.gateway.grpc.enabled && self.gateway.grpc.port == self.gateway.port {
+            return Err(Error::Configuration(
+                "gateway.grpc.port must differ from gateway.port".to_string(),
+            ));
+        }
+        let mqtt = &self.gateway.mqtt;
+        if mqtt.enabled {
+            if mqtt.request_topics.is_empty() || mqtt.client_id.is_empty() {
+                return Err(Error::Configuration(
+                    "gateway.mqtt needs a client_id and at least one request topic".to_string(),
+                ));
+            }
+            if mqtt.qos > 1 {
+                return Err(Error::Configuration(
+                    "gateway.mqtt.qos must be 0 or 1; QoS 2 is not supported".to_string(),
+                ));
+            }
+            if mqtt.reconnect_delay_secs == 0
+                || mqtt.max_reconnect_delay_secs < mqtt.reconnect_delay_secs
+                || mqtt.max_in_flight == 0
+            {
+                return Err(Error::Configuration(
+                    "gateway.mqtt.reconnect_delay_secs and max_in_flight must be positive, \
+                     and max_reconnect_delay_secs at least reconnect_delay_secs"
+                        .to_string(),
+                ));
+            }
+        }
+        let synthetic = &self.gateway.synthetic_probes;
+        if synthetic.enabled && (synthetic.interval_secs == 0 || synthetic.failure_threshold == 0) {
+            return Err(Error::Configuration(
+                "gateway.synthetic_probes.interval_secs and failure_threshold must be positive".to_string(),
+            ));
+        }
+        let idempotency = &self.gateway.idempotency;
+        if idempotency.enabled && (idempotency.window_secs == 0 || idempotency.max_entries == 0) {
+            return Err(Error::Configuration(
+                "gateway.idempotency.window_secs and max_entries must be positive".to_string(),
+            ));
+        }
+        let admin_safety = &self.gateway.admin_safety;
+        if admin_safety.enabled && (admin_safety.confirmation_ttl_secs == 0 || admin_safety.journal_size == 0) {
+            return Err(Error::Configuration(
+                "gateway.admin_safety.confirmation_ttl_secs and journal_size must be positive".to_string(),
+            ));
+        }
+        if self.router.hedging.enabled && self.router.hedging.fallback_threshold_ms == 0 {
+            return Err(Error::Configuration(
+                "router.hedging.fallback_threshold_ms must be positive".to_string(),
+            ));
+        }
+        let per_model = &self.concurrency.per_model;
+        if per_model.enabled
+            && std::iter::once(&per_model.default)
+                .chain(per_model.models.values())
+                .any(|limit| limit.max_concurrent == 0)
+        {
+            return Err(Error::Configuration(
+                "concurrency.per_model limits must allow at least one concurrent inference".to_string(),
+            ));
+        }
+        let storage_health = &self.storage.health;
+        if storage_health.enabled {
+            if storage_health.interval_secs == 0 {
+                return Err(Error::Configuration(
+                    "storage.health.interval_secs must be positive".to_string(),
+                ));
+            }
+            if storage_health.wear_warning_percent > storage_health.wear_critical_percent
+                || storage_health.wear_critical_percent > 100
+            {
+                return Err(Error::Configuration(
+                    "storage.health.wear_warning_percent must not exceed wear_critical_percent, \
+                     which must be at most 100"
+                        .to_string(),
+                ));
+            }
+            if storage_health.degraded_write_relaxation == 0 {
+                return Err(Error::Configuration(
+                    "storage.health.degraded_write_relaxation must be at least 1".to_string(),
+                ));
+            }
+        }
+        for (method, fixture) in &self.gateway.emulation.fixtures {
+            if fixture.result.is_null() && fixture.error.is_none() {
+                return Err(Error::Configuration(format!(
+                    "gateway.emulation.fixtures.{} needs a result or an error",
+                    method
+                )));
+            }
+        }
+
+        for endpoint in &self.router.cloud_endpoints {
+            check_timeout(&format!("router.cloud_endpoints[{}].timeout_ms", endpoint.name), endpoint.timeout_ms)?;
+            if let Some(connect_ms) = endpoint.connect_timeout_ms {
+                check_timeout(
+                    &format!("router.cloud_endpoints[{}].connect_timeout_ms", endpoint.name),
+                    connect_ms,
+                )?;
+            }
+            if endpoint.compression != PayloadCompression::None && endpoint.provider != CloudProviderConfig::Mcp {
+                return Err(Error::Configuration(format!(
+                    "router.cloud_endpoints[{}].compression needs the mcp provider",
+                    endpoint.name
+                )));
+            }
+        }
+
+        for route in &self.router.routes {
+            if route.endpoints.is_empty() {
+                return Err(Error::Configuration("router.routes entries need at least one endpoint".to_string()));
+            }
+            for name in &route.endpoints {
+                if !self.router.cloud_endpoints.iter().any(|endpoint| &endpoint.name == name) {
+                    return Err(Error::Configuration(format!(
+                        "router.routes names unknown cloud endpoint '{}'",
+                        name
+                    )));
+                }
+            }
+        }
+
+        for rule in &self.router.policy.rules {
+            rule.validate(&self.router.cloud_endpoints)?;
+        }
+
+        let admission = &self.platform.admission;
+        if !(admission.memory_percent > 0.0 && admission.memory_percent <= 100.0) {
+            return Err(Error::Configuration(
+                "platform.admission.memory_percent must be between 0 and 100".to_string(),
+            ));
+        }
+
+        let mut rollout_models = HashSet::new();
+        for rollout in &self.router.rollouts {
+            rollout.validate()?;
+            if !rollout_models.insert(&rollout.model) {
+                return Err(Error::Configuration(format!(
+                    "router.rollouts has more than one rollout of {}",
+                    rollout.model
+                )));
+            }
+        }
+
+        let pcrs = &self.security.attestation.pcrs;
+        if pcrs.len() > 8 || pcrs.iter().any(|&pcr| pcr > 23) {
+            return Err(Error::Configuration(
+                "security.attestation.pcrs must list at most 8 PCRs between 0 and 23".to_string(),
+            ));
         }
+
+        let batching = &self.models.embedding_batching;
+        if batching.enabled && batching.max_batch_size == 0 {
+            return Err(Error::Configuration(
+                "models.embedding_batching.max_batch_size must be positive".to_string(),
+            ));
+        }
+
+        let mut ensemble_names = HashSet::new();
+        for ensemble in &self.models.ensembles {
+            ensemble.validate()?;
+            if !ensemble_names.insert(&ensemble.name) {
+                return Err(Error::Configuration(format!(
+                    "models.ensembles has more than one ensemble named '{}'",
+                    ensemble.name
+                )));
+            }
+        }
+
+        let response_cache = &self.models.response_cache;
+        if response_cache.enabled && response_cache.max_size_mb == 0 {
+            return Err(Error::Configuration(
+                "models.response_cache.max_size_mb must be positive".to_string(),
+            ));
+        }
+        let threshold = response_cache.similarity_threshold;
+        if response_cache.semantic
+            && (!(threshold > 0.0 && threshold <= 1.0) || response_cache.embedding_dimensions == 0)
+        {
+            return Err(Error::Configuration(
+                "models.response_cache semantic mode needs a similarity_threshold between 0 and 1 \
+                 and positive embedding_dimensions"
+                    .to_string(),
+            ));
+        }
+
+        let signing = &self.models.signing;
+        if signing.mode == ModelSignatureMode::Enforce && signing.trusted_keys.is_empty() {
+            return Err(Error::Configuration(
+                "models.signing needs at least one trusted key to enforce signatures".to_string(),
+            ));
+        }
+        if signing.manifest_suffix.is_empty() {
+            return Err(Error::Configuration("models.signing.manifest_suffix must not be empty".to_string()));
+        }
+
+        let result_store = &self.models.result_store;
+        if result_store.enabled && (result_store.max_size_mb == 0 || result_store.max_entry_kb == 0) {
+            return Err(Error::Configuration(
+                "models.result_store needs positive max_size_mb and max_entry_kb".to_string(),
+            ));
+        }
+
+        if self.extensions.enabled {
+            let plugin_limits = self.extensions.plugins.iter().filter_map(|plugin| plugin.limits.as_ref());
+            for limits in std::iter::once(&self.extensions.limits).chain(plugin_limits) {
+                check_timeout("extensions limits timeout_ms", limits.timeout_ms)?;
+                if limits.fuel_per_call == 0 || limits.max_memory_bytes == 0 {
+                    return Err(Error::Configuration(
+                        "extensions limits need positive fuel_per_call and max_memory_bytes".to_string(),
+                    ));
+                }
+            }
+        }
+
+        for (method, splice) in &self.models.streaming.splice {
+            check_timeout(&format!("models.streaming.splice[{}].cloud_budget_ms", method), splice.cloud_budget_ms)?;
+        }
+
+        let guard = &self.pipeline_guard;
+        let fraction = |value: f64| (0.0..=1.0).contains(&value);
+        if guard.health_check_interval_secs == 0
+            || guard.recovery_timeout_secs == 0
+            || guard.alerts.batch_size == 0
+        {
+            return Err(Error::Configuration(
+                "pipeline_guard needs positive health_check_interval_secs, recovery_timeout_secs \
+                 and alerts.batch_size"
+                    .to_string(),
+            ));
+        }
+        if !fraction(guard.thresholds.max_error_rate) || !fraction(guard.thresholds.max_cpu_usage) {
+            return Err(Error::Configuration(
+                "pipeline_guard.thresholds max_error_rate and max_cpu_usage must be between 0 and 1"
+                    .to_string(),
+            ));
+        }
+        let canary = &guard.canary;
+        if canary.enabled {
+            if canary.interval_secs == 0 || canary.max_latency_ratio <= 0.0 {
+                return Err(Error::Configuration(
+                    "pipeline_guard.canary.interval_secs and max_latency_ratio must be positive".to_string(),
+                ));
+            }
+            if !fraction(canary.min_quality) || !fraction(canary.min_similarity) {
+                return Err(Error::Configuration(
+                    "pipeline_guard.canary min_quality and min_similarity must be between 0 and 1".to_string(),
+                ));
+            }
+        }
+
+        let scaling = &self.scaling;
+        let margins = [
+            scaling.resource_margin,
+            scaling.performance_margin,
+            scaling.cost_margin,
+            scaling.availability_margin,
+        ];
+        if scaling.decision_interval_secs == 0
+            || scaling.trend_window_secs == 0
+            || !margins.into_iter().all(fraction)
+            || !fraction(scaling.learning_rate)
+            || !fraction(scaling.exploration_rate)
+        {
+            return Err(Error::Configuration(
+                "scaling needs a positive decision_interval_secs and trend_window_secs, and margins and rates between 0 and 1"
+                    .to_string(),
+            ));
+        }
+
+        if !(0.0..=100.0).contains(&self.deployment.min_test_coverage_percent) {
+            return Err(Error::Configuration(
+                "deployment.min_test_coverage_percent must be between 0 and 100".to_string(),
+            ));
+        }
+
+        if self.kv.max_bytes_per_namespace == 0 || self.kv.max_keys_per_namespace == 0 {
+            return Err(Error::Configuration(
+                "kv needs positive max_bytes_per_namespace and max_keys_per_namespace".to_string(),
+            ));
+        }
+
+        if self.conversations.enabled {
+            if self.conversations.max_messages_per_session == 0 {
+                return Err(Error::Configuration(
+                    "conversations.max_messages_per_session must be positive".to_string(),
+                ));
+            }
+            if self.conversations.directory.is_none() && self.retention.sessions.path.is_none() {
+                return Err(Error::Configuration(
+                    "conversations need a directory or retention.sessions.path".to_string(),
+                ));
+            }
+        }
+
+        if self.sessions.enabled
+            && (self.sessions.max_context_tokens == 0
+                || self.sessions.max_messages_per_session == 0
+                || self.sessions.idle_timeout_secs == 0)
+        {
+            return Err(Error::Configuration(
+                "sessions need positive max_context_tokens, max_messages_per_session and idle_timeout_secs"
+                    .to_string(),
+            ));
+        }
+
+        if self.normalization.transliterate.keys().any(|from| from.is_empty()) {
+            return Err(Error::Configuration(
+                "normalization.transliterate keys must not be empty".to_string(),
+            ));
+        }
+
+        let power = &self.power;
+        if !(1..=100).contains(&power.cpu_throttle_percent) {
+            return Err(Error::Configuration(
+                "power.cpu_throttle_percent must be between 1 and 100".to_string(),
+            ));
+        }
+        if power.sleep_on_idle_ms == 0 || power.sync_batch_size == 0 {
+            return Err(Error::Configuration(
+                "power.sleep_on_idle_ms and power.sync_batch_size must be positive".to_string(),
+            ));
+        }
+        for threshold in &power.battery_thresholds {
+            let level_valid = threshold.below_percent > 0.0 && threshold.below_percent <= 100.0;
+            if !level_valid || threshold.sleep_multiplier == 0 {
+                return Err(Error::Configuration(format!(
+                    "power.battery_thresholds need a level in (0, 100] and a positive multiplier: {}% x{}",
+                    threshold.below_percent,
+                    threshold.sleep_multiplier
+                )));
+            }
+        }
+
+        let mut camera_names = HashSet::new();
+        for camera in &self.peripherals.cameras {
+            if camera.name.is_empty() || !camera_names.insert(camera.name.as_str()) {
+                return Err(Error::Configuration(format!(
+                    "peripherals.cameras names must be unique and non-empty: '{}'",
+                    camera.name
+                )));
+            }
+            if camera.interval_secs == Some(0) || camera.method.is_empty() {
+                return Err(Error::Configuration(format!(
+                    "peripherals.cameras[{}] needs a positive interval_secs and a method",
+                    camera.name
+                )));
+            }
+            check_timeout(
+                &format!("peripherals.cameras[{}].capture_timeout_ms", camera.name),
+                camera.capture_timeout_ms,
+            )?;
+            if let FrameSourceKind::Rtsp { url, .. } = &camera.source {
+                if !url.starts_with("rtsp://") && !url.starts_with("rtsps://") {
+                    return Err(Error::Configuration(format!(
+                        "peripherals.cameras[{}] needs an rtsp:// URL",
+                        camera.name
+                    )));
+                }
+            }
+        }
+
+        let mut microphone_names = HashSet::new();
+        for microphone in &self.peripherals.microphones {
+            if microphone.name.is_empty() || !microphone_names.insert(microphone.name.as_str()) {
+                return Err(Error::Configuration(format!(
+                    "peripherals.microphones names must be unique and non-empty: '{}'",
+                    microphone.name
+                )));
+            }
+            let wake_word = &microphone.wake_word;
+            if wake_word.phrases.iter().all(|phrase| phrase.trim().is_empty()) {
+                return Err(Error::Configuration(format!(
+                    "peripherals.microphones[{}].wake_word needs a phrase",
+                    microphone.name
+                )));
+            }
+            if !(0.0..=1.0).contains(&wake_word.sensitivity)
+                || !(0.0..=1.0).contains(&wake_word.energy_threshold)
+            {
+                return Err(Error::Configuration(format!(
+                    "peripherals.microphones[{}].wake_word sensitivity and energy_threshold must be \
+                     between 0.0 and 1.0",
+                    microphone.name
+                )));
+            }
+            if microphone.sample_rate == 0
+                || wake_word.window_ms == 0
+                || microphone.max_utterance_secs == 0
+            {
+                return Err(Error::Configuration(format!(
+                    "peripherals.microphones[{}] needs a positive sample_rate, window_ms and \
+                     max_utterance_secs",
+                    microphone.name
+                )));
+            }
+        }
+
+        if self.queue.connectivity.enabled {
+            check_timeout("queue.connectivity.timeout_ms", self.queue.connectivity.timeout_ms)?;
+        }
+        for policy in &self.queue.sync_policies {
+            let (interval, has_trigger) = match policy {
+                SyncPolicy::Interval { sync_interval_seconds } => (*sync_interval_seconds, true),
+                SyncPolicy::Connectivity { check_interval_seconds } => (*check_interval_seconds, true),
+                SyncPolicy::Threshold {
+                    max_depth,
+                    max_age_seconds,
+                    check_interval_seconds,
+                } => (*check_interval_seconds, max_depth.is_some() || max_age_seconds.is_some()),
+            };
+            if interval == 0 || !has_trigger {
+                return Err(Error::Configuration(format!(
+                    "queue.sync_policies: {:?} needs a positive interval and a threshold",
+                    policy
+                )));
+            }
+        }
+
+        if self.queue.analytics.window_secs == 0 {
+            return Err(Error::Configuration("queue.analytics.window_secs must be positive".to_string()));
+        }
+        let retry_policy = &self.queue.retry_policy;
+        if retry_policy.backoff_multiplier < 1.0 || retry_policy.initial_delay_ms > retry_policy.max_delay_ms {
+            return Err(Error::Configuration(
+                "queue.retry_policy.backoff_multiplier must be at least 1 and initial_delay_ms at most max_delay_ms"
+                    .to_string(),
+            ));
+        }
+        if self.queue.dead_letter.max_entries == 0 {
+            return Err(Error::Configuration("queue.dead_letter.max_entries must be positive".to_string()));
+        }
+
+        let warm_standby = &self.router.warm_standby;
+        if warm_standby.enabled && warm_standby.refresh_interval_secs == 0 {
+            return Err(Error::Configuration(
+                "router.warm_standby.refresh_interval_secs must be positive".to_string(),
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.redaction.full_payload_sample_rate) {
+            return Err(Error::Configuration(
+                "redaction.full_payload_sample_rate must be between 0 and 1".to_string(),
+            ));
+        }
+
+        let tracing = &self.telemetry.tracing;
+        if !(0.0..=1.0).contains(&tracing.sample_ratio) {
+            return Err(Error::Configuration(
+                "telemetry.tracing.sample_ratio must be between 0 and 1".to_string(),
+            ));
+        }
+        if let Some(endpoint) = &tracing.otlp_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                return Err(Error::Configuration(format!(
+                    "telemetry.tracing.otlp_endpoint must be an http(s) URL, got {}",
+                    endpoint
+                )));
+            }
+        }
+
+        let telemetry = &self.telemetry;
+        if telemetry.opentelemetry_enabled {
+            if let Some(endpoint) = &telemetry.export_endpoint {
+                if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                    return Err(Error::Configuration(format!(
+                        "telemetry.export_endpoint must be an http(s) URL, got {}",
+                        endpoint
+                    )));
+                }
+            }
+            if telemetry.compression_enabled
+                && !matches!(telemetry.compression_algorithm.as_str(), "lz4" | "zstd")
+            {
+                return Err(Error::Configuration(format!(
+                    "telemetry.compression_algorithm must be lz4 or zstd, got {}",
+                    telemetry.compression_algorithm
+                )));
+            }
+        }
+        let otlp = &telemetry.otlp;
+        if otlp.log_level.parse::<tracing::Level>().is_err() {
+            return Err(Error::Configuration(format!(
+                "telemetry.otlp.log_level must be a log level, got {}",
+                otlp.log_level
+            )));
+        }
+        if otlp.max_batch_logs == 0 || otlp.max_spilled_batches == 0 {
+            return Err(Error::Configuration(
+                "telemetry.otlp batch and spill limits must be positive".to_string(),
+            ));
+        }
+
+        let escalation = &self.telemetry.log_escalation;
+        for (field, level) in [("base_level", &escalation.base_level), ("level", &escalation.level)] {
+            if level.parse::<tracing::Level>().is_err() {
+                return Err(Error::Configuration(format!(
+                    "telemetry.log_escalation.{} must be a log level, got {}",
+                    field, level
+                )));
+            }
+        }
+        if escalation.enabled && escalation.duration_secs == 0 {
+            return Err(Error::Configuration(
+                "telemetry.log_escalation.duration_secs must be positive".to_string(),
+            ));
+        }
+
+        let timeline = &self.telemetry.timeline;
+        if timeline.enabled && (timeline.error_spike_threshold == 0 || timeline.error_spike_window_secs == 0) {
+            return Err(Error::Configuration(
+                "telemetry.timeline error spike threshold and window must be positive".to_string(),
+            ));
+        }
+
+        let prometheus = &self.telemetry.prometheus;
+        let names = std::iter::once(&prometheus.namespace).chain(prometheus.metric_names.values());
+        if let Some(name) = names.filter(|name| !name.is_empty()).find(|name| !is_metric_name(name)) {
+            return Err(Error::Configuration(format!(
+                "telemetry.prometheus: {} is not a valid Prometheus metric name",
+                name
+            )));
+        }
+        let buckets = &prometheus.latency_buckets_secs;
+        if buckets.is_empty()
+            || buckets.iter().any(|bound| !bound.is_finite() || *bound <= 0.0)
+            || buckets.windows(2).any(|pair| pair[0] >= pair[1])
+        {
+            return Err(Error::Configuration(
+                "telemetry.prometheus.latency_buckets_secs must be positive and increasing".to_string(),
+            ));
+        }
+
+        let plugins = &self.models.plugins;
+        check_timeout("models.plugins.startup_timeout_ms", plugins.startup_timeout_ms)?;
+        let mut plugin_models = HashMap::new();
+        for (index, backend) in plugins.backends.iter().enumerate() {
+            if plugins.backends[..index].iter().any(|other| other.name == backend.name) {
+                return Err(Error::Configuration(format!(
+                    "models.plugins.backends has more than one backend named {}",
+                    backend.name
+                )));
+            }
+            if backend.name.is_empty() || backend.models.is_empty() {
+                return Err(Error::Configuration(format!(
+                    "models.plugins.backends[{}] must have a name and serve at least one model",
+                    backend.name
+                )));
+            }
+            if backend.sandbox.cpu_max_percent == Some(0) || backend.sandbox.pids_max == Some(0) {
+                return Err(Error::Configuration(format!(
+                    "models.plugins.backends[{}].sandbox limits must be positive",
+                    backend.name
+                )));
+            }
+            for model in &backend.models {
+                if let Some(other) = plugin_models.insert(model, &backend.name) {
+                    return Err(Error::Configuration(format!(
+                        "model {} is served by both plugin backends {} and {}",
+                        model, other, backend.name
+                    )));
+                }
+            }
+        }
+
+        for webhook in &self.outputs.webhooks {
+            check_timeout(&format!("outputs.webhooks[{}].delivery.timeout_ms", webhook.name), webhook.delivery.timeout_ms)?;
+            if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+                return Err(Error::Configuration(format!(
+                    "outputs.webhooks[{}].url must be an http(s) URL, got {}",
+                    webhook.name, webhook.url
+                )));
+            }
+        }
+
+        if let Some(artifacts) = &self.outputs.artifacts {
+            check_timeout("outputs.artifacts.upload.timeout_ms", artifacts.upload.timeout_ms)?;
+            if !artifacts.endpoint.starts_with("http://") && !artifacts.endpoint.starts_with("https://") {
+                return Err(Error::Configuration(format!(
+                    "outputs.artifacts.endpoint must be an http(s) URL, got {}",
+                    artifacts.endpoint
+                )));
+            }
+            if artifacts.upload.part_size_bytes < 5 * 1024 * 1024 {
+                return Err(Error::Configuration(format!(
+                    "outputs.artifacts.upload.part_size_bytes must be at least 5 MiB, got {}",
+                    artifacts.upload.part_size_bytes
+                )));
+            }
+        }
+
+        if let Some(redis) = &self.cluster.redis {
+            check_timeout("cluster.redis.timeout_ms", redis.timeout_ms)?;
+            if !redis.url.starts_with("redis://") {
+                return Err(Error::Configuration(format!(
+                    "cluster.redis.url must be a redis:// URL, got {}",
+                    redis.url
+                )));
+            }
+        }
+
+        let routing = &self.cluster.routing;
+        if routing.enabled {
+            check_timeout("cluster.routing.proxy_timeout_ms", routing.proxy_timeout_ms)?;
+            if routing.virtual_nodes == 0 {
+                return Err(Error::Configuration(
+                    "cluster.routing.virtual_nodes must be positive".to_string(),
+                ));
+            }
+            if !routing.members.iter().any(|member| member.id == routing.node_id) {
+                return Err(Error::Configuration(format!(
+                    "cluster.routing.members must include this node ({})",
+                    routing.node_id
+                )));
+            }
+            for member in &routing.members {
+                if !member.url.starts_with("http://") && !member.url.starts_with("https://") {
+                    return Err(Error::Configuration(format!(
+                        "cluster.routing.members[{}].url must be an http(s) URL, got {}",
+                        member.id, member.url
+                    )));
+                }
+            }
+        }
+
+        let high_availability = &self.cluster.high_availability;
+        if high_availability.enabled {
+            check_timeout(
+                "cluster.high_availability.request_timeout_ms",
+                high_availability.request_timeout_ms,
+            )?;
+            if !high_availability.peer_url.starts_with("http://")
+                && !high_availability.peer_url.starts_with("https://")
+            {
+                return Err(Error::Configuration(format!(
+                    "cluster.high_availability.peer_url must be an http(s) URL, got '{}'",
+                    high_availability.peer_url
+                )));
+            }
+            if high_availability.shared_secret.as_deref().map_or(0, str::len) < 16 {
+                return Err(Error::Configuration(
+                    "cluster.high_availability.shared_secret must be at least 16 characters"
+                        .to_string(),
+                ));
+            }
+            if high_availability.sync_interval_ms == 0
+                || high_availability.failover_timeout_ms <= high_availability.sync_interval_ms
+            {
+                return Err(Error::Configuration(
+                    "cluster.high_availability.failover_timeout_ms must be longer than a positive \
+                     sync_interval_ms"
+                        .to_string(),
+                ));
+            }
+        }
+
+        let health_checks = &self.gateway.health_checks;
+        if !health_checks.auth_exempt && health_checks.probe_token.as_deref().map_or(true, str::is_empty) {
+            return Err(Error::Configuration(
+                "gateway.health_checks.probe_token is required when auth_exempt is false".to_string(),
+            ));
+        }
+
+        let rate_limits = &self.security.rate_limits;
+        if rate_limits.device_requests_per_minute == 0
+            || rate_limits.device_requests_per_hour == 0
+            || rate_limits.client_requests_per_window == 0
+            || rate_limits.client_window_secs == 0
+        {
+            return Err(Error::Configuration("security.rate_limits must all be positive".to_string()));
+        }
+
+        let enrollment = &self.security.enrollment;
+        if enrollment.enabled && enrollment.certificate_validity_days == 0 {
+            return Err(Error::Configuration(
+                "security.enrollment.certificate_validity_days must be positive".to_string(),
+            ));
+        }
+
+        let authentication = &self.security.authentication;
+        if authentication.require_authentication
+            && authentication.api_keys.is_empty()
+            && authentication.jwt.is_none()
+        {
+            return Err(Error::Configuration(
+                "security.authentication.require_authentication needs api_keys or jwt".to_string(),
+            ));
+        }
+        for key in &authentication.api_keys {
+            if key.key_sha256.len() != 64 || !key.key_sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(Error::Configuration(format!(
+                    "security.authentication.api_keys[{}].key_sha256 must be a hex SHA-256 digest",
+                    key.name
+                )));
+            }
+        }
+        if authentication.jwt.as_ref().is_some_and(|jwt| jwt.jwks_url.is_empty()) {
+            return Err(Error::Configuration(
+                "security.authentication.jwt.jwks_url is required".to_string(),
+            ));
+        }
+
+        let moderation = &self.security.output_moderation;
+        let mut categories = std::collections::HashSet::new();
+        for category in &moderation.categories {
+            if category.name.is_empty() || !categories.insert(category.name.as_str()) {
+                return Err(Error::Configuration(format!(
+                    "security.output_moderation.categories need unique non-empty names, got '{}'",
+                    category.name
+                )));
+            }
+            if category.patterns.is_empty() || category.threshold == 0 {
+                return Err(Error::Configuration(format!(
+                    "security.output_moderation.categories[{}] needs patterns and a positive threshold",
+                    category.name
+                )));
+            }
+        }
+
+        if crate::crypto::FIPS_MODE {
+            let algorithm = &self.security.encryption_algorithm;
+            if !algorithm.eq_ignore_ascii_case("AES-256-GCM") && !algorithm.eq_ignore_ascii_case("auto") {
+                return Err(Error::Configuration(format!(
+                    "security.encryption_algorithm must be AES-256-GCM in FIPS mode, got {}",
+                    algorithm
+                )));
+            }
+            for webhook in &self.outputs.webhooks {
+                if webhook.secret.as_ref().is_some_and(|secret| secret.len() < crate::crypto::MIN_HMAC_KEY_BYTES) {
+                    return Err(Error::Configuration(format!(
+                        "outputs.webhooks[{}].secret must be at least {} bytes in FIPS mode",
+                        webhook.name,
+                        crate::crypto::MIN_HMAC_KEY_BYTES
+                    )));
+                }
+            }
+        }
+
+        if self.retention.enabled && self.retention.purge_interval_secs == 0 {
+            return Err(Error::Configuration(
+                "retention.purge_interval_secs must be positive".to_string(),
+            ));
+        }
+        for hold in &self.retention.legal_holds {
+            if hold.device_id.is_none() && hold.path.is_none() {
+                return Err(Error::Configuration(format!(
+                    "retention.legal_holds[{}] must name a device_id or path",
+                    hold.id
+                )));
+            }
+        }
+
+        for connector in &self.outputs.connectors {
+            if let Some(timeout_ms) = connector.timeout_ms {
+                check_timeout(&format!("outputs.connectors[{}].timeout_ms", connector.name), timeout_ms)?;
+            }
+            if connector.telemetry_interval_secs == Some(0) {
+                return Err(Error::Configuration(format!(
+                    "outputs.connectors[{}].telemetry_interval_secs must be positive",
+                    connector.name
+                )));
+            }
+        }
+
+        for (method, timeouts) in &self.gateway.timeouts.methods {
+            let prefix = format!("gateway.timeouts.methods.{}", method);
+            for (field, value) in [
+                ("total_ms", timeouts.total_ms),
+                ("inference_ms", timeouts.inference_ms),
+                ("cloud_read_ms", timeouts.cloud_read_ms),
+            ] {
+                if let Some(value) = value {
+                    check_timeout(&format!("{}.{}", prefix, field), value)?;
+                }
+            }
+
+            let total = self.request_budget(method);
+            for (field, budget) in [
+                ("inference_ms", timeouts.inference_ms),
+                ("cloud_read_ms", timeouts.cloud_read_ms),
+            ] {
+                if budget.is_some_and(|ms| Duration::from_millis(ms) > total) {
+                    return Err(Error::Configuration(format!(
+                        "{}.{} exceeds the total request budget of {}ms",
+                        prefix,
+                        field,
+                        total.as_millis()
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// End-to-end latency budget for a method
+    pub fn request_budget(&self, method: &str) -> Duration {
+        let ms = self
+            .method_timeouts(method)
+            .and_then(|t| t.total_ms)
+            .unwrap_or(self.gateway.request_timeout_ms);
+        Duration::from_millis(ms)
+    }
+
+    /// Local inference budget for a method
+    pub fn inference_budget(&self, method: &str) -> Duration {
+        let ms = self
+            .method_timeouts(method)
+            .and_then(|t| t.inference_ms)
+            .unwrap_or(self.models.model_timeout_ms);
+        Duration::from_millis(ms)
+    }
+
+    /// Cloud response budget for a method sent to the given endpoint
+    pub fn cloud_read_budget(&self, method: &str, endpoint: &CloudEndpoint) -> Duration {
+        let ms = self
+            .method_timeouts(method)
+            .and_then(|t| t.cloud_read_ms)
+            .unwrap_or(endpoint.timeout_ms);
+        Duration::from_millis(ms)
+    }
+
+    /// Connect timeout for the given cloud endpoint
+    pub fn cloud_connect_timeout(&self, endpoint: &CloudEndpoint) -> Option<Duration> {
+        endpoint
+            .connect_timeout_ms
+            .or(self.gateway.timeouts.cloud_connect_timeout_ms)
+            .map(Duration::from_millis)
+    }
+
+    fn method_timeouts(&self, method: &str) -> Option<&MethodTimeouts> {
+        self.gateway.timeouts.methods.get(method)
+    }
+}
+
+fn check_timeout(field: &str, value_ms: u64) -> Result<()> {
+    if value_ms == 0 || value_ms > MAX_TIMEOUT_MS {
+        return Err(Error::Configuration(format!(
+            "{} must be between 1 and {}ms, got {}",
+            field, MAX_TIMEOUT_MS, value_ms
+        )));
+    }
+    Ok(())
+}
+
+/// Prometheus metric names: `[a-zA-Z_:][a-zA-Z0-9_:]*`
+fn is_metric_name(name: &str) -> bool {
+    name.chars().enumerate().all(|(index, c)| {
+        c.is_ascii_alphabetic() || c == '_' || c == ':' || (index > 0 && c.is_ascii_digit())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_method_budgets_override_defaults() {
+        let mut config = Config::default();
+        config.gateway.timeouts.methods.insert(
+            "embedding".to_string(),
+            MethodTimeouts {
+                total_ms: Some(2000),
+                inference_ms: Some(1500),
+                cloud_read_ms: None,
+            },
+        );
+
+        assert_eq!(config.request_budget("embedding"), Duration::from_millis(2000));
+        assert_eq!(config.inference_budget("embedding"), Duration::from_millis(1500));
+        assert_eq!(config.request_budget("completion"), Duration::from_millis(30000));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_inference_budget_must_fit_request_budget() {
+        let mut config = Config::default();
+        config.gateway.timeouts.methods.insert(
+            "completion".to_string(),
+            MethodTimeouts {
+                total_ms: Some(1000),
+                inference_ms: Some(5000),
+                cloud_read_ms: None,
+            },
+        );
+
+        assert!(matches!(config.validate(), Err(Error::Configuration(_))));
     }
 }
@@ -0,0 +1,289 @@
+//! Commented example configuration
+//!
+//! [`example_toml`] renders `Config::default()` as TOML, with every section
+//! and setting preceded by the doc comment of its field. The doc comments are
+//! read from `config.rs` at compile time, so the example always lists the
+//! settings, defaults and descriptions of the build it came from.
+
+use crate::config::Config;
+use crate::Result;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const CONFIG_SOURCE: &str = include_str!("config.rs");
+
+/// Field of a config struct as declared in `config.rs`
+struct FieldDoc {
+    name: &'static str,
+    ty: &'static str,
+    doc: Vec<&'static str>,
+}
+
+/// Config struct as declared in `config.rs`
+#[derive(Default)]
+struct StructDoc {
+    doc: Vec<&'static str>,
+    fields: Vec<FieldDoc>,
+}
+
+/// What a TOML table holds
+#[derive(Clone, Copy)]
+enum TableKind<'a> {
+    /// Fields of the named struct
+    Struct(&'a str),
+    /// Map entries whose values have the given type
+    Map(&'a str),
+}
+
+/// Default configuration as commented TOML
+pub fn example_toml() -> Result<String> {
+    let structs = parse_structs(CONFIG_SOURCE);
+    let Value::Object(root) = serde_json::to_value(Config::default())? else {
+        unreachable!("Config serializes to a table");
+    };
+    let mut out = String::new();
+    out.push_str("# MCP WASM Edge Gateway configuration\n");
+    out.push_str("#\n");
+    out.push_str("# Generated from the configuration types; every setting shows its default.\n");
+    out.push_str("# A configuration file only needs the settings it changes.\n");
+    write_table(&mut out, &structs, &[], &root, TableKind::Struct("Config"));
+    Ok(out)
+}
+
+/// Doc comments and fields of every `pub struct` in `source`
+fn parse_structs(source: &'static str) -> HashMap<&'static str, StructDoc> {
+    let mut structs = HashMap::new();
+    let mut current: Option<(&str, StructDoc)> = None;
+    let mut doc = Vec::new();
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if let Some(comment) = trimmed.strip_prefix("///") {
+            doc.push(comment.strip_prefix(' ').unwrap_or(comment));
+            continue;
+        }
+        if trimmed.starts_with("#[") {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("pub struct ").and_then(|rest| rest.strip_suffix(" {")) {
+            current = Some((name, StructDoc { doc: std::mem::take(&mut doc), fields: Vec::new() }));
+            continue;
+        }
+        if line == "}" {
+            if let Some((name, parsed)) = current.take() {
+                structs.insert(name, parsed);
+            }
+        } else if let Some((_, parsed)) = current.as_mut() {
+            let field = line
+                .strip_prefix("    pub ")
+                .and_then(|rest| rest.split_once(": "))
+                .and_then(|(name, ty)| Some((name, ty.strip_suffix(',')?)));
+            if let Some((name, ty)) = field {
+                parsed.fields.push(FieldDoc { name, ty, doc: std::mem::take(&mut doc) });
+            }
+        }
+        doc.clear();
+    }
+    structs
+}
+
+/// Struct name a field type refers to, looking through options and paths
+fn struct_name(ty: &str) -> &str {
+    let ty = ty.trim();
+    if let Some(inner) = ty.strip_prefix("Option<").and_then(|rest| rest.strip_suffix('>')) {
+        return struct_name(inner);
+    }
+    ty.rsplit("::").next().unwrap_or(ty)
+}
+
+/// Element type of a `Vec<T>` field
+fn list_element(ty: &str) -> Option<&str> {
+    ty.trim().strip_prefix("Vec<").and_then(|rest| rest.strip_suffix('>'))
+}
+
+/// Value type of a `HashMap<K, V>` or `BTreeMap<K, V>` field
+fn map_value(ty: &str) -> Option<&str> {
+    let ty = ty.trim();
+    let inner = ty
+        .strip_prefix("HashMap<")
+        .or_else(|| ty.strip_prefix("BTreeMap<"))?
+        .strip_suffix('>')?;
+    inner.split_once(", ").map(|(_, value)| value)
+}
+
+fn write_table(
+    out: &mut String,
+    structs: &HashMap<&'static str, StructDoc>,
+    path: &[String],
+    table: &Map<String, Value>,
+    kind: TableKind,
+) {
+    let declared = match kind {
+        TableKind::Struct(name) => structs.get(name),
+        TableKind::Map(_) => None,
+    };
+    // Declaration order first, then anything the source did not describe
+    let mut keys: Vec<&String> = Vec::with_capacity(table.len());
+    if let Some(declared) = declared {
+        let declared_keys = declared.fields.iter().filter_map(|field| table.get_key_value(field.name));
+        keys.extend(declared_keys.map(|(key, _)| key));
+    }
+    let mut rest: Vec<&String> = table.keys().filter(|key| !keys.contains(key)).collect();
+    rest.sort();
+    keys.extend(rest);
+
+    let field = |key: &str| declared?.fields.iter().find(|field| field.name == key);
+    let field_type = |key: &str| match kind {
+        TableKind::Struct(_) => field(key).map(|field| field.ty).unwrap_or_default(),
+        TableKind::Map(value) => value,
+    };
+
+    let (tables, settings): (Vec<&String>, Vec<&String>) =
+        keys.into_iter().partition(|key| is_table(&table[*key]) || is_table_list(&table[*key]));
+
+    for key in settings {
+        if let Some(field) = field(key) {
+            write_comment(out, &field.doc);
+        }
+        match &table[key] {
+            Value::Null => {
+                let _ = writeln!(out, "# {} is not set by default", key_name(key));
+            },
+            value => {
+                let _ = writeln!(out, "{} = {}", key_name(key), inline_value(value));
+            },
+        }
+    }
+
+    for key in tables {
+        let ty = field_type(key);
+        let mut child_path = path.to_vec();
+        child_path.push(key_name(key));
+        let header = child_path.join(".");
+        let doc = match field(key) {
+            Some(field) if !field.doc.is_empty() => field.doc.clone(),
+            _ => structs
+                .get(struct_name(list_element(ty).unwrap_or(ty)))
+                .map(|parsed| parsed.doc.clone())
+                .unwrap_or_default(),
+        };
+        match &table[key] {
+            Value::Object(child) => {
+                out.push('\n');
+                write_comment(out, &doc);
+                let _ = writeln!(out, "[{}]", header);
+                let child_kind = match map_value(ty) {
+                    Some(value) => TableKind::Map(value),
+                    None => TableKind::Struct(struct_name(ty)),
+                };
+                write_table(out, structs, &child_path, child, child_kind);
+            },
+            Value::Array(elements) => {
+                let element = struct_name(list_element(ty).unwrap_or_default());
+                for (index, value) in elements.iter().enumerate() {
+                    out.push('\n');
+                    if index == 0 {
+                        write_comment(out, &doc);
+                    }
+                    let _ = writeln!(out, "[[{}]]", header);
+                    if let Value::Object(child) = value {
+                        write_table(out, structs, &child_path, child, TableKind::Struct(element));
+                    }
+                }
+            },
+            _ => unreachable!("only tables are written as sections"),
+        }
+    }
+}
+
+fn is_table(value: &Value) -> bool {
+    matches!(value, Value::Object(_))
+}
+
+fn is_table_list(value: &Value) -> bool {
+    matches!(value, Value::Array(elements) if !elements.is_empty() && elements.iter().all(is_table))
+}
+
+fn write_comment(out: &mut String, doc: &[&str]) {
+    for line in doc {
+        if line.is_empty() {
+            out.push_str("#\n");
+        } else {
+            let _ = writeln!(out, "# {}", line);
+        }
+    }
+}
+
+fn key_name(key: &str) -> String {
+    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+/// TOML for a value written on one line; JSON string escapes are valid TOML
+fn inline_value(value: &Value) -> String {
+    match value {
+        Value::Array(elements) => {
+            let elements: Vec<String> = elements.iter().map(inline_value).collect();
+            format!("[{}]", elements.join(", "))
+        },
+        Value::Object(entries) => {
+            let mut entries: Vec<String> = entries
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| format!("{} = {}", key_name(key), inline_value(value)))
+                .collect();
+            entries.sort();
+            if entries.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", entries.join(", "))
+            }
+        },
+        Value::Number(number) if number.is_f64() => {
+            // `f32` settings widen to noisy `f64`s; print those at `f32` precision
+            let float = number.as_f64().unwrap_or_default();
+            let text = if float as f32 as f64 == float {
+                (float as f32).to_string()
+            } else {
+                float.to_string()
+            };
+            if text.contains(['.', 'e', 'E']) {
+                text
+            } else {
+                format!("{}.0", text)
+            }
+        },
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_section_is_documented_from_its_type() {
+        let example = example_toml().unwrap();
+        assert!(example.contains("\n# Signed manifests shipped next to model files\n"));
+        assert!(example.contains("[models.signing]\nmode = \"off\"\n"));
+        assert!(example.contains("# Consecutive failures before a component's circuit breaker trips\n"));
+        assert!(example.contains("[pipeline_guard.thresholds]\n"));
+        // Settings keep their declaration order
+        let port = example.find("\nport = ").unwrap();
+        assert!(example.find("\nbind_address = ").unwrap() < port);
+    }
+
+    #[test]
+    fn test_field_types_are_resolved_through_wrappers() {
+        assert_eq!(struct_name("Option<crate::config::TlsConfig>"), "TlsConfig");
+        assert_eq!(list_element("Vec<CameraConfig>"), Some("CameraConfig"));
+        assert_eq!(map_value("HashMap<String, ConcurrencyLimit>"), Some("ConcurrencyLimit"));
+        assert_eq!(key_name("a.b"), "\"a.b\"");
+        assert_eq!(inline_value(&serde_json::json!([1.0, "x"])), "[1.0, \"x\"]");
+        assert_eq!(inline_value(&serde_json::json!(0.7f32)), "0.7");
+    }
+}
@@ -0,0 +1,67 @@
+//! Crypto backend shared by all gateway crates
+//!
+//! Crates take their primitives from this module instead of depending on
+//! `ring` directly. Building with the `fips` feature swaps the backend for
+//! the FIPS 140-3 validated AWS-LC module, which exposes the same API, and
+//! restricts the gateway to approved algorithms and key sizes.
+
+use crate::{Error, Result};
+
+#[cfg(feature = "fips")]
+pub use aws_lc_rs::{aead, digest, hmac, rand, signature};
+#[cfg(not(feature = "fips"))]
+pub use ring::{aead, digest, hmac, rand, signature};
+
+/// Whether this build uses the FIPS-validated backend
+pub const FIPS_MODE: bool = cfg!(feature = "fips");
+
+/// Shortest HMAC key SP 800-131A allows, in bytes (112 bits)
+pub const MIN_HMAC_KEY_BYTES: usize = 14;
+
+/// Name of the crypto backend, for health and attestation output
+pub fn backend() -> &'static str {
+    if FIPS_MODE {
+        "aws-lc-fips"
+    } else {
+        "ring"
+    }
+}
+
+/// Fail startup if the backend's power-on self tests did not pass
+pub fn self_test() -> Result<()> {
+    #[cfg(feature = "fips")]
+    aws_lc_rs::try_fips_mode().map_err(|e| Error::Security(format!("FIPS self test failed: {}", e)))?;
+    Ok(())
+}
+
+/// Whether an AEAD may be used; only AES-GCM is approved in FIPS mode
+pub fn aead_approved(algorithm: &'static aead::Algorithm) -> bool {
+    !FIPS_MODE || algorithm == &aead::AES_256_GCM || algorithm == &aead::AES_128_GCM
+}
+
+/// HMAC-SHA256 key, refusing keys shorter than approved in FIPS mode
+pub fn hmac_key(secret: &[u8]) -> Result<hmac::Key> {
+    if FIPS_MODE && secret.len() < MIN_HMAC_KEY_BYTES {
+        return Err(Error::Security(format!(
+            "HMAC key of {} bytes is below the {} bytes FIPS mode requires",
+            secret.len(),
+            MIN_HMAC_KEY_BYTES
+        )));
+    }
+    Ok(hmac::Key::new(hmac::HMAC_SHA256, secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_matches_build() {
+        assert!(aead_approved(&aead::AES_256_GCM));
+        assert_eq!(aead_approved(&aead::CHACHA20_POLY1305), !FIPS_MODE);
+        assert_eq!(hmac_key(b"short").is_err(), FIPS_MODE);
+        assert!(hmac_key(b"a-sufficiently-long-secret").is_ok());
+        assert_eq!(backend() == "ring", !FIPS_MODE);
+        self_test().unwrap();
+    }
+}
@@ -1,5 +1,8 @@
 //! Error types and result handling for the MCP Edge Gateway
 
+use crate::concurrency::Rejection;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use thiserror::Error;
 
 /// Result type alias for MCP operations
@@ -32,15 +35,28 @@ pub enum Error {
     #[error("Resource exhausted: {0}")]
     ResourceExhausted(String),
 
+    #[error("Overloaded: {0}")]
+    Overloaded(OverloadDetails),
+
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
     
     #[error("Validation error: {0}")]
     Validation(String),
 
+    /// An idempotency key was reused for a different request
+    #[error("Idempotency key reused: {0}")]
+    IdempotencyKeyReused(String),
+
     #[error("Timeout: {0}")]
     Timeout(String),
 
+    #[error("Deadline exceeded: {0}")]
+    DeadlineExceeded(TimeoutDetails),
+
+    #[error("Response verification failed: {0}")]
+    VerificationFailed(String),
+
     #[error("Serialization error: {0}")]
     Serialization(String),
 
@@ -59,7 +75,11 @@ impl Error {
     pub fn is_retryable(&self) -> bool {
         matches!(
             self,
-            Error::Network(_) | Error::Timeout(_) | Error::ResourceExhausted(_)
+            Error::Network(_)
+                | Error::Timeout(_)
+                | Error::DeadlineExceeded(_)
+                | Error::ResourceExhausted(_)
+                | Error::Overloaded(_)
         )
     }
 
@@ -73,10 +93,11 @@ impl Error {
             Error::Queue(_) => "queue",
             Error::Routing(_) => "routing",
             Error::Telemetry(_) => "telemetry",
-            Error::ResourceExhausted(_) => "resource",
-            Error::InvalidRequest(_) => "request",
+            Error::ResourceExhausted(_) | Error::Overloaded(_) => "resource",
+            Error::InvalidRequest(_) | Error::IdempotencyKeyReused(_) => "request",
             Error::Validation(_) => "validation",
-            Error::Timeout(_) => "timeout",
+            Error::Timeout(_) | Error::DeadlineExceeded(_) => "timeout",
+            Error::VerificationFailed(_) => "verification",
             Error::Serialization(_) => "serialization",
             Error::Memory(_) => "memory",
             Error::Internal(_) => "internal",
@@ -92,15 +113,19 @@ impl Error {
             Error::Configuration(_) => 4,
             Error::Model(_) => 4,
             Error::ResourceExhausted(_) => 3,
+            Error::Overloaded(_) => 3,
             Error::Queue(_) => 3,
             Error::Routing(_) => 3,
             Error::Network(_) => 2,
             Error::Timeout(_) => 2,
+            Error::DeadlineExceeded(_) => 2,
             Error::Telemetry(_) => 1,
             Error::Memory(_) => 4,
             Error::InvalidRequest(_) => 2,
+            Error::IdempotencyKeyReused(_) => 2,
             Error::Validation(_) => 2,
             Error::Serialization(_) => 2,
+            Error::VerificationFailed(_) => 3,
             Error::Generic(_) => 3,
         }
     }
@@ -118,8 +143,9 @@ impl Error {
 
         match self {
             Error::Network(_) => Some(1000), // 1 second
-            Error::Timeout(_) => Some(2000), // 2 seconds
+            Error::Timeout(_) | Error::DeadlineExceeded(_) => Some(2000), // 2 seconds
             Error::ResourceExhausted(_) => Some(5000), // 5 seconds
+            Error::Overloaded(details) => Some(details.retry_after_ms),
             _ => None,
         }
     }
@@ -128,8 +154,8 @@ impl Error {
     pub fn max_retries(&self) -> u32 {
         match self {
             Error::Network(_) => 3,
-            Error::Timeout(_) => 2,
-            Error::ResourceExhausted(_) => 5,
+            Error::Timeout(_) | Error::DeadlineExceeded(_) => 2,
+            Error::ResourceExhausted(_) | Error::Overloaded(_) => 5,
             _ => 0,
         }
     }
@@ -148,6 +174,7 @@ impl Error {
             Error::InvalidRequest(msg) => Error::InvalidRequest(format!("{}: {}", context, msg)),
             Error::Validation(msg) => Error::Validation(format!("{}: {}", context, msg)),
             Error::Timeout(msg) => Error::Timeout(format!("{}: {}", context, msg)),
+            Error::VerificationFailed(msg) => Error::VerificationFailed(format!("{}: {}", context, msg)),
             Error::Memory(msg) => Error::Memory(format!("{}: {}", context, msg)),
             Error::Internal(msg) => Error::Internal(format!("{}: {}", context, msg)),
             other => other, // Cannot add context to these types
@@ -155,6 +182,90 @@ impl Error {
     }
 }
 
+/// Pipeline stage whose latency budget was exhausted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutStage {
+    Request,
+    Inference,
+    CloudConnect,
+    CloudRead,
+}
+
+/// Structured description of an exceeded latency budget
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeoutDetails {
+    pub stage: TimeoutStage,
+    pub method: String,
+    pub budget_ms: u64,
+    pub target: Option<String>,
+}
+
+impl TimeoutDetails {
+    pub fn new(stage: TimeoutStage, method: &str, budget: std::time::Duration) -> Self {
+        Self {
+            stage,
+            method: method.to_string(),
+            budget_ms: budget.as_millis() as u64,
+            target: None,
+        }
+    }
+
+    /// Attach the model or endpoint the budget applied to
+    pub fn with_target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+}
+
+impl fmt::Display for TimeoutStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TimeoutStage::Request => "request",
+            TimeoutStage::Inference => "inference",
+            TimeoutStage::CloudConnect => "cloud connect",
+            TimeoutStage::CloudRead => "cloud read",
+        };
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for TimeoutDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} budget of {}ms exceeded for method '{}'",
+            self.stage, self.budget_ms, self.method
+        )?;
+        if let Some(target) = &self.target {
+            write!(f, " ({})", target)?;
+        }
+        Ok(())
+    }
+}
+
+/// Structured description of a request turned away by the scheduler
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverloadDetails {
+    /// Model or pool whose queue turned the request away
+    pub target: String,
+    pub rejection: Rejection,
+    /// When the caller should try again
+    pub retry_after_ms: u64,
+}
+
+impl fmt::Display for OverloadDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}, retry after {}ms",
+            self.target,
+            self.rejection.as_str(),
+            self.retry_after_ms
+        )
+    }
+}
+
 /// Specialized result types for different operations
 pub type ConfigResult<T> = std::result::Result<T, Error>;
 pub type NetworkResult<T> = std::result::Result<T, Error>;
@@ -189,10 +300,10 @@ impl Error {
                 max_attempts: 2, 
                 base_delay_ms: 2000 
             },
-            Error::ResourceExhausted(_) => RecoveryStrategy::CircuitBreaker { 
+            Error::ResourceExhausted(_) | Error::Overloaded(_) => RecoveryStrategy::CircuitBreaker { 
                 timeout_ms: 30000 
             },
-            Error::Model(_) => RecoveryStrategy::Fallback("cloud".to_string()),
+            Error::Model(_) | Error::VerificationFailed(_) => RecoveryStrategy::Fallback("cloud".to_string()),
             Error::Routing(_) => RecoveryStrategy::Fallback("queue".to_string()),
             Error::Security(_) => RecoveryStrategy::NoRecovery,
             Error::Configuration(_) => RecoveryStrategy::NoRecovery,
@@ -216,9 +327,13 @@ impl Clone for Error {
             Error::Routing(s) => Error::Routing(s.clone()),
             Error::Telemetry(s) => Error::Telemetry(s.clone()),
             Error::ResourceExhausted(s) => Error::ResourceExhausted(s.clone()),
+            Error::Overloaded(d) => Error::Overloaded(d.clone()),
             Error::InvalidRequest(s) => Error::InvalidRequest(s.clone()),
             Error::Validation(s) => Error::Validation(s.clone()),
+            Error::IdempotencyKeyReused(s) => Error::IdempotencyKeyReused(s.clone()),
             Error::Timeout(s) => Error::Timeout(s.clone()),
+            Error::DeadlineExceeded(d) => Error::DeadlineExceeded(d.clone()),
+            Error::VerificationFailed(s) => Error::VerificationFailed(s.clone()),
             Error::Serialization(s) => Error::Serialization(s.clone()),
             Error::Memory(s) => Error::Memory(s.clone()),
             Error::Internal(s) => Error::Internal(s.clone()),
@@ -0,0 +1,339 @@
+//! Process-wide lifecycle event bus
+//!
+//! Every crate publishes typed lifecycle events (components starting and
+//! stopping, models loading, configuration reloads, connectivity changes,
+//! alerts, queue activity) on one bus instead of keeping its own broadcast
+//! channel. Subscribers choose the event kinds they want, each has a bounded
+//! buffer (a slow subscriber loses the oldest events rather than stalling
+//! publishers), and the number of subscribers is capped.
+
+use crate::{Error, ModelId, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Events buffered per subscriber before the oldest are dropped
+pub const SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Subscribers allowed at once on the global bus
+pub const MAX_SUBSCRIBERS: usize = 64;
+
+/// Severity of a raised alert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// What started a queue sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncTrigger {
+    /// `sync_with_cloud` was called
+    Manual,
+    Interval,
+    /// Connectivity returned after being lost
+    Connectivity,
+    /// Queue depth or age crossed a threshold
+    Threshold,
+}
+
+/// A change in offline queue state
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueueEvent {
+    Enqueued {
+        request_id: Uuid,
+        priority_score: f32,
+        queue_size: usize,
+        capacity: usize,
+    },
+    Dequeued {
+        request_id: Uuid,
+        queue_size: usize,
+    },
+    SyncStarted {
+        trigger: SyncTrigger,
+        pending: usize,
+    },
+    SyncFinished {
+        trigger: SyncTrigger,
+        synced: usize,
+        failed: usize,
+        queue_size: usize,
+    },
+    /// Sync that stopped with an error before finishing
+    SyncFailed {
+        trigger: SyncTrigger,
+        error: String,
+    },
+    Expired {
+        request_id: Uuid,
+    },
+    /// Request dropped after exhausting its sync retries
+    DeadLettered {
+        request_id: Uuid,
+        retries: u32,
+    },
+}
+
+/// Lifecycle event published on the bus
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GatewayEvent {
+    ComponentStarted {
+        component: String,
+    },
+    ComponentStopped {
+        component: String,
+    },
+    ModelLoaded {
+        model_id: ModelId,
+        memory_mb: u32,
+    },
+    ModelUnloaded {
+        model_id: ModelId,
+    },
+    ConfigReloaded {
+        section: String,
+    },
+    /// A reloaded configuration file passed validation and replaced the
+    /// running configuration
+    ConfigChanged {
+        version: u64,
+        /// Top-level sections that differ from the previous configuration
+        changed: Vec<String>,
+        /// Changed sections that only take effect after a restart
+        restart_required: Vec<String>,
+    },
+    ConnectivityChanged {
+        endpoint: String,
+        online: bool,
+    },
+    AlertRaised {
+        source: String,
+        severity: AlertSeverity,
+        message: String,
+    },
+    Queue {
+        event: QueueEvent,
+    },
+    DeploymentFinished {
+        deployment_id: Uuid,
+        version: String,
+        success: bool,
+    },
+    ScalingApplied {
+        action: String,
+    },
+    /// The pipeline guard tried to recover a degraded component
+    RecoveryAttempted {
+        component: String,
+        success: bool,
+        detail: String,
+    },
+}
+
+/// Coarse event category used for subscription filters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Component,
+    Model,
+    Config,
+    Connectivity,
+    Alert,
+    Queue,
+    Deployment,
+    Scaling,
+    Recovery,
+}
+
+impl EventKind {
+    /// Parse a kind name as used in subscription filters
+    pub fn parse(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.trim().to_lowercase())).ok()
+    }
+}
+
+impl GatewayEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            GatewayEvent::ComponentStarted { .. } | GatewayEvent::ComponentStopped { .. } => {
+                EventKind::Component
+            },
+            GatewayEvent::ModelLoaded { .. } | GatewayEvent::ModelUnloaded { .. } => EventKind::Model,
+            GatewayEvent::ConfigReloaded { .. } | GatewayEvent::ConfigChanged { .. } => EventKind::Config,
+            GatewayEvent::ConnectivityChanged { .. } => EventKind::Connectivity,
+            GatewayEvent::AlertRaised { .. } => EventKind::Alert,
+            GatewayEvent::Queue { .. } => EventKind::Queue,
+            GatewayEvent::DeploymentFinished { .. } => EventKind::Deployment,
+            GatewayEvent::ScalingApplied { .. } => EventKind::Scaling,
+            GatewayEvent::RecoveryAttempted { .. } => EventKind::Recovery,
+        }
+    }
+}
+
+/// Event with its bus sequence number and publish time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventEnvelope {
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: GatewayEvent,
+}
+
+/// Typed publish/subscribe bus
+pub struct EventBus {
+    sender: broadcast::Sender<EventEnvelope>,
+    sequence: AtomicU64,
+    max_subscribers: usize,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize, max_subscribers: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self {
+            sender,
+            sequence: AtomicU64::new(0),
+            max_subscribers,
+        }
+    }
+
+    /// The bus shared by every component in the process
+    pub fn global() -> &'static EventBus {
+        static GLOBAL: OnceLock<EventBus> = OnceLock::new();
+        GLOBAL.get_or_init(|| EventBus::new(SUBSCRIBER_CAPACITY, MAX_SUBSCRIBERS))
+    }
+
+    /// Publish an event; returns its sequence number
+    pub fn publish(&self, event: GatewayEvent) -> u64 {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        // Publishing with no subscribers is normal and not an error
+        let _ = self.sender.send(EventEnvelope {
+            sequence,
+            timestamp: Utc::now(),
+            event,
+        });
+        sequence
+    }
+
+    /// Subscribe to the given kinds, or to everything when `kinds` is empty
+    pub fn subscribe(&self, kinds: &[EventKind]) -> Result<EventSubscriber> {
+        if self.sender.receiver_count() >= self.max_subscribers {
+            return Err(Error::ResourceExhausted(format!(
+                "Event bus already has {} subscribers",
+                self.max_subscribers
+            )));
+        }
+        Ok(EventSubscriber {
+            receiver: self.sender.subscribe(),
+            kinds: kinds.to_vec(),
+            missed: 0,
+        })
+    }
+
+    /// Number of active subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+/// Publish an event on the global bus
+pub fn publish(event: GatewayEvent) -> u64 {
+    EventBus::global().publish(event)
+}
+
+/// Subscribe to the global bus
+pub fn subscribe(kinds: &[EventKind]) -> Result<EventSubscriber> {
+    EventBus::global().subscribe(kinds)
+}
+
+/// Filtered, bounded view of the bus
+pub struct EventSubscriber {
+    receiver: broadcast::Receiver<EventEnvelope>,
+    kinds: Vec<EventKind>,
+    missed: u64,
+}
+
+impl EventSubscriber {
+    /// Next matching event; `None` once the bus is gone
+    pub async fn recv(&mut self) -> Option<EventEnvelope> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(envelope) if self.matches(&envelope) => return Some(envelope),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => self.missed += skipped,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Next matching event if one is already buffered
+    pub fn try_recv(&mut self) -> Option<EventEnvelope> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(envelope) if self.matches(&envelope) => return Some(envelope),
+                Ok(_) => continue,
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => self.missed += skipped,
+                Err(_) => return None,
+            }
+        }
+    }
+
+    /// Events dropped because this subscriber fell behind
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    fn matches(&self, envelope: &EventEnvelope) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&envelope.event.kind())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribers_only_see_requested_kinds() {
+        let bus = EventBus::new(16, 4);
+        let mut models = bus.subscribe(&[EventKind::Model]).unwrap();
+        let mut everything = bus.subscribe(&[]).unwrap();
+
+        bus.publish(GatewayEvent::ComponentStarted {
+            component: "router".to_string(),
+        });
+        bus.publish(GatewayEvent::ModelLoaded {
+            model_id: "tinyllama-1.1b".to_string(),
+            memory_mb: 600,
+        });
+
+        let envelope = models.recv().await.unwrap();
+        assert_eq!(envelope.sequence, 2);
+        assert_eq!(envelope.event.kind(), EventKind::Model);
+        assert!(models.try_recv().is_none());
+        assert_eq!(everything.recv().await.unwrap().event.kind(), EventKind::Component);
+        assert_eq!(EventKind::parse("Alert"), Some(EventKind::Alert));
+    }
+
+    #[test]
+    fn test_subscriber_limit_and_lag_are_bounded() {
+        let bus = EventBus::new(2, 1);
+        let mut subscriber = bus.subscribe(&[]).unwrap();
+        assert!(bus.subscribe(&[]).is_err());
+
+        for component in ["a", "b", "c", "d"] {
+            bus.publish(GatewayEvent::ComponentStopped {
+                component: component.to_string(),
+            });
+        }
+        assert_eq!(subscriber.try_recv().unwrap().sequence, 3);
+        assert_eq!(subscriber.missed(), 2);
+    }
+}
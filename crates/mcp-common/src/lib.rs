@@ -4,22 +4,48 @@
 //! all components of the MCP Edge Gateway system.
 
 pub mod autonomous_deployment;
+pub mod bandwidth;
 pub mod autonomous_scaling;
 pub mod circuit_breaker;
+pub mod clock;
+pub mod compression;
+pub mod concurrency;
 pub mod config;
+pub mod config_docs;
+pub mod crypto;
+pub mod encoding;
 pub mod error;
+pub mod events;
 pub mod metrics;
+pub mod model_manifest;
 pub mod observability;
+pub mod redaction;
+pub mod request_id;
+pub mod request_signing;
 pub mod retry;
+pub mod scaling_trends;
 pub mod self_healing;
+pub mod shared_state;
+pub mod stage_timings;
+pub mod trace_context;
 pub mod types;
+pub mod usage;
 pub mod utils;
+pub mod vfs;
+pub mod write_policy;
 
 pub use circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState, with_circuit_breaker};
+pub use clock::{Clock, FakeClock, SystemClock};
+pub use concurrency::{ConcurrencyGauge, ConcurrencyLimiter, ConcurrencyPermit, PriorityWait, Rejection};
 pub use config::Config;
-pub use error::{Error, Result};
+pub use error::{Error, OverloadDetails, Result, TimeoutDetails, TimeoutStage};
+pub use events::{EventBus, EventKind, EventSubscriber, GatewayEvent};
 pub use retry::{RetryStrategy, RetryExecutor, retry_operation, retry_for_error};
 pub use types::*;
+pub use usage::ResourceUsage;
+pub use shared_state::{create_shared_state, SharedState};
+pub use trace_context::{Span, SpanKind, TraceContext};
+pub use vfs::{create_vfs, Vfs};
 pub use metrics::{HealthLevel, ComponentHealth, HealthStatus};
 
 #[cfg(target_arch = "wasm32")]
@@ -0,0 +1,116 @@
+//! Signed model manifests
+//!
+//! A model publisher ships each model file with a manifest of what was
+//! released: the file's SHA-256, its quantization, its license and the
+//! methods it may serve. The manifest is signed with the publisher's Ed25519
+//! key and stored next to the model file, `<model file>.manifest.json` by
+//! default. Verification against the trusted keys lives in `mcp-security`.
+
+use crate::{ModelId, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Algorithm manifests are signed with
+pub const MANIFEST_SIGNATURE_ALGORITHM: &str = "Ed25519";
+
+/// What a signed model release contains
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelManifest {
+    pub model_id: ModelId,
+    /// Hex SHA-256 of the model file
+    pub sha256: String,
+    pub quantization: String,
+    /// SPDX identifier or license name
+    pub license: String,
+    /// Methods the model may serve; any when empty
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+}
+
+impl ModelManifest {
+    /// Bytes the signature covers
+    pub fn signed_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods.is_empty() || self.allowed_methods.iter().any(|allowed| allowed == method)
+    }
+
+    /// Whether `sha256` is the hash the manifest was signed for
+    pub fn matches_hash(&self, sha256: &str) -> bool {
+        self.sha256.trim().eq_ignore_ascii_case(sha256.trim())
+    }
+
+    /// Whether `quantization` is the one the manifest was signed for
+    pub fn matches_quantization(&self, quantization: &str) -> bool {
+        self.quantization.eq_ignore_ascii_case(quantization)
+    }
+}
+
+/// Detached signature over the manifest's JSON serialization
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestSignature {
+    pub algorithm: String,
+    /// Base64 Ed25519 public key
+    pub public_key: String,
+    /// Base64 signature over [`ModelManifest::signed_bytes`]
+    pub value: String,
+}
+
+/// Manifest with its signature, as stored next to the model file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedModelManifest {
+    pub manifest: ModelManifest,
+    pub signature: ManifestSignature,
+}
+
+impl SignedModelManifest {
+    pub fn from_json(bytes: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    /// Where the signed manifest for `model_path` is stored
+    pub fn path_for(model_path: &Path, suffix: &str) -> PathBuf {
+        let mut path = model_path.as_os_str().to_owned();
+        path.push(suffix);
+        PathBuf::from(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> ModelManifest {
+        ModelManifest {
+            model_id: "tinyllama".to_string(),
+            sha256: "AB12".to_string(),
+            quantization: "q4_0".to_string(),
+            license: "Apache-2.0".to_string(),
+            allowed_methods: vec!["completion".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_manifest_checks() {
+        let manifest = manifest();
+        assert!(manifest.allows_method("completion"));
+        assert!(!manifest.allows_method("embedding"));
+        assert!(manifest.matches_hash("ab12"));
+        assert!(!manifest.matches_hash("ab13"));
+        assert!(manifest.matches_quantization("Q4_0"));
+
+        let open = ModelManifest {
+            allowed_methods: Vec::new(),
+            ..manifest
+        };
+        assert!(open.allows_method("embedding"));
+    }
+
+    #[test]
+    fn test_manifest_path_sits_next_to_the_model() {
+        let path = SignedModelManifest::path_for(Path::new("/models/tinyllama.gguf"), ".manifest.json");
+        assert_eq!(path, PathBuf::from("/models/tinyllama.gguf.manifest.json"));
+    }
+}
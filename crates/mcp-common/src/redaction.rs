@@ -0,0 +1,267 @@
+//! Redaction of payloads and identifiers for logs, traces and crash reports
+//!
+//! Everything that writes request content or device identifiers to a log
+//! line, a trace attribute or a panic report goes through the process-wide
+//! [`Redactor`] installed from the `redaction` config. It applies field
+//! allow/deny lists to JSON payloads, replaces identifier fields with a keyed
+//! hash so records about one device can still be correlated, masks e-mail
+//! addresses and long digit runs in free text, and only lets full payloads
+//! through for the configured sample of requests.
+
+use crate::config::RedactionConfig;
+use serde_json::{Map, Value};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Replacement for redacted values
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Applies the configured redaction rules
+pub struct Redactor {
+    allow: HashSet<String>,
+    deny: HashSet<String>,
+    hash: HashSet<String>,
+    salt: Option<String>,
+    /// Keys for identifier hashes when no salt is configured
+    process_keys: RandomState,
+    /// Every how many payloads one is logged in full, never when zero
+    sample_every: u64,
+    sampled: AtomicU64,
+    max_string_len: usize,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Self {
+        let fields = |names: &[String]| names.iter().map(|name| name.to_ascii_lowercase()).collect();
+        let rate = config.full_payload_sample_rate;
+        Self {
+            allow: fields(&config.allow_fields),
+            deny: fields(&config.deny_fields),
+            hash: fields(&config.hash_fields),
+            salt: config.hash_salt.clone(),
+            process_keys: RandomState::new(),
+            sample_every: if rate > 0.0 { (1.0 / rate.min(1.0)).round() as u64 } else { 0 },
+            sampled: AtomicU64::new(0),
+            max_string_len: config.max_string_len,
+        }
+    }
+
+    /// Keyed hash of an identifier, stable for the process or, with a
+    /// configured salt, across restarts of the same build
+    pub fn identifier(&self, value: &str) -> String {
+        let mut hasher = match &self.salt {
+            Some(salt) => {
+                let mut hasher = DefaultHasher::new();
+                salt.hash(&mut hasher);
+                hasher
+            },
+            None => self.process_keys.build_hasher(),
+        };
+        value.hash(&mut hasher);
+        format!("anon:{:016x}", hasher.finish())
+    }
+
+    /// Mask e-mail addresses and long digit runs in free text and cap its length
+    pub fn text(&self, text: &str) -> String {
+        let (masked, _) = mask_text(text, &[]);
+        truncate(masked, self.max_string_len)
+    }
+
+    /// Apply the field rules to a JSON value
+    pub fn fields(&self, value: &Value) -> Value {
+        self.redact_value(value, self.allow.is_empty())
+    }
+
+    /// Payload for a log line: redacted in full when sampled, otherwise only
+    /// its top-level keys and size
+    pub fn payload(&self, value: &Value) -> Value {
+        if self.sample_every > 0 && self.sampled.fetch_add(1, Ordering::Relaxed) % self.sample_every == 0 {
+            return self.fields(value);
+        }
+        let keys: Vec<&String> = value.as_object().map(|object| object.keys().collect()).unwrap_or_default();
+        serde_json::json!({
+            "redacted": true,
+            "keys": keys,
+            "bytes": serde_json::to_vec(value).map_or(0, |bytes| bytes.len()),
+        })
+    }
+
+    fn redact_value(&self, value: &Value, allowed: bool) -> Value {
+        match value {
+            Value::Object(object) => Value::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.redact_field(key, value)))
+                    .collect::<Map<_, _>>(),
+            ),
+            Value::Array(items) => Value::Array(items.iter().map(|item| self.redact_value(item, allowed)).collect()),
+            Value::Null => Value::Null,
+            _ if !allowed => Value::String(REDACTED.to_string()),
+            Value::String(text) => Value::String(self.text(text)),
+            other => other.clone(),
+        }
+    }
+
+    fn redact_field(&self, key: &str, value: &Value) -> Value {
+        let key = key.to_ascii_lowercase();
+        if self.deny.contains(&key) {
+            return Value::String(REDACTED.to_string());
+        }
+        if self.hash.contains(&key) {
+            return match value {
+                Value::Null => Value::Null,
+                Value::String(id) => Value::String(self.identifier(id)),
+                other => Value::String(self.identifier(&other.to_string())),
+            };
+        }
+        self.redact_value(value, self.allow.is_empty() || self.allow.contains(&key))
+    }
+}
+
+/// Replace e-mail addresses, long digit runs (phone, account and card
+/// numbers) and the given lowercase terms; returns the masked text and the
+/// number of replacements
+pub fn mask_text(text: &str, terms: &[String]) -> (String, usize) {
+    let mut replaced = 0;
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| {
+            let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+            let digits = bare.chars().filter(|c| c.is_ascii_digit()).count();
+            let replacement = if bare.contains('@') && bare.contains('.') {
+                Some("[EMAIL]")
+            } else if digits >= 6 {
+                Some("[NUMBER]")
+            } else if terms.contains(&bare.to_lowercase()) {
+                Some(REDACTED)
+            } else {
+                None
+            };
+            match replacement {
+                Some(replacement) => {
+                    replaced += 1;
+                    replacement.to_string()
+                },
+                None => word.to_string(),
+            }
+        })
+        .collect();
+    (words.join(" "), replaced)
+}
+
+fn truncate(mut text: String, max_len: usize) -> String {
+    if text.len() <= max_len {
+        return text;
+    }
+    let mut end = max_len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let dropped = text.len() - end;
+    text.truncate(end);
+    format!("{}... [{} bytes truncated]", text, dropped)
+}
+
+fn global_slot() -> &'static RwLock<Arc<Redactor>> {
+    static GLOBAL: OnceLock<RwLock<Arc<Redactor>>> = OnceLock::new();
+    GLOBAL.get_or_init(|| RwLock::new(Arc::new(Redactor::new(&RedactionConfig::default()))))
+}
+
+/// Replace the process-wide redactor, called when the gateway starts
+pub fn install(config: &RedactionConfig) {
+    *global_slot().write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(Redactor::new(config));
+}
+
+/// The process-wide redactor
+pub fn global() -> Arc<Redactor> {
+    global_slot().read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+/// Identifier for a log line, see [`Redactor::identifier`]
+pub fn id(value: &str) -> String {
+    global().identifier(value)
+}
+
+/// Free text for a log line, see [`Redactor::text`]
+pub fn text(text: &str) -> String {
+    global().text(text)
+}
+
+/// Payload for a log line, see [`Redactor::payload`]
+pub fn payload(value: &Value) -> Value {
+    global().payload(value)
+}
+
+/// Report panics through tracing with a redacted message instead of the
+/// default hook, which prints the raw message to stderr
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}", location.file(), location.line()))
+            .unwrap_or_default();
+        tracing::error!(target: "panic", "Panicked at {}: {}", location, text(&message));
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fields_are_denied_hashed_and_masked() {
+        let config = RedactionConfig {
+            hash_salt: Some("site-7".to_string()),
+            max_string_len: 32,
+            ..Default::default()
+        };
+        let redactor = Redactor::new(&config);
+        let payload = json!({
+            "device_id": "sensor-42",
+            "auth": { "API_KEY": "k-123" },
+            "prompt": "mail jane@example.com or call 5551234567",
+            "notes": ["x".repeat(40)],
+            "top_k": 3,
+        });
+
+        let redacted = redactor.fields(&payload);
+        assert_eq!(redacted["device_id"], json!(redactor.identifier("sensor-42")));
+        assert_eq!(redactor.identifier("sensor-42"), Redactor::new(&config).identifier("sensor-42"));
+        assert_eq!(redacted["auth"]["API_KEY"], json!(REDACTED));
+        assert_eq!(redacted["prompt"], json!("mail [EMAIL] or call [NUMBER]"));
+        assert!(redacted["notes"][0].as_str().unwrap().ends_with("[8 bytes truncated]"));
+        assert_eq!(redacted["top_k"], json!(3));
+
+        let allow_listed = Redactor::new(&RedactionConfig {
+            allow_fields: vec!["top_k".to_string()],
+            ..config
+        });
+        let redacted = allow_listed.fields(&payload);
+        assert_eq!(redacted["prompt"], json!(REDACTED));
+        assert_eq!(redacted["top_k"], json!(3));
+    }
+
+    #[test]
+    fn test_full_payloads_only_when_sampled() {
+        let payload = json!({ "prompt": "hello", "max_tokens": 16 });
+        let never = Redactor::new(&RedactionConfig::default());
+        assert_eq!(never.payload(&payload), json!({ "redacted": true, "keys": ["max_tokens", "prompt"], "bytes": 34 }));
+
+        let half = Redactor::new(&RedactionConfig {
+            full_payload_sample_rate: 0.5,
+            ..Default::default()
+        });
+        let logged: Vec<bool> = (0..4).map(|_| half.payload(&payload).get("redacted").is_none()).collect();
+        assert_eq!(logged, vec![true, false, true, false]);
+    }
+}
@@ -0,0 +1,226 @@
+//! Request ID generation
+//!
+//! IDs follow the configured [`IdScheme`]. UUIDv7 and ULID IDs begin with a
+//! millisecond timestamp, so they sort in the order they were issued: queue
+//! records scan oldest first and log lines from one time window share a
+//! prefix. Both are monotonic within the process; a second ID in the same
+//! millisecond increments the random bits of the previous one. Every scheme
+//! is carried in the same 128-bit [`RequestId`] and serialized in UUID form,
+//! which preserves the ordering.
+//!
+//! Clients may send their own ID as a UUID or as ULID text. Recently issued
+//! IDs are remembered together with their device, so an ID already held by
+//! another device is refused rather than crossing responses, dedup entries
+//! and queue records between the two.
+
+use crate::config::{IdScheme, RequestIdConfig};
+use crate::crypto::rand::{SecureRandom, SystemRandom};
+use crate::types::RequestId;
+use crate::{utils, Error, Result};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use uuid::Uuid;
+
+/// Crockford base32 alphabet used by ULIDs
+const ULID_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+struct IdState {
+    scheme: IdScheme,
+    accept_client_ids: bool,
+    window: usize,
+    /// Last time-ordered ID as the timestamp shifted above its random bits
+    last: u128,
+    owners: HashMap<RequestId, String>,
+    issued: VecDeque<RequestId>,
+}
+
+impl IdState {
+    fn next(&mut self) -> RequestId {
+        let random_bits = match self.scheme {
+            IdScheme::UuidV4 => return Uuid::new_v4(),
+            IdScheme::UuidV7 => 74,
+            IdScheme::Ulid => 80,
+        };
+        let now = u128::from(utils::current_timestamp_ms() & 0xFFFF_FFFF_FFFF);
+        // Same millisecond, or the clock stepped back: count on from the last ID
+        self.last = if now > self.last >> random_bits {
+            // Top random bit left clear so increments have room before the timestamp
+            (now << random_bits) | (random() & ((1u128 << (random_bits - 1)) - 1))
+        } else {
+            self.last + 1
+        };
+
+        match self.scheme {
+            IdScheme::UuidV7 => {
+                let millis = (self.last >> 74) & 0xFFFF_FFFF_FFFF;
+                let rand_a = (self.last >> 62) & 0xFFF;
+                let rand_b = self.last & ((1u128 << 62) - 1);
+                Uuid::from_u128((millis << 80) | (0x7 << 76) | (rand_a << 64) | (0b10 << 62) | rand_b)
+            },
+            _ => Uuid::from_u128(self.last),
+        }
+    }
+
+    fn claim(&mut self, id: RequestId, device_id: &str) -> Result<()> {
+        if self.window == 0 {
+            return Ok(());
+        }
+        if let Some(owner) = self.owners.get(&id) {
+            if owner == device_id {
+                return Ok(());
+            }
+            return Err(Error::InvalidRequest(format!("Request ID {} is already in use by another device", id)));
+        }
+        self.owners.insert(id, device_id.to_string());
+        self.issued.push_back(id);
+        while self.issued.len() > self.window {
+            if let Some(oldest) = self.issued.pop_front() {
+                self.owners.remove(&oldest);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn state() -> MutexGuard<'static, IdState> {
+    static GLOBAL: OnceLock<Mutex<IdState>> = OnceLock::new();
+    GLOBAL
+        .get_or_init(|| {
+            let defaults = RequestIdConfig::default();
+            Mutex::new(IdState {
+                scheme: defaults.scheme,
+                accept_client_ids: defaults.accept_client_ids,
+                window: defaults.collision_window,
+                last: 0,
+                owners: HashMap::new(),
+                issued: VecDeque::new(),
+            })
+        })
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Apply the request ID configuration, called when the gateway starts
+pub fn configure(config: &RequestIdConfig) {
+    let mut state = state();
+    if state.scheme != config.scheme {
+        state.last = 0;
+    }
+    state.scheme = config.scheme;
+    state.accept_client_ids = config.accept_client_ids;
+    state.window = config.collision_window;
+    while state.issued.len() > state.window {
+        if let Some(oldest) = state.issued.pop_front() {
+            state.owners.remove(&oldest);
+        }
+    }
+}
+
+/// New ID under the configured scheme
+pub fn generate() -> RequestId {
+    state().next()
+}
+
+/// Whether IDs sent by clients are used instead of generated ones
+pub fn accepts_client_ids() -> bool {
+    state().accept_client_ids
+}
+
+/// Record `id` as belonging to `device_id`, failing when another device
+/// already holds it. Claiming an ID again for the same device (a retry) is
+/// accepted.
+pub fn claim(id: RequestId, device_id: &str) -> Result<()> {
+    state().claim(id, device_id)
+}
+
+/// Parse a client-supplied ID in any UUID text form or as a ULID
+pub fn parse(text: &str) -> Option<RequestId> {
+    let text = text.trim();
+    if text.len() == 26 {
+        return parse_ulid(text);
+    }
+    Uuid::parse_str(text).ok().filter(|id| !id.is_nil())
+}
+
+fn parse_ulid(text: &str) -> Option<RequestId> {
+    let mut value: u128 = 0;
+    for (index, byte) in text.bytes().enumerate() {
+        let digit = ULID_ALPHABET.iter().position(|&c| c == byte.to_ascii_uppercase())? as u128;
+        // 26 digits hold 130 bits, so the first may only use three
+        if index == 0 && digit > 7 {
+            return None;
+        }
+        value = (value << 5) | digit;
+    }
+    Some(Uuid::from_u128(value)).filter(|id| !id.is_nil())
+}
+
+fn random() -> u128 {
+    let mut bytes = [0u8; 16];
+    match SystemRandom::new().fill(&mut bytes) {
+        Ok(()) => u128::from_be_bytes(bytes),
+        Err(_) => Uuid::new_v4().as_u128(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with(scheme: IdScheme) -> IdState {
+        IdState {
+            scheme,
+            accept_client_ids: true,
+            window: 2,
+            last: 0,
+            owners: HashMap::new(),
+            issued: VecDeque::new(),
+        }
+    }
+
+    #[test]
+    fn test_time_ordered_ids_are_monotonic() {
+        for scheme in [IdScheme::UuidV7, IdScheme::Ulid] {
+            let mut state = state_with(scheme);
+            let ids: Vec<RequestId> = (0..1000).map(|_| state.next()).collect();
+            assert!(ids.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", scheme);
+            // Ordering survives the UUID text form used on the wire
+            assert!(ids.windows(2).all(|pair| pair[0].to_string() < pair[1].to_string()));
+        }
+
+        let mut state = state_with(IdScheme::UuidV7);
+        let id = state.next();
+        assert_eq!(id.get_version_num(), 7);
+        assert_eq!(id.get_variant(), uuid::Variant::RFC4122);
+        let millis = (id.as_u128() >> 80) as u64;
+        assert!(utils::current_timestamp_ms().abs_diff(millis) < 1000);
+
+        // A clock stepping back does not reorder IDs
+        state.last += 1u128 << 74;
+        let ahead = state.next();
+        assert!(state.next() > ahead);
+    }
+
+    #[test]
+    fn test_parses_client_ids_and_detects_collisions() {
+        let uuid = Uuid::parse_str("0190163d-8694-739b-aea5-966c26f8ad91").unwrap();
+        assert_eq!(parse("0190163d-8694-739b-aea5-966c26f8ad91"), Some(uuid));
+        assert_eq!(parse("0190163d8694739baea5966c26f8ad91"), Some(uuid));
+        let ulid = parse("01ARZ3NDEKTSV4RRFFQ69G5FAV").unwrap();
+        assert_eq!(parse("01arz3ndektsv4rrffq69g5fav"), Some(ulid));
+        assert_eq!(ulid.as_u128() >> 80, 1_469_922_850_259);
+        for invalid in ["", "not-an-id", "81ARZ3NDEKTSV4RRFFQ69G5FAV", "01ARZ3NDEKTSV4RRFFQ69G5FAU", "00000000-0000-0000-0000-000000000000"] {
+            assert!(parse(invalid).is_none(), "{}", invalid);
+        }
+
+        let mut state = state_with(IdScheme::UuidV7);
+        let (first, second, third) = (state.next(), state.next(), state.next());
+        state.claim(first, "sensor-1").unwrap();
+        state.claim(first, "sensor-1").unwrap();
+        assert!(state.claim(first, "sensor-2").is_err());
+        state.claim(second, "sensor-2").unwrap();
+        state.claim(third, "sensor-2").unwrap();
+        // The oldest claim has left the window
+        state.claim(first, "sensor-2").unwrap();
+    }
+}
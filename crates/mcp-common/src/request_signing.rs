@@ -0,0 +1,75 @@
+//! Signatures on requests the gateway sends to the cloud
+//!
+//! When the security manager installs a device key, cloud forwards and
+//! offline queue replays carry the gateway's signature over
+//! `{timestamp}.{body}` — the scheme enrolled devices use towards the
+//! gateway — so the cloud can verify a request came from this gateway and
+//! not from anything else holding its API key. Signatures are ECDSA P-256
+//! with SHA-256 in fixed-length `r || s` form, base64-encoded.
+
+use crate::Result;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::sync::{Arc, OnceLock, RwLock};
+use tracing::warn;
+
+/// Header carrying the base64 signature over `{timestamp}.{body}`
+pub const GATEWAY_SIGNATURE_HEADER: &str = "X-MCP-Gateway-Signature";
+
+/// Header carrying the unix timestamp included in the signature
+pub const GATEWAY_TIMESTAMP_HEADER: &str = "X-MCP-Gateway-Timestamp";
+
+/// Header identifying the key that made the signature
+pub const GATEWAY_KEY_HEADER: &str = "X-MCP-Gateway-Key";
+
+/// Key outbound requests are signed with
+pub trait RequestSigner: Send + Sync {
+    /// Identifier of the key, sent so the cloud can look up its public half
+    fn key_id(&self) -> String;
+
+    /// Sign a message; may block while a hardware key does the work
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>>;
+}
+
+fn signer() -> &'static RwLock<Option<Arc<dyn RequestSigner>>> {
+    static SIGNER: OnceLock<RwLock<Option<Arc<dyn RequestSigner>>>> = OnceLock::new();
+    SIGNER.get_or_init(|| RwLock::new(None))
+}
+
+/// Sign outbound requests with `key`, or stop signing them
+pub fn install(key: Option<Arc<dyn RequestSigner>>) {
+    *signer().write().unwrap_or_else(|e| e.into_inner()) = key;
+}
+
+/// Whether outbound requests are being signed
+pub fn enabled() -> bool {
+    signer().read().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// Headers to add to an outbound request with this body; none when no key is
+/// installed or signing failed, which is logged but does not stop the request
+pub async fn signature_headers(body: &[u8]) -> Vec<(&'static str, String)> {
+    let Some(key) = signer().read().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return Vec::new();
+    };
+    let timestamp = chrono::Utc::now().timestamp();
+    let mut message = format!("{}.", timestamp).into_bytes();
+    message.extend_from_slice(body);
+
+    let signing = tokio::task::spawn_blocking(move || key.sign(&message).map(|signature| (key.key_id(), signature)));
+    match signing.await {
+        Ok(Ok((key_id, signature))) => vec![
+            (GATEWAY_SIGNATURE_HEADER, BASE64.encode(signature)),
+            (GATEWAY_TIMESTAMP_HEADER, timestamp.to_string()),
+            (GATEWAY_KEY_HEADER, key_id),
+        ],
+        Ok(Err(e)) => {
+            warn!("Could not sign outbound request: {}", e);
+            Vec::new()
+        },
+        Err(e) => {
+            warn!("Request signing task failed: {}", e);
+            Vec::new()
+        },
+    }
+}
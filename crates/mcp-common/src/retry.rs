@@ -103,7 +103,7 @@ impl RetryStrategy {
                     attempt_timeout: Some(Duration::from_secs(45)),
                 },
             },
-            Error::ResourceExhausted(_) => Self::ExponentialBackoff {
+            Error::ResourceExhausted(_) | Error::Overloaded(_) => Self::ExponentialBackoff {
                 config: RetryConfig {
                     max_attempts: 5,
                     base_delay: Duration::from_millis(5000),
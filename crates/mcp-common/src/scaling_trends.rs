@@ -0,0 +1,375 @@
+//! Trend features for scaling decisions
+//!
+//! [`MetricsWindow`] keeps the [`ResourceMetrics`] samples of a rolling
+//! window. Each sample is diffed against the one before it into a
+//! [`MetricsDelta`] of changes and per-second rates, and the window as a whole
+//! is summarized as [`TrendFeatures`]: latest value, mean, extremes and
+//! least-squares slope of every [`ScalingMetric`]. Scaling decisions read
+//! these instead of a single reading, so they follow sustained load and where
+//! it is heading rather than one spike or dip.
+
+use crate::autonomous_scaling::{EdgeMetrics, ResourceMetrics};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+
+/// A metric scaling decisions are based on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScalingMetric {
+    CpuUtilization,
+    MemoryUtilization,
+    DiskUtilization,
+    NetworkUtilization,
+    RequestRate,
+    ResponseTimeP95Ms,
+    ErrorRate,
+    QueueDepth,
+    ActiveConnections,
+}
+
+impl ScalingMetric {
+    pub const ALL: [ScalingMetric; 9] = [
+        ScalingMetric::CpuUtilization,
+        ScalingMetric::MemoryUtilization,
+        ScalingMetric::DiskUtilization,
+        ScalingMetric::NetworkUtilization,
+        ScalingMetric::RequestRate,
+        ScalingMetric::ResponseTimeP95Ms,
+        ScalingMetric::ErrorRate,
+        ScalingMetric::QueueDepth,
+        ScalingMetric::ActiveConnections,
+    ];
+
+    /// Name of the metric in collector readings
+    pub fn name(self) -> &'static str {
+        match self {
+            ScalingMetric::CpuUtilization => "cpu_utilization",
+            ScalingMetric::MemoryUtilization => "memory_utilization",
+            ScalingMetric::DiskUtilization => "disk_utilization",
+            ScalingMetric::NetworkUtilization => "network_utilization",
+            ScalingMetric::RequestRate => "request_rate",
+            ScalingMetric::ResponseTimeP95Ms => "response_time_p95_ms",
+            ScalingMetric::ErrorRate => "error_rate",
+            ScalingMetric::QueueDepth => "queue_depth",
+            ScalingMetric::ActiveConnections => "active_connections",
+        }
+    }
+
+    /// The metric's value in `metrics`
+    pub fn value(self, metrics: &ResourceMetrics) -> f64 {
+        match self {
+            ScalingMetric::CpuUtilization => metrics.cpu_utilization,
+            ScalingMetric::MemoryUtilization => metrics.memory_utilization,
+            ScalingMetric::DiskUtilization => metrics.disk_utilization,
+            ScalingMetric::NetworkUtilization => metrics.network_utilization,
+            ScalingMetric::RequestRate => metrics.request_rate,
+            ScalingMetric::ResponseTimeP95Ms => metrics.response_time_p95.as_secs_f64() * 1000.0,
+            ScalingMetric::ErrorRate => metrics.error_rate,
+            ScalingMetric::QueueDepth => metrics.queue_depth as f64,
+            ScalingMetric::ActiveConnections => metrics.active_connections as f64,
+        }
+    }
+}
+
+impl ResourceMetrics {
+    /// Metrics from collector readings keyed by [`ScalingMetric::name`] and
+    /// the [`EdgeMetrics`] field names; missing readings are zero
+    pub fn from_readings(readings: &HashMap<String, f64>) -> Self {
+        let reading = |metric: ScalingMetric| readings.get(metric.name()).copied().unwrap_or_default();
+        let edge = |name: &str| readings.get(name).copied();
+        Self {
+            cpu_utilization: reading(ScalingMetric::CpuUtilization),
+            memory_utilization: reading(ScalingMetric::MemoryUtilization),
+            disk_utilization: reading(ScalingMetric::DiskUtilization),
+            network_utilization: reading(ScalingMetric::NetworkUtilization),
+            request_rate: reading(ScalingMetric::RequestRate),
+            response_time_p95: Duration::from_secs_f64(reading(ScalingMetric::ResponseTimeP95Ms).max(0.0) / 1000.0),
+            error_rate: reading(ScalingMetric::ErrorRate),
+            queue_depth: reading(ScalingMetric::QueueDepth).max(0.0) as u32,
+            active_connections: reading(ScalingMetric::ActiveConnections).max(0.0) as u32,
+            edge_specific: EdgeMetrics {
+                battery_level: edge("battery_level"),
+                temperature_celsius: edge("temperature_celsius"),
+                connectivity_strength: edge("connectivity_strength").unwrap_or_default(),
+                bandwidth_availability: edge("bandwidth_availability").unwrap_or_default(),
+                latency_to_cloud: Duration::from_secs_f64(
+                    edge("latency_to_cloud_ms").unwrap_or_default().max(0.0) / 1000.0,
+                ),
+                local_processing_ratio: edge("local_processing_ratio").unwrap_or_default(),
+            },
+        }
+    }
+}
+
+/// Change of a metric between two consecutive samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricChange {
+    pub delta: f64,
+    pub per_sec: f64,
+}
+
+/// Difference between two consecutive samples
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsDelta {
+    pub elapsed: Duration,
+    pub changes: BTreeMap<ScalingMetric, MetricChange>,
+}
+
+impl MetricsDelta {
+    /// Changes from `previous` to `current`; `None` unless `current` is later
+    pub fn between(
+        (previous_at, previous): (DateTime<Utc>, &ResourceMetrics),
+        (current_at, current): (DateTime<Utc>, &ResourceMetrics),
+    ) -> Option<Self> {
+        let elapsed = (current_at - previous_at).to_std().ok().filter(|elapsed| !elapsed.is_zero())?;
+        let changes = ScalingMetric::ALL
+            .into_iter()
+            .map(|metric| {
+                let delta = metric.value(current) - metric.value(previous);
+                (
+                    metric,
+                    MetricChange {
+                        delta,
+                        per_sec: delta / elapsed.as_secs_f64(),
+                    },
+                )
+            })
+            .collect();
+        Some(Self { elapsed, changes })
+    }
+
+    pub fn change(&self, metric: ScalingMetric) -> MetricChange {
+        self.changes.get(&metric).copied().unwrap_or_default()
+    }
+}
+
+/// How a metric moved over the window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricTrend {
+    pub latest: f64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Least-squares fit of the samples, in units per second
+    pub slope_per_sec: f64,
+}
+
+/// Summary of the samples in the window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrendFeatures {
+    pub samples: usize,
+    /// Time between the oldest and the latest sample
+    pub span: Duration,
+    pub trends: BTreeMap<ScalingMetric, MetricTrend>,
+}
+
+impl TrendFeatures {
+    pub fn trend(&self, metric: ScalingMetric) -> MetricTrend {
+        self.trends.get(&metric).copied().unwrap_or_default()
+    }
+
+    /// Where `metric` will be `ahead` of the latest sample if its trend holds
+    pub fn projected(&self, metric: ScalingMetric, ahead: Duration) -> f64 {
+        let trend = self.trend(metric);
+        trend.latest + trend.slope_per_sec * ahead.as_secs_f64()
+    }
+}
+
+/// Rolling window of metrics samples
+#[derive(Debug)]
+pub struct MetricsWindow {
+    window: Duration,
+    /// Oldest first
+    samples: VecDeque<(DateTime<Utc>, ResourceMetrics)>,
+    last_delta: Option<MetricsDelta>,
+}
+
+impl MetricsWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+            last_delta: None,
+        }
+    }
+
+    /// Add a sample taken at `at` and return its change since the previous
+    /// one. Samples older than the window are dropped, though the previous
+    /// sample is always kept so there is something to diff against; samples
+    /// not newer than the latest are ignored.
+    pub fn record(&mut self, at: DateTime<Utc>, metrics: ResourceMetrics) -> Option<MetricsDelta> {
+        if let Some((latest_at, latest)) = self.samples.back() {
+            let delta = MetricsDelta::between((*latest_at, latest), (at, &metrics))?;
+            self.last_delta = Some(delta);
+        }
+        self.samples.push_back((at, metrics));
+
+        if let Some(cutoff) = chrono::Duration::from_std(self.window)
+            .ok()
+            .and_then(|window| at.checked_sub_signed(window))
+        {
+            while self.samples.len() > 2 && self.samples.front().is_some_and(|(sampled_at, _)| *sampled_at < cutoff) {
+                self.samples.pop_front();
+            }
+        }
+        self.last_delta.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The latest sample
+    pub fn latest(&self) -> Option<&ResourceMetrics> {
+        self.samples.back().map(|(_, metrics)| metrics)
+    }
+
+    /// Change between the two latest samples
+    pub fn latest_delta(&self) -> Option<&MetricsDelta> {
+        self.last_delta.as_ref()
+    }
+
+    /// Trends over the window, once it holds two samples
+    pub fn features(&self) -> Option<TrendFeatures> {
+        let (first_at, _) = self.samples.front()?;
+        let (latest_at, _) = self.samples.back()?;
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let times: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|(at, _)| (*at - *first_at).num_milliseconds() as f64 / 1000.0)
+            .collect();
+        let trends = ScalingMetric::ALL
+            .into_iter()
+            .map(|metric| {
+                let values: Vec<f64> = self.samples.iter().map(|(_, metrics)| metric.value(metrics)).collect();
+                (metric, trend(&times, &values))
+            })
+            .collect();
+        Some(TrendFeatures {
+            samples: self.samples.len(),
+            span: (*latest_at - *first_at).to_std().unwrap_or_default(),
+            trends,
+        })
+    }
+}
+
+fn trend(times: &[f64], values: &[f64]) -> MetricTrend {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let mean_time = times.iter().sum::<f64>() / n;
+    let (covariance, variance) = times
+        .iter()
+        .zip(values)
+        .fold((0.0, 0.0), |(covariance, variance), (time, value)| {
+            let dt = time - mean_time;
+            (covariance + dt * (value - mean), variance + dt * dt)
+        });
+    MetricTrend {
+        latest: values.last().copied().unwrap_or_default(),
+        mean,
+        min: values.iter().copied().fold(f64::INFINITY, f64::min),
+        max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        slope_per_sec: if variance > 0.0 { covariance / variance } else { 0.0 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autonomous_scaling::{AutonomousScalingOrchestrator, MetricsCollector, ScalingConfiguration, ScalingStrategy};
+    use crate::clock::{Clock, FakeClock};
+    use crate::config::ScalingConfig;
+    use std::sync::{Arc, Mutex};
+
+    /// Collector returning one CPU reading per collection
+    struct CpuReadings(Mutex<VecDeque<f64>>);
+
+    impl MetricsCollector for CpuReadings {
+        fn collect(&self) -> crate::Result<HashMap<String, f64>> {
+            let cpu = self.0.lock().unwrap().pop_front().unwrap_or_default();
+            Ok(HashMap::from([("cpu_utilization".to_string(), cpu)]))
+        }
+    }
+
+    fn sample(cpu: f64, queue_depth: u32) -> ResourceMetrics {
+        ResourceMetrics::from_readings(&HashMap::from([
+            ("cpu_utilization".to_string(), cpu),
+            ("queue_depth".to_string(), queue_depth as f64),
+            ("response_time_p95_ms".to_string(), 120.0),
+        ]))
+    }
+
+    #[test]
+    fn test_consecutive_samples_are_diffed_into_rates() {
+        let clock = FakeClock::default();
+        let mut window = MetricsWindow::new(Duration::from_secs(600));
+        assert!(window.record(clock.now(), sample(40.0, 10)).is_none());
+        assert!(window.features().is_none());
+
+        clock.advance(chrono::Duration::seconds(10));
+        let delta = window.record(clock.now(), sample(50.0, 30)).unwrap();
+        assert_eq!(delta.elapsed, Duration::from_secs(10));
+        assert_eq!(delta.change(ScalingMetric::CpuUtilization), MetricChange { delta: 10.0, per_sec: 1.0 });
+        assert_eq!(delta.change(ScalingMetric::QueueDepth).per_sec, 2.0);
+        assert_eq!(delta.change(ScalingMetric::ResponseTimeP95Ms).delta, 0.0);
+
+        // A sample from the past is not diffed
+        assert!(window.record(clock.now() - chrono::Duration::seconds(5), sample(0.0, 0)).is_none());
+        assert_eq!(window.len(), 2);
+    }
+
+    #[test]
+    fn test_window_trends_follow_sustained_change() {
+        let clock = FakeClock::default();
+        let mut window = MetricsWindow::new(Duration::from_secs(60));
+        for cpu in [95.0, 20.0, 30.0, 40.0, 50.0] {
+            window.record(clock.now(), sample(cpu, 0));
+            clock.advance(chrono::Duration::seconds(30));
+        }
+
+        // The spike 120 seconds ago fell out of the window
+        let features = window.features().unwrap();
+        assert_eq!((features.samples, features.span), (3, Duration::from_secs(60)));
+        let cpu = features.trend(ScalingMetric::CpuUtilization);
+        assert_eq!((cpu.latest, cpu.mean, cpu.min, cpu.max), (50.0, 40.0, 30.0, 50.0));
+        assert!((cpu.slope_per_sec - 1.0 / 3.0).abs() < 1e-9);
+        assert!((features.projected(ScalingMetric::CpuUtilization, Duration::from_secs(90)) - 80.0).abs() < 1e-9);
+        assert_eq!(features.trend(ScalingMetric::QueueDepth).slope_per_sec, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_orchestrator_decides_on_trends_not_single_readings() {
+        let clock = Arc::new(FakeClock::default());
+        let config = ScalingConfiguration::from(&ScalingConfig::default());
+        let orchestrator = AutonomousScalingOrchestrator::with_clock(config, clock.clone());
+        assert!(orchestrator.evaluate_scaling_need().await.is_err());
+
+        // 70% is below the threshold, but at this rate it passes it before
+        // the next decision
+        orchestrator.add_metrics_collector(Arc::new(CpuReadings(Mutex::new(VecDeque::from([40.0, 70.0])))));
+        assert!(orchestrator.evaluate_scaling_need().await.unwrap().is_none());
+        clock.advance(chrono::Duration::seconds(30));
+        let decision = orchestrator.evaluate_scaling_need().await.unwrap().unwrap();
+        assert_eq!(decision.strategy, ScalingStrategy::Horizontal);
+        assert_eq!(decision.target_configuration.instance_count, 3);
+
+        // A single quiet reading after a busy window does not scale in
+        let orchestrator = AutonomousScalingOrchestrator::with_clock(
+            ScalingConfiguration::from(&ScalingConfig::default()),
+            clock.clone(),
+        );
+        orchestrator.add_metrics_collector(Arc::new(CpuReadings(Mutex::new(VecDeque::from([60.0, 10.0])))));
+        orchestrator.evaluate_scaling_need().await.unwrap();
+        clock.advance(chrono::Duration::seconds(30));
+        assert!(orchestrator.evaluate_scaling_need().await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,435 @@
+//! Key-value state shared between clustered gateways
+//!
+//! Gateways behind one load balancer keep the response cache, the request
+//! dedup window and rate-limit counters in a [`SharedState`]. Without Redis
+//! configured every gateway keeps its own [`LocalState`]. With Redis, state
+//! lives in Redis and each gateway falls back to local state whenever Redis is
+//! unreachable or slow, retrying Redis after a back-off, so a Redis outage
+//! degrades the cluster to per-process limits instead of failing requests.
+
+use crate::config::ClusterConfig;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Entries kept by [`LocalState`] before expired ones are purged
+const LOCAL_PURGE_THRESHOLD: usize = 10_000;
+
+/// Expiring key-value store shared by gateway instances
+///
+/// Operations never fail; implementations degrade to local state instead.
+#[async_trait]
+pub trait SharedState: Send + Sync {
+    /// Backend name for logs and health reporting
+    fn name(&self) -> &'static str;
+
+    /// Whether state is actually shared with other gateways right now
+    fn is_shared(&self) -> bool;
+
+    /// Value stored under `key`, if present and not expired
+    async fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Store `value` under `key` for `ttl`
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration);
+
+    /// Store `value` only if `key` is absent; returns whether it was stored
+    async fn set_if_absent(&self, key: &str, value: &[u8], ttl: Duration) -> bool;
+
+    /// Increment a counter that expires `ttl` after its first increment,
+    /// returning the new count
+    async fn increment(&self, key: &str, ttl: Duration) -> u64;
+}
+
+/// Create the shared state selected by the cluster configuration
+pub fn create_shared_state(config: &ClusterConfig) -> Arc<dyn SharedState> {
+    match &config.redis {
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(redis) => Arc::new(redis::RedisState::new(redis.clone())),
+        _ => Arc::new(LocalState::new()),
+    }
+}
+
+/// Process-local state
+#[derive(Default)]
+pub struct LocalState {
+    entries: Mutex<HashMap<String, (Vec<u8>, Instant)>>,
+}
+
+impl LocalState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_live_entry<T>(&self, key: &str, f: impl FnOnce(Option<&mut Vec<u8>>) -> T) -> T {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if entries.get(key).is_some_and(|(_, expires)| *expires <= now) {
+            entries.remove(key);
+        }
+        f(entries.get_mut(key).map(|(value, _)| value))
+    }
+
+    fn insert(&self, key: &str, value: Vec<u8>, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if entries.len() >= LOCAL_PURGE_THRESHOLD {
+            entries.retain(|_, (_, expires)| *expires > now);
+        }
+        entries.insert(key.to_string(), (value, now + ttl));
+    }
+}
+
+#[async_trait]
+impl SharedState for LocalState {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn is_shared(&self) -> bool {
+        false
+    }
+
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.with_live_entry(key, |value| value.cloned())
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Duration) {
+        self.insert(key, value.to_vec(), ttl);
+    }
+
+    async fn set_if_absent(&self, key: &str, value: &[u8], ttl: Duration) -> bool {
+        if self.with_live_entry(key, |existing| existing.is_some()) {
+            return false;
+        }
+        self.insert(key, value.to_vec(), ttl);
+        true
+    }
+
+    async fn increment(&self, key: &str, ttl: Duration) -> u64 {
+        let incremented = self.with_live_entry(key, |existing| {
+            existing.map(|value| {
+                let count = parse_count(value) + 1;
+                *value = count.to_string().into_bytes();
+                count
+            })
+        });
+        match incremented {
+            Some(count) => count,
+            None => {
+                self.insert(key, b"1".to_vec(), ttl);
+                1
+            },
+        }
+    }
+}
+
+fn parse_count(value: &[u8]) -> u64 {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod redis {
+    use super::{LocalState, SharedState};
+    use crate::config::RedisConfig;
+    use crate::{Error, Result};
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+    use tracing::{info, warn};
+
+    /// Reply to a Redis command; arrays are never requested
+    #[derive(Debug, PartialEq)]
+    enum Reply {
+        Status(String),
+        Integer(i64),
+        Bulk(Option<Vec<u8>>),
+    }
+
+    /// Redis over RESP with local fallback
+    pub struct RedisState {
+        config: RedisConfig,
+        address: String,
+        username: Option<String>,
+        password: Option<String>,
+        database: Option<u32>,
+        connection: tokio::sync::Mutex<Option<BufReader<TcpStream>>>,
+        /// Set while Redis is considered down
+        down_until: Mutex<Option<Instant>>,
+        local: LocalState,
+    }
+
+    impl RedisState {
+        pub fn new(config: RedisConfig) -> Self {
+            let url = config.url.trim_start_matches("redis://");
+            let (credentials, location) = match url.rsplit_once('@') {
+                Some((credentials, location)) => (Some(credentials), location),
+                None => (None, url),
+            };
+            let (username, password) = match credentials.map(|c| c.split_once(':')) {
+                Some(Some((username, password))) => {
+                    ((!username.is_empty()).then(|| username.to_string()), Some(password.to_string()))
+                },
+                Some(None) => (None, credentials.map(str::to_string)),
+                None => (None, None),
+            };
+            let (host, database) = match location.split_once('/') {
+                Some((host, database)) => (host, database.parse().ok()),
+                None => (location, None),
+            };
+            let address = if host.contains(':') {
+                host.to_string()
+            } else {
+                format!("{}:6379", host)
+            };
+
+            Self {
+                config,
+                address,
+                username,
+                password,
+                database,
+                connection: tokio::sync::Mutex::new(None),
+                down_until: Mutex::new(None),
+                local: LocalState::new(),
+            }
+        }
+
+        fn key(&self, key: &str) -> String {
+            format!("{}:{}", self.config.key_prefix, key)
+        }
+
+        fn is_down(&self) -> bool {
+            let mut down_until = self.down_until.lock().unwrap_or_else(|e| e.into_inner());
+            match *down_until {
+                Some(until) if Instant::now() < until => true,
+                Some(_) => {
+                    *down_until = None;
+                    false
+                },
+                None => false,
+            }
+        }
+
+        /// Run a command, marking Redis down on any transport failure
+        async fn command(&self, args: &[&[u8]]) -> Result<Reply> {
+            if self.is_down() {
+                return Err(Error::Network("Redis marked unavailable".to_string()));
+            }
+            let timeout = Duration::from_millis(self.config.timeout_ms);
+            let mut connection = self.connection.lock().await;
+            let result = tokio::time::timeout(timeout, async {
+                if connection.is_none() {
+                    *connection = Some(self.connect().await?);
+                    info!("Connected to Redis at {}", self.address);
+                }
+                let stream = connection.as_mut().expect("connection just established");
+                send(stream, args).await
+            })
+            .await
+            .unwrap_or_else(|_| Err(Error::Network(format!("Redis command timed out after {:?}", timeout))));
+
+            if let Err(e) = &result {
+                *connection = None;
+                let retry = Duration::from_secs(self.config.retry_secs);
+                *self.down_until.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + retry);
+                warn!("Redis at {} unavailable, using local state for {:?}: {}", self.address, retry, e);
+            }
+            result
+        }
+
+        async fn connect(&self) -> Result<BufReader<TcpStream>> {
+            let stream = TcpStream::connect(&self.address)
+                .await
+                .map_err(|e| Error::Network(format!("Failed to connect to Redis at {}: {}", self.address, e)))?;
+            let mut stream = BufReader::new(stream);
+            if let Some(password) = &self.password {
+                let mut auth: Vec<&[u8]> = vec![b"AUTH"];
+                if let Some(username) = &self.username {
+                    auth.push(username.as_bytes());
+                }
+                auth.push(password.as_bytes());
+                send(&mut stream, &auth).await?;
+            }
+            if let Some(database) = self.database {
+                send(&mut stream, &[b"SELECT", database.to_string().as_bytes()]).await?;
+            }
+            Ok(stream)
+        }
+    }
+
+    #[async_trait]
+    impl SharedState for RedisState {
+        fn name(&self) -> &'static str {
+            "redis"
+        }
+
+        fn is_shared(&self) -> bool {
+            !self.is_down()
+        }
+
+        async fn get(&self, key: &str) -> Option<Vec<u8>> {
+            match self.command(&[b"GET", self.key(key).as_bytes()]).await {
+                Ok(Reply::Bulk(value)) => value,
+                Ok(_) => None,
+                Err(_) => self.local.get(key).await,
+            }
+        }
+
+        async fn set(&self, key: &str, value: &[u8], ttl: Duration) {
+            let (full_key, ttl_ms) = (self.key(key), ttl.as_millis().max(1).to_string());
+            let args: [&[u8]; 5] = [b"SET", full_key.as_bytes(), value, b"PX", ttl_ms.as_bytes()];
+            if self.command(&args).await.is_err() {
+                self.local.set(key, value, ttl).await;
+            }
+        }
+
+        async fn set_if_absent(&self, key: &str, value: &[u8], ttl: Duration) -> bool {
+            let (full_key, ttl_ms) = (self.key(key), ttl.as_millis().max(1).to_string());
+            let args: [&[u8]; 6] = [b"SET", full_key.as_bytes(), value, b"PX", ttl_ms.as_bytes(), b"NX"];
+            match self.command(&args).await {
+                Ok(reply) => reply == Reply::Status("OK".to_string()),
+                Err(_) => self.local.set_if_absent(key, value, ttl).await,
+            }
+        }
+
+        async fn increment(&self, key: &str, ttl: Duration) -> u64 {
+            let full_key = self.key(key);
+            let count = match self.command(&[b"INCR", full_key.as_bytes()]).await {
+                Ok(Reply::Integer(count)) => count.max(0) as u64,
+                _ => return self.local.increment(key, ttl).await,
+            };
+            if count == 1 {
+                let ttl_ms = ttl.as_millis().max(1).to_string();
+                let _ = self.command(&[b"PEXPIRE", full_key.as_bytes(), ttl_ms.as_bytes()]).await;
+            }
+            count
+        }
+    }
+
+    /// Write a command as a RESP array and read its reply
+    async fn send(stream: &mut BufReader<TcpStream>, args: &[&[u8]]) -> Result<Reply> {
+        let mut frame = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            frame.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            frame.extend_from_slice(arg);
+            frame.extend_from_slice(b"\r\n");
+        }
+        let io = |e: std::io::Error| Error::Network(format!("Redis connection failed: {}", e));
+        stream.get_mut().write_all(&frame).await.map_err(io)?;
+
+        let mut line = Vec::new();
+        stream.read_until(b'\n', &mut line).await.map_err(io)?;
+        if line.len() < 3 || !line.ends_with(b"\r\n") {
+            return Err(Error::Network("Redis closed the connection".to_string()));
+        }
+        let body = String::from_utf8_lossy(&line[1..line.len() - 2]).to_string();
+        match line[0] {
+            b'+' => Ok(Reply::Status(body)),
+            b'-' => Err(Error::Network(format!("Redis error: {}", body))),
+            b':' => body
+                .parse()
+                .map(Reply::Integer)
+                .map_err(|_| Error::Network(format!("Invalid Redis integer: {}", body))),
+            b'$' => {
+                let len: i64 = body
+                    .parse()
+                    .map_err(|_| Error::Network(format!("Invalid Redis bulk length: {}", body)))?;
+                if len < 0 {
+                    return Ok(Reply::Bulk(None));
+                }
+                let mut value = vec![0; len as usize + 2];
+                stream.read_exact(&mut value).await.map_err(io)?;
+                value.truncate(len as usize);
+                Ok(Reply::Bulk(Some(value)))
+            },
+            other => Err(Error::Network(format!("Unexpected Redis reply type {:?}", other as char))),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tokio::net::TcpListener;
+
+        #[tokio::test]
+        async fn test_redis_commands_and_fallback() {
+            // Minimal server answering SET NX, GET and INCR
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let server = tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+                let mut received = Vec::new();
+                for reply in [&b"+OK\r\n"[..], b"$2\r\nv1\r\n", b":1\r\n", b"+OK\r\n"] {
+                    let mut line = Vec::new();
+                    stream.read_until(b'\n', &mut line).await.unwrap();
+                    let argc: usize = std::str::from_utf8(&line[1..line.len() - 2]).unwrap().parse().unwrap();
+                    let mut args = Vec::new();
+                    for _ in 0..argc {
+                        line.clear();
+                        stream.read_until(b'\n', &mut line).await.unwrap();
+                        let len: usize = std::str::from_utf8(&line[1..line.len() - 2]).unwrap().parse().unwrap();
+                        let mut arg = vec![0; len + 2];
+                        stream.read_exact(&mut arg).await.unwrap();
+                        arg.truncate(len);
+                        args.push(String::from_utf8(arg).unwrap());
+                    }
+                    received.push(args.join(" "));
+                    stream.get_mut().write_all(reply).await.unwrap();
+                }
+                received
+            });
+
+            let state = RedisState::new(RedisConfig {
+                url: format!("redis://{}", address),
+                ..RedisConfig::default()
+            });
+            assert!(state.set_if_absent("dedup:1", b"v1", Duration::from_secs(2)).await);
+            assert_eq!(state.get("dedup:1").await, Some(b"v1".to_vec()));
+            assert_eq!(state.increment("rate:a", Duration::from_secs(60)).await, 1);
+            assert_eq!(
+                server.await.unwrap(),
+                vec![
+                    "SET mcp:dedup:1 v1 PX 2000 NX",
+                    "GET mcp:dedup:1",
+                    "INCR mcp:rate:a",
+                    "PEXPIRE mcp:rate:a 60000",
+                ]
+            );
+
+            // The server is gone, so state falls back to this process
+            assert!(state.set_if_absent("dedup:2", b"v2", Duration::from_secs(2)).await);
+            assert!(!state.is_shared());
+            assert!(!state.set_if_absent("dedup:2", b"v2", Duration::from_secs(2)).await);
+            assert_eq!(state.increment("rate:a", Duration::from_secs(60)).await, 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_state_expiry_and_counters() {
+        let state = LocalState::new();
+        assert!(state.set_if_absent("dedup:1", b"", Duration::from_secs(60)).await);
+        assert!(!state.set_if_absent("dedup:1", b"", Duration::from_secs(60)).await);
+
+        state.set("cache:a", b"cached", Duration::ZERO).await;
+        assert_eq!(state.get("cache:a").await, None);
+        assert!(state.set_if_absent("cache:a", b"fresh", Duration::from_secs(60)).await);
+        assert_eq!(state.get("cache:a").await, Some(b"fresh".to_vec()));
+
+        assert_eq!(state.increment("rate:a", Duration::from_secs(60)).await, 1);
+        assert_eq!(state.increment("rate:a", Duration::from_secs(60)).await, 2);
+        assert_eq!(state.increment("rate:b", Duration::ZERO).await, 1);
+        assert_eq!(state.increment("rate:b", Duration::ZERO).await, 1);
+    }
+}
@@ -0,0 +1,156 @@
+//! Per-request latency breakdown
+//!
+//! The gateway times the stages it runs itself: validation, routing and
+//! post-processing. Stages run deeper down are reported with [`record`]: the
+//! model engine reports time spent waiting for an inference slot or memory
+//! and time spent on inference, and model loaders split inference into
+//! prompt prefill and token decode. Reports land in the enclosing
+//! [`measure`] scope, so the breakdown follows the request across tasks
+//! polled within it.
+
+use crate::trace_context::Span;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Response result field the breakdown is attached to
+pub const STAGE_TIMINGS_FIELD: &str = "stage_timings";
+
+/// A stage of request processing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Validation,
+    /// Waiting for an inference slot, memory or another queue
+    Queueing,
+    Routing,
+    /// Local inference or cloud forwarding, including prefill and decode
+    Inference,
+    Prefill,
+    Decode,
+    PostProcessing,
+}
+
+/// Time spent per stage of a request, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageTimings {
+    pub validation_ms: f64,
+    pub queueing_ms: f64,
+    pub routing_ms: f64,
+    pub inference_ms: f64,
+    /// Part of the inference spent evaluating the prompt, when the model
+    /// reports it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefill_ms: Option<f64>,
+    /// Part of the inference spent generating tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decode_ms: Option<f64>,
+    pub post_processing_ms: f64,
+    pub total_ms: f64,
+}
+
+impl StageTimings {
+    pub fn add(&mut self, stage: Stage, elapsed: Duration) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        match stage {
+            Stage::Validation => self.validation_ms += ms,
+            Stage::Queueing => self.queueing_ms += ms,
+            Stage::Routing => self.routing_ms += ms,
+            Stage::Inference => self.inference_ms += ms,
+            Stage::Prefill => *self.prefill_ms.get_or_insert(0.0) += ms,
+            Stage::Decode => *self.decode_ms.get_or_insert(0.0) += ms,
+            Stage::PostProcessing => self.post_processing_ms += ms,
+        }
+    }
+
+    /// Record the breakdown as `stage.*_ms` attributes of a span
+    pub fn set_span_attributes(&self, span: &mut Span) {
+        for (stage, ms) in [
+            ("validation", Some(self.validation_ms)),
+            ("queueing", Some(self.queueing_ms)),
+            ("routing", Some(self.routing_ms)),
+            ("inference", Some(self.inference_ms)),
+            ("prefill", self.prefill_ms),
+            ("decode", self.decode_ms),
+            ("post_processing", Some(self.post_processing_ms)),
+            ("total", Some(self.total_ms)),
+        ] {
+            if let Some(ms) = ms {
+                span.set_attribute(&format!("stage.{}_ms", stage), format!("{:.3}", ms));
+            }
+        }
+    }
+}
+
+tokio::task_local! {
+    static TIMINGS: Arc<Mutex<StageTimings>>;
+}
+
+/// Run a future, returning its output with the stage timings reported
+/// while it ran
+pub async fn measure<F: Future>(future: F) -> (F::Output, StageTimings) {
+    let timings = Arc::new(Mutex::new(StageTimings::default()));
+    let output = TIMINGS.scope(Arc::clone(&timings), future).await;
+    let timings = *timings.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    (output, timings)
+}
+
+/// Add time spent in a stage to the current request.
+///
+/// Does nothing when called outside [`measure`].
+pub fn record(stage: Stage, elapsed: Duration) {
+    let _ = TIMINGS.try_with(|timings| {
+        timings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .add(stage, elapsed);
+    });
+}
+
+/// Run a future, recording the time it took as `stage`
+pub async fn timed<F: Future>(stage: Stage, future: F) -> F::Output {
+    let start = Instant::now();
+    let output = future.await;
+    record(stage, start.elapsed());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_measure_collects_reported_stages() {
+        let (value, timings) = measure(async {
+            record(Stage::Queueing, Duration::from_millis(3));
+            let value = timed(Stage::Inference, async {
+                record(Stage::Prefill, Duration::from_millis(2));
+                record(Stage::Decode, Duration::from_millis(5));
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                7
+            })
+            .await;
+            record(Stage::Queueing, Duration::from_millis(1));
+            value
+        })
+        .await;
+
+        assert_eq!(value, 7);
+        assert!((timings.queueing_ms - 4.0).abs() < 1e-9);
+        assert!(timings.inference_ms >= 10.0);
+        assert_eq!((timings.prefill_ms, timings.decode_ms), (Some(2.0), Some(5.0)));
+
+        // Reports outside a measured request are dropped
+        record(Stage::Validation, Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_unreported_prefill_and_decode_are_left_out() {
+        let mut timings = StageTimings::default();
+        timings.add(Stage::Routing, Duration::from_micros(1500));
+        let json = serde_json::to_value(timings).unwrap();
+        assert_eq!(json["routing_ms"], 1.5);
+        assert!(json.get("prefill_ms").is_none());
+        assert!(json.get("post_processing_ms").is_some());
+    }
+}
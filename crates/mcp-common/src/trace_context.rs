@@ -0,0 +1,335 @@
+//! W3C Trace Context propagation and request spans
+//!
+//! A request's trace travels in `RequestContext::trace` as the context of the
+//! span currently working on it. Each component opens a child [`Span`] for
+//! its part of the work; the span is recorded when it is dropped and kept in
+//! a bounded process-wide buffer until the telemetry exporter drains it.
+//! Outbound calls carry the span's context in a `traceparent` header.
+//!
+//! Spans are only recorded while tracing is enabled and the trace is
+//! sampled. Contexts are propagated either way, so a caller's trace is never
+//! broken by a gateway that is not exporting.
+
+use crate::config::TracingConfig;
+use crate::types::MCPRequest;
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use uuid::Uuid;
+
+/// Header carrying the trace context on HTTP requests and responses
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Position in a distributed trace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits
+    pub trace_id: String,
+    /// Span that work carrying this context belongs to, 16 lowercase hex digits
+    pub span_id: String,
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` header, None when it is malformed
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let (version, trace_id, span_id, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        // Version 00 has exactly four fields; later versions may append more
+        if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        if !is_hex(trace_id, 32) || !is_hex(span_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+        if trace_id.bytes().all(|b| b == b'0') || span_id.bytes().all(|b| b == b'0') {
+            return None;
+        }
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            sampled: u8::from_str_radix(flags, 16).ok()? & 0x01 != 0,
+        })
+    }
+
+    /// Start a new trace, sampled according to the configured ratio
+    pub fn new_root() -> Self {
+        let trace_id = Uuid::new_v4().simple().to_string();
+        let sampled = sample(&trace_id, tracer().sample_ratio());
+        Self {
+            trace_id,
+            span_id: new_span_id(),
+            sampled,
+        }
+    }
+
+    /// `traceparent` header value for this context
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{}", self.trace_id, self.span_id, if self.sampled { "01" } else { "00" })
+    }
+}
+
+/// Role of a span, as in OpenTelemetry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpanKind {
+    #[default]
+    Internal,
+    /// Handling a request from a caller
+    Server,
+    /// Calling out to another service
+    Client,
+}
+
+/// A finished span awaiting export
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpanRecord {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub attributes: BTreeMap<String, String>,
+    pub error: Option<String>,
+}
+
+/// An open span, recorded when dropped
+#[derive(Debug)]
+pub struct Span {
+    record: SpanRecord,
+    sampled: bool,
+}
+
+impl Span {
+    /// Start a span as a child of `parent`
+    pub fn child_of(parent: &TraceContext, name: &str, kind: SpanKind) -> Self {
+        Self {
+            record: SpanRecord {
+                trace_id: parent.trace_id.clone(),
+                span_id: new_span_id(),
+                parent_span_id: Some(parent.span_id.clone()),
+                name: name.to_string(),
+                kind,
+                start_time: Utc::now(),
+                ..Default::default()
+            },
+            sampled: parent.sampled,
+        }
+    }
+
+    /// Start a span for work on `request`, when the request carries a trace
+    pub fn for_request(request: &MCPRequest, name: &str, kind: SpanKind) -> Option<Self> {
+        request.trace().map(|parent| Self::child_of(parent, name, kind))
+    }
+
+    /// Start a span continuing `parent`, or a new trace when there is no
+    /// parent and tracing is enabled
+    pub fn continue_or_start(parent: Option<&TraceContext>, name: &str, kind: SpanKind) -> Option<Self> {
+        match parent {
+            Some(parent) => Some(Self::child_of(parent, name, kind)),
+            None if is_enabled() => {
+                let mut span = Self::child_of(&TraceContext::new_root(), name, kind);
+                span.record.parent_span_id = None;
+                Some(span)
+            },
+            None => None,
+        }
+    }
+
+    /// Context for work done within this span
+    pub fn context(&self) -> TraceContext {
+        TraceContext {
+            trace_id: self.record.trace_id.clone(),
+            span_id: self.record.span_id.clone(),
+            sampled: self.sampled,
+        }
+    }
+
+    pub fn set_attribute(&mut self, key: &str, value: impl Display) {
+        self.record.attributes.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn set_error(&mut self, error: impl Display) {
+        self.record.error = Some(error.to_string());
+    }
+
+    /// Mark the span failed if `result` is an error
+    pub fn record_result<T>(&mut self, result: &Result<T>) {
+        if let Err(e) = result {
+            self.set_error(e);
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if self.sampled && is_enabled() {
+            let mut record = std::mem::take(&mut self.record);
+            record.end_time = Utc::now();
+            tracer().push(record);
+        }
+    }
+}
+
+impl MCPRequest {
+    /// Trace this request is part of, if any
+    pub fn trace(&self) -> Option<&TraceContext> {
+        self.context.as_ref().and_then(|context| context.trace.as_ref())
+    }
+
+    /// Make `trace` the request's current trace context
+    pub fn set_trace(&mut self, trace: TraceContext) {
+        self.context.get_or_insert_with(Default::default).trace = Some(trace);
+    }
+}
+
+struct Tracer {
+    enabled: AtomicBool,
+    /// Sample ratio as f64 bits
+    sample_ratio: AtomicU64,
+    capacity: AtomicUsize,
+    dropped: AtomicU64,
+    spans: Mutex<VecDeque<SpanRecord>>,
+}
+
+impl Tracer {
+    fn sample_ratio(&self) -> f64 {
+        f64::from_bits(self.sample_ratio.load(Ordering::Relaxed))
+    }
+
+    fn push(&self, record: SpanRecord) {
+        let mut spans = self.lock();
+        if spans.len() >= self.capacity.load(Ordering::Relaxed) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        spans.push_back(record);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<SpanRecord>> {
+        self.spans.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn tracer() -> &'static Tracer {
+    static GLOBAL: OnceLock<Tracer> = OnceLock::new();
+    GLOBAL.get_or_init(|| {
+        let defaults = TracingConfig::default();
+        Tracer {
+            enabled: AtomicBool::new(defaults.enabled),
+            sample_ratio: AtomicU64::new(defaults.sample_ratio.to_bits()),
+            capacity: AtomicUsize::new(defaults.max_buffered_spans),
+            dropped: AtomicU64::new(0),
+            spans: Mutex::new(VecDeque::new()),
+        }
+    })
+}
+
+/// Apply the tracing configuration, called when the gateway starts
+pub fn configure(config: &TracingConfig) {
+    let tracer = tracer();
+    tracer.sample_ratio.store(config.sample_ratio.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    tracer.capacity.store(config.max_buffered_spans, Ordering::Relaxed);
+    tracer.enabled.store(config.enabled, Ordering::Relaxed);
+}
+
+/// Whether spans are being recorded
+pub fn is_enabled() -> bool {
+    tracer().enabled.load(Ordering::Relaxed)
+}
+
+/// Take up to `max` finished spans, oldest first
+pub fn drain(max: usize) -> Vec<SpanRecord> {
+    let mut spans = tracer().lock();
+    let count = max.min(spans.len());
+    spans.drain(..count).collect()
+}
+
+/// Spans waiting for export
+pub fn pending_spans() -> usize {
+    tracer().lock().len()
+}
+
+/// Spans discarded because the buffer was full
+pub fn dropped_spans() -> u64 {
+    tracer().dropped.load(Ordering::Relaxed)
+}
+
+/// Sampling decision from the random low bytes of the trace ID, so every
+/// participant with the same ratio agrees
+fn sample(trace_id: &str, ratio: f64) -> bool {
+    let random = u64::from_str_radix(&trace_id[trace_id.len() - 14..], 16).unwrap_or(0);
+    (random as f64) < ratio * (1u64 << 56) as f64
+}
+
+fn new_span_id() -> String {
+    format!("{:016x}", (Uuid::new_v4().as_u128() as u64).max(1))
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_and_formats_traceparent() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::parse(header).unwrap();
+        assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(context.sampled);
+        assert_eq!(context.traceparent(), header);
+        assert!(TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-future").is_some());
+
+        for invalid in [
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        ] {
+            assert!(TraceContext::parse(invalid).is_none(), "{}", invalid);
+        }
+        assert!(sample("4bf92f3577b34da6a3ce929d0e0e4736", 1.0));
+        assert!(!sample("4bf92f3577b34da6a3ce929d0e0e4736", 0.0));
+    }
+
+    #[test]
+    fn test_spans_nest_and_are_recorded_when_enabled() {
+        let parent = TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        configure(&TracingConfig::default());
+        drop(Span::child_of(&parent, "not.recorded", SpanKind::Internal));
+
+        configure(&TracingConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        let mut server = Span::child_of(&parent, "gateway.request", SpanKind::Server);
+        let mut client = Span::child_of(&server.context(), "router.cloud_forward", SpanKind::Client);
+        client.set_attribute("endpoint", "primary");
+        client.record_result::<()>(&Err(crate::Error::Network("unreachable".to_string())));
+        drop(client);
+        server.set_attribute("method", "completion");
+        let server_id = server.context().span_id;
+        drop(server);
+        drop(Span::child_of(&TraceContext { sampled: false, ..parent.clone() }, "unsampled", SpanKind::Internal));
+
+        let spans = drain(usize::MAX);
+        configure(&TracingConfig::default());
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].name, "router.cloud_forward");
+        assert_eq!(spans[0].parent_span_id.as_deref(), Some(server_id.as_str()));
+        assert!(spans[0].error.is_some());
+        assert_eq!(spans[1].parent_span_id.as_deref(), Some("00f067aa0ba902b7"));
+        assert!(spans.iter().all(|span| span.trace_id == parent.trace_id && span.end_time >= span.start_time));
+    }
+}
@@ -1,6 +1,7 @@
 //! Common types for the MCP Edge Gateway
 
 use chrono::{DateTime, Utc};
+use crate::trace_context::TraceContext;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -50,10 +51,82 @@ pub struct RequestContext {
     pub retry_count: u32,
     pub source: RequestSource,
     pub requirements: ProcessingRequirements,
+    /// Current position in the request's distributed trace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<TraceContext>,
+    /// Caller authenticated by the HTTP API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<Principal>,
+    /// ISO 639-1 code of the language detected in the request's text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Caller-chosen key shared by retries of the same request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// Rank among requests of the same priority waiting for a model, higher
+    /// first; [`DEFAULT_WEIGHT`] when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+}
+
+impl Default for RequestContext {
+    fn default() -> Self {
+        Self {
+            priority: Priority::Normal,
+            timeout_ms: None,
+            retry_count: 0,
+            source: RequestSource::Local,
+            requirements: ProcessingRequirements {
+                max_latency_ms: None,
+                min_accuracy: None,
+                max_memory_mb: None,
+                require_local: false,
+                allow_fallback: true,
+                pii_present: None,
+            },
+            trace: None,
+            principal: None,
+            language: None,
+            idempotency_key: None,
+            weight: None,
+        }
+    }
+}
+
+/// How a caller authenticated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    ApiKey,
+    Jwt,
+}
+
+/// An authenticated caller and the methods it may call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Principal {
+    /// API key name or JWT subject
+    pub subject: String,
+    pub scheme: AuthScheme,
+    /// Methods the caller may call, a trailing `*` matching any suffix;
+    /// any method when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_methods: Option<Vec<String>>,
+}
+
+impl Principal {
+    /// Whether the caller may call `method`
+    pub fn may_call(&self, method: &str) -> bool {
+        self.allowed_methods.as_ref().map_or(true, |allowed| {
+            allowed.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => method.starts_with(prefix),
+                None => pattern == method,
+            })
+        })
+    }
 }
 
 /// Request priority levels
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Priority {
     Low = 1,
     Normal = 2,
@@ -61,12 +134,109 @@ pub enum Priority {
     Critical = 4,
 }
 
+impl Priority {
+    pub const ALL: [Priority; 4] = [Priority::Critical, Priority::High, Priority::Normal, Priority::Low];
+
+    /// Lowercase name used in metric keys
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+            Priority::Critical => "critical",
+        }
+    }
+}
+
+/// Scheduling weight of requests no prioritizer weighed
+pub const DEFAULT_WEIGHT: u32 = 1;
+
+/// Product-specific importance of requests, supplied by the embedding
+/// application
+///
+/// The gateway asks before admission control and per-model scheduling see a
+/// request, so e.g. safety alerts can outrank chat without changing routing.
+/// The prioritizer is given the request's context and returns its priority
+/// and weight; the weight orders requests of the same priority in a model's
+/// wait list. Returning the context's priority with [`DEFAULT_WEIGHT`] leaves
+/// the request as it was. Closures taking the context work as prioritizers.
+pub trait RequestPrioritizer: Send + Sync {
+    /// Priority and weight for a request with `context`
+    fn prioritize(&self, context: &RequestContext) -> (Priority, u32);
+}
+
+impl<F> RequestPrioritizer for F
+where
+    F: Fn(&RequestContext) -> (Priority, u32) + Send + Sync,
+{
+    fn prioritize(&self, context: &RequestContext) -> (Priority, u32) {
+        self(context)
+    }
+}
+
+impl MCPRequest {
+    /// Scheduling priority, `Normal` for requests without a context
+    pub fn priority(&self) -> Priority {
+        self.context.as_ref().map_or(Priority::Normal, |context| context.priority)
+    }
+
+    /// Caller authenticated by the HTTP API, if any
+    pub fn principal(&self) -> Option<&Principal> {
+        self.context.as_ref()?.principal.as_ref()
+    }
+
+    pub fn set_principal(&mut self, principal: Principal) {
+        self.context.get_or_insert_with(Default::default).principal = Some(principal);
+    }
+
+    /// Change the scheduling priority, giving the request a default context
+    /// if it has none; admission control, the concurrency limiters and the
+    /// offline queue all order requests by it
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.context.get_or_insert_with(Default::default).priority = priority;
+    }
+
+    /// Rank among waiting requests of the same priority
+    pub fn weight(&self) -> u32 {
+        self.context.as_ref().and_then(|context| context.weight).unwrap_or(DEFAULT_WEIGHT)
+    }
+
+    pub fn set_weight(&mut self, weight: u32) {
+        self.context.get_or_insert_with(Default::default).weight = Some(weight);
+    }
+
+    /// Language detected in the request's text, if any
+    pub fn language(&self) -> Option<&str> {
+        self.context.as_ref()?.language.as_deref()
+    }
+
+    /// Key shared by retries of this request, if the caller set one
+    pub fn idempotency_key(&self) -> Option<&str> {
+        self.context.as_ref()?.idempotency_key.as_deref()
+    }
+
+    pub fn set_idempotency_key(&mut self, key: String) {
+        self.context.get_or_insert_with(Default::default).idempotency_key = Some(key);
+    }
+
+    pub fn set_language(&mut self, language: &str) {
+        self.context.get_or_insert_with(Default::default).language = Some(language.to_string());
+    }
+
+    /// Whether the gateway sent this request to probe itself
+    pub fn is_probe(&self) -> bool {
+        matches!(self.context.as_ref().map(|context| &context.source), Some(RequestSource::Probe))
+    }
+}
+
 /// Source of the request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RequestSource {
     Local,
     Remote(String),
     Queue,
+    /// Synthetic probe the gateway sent itself
+    Probe,
 }
 
 /// Processing requirements for requests
@@ -136,6 +306,7 @@ pub struct ModelInfo {
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
 pub enum ModelFormat {
     GGML,
+    GGUF,
     ONNX,
     TensorFlowLite,
     Custom(String),
@@ -0,0 +1,273 @@
+//! Per-request resource accounting
+//!
+//! Requests share one process and hop between runtime worker threads, so
+//! usage is attributed by sampling the polling thread's counters around every
+//! poll of the request's future: the thread CPU clock for CPU time,
+//! `getrusage(RUSAGE_THREAD)` for block IO, and the process resident set for
+//! memory growth. Work handed to other tasks is not seen; backends doing work
+//! elsewhere, such as out-of-process model runners, add their own share with
+//! [`report`]. Work that outlives the measured future, such as the generator
+//! of a token stream, reports through a [`DeferredUsage`] handle instead.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Resources consumed while serving a request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub cpu_time_us: u64,
+    /// Growth of resident memory above its level when the request started
+    pub peak_memory_delta_bytes: u64,
+    pub io_read_bytes: u64,
+    pub io_write_bytes: u64,
+}
+
+impl ResourceUsage {
+    pub fn add(&mut self, other: &ResourceUsage) {
+        self.cpu_time_us = self.cpu_time_us.saturating_add(other.cpu_time_us);
+        self.peak_memory_delta_bytes = self.peak_memory_delta_bytes.saturating_add(other.peak_memory_delta_bytes);
+        self.io_read_bytes = self.io_read_bytes.saturating_add(other.io_read_bytes);
+        self.io_write_bytes = self.io_write_bytes.saturating_add(other.io_write_bytes);
+    }
+}
+
+/// Usage accumulated by a tenant across its requests
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantUsage {
+    pub requests: u64,
+    pub total: ResourceUsage,
+    pub max_cpu_time_us: u64,
+    pub max_peak_memory_delta_bytes: u64,
+}
+
+impl TenantUsage {
+    pub fn record(&mut self, usage: &ResourceUsage) {
+        self.requests += 1;
+        self.total.add(usage);
+        self.max_cpu_time_us = self.max_cpu_time_us.max(usage.cpu_time_us);
+        self.max_peak_memory_delta_bytes = self.max_peak_memory_delta_bytes.max(usage.peak_memory_delta_bytes);
+    }
+}
+
+tokio::task_local! {
+    static REPORTED: Arc<Mutex<ResourceUsage>>;
+}
+
+/// Run a future, returning its output with the resources it consumed
+pub async fn measure<F: Future>(future: F) -> (F::Output, ResourceUsage) {
+    let reported = Arc::new(Mutex::new(ResourceUsage::default()));
+    let metered = Metered {
+        inner: Box::pin(future),
+        usage: ResourceUsage::default(),
+        baseline_rss: resident_bytes(),
+    };
+    let (output, mut usage) = REPORTED.scope(Arc::clone(&reported), metered).await;
+    // Taken, so that handles only collect what is reported from here on
+    usage.add(&std::mem::take(&mut *reported.lock().unwrap_or_else(|poisoned| poisoned.into_inner())));
+    (output, usage)
+}
+
+/// Add usage incurred outside the measured task to the current request.
+///
+/// Does nothing when called outside [`measure`].
+pub fn report(usage: &ResourceUsage) {
+    let _ = REPORTED.try_with(|reported| {
+        reported.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).add(usage);
+    });
+}
+
+/// Usage reported for a request after its measured future completed
+#[derive(Debug, Clone)]
+pub struct DeferredUsage(Arc<Mutex<ResourceUsage>>);
+
+impl DeferredUsage {
+    pub fn report(&self, usage: &ResourceUsage) {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).add(usage);
+    }
+
+    /// Usage reported since the measured future completed
+    pub fn total(&self) -> ResourceUsage {
+        *self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Handle for reporting usage of the current request from tasks it spawns,
+/// after [`measure`] returned; `None` outside [`measure`]
+pub fn deferred() -> Option<DeferredUsage> {
+    REPORTED.try_with(|reported| DeferredUsage(Arc::clone(reported))).ok()
+}
+
+/// Run a future, returning its output with the peak growth of resident
+/// memory while it ran. Unlike [`measure`] this leaves usage reported by the
+/// future to the enclosing request.
+pub async fn peak_memory_growth<F: Future>(future: F) -> (F::Output, u64) {
+    let mut inner = Box::pin(future);
+    let baseline_rss = resident_bytes();
+    let mut peak = 0;
+    let output = std::future::poll_fn(|cx| {
+        let poll = inner.as_mut().poll(cx);
+        peak = peak.max(resident_bytes().saturating_sub(baseline_rss));
+        poll
+    })
+    .await;
+    (output, peak)
+}
+
+struct Metered<F: Future> {
+    inner: Pin<Box<F>>,
+    usage: ResourceUsage,
+    baseline_rss: u64,
+}
+
+impl<F: Future> Future for Metered<F> {
+    type Output = (F::Output, ResourceUsage);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let before = ThreadSample::now();
+        let poll = self.inner.as_mut().poll(cx);
+        let after = ThreadSample::now();
+
+        let this = &mut *self;
+        this.usage.cpu_time_us += after.cpu_time_us.saturating_sub(before.cpu_time_us);
+        this.usage.io_read_bytes += after.io_read_bytes.saturating_sub(before.io_read_bytes);
+        this.usage.io_write_bytes += after.io_write_bytes.saturating_sub(before.io_write_bytes);
+        let growth = resident_bytes().saturating_sub(this.baseline_rss);
+        this.usage.peak_memory_delta_bytes = this.usage.peak_memory_delta_bytes.max(growth);
+
+        poll.map(|output| (output, this.usage))
+    }
+}
+
+/// Counters of the calling thread
+#[derive(Default)]
+struct ThreadSample {
+    cpu_time_us: u64,
+    io_read_bytes: u64,
+    io_write_bytes: u64,
+}
+
+impl ThreadSample {
+    #[cfg(target_os = "linux")]
+    fn now() -> Self {
+        // SAFETY: both calls only write the zeroed structs they are given
+        let mut sample = Self::default();
+        let mut cpu: libc::timespec = unsafe { std::mem::zeroed() };
+        if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut cpu) } == 0 {
+            sample.cpu_time_us = cpu.tv_sec as u64 * 1_000_000 + cpu.tv_nsec as u64 / 1000;
+        }
+        // rusage CPU times are tick-based, but block counts are exact
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_THREAD, &mut usage) } == 0 {
+            // Block counts are in 512-byte units
+            sample.io_read_bytes = usage.ru_inblock as u64 * 512;
+            sample.io_write_bytes = usage.ru_oublock as u64 * 512;
+        }
+        sample
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn now() -> Self {
+        Self::default()
+    }
+}
+
+/// Resident set size of the process
+#[cfg(target_os = "linux")]
+fn resident_bytes() -> u64 {
+    static PAGE_SIZE: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+    let page_size = *PAGE_SIZE.get_or_init(|| unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64);
+    std::fs::read_to_string("/proc/self/statm")
+        .ok()
+        .and_then(|statm| statm.split_whitespace().nth(1)?.parse::<u64>().ok())
+        .map_or(0, |pages| pages * page_size)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_bytes() -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_measure_attributes_cpu_and_reported_usage() {
+        let (sum, usage) = measure(async {
+            let mut sum = 0u64;
+            for i in 0..5_000_000u64 {
+                sum = sum.wrapping_add(std::hint::black_box(i * i));
+            }
+            tokio::task::yield_now().await;
+            report(&ResourceUsage {
+                cpu_time_us: 1_000_000,
+                io_read_bytes: 4096,
+                ..Default::default()
+            });
+            sum
+        })
+        .await;
+
+        assert!(sum > 0);
+        assert!(usage.cpu_time_us > 1_000_000, "{:?}", usage);
+        assert!(usage.io_read_bytes >= 4096);
+
+        // Reports outside a measured request are dropped
+        report(&ResourceUsage {
+            cpu_time_us: 1,
+            ..Default::default()
+        });
+    }
+
+    #[tokio::test]
+    async fn test_deferred_usage_collects_reports_after_measure() {
+        let (handle, usage) = measure(async {
+            report(&ResourceUsage {
+                io_read_bytes: 4096,
+                ..Default::default()
+            });
+            deferred().unwrap()
+        })
+        .await;
+        assert_eq!(usage.io_read_bytes, 4096);
+        assert_eq!(handle.total(), ResourceUsage::default());
+
+        let spawned = handle.clone();
+        tokio::spawn(async move {
+            spawned.report(&ResourceUsage {
+                cpu_time_us: 2_000,
+                ..Default::default()
+            });
+        })
+        .await
+        .unwrap();
+        assert_eq!(handle.total().cpu_time_us, 2_000);
+        assert!(deferred().is_none());
+    }
+
+    #[test]
+    fn test_tenant_usage_accumulates() {
+        let mut tenant = TenantUsage::default();
+        tenant.record(&ResourceUsage {
+            cpu_time_us: 300,
+            peak_memory_delta_bytes: 1024,
+            io_read_bytes: 10,
+            io_write_bytes: 0,
+        });
+        tenant.record(&ResourceUsage {
+            cpu_time_us: 100,
+            peak_memory_delta_bytes: 4096,
+            io_read_bytes: 0,
+            io_write_bytes: 20,
+        });
+
+        assert_eq!(tenant.requests, 2);
+        assert_eq!(tenant.total.cpu_time_us, 400);
+        assert_eq!(tenant.total.io_read_bytes + tenant.total.io_write_bytes, 30);
+        assert_eq!(tenant.max_cpu_time_us, 300);
+        assert_eq!(tenant.max_peak_memory_delta_bytes, 4096);
+    }
+}
@@ -4,9 +4,9 @@ use chrono::{DateTime, Utc};
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
-/// Generate a new request ID
+/// Generate a new request ID under the configured scheme
 pub fn generate_request_id() -> Uuid {
-    Uuid::new_v4()
+    crate::request_id::generate()
 }
 
 /// Get current timestamp
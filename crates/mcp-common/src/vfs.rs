@@ -0,0 +1,500 @@
+//! Virtual filesystem abstraction for component storage
+//!
+//! Components never touch the host filesystem directly; model files, queue
+//! databases and session data are resolved through a [`Vfs`]. The backend is
+//! chosen by [`StorageConfig`] so deployments can put storage on an encrypted
+//! partition, run from a read-only rootfs with a tmpfs overlay, or keep
+//! everything in memory on WASM targets, without the components knowing.
+//! Additional backends (object-store caches, IndexedDB) implement the same trait.
+
+use crate::config::{StorageBackend, StorageConfig};
+use crate::write_policy::WritePolicy;
+use crate::{Error, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::RwLock;
+
+/// Storage backend used by every component for file IO
+#[async_trait]
+pub trait Vfs: Send + Sync {
+    /// Backend name for logs and health reporting
+    fn name(&self) -> &'static str;
+
+    /// Read a whole file
+    async fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Read up to `len` bytes starting at `offset`; returns fewer at end of file
+    async fn read_at(&self, path: &Path, offset: u64, len: usize) -> Result<Vec<u8>>;
+
+    /// Create or replace a file
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Append to a file, creating it if missing; durable once this returns
+    /// unless the [`WritePolicy`] is relaxed for worn media
+    async fn append(&self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Atomically replace `to` with `from`
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Delete a file
+    async fn remove(&self, path: &Path) -> Result<()>;
+
+    /// Whether a file exists
+    async fn exists(&self, path: &Path) -> bool;
+
+    /// Size of a file in bytes
+    async fn size(&self, path: &Path) -> Result<u64>;
+
+    /// Last modification time of a file
+    async fn modified(&self, path: &Path) -> Result<SystemTime>;
+
+    /// Create a directory and its parents
+    async fn create_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Files directly inside a directory, sorted
+    async fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>>;
+
+    /// Host path backing `path`, for native libraries that need a real
+    /// directory (e.g. the queue database). `None` for non-host backends.
+    fn host_path(&self, path: &Path) -> Option<PathBuf>;
+}
+
+/// Create the VFS selected by the storage configuration
+pub fn create_vfs(config: &StorageConfig) -> Arc<dyn Vfs> {
+    match &config.backend {
+        StorageBackend::Local => Arc::new(LocalVfs::new()),
+        StorageBackend::Memory => Arc::new(MemoryVfs::new()),
+        StorageBackend::Overlay { upper_dir } => Arc::new(OverlayVfs::new(
+            Arc::new(LocalVfs::new()),
+            Arc::new(LocalVfs::rooted(upper_dir.clone())),
+        )),
+    }
+}
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> Error {
+    Error::Internal(format!("Failed to {} {:?}: {}", action, path, e))
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::Internal(format!("File not found: {:?}", path))
+}
+
+/// Host filesystem, optionally rooted under a directory
+#[derive(Debug, Clone, Default)]
+pub struct LocalVfs {
+    root: Option<PathBuf>,
+}
+
+impl LocalVfs {
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    /// Map every path beneath `root`, as for a tmpfs or encrypted mount
+    pub fn rooted(root: PathBuf) -> Self {
+        Self { root: Some(root) }
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(
+                path.components()
+                    .filter(|c| matches!(c, Component::Normal(_)))
+                    .collect::<PathBuf>(),
+            ),
+            None => path.to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl Vfs for LocalVfs {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let resolved = self.resolve(path);
+        tokio::fs::read(&resolved).await.map_err(|e| io_error("read", &resolved, e))
+    }
+
+    async fn read_at(&self, path: &Path, offset: u64, len: usize) -> Result<Vec<u8>> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+
+        let resolved = self.resolve(path);
+        let mut file = tokio::fs::File::open(&resolved)
+            .await
+            .map_err(|e| io_error("open", &resolved, e))?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| io_error("seek", &resolved, e))?;
+
+        let mut buffer = Vec::with_capacity(len);
+        file.take(len as u64)
+            .read_to_end(&mut buffer)
+            .await
+            .map_err(|e| io_error("read", &resolved, e))?;
+        Ok(buffer)
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let resolved = self.resolve(path);
+        tokio::fs::write(&resolved, data)
+            .await
+            .map_err(|e| io_error("write", &resolved, e))
+    }
+
+    async fn append(&self, path: &Path, data: &[u8]) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let resolved = self.resolve(path);
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&resolved)
+            .await
+            .map_err(|e| io_error("open", &resolved, e))?;
+        file.write_all(data).await.map_err(|e| io_error("append to", &resolved, e))?;
+        if !WritePolicy::global().should_sync() {
+            return Ok(());
+        }
+        file.sync_data().await.map_err(|e| io_error("sync", &resolved, e))
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from, to) = (self.resolve(from), self.resolve(to));
+        tokio::fs::rename(&from, &to)
+            .await
+            .map_err(|e| io_error("rename", &from, e))
+    }
+
+    async fn remove(&self, path: &Path) -> Result<()> {
+        let resolved = self.resolve(path);
+        tokio::fs::remove_file(&resolved)
+            .await
+            .map_err(|e| io_error("remove", &resolved, e))
+    }
+
+    async fn exists(&self, path: &Path) -> bool {
+        tokio::fs::try_exists(self.resolve(path)).await.unwrap_or(false)
+    }
+
+    async fn size(&self, path: &Path) -> Result<u64> {
+        let resolved = self.resolve(path);
+        tokio::fs::metadata(&resolved)
+            .await
+            .map(|metadata| metadata.len())
+            .map_err(|e| io_error("stat", &resolved, e))
+    }
+
+    async fn modified(&self, path: &Path) -> Result<SystemTime> {
+        let resolved = self.resolve(path);
+        tokio::fs::metadata(&resolved)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| io_error("stat", &resolved, e))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let resolved = self.resolve(path);
+        tokio::fs::create_dir_all(&resolved)
+            .await
+            .map_err(|e| io_error("create directory", &resolved, e))
+    }
+
+    async fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let resolved = self.resolve(path);
+        let mut entries = tokio::fs::read_dir(&resolved)
+            .await
+            .map_err(|e| io_error("list", &resolved, e))?;
+
+        let mut files = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| io_error("list", &resolved, e))?
+        {
+            if entry.file_type().await.is_ok_and(|file_type| file_type.is_file()) {
+                files.push(path.join(entry.file_name()));
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn host_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(self.resolve(path))
+    }
+}
+
+/// Volatile in-memory filesystem, used on WASM targets and in tests
+#[derive(Debug, Default)]
+pub struct MemoryVfs {
+    files: RwLock<HashMap<PathBuf, MemoryFile>>,
+}
+
+#[derive(Debug)]
+struct MemoryFile {
+    data: Vec<u8>,
+    modified: SystemTime,
+}
+
+impl MemoryVfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Vfs for MemoryVfs {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.files
+            .read()
+            .await
+            .get(path)
+            .map(|file| file.data.clone())
+            .ok_or_else(|| not_found(path))
+    }
+
+    async fn read_at(&self, path: &Path, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let files = self.files.read().await;
+        let data = &files.get(path).ok_or_else(|| not_found(path))?.data;
+        let start = (offset as usize).min(data.len());
+        let end = start.saturating_add(len).min(data.len());
+        Ok(data[start..end].to_vec())
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let file = MemoryFile {
+            data: data.to_vec(),
+            modified: SystemTime::now(),
+        };
+        self.files.write().await.insert(path.to_path_buf(), file);
+        Ok(())
+    }
+
+    async fn append(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let mut files = self.files.write().await;
+        let file = files.entry(path.to_path_buf()).or_insert_with(|| MemoryFile {
+            data: Vec::new(),
+            modified: SystemTime::now(),
+        });
+        file.data.extend_from_slice(data);
+        file.modified = SystemTime::now();
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut files = self.files.write().await;
+        let file = files.remove(from).ok_or_else(|| not_found(from))?;
+        files.insert(to.to_path_buf(), file);
+        Ok(())
+    }
+
+    async fn remove(&self, path: &Path) -> Result<()> {
+        self.files
+            .write()
+            .await
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| not_found(path))
+    }
+
+    async fn exists(&self, path: &Path) -> bool {
+        self.files.read().await.contains_key(path)
+    }
+
+    async fn size(&self, path: &Path) -> Result<u64> {
+        self.files
+            .read()
+            .await
+            .get(path)
+            .map(|file| file.data.len() as u64)
+            .ok_or_else(|| not_found(path))
+    }
+
+    async fn modified(&self, path: &Path) -> Result<SystemTime> {
+        self.files
+            .read()
+            .await
+            .get(path)
+            .map(|file| file.modified)
+            .ok_or_else(|| not_found(path))
+    }
+
+    async fn create_dir_all(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    async fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mut files: Vec<_> = self
+            .files
+            .read()
+            .await
+            .keys()
+            .filter(|file| file.parent() == Some(path))
+            .cloned()
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    fn host_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// Read-only lower layer with a writable upper layer, as for a read-only
+/// rootfs with a tmpfs overlay. Reads prefer the upper layer.
+pub struct OverlayVfs {
+    lower: Arc<dyn Vfs>,
+    upper: Arc<dyn Vfs>,
+}
+
+impl OverlayVfs {
+    pub fn new(lower: Arc<dyn Vfs>, upper: Arc<dyn Vfs>) -> Self {
+        Self { lower, upper }
+    }
+
+    async fn layer_for(&self, path: &Path) -> &Arc<dyn Vfs> {
+        if self.upper.exists(path).await {
+            &self.upper
+        } else {
+            &self.lower
+        }
+    }
+
+    async fn ensure_parent(&self, path: &Path) -> Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => self.upper.create_dir_all(parent).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl Vfs for OverlayVfs {
+    fn name(&self) -> &'static str {
+        "overlay"
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.layer_for(path).await.read(path).await
+    }
+
+    async fn read_at(&self, path: &Path, offset: u64, len: usize) -> Result<Vec<u8>> {
+        self.layer_for(path).await.read_at(path, offset, len).await
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.ensure_parent(path).await?;
+        self.upper.write(path, data).await
+    }
+
+    async fn append(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.ensure_parent(path).await?;
+        if !self.upper.exists(path).await && self.lower.exists(path).await {
+            let existing = self.lower.read(path).await?;
+            self.upper.write(path, &existing).await?;
+        }
+        self.upper.append(path, data).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        if !self.upper.exists(from).await {
+            let data = self.lower.read(from).await?;
+            self.ensure_parent(from).await?;
+            self.upper.write(from, &data).await?;
+        }
+        self.ensure_parent(to).await?;
+        self.upper.rename(from, to).await
+    }
+
+    async fn remove(&self, path: &Path) -> Result<()> {
+        if self.upper.exists(path).await {
+            self.upper.remove(path).await
+        } else {
+            Err(Error::Internal(format!(
+                "Cannot remove {:?} from the read-only lower layer",
+                path
+            )))
+        }
+    }
+
+    async fn exists(&self, path: &Path) -> bool {
+        self.upper.exists(path).await || self.lower.exists(path).await
+    }
+
+    async fn size(&self, path: &Path) -> Result<u64> {
+        self.layer_for(path).await.size(path).await
+    }
+
+    async fn modified(&self, path: &Path) -> Result<SystemTime> {
+        self.layer_for(path).await.modified(path).await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.upper.create_dir_all(path).await
+    }
+
+    async fn list_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let upper = self.upper.list_dir(path).await;
+        let lower = self.lower.list_dir(path).await;
+        if let (Err(e), Err(_)) = (&upper, &lower) {
+            return Err(e.clone());
+        }
+
+        let mut files: Vec<_> = upper.unwrap_or_default();
+        files.extend(lower.unwrap_or_default());
+        files.sort();
+        files.dedup();
+        Ok(files)
+    }
+
+    fn host_path(&self, path: &Path) -> Option<PathBuf> {
+        self.upper.host_path(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_vfs_round_trip() {
+        let vfs = MemoryVfs::new();
+        let path = Path::new("models/tiny.ggml");
+        vfs.write(path, b"weights").await.unwrap();
+
+        assert_eq!(vfs.size(path).await.unwrap(), 7);
+        assert_eq!(vfs.read_at(path, 2, 3).await.unwrap(), b"igh");
+        assert_eq!(vfs.read_at(path, 5, 10).await.unwrap(), b"ts");
+
+        vfs.rename(path, Path::new("models/small.ggml")).await.unwrap();
+        assert!(!vfs.exists(path).await);
+        assert!(vfs.host_path(path).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_overlay_writes_never_touch_lower_layer() {
+        let lower = Arc::new(MemoryVfs::new());
+        let upper = Arc::new(MemoryVfs::new());
+        let path = Path::new("models/tiny.ggml");
+        lower.write(path, b"factory").await.unwrap();
+
+        let overlay = OverlayVfs::new(lower.clone(), upper.clone());
+        assert_eq!(overlay.read(path).await.unwrap(), b"factory");
+        assert!(overlay.remove(path).await.is_err());
+
+        overlay.write(path, b"updated").await.unwrap();
+        assert_eq!(overlay.read(path).await.unwrap(), b"updated");
+        assert_eq!(lower.read(path).await.unwrap(), b"factory");
+    }
+}
@@ -1,8 +1,18 @@
 //! WASM-specific utilities and bindings
 
-use js_sys::Promise;
+use crate::MCPRequest;
+use chrono::{DateTime, Utc};
+use js_sys::{Array, Promise};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
 use wasm_bindgen::prelude::*;
-use web_sys::{console, window, Window};
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{
+    console, window, Headers, IdbCursorWithValue, IdbDatabase, IdbFactory, IdbObjectStore,
+    IdbObjectStoreParameters, IdbRequest, IdbTransaction, IdbTransactionMode, RequestInit,
+    Response, Window, WorkerGlobalScope,
+};
 
 /// Initialize WASM module with logging
 #[wasm_bindgen(start)]
@@ -191,3 +201,393 @@ impl WasmCapabilities {
         }
     }
 }
+
+/// Object store the offline queue keeps its requests in
+pub const QUEUE_STORE: &str = "requests";
+
+/// Prefix of `queue.storage_path` values that name an IndexedDB database
+pub const INDEXEDDB_SCHEME: &str = "indexeddb://";
+
+/// A request held in the IndexedDB offline queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdbQueueEntry {
+    pub request: MCPRequest,
+    pub queued_at: DateTime<Utc>,
+    pub retry_count: u32,
+}
+
+/// Outcome of one [`IdbQueueStore::sync`] pass
+#[derive(Debug, Clone, Default)]
+pub struct IdbSyncReport {
+    pub synced: Vec<Uuid>,
+    pub failed: usize,
+    /// Requests dropped after exceeding the retry limit, with their retries
+    pub dead_lettered: Vec<(Uuid, u32)>,
+}
+
+/// Offline requests kept in IndexedDB, oldest first
+///
+/// Only the database name is held; every operation opens its own
+/// connection, so the store is `Send` and works the same in a page, a
+/// worker or a service worker that is restarted between events.
+#[derive(Debug, Clone)]
+pub struct IdbQueueStore {
+    db_name: String,
+}
+
+impl IdbQueueStore {
+    /// Open or create the database `name`, given bare or as an
+    /// `indexeddb://` URL
+    pub async fn open(name: &str) -> crate::Result<Self> {
+        let db_name = name.strip_prefix(INDEXEDDB_SCHEME).unwrap_or(name);
+        if db_name.is_empty() {
+            return Err(crate::Error::Queue(
+                "IndexedDB database name is empty".to_string(),
+            ));
+        }
+        let store = Self {
+            db_name: db_name.to_string(),
+        };
+        store.database().await?.close();
+        Ok(store)
+    }
+
+    pub fn db_name(&self) -> &str {
+        &self.db_name
+    }
+
+    /// Queue `request`; returns the queue length afterwards
+    pub async fn push(&self, request: &MCPRequest) -> crate::Result<usize> {
+        let entry = IdbQueueEntry {
+            request: request.clone(),
+            queued_at: Utc::now(),
+            retry_count: 0,
+        };
+        let value = JsValue::from_str(&serde_json::to_string(&entry)?);
+        let tx = self.begin(IdbTransactionMode::Readwrite).await?;
+        tx.request(tx.store.add(&value)).await?;
+        let count = tx.request(tx.store.count()).await?;
+        tx.commit().await?;
+        Ok(count.as_f64().unwrap_or_default() as usize)
+    }
+
+    /// Remove and return the oldest queued request
+    pub async fn pop_front(&self) -> crate::Result<Option<MCPRequest>> {
+        let tx = self.begin(IdbTransactionMode::Readwrite).await?;
+        let cursor = tx.request(tx.store.open_cursor()).await?;
+        let Some(cursor) = cursor.dyn_ref::<IdbCursorWithValue>() else {
+            tx.commit().await?;
+            return Ok(None);
+        };
+        let entry = cursor
+            .value()
+            .map_err(|e| js_error("Failed to read queued request", e))
+            .and_then(parse_entry)?;
+        tx.request(cursor.delete()).await?;
+        tx.commit().await?;
+        Ok(Some(entry.request))
+    }
+
+    pub async fn len(&self) -> crate::Result<usize> {
+        let tx = self.begin(IdbTransactionMode::Readonly).await?;
+        let count = tx.request(tx.store.count()).await?;
+        tx.commit().await?;
+        Ok(count.as_f64().unwrap_or_default() as usize)
+    }
+
+    pub async fn is_empty(&self) -> crate::Result<bool> {
+        Ok(self.len().await? == 0)
+    }
+
+    /// Every queued entry with its key, oldest first; unreadable entries
+    /// are skipped
+    pub async fn entries(&self) -> crate::Result<Vec<(f64, IdbQueueEntry)>> {
+        let tx = self.begin(IdbTransactionMode::Readonly).await?;
+        let keys = Array::from(&tx.request(tx.store.get_all_keys()).await?);
+        let values = Array::from(&tx.request(tx.store.get_all()).await?);
+        tx.commit().await?;
+        Ok(keys
+            .iter()
+            .zip(values.iter())
+            .filter_map(|(key, value)| match parse_entry(value) {
+                Ok(entry) => Some((key.as_f64().unwrap_or_default(), entry)),
+                Err(e) => {
+                    warn!("Skipping unreadable IndexedDB queue entry: {}", e);
+                    None
+                },
+            })
+            .collect())
+    }
+
+    /// Delete the entries under `keys` in one transaction
+    pub async fn remove(&self, keys: &[f64]) -> crate::Result<()> {
+        let tx = self.begin(IdbTransactionMode::Readwrite).await?;
+        for key in keys {
+            tx.store
+                .delete(&JsValue::from_f64(*key))
+                .map_err(|e| js_error("Failed to delete queued request", e))?;
+        }
+        tx.commit().await
+    }
+
+    async fn update(&self, key: f64, entry: &IdbQueueEntry) -> crate::Result<()> {
+        let value = JsValue::from_str(&serde_json::to_string(entry)?);
+        let tx = self.begin(IdbTransactionMode::Readwrite).await?;
+        tx.store
+            .put_with_key(&value, &JsValue::from_f64(key))
+            .map_err(|e| js_error("Failed to update queued request", e))?;
+        tx.commit().await
+    }
+
+    /// POST up to `batch` queued requests to `endpoint`, oldest first
+    ///
+    /// Delivered requests are removed; a failed one has its retry count
+    /// raised and is dropped once that exceeds `max_retries`.
+    pub async fn sync(
+        &self,
+        endpoint: &str,
+        batch: usize,
+        max_retries: u32,
+    ) -> crate::Result<IdbSyncReport> {
+        let mut report = IdbSyncReport::default();
+        for (key, mut entry) in self.entries().await?.into_iter().take(batch) {
+            let mut payload = serde_json::to_value(&entry.request)?;
+            if let Some(object) = payload.as_object_mut() {
+                object.insert(
+                    "_queue_metadata".to_string(),
+                    serde_json::json!({
+                        "queued_at": entry.queued_at,
+                        "retry_count": entry.retry_count,
+                        "sync_attempt": Utc::now(),
+                    }),
+                );
+            }
+            let reason = match post_json(endpoint, &payload.to_string()).await {
+                Ok(status) if (200..300).contains(&status) => {
+                    self.remove(&[key]).await?;
+                    report.synced.push(entry.request.id);
+                    continue;
+                },
+                Ok(status) => format!("cloud returned HTTP {}", status),
+                Err(e) => e.to_string(),
+            };
+            warn!("Failed to sync request {}: {}", entry.request.id, reason);
+            report.failed += 1;
+            entry.retry_count += 1;
+            if entry.retry_count > max_retries {
+                self.remove(&[key]).await?;
+                report
+                    .dead_lettered
+                    .push((entry.request.id, entry.retry_count));
+            } else {
+                self.update(key, &entry).await?;
+            }
+        }
+        Ok(report)
+    }
+
+    async fn database(&self) -> crate::Result<IdbDatabase> {
+        let request = idb_factory()?
+            .open_with_u32(&self.db_name, 1)
+            .map_err(|e| js_error("Failed to open IndexedDB", e))?;
+        let on_upgrade = Closure::once_into_js({
+            let request = request.clone();
+            move || {
+                let Ok(db) = request.result().and_then(|db| db.dyn_into::<IdbDatabase>()) else {
+                    return;
+                };
+                if !db.object_store_names().contains(QUEUE_STORE) {
+                    let params = IdbObjectStoreParameters::new();
+                    params.set_auto_increment(true);
+                    if let Err(e) =
+                        db.create_object_store_with_optional_parameters(QUEUE_STORE, &params)
+                    {
+                        warn!("Failed to create IndexedDB queue store: {:?}", e);
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+        settle(&request)
+            .await
+            .and_then(|db| db.dyn_into::<IdbDatabase>())
+            .map_err(|e| js_error("Failed to open IndexedDB", e))
+    }
+
+    async fn begin(&self, mode: IdbTransactionMode) -> crate::Result<QueueTransaction> {
+        let db = self.database().await?;
+        let tx = db
+            .transaction_with_str_and_mode(QUEUE_STORE, mode)
+            .map_err(|e| js_error("Failed to start IndexedDB transaction", e))?;
+        let store = tx
+            .object_store(QUEUE_STORE)
+            .map_err(|e| js_error("Failed to start IndexedDB transaction", e))?;
+        let done = transaction_done(&tx);
+        Ok(QueueTransaction {
+            db,
+            store,
+            done,
+        })
+    }
+}
+
+/// One transaction on the queue store; the connection closes on drop
+struct QueueTransaction {
+    db: IdbDatabase,
+    store: IdbObjectStore,
+    done: JsFuture,
+}
+
+impl QueueTransaction {
+    async fn request(&self, request: Result<IdbRequest, JsValue>) -> crate::Result<JsValue> {
+        let request = request.map_err(|e| js_error("IndexedDB request failed", e))?;
+        settle(&request)
+            .await
+            .map_err(|e| js_error("IndexedDB request failed", e))
+    }
+
+    /// Wait until the transaction is durably committed
+    async fn commit(mut self) -> crate::Result<()> {
+        (&mut self.done)
+            .await
+            .map(|_| ())
+            .map_err(|e| js_error("IndexedDB transaction failed", e))
+    }
+}
+
+impl Drop for QueueTransaction {
+    fn drop(&mut self) {
+        self.db.close();
+    }
+}
+
+fn js_error(context: &str, value: JsValue) -> crate::Error {
+    crate::Error::Queue(format!("{}: {:?}", context, value))
+}
+
+fn parse_entry(value: JsValue) -> crate::Result<IdbQueueEntry> {
+    let json = value
+        .as_string()
+        .ok_or_else(|| crate::Error::Queue("Queued request is not JSON".to_string()))?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// The IndexedDB factory of the page or worker scope
+fn idb_factory() -> crate::Result<IdbFactory> {
+    let factory = match window() {
+        Some(window) => window.indexed_db(),
+        None => js_sys::global()
+            .dyn_into::<WorkerGlobalScope>()
+            .map_err(|_| crate::Error::Queue("No window or worker scope".to_string()))?
+            .indexed_db(),
+    };
+    factory
+        .map_err(|e| js_error("IndexedDB unavailable", e))?
+        .ok_or_else(|| crate::Error::Queue("IndexedDB unavailable".to_string()))
+}
+
+/// Resolve with the request's result once it succeeds
+async fn settle(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    let outcome = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+    match outcome {
+        Ok(_) => request.result(),
+        Err(event) => Err(request
+            .error()
+            .ok()
+            .flatten()
+            .map(JsValue::from)
+            .unwrap_or(event)),
+    }
+}
+
+/// Resolves when `tx` commits, rejects when it fails or aborts
+fn transaction_done(tx: &IdbTransaction) -> JsFuture {
+    JsFuture::from(Promise::new(&mut |resolve, reject| {
+        tx.set_oncomplete(Some(&resolve));
+        tx.set_onerror(Some(&reject));
+        tx.set_onabort(Some(&reject));
+    }))
+}
+
+/// POST a JSON body from a page or worker; returns the HTTP status
+async fn post_json(url: &str, body: &str) -> crate::Result<u16> {
+    let headers = Headers::new().map_err(|e| js_error("Failed to build request", e))?;
+    headers
+        .set("content-type", "application/json")
+        .map_err(|e| js_error("Failed to build request", e))?;
+    let init = RequestInit::new();
+    init.set_method("POST");
+    init.set_headers(&headers);
+    init.set_body(&JsValue::from_str(body));
+    let fetch = match window() {
+        Some(window) => window.fetch_with_str_and_init(url, &init),
+        None => js_sys::global()
+            .unchecked_into::<WorkerGlobalScope>()
+            .fetch_with_str_and_init(url, &init),
+    };
+    let response: Response = JsFuture::from(fetch)
+        .await
+        .and_then(|response| response.dyn_into())
+        .map_err(|e| js_error("Request failed", e))?;
+    Ok(response.status())
+}
+
+fn to_js(error: crate::Error) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+/// Offline queue for pages and service workers, backed by IndexedDB
+#[wasm_bindgen]
+pub struct WasmOfflineQueue {
+    store: IdbQueueStore,
+}
+
+#[wasm_bindgen]
+impl WasmOfflineQueue {
+    /// Open the queue kept in the IndexedDB database `name`
+    pub async fn open(name: String) -> Result<WasmOfflineQueue, JsValue> {
+        let store = IdbQueueStore::open(&name).await.map_err(to_js)?;
+        Ok(WasmOfflineQueue {
+            store,
+        })
+    }
+
+    /// Queue an MCP request given as JSON; resolves to the queue length
+    pub fn enqueue(&self, request_json: String) -> Promise {
+        let store = self.store.clone();
+        future_to_promise(async move {
+            let request: MCPRequest =
+                serde_json::from_str(&request_json).map_err(|e| to_js(e.into()))?;
+            let len = store.push(&request).await.map_err(to_js)?;
+            Ok(JsValue::from(len as u32))
+        })
+    }
+
+    /// Resolves to the number of queued requests
+    pub fn size(&self) -> Promise {
+        let store = self.store.clone();
+        future_to_promise(async move {
+            let len = store.len().await.map_err(to_js)?;
+            Ok(JsValue::from(len as u32))
+        })
+    }
+
+    /// Send every queued request to `endpoint`, oldest first; resolves to
+    /// the number delivered
+    pub fn sync(&self, endpoint: String, max_retries: u32) -> Promise {
+        let store = self.store.clone();
+        future_to_promise(async move {
+            let report = store
+                .sync(&endpoint, usize::MAX, max_retries)
+                .await
+                .map_err(to_js)?;
+            Ok(JsValue::from(report.synced.len() as u32))
+        })
+    }
+}
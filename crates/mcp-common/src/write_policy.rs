@@ -0,0 +1,89 @@
+//! Write policy for state persisted to local storage
+//!
+//! Flash media (eMMC, SD cards, SSDs) wear out with every erase cycle. When
+//! the gateway sees its storage degrading it relaxes the process-wide
+//! [`WritePolicy`]: periodic saves run less often and syncs to the medium are
+//! batched, so fewer, larger writes reach it. The cost is a wider window of
+//! writes lost on power failure, which is the better trade on a medium close
+//! to the end of its life.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// How hard components should try to spare the storage medium
+pub struct WritePolicy {
+    relaxation: AtomicU32,
+    writes: AtomicU64,
+}
+
+impl Default for WritePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WritePolicy {
+    pub fn new() -> Self {
+        Self {
+            relaxation: AtomicU32::new(1),
+            writes: AtomicU64::new(0),
+        }
+    }
+
+    /// The policy every component that persists state follows
+    pub fn global() -> &'static WritePolicy {
+        static GLOBAL: OnceLock<WritePolicy> = OnceLock::new();
+        GLOBAL.get_or_init(WritePolicy::new)
+    }
+
+    /// Factor writes are spread out by; 1 on healthy media
+    pub fn relaxation(&self) -> u32 {
+        self.relaxation.load(Ordering::Relaxed)
+    }
+
+    pub fn set_relaxation(&self, factor: u32) {
+        self.relaxation.store(factor.max(1), Ordering::Relaxed);
+    }
+
+    pub fn relaxed(&self) -> bool {
+        self.relaxation() > 1
+    }
+
+    /// Interval of a periodic save under the current policy
+    pub fn stretch(&self, interval: Duration) -> Duration {
+        interval.saturating_mul(self.relaxation())
+    }
+
+    /// Whether a write should be synced to the medium now; while relaxed,
+    /// one write in `relaxation` is, syncing the ones before it along
+    pub fn should_sync(&self) -> bool {
+        let relaxation = u64::from(self.relaxation());
+        relaxation <= 1 || self.writes.fetch_add(1, Ordering::Relaxed) % relaxation == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_healthy_policy_syncs_every_write() {
+        let policy = WritePolicy::new();
+        assert!(!policy.relaxed());
+        assert!((0..5).all(|_| policy.should_sync()));
+        assert_eq!(policy.stretch(Duration::from_secs(30)), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_relaxed_policy_batches_syncs_and_stretches_saves() {
+        let policy = WritePolicy::new();
+        policy.set_relaxation(4);
+        let synced = (0..12).filter(|_| policy.should_sync()).count();
+        assert_eq!(synced, 3);
+        assert_eq!(policy.stretch(Duration::from_secs(30)), Duration::from_secs(120));
+
+        policy.set_relaxation(0);
+        assert_eq!(policy.relaxation(), 1);
+    }
+}
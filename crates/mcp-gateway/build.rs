@@ -0,0 +1,15 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc();
+}
+
+/// Generate the gRPC service; protox parses the proto so protoc is not needed
+#[cfg(feature = "grpc")]
+fn grpc() {
+    const PROTO: &str = "proto/mcp_gateway.proto";
+    println!("cargo:rerun-if-changed={}", PROTO);
+    let descriptors = protox::compile([PROTO], ["proto"]).expect("Failed to parse the gRPC proto");
+    tonic_prost_build::configure()
+        .compile_fds(descriptors)
+        .expect("Failed to generate the gRPC service");
+}
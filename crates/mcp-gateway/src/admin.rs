@@ -0,0 +1,1072 @@
+//! Administrative HTTP endpoints for operators
+
+use axum::{
+    extract::{Json as ExtractJson, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+    routing::{get, post},
+    Router,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::admin_journal::{AdminOperation, ConfirmationRequired, CONFIRMATION_HEADER};
+use crate::artifacts::UploadRequest;
+use crate::auth;
+use crate::conversations::{ConversationPackage, ImportOptions};
+use crate::erasure::ErasureRequest;
+use crate::handlers::AppState;
+use crate::maintenance::MaintenanceRequest;
+use crate::retention::PurgeRequest;
+use crate::timeline::{TimelineCategory, TimelineQuery};
+use mcp_common::config::{ClusterMember, ModelRollout};
+use mcp_common::write_policy::WritePolicy;
+use mcp_common::{redaction, Error};
+use mcp_models::IngestRequest;
+use mcp_router::RolloutStatus;
+use mcp_security::{LiftRequest, RestrictRequest, RestrictionSource, RevokeRequest};
+
+/// Create the admin router, merged into the main router by `handlers::create_router`
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/v1/admin/maintenance",
+            get(maintenance_status)
+                .post(enable_maintenance)
+                .delete(disable_maintenance),
+        )
+        .route("/v1/admin/clock-skew", get(clock_skew_report))
+        .route("/v1/admin/clock-skew/{device_id}", get(device_clock_skew))
+        .route("/v1/admin/usage", get(tenant_usage))
+        .route("/v1/admin/priorities", get(priority_latency))
+        .route("/v1/admin/bandwidth", get(bandwidth_usage))
+        .route("/v1/admin/timeline", get(incident_timeline))
+        .route("/v1/admin/synthetic-probes", get(synthetic_probes))
+        .route("/v1/admin/models/canary", get(model_canary))
+        .route("/v1/admin/microphones", get(microphones))
+        .route("/v1/admin/config/reload", post(reload_config))
+        .route("/v1/admin/storage/health", get(storage_health).post(check_storage_health))
+        .route("/v1/admin/scheduler", get(scheduler_queues))
+        .route("/v1/admin/power", get(power_status))
+        .route("/v1/admin/queue/analytics", get(queue_analytics))
+        .route(
+            "/v1/admin/queue/dead-letters",
+            get(dead_letters).delete(purge_dead_letters),
+        )
+        .route(
+            "/v1/admin/queue/dead-letters/{request_id}",
+            axum::routing::delete(purge_dead_letter),
+        )
+        .route("/v1/admin/queue/dead-letters/{request_id}/requeue", post(requeue_dead_letter))
+        .route("/v1/admin/rollouts", get(model_rollouts))
+        .route(
+            "/v1/admin/rollouts/{model}",
+            axum::routing::put(set_rollout).delete(remove_rollout),
+        )
+        .route("/v1/admin/rollouts/{model}/split", axum::routing::put(set_rollout_split))
+        .route("/v1/admin/rollouts/{model}/promote", post(promote_rollout))
+        .route("/v1/admin/rollouts/{model}/rollback", post(rollback_rollout))
+        .route("/v1/admin/conversations", get(conversations))
+        .route("/v1/admin/conversations/import", post(import_conversation))
+        .route(
+            "/v1/admin/conversations/{session_id}",
+            get(conversation).delete(delete_conversation),
+        )
+        .route("/v1/admin/conversations/{session_id}/export", get(export_conversation))
+        .route("/v1/admin/kv", get(kv_namespaces))
+        .route("/v1/admin/kv/{namespace}", get(kv_keys).delete(clear_kv_namespace))
+        .route("/v1/admin/kv/{namespace}/{key}", get(kv_value).delete(delete_kv_key))
+        .route("/v1/admin/knowledge/ingest", post(ingest_document))
+        .route("/v1/admin/knowledge/scan", post(scan_knowledge))
+        .route("/v1/admin/knowledge/sources", get(knowledge_sources))
+        .route("/v1/admin/knowledge/index", get(knowledge_index))
+        .route("/v1/admin/knowledge/index/compact", post(compact_knowledge_index))
+        .route(
+            "/v1/admin/knowledge/sources/{*source}",
+            axum::routing::delete(remove_knowledge_source),
+        )
+        .route("/v1/admin/webhooks", get(webhook_stats))
+        .route("/v1/admin/connectors", get(connector_stats))
+        .route("/v1/admin/devices/restricted", get(restricted_devices))
+        .route(
+            "/v1/admin/devices/{device_id}/restriction",
+            post(restrict_device).delete(lift_restriction),
+        )
+        .route("/v1/admin/security/throttled", get(throttled_principals))
+        .route("/v1/admin/enrollment/devices", get(enrolled_devices))
+        .route("/v1/admin/enrollment/devices/{device_id}/revoke", post(revoke_device))
+        .route("/v1/admin/enrollment/denylist", get(enrollment_denylist))
+        .route("/v1/admin/keys", get(tenant_keys))
+        .route("/v1/admin/attestation", get(attestation_key))
+        .route("/v1/admin/attestation/quote", post(attestation_quote))
+        .route("/v1/admin/cluster", get(cluster_status))
+        .route("/v1/admin/cluster/members", axum::routing::put(update_cluster_members))
+        .route("/v1/admin/cluster/owners/{device_id}", get(device_owner))
+        .route("/v1/admin/retention", get(retention_status))
+        .route("/v1/admin/retention/purge", post(purge_now))
+        .route("/v1/admin/operations", get(admin_operations))
+        .route("/v1/admin/operations/{id}/undo", post(undo_admin_operation))
+        .route("/v1/admin/erasure", post(erase_subject))
+        .route("/v1/admin/compliance/report", get(compliance_report))
+        .route("/v1/admin/compliance/public-key", get(compliance_public_key))
+        .route("/v1/admin/artifacts", get(pending_artifacts))
+        .route("/v1/admin/artifacts/upload", post(upload_artifact))
+        .route_layer(axum::middleware::from_fn(auth::require_admin))
+}
+
+/// Get the current maintenance window
+pub async fn maintenance_status(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.maintenance().status().await)
+}
+
+/// Enter maintenance mode
+pub async fn enable_maintenance(
+    State(gateway): State<AppState>,
+    payload: Option<ExtractJson<MaintenanceRequest>>,
+) -> impl IntoResponse {
+    let request = payload.map(|ExtractJson(request)| request).unwrap_or_default();
+    info!("Maintenance mode requested via admin API");
+    Json(gateway.maintenance().enable(request).await)
+}
+
+/// Leave maintenance mode
+pub async fn disable_maintenance(State(gateway): State<AppState>) -> impl IntoResponse {
+    let was_active = gateway.maintenance().disable().await;
+    Json(serde_json::json!({
+        "status": "success",
+        "was_active": was_active,
+        "timestamp": chrono::Utc::now()
+    }))
+}
+
+/// List clock skew estimates for all tracked devices, most skewed first
+pub async fn clock_skew_report(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.clock_skew().devices().await)
+}
+
+/// Get the clock skew estimate for one device
+pub async fn device_clock_skew(
+    State(gateway): State<AppState>,
+    Path(device_id): Path<String>,
+) -> impl IntoResponse {
+    match gateway.clock_skew().device(&device_id).await {
+        Some(report) => Json(report).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Resource usage accumulated per tenant, for billing and spotting heavy prompts
+pub async fn tenant_usage(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.tenant_usage().await)
+}
+
+/// End-to-end latency per request priority, including time spent queued
+pub async fn priority_latency(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.priority_latency())
+}
+
+/// Bytes sent and received per subsystem, today, this month and per retained period
+pub async fn bandwidth_usage(State(gateway): State<AppState>) -> impl IntoResponse {
+    let usage = gateway.bandwidth().usage();
+    Json(serde_json::json!({
+        "today": usage.today(),
+        "this_month": usage.this_month(),
+        "days": usage.days,
+        "months": usage.months,
+    }))
+}
+
+/// Wake word checks and activations of each microphone
+pub async fn microphones(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({ "microphones": gateway.audio().stats() }))
+}
+
+/// Latest wear reports of the storage media and the write relaxation in effect
+pub async fn storage_health(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "media": gateway.storage_health().report(),
+        "write_relaxation": WritePolicy::global().relaxation(),
+    }))
+}
+
+/// Read the storage media's health now instead of at the next check
+pub async fn check_storage_health(State(gateway): State<AppState>) -> impl IntoResponse {
+    let media = gateway.storage_health().check().await;
+    Json(serde_json::json!({
+        "media": media,
+        "write_relaxation": WritePolicy::global().relaxation(),
+    }))
+}
+
+/// In-flight and queued local inferences of each model
+pub async fn scheduler_queues(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({ "models": gateway.scheduler().report() }))
+}
+
+/// Battery level and the power plan in effect
+pub async fn power_status(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.power().status())
+}
+
+/// Offline queue backlog age, flow rates and estimated time to drain
+pub async fn queue_analytics(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.queue_analytics().await {
+        Ok(Some(analytics)) => Json(analytics).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => request_error(e),
+    }
+}
+
+/// Requests the offline queue gave up on, most recent first
+pub async fn dead_letters(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.dead_letters().await {
+        Ok(dead_letters) => Json(dead_letters).into_response(),
+        Err(e) => request_error(e),
+    }
+}
+
+/// Move a dead-lettered request back into the queue with fresh retries
+pub async fn requeue_dead_letter(
+    State(gateway): State<AppState>,
+    Path(request_id): Path<uuid::Uuid>,
+) -> impl IntoResponse {
+    match gateway.requeue_dead_letter(request_id).await {
+        Ok(true) => {
+            info!("Admin requeued dead-lettered request {}", request_id);
+            Json(serde_json::json!({ "requeued": request_id })).into_response()
+        },
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => request_error(e),
+    }
+}
+
+/// Delete every dead-lettered request
+pub async fn purge_dead_letters(State(gateway): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    purge_dead_letters_matching(&gateway, PURGE_TARGET, &[], &headers).await
+}
+
+/// Delete one dead-lettered request
+pub async fn purge_dead_letter(
+    State(gateway): State<AppState>,
+    Path(request_id): Path<uuid::Uuid>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    purge_dead_letters_matching(&gateway, &request_id.to_string(), &[request_id], &headers).await
+}
+
+async fn purge_dead_letters_matching(
+    gateway: &AppState,
+    target: &str,
+    request_ids: &[uuid::Uuid],
+    headers: &HeaderMap,
+) -> axum::response::Response {
+    let journal = gateway.admin_journal();
+    if let Err(confirmation) = journal.confirm(AdminOperation::PurgeDeadLetters, target, confirmation_token(headers)) {
+        return confirmation_required(confirmation);
+    }
+    match gateway.purge_dead_letters(request_ids).await {
+        Ok(0) if !request_ids.is_empty() => StatusCode::NOT_FOUND.into_response(),
+        Ok(purged) => {
+            info!("Admin purged {} dead-lettered requests", purged);
+            journal.record(AdminOperation::PurgeDeadLetters, target);
+            Json(serde_json::json!({ "purged": purged })).into_response()
+        },
+        Err(e) => request_error(e),
+    }
+}
+
+/// Re-read the configuration file now instead of at the next check
+pub async fn reload_config(State(gateway): State<AppState>) -> impl IntoResponse {
+    info!("Configuration reload requested via admin API");
+    let manager = gateway.config_manager();
+    match manager.reload(|config| gateway.apply_config(config)).await {
+        Ok(report) => Json(serde_json::json!({
+            "version": manager.version(),
+            "reloaded": report,
+        }))
+        .into_response(),
+        Err(e @ Error::Configuration(_)) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+        Err(e) => request_error(e),
+    }
+}
+
+/// Latest outcome and failure streak of each synthetic probe
+pub async fn synthetic_probes(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "enabled": gateway.synthetic_probes().enabled(),
+        "probes": gateway.synthetic_probes().statuses(),
+    }))
+}
+
+/// Latest canary evaluation of each local model
+pub async fn model_canary(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "enabled": gateway.model_canary().enabled(),
+        "models": gateway.model_canary().reports(),
+    }))
+}
+
+/// Time range, categories and format of an incident timeline export
+#[derive(Debug, Deserialize)]
+pub struct TimelineRequest {
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Comma-separated categories, e.g. `alert,recovery`
+    category: Option<String>,
+    /// `json` (default), `jsonl` for one entry per line, or `text`
+    format: Option<String>,
+}
+
+/// Alerts, scaling, recovery attempts, configuration changes and error
+/// spikes of a time range, correlated into incidents
+pub async fn incident_timeline(
+    State(gateway): State<AppState>,
+    Query(request): Query<TimelineRequest>,
+) -> impl IntoResponse {
+    let mut categories = Vec::new();
+    for name in request.category.iter().flat_map(|names| names.split(',')) {
+        match TimelineCategory::parse(name) {
+            Some(category) => categories.push(category),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": format!("Unknown timeline category: {}", name) })),
+                )
+                    .into_response()
+            },
+        }
+    }
+    let timeline = gateway.timeline().query(&TimelineQuery {
+        since: request.since,
+        until: request.until,
+        categories,
+    });
+    match request.format.as_deref() {
+        Some("jsonl") => {
+            let lines: Vec<String> = timeline
+                .entries
+                .iter()
+                .filter_map(|entry| serde_json::to_string(entry).ok())
+                .collect();
+            ([(axum::http::header::CONTENT_TYPE, "application/x-ndjson")], lines.join("\n")).into_response()
+        },
+        Some("text") => timeline.to_text().into_response(),
+        _ => Json(timeline).into_response(),
+    }
+}
+
+/// Model version rollouts with the requests routed to each version
+pub async fn model_rollouts(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.rollouts().list())
+}
+
+/// Versions of a model and the share of devices its candidate gets
+#[derive(Debug, Deserialize)]
+pub struct RolloutRequest {
+    stable: String,
+    #[serde(default)]
+    candidate: Option<String>,
+    #[serde(default)]
+    candidate_percent: u8,
+}
+
+/// Start or replace a rollout; its versions are loaded before it applies
+pub async fn set_rollout(
+    State(gateway): State<AppState>,
+    Path(model): Path<String>,
+    ExtractJson(request): ExtractJson<RolloutRequest>,
+) -> impl IntoResponse {
+    let rollout = ModelRollout {
+        model,
+        stable: request.stable,
+        candidate: request.candidate,
+        candidate_percent: request.candidate_percent,
+    };
+    rollout_response(gateway.set_rollout(rollout).await)
+}
+
+/// Share of devices a rollout sends to its candidate
+#[derive(Debug, Deserialize)]
+pub struct SplitRequest {
+    candidate_percent: u8,
+}
+
+/// Change a rollout's split
+pub async fn set_rollout_split(
+    State(gateway): State<AppState>,
+    Path(model): Path<String>,
+    ExtractJson(request): ExtractJson<SplitRequest>,
+) -> impl IntoResponse {
+    rollout_response(gateway.set_rollout_split(&model, request.candidate_percent).await)
+}
+
+/// Make a rollout's candidate its stable version
+pub async fn promote_rollout(State(gateway): State<AppState>, Path(model): Path<String>) -> impl IntoResponse {
+    rollout_response(gateway.promote_rollout(&model))
+}
+
+/// Drop a rollout's candidate
+pub async fn rollback_rollout(State(gateway): State<AppState>, Path(model): Path<String>) -> impl IntoResponse {
+    rollout_response(gateway.rollback_rollout(&model))
+}
+
+/// Stop splitting a model's traffic
+pub async fn remove_rollout(State(gateway): State<AppState>, Path(model): Path<String>) -> impl IntoResponse {
+    match gateway.remove_rollout(&model) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => request_error(e),
+    }
+}
+
+fn rollout_response(result: mcp_common::Result<RolloutStatus>) -> axum::response::Response {
+    match result {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => request_error(e),
+    }
+}
+
+fn request_error(e: Error) -> axum::response::Response {
+    let status = match e {
+        Error::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+}
+
+/// Recorded conversations, most recently updated first
+pub async fn conversations(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.conversations().list().await {
+        Ok(conversations) => Json(conversations).into_response(),
+        Err(e) => request_error(e),
+    }
+}
+
+/// One session's conversation
+pub async fn conversation(State(gateway): State<AppState>, Path(session_id): Path<String>) -> impl IntoResponse {
+    match gateway.conversations().get(&session_id).await {
+        Ok(Some(conversation)) => Json(conversation).into_response(),
+        Ok(None) => conversation_not_found(&session_id),
+        Err(e) => request_error(e),
+    }
+}
+
+/// Package a session's conversation for another gateway
+pub async fn export_conversation(
+    State(gateway): State<AppState>,
+    Path(session_id): Path<String>,
+) -> impl IntoResponse {
+    match gateway.conversations().export(&session_id).await {
+        Ok(Some(package)) => {
+            info!("Admin exported conversation {}", session_id);
+            Json(package).into_response()
+        },
+        Ok(None) => conversation_not_found(&session_id),
+        Err(e) => request_error(e),
+    }
+}
+
+/// A package to import with how to store it
+#[derive(Debug, Deserialize)]
+pub struct ImportConversationRequest {
+    pub package: ConversationPackage,
+    #[serde(flatten)]
+    pub options: ImportOptions,
+}
+
+/// Import a conversation exported by this or another gateway
+pub async fn import_conversation(
+    State(gateway): State<AppState>,
+    ExtractJson(request): ExtractJson<ImportConversationRequest>,
+) -> impl IntoResponse {
+    match gateway.conversations().import(request.package, request.options).await {
+        Ok(conversation) => {
+            info!("Admin imported conversation {}", conversation.session_id);
+            (
+                StatusCode::CREATED,
+                Json(serde_json::json!({
+                    "session_id": conversation.session_id,
+                    "device_id": conversation.device_id,
+                    "messages": conversation.messages.len(),
+                })),
+            )
+                .into_response()
+        },
+        Err(e) => request_error(e),
+    }
+}
+
+/// Delete a session's conversation
+pub async fn delete_conversation(
+    State(gateway): State<AppState>,
+    Path(session_id): Path<String>,
+) -> impl IntoResponse {
+    match gateway.admin_journal().delete_conversation(&session_id).await {
+        Ok((deleted, operation)) => {
+            info!("Admin deleted conversation {}", session_id);
+            Json(serde_json::json!({ "deleted": deleted, "operation": operation })).into_response()
+        },
+        Err(e) => request_error(e),
+    }
+}
+
+fn conversation_not_found(session_id: &str) -> axum::response::Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": format!("No conversation for session {}", session_id) })),
+    )
+        .into_response()
+}
+
+/// Key-value namespaces with their size and key counts against their limits
+pub async fn kv_namespaces(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.kv().namespaces())
+}
+
+/// Keys in a namespace with their sizes and expiry, without values
+pub async fn kv_keys(State(gateway): State<AppState>, Path(namespace): Path<String>) -> impl IntoResponse {
+    Json(gateway.kv().keys(&namespace))
+}
+
+/// One value, base64-encoded
+pub async fn kv_value(
+    State(gateway): State<AppState>,
+    Path((namespace, key)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match gateway.kv().get(&namespace, &key) {
+        Some(value) => Json(serde_json::json!({
+            "namespace": namespace,
+            "key": key,
+            "value": BASE64.encode(value),
+        }))
+        .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": format!("No key {} in namespace {}", key, namespace) })),
+        )
+            .into_response(),
+    }
+}
+
+/// Delete one key
+pub async fn delete_kv_key(
+    State(gateway): State<AppState>,
+    Path((namespace, key)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let (deleted, operation) = gateway.admin_journal().delete_kv_key(&namespace, &key);
+    info!("Admin deleted key {} from key-value namespace {}", key, namespace);
+    Json(serde_json::json!({ "deleted": deleted, "operation": operation }))
+}
+
+/// Delete every key in a namespace
+pub async fn clear_kv_namespace(State(gateway): State<AppState>, Path(namespace): Path<String>) -> impl IntoResponse {
+    let (deleted, operation) = gateway.admin_journal().clear_kv_namespace(&namespace);
+    info!("Admin cleared key-value namespace {} ({} keys)", namespace, deleted);
+    Json(serde_json::json!({ "deleted": deleted, "operation": operation }))
+}
+
+/// Ingest a document from gateway storage or from uploaded content
+pub async fn ingest_document(
+    State(gateway): State<AppState>,
+    ExtractJson(request): ExtractJson<IngestRequest>,
+) -> impl IntoResponse {
+    match gateway.ingestion().ingest_request(request).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Rescan the ingestion watch directories now
+pub async fn scan_knowledge(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.ingestion().scan().await)
+}
+
+/// List ingested sources
+pub async fn knowledge_sources(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.ingestion().sources().await)
+}
+
+/// Remove an ingested source and its chunks
+pub async fn remove_knowledge_source(
+    State(gateway): State<AppState>,
+    Path(source): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let journal = gateway.admin_journal();
+    if let Err(confirmation) = journal.confirm(
+        AdminOperation::RemoveKnowledgeSource,
+        &source,
+        confirmation_token(&headers),
+    ) {
+        return confirmation_required(confirmation);
+    }
+    match gateway.ingestion().remove_source(&source).await {
+        Some(report) => {
+            journal.record(AdminOperation::RemoveKnowledgeSource, &source);
+            Json(report).into_response()
+        },
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Get retrieval index size, fragmentation and the last maintenance report
+pub async fn knowledge_index(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "stats": gateway.retriever().stats().await,
+        "last_maintenance": gateway.index_maintainer().last_report().await,
+    }))
+}
+
+/// Rebuild the retrieval index now
+pub async fn compact_knowledge_index(State(gateway): State<AppState>) -> impl IntoResponse {
+    info!("Retrieval index compaction requested via admin API");
+    match gateway.index_maintainer().run(true).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Delivery counters for each configured webhook
+pub async fn webhook_stats(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.webhooks().stats().await)
+}
+
+/// Publish counters for each message broker connector
+pub async fn connector_stats(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.connectors().stats())
+}
+
+fn restrictions_unsupported() -> axum::response::Response {
+    (
+        StatusCode::NOT_IMPLEMENTED,
+        Json(serde_json::json!({ "error": "Security manager does not support restricted mode" })),
+    )
+        .into_response()
+}
+
+/// Devices currently in restricted mode
+pub async fn restricted_devices(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.security().restrictions() {
+        Some(restrictions) => Json(restrictions.list().await).into_response(),
+        None => restrictions_unsupported(),
+    }
+}
+
+/// Switch a device to restricted mode
+pub async fn restrict_device(
+    State(gateway): State<AppState>,
+    Path(device_id): Path<String>,
+    payload: Option<ExtractJson<RestrictRequest>>,
+) -> impl IntoResponse {
+    let Some(restrictions) = gateway.security().restrictions() else {
+        return restrictions_unsupported();
+    };
+    let request = payload.map(|ExtractJson(request)| request).unwrap_or_default();
+    let reason = request.reason.unwrap_or_else(|| "Restricted by operator".to_string());
+    let restriction = restrictions
+        .restrict(&device_id, &reason, request.duration_secs, RestrictionSource::Admin)
+        .await;
+    Json(restriction).into_response()
+}
+
+/// Lift a restriction, optionally exempting the device from automatic restriction
+pub async fn lift_restriction(
+    State(gateway): State<AppState>,
+    Path(device_id): Path<String>,
+    Query(request): Query<LiftRequest>,
+) -> impl IntoResponse {
+    let Some(restrictions) = gateway.security().restrictions() else {
+        return restrictions_unsupported();
+    };
+    let was_restricted = restrictions.lift(&device_id, request.exempt_secs).await;
+    info!("Restriction on device {} lifted via admin API", redaction::id(&device_id));
+    Json(serde_json::json!({
+        "device_id": device_id,
+        "was_restricted": was_restricted,
+        "exempt_secs": request.exempt_secs,
+    }))
+    .into_response()
+}
+
+/// Devices and addresses locked out or accumulating authentication failures
+pub async fn throttled_principals(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.security().auth_guard() {
+        Some(auth_guard) => Json(auth_guard.throttled().await).into_response(),
+        None => (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Security manager does not track authentication failures" })),
+        )
+            .into_response(),
+    }
+}
+
+pub(crate) fn enrollment_disabled() -> axum::response::Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "error": "Device enrollment is not enabled" })),
+    )
+        .into_response()
+}
+
+/// Enrolled devices and their current certificates
+pub async fn enrolled_devices(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.security().enrollment() {
+        Some(enrollment) => Json(enrollment.devices().await).into_response(),
+        None => enrollment_disabled(),
+    }
+}
+
+/// Decommission a device, adding its certificate to the denylist
+pub async fn revoke_device(
+    State(gateway): State<AppState>,
+    Path(device_id): Path<String>,
+    headers: HeaderMap,
+    payload: Option<ExtractJson<RevokeRequest>>,
+) -> impl IntoResponse {
+    let Some(enrollment) = gateway.security().enrollment() else {
+        return enrollment_disabled();
+    };
+    let journal = gateway.admin_journal();
+    if let Err(confirmation) =
+        journal.confirm(AdminOperation::RevokeDevice, &device_id, confirmation_token(&headers))
+    {
+        return confirmation_required(confirmation);
+    }
+    let request = payload.map(|ExtractJson(request)| request).unwrap_or_default();
+    let reason = request.reason.unwrap_or_else(|| "Decommissioned by operator".to_string());
+    match enrollment.revoke(&device_id, &reason).await {
+        Ok(true) => {
+            info!("Device {} revoked via admin API", redaction::id(&device_id));
+            journal.record(AdminOperation::RevokeDevice, &device_id);
+            Json(enrollment.device(&device_id).await).into_response()
+        },
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": format!("Device {} is not enrolled", device_id) })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Revoked certificate serials, for TLS terminators and audits
+pub async fn enrollment_denylist(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.security().enrollment() {
+        Some(enrollment) => Json(serde_json::json!({
+            "issuer": enrollment.config().ca_name,
+            "generated_at": chrono::Utc::now(),
+            "revoked": enrollment.revocations().await,
+        }))
+        .into_response(),
+        None => enrollment_disabled(),
+    }
+}
+
+/// Tenant data keys, the active algorithm and the key usage audit trail
+pub async fn tenant_keys(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.security().keyring() {
+        Some(keyring) => Json(serde_json::json!({
+            "algorithm": keyring.algorithm(),
+            "aes_hardware": mcp_security::has_aes_hardware(),
+            "keys": keyring.keys().await,
+            "audit": keyring.audit_trail().await,
+        }))
+        .into_response(),
+        None => (
+            StatusCode::NOT_IMPLEMENTED,
+            Json(serde_json::json!({ "error": "Security manager does not use tenant keys" })),
+        )
+            .into_response(),
+    }
+}
+
+fn attestation_unavailable() -> axum::response::Response {
+    (
+        StatusCode::NOT_IMPLEMENTED,
+        Json(serde_json::json!({ "error": "Security manager does not hold a device key" })),
+    )
+        .into_response()
+}
+
+/// Device key and whether a TPM holds it
+pub async fn attestation_key(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.security().attestation() {
+        Some(attestation) => Json(serde_json::json!({
+            "provider": attestation.provider(),
+            "simulated": attestation.simulated(),
+            "key_id": attestation.key_id(),
+            "algorithm": "ECDSA-P256-SHA256",
+            "public_key": BASE64.encode(attestation.public_key()),
+        }))
+        .into_response(),
+        None => attestation_unavailable(),
+    }
+}
+
+/// Nonce a verifier wants included in a quote
+#[derive(Debug, Deserialize)]
+pub struct QuoteRequest {
+    /// Base64, at most 64 bytes
+    nonce: String,
+}
+
+/// Quote the platform PCRs over a verifier's nonce
+pub async fn attestation_quote(
+    State(gateway): State<AppState>,
+    ExtractJson(request): ExtractJson<QuoteRequest>,
+) -> impl IntoResponse {
+    let Some(attestation) = gateway.security().attestation() else {
+        return attestation_unavailable();
+    };
+    let Ok(nonce) = BASE64.decode(&request.nonce) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "nonce must be base64" })),
+        )
+            .into_response();
+    };
+    match attestation.quote(&nonce).await {
+        Ok(quote) => Json(quote).into_response(),
+        Err(e) => {
+            let status = match e {
+                Error::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+        },
+    }
+}
+
+/// Cluster members, their share of devices and proxy counters
+pub async fn cluster_status(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.cluster().status())
+}
+
+/// Member currently owning a device
+pub async fn device_owner(State(gateway): State<AppState>, Path(device_id): Path<String>) -> impl IntoResponse {
+    Json(gateway.cluster().ownership(&device_id))
+}
+
+/// Replace this gateway's view of cluster membership
+pub async fn update_cluster_members(
+    State(gateway): State<AppState>,
+    ExtractJson(members): ExtractJson<Vec<ClusterMember>>,
+) -> impl IntoResponse {
+    match gateway.cluster().set_members(members) {
+        Ok(()) => Json(gateway.cluster().status()).into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Retention policies, legal holds and the last purge
+pub async fn retention_status(State(gateway): State<AppState>) -> impl IntoResponse {
+    let retention = gateway.retention();
+    Json(serde_json::json!({
+        "policies": retention.config(),
+        "last_purge": retention.last_report().await,
+    }))
+}
+
+/// Purge data past retention now, or report what would be purged with `dry_run`
+pub async fn purge_now(
+    State(gateway): State<AppState>,
+    headers: HeaderMap,
+    payload: Option<ExtractJson<PurgeRequest>>,
+) -> impl IntoResponse {
+    let request = payload.map(|ExtractJson(request)| request).unwrap_or_default();
+    let journal = gateway.admin_journal();
+    if !request.dry_run {
+        if let Err(confirmation) =
+            journal.confirm(AdminOperation::RetentionPurge, PURGE_TARGET, confirmation_token(&headers))
+        {
+            return confirmation_required(confirmation);
+        }
+    }
+    info!("Retention purge requested via admin API (dry run: {})", request.dry_run);
+    match gateway.retention().purge(request.dry_run).await {
+        Ok(report) => {
+            if !request.dry_run {
+                journal.record(AdminOperation::RetentionPurge, PURGE_TARGET);
+            }
+            Json(report).into_response()
+        },
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Erase all data stored for a device, session or tenant and return the deletion manifest
+pub async fn erase_subject(
+    State(gateway): State<AppState>,
+    headers: HeaderMap,
+    ExtractJson(request): ExtractJson<ErasureRequest>,
+) -> impl IntoResponse {
+    let journal = gateway.admin_journal();
+    let target = serde_json::to_string(&request.subject).unwrap_or_default();
+    let dry_run = request.dry_run;
+    if !dry_run {
+        if let Err(confirmation) = journal.confirm(AdminOperation::Erasure, &target, confirmation_token(&headers)) {
+            return confirmation_required(confirmation);
+        }
+    }
+    info!(
+        "Erasure requested via admin API for {:?} (dry run: {})",
+        request.subject, request.dry_run
+    );
+    match gateway.erasure().erase(request).await {
+        Ok(manifest) => {
+            if !dry_run {
+                journal.record(AdminOperation::Erasure, &target);
+            }
+            Json(manifest).into_response()
+        },
+        Err(e) => {
+            let status = match e {
+                Error::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+                // Legal hold
+                Error::Security(_) => StatusCode::CONFLICT,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+        },
+    }
+}
+
+/// Target journaled for purges that are not limited to some entries
+const PURGE_TARGET: &str = "all";
+
+/// Destructive admin operations, most recent first
+pub async fn admin_operations(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.admin_journal().entries())
+}
+
+/// Restore what a journaled operation deleted, within its undo window
+pub async fn undo_admin_operation(State(gateway): State<AppState>, Path(id): Path<uuid::Uuid>) -> impl IntoResponse {
+    match gateway.admin_journal().undo(id).await {
+        Ok(Some(entry)) => Json(entry).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": format!("No operation {} in the journal", id) })),
+        )
+            .into_response(),
+        Err(e) => (StatusCode::CONFLICT, Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+fn confirmation_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(CONFIRMATION_HEADER).and_then(|token| token.to_str().ok())
+}
+
+fn confirmation_required(confirmation: ConfirmationRequired) -> axum::response::Response {
+    (
+        StatusCode::PRECONDITION_REQUIRED,
+        Json(serde_json::json!({
+            "error": format!(
+                "{:?} on {} cannot be undone; repeat the request with the {} header to confirm",
+                confirmation.operation, confirmation.target, CONFIRMATION_HEADER
+            ),
+            "confirmation": confirmation,
+        })),
+    )
+        .into_response()
+}
+
+/// Report format, `?format=markdown` for review documents
+#[derive(Debug, Default, Deserialize)]
+pub struct ReportQuery {
+    #[serde(default)]
+    format: Option<String>,
+}
+
+/// Generate a signed compliance report
+pub async fn compliance_report(
+    State(gateway): State<AppState>,
+    Query(query): Query<ReportQuery>,
+) -> impl IntoResponse {
+    match gateway.compliance().generate(&gateway).await {
+        Ok(signed) if query.format.as_deref() == Some("markdown") => (
+            [(axum::http::header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            signed.report.to_markdown(),
+        )
+            .into_response(),
+        Ok(signed) => {
+            info!("Compliance report {} generated via admin API", signed.report.report_id);
+            Json(signed).into_response()
+        },
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Key compliance reports are signed with
+pub async fn compliance_public_key(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.compliance().public_key().await {
+        Ok(public_key) => Json(serde_json::json!({ "algorithm": "Ed25519", "public_key": public_key })).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+fn artifacts_not_configured() -> axum::response::Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "error": "Artifact storage is not configured" })),
+    )
+        .into_response()
+}
+
+/// Upload a file to object storage, resuming an interrupted upload
+pub async fn upload_artifact(
+    State(gateway): State<AppState>,
+    ExtractJson(request): ExtractJson<UploadRequest>,
+) -> impl IntoResponse {
+    let Some(artifacts) = gateway.artifacts() else {
+        return artifacts_not_configured();
+    };
+    match artifacts.upload(request).await {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Keys of interrupted multipart uploads waiting to be resumed
+pub async fn pending_artifacts(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.artifacts() {
+        Some(artifacts) => Json(serde_json::json!({ "pending": artifacts.pending_uploads().await })).into_response(),
+        None => artifacts_not_configured(),
+    }
+}
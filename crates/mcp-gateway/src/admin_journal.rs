@@ -0,0 +1,394 @@
+//! Journal of destructive admin operations
+//!
+//! With `gateway.admin_safety` enabled, every destructive admin operation is
+//! recorded in the [`AdminJournal`]. Deleted conversations and key-value
+//! entries are kept for `undo_window_secs` so the deletion can be undone;
+//! operations that cannot be undone (purges, erasure, device revocation,
+//! knowledge source removal) only run when the request carries a
+//! confirmation token issued by an earlier, unconfirmed attempt at the same
+//! operation on the same target.
+
+use chrono::{DateTime, Utc};
+use mcp_common::clock::Clock;
+use mcp_common::config::AdminSafetyConfig;
+use mcp_common::{Error, Result};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::conversations::{ConversationPackage, ConversationStore, ImportOptions};
+use crate::kv::{KvStore, RemovedKey};
+
+/// Header carrying the token that confirms an irreversible operation
+pub const CONFIRMATION_HEADER: &str = "x-confirmation-token";
+
+/// Destructive admin operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminOperation {
+    DeleteConversation,
+    DeleteKvKey,
+    ClearKvNamespace,
+    RemoveKnowledgeSource,
+    RevokeDevice,
+    RetentionPurge,
+    Erasure,
+    PurgeDeadLetters,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    Applied,
+    Undone,
+}
+
+/// An operation in the journal
+#[derive(Debug, Clone, Serialize)]
+pub struct JournalEntry {
+    pub id: Uuid,
+    pub operation: AdminOperation,
+    pub target: String,
+    pub applied_at: DateTime<Utc>,
+    pub status: OperationStatus,
+    /// Until when the operation can be undone; `None` when it never could
+    pub undo_until: Option<DateTime<Utc>>,
+    pub undoable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undone_at: Option<DateTime<Utc>>,
+}
+
+/// An irreversible operation waiting to be repeated with its token
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfirmationRequired {
+    pub operation: AdminOperation,
+    pub target: String,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What was deleted, kept until the undo window closes
+enum Snapshot {
+    Conversation(ConversationPackage),
+    Keys { namespace: String, keys: Vec<RemovedKey> },
+}
+
+struct Record {
+    entry: JournalEntry,
+    snapshot: Option<Snapshot>,
+}
+
+/// Soft deletes, undo and confirmation tokens for the admin API
+pub struct AdminJournal {
+    config: AdminSafetyConfig,
+    kv: Arc<KvStore>,
+    conversations: Arc<ConversationStore>,
+    clock: Arc<dyn Clock>,
+    /// Oldest first
+    records: Mutex<VecDeque<Record>>,
+    confirmations: Mutex<HashMap<String, ConfirmationRequired>>,
+}
+
+impl AdminJournal {
+    pub fn new(
+        config: AdminSafetyConfig,
+        kv: Arc<KvStore>,
+        conversations: Arc<ConversationStore>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            config,
+            kv,
+            conversations,
+            clock,
+            records: Mutex::new(VecDeque::new()),
+            confirmations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Let an irreversible operation run when `token` was issued for it,
+    /// otherwise issue a token for the caller to repeat the request with.
+    /// Tokens are single-use.
+    pub fn confirm(
+        &self,
+        operation: AdminOperation,
+        target: &str,
+        token: Option<&str>,
+    ) -> std::result::Result<(), ConfirmationRequired> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let now = self.clock.now();
+        let mut confirmations = self.confirmations.lock();
+        confirmations.retain(|_, confirmation| confirmation.expires_at > now);
+        if let Some(token) = token {
+            let matches = confirmations
+                .get(token)
+                .is_some_and(|confirmation| confirmation.operation == operation && confirmation.target == target);
+            if matches {
+                confirmations.remove(token);
+                return Ok(());
+            }
+        }
+        let confirmation = ConfirmationRequired {
+            operation,
+            target: target.to_string(),
+            token: Uuid::new_v4().simple().to_string(),
+            expires_at: now + chrono::Duration::seconds(self.config.confirmation_ttl_secs as i64),
+        };
+        confirmations.insert(confirmation.token.clone(), confirmation.clone());
+        Err(confirmation)
+    }
+
+    /// Journal an operation that cannot be undone
+    pub fn record(&self, operation: AdminOperation, target: &str) -> Option<JournalEntry> {
+        self.push(operation, target, None)
+    }
+
+    /// Delete a session's conversation, keeping it for the undo window.
+    /// Returns whether there was one and its journal entry.
+    pub async fn delete_conversation(&self, session_id: &str) -> Result<(bool, Option<JournalEntry>)> {
+        if !self.config.enabled {
+            return Ok((self.conversations.delete(session_id).await?, None));
+        }
+        let Some(package) = self.conversations.export(session_id).await? else {
+            return Ok((false, None));
+        };
+        let deleted = self.conversations.delete(session_id).await?;
+        let entry = self.push(
+            AdminOperation::DeleteConversation,
+            session_id,
+            Some(Snapshot::Conversation(package)),
+        );
+        Ok((deleted, entry))
+    }
+
+    /// Delete a key, keeping it for the undo window
+    pub fn delete_kv_key(&self, namespace: &str, key: &str) -> (bool, Option<JournalEntry>) {
+        let Some(removed) = self.kv.remove(namespace, key) else {
+            return (false, None);
+        };
+        let snapshot = Snapshot::Keys {
+            namespace: namespace.to_string(),
+            keys: vec![removed],
+        };
+        (true, self.push(AdminOperation::DeleteKvKey, &format!("{}/{}", namespace, key), Some(snapshot)))
+    }
+
+    /// Delete every key in a namespace, keeping them for the undo window.
+    /// Returns how many keys were deleted.
+    pub fn clear_kv_namespace(&self, namespace: &str) -> (usize, Option<JournalEntry>) {
+        if !self.config.enabled {
+            return (self.kv.clear(namespace), None);
+        }
+        let keys = self.kv.drain(namespace);
+        if keys.is_empty() {
+            return (0, None);
+        }
+        let deleted = keys.len();
+        let snapshot = Snapshot::Keys {
+            namespace: namespace.to_string(),
+            keys,
+        };
+        (deleted, self.push(AdminOperation::ClearKvNamespace, namespace, Some(snapshot)))
+    }
+
+    /// Restore what an operation deleted. `None` when the operation is not
+    /// in the journal; fails once it is undone or its undo window closed.
+    pub async fn undo(&self, id: Uuid) -> Result<Option<JournalEntry>> {
+        let now = self.clock.now();
+        let snapshot = {
+            let mut records = self.records.lock();
+            self.expire(&mut records, now);
+            let Some(record) = records.iter_mut().find(|record| record.entry.id == id) else {
+                return Ok(None);
+            };
+            match record.snapshot.take() {
+                Some(snapshot) => snapshot,
+                None => {
+                    return Err(Error::InvalidRequest(match record.entry.status {
+                        OperationStatus::Undone => format!("Operation {} is already undone", id),
+                        OperationStatus::Applied if record.entry.undo_until.is_some() => {
+                            format!("The undo window of operation {} has closed", id)
+                        },
+                        OperationStatus::Applied => format!("Operation {} cannot be undone", id),
+                    }))
+                },
+            }
+        };
+
+        let restored = match &snapshot {
+            Snapshot::Conversation(package) => self
+                .conversations
+                .import(package.clone(), ImportOptions::default())
+                .await
+                .map(|_| ()),
+            Snapshot::Keys { namespace, keys } => self.restore_keys(namespace, keys, now),
+        };
+
+        let mut records = self.records.lock();
+        let Some(record) = records.iter_mut().find(|record| record.entry.id == id) else {
+            return restored.map(|()| None);
+        };
+        if let Err(e) = restored {
+            // Keep the snapshot so the undo can be retried
+            record.snapshot = Some(snapshot);
+            return Err(e);
+        }
+        record.entry.status = OperationStatus::Undone;
+        record.entry.undoable = false;
+        record.entry.undone_at = Some(now);
+        info!("Undid admin operation {} on {}", id, record.entry.target);
+        Ok(Some(record.entry.clone()))
+    }
+
+    /// Journaled operations, most recent first
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        let mut records = self.records.lock();
+        self.expire(&mut records, self.clock.now());
+        records.iter().rev().map(|record| record.entry.clone()).collect()
+    }
+
+    fn restore_keys(&self, namespace: &str, keys: &[RemovedKey], now: DateTime<Utc>) -> Result<()> {
+        for removed in keys {
+            let ttl = match removed.expires_at {
+                Some(expires_at) => match (expires_at - now).to_std() {
+                    Ok(ttl) if !ttl.is_zero() => Some(ttl),
+                    // Expired since it was deleted
+                    _ => continue,
+                },
+                None => None,
+            };
+            self.kv.set(namespace, &removed.key, removed.value.clone(), ttl)?;
+        }
+        Ok(())
+    }
+
+    fn push(&self, operation: AdminOperation, target: &str, snapshot: Option<Snapshot>) -> Option<JournalEntry> {
+        if !self.config.enabled {
+            return None;
+        }
+        let applied_at = self.clock.now();
+        let entry = JournalEntry {
+            id: Uuid::new_v4(),
+            operation,
+            target: target.to_string(),
+            applied_at,
+            status: OperationStatus::Applied,
+            undo_until: snapshot
+                .as_ref()
+                .map(|_| applied_at + chrono::Duration::seconds(self.config.undo_window_secs as i64)),
+            undoable: snapshot.is_some(),
+            undone_at: None,
+        };
+        info!("Admin operation {:?} on {} journaled as {}", operation, target, entry.id);
+        let mut records = self.records.lock();
+        records.push_back(Record {
+            entry: entry.clone(),
+            snapshot,
+        });
+        while records.len() > self.config.journal_size {
+            records.pop_front();
+        }
+        Some(entry)
+    }
+
+    /// Drop the snapshots of operations whose undo window closed
+    fn expire(&self, records: &mut VecDeque<Record>, now: DateTime<Utc>) {
+        for record in records.iter_mut() {
+            if record.entry.undo_until.is_some_and(|undo_until| undo_until <= now) {
+                record.snapshot = None;
+                record.entry.undoable = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::clock::FakeClock;
+    use mcp_common::config::{Config, KvStoreConfig};
+    use mcp_common::vfs::MemoryVfs;
+    use std::time::Duration;
+
+    fn journal(clock: Arc<FakeClock>) -> (AdminJournal, Arc<KvStore>) {
+        let storage = Arc::new(MemoryVfs::new());
+        let kv = Arc::new(KvStore::with_clock(KvStoreConfig::default(), storage.clone(), clock.clone()));
+        let conversations = Arc::new(ConversationStore::with_clock(&Config::default(), storage, clock.clone()));
+        let journal = AdminJournal::new(AdminSafetyConfig::default(), kv.clone(), conversations, clock);
+        (journal, kv)
+    }
+
+    #[tokio::test]
+    async fn test_cleared_namespace_is_restored_by_undo() {
+        let clock = Arc::new(FakeClock::default());
+        let (journal, kv) = journal(clock.clone());
+        kv.set("tools", "count", b"3".to_vec(), None).unwrap();
+        kv.set("tools", "token", b"abc".to_vec(), Some(Duration::from_secs(60))).unwrap();
+
+        let (deleted, entry) = journal.clear_kv_namespace("tools");
+        let entry = entry.unwrap();
+        assert_eq!((deleted, entry.undoable), (2, true));
+        assert!(kv.keys("tools").is_empty());
+
+        clock.advance(chrono::Duration::seconds(30));
+        let undone = journal.undo(entry.id).await.unwrap().unwrap();
+        assert_eq!(undone.status, OperationStatus::Undone);
+        assert_eq!(kv.get("tools", "count"), Some(b"3".to_vec()));
+        // The TTL keeps running from before the deletion
+        assert_eq!(
+            kv.keys("tools")[1].expires_at,
+            Some(FakeClock::default().now() + chrono::Duration::seconds(60))
+        );
+        assert!(journal.undo(entry.id).await.is_err());
+        assert!(journal.undo(Uuid::new_v4()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_undo_window_closes() {
+        let clock = Arc::new(FakeClock::default());
+        let (journal, kv) = journal(clock.clone());
+        kv.set("tools", "count", b"3".to_vec(), None).unwrap();
+        let entry = journal.delete_kv_key("tools", "count").1.unwrap();
+        assert_eq!(entry.target, "tools/count");
+
+        clock.advance(chrono::Duration::seconds(15 * 60));
+        assert!(!journal.entries()[0].undoable);
+        let error = journal.undo(entry.id).await.unwrap_err();
+        assert!(error.to_string().contains("undo window"), "{}", error);
+        assert_eq!(kv.get("tools", "count"), None);
+    }
+
+    #[test]
+    fn test_confirmation_tokens_are_bound_single_use_and_expire() {
+        let clock = Arc::new(FakeClock::default());
+        let (journal, _) = journal(clock.clone());
+        let first = journal.confirm(AdminOperation::RevokeDevice, "kiosk-1", None).unwrap_err();
+
+        // A token only confirms the operation and target it was issued for
+        assert!(journal
+            .confirm(AdminOperation::RevokeDevice, "kiosk-2", Some(&first.token))
+            .is_err());
+        assert!(journal.confirm(AdminOperation::Erasure, "kiosk-1", Some(&first.token)).is_err());
+        journal
+            .confirm(AdminOperation::RevokeDevice, "kiosk-1", Some(&first.token))
+            .unwrap();
+        assert!(journal
+            .confirm(AdminOperation::RevokeDevice, "kiosk-1", Some(&first.token))
+            .is_err());
+
+        let second = journal.confirm(AdminOperation::RetentionPurge, "all", None).unwrap_err();
+        clock.advance(chrono::Duration::seconds(121));
+        assert!(journal
+            .confirm(AdminOperation::RetentionPurge, "all", Some(&second.token))
+            .is_err());
+    }
+}
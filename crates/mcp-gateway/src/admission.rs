@@ -0,0 +1,438 @@
+//! Admission control on device memory and temperature
+//!
+//! Small boards keep accepting work until the OOM killer or thermal
+//! throttling steps in. The controller samples the gateway's resident memory,
+//! device memory use and the hottest SoC thermal zone, reusing a reading for
+//! `sample_interval_ms`. While a limit is exceeded, requests below
+//! `min_priority` are queued for cloud sync or shed. Pressure on a resource
+//! ends only once it falls a margin below its limit, so admission does not
+//! flap around the threshold.
+
+use mcp_common::autonomous_scaling::EdgeMetrics;
+use mcp_common::config::{AdmissionAction, AdmissionConfig, PlatformConfig};
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
+use mcp_common::{ComponentHealth, HealthLevel, MCPRequest};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Share of a memory limit usage must fall below before pressure ends
+const MEMORY_RECOVERY_FRACTION: f64 = 0.95;
+
+/// Degrees below the temperature limit the SoC must cool to before pressure ends
+const THERMAL_RECOVERY_CELSIUS: f64 = 5.0;
+
+/// One reading of the device's resources; unreadable values are `None`
+#[derive(Debug, Clone, Default)]
+pub struct DeviceSample {
+    /// Resident memory of the gateway process
+    pub process_memory_mb: Option<f64>,
+    /// Share of device memory in use
+    pub memory_percent: Option<f64>,
+    /// SoC temperature and battery
+    pub edge: EdgeMetrics,
+}
+
+/// Source of device readings
+pub trait DeviceSampler: Send + Sync {
+    fn sample(&self) -> DeviceSample;
+}
+
+/// Reads `/proc` and the kernel thermal zones
+pub struct SystemSampler {
+    thermal_zones_dir: PathBuf,
+}
+
+impl SystemSampler {
+    pub fn new(thermal_zones_dir: PathBuf) -> Self {
+        Self {
+            thermal_zones_dir,
+        }
+    }
+}
+
+impl DeviceSampler for SystemSampler {
+    fn sample(&self) -> DeviceSample {
+        let meminfo = std::fs::read_to_string("/proc/meminfo").unwrap_or_default();
+        let memory_percent = match (
+            field_kb(&meminfo, "MemTotal:"),
+            field_kb(&meminfo, "MemAvailable:"),
+        ) {
+            (Some(total), Some(available)) if total > 0.0 => {
+                Some((total - available) / total * 100.0)
+            },
+            _ => None,
+        };
+        let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+        DeviceSample {
+            process_memory_mb: field_kb(&status, "VmRSS:").map(|kb| kb / 1024.0),
+            memory_percent,
+            edge: EdgeMetrics {
+                temperature_celsius: hottest_zone(&self.thermal_zones_dir),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Value of a `Name:   1234 kB` line
+fn field_kb(text: &str, name: &str) -> Option<f64> {
+    text.lines()
+        .find_map(|line| line.strip_prefix(name))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Highest temperature of the `thermal_zone*` directories, which report
+/// millidegrees Celsius
+fn hottest_zone(dir: &Path) -> Option<f64> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+        })
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("temp")).ok())
+        .filter_map(|temp| temp.trim().parse::<f64>().ok())
+        .map(|millidegrees| millidegrees / 1000.0)
+        .reduce(f64::max)
+}
+
+/// A resource over its limit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pressure {
+    /// `process_memory`, `device_memory` or `thermal`
+    pub resource: &'static str,
+    pub value: f64,
+    pub limit: f64,
+}
+
+impl fmt::Display for Pressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {:.1} over the limit of {:.1}",
+            self.resource, self.value, self.limit
+        )
+    }
+}
+
+/// Outcome of admitting a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum Admission {
+    Admit,
+    /// Queue the request for cloud sync
+    Queue(Pressure),
+    /// Reject the request
+    Shed(Pressure),
+}
+
+struct Reading {
+    sample: DeviceSample,
+    taken_at: Instant,
+    pressure: Option<Pressure>,
+}
+
+/// Holds back low-priority requests while the device is under pressure
+pub struct AdmissionController {
+    config: AdmissionConfig,
+    max_process_memory_mb: f64,
+    max_temperature_celsius: f64,
+    sampler: Arc<dyn DeviceSampler>,
+    reading: Mutex<Option<Reading>>,
+    queued: AtomicU64,
+    shed: AtomicU64,
+}
+
+impl AdmissionController {
+    pub fn new(platform: &PlatformConfig) -> Self {
+        let sampler = Arc::new(SystemSampler::new(
+            platform.admission.thermal_zones_dir.clone(),
+        ));
+        Self::with_sampler(platform, sampler)
+    }
+
+    pub fn with_sampler(platform: &PlatformConfig, sampler: Arc<dyn DeviceSampler>) -> Self {
+        let config = platform.admission.clone();
+        Self {
+            max_process_memory_mb: f64::from(platform.max_memory_mb),
+            max_temperature_celsius: config
+                .max_temperature_celsius
+                .unwrap_or_else(|| platform.thermal_management.temperature_limit_celsius()),
+            config,
+            sampler,
+            reading: Mutex::new(None),
+            queued: AtomicU64::new(0),
+            shed: AtomicU64::new(0),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Pressure that holds `request` back, if any
+    pub fn pressure_for(&self, request: &MCPRequest) -> Option<Pressure> {
+        if !self.config.enabled || request.priority() >= self.config.min_priority {
+            return None;
+        }
+        self.pressure()
+    }
+
+    /// Decide whether `request` is processed now
+    pub fn admit(&self, request: &MCPRequest) -> Admission {
+        let Some(pressure) = self.pressure_for(request) else {
+            return Admission::Admit;
+        };
+        match self.config.action {
+            AdmissionAction::Queue => {
+                self.queued.fetch_add(1, Ordering::Relaxed);
+                Admission::Queue(pressure)
+            },
+            AdmissionAction::Shed => {
+                self.shed.fetch_add(1, Ordering::Relaxed);
+                Admission::Shed(pressure)
+            },
+        }
+    }
+
+    /// Current pressure, sampling the device when the last reading is stale
+    pub fn pressure(&self) -> Option<Pressure> {
+        let mut reading = self.lock_reading();
+        let interval = Duration::from_millis(self.config.sample_interval_ms);
+        if reading
+            .as_ref()
+            .is_some_and(|reading| reading.taken_at.elapsed() < interval)
+        {
+            return reading
+                .as_ref()
+                .and_then(|reading| reading.pressure.clone());
+        }
+
+        let sample = self.sampler.sample();
+        let previous = reading
+            .as_ref()
+            .and_then(|reading| reading.pressure.as_ref());
+        let pressure = self.evaluate(&sample, previous);
+        match (previous, &pressure) {
+            (None, Some(pressure)) => {
+                warn!(
+                    "Device under pressure, holding back low-priority requests: {}",
+                    pressure
+                );
+                events::publish(GatewayEvent::AlertRaised {
+                    source: "admission".to_string(),
+                    severity: AlertSeverity::Warning,
+                    message: format!("Holding back low-priority requests: {}", pressure),
+                });
+            },
+            (Some(_), None) => info!("Device pressure cleared, admitting all requests"),
+            _ => {},
+        }
+        *reading = Some(Reading {
+            sample,
+            taken_at: Instant::now(),
+            pressure: pressure.clone(),
+        });
+        pressure
+    }
+
+    /// First resource over its limit; a resource already under pressure must
+    /// recover past its margin before it is released
+    fn evaluate(&self, sample: &DeviceSample, previous: Option<&Pressure>) -> Option<Pressure> {
+        let device_memory_limit = f64::from(self.config.memory_percent);
+        let signals = [
+            (
+                "process_memory",
+                sample.process_memory_mb,
+                self.max_process_memory_mb,
+                self.max_process_memory_mb * MEMORY_RECOVERY_FRACTION,
+            ),
+            (
+                "device_memory",
+                sample.memory_percent,
+                device_memory_limit,
+                device_memory_limit * MEMORY_RECOVERY_FRACTION,
+            ),
+            (
+                "thermal",
+                sample.edge.temperature_celsius,
+                self.max_temperature_celsius,
+                self.max_temperature_celsius - THERMAL_RECOVERY_CELSIUS,
+            ),
+        ];
+        signals
+            .into_iter()
+            .find_map(|(resource, value, limit, recovered)| {
+                let value = value?;
+                let held = previous.is_some_and(|pressure| pressure.resource == resource);
+                let over = if held {
+                    value > recovered
+                } else {
+                    value > limit
+                };
+                over.then_some(Pressure {
+                    resource,
+                    value,
+                    limit,
+                })
+            })
+    }
+
+    pub fn health(&self) -> ComponentHealth {
+        let pressure = self.pressure();
+        let mut metrics = HashMap::new();
+        if let Some(reading) = self.lock_reading().as_ref() {
+            let sample = &reading.sample;
+            let values = [
+                ("admission_process_memory_mb", sample.process_memory_mb),
+                ("admission_memory_percent", sample.memory_percent),
+                (
+                    "admission_temperature_celsius",
+                    sample.edge.temperature_celsius,
+                ),
+            ];
+            for (name, value) in values {
+                if let Some(value) = value {
+                    metrics.insert(name.to_string(), value as f32);
+                }
+            }
+        }
+        metrics.insert(
+            "admission_pressure".to_string(),
+            if pressure.is_some() { 1.0 } else { 0.0 },
+        );
+        metrics.insert(
+            "admission_queued".to_string(),
+            self.queued.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "admission_shed".to_string(),
+            self.shed.load(Ordering::Relaxed) as f32,
+        );
+        let (status, message) = match &pressure {
+            Some(pressure) => (
+                HealthLevel::Degraded,
+                format!(
+                    "Holding back requests below {:?} priority: {}",
+                    self.config.min_priority, pressure
+                ),
+            ),
+            None => (HealthLevel::Healthy, "Admitting all requests".to_string()),
+        };
+        ComponentHealth {
+            status,
+            message,
+            last_check: chrono::Utc::now(),
+            metrics,
+        }
+    }
+
+    fn lock_reading(&self) -> std::sync::MutexGuard<'_, Option<Reading>> {
+        self.reading
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::{Config, Priority, RequestContext};
+
+    /// Sampler returning whatever the test last set
+    struct FixedSampler(Mutex<DeviceSample>);
+
+    impl FixedSampler {
+        fn set(&self, memory_percent: f64, temperature_celsius: f64) {
+            let mut sample = self.0.lock().unwrap();
+            sample.memory_percent = Some(memory_percent);
+            sample.edge.temperature_celsius = Some(temperature_celsius);
+        }
+    }
+
+    impl DeviceSampler for FixedSampler {
+        fn sample(&self) -> DeviceSample {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    fn controller_with(action: AdmissionAction) -> (AdmissionController, Arc<FixedSampler>) {
+        let mut platform = Config::default().platform;
+        platform.admission.enabled = true;
+        platform.admission.action = action;
+        platform.admission.sample_interval_ms = 0;
+        let sampler = Arc::new(FixedSampler(Mutex::new(DeviceSample::default())));
+        (
+            AdmissionController::with_sampler(&platform, sampler.clone()),
+            sampler,
+        )
+    }
+
+    fn request(priority: Priority) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "sensor-1".to_string(),
+            method: "completion".to_string(),
+            params: HashMap::new(),
+            context: Some(RequestContext {
+                priority,
+                ..Default::default()
+            }),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_low_priority_requests_are_held_back_under_pressure() {
+        let (controller, sampler) = controller_with(AdmissionAction::Queue);
+        sampler.set(50.0, 60.0);
+        assert_eq!(controller.admit(&request(Priority::Low)), Admission::Admit);
+
+        // Moderate thermal profile limits the SoC to 80°C
+        sampler.set(50.0, 83.0);
+        let Admission::Queue(pressure) = controller.admit(&request(Priority::Low)) else {
+            panic!("low-priority request was admitted while overheating");
+        };
+        assert_eq!((pressure.resource, pressure.limit), ("thermal", 80.0));
+        assert_eq!(
+            controller.admit(&request(Priority::Normal)),
+            Admission::Admit
+        );
+        assert_eq!(controller.health().status, HealthLevel::Degraded);
+
+        let (controller, sampler) = controller_with(AdmissionAction::Shed);
+        sampler.set(97.0, 40.0);
+        assert!(matches!(
+            controller.admit(&request(Priority::Low)),
+            Admission::Shed(Pressure {
+                resource: "device_memory",
+                ..
+            })
+        ));
+        assert_eq!(controller.health().metrics["admission_shed"], 1.0);
+    }
+
+    #[test]
+    fn test_pressure_clears_only_below_the_recovery_margin() {
+        let (controller, sampler) = controller_with(AdmissionAction::Queue);
+        sampler.set(50.0, 81.0);
+        assert!(controller.pressure().is_some());
+        // Still within 5°C of the limit
+        sampler.set(50.0, 77.0);
+        assert!(controller.pressure().is_some());
+        sampler.set(50.0, 74.0);
+        assert!(controller.pressure().is_none());
+        // Crossing back needs the full limit again
+        sampler.set(50.0, 79.0);
+        assert!(controller.pressure().is_none());
+    }
+}
@@ -0,0 +1,724 @@
+//! Wake word gated audio pipeline
+//!
+//! Each microphone in `peripherals.microphones` streams mono 16-bit PCM from
+//! `arecord` or a custom command. While idle, overlapping windows of audio
+//! are checked for a wake phrase: windows below the energy threshold are
+//! dropped without any inference, and only voiced windows are transcribed
+//! by the tiny wake word model, kept on the device. Once a phrase matches,
+//! the utterance that follows is recorded until silence, transcribed by the
+//! full model and sent as a prompt to the completion method, and the
+//! [`AudioInteraction`] is published as an [`AUDIO_NOTIFICATION`] to MCP
+//! WebSocket clients. Activation counts are reported in the gateway health.
+
+use crate::gateway::Gateway;
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use mcp_common::config::{AudioSourceKind, MicrophoneConfig, PeripheralsConfig};
+use mcp_common::{
+    ComponentHealth, Error, HealthLevel, MCPRequest, MCPResponse, RequestContext, Result,
+};
+use mcp_router::model_aliases::MODEL_PARAM;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Notification carrying every [`AudioInteraction`]
+pub const AUDIO_NOTIFICATION: &str = "notifications/audio/interaction";
+
+/// Interactions buffered for each slow WebSocket client
+const NOTIFICATION_CAPACITY: usize = 16;
+
+/// Wait before restarting a capture program that exited
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Wake phrase found in a window of audio
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WakeDetection {
+    pub phrase: String,
+    pub score: f32,
+    pub heard: String,
+}
+
+/// What was said after a wake word and the answer to it
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioInteraction {
+    pub microphone: String,
+    pub wake_phrase: String,
+    pub started_at: DateTime<Utc>,
+    pub transcript: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<MCPResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Activity of one microphone
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MicrophoneStats {
+    pub name: String,
+    /// Windows dropped as silence without running the wake word model
+    pub silent_windows: u64,
+    /// Windows the wake word model checked
+    pub wake_checks: u64,
+    pub activations: u64,
+    pub failures: u64,
+    pub last_activation: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// Listens on the configured microphones and publishes the interactions
+pub struct AudioPipeline {
+    microphones: Vec<MicrophoneConfig>,
+    stats: Mutex<HashMap<String, MicrophoneStats>>,
+    interactions: broadcast::Sender<AudioInteraction>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl AudioPipeline {
+    pub fn new(config: PeripheralsConfig) -> Self {
+        let stats = config
+            .microphones
+            .iter()
+            .map(|microphone| {
+                let stats = MicrophoneStats {
+                    name: microphone.name.clone(),
+                    ..Default::default()
+                };
+                (microphone.name.clone(), stats)
+            })
+            .collect();
+        let (interactions, _) = broadcast::channel(NOTIFICATION_CAPACITY);
+        Self {
+            microphones: config.microphones,
+            stats: Mutex::new(stats),
+            interactions,
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.microphones.is_empty()
+    }
+
+    /// Listen on every microphone in the background
+    pub fn start(self: &Arc<Self>, gateway: Weak<Gateway>) {
+        let handles: Vec<JoinHandle<()>> = self
+            .microphones
+            .iter()
+            .map(|microphone| {
+                let pipeline = Arc::downgrade(self);
+                let gateway = gateway.clone();
+                let microphone = microphone.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let mut stream = match MicrophoneStream::open(&microphone) {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                warn!("Failed to open microphone {}: {}", microphone.name, e);
+                                tokio::time::sleep(RESTART_DELAY).await;
+                                continue;
+                            },
+                        };
+                        let (Some(pipeline), Some(gateway)) = (pipeline.upgrade(), gateway.upgrade())
+                        else {
+                            break;
+                        };
+                        let send = |request| gateway.process_request(request);
+                        match pipeline.listen(&microphone, &mut stream, send).await {
+                            Ok(()) => warn!("Microphone {} stopped capturing", microphone.name),
+                            Err(e) => warn!("Microphone {} failed: {}", microphone.name, e),
+                        }
+                        tokio::time::sleep(RESTART_DELAY).await;
+                    }
+                })
+            })
+            .collect();
+        for previous in std::mem::replace(&mut *self.lock_tasks(), handles) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        for handle in self.lock_tasks().drain(..) {
+            handle.abort();
+        }
+    }
+
+    /// Check windows of `stream` for a wake phrase and run an interaction
+    /// for each one found, until the stream ends
+    async fn listen<F, Fut>(
+        &self,
+        microphone: &MicrophoneConfig,
+        stream: &mut MicrophoneStream,
+        send: F,
+    ) -> Result<()>
+    where
+        F: Fn(MCPRequest) -> Fut,
+        Fut: Future<Output = Result<MCPResponse>>,
+    {
+        let wake_word = &microphone.wake_word;
+        let half_window = samples_for(microphone.sample_rate, wake_word.window_ms / 2).max(1);
+        let mut previous = Vec::new();
+        while let Some(chunk) = stream.read(half_window).await? {
+            let window = [previous.as_slice(), chunk.as_slice()].concat();
+            previous = chunk;
+            let Some(detection) = self.detect(microphone, &window, &send).await else {
+                continue;
+            };
+            info!(
+                "Wake phrase '{}' heard on {} (score {:.2})",
+                detection.phrase, microphone.name, detection.score
+            );
+
+            let utterance = record_utterance(microphone, stream).await?;
+            self.interact(microphone, &detection, &utterance, &send)
+                .await;
+
+            // Drop the cooldown's worth of audio so the answer being read out
+            // or a repeated phrase does not wake it again
+            previous.clear();
+            let cooldown = samples_for(microphone.sample_rate, wake_word.cooldown_ms);
+            if cooldown > 0 && stream.read(cooldown).await?.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wake phrase in `window`, skipping the model for silent windows
+    pub async fn detect<F, Fut>(
+        &self,
+        microphone: &MicrophoneConfig,
+        window: &[i16],
+        send: F,
+    ) -> Option<WakeDetection>
+    where
+        F: Fn(MCPRequest) -> Fut,
+        Fut: Future<Output = Result<MCPResponse>>,
+    {
+        let wake_word = &microphone.wake_word;
+        if rms(window) < wake_word.energy_threshold {
+            self.update(&microphone.name, |stats| stats.silent_windows += 1);
+            return None;
+        }
+        self.update(&microphone.name, |stats| stats.wake_checks += 1);
+
+        let mut request = audio_request(microphone, &microphone.transcription_method, window);
+        request
+            .params
+            .insert(MODEL_PARAM.to_string(), serde_json::json!(wake_word.model));
+        // The wake word model must not wake the uplink either
+        let mut context = RequestContext::default();
+        context.requirements.require_local = true;
+        context.requirements.allow_fallback = false;
+        request.context = Some(context);
+
+        let heard = match send(request).await.and_then(transcript) {
+            Ok(heard) => heard,
+            Err(e) => {
+                debug!("Wake word check on {} failed: {}", microphone.name, e);
+                self.record_failure(&microphone.name, &e);
+                return None;
+            },
+        };
+        let detection = wake_word
+            .phrases
+            .iter()
+            .map(|phrase| WakeDetection {
+                phrase: phrase.clone(),
+                score: phrase_score(phrase, &heard),
+                heard: heard.clone(),
+            })
+            .max_by(|a, b| a.score.total_cmp(&b.score))?;
+        (detection.score >= 1.0 - wake_word.sensitivity).then(|| {
+            self.update(&microphone.name, |stats| {
+                stats.activations += 1;
+                stats.last_activation = Some(Utc::now());
+            });
+            detection
+        })
+    }
+
+    /// Transcribe `utterance`, send the transcript to the completion method
+    /// and publish the outcome
+    pub async fn interact<F, Fut>(
+        &self,
+        microphone: &MicrophoneConfig,
+        detection: &WakeDetection,
+        utterance: &[i16],
+        send: F,
+    ) -> AudioInteraction
+    where
+        F: Fn(MCPRequest) -> Fut,
+        Fut: Future<Output = Result<MCPResponse>>,
+    {
+        let mut interaction = AudioInteraction {
+            microphone: microphone.name.clone(),
+            wake_phrase: detection.phrase.clone(),
+            started_at: Utc::now(),
+            transcript: None,
+            response: None,
+            error: None,
+        };
+        let outcome = async {
+            let mut request =
+                audio_request(microphone, &microphone.transcription_method, utterance);
+            if let Some(model) = &microphone.transcription_model {
+                request
+                    .params
+                    .insert(MODEL_PARAM.to_string(), serde_json::json!(model));
+            }
+            let transcript = transcript(send(request).await?)?;
+            interaction.transcript = Some(transcript.clone());
+            if let Some(method) = &microphone.completion_method {
+                let prompt = strip_phrase(&transcript, &detection.phrase);
+                let request = MCPRequest {
+                    id: Uuid::new_v4(),
+                    device_id: device_id(microphone),
+                    method: method.clone(),
+                    params: HashMap::from([("prompt".to_string(), serde_json::json!(prompt))]),
+                    context: None,
+                    timestamp: Utc::now(),
+                };
+                interaction.response = Some(send(request).await?);
+            }
+            Ok::<_, Error>(())
+        }
+        .await;
+        if let Err(e) = outcome {
+            warn!("Interaction on {} failed: {}", microphone.name, e);
+            self.record_failure(&microphone.name, &e);
+            interaction.error = Some(e.to_string());
+        }
+        // Nobody listening is fine; interactions are also counted
+        let _ = self.interactions.send(interaction.clone());
+        interaction
+    }
+
+    /// Interactions from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<AudioInteraction> {
+        self.interactions.subscribe()
+    }
+
+    /// Activity of every microphone, by name
+    pub fn stats(&self) -> Vec<MicrophoneStats> {
+        let mut stats: Vec<MicrophoneStats> = self.lock_stats().values().cloned().collect();
+        stats.sort_by(|a, b| a.name.cmp(&b.name));
+        stats
+    }
+
+    /// Degraded while any microphone's latest interaction or check failed
+    pub fn health(&self) -> ComponentHealth {
+        let stats = self.stats();
+        let mut metrics = HashMap::new();
+        for stats in &stats {
+            metrics.insert(format!("{}_activations", stats.name), stats.activations as f32);
+            metrics.insert(format!("{}_wake_checks", stats.name), stats.wake_checks as f32);
+            metrics.insert(
+                format!("{}_silent_windows", stats.name),
+                stats.silent_windows as f32,
+            );
+        }
+        let failing: Vec<&str> = stats
+            .iter()
+            .filter(|stats| stats.last_error.is_some())
+            .map(|stats| stats.name.as_str())
+            .collect();
+        let (status, message) = if failing.is_empty() {
+            (
+                HealthLevel::Healthy,
+                format!("Listening on {} microphones", stats.len()),
+            )
+        } else {
+            (
+                HealthLevel::Degraded,
+                format!("Failing microphones: {}", failing.join(", ")),
+            )
+        };
+        ComponentHealth {
+            status,
+            message,
+            last_check: Utc::now(),
+            metrics,
+        }
+    }
+
+    fn record_failure(&self, name: &str, error: &Error) {
+        self.update(name, |stats| {
+            stats.failures += 1;
+            stats.last_error = Some(error.to_string());
+        });
+    }
+
+    fn update(&self, name: &str, change: impl FnOnce(&mut MicrophoneStats)) {
+        if let Some(stats) = self.lock_stats().get_mut(name) {
+            change(stats);
+        }
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, HashMap<String, MicrophoneStats>> {
+        self.stats
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_tasks(&self) -> std::sync::MutexGuard<'_, Vec<JoinHandle<()>>> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Raw PCM read from a capture program
+struct MicrophoneStream {
+    _child: Child,
+    stdout: ChildStdout,
+}
+
+impl MicrophoneStream {
+    fn open(microphone: &MicrophoneConfig) -> Result<Self> {
+        let (program, args) = match &microphone.source {
+            AudioSourceKind::Alsa {
+                device,
+            } => (
+                "arecord".to_string(),
+                vec![
+                    "-q".to_string(),
+                    "-D".to_string(),
+                    device.clone(),
+                    "-t".to_string(),
+                    "raw".to_string(),
+                    "-f".to_string(),
+                    "S16_LE".to_string(),
+                    "-c".to_string(),
+                    "1".to_string(),
+                    "-r".to_string(),
+                    microphone.sample_rate.to_string(),
+                ],
+            ),
+            AudioSourceKind::Command {
+                program,
+                args,
+            } => (program.clone(), args.clone()),
+        };
+        let mut child = Command::new(&program)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Error::Internal(format!("Failed to run {}: {}", program, e)))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| Error::Internal(format!("{} has no stdout", program)))?;
+        Ok(Self {
+            _child: child,
+            stdout,
+        })
+    }
+
+    /// Next `samples` samples, or None once the program stops
+    async fn read(&mut self, samples: usize) -> Result<Option<Vec<i16>>> {
+        let mut bytes = vec![0u8; samples * 2];
+        match self.stdout.read_exact(&mut bytes).await {
+            Ok(_) => Ok(Some(
+                bytes
+                    .chunks_exact(2)
+                    .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+                    .collect(),
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(Error::Internal(format!("Failed to read audio: {}", e))),
+        }
+    }
+}
+
+/// Audio after a wake word, up to the first `end_of_speech_ms` of silence
+async fn record_utterance(
+    microphone: &MicrophoneConfig,
+    stream: &mut MicrophoneStream,
+) -> Result<Vec<i16>> {
+    // Speech is checked for in 100ms blocks
+    let block = samples_for(microphone.sample_rate, 100).max(1);
+    let max_samples = microphone.sample_rate as usize * microphone.max_utterance_secs as usize;
+    let silent_blocks_to_stop = (microphone.end_of_speech_ms / 100).max(1);
+    let mut utterance = Vec::new();
+    let mut silent_blocks = 0;
+    while utterance.len() < max_samples && silent_blocks < silent_blocks_to_stop {
+        let Some(samples) = stream.read(block).await? else {
+            break;
+        };
+        if rms(&samples) < microphone.wake_word.energy_threshold {
+            silent_blocks += 1;
+        } else {
+            silent_blocks = 0;
+        }
+        utterance.extend(samples);
+    }
+    Ok(utterance)
+}
+
+fn samples_for(sample_rate: u32, ms: u64) -> usize {
+    (sample_rate as u64 * ms / 1000) as usize
+}
+
+/// Root mean square level as a fraction of full scale
+fn rms(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = samples
+        .iter()
+        .map(|&sample| (sample as f64 / i16::MAX as f64).powi(2))
+        .sum();
+    (sum / samples.len() as f64).sqrt() as f32
+}
+
+/// Best similarity between `phrase` and any run of about as many words in
+/// `heard`
+fn phrase_score(phrase: &str, heard: &str) -> f32 {
+    let normalize = |text: &str| -> Vec<String> {
+        text.split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>()
+            })
+            .filter(|word| !word.is_empty())
+            .collect()
+    };
+    let phrase = normalize(phrase).join(" ");
+    let words = normalize(heard);
+    if phrase.is_empty() || words.is_empty() {
+        return 0.0;
+    }
+    // Runs one word shorter or longer catch words heard split or merged
+    let span = phrase.split(' ').count();
+    (span.saturating_sub(1).max(1)..=span + 1)
+        .filter(|&length| length <= words.len())
+        .flat_map(|length| words.windows(length))
+        .map(|run| similarity(&phrase, &run.join(" ")))
+        .fold(0.0, f32::max)
+}
+
+/// 1.0 minus the edit distance relative to the longer text
+fn similarity(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    let longest = a.len().max(b.len()).max(1);
+    1.0 - previous[b.len()] as f32 / longest as f32
+}
+
+/// `transcript` without the wake phrase it starts with
+fn strip_phrase(transcript: &str, phrase: &str) -> String {
+    let words: Vec<&str> = transcript.split_whitespace().collect();
+    let skip = phrase.split_whitespace().count();
+    if words.len() > skip && phrase_score(phrase, &words[..skip].join(" ")) >= 0.5 {
+        words[skip..].join(" ")
+    } else {
+        transcript.to_string()
+    }
+}
+
+fn device_id(microphone: &MicrophoneConfig) -> String {
+    format!("microphone-{}", microphone.name)
+}
+
+/// Request sending `samples` as a WAV file to `method`
+fn audio_request(microphone: &MicrophoneConfig, method: &str, samples: &[i16]) -> MCPRequest {
+    let audio = wav(samples, microphone.sample_rate);
+    MCPRequest {
+        id: Uuid::new_v4(),
+        device_id: device_id(microphone),
+        method: method.to_string(),
+        params: HashMap::from([
+            (
+                "audio".to_string(),
+                serde_json::json!(base64::engine::general_purpose::STANDARD.encode(audio)),
+            ),
+            ("mime_type".to_string(), serde_json::json!("audio/wav")),
+            ("microphone".to_string(), serde_json::json!(microphone.name)),
+        ]),
+        context: None,
+        timestamp: Utc::now(),
+    }
+}
+
+/// Mono 16-bit PCM WAV file
+fn wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// Text of a transcription response
+fn transcript(response: MCPResponse) -> Result<String> {
+    if let Some(error) = response.error {
+        return Err(Error::Model(error.message));
+    }
+    response
+        .result
+        .as_ref()
+        .and_then(|result| {
+            ["text", "transcription", "transcript"]
+                .iter()
+                .find_map(|field| result.get(field).and_then(|text| text.as_str()))
+        })
+        .map(str::to_string)
+        .ok_or_else(|| Error::Model("Transcription response without text".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn pipeline() -> (AudioPipeline, MicrophoneConfig) {
+        let microphone = MicrophoneConfig {
+            name: "kitchen".to_string(),
+            ..Default::default()
+        };
+        let pipeline = AudioPipeline::new(PeripheralsConfig {
+            microphones: vec![microphone.clone()],
+            ..Default::default()
+        });
+        (pipeline, microphone)
+    }
+
+    fn text(request: &MCPRequest, text: &str) -> Result<MCPResponse> {
+        Ok(MCPResponse {
+            id: request.id,
+            result: Some(serde_json::json!({ "text": text })),
+            error: None,
+            timestamp: Utc::now(),
+        })
+    }
+
+    fn speech(samples: usize) -> Vec<i16> {
+        (0..samples)
+            .map(|i| if i % 2 == 0 { 8000 } else { -8000 })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_silence_skips_the_model_and_phrases_activate() {
+        let (pipeline, microphone) = pipeline();
+        let checks = AtomicUsize::new(0);
+        let send = |request: MCPRequest| {
+            checks.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(request.params[MODEL_PARAM], "whisper-tiny");
+            assert!(request.context.as_ref().unwrap().requirements.require_local);
+            let heard = if checks.load(Ordering::SeqCst) == 1 {
+                "turn the lights off"
+            } else {
+                "Hey, Gateway!"
+            };
+            async move { text(&request, heard) }
+        };
+
+        assert!(pipeline
+            .detect(&microphone, &[3; 24_000], &send)
+            .await
+            .is_none());
+        assert_eq!(checks.load(Ordering::SeqCst), 0);
+
+        assert!(pipeline
+            .detect(&microphone, &speech(24_000), &send)
+            .await
+            .is_none());
+        let detection = pipeline
+            .detect(&microphone, &speech(24_000), &send)
+            .await
+            .unwrap();
+        assert_eq!(detection.phrase, "hey gateway");
+        assert_eq!(detection.score, 1.0);
+
+        let stats = &pipeline.stats()[0];
+        assert_eq!(
+            (stats.silent_windows, stats.wake_checks, stats.activations),
+            (1, 2, 1)
+        );
+        assert_eq!(pipeline.health().metrics["kitchen_activations"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_interaction_transcribes_then_completes() {
+        let (pipeline, microphone) = pipeline();
+        let mut interactions = pipeline.subscribe();
+        let detection = WakeDetection {
+            phrase: "hey gateway".to_string(),
+            score: 1.0,
+            heard: "hey gateway".to_string(),
+        };
+        let interaction = pipeline
+            .interact(&microphone, &detection, &speech(16_000), |request| async move {
+                match request.method.as_str() {
+                    "transcription" => {
+                        assert!(!request.params.contains_key(MODEL_PARAM));
+                        text(&request, "hey gateway what time is it")
+                    },
+                    _ => {
+                        assert_eq!(request.params["prompt"], "what time is it");
+                        text(&request, "It is noon")
+                    },
+                }
+            })
+            .await;
+        assert_eq!(
+            interaction.transcript.as_deref(),
+            Some("hey gateway what time is it")
+        );
+        assert!(interaction.error.is_none());
+        let published = interactions.recv().await.unwrap();
+        assert_eq!(
+            published.response.unwrap().result.unwrap()["text"],
+            "It is noon"
+        );
+    }
+
+    #[test]
+    fn test_phrase_matching_tolerates_mishearing() {
+        assert_eq!(phrase_score("hey gateway", "ok so hey gateway now"), 1.0);
+        assert!(phrase_score("hey gateway", "hey gate way") > 0.8);
+        assert!(phrase_score("hey gateway", "what is the weather") < 0.5);
+        assert_eq!(phrase_score("hey gateway", ""), 0.0);
+        assert_eq!(rms(&[i16::MAX; 10]), 1.0);
+        assert_eq!(&wav(&[1, 2], 16_000)[..4], b"RIFF");
+    }
+}
@@ -0,0 +1,635 @@
+//! Caller authentication for the HTTP API
+//!
+//! Callers present a static API key in `x-api-key` or as a bearer token, or
+//! a JWT bearer token signed with RS256 or ES256 by a key from the
+//! configured JWKS. Keys are fetched on first use and kept for
+//! `jwks_cache_secs`; a token signed by an unknown key triggers an early
+//! refetch, so issuer key rotation needs no restart. The authenticated
+//! [`Principal`] is attached to the request extensions for handlers, which
+//! pass it on in the request context for the security manager to authorize
+//! each method. Admin endpoints are authorized the same way, as the method
+//! `admin/<path>` (`admin/queue/dead-letters` for
+//! `/v1/admin/queue/dead-letters`), so only credentials granting `admin/*`
+//! or any method reach all of them.
+
+use crate::handlers::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use mcp_common::clock::Clock;
+use mcp_common::config::{AuthenticationConfig, JwtConfig};
+use mcp_common::crypto::{digest, signature};
+use mcp_common::encoding::hex;
+use mcp_common::{AuthScheme, Error, Principal, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Header carrying a static API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Shortest interval between JWKS fetches triggered by unknown keys
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A key from the issuer's JWKS
+#[derive(Debug, Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+    #[serde(default)]
+    crv: Option<String>,
+    #[serde(default)]
+    x: Option<String>,
+    #[serde(default)]
+    y: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+struct CachedKeys {
+    keys: Vec<Jwk>,
+    fetched_at: Instant,
+}
+
+/// Verifies API keys and JWTs against the configured credentials
+pub struct Authenticator {
+    config: AuthenticationConfig,
+    /// Principals by hex SHA-256 of their key
+    api_keys: HashMap<String, Principal>,
+    jwks: RwLock<Option<CachedKeys>>,
+    client: reqwest::Client,
+    clock: Arc<dyn Clock>,
+}
+
+impl Authenticator {
+    pub fn new(config: AuthenticationConfig, clock: Arc<dyn Clock>) -> Self {
+        let api_keys = config
+            .api_keys
+            .iter()
+            .map(|key| {
+                let principal = Principal {
+                    subject: key.name.clone(),
+                    scheme: AuthScheme::ApiKey,
+                    allowed_methods: (!key.allowed_methods.is_empty())
+                        .then(|| key.allowed_methods.clone()),
+                };
+                (key.key_sha256.to_ascii_lowercase(), principal)
+            })
+            .collect();
+        Self {
+            config,
+            api_keys,
+            jwks: RwLock::new(None),
+            client: reqwest::Client::new(),
+            clock,
+        }
+    }
+
+    pub fn config(&self) -> &AuthenticationConfig {
+        &self.config
+    }
+
+    /// Whether `path` is served without credentials
+    pub fn is_exempt(&self, path: &str) -> bool {
+        self.config
+            .exempt_paths
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()))
+    }
+
+    /// Authenticate the credentials in `headers`; `Ok(None)` when there are none
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Principal>> {
+        if let Some(key) = headers.get(API_KEY_HEADER) {
+            let key = key
+                .to_str()
+                .map_err(|_| Error::Security("Malformed API key".to_string()))?;
+            return self.verify_api_key(key).map(Some);
+        }
+        let Some(authorization) = headers.get(header::AUTHORIZATION) else {
+            return Ok(None);
+        };
+        let token = authorization
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Error::Security("Expected a bearer token".to_string()))?
+            .trim();
+        if token.split('.').count() == 3 {
+            self.verify_jwt(token).await.map(Some)
+        } else {
+            self.verify_api_key(token).map(Some)
+        }
+    }
+
+    fn verify_api_key(&self, key: &str) -> Result<Principal> {
+        let hash = hex(digest::digest(&digest::SHA256, key.as_bytes()).as_ref());
+        self.api_keys
+            .get(&hash)
+            .cloned()
+            .ok_or_else(|| Error::Security("Unknown API key".to_string()))
+    }
+
+    async fn verify_jwt(&self, token: &str) -> Result<Principal> {
+        let jwt =
+            self.config.jwt.as_ref().ok_or_else(|| {
+                Error::Security("JWT authentication is not configured".to_string())
+            })?;
+        let invalid = |reason: &str| Error::Security(format!("Invalid token: {}", reason));
+
+        let (signed, sig) = token
+            .rsplit_once('.')
+            .ok_or_else(|| invalid("expected three segments"))?;
+        let (header, claims) = signed
+            .split_once('.')
+            .ok_or_else(|| invalid("expected three segments"))?;
+        let decode = |segment: &str| {
+            URL_SAFE_NO_PAD
+                .decode(segment)
+                .map_err(|_| invalid("bad base64"))
+        };
+        let header: JwtHeader =
+            serde_json::from_slice(&decode(header)?).map_err(|_| invalid("bad header"))?;
+        let claims_json: Value =
+            serde_json::from_slice(&decode(claims)?).map_err(|_| invalid("bad claims"))?;
+        let sig = decode(sig)?;
+
+        let key = self.key_for(jwt, header.kid.as_deref()).await?;
+        verify_signature(&header.alg, &key, signed.as_bytes(), &sig)?;
+        self.check_claims(jwt, &claims_json)?;
+
+        let subject = claims_json
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("missing sub"))?;
+        let allowed_methods =
+            claims_json
+                .get(&jwt.methods_claim)
+                .and_then(|methods| match methods {
+                    Value::String(methods) => {
+                        Some(methods.split_whitespace().map(str::to_string).collect())
+                    },
+                    Value::Array(methods) => Some(
+                        methods
+                            .iter()
+                            .filter_map(Value::as_str)
+                            .map(str::to_string)
+                            .collect(),
+                    ),
+                    _ => None,
+                });
+        Ok(Principal {
+            subject: subject.to_string(),
+            scheme: AuthScheme::Jwt,
+            allowed_methods,
+        })
+    }
+
+    fn check_claims(&self, jwt: &JwtConfig, claims: &Value) -> Result<()> {
+        let now = self.clock.now().timestamp();
+        let leeway = jwt.leeway_secs as i64;
+        let invalid = |reason: &str| Error::Security(format!("Invalid token: {}", reason));
+
+        let exp = claims
+            .get("exp")
+            .and_then(Value::as_i64)
+            .ok_or_else(|| invalid("missing exp"))?;
+        if now > exp + leeway {
+            return Err(invalid("expired"));
+        }
+        if claims
+            .get("nbf")
+            .and_then(Value::as_i64)
+            .is_some_and(|nbf| now + leeway < nbf)
+        {
+            return Err(invalid("not yet valid"));
+        }
+        if let Some(issuer) = &jwt.issuer {
+            if claims.get("iss").and_then(Value::as_str) != Some(issuer.as_str()) {
+                return Err(invalid("wrong issuer"));
+            }
+        }
+        if let Some(audience) = &jwt.audience {
+            let matches = match claims.get("aud") {
+                Some(Value::String(aud)) => aud == audience,
+                Some(Value::Array(auds)) => auds
+                    .iter()
+                    .any(|aud| aud.as_str() == Some(audience.as_str())),
+                _ => false,
+            };
+            if !matches {
+                return Err(invalid("wrong audience"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The JWKS key with `kid`, or the only key when the token names none
+    async fn key_for(&self, jwt: &JwtConfig, kid: Option<&str>) -> Result<Jwk> {
+        let ttl = Duration::from_secs(jwt.jwks_cache_secs);
+        let (cached, stale) = {
+            let jwks = self.jwks.read().await;
+            match jwks.as_ref() {
+                Some(cached) => (
+                    find_key(&cached.keys, kid),
+                    cached.fetched_at.elapsed() >= ttl,
+                ),
+                None => (None, true),
+            }
+        };
+        if let Some(key) = cached.filter(|_| !stale) {
+            return Ok(key);
+        }
+
+        let mut jwks = self.jwks.write().await;
+        // Another request may have fetched meanwhile; unknown keys refetch at a bounded rate
+        let refetch = match jwks.as_ref() {
+            None => true,
+            Some(cached) => {
+                let age = cached.fetched_at.elapsed();
+                age >= ttl || (find_key(&cached.keys, kid).is_none() && age >= MIN_REFETCH_INTERVAL)
+            },
+        };
+        if refetch {
+            match self.fetch_keys(&jwt.jwks_url).await {
+                Ok(keys) => {
+                    debug!("Fetched {} keys from {}", keys.len(), jwt.jwks_url);
+                    *jwks = Some(CachedKeys {
+                        keys,
+                        fetched_at: Instant::now(),
+                    });
+                },
+                // Keep verifying with the keys we have while the issuer is unreachable
+                Err(e) if jwks.is_some() => warn!("{}; using cached keys", e),
+                Err(e) => return Err(e),
+            }
+        }
+        jwks.as_ref()
+            .and_then(|cached| find_key(&cached.keys, kid))
+            .ok_or_else(|| Error::Security("Invalid token: unknown signing key".to_string()))
+    }
+
+    async fn fetch_keys(&self, url: &str) -> Result<Vec<Jwk>> {
+        let response = self
+            .client
+            .get(url)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::Network(format!("JWKS fetch from {} failed: {}", url, e)))?;
+        let set: JwkSet = response
+            .json()
+            .await
+            .map_err(|e| Error::Network(format!("Invalid JWKS from {}: {}", url, e)))?;
+        Ok(set.keys)
+    }
+}
+
+fn find_key(keys: &[Jwk], kid: Option<&str>) -> Option<Jwk> {
+    match kid {
+        Some(kid) => keys
+            .iter()
+            .find(|key| key.kid.as_deref() == Some(kid))
+            .cloned(),
+        None if keys.len() == 1 => keys.first().cloned(),
+        None => None,
+    }
+}
+
+fn verify_signature(alg: &str, key: &Jwk, signed: &[u8], sig: &[u8]) -> Result<()> {
+    let invalid = |reason: &str| Error::Security(format!("Invalid token: {}", reason));
+    let component = |value: &Option<String>| {
+        value
+            .as_deref()
+            .and_then(|value| URL_SAFE_NO_PAD.decode(value).ok())
+            .ok_or_else(|| invalid("incomplete signing key"))
+    };
+    let verified = match (alg, key.kty.as_str()) {
+        ("RS256", "RSA") => signature::RsaPublicKeyComponents {
+            n: component(&key.n)?,
+            e: component(&key.e)?,
+        }
+        .verify(&signature::RSA_PKCS1_2048_8192_SHA256, signed, sig)
+        .is_ok(),
+        ("ES256", "EC") if key.crv.as_deref() == Some("P-256") => {
+            let mut point = vec![0x04];
+            point.extend(component(&key.x)?);
+            point.extend(component(&key.y)?);
+            signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                .verify(signed, sig)
+                .is_ok()
+        },
+        _ => return Err(invalid(&format!("unsupported algorithm {}", alg))),
+    };
+    if verified {
+        Ok(())
+    } else {
+        Err(invalid("bad signature"))
+    }
+}
+
+fn unauthenticated(message: String) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        Json(serde_json::json!({
+            "error": {
+                "code": "UNAUTHENTICATED",
+                "message": message
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// Authenticate callers, attaching their [`Principal`] to the request
+///
+/// Invalid credentials are always rejected; missing ones only when
+/// authentication is required and the path is not exempt.
+pub async fn authenticate(
+    State(gateway): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let authenticator = gateway.authenticator();
+    if authenticator.is_exempt(request.uri().path()) {
+        return next.run(request).await;
+    }
+    match authenticator.authenticate(request.headers()).await {
+        Ok(Some(principal)) => {
+            request.extensions_mut().insert(principal);
+        },
+        Ok(None) if authenticator.config().require_authentication => {
+            return unauthenticated("Authentication required".to_string());
+        },
+        Ok(None) => {},
+        Err(e) => {
+            warn!("Rejected request to {}: {}", request.uri().path(), e);
+            return unauthenticated(e.to_string());
+        },
+    }
+    next.run(request).await
+}
+
+/// Method an admin API path is authorized as
+pub fn admin_method(path: &str) -> &str {
+    path.strip_prefix("/v1/").unwrap_or(path).trim_end_matches('/')
+}
+
+/// Reject callers whose credentials do not grant the admin operation;
+/// layered on the admin routes inside [`authenticate`]
+///
+/// Admin routes always need credentials, whether or not
+/// `require_authentication` is set.
+pub async fn require_admin(request: Request, next: Next) -> Response {
+    let method = admin_method(request.uri().path());
+    let Some(principal) = request.extensions().get::<Principal>() else {
+        warn!("Rejected anonymous admin request to {}", method);
+        return unauthenticated("Admin credentials required".to_string());
+    };
+    if !principal.may_call(method) {
+        warn!("Rejected admin request: {} may not call {}", principal.subject, method);
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "error": {
+                    "code": "FORBIDDEN",
+                    "message": format!("Not allowed to call {}", method)
+                }
+            })),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use mcp_common::clock::SystemClock;
+    use mcp_common::config::ApiKeyConfig;
+    use mcp_common::crypto::rand::SystemRandom;
+    use signature::{EcdsaKeyPair, KeyPair};
+
+    fn headers(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            HeaderValue::from_str(value).unwrap(),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_authenticate_with_their_methods() {
+        let config = AuthenticationConfig {
+            require_authentication: true,
+            api_keys: vec![ApiKeyConfig {
+                name: "line-7".to_string(),
+                key_sha256: hex(digest::digest(&digest::SHA256, b"secret-key").as_ref()),
+                allowed_methods: vec!["tools/*".to_string()],
+            }],
+            ..Default::default()
+        };
+        let authenticator = Authenticator::new(config, Arc::new(SystemClock));
+
+        let principal = authenticator
+            .authenticate(&headers(API_KEY_HEADER, "secret-key"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(principal.subject, "line-7");
+        assert!(principal.may_call("tools/list"));
+        assert!(!principal.may_call("completion"));
+
+        let bearer = authenticator
+            .authenticate(&headers("authorization", "Bearer secret-key"))
+            .await;
+        assert_eq!(bearer.unwrap().unwrap().scheme, AuthScheme::ApiKey);
+        assert!(authenticator
+            .authenticate(&headers(API_KEY_HEADER, "guess"))
+            .await
+            .is_err());
+        assert!(authenticator
+            .authenticate(&HeaderMap::new())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(authenticator.is_exempt("/health/detailed"));
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_need_admin_credentials() {
+        use tower::ServiceExt;
+
+        let key = |name: &str, allowed_methods: &[&str]| ApiKeyConfig {
+            name: name.to_string(),
+            key_sha256: hex(digest::digest(&digest::SHA256, name.as_bytes()).as_ref()),
+            allowed_methods: allowed_methods.iter().map(|method| method.to_string()).collect(),
+        };
+        let mut config = mcp_common::Config::default();
+        config.security.authentication = AuthenticationConfig {
+            require_authentication: true,
+            api_keys: vec![
+                key("line-7", &["completion"]),
+                key("auditor", &["admin/usage"]),
+                key("operator", &["admin/*"]),
+                key("root", &[]),
+            ],
+            ..Default::default()
+        };
+        let gateway = Arc::new(crate::Gateway::builder(config).deterministic().build().await.unwrap());
+        let app = crate::handlers::create_router(gateway.clone())
+            .layer(axum::middleware::from_fn_with_state(gateway, authenticate));
+        let status = |key: &str, path: &str| {
+            let request = Request::builder()
+                .uri(path)
+                .header(API_KEY_HEADER, key)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(status("line-7", "/v1/admin/usage").await, StatusCode::FORBIDDEN);
+        assert_eq!(status("auditor", "/v1/admin/usage").await, StatusCode::OK);
+        assert_eq!(status("auditor", "/v1/admin/kv").await, StatusCode::FORBIDDEN);
+        assert_eq!(status("operator", "/v1/admin/kv").await, StatusCode::OK);
+        assert_eq!(status("root", "/v1/admin/usage").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_admin_requests_are_rejected() {
+        use tower::ServiceExt;
+
+        // Authentication is optional by default, yet admin routes need it
+        let config = mcp_common::Config::default();
+        assert!(!config.security.authentication.require_authentication);
+        let gateway = Arc::new(crate::Gateway::builder(config).deterministic().build().await.unwrap());
+        let app = crate::handlers::create_router(gateway.clone())
+            .layer(axum::middleware::from_fn_with_state(gateway, authenticate));
+
+        let routes = [
+            ("GET", "/v1/admin/usage"),
+            ("POST", "/v1/admin/erasure"),
+            ("DELETE", "/v1/admin/kv/notes"),
+        ];
+        for (method, path) in routes {
+            let request = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{} {}", method, path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_es256_jwt_verified_against_cached_jwks() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .unwrap();
+        let key_pair = EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            pkcs8.as_ref(),
+            &rng,
+        )
+        .unwrap();
+        let point = key_pair.public_key().as_ref();
+
+        let config = AuthenticationConfig {
+            jwt: Some(JwtConfig {
+                jwks_url: "http://127.0.0.1:9/jwks".to_string(),
+                issuer: Some("https://idp.example".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let authenticator = Authenticator::new(config, Arc::new(SystemClock));
+        *authenticator.jwks.write().await = Some(CachedKeys {
+            keys: vec![Jwk {
+                kty: "EC".to_string(),
+                kid: Some("k1".to_string()),
+                n: None,
+                e: None,
+                crv: Some("P-256".to_string()),
+                x: Some(URL_SAFE_NO_PAD.encode(&point[1..33])),
+                y: Some(URL_SAFE_NO_PAD.encode(&point[33..65])),
+            }],
+            fetched_at: Instant::now(),
+        });
+
+        let token = |claims: Value| {
+            let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"ES256","kid":"k1"}"#);
+            let signed = format!("{}.{}", header, URL_SAFE_NO_PAD.encode(claims.to_string()));
+            let sig = key_pair.sign(&rng, signed.as_bytes()).unwrap();
+            format!("Bearer {}.{}", signed, URL_SAFE_NO_PAD.encode(sig.as_ref()))
+        };
+        let exp = chrono::Utc::now().timestamp() + 300;
+
+        let valid = token(serde_json::json!({
+            "sub": "dashboard", "iss": "https://idp.example", "exp": exp, "mcp_methods": "completion embeddings"
+        }));
+        let principal = authenticator
+            .authenticate(&headers("authorization", &valid))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(principal.scheme, AuthScheme::Jwt);
+        assert_eq!(principal.subject, "dashboard");
+        assert!(principal.may_call("embeddings"));
+        assert!(!principal.may_call("admin"));
+
+        let expired = token(
+            serde_json::json!({ "sub": "dashboard", "iss": "https://idp.example", "exp": exp - 3600 }),
+        );
+        assert!(authenticator
+            .authenticate(&headers("authorization", &expired))
+            .await
+            .is_err());
+        let foreign = token(
+            serde_json::json!({ "sub": "dashboard", "iss": "https://other.example", "exp": exp }),
+        );
+        assert!(authenticator
+            .authenticate(&headers("authorization", &foreign))
+            .await
+            .is_err());
+
+        // Claims swapped under a valid signature
+        let (_, sig) = valid.rsplit_once('.').unwrap();
+        let (header, _) = valid.split_once('.').unwrap();
+        let escalated =
+            URL_SAFE_NO_PAD.encode(serde_json::json!({ "sub": "root", "exp": exp }).to_string());
+        let tampered = format!("{}.{}.{}", header, escalated, sig);
+        assert!(authenticator
+            .authenticate(&headers("authorization", &tampered))
+            .await
+            .is_err());
+    }
+}
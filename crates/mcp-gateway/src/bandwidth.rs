@@ -0,0 +1,99 @@
+//! Persistence of the bandwidth counters
+//!
+//! Subsystems report their traffic to the process-wide meter in
+//! [`mcp_common::bandwidth`]. This restores the counters saved by the previous
+//! run on startup and saves them every `flush_interval_secs` and on shutdown,
+//! so daily and monthly totals survive restarts.
+
+use mcp_common::bandwidth::{BandwidthMeter, BandwidthUsage};
+use mcp_common::config::BandwidthConfig;
+use mcp_common::write_policy::WritePolicy;
+use mcp_common::{ComponentHealth, HealthLevel, Result, Vfs};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Saves the global bandwidth counters to gateway storage
+pub struct BandwidthLedger {
+    config: BandwidthConfig,
+    storage: Arc<dyn Vfs>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BandwidthLedger {
+    pub fn new(config: BandwidthConfig, storage: Arc<dyn Vfs>) -> Self {
+        Self {
+            config,
+            storage,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Merge the counters saved by the previous run into the meter
+    pub async fn restore(&self) {
+        match BandwidthMeter::global().load(self.storage.as_ref(), &self.config.state_path).await {
+            Ok(()) => info!("Restored bandwidth counters from {}", self.config.state_path.display()),
+            Err(e) => warn!("Failed to restore bandwidth counters, starting from zero: {}", e),
+        }
+    }
+
+    /// Save the counters periodically in the background, less often while
+    /// the write policy is relaxed
+    pub fn start(self: &Arc<Self>) {
+        let ledger = Arc::downgrade(self);
+        let interval_secs = self.config.flush_interval_secs.max(1);
+        let handle = tokio::spawn(async move {
+            loop {
+                let interval = WritePolicy::global().stretch(Duration::from_secs(interval_secs));
+                tokio::time::sleep(interval).await;
+                let Some(ledger) = ledger.upgrade() else {
+                    break;
+                };
+                if let Err(e) = ledger.flush().await {
+                    warn!("Failed to save bandwidth counters: {}", e);
+                }
+            }
+        });
+        if let Some(previous) = self.lock_task().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stop the periodic save and save one last time
+    pub async fn stop(&self) {
+        if let Some(handle) = self.lock_task().take() {
+            handle.abort();
+        }
+        if let Err(e) = self.flush().await {
+            warn!("Failed to save bandwidth counters on shutdown: {}", e);
+        }
+    }
+
+    pub async fn flush(&self) -> Result<()> {
+        BandwidthMeter::global().save(self.storage.as_ref(), &self.config.state_path).await
+    }
+
+    /// All retained daily and monthly counters
+    pub fn usage(&self) -> BandwidthUsage {
+        BandwidthMeter::global().usage()
+    }
+
+    /// Today's and this month's traffic per subsystem as health metrics
+    pub fn health(&self) -> ComponentHealth {
+        let mut metrics = HashMap::new();
+        BandwidthMeter::global().write_metrics(&mut metrics);
+        let today: u64 = self.usage().today().values().map(|traffic| traffic.total_bytes()).sum();
+        ComponentHealth {
+            status: HealthLevel::Healthy,
+            message: format!("{} bytes transferred today", today),
+            last_check: chrono::Utc::now(),
+            metrics,
+        }
+    }
+
+    fn lock_task(&self) -> std::sync::MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.task.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,130 @@
+//! Offline verification of exported audit trails
+//!
+//! `verify` checks the hash chain and signatures of an audit directory
+//! copied off a gateway, optionally pinning the public key the auditor was
+//! given; with `--key` it also decrypts every digest, and `--print` writes
+//! them as JSON lines. `load-model` verifies the trail the same way and
+//! prints a [`LoadModel`] of the traffic it records, for capacity tests.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use clap::{Parser, Subcommand};
+use mcp_gateway::audit::{verify_chain, ChainVerification, PUBLIC_KEY_FILE};
+use mcp_gateway::load_model::LoadModel;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser, Debug)]
+#[command(name = "mcp-audit", about = "Verify audit trails exported from an air-gapped gateway")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check the hash chain and signatures of an exported audit directory
+    Verify {
+        directory: PathBuf,
+
+        /// Base64 public key the records must be signed with; defaults to
+        /// the audit.pub shipped in the directory
+        #[arg(long)]
+        public_key: Option<String>,
+
+        /// Audit encryption key file, to decrypt and check every digest
+        #[arg(long)]
+        key: Option<PathBuf>,
+
+        /// Print the decrypted digests as JSON lines
+        #[arg(long, requires = "key")]
+        print: bool,
+    },
+    /// Print the arrival process, request sizes and method mix of the
+    /// requests in an exported audit directory as a JSON load model
+    LoadModel {
+        directory: PathBuf,
+
+        /// Base64 public key the records must be signed with; defaults to
+        /// the audit.pub shipped in the directory
+        #[arg(long)]
+        public_key: Option<String>,
+
+        /// Audit encryption key file
+        #[arg(long)]
+        key: PathBuf,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    match Args::parse().command {
+        Command::Verify {
+            directory,
+            public_key,
+            key,
+            print,
+        } => {
+            let key = match key {
+                Some(path) => Some(tokio::fs::read(path).await?),
+                None => None,
+            };
+            let verification = verify(&directory, public_key, key.as_deref()).await?;
+            if print {
+                for digest in &verification.digests {
+                    println!("{}", serde_json::to_string(digest)?);
+                }
+            }
+        },
+        Command::LoadModel {
+            directory,
+            public_key,
+            key,
+        } => {
+            let key = tokio::fs::read(key).await?;
+            let verification = verify(&directory, public_key, Some(&key)).await?;
+            match LoadModel::from_digests(&verification.digests) {
+                Some(model) => println!("{}", serde_json::to_string_pretty(&model)?),
+                None => anyhow::bail!("{} records no requests to model", directory.display()),
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Verify the audit trail in `directory`, reporting the outcome on stderr
+async fn verify(directory: &Path, public_key: Option<String>, key: Option<&[u8]>) -> anyhow::Result<ChainVerification> {
+    let public_key = match public_key {
+        Some(public_key) => public_key,
+        None => {
+            eprintln!("No --public-key given; trusting {} from the export", PUBLIC_KEY_FILE);
+            tokio::fs::read_to_string(directory.join(PUBLIC_KEY_FILE)).await?
+        },
+    };
+    let public_key = BASE64.decode(public_key.trim())?;
+
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            files.push((entry.file_name().to_string_lossy().into_owned(), tokio::fs::read(entry.path()).await?));
+        }
+    }
+
+    let verification = verify_chain(&files, &public_key, key)?;
+    match (verification.first_seq, verification.last_seq) {
+        (Some(first), Some(last)) => eprintln!(
+            "{}: {} records ({}..={}) in {} files verified, head {}",
+            directory.display(),
+            verification.records,
+            first,
+            last,
+            verification.files,
+            verification.last_hash
+        ),
+        _ => eprintln!("{}: no audit records found", directory.display()),
+    }
+    if verification.records > 0 && !verification.complete_from_genesis {
+        eprintln!("Warning: the trail does not start at the first record; earlier files are missing");
+    }
+    Ok(verification)
+}
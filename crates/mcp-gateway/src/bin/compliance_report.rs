@@ -0,0 +1,89 @@
+//! Command-line compliance reports for a running gateway
+//!
+//! `generate` fetches a signed report from the gateway's admin API and writes
+//! it to a file or stdout; `verify` checks the signature of a saved report,
+//! optionally against the gateway key the auditor was given.
+
+use clap::{Parser, Subcommand};
+use mcp_common::Config;
+use mcp_gateway::compliance::SignedComplianceReport;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "mcp-compliance-report", about = "Generate and verify signed compliance reports")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Fetch a signed report from the gateway
+    Generate {
+        /// Gateway base URL; defaults to the configured bind address
+        #[arg(long)]
+        gateway: Option<String>,
+
+        /// Write the report here instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Render the report as Markdown instead of signed JSON
+        #[arg(long)]
+        markdown: bool,
+    },
+    /// Check the signature of a saved JSON report
+    Verify {
+        report: PathBuf,
+
+        /// Base64 public key the report must be signed with
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    match Args::parse().command {
+        Command::Generate {
+            gateway,
+            output,
+            markdown,
+        } => {
+            let gateway = gateway.unwrap_or_else(|| {
+                let config = Config::default();
+                format!("http://{}:{}", config.gateway.bind_address, config.gateway.port)
+            });
+            let mut endpoint = format!("{}/v1/admin/compliance/report", gateway.trim_end_matches('/'));
+            if markdown {
+                endpoint.push_str("?format=markdown");
+            }
+
+            let response = reqwest::get(&endpoint).await?;
+            let status = response.status();
+            let body = response.text().await?;
+            if !status.is_success() {
+                anyhow::bail!("Report generation failed ({}): {}", status, body);
+            }
+            match output {
+                Some(path) => {
+                    tokio::fs::write(&path, &body).await?;
+                    eprintln!("Wrote compliance report to {}", path.display());
+                },
+                None => println!("{}", body),
+            }
+        },
+        Command::Verify { report, public_key } => {
+            let signed: SignedComplianceReport = serde_json::from_slice(&tokio::fs::read(&report).await?)?;
+            signed.verify(public_key.as_deref())?;
+            println!(
+                "{}: valid signature by {} (report {}, generated {})",
+                report.display(),
+                signed.signature.public_key,
+                signed.report.report_id,
+                signed.report.generated_at.to_rfc3339()
+            );
+        },
+    }
+    Ok(())
+}
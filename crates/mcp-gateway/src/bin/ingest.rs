@@ -0,0 +1,75 @@
+//! Command-line document ingestion for a running gateway
+//!
+//! Uploads text, markdown and PDF files to the gateway's knowledge ingestion
+//! endpoint. Files that have not changed since they were last ingested are
+//! reported as unchanged, so the command can be re-run freely.
+
+use base64::Engine as _;
+use clap::Parser;
+use mcp_common::Config;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "mcp-ingest", about = "Ingest documents into the gateway knowledge store")]
+struct Args {
+    /// Files to ingest
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Gateway base URL; defaults to the configured bind address
+    #[arg(long)]
+    gateway: Option<String>,
+
+    /// Ingest paths from the gateway's own storage instead of uploading them
+    #[arg(long)]
+    server_side: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let gateway = args.gateway.unwrap_or_else(|| {
+        let config = Config::default();
+        format!("http://{}:{}", config.gateway.bind_address, config.gateway.port)
+    });
+    let endpoint = format!("{}/v1/admin/knowledge/ingest", gateway.trim_end_matches('/'));
+    let client = reqwest::Client::new();
+
+    let mut failures = 0;
+    for file in &args.files {
+        let body = if args.server_side {
+            serde_json::json!({ "path": file })
+        } else {
+            let bytes = tokio::fs::read(file).await?;
+            serde_json::json!({
+                "source": file,
+                "content_base64": base64::engine::general_purpose::STANDARD.encode(bytes),
+            })
+        };
+
+        let response = client.post(&endpoint).json(&body).send().await?;
+        let status = response.status();
+        let report: serde_json::Value = response.json().await.unwrap_or_default();
+        if status.is_success() {
+            println!(
+                "{}: {} ({} chunks)",
+                file.display(),
+                report["status"].as_str().unwrap_or("unknown"),
+                report["chunks"]
+            );
+        } else {
+            failures += 1;
+            eprintln!(
+                "{}: failed ({}): {}",
+                file.display(),
+                status,
+                report["error"].as_str().unwrap_or("no details")
+            );
+        }
+    }
+
+    if failures > 0 {
+        anyhow::bail!("{} of {} files failed to ingest", failures, args.files.len());
+    }
+    Ok(())
+}
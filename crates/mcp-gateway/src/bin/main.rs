@@ -1,22 +1,49 @@
 //! MCP Gateway main executable
 
-use mcp_common::Config;
-use mcp_gateway::{Gateway, start_server};
-use tracing::{error, info};
+use clap::Parser;
+use mcp_common::config::DeploymentProfile;
+use mcp_common::{config_docs, create_vfs, redaction, Config};
+use mcp_gateway::{config_reload, listener, Gateway, start_server};
+use mcp_pipeline_guard::LogEscalation;
+use tracing::{error, info, warn};
+
+#[derive(Parser, Debug)]
+#[command(name = "mcp-gateway", about = "MCP WASM Edge Gateway")]
+struct Args {
+    /// Check every model file against its signed manifest before serving;
+    /// the production profile refuses to start with unsigned models
+    #[arg(long)]
+    verify_models: bool,
+
+    /// Print the default configuration as commented TOML and exit
+    #[arg(long)]
+    print_example_config: bool,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize basic tracing
-    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+    if args.print_example_config {
+        print!("{}", config_docs::example_toml()?);
+        return Ok(());
+    }
 
-    info!("Starting MCP WASM Edge Gateway v0.1.0");
+    // Load the configuration file named by MCP_GATEWAY_CONFIG over the defaults
+    let mut config = config_reload::load_from_env()?;
+
+    // Log at the base level, escalating degraded components
+    LogEscalation::install(config.telemetry.log_escalation.clone());
+    redaction::install_panic_hook();
 
-    // Load default configuration
-    let config = Config::default();
+    info!("Starting MCP WASM Edge Gateway v0.1.0");
     
     info!("Loaded configuration: bind_address={}:{}", 
           config.gateway.bind_address, config.gateway.port);
 
+    if args.verify_models {
+        verify_models(&mut config).await?;
+    }
+
     // Initialize gateway
     let gateway = match Gateway::new(config.clone()).await {
         Ok(g) => g,
@@ -29,7 +56,7 @@ async fn main() -> anyhow::Result<()> {
     info!("Gateway initialized successfully");
 
     // Start the server
-    let bind_addr = format!("{}:{}", config.gateway.bind_address, config.gateway.port);
+    let bind_addr = listener::listen_address(&config.gateway.bind_address, config.gateway.port);
     
     info!("Starting server on {}", bind_addr);
     
@@ -45,3 +72,28 @@ async fn main() -> anyhow::Result<()> {
     }
 }
 
+/// Check the models directory against signed manifests and keep checking
+/// every model load, enforcing signatures in the production profile
+async fn verify_models(config: &mut Config) -> anyhow::Result<()> {
+    config.models.signing.mode = config.profile.model_verification();
+    let vfs = create_vfs(&config.storage);
+    let statuses = mcp_models::verify_models(&config.models, vfs.as_ref()).await?;
+    let unsigned: Vec<_> = statuses.iter().filter(|status| !status.verified()).collect();
+    for status in &unsigned {
+        warn!(
+            "Model {} ({}) is not signed: {}",
+            status.model_id,
+            status.path.display(),
+            status.error.as_deref().unwrap_or_default()
+        );
+    }
+    info!(
+        "{} of {} model files have valid signed manifests",
+        statuses.len() - unsigned.len(),
+        statuses.len()
+    );
+    if !unsigned.is_empty() && config.profile == DeploymentProfile::Production {
+        anyhow::bail!("Refusing to serve {} unsigned models in the production profile", unsigned.len());
+    }
+    Ok(())
+}
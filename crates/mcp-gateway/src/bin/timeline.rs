@@ -0,0 +1,94 @@
+//! Command-line export of a gateway's incident timeline
+//!
+//! Fetches the alerts, scaling actions, recovery attempts, configuration
+//! changes and error spikes of a time range from the gateway's admin API,
+//! correlated into incidents, and prints them or writes them to a file.
+
+use clap::Parser;
+use mcp_common::Config;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "mcp-timeline", about = "Export the incident timeline of a gateway")]
+struct Args {
+    /// Gateway base URL; defaults to the configured bind address
+    #[arg(long)]
+    gateway: Option<String>,
+
+    /// Start of the range: an RFC 3339 time, or a duration back from now
+    /// such as `12h`, `30m` or `2d`
+    #[arg(long, default_value = "24h")]
+    since: String,
+
+    /// End of the range as an RFC 3339 time; now when unset
+    #[arg(long)]
+    until: Option<String>,
+
+    /// Comma-separated categories: alert, scaling, recovery, config,
+    /// connectivity, deployment, error_spike
+    #[arg(long)]
+    category: Option<String>,
+
+    /// Output format: text, json or jsonl
+    #[arg(long, default_value = "text")]
+    format: String,
+
+    /// Write the timeline here instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+/// Parse an RFC 3339 time or a duration back from now
+fn parse_time(value: &str) -> anyhow::Result<chrono::DateTime<chrono::Utc>> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    let split = value.len().saturating_sub(1);
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid time {:?}: expected RFC 3339 or e.g. 12h", value))?;
+    let duration = match unit {
+        "m" => chrono::Duration::minutes(amount),
+        "h" => chrono::Duration::hours(amount),
+        "d" => chrono::Duration::days(amount),
+        _ => anyhow::bail!("Invalid duration unit in {:?}: use m, h or d", value),
+    };
+    Ok(chrono::Utc::now() - duration)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let gateway = args.gateway.unwrap_or_else(|| {
+        let config = Config::default();
+        format!("http://{}:{}", config.gateway.bind_address, config.gateway.port)
+    });
+
+    let mut query = vec![
+        ("since", parse_time(&args.since)?.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+        ("format", args.format),
+    ];
+    if let Some(until) = &args.until {
+        query.push(("until", parse_time(until)?.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)));
+    }
+    if let Some(category) = args.category {
+        query.push(("category", category));
+    }
+
+    let endpoint = format!("{}/v1/admin/timeline", gateway.trim_end_matches('/'));
+    let response = reqwest::Client::new().get(&endpoint).query(&query).send().await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        anyhow::bail!("Timeline export failed ({}): {}", status, body);
+    }
+    match args.output {
+        Some(path) => {
+            tokio::fs::write(&path, &body).await?;
+            eprintln!("Wrote incident timeline to {}", path.display());
+        },
+        None => print!("{}", body),
+    }
+    Ok(())
+}
@@ -0,0 +1,347 @@
+//! Request pipeline for browser builds
+//!
+//! `EdgeGateway` is the wasm-bindgen export pages and service workers use in
+//! place of the HTTP server. `processRequest` runs each request through the
+//! same router as the native gateway. Cloud decisions are forwarded with
+//! the scope's `fetch`. Requests the router queues, and cloud requests made
+//! while the browser is offline or that fail to send, go to the IndexedDB
+//! offline queue until `syncQueue` delivers them. Browser builds ship no
+//! model engine, so local decisions go to the handler a page registers with
+//! `setLocalHandler`, or to the cloud without one. Each outcome is recorded
+//! in a bounded telemetry buffer that scripts drain with `drainTelemetry`.
+
+use chrono::{DateTime, Utc};
+use config::FileFormat;
+use js_sys::{Function, Promise, Reflect};
+use mcp_common::{Config, Error, MCPRequest, MCPResponse, Result, RoutingDecision};
+use mcp_queue::OfflineQueue;
+use mcp_router::Router;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+/// Telemetry records kept until a script drains them; the oldest are
+/// dropped first
+const TELEMETRY_CAPACITY: usize = 1000;
+
+/// How a request left the pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Local,
+    Cloud,
+    Queued,
+    Failed,
+}
+
+/// One request through the pipeline, or one queue sync
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryRecord {
+    pub timestamp: DateTime<Utc>,
+    /// `None` for queue syncs
+    pub request_id: Option<Uuid>,
+    pub method: Option<String>,
+    pub outcome: Option<Outcome>,
+    /// Model or endpoint the request went to
+    pub target: Option<String>,
+    pub latency_ms: f64,
+    pub queue_size: u32,
+    pub error: Option<String>,
+}
+
+/// Totals since the gateway was created, kept when records are drained
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TelemetryTotals {
+    pub requests: u64,
+    pub local: u64,
+    pub cloud: u64,
+    pub queued: u64,
+    pub failed: u64,
+    pub synced: u64,
+    pub dropped_records: u64,
+    pub avg_latency_ms: f64,
+}
+
+/// Bounded buffer of telemetry records for page scripts
+#[derive(Debug, Default)]
+pub struct TelemetryBuffer {
+    records: VecDeque<TelemetryRecord>,
+    totals: TelemetryTotals,
+}
+
+impl TelemetryBuffer {
+    fn record_request(&mut self, record: TelemetryRecord) {
+        let totals = &mut self.totals;
+        match record.outcome {
+            Some(Outcome::Local) => totals.local += 1,
+            Some(Outcome::Cloud) => totals.cloud += 1,
+            Some(Outcome::Queued) => totals.queued += 1,
+            Some(Outcome::Failed) | None => totals.failed += 1,
+        }
+        totals.requests += 1;
+        totals.avg_latency_ms +=
+            (record.latency_ms - totals.avg_latency_ms) / totals.requests as f64;
+        self.push(record);
+    }
+
+    fn record_sync(&mut self, synced: u32, record: TelemetryRecord) {
+        self.totals.synced += u64::from(synced);
+        self.push(record);
+    }
+
+    fn push(&mut self, record: TelemetryRecord) {
+        if self.records.len() == TELEMETRY_CAPACITY {
+            self.records.pop_front();
+            self.totals.dropped_records += 1;
+        }
+        self.records.push_back(record);
+    }
+
+    /// Take every buffered record, oldest first
+    pub fn drain(&mut self) -> Vec<TelemetryRecord> {
+        self.records.drain(..).collect()
+    }
+
+    pub fn totals(&self) -> &TelemetryTotals {
+        &self.totals
+    }
+}
+
+struct Pipeline {
+    config: Arc<Config>,
+    router: Arc<dyn Router + Send + Sync>,
+    queue: Arc<dyn OfflineQueue + Send + Sync>,
+    telemetry: RefCell<TelemetryBuffer>,
+    local_handler: RefCell<Option<Function>>,
+}
+
+impl Pipeline {
+    async fn process(&self, request: MCPRequest) -> Result<MCPResponse> {
+        let started = js_sys::Date::now();
+        let decision = self.router.route(&request).await;
+        let (outcome, target, result) = match decision {
+            Ok(RoutingDecision::Local { model_id, .. }) => match self.local_handler() {
+                Some(handler) => {
+                    let result = call_local(&handler, &request).await;
+                    (Outcome::Local, Some(model_id), result)
+                },
+                None => self.forward(&request, None).await,
+            },
+            Ok(RoutingDecision::Cloud { endpoint, .. }) => {
+                self.forward(&request, Some(endpoint)).await
+            },
+            Ok(RoutingDecision::Queue { reason, .. }) => {
+                debug!("Request {} queued by the router: {}", request.id, reason);
+                (Outcome::Queued, None, self.queue.enqueue_request(request.clone()).await)
+            },
+            Err(e) => (Outcome::Failed, None, Err(e)),
+        };
+
+        let queue_size = self.queue.queue_size().await.unwrap_or(0);
+        let outcome = if result.is_ok() { outcome } else { Outcome::Failed };
+        self.telemetry.borrow_mut().record_request(TelemetryRecord {
+            timestamp: Utc::now(),
+            request_id: Some(request.id),
+            method: Some(request.method.clone()),
+            outcome: Some(outcome),
+            target,
+            latency_ms: js_sys::Date::now() - started,
+            queue_size,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        result
+    }
+
+    /// Send `request` to the cloud, or queue it when the browser is offline
+    /// or the send fails
+    async fn forward(
+        &self,
+        request: &MCPRequest,
+        endpoint: Option<String>,
+    ) -> (Outcome, Option<String>, Result<MCPResponse>) {
+        if navigator_online() {
+            let sent = match &endpoint {
+                Some(endpoint) => self.router.forward_to_cloud(request, endpoint).await,
+                None => self.router.fallback_to_cloud(request).await,
+            };
+            match sent {
+                Ok(response) => return (Outcome::Cloud, endpoint, Ok(response)),
+                Err(e) => warn!("Request {} not sent, queueing it: {}", request.id, e),
+            }
+        }
+        (Outcome::Queued, endpoint, self.queue.enqueue_request(request.clone()).await)
+    }
+
+    fn local_handler(&self) -> Option<Function> {
+        self.local_handler.borrow().clone()
+    }
+
+    async fn sync(&self) -> Result<u32> {
+        let started = js_sys::Date::now();
+        let before = self.queue.queue_size().await?;
+        let result = self.queue.sync_with_cloud().await;
+        let after = self.queue.queue_size().await?;
+        let synced = before.saturating_sub(after);
+        self.telemetry.borrow_mut().record_sync(
+            synced,
+            TelemetryRecord {
+                timestamp: Utc::now(),
+                request_id: None,
+                method: None,
+                outcome: None,
+                target: self.config.router.cloud_endpoints.first().map(|e| e.url.clone()),
+                latency_ms: js_sys::Date::now() - started,
+                queue_size: after,
+                error: result.as_ref().err().map(|e| e.to_string()),
+            },
+        );
+        result.map(|_| synced)
+    }
+}
+
+/// Whether the scope's `navigator.onLine` reports a connection; assumed
+/// online where the browser gives no answer
+fn navigator_online() -> bool {
+    Reflect::get(&js_sys::global(), &JsValue::from_str("navigator"))
+        .and_then(|navigator| Reflect::get(&navigator, &JsValue::from_str("onLine")))
+        .ok()
+        .and_then(|online| online.as_bool())
+        .unwrap_or(true)
+}
+
+/// Run the page's local handler with the request JSON; it returns the
+/// result, or a promise of it
+async fn call_local(handler: &Function, request: &MCPRequest) -> Result<MCPResponse> {
+    let request_json = serde_json::to_string(request)?;
+    let returned = handler
+        .call1(&JsValue::NULL, &JsValue::from_str(&request_json))
+        .map_err(|e| js_error("Local handler failed", e))?;
+    let value = JsFuture::from(Promise::resolve(&returned))
+        .await
+        .map_err(|e| js_error("Local handler failed", e))?;
+    let result = match value.as_string() {
+        Some(text) => serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)),
+        None => {
+            let text: String = js_sys::JSON::stringify(&value)
+                .map_err(|e| js_error("Local handler result is not JSON", e))?
+                .into();
+            serde_json::from_str(&text)?
+        },
+    };
+    Ok(MCPResponse {
+        id: request.id,
+        result: Some(result),
+        error: None,
+        timestamp: Utc::now(),
+    })
+}
+
+fn js_error(context: &str, value: JsValue) -> Error {
+    Error::Model(format!("{}: {:?}", context, value))
+}
+
+fn to_js(error: Error) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+fn to_json<T: Serialize>(value: &T) -> std::result::Result<JsValue, JsValue> {
+    serde_json::to_string(value)
+        .map(|json| JsValue::from_str(&json))
+        .map_err(|e| to_js(e.into()))
+}
+
+/// The gateway's request pipeline for pages and service workers
+#[wasm_bindgen]
+pub struct EdgeGateway {
+    pipeline: Rc<Pipeline>,
+}
+
+#[wasm_bindgen]
+impl EdgeGateway {
+    /// Create a gateway from JSON settings laid over the defaults, as a
+    /// configuration file is; the offline queue opens the IndexedDB database
+    /// named by `queue.storage_path`
+    pub async fn create(config_json: Option<String>) -> std::result::Result<EdgeGateway, JsValue> {
+        let config = match config_json {
+            Some(json) => crate::config_reload::parse(&json, FileFormat::Json),
+            None => Ok(Config::default()),
+        }
+        .map_err(to_js)?;
+        let config = Arc::new(config);
+        let router = mcp_router::create_router(config.clone()).await.map_err(to_js)?;
+        let queue = mcp_queue::create_offline_queue(config.clone())
+            .await
+            .map_err(to_js)?;
+        info!("Browser gateway ready with {} cloud endpoints", config.router.cloud_endpoints.len());
+        Ok(EdgeGateway {
+            pipeline: Rc::new(Pipeline {
+                config,
+                router,
+                queue,
+                telemetry: RefCell::new(TelemetryBuffer::default()),
+                local_handler: RefCell::new(None),
+            }),
+        })
+    }
+
+    /// Route and process an MCP request given as JSON; resolves to the
+    /// response JSON, which reports the queue position for queued requests
+    #[wasm_bindgen(js_name = processRequest)]
+    pub fn process_request(&self, request_json: String) -> Promise {
+        let pipeline = self.pipeline.clone();
+        future_to_promise(async move {
+            let request: MCPRequest =
+                serde_json::from_str(&request_json).map_err(|e| to_js(e.into()))?;
+            let response = pipeline.process(request).await.map_err(to_js)?;
+            to_json(&response)
+        })
+    }
+
+    /// Handle requests routed to local models with `handler(requestJson)`,
+    /// which returns the result or a promise of it; `null` sends them to the
+    /// cloud
+    #[wasm_bindgen(js_name = setLocalHandler)]
+    pub fn set_local_handler(&self, handler: Option<Function>) {
+        *self.pipeline.local_handler.borrow_mut() = handler;
+    }
+
+    /// Send the oldest batch of queued requests to the first cloud endpoint;
+    /// resolves to the number delivered
+    #[wasm_bindgen(js_name = syncQueue)]
+    pub fn sync_queue(&self) -> Promise {
+        let pipeline = self.pipeline.clone();
+        future_to_promise(async move {
+            let synced = pipeline.sync().await.map_err(to_js)?;
+            Ok(JsValue::from(synced))
+        })
+    }
+
+    /// Resolves to the number of requests in the offline queue
+    #[wasm_bindgen(js_name = queueSize)]
+    pub fn queue_size(&self) -> Promise {
+        let pipeline = self.pipeline.clone();
+        future_to_promise(async move {
+            let size = pipeline.queue.queue_size().await.map_err(to_js)?;
+            Ok(JsValue::from(size))
+        })
+    }
+
+    /// Take the buffered telemetry records as a JSON array, oldest first
+    #[wasm_bindgen(js_name = drainTelemetry)]
+    pub fn drain_telemetry(&self) -> std::result::Result<JsValue, JsValue> {
+        let records = self.pipeline.telemetry.borrow_mut().drain();
+        to_json(&records)
+    }
+
+    /// Request totals since the gateway was created, as JSON
+    #[wasm_bindgen(js_name = telemetryTotals)]
+    pub fn telemetry_totals(&self) -> std::result::Result<JsValue, JsValue> {
+        to_json(self.pipeline.telemetry.borrow().totals())
+    }
+}
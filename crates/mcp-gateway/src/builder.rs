@@ -0,0 +1,491 @@
+//! Gateway construction with injectable components
+//!
+//! [`Gateway::new`](crate::Gateway::new) builds every subsystem from
+//! configuration. [`GatewayBuilder`] lets embedders replace individual
+//! subsystems (router, model engine, offline queue, security manager,
+//! telemetry collector) with their own implementations of the component
+//! traits without forking the crate; components that are not replaced are
+//! built from configuration as usual. Components are accepted as the
+//! `mcp_api::v1` handles, the surface kept stable for plugins. A
+//! [`RequestPrioritizer`](mcp_api::v1::RequestPrioritizer) can be added the
+//! same way to rank requests by product-specific importance.
+
+use crate::gateway::Gateway;
+use crate::testing::{InMemoryQueue, ScriptedCloudClient, StubModelEngine};
+use mcp_common::clock::{Clock, FakeClock};
+use mcp_common::config::StorageBackend;
+use mcp_api::v1::{
+    SharedCloudTransport, SharedModelEngine, SharedPrioritizer, SharedQueue, SharedRouter, SharedSecurityManager,
+    SharedTelemetryCollector,
+};
+use mcp_common::{Config, Result};
+use std::sync::Arc;
+
+/// Builder for a [`Gateway`] with optional component overrides
+pub struct GatewayBuilder {
+    pub(crate) config: Config,
+    pub(crate) router: Option<SharedRouter>,
+    pub(crate) model_engine: Option<SharedModelEngine>,
+    pub(crate) queue: Option<SharedQueue>,
+    pub(crate) security: Option<SharedSecurityManager>,
+    pub(crate) telemetry: Option<SharedTelemetryCollector>,
+    pub(crate) cloud_transport: Option<SharedCloudTransport>,
+    pub(crate) prioritizer: Option<SharedPrioritizer>,
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+}
+
+impl GatewayBuilder {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            router: None,
+            model_engine: None,
+            queue: None,
+            security: None,
+            telemetry: None,
+            cloud_transport: None,
+            prioritizer: None,
+            clock: None,
+        }
+    }
+
+    /// Use a custom router instead of the intelligent router
+    ///
+    /// A custom router does its own cloud forwarding, so any transport set
+    /// with [`with_cloud_transport`](Self::with_cloud_transport) is ignored.
+    pub fn with_router(mut self, router: SharedRouter) -> Self {
+        self.router = Some(router);
+        self
+    }
+
+    /// Use a custom model engine instead of the standard one
+    pub fn with_model_engine(mut self, model_engine: SharedModelEngine) -> Self {
+        self.model_engine = Some(model_engine);
+        self
+    }
+
+    /// Use a custom offline queue instead of the persistent one
+    pub fn with_queue(mut self, queue: SharedQueue) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    /// Use a custom security manager instead of the standard one
+    pub fn with_security_manager(mut self, security: SharedSecurityManager) -> Self {
+        self.security = Some(security);
+        self
+    }
+
+    /// Use a custom telemetry collector instead of the standard one
+    pub fn with_telemetry_collector(mut self, telemetry: SharedTelemetryCollector) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Forward cloud requests through `transport` instead of HTTP
+    pub fn with_cloud_transport(mut self, transport: SharedCloudTransport) -> Self {
+        self.cloud_transport = Some(transport);
+        self
+    }
+
+    /// Let `prioritizer` set the priority of each request before admission
+    /// control and per-model scheduling see it
+    pub fn with_prioritizer(mut self, prioritizer: SharedPrioritizer) -> Self {
+        self.prioritizer = Some(prioritizer);
+        self
+    }
+
+    /// Read gateway time from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Fill every component not already supplied with its in-memory test
+    /// double and keep storage in memory
+    ///
+    /// Supply components with the `with_*` methods first to keep handles for
+    /// scripting responses, advancing the clock or inspecting the queue.
+    pub fn deterministic(mut self) -> Self {
+        self.config.storage.backend = StorageBackend::Memory;
+        let clock = self
+            .clock
+            .get_or_insert_with(|| Arc::new(FakeClock::default()))
+            .clone();
+        if self.model_engine.is_none() {
+            self.model_engine = Some(Arc::new(StubModelEngine::with_clock(clock.clone())));
+        }
+        if self.queue.is_none() {
+            let capacity = self.config.queue.max_queue_size as usize;
+            self.queue = Some(Arc::new(InMemoryQueue::with_clock(capacity, clock.clone())));
+        }
+        if self.cloud_transport.is_none() {
+            self.cloud_transport = Some(Arc::new(ScriptedCloudClient::with_clock(clock)));
+        }
+        self
+    }
+
+    /// Assemble the gateway
+    pub async fn build(self) -> Result<Gateway> {
+        Gateway::from_builder(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use chrono::Duration;
+    use mcp_common::config::{CloudEndpoint, CloudProviderConfig, CloudRoute, RoutingRule, RoutingTarget};
+    use mcp_common::metrics::{ComponentHealth, HealthLevel};
+    use mcp_common::{
+        AuthScheme, Error, MCPRequest, MCPResponse, ModelId, Principal, Priority, RequestContext, RoutingDecision,
+        DEFAULT_WEIGHT,
+    };
+    use mcp_models::StreamChunk;
+    use mcp_router::Router;
+    use std::collections::HashMap;
+
+    /// Router that sends everything to one local model
+    struct PinnedRouter;
+
+    #[async_trait]
+    impl Router for PinnedRouter {
+        async fn route(&self, _request: &MCPRequest) -> Result<RoutingDecision> {
+            Ok(RoutingDecision::Local {
+                model_id: "site-model".to_string(),
+                estimated_latency_ms: 10,
+            })
+        }
+
+        async fn forward_to_cloud(&self, _request: &MCPRequest, _endpoint: &str) -> Result<MCPResponse> {
+            Err(Error::Routing("Cloud disabled".to_string()))
+        }
+
+        async fn fallback_to_cloud(&self, _request: &MCPRequest) -> Result<MCPResponse> {
+            Err(Error::Routing("Cloud disabled".to_string()))
+        }
+
+        fn available_models(&self) -> Vec<ModelId> {
+            vec!["site-model".to_string()]
+        }
+
+        async fn update_metrics(&self, _metrics: &mcp_common::PerformanceMetrics) -> Result<()> {
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<ComponentHealth> {
+            Ok(ComponentHealth {
+                status: HealthLevel::Healthy,
+                message: "Pinned router".to_string(),
+                last_check: chrono::Utc::now(),
+                metrics: HashMap::new(),
+            })
+        }
+
+        async fn shutdown(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request(method: &str, gateway: &Gateway) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "builder-test".to_string(),
+            method: method.to_string(),
+            params: HashMap::new(),
+            context: None,
+            timestamp: gateway.clock().now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_gateway_replays_scripted_cloud() {
+        let mut config = Config::default();
+        config.router.cloud_endpoints = vec![CloudEndpoint {
+            name: "scripted".to_string(),
+            url: "https://cloud.test".to_string(),
+            api_key: None,
+            timeout_ms: 1000,
+            max_retries: 0,
+            connect_timeout_ms: None,
+            region: None,
+            provider: Default::default(),
+            compression: Default::default(),
+        }];
+        let cloud = Arc::new(ScriptedCloudClient::new());
+        cloud.push_response(serde_json::json!({"text": "from the cloud"}));
+        let gateway = Gateway::builder(config)
+            .with_cloud_transport(cloud.clone())
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+
+        let response = gateway.process_request(request("completion", &gateway)).await.unwrap();
+        assert_eq!(response.result.unwrap()["text"], "from the cloud");
+        assert_eq!(cloud.sent().len(), 1);
+        assert_eq!(cloud.sent()[0].0, "https://cloud.test");
+    }
+
+    #[tokio::test]
+    async fn test_cloud_route_fails_over_between_providers() {
+        let endpoint = |name: &str, provider| CloudEndpoint {
+            name: name.to_string(),
+            url: format!("https://{}.test", name),
+            api_key: None,
+            timeout_ms: 1000,
+            max_retries: 0,
+            connect_timeout_ms: None,
+            region: None,
+            provider,
+            compression: Default::default(),
+        };
+        let mut config = Config::default();
+        config.router.cloud_endpoints = vec![
+            endpoint("openai", CloudProviderConfig::OpenAi { model: None }),
+            endpoint("anthropic", CloudProviderConfig::Anthropic {
+                model: None,
+                version: None,
+                max_tokens: None,
+            }),
+        ];
+        config.router.routes = vec![CloudRoute {
+            methods: vec!["completion".to_string()],
+            endpoints: vec!["anthropic".to_string(), "openai".to_string()],
+        }];
+        let cloud = Arc::new(ScriptedCloudClient::new());
+        cloud.push_error(Error::Network("anthropic request failed with status 529: Overloaded".to_string()));
+        cloud.push_response(serde_json::json!({"text": "from the backup"}));
+        let gateway = Gateway::builder(config)
+            .with_cloud_transport(cloud.clone())
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+
+        let response = gateway.process_request(request("completion", &gateway)).await.unwrap();
+        assert_eq!(response.result.unwrap()["text"], "from the backup");
+        let endpoints: Vec<String> = cloud.sent().into_iter().map(|(endpoint, _)| endpoint).collect();
+        assert_eq!(endpoints, ["https://anthropic.test", "https://openai.test"]);
+    }
+
+    #[tokio::test]
+    async fn test_fake_clock_drives_gateway_uptime() {
+        let clock = Arc::new(FakeClock::default());
+        let gateway = Gateway::builder(Config::default())
+            .with_clock(clock.clone())
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+
+        clock.advance(Duration::seconds(90));
+        let health = gateway.health_check().await.unwrap();
+        assert_eq!(health.uptime_seconds, 90);
+        assert_eq!(gateway.state().await.last_health_check, clock.now());
+    }
+
+    #[tokio::test]
+    async fn test_custom_router_replaces_intelligent_router() {
+        let engine = Arc::new(StubModelEngine::new());
+        let gateway = Gateway::builder(Config::default())
+            .with_router(Arc::new(PinnedRouter))
+            .with_model_engine(engine.clone())
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+
+        let response = gateway.process_request(request("completion", &gateway)).await.unwrap();
+        assert_eq!(response.result.unwrap()["model"], "site-model");
+        assert_eq!(engine.calls()[0].1, "site-model");
+        assert_eq!(gateway.capabilities().models, vec!["site-model".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_window_rejects_replayed_request() {
+        let mut config = Config::default();
+        config.cluster.dedup_window_secs = 60;
+        let gateway = Gateway::builder(config)
+            .with_router(Arc::new(PinnedRouter))
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+
+        let first = request("completion", &gateway);
+        let replay = first.clone();
+        assert!(gateway.process_request(first).await.is_ok());
+        assert!(matches!(
+            gateway.process_request(replay).await,
+            Err(Error::InvalidRequest(_))
+        ));
+        assert!(gateway.process_request(request("completion", &gateway)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_prioritizer_sets_request_priority() {
+        let prioritizer = |context: &RequestContext| match &context.principal {
+            Some(principal) if principal.subject == "safety-panel" => (Priority::Critical, 10),
+            _ => (context.priority, DEFAULT_WEIGHT),
+        };
+        let gateway = Gateway::builder(Config::default())
+            .with_router(Arc::new(PinnedRouter))
+            .with_prioritizer(Arc::new(prioritizer))
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+
+        let mut alert = request("safety_alert", &gateway);
+        alert.set_principal(Principal {
+            subject: "safety-panel".to_string(),
+            scheme: AuthScheme::ApiKey,
+            allowed_methods: None,
+        });
+        assert!(gateway.process_request(alert).await.is_ok());
+        assert!(gateway.process_request(request("completion", &gateway)).await.is_ok());
+        let requests = |priority| {
+            gateway
+                .priority_latency()
+                .into_iter()
+                .find(|report| report.priority == priority)
+                .map_or(0, |report| report.requests)
+        };
+        assert_eq!(requests(Priority::Critical), 1);
+        assert_eq!(requests(Priority::Normal), 1);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_requests_are_audited_with_provenance() {
+        let mut config = Config::default();
+        config.audit.enabled = true;
+        config.gateway.response_provenance.enabled = true;
+        let gateway = Gateway::builder(config)
+            .with_router(Arc::new(PinnedRouter))
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+        let gateway = Arc::new(gateway);
+
+        let mut stream = gateway
+            .process_request_streaming(request("completion", &gateway))
+            .await
+            .unwrap();
+        let mut last = None;
+        while let Some(chunk) = stream.recv().await {
+            last = Some(chunk.unwrap());
+        }
+        let Some(StreamChunk::Done(response)) = last else {
+            panic!("Stream ended without its final response");
+        };
+        let provenance = &response.result.unwrap()["provenance"];
+        assert_eq!(provenance["origin"], "local");
+        assert_eq!(provenance["model_id"], "site-model");
+
+        let health = gateway.health_check().await.unwrap();
+        assert_eq!(health.components["audit"].metrics["records_written"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_requests_are_routed_on_normalized_text() {
+        let mut config = Config::default();
+        config.normalization.enabled = true;
+        config.router.policy.enabled = true;
+        config.router.policy.rules = vec![RoutingRule {
+            name: "german".to_string(),
+            languages: vec!["de".to_string()],
+            target: RoutingTarget::Local,
+            model: Some("de-model".to_string()),
+            ..Default::default()
+        }];
+        let engine = Arc::new(StubModelEngine::new());
+        let gateway = Gateway::builder(config)
+            .with_model_engine(engine.clone())
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+        let gateway = Arc::new(gateway);
+
+        let mut request = request("completion", &gateway);
+        request.params.insert(
+            "prompt".to_string(),
+            serde_json::json!("Wie ist das Wetter morgen, und kann ich bitte die Fenster öffnen?"),
+        );
+        let mut stream = gateway.process_request_streaming(request).await.unwrap();
+        while stream.recv().await.is_some() {}
+        assert_eq!(engine.calls(), [("completion".to_string(), "de-model".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_requests_are_accounted_to_their_tenant() {
+        let mut config = Config::default();
+        config.gateway.resource_accounting.include_in_response = true;
+        let gateway = Gateway::builder(config)
+            .with_router(Arc::new(PinnedRouter))
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+        let gateway = Arc::new(gateway);
+
+        let mut request = request("completion", &gateway);
+        request.params.insert("tenant".to_string(), serde_json::json!("line-7"));
+        let mut stream = gateway.process_request_streaming(request).await.unwrap();
+        let mut last = None;
+        while let Some(chunk) = stream.recv().await {
+            last = Some(chunk.unwrap());
+        }
+        let Some(StreamChunk::Done(response)) = last else {
+            panic!("Stream ended without its final response");
+        };
+        assert!(response.result.unwrap().get("resource_usage").is_some());
+        assert_eq!(gateway.tenant_usage().await["line-7"].requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_rate_limits_apply_to_the_running_gateway() {
+        use tower::ServiceExt;
+
+        let gateway = Gateway::builder(Config::default())
+            .with_router(Arc::new(PinnedRouter))
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+        let gateway = Arc::new(gateway);
+        let app = crate::handlers::create_router(gateway.clone()).layer(gateway.rate_limit_layer().clone());
+        let health = || {
+            let request = axum::extract::Request::builder().uri("/health").body(axum::body::Body::empty()).unwrap();
+            app.clone().oneshot(request)
+        };
+        assert!(gateway.process_request(request("completion", &gateway)).await.is_ok());
+        assert_eq!(health().await.unwrap().status(), axum::http::StatusCode::OK);
+
+        let mut config = Config::default();
+        config.security.rate_limits.device_requests_per_minute = 1;
+        config.security.rate_limits.client_requests_per_window = 1;
+        config.security.rate_limits.client_window_secs = 30;
+        gateway.apply_config(&config);
+
+        // Two more requests from the device cannot both fit one request a
+        // minute; distinct prompts keep them out of the response cache
+        let mut rejected = 0;
+        for attempt in 0..2 {
+            let mut request = request("completion", &gateway);
+            request.params.insert("prompt".to_string(), serde_json::json!(format!("attempt {}", attempt)));
+            match gateway.process_request(request).await {
+                Err(Error::Security(message)) if message == "Rate limit exceeded" => rejected += 1,
+                result => assert!(result.is_ok()),
+            }
+        }
+        assert!(rejected >= 1);
+
+        let response = health().await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "30");
+    }
+}
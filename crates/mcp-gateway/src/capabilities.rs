@@ -0,0 +1,115 @@
+//! Capability negotiation with clients
+//!
+//! Clients call `mcp.capabilities` (or read the first frame of a WebSocket
+//! session) to learn which methods, models, payload sizes and protocol
+//! extensions this gateway supports, so they can adapt instead of failing on
+//! features a particular device does not offer.
+
+use crate::conversations::{EXPORT_METHOD, IMPORT_METHOD};
+use crate::peripherals::CAMERA_CAPTURE_METHOD;
+use mcp_common::{Config, ModelId};
+use mcp_models::{
+    EMBEDDING_BATCH_METHOD, LIST_MODELS_METHOD, RETRIEVAL_INDEX_METHOD, RETRIEVAL_SEARCH_METHOD,
+};
+use serde::{Deserialize, Serialize};
+
+/// Method clients use to request the capability document
+pub const CAPABILITIES_METHOD: &str = "mcp.capabilities";
+
+/// Version of the gateway request/response protocol
+pub const PROTOCOL_VERSION: &str = "1.0";
+
+/// Methods served by the inference pipeline
+pub const INFERENCE_METHODS: &[&str] = &["completion", "embedding", "chat", "summarization"];
+
+/// Optional protocol behaviour clients may rely on
+pub const PROTOCOL_EXTENSIONS: &[&str] = &["deadlines", "maintenance-mode", "offline-queue"];
+
+/// Capability document advertised to clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub protocol_version: String,
+    pub gateway_version: String,
+    pub methods: Vec<String>,
+    pub models: Vec<ModelId>,
+    pub limits: PayloadLimits,
+    pub streaming: StreamingSupport,
+    pub extensions: Vec<String>,
+}
+
+/// Request size limits enforced by the gateway
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadLimits {
+    pub max_request_bytes: u64,
+    pub max_method_length: usize,
+}
+
+/// Transports and streaming modes available to clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamingSupport {
+    pub websocket: bool,
+    pub token_streaming: bool,
+}
+
+impl Capabilities {
+    /// Build the capability document for this gateway
+    pub fn new(config: &Config, models: Vec<ModelId>) -> Self {
+        let mut methods: Vec<String> = INFERENCE_METHODS
+            .iter()
+            .chain([&EMBEDDING_BATCH_METHOD, &CAPABILITIES_METHOD, &LIST_MODELS_METHOD])
+            .map(|method| method.to_string())
+            .collect();
+        if config.models.retrieval.enabled {
+            methods.push(RETRIEVAL_SEARCH_METHOD.to_string());
+            methods.push(RETRIEVAL_INDEX_METHOD.to_string());
+        }
+        if config.conversations.enabled {
+            methods.push(EXPORT_METHOD.to_string());
+            methods.push(IMPORT_METHOD.to_string());
+        }
+        if !config.peripherals.cameras.is_empty() {
+            methods.push(CAMERA_CAPTURE_METHOD.to_string());
+        }
+
+        Self {
+            protocol_version: PROTOCOL_VERSION.to_string(),
+            gateway_version: env!("CARGO_PKG_VERSION").to_string(),
+            methods,
+            models,
+            limits: PayloadLimits {
+                max_request_bytes: config.gateway.max_request_size_bytes,
+                max_method_length: crate::handlers::MAX_METHOD_LENGTH,
+            },
+            streaming: StreamingSupport {
+                websocket: true,
+                token_streaming: true,
+            },
+            extensions: PROTOCOL_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+        }
+    }
+
+    /// Whether a method is advertised
+    pub fn supports_method(&self, method: &str) -> bool {
+        self.methods.iter().any(|m| m == method)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_advertise_handshake_method_and_limits() {
+        let config = Config::default();
+        let capabilities = Capabilities::new(&config, vec!["tinyllama-1.1b".to_string()]);
+
+        assert!(capabilities.supports_method(CAPABILITIES_METHOD));
+        assert!(capabilities.supports_method(LIST_MODELS_METHOD));
+        assert!(capabilities.supports_method("completion"));
+        assert!(!capabilities.supports_method("shell"));
+        assert_eq!(
+            capabilities.limits.max_request_bytes,
+            config.gateway.max_request_size_bytes
+        );
+    }
+}
@@ -47,7 +47,9 @@ pub struct CircuitBreaker {
     state: Arc<RwLock<CircuitState>>,
     failure_count: AtomicU32,
     success_count: AtomicU32,
+    /// Milliseconds after `created` of the last failure
     last_failure_time: AtomicU64,
+    created: Instant,
     name: String,
 }
 
@@ -59,6 +61,7 @@ impl CircuitBreaker {
             failure_count: AtomicU32::new(0),
             success_count: AtomicU32::new(0),
             last_failure_time: AtomicU64::new(0),
+            created: Instant::now(),
             name: name.into(),
         }
     }
@@ -71,8 +74,8 @@ impl CircuitBreaker {
             CircuitState::Open => {
                 // Check if timeout has elapsed
                 let last_failure = self.last_failure_time.load(Ordering::Relaxed);
-                let now = Instant::now().elapsed().as_secs();
-                if now - last_failure >= self.config.timeout.as_secs() {
+                let now = self.elapsed_ms();
+                if now.saturating_sub(last_failure) >= self.config.timeout.as_millis() as u64 {
                     drop(state);
                     self.transition_to_half_open().await;
                     true
@@ -109,10 +112,7 @@ impl CircuitBreaker {
     /// Record a failed execution
     pub async fn record_failure(&self) {
         let failures = self.failure_count.fetch_add(1, Ordering::Relaxed) + 1;
-        self.last_failure_time.store(
-            Instant::now().elapsed().as_secs(),
-            Ordering::Relaxed,
-        );
+        self.last_failure_time.store(self.elapsed_ms(), Ordering::Relaxed);
 
         let state = self.state.read().await;
         match *state {
@@ -132,6 +132,10 @@ impl CircuitBreaker {
         }
     }
 
+    fn elapsed_ms(&self) -> u64 {
+        self.created.elapsed().as_millis() as u64
+    }
+
     /// Get current state
     pub async fn state(&self) -> CircuitState {
         self.state.read().await.clone()
@@ -0,0 +1,183 @@
+//! Per-device clock skew detection
+//!
+//! Every request carries the timestamp reported by the device. Comparing it
+//! with gateway time gives a per-device skew estimate (smoothed, so a single
+//! slow network hop does not flag a device). Requests from devices with
+//! significant skew get their timestamp corrected to gateway time before
+//! they reach telemetry or the offline queue, and the estimate is exposed via
+//! the admin API and returned to clients as a correction hint.
+
+use chrono::{DateTime, Duration, Utc};
+use mcp_common::config::ClockSkewConfig;
+use mcp_common::clock::{self, Clock};
+use mcp_common::redaction;
+use mcp_common::DeviceId;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// Result of comparing one request timestamp with gateway time
+#[derive(Debug, Clone, Copy)]
+pub struct SkewObservation {
+    /// Smoothed skew; positive means the device clock is ahead
+    pub skew_ms: i64,
+    /// Reported timestamp shifted onto gateway time
+    pub corrected: DateTime<Utc>,
+    pub significant: bool,
+}
+
+/// Skew estimate for one device, as exposed by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceSkewReport {
+    pub device_id: DeviceId,
+    pub skew_ms: i64,
+    pub last_sample_ms: i64,
+    pub samples: u64,
+    pub significant: bool,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+struct DeviceSkew {
+    skew_ms: f64,
+    last_sample_ms: i64,
+    samples: u64,
+    last_seen: DateTime<Utc>,
+}
+
+/// Tracks clock skew per device
+pub struct ClockSkewTracker {
+    config: ClockSkewConfig,
+    devices: RwLock<HashMap<DeviceId, DeviceSkew>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ClockSkewTracker {
+    pub fn new(config: ClockSkewConfig) -> Self {
+        Self::with_clock(config, clock::system_clock())
+    }
+
+    /// Tracker that reads gateway time from `clock`
+    pub fn with_clock(config: ClockSkewConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            devices: RwLock::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    /// Record a device-reported timestamp and return the current estimate
+    pub async fn observe(&self, device_id: &str, reported: DateTime<Utc>) -> SkewObservation {
+        let now = self.clock.now();
+        let sample_ms = reported.signed_duration_since(now).num_milliseconds();
+        let smoothing = self.config.smoothing.clamp(f64::EPSILON, 1.0);
+
+        let mut devices = self.devices.write().await;
+        if !devices.contains_key(device_id) && devices.len() >= self.config.max_tracked_devices {
+            if let Some(stale) = devices
+                .iter()
+                .min_by_key(|(_, skew)| skew.last_seen)
+                .map(|(id, _)| id.clone())
+            {
+                devices.remove(&stale);
+            }
+        }
+
+        let entry = devices.entry(device_id.to_string()).or_insert(DeviceSkew {
+            skew_ms: sample_ms as f64,
+            last_sample_ms: sample_ms,
+            samples: 0,
+            last_seen: now,
+        });
+        if entry.samples > 0 {
+            entry.skew_ms += smoothing * (sample_ms as f64 - entry.skew_ms);
+        }
+        entry.last_sample_ms = sample_ms;
+        entry.samples += 1;
+        entry.last_seen = now;
+
+        let skew_ms = entry.skew_ms.round() as i64;
+        let significant = self.is_significant(skew_ms);
+        if significant && entry.samples == 1 {
+            warn!(
+                "Device {} clock is skewed by {}ms relative to the gateway",
+                redaction::id(device_id),
+                skew_ms
+            );
+        }
+
+        SkewObservation {
+            skew_ms,
+            corrected: reported - Duration::milliseconds(skew_ms),
+            significant,
+        }
+    }
+
+    /// Skew estimate for a single device
+    pub async fn device(&self, device_id: &str) -> Option<DeviceSkewReport> {
+        self.devices
+            .read()
+            .await
+            .get(device_id)
+            .map(|skew| self.report(device_id, skew))
+    }
+
+    /// Skew estimates for every tracked device, most skewed first
+    pub async fn devices(&self) -> Vec<DeviceSkewReport> {
+        let mut reports: Vec<_> = self
+            .devices
+            .read()
+            .await
+            .iter()
+            .map(|(id, skew)| self.report(id, skew))
+            .collect();
+        reports.sort_by_key(|report| std::cmp::Reverse(report.skew_ms.abs()));
+        reports
+    }
+
+    fn report(&self, device_id: &str, skew: &DeviceSkew) -> DeviceSkewReport {
+        let skew_ms = skew.skew_ms.round() as i64;
+        DeviceSkewReport {
+            device_id: device_id.to_string(),
+            skew_ms,
+            last_sample_ms: skew.last_sample_ms,
+            samples: skew.samples,
+            significant: self.is_significant(skew_ms),
+            last_seen: skew.last_seen,
+        }
+    }
+
+    fn is_significant(&self, skew_ms: i64) -> bool {
+        skew_ms.unsigned_abs() > self.config.significant_skew_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_future_dated_device_is_flagged_and_corrected() {
+        let tracker = ClockSkewTracker::new(ClockSkewConfig::default());
+        let reported = Utc::now() + Duration::minutes(10);
+
+        let observation = tracker.observe("sensor-7", reported).await;
+        assert!(observation.significant);
+        assert!(observation.skew_ms > 9 * 60 * 1000);
+        assert!((observation.corrected - Utc::now()).num_seconds().abs() < 5);
+
+        let report = tracker.device("sensor-7").await.unwrap();
+        assert!(report.significant);
+        assert_eq!(report.samples, 1);
+    }
+
+    #[tokio::test]
+    async fn test_small_skew_is_not_significant() {
+        let tracker = ClockSkewTracker::new(ClockSkewConfig::default());
+        let observation = tracker.observe("sensor-1", Utc::now()).await;
+        assert!(!observation.significant);
+        assert!(tracker.devices().await.len() == 1);
+    }
+}
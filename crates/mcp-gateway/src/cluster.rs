@@ -0,0 +1,363 @@
+//! Consistent hashing of devices to cluster members
+//!
+//! In cluster mode every device is owned by one gateway, chosen by a hash
+//! ring with virtual nodes, so session state, caches and queues for a device
+//! stay on one member. Gateways that receive a request for a device they do
+//! not own proxy it to the owner, falling back to local processing if the
+//! owner is unreachable. When membership changes, devices whose owner moved
+//! stay with their previous owner for a grace period so in-flight sessions
+//! drain instead of being cut over mid-conversation.
+//!
+//! Membership is updated per gateway (config or admin API); operators are
+//! expected to push the same member list to every gateway.
+
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::config::{ClusterMember, ClusterRoutingConfig};
+use mcp_common::crypto::digest;
+use mcp_common::{Error, Result};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Header marking a request proxied by another member; it is always handled locally
+pub const FORWARDED_HEADER: &str = "X-MCP-Cluster-Forwarded";
+
+/// Who handles a device's requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ownership {
+    Local,
+    Remote(ClusterMember),
+}
+
+/// Ownership of one device, as reported by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct OwnershipInfo {
+    pub device_id: String,
+    pub owner: Option<String>,
+    pub local: bool,
+    /// New owner the device moves to once the rebalance grace period ends
+    pub moving_to: Option<String>,
+}
+
+/// Member share of the ring
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberStatus {
+    pub id: String,
+    pub url: String,
+    /// Fraction of the hash space owned by this member
+    pub share: f64,
+}
+
+/// Cluster routing summary for the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterStatus {
+    pub enabled: bool,
+    pub node_id: String,
+    pub members: Vec<MemberStatus>,
+    /// Seconds until devices moved by the last membership change switch owner
+    pub rebalance_remaining_secs: Option<u64>,
+    pub proxied: u64,
+    pub proxy_failures: u64,
+}
+
+fn hash(value: &str) -> u64 {
+    let digest = digest::digest(&digest::SHA256, value.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest.as_ref()[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// Hash ring with `virtual_nodes` points per member
+struct HashRing {
+    members: Vec<ClusterMember>,
+    /// `(point, member index)`, sorted by point
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    fn new(members: Vec<ClusterMember>, virtual_nodes: u32) -> Self {
+        let mut points: Vec<(u64, usize)> = members
+            .iter()
+            .enumerate()
+            .flat_map(|(index, member)| {
+                (0..virtual_nodes).map(move |vnode| (hash(&format!("{}#{}", member.id, vnode)), index))
+            })
+            .collect();
+        points.sort_unstable();
+        Self { members, points }
+    }
+
+    fn owner(&self, device_id: &str) -> Option<&ClusterMember> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let point = hash(device_id);
+        let slot = self.points.partition_point(|(p, _)| *p < point) % self.points.len();
+        Some(&self.members[self.points[slot].1])
+    }
+
+    /// Fraction of the hash space owned by each member
+    fn shares(&self) -> Vec<f64> {
+        let mut shares = vec![0.0; self.members.len()];
+        for (i, (point, _)) in self.points.iter().enumerate() {
+            // Each point owns the arc from the previous point up to itself
+            let previous = if i == 0 {
+                self.points[self.points.len() - 1].0
+            } else {
+                self.points[i - 1].0
+            };
+            let owner = self.points[i].1;
+            shares[owner] += point.wrapping_sub(previous) as f64 / u64::MAX as f64;
+        }
+        if self.points.len() == 1 {
+            shares[0] = 1.0;
+        }
+        shares
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.members.iter().any(|member| member.id == id)
+    }
+}
+
+struct Rings {
+    current: HashRing,
+    /// Ring before the last membership change, used until the deadline
+    previous: Option<(HashRing, Instant)>,
+}
+
+/// Device ownership and proxying for cluster mode
+pub struct ClusterMembership {
+    config: ClusterRoutingConfig,
+    rings: RwLock<Rings>,
+    client: reqwest::Client,
+    proxied: AtomicU64,
+    proxy_failures: AtomicU64,
+}
+
+impl ClusterMembership {
+    pub fn new(config: ClusterRoutingConfig) -> Self {
+        let current = HashRing::new(config.members.clone(), config.virtual_nodes);
+        Self {
+            rings: RwLock::new(Rings { current, previous: None }),
+            client: reqwest::Client::new(),
+            proxied: AtomicU64::new(0),
+            proxy_failures: AtomicU64::new(0),
+            config,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.config.node_id
+    }
+
+    /// Replace the member list, starting a graceful rebalance
+    pub fn set_members(&self, members: Vec<ClusterMember>) -> Result<()> {
+        if !members.iter().any(|member| member.id == self.config.node_id) {
+            return Err(Error::InvalidRequest(format!(
+                "Members must include this node ({})",
+                self.config.node_id
+            )));
+        }
+        let mut rings = self.rings.write();
+        let new_ring = HashRing::new(members, self.config.virtual_nodes);
+        let old_ring = std::mem::replace(&mut rings.current, new_ring);
+        let deadline = Instant::now() + Duration::from_secs(self.config.rebalance_grace_secs);
+        rings.previous = Some((old_ring, deadline));
+        info!(
+            "Cluster membership changed to {} members; rebalancing over {}s",
+            rings.current.members.len(),
+            self.config.rebalance_grace_secs
+        );
+        Ok(())
+    }
+
+    /// Owner of a device, keeping the previous owner during a rebalance
+    pub fn ownership(&self, device_id: &str) -> OwnershipInfo {
+        let rings = self.rings.read();
+        let target = rings.current.owner(device_id).map(|member| member.id.clone());
+        let previous = rings
+            .previous
+            .as_ref()
+            .filter(|(_, deadline)| Instant::now() < *deadline)
+            .and_then(|(ring, _)| ring.owner(device_id))
+            .filter(|member| rings.current.contains(&member.id))
+            .map(|member| member.id.clone());
+
+        let (owner, moving_to) = match previous {
+            Some(previous) if Some(&previous) != target.as_ref() => (Some(previous), target),
+            _ => (target, None),
+        };
+        OwnershipInfo {
+            device_id: device_id.to_string(),
+            local: owner.as_deref() == Some(self.config.node_id.as_str()),
+            owner,
+            moving_to,
+        }
+    }
+
+    /// Where a request from `device_id` should be handled
+    pub fn route(&self, device_id: &str) -> Ownership {
+        if !self.config.enabled {
+            return Ownership::Local;
+        }
+        let ownership = self.ownership(device_id);
+        if ownership.local {
+            return Ownership::Local;
+        }
+        let rings = self.rings.read();
+        match ownership
+            .owner
+            .and_then(|owner| rings.current.members.iter().find(|member| member.id == owner).cloned())
+        {
+            Some(member) => Ownership::Remote(member),
+            None => Ownership::Local,
+        }
+    }
+
+    /// POST a JSON `body` unchanged to the owner's `path`, returning its status and body
+    pub async fn proxy(
+        &self,
+        member: &ClusterMember,
+        path: &str,
+        body: Vec<u8>,
+        headers: &[(&str, &str)],
+    ) -> Result<(u16, Vec<u8>)> {
+        let url = format!("{}{}", member.url.trim_end_matches('/'), path);
+        let result = async {
+            let mut request = self
+                .client
+                .post(&url)
+                .timeout(Duration::from_millis(self.config.proxy_timeout_ms))
+                .header(FORWARDED_HEADER, &self.config.node_id)
+                .header(reqwest::header::CONTENT_TYPE, "application/json");
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            let sent = body.len() as u64;
+            let response = request
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| Error::Network(format!("Proxy to {} failed: {}", member.id, e)))?;
+            let status = response.status().as_u16();
+            let body = response.bytes().await;
+            bandwidth::record(Subsystem::Cluster, sent, body.as_ref().map_or(0, |body| body.len() as u64));
+            let body = body.map_err(|e| Error::Network(format!("Proxy to {} failed: {}", member.id, e)))?;
+            Ok((status, body.to_vec()))
+        }
+        .await;
+
+        match &result {
+            Ok(_) => {
+                self.proxied.fetch_add(1, Ordering::Relaxed);
+                debug!("Proxied request to cluster member {}", member.id);
+            },
+            Err(_) => {
+                self.proxy_failures.fetch_add(1, Ordering::Relaxed);
+            },
+        }
+        result
+    }
+
+    pub fn status(&self) -> ClusterStatus {
+        let rings = self.rings.read();
+        let shares = rings.current.shares();
+        let now = Instant::now();
+        ClusterStatus {
+            enabled: self.config.enabled,
+            node_id: self.config.node_id.clone(),
+            members: rings
+                .current
+                .members
+                .iter()
+                .zip(shares)
+                .map(|(member, share)| MemberStatus {
+                    id: member.id.clone(),
+                    url: member.url.clone(),
+                    share,
+                })
+                .collect(),
+            rebalance_remaining_secs: rings
+                .previous
+                .as_ref()
+                .filter(|(_, deadline)| now < *deadline)
+                .map(|(_, deadline)| (*deadline - now).as_secs()),
+            proxied: self.proxied.load(Ordering::Relaxed),
+            proxy_failures: self.proxy_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(id: &str) -> ClusterMember {
+        ClusterMember {
+            id: id.to_string(),
+            url: format!("http://{}.cluster.test:8080", id),
+        }
+    }
+
+    fn membership(members: &[&str], grace_secs: u64) -> ClusterMembership {
+        ClusterMembership::new(ClusterRoutingConfig {
+            enabled: true,
+            node_id: "a".to_string(),
+            members: members.iter().map(|id| member(id)).collect(),
+            rebalance_grace_secs: grace_secs,
+            ..ClusterRoutingConfig::default()
+        })
+    }
+
+    fn devices() -> Vec<String> {
+        (0..1000).map(|i| format!("device-{}", i)).collect()
+    }
+
+    #[test]
+    fn test_adding_member_moves_only_its_share() {
+        let cluster = membership(&["a", "b", "c"], 0);
+        let before: Vec<_> = devices().iter().map(|d| cluster.ownership(d).owner).collect();
+        let counts = |owners: &[Option<String>], id: &str| owners.iter().filter(|o| o.as_deref() == Some(id)).count();
+        for id in ["a", "b", "c"] {
+            assert!(counts(&before, id) > 200, "member {} owns too few devices", id);
+        }
+
+        cluster.set_members(vec![member("a"), member("b"), member("c"), member("d")]).unwrap();
+        let after: Vec<_> = devices().iter().map(|d| cluster.ownership(d).owner).collect();
+        let moved: Vec<_> = before.iter().zip(&after).filter(|(b, a)| b != a).collect();
+        assert!(moved.iter().all(|(_, a)| a.as_deref() == Some("d")));
+        assert!(moved.len() > 150 && moved.len() < 350, "moved {} devices", moved.len());
+
+        let total: f64 = cluster.status().members.iter().map(|m| m.share).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_moved_devices_stay_with_previous_owner_during_grace() {
+        let cluster = membership(&["a", "b"], 300);
+        cluster.set_members(vec![member("a"), member("b"), member("c")]).unwrap();
+
+        let moving = devices()
+            .into_iter()
+            .map(|d| cluster.ownership(&d))
+            .find(|info| info.moving_to.is_some())
+            .expect("some device moves to the new member");
+        assert_eq!(moving.moving_to.as_deref(), Some("c"));
+        assert_ne!(moving.owner.as_deref(), Some("c"));
+        assert!(cluster.status().rebalance_remaining_secs.is_some());
+
+        // Removing a member hands its devices over immediately
+        cluster.set_members(vec![member("a"), member("c")]).unwrap();
+        for device in devices() {
+            assert_ne!(cluster.ownership(&device).owner.as_deref(), Some("b"));
+        }
+        assert!(cluster.set_members(vec![member("b")]).is_err());
+    }
+}
@@ -0,0 +1,726 @@
+//! Signed compliance reports for SOC 2 / GDPR oriented reviews
+//!
+//! A report compiles what a reviewer asks for from an edge deployment: a
+//! summary of security audit counters, where request data was processed
+//! (on the device, in which cloud region, or held in the offline queue),
+//! how long each class of persisted data is kept, and the security posture
+//! implied by the configuration. Findings flag settings a reviewer would
+//! question.
+//!
+//! Reports are signed with an Ed25519 key kept on the storage backend, so a
+//! report handed to an auditor can be checked against the gateway's published
+//! key with `mcp-compliance-report verify`. Sections are flat and titled so
+//! the Markdown rendering converts to PDF as-is.
+
+use crate::gateway::Gateway;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use mcp_common::config::{ComplianceConfig, DataClass};
+use mcp_common::crypto;
+use mcp_common::crypto::rand::SystemRandom;
+use mcp_common::crypto::signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+use mcp_common::events::AlertSeverity;
+use mcp_common::{Config, Error, Result, Vfs};
+use mcp_models::ModelProvenance;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tracing::info;
+
+const SIGNATURE_ALGORITHM: &str = "Ed25519";
+
+/// Requests processed in one cloud destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloudDestination {
+    pub endpoint: String,
+    pub host: String,
+    pub region: Option<String>,
+    pub requests: u64,
+}
+
+/// Where request data was processed since the gateway started
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResidencySummary {
+    pub on_device: u64,
+    pub queued: u64,
+    pub cloud: Vec<CloudDestination>,
+    /// Requests handed to the owning gateway in cluster mode
+    pub proxied_to_cluster_members: u64,
+    /// Share of routed requests that never left the device
+    pub on_device_ratio: f64,
+}
+
+/// Security audit counters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditSummary {
+    /// Counters reported by the security manager
+    pub security_counters: BTreeMap<String, f64>,
+    pub restricted_devices: usize,
+    pub throttled_principals: usize,
+    pub enrolled_devices: usize,
+    pub revoked_devices: usize,
+}
+
+/// Retention of one class of persisted data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionEntry {
+    pub data_class: String,
+    /// Configured retention, `None` when the data is kept indefinitely
+    pub retention_secs: Option<u64>,
+    /// Whether the gateway deletes the data itself once retention expires
+    pub enforced: bool,
+    pub note: String,
+}
+
+/// Transport security settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsPosture {
+    pub mutual_tls: bool,
+    pub certificate_configured: bool,
+    /// Outbound endpoints reached over plain HTTP
+    pub plaintext_endpoints: Vec<String>,
+}
+
+/// Encryption settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionPosture {
+    pub algorithm: String,
+    /// Absent in reports from builds before the backend was recorded
+    #[serde(default)]
+    pub crypto_backend: String,
+    #[serde(default)]
+    pub fips_mode: bool,
+    pub key_rotation_interval_hours: u64,
+    pub offline_queue_encrypted: bool,
+}
+
+/// Security-relevant configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityPosture {
+    pub tls: TlsPosture,
+    pub encryption: EncryptionPosture,
+    pub tpm_enabled: bool,
+    pub device_attestation: bool,
+    pub device_enrollment: bool,
+    pub require_enrollment: bool,
+    pub auth_lockout_after_failures: u32,
+    pub auto_restrict_suspicious_devices: bool,
+}
+
+/// Something a reviewer should look at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    pub severity: AlertSeverity,
+    /// Control the finding relates to, e.g. `SOC2 CC6.7` or `GDPR Art. 32`
+    pub control: String,
+    pub message: String,
+}
+
+/// Unsigned report contents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceReport {
+    pub report_id: String,
+    pub generated_at: DateTime<Utc>,
+    pub period_start: DateTime<Utc>,
+    pub organization: Option<String>,
+    pub gateway_node: String,
+    pub gateway_version: String,
+    pub audit: AuditSummary,
+    pub data_residency: ResidencySummary,
+    pub retention: Vec<RetentionEntry>,
+    pub security_posture: SecurityPosture,
+    /// License and source of the models loaded during the period
+    #[serde(default)]
+    pub models: Vec<ModelProvenance>,
+    pub findings: Vec<Finding>,
+}
+
+/// Detached signature over the serialized report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportSignature {
+    pub algorithm: String,
+    /// Base64 Ed25519 public key
+    pub public_key: String,
+    /// Base64 signature over the JSON serialization of `report`
+    pub value: String,
+}
+
+/// Report with its signature, as exported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedComplianceReport {
+    pub report: ComplianceReport,
+    pub signature: ReportSignature,
+}
+
+impl SignedComplianceReport {
+    /// Check the signature, and that it was made by `public_key` when given
+    pub fn verify(&self, public_key: Option<&str>) -> Result<()> {
+        if self.signature.algorithm != SIGNATURE_ALGORITHM {
+            return Err(Error::Security(format!(
+                "Unsupported signature algorithm {}",
+                self.signature.algorithm
+            )));
+        }
+        if public_key.is_some_and(|expected| expected.trim() != self.signature.public_key) {
+            return Err(Error::Security("Report was signed by a different key".to_string()));
+        }
+        let key = BASE64
+            .decode(&self.signature.public_key)
+            .map_err(|_| Error::Security("Signature public key is not valid base64".to_string()))?;
+        let value = BASE64
+            .decode(&self.signature.value)
+            .map_err(|_| Error::Security("Signature is not valid base64".to_string()))?;
+        UnparsedPublicKey::new(&signature::ED25519, &key)
+            .verify(&serde_json::to_vec(&self.report)?, &value)
+            .map_err(|_| Error::Security("Report signature does not match its contents".to_string()))
+    }
+}
+
+impl ComplianceReport {
+    /// Markdown rendering for review documents
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Compliance Report\n");
+        if let Some(organization) = &self.organization {
+            let _ = writeln!(out, "- Organization: {}", organization);
+        }
+        let _ = writeln!(out, "- Report: {}", self.report_id);
+        let _ = writeln!(out, "- Gateway: {} ({})", self.gateway_node, self.gateway_version);
+        let _ = writeln!(out, "- Period: {} to {}\n", self.period_start.to_rfc3339(), self.generated_at.to_rfc3339());
+
+        let _ = writeln!(out, "## Findings\n");
+        if self.findings.is_empty() {
+            let _ = writeln!(out, "No findings.");
+        }
+        for finding in &self.findings {
+            let _ = writeln!(out, "- **{:?}** ({}): {}", finding.severity, finding.control, finding.message);
+        }
+
+        let _ = writeln!(out, "\n## Audit Summary\n");
+        let audit = &self.audit;
+        let _ = writeln!(out, "- Restricted devices: {}", audit.restricted_devices);
+        let _ = writeln!(out, "- Throttled principals: {}", audit.throttled_principals);
+        let _ = writeln!(out, "- Enrolled devices: {} ({} revoked)", audit.enrolled_devices, audit.revoked_devices);
+        for (counter, value) in &audit.security_counters {
+            let _ = writeln!(out, "- {}: {}", counter, value);
+        }
+
+        let _ = writeln!(out, "\n## Data Residency\n");
+        let residency = &self.data_residency;
+        let _ = writeln!(
+            out,
+            "- Processed on device: {} ({:.1}%)",
+            residency.on_device,
+            residency.on_device_ratio * 100.0
+        );
+        let _ = writeln!(out, "- Held in offline queue: {}", residency.queued);
+        let _ = writeln!(out, "- Proxied to cluster members: {}", residency.proxied_to_cluster_members);
+        for destination in &residency.cloud {
+            let _ = writeln!(
+                out,
+                "- Cloud {} ({}, region {}): {}",
+                destination.endpoint,
+                destination.host,
+                destination.region.as_deref().unwrap_or("unspecified"),
+                destination.requests
+            );
+        }
+
+        let _ = writeln!(out, "\n## Retention\n");
+        let _ = writeln!(out, "| Data class | Retention | Enforced | Note |");
+        let _ = writeln!(out, "|---|---|---|---|");
+        for entry in &self.retention {
+            let retention = entry
+                .retention_secs
+                .map(|secs| format!("{}s", secs))
+                .unwrap_or_else(|| "indefinite".to_string());
+            let enforced = if entry.enforced { "yes" } else { "no" };
+            let _ = writeln!(out, "| {} | {} | {} | {} |", entry.data_class, retention, enforced, entry.note);
+        }
+
+        let _ = writeln!(out, "\n## Security Posture\n");
+        let posture = &self.security_posture;
+        let _ = writeln!(out, "- Mutual TLS: {}", posture.tls.mutual_tls);
+        let _ = writeln!(out, "- TLS certificate configured: {}", posture.tls.certificate_configured);
+        let _ = writeln!(out, "- Plaintext endpoints: {}", posture.tls.plaintext_endpoints.len());
+        let _ = writeln!(
+            out,
+            "- Encryption: {} (key rotation every {}h)",
+            posture.encryption.algorithm, posture.encryption.key_rotation_interval_hours
+        );
+        let _ = writeln!(
+            out,
+            "- Crypto backend: {} (FIPS mode: {})",
+            posture.encryption.crypto_backend, posture.encryption.fips_mode
+        );
+        let _ = writeln!(out, "- Offline queue encrypted: {}", posture.encryption.offline_queue_encrypted);
+        let _ = writeln!(out, "- TPM: {}", posture.tpm_enabled);
+        let _ = writeln!(out, "- Device attestation: {}", posture.device_attestation);
+        let _ = writeln!(
+            out,
+            "- Device enrollment: {} (required: {})",
+            posture.device_enrollment, posture.require_enrollment
+        );
+
+        let _ = writeln!(out, "\n## Model Provenance\n");
+        if self.models.is_empty() {
+            let _ = writeln!(out, "No models loaded.");
+        } else {
+            let _ = writeln!(out, "| Model | License | Source | Checksum verified |");
+            let _ = writeln!(out, "|---|---|---|---|");
+        }
+        for model in &self.models {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                model.model_id,
+                model.license.as_deref().unwrap_or("unknown"),
+                model.source_url.as_deref().unwrap_or("unknown"),
+                if model.checksum_verified { "yes" } else { "no" }
+            );
+        }
+        out
+    }
+}
+
+/// Counts of where routed requests were processed
+#[derive(Default)]
+struct ResidencyLedger {
+    on_device: AtomicU64,
+    queued: AtomicU64,
+    /// Requests per cloud endpoint URL
+    cloud: Mutex<BTreeMap<String, u64>>,
+}
+
+/// Records data residency and produces signed reports
+pub struct ComplianceReporter {
+    config: ComplianceConfig,
+    storage: Arc<dyn Vfs>,
+    signing_key: OnceCell<Ed25519KeyPair>,
+    ledger: ResidencyLedger,
+}
+
+impl ComplianceReporter {
+    pub fn new(config: ComplianceConfig, storage: Arc<dyn Vfs>) -> Self {
+        Self {
+            config,
+            storage,
+            signing_key: OnceCell::new(),
+            ledger: ResidencyLedger::default(),
+        }
+    }
+
+    pub fn record_on_device(&self) {
+        self.ledger.on_device.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_queued(&self) {
+        self.ledger.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cloud(&self, endpoint: &str) {
+        *self.ledger.cloud.lock().entry(endpoint.to_string()).or_insert(0) += 1;
+    }
+
+    /// Signing key, loaded or generated on first use
+    async fn signing_key(&self) -> Result<&Ed25519KeyPair> {
+        self.signing_key
+            .get_or_try_init(|| async {
+                let path = &self.config.signing_key_path;
+                let pkcs8 = if self.storage.exists(path).await {
+                    self.storage.read(path).await?
+                } else {
+                    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                        .map_err(|_| Error::Security("Failed to generate report signing key".to_string()))?;
+                    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                        self.storage.create_dir_all(parent).await?;
+                    }
+                    self.storage.write(path, pkcs8.as_ref()).await?;
+                    info!("Generated compliance report signing key at {:?}", path);
+                    pkcs8.as_ref().to_vec()
+                };
+                Ed25519KeyPair::from_pkcs8(&pkcs8)
+                    .map_err(|e| Error::Security(format!("Invalid report signing key {:?}: {}", path, e)))
+            })
+            .await
+    }
+
+    /// Base64 public key reports are signed with
+    pub async fn public_key(&self) -> Result<String> {
+        Ok(BASE64.encode(self.signing_key().await?.public_key().as_ref()))
+    }
+
+    /// Compile and sign a report for the gateway's current state
+    pub async fn generate(&self, gateway: &Gateway) -> Result<SignedComplianceReport> {
+        let config = gateway.config();
+        let security = gateway.security();
+
+        let security_counters = security
+            .health_check()
+            .await?
+            .metrics
+            .into_iter()
+            .map(|(counter, value)| (counter, value as f64))
+            .collect();
+        let (enrolled_devices, revoked_devices) = match security.enrollment() {
+            Some(enrollment) => (enrollment.devices().await.len(), enrollment.revocations().await.len()),
+            None => (0, 0),
+        };
+        let audit = AuditSummary {
+            security_counters,
+            restricted_devices: match security.restrictions() {
+                Some(restrictions) => restrictions.list().await.len(),
+                None => 0,
+            },
+            throttled_principals: match security.auth_guard() {
+                Some(auth_guard) => auth_guard.throttled().await.len(),
+                None => 0,
+            },
+            enrolled_devices,
+            revoked_devices,
+        };
+
+        let report = ComplianceReport {
+            report_id: uuid::Uuid::new_v4().to_string(),
+            generated_at: gateway.clock().now(),
+            period_start: gateway.state().await.started_at,
+            organization: self.config.organization.clone(),
+            gateway_node: gateway.cluster().node_id().to_string(),
+            gateway_version: env!("CARGO_PKG_VERSION").to_string(),
+            audit,
+            data_residency: self.residency(config, gateway.cluster().status().proxied),
+            retention: retention(config),
+            security_posture: posture(config),
+            models: gateway.model_engine().model_provenance().await,
+            findings: Vec::new(),
+        };
+        let report = ComplianceReport {
+            findings: findings(config, &report),
+            ..report
+        };
+        self.sign(report).await
+    }
+
+    async fn sign(&self, report: ComplianceReport) -> Result<SignedComplianceReport> {
+        let key = self.signing_key().await?;
+        let signature = key.sign(&serde_json::to_vec(&report)?);
+        Ok(SignedComplianceReport {
+            report,
+            signature: ReportSignature {
+                algorithm: SIGNATURE_ALGORITHM.to_string(),
+                public_key: BASE64.encode(key.public_key().as_ref()),
+                value: BASE64.encode(signature.as_ref()),
+            },
+        })
+    }
+
+    /// Where request data was processed since the gateway started
+    pub fn residency(&self, config: &Config, proxied: u64) -> ResidencySummary {
+        let on_device = self.ledger.on_device.load(Ordering::Relaxed);
+        let queued = self.ledger.queued.load(Ordering::Relaxed);
+        let cloud: Vec<CloudDestination> = self
+            .ledger
+            .cloud
+            .lock()
+            .iter()
+            .map(|(url, requests)| {
+                let endpoint = config.router.cloud_endpoints.iter().find(|endpoint| &endpoint.url == url);
+                CloudDestination {
+                    endpoint: endpoint.map(|endpoint| endpoint.name.clone()).unwrap_or_else(|| url.clone()),
+                    host: reqwest::Url::parse(url)
+                        .ok()
+                        .and_then(|url| url.host_str().map(str::to_string))
+                        .unwrap_or_else(|| url.clone()),
+                    region: endpoint.and_then(|endpoint| endpoint.region.clone()),
+                    requests: *requests,
+                }
+            })
+            .collect();
+        let routed = on_device + queued + cloud.iter().map(|destination| destination.requests).sum::<u64>();
+        ResidencySummary {
+            on_device,
+            queued,
+            cloud,
+            proxied_to_cluster_members: proxied,
+            on_device_ratio: if routed == 0 { 1.0 } else { on_device as f64 / routed as f64 },
+        }
+    }
+}
+
+fn retention(config: &Config) -> Vec<RetentionEntry> {
+    let retention = &config.retention;
+    let holds = retention.legal_holds.len();
+    let mut entries: Vec<RetentionEntry> = DataClass::ALL
+        .into_iter()
+        .map(|class| {
+            let policy = retention.policy(class);
+            RetentionEntry {
+                data_class: class.as_str().to_string(),
+                retention_secs: policy.max_age_days.map(|days| days as u64 * 86_400),
+                enforced: retention.enabled && policy.max_age_days.is_some(),
+                note: match (policy.max_age_days, holds) {
+                    (None, _) => "No retention configured".to_string(),
+                    (Some(_), 0) => "Purged on schedule".to_string(),
+                    (Some(_), holds) => format!("Purged on schedule, {} legal holds exempt", holds),
+                },
+            }
+        })
+        .collect();
+    entries.push(RetentionEntry {
+        data_class: "request_dedup_window".to_string(),
+        retention_secs: Some(config.cluster.dedup_window_secs),
+        enforced: true,
+        note: "Request ids only".to_string(),
+    });
+    entries.push(RetentionEntry {
+        data_class: "device_enrollment_registry".to_string(),
+        retention_secs: None,
+        enforced: false,
+        note: "Kept so revoked certificates stay on the denylist".to_string(),
+    });
+    entries
+}
+
+fn posture(config: &Config) -> SecurityPosture {
+    let security = &config.security;
+    let plaintext_endpoints = config
+        .router
+        .cloud_endpoints
+        .iter()
+        .map(|endpoint| (&endpoint.name, &endpoint.url))
+        .chain(config.outputs.webhooks.iter().map(|webhook| (&webhook.name, &webhook.url)))
+        .filter(|(_, url)| url.starts_with("http://"))
+        .map(|(name, _)| name.clone())
+        .collect();
+    SecurityPosture {
+        tls: TlsPosture {
+            mutual_tls: security.mutual_tls,
+            certificate_configured: security.cert_path.is_some() && security.key_path.is_some(),
+            plaintext_endpoints,
+        },
+        encryption: EncryptionPosture {
+            algorithm: security.encryption_algorithm.clone(),
+            crypto_backend: crypto::backend().to_string(),
+            fips_mode: crypto::FIPS_MODE,
+            key_rotation_interval_hours: security.key_rotation_interval_hours,
+            offline_queue_encrypted: config.queue.encryption_enabled,
+        },
+        tpm_enabled: security.tpm_enabled,
+        device_attestation: security.device_attestation,
+        device_enrollment: security.enrollment.enabled,
+        require_enrollment: security.enrollment.enabled && security.enrollment.require_enrollment,
+        auth_lockout_after_failures: security.auth_protection.max_failures,
+        auto_restrict_suspicious_devices: security.restricted_mode.auto_restrict,
+    }
+}
+
+fn findings(config: &Config, report: &ComplianceReport) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut add = |severity, control: &str, message: String| {
+        findings.push(Finding {
+            severity,
+            control: control.to_string(),
+            message,
+        })
+    };
+    let posture = &report.security_posture;
+
+    for endpoint in &posture.tls.plaintext_endpoints {
+        add(
+            AlertSeverity::Critical,
+            "SOC2 CC6.7",
+            format!("Endpoint {} is reached over plain HTTP", endpoint),
+        );
+    }
+    if !posture.tls.mutual_tls && !posture.device_enrollment {
+        add(
+            AlertSeverity::Warning,
+            "SOC2 CC6.1",
+            "Devices are not authenticated by certificate (mutual TLS and enrollment are off)".to_string(),
+        );
+    }
+    if !posture.encryption.offline_queue_encrypted {
+        add(
+            AlertSeverity::Warning,
+            "GDPR Art. 32",
+            "Offline queue entries are stored unencrypted".to_string(),
+        );
+    }
+    if !posture.tpm_enabled {
+        add(
+            AlertSeverity::Info,
+            "SOC2 CC6.1",
+            "Keys are not protected by a TPM".to_string(),
+        );
+    }
+
+    for destination in &report.data_residency.cloud {
+        match &destination.region {
+            None => add(
+                AlertSeverity::Warning,
+                "GDPR Art. 44",
+                format!(
+                    "{} requests went to {} whose processing region is not declared",
+                    destination.requests, destination.endpoint
+                ),
+            ),
+            Some(region)
+                if !config.compliance.allowed_regions.is_empty()
+                    && !config.compliance.allowed_regions.contains(region) =>
+            {
+                add(
+                    AlertSeverity::Critical,
+                    "GDPR Art. 44",
+                    format!(
+                        "{} requests went to {} in region {}, outside the allowed regions",
+                        destination.requests, destination.endpoint, region
+                    ),
+                )
+            },
+            Some(_) => {},
+        }
+    }
+
+    for entry in report.retention.iter().filter(|entry| !entry.enforced) {
+        add(
+            AlertSeverity::Info,
+            "GDPR Art. 5(1)(e)",
+            format!("Retention of {} is not enforced by the gateway", entry.data_class),
+        );
+    }
+
+    for model in &report.models {
+        if model.license.is_none() || model.source_url.is_none() {
+            add(
+                AlertSeverity::Warning,
+                "SOC2 CC9.2",
+                format!("Model {} was loaded without a recorded license and source", model.model_id),
+            );
+        } else if !model.checksum_verified {
+            add(
+                AlertSeverity::Info,
+                "SOC2 CC8.1",
+                format!("Model {} was not checked against a published checksum", model.model_id),
+            );
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::config::CloudEndpoint;
+    use mcp_common::vfs::MemoryVfs;
+
+    #[tokio::test]
+    async fn test_signed_report_detects_tampering() {
+        let reporter = ComplianceReporter::new(ComplianceConfig::default(), Arc::new(MemoryVfs::new()));
+        reporter.record_on_device();
+        let config = Config::default();
+        let report = ComplianceReport {
+            report_id: "r-1".to_string(),
+            generated_at: Utc::now(),
+            period_start: Utc::now(),
+            organization: Some("Acme Plant 4".to_string()),
+            gateway_node: "gateway-1".to_string(),
+            gateway_version: "0.1.0".to_string(),
+            audit: AuditSummary {
+                security_counters: BTreeMap::from([("blocked_requests".to_string(), 2.0)]),
+                restricted_devices: 0,
+                throttled_principals: 1,
+                enrolled_devices: 0,
+                revoked_devices: 0,
+            },
+            data_residency: reporter.residency(&config, 0),
+            retention: retention(&config),
+            security_posture: posture(&config),
+            models: vec![ModelProvenance {
+                model_id: "tinyllama".to_string(),
+                path: "models/tinyllama.gguf".into(),
+                license: Some("Apache-2.0".to_string()),
+                source_url: None,
+                commercial_use: Some(true),
+                sha256: None,
+                checksum_verified: false,
+                recorded_at: Utc::now(),
+            }],
+            findings: Vec::new(),
+        };
+        let signed = reporter.sign(report).await.unwrap();
+
+        // Round-trips through the exported JSON
+        let exported: SignedComplianceReport = serde_json::from_slice(&serde_json::to_vec(&signed).unwrap()).unwrap();
+        let public_key = reporter.public_key().await.unwrap();
+        exported.verify(Some(&public_key)).unwrap();
+        assert!(exported.verify(Some("c29tZW9uZSBlbHNl")).is_err());
+
+        let mut tampered = exported.clone();
+        tampered.report.audit.throttled_principals = 0;
+        assert!(tampered.verify(None).is_err());
+        assert!(exported.report.to_markdown().contains("Acme Plant 4"));
+        assert!(exported.report.to_markdown().contains("| tinyllama | Apache-2.0 | unknown | no |"));
+        assert!(findings(&config, &exported.report).iter().any(|f| f.control == "SOC2 CC9.2"));
+    }
+
+    #[test]
+    fn test_residency_findings_flag_undeclared_and_disallowed_regions() {
+        let reporter = ComplianceReporter::new(ComplianceConfig::default(), Arc::new(MemoryVfs::new()));
+        let mut config = Config::default();
+        config.compliance.allowed_regions = vec!["eu-central-1".to_string()];
+        for (name, region) in [("frankfurt", Some("eu-central-1")), ("virginia", Some("us-east-1")), ("legacy", None)] {
+            config.router.cloud_endpoints.push(CloudEndpoint {
+                name: name.to_string(),
+                url: format!("https://{}.cloud.test/v1", name),
+                api_key: None,
+                timeout_ms: 1000,
+                max_retries: 0,
+                connect_timeout_ms: None,
+                region: region.map(str::to_string),
+                provider: Default::default(),
+                compression: Default::default(),
+            });
+            reporter.record_cloud(&format!("https://{}.cloud.test/v1", name));
+        }
+        reporter.record_on_device();
+        reporter.record_queued();
+
+        let residency = reporter.residency(&config, 0);
+        assert_eq!(residency.cloud.len(), 3);
+        assert_eq!(residency.on_device_ratio, 0.2);
+        assert!(residency.cloud.iter().any(|d| d.host == "frankfurt.cloud.test"));
+
+        let report = ComplianceReport {
+            report_id: "r-2".to_string(),
+            generated_at: Utc::now(),
+            period_start: Utc::now(),
+            organization: None,
+            gateway_node: "gateway-1".to_string(),
+            gateway_version: "0.1.0".to_string(),
+            audit: AuditSummary {
+                security_counters: BTreeMap::new(),
+                restricted_devices: 0,
+                throttled_principals: 0,
+                enrolled_devices: 0,
+                revoked_devices: 0,
+            },
+            data_residency: residency,
+            retention: Vec::new(),
+            security_posture: posture(&config),
+            models: Vec::new(),
+            findings: Vec::new(),
+        };
+        let residency_findings: Vec<Finding> = findings(&config, &report)
+            .into_iter()
+            .filter(|finding| finding.control == "GDPR Art. 44")
+            .collect();
+        assert_eq!(residency_findings.len(), 2);
+        assert!(residency_findings.iter().any(|f| f.severity == AlertSeverity::Critical && f.message.contains("virginia")));
+        assert!(residency_findings.iter().any(|f| f.message.contains("legacy")));
+    }
+}
@@ -0,0 +1,384 @@
+//! Configuration file loading and hot reload
+//!
+//! The gateway starts from `Config::default()` overlaid with an optional
+//! TOML, YAML or JSON file, so a file only needs the settings it changes.
+//! The file is polled for changes; a changed file is parsed and validated as
+//! a whole before anything is applied, so a typo never leaves the gateway
+//! half reconfigured. Settings components can change in place (routing
+//! rules, rate limits, the span export interval) take effect at once, and a
+//! `ConfigChanged` event on the event bus lists the sections that changed and
+//! those that only take effect after a restart.
+
+use crate::gateway::Gateway;
+use config::FileFormat;
+use mcp_common::events::{self, GatewayEvent};
+use mcp_common::{Config, Error, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Environment variable naming the configuration file
+pub const CONFIG_PATH_ENV: &str = "MCP_GATEWAY_CONFIG";
+
+/// Settings applied to running components, as JSON pointers into the config
+const HOT_SETTINGS: &[&str] = &[
+    "/router/policy",
+    "/security/restricted_mode",
+    "/security/rate_limits",
+    "/telemetry/tracing/export_interval_ms",
+];
+
+/// Difference between two configurations by top-level section
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigDiff {
+    pub changed: Vec<String>,
+    /// Changed sections with settings that only take effect after a restart
+    pub restart_required: Vec<String>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+    }
+}
+
+/// Outcome of a reload that replaced the configuration
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadReport {
+    pub version: u64,
+    #[serde(flatten)]
+    pub diff: ConfigDiff,
+}
+
+/// Load and validate the configuration file at `path`, remembering it as the
+/// file to watch
+pub fn load_file(path: &Path) -> Result<Config> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::Configuration(format!("Failed to read {:?}: {}", path, e)))?;
+    let mut config = parse(&contents, file_format(path)?)?;
+    config.gateway.config_reload.path.get_or_insert_with(|| path.to_path_buf());
+    Ok(config)
+}
+
+/// Load the file named by `MCP_GATEWAY_CONFIG`, or the defaults if unset
+pub fn load_from_env() -> Result<Config> {
+    match path_from_env() {
+        Some(path) => load_file(&path),
+        None => Ok(Config::default()),
+    }
+}
+
+/// Configuration file named by `MCP_GATEWAY_CONFIG`, if set
+pub fn path_from_env() -> Option<PathBuf> {
+    std::env::var_os(CONFIG_PATH_ENV)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Format of a configuration file by its extension
+pub fn file_format(path: &Path) -> Result<FileFormat> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => Ok(FileFormat::Toml),
+        Some("yaml" | "yml") => Ok(FileFormat::Yaml),
+        Some("json") => Ok(FileFormat::Json),
+        _ => Err(Error::Configuration(format!(
+            "Configuration file {:?} must end in .toml, .yaml, .yml or .json",
+            path
+        ))),
+    }
+}
+
+/// Parse a configuration file over the defaults and validate the result
+pub fn parse(contents: &str, format: FileFormat) -> Result<Config> {
+    let overrides: Value = config::Config::builder()
+        .add_source(config::File::from_str(contents, format))
+        .build()
+        .and_then(|file| file.try_deserialize())
+        .map_err(|e| Error::Configuration(format!("Invalid configuration file: {}", e)))?;
+    let mut merged = to_value(&Config::default())?;
+    merge(&mut merged, overrides);
+    let config: Config = serde_json::from_value(merged)
+        .map_err(|e| Error::Configuration(format!("Invalid configuration file: {}", e)))?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Sections that differ between `old` and `new`
+pub fn diff(old: &Config, new: &Config) -> Result<ConfigDiff> {
+    let old = to_value(old)?;
+    let new = to_value(new)?;
+    let mut patched = old.clone();
+    for pointer in HOT_SETTINGS {
+        if let (Some(slot), Some(value)) = (patched.pointer_mut(pointer), new.pointer(pointer)) {
+            *slot = value.clone();
+        }
+    }
+
+    let mut diff = ConfigDiff::default();
+    let Some(sections) = new.as_object() else {
+        return Ok(diff);
+    };
+    for (section, value) in sections {
+        if old.get(section) == Some(value) {
+            continue;
+        }
+        diff.changed.push(section.clone());
+        if patched.get(section) != Some(value) {
+            diff.restart_required.push(section.clone());
+        }
+    }
+    Ok(diff)
+}
+
+fn to_value(config: &Config) -> Result<Value> {
+    serde_json::to_value(config).map_err(|e| Error::Serialization(e.to_string()))
+}
+
+/// Overlay `overrides` on `base`, merging tables and replacing everything else
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(slot) => merge(slot, value),
+                    None => {
+                        base.insert(key, value);
+                    },
+                }
+            }
+        },
+        (slot, value) => *slot = value,
+    }
+}
+
+fn fingerprint(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The running configuration and the watch on its file
+pub struct ConfigManager {
+    current: RwLock<Arc<Config>>,
+    version: AtomicU64,
+    fingerprint: Mutex<Option<u64>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ConfigManager {
+    pub fn new(config: Config) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(config)),
+            version: AtomicU64::new(1),
+            fingerprint: Mutex::new(None),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Configuration in effect now
+    pub fn current(&self) -> Arc<Config> {
+        Arc::clone(&self.current.read().unwrap_or_else(|p| p.into_inner()))
+    }
+
+    /// Incremented every time a reload replaces the configuration
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    /// Watched file, if any
+    pub fn path(&self) -> Option<PathBuf> {
+        self.current().gateway.config_reload.path.clone()
+    }
+
+    /// Check the file on the configured interval and apply changes to the
+    /// gateway's components
+    pub fn start(self: &Arc<Self>, gateway: Weak<Gateway>) {
+        let settings = &self.current().gateway.config_reload;
+        if settings.path.is_none() || settings.interval_secs == 0 {
+            return;
+        }
+        let manager = Arc::downgrade(self);
+        let interval_secs = settings.interval_secs;
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let (Some(manager), Some(gateway)) = (manager.upgrade(), gateway.upgrade()) else {
+                    break;
+                };
+                if let Err(e) = manager.reload(|config| gateway.apply_config(config)).await {
+                    warn!("Configuration reload rejected: {}", e);
+                }
+            }
+        });
+        if let Some(previous) = self.lock_task().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.lock_task().take() {
+            handle.abort();
+        }
+    }
+
+    /// Re-read the file and, if it changed and is valid, apply it through
+    /// `apply` and make it current; returns None when nothing changed
+    pub async fn reload<F>(&self, apply: F) -> Result<Option<ReloadReport>>
+    where
+        F: FnOnce(&Config),
+    {
+        let Some(path) = self.path() else {
+            return Err(Error::Configuration("No configuration file to reload".to_string()));
+        };
+        let contents = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| Error::Configuration(format!("Failed to read {:?}: {}", path, e)))?;
+        let fingerprint = fingerprint(&contents);
+        if *self.lock_fingerprint() == Some(fingerprint) {
+            return Ok(None);
+        }
+
+        let mut config = parse(&contents, file_format(&path)?)?;
+        config.gateway.config_reload.path.get_or_insert(path);
+        let result = self.replace(config, apply);
+        // A rejected file is not retried until it changes again
+        *self.lock_fingerprint() = Some(fingerprint);
+        result
+    }
+
+    /// Make an already validated configuration current
+    pub fn replace<F>(&self, config: Config, apply: F) -> Result<Option<ReloadReport>>
+    where
+        F: FnOnce(&Config),
+    {
+        let diff = diff(&self.current(), &config)?;
+        if diff.is_empty() {
+            return Ok(None);
+        }
+
+        apply(&config);
+        *self.current.write().unwrap_or_else(|p| p.into_inner()) = Arc::new(config);
+        let version = self.version.fetch_add(1, Ordering::Relaxed) + 1;
+        info!(
+            "Configuration version {} applied: {} changed, restart required for [{}]",
+            version,
+            diff.changed.join(", "),
+            diff.restart_required.join(", ")
+        );
+        events::publish(GatewayEvent::ConfigChanged {
+            version,
+            changed: diff.changed.clone(),
+            restart_required: diff.restart_required.clone(),
+        });
+        Ok(Some(ReloadReport {
+            version,
+            diff,
+        }))
+    }
+
+    fn lock_fingerprint(&self) -> std::sync::MutexGuard<'_, Option<u64>> {
+        self.fingerprint
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_task(&self) -> std::sync::MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.task
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_overlays_file_on_defaults() {
+        let toml = r#"
+            [gateway]
+            port = 9090
+
+            [security.restricted_mode]
+            requests_per_minute = 3
+        "#;
+        let config = parse(toml, FileFormat::Toml).unwrap();
+        assert_eq!(config.gateway.port, 9090);
+        assert_eq!(config.security.restricted_mode.requests_per_minute, 3);
+        assert_eq!(config.gateway.bind_address, Config::default().gateway.bind_address);
+
+        let yaml = "telemetry:\n  tracing:\n    export_interval_ms: 2500\n";
+        let config = parse(yaml, FileFormat::Yaml).unwrap();
+        assert_eq!(config.telemetry.tracing.export_interval_ms, 2500);
+
+        // Invalid values are rejected as a whole
+        assert!(parse("[gateway.synthetic_probes]\nenabled = true\ninterval_secs = 0\n", FileFormat::Toml).is_err());
+        assert!(parse("[gateway\nport = 1", FileFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn test_example_config_is_current_and_loads_as_the_defaults() {
+        let example = mcp_common::config_docs::example_toml().unwrap();
+        assert!(
+            include_str!("../../../config/gateway.example.toml") == example,
+            "config/gateway.example.toml is stale; regenerate it with `mcp-gateway --print-example-config`"
+        );
+        let config = parse(&example, FileFormat::Toml).unwrap();
+        assert_eq!(to_value(&config).unwrap(), to_value(&Config::default()).unwrap());
+    }
+
+    #[test]
+    fn test_diff_separates_hot_settings_from_restart_settings() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.security.restricted_mode.requests_per_minute = 2;
+        new.security.rate_limits.client_requests_per_window = 20;
+        new.telemetry.tracing.export_interval_ms += 1000;
+        let diff = diff(&old, &new).unwrap();
+        assert_eq!(diff.changed, vec!["security".to_string(), "telemetry".to_string()]);
+        assert!(diff.restart_required.is_empty());
+
+        new.gateway.port += 1;
+        let diff = super::diff(&old, &new).unwrap();
+        assert_eq!(diff.changed.len(), 3);
+        assert_eq!(diff.restart_required, vec!["gateway".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_changed_file_once() {
+        let path = std::env::temp_dir().join(format!("mcp-config-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "[security.restricted_mode]\nrequests_per_minute = 4\n").unwrap();
+        let manager = ConfigManager::new(load_file(&path).unwrap());
+        assert_eq!(manager.current().security.restricted_mode.requests_per_minute, 4);
+
+        // The file as loaded is not a change
+        assert!(manager.reload(|_| panic!("nothing changed")).await.unwrap().is_none());
+
+        std::fs::write(&path, "[security.restricted_mode]\nrequests_per_minute = 6\n").unwrap();
+        let mut applied = None;
+        let report = manager
+            .reload(|config| applied = Some(config.security.restricted_mode.requests_per_minute))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(applied, Some(6));
+        assert_eq!(report.version, 2);
+        assert_eq!(report.diff.changed, vec!["security".to_string()]);
+        assert_eq!(manager.current().security.restricted_mode.requests_per_minute, 6);
+
+        // An invalid file leaves the running configuration alone
+        std::fs::write(&path, "[gateway.synthetic_probes]\nenabled = true\ninterval_secs = 0\n").unwrap();
+        assert!(manager.reload(|_| panic!("invalid file applied")).await.is_err());
+        assert_eq!(manager.version(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
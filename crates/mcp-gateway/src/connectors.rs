@@ -0,0 +1,483 @@
+//! Message broker output connectors
+//!
+//! Sites that already run a local broker can have the gateway publish
+//! telemetry batches and completed responses to it instead of (or as well
+//! as) webhooks. Kafka is reached through a REST Proxy and NATS through its
+//! client protocol, optionally waiting for JetStream acknowledgements. Each
+//! broker sits behind a cargo feature (`kafka`, `nats`); configuring one the
+//! binary was built without fails at startup.
+
+use crate::webhooks::{RequestSummary, WebhookPayload};
+use async_trait::async_trait;
+use chrono::Utc;
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::config::{BrokerConfig, ConnectorConfig, PayloadFormat};
+use mcp_common::{Error, MCPResponse, Result};
+use mcp_telemetry::TelemetryCollector;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+const DEFAULT_TELEMETRY_INTERVAL_SECS: u64 = 60;
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+/// Sends encoded payloads to one broker
+#[async_trait]
+trait Publisher: Send + Sync {
+    async fn publish(&self, subject: &str, payload: &[u8]) -> Result<()>;
+}
+
+/// Publish counters for one connector
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectorStats {
+    pub name: String,
+    pub published: u64,
+    pub failed: u64,
+}
+
+struct Connector {
+    config: ConnectorConfig,
+    publisher: Box<dyn Publisher>,
+    timeout: Duration,
+    published: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl Connector {
+    async fn send<T: Serialize>(&self, subsystem: Subsystem, subject: &str, value: &T) {
+        let result = match encode(self.config.format, value) {
+            Ok(payload) => {
+                let result = tokio::time::timeout(self.timeout, self.publisher.publish(subject, &payload))
+                    .await
+                    .unwrap_or_else(|_| Err(Error::Timeout(format!("Publishing to {} timed out", subject))));
+                bandwidth::record(subsystem, payload.len() as u64, 0);
+                result
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                self.published.fetch_add(1, Ordering::Relaxed);
+            },
+            Err(e) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                warn!("Connector {} failed to publish to {}: {}", self.config.name, subject, e);
+            },
+        }
+    }
+}
+
+/// All configured broker connectors
+pub struct OutputConnectors {
+    connectors: Vec<Arc<Connector>>,
+}
+
+impl OutputConnectors {
+    pub fn new(configs: &[ConnectorConfig]) -> Result<Self> {
+        let connectors = configs
+            .iter()
+            .map(|config| {
+                Ok(Arc::new(Connector {
+                    publisher: create_publisher(&config.broker)?,
+                    timeout: Duration::from_millis(config.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS)),
+                    config: config.clone(),
+                    published: AtomicU64::new(0),
+                    failed: AtomicU64::new(0),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { connectors })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connectors.is_empty()
+    }
+
+    /// Publish a completed response, using the same body as webhooks
+    pub fn publish_response(&self, request: &RequestSummary, response: &MCPResponse) {
+        for connector in &self.connectors {
+            let Some(subject) = connector.config.responses_subject.clone() else {
+                continue;
+            };
+            let payload = WebhookPayload {
+                request_id: response.id,
+                device_id: request.device_id.clone(),
+                method: request.method.clone(),
+                tags: request.tags.clone(),
+                response: response.clone(),
+                completed_at: Utc::now(),
+            };
+            let connector = connector.clone();
+            tokio::spawn(async move { connector.send(Subsystem::Connectors, &subject, &payload).await });
+        }
+    }
+
+    /// Publish aggregated telemetry on each connector's interval
+    pub fn start_telemetry(&self, telemetry: Arc<dyn TelemetryCollector + Send + Sync>) {
+        for connector in &self.connectors {
+            let Some(subject) = connector.config.telemetry_subject.clone() else {
+                continue;
+            };
+            let interval_secs = connector
+                .config
+                .telemetry_interval_secs
+                .unwrap_or(DEFAULT_TELEMETRY_INTERVAL_SECS);
+            let connector = Arc::downgrade(connector);
+            let telemetry = telemetry.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let Some(connector) = connector.upgrade() else {
+                        break;
+                    };
+                    match telemetry.get_aggregated_metrics().await {
+                        Ok(metrics) => connector.send(Subsystem::Telemetry, &subject, &metrics).await,
+                        Err(e) => debug!("No telemetry batch for connector {}: {}", connector.config.name, e),
+                    }
+                }
+            });
+        }
+    }
+
+    /// Publish counters for every connector
+    pub fn stats(&self) -> Vec<ConnectorStats> {
+        self.connectors
+            .iter()
+            .map(|connector| ConnectorStats {
+                name: connector.config.name.clone(),
+                published: connector.published.load(Ordering::Relaxed),
+                failed: connector.failed.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+fn create_publisher(broker: &BrokerConfig) -> Result<Box<dyn Publisher>> {
+    match broker {
+        #[cfg(feature = "kafka")]
+        BrokerConfig::Kafka { rest_proxy_url } => Ok(Box::new(kafka::KafkaRestPublisher::new(rest_proxy_url))),
+        #[cfg(feature = "nats")]
+        BrokerConfig::Nats { url, jetstream } => Ok(Box::new(nats::NatsPublisher::new(url, *jetstream)?)),
+        #[allow(unreachable_patterns)]
+        other => Err(Error::Configuration(format!(
+            "Connector broker {:?} requires a gateway built with the matching feature",
+            other
+        ))),
+    }
+}
+
+/// Serialize a value in the configured format
+pub fn encode<T: Serialize>(format: PayloadFormat, value: &T) -> Result<Vec<u8>> {
+    match format {
+        PayloadFormat::Json => Ok(serde_json::to_vec(value)?),
+        PayloadFormat::MessagePack => {
+            let mut out = Vec::new();
+            write_msgpack(&serde_json::to_value(value)?, &mut out);
+            Ok(out)
+        },
+    }
+}
+
+fn write_msgpack(value: &serde_json::Value, out: &mut Vec<u8>) {
+    use serde_json::Value;
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(b) => out.push(if *b { 0xc3 } else { 0xc2 }),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                match u {
+                    0..=0x7f => out.push(u as u8),
+                    0x80..=0xff => out.extend_from_slice(&[0xcc, u as u8]),
+                    0x100..=0xffff => {
+                        out.push(0xcd);
+                        out.extend_from_slice(&(u as u16).to_be_bytes());
+                    },
+                    0x1_0000..=0xffff_ffff => {
+                        out.push(0xce);
+                        out.extend_from_slice(&(u as u32).to_be_bytes());
+                    },
+                    _ => {
+                        out.push(0xcf);
+                        out.extend_from_slice(&u.to_be_bytes());
+                    },
+                }
+            } else if let Some(i) = n.as_i64() {
+                if i >= -32 {
+                    out.push(i as i8 as u8);
+                } else {
+                    out.push(0xd3);
+                    out.extend_from_slice(&i.to_be_bytes());
+                }
+            } else {
+                out.push(0xcb);
+                out.extend_from_slice(&n.as_f64().unwrap_or_default().to_be_bytes());
+            }
+        },
+        Value::String(s) => {
+            write_msgpack_len(s.len(), 0xa0, 31, [0xd9, 0xda, 0xdb], out);
+            out.extend_from_slice(s.as_bytes());
+        },
+        Value::Array(items) => {
+            write_msgpack_len(items.len(), 0x90, 15, [0, 0xdc, 0xdd], out);
+            for item in items {
+                write_msgpack(item, out);
+            }
+        },
+        Value::Object(map) => {
+            write_msgpack_len(map.len(), 0x80, 15, [0, 0xde, 0xdf], out);
+            for (key, item) in map {
+                write_msgpack_len(key.len(), 0xa0, 31, [0xd9, 0xda, 0xdb], out);
+                out.extend_from_slice(key.as_bytes());
+                write_msgpack(item, out);
+            }
+        },
+    }
+}
+
+/// Length header: fix form up to `fix_max`, then 8 (if the type has one), 16 and 32 bit forms
+fn write_msgpack_len(len: usize, fix: u8, fix_max: usize, markers: [u8; 3], out: &mut Vec<u8>) {
+    if len <= fix_max {
+        out.push(fix | len as u8);
+    } else if markers[0] != 0 && len <= 0xff {
+        out.extend_from_slice(&[markers[0], len as u8]);
+    } else if len <= 0xffff {
+        out.push(markers[1]);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(markers[2]);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use super::Publisher;
+    use async_trait::async_trait;
+    use base64::Engine as _;
+    use mcp_common::{Error, Result};
+
+    /// Produces through a Kafka REST Proxy using the binary embedded format,
+    /// so consumers see the same bytes as from a native producer
+    pub(super) struct KafkaRestPublisher {
+        base_url: String,
+        client: reqwest::Client,
+    }
+
+    impl KafkaRestPublisher {
+        pub(super) fn new(base_url: &str) -> Self {
+            Self {
+                base_url: base_url.trim_end_matches('/').to_string(),
+                client: reqwest::Client::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Publisher for KafkaRestPublisher {
+        async fn publish(&self, topic: &str, payload: &[u8]) -> Result<()> {
+            let body = serde_json::json!({
+                "records": [{ "value": base64::engine::general_purpose::STANDARD.encode(payload) }]
+            });
+            let response = self
+                .client
+                .post(format!("{}/topics/{}", self.base_url, topic))
+                .header(reqwest::header::CONTENT_TYPE, "application/vnd.kafka.binary.v2+json")
+                .body(serde_json::to_vec(&body)?)
+                .send()
+                .await
+                .map_err(|e| Error::Network(format!("Kafka REST Proxy request failed: {}", e)))?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(Error::Network(format!("Kafka REST Proxy returned {}", response.status())))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+mod nats {
+    use super::Publisher;
+    use async_trait::async_trait;
+    use mcp_common::{Error, Result};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+    use tokio::sync::Mutex;
+
+    const INBOX: &str = "_INBOX.mcp-gateway";
+
+    fn network(e: impl std::fmt::Display) -> Error {
+        Error::Network(format!("NATS connection failed: {}", e))
+    }
+
+    /// Publishes over the NATS client protocol on one shared connection
+    pub(super) struct NatsPublisher {
+        address: String,
+        jetstream: bool,
+        connection: Mutex<Option<BufReader<TcpStream>>>,
+    }
+
+    impl NatsPublisher {
+        pub(super) fn new(url: &str, jetstream: bool) -> Result<Self> {
+            let host = url.strip_prefix("nats://").unwrap_or(url).trim_end_matches('/');
+            if host.is_empty() {
+                return Err(Error::Configuration(format!("Invalid NATS URL: {}", url)));
+            }
+            let address = if host.contains(':') {
+                host.to_string()
+            } else {
+                format!("{}:4222", host)
+            };
+            Ok(Self {
+                address,
+                jetstream,
+                connection: Mutex::new(None),
+            })
+        }
+
+        async fn connect(&self) -> Result<BufReader<TcpStream>> {
+            let stream = TcpStream::connect(&self.address).await.map_err(network)?;
+            let mut connection = BufReader::new(stream);
+            let mut info = String::new();
+            connection.read_line(&mut info).await.map_err(network)?;
+            if !info.starts_with("INFO") {
+                return Err(Error::Network(format!("Unexpected NATS greeting: {}", info.trim())));
+            }
+            let mut handshake = String::from(
+                "CONNECT {\"verbose\":false,\"pedantic\":false,\"name\":\"mcp-gateway\"}\r\n",
+            );
+            if self.jetstream {
+                handshake.push_str(&format!("SUB {} 1\r\n", INBOX));
+            }
+            connection.get_mut().write_all(handshake.as_bytes()).await.map_err(network)?;
+            Ok(connection)
+        }
+
+        /// Wait for the JetStream ack, answering server pings meanwhile
+        async fn read_ack(connection: &mut BufReader<TcpStream>) -> Result<()> {
+            loop {
+                let mut line = String::new();
+                if connection.read_line(&mut line).await.map_err(network)? == 0 {
+                    return Err(Error::Network("NATS connection closed".to_string()));
+                }
+                if line.starts_with("PING") {
+                    connection.get_mut().write_all(b"PONG\r\n").await.map_err(network)?;
+                } else if line.starts_with("-ERR") {
+                    return Err(Error::Network(format!("NATS error: {}", line.trim())));
+                } else if line.starts_with("MSG") {
+                    let size: usize = line
+                        .split_whitespace()
+                        .last()
+                        .and_then(|size| size.parse().ok())
+                        .ok_or_else(|| Error::Network(format!("Malformed NATS message: {}", line.trim())))?;
+                    let mut body = vec![0; size + 2];
+                    connection.read_exact(&mut body).await.map_err(network)?;
+                    let ack: serde_json::Value = serde_json::from_slice(&body[..size])?;
+                    return match ack.get("error") {
+                        Some(error) => Err(Error::Queue(format!("JetStream rejected publish: {}", error))),
+                        None => Ok(()),
+                    };
+                }
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Publisher for NatsPublisher {
+        async fn publish(&self, subject: &str, payload: &[u8]) -> Result<()> {
+            let mut guard = self.connection.lock().await;
+            if guard.is_none() {
+                *guard = Some(self.connect().await?);
+            }
+            let Some(connection) = guard.as_mut() else {
+                return Err(Error::Network("NATS connection unavailable".to_string()));
+            };
+
+            let header = if self.jetstream {
+                format!("PUB {} {} {}\r\n", subject, INBOX, payload.len())
+            } else {
+                format!("PUB {} {}\r\n", subject, payload.len())
+            };
+            let mut frame = header.into_bytes();
+            frame.extend_from_slice(payload);
+            frame.extend_from_slice(b"\r\n");
+
+            let result = match connection.get_mut().write_all(&frame).await {
+                Ok(()) if self.jetstream => Self::read_ack(connection).await,
+                Ok(()) => Ok(()),
+                Err(e) => Err(network(e)),
+            };
+            if matches!(result, Err(Error::Network(_))) {
+                // Reconnect on the next publish
+                *guard = None;
+            }
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_pack_encoding() {
+        let value = serde_json::json!({"ok": true, "n": 300, "neg": -5, "s": "hi", "list": [null, 1.5]});
+        let encoded = encode(PayloadFormat::MessagePack, &value).unwrap();
+        assert_eq!(encoded[0], 0x85);
+        // serde_json orders keys alphabetically: list, n, neg, ok, s
+        assert_eq!(&encoded[1..6], &[0xa4, b'l', b'i', b's', b't']);
+        assert_eq!(&encoded[6..8], &[0x92, 0xc0]);
+        assert_eq!(encoded[8], 0xcb);
+        assert_eq!(&encoded[17..22], &[0xa1, b'n', 0xcd, 0x01, 0x2c]);
+        assert_eq!(&encoded[22..27], &[0xa3, b'n', b'e', b'g', 0xfb]);
+        assert_eq!(&encoded[27..31], &[0xa2, b'o', b'k', 0xc3]);
+        assert_eq!(&encoded[31..], &[0xa1, b's', 0xa2, b'h', b'i']);
+    }
+
+    #[cfg(feature = "nats")]
+    #[tokio::test]
+    async fn test_nats_core_publish() {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            stream.get_mut().write_all(b"INFO {}\r\n").await.unwrap();
+            let mut connect = String::new();
+            stream.read_line(&mut connect).await.unwrap();
+            let mut publish = String::new();
+            stream.read_line(&mut publish).await.unwrap();
+            let mut body = [0u8; 9];
+            stream.read_exact(&mut body).await.unwrap();
+            (connect, publish, body)
+        });
+
+        let connectors = OutputConnectors::new(&[ConnectorConfig {
+            name: "site-broker".to_string(),
+            broker: BrokerConfig::Nats {
+                url: format!("nats://{}", address),
+                jetstream: false,
+            },
+            format: PayloadFormat::Json,
+            responses_subject: None,
+            telemetry_subject: None,
+            telemetry_interval_secs: None,
+            timeout_ms: None,
+        }])
+        .unwrap();
+        connectors.connectors[0].send(Subsystem::Connectors, "edge.responses", &serde_json::json!({"a": 1})).await;
+
+        let (connect, publish, body) = server.await.unwrap();
+        assert!(connect.starts_with("CONNECT "));
+        assert_eq!(publish, "PUB edge.responses 7\r\n");
+        assert_eq!(&body, b"{\"a\":1}\r\n");
+        assert_eq!(connectors.stats()[0].published, 1);
+    }
+}
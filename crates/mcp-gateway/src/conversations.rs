@@ -0,0 +1,507 @@
+//! Session conversations and their portable export packages
+//!
+//! Requests carrying a `session_id` param are recorded with their responses
+//! as the messages of that session's conversation, one file per session.
+//! A conversation can be exported as a self-contained JSON package and
+//! imported on another gateway, keeping its message ids and timestamps, so
+//! a replacement device picks up where the old one left off and users can
+//! take their data with them.
+
+use crate::erasure::SESSION_PARAM;
+use chrono::{DateTime, Utc};
+use mcp_common::clock::{self, Clock};
+use mcp_common::config::{Config, ConversationsConfig};
+use mcp_common::crypto::digest;
+use mcp_common::encoding::hex;
+use mcp_common::{Error, MCPRequest, MCPResponse, RequestId, Result, Vfs};
+use mcp_router::model_aliases::TENANT_PARAM;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Method exporting one of the calling device's sessions
+pub const EXPORT_METHOD: &str = "conversation/export";
+
+/// Method importing a package as a session of the calling device
+pub const IMPORT_METHOD: &str = "conversation/import";
+
+/// Format name of an export package
+pub const PACKAGE_FORMAT: &str = "mcp-conversation";
+
+/// Version of the package layout this gateway writes and reads
+pub const PACKAGE_VERSION: u32 = 1;
+
+/// Longest session id, which is also its file name
+const MAX_SESSION_ID_LEN: usize = 128;
+
+/// Who a message came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+    /// A tool invocation made on the user's behalf
+    Tool,
+}
+
+/// One message of a conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub id: uuid::Uuid,
+    pub role: Role,
+    pub method: String,
+    pub content: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<serde_json::Value>,
+    /// Request the message belongs to
+    pub request_id: RequestId,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A session's messages with what is known about it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Conversation {
+    pub session_id: String,
+    pub device_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, serde_json::Value>,
+    pub messages: Vec<Message>,
+}
+
+/// A conversation as listed for operators, without its messages
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationSummary {
+    pub session_id: String,
+    pub device_id: String,
+    pub messages: usize,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A conversation packaged for another gateway
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationPackage {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    /// Gateway the package was exported from
+    pub source: String,
+    pub conversation: Conversation,
+    /// Hex SHA-256 of the serialized conversation
+    pub sha256: String,
+}
+
+/// How an imported package is stored
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ImportOptions {
+    /// Overwrite a session with the same id instead of failing
+    pub replace: bool,
+    /// Device the session now belongs to; kept from the package when unset
+    pub device_id: Option<String>,
+}
+
+/// A request waiting for its response to be recorded
+pub struct PendingTurn {
+    session_id: String,
+    device_id: String,
+    tenant: Option<String>,
+    message: Message,
+}
+
+/// Conversations by session, saved to gateway storage
+pub struct ConversationStore {
+    config: ConversationsConfig,
+    directory: PathBuf,
+    source: String,
+    storage: Arc<dyn Vfs>,
+    clock: Arc<dyn Clock>,
+    /// Serializes the read-modify-write of session files
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl ConversationStore {
+    pub fn new(config: &Config, storage: Arc<dyn Vfs>) -> Self {
+        Self::with_clock(config, storage, clock::system_clock())
+    }
+
+    pub fn with_clock(config: &Config, storage: Arc<dyn Vfs>, clock: Arc<dyn Clock>) -> Self {
+        let directory = config
+            .conversations
+            .directory
+            .clone()
+            .or_else(|| config.retention.sessions.path.clone())
+            .unwrap_or_else(|| PathBuf::from("./data/sessions"));
+        Self {
+            config: config.conversations.clone(),
+            directory,
+            source: config.cluster.routing.node_id.clone(),
+            storage,
+            clock,
+            lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// The message a request adds to its session, if it belongs to one
+    pub fn turn(&self, request: &MCPRequest) -> Option<PendingTurn> {
+        if !self.enabled() || request.method == EXPORT_METHOD || request.method == IMPORT_METHOD {
+            return None;
+        }
+        let session_id = request.params.get(SESSION_PARAM)?.as_str()?;
+        if validate_session_id(session_id).is_err() {
+            return None;
+        }
+        let content = request
+            .params
+            .iter()
+            .filter(|(name, _)| name.as_str() != SESSION_PARAM && name.as_str() != TENANT_PARAM)
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<serde_json::Map<_, _>>();
+        Some(PendingTurn {
+            session_id: session_id.to_string(),
+            device_id: request.device_id.clone(),
+            tenant: request
+                .params
+                .get(TENANT_PARAM)
+                .and_then(|value| value.as_str())
+                .map(str::to_string),
+            message: Message {
+                id: request.id,
+                role: if request.method.starts_with("tools/") {
+                    Role::Tool
+                } else {
+                    Role::User
+                },
+                method: request.method.clone(),
+                content: serde_json::Value::Object(content),
+                tool_calls: Vec::new(),
+                request_id: request.id,
+                timestamp: request.timestamp,
+            },
+        })
+    }
+
+    /// Record a request and its response in their session; failures are
+    /// logged and do not affect the response
+    pub async fn record(&self, turn: PendingTurn, response: &MCPResponse) {
+        let session_id = turn.session_id.clone();
+        if let Err(e) = self.append(turn, response).await {
+            warn!("Failed to record conversation {}: {}", session_id, e);
+        }
+    }
+
+    async fn append(&self, turn: PendingTurn, response: &MCPResponse) -> Result<()> {
+        let content = match (&response.result, &response.error) {
+            (_, Some(error)) => serde_json::json!({ "error": error }),
+            (Some(result), None) => result.clone(),
+            (None, None) => serde_json::Value::Null,
+        };
+        let tool_calls = content
+            .get("tool_calls")
+            .and_then(|calls| calls.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let reply = Message {
+            id: uuid::Uuid::new_v4(),
+            role: Role::Assistant,
+            method: turn.message.method.clone(),
+            content,
+            tool_calls,
+            request_id: response.id,
+            timestamp: response.timestamp,
+        };
+
+        let _guard = self.lock.lock().await;
+        let now = self.clock.now();
+        let mut conversation = self
+            .load(&turn.session_id)
+            .await?
+            .unwrap_or_else(|| Conversation {
+                session_id: turn.session_id.clone(),
+                device_id: turn.device_id.clone(),
+                tenant: turn.tenant.clone(),
+                created_at: turn.message.timestamp,
+                updated_at: now,
+                metadata: BTreeMap::new(),
+                messages: Vec::new(),
+            });
+        conversation.messages.extend([turn.message, reply]);
+        let excess = conversation
+            .messages
+            .len()
+            .saturating_sub(self.config.max_messages_per_session);
+        conversation.messages.drain(..excess);
+        conversation.updated_at = now;
+        self.save(&conversation).await
+    }
+
+    pub async fn get(&self, session_id: &str) -> Result<Option<Conversation>> {
+        validate_session_id(session_id)?;
+        self.load(session_id).await
+    }
+
+    /// Recorded conversations, most recently updated first
+    pub async fn list(&self) -> Result<Vec<ConversationSummary>> {
+        let files = match self.storage.list_dir(&self.directory).await {
+            Ok(files) => files,
+            Err(_) if !self.storage.exists(&self.directory).await => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut conversations = Vec::new();
+        for path in files
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        {
+            // The directory is shared with other session data
+            let Ok(conversation) = self.read(&path).await else {
+                continue;
+            };
+            conversations.push(ConversationSummary {
+                session_id: conversation.session_id,
+                device_id: conversation.device_id,
+                messages: conversation.messages.len(),
+                updated_at: conversation.updated_at,
+            });
+        }
+        conversations.sort_by_key(|conversation| std::cmp::Reverse(conversation.updated_at));
+        Ok(conversations)
+    }
+
+    /// Package a session for another gateway
+    pub async fn export(&self, session_id: &str) -> Result<Option<ConversationPackage>> {
+        let Some(conversation) = self.get(session_id).await? else {
+            return Ok(None);
+        };
+        Ok(Some(ConversationPackage {
+            format: PACKAGE_FORMAT.to_string(),
+            version: PACKAGE_VERSION,
+            exported_at: self.clock.now(),
+            source: self.source.clone(),
+            sha256: checksum(&conversation)?,
+            conversation,
+        }))
+    }
+
+    /// Store a package exported by this or another gateway, keeping its
+    /// message ids and timestamps
+    pub async fn import(
+        &self,
+        package: ConversationPackage,
+        options: ImportOptions,
+    ) -> Result<Conversation> {
+        if package.format != PACKAGE_FORMAT {
+            return Err(Error::InvalidRequest(format!(
+                "Not a conversation package: {}",
+                package.format
+            )));
+        }
+        if package.version > PACKAGE_VERSION {
+            return Err(Error::InvalidRequest(format!(
+                "Conversation package version {} is newer than the supported {}",
+                package.version, PACKAGE_VERSION
+            )));
+        }
+        if checksum(&package.conversation)? != package.sha256 {
+            return Err(Error::InvalidRequest(
+                "Conversation package checksum does not match".to_string(),
+            ));
+        }
+
+        let mut conversation = package.conversation;
+        validate_session_id(&conversation.session_id)?;
+        if let Some(device_id) = options.device_id {
+            conversation.device_id = device_id;
+        }
+        let _guard = self.lock.lock().await;
+        if !options.replace && self.load(&conversation.session_id).await?.is_some() {
+            return Err(Error::InvalidRequest(format!(
+                "Session {} already has a conversation",
+                conversation.session_id
+            )));
+        }
+        self.save(&conversation).await?;
+        info!(
+            "Imported conversation {} from {} with {} messages",
+            conversation.session_id,
+            package.source,
+            conversation.messages.len()
+        );
+        Ok(conversation)
+    }
+
+    pub async fn delete(&self, session_id: &str) -> Result<bool> {
+        validate_session_id(session_id)?;
+        let _guard = self.lock.lock().await;
+        let path = self.path(session_id);
+        if !self.storage.exists(&path).await {
+            return Ok(false);
+        }
+        self.storage.remove(&path).await?;
+        Ok(true)
+    }
+
+    async fn load(&self, session_id: &str) -> Result<Option<Conversation>> {
+        let path = self.path(session_id);
+        if !self.storage.exists(&path).await {
+            return Ok(None);
+        }
+        self.read(&path).await.map(Some)
+    }
+
+    async fn read(&self, path: &Path) -> Result<Conversation> {
+        Ok(serde_json::from_slice(&self.storage.read(path).await?)?)
+    }
+
+    async fn save(&self, conversation: &Conversation) -> Result<()> {
+        self.storage.create_dir_all(&self.directory).await?;
+        let path = self.path(&conversation.session_id);
+        let tmp = path.with_extension("json.tmp");
+        self.storage
+            .write(&tmp, &serde_json::to_vec(conversation)?)
+            .await?;
+        self.storage.rename(&tmp, &path).await
+    }
+
+    fn path(&self, session_id: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", session_id))
+    }
+}
+
+fn validate_session_id(session_id: &str) -> Result<()> {
+    let valid = !session_id.is_empty()
+        && session_id.len() <= MAX_SESSION_ID_LEN
+        && !session_id.starts_with('.')
+        && session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidRequest(format!(
+            "Invalid session id: {}",
+            session_id
+        )))
+    }
+}
+
+fn checksum(conversation: &Conversation) -> Result<String> {
+    let data = serde_json::to_vec(conversation)?;
+    Ok(hex(digest::digest(&digest::SHA256, &data).as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::clock::FakeClock;
+    use mcp_common::vfs::MemoryVfs;
+    use std::collections::HashMap;
+
+    fn store(storage: Arc<dyn Vfs>) -> ConversationStore {
+        let mut config = Config::default();
+        config.conversations.enabled = true;
+        config.conversations.max_messages_per_session = 4;
+        ConversationStore::with_clock(&config, storage, Arc::new(FakeClock::new(Utc::now())))
+    }
+
+    fn request(method: &str, session_id: &str) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "kiosk-1".to_string(),
+            method: method.to_string(),
+            params: HashMap::from([
+                (SESSION_PARAM.to_string(), serde_json::json!(session_id)),
+                ("prompt".to_string(), serde_json::json!("hello")),
+            ]),
+            context: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn response(request: &MCPRequest, result: serde_json::Value) -> MCPResponse {
+        MCPResponse {
+            id: request.id,
+            result: Some(result),
+            error: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn records_turns_and_keeps_the_latest_messages() {
+        let store = store(Arc::new(MemoryVfs::new()));
+        let mut last = None;
+        for method in ["completion", "tools/call", "completion"] {
+            let request = request(method, "chat-1");
+            let turn = store.turn(&request).unwrap();
+            let result = serde_json::json!({ "text": "hi", "tool_calls": [{ "name": "lookup" }] });
+            store.record(turn, &response(&request, result)).await;
+            last = Some(request);
+        }
+        assert!(store.turn(&request("completion", "../etc")).is_none());
+        assert!(store.turn(&request(EXPORT_METHOD, "chat-1")).is_none());
+
+        let conversation = store.get("chat-1").await.unwrap().unwrap();
+        let roles: Vec<Role> = conversation
+            .messages
+            .iter()
+            .map(|message| message.role)
+            .collect();
+        assert_eq!(
+            roles,
+            [Role::Tool, Role::Assistant, Role::User, Role::Assistant]
+        );
+        let last = last.unwrap();
+        assert_eq!(conversation.messages[2].id, last.id);
+        assert_eq!(
+            conversation.messages[2].content,
+            serde_json::json!({ "prompt": "hello" })
+        );
+        assert_eq!(conversation.messages[3].tool_calls.len(), 1);
+        assert_eq!(store.list().await.unwrap()[0].messages, 4);
+    }
+
+    #[tokio::test]
+    async fn imports_an_export_on_another_gateway_unchanged() {
+        let source = store(Arc::new(MemoryVfs::new()));
+        let request = request("completion", "chat-2");
+        source
+            .record(
+                source.turn(&request).unwrap(),
+                &response(&request, serde_json::json!("hi")),
+            )
+            .await;
+        let package = source.export("chat-2").await.unwrap().unwrap();
+        let package: ConversationPackage =
+            serde_json::from_slice(&serde_json::to_vec(&package).unwrap()).unwrap();
+
+        let target = store(Arc::new(MemoryVfs::new()));
+        let replacement = ImportOptions {
+            replace: false,
+            device_id: Some("kiosk-2".to_string()),
+        };
+        let imported = target
+            .import(package.clone(), replacement.clone())
+            .await
+            .unwrap();
+        assert_eq!(imported.device_id, "kiosk-2");
+        assert_eq!(imported.messages, package.conversation.messages);
+        assert!(target.import(package.clone(), replacement).await.is_err());
+
+        let mut tampered = package;
+        tampered.conversation.messages.pop();
+        let options = ImportOptions {
+            replace: true,
+            device_id: None,
+        };
+        assert!(target.import(tampered, options).await.is_err());
+    }
+}
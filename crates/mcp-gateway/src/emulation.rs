@@ -0,0 +1,366 @@
+//! Emulation mode for client development
+//!
+//! With `gateway.emulation.enabled` the gateway loads no models and never
+//! calls the cloud: every request is routed to the [`EMULATED_MODEL`] and
+//! answered from a fixture for its method after the configured latency.
+//! Responses depend only on the request, so client test suites get the same
+//! answers on every laptop and CI run. Everything else in the request path,
+//! such as authentication, rate limits, caching and the offline queue, runs
+//! as in production.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use mcp_common::config::{EmulationConfig, EmulationFixture};
+use mcp_common::metrics::{ComponentHealth, HealthLevel};
+use mcp_common::{
+    Error, MCPRequest, MCPResponse, ModelId, PerformanceMetrics, Result, RoutingDecision,
+};
+use mcp_models::ModelEngine;
+use mcp_router::Router;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Model every emulated request is routed to
+pub const EMULATED_MODEL: &str = "emulator";
+
+/// Dimensions of built-in embedding responses, as the bundled models
+const EMBEDDING_DIMENSIONS: usize = 384;
+
+/// Router that sends every request to the emulated model
+pub struct EmulatedRouter {
+    config: EmulationConfig,
+}
+
+impl EmulatedRouter {
+    pub fn new(config: EmulationConfig) -> Self {
+        Self {
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Router for EmulatedRouter {
+    async fn route(&self, _request: &MCPRequest) -> Result<RoutingDecision> {
+        Ok(RoutingDecision::Local {
+            model_id: EMULATED_MODEL.to_string(),
+            estimated_latency_ms: self.config.latency_ms,
+        })
+    }
+
+    async fn forward_to_cloud(
+        &self,
+        _request: &MCPRequest,
+        _endpoint: &str,
+    ) -> Result<MCPResponse> {
+        Err(Error::Routing(
+            "Cloud is disabled in emulation mode".to_string(),
+        ))
+    }
+
+    async fn fallback_to_cloud(&self, _request: &MCPRequest) -> Result<MCPResponse> {
+        Err(Error::Routing(
+            "Cloud is disabled in emulation mode".to_string(),
+        ))
+    }
+
+    fn available_models(&self) -> Vec<ModelId> {
+        vec![EMULATED_MODEL.to_string()]
+    }
+
+    async fn update_metrics(&self, _metrics: &PerformanceMetrics) -> Result<()> {
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<ComponentHealth> {
+        Ok(ComponentHealth {
+            status: HealthLevel::Healthy,
+            message: "Emulation mode: all requests go to the emulator".to_string(),
+            last_check: Utc::now(),
+            metrics: HashMap::new(),
+        })
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Model engine that answers from fixtures
+pub struct EmulatedModelEngine {
+    config: EmulationConfig,
+    fixtures: HashMap<String, EmulationFixture>,
+}
+
+impl EmulatedModelEngine {
+    /// Engine serving the inline fixtures over those in `fixtures_path`
+    pub fn new(config: EmulationConfig) -> Result<Self> {
+        let mut fixtures = match &config.fixtures_path {
+            Some(path) => {
+                let raw = std::fs::read_to_string(path).map_err(|e| {
+                    Error::Configuration(format!(
+                        "Failed to read emulation fixtures {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                serde_json::from_str::<HashMap<String, EmulationFixture>>(&raw).map_err(|e| {
+                    Error::Configuration(format!(
+                        "Invalid emulation fixtures {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?
+            },
+            None => HashMap::new(),
+        };
+        fixtures.extend(config.fixtures.clone());
+        warn!(
+            "Emulation mode: serving canned responses for {} methods, no models are loaded",
+            fixtures.len()
+        );
+        Ok(Self {
+            config,
+            fixtures,
+        })
+    }
+
+    /// Latency of the response to `request`
+    fn latency(&self, request: &MCPRequest) -> Duration {
+        let base = self
+            .fixtures
+            .get(&request.method)
+            .and_then(|fixture| fixture.latency_ms)
+            .unwrap_or(self.config.latency_ms);
+        let jitter = match self.config.jitter_ms {
+            0 => 0,
+            jitter_ms => request_hash(request) % (jitter_ms + 1),
+        };
+        Duration::from_millis(base + jitter)
+    }
+
+    fn respond(&self, request: &MCPRequest) -> Result<serde_json::Value> {
+        match self.fixtures.get(&request.method) {
+            Some(fixture) => match &fixture.error {
+                Some(message) => Err(Error::Model(message.clone())),
+                None => Ok(fill_params(&fixture.result, request)),
+            },
+            None => builtin_response(request),
+        }
+    }
+}
+
+#[async_trait]
+impl ModelEngine for EmulatedModelEngine {
+    async fn process_request(
+        &self,
+        request: &MCPRequest,
+        model_id: &ModelId,
+    ) -> Result<MCPResponse> {
+        let latency = self.latency(request);
+        debug!(
+            "Emulating {} on {} after {:?}",
+            request.method, model_id, latency
+        );
+        tokio::time::sleep(latency).await;
+        Ok(MCPResponse {
+            id: request.id,
+            result: Some(self.respond(request)?),
+            error: None,
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn load_model(&self, model_id: &ModelId) -> Result<()> {
+        debug!("Emulation mode: not loading {}", model_id);
+        Ok(())
+    }
+
+    async fn unload_model(&self, _model_id: &ModelId) -> Result<()> {
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<ComponentHealth> {
+        let mut metrics = HashMap::new();
+        metrics.insert("emulated_methods".to_string(), self.fixtures.len() as f32);
+        metrics.insert("latency_ms".to_string(), self.config.latency_ms as f32);
+        Ok(ComponentHealth {
+            status: HealthLevel::Healthy,
+            message: "Emulation mode: responses are canned".to_string(),
+            last_check: Utc::now(),
+            metrics,
+        })
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// FNV-1a over the method and params with keys in order, stable across runs
+fn request_hash(request: &MCPRequest) -> u64 {
+    let params: BTreeMap<&String, &serde_json::Value> = request.params.iter().collect();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let input = format!("{}:{}", request.method, serde_json::json!(params));
+    for byte in input.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// `template` with `{{param}}` in its strings replaced by request params
+fn fill_params(template: &serde_json::Value, request: &MCPRequest) -> serde_json::Value {
+    match template {
+        serde_json::Value::String(text) if text.contains("{{") => {
+            let mut filled = text.clone();
+            for (name, value) in &request.params {
+                let value = match value {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                filled = filled.replace(&format!("{{{{{}}}}}", name), &value);
+            }
+            serde_json::Value::String(filled)
+        },
+        serde_json::Value::Array(items) => serde_json::Value::Array(
+            items
+                .iter()
+                .map(|item| fill_params(item, request))
+                .collect(),
+        ),
+        serde_json::Value::Object(fields) => serde_json::Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), fill_params(value, request)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Response shaped like the bundled models' for methods without a fixture
+fn builtin_response(request: &MCPRequest) -> Result<serde_json::Value> {
+    let result = match request.method.as_str() {
+        "completion" => serde_json::json!({
+            "text": "This is an emulated completion.",
+            "tokens_generated": 6,
+            "model": EMULATED_MODEL,
+            "finish_reason": "stop",
+        }),
+        "chat" => serde_json::json!({
+            "response": "This is an emulated chat response.",
+            "tokens_generated": 7,
+            "model": EMULATED_MODEL,
+            "finish_reason": "stop",
+        }),
+        "embedding" => {
+            let seed = request_hash(request);
+            let embedding: Vec<f32> = (0..EMBEDDING_DIMENSIONS)
+                .map(|i| ((seed % 1000) as f32 + i as f32).sin() * 0.5)
+                .collect();
+            serde_json::json!({
+                "embedding": embedding,
+                "dimensions": EMBEDDING_DIMENSIONS,
+                "model": EMULATED_MODEL,
+            })
+        },
+        "summarization" => serde_json::json!({
+            "summary": "This is an emulated summary.",
+            "model": EMULATED_MODEL,
+        }),
+        method => return Err(Error::Model(format!("Unsupported method: {}", method))),
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, prompt: &str) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "laptop".to_string(),
+            method: method.to_string(),
+            params: HashMap::from([("prompt".to_string(), serde_json::json!(prompt))]),
+            context: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn engine(jitter_ms: u64) -> EmulatedModelEngine {
+        let fixtures = HashMap::from([
+            (
+                "completion".to_string(),
+                EmulationFixture {
+                    result: serde_json::json!({ "text": "You said: {{prompt}}", "tokens": [1, 2] }),
+                    latency_ms: Some(5),
+                    ..Default::default()
+                },
+            ),
+            (
+                "chat".to_string(),
+                EmulationFixture {
+                    error: Some("model overloaded".to_string()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        EmulatedModelEngine::new(EmulationConfig {
+            enabled: true,
+            latency_ms: 20,
+            jitter_ms,
+            fixtures,
+            fixtures_path: None,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fixtures_answer_by_method() {
+        let engine = engine(0);
+        let model = EMULATED_MODEL.to_string();
+        let response = engine
+            .process_request(&request("completion", "hello"), &model)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.result.unwrap(),
+            serde_json::json!({ "text": "You said: hello", "tokens": [1, 2] })
+        );
+
+        let error = engine
+            .process_request(&request("chat", "hello"), &model)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Model(message) if message == "model overloaded"));
+
+        // Methods without a fixture fall back to the built-in responses
+        let embedding = engine.respond(&request("embedding", "hello")).unwrap();
+        assert_eq!(embedding["dimensions"], EMBEDDING_DIMENSIONS);
+        assert!(engine.respond(&request("translate", "hello")).is_err());
+    }
+
+    #[test]
+    fn test_responses_and_latency_are_deterministic() {
+        let engine = engine(100);
+        let first = request("embedding", "same text");
+        let again = request("embedding", "same text");
+        assert_eq!(
+            engine.respond(&first).unwrap(),
+            engine.respond(&again).unwrap()
+        );
+        assert_eq!(engine.latency(&first), engine.latency(&again));
+        assert!(engine.latency(&first) <= Duration::from_millis(120));
+        assert_ne!(
+            engine.respond(&first).unwrap(),
+            engine.respond(&request("embedding", "other text")).unwrap()
+        );
+
+        // The fixture's own latency replaces the default
+        let completion = engine.latency(&request("completion", "hi"));
+        assert!(completion >= Duration::from_millis(5) && completion <= Duration::from_millis(105));
+    }
+}
@@ -0,0 +1,516 @@
+//! Subject data erasure (GDPR right to be forgotten, CCPA deletion)
+//!
+//! An erasure request names a data subject by device id, session id and/or
+//! tenant and deletes everything the gateway stores for any of them: queued
+//! requests and their stored cloud responses, documents in the retrieval
+//! index (and its snapshot), chat histories, session files, and lines in
+//! audit logs, telemetry spool files and captured datasets. The returned
+//! manifest lists what was deleted from each store and serves as the
+//! deletion record.
+//!
+//! Data under a legal hold is never erased. A request for a held device is
+//! refused outright; files covered by a path hold are skipped and listed.
+
+use crate::retention::holds_file;
+use crate::sessions::{SessionHistory, SessionStore};
+use chrono::{DateTime, Utc};
+use mcp_common::config::{DataClass, RetentionConfig};
+use mcp_common::{Error, MCPRequest, Result, Vfs};
+use mcp_models::{Document, HybridRetriever, IndexMaintainer};
+use mcp_queue::OfflineQueue;
+use mcp_router::model_aliases::TENANT_PARAM;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Request parameter and document metadata key carrying a session id
+pub const SESSION_PARAM: &str = "session_id";
+/// Document metadata key carrying the device that indexed it
+pub const DEVICE_METADATA: &str = "device_id";
+
+/// Whose data to erase; data matching any of the identifiers is erased
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ErasureSubject {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+impl ErasureSubject {
+    fn identifiers(&self) -> impl Iterator<Item = &str> {
+        [&self.device_id, &self.session_id, &self.tenant]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    fn matches_request(&self, request: &MCPRequest) -> bool {
+        let param = |key: &str| request.params.get(key).and_then(|value| value.as_str());
+        self.device_id.as_deref() == Some(request.device_id.as_str())
+            || (self.session_id.is_some() && param(SESSION_PARAM) == self.session_id.as_deref())
+            || (self.tenant.is_some() && param(TENANT_PARAM) == self.tenant.as_deref())
+    }
+
+    fn matches_session(&self, history: &SessionHistory) -> bool {
+        self.device_id.as_deref() == Some(history.device_id.as_str())
+            || self.session_id.as_deref() == Some(history.session_id.as_str())
+            || (self.tenant.is_some() && history.tenant == self.tenant)
+    }
+
+    fn matches_document(&self, document: &Document) -> bool {
+        let metadata = |key: &str| document.metadata.get(key).and_then(|value| value.as_str());
+        [
+            (DEVICE_METADATA, &self.device_id),
+            (SESSION_PARAM, &self.session_id),
+            (TENANT_PARAM, &self.tenant),
+        ]
+        .into_iter()
+        .any(|(key, id)| id.is_some() && metadata(key) == id.as_deref())
+    }
+
+    /// Whether `text` mentions an identifier as a whole token
+    fn mentioned_in(&self, text: &str) -> bool {
+        self.identifiers().any(|id| mentions(text, id))
+    }
+}
+
+/// Admin request to erase a subject's data
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ErasureRequest {
+    #[serde(flatten)]
+    pub subject: ErasureSubject,
+    /// Only report what would be erased
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// What was erased from one store
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreErasure {
+    pub store: String,
+    /// Records erased: queue entries, documents, chat histories, session
+    /// files or log lines
+    pub records: usize,
+    /// Queue request ids, document ids, session ids or file paths affected
+    pub items: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl StoreErasure {
+    fn new(store: &str) -> Self {
+        Self {
+            store: store.to_string(),
+            records: 0,
+            items: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// Deletion record returned for an erasure request
+#[derive(Debug, Clone, Serialize)]
+pub struct ErasureManifest {
+    pub manifest_id: Uuid,
+    pub subject: ErasureSubject,
+    pub dry_run: bool,
+    pub requested_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    pub stores: Vec<StoreErasure>,
+    /// Files mentioning the subject kept for a legal hold
+    pub held: Vec<String>,
+}
+
+impl ErasureManifest {
+    /// Whether every store was erased without errors
+    pub fn is_complete(&self) -> bool {
+        self.stores.iter().all(|store| store.errors.is_empty())
+    }
+}
+
+/// Erases a data subject's records from every store
+pub struct DataErasure {
+    config: RetentionConfig,
+    queue: Arc<dyn OfflineQueue + Send + Sync>,
+    storage: Arc<dyn Vfs>,
+    retriever: Arc<HybridRetriever>,
+    index_maintainer: Arc<IndexMaintainer>,
+    sessions: Arc<SessionStore>,
+    /// Serializes erasures so two requests never rewrite the same file at once
+    running: Mutex<()>,
+}
+
+impl DataErasure {
+    pub fn new(
+        config: RetentionConfig,
+        queue: Arc<dyn OfflineQueue + Send + Sync>,
+        storage: Arc<dyn Vfs>,
+        retriever: Arc<HybridRetriever>,
+        index_maintainer: Arc<IndexMaintainer>,
+        sessions: Arc<SessionStore>,
+    ) -> Self {
+        Self {
+            config,
+            queue,
+            storage,
+            retriever,
+            index_maintainer,
+            sessions,
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Erase everything stored for the subject, or report it with `dry_run`
+    pub async fn erase(&self, request: ErasureRequest) -> Result<ErasureManifest> {
+        let ErasureRequest { mut subject, dry_run } = request;
+        for id in [&mut subject.device_id, &mut subject.session_id, &mut subject.tenant] {
+            *id = id.take().filter(|id| !id.trim().is_empty());
+        }
+        if subject.identifiers().next().is_none() {
+            return Err(Error::InvalidRequest(
+                "Erasure needs a device_id, session_id or tenant".to_string(),
+            ));
+        }
+        if let Some(hold) = self
+            .config
+            .legal_holds
+            .iter()
+            .find(|hold| hold.device_id.is_some() && hold.device_id == subject.device_id)
+        {
+            return Err(Error::Security(format!(
+                "Device {} is under legal hold {}",
+                hold.device_id.as_deref().unwrap_or_default(),
+                hold.id
+            )));
+        }
+
+        let _running = self.running.lock().await;
+        let requested_at = Utc::now();
+        let mut stores = vec![
+            self.erase_queue(&subject, dry_run).await,
+            self.erase_documents(&subject, dry_run).await,
+            self.erase_session_context(&subject, dry_run),
+        ];
+        let mut held = Vec::new();
+        for class in [
+            DataClass::Sessions,
+            DataClass::AuditLogs,
+            DataClass::TelemetrySpool,
+            DataClass::CapturedDatasets,
+        ] {
+            let mut erasure = StoreErasure::new(class.as_str());
+            if let Some(dir) = &self.config.policy(class).path {
+                // Session files belong to one session; the others mix subjects line by line
+                let whole_files = class == DataClass::Sessions;
+                self.erase_files(dir, &subject, whole_files, dry_run, &mut erasure, &mut held)
+                    .await;
+            }
+            stores.push(erasure);
+        }
+
+        let manifest = ErasureManifest {
+            manifest_id: Uuid::new_v4(),
+            subject,
+            dry_run,
+            requested_at,
+            completed_at: Utc::now(),
+            stores,
+            held,
+        };
+        if !dry_run {
+            let records: usize = manifest.stores.iter().map(|store| store.records).sum();
+            info!(
+                "Erasure {} removed {} records ({} files held)",
+                manifest.manifest_id,
+                records,
+                manifest.held.len()
+            );
+        }
+        Ok(manifest)
+    }
+
+    async fn erase_queue(&self, subject: &ErasureSubject, dry_run: bool) -> StoreErasure {
+        let mut erasure = StoreErasure::new(DataClass::QueueEntries.as_str());
+        match self.queue.erase(&|request| subject.matches_request(request), dry_run).await {
+            Ok(request_ids) => {
+                erasure.records = request_ids.len();
+                erasure.items = request_ids.iter().map(Uuid::to_string).collect();
+            },
+            Err(e) => erasure.errors.push(e.to_string()),
+        }
+        erasure
+    }
+
+    async fn erase_documents(&self, subject: &ErasureSubject, dry_run: bool) -> StoreErasure {
+        let mut erasure = StoreErasure::new("vector_store");
+        erasure.items = self
+            .retriever
+            .erase_where(|document| subject.matches_document(document), dry_run)
+            .await;
+        erasure.records = erasure.items.len();
+        if !dry_run && erasure.records > 0 {
+            // Rewrite the snapshot so the erased documents are not reloaded
+            if let Err(e) = self.index_maintainer.run(true).await {
+                erasure.errors.push(format!("Failed to rewrite index snapshot: {}", e));
+            }
+        }
+        erasure
+    }
+
+    fn erase_session_context(&self, subject: &ErasureSubject, dry_run: bool) -> StoreErasure {
+        let mut erasure = StoreErasure::new("session_context");
+        match self.sessions.erase(|history| subject.matches_session(history), dry_run) {
+            Ok(session_ids) => {
+                erasure.records = session_ids.len();
+                erasure.items = session_ids;
+            },
+            Err(e) => erasure.errors.push(e.to_string()),
+        }
+        erasure
+    }
+
+    async fn erase_files(
+        &self,
+        dir: &Path,
+        subject: &ErasureSubject,
+        whole_files: bool,
+        dry_run: bool,
+        erasure: &mut StoreErasure,
+        held: &mut Vec<String>,
+    ) {
+        let files = match self.storage.list_dir(dir).await {
+            Ok(files) => files,
+            Err(_) if !self.storage.exists(dir).await => return,
+            Err(e) => {
+                erasure.errors.push(e.to_string());
+                return;
+            },
+        };
+        for file in files {
+            let named = file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| subject.mentioned_in(name));
+            // `None` removes the file, otherwise it is rewritten without the subject's lines
+            let (records, kept) = if named {
+                (1, None)
+            } else if whole_files {
+                continue;
+            } else {
+                match self.storage.read(&file).await {
+                    Ok(data) => {
+                        let text = String::from_utf8_lossy(&data);
+                        let (matching, kept): (Vec<&str>, Vec<&str>) =
+                            text.lines().partition(|line| subject.mentioned_in(line));
+                        if matching.is_empty() {
+                            continue;
+                        }
+                        (matching.len(), Some(rewrite(&kept)))
+                    },
+                    Err(e) => {
+                        erasure.errors.push(e.to_string());
+                        continue;
+                    },
+                }
+            };
+            if self.config.legal_holds.iter().any(|hold| holds_file(hold, &file)) {
+                held.push(file.display().to_string());
+                continue;
+            }
+
+            erasure.records += records;
+            erasure.items.push(file.display().to_string());
+            if dry_run {
+                continue;
+            }
+            let result = match kept {
+                Some(kept) => self.storage.write(&file, kept.as_bytes()).await,
+                None => self.storage.remove(&file).await,
+            };
+            if let Err(e) = result {
+                warn!("Failed to erase subject data from {:?}: {}", file, e);
+                erasure.errors.push(e.to_string());
+            }
+        }
+    }
+}
+
+fn rewrite(lines: &[&str]) -> String {
+    let mut text = lines.join("\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+/// Whether `text` contains `id` not embedded in a longer identifier, so
+/// erasing `press-1` leaves `press-12` alone
+fn mentions(text: &str, id: &str) -> bool {
+    let is_id_char = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    text.match_indices(id).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + id.len()..].chars().next();
+        !before.is_some_and(is_id_char) && !after.is_some_and(is_id_char)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::InMemoryQueue;
+    use mcp_common::config::{Config, LegalHold, RetentionConfig};
+    use mcp_common::vfs::MemoryVfs;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn request(device_id: &str, session_id: &str) -> MCPRequest {
+        MCPRequest {
+            id: Uuid::new_v4(),
+            device_id: device_id.to_string(),
+            method: "completion".to_string(),
+            params: HashMap::from([(SESSION_PARAM.to_string(), serde_json::json!(session_id))]),
+            context: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn document(id: &str, device_id: &str) -> Document {
+        Document {
+            id: id.to_string(),
+            text: format!("notes from {}", device_id),
+            metadata: HashMap::from([(DEVICE_METADATA.to_string(), serde_json::json!(device_id))]),
+        }
+    }
+
+    fn erasure(config: RetentionConfig) -> (DataErasure, Arc<InMemoryQueue>, Arc<MemoryVfs>, Arc<HybridRetriever>) {
+        let queue = Arc::new(InMemoryQueue::new(10));
+        let storage = Arc::new(MemoryVfs::new());
+        let retriever = Arc::new(HybridRetriever::new(&Default::default()));
+        let maintainer = Arc::new(IndexMaintainer::new(
+            Default::default(),
+            retriever.clone(),
+            storage.clone(),
+            None,
+        ));
+        let sessions = Arc::new(SessionStore::new(&Config::default(), storage.as_ref()).unwrap());
+        let erasure = DataErasure::new(
+            config,
+            queue.clone(),
+            storage.clone(),
+            retriever.clone(),
+            maintainer,
+            sessions,
+        );
+        (erasure, queue, storage, retriever)
+    }
+
+    #[tokio::test]
+    async fn test_erasure_covers_every_store_and_spares_other_subjects() {
+        let (erasure, queue, storage, retriever) = erasure(RetentionConfig::default());
+        queue.enqueue_request(request("press-1", "s-100")).await.unwrap();
+        queue.enqueue_request(request("press-12", "s-200")).await.unwrap();
+        queue.enqueue_request(request("press-12", "s-100")).await.unwrap();
+        retriever
+            .index(vec![document("doc-a", "press-1"), document("doc-b", "press-12")])
+            .await;
+        storage.write(Path::new("./data/sessions/press-1.json"), b"{}").await.unwrap();
+        storage.write(Path::new("./data/sessions/press-12.json"), b"{}").await.unwrap();
+        storage
+            .write(
+                Path::new("./logs/audit/2026-06.log"),
+                b"login device=press-1\nlogin device=press-12\nsession s-100 closed\n",
+            )
+            .await
+            .unwrap();
+
+        let subject = ErasureSubject {
+            device_id: Some("press-1".to_string()),
+            session_id: Some("s-100".to_string()),
+            tenant: None,
+        };
+        let preview = erasure
+            .erase(ErasureRequest { subject: subject.clone(), dry_run: true })
+            .await
+            .unwrap();
+        assert_eq!(queue.pending().len(), 3);
+        let manifest = erasure.erase(ErasureRequest { subject, dry_run: false }).await.unwrap();
+        assert!(manifest.is_complete());
+        let records: Vec<(&str, usize)> = manifest.stores.iter().map(|s| (s.store.as_str(), s.records)).collect();
+        let preview_records: Vec<(&str, usize)> = preview.stores.iter().map(|s| (s.store.as_str(), s.records)).collect();
+        assert_eq!(records, preview_records);
+        assert_eq!(
+            records,
+            vec![
+                ("queue_entries", 2),
+                ("vector_store", 1),
+                ("session_context", 0),
+                ("sessions", 1),
+                ("audit_logs", 2),
+                ("telemetry_spool", 0),
+                ("captured_datasets", 0),
+            ]
+        );
+
+        let remaining: Vec<String> = queue.pending().into_iter().map(|r| r.device_id).collect();
+        assert_eq!(remaining, vec!["press-12".to_string()]);
+        assert_eq!(retriever.len().await, 1);
+        assert!(storage.exists(Path::new("./data/sessions/press-12.json")).await);
+        assert!(!storage.exists(Path::new("./data/sessions/press-1.json")).await);
+        let log = storage.read(Path::new("./logs/audit/2026-06.log")).await.unwrap();
+        assert_eq!(log, b"login device=press-12\n");
+    }
+
+    #[tokio::test]
+    async fn test_legal_holds_block_or_skip_erasure() {
+        let config = RetentionConfig {
+            legal_holds: vec![
+                LegalHold {
+                    id: "case-17".to_string(),
+                    reason: "litigation".to_string(),
+                    device_id: Some("press-4".to_string()),
+                    path: None,
+                },
+                LegalHold {
+                    id: "june-audit".to_string(),
+                    reason: "external audit".to_string(),
+                    device_id: None,
+                    path: Some(PathBuf::from("./logs/audit/2026-06.log")),
+                },
+            ],
+            ..RetentionConfig::default()
+        };
+        let (erasure, _, storage, _) = erasure(config);
+        storage.write(Path::new("./logs/audit/2026-06.log"), b"login device=press-1\n").await.unwrap();
+
+        let held_device = ErasureRequest {
+            subject: ErasureSubject {
+                device_id: Some("press-4".to_string()),
+                ..ErasureSubject::default()
+            },
+            dry_run: false,
+        };
+        assert!(matches!(erasure.erase(held_device).await, Err(Error::Security(_))));
+        assert!(matches!(
+            erasure.erase(ErasureRequest::default()).await,
+            Err(Error::InvalidRequest(_))
+        ));
+
+        let manifest = erasure
+            .erase(ErasureRequest {
+                subject: ErasureSubject {
+                    device_id: Some("press-1".to_string()),
+                    ..ErasureSubject::default()
+                },
+                dry_run: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(manifest.held, vec!["./logs/audit/2026-06.log".to_string()]);
+        assert!(storage.exists(Path::new("./logs/audit/2026-06.log")).await);
+    }
+}
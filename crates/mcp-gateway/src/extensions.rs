@@ -0,0 +1,739 @@
+//! WebAssembly extension plugins
+//!
+//! Extensions are WebAssembly components built against the `extension`
+//! world in `wit/extension.wit` and loaded when the gateway starts. Each
+//! ships a `manifest.json` naming its component, the hooks it implements and
+//! the host interfaces it imports:
+//!
+//! ```json
+//! {
+//!   "name": "pii-scrubber",
+//!   "version": "1.2.0",
+//!   "component": "pii_scrubber.wasm",
+//!   "hooks": ["transform_request"],
+//!   "permissions": ["log", "settings"]
+//! }
+//! ```
+//!
+//! Hooks run in configuration order. Every `transform_request` extension
+//! rewrites the request in turn before it is validated; the first `route`
+//! extension to return a decision overrides the router; and a `handle`
+//! extension answers the methods its manifest lists. An extension only gets
+//! the host interfaces its manifest declares, and only when the operator
+//! allows them. Every call runs in a fresh instance under the plugin's fuel,
+//! memory and time limits; an extension that fails or exceeds a limit fails
+//! the request rather than being skipped. The `kv` interface is the
+//! extension's `ext.<name>` namespace of the gateway's [`KvStore`].
+
+use crate::kv::KvStore;
+use mcp_common::config::{Config, ExtensionPermission, ExtensionPluginConfig};
+use mcp_common::{Error, MCPRequest, MCPResponse, Result, RoutingDecision};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Manifest shipped with every extension
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtensionManifest {
+    pub name: String,
+    pub version: String,
+    /// Component file, relative to the manifest
+    pub component: PathBuf,
+    pub hooks: Vec<ExtensionHook>,
+    /// Methods the `handle` hook answers
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// Host interfaces the component imports
+    #[serde(default)]
+    pub permissions: Vec<ExtensionPermission>,
+}
+
+/// Extension points a plugin can implement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtensionHook {
+    TransformRequest,
+    Route,
+    Handle,
+}
+
+impl ExtensionManifest {
+    /// Read a plugin's manifest and check it against what the operator allows
+    pub fn load(plugin: &ExtensionPluginConfig) -> Result<Self> {
+        let path = plugin.manifest.display();
+        let text = std::fs::read_to_string(&plugin.manifest)
+            .map_err(|e| Error::Configuration(format!("Failed to read extension manifest {}: {}", path, e)))?;
+        let manifest: Self = serde_json::from_str(&text)
+            .map_err(|e| Error::Configuration(format!("Invalid extension manifest {}: {}", path, e)))?;
+        manifest.check(plugin)?;
+        Ok(manifest)
+    }
+
+    fn check(&self, plugin: &ExtensionPluginConfig) -> Result<()> {
+        let invalid = |reason: &str| Error::Configuration(format!("Extension {}: {}", self.name, reason));
+        if self.hooks.is_empty() {
+            return Err(invalid("manifest declares no hooks"));
+        }
+        if self.implements(ExtensionHook::Handle) == self.methods.is_empty() {
+            return Err(invalid("the handle hook and a list of methods go together"));
+        }
+        if let Some(permission) = self.permissions.iter().find(|permission| !plugin.allow.contains(permission)) {
+            return Err(Error::Security(format!(
+                "Extension {} asks for the {} permission, which is not allowed",
+                self.name,
+                interface_name(*permission)
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn implements(&self, hook: ExtensionHook) -> bool {
+        self.hooks.contains(&hook)
+    }
+
+    /// Component path, resolved against the manifest's directory
+    pub fn component_path(&self, manifest: &Path) -> PathBuf {
+        manifest.parent().unwrap_or_else(|| Path::new(".")).join(&self.component)
+    }
+}
+
+/// WIT interface a permission grants
+fn interface_name(permission: ExtensionPermission) -> &'static str {
+    match permission {
+        ExtensionPermission::Log => "log",
+        ExtensionPermission::Settings => "settings",
+        ExtensionPermission::Kv => "kv",
+    }
+}
+
+/// Manifests of the configured plugins, in configuration order
+fn load_manifests(config: &Config) -> Result<Vec<ExtensionManifest>> {
+    let manifests = config
+        .extensions
+        .plugins
+        .iter()
+        .map(ExtensionManifest::load)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut methods = HashSet::new();
+    for manifest in &manifests {
+        if let Some(method) = manifest.methods.iter().find(|method| !methods.insert(method.as_str())) {
+            return Err(Error::Configuration(format!(
+                "Method '{}' is handled by more than one extension",
+                method
+            )));
+        }
+    }
+    Ok(manifests)
+}
+
+/// Loaded extensions, called on the request path
+#[derive(Default)]
+pub struct Extensions {
+    #[cfg(feature = "wasm-extensions")]
+    runtime: Option<runtime::Runtime>,
+}
+
+impl Extensions {
+    /// Load the configured plugins; no-op hooks when extensions are disabled
+    pub fn load(config: &Config, kv: &Arc<KvStore>) -> Result<Self> {
+        if !config.extensions.enabled || config.extensions.plugins.is_empty() {
+            return Ok(Self::default());
+        }
+        let manifests = load_manifests(config)?;
+
+        #[cfg(feature = "wasm-extensions")]
+        {
+            Ok(Self {
+                runtime: Some(runtime::Runtime::new(config, manifests, kv)?),
+            })
+        }
+        #[cfg(not(feature = "wasm-extensions"))]
+        {
+            drop((manifests, kv));
+            Err(Error::Configuration(
+                "Extensions require a gateway built with the `wasm-extensions` feature".to_string(),
+            ))
+        }
+    }
+
+    /// Names and versions of the loaded extensions
+    pub fn loaded(&self) -> Vec<(String, String)> {
+        #[cfg(feature = "wasm-extensions")]
+        if let Some(runtime) = &self.runtime {
+            return runtime.loaded();
+        }
+        Vec::new()
+    }
+
+    /// Methods answered by `handle` extensions
+    pub fn methods(&self) -> Vec<String> {
+        #[cfg(feature = "wasm-extensions")]
+        if let Some(runtime) = &self.runtime {
+            return runtime.methods();
+        }
+        Vec::new()
+    }
+
+    /// Run the request through every `transform_request` extension
+    pub async fn transform(&self, request: MCPRequest) -> Result<MCPRequest> {
+        #[cfg(feature = "wasm-extensions")]
+        if let Some(runtime) = &self.runtime {
+            return runtime.transform(request).await;
+        }
+        Ok(request)
+    }
+
+    /// The first routing decision a `route` extension makes for the request
+    #[cfg_attr(not(feature = "wasm-extensions"), allow(unused_variables))]
+    pub async fn route(&self, request: &MCPRequest) -> Result<Option<RoutingDecision>> {
+        #[cfg(feature = "wasm-extensions")]
+        if let Some(runtime) = &self.runtime {
+            return runtime.route(request).await;
+        }
+        Ok(None)
+    }
+
+    /// The response of the `handle` extension for the request's method, if any
+    #[cfg_attr(not(feature = "wasm-extensions"), allow(unused_variables))]
+    pub async fn handle(&self, request: &MCPRequest) -> Result<Option<MCPResponse>> {
+        #[cfg(feature = "wasm-extensions")]
+        if let Some(runtime) = &self.runtime {
+            return runtime.handle(request).await;
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "wasm-extensions")]
+mod runtime {
+    use super::{interface_name, ExtensionHook, ExtensionManifest};
+    use crate::kv::{KvNamespace, KvStore};
+    use mcp_common::config::{Config, ExtensionLimits, ExtensionPermission};
+    use mcp_common::{redaction, Error, MCPRequest, MCPResponse, Result, RoutingDecision};
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tracing::{debug, error, info, warn};
+    use wasmtime::component::{Component, HasSelf, Linker};
+    use wasmtime::{Engine, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+    mod bindings {
+        wasmtime::component::bindgen!({
+            path: "wit",
+            world: "extension",
+        });
+    }
+
+    use bindings::mcp::extension::{kv, log, settings};
+    use bindings::{Extension, ExtensionPre, Request, RouteDecision};
+
+    /// Interval the engine epoch advances at, the resolution of call timeouts
+    const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+    /// Retry hint for requests an extension sends to the queue
+    const QUEUE_RETRY_AFTER_MS: u64 = 5000;
+
+    pub struct Runtime {
+        plugins: Vec<Arc<Plugin>>,
+        /// Cloud endpoint URLs by name, for `route` decisions
+        cloud_endpoints: HashMap<String, String>,
+        stop_ticker: Arc<AtomicBool>,
+    }
+
+    impl Runtime {
+        pub fn new(config: &Config, manifests: Vec<ExtensionManifest>, kv: &Arc<KvStore>) -> Result<Self> {
+            let mut engine_config = wasmtime::Config::new();
+            engine_config.consume_fuel(true).epoch_interruption(true);
+            let engine = Engine::new(&engine_config)
+                .map_err(|e| Error::Internal(format!("Failed to create extension engine: {}", e)))?;
+
+            let mut plugins = Vec::new();
+            for (plugin_config, manifest) in config.extensions.plugins.iter().zip(manifests) {
+                let limits = plugin_config.limits.clone().unwrap_or_else(|| config.extensions.limits.clone());
+                let pre = instantiate_pre(&engine, &manifest, &manifest.component_path(&plugin_config.manifest))?;
+                let namespace = kv.namespace(&format!("ext.{}", manifest.name))?;
+                kv.set_limit(namespace.name(), limits.max_kv_bytes);
+                info!("Loaded extension {} {} ({:?})", manifest.name, manifest.version, manifest.hooks);
+                plugins.push(Arc::new(Plugin {
+                    engine: engine.clone(),
+                    pre,
+                    settings: Arc::new(plugin_config.settings.clone()),
+                    kv: namespace,
+                    limits,
+                    manifest,
+                }));
+            }
+
+            let stop_ticker = Arc::new(AtomicBool::new(false));
+            let stop = Arc::clone(&stop_ticker);
+            std::thread::Builder::new()
+                .name("extension-epoch".to_string())
+                .spawn(move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        std::thread::sleep(EPOCH_TICK);
+                        engine.increment_epoch();
+                    }
+                })
+                .map_err(|e| Error::Internal(format!("Failed to start extension timer: {}", e)))?;
+
+            Ok(Self {
+                plugins,
+                cloud_endpoints: config
+                    .router
+                    .cloud_endpoints
+                    .iter()
+                    .map(|endpoint| (endpoint.name.clone(), endpoint.url.clone()))
+                    .collect(),
+                stop_ticker,
+            })
+        }
+
+        pub fn loaded(&self) -> Vec<(String, String)> {
+            self.plugins
+                .iter()
+                .map(|plugin| (plugin.manifest.name.clone(), plugin.manifest.version.clone()))
+                .collect()
+        }
+
+        pub fn methods(&self) -> Vec<String> {
+            self.with_hook(ExtensionHook::Handle)
+                .flat_map(|plugin| plugin.manifest.methods.iter().cloned())
+                .collect()
+        }
+
+        fn with_hook(&self, hook: ExtensionHook) -> impl Iterator<Item = &Arc<Plugin>> {
+            self.plugins.iter().filter(move |plugin| plugin.manifest.implements(hook))
+        }
+
+        pub async fn transform(&self, mut request: MCPRequest) -> Result<MCPRequest> {
+            for plugin in self.with_hook(ExtensionHook::TransformRequest) {
+                let input = to_wit(&request)?;
+                let name = plugin.manifest.name.clone();
+                match plugin.call("transform_request", move |bindings, store| bindings.call_transform_request(store, &input)).await? {
+                    Ok(transformed) => {
+                        request.method = transformed.method;
+                        request.params = serde_json::from_str(&transformed.params).map_err(|e| {
+                            Error::Internal(format!("Extension {} returned invalid params: {}", name, e))
+                        })?;
+                    },
+                    Err(reason) => {
+                        return Err(Error::InvalidRequest(format!("Rejected by extension {}: {}", name, reason)));
+                    },
+                }
+            }
+            Ok(request)
+        }
+
+        pub async fn route(&self, request: &MCPRequest) -> Result<Option<RoutingDecision>> {
+            for plugin in self.with_hook(ExtensionHook::Route) {
+                let input = to_wit(request)?;
+                let route = plugin.call("route", move |bindings, store| bindings.call_route(store, &input)).await?;
+                let decision = match route {
+                    None => continue,
+                    Some(RouteDecision::Local(model_id)) => RoutingDecision::Local {
+                        model_id,
+                        estimated_latency_ms: 0,
+                    },
+                    Some(RouteDecision::Cloud(name)) => RoutingDecision::Cloud {
+                        endpoint: self.cloud_endpoints.get(&name).cloned().ok_or_else(|| {
+                            Error::Routing(format!(
+                                "Extension {} routed to unknown cloud endpoint '{}'",
+                                plugin.manifest.name, name
+                            ))
+                        })?,
+                        estimated_latency_ms: 0,
+                    },
+                    Some(RouteDecision::Queue(reason)) => RoutingDecision::Queue {
+                        reason,
+                        retry_after_ms: QUEUE_RETRY_AFTER_MS,
+                    },
+                };
+                debug!("Extension {} routed request {}", plugin.manifest.name, request.id);
+                return Ok(Some(decision));
+            }
+            Ok(None)
+        }
+
+        pub async fn handle(&self, request: &MCPRequest) -> Result<Option<MCPResponse>> {
+            let Some(plugin) = self
+                .with_hook(ExtensionHook::Handle)
+                .find(|plugin| plugin.manifest.methods.contains(&request.method))
+            else {
+                return Ok(None);
+            };
+            let input = to_wit(request)?;
+            let result = plugin
+                .call("handle", move |bindings, store| bindings.call_handle(store, &input))
+                .await?
+                .map_err(|message| Error::InvalidRequest(format!("Extension {}: {}", plugin.manifest.name, message)))?;
+            let result = serde_json::from_str(&result).map_err(|e| {
+                Error::Internal(format!("Extension {} returned an invalid result: {}", plugin.manifest.name, e))
+            })?;
+            Ok(Some(MCPResponse {
+                id: request.id,
+                result: Some(result),
+                error: None,
+                timestamp: chrono::Utc::now(),
+            }))
+        }
+    }
+
+    impl Drop for Runtime {
+        fn drop(&mut self) {
+            self.stop_ticker.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Link the host interfaces the manifest declares and check the
+    /// component against the world
+    fn instantiate_pre(
+        engine: &Engine,
+        manifest: &ExtensionManifest,
+        path: &std::path::Path,
+    ) -> Result<ExtensionPre<PluginState>> {
+        let load_error = |e: wasmtime::Error| {
+            Error::Configuration(format!("Failed to load extension {} from {}: {:#}", manifest.name, path.display(), e))
+        };
+        let component = Component::from_file(engine, path).map_err(load_error)?;
+
+        let mut linker = Linker::<PluginState>::new(engine);
+        for permission in &manifest.permissions {
+            match permission {
+                ExtensionPermission::Log => log::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state),
+                ExtensionPermission::Settings => settings::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state),
+                ExtensionPermission::Kv => kv::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state),
+            }
+            .map_err(load_error)?;
+        }
+
+        // Imports left unlinked are interfaces the plugin was not granted
+        let pre = linker.instantiate_pre(&component).map_err(|e| {
+            let declared: Vec<&str> = manifest.permissions.iter().map(|p| interface_name(*p)).collect();
+            Error::Security(format!(
+                "Extension {} imports more than its declared permissions [{}]: {:#}",
+                manifest.name,
+                declared.join(", "),
+                e
+            ))
+        })?;
+        ExtensionPre::new(pre).map_err(load_error)
+    }
+
+    struct Plugin {
+        manifest: ExtensionManifest,
+        limits: ExtensionLimits,
+        engine: Engine,
+        pre: ExtensionPre<PluginState>,
+        settings: Arc<BTreeMap<String, String>>,
+        kv: KvNamespace,
+    }
+
+    impl Plugin {
+        /// Run `call` against a fresh instance on a blocking thread
+        async fn call<R: Send + 'static>(
+            self: &Arc<Self>,
+            hook: &'static str,
+            call: impl FnOnce(&Extension, &mut Store<PluginState>) -> wasmtime::Result<R> + Send + 'static,
+        ) -> Result<R> {
+            let plugin = Arc::clone(self);
+            let result = tokio::task::spawn_blocking(move || {
+                let mut store = plugin.store()?;
+                let bindings = plugin.pre.instantiate(&mut store)?;
+                call(&bindings, &mut store)
+            })
+            .await
+            .map_err(|e| Error::Internal(format!("Extension {} call panicked: {}", self.manifest.name, e)))?;
+
+            result.map_err(|e| {
+                let name = &self.manifest.name;
+                match e.downcast_ref::<Trap>() {
+                    Some(Trap::OutOfFuel) => Error::ResourceExhausted(format!("Extension {} ran out of fuel in {}", name, hook)),
+                    Some(Trap::Interrupt) => Error::ResourceExhausted(format!(
+                        "Extension {} exceeded its {}ms time limit in {}",
+                        name, self.limits.timeout_ms, hook
+                    )),
+                    _ => Error::Internal(format!("Extension {} failed in {}: {:#}", name, hook, e)),
+                }
+            })
+        }
+
+        fn store(&self) -> wasmtime::Result<Store<PluginState>> {
+            let state = PluginState {
+                name: self.manifest.name.clone(),
+                settings: Arc::clone(&self.settings),
+                kv: self.kv.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(usize::try_from(self.limits.max_memory_bytes).unwrap_or(usize::MAX))
+                    .build(),
+            };
+            let mut store = Store::new(&self.engine, state);
+            store.limiter(|state| &mut state.limits);
+            store.set_fuel(self.limits.fuel_per_call)?;
+            let tick_ms = EPOCH_TICK.as_millis() as u64;
+            store.set_epoch_deadline(self.limits.timeout_ms.div_ceil(tick_ms).max(1));
+            Ok(store)
+        }
+    }
+
+    fn to_wit(request: &MCPRequest) -> Result<Request> {
+        Ok(Request {
+            id: request.id.to_string(),
+            device_id: request.device_id.clone(),
+            method: request.method.clone(),
+            params: serde_json::to_string(&request.params)?,
+        })
+    }
+
+    /// Host side of one extension call
+    struct PluginState {
+        name: String,
+        settings: Arc<BTreeMap<String, String>>,
+        kv: KvNamespace,
+        limits: StoreLimits,
+    }
+
+    impl log::Host for PluginState {
+        fn write(&mut self, level: log::Level, message: String) {
+            let message = redaction::text(&message);
+            match level {
+                log::Level::Debug => debug!("[extension {}] {}", self.name, message),
+                log::Level::Info => info!("[extension {}] {}", self.name, message),
+                log::Level::Warn => warn!("[extension {}] {}", self.name, message),
+                log::Level::Error => error!("[extension {}] {}", self.name, message),
+            }
+        }
+    }
+
+    impl settings::Host for PluginState {
+        fn get(&mut self, key: String) -> Option<String> {
+            self.settings.get(&key).cloned()
+        }
+    }
+
+    impl kv::Host for PluginState {
+        fn get(&mut self, key: String) -> Option<Vec<u8>> {
+            self.kv.get(&key)
+        }
+
+        fn set(&mut self, key: String, value: Vec<u8>) -> std::result::Result<(), String> {
+            self.kv.set(&key, value, None).map_err(|e| e.to_string())
+        }
+
+        fn set_with_ttl(&mut self, key: String, value: Vec<u8>, ttl_secs: u64) -> std::result::Result<(), String> {
+            self.kv
+                .set(&key, value, Some(Duration::from_secs(ttl_secs)))
+                .map_err(|e| e.to_string())
+        }
+
+        fn delete(&mut self, key: String) {
+            self.kv.delete(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::config::ExtensionPluginConfig;
+
+    fn write_manifest(dir: &Path, manifest: serde_json::Value) -> ExtensionPluginConfig {
+        let path = dir.join("manifest.json");
+        std::fs::write(&path, manifest.to_string()).unwrap();
+        ExtensionPluginConfig {
+            manifest: path,
+            allow: vec![ExtensionPermission::Log],
+            limits: None,
+            settings: Default::default(),
+        }
+    }
+
+    fn kv_store() -> Arc<KvStore> {
+        Arc::new(KvStore::new(Default::default(), Arc::new(mcp_common::vfs::MemoryVfs::new())))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcp-extensions-{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_manifest_permissions_must_be_allowed() {
+        let dir = temp_dir("permissions");
+        let plugin = write_manifest(
+            &dir,
+            serde_json::json!({
+                "name": "scrubber",
+                "version": "1.0.0",
+                "component": "scrubber.wasm",
+                "hooks": ["transform_request"],
+                "permissions": ["log"],
+            }),
+        );
+        let manifest = ExtensionManifest::load(&plugin).unwrap();
+        assert_eq!(manifest.component_path(&plugin.manifest), dir.join("scrubber.wasm"));
+        assert!(manifest.implements(ExtensionHook::TransformRequest));
+
+        let plugin = write_manifest(
+            &dir,
+            serde_json::json!({
+                "name": "scrubber",
+                "version": "1.0.0",
+                "component": "scrubber.wasm",
+                "hooks": ["transform_request"],
+                "permissions": ["log", "kv"],
+            }),
+        );
+        assert!(matches!(ExtensionManifest::load(&plugin), Err(Error::Security(_))));
+
+        // Handlers must name their methods, and other hooks may not
+        let plugin = write_manifest(
+            &dir,
+            serde_json::json!({"name": "tools", "version": "1.0.0", "component": "tools.wasm", "hooks": ["handle"]}),
+        );
+        assert!(ExtensionManifest::load(&plugin).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_methods_are_handled_by_one_extension() {
+        let mut config = Config::default();
+        config.extensions.enabled = true;
+        for name in ["weather", "forecast"] {
+            let dir = temp_dir(name);
+            config.extensions.plugins.push(write_manifest(
+                &dir,
+                serde_json::json!({
+                    "name": name,
+                    "version": "1.0.0",
+                    "component": "plugin.wasm",
+                    "hooks": ["handle"],
+                    "methods": ["tools/weather"],
+                }),
+            ));
+        }
+        let error = load_manifests(&config).unwrap_err();
+        assert!(error.to_string().contains("tools/weather"), "{}", error);
+
+        // Disabled extensions pass requests through untouched
+        config.extensions.enabled = false;
+        let extensions = Extensions::load(&config, &kv_store()).unwrap();
+        assert!(extensions.loaded().is_empty());
+        let request = MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "sensor-1".to_string(),
+            method: "tools/weather".to_string(),
+            params: Default::default(),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        };
+        assert!(extensions.handle(&request).await.unwrap().is_none());
+        assert!(extensions.route(&request).await.unwrap().is_none());
+        for plugin in &config.extensions.plugins {
+            std::fs::remove_dir_all(plugin.manifest.parent().unwrap()).unwrap();
+        }
+    }
+
+    /// Component whose `route` hook runs `route_body`, optionally importing `kv`
+    #[cfg(feature = "wasm-extensions")]
+    fn component(route_body: &str, import_kv: bool) -> String {
+        let import = if import_kv {
+            r#"(import "mcp:extension/kv@1.0.0" (instance (export "delete" (func (param "key" string)))))"#
+        } else {
+            ""
+        };
+        format!(
+            r#"(component
+                {import}
+                (core module $m
+                    (memory (export "memory") 1)
+                    (global $next (mut i32) (i32.const 1024))
+                    (data (i32.const 0) "night")
+                    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                        (global.get $next)
+                        (global.set $next (i32.add (global.get $next) (local.get 3))))
+                    (func (export "route") (param i32 i32 i32 i32 i32 i32 i32 i32) (result i32) {route_body})
+                    (func (export "unused") (param i32 i32 i32 i32 i32 i32 i32 i32) (result i32) unreachable))
+                (core instance $i (instantiate $m))
+                (type $request' (record (field "id" string) (field "device-id" string) (field "method" string) (field "params" string)))
+                (export $request "request" (type $request'))
+                (type $route-decision' (variant (case "local" string) (case "cloud" string) (case "queue" string)))
+                (export $route-decision "route-decision" (type $route-decision'))
+                (func (export "transform-request") (param "request" $request) (result (result $request (error string)))
+                    (canon lift (core func $i "unused") (memory (core memory $i "memory")) (realloc (core func $i "realloc"))))
+                (func (export "route") (param "request" $request) (result (option $route-decision))
+                    (canon lift (core func $i "route") (memory (core memory $i "memory")) (realloc (core func $i "realloc"))))
+                (func (export "handle") (param "request" $request) (result (result string (error string)))
+                    (canon lift (core func $i "unused") (memory (core memory $i "memory")) (realloc (core func $i "realloc")))))"#
+        )
+    }
+
+    #[cfg(feature = "wasm-extensions")]
+    #[tokio::test]
+    async fn test_components_run_within_permissions_and_limits() {
+        // `queue("night")`: option and variant tags, then the string
+        const QUEUE_NIGHT: &str = "(i32.store8 (i32.const 16) (i32.const 1))
+            (i32.store8 (i32.const 20) (i32.const 2))
+            (i32.store (i32.const 24) (i32.const 0))
+            (i32.store (i32.const 28) (i32.const 5))
+            (i32.const 16)";
+        const SPIN: &str = "(loop $spin (br $spin)) unreachable";
+
+        let load = |name: &str, route_body: &str, import_kv: bool, grant_kv: bool| {
+            let dir = temp_dir(name);
+            std::fs::write(dir.join("plugin.wat"), component(route_body, import_kv)).unwrap();
+            let permissions = if grant_kv { vec!["kv"] } else { Vec::new() };
+            let mut plugin = write_manifest(
+                &dir,
+                serde_json::json!({
+                    "name": name,
+                    "version": "1.0.0",
+                    "component": "plugin.wat",
+                    "hooks": ["route"],
+                    "permissions": permissions,
+                }),
+            );
+            if grant_kv {
+                plugin.allow.push(ExtensionPermission::Kv);
+            }
+            plugin.limits = Some(mcp_common::config::ExtensionLimits {
+                fuel_per_call: 1_000_000,
+                ..Default::default()
+            });
+            let mut config = Config::default();
+            config.extensions.enabled = true;
+            config.extensions.plugins.push(plugin);
+            let extensions = Extensions::load(&config, &kv_store());
+            std::fs::remove_dir_all(dir).unwrap();
+            extensions
+        };
+        let request = MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "sensor-1".to_string(),
+            method: "completion".to_string(),
+            params: Default::default(),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        };
+
+        let extensions = load("nightly", QUEUE_NIGHT, false, false).unwrap();
+        assert_eq!(extensions.loaded(), vec![("nightly".to_string(), "1.0.0".to_string())]);
+        match extensions.route(&request).await.unwrap() {
+            Some(RoutingDecision::Queue { reason, .. }) => assert_eq!(reason, "night"),
+            other => panic!("unexpected decision {:?}", other),
+        }
+
+        // A runaway call is stopped by its fuel limit
+        let extensions = load("spinner", SPIN, false, false).unwrap();
+        assert!(matches!(extensions.route(&request).await, Err(Error::ResourceExhausted(_))));
+
+        // Importing an interface the manifest does not declare fails the load
+        assert!(matches!(load("sneaky", QUEUE_NIGHT, true, false), Err(Error::Security(_))));
+
+        // Components built against `kv` 1.0 still link once it is granted
+        assert!(load("legacy-kv", QUEUE_NIGHT, true, true).is_ok());
+    }
+}
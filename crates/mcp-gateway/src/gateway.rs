@@ -1,20 +1,84 @@
 //! Core gateway implementation
 
-use mcp_common::{Config, Error, MCPRequest, MCPResponse, Result};
+use mcp_common::clock::{self as clock, Clock};
+use mcp_common::{CircuitState, Config, Error, MCPRequest, MCPResponse, ModelId, RequestPrioritizer, RequestSource, Result, SharedState, Span, SpanKind, TimeoutDetails, TimeoutStage};
+use mcp_common::config::{ModelRollout, VerificationFailureAction};
+use mcp_common::crypto;
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
 use mcp_common::metrics::{ComponentHealth, HealthLevel};
-use mcp_models::ModelEngine;
+use mcp_common::redaction;
+use mcp_common::request_id;
+use mcp_common::request_signing;
+use mcp_common::stage_timings::{self, Stage, StageTimings, STAGE_TIMINGS_FIELD};
+use mcp_common::trace_context;
+use mcp_common::usage::{self, DeferredUsage, ResourceUsage, TenantUsage};
+use mcp_common::write_policy::WritePolicy;
+use mcp_models::{
+    buffered_stream, splice_stream, token_stream, Document, HybridRetriever, IndexMaintainer, IngestionPipeline,
+    ModelEngine, StreamChunk, TokenStream,
+    LIST_MODELS_METHOD, RETRIEVAL_INDEX_METHOD, RETRIEVAL_SEARCH_METHOD, STREAMING_METHOD,
+};
 use mcp_queue::OfflineQueue;
-use mcp_router::Router;
-use mcp_security::SecurityManager;
-use mcp_telemetry::TelemetryCollector;
-use mcp_pipeline_guard::PipelineGuard;
+use mcp_router::model_aliases::TENANT_PARAM;
+use mcp_router::model_rollouts::version_id;
+use mcp_router::{ModelRollouts, RolloutStatus, Router};
+use mcp_security::{OutputModerator, SecurityManager};
+use mcp_telemetry::{Labels, MetricKind, PrometheusEncoder, TelemetryCollector};
+use mcp_pipeline_guard::{CanaryTarget, ModelCanary, PipelineGuard};
+use crate::admission::{Admission, AdmissionController};
+use crate::artifacts::ArtifactUploader;
+use crate::audio::AudioPipeline;
+use crate::audit::{AuditDigest, AuditSink};
+use crate::builder::GatewayBuilder;
+use crate::capabilities::{Capabilities, CAPABILITIES_METHOD};
+use crate::clock_skew::ClockSkewTracker;
+use crate::config_reload::ConfigManager;
+use crate::emulation::{EmulatedModelEngine, EmulatedRouter};
+use crate::auth::Authenticator;
+use crate::bandwidth::BandwidthLedger;
+use crate::priority_latency::{PriorityLatencyReport, PriorityLatencyTracker};
+use crate::cluster::ClusterMembership;
+use crate::compliance::ComplianceReporter;
+use crate::connectors::OutputConnectors;
+use crate::maintenance::MaintenanceMode;
+use crate::middleware::RateLimitLayer;
 use crate::performance::{PerformanceManager, PerformanceConfig};
+use crate::erasure::{DataErasure, DEVICE_METADATA, SESSION_PARAM};
+use crate::extensions::Extensions;
+use crate::hedging::{HedgeOutcome, Hedger};
+use crate::idempotency::IdempotencyCache;
+use crate::high_availability::HighAvailability;
+use crate::kv::KvStore;
+use crate::admin_journal::AdminJournal;
+use crate::conversations::{self, ConversationPackage, ConversationStore, ImportOptions};
+use crate::normalization::TextNormalizer;
+use crate::sessions::SessionStore;
+use crate::peripherals::{Peripherals, CAMERA_CAPTURE_METHOD};
+use crate::power::PowerManager;
+use crate::probes::HealthProbe;
+use crate::provenance::{self, Origin, ResponseSigner, Served};
+use crate::retention::RetentionManager;
+use crate::scheduler::{InferenceScheduler, ModelQueueReport};
+use crate::storage_health::{MediumHealth, StorageHealthMonitor};
+use crate::synthetic::SyntheticProbes;
+use crate::timeline::IncidentTimeline;
+use crate::webhooks::{RequestSummary, WebhookSink};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+/// Response future of a request the gateway sends itself while processing another
+type BoxedResponse<'a> = std::pin::Pin<Box<dyn std::future::Future<Output = Result<MCPResponse>> + Send + 'a>>;
+
+/// Components reported on the event bus as the gateway starts and stops
+const COMPONENTS: &[&str] = &["router", "model_engine", "queue", "security", "telemetry", "pipeline_guard"];
+
+/// Residency label for cloud fallbacks, whose endpoint the router picks
+const CLOUD_FALLBACK_DESTINATION: &str = "cloud-fallback";
+
 /// Main gateway component that orchestrates all other components
 pub struct Gateway {
     config: Arc<Config>,
@@ -24,7 +88,54 @@ pub struct Gateway {
     security: Arc<dyn SecurityManager + Send + Sync>,
     telemetry: Arc<dyn TelemetryCollector + Send + Sync>,
     pipeline_guard: Arc<PipelineGuard>,
+    /// Golden prompt evaluation of loaded local models
+    model_canary: Arc<ModelCanary>,
     performance: Arc<RwLock<PerformanceManager>>,
+    /// Response cache, dedup window and limits shared across clustered gateways
+    shared_state: Arc<dyn SharedState>,
+    cache_ttl: Duration,
+    cluster: Arc<ClusterMembership>,
+    maintenance: Arc<MaintenanceMode>,
+    clock_skew: Arc<ClockSkewTracker>,
+    priority_latency: PriorityLatencyTracker,
+    /// Embedder-supplied ranking of requests, if any
+    prioritizer: Option<Arc<dyn RequestPrioritizer>>,
+    idempotency: IdempotencyCache,
+    response_signer: ResponseSigner,
+    retriever: Arc<HybridRetriever>,
+    ingestion: Arc<IngestionPipeline>,
+    index_maintainer: Arc<IndexMaintainer>,
+    webhooks: Arc<WebhookSink>,
+    connectors: Arc<OutputConnectors>,
+    artifacts: Option<Arc<ArtifactUploader>>,
+    compliance: Arc<ComplianceReporter>,
+    retention: Arc<RetentionManager>,
+    power: Arc<PowerManager>,
+    bandwidth: Arc<BandwidthLedger>,
+    timeline: Arc<IncidentTimeline>,
+    synthetic_probes: Arc<SyntheticProbes>,
+    peripherals: Arc<Peripherals>,
+    audio: Arc<AudioPipeline>,
+    audit: Option<Arc<AuditSink>>,
+    extensions: Arc<Extensions>,
+    kv: Arc<KvStore>,
+    conversations: Arc<ConversationStore>,
+    admin_journal: AdminJournal,
+    sessions: Arc<SessionStore>,
+    normalizer: TextNormalizer,
+    moderator: OutputModerator,
+    high_availability: Arc<HighAvailability>,
+    config_manager: Arc<ConfigManager>,
+    storage_health: Arc<StorageHealthMonitor>,
+    scheduler: Arc<InferenceScheduler>,
+    hedger: Arc<Hedger>,
+    rollouts: Arc<ModelRollouts>,
+    admission: Arc<AdmissionController>,
+    erasure: Arc<DataErasure>,
+    health_probe: Arc<HealthProbe>,
+    authenticator: Arc<Authenticator>,
+    rate_limit: RateLimitLayer,
+    clock: Arc<dyn Clock>,
     state: Arc<RwLock<GatewayState>>,
 }
 
@@ -41,32 +152,181 @@ pub struct GatewayState {
 impl Gateway {
     /// Create a new gateway instance
     pub async fn new(config: Config) -> Result<Self> {
+        GatewayBuilder::new(config).build().await
+    }
+
+    /// Start building a gateway with custom components
+    pub fn builder(config: Config) -> GatewayBuilder {
+        GatewayBuilder::new(config)
+    }
+
+    pub(crate) async fn from_builder(builder: GatewayBuilder) -> Result<Self> {
         info!("Initializing MCP Gateway");
 
-        let config = Arc::new(config);
+        builder.config.validate()?;
+        crypto::self_test()?;
+        info!("Crypto backend: {} (FIPS mode: {})", crypto::backend(), crypto::FIPS_MODE);
+        redaction::install(&builder.config.redaction);
+        trace_context::configure(&builder.config.telemetry.tracing);
+        request_id::configure(&builder.config.gateway.request_ids);
+        let config = Arc::new(builder.config);
+        let clock = builder.clock.unwrap_or_else(clock::system_clock);
 
-        // Initialize components
-        let router = mcp_router::create_router(config.clone()).await?;
-        let model_engine = mcp_models::create_model_engine(config.clone()).await?;
-        let queue = mcp_queue::create_offline_queue(config.clone()).await?;
-        let security = mcp_security::create_security_manager(config.clone()).await?;
-        let telemetry = mcp_telemetry::create_telemetry_collector(config.clone()).await?;
+        // Initialize components, preferring any supplied by the builder
+        let emulation = &config.gateway.emulation;
+        let router = match (builder.router, builder.cloud_transport) {
+            (Some(router), _) => router,
+            (None, _) if emulation.enabled => Arc::new(EmulatedRouter::new(emulation.clone())),
+            (None, Some(transport)) => mcp_router::create_router_with_transport(config.clone(), transport).await?,
+            (None, None) => mcp_router::create_router(config.clone()).await?,
+        };
+        let model_engine = match builder.model_engine {
+            Some(model_engine) => model_engine,
+            None if emulation.enabled => Arc::new(EmulatedModelEngine::new(emulation.clone())?),
+            None => mcp_models::create_model_engine(config.clone()).await?,
+        };
+        let queue = match builder.queue {
+            Some(queue) => queue,
+            None => mcp_queue::create_offline_queue(config.clone()).await?,
+        };
+        let security = match builder.security {
+            Some(security) => security,
+            None => mcp_security::create_security_manager(config.clone()).await?,
+        };
+        let signer = security
+            .attestation()
+            .filter(|_| config.security.attestation.sign_cloud_requests)
+            .map(|attestation| attestation.signer());
+        request_signing::install(signer);
+        let telemetry = match builder.telemetry {
+            Some(telemetry) => telemetry,
+            None => mcp_telemetry::create_telemetry_collector(config.clone()).await?,
+        };
         let pipeline_guard = Arc::new(mcp_pipeline_guard::create_pipeline_guard((*config).clone()).await?);
+        let model_canary = Arc::new(ModelCanary::new(
+            config.pipeline_guard.canary.clone(),
+            Arc::new(EngineCanaryTarget(model_engine.clone())),
+        ));
+        model_canary.start();
 
         // Initialize performance management
         let perf_config = PerformanceConfig::default();
+        let cache_ttl = Duration::from_secs(perf_config.cache_ttl_seconds);
+        let shared_state = mcp_common::create_shared_state(&config.cluster);
+        let cluster = Arc::new(ClusterMembership::new(config.cluster.routing.clone()));
         let mut performance_manager = PerformanceManager::new(perf_config);
         performance_manager.start_monitoring().await;
         let performance = Arc::new(RwLock::new(performance_manager));
 
+        let maintenance = Arc::new(MaintenanceMode::with_clock(config.gateway.maintenance.clone(), clock.clone()));
+        let clock_skew = Arc::new(ClockSkewTracker::with_clock(config.gateway.clock_skew.clone(), clock.clone()));
+        let retriever = Arc::new(HybridRetriever::new(&config.models.retrieval));
+        let storage = mcp_common::create_vfs(&config.storage);
+        let index_maintainer = Arc::new(IndexMaintainer::new(
+            config.models.retrieval.maintenance.clone(),
+            retriever.clone(),
+            storage.clone(),
+            config.models.retrieval.index_path.clone(),
+        ));
+        index_maintainer.start().await;
+        let ingestion = Arc::new(IngestionPipeline::new(
+            config.models.retrieval.ingestion.clone(),
+            retriever.clone(),
+            storage.clone(),
+        ));
+        ingestion.start().await;
+        let webhooks = Arc::new(WebhookSink::new(&config.outputs.webhooks));
+        let connectors = Arc::new(OutputConnectors::new(&config.outputs.connectors)?);
+        connectors.start_telemetry(telemetry.clone());
+        let artifacts = config
+            .outputs
+            .artifacts
+            .clone()
+            .map(|artifacts| Arc::new(ArtifactUploader::new(artifacts, storage.clone())));
+        let compliance = Arc::new(ComplianceReporter::new(config.compliance.clone(), storage.clone()));
+        let retention = Arc::new(RetentionManager::with_clock(
+            config.retention.clone(),
+            queue.clone(),
+            storage.clone(),
+            clock.clone(),
+        ));
+        retention.start();
+        let power = Arc::new(PowerManager::new(&config, model_engine.clone(), queue.clone()));
+        power.start();
+        let bandwidth = Arc::new(BandwidthLedger::new(config.gateway.bandwidth.clone(), storage.clone()));
+        bandwidth.restore().await;
+        bandwidth.start();
+        let timeline = Arc::new(IncidentTimeline::new(config.telemetry.timeline.clone(), storage.clone()));
+        timeline.restore().await;
+        timeline.start();
+        let synthetic_probes = Arc::new(SyntheticProbes::new(config.gateway.synthetic_probes.clone()));
+        let peripherals = Arc::new(Peripherals::new(config.peripherals.clone()));
+        let audio = Arc::new(AudioPipeline::new(config.peripherals.clone()));
+        let audit = if config.audit.enabled {
+            Some(Arc::new(AuditSink::open(config.audit.clone(), storage.clone()).await?))
+        } else {
+            None
+        };
+        let rollouts = Arc::new(ModelRollouts::new(&config.router.rollouts));
+        let admission = Arc::new(AdmissionController::new(&config.platform));
+        let kv = Arc::new(KvStore::with_clock(config.kv.clone(), storage.clone(), clock.clone()));
+        kv.restore().await;
+        kv.start();
+        let extensions = Arc::new(Extensions::load(&config, &kv)?);
+        let conversations = Arc::new(ConversationStore::with_clock(&config, storage.clone(), clock.clone()));
+        let admin_journal = AdminJournal::new(
+            config.gateway.admin_safety.clone(),
+            kv.clone(),
+            conversations.clone(),
+            clock.clone(),
+        );
+        let sessions = Arc::new(SessionStore::with_clock(&config, storage.as_ref(), clock.clone())?);
+        let normalizer = TextNormalizer::new(&config.normalization);
+        let moderator = OutputModerator::new(&config.security.output_moderation)?;
+        let high_availability = Arc::new(HighAvailability::new(
+            config.cluster.high_availability.clone(),
+            conversations.clone(),
+            queue.clone(),
+            security.clone(),
+        ));
+        high_availability.start();
+        let config_manager = Arc::new(ConfigManager::new(config.as_ref().clone()));
+        let storage_health = Arc::new(StorageHealthMonitor::new(config.storage.health.clone()));
+        let scheduler = Arc::new(InferenceScheduler::new(config.concurrency.per_model.clone()));
+        let hedger = Arc::new(Hedger::new(config.router.hedging.clone()));
+        let health_probe = Arc::new(HealthProbe::new(config.gateway.health_checks.clone()));
+        let authenticator = Arc::new(Authenticator::new(config.security.authentication.clone(), clock.clone()));
+        let rate_limits = &config.security.rate_limits;
+        let rate_limit = RateLimitLayer::new(rate_limits.client_requests_per_window, rate_limits.client_window_secs);
+        let idempotency = IdempotencyCache::with_clock(config.gateway.idempotency.clone(), clock.clone());
+        let response_signer = ResponseSigner::new(
+            config.gateway.response_provenance.clone(),
+            security.attestation().map(|attestation| attestation.signer()),
+            cluster.node_id(),
+        );
+        let erasure = Arc::new(DataErasure::new(
+            config.retention.clone(),
+            queue.clone(),
+            storage,
+            retriever.clone(),
+            index_maintainer.clone(),
+            sessions.clone(),
+        ));
+
         let state = Arc::new(RwLock::new(GatewayState {
-            started_at: chrono::Utc::now(),
+            started_at: clock.now(),
             active_requests: 0,
             total_requests: 0,
-            last_health_check: chrono::Utc::now(),
+            last_health_check: clock.now(),
             is_healthy: true,
         }));
 
+        for component in COMPONENTS {
+            events::publish(GatewayEvent::ComponentStarted {
+                component: component.to_string(),
+            });
+        }
+
         info!("Gateway initialized successfully with performance optimization");
 
         Ok(Gateway {
@@ -77,24 +337,133 @@ impl Gateway {
             security,
             telemetry,
             pipeline_guard,
+            model_canary,
             performance,
+            shared_state,
+            cache_ttl,
+            cluster,
+            maintenance,
+            clock_skew,
+            priority_latency: PriorityLatencyTracker::new(),
+            prioritizer: builder.prioritizer,
+            idempotency,
+            response_signer,
+            retriever,
+            ingestion,
+            index_maintainer,
+            webhooks,
+            connectors,
+            artifacts,
+            compliance,
+            retention,
+            power,
+            bandwidth,
+            timeline,
+            synthetic_probes,
+            peripherals,
+            audio,
+            audit,
+            extensions,
+            kv,
+            conversations,
+            admin_journal,
+            sessions,
+            normalizer,
+            moderator,
+            high_availability,
+            config_manager,
+            storage_health,
+            scheduler,
+            hedger,
+            rollouts,
+            admission,
+            erasure,
+            health_probe,
+            authenticator,
+            rate_limit,
+            clock,
             state,
         })
     }
 
+    /// Process an MCP request; retries sharing its idempotency key get the
+    /// response of the first attempt instead of running it again
+    pub async fn process_request(&self, request: MCPRequest) -> Result<MCPResponse> {
+        self.idempotency
+            .run(request, |request| self.process_request_once(request))
+            .await
+    }
+
     /// Process an MCP request with performance optimization and caching
-    pub async fn process_request(&self, mut request: MCPRequest) -> Result<MCPResponse> {
+    async fn process_request_once(&self, mut request: MCPRequest) -> Result<MCPResponse> {
         let request_id = request.id;
         let start_time = Instant::now();
         debug!("Processing request {} with performance optimization", request_id);
 
+        // Move timestamps from devices with skewed clocks onto gateway time
+        let skew = self.clock_skew.observe(&request.device_id, request.timestamp).await;
+        if skew.significant {
+            debug!("Correcting timestamp of request {} by {}ms", request_id, -skew.skew_ms);
+            request.timestamp = skew.corrected;
+            self.telemetry.record_clock_skew(&request.device_id, skew.skew_ms).await;
+        }
+        self.prioritize(&mut request);
+
+        let mut span = Span::continue_or_start(request.trace(), "gateway.process_request", SpanKind::Internal);
+        if let Some(span) = span.as_mut() {
+            span.set_attribute("mcp.method", &request.method);
+            span.set_attribute("mcp.priority", request.priority().as_str());
+            request.set_trace(span.context());
+        }
+
+        // Reject retries of a request this or another gateway already accepted
+        let dedup_window = self.config.cluster.dedup_window_secs;
+        if dedup_window > 0 {
+            let key = format!("dedup:{}", request_id);
+            let window = Duration::from_secs(dedup_window);
+            if !self.shared_state.set_if_absent(&key, request.device_id.as_bytes(), window).await {
+                debug!("Rejecting duplicate request {}", request_id);
+                return Err(Error::InvalidRequest(format!(
+                    "Duplicate request {} within the {}s dedup window",
+                    request_id, dedup_window
+                )));
+            }
+        }
+
+        let audit_digest = self.audit.as_ref().map(|_| AuditDigest::for_request(&request));
+
+        // Probes must exercise the model, and stay out of usage and outputs
+        let probe = request.is_probe();
+
+        // Normalized text is what gets cached, recorded as history and routed
+        // by language
+        if !probe {
+            if let Some(language) = self.normalizer.apply(&mut request) {
+                if let Some(span) = span.as_mut() {
+                    span.set_attribute("mcp.language", language);
+                }
+            }
+        }
+
+        // Chats carry their session's history from here on, so it is part of
+        // the cache key and reaches whichever model serves the request
+        let session_turn = if probe { None } else { self.sessions.prepare(&mut request).await? };
+
         // Check cache first for GET-like operations
         let cache_key = self.generate_cache_key(&request);
-        if let Some(cached_response) = self.performance.read().await.get_cached_response(&cache_key).await {
+        let cached = if probe { None } else { self.cached_response(&cache_key).await };
+        if let Some(response) = cached {
             debug!("Cache hit for request {}", request_id);
-            if let Ok(response) = serde_json::from_value(cached_response) {
-                return Ok(response);
+            if let Some(turn) = session_turn {
+                self.sessions.record(turn, &response).await;
+            }
+            if let Some(span) = span.as_mut() {
+                span.set_attribute("gateway.cache_hit", true);
             }
+            let mut result = Ok(response);
+            self.finish_response(Served::with_origin(Origin::Cache), audit_digest, &mut result, start_time.elapsed())
+                .await;
+            return result;
         }
 
         // Update state
@@ -104,7 +473,35 @@ impl Gateway {
             state.total_requests += 1;
         }
 
-        let result = self.process_request_internal(request).await;
+        let method = request.method.clone();
+        let priority = request.priority();
+        let issued_at = request.timestamp;
+        let replayed = matches!(
+            request.context.as_ref().map(|context| &context.source),
+            Some(RequestSource::Queue)
+        );
+        let tenant = tenant_of(&request);
+        let summary = (!probe && (!self.webhooks.is_empty() || !self.connectors.is_empty()))
+            .then(|| RequestSummary::new(&request));
+        let turn = if probe { None } else { self.conversations.turn(&request) };
+        let budget = self.config.request_budget(&method);
+        let processing = stage_timings::measure(provenance::trace(async {
+            match tokio::time::timeout(budget, self.process_request_internal(request)).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::DeadlineExceeded(TimeoutDetails::new(
+                    TimeoutStage::Request,
+                    &method,
+                    budget,
+                ))),
+            }
+        }));
+        let (((mut result, served), mut timings), usage) = if self.config.gateway.resource_accounting.enabled {
+            let (result, usage) = usage::measure(processing).await;
+            (result, Some(usage))
+        } else {
+            (processing.await, None)
+        };
+        let post_processing = Instant::now();
 
         // Update state and performance metrics
         {
@@ -117,18 +514,36 @@ impl Gateway {
 
         // Record performance metrics
         self.performance.write().await.record_request(duration, success).await;
+        self.telemetry.record_request_latency(duration).await;
+        self.priority_latency.record(priority, issued_at, self.clock.now(), replayed);
 
         match &result {
             Ok(response) => {
                 debug!("Request {} completed successfully in {:?}", request_id, duration);
                 
                 // Cache successful responses for cacheable methods
-                if self.is_cacheable_method(&response) {
-                    if let Ok(cached_value) = serde_json::to_value(response) {
-                        self.performance.write().await.cache_response(cache_key, cached_value).await;
-                    }
+                if !probe && self.is_cacheable_method(&method, response) {
+                    self.cache_response(cache_key, response).await;
                 }
                 
+                if let Some(summary) = &summary {
+                    if !is_queued_response(response) {
+                        self.webhooks.dispatch(summary, response);
+                        self.connectors.publish_response(summary, response);
+                    }
+                }
+
+                if let Some(turn) = turn {
+                    if !is_queued_response(response) {
+                        self.conversations.record(turn, response).await;
+                    }
+                }
+                if let Some(turn) = session_turn {
+                    if !is_queued_response(response) {
+                        self.sessions.record(turn, response).await;
+                    }
+                }
+
                 self.telemetry
                     .record_request_success(request_id, response)
                     .await;
@@ -136,39 +551,555 @@ impl Gateway {
             Err(error) => {
                 error!("Request {} failed in {:?}: {}", request_id, duration, error);
                 self.telemetry.record_request_error(request_id, error).await;
+                if !probe {
+                    self.timeline.record_error(self.clock.now()).await;
+                }
             },
         }
 
+        if let Some(usage) = usage.filter(|_| !probe) {
+            self.record_usage(request_id, &tenant, &method, &usage, &mut result).await;
+        }
+        timings.add(Stage::PostProcessing, post_processing.elapsed());
+        timings.total_ms = start_time.elapsed().as_secs_f64() * 1000.0;
+        self.record_stage_timings(&timings, span.as_mut(), &mut result);
+        let moderation = self.moderator.take(request_id);
+        let audit_digest = audit_digest.map(|digest| digest.moderated(moderation));
+        if probe {
+            self.record_audit(audit_digest, &result, duration).await;
+        } else {
+            self.finish_response(served, audit_digest, &mut result, duration).await;
+        }
+        if let Some(span) = span.as_mut() {
+            span.record_result(&result);
+        }
+
         result
     }
 
-    async fn process_request_internal(&self, request: MCPRequest) -> Result<MCPResponse> {
-        // Security validation
+    /// Process a completion, streaming its tokens when a local model serves
+    /// it; every other outcome arrives as a single final chunk
+    pub async fn process_request_streaming(self: &Arc<Self>, mut request: MCPRequest) -> Result<TokenStream> {
+        let buffer_chunks = self.config.models.streaming.buffer_chunks;
+        self.prioritize(&mut request);
+
+        // Maintenance parking, admission under pressure, verification fallback,
+        // output moderation, idempotent retries and other methods need the
+        // complete response, so they take the regular path
+        if request.method != STREAMING_METHOD
+            || request.idempotency_key().is_some()
+            || self.config.models.verification.enabled
+            || self.moderator.enabled()
+            || self.maintenance.status().await.active
+            || self.admission.pressure_for(&request).is_some()
+        {
+            return Ok(buffered_stream(self.process_request(request).await?, buffer_chunks));
+        }
+
+        // Covers setting the stream up; tokens are produced after it ends
+        let mut span = Span::continue_or_start(request.trace(), "gateway.process_request_streaming", SpanKind::Internal);
+        if let Some(span) = &span {
+            request.set_trace(span.context());
+        }
+
+        self.state.write().await.total_requests += 1;
+        let audit_digest = self.audit.as_ref().map(|_| AuditDigest::for_request(&request));
+
+        // Streams are routed on the same normalized text as buffered requests
+        let probe = request.is_probe();
+        if !probe {
+            if let Some(language) = self.normalizer.apply(&mut request) {
+                if let Some(span) = span.as_mut() {
+                    span.set_attribute("mcp.language", language);
+                }
+            }
+        }
+
+        let request_id = request.id;
+        let method = request.method.clone();
+        let tenant = tenant_of(&request);
+        let started = Instant::now();
+        let opening = async { (self.open_stream(request, buffer_chunks).await, usage::deferred()) };
+        let ((opened, deferred_usage), usage) = if self.config.gateway.resource_accounting.enabled && !probe {
+            let (opened, usage) = usage::measure(opening).await;
+            (opened, Some(usage))
+        } else {
+            (opening.await, None)
+        };
+        match opened {
+            Ok((stream, served)) => {
+                let pending = PendingStream {
+                    request_id,
+                    method,
+                    tenant,
+                    served,
+                    audit_digest,
+                    usage: usage.map(|usage| (usage, deferred_usage)),
+                    started,
+                };
+                Ok(self.finish_stream(stream, pending, buffer_chunks))
+            },
+            Err(e) => {
+                let mut result = Err(e);
+                if let Some(usage) = usage {
+                    self.record_usage(request_id, &tenant, &method, &usage, &mut result).await;
+                }
+                self.record_audit(audit_digest, &result, started.elapsed()).await;
+                result.map(|response| buffered_stream(response, buffer_chunks))
+            },
+        }
+    }
+
+    /// Start streaming a validated request, with where it is served
+    async fn open_stream(&self, request: MCPRequest, buffer_chunks: usize) -> Result<(TokenStream, Served)> {
+        let request = self.extensions.transform(request).await?;
         self.security.validate_request(&request).await?;
+        match self.route(&request).await? {
+            mcp_common::RoutingDecision::Local { model_id, .. } => {
+                let slot = self.scheduler.admit(&model_id, &request).await?;
+                self.compliance.record_on_device();
+                let local = self.model_engine.process_request_streaming(&request, &model_id).await?;
+                let local = match slot {
+                    Some(slot) => slot.hold_for(local, buffer_chunks),
+                    None => local,
+                };
+                let served = Served::local(&model_id);
+                match self.config.models.streaming.splice.get(&request.method) {
+                    Some(splice) if may_leave_device(&request) && !self.config.router.cloud_endpoints.is_empty() => {
+                        debug!("Splicing stream of request {} with the cloud", request.id);
+                        self.compliance.record_cloud(CLOUD_FALLBACK_DESTINATION);
+                        let router = self.router.clone();
+                        let cloud = async move { router.fallback_to_cloud(&request).await };
+                        let budget = Duration::from_millis(splice.cloud_budget_ms);
+                        Ok((splice_stream(local, cloud, budget, buffer_chunks), served))
+                    },
+                    _ => Ok((local, served)),
+                }
+            },
+            routing_decision => {
+                let (response, served) = provenance::trace(self.dispatch(request, routing_decision)).await;
+                Ok((buffered_stream(response?, buffer_chunks), served))
+            },
+        }
+    }
+
+    /// Relay a stream, putting its final response through
+    /// [`Self::finish_response`] before the reader sees it, with its usage
+    /// recorded. A stream that ends without one, because it was cancelled,
+    /// is accounted and audited as failed
+    fn finish_stream(self: &Arc<Self>, mut stream: TokenStream, pending: PendingStream, buffer_chunks: usize) -> TokenStream {
+        if pending.audit_digest.is_none() && pending.usage.is_none() && !self.response_signer.enabled() {
+            return stream;
+        }
+        let (sender, relayed) = token_stream(buffer_chunks);
+        let gateway = Arc::clone(self);
+        tokio::spawn(async move {
+            let PendingStream {
+                request_id,
+                method,
+                tenant,
+                served,
+                mut audit_digest,
+                mut usage,
+                started,
+            } = pending;
+            let mut served = Some(served);
+            while let Some(chunk) = stream.recv().await {
+                let mut result = match chunk {
+                    Ok(StreamChunk::Done(response)) => Ok(response),
+                    Err(e) => Err(e),
+                    token => {
+                        if sender.send(token).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    },
+                };
+                if let Some(served) = served.take() {
+                    if let Some(usage) = usage.take() {
+                        gateway.record_stream_usage(request_id, &tenant, &method, usage, &mut result).await;
+                    }
+                    // Spliced streams end with the cloud's answer
+                    let spliced = matches!(&result, Ok(response) if is_spliced(response));
+                    let served = if spliced { Served::cloud(CLOUD_FALLBACK_DESTINATION) } else { served };
+                    gateway.finish_response(served, audit_digest.take(), &mut result, started.elapsed()).await;
+                }
+                if sender.send(result.map(StreamChunk::Done)).await.is_err() {
+                    break;
+                }
+            }
+            let mut cancelled = Err(Error::Model("Stream ended before its final response".to_string()));
+            if let Some(usage) = usage {
+                gateway.record_stream_usage(request_id, &tenant, &method, usage, &mut cancelled).await;
+            }
+            gateway.record_audit(audit_digest, &cancelled, started.elapsed()).await;
+        });
+        relayed
+    }
+
+    /// Record the usage of setting a stream up together with that of its
+    /// generation
+    async fn record_stream_usage(
+        &self,
+        request_id: Uuid,
+        tenant: &str,
+        method: &str,
+        (mut usage, generation): (ResourceUsage, Option<DeferredUsage>),
+        result: &mut Result<MCPResponse>,
+    ) {
+        if let Some(generation) = generation {
+            usage.add(&generation.total());
+        }
+        self.record_usage(request_id, tenant, method, &usage, result).await;
+    }
+
+    /// Steps every finished request goes through, buffered or streamed:
+    /// provenance is attached to its response and its outcome is audited
+    async fn finish_response(
+        &self,
+        served: Served,
+        audit_digest: Option<AuditDigest>,
+        result: &mut Result<MCPResponse>,
+        latency: Duration,
+    ) {
+        self.attach_provenance(served, result).await;
+        self.record_audit(audit_digest, result, latency).await;
+    }
+
+    /// Append a request's outcome to the local audit trail
+    async fn record_audit(&self, digest: Option<AuditDigest>, result: &Result<MCPResponse>, latency: Duration) {
+        let (Some(audit), Some(digest)) = (&self.audit, digest) else {
+            return;
+        };
+        let request_id = digest.request_id;
+        if let Err(e) = audit.record(&digest.complete(result, latency)).await {
+            error!("Failed to write audit record for request {}: {}", request_id, e);
+        }
+    }
+
+    /// Report a request's resource usage, flagging requests over the thresholds
+    async fn record_usage(
+        &self,
+        request_id: Uuid,
+        tenant: &str,
+        method: &str,
+        usage: &ResourceUsage,
+        result: &mut Result<MCPResponse>,
+    ) {
+        let config = &self.config.gateway.resource_accounting;
+        let pathological = usage.cpu_time_us > config.pathological_cpu_ms.saturating_mul(1000)
+            || usage.peak_memory_delta_bytes > config.pathological_memory_bytes;
+        if pathological {
+            warn!(
+                "Request {} ({} for tenant {}) used {}ms CPU and {} bytes of memory",
+                request_id,
+                method,
+                tenant,
+                usage.cpu_time_us / 1000,
+                usage.peak_memory_delta_bytes
+            );
+            events::publish(GatewayEvent::AlertRaised {
+                source: "resource_accounting".to_string(),
+                severity: AlertSeverity::Warning,
+                message: format!(
+                    "Request {} ({}) from tenant {} exceeded resource thresholds",
+                    request_id, method, tenant
+                ),
+            });
+        }
+        self.telemetry.record_request_usage(request_id, tenant, usage, pathological).await;
+
+        if config.include_in_response {
+            if let Ok(MCPResponse {
+                result: Some(serde_json::Value::Object(fields)),
+                ..
+            }) = result
+            {
+                if let Ok(usage) = serde_json::to_value(usage) {
+                    fields.insert("resource_usage".to_string(), usage);
+                }
+            }
+        }
+    }
 
-        // Route the request
-        let routing_decision = self.router.route(&request).await?;
+    /// Record where a request's time went on its span, and on the response
+    /// when configured
+    fn record_stage_timings(&self, timings: &StageTimings, span: Option<&mut Span>, result: &mut Result<MCPResponse>) {
+        if let Some(span) = span {
+            timings.set_span_attributes(span);
+        }
+        if self.config.gateway.stage_timings.include_in_response {
+            if let Ok(MCPResponse {
+                result: Some(serde_json::Value::Object(fields)),
+                ..
+            }) = result
+            {
+                if let Ok(timings) = serde_json::to_value(timings) {
+                    fields.insert(STAGE_TIMINGS_FIELD.to_string(), timings);
+                }
+            }
+        }
+    }
+
+    /// Attach signed provenance to a response when configured
+    async fn attach_provenance(&self, served: Served, result: &mut Result<MCPResponse>) {
+        let Ok(response) = result else {
+            return;
+        };
+        if !self.response_signer.enabled() {
+            return;
+        }
+        let model_sha256 = match &served.model_id {
+            Some(model_id) => self
+                .model_engine
+                .model_provenance()
+                .await
+                .into_iter()
+                .find(|provenance| &provenance.model_id == model_id)
+                .and_then(|provenance| provenance.sha256),
+            None => None,
+        };
+        if let Err(e) = self.response_signer.attach(response, served, model_sha256, self.clock.now()).await {
+            warn!("Failed to attach provenance to response {}: {}", response.id, e);
+        }
+    }
+
+    /// Resource usage accumulated per tenant
+    pub async fn tenant_usage(&self) -> HashMap<String, TenantUsage> {
+        self.telemetry.usage_by_tenant().await
+    }
+
+    async fn process_request_internal(&self, request: MCPRequest) -> Result<MCPResponse> {
+        let request = stage_timings::timed(Stage::Validation, async {
+            // Extensions may rewrite or reject the request before it is validated
+            let request = self.extensions.transform(request).await?;
+
+            // Security validation
+            self.security.validate_request(&request).await?;
+            Ok::<_, Error>(request)
+        })
+        .await?;
+
+        // Capability negotiation is answered by the gateway itself
+        if request.method == CAPABILITIES_METHOD {
+            return Ok(MCPResponse {
+                id: request.id,
+                result: Some(serde_json::to_value(self.capabilities())?),
+                error: None,
+                timestamp: chrono::Utc::now(),
+            });
+        }
+
+        // Loaded models and their provenance are listed by the gateway itself
+        if request.method == LIST_MODELS_METHOD {
+            return Ok(MCPResponse {
+                id: request.id,
+                result: Some(serde_json::json!({ "models": self.model_engine.list_models().await? })),
+                error: None,
+                timestamp: chrono::Utc::now(),
+            });
+        }
+
+        // Conversations are exported and imported by the gateway itself
+        if self.conversations.enabled()
+            && (request.method == conversations::EXPORT_METHOD || request.method == conversations::IMPORT_METHOD)
+        {
+            return self.process_conversation(&request).await;
+        }
+
+        // Frames are captured on demand by the gateway itself and sent back
+        // through it as the camera's method
+        if request.method == CAMERA_CAPTURE_METHOD && self.peripherals.enabled() {
+            let camera = request
+                .params
+                .get("camera")
+                .and_then(|value| value.as_str())
+                .ok_or_else(|| Error::InvalidRequest(format!("{} requires a camera", request.method)))?;
+            let result = self
+                .peripherals
+                .capture(camera, |frame| self.process_frame(frame))
+                .await?;
+            return Ok(MCPResponse {
+                id: request.id,
+                result: Some(serde_json::to_value(result)?),
+                error: None,
+                timestamp: chrono::Utc::now(),
+            });
+        }
+
+        // Park non allow-listed requests while in maintenance mode
+        if let Some(banner) = self.maintenance.intercept(&request.method).await {
+            let request_id = request.id;
+            self.enqueue(request, "maintenance").await?;
+            self.compliance.record_queued();
+            return Ok(MCPResponse {
+                id: request_id,
+                result: Some(serde_json::json!({
+                    "status": "queued",
+                    "reason": "maintenance",
+                    "maintenance": {
+                        "message": banner,
+                        "expires_at": self.maintenance.status().await.expires_at,
+                    }
+                })),
+                error: None,
+                timestamp: chrono::Utc::now(),
+            });
+        }
 
-        // Process based on routing decision
-        let response = match routing_decision {
+        // Hold back low-priority requests while memory is short or the SoC overheats
+        match self.admission.admit(&request) {
+            Admission::Admit => {},
+            Admission::Queue(pressure) => {
+                let request_id = request.id;
+                self.enqueue(request, "admission").await?;
+                self.compliance.record_queued();
+                return Ok(MCPResponse {
+                    id: request_id,
+                    result: Some(serde_json::json!({
+                        "status": "queued",
+                        "reason": "admission",
+                        "pressure": pressure,
+                    })),
+                    error: None,
+                    timestamp: chrono::Utc::now(),
+                });
+            },
+            Admission::Shed(pressure) => {
+                return Err(Error::ResourceExhausted(format!("Request {} not admitted: {}", request.id, pressure)));
+            },
+        }
+
+        // Retrieval is served from the local store, with cloud search as fallback
+        if self.retriever.enabled()
+            && (request.method == RETRIEVAL_SEARCH_METHOD || request.method == RETRIEVAL_INDEX_METHOD)
+        {
+            self.compliance.record_on_device();
+            return self.process_retrieval(&request).await;
+        }
+
+        // Methods added by extensions are answered by them
+        if let Some(response) = self.extensions.handle(&request).await? {
+            return Ok(response);
+        }
+
+        let routing_decision = stage_timings::timed(Stage::Routing, self.route(&request)).await?;
+        self.dispatch(request, routing_decision).await
+    }
+
+    /// Give a request the priority and weight the embedder's prioritizer
+    /// picks for it
+    fn prioritize(&self, request: &mut MCPRequest) {
+        let Some(prioritizer) = self.prioritizer.as_ref() else {
+            return;
+        };
+        let (priority, weight) = prioritizer.prioritize(request.context.get_or_insert_with(Default::default));
+        if priority != request.priority() {
+            debug!("Prioritizer moved request {} from {:?} to {:?}", request.id, request.priority(), priority);
+        }
+        request.set_priority(priority);
+        request.set_weight(weight);
+    }
+
+    /// Route a request, letting routing policy extensions decide first, and
+    /// send local requests to the model version their rollout picks unless
+    /// that model failed its canary prompts
+    async fn route(&self, request: &MCPRequest) -> Result<mcp_common::RoutingDecision> {
+        let routing_decision = match self.extensions.route(request).await? {
+            Some(routing_decision) => routing_decision,
+            None => self.router.route(request).await?,
+        };
+        Ok(self.route_around_canary_failures(request, self.rollouts.apply(request, routing_decision)))
+    }
+
+    /// Send requests for a model that failed its canary prompts to the
+    /// cloud, or queue them when they may not leave the device
+    fn route_around_canary_failures(
+        &self,
+        request: &MCPRequest,
+        routing_decision: mcp_common::RoutingDecision,
+    ) -> mcp_common::RoutingDecision {
+        let mcp_common::RoutingDecision::Local { model_id, .. } = &routing_decision else {
+            return routing_decision;
+        };
+        if !self.model_canary.is_unhealthy(model_id) {
+            return routing_decision;
+        }
+        let endpoint = self.config.router.cloud_endpoints.first().filter(|_| may_leave_device(request));
+        match endpoint {
+            Some(endpoint) => {
+                debug!("Routing request {} around model {}, which failed its canary prompts", request.id, model_id);
+                mcp_common::RoutingDecision::Cloud {
+                    endpoint: endpoint.url.clone(),
+                    estimated_latency_ms: 0,
+                }
+            },
+            None => mcp_common::RoutingDecision::Queue {
+                reason: format!("model {} failed its canary prompts", model_id),
+                retry_after_ms: 30_000,
+            },
+        }
+    }
+
+    /// Queue a request for later. Synthetic probes fail instead, as a probe
+    /// that cannot be served now has found a problem and replaying it later
+    /// proves nothing.
+    async fn enqueue(&self, request: MCPRequest, reason: &str) -> Result<()> {
+        if request.is_probe() {
+            return Err(Error::Routing(format!("Synthetic probe would have been queued: {}", reason)));
+        }
+        self.high_availability.track_queued(&request);
+        self.queue.enqueue_request(request).await?;
+        Ok(())
+    }
+
+    /// Process a request based on its routing decision
+    async fn dispatch(&self, request: MCPRequest, routing_decision: mcp_common::RoutingDecision) -> Result<MCPResponse> {
+        let moderated = match &routing_decision {
+            mcp_common::RoutingDecision::Local { .. } => self.moderator.enabled(),
+            mcp_common::RoutingDecision::Cloud { .. } => self.moderator.moderates_cloud(),
+            mcp_common::RoutingDecision::Queue { .. } => false,
+        };
+        let mut response = match routing_decision {
             mcp_common::RoutingDecision::Local {
                 model_id,
                 ..
-            } => {
-                self.model_engine
-                    .process_request(&request, &model_id)
-                    .await?
+            } if self.hedges(&request) => self.process_hedged(&request, &model_id).await?,
+            mcp_common::RoutingDecision::Local {
+                model_id,
+                ..
+            } => match self.process_local(&request, &model_id).await {
+                Err(Error::VerificationFailed(reason))
+                    if self.config.models.verification.on_failure == VerificationFailureAction::CloudFallback =>
+                {
+                    info!("Local response for request {} failed verification ({}), falling back to cloud", request.id, reason);
+                    self.compliance.record_cloud(CLOUD_FALLBACK_DESTINATION);
+                    provenance::record(Served::cloud(CLOUD_FALLBACK_DESTINATION));
+                    stage_timings::timed(Stage::Inference, self.router.fallback_to_cloud(&request)).await?
+                },
+                result => {
+                    self.compliance.record_on_device();
+                    provenance::record(Served::local(&model_id));
+                    result?
+                },
             },
             mcp_common::RoutingDecision::Cloud {
                 endpoint,
                 ..
-            } => self.router.forward_to_cloud(&request, &endpoint).await?,
+            } => {
+                self.compliance.record_cloud(&endpoint);
+                provenance::record(Served::cloud(&endpoint));
+                let forwarding = self.router.forward_to_cloud(&request, &endpoint);
+                stage_timings::timed(Stage::Inference, forwarding).await?
+            },
             mcp_common::RoutingDecision::Queue {
                 reason,
                 ..
             } => {
                 let request_id = request.id;
-                self.queue.enqueue_request(request).await?;
+                self.enqueue(request, &reason).await?;
+                self.compliance.record_queued();
+                provenance::record(Served::with_origin(Origin::Queued));
                 MCPResponse {
                     id: request_id,
                     result: Some(serde_json::json!({
@@ -181,25 +1112,133 @@ impl Gateway {
             },
         };
 
+        // Generated text passes the output policy before it is returned
+        if moderated {
+            self.moderator.moderate(&mut response)?;
+        }
         Ok(response)
     }
 
+    /// Run a local inference once the model's scheduler admits it
+    async fn process_local(&self, request: &MCPRequest, model_id: &ModelId) -> Result<MCPResponse> {
+        let _slot = self.scheduler.admit(model_id, request).await?;
+        self.model_engine.process_request(request, model_id).await
+    }
+
+    /// Whether a local inference for `request` may be raced against the cloud
+    fn hedges(&self, request: &MCPRequest) -> bool {
+        self.hedger.applies_to(&request.method)
+            && may_leave_device(request)
+            && !self.config.router.cloud_endpoints.is_empty()
+    }
+
+    /// Run a local inference, racing it against the cloud once it is slow to
+    /// produce its first token
+    async fn process_hedged(&self, request: &MCPRequest, model_id: &ModelId) -> Result<MCPResponse> {
+        let _slot = self.scheduler.admit(model_id, request).await?;
+        let local = self.model_engine.process_request_streaming(request, model_id).await?;
+        let (result, outcome) = self.hedger.race(local, self.router.fallback_to_cloud(request)).await;
+        // The request reached the cloud whenever it was hedged, whichever
+        // answer was used
+        if outcome.hedged() {
+            debug!("Hedged request {} answered as {}", request.id, outcome.as_str());
+            self.compliance.record_cloud(CLOUD_FALLBACK_DESTINATION);
+        }
+        if outcome != HedgeOutcome::CloudWon {
+            self.compliance.record_on_device();
+            provenance::record(Served::local(model_id));
+        } else {
+            provenance::record(Served::cloud(CLOUD_FALLBACK_DESTINATION));
+        }
+        result
+    }
+
+    async fn process_retrieval(&self, request: &MCPRequest) -> Result<MCPResponse> {
+        let result = if request.method == RETRIEVAL_INDEX_METHOD {
+            let mut documents: Vec<Document> = request
+                .params
+                .get("documents")
+                .cloned()
+                .map(serde_json::from_value)
+                .transpose()?
+                .ok_or_else(|| Error::InvalidRequest("Missing 'documents' parameter".to_string()))?;
+            // Record the owner so the documents can be found by an erasure request
+            for document in &mut documents {
+                document
+                    .metadata
+                    .entry(DEVICE_METADATA.to_string())
+                    .or_insert_with(|| serde_json::Value::String(request.device_id.clone()));
+            }
+            let indexed = self.retriever.index(documents).await;
+            serde_json::json!({
+                "indexed": indexed,
+                "total": self.retriever.len().await,
+            })
+        } else {
+            let query = request
+                .params
+                .get("query")
+                .and_then(|value| value.as_str())
+                .ok_or_else(|| Error::InvalidRequest("Missing 'query' parameter".to_string()))?;
+            let top_k = request
+                .params
+                .get("top_k")
+                .and_then(|value| value.as_u64())
+                .map(|top_k| top_k as usize);
+            serde_json::to_value(self.retriever.search(query, top_k).await?)?
+        };
+
+        Ok(MCPResponse {
+            id: request.id,
+            result: Some(result),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
     /// Generate cache key for request
     fn generate_cache_key(&self, request: &MCPRequest) -> String {
         // Create a deterministic cache key based on method and params
         let params_hash = if request.params.is_empty() {
             "empty".to_string()
         } else {
-            // Create a simple hash of parameters for caching
-            format!("{:?}", request.params)
+            // Sort params so every gateway in a cluster derives the same key
+            let params: BTreeMap<_, _> = request.params.iter().collect();
+            serde_json::to_string(&params).unwrap_or_else(|_| format!("{:?}", params))
         };
         format!("{}:{}", request.method, params_hash)
     }
 
+    /// Look up a cached response, in shared state when clustered
+    async fn cached_response(&self, key: &str) -> Option<MCPResponse> {
+        if self.config.cluster.redis.is_some() {
+            let cached = self.shared_state.get(&format!("cache:{}", key)).await?;
+            return serde_json::from_slice(&cached).ok();
+        }
+        let cached = self.performance.read().await.get_cached_response(key).await?;
+        serde_json::from_value(cached).ok()
+    }
+
+    /// Cache a response, in shared state when clustered
+    async fn cache_response(&self, key: String, response: &MCPResponse) {
+        if self.config.cluster.redis.is_some() {
+            if let Ok(cached) = serde_json::to_vec(response) {
+                self.shared_state.set(&format!("cache:{}", key), &cached, self.cache_ttl).await;
+            }
+        } else if let Ok(cached) = serde_json::to_value(response) {
+            self.performance.write().await.cache_response(key, cached).await;
+        }
+    }
+
     /// Check if method/response is cacheable
-    fn is_cacheable_method(&self, response: &MCPResponse) -> bool {
+    fn is_cacheable_method(&self, method: &str, response: &MCPResponse) -> bool {
+        // Retrieval results change whenever documents are indexed
+        if method == RETRIEVAL_SEARCH_METHOD || method == RETRIEVAL_INDEX_METHOD {
+            return false;
+        }
+
         // Only cache successful responses for GET-like operations
-        response.error.is_none() && response.result.is_some()
+        response.error.is_none() && response.result.is_some() && !is_queued_response(response)
     }
 
     /// Get performance metrics
@@ -212,7 +1251,392 @@ impl Gateway {
         self.performance.read().await.should_scale_up().await
     }
 
-    /// Get gateway configuration
+    /// Capability document advertised to clients
+    pub fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::new(&self.config, self.router.available_models());
+        capabilities.methods.extend(self.extensions.methods());
+        capabilities
+    }
+
+    /// Get the hybrid retrieval store
+    pub fn retriever(&self) -> &HybridRetriever {
+        &self.retriever
+    }
+
+    /// Get the document ingestion pipeline
+    pub fn ingestion(&self) -> &IngestionPipeline {
+        &self.ingestion
+    }
+
+    /// Subscribe to offline queue events
+    pub fn subscribe_queue_events(&self) -> Result<mcp_common::EventSubscriber> {
+        self.queue.subscribe()
+    }
+
+    /// Offline queue backlog age, flow rates and estimated time to drain
+    pub async fn queue_analytics(&self) -> Result<Option<mcp_queue::QueueAnalytics>> {
+        self.queue.analytics().await
+    }
+
+    /// Requests the offline queue gave up on, most recent first
+    pub async fn dead_letters(&self) -> Result<Vec<mcp_queue::DeadLetter>> {
+        self.queue.dead_letters().await
+    }
+
+    /// Move a dead-lettered request back into the offline queue
+    pub async fn requeue_dead_letter(&self, request_id: Uuid) -> Result<bool> {
+        self.queue.requeue_dead_letter(request_id).await
+    }
+
+    /// Delete dead-lettered requests, all of them when `request_ids` is empty
+    pub async fn purge_dead_letters(&self, request_ids: &[Uuid]) -> Result<usize> {
+        self.queue.purge_dead_letters(request_ids).await
+    }
+
+    /// Get the completed-response webhook sink
+    pub fn webhooks(&self) -> &WebhookSink {
+        &self.webhooks
+    }
+
+    /// Get the model engine
+    pub fn model_engine(&self) -> &(dyn ModelEngine + Send + Sync) {
+        self.model_engine.as_ref()
+    }
+
+    /// Get the security manager
+    pub fn security(&self) -> &(dyn SecurityManager + Send + Sync) {
+        self.security.as_ref()
+    }
+
+    /// Get the message broker output connectors
+    pub fn connectors(&self) -> &OutputConnectors {
+        &self.connectors
+    }
+
+    /// Get the state shared with other gateways in the cluster
+    pub fn shared_state(&self) -> &dyn SharedState {
+        self.shared_state.as_ref()
+    }
+
+    /// Get device ownership across cluster members
+    pub fn cluster(&self) -> &ClusterMembership {
+        &self.cluster
+    }
+
+    /// Get the artifact uploader, if object storage is configured
+    pub fn artifacts(&self) -> Option<&ArtifactUploader> {
+        self.artifacts.as_deref()
+    }
+
+    /// Get the compliance reporter
+    pub fn compliance(&self) -> &ComplianceReporter {
+        &self.compliance
+    }
+
+    /// Get the retention manager
+    pub fn retention(&self) -> &RetentionManager {
+        &self.retention
+    }
+
+    /// Get the power manager
+    pub fn power(&self) -> &PowerManager {
+        &self.power
+    }
+
+    /// Get the bandwidth counter persistence
+    pub fn bandwidth(&self) -> &BandwidthLedger {
+        &self.bandwidth
+    }
+
+    /// Get the incident timeline
+    pub fn timeline(&self) -> &IncidentTimeline {
+        &self.timeline
+    }
+
+    /// Get the synthetic probes
+    pub fn synthetic_probes(&self) -> &SyntheticProbes {
+        &self.synthetic_probes
+    }
+
+    /// Start sending the configured synthetic probes through this gateway
+    pub fn start_synthetic_probes(self: &Arc<Self>) {
+        self.synthetic_probes.start(Arc::downgrade(self));
+    }
+
+    /// Send a captured frame through the gateway; boxed since capturing is
+    /// itself a request
+    fn process_frame(&self, request: MCPRequest) -> BoxedResponse<'_> {
+        Box::pin(self.process_request(request))
+    }
+
+    /// Get the attached cameras
+    pub fn peripherals(&self) -> &Peripherals {
+        &self.peripherals
+    }
+
+    /// Get the wake word gated microphones
+    pub fn audio(&self) -> &AudioPipeline {
+        &self.audio
+    }
+
+    /// Start capturing frames from cameras with a schedule and listening on
+    /// microphones
+    pub fn start_peripherals(self: &Arc<Self>) {
+        self.peripherals.start(Arc::downgrade(self));
+        self.audio.start(Arc::downgrade(self));
+    }
+
+    /// Get the API caller authentication
+    pub fn authenticator(&self) -> &Authenticator {
+        &self.authenticator
+    }
+
+    /// Get the canary evaluation of local models
+    pub fn model_canary(&self) -> &ModelCanary {
+        &self.model_canary
+    }
+
+    /// Get the model version rollouts
+    pub fn rollouts(&self) -> &ModelRollouts {
+        &self.rollouts
+    }
+
+    /// Get the admission controller for memory and thermal pressure
+    pub fn admission(&self) -> &AdmissionController {
+        &self.admission
+    }
+
+    /// Start or replace a rollout, loading its versions before they get
+    /// traffic and retiring the versions that no longer do
+    pub async fn set_rollout(&self, rollout: ModelRollout) -> Result<RolloutStatus> {
+        rollout.validate().map_err(|e| Error::InvalidRequest(e.to_string()))?;
+        let model = rollout.model.clone();
+        for version in std::iter::once(&rollout.stable).chain(rollout.candidate.as_ref()) {
+            self.model_engine.load_model(&version_id(&model, version)).await?;
+        }
+        let retired = self.rollouts.set(rollout)?;
+        self.retire_models(retired);
+        self.rollout_status(&model)
+    }
+
+    /// Change the share of devices a rollout sends to its candidate
+    pub async fn set_rollout_split(&self, model: &str, candidate_percent: u8) -> Result<RolloutStatus> {
+        if let Some(candidate) = self.rollout_status(model)?.rollout.candidate {
+            self.model_engine.load_model(&version_id(model, &candidate)).await?;
+        }
+        self.rollouts.set_split(model, candidate_percent)
+    }
+
+    /// Send all of a model's traffic to its candidate version
+    pub fn promote_rollout(&self, model: &str) -> Result<RolloutStatus> {
+        let retired = self.rollouts.promote(model)?;
+        info!("Promoted the candidate version of {}, retiring {}", model, retired);
+        self.retire_models(vec![retired]);
+        self.rollout_status(model)
+    }
+
+    /// Send all of a model's traffic back to its stable version
+    pub fn rollback_rollout(&self, model: &str) -> Result<RolloutStatus> {
+        let retired = self.rollouts.rollback(model)?;
+        info!("Rolled back the candidate version of {}, retiring {}", model, retired);
+        self.retire_models(vec![retired]);
+        self.rollout_status(model)
+    }
+
+    /// Stop splitting a model's traffic, retiring its versions
+    pub fn remove_rollout(&self, model: &str) -> Result<()> {
+        let retired = self.rollouts.remove(model)?;
+        self.retire_models(retired);
+        Ok(())
+    }
+
+    fn rollout_status(&self, model: &str) -> Result<RolloutStatus> {
+        self.rollouts
+            .get(model)
+            .ok_or_else(|| Error::InvalidRequest(format!("No rollout of model {}", model)))
+    }
+
+    /// Unload versions in the background once their in-flight requests finish
+    fn retire_models(&self, models: Vec<mcp_common::ModelId>) {
+        if models.is_empty() {
+            return;
+        }
+        let engine = self.model_engine.clone();
+        let drain_timeout = Duration::from_millis(self.config.models.model_timeout_ms);
+        tokio::spawn(async move {
+            for model_id in models {
+                if let Err(e) = engine.retire_model(&model_id, drain_timeout).await {
+                    warn!("Failed to retire model {}: {}", model_id, e);
+                }
+            }
+        });
+    }
+
+    /// Get the recorded session conversations
+    pub fn conversations(&self) -> &Arc<ConversationStore> {
+        &self.conversations
+    }
+
+    /// Get the journal of destructive admin operations
+    pub fn admin_journal(&self) -> &AdminJournal {
+        &self.admin_journal
+    }
+
+    /// Get the chat histories kept per session
+    pub fn sessions(&self) -> &Arc<SessionStore> {
+        &self.sessions
+    }
+
+    /// Get the text normalizer and its per-language request counts
+    pub fn normalizer(&self) -> &TextNormalizer {
+        &self.normalizer
+    }
+
+    /// Get the output moderator
+    pub fn moderator(&self) -> &OutputModerator {
+        &self.moderator
+    }
+
+    /// Export one of the requesting device's sessions, or import a package
+    /// as a session of that device
+    async fn process_conversation(&self, request: &MCPRequest) -> Result<MCPResponse> {
+        let result = if request.method == conversations::EXPORT_METHOD {
+            let session_id = request
+                .params
+                .get(SESSION_PARAM)
+                .and_then(|value| value.as_str())
+                .ok_or_else(|| Error::InvalidRequest(format!("{} requires a session_id", request.method)))?;
+            // Devices only see their own sessions
+            let package = self
+                .conversations
+                .export(session_id)
+                .await?
+                .filter(|package| package.conversation.device_id == request.device_id)
+                .ok_or_else(|| Error::InvalidRequest(format!("No conversation for session {}", session_id)))?;
+            serde_json::to_value(package)?
+        } else {
+            let package: ConversationPackage = serde_json::from_value(
+                request
+                    .params
+                    .get("package")
+                    .cloned()
+                    .ok_or_else(|| Error::InvalidRequest(format!("{} requires a package", request.method)))?,
+            )?;
+            // Devices may not take over another device's session
+            let session_id = &package.conversation.session_id;
+            if let Some(existing) = self.conversations.get(session_id).await? {
+                if existing.device_id != request.device_id {
+                    return Err(Error::InvalidRequest(format!("Session {} already has a conversation", session_id)));
+                }
+            }
+            let options = ImportOptions {
+                replace: request.params.get("replace").and_then(|value| value.as_bool()).unwrap_or(false),
+                device_id: Some(request.device_id.clone()),
+            };
+            let conversation = self.conversations.import(package, options).await?;
+            serde_json::json!({
+                "session_id": conversation.session_id,
+                "messages": conversation.messages.len(),
+            })
+        };
+        Ok(MCPResponse {
+            id: request.id,
+            result: Some(result),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        })
+    }
+
+    /// Get the active/standby replication
+    pub fn high_availability(&self) -> &HighAvailability {
+        &self.high_availability
+    }
+
+    /// Get the running configuration and the watch on its file
+    pub fn config_manager(&self) -> &Arc<ConfigManager> {
+        &self.config_manager
+    }
+
+    /// Watch the configuration file and apply changes as it is edited
+    pub fn start_config_reload(self: &Arc<Self>) {
+        self.config_manager.start(Arc::downgrade(self));
+    }
+
+    /// Apply the settings of a reloaded configuration that running
+    /// components can change in place
+    pub fn apply_config(&self, config: &Config) {
+        self.router.reconfigure(config);
+        self.telemetry.reconfigure(config);
+        if let Some(restrictions) = self.security.restrictions() {
+            restrictions.reconfigure(config.security.restricted_mode.clone());
+        }
+        let rate_limits = &config.security.rate_limits;
+        self.security.reconfigure_rate_limits(rate_limits.clone());
+        self.rate_limit.reconfigure(rate_limits.client_requests_per_window, rate_limits.client_window_secs);
+    }
+
+    /// Get the storage medium wear monitor
+    pub fn storage_health(&self) -> &StorageHealthMonitor {
+        &self.storage_health
+    }
+
+    /// Check the storage media's health periodically
+    pub fn start_storage_health(&self) {
+        self.storage_health.start();
+    }
+
+    /// Get the per-model inference scheduler
+    pub fn scheduler(&self) -> &InferenceScheduler {
+        &self.scheduler
+    }
+
+    /// Get the hedged cloud fallback for slow local inferences
+    pub fn hedger(&self) -> &Hedger {
+        &self.hedger
+    }
+
+    /// Get the key-value store shared by tool handlers and extensions
+    pub fn kv(&self) -> &Arc<KvStore> {
+        &self.kv
+    }
+
+    /// Get the subject data erasure service
+    pub fn erasure(&self) -> &DataErasure {
+        &self.erasure
+    }
+
+    /// Get the per-client HTTP rate limit
+    pub fn rate_limit_layer(&self) -> &RateLimitLayer {
+        &self.rate_limit
+    }
+
+    /// Get the load balancer health probe
+    pub fn health_probe(&self) -> &HealthProbe {
+        &self.health_probe
+    }
+
+    /// Get the retrieval index maintainer
+    pub fn index_maintainer(&self) -> &IndexMaintainer {
+        &self.index_maintainer
+    }
+
+    /// Clock the gateway reads time from
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Get the per-device clock skew tracker
+    pub fn clock_skew(&self) -> &ClockSkewTracker {
+        &self.clock_skew
+    }
+
+    /// End-to-end latency per request priority
+    pub fn priority_latency(&self) -> Vec<PriorityLatencyReport> {
+        self.priority_latency.report()
+    }
+
+    /// Get the configuration the gateway started with; see
+    /// [`config_manager`](Self::config_manager) for the one in effect now
     pub fn config(&self) -> &Config {
         &self.config
     }
@@ -229,10 +1653,11 @@ impl Gateway {
         let mut health_status = mcp_common::HealthStatus {
             overall_health: HealthLevel::Healthy,
             components: std::collections::HashMap::new(),
-            last_check: chrono::Utc::now(),
+            last_check: self.clock.now(),
             uptime_seconds: {
                 let state = self.state.read().await;
-                chrono::Utc::now()
+                self.clock
+                    .now()
                     .signed_duration_since(state.started_at)
                     .num_seconds() as u64
             },
@@ -252,17 +1677,19 @@ impl Gateway {
                 }),
         );
 
-        health_status.components.insert(
-            "model_engine".to_string(),
-            self.model_engine.health_check().await.unwrap_or_else(|_| {
-                ComponentHealth {
-                    status: HealthLevel::Critical,
-                    message: "Model engine health check failed".to_string(),
-                    last_check: chrono::Utc::now(),
-                    metrics: std::collections::HashMap::new(),
-                }
-            }),
-        );
+        let mut model_engine_health = self.model_engine.health_check().await.unwrap_or_else(|_| {
+            ComponentHealth {
+                status: HealthLevel::Critical,
+                message: "Model engine health check failed".to_string(),
+                last_check: chrono::Utc::now(),
+                metrics: std::collections::HashMap::new(),
+            }
+        });
+        self.rollouts.write_metrics(&mut model_engine_health.metrics);
+        self.model_canary.write_metrics(&mut model_engine_health.metrics);
+        health_status
+            .components
+            .insert("model_engine".to_string(), model_engine_health);
 
         health_status.components.insert(
             "queue".to_string(),
@@ -316,13 +1743,53 @@ impl Gateway {
                 }),
         );
 
+        health_status.components.insert(
+            "maintenance".to_string(),
+            self.maintenance.health().await,
+        );
+
+        health_status.components.insert(
+            "retrieval_index".to_string(),
+            self.index_maintainer.health().await,
+        );
+
+        health_status
+            .components
+            .insert("bandwidth".to_string(), self.bandwidth.health());
+        health_status.components.insert("kv".to_string(), self.kv.health());
+        if let Some(audit) = &self.audit {
+            health_status.components.insert("audit".to_string(), audit.health());
+        }
+        if self.admission.enabled() {
+            health_status.components.insert("admission".to_string(), self.admission.health());
+        }
+        if self.synthetic_probes.enabled() {
+            health_status.components.insert("synthetic_probes".to_string(), self.synthetic_probes.health());
+        }
+        if self.audio.enabled() {
+            health_status.components.insert("audio".to_string(), self.audio.health());
+        }
+        if self.high_availability.enabled() {
+            health_status
+                .components
+                .insert("high_availability".to_string(), self.high_availability.health());
+        }
+        if self.storage_health.enabled() {
+            health_status
+                .components
+                .insert("storage".to_string(), self.storage_health.health());
+        }
+        if self.scheduler.enabled() {
+            health_status.components.insert("scheduler".to_string(), self.scheduler.health());
+        }
+
         // Calculate overall health
         health_status.calculate_overall_health();
 
         // Update gateway state
         {
             let mut state = self.state.write().await;
-            state.last_health_check = chrono::Utc::now();
+            state.last_health_check = self.clock.now();
             state.is_healthy = health_status.overall_health == HealthLevel::Healthy;
         }
 
@@ -334,11 +1801,260 @@ impl Gateway {
         self.telemetry.get_aggregated_metrics().await
     }
 
+    /// Current metrics in Prometheus text format
+    pub async fn render_metrics(&self) -> String {
+        let mut encoder = PrometheusEncoder::new(&self.config.telemetry.prometheus);
+        self.telemetry.write_prometheus(&mut encoder).await;
+
+        let state = self.state().await;
+        encoder.gauge("gateway_active_requests", "Requests being processed", state.active_requests as f64);
+        encoder.counter("gateway_total_requests", "Requests accepted since start", state.total_requests as f64);
+        encoder.gauge(
+            "gateway_uptime_seconds",
+            "Seconds since the gateway started",
+            chrono::Utc::now().signed_duration_since(state.started_at).num_seconds() as f64,
+        );
+
+        match self.queue.queue_size().await {
+            Ok(depth) => encoder.gauge("queue_depth", "Requests waiting in the offline queue", depth as f64),
+            Err(e) => warn!("Failed to read queue depth for metrics: {}", e),
+        }
+        match self.queue.dead_letters().await {
+            Ok(dead_letters) => encoder.gauge(
+                "queue_dead_letter_depth",
+                "Requests moved to the dead-letter queue after using all their retries",
+                dead_letters.len() as f64,
+            ),
+            Err(e) => warn!("Failed to read dead-letter depth for metrics: {}", e),
+        }
+        match self.queue.analytics().await {
+            Ok(Some(analytics)) => {
+                let ages = analytics.age_secs;
+                let quantiles: Vec<(String, f64)> = [
+                    ("0.5", ages.p50),
+                    ("0.9", ages.p90),
+                    ("0.99", ages.p99),
+                    ("1", ages.max),
+                ]
+                .into_iter()
+                .map(|(quantile, secs)| (quantile.to_string(), secs as f64))
+                .collect();
+                encoder.labelled(
+                    MetricKind::Gauge,
+                    "queue_age_seconds",
+                    "Age of requests waiting in the offline queue",
+                    "quantile",
+                    &quantiles,
+                );
+                let rates = analytics.rates;
+                encoder.gauge("queue_arrival_rate", "Requests queued per minute", rates.arrivals_per_min);
+                encoder.gauge(
+                    "queue_drain_rate",
+                    "Queued requests dequeued or synced per minute",
+                    rates.drains_per_min,
+                );
+                encoder.gauge(
+                    "queue_drop_rate",
+                    "Queued requests expired or dead-lettered per minute",
+                    rates.drops_per_min,
+                );
+                if let Some(secs) = analytics.time_to_drain_secs {
+                    encoder.gauge(
+                        "queue_time_to_drain_seconds",
+                        "Estimated seconds until the offline queue is empty",
+                        secs as f64,
+                    );
+                }
+            },
+            Ok(None) => {},
+            Err(e) => warn!("Failed to compute queue analytics for metrics: {}", e),
+        }
+
+        match self.model_engine.list_models().await {
+            Ok(models) => {
+                let memory: Vec<(String, f64)> = models
+                    .into_iter()
+                    .map(|model| (model.model_id, f64::from(model.memory_usage_mb) * 1024.0 * 1024.0))
+                    .collect();
+                encoder.labelled(MetricKind::Gauge, "model_memory_bytes", "Memory used by each loaded model", "model", &memory);
+            },
+            Err(e) => warn!("Failed to list models for metrics: {}", e),
+        }
+
+        let media = self.storage_health.report();
+        let per_device = |value: fn(&MediumHealth) -> Option<f64>| -> Vec<(String, f64)> {
+            media
+                .iter()
+                .filter_map(|medium| Some((medium.device.clone(), value(medium)?)))
+                .collect()
+        };
+        encoder.labelled(
+            MetricKind::Gauge,
+            "storage_wear_percent",
+            "Estimated share of the storage medium's rated life used",
+            "device",
+            &per_device(|medium| medium.wear_percent.map(f64::from)),
+        );
+        encoder.labelled(
+            MetricKind::Gauge,
+            "storage_reallocated_sectors",
+            "Sectors the drive has reallocated",
+            "device",
+            &per_device(|medium| medium.reallocated_sectors.map(|count| count as f64)),
+        );
+        encoder.labelled(
+            MetricKind::Gauge,
+            "storage_pending_sectors",
+            "Sectors waiting to be reallocated",
+            "device",
+            &per_device(|medium| medium.pending_sectors.map(|count| count as f64)),
+        );
+        encoder.gauge(
+            "storage_write_relaxation",
+            "Factor storage writes are spread out by to spare worn media",
+            f64::from(WritePolicy::global().relaxation()),
+        );
+
+        let hedges: Vec<(String, f64)> = self
+            .hedger
+            .outcomes()
+            .into_iter()
+            .map(|(outcome, count)| (outcome.as_str().to_string(), count as f64))
+            .collect();
+        encoder.labelled(
+            MetricKind::Counter,
+            "hedged_requests_total",
+            "Hedging-eligible local inferences by the path that answered",
+            "outcome",
+            &hedges,
+        );
+
+        if self.normalizer.enabled() {
+            let languages: Vec<(String, f64)> = self
+                .normalizer
+                .requests_by_language()
+                .into_iter()
+                .map(|(language, count)| (language, count as f64))
+                .collect();
+            encoder.labelled(
+                MetricKind::Counter,
+                "requests_by_language_total",
+                "Requests by the language detected in their text",
+                "language",
+                &languages,
+            );
+        }
+
+        let queues = self.scheduler.report();
+        let per_model = |value: fn(&ModelQueueReport) -> f64| -> Vec<(String, f64)> {
+            queues.iter().map(|queue| (queue.model_id.clone(), value(queue))).collect()
+        };
+        encoder.labelled(
+            MetricKind::Gauge,
+            "scheduler_in_flight",
+            "Local inferences running for each model",
+            "model",
+            &per_model(|queue| queue.in_use as f64),
+        );
+        encoder.labelled(
+            MetricKind::Gauge,
+            "scheduler_queued",
+            "Requests waiting for an inference slot on each model",
+            "model",
+            &per_model(|queue| queue.waiting as f64),
+        );
+        encoder.labelled(
+            MetricKind::Counter,
+            "scheduler_rejected_total",
+            "Requests turned away by each model's scheduler",
+            "model",
+            &per_model(|queue| queue.rejected_total as f64),
+        );
+
+        let power = self.power.status();
+        let low_power = if power.low_power { 1.0 } else { 0.0 };
+        encoder.gauge("power_low_power_mode", "1 while in low-power mode", low_power);
+        if let Some(battery) = power.battery_percent {
+            encoder.gauge("power_battery_percent", "Battery charge", battery);
+        }
+
+        // One sample per possible state, set to 1 for the current one
+        let breakers = self.webhooks.breaker_states().await;
+        let mut breaker_labels = Vec::new();
+        for (name, current) in &breakers {
+            for (state, label) in [
+                (CircuitState::Closed, "closed"),
+                (CircuitState::HalfOpen, "half_open"),
+                (CircuitState::Open, "open"),
+            ] {
+                let value = if *current == state { 1.0 } else { 0.0 };
+                breaker_labels.push(([("breaker", name.as_str()), ("state", label)], value));
+            }
+        }
+        let samples: Vec<(Labels, f64)> = breaker_labels.iter().map(|(labels, value)| (&labels[..], *value)).collect();
+        encoder.family(MetricKind::Gauge, "circuit_breaker_state", "Circuit breaker state", &samples);
+
+        let pipeline_metrics: BTreeMap<String, f32> = self.pipeline_guard.get_pipeline_metrics().await.into_iter().collect();
+        for (key, value) in pipeline_metrics {
+            encoder.gauge(&format!("pipeline_{}", key), "Pipeline guard metric", f64::from(value));
+        }
+
+        // Per-component concurrency gauges, retrieval index metrics,
+        // inference memory estimates, session prefix reuse, embedding batching,
+        // model ensembles and the model response cache
+        if let Ok(health) = self.health_check().await {
+            let mut concurrency: BTreeMap<String, Vec<(String, f64)>> = BTreeMap::new();
+            let mut retrieval = BTreeMap::new();
+            let mut inference_memory = BTreeMap::new();
+            let mut embedding_batching = BTreeMap::new();
+            let mut engine = BTreeMap::new();
+            for (component, component_health) in &health.components {
+                for (key, value) in &component_health.metrics {
+                    if let Some(gauge) = key.strip_prefix("concurrency_") {
+                        concurrency.entry(gauge.to_string()).or_default().push((component.clone(), f64::from(*value)));
+                    } else if key.starts_with("index_") {
+                        retrieval.insert(key.clone(), f64::from(*value));
+                    } else if key.starts_with("inference_memory_") || key.starts_with("prefix_cache_") {
+                        inference_memory.insert(key.clone(), f64::from(*value));
+                    } else if key.starts_with("embedding_batch") {
+                        embedding_batching.insert(key.clone(), f64::from(*value));
+                    } else if key.starts_with("ensemble_") || key.starts_with("response_cache_") {
+                        engine.insert(key.clone(), f64::from(*value));
+                    }
+                }
+            }
+            for (gauge, mut samples) in concurrency {
+                samples.sort_by(|a, b| a.0.cmp(&b.0));
+                let metric = format!("concurrency_{}", gauge);
+                encoder.labelled(MetricKind::Gauge, &metric, "Concurrency gauge per component", "component", &samples);
+            }
+            for (key, value) in retrieval {
+                encoder.gauge(&format!("retrieval_{}", key), "Retrieval index metric", value);
+            }
+            for (key, value) in inference_memory {
+                encoder.gauge(&key, "Local inference memory metric", value);
+            }
+            for (key, value) in embedding_batching {
+                encoder.gauge(&key, "Embedding micro-batching metric", value);
+            }
+            for (key, value) in engine {
+                encoder.gauge(&key, "Model engine metric", value);
+            }
+        }
+
+        encoder.finish()
+    }
+
     /// Get pipeline guard instance
     pub fn pipeline_guard(&self) -> &PipelineGuard {
         &self.pipeline_guard
     }
 
+    /// Get maintenance mode controller
+    pub fn maintenance(&self) -> &MaintenanceMode {
+        &self.maintenance
+    }
+
     /// Shutdown the gateway gracefully
     pub async fn shutdown(&self) -> Result<()> {
         info!("Shutting down gateway");
@@ -371,7 +2087,88 @@ impl Gateway {
             error!("Error shutting down router: {}", e);
         }
 
+        self.bandwidth.stop().await;
+        self.timeline.stop();
+        self.synthetic_probes.stop();
+        self.model_canary.stop();
+        self.peripherals.stop();
+        self.audio.stop();
+        self.high_availability.stop();
+        self.config_manager.stop();
+        self.storage_health.stop();
+        self.kv.stop().await;
+
+        for component in COMPONENTS.iter().rev() {
+            events::publish(GatewayEvent::ComponentStopped {
+                component: component.to_string(),
+            });
+        }
+
         info!("Gateway shutdown complete with performance optimization cleanup");
         Ok(())
     }
 }
+
+/// Whether a response only acknowledges that the request was queued
+fn is_queued_response(response: &MCPResponse) -> bool {
+    response
+        .result
+        .as_ref()
+        .and_then(|result| result.get("status"))
+        .is_some_and(|status| status == "queued")
+}
+
+/// Tenant a request's usage is accounted to
+fn tenant_of(request: &MCPRequest) -> String {
+    request
+        .params
+        .get(TENANT_PARAM)
+        .and_then(|value| value.as_str())
+        .unwrap_or(&request.device_id)
+        .to_string()
+}
+
+/// A relayed stream, and what its final response is finished with
+struct PendingStream {
+    request_id: Uuid,
+    method: String,
+    tenant: String,
+    served: Served,
+    audit_digest: Option<AuditDigest>,
+    /// Usage of setting the stream up, and the handle generation reports to
+    usage: Option<(ResourceUsage, Option<DeferredUsage>)>,
+    started: Instant,
+}
+
+/// Whether a stream's final response is the cloud answer it switched to
+fn is_spliced(response: &MCPResponse) -> bool {
+    response
+        .result
+        .as_ref()
+        .and_then(|result| result.get("splice"))
+        .and_then(|splice| splice.get("spliced"))
+        .is_some_and(|spliced| spliced == true)
+}
+
+/// Runs canary prompts directly on the model engine, outside the request path
+struct EngineCanaryTarget(Arc<dyn ModelEngine + Send + Sync>);
+
+#[async_trait::async_trait]
+impl CanaryTarget for EngineCanaryTarget {
+    async fn infer(&self, model_id: &ModelId, request: MCPRequest) -> Result<MCPResponse> {
+        self.0.process_request(&request, model_id).await
+    }
+
+    async fn loaded_models(&self) -> Result<Vec<ModelId>> {
+        let listings = self.0.list_models().await?;
+        Ok(listings.into_iter().map(|listing| listing.model_id).collect())
+    }
+}
+
+/// Whether a request may be sent off the device, which requests marked as
+/// local-only or carrying PII may not
+fn may_leave_device(request: &MCPRequest) -> bool {
+    request.context.as_ref().map_or(true, |context| {
+        !context.requirements.require_local && context.requirements.pii_present != Some(true)
+    })
+}
@@ -0,0 +1,345 @@
+//! MCP over gRPC
+//!
+//! Serves `proto/mcp_gateway.proto` next to the HTTP/WebSocket API, backed by
+//! the same `AppState`. Requests go through the checks `POST
+//! /v1/mcp/completions` applies: client request IDs, method length, device
+//! signatures and request ID claims. Enrolled devices send their signature in
+//! the `x-mcp-device-signature` and `x-mcp-device-timestamp` metadata, over
+//! the protobuf encoding of the request. Requests are served by the gateway
+//! that receives them; forwarding to the owning cluster member is HTTP only.
+
+use crate::handlers::{verify_device_signature, MAX_METHOD_LENGTH};
+use crate::server::AppState;
+use futures_util::{Stream, StreamExt};
+use mcp_common::request_id as ids;
+use mcp_common::trace_context::TRACEPARENT_HEADER;
+use mcp_common::{redaction, Error, MCPRequest, MCPResponse, Rejection, Result, Span, SpanKind, TraceContext};
+use mcp_models::StreamChunk;
+use std::pin::Pin;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+/// Messages and service generated from `proto/mcp_gateway.proto`
+pub mod proto {
+    tonic::include_proto!("mcp.gateway.v1");
+}
+
+use proto::mcp_gateway_server::{McpGateway, McpGatewayServer};
+
+/// Device ID of requests that do not name one
+const DEFAULT_DEVICE_ID: &str = "grpc_client";
+
+/// Serve gRPC on `listener` until the server fails
+pub async fn serve(gateway: AppState, listener: TcpListener) -> Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(GrpcService::new(gateway).into_server())
+        .serve_with_incoming(tonic::transport::server::TcpIncoming::from(listener))
+        .await
+        .map_err(|e| Error::Network(format!("gRPC server error: {}", e)))
+}
+
+/// The `McpGateway` service over a gateway
+pub struct GrpcService {
+    gateway: AppState,
+}
+
+impl GrpcService {
+    pub fn new(gateway: AppState) -> Self {
+        Self {
+            gateway,
+        }
+    }
+
+    pub fn into_server(self) -> McpGatewayServer<Self> {
+        McpGatewayServer::new(self)
+    }
+
+    /// Validate a request and convert it into a gateway request
+    async fn admit(
+        &self,
+        request: Request<proto::McpRequest>,
+    ) -> std::result::Result<(MCPRequest, Option<Span>), Status> {
+        let headers = request.metadata().clone().into_headers();
+        let message = request.into_inner();
+
+        let request_id = match message.id.as_deref() {
+            Some(id) if ids::accepts_client_ids() => ids::parse(id).ok_or_else(|| {
+                Status::invalid_argument(format!("Invalid request ID '{}': expected a UUID or ULID", id))
+            })?,
+            _ => ids::generate(),
+        };
+        if message.method.is_empty() || message.method.len() > MAX_METHOD_LENGTH {
+            warn!("Rejected gRPC MCP request {} with an invalid method", request_id);
+            return Err(Status::invalid_argument(format!(
+                "Method must be between 1 and {} characters",
+                MAX_METHOD_LENGTH
+            )));
+        }
+        let params = match message.params_json.trim() {
+            "" => Default::default(),
+            json => match serde_json::from_str(json) {
+                Ok(serde_json::Value::Object(params)) => params.into_iter().collect(),
+                Ok(_) => return Err(Status::invalid_argument("params_json must be a JSON object")),
+                Err(e) => return Err(Status::invalid_argument(format!("Invalid params_json: {}", e))),
+            },
+        };
+        let timestamp = match message.timestamp_ms {
+            Some(ms) => chrono::DateTime::from_timestamp_millis(ms)
+                .ok_or_else(|| Status::invalid_argument("timestamp_ms is out of range"))?,
+            None => chrono::Utc::now(),
+        };
+        let device_id = message
+            .device_id
+            .clone()
+            .unwrap_or_else(|| DEFAULT_DEVICE_ID.to_string());
+
+        let body = prost::Message::encode_to_vec(&message);
+        if let Err(e) = verify_device_signature(&self.gateway, &headers, &device_id, &body).await {
+            warn!(
+                "Rejected gRPC MCP request {} from device {}: {}",
+                request_id,
+                redaction::id(&device_id),
+                e
+            );
+            if let Some(auth_guard) = self.gateway.security().auth_guard() {
+                auth_guard.record_failure(&device_id, None).await;
+            }
+            return Err(Status::unauthenticated(e.to_string()));
+        }
+        if let Err(e) = ids::claim(request_id, &device_id) {
+            warn!(
+                "Rejected gRPC MCP request {} from device {}: {}",
+                request_id,
+                redaction::id(&device_id),
+                e
+            );
+            return Err(Status::already_exists(e.to_string()));
+        }
+
+        let mut request = MCPRequest {
+            id: request_id,
+            device_id,
+            method: message.method,
+            params,
+            context: None,
+            timestamp,
+        };
+
+        let parent = headers
+            .get(TRACEPARENT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(TraceContext::parse);
+        let mut span = Span::continue_or_start(parent.as_ref(), "gateway.grpc_request", SpanKind::Server);
+        if let Some(span) = span.as_mut() {
+            span.set_attribute("mcp.method", &request.method);
+            span.set_attribute("mcp.request_id", request_id);
+            request.set_trace(span.context());
+        }
+
+        info!(
+            "Processing gRPC MCP request: method={}, id={}",
+            request.method, request_id
+        );
+        Ok((request, span))
+    }
+}
+
+type ChunkStream = Pin<Box<dyn Stream<Item = std::result::Result<proto::StreamChunk, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl McpGateway for GrpcService {
+    async fn process(
+        &self,
+        request: Request<proto::McpRequest>,
+    ) -> std::result::Result<Response<proto::McpResponse>, Status> {
+        let (request, mut span) = self.admit(request).await?;
+        let request_id = request.id;
+        let result = self.gateway.process_request(request).await;
+        if let Some(span) = span.as_mut() {
+            span.record_result(&result);
+        }
+        match result {
+            Ok(response) => Ok(Response::new(to_response(&response)?)),
+            Err(e) => {
+                warn!("gRPC MCP request {} failed: {}", request_id, e);
+                Err(to_status(e))
+            },
+        }
+    }
+
+    type ProcessStreamStream = ChunkStream;
+
+    /// Chunks are taken from the generator only as the client reads them, so
+    /// a slow client pauses generation instead of being buffered for
+    async fn process_stream(
+        &self,
+        request: Request<proto::McpRequest>,
+    ) -> std::result::Result<Response<Self::ProcessStreamStream>, Status> {
+        let (request, _span) = self.admit(request).await?;
+        let request_id = request.id;
+        let chunks = self
+            .gateway
+            .process_request_streaming(request)
+            .await
+            .map_err(to_status)?;
+        let stream = ReceiverStream::new(chunks).map(move |chunk| {
+            let chunk = match chunk {
+                Ok(StreamChunk::Token {
+                    index,
+                    text,
+                }) => proto::stream_chunk::Chunk::Token(proto::Token {
+                    index: index as u32,
+                    text,
+                }),
+                Ok(StreamChunk::Done(response)) => proto::stream_chunk::Chunk::Response(to_response(&response)?),
+                Err(e) => {
+                    warn!("Streamed gRPC MCP request {} failed: {}", request_id, e);
+                    return Err(to_status(e));
+                },
+            };
+            Ok(proto::StreamChunk {
+                chunk: Some(chunk),
+            })
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn capabilities(
+        &self,
+        _request: Request<proto::CapabilitiesRequest>,
+    ) -> std::result::Result<Response<proto::CapabilitiesResponse>, Status> {
+        let capabilities_json = serde_json::to_string(&self.gateway.capabilities())
+            .map_err(|e| Status::internal(format!("Failed to encode capabilities: {}", e)))?;
+        Ok(Response::new(proto::CapabilitiesResponse {
+            capabilities_json,
+        }))
+    }
+}
+
+fn to_response(response: &MCPResponse) -> std::result::Result<proto::McpResponse, Status> {
+    Ok(proto::McpResponse {
+        request_id: response.id.to_string(),
+        response_json: serde_json::to_string(response)
+            .map_err(|e| Status::internal(format!("Failed to encode response: {}", e)))?,
+    })
+}
+
+fn to_status(error: Error) -> Status {
+    match error {
+        Error::InvalidRequest(message) => Status::invalid_argument(message),
+        Error::DeadlineExceeded(details) => Status::deadline_exceeded(details.to_string()),
+        Error::Security(message) => Status::permission_denied(message),
+        Error::ResourceExhausted(message) => Status::resource_exhausted(message),
+        Error::Overloaded(details) => match details.rejection {
+            Rejection::QueueFull => Status::resource_exhausted(details.to_string()),
+            _ => Status::unavailable(details.to_string()),
+        },
+        e => Status::internal(format!("Request processing failed: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::mcp_gateway_client::McpGatewayClient;
+    use super::*;
+    use crate::testing::ScriptedCloudClient;
+    use crate::Gateway;
+    use mcp_common::config::CloudEndpoint;
+    use mcp_common::Config;
+    use std::sync::Arc;
+
+    async fn client() -> McpGatewayClient<tonic::transport::Channel> {
+        let mut config = Config::default();
+        config.router.cloud_endpoints = vec![CloudEndpoint {
+            name: "scripted".to_string(),
+            url: "https://cloud.test".to_string(),
+            api_key: None,
+            timeout_ms: 1000,
+            max_retries: 0,
+            connect_timeout_ms: None,
+            region: None,
+            provider: Default::default(),
+            compression: Default::default(),
+        }];
+        let cloud = Arc::new(ScriptedCloudClient::new());
+        cloud.push_response(serde_json::json!({"text": "over grpc"}));
+        let gateway = Gateway::builder(config)
+            .with_cloud_transport(cloud)
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(Arc::new(gateway), listener));
+        McpGatewayClient::connect(format!("http://{}", address))
+            .await
+            .unwrap()
+    }
+
+    fn request(method: &str) -> proto::McpRequest {
+        proto::McpRequest {
+            id: None,
+            device_id: Some("sensor-1".to_string()),
+            method: method.to_string(),
+            params_json: r#"{"prompt":"hello"}"#.to_string(),
+            timestamp_ms: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_and_capabilities_over_grpc() {
+        let mut client = client().await;
+        let capabilities = client
+            .capabilities(proto::CapabilitiesRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        let capabilities: serde_json::Value = serde_json::from_str(&capabilities.capabilities_json).unwrap();
+        assert!(capabilities.is_object());
+
+        let mut with_id = request("completion");
+        with_id.id = Some(uuid::Uuid::new_v4().to_string());
+        let response = client.process(with_id.clone()).await.unwrap().into_inner();
+        let body: serde_json::Value = serde_json::from_str(&response.response_json).unwrap();
+        assert_eq!(Some(response.request_id.clone()), with_id.id);
+        assert_eq!(body["id"], response.request_id);
+        assert_eq!(body["result"]["text"], "over grpc");
+
+        let status = client.process(request("")).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let mut bad_params = request("completion");
+        bad_params.params_json = "[1, 2]".to_string();
+        let status = client.process(bad_params).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        // Another device may not reuse the request ID
+        with_id.device_id = Some("sensor-2".to_string());
+        let status = client.process(with_id).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_with_the_response() {
+        let mut client = client().await;
+        let mut stream = client
+            .process_stream(request("completion"))
+            .await
+            .unwrap()
+            .into_inner();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = stream.message().await.unwrap() {
+            chunks.push(chunk.chunk.unwrap());
+        }
+        let Some(proto::stream_chunk::Chunk::Response(response)) = chunks.pop() else {
+            panic!("stream did not end with a response");
+        };
+        let body: serde_json::Value = serde_json::from_str(&response.response_json).unwrap();
+        assert_eq!(body["result"]["text"], "over grpc");
+        assert!(chunks
+            .iter()
+            .all(|chunk| matches!(chunk, proto::stream_chunk::Chunk::Token(_))));
+    }
+}
@@ -1,23 +1,45 @@
 //! HTTP handlers for the MCP Gateway
 
 use axum::{
-    extract::{Json as ExtractJson, Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Extension, Json as ExtractJson, Path, Query, State,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
-use mcp_common::{MCPRequest, MCPResponse, Error, Result};
+use mcp_common::redaction;
+use mcp_common::request_id as ids;
+use mcp_common::trace_context::TRACEPARENT_HEADER;
+use mcp_common::{
+    Error, EventKind, EventSubscriber, MCPRequest, Principal, Rejection, Result, Span, SpanKind,
+    TraceContext,
+};
+use mcp_models::StreamChunk;
+use mcp_security::{EnrollmentRequest, DEVICE_SIGNATURE_HEADER, DEVICE_TIMESTAMP_HEADER};
+use mcp_telemetry::PROMETHEUS_CONTENT_TYPE;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
-use tracing::{error, info, warn};
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
 
+use crate::auth::API_KEY_HEADER;
+use crate::cluster::{Ownership, FORWARDED_HEADER};
 use crate::gateway::Gateway;
+use crate::idempotency::{IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LENGTH};
+use crate::audio::AUDIO_NOTIFICATION;
+use crate::peripherals::{CAMERA_CAPTURE_METHOD, FRAME_NOTIFICATION};
 
 /// Application state for handlers
 pub type AppState = Arc<Gateway>;
 
+/// Longest method name accepted from clients
+pub const MAX_METHOD_LENGTH: usize = 128;
+
 /// Health check response
 #[derive(Serialize)]
 pub struct HealthResponse {
@@ -38,10 +60,26 @@ pub struct ComponentStatusInfo {
 /// MCP request wrapper for HTTP
 #[derive(Deserialize)]
 pub struct HttpMCPRequest {
+    /// Client-chosen request ID, as a UUID or ULID
+    #[serde(default)]
+    id: Option<String>,
     method: String,
     params: Value,
     #[serde(default)]
     context: Option<Value>,
+    /// Identifier of the calling device
+    #[serde(default)]
+    device_id: Option<String>,
+    /// Device-local time the request was created
+    #[serde(default)]
+    timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    /// Stream generated tokens before the final response (WebSocket only)
+    #[serde(default)]
+    stream: bool,
+    /// Key shared by retries of this request; the `Idempotency-Key` header
+    /// takes precedence
+    #[serde(default)]
+    idempotency_key: Option<String>,
 }
 
 /// Create the router with all endpoints
@@ -53,18 +91,31 @@ pub fn create_router(gateway: Arc<Gateway>) -> Router {
         
         // MCP endpoints
         .route("/v1/mcp/completions", post(handle_mcp_request))
+        .route("/v1/mcp/capabilities", get(capabilities))
+        .route("/v1/mcp/ws", get(mcp_websocket))
+        .route("/v1/events/ws", get(events_websocket))
+
+        // Device enrollment
+        .route("/v1/enroll", post(enroll_device))
+        .route("/v1/enroll/ca", get(enrollment_ca))
         
         // Pipeline guard endpoints
         .route("/v1/pipeline/health", get(pipeline_health))
         .route("/v1/pipeline/metrics", get(pipeline_metrics))
         .route("/v1/pipeline/status", get(pipeline_status))
         .route("/v1/pipeline/components", get(pipeline_components))
-        .route("/v1/pipeline/recover/:component_id", post(recover_component))
+        .route("/v1/pipeline/recover/{component_id}", post(recover_component))
         .route("/v1/pipeline/force-check", post(force_health_check))
         
         // Metrics endpoints
         .route("/metrics", get(get_metrics))
         .route("/v1/metrics/performance", get(performance_metrics))
+
+        // Service mesh metadata and xDS discovery
+        .merge(crate::mesh::routes())
+
+        // Admin endpoints
+        .merge(crate::admin::routes())
         
         .with_state(gateway)
 }
@@ -80,11 +131,16 @@ pub async fn health_check(State(gateway): State<AppState>) -> impl IntoResponse
                 mcp_common::HealthLevel::Unknown => "unknown",
             };
             
-            Json(serde_json::json!({
+            // Load balancers eject backends on 5xx
+            let code = match health.overall_health {
+                mcp_common::HealthLevel::Critical => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::OK,
+            };
+            (code, Json(serde_json::json!({
                 "status": status,
                 "timestamp": health.last_check,
                 "uptime_seconds": health.uptime_seconds
-            })).into_response()
+            }))).into_response()
         }
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
@@ -133,11 +189,48 @@ pub async fn detailed_health_check(State(gateway): State<AppState>) -> impl Into
 /// Handle MCP requests with comprehensive validation and error handling
 pub async fn handle_mcp_request(
     State(gateway): State<AppState>,
-    ExtractJson(payload): ExtractJson<HttpMCPRequest>,
+    principal: Option<Extension<Principal>>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> impl IntoResponse {
     let start_time = std::time::Instant::now();
-    let request_id = uuid::Uuid::new_v4();
+    let request_id = ids::generate();
+
+    // Parsed by hand so signatures can be checked against the raw body
+    let payload: HttpMCPRequest = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            warn!("Rejected malformed MCP request: {}", redaction::text(&e.to_string()));
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": {
+                        "code": "INVALID_REQUEST",
+                        "message": format!("Invalid request body: {}", e),
+                        "request_id": request_id
+                    }
+                }))
+            ).into_response();
+        }
+    };
     
+    let request_id = match resolve_request_id(&payload) {
+        Ok(id) => id,
+        Err(e) => {
+            warn!("Rejected MCP request: {}", redaction::text(&e.to_string()));
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": {
+                        "code": "INVALID_REQUEST",
+                        "message": e.to_string(),
+                        "request_id": request_id
+                    }
+                }))
+            ).into_response();
+        }
+    };
+
     // Input validation
     if payload.method.is_empty() {
         warn!("Rejected MCP request with empty method");
@@ -153,7 +246,7 @@ pub async fn handle_mcp_request(
         ).into_response();
     }
     
-    if payload.method.len() > 128 {
+    if payload.method.len() > MAX_METHOD_LENGTH {
         warn!("Rejected MCP request with oversized method: {} chars", payload.method.len());
         return (
             StatusCode::BAD_REQUEST,
@@ -167,27 +260,227 @@ pub async fn handle_mcp_request(
         ).into_response();
     }
 
+    if let Some(Extension(principal)) = &principal {
+        if !principal.may_call(&payload.method) {
+            warn!(
+                "Rejected MCP request {}: {} may not call {}",
+                request_id, principal.subject, payload.method
+            );
+            return (
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({
+                    "error": {
+                        "code": "FORBIDDEN",
+                        "message": format!("Not allowed to call {}", payload.method),
+                        "request_id": request_id
+                    }
+                }))
+            ).into_response();
+        }
+    }
+
+    let idempotency_key = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| payload.idempotency_key.clone());
+    if idempotency_key
+        .as_ref()
+        .is_some_and(|key| key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH)
+    {
+        warn!("Rejected MCP request {} with an invalid idempotency key", request_id);
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": {
+                    "code": "INVALID_REQUEST",
+                    "message": format!("Idempotency key must be 1 to {} characters", MAX_IDEMPOTENCY_KEY_LENGTH),
+                    "request_id": request_id
+                }
+            }))
+        ).into_response();
+    }
+
     info!("Processing MCP request: method={}, id={}", payload.method, request_id);
+    debug!("Request {} params: {}", request_id, redaction::payload(&payload.params));
 
-    let request = MCPRequest {
-        id: request_id,
-        device_id: "http_client".to_string(),
-        method: payload.method.clone(),
-        params: payload.params.as_object()
-            .map(|obj| obj.iter()
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect())
-            .unwrap_or_default(),
-        context: None, // Will be populated by the gateway if needed
-        timestamp: chrono::Utc::now(),
-    };
+    let mut request = to_mcp_request(request_id, &payload);
+    if let Some(Extension(principal)) = principal {
+        request.set_principal(principal);
+    }
+    if let Some(key) = idempotency_key {
+        request.set_idempotency_key(key);
+    }
+    let device_id = request.device_id.clone();
+
+    // Continue the caller's trace, or start one when tracing is enabled
+    let parent = headers
+        .get(TRACEPARENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceContext::parse);
+    let mut span = Span::continue_or_start(parent.as_ref(), "gateway.http_request", SpanKind::Server);
+    let traceparent = span.as_ref().map(|span| span.context().traceparent());
+    if let Some(span) = span.as_mut() {
+        span.set_attribute("mcp.method", &payload.method);
+        span.set_attribute("mcp.request_id", request_id);
+        request.set_trace(span.context());
+    }
+
+    if let Err(e) = verify_device_signature(&gateway, &headers, &device_id, &body).await {
+        warn!("Rejected MCP request {} from device {}: {}", request_id, redaction::id(&device_id), e);
+        if let Some(auth_guard) = gateway.security().auth_guard() {
+            auth_guard.record_failure(&device_id, None).await;
+        }
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({
+                "error": {
+                    "code": "UNAUTHORIZED",
+                    "message": e.to_string(),
+                    "request_id": request_id
+                }
+            }))
+        ).into_response();
+    }
 
-    match gateway.process_request(request).await {
+    if let Err(e) = ids::claim(request_id, &device_id) {
+        warn!("Rejected MCP request {} from device {}: {}", request_id, redaction::id(&device_id), e);
+        return (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": {
+                    "code": "REQUEST_ID_CONFLICT",
+                    "message": e.to_string(),
+                    "request_id": request_id
+                }
+            }))
+        ).into_response();
+    }
+
+    // In cluster mode, devices owned by another member are served by that member
+    if !headers.contains_key(FORWARDED_HEADER) {
+        if let Ownership::Remote(owner) = gateway.cluster().route(&device_id) {
+            // The owner checks the device signature again, so pass it along,
+            // and deduplicates retries, so pass their key too
+            let mut signature_headers: Vec<(&str, &str)> = [
+                DEVICE_SIGNATURE_HEADER,
+                DEVICE_TIMESTAMP_HEADER,
+                API_KEY_HEADER,
+                header::AUTHORIZATION.as_str(),
+                IDEMPOTENCY_KEY_HEADER,
+            ]
+            .into_iter()
+            .filter_map(|name| Some((name, headers.get(name)?.to_str().ok()?)))
+            .collect();
+            if let Some(traceparent) = &traceparent {
+                signature_headers.push((TRACEPARENT_HEADER, traceparent));
+            }
+            match gateway
+                .cluster()
+                .proxy(&owner, "/v1/mcp/completions", body.to_vec(), &signature_headers)
+                .await
+            {
+                Ok((status, body)) => {
+                    info!(
+                        "Proxied MCP request {} for device {} to {}",
+                        request_id,
+                        redaction::id(&device_id),
+                        owner.id
+                    );
+                    return (
+                        StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY),
+                        [(header::CONTENT_TYPE, "application/json")],
+                        body,
+                    )
+                        .into_response();
+                },
+                Err(e) => warn!("{}; handling request {} locally", e, request_id),
+            }
+        }
+    }
+
+    let in_maintenance = gateway.maintenance().is_active().await;
+
+    let mut http_response = match gateway.process_request(request).await {
         Ok(response) => {
             let duration = start_time.elapsed();
             info!("MCP request completed: method={}, id={}, duration={:?}", 
                   payload.method, request_id, duration);
-            Json(response).into_response()
+            let mut http_response = Json(response).into_response();
+            if in_maintenance {
+                http_response.headers_mut().insert("x-maintenance-mode", HeaderValue::from_static("active"));
+            }
+            // Correction hint for devices whose clock is off
+            if let Some(skew) = gateway.clock_skew().device(&device_id).await.filter(|skew| skew.significant) {
+                http_response.headers_mut().insert("x-clock-skew-ms", HeaderValue::from(skew.skew_ms));
+            }
+            http_response
+        }
+        Err(Error::DeadlineExceeded(details)) => {
+            let duration = start_time.elapsed();
+            warn!("MCP request timed out: method={}, id={}, duration={:?}, {}",
+                  payload.method, request_id, duration, details);
+
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                Json(serde_json::json!({
+                    "error": {
+                        "code": "DEADLINE_EXCEEDED",
+                        "message": details.to_string(),
+                        "request_id": request_id,
+                        "timeout": details
+                    }
+                }))
+            ).into_response()
+        }
+        Err(Error::Overloaded(details)) => {
+            warn!("MCP request not scheduled: method={}, id={}, {}", payload.method, request_id, details);
+            // A full wait list is the caller sending too much; a request that
+            // waited out its deadline met a model that is too slow
+            let status = match details.rejection {
+                Rejection::QueueFull => StatusCode::TOO_MANY_REQUESTS,
+                _ => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            (
+                status,
+                [(header::RETRY_AFTER, details.retry_after_ms.div_ceil(1000).to_string())],
+                Json(serde_json::json!({
+                    "error": {
+                        "code": "OVERLOADED",
+                        "message": details.to_string(),
+                        "request_id": request_id,
+                        "overload": details
+                    }
+                }))
+            ).into_response()
+        }
+        Err(e @ Error::ResourceExhausted(_)) => {
+            warn!("MCP request not admitted: method={}, id={}, {}", payload.method, request_id, e);
+            let retry_after_secs = e.retry_delay_ms().unwrap_or(5000).div_ceil(1000);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                Json(serde_json::json!({
+                    "error": {
+                        "code": "RESOURCE_EXHAUSTED",
+                        "message": e.to_string(),
+                        "request_id": request_id
+                    }
+                }))
+            ).into_response()
+        }
+        Err(e @ Error::IdempotencyKeyReused(_)) => {
+            warn!("MCP request rejected: method={}, id={}, {}", payload.method, request_id, e);
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({
+                    "error": {
+                        "code": "IDEMPOTENCY_KEY_REUSED",
+                        "message": e.to_string(),
+                        "request_id": request_id
+                    }
+                }))
+            ).into_response()
         }
         Err(e) => {
             let duration = start_time.elapsed();
@@ -207,9 +500,335 @@ pub async fn handle_mcp_request(
                 }))
             ).into_response()
         }
+    };
+
+    if let Some(span) = span.as_mut() {
+        span.set_attribute("http.status_code", http_response.status().as_u16());
+        if http_response.status().is_server_error() {
+            span.set_error(http_response.status());
+        }
+    }
+    if let Some(value) = traceparent.and_then(|traceparent| HeaderValue::from_str(&traceparent).ok()) {
+        http_response.headers_mut().insert(TRACEPARENT_HEADER, value);
+    }
+    http_response
+}
+
+/// Check the request signature of enrolled devices
+///
+/// Devices holding a certificate must sign every request; a signature from
+/// any other device is rejected rather than ignored.
+pub(crate) async fn verify_device_signature(gateway: &Gateway, headers: &HeaderMap, device_id: &str, body: &[u8]) -> Result<()> {
+    let Some(enrollment) = gateway.security().enrollment() else {
+        return Ok(());
+    };
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    match (header(DEVICE_SIGNATURE_HEADER), header(DEVICE_TIMESTAMP_HEADER)) {
+        (Some(signature), Some(timestamp)) => {
+            let timestamp = timestamp
+                .parse::<i64>()
+                .map_err(|_| Error::Security(format!("{} must be a unix timestamp", DEVICE_TIMESTAMP_HEADER)))?;
+            enrollment.verify_signature(device_id, timestamp, body, signature).await
+        }
+        (None, None) if !enrollment.is_enrolled(device_id).await => Ok(()),
+        (None, None) => Err(Error::Security(format!("Enrolled device {} must sign its requests", device_id))),
+        _ => Err(Error::Security(format!(
+            "{} and {} must be sent together",
+            DEVICE_SIGNATURE_HEADER, DEVICE_TIMESTAMP_HEADER
+        ))),
+    }
+}
+
+/// Convert an HTTP/WebSocket payload into a gateway request
+/// The client's own ID when it sent one and those are accepted, otherwise a new one
+fn resolve_request_id(payload: &HttpMCPRequest) -> Result<uuid::Uuid> {
+    match payload.id.as_deref() {
+        Some(id) if ids::accepts_client_ids() => ids::parse(id)
+            .ok_or_else(|| Error::InvalidRequest(format!("Invalid request ID '{}': expected a UUID or ULID", id))),
+        _ => Ok(ids::generate()),
+    }
+}
+
+fn to_mcp_request(request_id: uuid::Uuid, payload: &HttpMCPRequest) -> MCPRequest {
+    MCPRequest {
+        id: request_id,
+        device_id: payload.device_id.clone().unwrap_or_else(|| "http_client".to_string()),
+        method: payload.method.clone(),
+        params: payload.params.as_object()
+            .map(|obj| obj.iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect())
+            .unwrap_or_default(),
+        context: None, // Will be populated by the gateway if needed
+        timestamp: payload.timestamp.unwrap_or_else(chrono::Utc::now),
+    }
+}
+
+/// Exchange a bootstrap token for a device certificate
+pub async fn enroll_device(
+    State(gateway): State<AppState>,
+    ExtractJson(request): ExtractJson<EnrollmentRequest>,
+) -> impl IntoResponse {
+    let Some(enrollment) = gateway.security().enrollment() else {
+        return crate::admin::enrollment_disabled();
+    };
+    let device_id = request.device_id.clone();
+    match enrollment.enroll(request).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => {
+            warn!("Enrollment of device {} failed: {}", redaction::id(&device_id), e);
+            let status = match e {
+                Error::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+                Error::Security(_) => StatusCode::FORBIDDEN,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+        }
     }
 }
 
+/// Fleet CA certificate devices and TLS terminators trust
+pub async fn enrollment_ca(State(gateway): State<AppState>) -> impl IntoResponse {
+    match gateway.security().enrollment() {
+        Some(enrollment) => (
+            [(header::CONTENT_TYPE, "application/x-pem-file")],
+            enrollment.ca_certificate_pem(),
+        )
+            .into_response(),
+        None => crate::admin::enrollment_disabled(),
+    }
+}
+
+/// Advertise gateway capabilities
+pub async fn capabilities(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.capabilities())
+}
+
+/// MCP over WebSocket; the first frame sent is the capability handshake
+pub async fn mcp_websocket(
+    State(gateway): State<AppState>,
+    principal: Option<Extension<Principal>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let principal = principal.map(|Extension(principal)| principal);
+    ws.on_upgrade(move |socket| handle_websocket(socket, gateway, principal))
+}
+
+/// Event stream filter, e.g. `?kinds=model,alert`
+#[derive(Debug, Default, Deserialize)]
+pub struct EventStreamQuery {
+    kinds: Option<String>,
+}
+
+/// Lifecycle events over WebSocket for dashboards
+pub async fn events_websocket(
+    Query(query): Query<EventStreamQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let mut kinds = Vec::new();
+    for name in query.kinds.iter().flat_map(|kinds| kinds.split(',')) {
+        match EventKind::parse(name) {
+            Some(kind) => kinds.push(kind),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "error": format!("Unknown event kind '{}'", name) })),
+                )
+                    .into_response()
+            },
+        }
+    }
+
+    match mcp_common::events::subscribe(&kinds) {
+        Ok(subscriber) => ws.on_upgrade(move |socket| stream_events(socket, subscriber)),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+async fn stream_events(mut socket: WebSocket, mut subscriber: EventSubscriber) {
+    loop {
+        tokio::select! {
+            envelope = subscriber.recv() => {
+                let Some(envelope) = envelope else { break };
+                let Ok(frame) = serde_json::to_string(&envelope) else { continue };
+                if socket.send(Message::Text(frame.into())).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                if !matches!(message, Some(Ok(message)) if !matches!(message, Message::Close(_))) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn handle_websocket(mut socket: WebSocket, gateway: AppState, principal: Option<Principal>) {
+    let handshake = serde_json::json!({
+        "type": "capabilities",
+        "capabilities": gateway.capabilities()
+    });
+    if socket.send(Message::Text(handshake.to_string().into())).await.is_err() {
+        return;
+    }
+
+    // Clients allowed to capture frames are notified of every frame result,
+    // and every client of audio interactions
+    let mut frames = (gateway.peripherals().enabled()
+        && !principal.as_ref().is_some_and(|principal| !principal.may_call(CAMERA_CAPTURE_METHOD)))
+    .then(|| gateway.peripherals().subscribe());
+    let mut interactions = gateway.audio().enabled().then(|| gateway.audio().subscribe());
+
+    loop {
+        let message = tokio::select! {
+            message = socket.recv() => message,
+            result = next_notification(&mut frames) => {
+                let notification = serde_json::json!({
+                    "type": "notification",
+                    "method": FRAME_NOTIFICATION,
+                    "params": result
+                });
+                if socket.send(Message::Text(notification.to_string().into())).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+            interaction = next_notification(&mut interactions) => {
+                let notification = serde_json::json!({
+                    "type": "notification",
+                    "method": AUDIO_NOTIFICATION,
+                    "params": interaction
+                });
+                if socket.send(Message::Text(notification.to_string().into())).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+        let Some(Ok(message)) = message else { break };
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let mut request_id = ids::generate();
+        let payload = serde_json::from_str::<HttpMCPRequest>(&text).and_then(|payload| {
+            request_id = resolve_request_id(&payload).map_err(serde::de::Error::custom)?;
+            Ok(payload)
+        });
+        let to_request = |payload: &HttpMCPRequest| {
+            let mut request = to_mcp_request(request_id, payload);
+            if let Some(principal) = &principal {
+                request.set_principal(principal.clone());
+            }
+            request
+        };
+        let reply = match payload {
+            Ok(payload) if payload.method.is_empty() || payload.method.len() > MAX_METHOD_LENGTH => {
+                websocket_error("INVALID_REQUEST", "Invalid method name", request_id)
+            }
+            Ok(payload) if principal.as_ref().is_some_and(|principal| !principal.may_call(&payload.method)) => {
+                websocket_error("FORBIDDEN", &format!("Not allowed to call {}", payload.method), request_id)
+            }
+            Ok(payload) if ids::claim(request_id, payload.device_id.as_deref().unwrap_or("http_client")).is_err() => {
+                websocket_error("REQUEST_ID_CONFLICT", "Request ID is already in use by another device", request_id)
+            }
+            Ok(payload) if payload.stream => {
+                if !stream_websocket_request(&mut socket, &gateway, to_request(&payload)).await {
+                    break;
+                }
+                continue;
+            }
+            Ok(payload) => match gateway.process_request(to_request(&payload)).await {
+                Ok(response) => serde_json::json!({ "type": "response", "response": response }),
+                Err(e) => {
+                    warn!("WebSocket MCP request {} failed: {}", request_id, e);
+                    websocket_error("PROCESSING_FAILED", &e.to_string(), request_id)
+                }
+            },
+            Err(e) => websocket_error("INVALID_REQUEST", &format!("Malformed request: {}", e), request_id),
+        };
+
+        if socket.send(Message::Text(reply.to_string().into())).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Next notification for a subscribed client; pending forever otherwise
+async fn next_notification<T: Clone>(notifications: &mut Option<broadcast::Receiver<T>>) -> T {
+    if let Some(receiver) = notifications {
+        loop {
+            match receiver.recv().await {
+                Ok(notification) => return notification,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("WebSocket client missed {} notifications", skipped);
+                },
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+        *notifications = None;
+    }
+    std::future::pending().await
+}
+
+/// Send a streamed request's tokens as they are generated, then its response.
+/// Each frame is sent before the next chunk is taken, so a slow client pauses
+/// generation instead of being buffered for; returns false once the client
+/// has gone away
+async fn stream_websocket_request(socket: &mut WebSocket, gateway: &AppState, request: MCPRequest) -> bool {
+    let request_id = request.id;
+    let mut frames = match gateway.process_request_streaming(request).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!("Streamed WebSocket MCP request {} failed: {}", request_id, e);
+            let code = match e {
+                Error::Overloaded(_) => "OVERLOADED",
+                _ => "PROCESSING_FAILED",
+            };
+            let reply = websocket_error(code, &e.to_string(), request_id);
+            return socket.send(Message::Text(reply.to_string().into())).await.is_ok();
+        },
+    };
+
+    while let Some(chunk) = frames.recv().await {
+        let frame = match chunk {
+            Ok(StreamChunk::Token { index, text }) => serde_json::json!({
+                "type": "token",
+                "request_id": request_id,
+                "index": index,
+                "text": text
+            }),
+            Ok(StreamChunk::Done(response)) => serde_json::json!({ "type": "response", "response": response }),
+            Err(e) => {
+                warn!("Streamed WebSocket MCP request {} failed: {}", request_id, e);
+                websocket_error("PROCESSING_FAILED", &e.to_string(), request_id)
+            },
+        };
+        if socket.send(Message::Text(frame.to_string().into())).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+fn websocket_error(code: &str, message: &str, request_id: uuid::Uuid) -> Value {
+    serde_json::json!({
+        "type": "error",
+        "error": {
+            "code": code,
+            "message": message,
+            "request_id": request_id
+        }
+    })
+}
+
 /// Get pipeline health status
 pub async fn pipeline_health(State(gateway): State<AppState>) -> impl IntoResponse {
     match gateway.pipeline_guard().get_health_status().await {
@@ -318,38 +937,16 @@ pub async fn force_health_check(State(gateway): State<AppState>) -> impl IntoRes
     }
 }
 
-/// Get system metrics
+/// Metrics in Prometheus text format, unless `telemetry.prometheus_enabled` is off
 pub async fn get_metrics(State(gateway): State<AppState>) -> Response {
-    let result = async {
-        // Convert to Prometheus format
-        let mut output = String::new();
-        
-        // Add basic gateway metrics
-        let state = gateway.state().await;
-        output.push_str(&format!("mcp_gateway_active_requests {}\n", state.active_requests));
-        output.push_str(&format!("mcp_gateway_total_requests {}\n", state.total_requests));
-        output.push_str(&format!("mcp_gateway_uptime_seconds {}\n", 
-            chrono::Utc::now().signed_duration_since(state.started_at).num_seconds()));
-        
-        // Add pipeline guard metrics
-        let pipeline_metrics = gateway.pipeline_guard().get_pipeline_metrics().await;
-        for (key, value) in pipeline_metrics {
-            output.push_str(&format!("mcp_pipeline_{} {}\n", key, value));
-        }
-        
-        output
-    }.await;
-    
-    Response::builder()
-        .header("content-type", "text/plain; charset=utf-8")
-        .body(result.into())
-        .unwrap_or_else(|e| {
-            error!("Failed to build metrics response: {}", e);
-            Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body("Failed to generate metrics".into())
-                .unwrap_or_default()
-        })
+    if !gateway.config().telemetry.prometheus_enabled {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        gateway.render_metrics().await,
+    )
+        .into_response()
 }
 
 /// Get detailed performance metrics
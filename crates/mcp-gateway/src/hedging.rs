@@ -0,0 +1,252 @@
+//! Hedged cloud fallback for slow local inference
+//!
+//! On a busy or throttled device a local model can take a long time to start
+//! answering. With `router.hedging` enabled, a local inference that has not
+//! produced its first token within the threshold is raced against the same
+//! request sent to the cloud, and whichever answer completes first is
+//! returned. The loser is cancelled: dropping the local stream stops
+//! generation in the model engine and frees its slot, and dropping the cloud
+//! future abandons the request in flight.
+//!
+//! Every request is counted under exactly one [`HedgeOutcome`], so the
+//! counters show how often hedging fires and which path wins when it does.
+
+use mcp_common::config::HedgingConfig;
+use mcp_common::{Error, MCPResponse, Result};
+use mcp_models::{StreamChunk, TokenStream};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::debug;
+
+/// Which path answered a request that was eligible for hedging
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HedgeOutcome {
+    /// Local inference produced a token in time; the cloud was not asked
+    Unhedged,
+    /// Both ran and the local answer completed first
+    LocalWon,
+    /// Both ran and the cloud answer completed first
+    CloudWon,
+    /// Both ran and neither produced an answer
+    Failed,
+}
+
+impl HedgeOutcome {
+    pub const ALL: [HedgeOutcome; 4] = [
+        HedgeOutcome::Unhedged,
+        HedgeOutcome::LocalWon,
+        HedgeOutcome::CloudWon,
+        HedgeOutcome::Failed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HedgeOutcome::Unhedged => "unhedged",
+            HedgeOutcome::LocalWon => "local_won",
+            HedgeOutcome::CloudWon => "cloud_won",
+            HedgeOutcome::Failed => "failed",
+        }
+    }
+
+    /// Whether the request was sent to the cloud
+    pub fn hedged(&self) -> bool {
+        *self != HedgeOutcome::Unhedged
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Races slow local inferences against the cloud
+pub struct Hedger {
+    config: HedgingConfig,
+    outcomes: [AtomicU64; 4],
+}
+
+impl Hedger {
+    pub fn new(config: HedgingConfig) -> Self {
+        Self {
+            config,
+            outcomes: Default::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Whether requests for `method` are hedged
+    pub fn applies_to(&self, method: &str) -> bool {
+        self.config.applies_to(method)
+    }
+
+    /// Requests counted under each outcome
+    pub fn outcomes(&self) -> Vec<(HedgeOutcome, u64)> {
+        HedgeOutcome::ALL
+            .iter()
+            .map(|outcome| (*outcome, self.outcomes[outcome.index()].load(Ordering::Relaxed)))
+            .collect()
+    }
+
+    /// Wait for `local`'s first chunk, then for whichever of it and `cloud`
+    /// completes first; `cloud` is only polled once the threshold has passed
+    pub async fn race<F>(&self, mut local: TokenStream, cloud: F) -> (Result<MCPResponse>, HedgeOutcome)
+    where
+        F: Future<Output = Result<MCPResponse>>,
+    {
+        let threshold = Duration::from_millis(self.config.fallback_threshold_ms);
+        let (result, outcome) = match tokio::time::timeout(threshold, local.recv()).await {
+            Ok(first) => (finish(first, local).await, HedgeOutcome::Unhedged),
+            Err(_) => {
+                debug!("No local token within {:?}, hedging with the cloud", threshold);
+                let local = collect(local);
+                tokio::pin!(local);
+                tokio::pin!(cloud);
+                tokio::select! {
+                    result = &mut local => match result {
+                        Ok(response) => (Ok(response), HedgeOutcome::LocalWon),
+                        Err(e) => match cloud.await {
+                            Ok(response) => (Ok(response), HedgeOutcome::CloudWon),
+                            Err(_) => (Err(e), HedgeOutcome::Failed),
+                        },
+                    },
+                    result = &mut cloud => match result {
+                        Ok(response) => (Ok(response), HedgeOutcome::CloudWon),
+                        Err(_) => match local.await {
+                            Ok(response) => (Ok(response), HedgeOutcome::LocalWon),
+                            Err(e) => (Err(e), HedgeOutcome::Failed),
+                        },
+                    },
+                }
+            },
+        };
+        self.outcomes[outcome.index()].fetch_add(1, Ordering::Relaxed);
+        (result, outcome)
+    }
+}
+
+/// Read a local stream to its final response, given its first chunk
+async fn finish(first: Option<Result<StreamChunk>>, stream: TokenStream) -> Result<MCPResponse> {
+    match first {
+        Some(Ok(StreamChunk::Done(response))) => Ok(response),
+        Some(Ok(StreamChunk::Token { .. })) => collect(stream).await,
+        Some(Err(e)) => Err(e),
+        None => Err(ended_early()),
+    }
+}
+
+/// Read a local stream to its final response; dropping this future drops the
+/// stream, which cancels the generation behind it
+async fn collect(mut stream: TokenStream) -> Result<MCPResponse> {
+    while let Some(chunk) = stream.recv().await {
+        match chunk? {
+            StreamChunk::Done(response) => return Ok(response),
+            StreamChunk::Token { .. } => {},
+        }
+    }
+    Err(ended_early())
+}
+
+fn ended_early() -> Error {
+    Error::Model("Local stream ended without a response".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    fn hedger() -> Hedger {
+        Hedger::new(HedgingConfig {
+            enabled: true,
+            fallback_threshold_ms: 20,
+            methods: Vec::new(),
+        })
+    }
+
+    fn response(text: &str) -> MCPResponse {
+        MCPResponse {
+            id: uuid::Uuid::new_v4(),
+            result: Some(serde_json::json!({ "text": text })),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    fn text(result: &Result<MCPResponse>) -> &str {
+        result.as_ref().unwrap().result.as_ref().unwrap()["text"].as_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_local_token_in_time_never_asks_the_cloud() {
+        let hedger = hedger();
+        let (sender, local) = mpsc::channel(4);
+        sender.send(Ok(StreamChunk::Token { index: 0, text: "hi".to_string() })).await.unwrap();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            let _ = sender.send(Ok(StreamChunk::Done(response("local")))).await;
+        });
+
+        let asked = Arc::new(AtomicBool::new(false));
+        let cloud = {
+            let asked = asked.clone();
+            async move {
+                asked.store(true, Ordering::SeqCst);
+                Ok(response("cloud"))
+            }
+        };
+        let (result, outcome) = hedger.race(local, cloud).await;
+        assert_eq!(outcome, HedgeOutcome::Unhedged);
+        assert_eq!(text(&result), "local");
+        assert!(!asked.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_faster_cloud_wins_and_cancels_local() {
+        let hedger = hedger();
+        let (sender, local) = mpsc::channel::<Result<StreamChunk>>(4);
+        let cloud = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(response("cloud"))
+        };
+        let (result, outcome) = hedger.race(local, cloud).await;
+        assert_eq!(outcome, HedgeOutcome::CloudWon);
+        assert_eq!(text(&result), "cloud");
+        // The generator sees its reader gone and stops
+        assert!(sender.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_local_answer_survives_cloud_failure() {
+        let hedger = hedger();
+        let (sender, local) = mpsc::channel(4);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            let _ = sender.send(Ok(StreamChunk::Done(response("local")))).await;
+        });
+        let cloud = async { Err(Error::Network("unreachable".to_string())) };
+        let (result, outcome) = hedger.race(local, cloud).await;
+        assert_eq!(outcome, HedgeOutcome::LocalWon);
+        assert_eq!(text(&result), "local");
+
+        let (sender, local) = mpsc::channel(1);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            let _ = sender.send(Err(Error::Model("out of memory".to_string()))).await;
+        });
+        let cloud = async { Err(Error::Network("unreachable".to_string())) };
+        let (result, outcome) = hedger.race(local, cloud).await;
+        assert_eq!(outcome, HedgeOutcome::Failed);
+        assert!(matches!(result, Err(Error::Model(_))));
+
+        let counts = hedger.outcomes();
+        assert_eq!(counts[HedgeOutcome::LocalWon.index()].1, 1);
+        assert_eq!(counts[HedgeOutcome::Failed.index()].1, 1);
+    }
+}
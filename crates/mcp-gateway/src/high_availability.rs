@@ -0,0 +1,738 @@
+//! Active/standby replication between a gateway pair
+//!
+//! With `cluster.high_availability` the active gateway pushes what changed
+//! since the last batch its peer acknowledged: conversation sessions,
+//! requests waiting in its offline queue and enrolled devices. Batches are
+//! signed with the shared secret as webhooks are, and double as heartbeats.
+//!
+//! The standby stores sessions and devices as they arrive and holds queued
+//! requests aside, since the active will sync them itself. Once it has not
+//! heard from the active for `failover_timeout_ms` it promotes itself under
+//! a new epoch and moves the held requests into its own queue, so no queued
+//! work is lost. Conflicts are resolved by epoch: a gateway receiving a
+//! batch from a higher epoch, or from the lower node id at the same epoch,
+//! becomes the standby; sessions keep the latest update and devices the
+//! newest certificate, with revocations never undone.
+
+use crate::conversations::{ConversationPackage, ConversationStore, ImportOptions};
+use crate::handlers::AppState;
+use crate::webhooks;
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use mcp_common::config::{HaRole, HighAvailabilityConfig};
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
+use mcp_common::{ComponentHealth, Error, HealthLevel, MCPRequest, Result};
+use mcp_queue::OfflineQueue;
+use mcp_security::{EnrolledDevice, SecurityManager};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Header carrying the HMAC signature over `{timestamp}.{body}`
+pub const HA_SIGNATURE_HEADER: &str = "X-MCP-HA-Signature";
+
+/// Header carrying the unix timestamp included in the signature
+pub const HA_TIMESTAMP_HEADER: &str = "X-MCP-HA-Timestamp";
+
+/// Path batches are pushed to on the peer
+pub const REPLICATE_PATH: &str = "/v1/ha/replicate";
+
+/// Oldest signature accepted, against replays
+const MAX_SIGNATURE_AGE_SECS: i64 = 30;
+
+/// Changes the active gateway sends its peer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplicationBatch {
+    pub node_id: String,
+    pub epoch: u64,
+    pub sessions: Vec<ConversationPackage>,
+    pub removed_sessions: Vec<String>,
+    pub queued: Vec<MCPRequest>,
+    /// Requests that left the sender's queue
+    pub dequeued: Vec<Uuid>,
+    pub devices: Vec<EnrolledDevice>,
+}
+
+impl ReplicationBatch {
+    fn len(&self) -> usize {
+        self.sessions.len()
+            + self.removed_sessions.len()
+            + self.queued.len()
+            + self.dequeued.len()
+            + self.devices.len()
+    }
+}
+
+/// Reply to a batch; a rejected batch means the sender must stand by
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicationAck {
+    pub node_id: String,
+    pub epoch: u64,
+    pub role: HaRole,
+    /// Changes when the peer restarts, so everything is sent again
+    pub instance: Uuid,
+    pub accepted: bool,
+}
+
+/// Replication state, as reported by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct HaStatus {
+    pub node_id: String,
+    pub role: HaRole,
+    pub epoch: u64,
+    pub peer_last_seen: Option<DateTime<Utc>>,
+    /// Queued requests held for a failover
+    pub held_requests: usize,
+    pub promotions: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct Replicated {
+    peer_instance: Option<Uuid>,
+    sessions: HashMap<String, DateTime<Utc>>,
+    queued: HashSet<Uuid>,
+    devices: HashMap<String, (String, bool)>,
+}
+
+struct PairState {
+    role: HaRole,
+    epoch: u64,
+    last_heard: Instant,
+    peer_last_seen: Option<DateTime<Utc>>,
+    /// Requests queued here since start, sent while they wait
+    tracked: HashMap<Uuid, MCPRequest>,
+    /// What the peer acknowledged holding
+    replicated: Replicated,
+    /// Requests queued on the active peer, taken over on promotion
+    held: HashMap<Uuid, MCPRequest>,
+    promotions: u64,
+    last_error: Option<String>,
+}
+
+/// One gateway of an active/standby pair
+pub struct HighAvailability {
+    config: HighAvailabilityConfig,
+    instance: Uuid,
+    conversations: Arc<ConversationStore>,
+    queue: Arc<dyn OfflineQueue + Send + Sync>,
+    security: Arc<dyn SecurityManager + Send + Sync>,
+    client: reqwest::Client,
+    state: Mutex<PairState>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl HighAvailability {
+    pub fn new(
+        config: HighAvailabilityConfig,
+        conversations: Arc<ConversationStore>,
+        queue: Arc<dyn OfflineQueue + Send + Sync>,
+        security: Arc<dyn SecurityManager + Send + Sync>,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.request_timeout_ms))
+            .build()
+            .unwrap_or_default();
+        let state = PairState {
+            role: config.role,
+            epoch: 0,
+            last_heard: Instant::now(),
+            peer_last_seen: None,
+            tracked: HashMap::new(),
+            replicated: Replicated::default(),
+            held: HashMap::new(),
+            promotions: 0,
+            last_error: None,
+        };
+        Self {
+            config,
+            instance: Uuid::new_v4(),
+            conversations,
+            queue,
+            security,
+            client,
+            state: Mutex::new(state),
+            task: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn role(&self) -> HaRole {
+        self.lock_state().role
+    }
+
+    /// Remember a request this gateway queued so the standby holds it too
+    pub fn track_queued(&self, request: &MCPRequest) {
+        let mut state = self.lock_state();
+        if self.enabled() && state.role == HaRole::Active {
+            state.tracked.insert(request.id, request.clone());
+        }
+    }
+
+    /// Push batches while active and watch for failover while standby
+    pub fn start(self: &Arc<Self>) {
+        if !self.enabled() {
+            return;
+        }
+        let high_availability: Weak<Self> = Arc::downgrade(self);
+        let interval = Duration::from_millis(self.config.sync_interval_ms.max(1));
+        let handle = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                let Some(high_availability) = high_availability.upgrade() else {
+                    break;
+                };
+                high_availability.tick().await;
+            }
+        });
+        if let Some(previous) = self.lock_task().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.lock_task().take() {
+            handle.abort();
+        }
+    }
+
+    async fn tick(&self) {
+        let (role, silent_for) = {
+            let state = self.lock_state();
+            (state.role, state.last_heard.elapsed())
+        };
+        match role {
+            HaRole::Active => {
+                if let Err(e) = self.push().await {
+                    debug!("Replication to {} failed: {}", self.config.peer_url, e);
+                    self.lock_state().last_error = Some(e.to_string());
+                }
+            },
+            HaRole::Standby if silent_for > Duration::from_millis(self.config.failover_timeout_ms) => {
+                if let Err(e) = self.promote().await {
+                    warn!("Promotion to active failed: {}", e);
+                }
+            },
+            HaRole::Standby => {},
+        }
+    }
+
+    /// Send the peer everything it has not acknowledged
+    async fn push(&self) -> Result<()> {
+        let batch = self.changes().await?;
+        let body = serde_json::to_vec(&batch)?;
+        let timestamp = Utc::now().timestamp();
+        let url = format!("{}{}", self.config.peer_url.trim_end_matches('/'), REPLICATE_PATH);
+        let response = self
+            .client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header(HA_TIMESTAMP_HEADER, timestamp.to_string())
+            .header(HA_SIGNATURE_HEADER, webhooks::sign(self.secret(), timestamp, &body))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Error::Network(format!("Replication request failed: {}", e)))?;
+        let status = response.status();
+        let ack: ReplicationAck = response
+            .json()
+            .await
+            .map_err(|e| Error::Network(format!("Peer returned {} without an ack: {}", status, e)))?;
+        self.acknowledged(&batch, &ack);
+        Ok(())
+    }
+
+    /// Changes since the peer's last acknowledgement
+    async fn changes(&self) -> Result<ReplicationBatch> {
+        let sessions = self.conversations.list().await?;
+        let queued: HashSet<Uuid> = self
+            .queue
+            .erase(&|_| true, true)
+            .await?
+            .into_iter()
+            .collect();
+        let devices = match self.security.enrollment() {
+            Some(enrollment) => enrollment.devices().await,
+            None => Vec::new(),
+        };
+
+        let (mut batch, changed_sessions) = {
+            let mut state = self.lock_state();
+            // Requests that left the queue are no longer tracked
+            state.tracked.retain(|id, _| queued.contains(id));
+            let replicated = &state.replicated;
+            let changed_sessions: Vec<String> = sessions
+                .iter()
+                .filter(|session| replicated.sessions.get(&session.session_id) != Some(&session.updated_at))
+                .map(|session| session.session_id.clone())
+                .collect();
+            let batch = ReplicationBatch {
+                node_id: self.config.node_id.clone(),
+                epoch: state.epoch,
+                sessions: Vec::new(),
+                removed_sessions: replicated
+                    .sessions
+                    .keys()
+                    .filter(|id| !sessions.iter().any(|session| &session.session_id == *id))
+                    .cloned()
+                    .collect(),
+                queued: state
+                    .tracked
+                    .values()
+                    .filter(|request| !replicated.queued.contains(&request.id))
+                    .cloned()
+                    .collect(),
+                dequeued: replicated
+                    .queued
+                    .iter()
+                    .filter(|id| !state.tracked.contains_key(id))
+                    .copied()
+                    .collect(),
+                devices: devices
+                    .into_iter()
+                    .filter(|device| {
+                        replicated.devices.get(&device.device_id)
+                            != Some(&(device.serial.clone(), device.revocation.is_some()))
+                    })
+                    .collect(),
+            };
+            (batch, changed_sessions)
+        };
+        for session_id in changed_sessions {
+            if let Some(package) = self.conversations.export(&session_id).await? {
+                batch.sessions.push(package);
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Record what the peer now holds, or stand by if it rejected the batch
+    fn acknowledged(&self, batch: &ReplicationBatch, ack: &ReplicationAck) {
+        let mut state = self.lock_state();
+        state.last_error = None;
+        state.peer_last_seen = Some(Utc::now());
+        if !ack.accepted {
+            warn!(
+                "Peer {} is active at epoch {}, standing by",
+                ack.node_id, ack.epoch
+            );
+            state.role = HaRole::Standby;
+            state.epoch = ack.epoch;
+            state.last_heard = Instant::now();
+            return;
+        }
+        let replicated = &mut state.replicated;
+        if replicated.peer_instance != Some(ack.instance) {
+            // A restarted peer has nothing; the next batch sends everything
+            *replicated = Replicated {
+                peer_instance: Some(ack.instance),
+                ..Default::default()
+            };
+            return;
+        }
+        for package in &batch.sessions {
+            let conversation = &package.conversation;
+            replicated
+                .sessions
+                .insert(conversation.session_id.clone(), conversation.updated_at);
+        }
+        for session_id in &batch.removed_sessions {
+            replicated.sessions.remove(session_id);
+        }
+        replicated
+            .queued
+            .extend(batch.queued.iter().map(|request| request.id));
+        for id in &batch.dequeued {
+            replicated.queued.remove(id);
+        }
+        for device in &batch.devices {
+            replicated.devices.insert(
+                device.device_id.clone(),
+                (device.serial.clone(), device.revocation.is_some()),
+            );
+        }
+        if batch.len() > 0 {
+            debug!("Peer {} acknowledged {} changes", ack.node_id, batch.len());
+        }
+    }
+
+    /// Apply a batch from the peer, unless this gateway stays active
+    pub async fn receive(&self, batch: ReplicationBatch) -> Result<ReplicationAck> {
+        let accepted = {
+            let mut state = self.lock_state();
+            let accepted = state.role == HaRole::Standby
+                || peer_wins(state.epoch, &self.config.node_id, batch.epoch, &batch.node_id);
+            if accepted {
+                if state.role == HaRole::Active {
+                    warn!(
+                        "Peer {} is active at epoch {}, standing by",
+                        batch.node_id, batch.epoch
+                    );
+                    events::publish(GatewayEvent::AlertRaised {
+                        source: "high_availability".to_string(),
+                        severity: AlertSeverity::Warning,
+                        message: format!("Demoted to standby by {}", batch.node_id),
+                    });
+                }
+                state.role = HaRole::Standby;
+                state.epoch = state.epoch.max(batch.epoch);
+                state.last_heard = Instant::now();
+                state.peer_last_seen = Some(Utc::now());
+                for request in &batch.queued {
+                    state.held.insert(request.id, request.clone());
+                }
+                for id in &batch.dequeued {
+                    state.held.remove(id);
+                }
+            }
+            accepted
+        };
+        if accepted {
+            self.apply(batch).await?;
+        }
+        let state = self.lock_state();
+        Ok(ReplicationAck {
+            node_id: self.config.node_id.clone(),
+            epoch: state.epoch,
+            role: state.role,
+            instance: self.instance,
+            accepted,
+        })
+    }
+
+    async fn apply(&self, batch: ReplicationBatch) -> Result<()> {
+        for package in batch.sessions {
+            let newer = match self.conversations.get(&package.conversation.session_id).await? {
+                Some(existing) => existing.updated_at < package.conversation.updated_at,
+                None => true,
+            };
+            if newer {
+                let options = ImportOptions {
+                    replace: true,
+                    ..Default::default()
+                };
+                self.conversations.import(package, options).await?;
+            }
+        }
+        for session_id in &batch.removed_sessions {
+            self.conversations.delete(session_id).await?;
+        }
+        if let Some(enrollment) = self.security.enrollment() {
+            for device in batch.devices {
+                enrollment.replicate(device).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Take over as active, queueing the requests held for the peer
+    pub async fn promote(&self) -> Result<()> {
+        let held: Vec<MCPRequest> = {
+            let mut state = self.lock_state();
+            if state.role == HaRole::Active {
+                return Ok(());
+            }
+            state.role = HaRole::Active;
+            state.epoch += 1;
+            state.promotions += 1;
+            state.replicated = Replicated::default();
+            state.held.drain().map(|(_, request)| request).collect()
+        };
+        let mut requeued = 0;
+        for request in held {
+            let id = request.id;
+            match self.queue.enqueue_request(request.clone()).await {
+                Ok(_) => {
+                    requeued += 1;
+                    self.track_queued(&request);
+                },
+                Err(e) => warn!("Failed to take over queued request {}: {}", id, e),
+            }
+        }
+        let epoch = self.lock_state().epoch;
+        info!("Promoted to active at epoch {}, took over {} queued requests", epoch, requeued);
+        events::publish(GatewayEvent::AlertRaised {
+            source: "high_availability".to_string(),
+            severity: AlertSeverity::Warning,
+            message: format!(
+                "{} promoted to active after losing {}; took over {} queued requests",
+                self.config.node_id, self.config.peer_url, requeued
+            ),
+        });
+        Ok(())
+    }
+
+    pub fn status(&self) -> HaStatus {
+        let state = self.lock_state();
+        HaStatus {
+            node_id: self.config.node_id.clone(),
+            role: state.role,
+            epoch: state.epoch,
+            peer_last_seen: state.peer_last_seen,
+            held_requests: state.held.len(),
+            promotions: state.promotions,
+            last_error: state.last_error.clone(),
+        }
+    }
+
+    /// Degraded while the peer is unreachable, since there is no failover
+    pub fn health(&self) -> ComponentHealth {
+        let status = self.status();
+        let mut metrics = HashMap::new();
+        metrics.insert("epoch".to_string(), status.epoch as f32);
+        metrics.insert("held_requests".to_string(), status.held_requests as f32);
+        metrics.insert("promotions".to_string(), status.promotions as f32);
+        let (level, message) = match &status.last_error {
+            Some(error) => (
+                HealthLevel::Degraded,
+                format!("{:?} without a reachable peer: {}", status.role, error),
+            ),
+            None => (
+                HealthLevel::Healthy,
+                format!("{:?} at epoch {}", status.role, status.epoch),
+            ),
+        };
+        ComponentHealth {
+            status: level,
+            message,
+            last_check: Utc::now(),
+            metrics,
+        }
+    }
+
+    /// Check a batch's signature against the shared secret
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<()> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let timestamp: i64 = header(HA_TIMESTAMP_HEADER)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| Error::Security("Missing replication timestamp".to_string()))?;
+        if (Utc::now().timestamp() - timestamp).abs() > MAX_SIGNATURE_AGE_SECS {
+            return Err(Error::Security("Stale replication signature".to_string()));
+        }
+        let signature = header(HA_SIGNATURE_HEADER)
+            .ok_or_else(|| Error::Security("Missing replication signature".to_string()))?;
+        webhooks::verify(self.secret(), timestamp, body, signature)
+    }
+
+    fn secret(&self) -> &str {
+        self.config.shared_secret.as_deref().unwrap_or_default()
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, PairState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_task(&self) -> std::sync::MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.task
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Whether a peer at `peer_epoch` takes over from this active gateway
+fn peer_wins(epoch: u64, node_id: &str, peer_epoch: u64, peer_node_id: &str) -> bool {
+    peer_epoch > epoch || (peer_epoch == epoch && peer_node_id < node_id)
+}
+
+/// Replication routes, kept outside caller authentication by `Server` since
+/// batches carry their own signature
+pub fn routes(gateway: Arc<crate::Gateway>) -> Router {
+    Router::new()
+        .route(REPLICATE_PATH, post(replicate))
+        .route("/v1/ha/status", get(status))
+        .with_state(gateway)
+}
+
+pub async fn replicate(State(gateway): State<AppState>, headers: HeaderMap, body: Bytes) -> Response {
+    let high_availability = gateway.high_availability();
+    if !high_availability.enabled() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Err(e) = high_availability.verify(&headers, &body) {
+        warn!("Rejected replication batch: {}", e);
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let batch: ReplicationBatch = match serde_json::from_slice(&body) {
+        Ok(batch) => batch,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": format!("Invalid batch: {}", e) })),
+            )
+                .into_response()
+        },
+    };
+    match high_availability.receive(batch).await {
+        Ok(ack) if ack.accepted => Json(ack).into_response(),
+        Ok(ack) => (StatusCode::CONFLICT, Json(ack)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn status(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(gateway.high_availability().status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::InMemoryQueue;
+    use async_trait::async_trait;
+    use mcp_common::vfs::MemoryVfs;
+    use mcp_common::Config;
+
+    struct NoSecurity;
+
+    #[async_trait]
+    impl SecurityManager for NoSecurity {
+        async fn validate_request(&self, _request: &MCPRequest) -> Result<()> {
+            Ok(())
+        }
+
+        async fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>> {
+            Ok(data.to_vec())
+        }
+
+        async fn decrypt_data(&self, data: &[u8]) -> Result<Vec<u8>> {
+            Ok(data.to_vec())
+        }
+
+        async fn health_check(&self) -> Result<ComponentHealth> {
+            Ok(ComponentHealth {
+                status: HealthLevel::Healthy,
+                message: String::new(),
+                last_check: Utc::now(),
+                metrics: HashMap::new(),
+            })
+        }
+
+        async fn shutdown(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn gateway(
+        node_id: &str,
+        role: HaRole,
+    ) -> (HighAvailability, Arc<ConversationStore>, Arc<InMemoryQueue>) {
+        let mut config = Config::default();
+        config.conversations.enabled = true;
+        let conversations = Arc::new(ConversationStore::new(&config, Arc::new(MemoryVfs::new())));
+        let queue = Arc::new(InMemoryQueue::new(10));
+        let high_availability = HighAvailability::new(
+            HighAvailabilityConfig {
+                enabled: true,
+                node_id: node_id.to_string(),
+                role,
+                ..Default::default()
+            },
+            conversations.clone(),
+            queue.clone(),
+            Arc::new(NoSecurity),
+        );
+        (high_availability, conversations, queue)
+    }
+
+    fn request(session_id: &str) -> MCPRequest {
+        MCPRequest {
+            id: Uuid::new_v4(),
+            device_id: "kiosk-1".to_string(),
+            method: "chat".to_string(),
+            params: HashMap::from([
+                ("session_id".to_string(), serde_json::json!(session_id)),
+                ("prompt".to_string(), serde_json::json!("hello")),
+            ]),
+            context: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Push one batch from `from` to `to` as `push` does, without HTTP
+    async fn replicate(from: &HighAvailability, to: &HighAvailability) -> ReplicationBatch {
+        let batch = from.changes().await.unwrap();
+        let ack = to.receive(batch.clone()).await.unwrap();
+        from.acknowledged(&batch, &ack);
+        batch
+    }
+
+    #[tokio::test]
+    async fn test_standby_takes_over_sessions_and_queued_work() {
+        let (active, active_sessions, active_queue) = gateway("gateway-a", HaRole::Active);
+        let (standby, standby_sessions, standby_queue) = gateway("gateway-b", HaRole::Standby);
+
+        let chat = request("s-1");
+        let turn = active_sessions.turn(&chat).unwrap();
+        let response = mcp_common::MCPResponse {
+            id: chat.id,
+            result: Some(serde_json::json!({ "response": "hi" })),
+            error: None,
+            timestamp: Utc::now(),
+        };
+        active_sessions.record(turn, &response).await;
+        for request in [request("s-2"), request("s-3")] {
+            active_queue.enqueue_request(request.clone()).await.unwrap();
+            active.track_queued(&request);
+        }
+
+        // The first batch only learns the peer's instance; the second sends
+        // everything, and a third has nothing left to send
+        replicate(&active, &standby).await;
+        let batch = replicate(&active, &standby).await;
+        assert_eq!((batch.sessions.len(), batch.queued.len()), (1, 2));
+        assert_eq!(replicate(&active, &standby).await.len(), 0);
+        assert!(standby_sessions.get("s-1").await.unwrap().is_some());
+        assert_eq!(standby.status().held_requests, 2);
+
+        // One request is synced by the active before it fails
+        active_queue.dequeue_request().await.unwrap();
+        let batch = replicate(&active, &standby).await;
+        assert_eq!(batch.dequeued.len(), 1);
+
+        standby.promote().await.unwrap();
+        assert_eq!(standby.role(), HaRole::Active);
+        assert_eq!(standby_queue.pending().len(), 1);
+        assert_eq!(standby.status().epoch, 1);
+    }
+
+    #[tokio::test]
+    async fn test_higher_epoch_demotes_a_returning_active() {
+        let (returning, _, _) = gateway("gateway-a", HaRole::Active);
+        let (promoted, _, _) = gateway("gateway-b", HaRole::Standby);
+        promoted.promote().await.unwrap();
+
+        // The old active is rejected and stands by
+        let batch = returning.changes().await.unwrap();
+        let ack = promoted.receive(batch.clone()).await.unwrap();
+        assert!(!ack.accepted);
+        returning.acknowledged(&batch, &ack);
+        assert_eq!(returning.role(), HaRole::Standby);
+        assert_eq!(returning.status().epoch, 1);
+
+        // At the same epoch the lower node id stays active
+        assert!(peer_wins(1, "gateway-b", 1, "gateway-a"));
+        assert!(!peer_wins(1, "gateway-a", 1, "gateway-b"));
+        assert!(!peer_wins(2, "gateway-b", 1, "gateway-a"));
+    }
+}
@@ -0,0 +1,354 @@
+//! Deduplication of retried requests by idempotency key
+//!
+//! Clients on flaky links retry requests whose response they never saw,
+//! which runs the completion (and pays for the cloud call) twice. A request
+//! carrying an idempotency key, from the `Idempotency-Key` header or its
+//! context, claims that key for its device. A retry arriving while the first
+//! attempt is still running waits for it and shares its response; one
+//! arriving after it completed gets the stored response for `window_secs`.
+//! Failed attempts are not stored, so the next retry runs the request again.
+//!
+//! Each key remembers a fingerprint of the method and params it was first
+//! used with. Reusing the key for a different request is rejected with
+//! [`Error::IdempotencyKeyReused`] rather than answered with the earlier
+//! response.
+
+use chrono::{DateTime, Utc};
+use mcp_common::clock::{self, Clock};
+use mcp_common::config::IdempotencyConfig;
+use mcp_common::crypto::digest;
+use mcp_common::{Error, MCPRequest, MCPResponse, RequestId, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::debug;
+
+/// HTTP header carrying a request's idempotency key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Longest idempotency key accepted
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// SHA-256 of a request's method and params
+type Fingerprint = Vec<u8>;
+
+enum Entry {
+    /// The first attempt is running; its response is sent once it succeeds
+    InFlight {
+        receiver: watch::Receiver<Option<MCPResponse>>,
+        fingerprint: Fingerprint,
+    },
+    Completed {
+        response: MCPResponse,
+        fingerprint: Fingerprint,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+impl Entry {
+    fn fingerprint(&self) -> &Fingerprint {
+        match self {
+            Entry::InFlight { fingerprint, .. } | Entry::Completed { fingerprint, .. } => fingerprint,
+        }
+    }
+}
+
+enum Claim {
+    Completed(MCPResponse),
+    Wait(watch::Receiver<Option<MCPResponse>>),
+    Run(Flight),
+}
+
+/// Claim on a key held by the attempt that runs the request; dropping it
+/// without finishing releases the key for a retry
+struct Flight {
+    key: String,
+    fingerprint: Fingerprint,
+    sender: watch::Sender<Option<MCPResponse>>,
+    cache: Arc<Mutex<HashMap<String, Entry>>>,
+    finished: bool,
+}
+
+impl Drop for Flight {
+    fn drop(&mut self) {
+        if !self.finished {
+            lock(&self.cache).remove(&self.key);
+        }
+    }
+}
+
+/// Responses of recent requests by idempotency key
+pub struct IdempotencyCache {
+    config: IdempotencyConfig,
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl IdempotencyCache {
+    pub fn new(config: IdempotencyConfig) -> Self {
+        Self::with_clock(config, clock::system_clock())
+    }
+
+    pub fn with_clock(config: IdempotencyConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            clock,
+        }
+    }
+
+    /// Run `process` on `request` unless an attempt with the same key
+    /// already succeeded or is running, in which case its response is
+    /// returned under this request's ID. Fails without running `process`
+    /// when the key was used for a request with another method or params
+    pub async fn run<F, Fut>(&self, request: MCPRequest, process: F) -> Result<MCPResponse>
+    where
+        F: FnOnce(MCPRequest) -> Fut,
+        Fut: Future<Output = Result<MCPResponse>>,
+    {
+        let Some(key) = self.key(&request) else {
+            return process(request).await;
+        };
+        let request_id = request.id;
+        let fingerprint = fingerprint(&request)?;
+        let mut flight = loop {
+            match self.claim(&key, &fingerprint)? {
+                Claim::Completed(response) => {
+                    debug!("Request {} repeats a completed request, returning its response", request_id);
+                    return Ok(replayed(response, request_id));
+                },
+                Claim::Wait(mut receiver) => {
+                    debug!("Request {} repeats a running request, waiting for it", request_id);
+                    let response = loop {
+                        if let Some(response) = receiver.borrow().clone() {
+                            break Some(response);
+                        }
+                        if receiver.changed().await.is_err() {
+                            break receiver.borrow().clone();
+                        }
+                    };
+                    if let Some(response) = response {
+                        return Ok(replayed(response, request_id));
+                    }
+                    // The first attempt failed or was cancelled; take over
+                },
+                Claim::Run(flight) => break flight,
+            }
+        };
+
+        let result = process(request).await;
+        if let Ok(response) = &result {
+            flight.finished = true;
+            let expires_at = self.clock.now() + chrono::Duration::seconds(self.config.window_secs as i64);
+            lock(&self.entries).insert(
+                key,
+                Entry::Completed {
+                    response: response.clone(),
+                    fingerprint: std::mem::take(&mut flight.fingerprint),
+                    expires_at,
+                },
+            );
+            let _ = flight.sender.send(Some(response.clone()));
+        }
+        result
+    }
+
+    /// Key of `request` scoped to its device, if it has one and dedup is on
+    fn key(&self, request: &MCPRequest) -> Option<String> {
+        let key = request.idempotency_key().filter(|_| self.config.enabled)?;
+        Some(format!("{}\n{}", request.device_id, key))
+    }
+
+    fn claim(&self, key: &str, fingerprint: &Fingerprint) -> Result<Claim> {
+        let now = self.clock.now();
+        let mut entries = lock(&self.entries);
+        let current = entries
+            .get(key)
+            .filter(|entry| !matches!(entry, Entry::Completed { expires_at, .. } if *expires_at <= now));
+        if let Some(entry) = current {
+            if entry.fingerprint() != fingerprint {
+                return Err(Error::IdempotencyKeyReused(
+                    "the key was used for a request with a different method or params".to_string(),
+                ));
+            }
+            return Ok(match entry {
+                Entry::Completed { response, .. } => Claim::Completed(response.clone()),
+                Entry::InFlight { receiver, .. } => Claim::Wait(receiver.clone()),
+            });
+        }
+
+        if entries.len() >= self.config.max_entries {
+            entries.retain(|_, entry| !matches!(entry, Entry::Completed { expires_at, .. } if *expires_at <= now));
+        }
+        if entries.len() >= self.config.max_entries {
+            let oldest = entries
+                .iter()
+                .filter_map(|(key, entry)| match entry {
+                    Entry::Completed { expires_at, .. } => Some((*expires_at, key.clone())),
+                    Entry::InFlight { .. } => None,
+                })
+                .min();
+            if let Some((_, oldest)) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        entries.insert(
+            key.to_string(),
+            Entry::InFlight {
+                receiver,
+                fingerprint: fingerprint.clone(),
+            },
+        );
+        Ok(Claim::Run(Flight {
+            key: key.to_string(),
+            fingerprint: fingerprint.clone(),
+            sender,
+            cache: self.entries.clone(),
+            finished: false,
+        }))
+    }
+
+    /// Requests running and responses kept
+    pub fn len(&self) -> usize {
+        lock(&self.entries).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Digest of the method and params; params go through `Value` so object
+/// keys are serialized sorted
+fn fingerprint(request: &MCPRequest) -> Result<Fingerprint> {
+    let params = serde_json::to_value(&request.params)?;
+    let bytes = serde_json::to_vec(&(&request.method, params))?;
+    Ok(digest::digest(&digest::SHA256, &bytes).as_ref().to_vec())
+}
+
+/// A stored response answering a retry
+fn replayed(mut response: MCPResponse, request_id: RequestId) -> MCPResponse {
+    response.id = request_id;
+    response
+}
+
+fn lock(entries: &Mutex<HashMap<String, Entry>>) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+    entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::clock::FakeClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn request(key: Option<&str>) -> MCPRequest {
+        let mut request = MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "sensor-1".to_string(),
+            method: "completion".to_string(),
+            params: HashMap::new(),
+            context: None,
+            timestamp: Utc::now(),
+        };
+        if let Some(key) = key {
+            request.set_idempotency_key(key.to_string());
+        }
+        request
+    }
+
+    fn response(text: &str) -> MCPResponse {
+        MCPResponse {
+            id: uuid::Uuid::new_v4(),
+            result: Some(serde_json::json!({ "text": text })),
+            error: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_gets_the_completed_response_within_the_window() {
+        let clock = Arc::new(FakeClock::default());
+        let cache = IdempotencyCache::with_clock(IdempotencyConfig::default(), clock.clone());
+        let process = |text: &'static str| move |_: MCPRequest| async move { Ok::<_, Error>(response(text)) };
+
+        let first = cache.run(request(Some("abc")), process("first")).await.unwrap();
+        let retry = request(Some("abc"));
+        let retry_id = retry.id;
+        let replay = cache.run(retry, process("second")).await.unwrap();
+        assert_eq!(replay.result, first.result);
+        assert_eq!(replay.id, retry_id);
+
+        // Requests without a key are never deduplicated
+        let unkeyed = cache.run(request(None), process("unkeyed")).await.unwrap();
+        assert_eq!(unkeyed.result.unwrap()["text"], "unkeyed");
+
+        clock.advance(chrono::Duration::seconds(601));
+        let expired = cache.run(request(Some("abc")), process("third")).await.unwrap();
+        assert_eq!(expired.result.unwrap()["text"], "third");
+    }
+
+    #[tokio::test]
+    async fn test_retry_attaches_to_the_running_attempt() {
+        let cache = IdempotencyCache::new(IdempotencyConfig::default());
+        let runs = AtomicUsize::new(0);
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let first = cache.run(request(Some("abc")), |_| async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            released.await.unwrap();
+            Ok(response("shared"))
+        });
+        let retry = cache.run(request(Some("abc")), |_| async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            Ok(response("again"))
+        });
+        let release = async {
+            tokio::task::yield_now().await;
+            release.send(()).unwrap();
+        };
+
+        let (first, retry, ()) = tokio::join!(first, retry, release);
+        assert_eq!(first.unwrap().result, retry.unwrap().result);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_attempt_is_not_stored() {
+        let cache = IdempotencyCache::new(IdempotencyConfig::default());
+        let failed = cache
+            .run(request(Some("abc")), |_| async { Err(Error::Network("link lost".to_string())) })
+            .await;
+        assert!(failed.is_err());
+        assert!(cache.is_empty());
+
+        let retried = cache.run(request(Some("abc")), |_| async { Ok(response("retried")) }).await.unwrap();
+        assert_eq!(retried.result.unwrap()["text"], "retried");
+    }
+
+    #[tokio::test]
+    async fn test_key_reused_for_another_request_is_rejected() {
+        let cache = IdempotencyCache::new(IdempotencyConfig::default());
+        let mut first = request(Some("abc"));
+        first.params.insert("prompt".to_string(), serde_json::json!("close valve 3"));
+        cache.run(first.clone(), |_| async { Ok(response("closed")) }).await.unwrap();
+
+        let mut other = request(Some("abc"));
+        other.params.insert("prompt".to_string(), serde_json::json!("open valve 3"));
+        let runs = AtomicUsize::new(0);
+        let rejected = cache
+            .run(other, |_| async {
+                runs.fetch_add(1, Ordering::SeqCst);
+                Ok(response("opened"))
+            })
+            .await;
+        assert!(matches!(rejected, Err(Error::IdempotencyKeyReused(_))));
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        // The same request under the key is still answered from the cache
+        let replay = cache.run(first, |_| async { Ok(response("again")) }).await.unwrap();
+        assert_eq!(replay.result.unwrap()["text"], "closed");
+    }
+}
@@ -0,0 +1,745 @@
+//! Persistent key-value store for tool handlers and extensions
+//!
+//! Small automations keep their state here instead of in an external
+//! database. Every caller works in a namespace — extensions get
+//! `ext.<name>` — limited in total size and key count, and entries may
+//! expire after a TTL. Reads and writes are served from memory; namespaces
+//! that changed are saved to one file each every `flush_interval_secs` and on
+//! shutdown, so a busy tool does not wear out flash storage. Expired entries
+//! are dropped on read and purged before each save.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use mcp_common::clock::{self, Clock};
+use mcp_common::config::KvStoreConfig;
+use mcp_common::write_policy::WritePolicy;
+use mcp_common::{ComponentHealth, Error, HealthLevel, Result, Vfs};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Longest namespace name, which is also its file name
+const MAX_NAMESPACE_LEN: usize = 64;
+
+/// Longest key
+const MAX_KEY_LEN: usize = 256;
+
+/// Usage of one namespace
+#[derive(Debug, Clone, Serialize)]
+pub struct NamespaceUsage {
+    pub namespace: String,
+    pub keys: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+}
+
+/// A key as listed for operators, without its value
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyInfo {
+    pub key: String,
+    pub size: usize,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A key taken out of a namespace, with what it takes to put it back
+#[derive(Debug, Clone)]
+pub struct RemovedKey {
+    pub key: String,
+    pub value: Vec<u8>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+struct Entry {
+    value: Vec<u8>,
+    updated_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl Entry {
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+#[derive(Default)]
+struct Namespace {
+    entries: BTreeMap<String, Entry>,
+    /// Total size of keys and values
+    bytes: u64,
+    /// Changed since it was last saved
+    dirty: bool,
+}
+
+impl Namespace {
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.bytes -= (key.len() + entry.value.len()) as u64;
+        self.dirty = true;
+        Some(entry)
+    }
+
+    fn purge(&mut self, now: DateTime<Utc>) -> usize {
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.expired(now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            self.remove(key);
+        }
+        expired.len()
+    }
+}
+
+/// A namespace as saved to storage
+#[derive(Serialize, Deserialize)]
+struct StoredNamespace {
+    namespace: String,
+    entries: BTreeMap<String, StoredEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredEntry {
+    /// Base64-encoded value
+    value: String,
+    updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct KvStats {
+    reads: AtomicU64,
+    hits: AtomicU64,
+    writes: AtomicU64,
+    rejected: AtomicU64,
+    expired: AtomicU64,
+    flush_failures: AtomicU64,
+    /// The last save of some namespace failed
+    flush_failing: AtomicBool,
+}
+
+/// Namespaced key-value store saved to gateway storage
+pub struct KvStore {
+    config: KvStoreConfig,
+    storage: Arc<dyn Vfs>,
+    clock: Arc<dyn Clock>,
+    namespaces: Mutex<HashMap<String, Namespace>>,
+    /// Size limits that replace `max_bytes_per_namespace`, by namespace
+    limits: Mutex<HashMap<String, u64>>,
+    stats: KvStats,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl KvStore {
+    pub fn new(config: KvStoreConfig, storage: Arc<dyn Vfs>) -> Self {
+        Self::with_clock(config, storage, clock::system_clock())
+    }
+
+    pub fn with_clock(config: KvStoreConfig, storage: Arc<dyn Vfs>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            storage,
+            clock,
+            namespaces: Mutex::new(HashMap::new()),
+            limits: Mutex::new(HashMap::new()),
+            stats: KvStats::default(),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Handle confined to one namespace, for a tool handler or extension
+    pub fn namespace(self: &Arc<Self>, namespace: &str) -> Result<KvNamespace> {
+        check_namespace(namespace)?;
+        Ok(KvNamespace {
+            store: Arc::clone(self),
+            namespace: namespace.to_string(),
+        })
+    }
+
+    /// Limit `namespace` to `max_bytes` instead of `max_bytes_per_namespace`
+    pub fn set_limit(&self, namespace: &str, max_bytes: u64) {
+        self.limits.lock().insert(namespace.to_string(), max_bytes);
+    }
+
+    fn max_bytes(&self, namespace: &str) -> u64 {
+        self.limits
+            .lock()
+            .get(namespace)
+            .copied()
+            .unwrap_or(self.config.max_bytes_per_namespace)
+    }
+
+    /// Load the namespaces saved by the previous run, dropping expired entries
+    pub async fn restore(&self) {
+        let files = match self.storage.list_dir(&self.config.directory).await {
+            Ok(files) => files,
+            Err(e) => {
+                if self.storage.exists(&self.config.directory).await {
+                    warn!("Failed to list key-value namespaces: {}", e);
+                }
+                return;
+            },
+        };
+
+        let now = self.clock.now();
+        let mut restored = 0;
+        for path in files
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        {
+            let stored: StoredNamespace = match self
+                .storage
+                .read(&path)
+                .await
+                .and_then(|data| Ok(serde_json::from_slice(&data)?))
+            {
+                Ok(stored) => stored,
+                Err(e) => {
+                    warn!(
+                        "Skipping unreadable key-value namespace {}: {}",
+                        path.display(),
+                        e
+                    );
+                    continue;
+                },
+            };
+            let mut namespace = Namespace::default();
+            for (key, entry) in stored.entries {
+                let Ok(value) = BASE64.decode(&entry.value) else {
+                    warn!(
+                        "Skipping corrupt key {} in namespace {}",
+                        key, stored.namespace
+                    );
+                    continue;
+                };
+                let entry = Entry {
+                    value,
+                    updated_at: entry.updated_at,
+                    expires_at: entry.expires_at,
+                };
+                if entry.expired(now) {
+                    namespace.dirty = true;
+                    continue;
+                }
+                namespace.bytes += (key.len() + entry.value.len()) as u64;
+                namespace.entries.insert(key, entry);
+            }
+            restored += 1;
+            self.namespaces.lock().insert(stored.namespace, namespace);
+        }
+        if restored > 0 {
+            info!(
+                "Restored {} key-value namespaces from {}",
+                restored,
+                self.config.directory.display()
+            );
+        }
+    }
+
+    /// Save changed namespaces periodically in the background, less often
+    /// while the write policy is relaxed
+    pub fn start(self: &Arc<Self>) {
+        let store = Arc::downgrade(self);
+        let interval_secs = self.config.flush_interval_secs.max(1);
+        let handle = tokio::spawn(async move {
+            loop {
+                let interval = WritePolicy::global().stretch(Duration::from_secs(interval_secs));
+                tokio::time::sleep(interval).await;
+                let Some(store) = store.upgrade() else {
+                    break;
+                };
+                if let Err(e) = store.flush().await {
+                    warn!("Failed to save key-value store: {}", e);
+                }
+            }
+        });
+        if let Some(previous) = self.task.lock().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stop the periodic save and save one last time
+    pub async fn stop(&self) {
+        if let Some(handle) = self.task.lock().take() {
+            handle.abort();
+        }
+        if let Err(e) = self.flush().await {
+            warn!("Failed to save key-value store on shutdown: {}", e);
+        }
+    }
+
+    /// Purge expired entries and save every namespace changed since the last save
+    pub async fn flush(&self) -> Result<()> {
+        let now = self.clock.now();
+        let changed: Vec<(String, Option<Vec<u8>>)> = {
+            let mut namespaces = self.namespaces.lock();
+            let mut changed = Vec::new();
+            for (name, namespace) in namespaces.iter_mut() {
+                let purged = namespace.purge(now);
+                self.stats
+                    .expired
+                    .fetch_add(purged as u64, Ordering::Relaxed);
+                if !namespace.dirty {
+                    continue;
+                }
+                namespace.dirty = false;
+                if namespace.entries.is_empty() {
+                    changed.push((name.clone(), None));
+                    continue;
+                }
+                let stored = StoredNamespace {
+                    namespace: name.clone(),
+                    entries: namespace
+                        .entries
+                        .iter()
+                        .map(|(key, entry)| {
+                            let stored = StoredEntry {
+                                value: BASE64.encode(&entry.value),
+                                updated_at: entry.updated_at,
+                                expires_at: entry.expires_at,
+                            };
+                            (key.clone(), stored)
+                        })
+                        .collect(),
+                };
+                changed.push((name.clone(), Some(serde_json::to_vec(&stored)?)));
+            }
+            namespaces.retain(|_, namespace| !namespace.entries.is_empty());
+            changed
+        };
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        self.storage.create_dir_all(&self.config.directory).await?;
+        let mut result = Ok(());
+        for (name, data) in changed {
+            let saved = self.save(&name, data).await;
+            if let Err(e) = saved {
+                self.stats.flush_failures.fetch_add(1, Ordering::Relaxed);
+                // Try again on the next flush
+                if let Some(namespace) = self.namespaces.lock().get_mut(&name) {
+                    namespace.dirty = true;
+                }
+                result = Err(e);
+            }
+        }
+        self.stats
+            .flush_failing
+            .store(result.is_err(), Ordering::Relaxed);
+        result
+    }
+
+    async fn save(&self, namespace: &str, data: Option<Vec<u8>>) -> Result<()> {
+        let path = self.path(namespace);
+        match data {
+            Some(data) => {
+                let tmp = path.with_extension("json.tmp");
+                self.storage.write(&tmp, &data).await?;
+                self.storage.rename(&tmp, &path).await
+            },
+            None if self.storage.exists(&path).await => self.storage.remove(&path).await,
+            None => Ok(()),
+        }
+    }
+
+    fn path(&self, namespace: &str) -> PathBuf {
+        self.config.directory.join(format!("{}.json", namespace))
+    }
+
+    /// Value of `key`, unless it is missing or expired
+    pub fn get(&self, namespace: &str, key: &str) -> Option<Vec<u8>> {
+        self.stats.reads.fetch_add(1, Ordering::Relaxed);
+        let now = self.clock.now();
+        let mut namespaces = self.namespaces.lock();
+        let entries = namespaces.get_mut(namespace)?;
+        let entry = entries.entries.get(key)?;
+        if entry.expired(now) {
+            entries.remove(key);
+            self.stats.expired.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        self.stats.hits.fetch_add(1, Ordering::Relaxed);
+        Some(entry.value.clone())
+    }
+
+    /// Store `value` under `key`, expiring after `ttl` when given; fails when
+    /// the namespace would exceed its size or key limit
+    pub fn set(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> Result<()> {
+        check_namespace(namespace)?;
+        if key.is_empty() || key.len() > MAX_KEY_LEN {
+            return Err(Error::InvalidRequest(format!(
+                "Keys must be 1 to {} bytes",
+                MAX_KEY_LEN
+            )));
+        }
+        let now = self.clock.now();
+        let expires_at = match ttl {
+            Some(ttl) => {
+                if let Some(max_ttl) = self
+                    .config
+                    .max_ttl_secs
+                    .filter(|max_ttl| ttl.as_secs() > *max_ttl)
+                {
+                    return Err(Error::InvalidRequest(format!(
+                        "TTL is limited to {}s",
+                        max_ttl
+                    )));
+                }
+                let ttl = chrono::Duration::from_std(ttl)
+                    .map_err(|_| Error::InvalidRequest("TTL is too long".to_string()))?;
+                Some(now + ttl)
+            },
+            None => None,
+        };
+        let max_bytes = self.max_bytes(namespace);
+
+        let mut namespaces = self.namespaces.lock();
+        let entries = namespaces.entry(namespace.to_string()).or_default();
+        self.stats
+            .expired
+            .fetch_add(entries.purge(now) as u64, Ordering::Relaxed);
+        let replaced = entries
+            .entries
+            .get(key)
+            .map_or(0, |old| (key.len() + old.value.len()) as u64);
+        let bytes = entries.bytes - replaced + (key.len() + value.len()) as u64;
+        if bytes > max_bytes {
+            self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(Error::ResourceExhausted(format!(
+                "Key-value namespace {} is limited to {} bytes",
+                namespace, max_bytes
+            )));
+        }
+        if replaced == 0 && entries.entries.len() >= self.config.max_keys_per_namespace {
+            self.stats.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(Error::ResourceExhausted(format!(
+                "Key-value namespace {} is limited to {} keys",
+                namespace, self.config.max_keys_per_namespace
+            )));
+        }
+
+        entries.bytes = bytes;
+        entries.dirty = true;
+        entries.entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                updated_at: now,
+                expires_at,
+            },
+        );
+        self.stats.writes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Remove `key`, returning whether it existed
+    pub fn delete(&self, namespace: &str, key: &str) -> bool {
+        self.remove(namespace, key).is_some()
+    }
+
+    /// Remove `key` and return it, unless it was missing or expired
+    pub fn remove(&self, namespace: &str, key: &str) -> Option<RemovedKey> {
+        let now = self.clock.now();
+        let mut namespaces = self.namespaces.lock();
+        let entry = namespaces.get_mut(namespace)?.remove(key)?;
+        (!entry.expired(now)).then(|| RemovedKey {
+            key: key.to_string(),
+            value: entry.value,
+            expires_at: entry.expires_at,
+        })
+    }
+
+    /// Remove every key in `namespace`, returning how many there were
+    pub fn clear(&self, namespace: &str) -> usize {
+        let mut namespaces = self.namespaces.lock();
+        let Some(entries) = namespaces.get_mut(namespace) else {
+            return 0;
+        };
+        let keys = entries.entries.len();
+        entries.entries.clear();
+        entries.bytes = 0;
+        entries.dirty = true;
+        keys
+    }
+
+    /// Remove every key in `namespace`, returning the ones that had not expired
+    pub fn drain(&self, namespace: &str) -> Vec<RemovedKey> {
+        let now = self.clock.now();
+        let mut namespaces = self.namespaces.lock();
+        let Some(entries) = namespaces.get_mut(namespace) else {
+            return Vec::new();
+        };
+        entries.bytes = 0;
+        entries.dirty = true;
+        std::mem::take(&mut entries.entries)
+            .into_iter()
+            .filter(|(_, entry)| !entry.expired(now))
+            .map(|(key, entry)| RemovedKey {
+                key,
+                value: entry.value,
+                expires_at: entry.expires_at,
+            })
+            .collect()
+    }
+
+    /// Keys in `namespace` that have not expired, in order
+    pub fn keys(&self, namespace: &str) -> Vec<KeyInfo> {
+        let now = self.clock.now();
+        let namespaces = self.namespaces.lock();
+        let Some(entries) = namespaces.get(namespace) else {
+            return Vec::new();
+        };
+        entries
+            .entries
+            .iter()
+            .filter(|(_, entry)| !entry.expired(now))
+            .map(|(key, entry)| KeyInfo {
+                key: key.clone(),
+                size: entry.value.len(),
+                updated_at: entry.updated_at,
+                expires_at: entry.expires_at,
+            })
+            .collect()
+    }
+
+    /// Usage of every namespace holding keys, by name
+    pub fn namespaces(&self) -> Vec<NamespaceUsage> {
+        let mut usage: Vec<NamespaceUsage> = self
+            .namespaces
+            .lock()
+            .iter()
+            .filter(|(_, namespace)| !namespace.entries.is_empty())
+            .map(|(name, namespace)| NamespaceUsage {
+                namespace: name.clone(),
+                keys: namespace.entries.len(),
+                bytes: namespace.bytes,
+                max_bytes: 0,
+            })
+            .collect();
+        for namespace in &mut usage {
+            namespace.max_bytes = self.max_bytes(&namespace.namespace);
+        }
+        usage.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+        usage
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let (namespaces, keys, bytes) = {
+            let namespaces = self.namespaces.lock();
+            let keys: usize = namespaces
+                .values()
+                .map(|namespace| namespace.entries.len())
+                .sum();
+            let bytes: u64 = namespaces.values().map(|namespace| namespace.bytes).sum();
+            (namespaces.len(), keys, bytes)
+        };
+        let stats = &self.stats;
+        metrics.insert("kv_namespaces".to_string(), namespaces as f32);
+        metrics.insert("kv_keys".to_string(), keys as f32);
+        metrics.insert("kv_bytes".to_string(), bytes as f32);
+        metrics.insert(
+            "kv_reads_total".to_string(),
+            stats.reads.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "kv_hits_total".to_string(),
+            stats.hits.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "kv_writes_total".to_string(),
+            stats.writes.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "kv_rejected_total".to_string(),
+            stats.rejected.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "kv_expired_total".to_string(),
+            stats.expired.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "kv_flush_failures_total".to_string(),
+            stats.flush_failures.load(Ordering::Relaxed) as f32,
+        );
+    }
+
+    pub fn health(&self) -> ComponentHealth {
+        let mut metrics = HashMap::new();
+        self.write_metrics(&mut metrics);
+        let (status, message) = if self.stats.flush_failing.load(Ordering::Relaxed) {
+            (
+                HealthLevel::Degraded,
+                "Failed to save changed namespaces".to_string(),
+            )
+        } else {
+            (
+                HealthLevel::Healthy,
+                format!(
+                    "{} keys in {} namespaces",
+                    metrics["kv_keys"], metrics["kv_namespaces"]
+                ),
+            )
+        };
+        ComponentHealth {
+            status,
+            message,
+            last_check: self.clock.now(),
+            metrics,
+        }
+    }
+}
+
+/// Namespace names double as file names
+fn check_namespace(namespace: &str) -> Result<()> {
+    let valid = !namespace.is_empty()
+        && namespace.len() <= MAX_NAMESPACE_LEN
+        && !namespace.starts_with('.')
+        && namespace
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidRequest(format!(
+            "Invalid key-value namespace {:?}: use up to {} letters, digits, '-', '_' and '.'",
+            namespace, MAX_NAMESPACE_LEN
+        )))
+    }
+}
+
+/// One namespace of the [`KvStore`], handed to a single tool or extension
+#[derive(Clone)]
+pub struct KvNamespace {
+    store: Arc<KvStore>,
+    namespace: String,
+}
+
+impl KvNamespace {
+    pub fn name(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.store.get(&self.namespace, key)
+    }
+
+    pub fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> Result<()> {
+        self.store.set(&self.namespace, key, value, ttl)
+    }
+
+    pub fn delete(&self, key: &str) -> bool {
+        self.store.delete(&self.namespace, key)
+    }
+
+    pub fn keys(&self) -> Vec<KeyInfo> {
+        self.store.keys(&self.namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::clock::FakeClock;
+    use mcp_common::vfs::MemoryVfs;
+
+    fn store(storage: Arc<dyn Vfs>, clock: Arc<FakeClock>) -> Arc<KvStore> {
+        let config = KvStoreConfig {
+            max_bytes_per_namespace: 64,
+            max_keys_per_namespace: 3,
+            ..KvStoreConfig::default()
+        };
+        Arc::new(KvStore::with_clock(config, storage, clock))
+    }
+
+    #[tokio::test]
+    async fn namespaces_are_limited_and_expire_entries() {
+        let clock = Arc::new(FakeClock::default());
+        let kv = store(Arc::new(MemoryVfs::new()), clock.clone());
+        let counter = kv.namespace("counter-tool").unwrap();
+        let other = kv.namespace("ext.scrubber").unwrap();
+        assert!(kv.namespace("../etc").is_err());
+
+        counter.set("count", b"1".to_vec(), None).unwrap();
+        counter
+            .set("session", b"abc".to_vec(), Some(Duration::from_secs(60)))
+            .unwrap();
+        assert_eq!(counter.get("count"), Some(b"1".to_vec()));
+        assert_eq!(other.get("count"), None);
+
+        // Size and key-count limits are per namespace
+        let error = counter.set("big", vec![0; 64], None).unwrap_err();
+        assert!(matches!(error, Error::ResourceExhausted(_)), "{}", error);
+        counter.set("third", Vec::new(), None).unwrap();
+        assert!(counter.set("fourth", Vec::new(), None).is_err());
+        counter.set("count", b"2".to_vec(), None).unwrap();
+        other.set("fourth", Vec::new(), None).unwrap();
+
+        kv.set_limit("ext.scrubber", 8);
+        assert!(other.set("key", vec![0; 8], None).is_err());
+
+        clock.advance(chrono::Duration::seconds(61));
+        assert_eq!(counter.get("session"), None);
+        let keys: Vec<String> = counter.keys().into_iter().map(|key| key.key).collect();
+        assert_eq!(keys, vec!["count", "third"]);
+        assert!(counter.delete("count"));
+        assert!(!counter.delete("count"));
+    }
+
+    #[tokio::test]
+    async fn flushes_changed_namespaces_and_restores_them() {
+        let storage: Arc<dyn Vfs> = Arc::new(MemoryVfs::new());
+        let clock = Arc::new(FakeClock::default());
+        let kv = store(storage.clone(), clock.clone());
+        kv.set("tool-a", "state", b"{\"n\":1}".to_vec(), None)
+            .unwrap();
+        kv.set(
+            "tool-a",
+            "lease",
+            b"x".to_vec(),
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap();
+        kv.set("tool-b", "gone", b"y".to_vec(), None).unwrap();
+        kv.flush().await.unwrap();
+        assert!(
+            storage
+                .exists(&PathBuf::from("./data/kv/tool-b.json"))
+                .await
+        );
+
+        // An emptied namespace loses its file
+        kv.clear("tool-b");
+        kv.flush().await.unwrap();
+        assert!(
+            !storage
+                .exists(&PathBuf::from("./data/kv/tool-b.json"))
+                .await
+        );
+
+        clock.advance(chrono::Duration::seconds(11));
+        let restored = store(storage, clock);
+        restored.restore().await;
+        assert_eq!(restored.get("tool-a", "state"), Some(b"{\"n\":1}".to_vec()));
+        assert_eq!(restored.get("tool-a", "lease"), None);
+        let usage = restored.namespaces();
+        assert_eq!(usage.len(), 1);
+        assert_eq!((usage[0].keys, usage[0].bytes), (1, 12));
+    }
+}
@@ -3,14 +3,62 @@
 //! This crate provides the main gateway functionality including request handling,
 //! component orchestration, and the REST/WebSocket APIs.
 
+pub mod admin;
+pub mod admin_journal;
+pub mod admission;
+pub mod artifacts;
+pub mod audio;
+pub mod audit;
+pub mod auth;
+pub mod bandwidth;
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub mod browser;
+pub mod builder;
+pub mod capabilities;
 pub mod circuit_breaker;
+pub mod clock_skew;
+pub mod cluster;
+pub mod compliance;
+pub mod config_reload;
+pub mod connectors;
+pub mod conversations;
+pub mod emulation;
+pub mod erasure;
+pub mod extensions;
 pub mod gateway;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
 pub mod handlers;
 pub mod health;
+pub mod hedging;
+pub mod high_availability;
+pub mod idempotency;
+pub mod kv;
+pub mod listener;
+pub mod load_model;
+pub mod maintenance;
+pub mod mesh;
 pub mod middleware;
+pub mod normalization;
 pub mod performance;
+pub mod peripherals;
+pub mod power;
+pub mod priority_latency;
+pub mod probes;
+pub mod provenance;
+pub mod retention;
+pub mod scheduler;
 pub mod server;
+pub mod sessions;
+pub mod storage_health;
+pub mod synthetic;
+pub mod testing;
+pub mod timeline;
+pub mod webhooks;
 
+pub use builder::GatewayBuilder;
 pub use gateway::Gateway;
 pub use server::{AppState, Server};
 
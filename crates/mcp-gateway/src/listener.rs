@@ -0,0 +1,104 @@
+//! Listener setup with IPv6 and dual-stack support
+//!
+//! Binding the IPv6 wildcard address only accepts IPv4 clients when the
+//! socket has `IPV6_V6ONLY` cleared, and the default for that flag differs
+//! between systems. The flag is therefore set explicitly from
+//! `network.dual_stack`. Hosts with IPv6 disabled fall back to the IPv4
+//! wildcard instead of failing to start.
+
+use mcp_common::config::NetworkConfig;
+use mcp_common::{Error, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+/// Connections waiting to be accepted before the kernel refuses new ones
+const BACKLOG: i32 = 1024;
+
+/// Join a configured host and port, bracketing bare IPv6 addresses
+pub fn listen_address(host: &str, port: u16) -> String {
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(address)) => format!("[{}]:{}", address, port),
+        _ => format!("{}:{}", host, port),
+    }
+}
+
+/// Bind the gateway listener
+pub async fn bind(bind_addr: &str, network: &NetworkConfig) -> Result<TcpListener> {
+    let address = match bind_addr.parse::<SocketAddr>() {
+        Ok(address) => address,
+        // Host names are resolved and bound as-is
+        Err(_) => {
+            return TcpListener::bind(bind_addr)
+                .await
+                .map_err(|e| Error::Network(format!("Failed to bind to {}: {}", bind_addr, e)));
+        },
+    };
+
+    match bind_socket(address, network.dual_stack) {
+        Ok(listener) => {
+            if address.is_ipv6() && address.ip().is_unspecified() {
+                let families = if network.dual_stack { "IPv6 and IPv4" } else { "IPv6 only" };
+                info!("Listening on {} ({})", address, families);
+            }
+            Ok(listener)
+        },
+        Err(e) if address.is_ipv6() && address.ip().is_unspecified() && is_family_unsupported(&e) => {
+            let fallback = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), address.port());
+            warn!("IPv6 is not available on this host, listening on {} instead", fallback);
+            bind_socket(fallback, network.dual_stack)
+                .map_err(|e| Error::Network(format!("Failed to bind to {}: {}", fallback, e)))
+        },
+        Err(e) => Err(Error::Network(format!("Failed to bind to {}: {}", address, e))),
+    }
+}
+
+fn bind_socket(address: SocketAddr, dual_stack: bool) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+    if address.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Whether binding failed because the host lacks IPv6 rather than because the
+/// port is unavailable
+fn is_family_unsupported(error: &std::io::Error) -> bool {
+    !matches!(
+        error.kind(),
+        std::io::ErrorKind::AddrInUse | std::io::ErrorKind::PermissionDenied
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn test_ipv6_hosts_are_bracketed() {
+        assert_eq!(listen_address("::", 8080), "[::]:8080");
+        assert_eq!(listen_address("0.0.0.0", 8080), "0.0.0.0:8080");
+        assert_eq!(listen_address("gateway.local", 80), "gateway.local:80");
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack_listener_accepts_both_families() {
+        let network = NetworkConfig::default();
+        let listener = bind("[::]:0", &network).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        if listener.local_addr().unwrap().is_ipv4() {
+            // IPv6 is disabled on this host; the fallback still serves IPv4
+            assert!(TcpStream::connect(("127.0.0.1", port)).await.is_ok());
+            return;
+        }
+
+        assert!(TcpStream::connect(("127.0.0.1", port)).await.is_ok());
+        assert!(TcpStream::connect(("::1", port)).await.is_ok());
+    }
+}
@@ -0,0 +1,219 @@
+//! Load models extracted from recorded traffic
+//!
+//! Capacity tests should send the traffic a site actually sees rather than a
+//! flat request rate. [`LoadModel::from_digests`] summarizes the decrypted
+//! digests of an exported audit trail, which hold one entry per request,
+//! into its arrival process, request size distribution and method mix.
+//! `mcp-audit load-model` writes the model as JSON for a load generator to
+//! draw requests from. Digests never hold payloads, so the model describes
+//! how large requests are, not what they contain.
+
+use crate::audit::AuditDigest;
+use chrono::{DateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Format version of [`LoadModel`]
+pub const LOAD_MODEL_VERSION: u32 = 1;
+
+/// Statistical model of a site's request traffic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoadModel {
+    pub version: u32,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub requests: u64,
+    pub devices: usize,
+    pub arrivals: ArrivalModel,
+    /// Size of the method and parameters as JSON; requests recorded before
+    /// sizes were kept are left out
+    pub request_bytes: Distribution,
+    /// Methods by share of requests, most frequent first
+    pub methods: Vec<MethodShare>,
+}
+
+/// When requests arrive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArrivalModel {
+    /// Mean requests per second over the window
+    pub mean_rate_per_sec: f64,
+    /// Gaps between consecutive requests
+    pub interarrival_ms: Distribution,
+    /// Standard deviation of the gaps over their mean: about 1 for Poisson
+    /// arrivals, higher for bursty traffic
+    pub burstiness: f64,
+    /// Share of requests arriving in each UTC hour of the day
+    pub hourly_share: Vec<f64>,
+}
+
+/// Summary of a sample of non-negative values
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Distribution {
+    pub samples: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Distribution {
+    fn of(mut values: Vec<u64>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_unstable();
+        Self {
+            samples: values.len() as u64,
+            mean: values.iter().sum::<u64>() as f64 / values.len() as f64,
+            p50: percentile(&values, 0.50),
+            p90: percentile(&values, 0.90),
+            p99: percentile(&values, 0.99),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// How often one method is called
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MethodShare {
+    pub method: String,
+    pub requests: u64,
+    pub share: f64,
+    pub success_rate: f64,
+}
+
+impl LoadModel {
+    /// Model of the requests `digests` record; `None` without any
+    pub fn from_digests(digests: &[AuditDigest]) -> Option<Self> {
+        let mut arrivals: Vec<DateTime<Utc>> = digests.iter().map(|digest| digest.received_at).collect();
+        arrivals.sort_unstable();
+        let (window_start, window_end) = (*arrivals.first()?, *arrivals.last()?);
+        let requests = digests.len() as u64;
+
+        let gaps: Vec<u64> = arrivals
+            .windows(2)
+            .map(|pair| pair[1].signed_duration_since(pair[0]).num_milliseconds().max(0) as u64)
+            .collect();
+        let interarrival_ms = Distribution::of(gaps.clone());
+        let burstiness = if interarrival_ms.mean > 0.0 {
+            let variance = gaps.iter().map(|gap| (*gap as f64 - interarrival_ms.mean).powi(2)).sum::<f64>()
+                / gaps.len() as f64;
+            variance.sqrt() / interarrival_ms.mean
+        } else {
+            0.0
+        };
+        let window_secs = window_end.signed_duration_since(window_start).num_milliseconds() as f64 / 1000.0;
+        let mut hourly_share = vec![0.0; 24];
+        for arrival in &arrivals {
+            hourly_share[arrival.hour() as usize] += 1.0 / requests as f64;
+        }
+
+        let mut calls: HashMap<&str, (u64, u64)> = HashMap::new();
+        for digest in digests {
+            let (count, succeeded) = calls.entry(digest.method.as_str()).or_default();
+            *count += 1;
+            *succeeded += u64::from(digest.success);
+        }
+        let mut methods: Vec<MethodShare> = calls
+            .into_iter()
+            .map(|(method, (count, succeeded))| MethodShare {
+                method: method.to_string(),
+                requests: count,
+                share: count as f64 / requests as f64,
+                success_rate: succeeded as f64 / count as f64,
+            })
+            .collect();
+        methods.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.method.cmp(&b.method)));
+
+        Some(Self {
+            version: LOAD_MODEL_VERSION,
+            window_start,
+            window_end,
+            requests,
+            devices: digests.iter().map(|digest| digest.device_id.as_str()).collect::<HashSet<_>>().len(),
+            arrivals: ArrivalModel {
+                mean_rate_per_sec: if window_secs > 0.0 { (requests - 1) as f64 / window_secs } else { 0.0 },
+                interarrival_ms,
+                burstiness,
+                hourly_share,
+            },
+            request_bytes: Distribution::of(
+                digests.iter().map(|digest| digest.request_bytes).filter(|bytes| *bytes > 0).collect(),
+            ),
+            methods,
+        })
+    }
+}
+
+fn percentile(sorted: &[u64], quantile: f64) -> u64 {
+    let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use mcp_common::MCPRequest;
+
+    fn digest(method: &str, device: &str, at: DateTime<Utc>, prompt_len: usize, success: bool) -> AuditDigest {
+        let request = MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: device.to_string(),
+            method: method.to_string(),
+            params: HashMap::from([("prompt".to_string(), serde_json::json!("x".repeat(prompt_len)))]),
+            context: None,
+            timestamp: at,
+        };
+        AuditDigest {
+            success,
+            ..AuditDigest::for_request(&request)
+        }
+    }
+
+    #[test]
+    fn test_model_summarizes_arrivals_sizes_and_method_mix() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 2, 8, 0, 0).unwrap();
+        let digests = vec![
+            digest("completion", "sensor-1", start, 10, true),
+            digest("completion", "sensor-2", start + Duration::seconds(1), 100, true),
+            digest("embedding", "sensor-1", start + Duration::seconds(2), 1000, false),
+            digest("completion", "sensor-1", start + Duration::seconds(3), 10, false),
+        ];
+        let model = LoadModel::from_digests(&digests).unwrap();
+
+        assert_eq!(model.requests, 4);
+        assert_eq!(model.devices, 2);
+        assert_eq!(model.window_end - model.window_start, Duration::seconds(3));
+        assert!((model.arrivals.mean_rate_per_sec - 1.0).abs() < 1e-9);
+        assert_eq!(model.arrivals.interarrival_ms.p50, 1000);
+        // Evenly spaced arrivals are not bursty at all
+        assert_eq!(model.arrivals.burstiness, 0.0);
+        assert_eq!(model.arrivals.hourly_share[8], 1.0);
+
+        assert_eq!(model.request_bytes.samples, 4);
+        assert!(model.request_bytes.p50 > 10 && model.request_bytes.max > 1000);
+
+        assert_eq!(model.methods[0].method, "completion");
+        assert_eq!(model.methods[0].requests, 3);
+        assert!((model.methods[0].share - 0.75).abs() < 1e-9);
+        assert!((model.methods[0].success_rate - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(model.methods[1].method, "embedding");
+    }
+
+    #[test]
+    fn test_bursts_and_unsized_records() {
+        let start = Utc.with_ymd_and_hms(2026, 3, 2, 8, 0, 0).unwrap();
+        let mut digests: Vec<AuditDigest> = [0, 10, 20, 10_000]
+            .into_iter()
+            .map(|ms| digest("completion", "sensor-1", start + Duration::milliseconds(ms), 10, true))
+            .collect();
+        digests[0].request_bytes = 0;
+        let model = LoadModel::from_digests(&digests).unwrap();
+
+        assert!(model.arrivals.burstiness > 1.0);
+        assert_eq!(model.request_bytes.samples, 3);
+        assert!(LoadModel::from_digests(&[]).is_none());
+    }
+}
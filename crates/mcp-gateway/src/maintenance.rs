@@ -0,0 +1,236 @@
+//! Maintenance mode handling
+//!
+//! While maintenance mode is active the gateway only processes allow-listed
+//! methods; every other request is placed on the offline queue and answered
+//! with the operator banner. Maintenance windows always carry an expiry so a
+//! forgotten toggle cannot leave a device parked indefinitely.
+
+use chrono::{DateTime, Duration, Utc};
+use mcp_common::clock::{self, Clock};
+use mcp_common::config::MaintenanceConfig;
+use mcp_common::metrics::{ComponentHealth, HealthLevel};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Operator request to enter maintenance mode
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MaintenanceRequest {
+    /// Banner returned to clients while maintenance is active
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Window length; falls back to the configured default
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+    /// Methods that bypass the maintenance queue; falls back to config
+    #[serde(default)]
+    pub allowed_methods: Option<Vec<String>>,
+}
+
+/// Snapshot of the current maintenance window
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceStatus {
+    pub active: bool,
+    pub message: Option<String>,
+    pub allowed_methods: Vec<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub queued_requests: u64,
+}
+
+#[derive(Debug, Clone)]
+struct MaintenanceWindow {
+    message: String,
+    allowed_methods: Vec<String>,
+    started_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    queued_requests: u64,
+}
+
+/// Admin-controlled maintenance mode
+pub struct MaintenanceMode {
+    config: MaintenanceConfig,
+    window: RwLock<Option<MaintenanceWindow>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl MaintenanceMode {
+    pub fn new(config: MaintenanceConfig) -> Self {
+        Self::with_clock(config, clock::system_clock())
+    }
+
+    /// Maintenance mode whose windows expire according to `clock`
+    pub fn with_clock(config: MaintenanceConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            config,
+            window: RwLock::new(None),
+            clock,
+        }
+    }
+
+    /// Enter maintenance mode, replacing any window already in effect
+    pub async fn enable(&self, request: MaintenanceRequest) -> MaintenanceStatus {
+        let duration_secs = request
+            .duration_secs
+            .unwrap_or(self.config.default_duration_secs)
+            .min(self.config.max_duration_secs);
+        let started_at = self.clock.now();
+        let window = MaintenanceWindow {
+            message: request
+                .message
+                .unwrap_or_else(|| self.config.default_message.clone()),
+            allowed_methods: request
+                .allowed_methods
+                .unwrap_or_else(|| self.config.allowed_methods.clone()),
+            started_at,
+            expires_at: started_at + Duration::seconds(duration_secs as i64),
+            queued_requests: 0,
+        };
+
+        info!(
+            "Maintenance mode enabled until {} ({})",
+            window.expires_at, window.message
+        );
+
+        *self.window.write().await = Some(window);
+        self.status().await
+    }
+
+    /// Leave maintenance mode; returns whether a window was active
+    pub async fn disable(&self) -> bool {
+        let previous = self.window.write().await.take();
+        if let Some(window) = &previous {
+            info!(
+                "Maintenance mode disabled ({} requests queued during window)",
+                window.queued_requests
+            );
+        }
+        previous.is_some()
+    }
+
+    /// Whether maintenance mode is currently in effect
+    pub async fn is_active(&self) -> bool {
+        self.expire_if_due().await;
+        self.window.read().await.is_some()
+    }
+
+    /// Check whether a method must be queued instead of processed.
+    ///
+    /// Returns the banner to attach to the queued response, and counts the
+    /// request against the active window.
+    pub async fn intercept(&self, method: &str) -> Option<String> {
+        self.expire_if_due().await;
+        let mut guard = self.window.write().await;
+        let window = guard.as_mut()?;
+        if window.allowed_methods.iter().any(|m| m == method) {
+            return None;
+        }
+        window.queued_requests += 1;
+        Some(window.message.clone())
+    }
+
+    /// Current maintenance status for the admin API and responses
+    pub async fn status(&self) -> MaintenanceStatus {
+        self.expire_if_due().await;
+        match self.window.read().await.as_ref() {
+            Some(window) => MaintenanceStatus {
+                active: true,
+                message: Some(window.message.clone()),
+                allowed_methods: window.allowed_methods.clone(),
+                started_at: Some(window.started_at),
+                expires_at: Some(window.expires_at),
+                queued_requests: window.queued_requests,
+            },
+            None => MaintenanceStatus {
+                active: false,
+                message: None,
+                allowed_methods: Vec::new(),
+                started_at: None,
+                expires_at: None,
+                queued_requests: 0,
+            },
+        }
+    }
+
+    /// Health component reported alongside the regular subsystems
+    pub async fn health(&self) -> ComponentHealth {
+        let status = self.status().await;
+        let mut metrics = HashMap::new();
+        metrics.insert("active".to_string(), if status.active { 1.0 } else { 0.0 });
+        metrics.insert("queued_requests".to_string(), status.queued_requests as f32);
+        if let Some(expires_at) = status.expires_at {
+            let remaining = expires_at.signed_duration_since(self.clock.now()).num_seconds();
+            metrics.insert("remaining_seconds".to_string(), remaining.max(0) as f32);
+        }
+
+        ComponentHealth {
+            status: if status.active {
+                HealthLevel::Degraded
+            } else {
+                HealthLevel::Healthy
+            },
+            message: status
+                .message
+                .unwrap_or_else(|| "Not in maintenance".to_string()),
+            last_check: self.clock.now(),
+            metrics,
+        }
+    }
+
+    async fn expire_if_due(&self) {
+        let expired = matches!(
+            self.window.read().await.as_ref(),
+            Some(window) if self.clock.now() >= window.expires_at
+        );
+        if expired {
+            let mut guard = self.window.write().await;
+            if matches!(guard.as_ref(), Some(window) if self.clock.now() >= window.expires_at) {
+                info!("Maintenance window expired, resuming normal processing");
+                *guard = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_allow_listed_methods_bypass_maintenance() {
+        let mode = MaintenanceMode::new(MaintenanceConfig::default());
+        assert!(mode.intercept("completion").await.is_none());
+
+        mode.enable(MaintenanceRequest {
+            message: Some("Upgrading models".to_string()),
+            duration_secs: Some(60),
+            allowed_methods: Some(vec!["health".to_string()]),
+        })
+        .await;
+
+        assert!(mode.intercept("health").await.is_none());
+        assert_eq!(
+            mode.intercept("completion").await.as_deref(),
+            Some("Upgrading models")
+        );
+        assert_eq!(mode.status().await.queued_requests, 1);
+
+        assert!(mode.disable().await);
+        assert!(!mode.is_active().await);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_expires_automatically() {
+        let mode = MaintenanceMode::new(MaintenanceConfig::default());
+        mode.enable(MaintenanceRequest {
+            duration_secs: Some(0),
+            ..Default::default()
+        })
+        .await;
+
+        assert!(!mode.is_active().await);
+        assert!(mode.intercept("completion").await.is_none());
+    }
+}
@@ -0,0 +1,448 @@
+//! Service mesh integration through Envoy-compatible xDS resources
+//!
+//! At larger edge sites the gateways sit inside a service mesh. This module
+//! publishes what the mesh needs to route to and around them, in the JSON
+//! form of the Envoy v3 API ("xDS-lite": REST polling only, no streaming):
+//!
+//! - `GET /v1/mesh/node`: node identity, locality and metadata, including the
+//!   router's current on-device/cloud split
+//! - `POST /v3/discovery:clusters`: the gateway cluster and the cloud cluster
+//! - `POST /v3/discovery:endpoints`: their endpoints, with the gateways
+//!   weighted by their share of the device hash ring and the cloud endpoints
+//!   by how often the router actually chose them
+//!
+//! Mesh traffic shifting then follows the internal router's decisions instead
+//! of fighting them. Responses carry a content-derived `version_info`; a
+//! request already at the current version gets `304 Not Modified`, as
+//! Envoy's REST transport expects.
+
+use crate::cluster::MemberStatus;
+use crate::compliance::ResidencySummary;
+use crate::handlers::AppState;
+use crate::probes::ServingStatus;
+use axum::{
+    extract::{Json as ExtractJson, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use mcp_common::Config;
+use mcp_common::crypto::digest;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub const CLUSTER_TYPE_URL: &str = "type.googleapis.com/envoy.config.cluster.v3.Cluster";
+pub const ENDPOINT_TYPE_URL: &str = "type.googleapis.com/envoy.config.endpoint.v3.ClusterLoadAssignment";
+
+/// Metadata namespace for gateway-specific endpoint and node metadata
+const METADATA_NAMESPACE: &str = "mcp";
+/// Endpoint weight given to a member owning the whole hash ring
+const WEIGHT_SCALE: f64 = 1000.0;
+
+/// Envoy `DiscoveryRequest`, as sent by the REST transport
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DiscoveryRequest {
+    #[serde(default)]
+    pub version_info: String,
+    #[serde(default)]
+    pub resource_names: Vec<String>,
+}
+
+/// Gateway state the mesh resources are built from
+#[derive(Debug, Clone)]
+pub struct MeshView {
+    pub node_id: String,
+    /// Cluster members and their hash ring shares; empty without cluster routing
+    pub members: Vec<MemberStatus>,
+    pub local_status: ServingStatus,
+    pub maintenance: bool,
+    pub residency: ResidencySummary,
+}
+
+impl MeshView {
+    /// Current view of a gateway
+    pub async fn of(gateway: &crate::Gateway) -> Self {
+        let cluster = gateway.cluster().status();
+        let probe = gateway.health_probe();
+        let snapshot = probe.snapshot(gateway).await;
+        Self {
+            node_id: cluster.node_id,
+            members: if cluster.enabled { cluster.members } else { Vec::new() },
+            local_status: snapshot.status("", probe.config()),
+            maintenance: snapshot.maintenance,
+            residency: gateway.compliance().residency(gateway.config(), cluster.proxied),
+        }
+    }
+}
+
+/// Mesh routes, merged into the main router by `handlers::create_router`
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/v1/mesh/node", get(node_metadata))
+        .route("/v3/discovery:clusters", post(discover_clusters))
+        .route("/v3/discovery:endpoints", post(discover_endpoints))
+}
+
+/// Node identity and metadata in Envoy `Node` form
+pub async fn node_metadata(State(gateway): State<AppState>) -> impl IntoResponse {
+    Json(node(gateway.config(), &MeshView::of(&gateway).await))
+}
+
+/// Cluster discovery (CDS)
+pub async fn discover_clusters(
+    State(gateway): State<AppState>,
+    payload: Option<ExtractJson<DiscoveryRequest>>,
+) -> Response {
+    let request = payload.map(|ExtractJson(request)| request).unwrap_or_default();
+    discovery_response(CLUSTER_TYPE_URL, clusters(gateway.config()), &request)
+}
+
+/// Endpoint discovery (EDS)
+pub async fn discover_endpoints(
+    State(gateway): State<AppState>,
+    payload: Option<ExtractJson<DiscoveryRequest>>,
+) -> Response {
+    let request = payload.map(|ExtractJson(request)| request).unwrap_or_default();
+    let assignments = load_assignments(gateway.config(), &MeshView::of(&gateway).await);
+    discovery_response(ENDPOINT_TYPE_URL, assignments, &request)
+}
+
+/// Envoy `Node` for this gateway
+pub fn node(config: &Config, view: &MeshView) -> Value {
+    let mesh = &config.cluster.mesh;
+    let mut metadata = json!({
+        "gateway_version": env!("CARGO_PKG_VERSION"),
+        "cluster_routing": !view.members.is_empty(),
+        "serving": view.local_status == ServingStatus::Serving,
+        "maintenance": view.maintenance,
+        "routing_strategy": config.router.strategy,
+        "on_device_ratio": view.residency.on_device_ratio,
+        "cloud_endpoints": config.router.cloud_endpoints.iter().map(|endpoint| &endpoint.name).collect::<Vec<_>>(),
+    });
+    for (label, value) in &mesh.labels {
+        metadata[label] = json!(value);
+    }
+    json!({
+        "id": view.node_id,
+        "cluster": mesh.cluster_name,
+        "locality": locality(config),
+        "metadata": metadata,
+        "user_agent_name": "mcp-edge-gateway",
+        "user_agent_version": env!("CARGO_PKG_VERSION"),
+    })
+}
+
+/// CDS resources: the gateway cluster and the cloud cluster
+pub fn clusters(config: &Config) -> Vec<Value> {
+    let mesh = &config.cluster.mesh;
+    let eds_cluster_config = |name: &str| {
+        let mut eds = json!({ "service_name": name });
+        if let Some(xds_cluster) = &mesh.xds_cluster {
+            eds["eds_config"] = json!({
+                "resource_api_version": "V3",
+                "api_config_source": {
+                    "api_type": "REST",
+                    "transport_api_version": "V3",
+                    "cluster_names": [xds_cluster],
+                    "refresh_delay": "5s",
+                },
+            });
+        }
+        eds
+    };
+
+    let gateways = json!({
+        "@type": CLUSTER_TYPE_URL,
+        "name": mesh.cluster_name,
+        "type": "EDS",
+        "eds_cluster_config": eds_cluster_config(&mesh.cluster_name),
+        "connect_timeout": format!("{}s", config.cluster.routing.proxy_timeout_ms.div_ceil(1000).max(1)),
+        // Weights follow hash ring shares; members proxy to the device owner anyway
+        "lb_policy": "ROUND_ROBIN",
+        "health_checks": [{
+            "timeout": "1s",
+            "interval": "5s",
+            "unhealthy_threshold": 2,
+            "healthy_threshold": 1,
+            "http_health_check": { "path": "/healthz" },
+        }],
+    });
+
+    let timeout_ms = config
+        .router
+        .cloud_endpoints
+        .iter()
+        .map(|endpoint| endpoint.connect_timeout_ms.unwrap_or(endpoint.timeout_ms))
+        .max()
+        .unwrap_or(5000);
+    let mut cloud = json!({
+        "@type": CLUSTER_TYPE_URL,
+        "name": mesh.cloud_cluster_name,
+        "type": "EDS",
+        "eds_cluster_config": eds_cluster_config(&mesh.cloud_cluster_name),
+        "connect_timeout": format!("{}s", timeout_ms.div_ceil(1000).max(1)),
+        "lb_policy": "ROUND_ROBIN",
+    });
+    if config.router.cloud_endpoints.iter().any(|endpoint| endpoint.url.starts_with("https://")) {
+        cloud["transport_socket"] = json!({
+            "name": "envoy.transport_sockets.tls",
+            "typed_config": {
+                "@type": "type.googleapis.com/envoy.extensions.transport_sockets.tls.v3.UpstreamTlsContext",
+            },
+        });
+        cloud["typed_extension_protocol_options"] = json!({
+            "envoy.extensions.upstreams.http.v3.HttpProtocolOptions": {
+                "@type": "type.googleapis.com/envoy.extensions.upstreams.http.v3.HttpProtocolOptions",
+                "upstream_http_protocol_options": { "auto_sni": true },
+                "explicit_http_config": { "http_protocol_options": {} },
+            },
+        });
+    }
+    vec![gateways, cloud]
+}
+
+/// EDS resources for the gateway and cloud clusters
+pub fn load_assignments(config: &Config, view: &MeshView) -> Vec<Value> {
+    let mesh = &config.cluster.mesh;
+    let local_health = if view.maintenance {
+        "DRAINING"
+    } else if view.local_status == ServingStatus::Serving {
+        "HEALTHY"
+    } else {
+        "UNHEALTHY"
+    };
+
+    let gateways: Vec<Value> = if view.members.is_empty() {
+        let address = mesh
+            .advertise_address
+            .clone()
+            .unwrap_or_else(|| format!("{}:{}", config.gateway.bind_address, config.gateway.port));
+        socket_address(&format!("http://{}", address))
+            .map(|address| lb_endpoint(address, local_health, 1, json!({ "member_id": view.node_id })))
+            .into_iter()
+            .collect()
+    } else {
+        view.members
+            .iter()
+            .filter_map(|member| {
+                // Only this gateway's health is known here; the mesh checks the others
+                let health = if member.id == view.node_id { local_health } else { "UNKNOWN" };
+                let weight = ((member.share * WEIGHT_SCALE).round() as u32).max(1);
+                let metadata = json!({ "member_id": member.id, "ring_share": member.share });
+                socket_address(&member.url).map(|address| lb_endpoint(address, health, weight, metadata))
+            })
+            .collect()
+    };
+
+    // Weight cloud endpoints by how often the router chose each one
+    let routed: u64 = view.residency.cloud.iter().map(|destination| destination.requests).sum();
+    let cloud: Vec<Value> = config
+        .router
+        .cloud_endpoints
+        .iter()
+        .filter_map(|endpoint| {
+            let requests = view
+                .residency
+                .cloud
+                .iter()
+                .find(|destination| destination.endpoint == endpoint.name)
+                .map_or(0, |destination| destination.requests);
+            let weight = if routed == 0 {
+                1
+            } else {
+                ((requests as f64 / routed as f64 * WEIGHT_SCALE).round() as u32).max(1)
+            };
+            let metadata = json!({
+                "endpoint": endpoint.name,
+                "region": endpoint.region,
+                "routed_requests": requests,
+            });
+            socket_address(&endpoint.url).map(|address| lb_endpoint(address, "UNKNOWN", weight, metadata))
+        })
+        .collect();
+
+    vec![
+        load_assignment(&mesh.cluster_name, locality(config), gateways),
+        load_assignment(&mesh.cloud_cluster_name, json!({}), cloud),
+    ]
+}
+
+/// `DiscoveryResponse` for the requested resources, or 304 when unchanged
+fn discovery_response(type_url: &str, resources: Vec<Value>, request: &DiscoveryRequest) -> Response {
+    let name_field = if type_url == ENDPOINT_TYPE_URL { "cluster_name" } else { "name" };
+    let resources: Vec<Value> = resources
+        .into_iter()
+        .filter(|resource| {
+            request.resource_names.is_empty()
+                || resource[name_field]
+                    .as_str()
+                    .is_some_and(|name| request.resource_names.iter().any(|requested| requested == name))
+        })
+        .collect();
+    let version = version_of(&resources);
+    if request.version_info == version {
+        return StatusCode::NOT_MODIFIED.into_response();
+    }
+    Json(json!({
+        "version_info": version,
+        "resources": resources,
+        "type_url": type_url,
+        "nonce": version,
+    }))
+    .into_response()
+}
+
+/// Content-derived version, stable while the resources are unchanged
+fn version_of(resources: &[Value]) -> String {
+    let serialized = serde_json::to_vec(resources).unwrap_or_default();
+    let digest = digest::digest(&digest::SHA256, &serialized);
+    digest.as_ref()[..8].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn locality(config: &Config) -> Value {
+    let mesh = &config.cluster.mesh;
+    let mut locality = json!({});
+    for (key, value) in [("region", &mesh.region), ("zone", &mesh.zone), ("sub_zone", &mesh.sub_zone)] {
+        if let Some(value) = value {
+            locality[key] = json!(value);
+        }
+    }
+    locality
+}
+
+fn load_assignment(cluster_name: &str, locality: Value, lb_endpoints: Vec<Value>) -> Value {
+    json!({
+        "@type": ENDPOINT_TYPE_URL,
+        "cluster_name": cluster_name,
+        "endpoints": [{ "locality": locality, "lb_endpoints": lb_endpoints }],
+    })
+}
+
+fn lb_endpoint(address: Value, health: &str, weight: u32, metadata: Value) -> Value {
+    json!({
+        "endpoint": { "address": address },
+        "health_status": health,
+        "load_balancing_weight": weight,
+        "metadata": { "filter_metadata": { METADATA_NAMESPACE: metadata } },
+    })
+}
+
+/// Envoy `Address` for a URL, using the scheme's default port when none is given
+fn socket_address(url: &str) -> Option<Value> {
+    let url = reqwest::Url::parse(url).ok()?;
+    Some(json!({
+        "socket_address": {
+            "address": url.host_str()?,
+            "port_value": url.port_or_known_default()?,
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compliance::CloudDestination;
+    use mcp_common::config::CloudEndpoint;
+
+    fn endpoint(name: &str, url: &str) -> CloudEndpoint {
+        CloudEndpoint {
+            name: name.to_string(),
+            url: url.to_string(),
+            api_key: None,
+            timeout_ms: 10_000,
+            max_retries: 3,
+            connect_timeout_ms: None,
+            region: Some("eu-west-1".to_string()),
+            provider: Default::default(),
+            compression: Default::default(),
+        }
+    }
+
+    fn view() -> MeshView {
+        MeshView {
+            node_id: "gw-1".to_string(),
+            members: vec![
+                MemberStatus {
+                    id: "gw-1".to_string(),
+                    url: "http://10.0.0.11:8080".to_string(),
+                    share: 0.25,
+                },
+                MemberStatus {
+                    id: "gw-2".to_string(),
+                    url: "http://10.0.0.12:8080".to_string(),
+                    share: 0.75,
+                },
+            ],
+            local_status: ServingStatus::Serving,
+            maintenance: true,
+            residency: ResidencySummary {
+                on_device: 10,
+                queued: 0,
+                cloud: vec![CloudDestination {
+                    endpoint: "primary".to_string(),
+                    host: "api.example.com".to_string(),
+                    region: Some("eu-west-1".to_string()),
+                    requests: 30,
+                }],
+                proxied_to_cluster_members: 0,
+                on_device_ratio: 0.25,
+            },
+        }
+    }
+
+    #[test]
+    fn test_endpoints_follow_ring_shares_and_router_choices() {
+        let mut config = Config::default();
+        config.router.cloud_endpoints = vec![
+            endpoint("primary", "https://api.example.com/v1"),
+            endpoint("secondary", "https://backup.example.com:8443/v1"),
+        ];
+        config.cluster.mesh.zone = Some("line-3".to_string());
+
+        let assignments = load_assignments(&config, &view());
+        let gateways = &assignments[0]["endpoints"][0];
+        assert_eq!(gateways["locality"], json!({ "zone": "line-3" }));
+        let members = gateways["lb_endpoints"].as_array().unwrap();
+        assert_eq!(members[0]["health_status"], "DRAINING");
+        assert_eq!(members[0]["load_balancing_weight"], 250);
+        assert_eq!(members[1]["health_status"], "UNKNOWN");
+        assert_eq!(members[1]["endpoint"]["address"]["socket_address"]["address"], "10.0.0.12");
+
+        let cloud = assignments[1]["endpoints"][0]["lb_endpoints"].as_array().unwrap();
+        assert_eq!(assignments[1]["cluster_name"], "mcp-cloud");
+        assert_eq!(cloud[0]["load_balancing_weight"], 1000);
+        assert_eq!(cloud[0]["endpoint"]["address"]["socket_address"]["port_value"], 443);
+        // Never chosen, but kept routable
+        assert_eq!(cloud[1]["load_balancing_weight"], 1);
+        assert_eq!(cloud[1]["endpoint"]["address"]["socket_address"]["port_value"], 8443);
+
+        let clusters = clusters(&config);
+        assert_eq!(clusters[1]["transport_socket"]["name"], "envoy.transport_sockets.tls");
+        assert!(clusters[0]["eds_cluster_config"].get("eds_config").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_discovery_filters_resources_and_reports_unchanged_versions() {
+        let config = Config::default();
+        let assignments = load_assignments(&config, &view());
+        let request = DiscoveryRequest {
+            version_info: String::new(),
+            resource_names: vec!["mcp-cloud".to_string()],
+        };
+        let response = discovery_response(ENDPOINT_TYPE_URL, assignments.clone(), &request);
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["resources"].as_array().unwrap().len(), 1);
+        assert_eq!(body["resources"][0]["cluster_name"], "mcp-cloud");
+        assert_eq!(body["type_url"], ENDPOINT_TYPE_URL);
+
+        let request = DiscoveryRequest {
+            version_info: body["version_info"].as_str().unwrap().to_string(),
+            ..request
+        };
+        let response = discovery_response(ENDPOINT_TYPE_URL, assignments, &request);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+}
@@ -8,6 +8,7 @@ use axum::{
 };
 use std::collections::HashMap;
 use std::net::IpAddr;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
@@ -132,14 +133,14 @@ impl ClientRateLimit {
 /// Rate limiting middleware
 #[derive(Clone)]
 pub struct RateLimitLayer {
-    requests_per_window: u32,
-    window_seconds: u64,
+    requests_per_window: Arc<AtomicU32>,
+    window_seconds: Arc<AtomicU64>,
     clients: Arc<RwLock<HashMap<String, ClientRateLimit>>>,
 }
 
 impl RateLimitLayer {
     pub fn new(requests_per_window: u32, window_seconds: u64) -> Self {
-        let clients = Arc::new(RwLock::new(HashMap::new()));
+        let clients: Arc<RwLock<HashMap<String, ClientRateLimit>>> = Arc::new(RwLock::new(HashMap::new()));
         
         // Spawn cleanup task to remove old client data
         let clients_cleanup = clients.clone();
@@ -165,13 +166,26 @@ impl RateLimitLayer {
         });
         
         Self {
-            requests_per_window,
-            window_seconds,
+            requests_per_window: Arc::new(AtomicU32::new(requests_per_window)),
+            window_seconds: Arc::new(AtomicU64::new(window_seconds)),
             clients,
         }
     }
+
+    /// Change the limit of this layer and every service it produced; request
+    /// counts already in a window are kept
+    pub fn reconfigure(&self, requests_per_window: u32, window_seconds: u64) {
+        self.requests_per_window.store(requests_per_window, Ordering::Relaxed);
+        self.window_seconds.store(window_seconds, Ordering::Relaxed);
+    }
+
+    fn window_seconds(&self) -> u64 {
+        self.window_seconds.load(Ordering::Relaxed)
+    }
     
     async fn check_rate_limit(&self, client_id: &str) -> bool {
+        let requests_per_window = self.requests_per_window.load(Ordering::Relaxed);
+        let window_seconds = self.window_seconds();
         let mut clients = self.clients.write().await;
         let client = clients.entry(client_id.to_string()).or_insert_with(ClientRateLimit::new);
         
@@ -181,13 +195,13 @@ impl RateLimitLayer {
         }
         
         // Clean up old requests
-        client.cleanup_old_requests(self.window_seconds);
+        client.cleanup_old_requests(window_seconds);
         
         // Check if adding this request would exceed the rate limit
-        if client.requests.len() >= self.requests_per_window as usize {
+        if client.requests.len() >= requests_per_window as usize {
             // Block the client for twice the window duration
-            client.block_client(self.window_seconds * 2);
-            warn!("Rate limit exceeded for client {}, blocking for {}s", client_id, self.window_seconds * 2);
+            client.block_client(window_seconds * 2);
+            warn!("Rate limit exceeded for client {}, blocking for {}s", client_id, window_seconds * 2);
             return false;
         }
         
@@ -250,7 +264,7 @@ where
                 *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
                 response.headers_mut().insert(
                     "Retry-After", 
-                    HeaderValue::from_str(&layer.window_seconds.to_string()).unwrap_or_default()
+                    HeaderValue::from(layer.window_seconds())
                 );
                 return Ok(response);
             }
@@ -329,7 +343,6 @@ async fn collect_request_metrics(
 
     Ok(())
 }
-}
 
 /// Metrics collection middleware
 #[derive(Clone)]
@@ -0,0 +1,705 @@
+//! MQTT ingestion bridge
+//!
+//! Sensors that only speak MQTT publish requests to the `gateway.mqtt`
+//! request topics. Each message is a JSON object with `method` and `params`
+//! and optionally `id`, `device_id`, `timestamp`, `traceparent` and
+//! `reply_topic`; the device ID defaults to the last level of the topic. The
+//! request goes through `process_request` like any other, and the response,
+//! or an error in the WebSocket error shape, is published to the request's
+//! `reply_topic` or else `gateway.mqtt.response_topic`.
+//!
+//! The client speaks MQTT 3.1.1 over plain TCP. Incoming QoS 1 messages are
+//! acknowledged on receipt; QoS 1 responses are kept until the broker
+//! acknowledges them and sent again after a reconnect. MQTT 3.1.1 has no
+//! headers to carry device signatures, so enrolled devices must use HTTP or
+//! gRPC; restrict who may publish to the request topics with broker ACLs.
+
+use crate::handlers::{verify_device_signature, MAX_METHOD_LENGTH};
+use crate::server::AppState;
+use crate::Gateway;
+use axum::http::HeaderMap;
+use mcp_common::config::MqttBridgeConfig;
+use mcp_common::request_id as ids;
+use mcp_common::{redaction, Error, MCPRequest, Result, Span, SpanKind, TraceContext};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{info, warn};
+
+/// Device ID of requests whose topic and payload do not name one
+const DEFAULT_DEVICE_ID: &str = "mqtt_client";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest packet read from the broker
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+const SUBACK_FAILURE: u8 = 0x80;
+
+const PINGREQ: [u8; 2] = [0xc0, 0x00];
+
+fn network(e: impl std::fmt::Display) -> Error {
+    Error::Network(format!("MQTT connection failed: {}", e))
+}
+
+/// A PUBLISH packet in either direction
+#[derive(Debug, Clone, PartialEq)]
+struct Publish {
+    topic: String,
+    qos: u8,
+    /// Set for QoS 1
+    packet_id: Option<u16>,
+    dup: bool,
+    payload: Vec<u8>,
+}
+
+/// Packets the broker sends a subscriber
+#[derive(Debug, PartialEq)]
+enum Packet {
+    ConnAck { code: u8 },
+    Publish(Publish),
+    PubAck(u16),
+    SubAck { codes: Vec<u8> },
+    PingResp,
+    Other,
+}
+
+/// Prefix `body` with the fixed header: packet type and flags, then the
+/// remaining length as a variable-length integer
+fn frame(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn put_str(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+fn encode_connect(config: &MqttBridgeConfig) -> Vec<u8> {
+    let mut flags = 0u8;
+    if config.username.is_some() {
+        flags |= 0x80;
+    }
+    if config.password.is_some() {
+        flags |= 0x40;
+    }
+    if config.clean_session {
+        flags |= 0x02;
+    }
+    let mut body = Vec::new();
+    put_str(&mut body, "MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&config.keep_alive_secs.to_be_bytes());
+    put_str(&mut body, &config.client_id);
+    for credential in [&config.username, &config.password].into_iter().flatten() {
+        put_str(&mut body, credential);
+    }
+    frame(0x10, &body)
+}
+
+fn encode_subscribe(packet_id: u16, topics: &[String], qos: u8) -> Vec<u8> {
+    let mut body = packet_id.to_be_bytes().to_vec();
+    for topic in topics {
+        put_str(&mut body, topic);
+        body.push(qos);
+    }
+    frame(0x82, &body)
+}
+
+fn encode_publish(publish: &Publish) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_str(&mut body, &publish.topic);
+    if let Some(packet_id) = publish.packet_id {
+        body.extend_from_slice(&packet_id.to_be_bytes());
+    }
+    body.extend_from_slice(&publish.payload);
+    frame(0x30 | ((publish.dup as u8) << 3) | (publish.qos << 1), &body)
+}
+
+fn encode_puback(packet_id: u16) -> Vec<u8> {
+    frame(0x40, &packet_id.to_be_bytes())
+}
+
+async fn read_packet(reader: &mut (impl AsyncRead + Unpin)) -> Result<Packet> {
+    let header = reader.read_u8().await.map_err(network)?;
+    let mut length = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = reader.read_u8().await.map_err(network)?;
+        length |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 21 {
+            return Err(Error::Network("Malformed MQTT remaining length".to_string()));
+        }
+    }
+    if length > MAX_PACKET_SIZE {
+        return Err(Error::Network(format!("MQTT packet of {} bytes is too large", length)));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await.map_err(network)?;
+    decode(header, &body)
+}
+
+fn decode(header: u8, body: &[u8]) -> Result<Packet> {
+    let malformed = || Error::Network(format!("Malformed MQTT packet {:#04x}", header));
+    let u16_at = |at: usize| {
+        body.get(at..at + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(malformed)
+    };
+    Ok(match header >> 4 {
+        2 => Packet::ConnAck {
+            code: *body.get(1).ok_or_else(malformed)?,
+        },
+        3 => {
+            let qos = (header >> 1) & 0x03;
+            if qos > 1 {
+                return Err(Error::Network("MQTT broker sent a QoS 2 message".to_string()));
+            }
+            let topic_end = 2 + u16_at(0)? as usize;
+            let topic = body.get(2..topic_end).ok_or_else(malformed)?;
+            let topic = String::from_utf8(topic.to_vec()).map_err(|_| malformed())?;
+            let packet_id = if qos > 0 { Some(u16_at(topic_end)?) } else { None };
+            let payload_start = topic_end + if qos > 0 { 2 } else { 0 };
+            Packet::Publish(Publish {
+                topic,
+                qos,
+                packet_id,
+                dup: header & 0x08 != 0,
+                payload: body[payload_start..].to_vec(),
+            })
+        },
+        4 => Packet::PubAck(u16_at(0)?),
+        9 => Packet::SubAck {
+            codes: body.get(2..).ok_or_else(malformed)?.to_vec(),
+        },
+        13 => Packet::PingResp,
+        _ => Packet::Other,
+    })
+}
+
+fn connack_reason(code: u8) -> &'static str {
+    match code {
+        1 => "unacceptable protocol version",
+        2 => "client identifier rejected",
+        3 => "server unavailable",
+        4 => "bad user name or password",
+        5 => "not authorized",
+        _ => "unknown reason",
+    }
+}
+
+/// A response waiting to be published
+struct Outgoing {
+    topic: String,
+    payload: Vec<u8>,
+}
+
+/// Client state kept across reconnects
+#[derive(Default)]
+struct Session {
+    last_packet_id: u16,
+    /// QoS 1 responses the broker has not acknowledged yet
+    unacked: BTreeMap<u16, Publish>,
+}
+
+impl Session {
+    fn next_packet_id(&mut self) -> u16 {
+        self.last_packet_id = self.last_packet_id % u16::MAX + 1;
+        self.last_packet_id
+    }
+
+    fn publish(&mut self, outgoing: Outgoing, qos: u8) -> Publish {
+        let publish = Publish {
+            topic: outgoing.topic,
+            qos,
+            packet_id: (qos > 0).then(|| self.next_packet_id()),
+            dup: false,
+            payload: outgoing.payload,
+        };
+        if let Some(packet_id) = publish.packet_id {
+            self.unacked.insert(packet_id, publish.clone());
+        }
+        publish
+    }
+}
+
+/// A request message as sensors publish it
+#[derive(Deserialize)]
+struct MqttRequest {
+    #[serde(default)]
+    id: Option<String>,
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+    #[serde(default)]
+    device_id: Option<String>,
+    #[serde(default)]
+    timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    traceparent: Option<String>,
+    /// Topic to publish the response to instead of `response_topic`
+    #[serde(default)]
+    reply_topic: Option<String>,
+}
+
+/// Subscribes to the request topics and answers them through the gateway
+pub struct MqttBridge {
+    gateway: AppState,
+    config: Arc<MqttBridgeConfig>,
+    address: String,
+    in_flight: Arc<Semaphore>,
+}
+
+impl MqttBridge {
+    pub fn new(gateway: AppState) -> Result<Self> {
+        let config = gateway.config().gateway.mqtt.clone();
+        let url = &config.broker_url;
+        let host = url.strip_prefix("mqtt://").unwrap_or(url).trim_end_matches('/');
+        if host.is_empty() || host.contains("://") {
+            return Err(Error::Configuration(format!("Unsupported MQTT broker URL: {}", url)));
+        }
+        let address = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{}:1883", host)
+        };
+        Ok(Self {
+            gateway,
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight)),
+            config: Arc::new(config),
+            address,
+        })
+    }
+
+    /// Stay connected to the broker, reconnecting with exponential backoff
+    pub async fn run(self) {
+        let (responses, mut outgoing) = mpsc::channel(self.config.max_in_flight);
+        let mut session = Session::default();
+        let initial_delay = Duration::from_secs(self.config.reconnect_delay_secs);
+        let mut delay = initial_delay;
+        loop {
+            match self.connect(&mut session).await {
+                Ok(stream) => {
+                    info!("MQTT bridge connected to {}", self.address);
+                    delay = initial_delay;
+                    let e = self.serve(stream, &mut session, &responses, &mut outgoing).await;
+                    warn!("MQTT bridge lost its connection to {}: {}", self.address, e);
+                },
+                Err(e) => warn!("MQTT bridge could not connect to {}: {}", self.address, e),
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(Duration::from_secs(self.config.max_reconnect_delay_secs));
+        }
+    }
+
+    /// Connect, subscribe and resend unacknowledged responses. The SUBACK is
+    /// handled with the rest of the traffic, since a persistent session may
+    /// deliver stored messages first.
+    async fn connect(&self, session: &mut Session) -> Result<TcpStream> {
+        let mut stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&self.address))
+            .await
+            .map_err(|_| Error::Network("MQTT connect timed out".to_string()))?
+            .map_err(network)?;
+        stream.write_all(&encode_connect(&self.config)).await.map_err(network)?;
+        match tokio::time::timeout(CONNECT_TIMEOUT, read_packet(&mut stream))
+            .await
+            .map_err(|_| Error::Network("MQTT broker did not answer CONNECT".to_string()))??
+        {
+            Packet::ConnAck { code: 0 } => {},
+            Packet::ConnAck { code } => {
+                return Err(Error::Network(format!(
+                    "MQTT broker refused the connection: {}",
+                    connack_reason(code)
+                )))
+            },
+            _ => return Err(Error::Network("Expected CONNACK from the MQTT broker".to_string())),
+        }
+
+        let packet_id = session.next_packet_id();
+        let subscribe = encode_subscribe(packet_id, &self.config.request_topics, self.config.qos);
+        stream.write_all(&subscribe).await.map_err(network)?;
+        for publish in session.unacked.values_mut() {
+            publish.dup = true;
+            stream.write_all(&encode_publish(publish)).await.map_err(network)?;
+        }
+        Ok(stream)
+    }
+
+    /// Exchange packets until the connection fails
+    async fn serve(
+        &self,
+        stream: TcpStream,
+        session: &mut Session,
+        responses: &mpsc::Sender<Outgoing>,
+        outgoing: &mut mpsc::Receiver<Outgoing>,
+    ) -> Error {
+        let (mut reader, mut writer) = stream.into_split();
+        // Reading happens on its own task, as a half-read packet cannot be
+        // resumed after losing a select
+        let (packets_tx, mut packets) = mpsc::channel(16);
+        let reading = tokio::spawn(async move {
+            loop {
+                let packet = read_packet(&mut reader).await;
+                let failed = packet.is_err();
+                if packets_tx.send(packet).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let result = self.exchange(&mut writer, &mut packets, session, responses, outgoing).await;
+        reading.abort();
+        match result {
+            Ok(()) => Error::Network("MQTT connection closed".to_string()),
+            Err(e) => e,
+        }
+    }
+
+    async fn exchange(
+        &self,
+        writer: &mut OwnedWriteHalf,
+        packets: &mut mpsc::Receiver<Result<Packet>>,
+        session: &mut Session,
+        responses: &mpsc::Sender<Outgoing>,
+        outgoing: &mut mpsc::Receiver<Outgoing>,
+    ) -> Result<()> {
+        let keep_alive = Duration::from_secs(u64::from(self.config.keep_alive_secs.max(1)));
+        let mut ping = tokio::time::interval_at(tokio::time::Instant::now() + keep_alive, keep_alive);
+        let mut awaiting_pong = false;
+        loop {
+            tokio::select! {
+                packet = packets.recv() => match packet.transpose()? {
+                    Some(Packet::Publish(message)) => {
+                        if let Some(packet_id) = message.packet_id {
+                            writer.write_all(&encode_puback(packet_id)).await.map_err(network)?;
+                        }
+                        self.dispatch(message, responses.clone());
+                    },
+                    Some(Packet::PubAck(packet_id)) => {
+                        session.unacked.remove(&packet_id);
+                    },
+                    Some(Packet::SubAck { codes }) if codes.contains(&SUBACK_FAILURE) => {
+                        return Err(Error::Configuration(format!(
+                            "MQTT broker rejected the subscription to {:?}",
+                            self.config.request_topics
+                        )));
+                    },
+                    Some(Packet::PingResp) => awaiting_pong = false,
+                    Some(_) => {},
+                    None => return Ok(()),
+                },
+                Some(response) = outgoing.recv() => {
+                    let publish = session.publish(response, self.config.qos);
+                    writer.write_all(&encode_publish(&publish)).await.map_err(network)?;
+                },
+                _ = ping.tick(), if self.config.keep_alive_secs > 0 => {
+                    if awaiting_pong {
+                        return Err(Error::Network("MQTT broker stopped answering pings".to_string()));
+                    }
+                    writer.write_all(&PINGREQ).await.map_err(network)?;
+                    awaiting_pong = true;
+                },
+            }
+        }
+    }
+
+    /// Process a request message in the background and queue its response
+    fn dispatch(&self, message: Publish, responses: mpsc::Sender<Outgoing>) {
+        let gateway = self.gateway.clone();
+        let config = self.config.clone();
+        let in_flight = self.in_flight.clone();
+        tokio::spawn(async move {
+            let Ok(_permit) = in_flight.acquire_owned().await else {
+                return;
+            };
+            let (topic, reply) = handle(&gateway, &config, &message).await;
+            let outgoing = Outgoing {
+                topic,
+                payload: reply.to_string().into_bytes(),
+            };
+            // Responses wait here while the bridge reconnects
+            let _ = responses.send(outgoing).await;
+        });
+    }
+}
+
+/// Answer one request message; returns the topic to publish the reply to
+async fn handle(
+    gateway: &Gateway,
+    config: &MqttBridgeConfig,
+    message: &Publish,
+) -> (String, serde_json::Value) {
+    let topic_device = message
+        .topic
+        .rsplit('/')
+        .next()
+        .filter(|level| !level.is_empty())
+        .unwrap_or(DEFAULT_DEVICE_ID);
+    let payload = match serde_json::from_slice::<MqttRequest>(&message.payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            let message = format!("Malformed request: {}", e);
+            let reply = error_reply("INVALID_REQUEST", &message, ids::generate());
+            return (reply_topic(config, None, topic_device), reply);
+        },
+    };
+    let device_id = payload.device_id.clone().unwrap_or_else(|| topic_device.to_string());
+    let topic = reply_topic(config, payload.reply_topic.as_deref(), &device_id);
+
+    let (request, mut span) = match admit(gateway, payload, device_id, message).await {
+        Ok(admitted) => admitted,
+        Err(reply) => return (topic, reply),
+    };
+    let request_id = request.id;
+    let result = gateway.process_request(request).await;
+    if let Some(span) = span.as_mut() {
+        span.record_result(&result);
+    }
+    let reply = match result {
+        Ok(response) => serde_json::json!({ "type": "response", "response": response }),
+        Err(e) => {
+            warn!("MQTT MCP request {} failed: {}", request_id, e);
+            let code = match e {
+                Error::Overloaded(_) => "OVERLOADED",
+                _ => "PROCESSING_FAILED",
+            };
+            error_reply(code, &e.to_string(), request_id)
+        },
+    };
+    (topic, reply)
+}
+
+/// Apply the checks `POST /v1/mcp/completions` does; the error reply if any fails
+async fn admit(
+    gateway: &Gateway,
+    payload: MqttRequest,
+    device_id: String,
+    message: &Publish,
+) -> std::result::Result<(MCPRequest, Option<Span>), serde_json::Value> {
+    let request_id = match payload.id.as_deref() {
+        Some(id) if ids::accepts_client_ids() => ids::parse(id).ok_or_else(|| {
+            let message = format!("Invalid request ID '{}': expected a UUID or ULID", id);
+            error_reply("INVALID_REQUEST", &message, ids::generate())
+        })?,
+        _ => ids::generate(),
+    };
+    if payload.method.is_empty() || payload.method.len() > MAX_METHOD_LENGTH {
+        return Err(error_reply("INVALID_REQUEST", "Invalid method name", request_id));
+    }
+    let params = match payload.params {
+        serde_json::Value::Object(params) => params.into_iter().collect(),
+        serde_json::Value::Null => Default::default(),
+        _ => return Err(error_reply("INVALID_REQUEST", "params must be a JSON object", request_id)),
+    };
+    if let Err(e) = verify_device_signature(gateway, &HeaderMap::new(), &device_id, &message.payload).await {
+        warn!(
+            "Rejected MQTT MCP request {} from device {}: {}",
+            request_id,
+            redaction::id(&device_id),
+            e
+        );
+        if let Some(auth_guard) = gateway.security().auth_guard() {
+            auth_guard.record_failure(&device_id, None).await;
+        }
+        return Err(error_reply("FORBIDDEN", &e.to_string(), request_id));
+    }
+    if ids::claim(request_id, &device_id).is_err() {
+        return Err(error_reply(
+            "REQUEST_ID_CONFLICT",
+            "Request ID is already in use by another device",
+            request_id,
+        ));
+    }
+
+    let mut request = MCPRequest {
+        id: request_id,
+        device_id,
+        method: payload.method,
+        params,
+        context: None,
+        timestamp: payload.timestamp.unwrap_or_else(chrono::Utc::now),
+    };
+    let parent = payload.traceparent.as_deref().and_then(TraceContext::parse);
+    let mut span = Span::continue_or_start(parent.as_ref(), "gateway.mqtt_request", SpanKind::Server);
+    if let Some(span) = span.as_mut() {
+        span.set_attribute("mcp.method", &request.method);
+        span.set_attribute("mcp.request_id", request_id);
+        span.set_attribute("messaging.destination", &message.topic);
+        request.set_trace(span.context());
+    }
+    info!(
+        "Processing MQTT MCP request: method={}, id={}",
+        request.method, request_id
+    );
+    Ok((request, span))
+}
+
+/// The request's own reply topic, unless it is empty or a wildcard filter,
+/// else the configured response topic for the device
+fn reply_topic(config: &MqttBridgeConfig, requested: Option<&str>, device_id: &str) -> String {
+    match requested {
+        Some(topic) if !topic.is_empty() && !topic.contains(['+', '#']) => topic.to_string(),
+        _ => config.response_topic.replace("{device_id}", device_id),
+    }
+}
+
+fn error_reply(code: &str, message: &str, request_id: uuid::Uuid) -> serde_json::Value {
+    serde_json::json!({
+        "type": "error",
+        "error": {
+            "code": code,
+            "message": message,
+            "request_id": request_id
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ScriptedCloudClient;
+    use mcp_common::config::CloudEndpoint;
+    use mcp_common::Config;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_packet_round_trip() {
+        let publish = Publish {
+            topic: "mcp/requests/sensor-7".to_string(),
+            qos: 1,
+            packet_id: Some(300),
+            dup: true,
+            payload: vec![b'x'; 200],
+        };
+        let encoded = encode_publish(&publish);
+        // The 225 bytes after the fixed header take two length bytes
+        assert_eq!(&encoded[..3], &[0x3a, 0xe1, 0x01]);
+        let decoded = read_packet(&mut encoded.as_slice()).await.unwrap();
+        assert_eq!(decoded, Packet::Publish(publish));
+
+        let config = MqttBridgeConfig {
+            username: Some("sensor".to_string()),
+            ..Default::default()
+        };
+        let connect = encode_connect(&config);
+        assert_eq!(&connect[2..10], &[0, 4, b'M', b'Q', b'T', b'T', 4, 0x80]);
+        assert_eq!(decode(0x90, &[0, 1, SUBACK_FAILURE]).unwrap(), Packet::SubAck { codes: vec![0x80] });
+        assert!(decode(0x30, &[0, 9, b'a']).is_err());
+    }
+
+    #[test]
+    fn test_reply_topic() {
+        let config = MqttBridgeConfig::default();
+        assert_eq!(reply_topic(&config, None, "sensor-7"), "mcp/responses/sensor-7");
+        assert_eq!(reply_topic(&config, Some("site/replies/7"), "sensor-7"), "site/replies/7");
+        assert_eq!(reply_topic(&config, Some("site/#"), "sensor-7"), "mcp/responses/sensor-7");
+    }
+
+    async fn expect_packet(stream: &mut TcpStream) -> Packet {
+        tokio::time::timeout(Duration::from_secs(5), read_packet(stream))
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Accept the bridge, answer CONNECT and return its first packet after SUBSCRIBE
+    async fn accept(listener: &TcpListener) -> (TcpStream, Option<Packet>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut header = [0u8; 2];
+        stream.read_exact(&mut header).await.unwrap();
+        assert_eq!(header[0], 0x10);
+        let mut connect = vec![0; header[1] as usize];
+        stream.read_exact(&mut connect).await.unwrap();
+        stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+        let mut subscribe = [0u8; 2];
+        stream.read_exact(&mut subscribe).await.unwrap();
+        assert_eq!(subscribe[0], 0x82);
+        let mut body = vec![0; subscribe[1] as usize];
+        stream.read_exact(&mut body).await.unwrap();
+        stream.write_all(&[0x90, 0x03, body[0], body[1], 0x01]).await.unwrap();
+        let resent = tokio::time::timeout(Duration::from_millis(200), read_packet(&mut stream))
+            .await
+            .ok()
+            .map(|packet| packet.unwrap());
+        (stream, resent)
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_answered_and_unacked_responses_resent() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = Config::default();
+        config.gateway.mqtt.enabled = true;
+        config.gateway.mqtt.broker_url = format!("mqtt://{}", listener.local_addr().unwrap());
+        config.router.cloud_endpoints = vec![CloudEndpoint {
+            name: "scripted".to_string(),
+            url: "https://cloud.test".to_string(),
+            api_key: None,
+            timeout_ms: 1000,
+            max_retries: 0,
+            connect_timeout_ms: None,
+            region: None,
+            provider: Default::default(),
+            compression: Default::default(),
+        }];
+        let cloud = Arc::new(ScriptedCloudClient::new());
+        cloud.push_response(serde_json::json!({"text": "over mqtt"}));
+        let gateway = Gateway::builder(config)
+            .with_cloud_transport(cloud)
+            .deterministic()
+            .build()
+            .await
+            .unwrap();
+        tokio::spawn(MqttBridge::new(Arc::new(gateway)).unwrap().run());
+
+        let (mut stream, resent) = accept(&listener).await;
+        assert!(resent.is_none());
+        let request = Publish {
+            topic: "mcp/requests/sensor-7".to_string(),
+            qos: 1,
+            packet_id: Some(7),
+            dup: false,
+            payload: br#"{"method":"completion","params":{"prompt":"hello"}}"#.to_vec(),
+        };
+        stream.write_all(&encode_publish(&request)).await.unwrap();
+        assert_eq!(expect_packet(&mut stream).await, Packet::PubAck(7));
+        let Packet::Publish(response) = expect_packet(&mut stream).await else {
+            panic!("expected the response");
+        };
+        assert_eq!(response.topic, "mcp/responses/sensor-7");
+        let body: serde_json::Value = serde_json::from_slice(&response.payload).unwrap();
+        assert_eq!(body["response"]["result"]["text"], "over mqtt");
+
+        // Without a PUBACK the response is sent again after reconnecting
+        drop(stream);
+        let (_stream, resent) = accept(&listener).await;
+        let Some(Packet::Publish(resent)) = resent else {
+            panic!("expected the response again");
+        };
+        assert!(resent.dup);
+        assert_eq!((resent.packet_id, resent.payload), (response.packet_id, response.payload));
+    }
+}
@@ -0,0 +1,355 @@
+//! Text normalization and language detection
+//!
+//! With `normalization.enabled`, strings in the configured params are
+//! cleaned before a request is cached, routed or served, so text typed on
+//! different keyboards and devices reaches models, and the response cache,
+//! in one form. The language of the text is then guessed from its script
+//! and, for Latin text, from common words, and attached to the request's
+//! context. The guess is good enough to route by and to split metrics by;
+//! it does not replace a language model.
+
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
+use mcp_common::config::{NormalizationForm, TextNormalizationConfig};
+use mcp_common::MCPRequest;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Label of requests whose language could not be told
+pub const UNKNOWN_LANGUAGE: &str = "unknown";
+
+/// Common words of the Latin-script languages told apart, by ISO 639-1 code
+const LATIN_STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &[
+        "the", "and", "is", "are", "was", "of", "to", "with", "that", "this", "what", "how", "you",
+        "it", "for", "not", "be", "have", "can", "please",
+    ]),
+    ("es", &[
+        "el", "los", "las", "que", "es", "está", "por", "para", "con", "una", "qué", "cómo", "pero",
+        "muy", "del", "yo", "tiene", "puedes",
+    ]),
+    ("fr", &[
+        "le", "les", "des", "est", "et", "une", "pour", "dans", "pas", "qui", "je", "vous", "avec",
+        "sur", "ce", "mais", "très", "quoi",
+    ]),
+    ("de", &[
+        "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "ich", "zu", "mit", "wie", "auf",
+        "für", "sie", "was", "bitte", "kann",
+    ]),
+    ("it", &[
+        "il", "di", "che", "è", "non", "sono", "per", "una", "con", "come", "della", "del", "gli",
+        "mi", "cosa", "puoi", "questo", "molto",
+    ]),
+    ("pt", &[
+        "os", "que", "é", "não", "um", "uma", "para", "com", "em", "do", "da", "como", "você",
+        "está", "muito", "por", "isso", "pode",
+    ]),
+    ("nl", &[
+        "het", "een", "en", "is", "van", "niet", "dat", "ik", "je", "op", "met", "voor", "zijn",
+        "wat", "hoe", "kun", "deze", "graag",
+    ]),
+];
+
+/// Cleans request text and records the languages seen
+pub struct TextNormalizer {
+    config: TextNormalizationConfig,
+    /// `transliterate`, longest keys first
+    replacements: Vec<(String, String)>,
+    by_language: Mutex<HashMap<&'static str, u64>>,
+}
+
+impl TextNormalizer {
+    pub fn new(config: &TextNormalizationConfig) -> Self {
+        let mut replacements: Vec<(String, String)> = config
+            .transliterate
+            .iter()
+            .map(|(from, to)| (from.clone(), to.clone()))
+            .collect();
+        replacements.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        Self {
+            config: config.clone(),
+            replacements,
+            by_language: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Clean the request's text and attach its language; returns the language
+    pub fn apply(&self, request: &mut MCPRequest) -> Option<&'static str> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut text = String::new();
+        for field in &self.config.fields {
+            if let Some(value) = request.params.get_mut(field) {
+                self.normalize_value(value);
+                collect_text(value, &mut text);
+            }
+        }
+        if !self.config.detect_language {
+            return None;
+        }
+        let language = detect_language(&text, self.config.min_detection_letters);
+        if let Some(language) = language {
+            request.set_language(language);
+        }
+        let mut by_language = self.by_language.lock().unwrap_or_else(|e| e.into_inner());
+        *by_language.entry(language.unwrap_or(UNKNOWN_LANGUAGE)).or_insert(0) += 1;
+        language
+    }
+
+    /// Requests seen by detected language
+    pub fn requests_by_language(&self) -> Vec<(String, u64)> {
+        let by_language = self.by_language.lock().unwrap_or_else(|e| e.into_inner());
+        let mut counts: Vec<(String, u64)> = by_language
+            .iter()
+            .map(|(language, count)| (language.to_string(), *count))
+            .collect();
+        counts.sort();
+        counts
+    }
+
+    pub fn normalize(&self, text: &str) -> String {
+        let mut text = if self.config.strip_control_chars {
+            text.chars().filter(|c| !is_stripped_control(*c)).collect()
+        } else {
+            text.to_string()
+        };
+        text = match self.config.form {
+            NormalizationForm::None => text,
+            NormalizationForm::Nfc => ComposingNormalizerBorrowed::new_nfc().normalize(&text).into_owned(),
+            NormalizationForm::Nfkc => ComposingNormalizerBorrowed::new_nfkc().normalize(&text).into_owned(),
+        };
+        if self.config.strip_diacritics {
+            let decomposed = DecomposingNormalizerBorrowed::new_nfd().normalize(&text);
+            let stripped: String = decomposed.chars().filter(|c| !is_combining_mark(*c)).collect();
+            text = ComposingNormalizerBorrowed::new_nfc().normalize(&stripped).into_owned();
+        }
+        for (from, to) in &self.replacements {
+            if text.contains(from.as_str()) {
+                text = text.replace(from.as_str(), to);
+            }
+        }
+        text
+    }
+
+    fn normalize_value(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(text) => *text = self.normalize(text),
+            serde_json::Value::Array(values) => {
+                values.iter_mut().for_each(|value| self.normalize_value(value))
+            },
+            serde_json::Value::Object(fields) => {
+                fields.values_mut().for_each(|value| self.normalize_value(value))
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Control characters other than tab and line breaks, zero-width spaces and
+/// bidirectional overrides, which can hide text from a reader
+fn is_stripped_control(c: char) -> bool {
+    (c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+        || matches!(c, '\u{200B}' | '\u{FEFF}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Characters of the combining diacritical mark blocks
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Text of a param for language detection; of objects, such as chat
+/// messages, only `content` and `text` count
+fn collect_text(value: &serde_json::Value, text: &mut String) {
+    match value {
+        serde_json::Value::String(value) => {
+            text.push_str(value);
+            text.push(' ');
+        },
+        serde_json::Value::Array(values) => values.iter().for_each(|value| collect_text(value, text)),
+        serde_json::Value::Object(fields) => {
+            for key in ["content", "text"] {
+                if let Some(value) = fields.get(key) {
+                    collect_text(value, text);
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Thai,
+    Hangul,
+    Kana,
+    Han,
+}
+
+/// Every script, in declaration order
+const SCRIPTS: [Script; 10] = [
+    Script::Latin,
+    Script::Greek,
+    Script::Cyrillic,
+    Script::Hebrew,
+    Script::Arabic,
+    Script::Devanagari,
+    Script::Thai,
+    Script::Hangul,
+    Script::Kana,
+    Script::Han,
+];
+
+fn script(c: char) -> Option<Script> {
+    if !c.is_alphabetic() {
+        return None;
+    }
+    Some(match c {
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => Script::Latin,
+        '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Script::Greek,
+        '\u{0400}'..='\u{052F}' => Script::Cyrillic,
+        '\u{0590}'..='\u{05FF}' => Script::Hebrew,
+        '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}' => Script::Arabic,
+        '\u{0900}'..='\u{097F}' => Script::Devanagari,
+        '\u{0E00}'..='\u{0E7F}' => Script::Thai,
+        '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}' => Script::Hangul,
+        '\u{3040}'..='\u{30FF}' => Script::Kana,
+        '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' => Script::Han,
+        _ => return None,
+    })
+}
+
+/// ISO 639-1 code of the language `text` is most likely written in, once it
+/// has at least `min_letters` letters
+pub fn detect_language(text: &str, min_letters: usize) -> Option<&'static str> {
+    let mut counts = [0usize; SCRIPTS.len()];
+    for script in text.chars().filter_map(script) {
+        counts[script as usize] += 1;
+    }
+    let total: usize = counts.iter().sum();
+    if total == 0 || total < min_letters {
+        return None;
+    }
+    // Japanese mixes kana with Han characters; any real share of kana tells
+    // it apart from Chinese
+    if counts[Script::Kana as usize] * 10 >= total {
+        return Some("ja");
+    }
+    let (dominant, _) = counts.iter().enumerate().max_by_key(|(_, count)| **count)?;
+    match SCRIPTS[dominant] {
+        Script::Latin => latin_language(text),
+        Script::Cyrillic if text.contains(['і', 'ї', 'є', 'ґ']) => Some("uk"),
+        Script::Cyrillic => Some("ru"),
+        Script::Greek => Some("el"),
+        Script::Hebrew => Some("he"),
+        Script::Arabic => Some("ar"),
+        Script::Devanagari => Some("hi"),
+        Script::Thai => Some("th"),
+        Script::Hangul => Some("ko"),
+        Script::Kana => Some("ja"),
+        Script::Han => Some("zh"),
+    }
+}
+
+/// The Latin-script language with the most common words in `text`; the
+/// earlier language wins a tie
+fn latin_language(text: &str) -> Option<&'static str> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut best = None;
+    let mut best_score = 0;
+    for (language, stopwords) in LATIN_STOPWORDS {
+        let score = words.iter().filter(|word| stopwords.contains(&word.as_str())).count();
+        if score > best_score {
+            best = Some(*language);
+            best_score = score;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizer(configure: impl FnOnce(&mut TextNormalizationConfig)) -> TextNormalizer {
+        let mut config = TextNormalizationConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        configure(&mut config);
+        TextNormalizer::new(&config)
+    }
+
+    #[test]
+    fn test_normalization_steps() {
+        // "e" followed by a combining acute accent composes into "é"
+        let nfc = normalizer(|_| {});
+        assert_eq!(nfc.normalize("cafe\u{0301}\u{0007} \u{202E}menu\u{200B}\n"), "café menu\n");
+
+        let folded = normalizer(|config| {
+            config.form = NormalizationForm::Nfkc;
+            config.strip_diacritics = true;
+            config.transliterate = HashMap::from([("ß".to_string(), "ss".to_string())]);
+        });
+        assert_eq!(folded.normalize("Straße ﬁnden, Café Ｔ２"), "Strasse finden, Cafe T2");
+    }
+
+    #[test]
+    fn test_detects_languages() {
+        let detect = |text: &str| detect_language(text, 12);
+        assert_eq!(detect("What is the weather like in Berlin today?"), Some("en"));
+        assert_eq!(detect("¿Cuál es el mejor camino para llegar a la estación?"), Some("es"));
+        assert_eq!(detect("Wie ist das Wetter heute in Berlin und morgen?"), Some("de"));
+        assert_eq!(detect("Какая погода сегодня в Москве?"), Some("ru"));
+        assert_eq!(detect("東京の今日の天気はどうですか、教えてください"), Some("ja"));
+        assert_eq!(detect("今天北京的天气怎么样，请告诉我"), Some("zh"));
+        assert_eq!(detect("ok thanks"), None);
+    }
+
+    #[test]
+    fn test_apply_cleans_fields_and_attaches_the_language() {
+        let normalizer = normalizer(|_| {});
+        let mut request = MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "kiosk-1".to_string(),
+            method: "chat".to_string(),
+            params: HashMap::from([
+                (
+                    "messages".to_string(),
+                    serde_json::json!([
+                        { "role": "user", "content": "Où est la gare\u{0000}, s'il vous plaît?" }
+                    ]),
+                ),
+                ("session_id".to_string(), serde_json::json!("a\u{0000}b")),
+            ]),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        };
+        assert_eq!(normalizer.apply(&mut request), Some("fr"));
+        assert_eq!(request.language(), Some("fr"));
+        assert_eq!(request.params["messages"][0]["content"], "Où est la gare, s'il vous plaît?");
+        // Params outside `fields` are left alone
+        assert_eq!(request.params["session_id"], "a\u{0000}b");
+        assert_eq!(normalizer.requests_by_language(), [("fr".to_string(), 1)]);
+    }
+}
@@ -548,12 +548,15 @@ mod tests {
 
     #[tokio::test]
     async fn test_execute_with_resilience() {
-        let mut attempt_count = 0;
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let attempt_count = Arc::new(AtomicU32::new(0));
         
-        let operation = || {
-            attempt_count += 1;
+        let attempts = attempt_count.clone();
+        let operation = move || -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<&'static str, &'static str>> + Send>> {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
             Box::pin(async move {
-                if attempt_count < 3 {
+                if attempt < 3 {
                     Err("simulated failure")
                 } else {
                     Ok("success")
@@ -570,6 +573,6 @@ mod tests {
 
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "success");
-        assert_eq!(attempt_count, 3);
+        assert_eq!(attempt_count.load(Ordering::SeqCst), 3);
     }
 }
\ No newline at end of file
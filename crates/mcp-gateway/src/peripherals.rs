@@ -0,0 +1,492 @@
+//! Camera frame ingestion
+//!
+//! Each camera in `peripherals.cameras` is a [`FrameSource`] capturing one
+//! JPEG frame at a time from a V4L2 device, a libcamera camera, an RTSP
+//! stream or a custom command. Frames are captured every `interval_secs`, or
+//! when a client calls [`CAMERA_CAPTURE_METHOD`], and sent through the
+//! gateway as the camera's method with the image base64 encoded in the
+//! `image` param, so the router runs them on a vision-capable local model or
+//! forwards them to the cloud. Every outcome is published as a
+//! [`FRAME_NOTIFICATION`] to MCP WebSocket clients.
+
+use crate::gateway::Gateway;
+use async_trait::async_trait;
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use mcp_common::config::{CameraConfig, FrameSourceKind, PeripheralsConfig};
+use mcp_common::{Error, MCPRequest, MCPResponse, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Method clients call to capture a frame now, with the `camera` param
+pub const CAMERA_CAPTURE_METHOD: &str = "camera.capture";
+
+/// Notification carrying the [`FrameResult`] of every captured frame
+pub const FRAME_NOTIFICATION: &str = "notifications/camera/frame";
+
+/// Frame results buffered for each slow WebSocket client
+const NOTIFICATION_CAPACITY: usize = 64;
+
+/// One captured image
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub source: String,
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    pub captured_at: DateTime<Utc>,
+}
+
+/// Something frames can be captured from
+#[async_trait]
+pub trait FrameSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn capture(&self) -> Result<Frame>;
+}
+
+/// Frame source reading a JPEG from the stdout of a capture program
+pub struct CommandFrameSource {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+    max_frame_bytes: usize,
+}
+
+impl CommandFrameSource {
+    pub fn new(config: &CameraConfig) -> Self {
+        let (program, args) = capture_command(&config.source);
+        Self {
+            name: config.name.clone(),
+            program,
+            args,
+            timeout: Duration::from_millis(config.capture_timeout_ms),
+            max_frame_bytes: config.max_frame_bytes,
+        }
+    }
+
+    async fn read_frame(&self) -> Result<Vec<u8>> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Error::Internal(format!("Failed to run {}: {}", self.program, e)))?;
+        let Some(stdout) = child.stdout.take() else {
+            return Err(Error::Internal(format!("{} has no stdout", self.program)));
+        };
+
+        // Read one byte past the limit to tell a full frame from a cut one
+        let mut data = Vec::new();
+        stdout
+            .take(self.max_frame_bytes as u64 + 1)
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to read frame from {}: {}", self.name, e)))?;
+        if data.len() > self.max_frame_bytes {
+            return Err(Error::ResourceExhausted(format!(
+                "Frame from {} is over {} bytes",
+                self.name, self.max_frame_bytes
+            )));
+        }
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| Error::Internal(format!("Failed to wait for {}: {}", self.program, e)))?;
+        if !status.success() {
+            return Err(Error::Internal(format!(
+                "{} exited with {}",
+                self.program, status
+            )));
+        }
+        if data.is_empty() {
+            return Err(Error::Internal(format!(
+                "{} captured an empty frame",
+                self.name
+            )));
+        }
+        Ok(data)
+    }
+}
+
+#[async_trait]
+impl FrameSource for CommandFrameSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn capture(&self) -> Result<Frame> {
+        let data = tokio::time::timeout(self.timeout, self.read_frame())
+            .await
+            .map_err(|_| {
+                Error::Timeout(format!(
+                    "Capturing from {} took over {:?}",
+                    self.name, self.timeout
+                ))
+            })??;
+        Ok(Frame {
+            source: self.name.clone(),
+            mime_type: mime_type(&data).to_string(),
+            data,
+            captured_at: Utc::now(),
+        })
+    }
+}
+
+/// Program and arguments writing one frame of `source` to stdout
+fn capture_command(source: &FrameSourceKind) -> (String, Vec<String>) {
+    let to_stdout = ["-frames:v", "1", "-f", "image2pipe", "-vcodec", "mjpeg", "-"];
+    let ffmpeg = |input: Vec<String>| {
+        let mut args: Vec<String> = ["-hide_banner", "-loglevel", "error"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        args.extend(input);
+        args.extend(to_stdout.iter().map(|arg| arg.to_string()));
+        ("ffmpeg".to_string(), args)
+    };
+    match source {
+        FrameSourceKind::V4l2 {
+            device,
+            width,
+            height,
+        } => {
+            let mut input = vec!["-f".to_string(), "v4l2".to_string()];
+            if let (Some(width), Some(height)) = (width, height) {
+                input.extend(["-video_size".to_string(), format!("{}x{}", width, height)]);
+            }
+            input.extend(["-i".to_string(), device.to_string_lossy().into_owned()]);
+            ffmpeg(input)
+        },
+        FrameSourceKind::Libcamera {
+            camera,
+            width,
+            height,
+        } => {
+            let mut args = vec![
+                "--camera".to_string(),
+                camera.to_string(),
+                "--nopreview".to_string(),
+                "--immediate".to_string(),
+                "--encoding".to_string(),
+                "jpg".to_string(),
+                "-o".to_string(),
+                "-".to_string(),
+            ];
+            if let Some(width) = width {
+                args.extend(["--width".to_string(), width.to_string()]);
+            }
+            if let Some(height) = height {
+                args.extend(["--height".to_string(), height.to_string()]);
+            }
+            ("libcamera-still".to_string(), args)
+        },
+        FrameSourceKind::Rtsp {
+            url,
+            udp,
+        } => {
+            let transport = if *udp { "udp" } else { "tcp" };
+            ffmpeg(vec![
+                "-rtsp_transport".to_string(),
+                transport.to_string(),
+                "-i".to_string(),
+                url.clone(),
+            ])
+        },
+        FrameSourceKind::Command {
+            program,
+            args,
+        } => (program.clone(), args.clone()),
+    }
+}
+
+/// MIME type from the image's magic bytes
+fn mime_type(data: &[u8]) -> &'static str {
+    if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        "image/jpeg"
+    } else if data.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Outcome of sending one frame through the gateway
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameResult {
+    pub camera: String,
+    pub request_id: Uuid,
+    pub captured_at: DateTime<Utc>,
+    pub frame_bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<MCPResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Captures frames from the configured cameras and publishes the results
+pub struct Peripherals {
+    cameras: Vec<(CameraConfig, Arc<dyn FrameSource>)>,
+    results: broadcast::Sender<FrameResult>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Peripherals {
+    pub fn new(config: PeripheralsConfig) -> Self {
+        let cameras = config
+            .cameras
+            .into_iter()
+            .map(|camera| {
+                let source: Arc<dyn FrameSource> = Arc::new(CommandFrameSource::new(&camera));
+                (camera, source)
+            })
+            .collect();
+        Self::with_cameras(cameras)
+    }
+
+    /// Peripherals capturing from the given sources instead of the
+    /// configured commands
+    pub fn with_cameras(cameras: Vec<(CameraConfig, Arc<dyn FrameSource>)>) -> Self {
+        let (results, _) = broadcast::channel(NOTIFICATION_CAPACITY);
+        Self {
+            cameras,
+            results,
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.cameras.is_empty()
+    }
+
+    /// Names of the configured cameras
+    pub fn camera_names(&self) -> Vec<String> {
+        self.cameras
+            .iter()
+            .map(|(camera, _)| camera.name.clone())
+            .collect()
+    }
+
+    /// Capture from each camera with an `interval_secs` in the background
+    pub fn start(self: &Arc<Self>, gateway: Weak<Gateway>) {
+        let handles: Vec<JoinHandle<()>> = self
+            .cameras
+            .iter()
+            .filter_map(|(camera, _)| Some((camera.name.clone(), camera.interval_secs?)))
+            .map(|(name, interval_secs)| {
+                let peripherals = Arc::downgrade(self);
+                let gateway = gateway.clone();
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+                    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                    loop {
+                        interval.tick().await;
+                        let (Some(peripherals), Some(gateway)) =
+                            (peripherals.upgrade(), gateway.upgrade())
+                        else {
+                            break;
+                        };
+                        if let Err(e) = peripherals
+                            .capture(&name, |request| gateway.process_request(request))
+                            .await
+                        {
+                            warn!("Scheduled capture from {} failed: {}", name, e);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for previous in std::mem::replace(&mut *self.lock_tasks(), handles) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        for handle in self.lock_tasks().drain(..) {
+            handle.abort();
+        }
+    }
+
+    /// Capture a frame from `camera`, send it through `send` and publish
+    /// the outcome; fails only when the frame cannot be captured
+    pub async fn capture<F, Fut>(&self, camera: &str, send: F) -> Result<FrameResult>
+    where
+        F: FnOnce(MCPRequest) -> Fut,
+        Fut: Future<Output = Result<MCPResponse>>,
+    {
+        let Some((config, source)) = self.cameras.iter().find(|(config, _)| config.name == camera)
+        else {
+            return Err(Error::InvalidRequest(format!("Unknown camera '{}'", camera)));
+        };
+        let frame = source.capture().await?;
+        debug!(
+            "Captured {} byte frame from {}",
+            frame.data.len(),
+            frame.source
+        );
+
+        let request = frame_request(config, &frame);
+        let request_id = request.id;
+        let (response, error) = match send(request).await {
+            Ok(response) => (Some(response), None),
+            Err(e) => {
+                warn!("Frame {} from {} failed: {}", request_id, camera, e);
+                (None, Some(e.to_string()))
+            },
+        };
+        let result = FrameResult {
+            camera: camera.to_string(),
+            request_id,
+            captured_at: frame.captured_at,
+            frame_bytes: frame.data.len(),
+            response,
+            error,
+        };
+        // Nobody listening is fine; results are also returned to the caller
+        let _ = self.results.send(result.clone());
+        Ok(result)
+    }
+
+    /// Results of frames captured from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<FrameResult> {
+        self.results.subscribe()
+    }
+
+    fn lock_tasks(&self) -> std::sync::MutexGuard<'_, Vec<JoinHandle<()>>> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Request sending `frame` as `camera`'s method, with its params
+fn frame_request(camera: &CameraConfig, frame: &Frame) -> MCPRequest {
+    let mut params: HashMap<String, serde_json::Value> = camera.params.clone();
+    params.insert(
+        "image".to_string(),
+        serde_json::json!(base64::engine::general_purpose::STANDARD.encode(&frame.data)),
+    );
+    params.insert("mime_type".to_string(), serde_json::json!(frame.mime_type));
+    params.insert("camera".to_string(), serde_json::json!(frame.source));
+    params.insert("captured_at".to_string(), serde_json::json!(frame.captured_at));
+    MCPRequest {
+        id: Uuid::new_v4(),
+        device_id: format!("camera-{}", camera.name),
+        method: camera.method.clone(),
+        params,
+        context: None,
+        timestamp: Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(name: &str, source: FrameSourceKind) -> CameraConfig {
+        CameraConfig {
+            name: name.to_string(),
+            source,
+            params: HashMap::from([("prompt".to_string(), serde_json::json!("Count the people"))]),
+            max_frame_bytes: 16,
+            ..Default::default()
+        }
+    }
+
+    fn printf(output: &str) -> FrameSourceKind {
+        FrameSourceKind::Command {
+            program: "printf".to_string(),
+            args: vec![output.to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frames_are_sent_as_vision_requests_and_published() {
+        let peripherals = Peripherals::new(PeripheralsConfig {
+            cameras: vec![
+                camera("door", printf("\\377\\330\\377frame")),
+                camera("yard", printf("an image far over the limit")),
+            ],
+            ..Default::default()
+        });
+        let mut results = peripherals.subscribe();
+
+        let result = peripherals
+            .capture("door", |request| async move {
+                assert_eq!(request.method, "vision");
+                assert_eq!(request.device_id, "camera-door");
+                assert_eq!(request.params["prompt"], "Count the people");
+                assert_eq!(request.params["mime_type"], "image/jpeg");
+                let image = base64::engine::general_purpose::STANDARD
+                    .decode(request.params["image"].as_str().unwrap())
+                    .unwrap();
+                assert_eq!(image, b"\xff\xd8\xffframe");
+                Ok(MCPResponse {
+                    id: request.id,
+                    result: Some(serde_json::json!({ "people": 2 })),
+                    error: None,
+                    timestamp: Utc::now(),
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(result.frame_bytes, 8);
+        let published = results.recv().await.unwrap();
+        assert_eq!(published.request_id, result.request_id);
+        assert_eq!(published.response.unwrap().result.unwrap()["people"], 2);
+
+        // A failed request is still published, with its error
+        let failed = peripherals
+            .capture("door", |_| async { Err(Error::Routing("no vision model".to_string())) })
+            .await
+            .unwrap();
+        assert!(failed.error.unwrap().contains("no vision model"));
+
+        let oversized = peripherals.capture("yard", |_| async { unreachable!() }).await;
+        assert!(matches!(oversized, Err(Error::ResourceExhausted(_))));
+        let unknown = peripherals.capture("garage", |_| async { unreachable!() }).await;
+        assert!(matches!(unknown, Err(Error::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn test_capture_commands_per_source() {
+        let (program, args) = capture_command(&FrameSourceKind::V4l2 {
+            device: "/dev/video2".into(),
+            width: Some(640),
+            height: Some(480),
+        });
+        assert_eq!(program, "ffmpeg");
+        let args = args.join(" ");
+        assert!(args.contains("-f v4l2 -video_size 640x480 -i /dev/video2 -frames:v 1"));
+
+        let (program, args) = capture_command(&FrameSourceKind::Rtsp {
+            url: "rtsp://10.0.0.5/stream".to_string(),
+            udp: false,
+        });
+        assert_eq!(program, "ffmpeg");
+        assert!(args
+            .join(" ")
+            .contains("-rtsp_transport tcp -i rtsp://10.0.0.5/stream"));
+
+        let (program, args) = capture_command(&FrameSourceKind::Libcamera {
+            camera: 1,
+            width: None,
+            height: None,
+        });
+        assert_eq!(program, "libcamera-still");
+        assert_eq!(args[..2], ["--camera".to_string(), "1".to_string()]);
+        assert_eq!(args.last().unwrap(), "-");
+    }
+}
@@ -0,0 +1,215 @@
+//! Power management for battery-powered devices
+//!
+//! The [`PowerManager`] reads the battery every `power.sleep_on_idle_ms`
+//! and keeps the gateway in low-power mode while `power.low_power_mode` is
+//! set or the battery is below a `power.battery_thresholds` level. Entering
+//! low-power mode throttles local inference to `cpu_throttle_percent` of its
+//! concurrency and replaces the queue's interval and connectivity syncs with
+//! one that waits for a batch; while in it, idle models are unloaded. The
+//! lower the battery, the longer the manager sleeps between readings and
+//! batch checks. Leaving low-power mode restores the configured concurrency
+//! and sync policies.
+
+use mcp_common::config::{Config, PowerConfig, SyncPolicy};
+use mcp_models::ModelEngine;
+use mcp_queue::OfflineQueue;
+use mcp_router::routing_policy::read_battery;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+
+/// How the gateway runs at a battery level
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PowerPlan {
+    pub low_power: bool,
+    /// `None` on mains-powered devices
+    pub battery_percent: Option<f64>,
+    /// Share of the local inference concurrency in use
+    pub inference_percent: u8,
+    /// Time until the next battery reading
+    pub sleep_interval_ms: u64,
+}
+
+impl PowerPlan {
+    pub fn for_battery(config: &PowerConfig, battery_percent: Option<f64>) -> Self {
+        // The lowest threshold the battery is under sets the sleep depth
+        let threshold = battery_percent.and_then(|battery| {
+            config
+                .battery_thresholds
+                .iter()
+                .filter(|threshold| battery < threshold.below_percent)
+                .min_by(|a, b| a.below_percent.total_cmp(&b.below_percent))
+        });
+        let low_power = config.low_power_mode || threshold.is_some();
+        let multiplier = threshold.map_or(1, |threshold| threshold.sleep_multiplier);
+        Self {
+            low_power,
+            battery_percent,
+            inference_percent: if low_power { config.cpu_throttle_percent } else { 100 },
+            sleep_interval_ms: config.sleep_on_idle_ms.saturating_mul(multiplier as u64),
+        }
+    }
+
+    fn full_power(config: &PowerConfig) -> Self {
+        Self {
+            low_power: false,
+            battery_percent: None,
+            inference_percent: 100,
+            sleep_interval_ms: config.sleep_on_idle_ms,
+        }
+    }
+}
+
+/// Moves the gateway in and out of low-power mode
+pub struct PowerManager {
+    config: PowerConfig,
+    /// Sync policies from the configuration, restored at full power
+    sync_policies: Vec<SyncPolicy>,
+    engine: Arc<dyn ModelEngine + Send + Sync>,
+    queue: Arc<dyn OfflineQueue + Send + Sync>,
+    plan: Mutex<PowerPlan>,
+}
+
+impl PowerManager {
+    pub fn new(
+        config: &Config,
+        engine: Arc<dyn ModelEngine + Send + Sync>,
+        queue: Arc<dyn OfflineQueue + Send + Sync>,
+    ) -> Self {
+        Self {
+            config: config.power.clone(),
+            sync_policies: config.queue.sync_policies.clone(),
+            engine,
+            queue,
+            plan: Mutex::new(PowerPlan::full_power(&config.power)),
+        }
+    }
+
+    /// Read the battery and apply its plan until the manager is dropped
+    pub fn start(self: &Arc<Self>) {
+        let manager = Arc::downgrade(self);
+        let power_supply_dir = self.config.power_supply_dir.clone();
+        tokio::spawn(async move {
+            loop {
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                let plan = manager.apply(read_battery(&power_supply_dir)).await;
+                drop(manager);
+                tokio::time::sleep(Duration::from_millis(plan.sleep_interval_ms)).await;
+            }
+        });
+    }
+
+    /// Switch to the plan for a battery reading
+    pub async fn apply(&self, battery_percent: Option<f64>) -> PowerPlan {
+        let plan = PowerPlan::for_battery(&self.config, battery_percent);
+        let previous = std::mem::replace(&mut *self.lock_plan(), plan.clone());
+
+        if plan.low_power != previous.low_power {
+            match plan.battery_percent {
+                Some(battery) => info!(
+                    "{} low-power mode at {:.0}% battery",
+                    if plan.low_power { "Entering" } else { "Leaving" },
+                    battery
+                ),
+                None => info!("{} low-power mode", if plan.low_power { "Entering" } else { "Leaving" }),
+            }
+        }
+        if plan.inference_percent != previous.inference_percent {
+            self.engine.throttle_inference(plan.inference_percent);
+        }
+        if (plan.low_power, plan.sleep_interval_ms) != (previous.low_power, previous.sleep_interval_ms) {
+            self.queue.set_sync_policies(&self.sync_policies_for(&plan));
+        }
+        if plan.low_power && self.config.idle_model_unload_secs > 0 {
+            let idle = Duration::from_secs(self.config.idle_model_unload_secs);
+            let unloaded = self.engine.unload_idle_models(idle).await;
+            if !unloaded.is_empty() {
+                info!("Unloaded idle models to save power: {}", unloaded.join(", "));
+            }
+        }
+        plan
+    }
+
+    /// The plan in effect
+    pub fn status(&self) -> PowerPlan {
+        self.lock_plan().clone()
+    }
+
+    /// Configured sync policies, with interval and connectivity syncs
+    /// batched in low-power mode
+    fn sync_policies_for(&self, plan: &PowerPlan) -> Vec<SyncPolicy> {
+        if !plan.low_power {
+            return self.sync_policies.clone();
+        }
+        let (batched, mut policies): (Vec<SyncPolicy>, Vec<SyncPolicy>) =
+            self.sync_policies.iter().cloned().partition(|policy| {
+                matches!(policy, SyncPolicy::Interval { .. } | SyncPolicy::Connectivity { .. })
+            });
+        if !batched.is_empty() {
+            policies.push(SyncPolicy::Threshold {
+                max_depth: Some(self.config.sync_batch_size),
+                max_age_seconds: Some(self.config.sync_batch_max_age_secs),
+                check_interval_seconds: (plan.sleep_interval_ms / 1000).max(1),
+            });
+        }
+        policies
+    }
+
+    fn lock_plan(&self) -> std::sync::MutexGuard<'_, PowerPlan> {
+        self.plan.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{InMemoryQueue, StubModelEngine};
+
+    #[test]
+    fn test_lowest_threshold_reached_sets_the_sleep_depth() {
+        let config = PowerConfig::default();
+        let mains = PowerPlan::for_battery(&config, None);
+        assert_eq!((mains.low_power, mains.inference_percent, mains.sleep_interval_ms), (false, 100, 1000));
+
+        assert!(!PowerPlan::for_battery(&config, Some(80.0)).low_power);
+        let low = PowerPlan::for_battery(&config, Some(25.0));
+        assert_eq!((low.low_power, low.inference_percent, low.sleep_interval_ms), (true, 50, 2000));
+        assert_eq!(PowerPlan::for_battery(&config, Some(10.0)).sleep_interval_ms, 4000);
+
+        let forced = PowerConfig {
+            low_power_mode: true,
+            ..PowerConfig::default()
+        };
+        let plan = PowerPlan::for_battery(&forced, None);
+        assert_eq!((plan.low_power, plan.sleep_interval_ms), (true, 1000));
+    }
+
+    #[tokio::test]
+    async fn test_low_battery_throttles_and_batches_syncs_until_recharged() {
+        let config = Config::default();
+        let engine = Arc::new(StubModelEngine::new());
+        let queue = Arc::new(InMemoryQueue::new(10));
+        let manager = PowerManager::new(&config, engine.clone(), queue.clone());
+
+        // A battery reading that changes nothing leaves the queue alone
+        manager.apply(Some(90.0)).await;
+        assert_eq!(queue.sync_policies(), None);
+
+        manager.apply(Some(20.0)).await;
+        assert_eq!(engine.inference_percent(), 50);
+        let batched = SyncPolicy::Threshold {
+            max_depth: Some(20),
+            max_age_seconds: Some(300),
+            check_interval_seconds: 2,
+        };
+        assert_eq!(queue.sync_policies(), Some(vec![batched]));
+
+        manager.apply(Some(90.0)).await;
+        assert!(!manager.status().low_power);
+        assert_eq!(engine.inference_percent(), 100);
+        assert_eq!(queue.sync_policies(), Some(config.queue.sync_policies));
+    }
+}
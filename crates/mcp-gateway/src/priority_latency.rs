@@ -0,0 +1,129 @@
+//! End-to-end latency per request priority
+//!
+//! Latency is measured from the request's own timestamp (corrected for device
+//! clock skew) to the moment the gateway has a result, so time a request spent
+//! in a device or gateway queue before being replayed counts against it. This
+//! shows whether priority actually survives the queue, the inference limiter
+//! and cloud forwarding rather than only the queue ordering.
+
+use chrono::{DateTime, Utc};
+use mcp_common::Priority;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// Recent samples kept per priority for percentiles
+const WINDOW: usize = 512;
+
+/// Latency summary for one priority, as exposed by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct PriorityLatencyReport {
+    pub priority: Priority,
+    pub requests: u64,
+    /// Requests that had been replayed from an offline queue
+    pub replayed: u64,
+    pub avg_ms: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Default)]
+struct Samples {
+    requests: u64,
+    replayed: u64,
+    total_ms: u64,
+    max_ms: u64,
+    recent: VecDeque<u64>,
+}
+
+/// Tracks end-to-end latency per priority
+#[derive(Default)]
+pub struct PriorityLatencyTracker {
+    priorities: Mutex<HashMap<Priority, Samples>>,
+}
+
+impl PriorityLatencyTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a completed request that was issued at `issued_at`
+    pub fn record(&self, priority: Priority, issued_at: DateTime<Utc>, completed_at: DateTime<Utc>, replayed: bool) {
+        let latency_ms = completed_at.signed_duration_since(issued_at).num_milliseconds().max(0) as u64;
+        let mut priorities = self.priorities.lock();
+        let samples = priorities.entry(priority).or_default();
+        samples.requests += 1;
+        samples.replayed += u64::from(replayed);
+        samples.total_ms = samples.total_ms.saturating_add(latency_ms);
+        samples.max_ms = samples.max_ms.max(latency_ms);
+        if samples.recent.len() == WINDOW {
+            samples.recent.pop_front();
+        }
+        samples.recent.push_back(latency_ms);
+    }
+
+    /// Summaries for every priority seen so far, most urgent first
+    pub fn report(&self) -> Vec<PriorityLatencyReport> {
+        let priorities = self.priorities.lock();
+        Priority::ALL
+            .iter()
+            .filter_map(|priority| {
+                let samples = priorities.get(priority)?;
+                let mut recent: Vec<u64> = samples.recent.iter().copied().collect();
+                recent.sort_unstable();
+                Some(PriorityLatencyReport {
+                    priority: *priority,
+                    requests: samples.requests,
+                    replayed: samples.replayed,
+                    avg_ms: samples.total_ms as f64 / samples.requests as f64,
+                    p50_ms: percentile(&recent, 0.50),
+                    p95_ms: percentile(&recent, 0.95),
+                    max_ms: samples.max_ms,
+                })
+            })
+            .collect()
+    }
+}
+
+fn percentile(sorted: &[u64], quantile: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_latency_is_measured_from_issue_time() {
+        let tracker = PriorityLatencyTracker::new();
+        let now = Utc::now();
+        tracker.record(Priority::Critical, now - Duration::milliseconds(40), now, false);
+        tracker.record(Priority::Critical, now - Duration::seconds(30), now, true);
+        tracker.record(Priority::Low, now - Duration::milliseconds(900), now, false);
+
+        let report = tracker.report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].priority, Priority::Critical);
+        assert_eq!(report[0].requests, 2);
+        assert_eq!(report[0].replayed, 1);
+        assert_eq!(report[0].max_ms, 30_000);
+        assert_eq!(report[1].p50_ms, 900);
+    }
+
+    #[test]
+    fn test_future_timestamps_count_as_zero_latency() {
+        let tracker = PriorityLatencyTracker::new();
+        let now = Utc::now();
+        tracker.record(Priority::Normal, now + Duration::seconds(5), now, false);
+
+        let report = tracker.report();
+        assert_eq!(report[0].avg_ms, 0.0);
+        assert_eq!(report[0].p95_ms, 0);
+    }
+}
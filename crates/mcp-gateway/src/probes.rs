@@ -0,0 +1,350 @@
+//! Health probes for load balancers
+//!
+//! HAProxy, Envoy and NGINX eject backends based on HTTP status codes or the
+//! standard gRPC Health Checking Protocol (`grpc.health.v1.Health`). The
+//! status-only endpoint `/healthz` answers with a bare 200 or 503, and
+//! `/grpc.health.v1.Health/Check` answers over HTTP/2 for the gateway as a
+//! whole (service `""` or `mcp.gateway`) or for a single component such as
+//! `queue` or `model_engine`.
+//!
+//! Probe results are cached for `cache_ttl_ms` so aggressive check intervals
+//! across many load balancers do not each run the full component checks.
+//! Probes are routed around the request rate limiter, and need a bearer
+//! token only when the configured auth exemption is turned off.
+
+use crate::gateway::Gateway;
+use crate::handlers::AppState;
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use http_body::Frame;
+use mcp_common::config::HealthCheckConfig;
+use mcp_common::HealthLevel;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// gRPC service name for the gateway as a whole
+pub const GATEWAY_SERVICE: &str = "mcp.gateway";
+
+const GRPC_CONTENT_TYPE: &str = "application/grpc";
+const GRPC_OK: u32 = 0;
+const GRPC_INVALID_ARGUMENT: u32 = 3;
+const GRPC_NOT_FOUND: u32 = 5;
+const GRPC_UNIMPLEMENTED: u32 = 12;
+const GRPC_UNAUTHENTICATED: u32 = 16;
+
+/// `grpc.health.v1.HealthCheckResponse.ServingStatus`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServingStatus {
+    Unknown = 0,
+    Serving = 1,
+    NotServing = 2,
+    ServiceUnknown = 3,
+}
+
+/// Health levels a probe answers from
+#[derive(Debug, Clone)]
+pub struct ProbeSnapshot {
+    pub overall: HealthLevel,
+    pub components: HashMap<String, HealthLevel>,
+    pub maintenance: bool,
+}
+
+impl ProbeSnapshot {
+    /// Serving status of the gateway (`""` or [`GATEWAY_SERVICE`]) or one component
+    pub fn status(&self, service: &str, config: &HealthCheckConfig) -> ServingStatus {
+        let level = match service {
+            "" | GATEWAY_SERVICE => {
+                if self.maintenance && config.drain_during_maintenance {
+                    return ServingStatus::NotServing;
+                }
+                &self.overall
+            },
+            component => match self.components.get(component) {
+                Some(level) => level,
+                None => return ServingStatus::ServiceUnknown,
+            },
+        };
+        match level {
+            HealthLevel::Critical => ServingStatus::NotServing,
+            HealthLevel::Degraded if config.fail_on_degraded => ServingStatus::NotServing,
+            _ => ServingStatus::Serving,
+        }
+    }
+}
+
+/// Cached health status served to probes
+pub struct HealthProbe {
+    config: HealthCheckConfig,
+    cached: RwLock<Option<(Instant, ProbeSnapshot)>>,
+}
+
+impl HealthProbe {
+    pub fn new(config: HealthCheckConfig) -> Self {
+        Self {
+            config,
+            cached: RwLock::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &HealthCheckConfig {
+        &self.config
+    }
+
+    /// Whether a probe request may be answered
+    pub fn authorized(&self, headers: &HeaderMap) -> bool {
+        if self.config.auth_exempt {
+            return true;
+        }
+        let presented = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        presented.is_some() && presented == self.config.probe_token.as_deref()
+    }
+
+    /// Current snapshot, refreshed once the cached one is older than `cache_ttl_ms`
+    pub async fn snapshot(&self, gateway: &Gateway) -> ProbeSnapshot {
+        let ttl = Duration::from_millis(self.config.cache_ttl_ms);
+        if let Some((at, snapshot)) = self.cached.read().await.as_ref() {
+            if at.elapsed() < ttl {
+                return snapshot.clone();
+            }
+        }
+
+        let snapshot = match gateway.health_check().await {
+            Ok(health) => ProbeSnapshot {
+                overall: health.overall_health,
+                components: health
+                    .components
+                    .into_iter()
+                    .map(|(name, component)| (name, component.status))
+                    .collect(),
+                maintenance: gateway.maintenance().is_active().await,
+            },
+            Err(e) => {
+                warn!("Health probe check failed: {}", e);
+                ProbeSnapshot {
+                    overall: HealthLevel::Critical,
+                    components: HashMap::new(),
+                    maintenance: false,
+                }
+            },
+        };
+        *self.cached.write().await = Some((Instant::now(), snapshot.clone()));
+        snapshot
+    }
+
+    /// Serving status of the gateway or one component
+    pub async fn status(&self, gateway: &Gateway, service: &str) -> ServingStatus {
+        self.snapshot(gateway).await.status(service, &self.config)
+    }
+}
+
+/// Probe routes, kept outside the rate limiter by `Server`
+pub fn routes(gateway: Arc<Gateway>) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/grpc.health.v1.Health/Check", post(grpc_check))
+        .route("/grpc.health.v1.Health/Watch", post(grpc_watch))
+        .with_state(gateway)
+}
+
+/// Status-only health check: 200 while serving, 503 otherwise
+pub async fn healthz(State(gateway): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let probe = gateway.health_probe();
+    if !probe.authorized(&headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let (status, body) = match probe.status(&gateway, "").await {
+        ServingStatus::Serving => (StatusCode::OK, "SERVING"),
+        _ => (StatusCode::SERVICE_UNAVAILABLE, "NOT_SERVING"),
+    };
+    (status, [(header::CACHE_CONTROL, "no-store")], body).into_response()
+}
+
+/// `grpc.health.v1.Health/Check`
+pub async fn grpc_check(State(gateway): State<AppState>, headers: HeaderMap, body: Bytes) -> Response {
+    let probe = gateway.health_probe();
+    if !probe.authorized(&headers) {
+        return grpc_error(GRPC_UNAUTHENTICATED, "Missing or invalid probe token");
+    }
+    let Some(service) = decode_check_request(&body) else {
+        return grpc_error(GRPC_INVALID_ARGUMENT, "Malformed HealthCheckRequest");
+    };
+    match probe.status(&gateway, &service).await {
+        ServingStatus::ServiceUnknown => grpc_error(GRPC_NOT_FOUND, &format!("Unknown service {}", service)),
+        status => {
+            let mut trailers = HeaderMap::new();
+            trailers.insert("grpc-status", HeaderValue::from(GRPC_OK));
+            let body = GrpcBody {
+                message: Some(encode_check_response(status)),
+                trailers: Some(trailers),
+            };
+            ([(header::CONTENT_TYPE, GRPC_CONTENT_TYPE)], Body::new(body)).into_response()
+        },
+    }
+}
+
+/// `grpc.health.v1.Health/Watch` is not offered; load balancers poll `Check`
+pub async fn grpc_watch() -> Response {
+    grpc_error(GRPC_UNIMPLEMENTED, "Watch is not supported, use Check")
+}
+
+/// Trailers-only gRPC response carrying an error status
+fn grpc_error(code: u32, message: &str) -> Response {
+    let mut response = StatusCode::OK.into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(GRPC_CONTENT_TYPE));
+    headers.insert("grpc-status", HeaderValue::from(code));
+    if let Ok(message) = HeaderValue::from_str(message) {
+        headers.insert("grpc-message", message);
+    }
+    response
+}
+
+/// Length-prefixed gRPC message followed by trailers
+struct GrpcBody {
+    message: Option<Bytes>,
+    trailers: Option<HeaderMap>,
+}
+
+impl http_body::Body for GrpcBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if let Some(message) = self.message.take() {
+            return Poll::Ready(Some(Ok(Frame::data(message))));
+        }
+        Poll::Ready(self.trailers.take().map(|trailers| Ok(Frame::trailers(trailers))))
+    }
+}
+
+/// Service name from a framed `HealthCheckRequest`; an empty body is the empty request
+fn decode_check_request(body: &[u8]) -> Option<String> {
+    if body.is_empty() {
+        return Some(String::new());
+    }
+    // Compression is never negotiated, so the flag must be clear
+    if body.len() < 5 || body[0] != 0 {
+        return None;
+    }
+    let len = u32::from_be_bytes([body[1], body[2], body[3], body[4]]) as usize;
+    let message = body[5..].get(..len)?;
+
+    let mut service = String::new();
+    let mut rest = message;
+    while !rest.is_empty() {
+        let key = read_varint(&mut rest)?;
+        match key & 0x7 {
+            0 => {
+                read_varint(&mut rest)?;
+            },
+            1 => rest = rest.get(8..)?,
+            2 => {
+                let len = read_varint(&mut rest)? as usize;
+                let value = rest.get(..len)?;
+                if key >> 3 == 1 {
+                    service = String::from_utf8(value.to_vec()).ok()?;
+                }
+                rest = &rest[len..];
+            },
+            5 => rest = rest.get(4..)?,
+            _ => return None,
+        }
+    }
+    Some(service)
+}
+
+/// Framed `HealthCheckResponse { status }`
+fn encode_check_response(status: ServingStatus) -> Bytes {
+    // Field 1, varint; every status fits in one byte
+    let message = [0x08, status as u8];
+    let mut framed = vec![0];
+    framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    framed.extend_from_slice(&message);
+    Bytes::from(framed)
+}
+
+fn read_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_request_decoding_and_response_framing() {
+        assert_eq!(decode_check_request(&[]).as_deref(), Some(""));
+        assert_eq!(decode_check_request(&[0, 0, 0, 0, 0]).as_deref(), Some(""));
+
+        // An unknown varint field (2) ahead of service = "queue"
+        let message = [0x10, 0x96, 0x01, 0x0a, 5, b'q', b'u', b'e', b'u', b'e'];
+        let mut framed = vec![0, 0, 0, 0, message.len() as u8];
+        framed.extend_from_slice(&message);
+        assert_eq!(decode_check_request(&framed).as_deref(), Some("queue"));
+        // Truncated message and compressed flag
+        assert!(decode_check_request(&framed[..framed.len() - 1]).is_none());
+        framed[0] = 1;
+        assert!(decode_check_request(&framed).is_none());
+
+        assert_eq!(
+            encode_check_response(ServingStatus::NotServing).as_ref(),
+            &[0, 0, 0, 0, 2, 0x08, 2]
+        );
+    }
+
+    #[test]
+    fn test_serving_status_follows_health_and_maintenance() {
+        let mut snapshot = ProbeSnapshot {
+            overall: HealthLevel::Degraded,
+            components: HashMap::from([
+                ("queue".to_string(), HealthLevel::Critical),
+                ("router".to_string(), HealthLevel::Healthy),
+            ]),
+            maintenance: false,
+        };
+        let config = HealthCheckConfig::default();
+        assert_eq!(snapshot.status("", &config), ServingStatus::Serving);
+        assert_eq!(snapshot.status("queue", &config), ServingStatus::NotServing);
+        assert_eq!(snapshot.status("router", &config), ServingStatus::Serving);
+        assert_eq!(snapshot.status("billing", &config), ServingStatus::ServiceUnknown);
+
+        let strict = HealthCheckConfig {
+            fail_on_degraded: true,
+            ..HealthCheckConfig::default()
+        };
+        assert_eq!(snapshot.status(GATEWAY_SERVICE, &strict), ServingStatus::NotServing);
+
+        snapshot.overall = HealthLevel::Healthy;
+        snapshot.maintenance = true;
+        assert_eq!(snapshot.status("", &config), ServingStatus::NotServing);
+        // Components keep reporting their own health during maintenance
+        assert_eq!(snapshot.status("router", &config), ServingStatus::Serving);
+    }
+}
@@ -0,0 +1,301 @@
+//! Signed provenance of responses
+//!
+//! Systems acting on an answer, such as industrial decision support, need an
+//! audit trail of which device and model produced it. With
+//! `gateway.response_provenance` enabled, object results carry a
+//! `provenance` field naming the gateway node and version, where the answer
+//! came from (a local model with its file checksum, a cloud endpoint, the
+//! offline queue or the response cache) and the SHA-256 of the result without
+//! the `provenance` field.
+//!
+//! Both the result digest and the signature are taken over compact JSON with
+//! object keys sorted. The signature covers the `provenance` object without
+//! its `signature` field and is made with the device key cloud requests are
+//! signed with: ECDSA P-256 with SHA-256 in fixed-length `r || s` form,
+//! base64-encoded.
+//!
+//! The dispatch path reports where a request was served with [`record`];
+//! reports land in the enclosing [`trace`] scope.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use mcp_common::config::ResponseProvenanceConfig;
+use mcp_common::crypto::digest;
+use mcp_common::encoding::hex;
+use mcp_common::request_signing::RequestSigner;
+use mcp_common::{MCPResponse, ModelId, RequestId, Result};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Response result field the provenance is attached to
+pub const PROVENANCE_FIELD: &str = "provenance";
+
+/// Signature scheme of the device key
+pub const SIGNATURE_ALGORITHM: &str = "ES256";
+
+/// Where a response was produced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    /// On the device, by a local model or the gateway itself
+    #[default]
+    Local,
+    Cloud,
+    /// Accepted into the offline queue for later delivery
+    Queued,
+    /// Returned from the response cache
+    Cache,
+}
+
+/// Where and by what a request was served
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Served {
+    pub origin: Origin,
+    pub model_id: Option<ModelId>,
+    pub cloud_endpoint: Option<String>,
+}
+
+impl Served {
+    pub fn local(model_id: &ModelId) -> Self {
+        Self {
+            origin: Origin::Local,
+            model_id: Some(model_id.clone()),
+            cloud_endpoint: None,
+        }
+    }
+
+    pub fn cloud(endpoint: &str) -> Self {
+        Self {
+            origin: Origin::Cloud,
+            model_id: None,
+            cloud_endpoint: Some(endpoint.to_string()),
+        }
+    }
+
+    pub fn with_origin(origin: Origin) -> Self {
+        Self {
+            origin,
+            ..Default::default()
+        }
+    }
+}
+
+/// Provenance metadata as attached to a result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseProvenance {
+    pub request_id: RequestId,
+    pub origin: Origin,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<ModelId>,
+    /// Checksum of the model file that produced the result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloud_endpoint: Option<String>,
+    pub gateway_node: String,
+    pub gateway_version: String,
+    /// SHA-256 of the result without the provenance field
+    pub result_sha256: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ProvenanceSignature>,
+}
+
+/// Device key signature over the rest of the provenance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvenanceSignature {
+    pub algorithm: String,
+    /// Device key that made the signature
+    pub key_id: String,
+    pub value: String,
+}
+
+impl ResponseProvenance {
+    /// Bytes the signature is made over
+    pub fn signed_bytes(&self) -> Result<Vec<u8>> {
+        let unsigned = ResponseProvenance {
+            signature: None,
+            ..self.clone()
+        };
+        canonical_json(&serde_json::to_value(unsigned)?)
+    }
+}
+
+/// Attaches provenance to responses and signs it
+pub struct ResponseSigner {
+    config: ResponseProvenanceConfig,
+    key: Option<Arc<dyn RequestSigner>>,
+    gateway_node: String,
+}
+
+impl ResponseSigner {
+    pub fn new(config: ResponseProvenanceConfig, key: Option<Arc<dyn RequestSigner>>, gateway_node: &str) -> Self {
+        if config.enabled && config.sign && key.is_none() {
+            warn!("Response provenance is enabled without a device key; responses are not signed");
+        }
+        Self {
+            key: key.filter(|_| config.sign),
+            config,
+            gateway_node: gateway_node.to_string(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Attach provenance to an object result; signing failures are logged
+    /// and leave the provenance unsigned
+    pub async fn attach(
+        &self,
+        response: &mut MCPResponse,
+        served: Served,
+        model_sha256: Option<String>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<()> {
+        let Some(serde_json::Value::Object(fields)) = response.result.as_mut() else {
+            return Ok(());
+        };
+        fields.remove(PROVENANCE_FIELD);
+        let result = canonical_json(&serde_json::Value::Object(fields.clone()))?;
+        let mut provenance = ResponseProvenance {
+            request_id: response.id,
+            origin: served.origin,
+            model_id: served.model_id,
+            model_sha256,
+            cloud_endpoint: served.cloud_endpoint,
+            gateway_node: self.gateway_node.clone(),
+            gateway_version: env!("CARGO_PKG_VERSION").to_string(),
+            result_sha256: hex(digest::digest(&digest::SHA256, &result).as_ref()),
+            timestamp,
+            signature: None,
+        };
+        if let Some(key) = self.key.clone() {
+            let message = provenance.signed_bytes()?;
+            let signing = tokio::task::spawn_blocking(move || key.sign(&message).map(|value| (key.key_id(), value)));
+            match signing.await {
+                Ok(Ok((key_id, value))) => {
+                    provenance.signature = Some(ProvenanceSignature {
+                        algorithm: SIGNATURE_ALGORITHM.to_string(),
+                        key_id,
+                        value: BASE64.encode(value),
+                    });
+                },
+                Ok(Err(e)) => warn!("Could not sign provenance of response {}: {}", response.id, e),
+                Err(e) => warn!("Provenance signing task failed: {}", e),
+            }
+        }
+        fields.insert(PROVENANCE_FIELD.to_string(), serde_json::to_value(provenance)?);
+        Ok(())
+    }
+}
+
+tokio::task_local! {
+    static SERVED: Arc<Mutex<Served>>;
+}
+
+/// Run a future, returning its output with where it reported the request
+/// was served; on the device when nothing was reported
+pub async fn trace<F: Future>(future: F) -> (F::Output, Served) {
+    let served = Arc::new(Mutex::new(Served::default()));
+    let output = SERVED.scope(Arc::clone(&served), future).await;
+    let served = served.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    (output, served)
+}
+
+/// Report where the current request was served, replacing any earlier report.
+///
+/// Does nothing when called outside [`trace`].
+pub fn record(served: Served) {
+    let _ = SERVED.try_with(|current| {
+        *current.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = served;
+    });
+}
+
+/// Compact JSON with object keys sorted
+fn canonical_json(value: &serde_json::Value) -> Result<Vec<u8>> {
+    // Maps without `preserve_order` keep their keys sorted
+    Ok(serde_json::to_vec(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signs by prefixing the message, so tests can see what was signed
+    struct EchoSigner;
+
+    impl RequestSigner for EchoSigner {
+        fn key_id(&self) -> String {
+            "device-key".to_string()
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+            Ok([b"signed:".as_slice(), message].concat())
+        }
+    }
+
+    fn response() -> MCPResponse {
+        MCPResponse {
+            id: uuid::Uuid::new_v4(),
+            result: Some(serde_json::json!({ "text": "valve 3 closed", "confidence": 0.9 })),
+            error: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn enabled() -> ResponseProvenanceConfig {
+        ResponseProvenanceConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_attached_provenance_is_signed_over_the_result() {
+        let signer = ResponseSigner::new(enabled(), Some(Arc::new(EchoSigner)), "edge-7");
+        let mut response = response();
+        let expected_digest = hex(
+            digest::digest(&digest::SHA256, &canonical_json(response.result.as_ref().unwrap()).unwrap()).as_ref(),
+        );
+        signer
+            .attach(&mut response, Served::local(&"llama@2".to_string()), Some("ab12".to_string()), Utc::now())
+            .await
+            .unwrap();
+
+        let result = response.result.unwrap();
+        assert_eq!(result["text"], "valve 3 closed");
+        let provenance: ResponseProvenance = serde_json::from_value(result[PROVENANCE_FIELD].clone()).unwrap();
+        assert_eq!(provenance.request_id, response.id);
+        assert_eq!(provenance.origin, Origin::Local);
+        assert_eq!(provenance.model_id.as_deref(), Some("llama@2"));
+        assert_eq!(provenance.gateway_node, "edge-7");
+        assert_eq!(provenance.result_sha256, expected_digest);
+
+        let signature = provenance.signature.clone().unwrap();
+        assert_eq!(signature.key_id, "device-key");
+        let signed = BASE64.decode(signature.value).unwrap();
+        assert_eq!(signed, [b"signed:".as_slice(), &provenance.signed_bytes().unwrap()].concat());
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_without_a_key_and_origin_from_trace() {
+        let signer = ResponseSigner::new(enabled(), None, "edge-7");
+        let (mut response, served) = trace(async {
+            record(Served::local(&"llama@2".to_string()));
+            record(Served::cloud("cloud-fallback"));
+            response()
+        })
+        .await;
+        assert_eq!(served.origin, Origin::Cloud);
+        signer.attach(&mut response, served, None, Utc::now()).await.unwrap();
+
+        let provenance = &response.result.unwrap()[PROVENANCE_FIELD];
+        assert_eq!(provenance["origin"], "cloud");
+        assert_eq!(provenance["cloud_endpoint"], "cloud-fallback");
+        assert!(provenance.get("signature").is_none());
+    }
+}
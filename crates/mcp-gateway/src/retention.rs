@@ -0,0 +1,332 @@
+//! Retention policies and purging of persisted data
+//!
+//! Each class of persisted data has its own maximum age. Queue entries are
+//! purged through the offline queue; the other classes are files in a
+//! configured directory and are purged by modification time. Data under a
+//! legal hold, by device or by path, is never deleted and is reported as
+//! held instead. Purges run on a schedule when enabled, and operators can run
+//! one at any time, as a dry run that only reports what would be deleted.
+
+use chrono::{DateTime, Duration, Utc};
+use mcp_common::clock::{self, Clock};
+use mcp_common::config::{DataClass, LegalHold, RetentionConfig};
+use mcp_common::{Result, Vfs};
+use mcp_queue::OfflineQueue;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, info, warn};
+
+/// Items listed per data class in a purge report
+const MAX_REPORTED_ITEMS: usize = 100;
+
+/// Admin request to purge now
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PurgeRequest {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Purge outcome for one data class
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassPurge {
+    pub data_class: DataClass,
+    /// Data older than this is purged; `None` when the class has no retention
+    pub cutoff: Option<DateTime<Utc>>,
+    /// Items past retention and not held
+    pub purged: usize,
+    /// Items past retention kept for a legal hold
+    pub held: usize,
+    pub bytes: u64,
+    /// Queue request ids or file paths, truncated to the first 100
+    pub items: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// Outcome of one purge run
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeReport {
+    pub dry_run: bool,
+    pub started_at: DateTime<Utc>,
+    pub classes: Vec<ClassPurge>,
+}
+
+/// Applies retention policies to every persisted data class
+pub struct RetentionManager {
+    config: RetentionConfig,
+    queue: Arc<dyn OfflineQueue + Send + Sync>,
+    storage: Arc<dyn Vfs>,
+    clock: Arc<dyn Clock>,
+    last_report: RwLock<Option<PurgeReport>>,
+    /// Serializes purges so a scheduled run and an admin run never overlap
+    running: Mutex<()>,
+}
+
+impl RetentionManager {
+    pub fn new(config: RetentionConfig, queue: Arc<dyn OfflineQueue + Send + Sync>, storage: Arc<dyn Vfs>) -> Self {
+        Self::with_clock(config, queue, storage, clock::system_clock())
+    }
+
+    pub fn with_clock(
+        config: RetentionConfig,
+        queue: Arc<dyn OfflineQueue + Send + Sync>,
+        storage: Arc<dyn Vfs>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            config,
+            queue,
+            storage,
+            clock,
+            last_report: RwLock::new(None),
+            running: Mutex::new(()),
+        }
+    }
+
+    pub fn config(&self) -> &RetentionConfig {
+        &self.config
+    }
+
+    /// Start scheduled purging when enabled
+    pub fn start(self: &Arc<Self>) {
+        if !self.config.enabled {
+            return;
+        }
+        let manager = Arc::downgrade(self);
+        let interval_secs = self.config.purge_interval_secs.max(1);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if let Err(e) = manager.purge(false).await {
+                    warn!("Scheduled retention purge failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Purge every data class past its retention, or report what would be purged
+    pub async fn purge(&self, dry_run: bool) -> Result<PurgeReport> {
+        let _running = self.running.lock().await;
+        let started_at = self.clock.now();
+        let mut classes = Vec::new();
+        for class in DataClass::ALL {
+            let policy = self.config.policy(class);
+            let cutoff = policy
+                .max_age_days
+                .map(|days| started_at - Duration::days(days as i64));
+            let mut outcome = ClassPurge {
+                data_class: class,
+                cutoff,
+                purged: 0,
+                held: 0,
+                bytes: 0,
+                items: Vec::new(),
+                errors: Vec::new(),
+            };
+            if let Some(cutoff) = cutoff {
+                if class == DataClass::QueueEntries {
+                    self.purge_queue(cutoff, dry_run, &mut outcome).await?;
+                } else if let Some(dir) = &policy.path {
+                    self.purge_files(dir, cutoff, dry_run, &mut outcome).await;
+                }
+            }
+            if outcome.purged > 0 && !dry_run {
+                info!(
+                    "Retention purged {} {} items ({} held)",
+                    outcome.purged,
+                    class.as_str(),
+                    outcome.held
+                );
+            }
+            classes.push(outcome);
+        }
+
+        let report = PurgeReport {
+            dry_run,
+            started_at,
+            classes,
+        };
+        if !dry_run {
+            *self.last_report.write().await = Some(report.clone());
+        }
+        Ok(report)
+    }
+
+    /// Most recent purge that deleted data
+    pub async fn last_report(&self) -> Option<PurgeReport> {
+        self.last_report.read().await.clone()
+    }
+
+    async fn purge_queue(&self, cutoff: DateTime<Utc>, dry_run: bool, outcome: &mut ClassPurge) -> Result<()> {
+        let held_devices: Vec<String> = self
+            .config
+            .legal_holds
+            .iter()
+            .filter_map(|hold| hold.device_id.clone())
+            .collect();
+        let purge = self.queue.purge(cutoff, &held_devices, dry_run).await?;
+        outcome.purged = purge.request_ids.len();
+        outcome.held = purge.held;
+        outcome.items = purge
+            .request_ids
+            .iter()
+            .take(MAX_REPORTED_ITEMS)
+            .map(|id| id.to_string())
+            .collect();
+        Ok(())
+    }
+
+    async fn purge_files(&self, dir: &Path, cutoff: DateTime<Utc>, dry_run: bool, outcome: &mut ClassPurge) {
+        let files = match self.storage.list_dir(dir).await {
+            Ok(files) => files,
+            // Nothing has been written for this class yet
+            Err(_) if !self.storage.exists(dir).await => return,
+            Err(e) => {
+                outcome.errors.push(e.to_string());
+                return;
+            },
+        };
+        for file in files {
+            let modified = match self.storage.modified(&file).await {
+                Ok(modified) => DateTime::<Utc>::from(modified),
+                Err(e) => {
+                    outcome.errors.push(e.to_string());
+                    continue;
+                },
+            };
+            if modified >= cutoff {
+                continue;
+            }
+            if let Some(hold) = self.config.legal_holds.iter().find(|hold| holds_file(hold, &file)) {
+                outcome.held += 1;
+                debug!("{:?} is under legal hold {}", file, hold.id);
+                continue;
+            }
+
+            let size = self.storage.size(&file).await.unwrap_or(0);
+            if !dry_run {
+                if let Err(e) = self.storage.remove(&file).await {
+                    outcome.errors.push(e.to_string());
+                    continue;
+                }
+            }
+            outcome.purged += 1;
+            outcome.bytes += size;
+            if outcome.items.len() < MAX_REPORTED_ITEMS {
+                outcome.items.push(file.display().to_string());
+            }
+        }
+    }
+}
+
+/// Whether a legal hold covers a file, by path prefix or device id in the file name
+pub(crate) fn holds_file(hold: &LegalHold, file: &Path) -> bool {
+    let by_path = hold.path.as_ref().is_some_and(|path| file.starts_with(path));
+    let by_device = hold.device_id.as_ref().is_some_and(|device_id| {
+        file.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.contains(device_id.as_str()))
+    });
+    by_path || by_device
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::InMemoryQueue;
+    use mcp_common::clock::FakeClock;
+    use mcp_common::config::RetentionPolicy;
+    use mcp_common::vfs::MemoryVfs;
+    use mcp_common::MCPRequest;
+    use std::path::PathBuf;
+
+    fn request(device_id: &str, timestamp: DateTime<Utc>) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: device_id.to_string(),
+            method: "completion".to_string(),
+            params: Default::default(),
+            context: None,
+            timestamp,
+        }
+    }
+
+    fn config() -> RetentionConfig {
+        let days = |days, path: Option<&str>| RetentionPolicy {
+            max_age_days: Some(days),
+            path: path.map(PathBuf::from),
+        };
+        RetentionConfig {
+            queue_entries: days(7, None),
+            audit_logs: days(30, Some("logs/audit")),
+            legal_holds: vec![
+                LegalHold {
+                    id: "case-17".to_string(),
+                    reason: "litigation".to_string(),
+                    device_id: Some("press-4".to_string()),
+                    path: None,
+                },
+                LegalHold {
+                    id: "june-audit".to_string(),
+                    reason: "external audit".to_string(),
+                    device_id: None,
+                    path: Some(PathBuf::from("logs/audit/2026-06.log")),
+                },
+            ],
+            ..RetentionConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_without_deleting_held_queue_entries() {
+        let queue = Arc::new(InMemoryQueue::new(10));
+        let now = Utc::now();
+        queue.enqueue_request(request("press-1", now - Duration::days(10))).await.unwrap();
+        queue.enqueue_request(request("press-4", now - Duration::days(10))).await.unwrap();
+        queue.enqueue_request(request("press-1", now - Duration::days(1))).await.unwrap();
+        let manager = RetentionManager::new(config(), queue.clone(), Arc::new(MemoryVfs::new()));
+
+        let dry_run = manager.purge(true).await.unwrap();
+        let queued = &dry_run.classes[0];
+        assert_eq!(queued.data_class, DataClass::QueueEntries);
+        assert_eq!((queued.purged, queued.held), (1, 1));
+        assert_eq!(queue.pending().len(), 3);
+        assert!(manager.last_report().await.is_none());
+
+        manager.purge(false).await.unwrap();
+        let remaining: Vec<String> = queue.pending().into_iter().map(|r| r.device_id).collect();
+        assert_eq!(remaining, vec!["press-4".to_string(), "press-1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_files_past_retention_are_purged_except_held_paths() {
+        let storage = Arc::new(MemoryVfs::new());
+        for file in ["logs/audit/2026-05.log", "logs/audit/2026-06.log", "logs/audit/press-4.log"] {
+            storage.write(Path::new(file), b"entries").await.unwrap();
+        }
+
+        // Sixty days later everything is past the 30 day audit log retention
+        let clock = Arc::new(FakeClock::new(Utc::now() + Duration::days(60)));
+        let manager = RetentionManager::with_clock(
+            config(),
+            Arc::new(InMemoryQueue::new(10)),
+            storage.clone(),
+            clock,
+        );
+        let report = manager.purge(false).await.unwrap();
+        let audit = report
+            .classes
+            .iter()
+            .find(|class| class.data_class == DataClass::AuditLogs)
+            .unwrap();
+        assert_eq!(audit.items, vec!["logs/audit/2026-05.log".to_string()]);
+        assert_eq!((audit.purged, audit.held, audit.bytes), (1, 2, 7));
+        assert!(!storage.exists(Path::new("logs/audit/2026-05.log")).await);
+        assert!(storage.exists(Path::new("logs/audit/press-4.log")).await);
+    }
+}
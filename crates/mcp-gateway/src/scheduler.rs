@@ -0,0 +1,325 @@
+//! Per-model admission of local inferences
+//!
+//! Every local model gets its own slot pool and bounded wait list, so a burst
+//! against one model queues behind that model instead of holding up the rest.
+//! A request waits for a slot until its queue deadline (the configured queue
+//! timeout, shortened by the request's own timeout) and is turned away with
+//! [`Error::Overloaded`] when the wait list is full or the deadline passes.
+//! Waiters are served by priority, then by the weight an embedder's
+//! prioritizer gave them, then in arrival order.
+//! The error carries an estimate of when a slot frees up, taken from the
+//! model's recent inference latency and the number of requests ahead, which
+//! the HTTP API returns as `Retry-After`.
+
+use chrono::Utc;
+use mcp_common::config::{ConcurrencyLimit, ModelSchedulingConfig};
+use mcp_common::{
+    ComponentHealth, ConcurrencyLimiter, ConcurrencyPermit, Error, HealthLevel, MCPRequest, ModelId,
+    OverloadDetails, Result,
+};
+use mcp_models::TokenStream;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Retry delay suggested while a model has no latency samples yet
+const DEFAULT_RETRY_AFTER_MS: u64 = 1000;
+
+/// Scheduling state of one model, as exposed by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelQueueReport {
+    pub model_id: ModelId,
+    pub limit: usize,
+    pub in_use: usize,
+    pub waiting: usize,
+    pub max_queued: usize,
+    pub rejected_total: u64,
+    /// Moving average of inference latency
+    pub avg_latency_ms: u64,
+}
+
+struct ModelQueue {
+    limiter: ConcurrencyLimiter,
+    limit: ConcurrencyLimit,
+    /// Exponentially weighted latency in milliseconds, 0 until the first sample
+    latency_ms: AtomicU64,
+}
+
+impl ModelQueue {
+    fn record_latency(&self, elapsed: Duration) {
+        let sample = elapsed.as_millis() as u64;
+        let _ = self.latency_ms.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+            Some(if average == 0 { sample.max(1) } else { (average * 7 + sample) / 8 })
+        });
+    }
+
+    /// Time until a request joining the back of the wait list would get a slot
+    fn retry_after_ms(&self) -> u64 {
+        let gauge = self.limiter.gauge();
+        let latency_ms = match self.latency_ms.load(Ordering::Relaxed) {
+            0 => DEFAULT_RETRY_AFTER_MS,
+            latency_ms => latency_ms,
+        };
+        let rounds = (gauge.waiting + 1).div_ceil(gauge.limit) as u64;
+        latency_ms.saturating_mul(rounds).max(DEFAULT_RETRY_AFTER_MS)
+    }
+}
+
+/// Slot held for the duration of one local inference
+pub struct InferenceSlot {
+    _permit: ConcurrencyPermit,
+    queue: Arc<ModelQueue>,
+    started: Instant,
+}
+
+impl Drop for InferenceSlot {
+    fn drop(&mut self) {
+        self.queue.record_latency(self.started.elapsed());
+    }
+}
+
+impl InferenceSlot {
+    /// Keep the slot until the stream has been read to the end or dropped
+    pub fn hold_for(self, mut stream: TokenStream, buffer_chunks: usize) -> TokenStream {
+        let (sender, receiver) = mpsc::channel(buffer_chunks.max(1));
+        tokio::spawn(async move {
+            let _slot = self;
+            while let Some(chunk) = stream.recv().await {
+                if sender.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+}
+
+/// Bounds concurrent and queued local inferences per model
+pub struct InferenceScheduler {
+    config: ModelSchedulingConfig,
+    queues: Mutex<HashMap<ModelId, Arc<ModelQueue>>>,
+}
+
+impl InferenceScheduler {
+    pub fn new(config: ModelSchedulingConfig) -> Self {
+        Self {
+            config,
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Wait for a slot on `model_id`; `None` when scheduling is disabled
+    pub async fn admit(&self, model_id: &str, request: &MCPRequest) -> Result<Option<InferenceSlot>> {
+        if !self.config.enabled {
+            return Ok(None);
+        }
+        let queue = self.queue(model_id);
+        let mut deadline = Duration::from_millis(queue.limit.queue_timeout_ms);
+        if let Some(timeout_ms) = request.context.as_ref().and_then(|context| context.timeout_ms) {
+            deadline = deadline.min(Duration::from_millis(timeout_ms));
+        }
+        match queue.limiter.acquire_weighted(request.priority(), request.weight(), deadline).await {
+            Ok(permit) => Ok(Some(InferenceSlot {
+                _permit: permit,
+                queue,
+                started: Instant::now(),
+            })),
+            Err(rejection) => Err(Error::Overloaded(OverloadDetails {
+                target: model_id.to_string(),
+                rejection,
+                retry_after_ms: queue.retry_after_ms(),
+            })),
+        }
+    }
+
+    /// Scheduling state of every model that has received a request
+    pub fn report(&self) -> Vec<ModelQueueReport> {
+        let queues = self.queues.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut report: Vec<ModelQueueReport> = queues
+            .iter()
+            .map(|(model_id, queue)| {
+                let gauge = queue.limiter.gauge();
+                ModelQueueReport {
+                    model_id: model_id.clone(),
+                    limit: gauge.limit,
+                    in_use: gauge.in_use,
+                    waiting: gauge.waiting,
+                    max_queued: queue.limit.max_queued,
+                    rejected_total: gauge.rejected_total,
+                    avg_latency_ms: queue.latency_ms.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        report.sort_by(|a, b| a.model_id.cmp(&b.model_id));
+        report
+    }
+
+    /// Degraded while any model's wait list is full
+    pub fn health(&self) -> ComponentHealth {
+        let report = self.report();
+        let mut metrics = HashMap::new();
+        for model in &report {
+            metrics.insert(format!("{}_in_use", model.model_id), model.in_use as f32);
+            metrics.insert(format!("{}_waiting", model.model_id), model.waiting as f32);
+            metrics.insert(format!("{}_rejected_total", model.model_id), model.rejected_total as f32);
+        }
+        let saturated: Vec<&str> = report
+            .iter()
+            .filter(|model| model.in_use >= model.limit && model.waiting >= model.max_queued)
+            .map(|model| model.model_id.as_str())
+            .collect();
+        let (status, message) = if saturated.is_empty() {
+            (HealthLevel::Healthy, format!("{} models scheduled", report.len()))
+        } else {
+            (HealthLevel::Degraded, format!("Wait list full for {}", saturated.join(", ")))
+        };
+        ComponentHealth {
+            status,
+            message,
+            last_check: Utc::now(),
+            metrics,
+        }
+    }
+
+    fn queue(&self, model_id: &str) -> Arc<ModelQueue> {
+        let mut queues = self.queues.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        queues
+            .entry(model_id.to_string())
+            .or_insert_with(|| {
+                let limit = self.config.models.get(model_id).unwrap_or(&self.config.default).clone();
+                Arc::new(ModelQueue {
+                    limiter: ConcurrencyLimiter::new(model_id, &limit),
+                    limit,
+                    latency_ms: AtomicU64::new(0),
+                })
+            })
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::{Priority, Rejection, RequestContext};
+
+    fn scheduler(max_queued: usize, queue_timeout_ms: u64) -> InferenceScheduler {
+        let mut config = ModelSchedulingConfig::default();
+        config.default.max_queued = max_queued;
+        config.default.queue_timeout_ms = queue_timeout_ms;
+        config.models.insert(
+            "wide".to_string(),
+            ConcurrencyLimit {
+                max_concurrent: 4,
+                ..config.default.clone()
+            },
+        );
+        InferenceScheduler::new(config)
+    }
+
+    fn request() -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "sensor-1".to_string(),
+            method: "completion".to_string(),
+            params: HashMap::new(),
+            context: Some(RequestContext {
+                priority: Priority::Normal,
+                ..Default::default()
+            }),
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn rejection(result: Result<Option<InferenceSlot>>) -> OverloadDetails {
+        match result {
+            Err(Error::Overloaded(details)) => details,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("request was admitted"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_wait_list_rejects_with_retry_after() {
+        let scheduler = scheduler(0, 1000);
+        let slot = scheduler.admit("tiny", &request()).await.unwrap();
+        assert!(slot.is_some());
+
+        let details = rejection(scheduler.admit("tiny", &request()).await);
+        assert_eq!(details.rejection, Rejection::QueueFull);
+        assert_eq!(details.target, "tiny");
+        assert!(details.retry_after_ms >= DEFAULT_RETRY_AFTER_MS);
+        assert_eq!(scheduler.health().status, HealthLevel::Degraded);
+
+        // Other models keep their own slots
+        let mut wide = Vec::new();
+        for _ in 0..4 {
+            wide.push(scheduler.admit("wide", &request()).await.unwrap());
+        }
+        assert!(wide.iter().all(Option::is_some));
+        drop(slot);
+        assert!(scheduler.admit("tiny", &request()).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_is_rejected_at_its_deadline() {
+        let scheduler = scheduler(4, 10_000);
+        let _slot = scheduler.admit("tiny", &request()).await.unwrap();
+
+        let mut impatient = request();
+        impatient.context.as_mut().unwrap().timeout_ms = Some(20);
+        let started = Instant::now();
+        let details = rejection(scheduler.admit("tiny", &impatient).await);
+        assert_eq!(details.rejection, Rejection::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let report = scheduler.report();
+        assert_eq!(report[0].model_id, "tiny");
+        assert_eq!(report[0].rejected_total, 1);
+        assert_eq!(report[0].waiting, 0);
+    }
+
+    #[tokio::test]
+    async fn test_heavier_waiter_of_the_same_priority_is_served_first() {
+        let scheduler = Arc::new(scheduler(4, 10_000));
+        let slot = scheduler.admit("tiny", &request()).await.unwrap();
+        let (order_tx, mut order_rx) = mpsc::unbounded_channel();
+        let mut waiters = Vec::new();
+        for weight in [1, 5] {
+            let waiting = Arc::clone(&scheduler);
+            let order_tx = order_tx.clone();
+            let mut request = request();
+            request.set_weight(weight);
+            waiters.push(tokio::spawn(async move {
+                let _slot = waiting.admit("tiny", &request).await.unwrap();
+                order_tx.send(weight).unwrap();
+            }));
+            while scheduler.report()[0].waiting < waiters.len() {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        drop(slot);
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+        assert_eq!(order_rx.recv().await, Some(5));
+        assert_eq!(order_rx.recv().await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_scheduler_admits_without_slots() {
+        let scheduler = InferenceScheduler::new(ModelSchedulingConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        assert!(scheduler.admit("tiny", &request()).await.unwrap().is_none());
+        assert!(scheduler.report().is_empty());
+    }
+}
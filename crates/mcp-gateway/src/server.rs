@@ -1,7 +1,12 @@
-//! HTTP/WebSocket server implementation
+//! HTTP/WebSocket server implementation, with gRPC and the MQTT bridge
+//! alongside when enabled
 
+use crate::auth;
 use crate::handlers;
+use crate::high_availability;
+use crate::listener;
 use crate::middleware;
+use crate::probes;
 use crate::Gateway;
 use axum::{
     extract::State,
@@ -36,12 +41,22 @@ impl Server {
     /// Run the server on the specified address
     pub async fn run(&self, bind_addr: &str) -> Result<()> {
         let app = self.create_app();
+        self.gateway.start_synthetic_probes();
+        self.gateway.start_peripherals();
+        self.gateway.start_config_reload();
+        self.gateway.start_storage_health();
 
         info!("Starting server on {}", bind_addr);
 
-        let listener = tokio::net::TcpListener::bind(bind_addr)
-            .await
-            .map_err(|e| Error::Network(format!("Failed to bind to {}: {}", bind_addr, e)))?;
+        let listener = listener::bind(bind_addr, &self.gateway.config().network).await?;
+
+        let grpc = &self.gateway.config().gateway.grpc;
+        if grpc.enabled {
+            self.spawn_grpc(grpc.port).await?;
+        }
+        if self.gateway.config().gateway.mqtt.enabled {
+            self.spawn_mqtt()?;
+        }
 
         axum::serve(listener, app)
             .await
@@ -50,6 +65,52 @@ impl Server {
         Ok(())
     }
 
+    /// Run only the gRPC server on the specified address
+    #[cfg(feature = "grpc")]
+    pub async fn run_grpc(&self, bind_addr: &str) -> Result<()> {
+        info!("Starting gRPC server on {}", bind_addr);
+        let listener = listener::bind(bind_addr, &self.gateway.config().network).await?;
+        crate::grpc::serve(self.gateway.clone(), listener).await
+    }
+
+    /// Serve gRPC on `port` of the configured bind address in the background
+    #[cfg(feature = "grpc")]
+    async fn spawn_grpc(&self, port: u16) -> Result<()> {
+        let config = self.gateway.config();
+        let bind_addr = listener::listen_address(&config.gateway.bind_address, port);
+        info!("Starting gRPC server on {}", bind_addr);
+        let listener = listener::bind(&bind_addr, &config.network).await?;
+        let gateway = self.gateway.clone();
+        tokio::spawn(async move {
+            if let Err(e) = crate::grpc::serve(gateway, listener).await {
+                error!("{}", e);
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(not(feature = "grpc"))]
+    async fn spawn_grpc(&self, _port: u16) -> Result<()> {
+        Err(Error::Configuration(
+            "gateway.grpc is enabled but the gateway was built without the grpc feature".to_string(),
+        ))
+    }
+
+    /// Bridge the MQTT request topics to the gateway in the background
+    #[cfg(feature = "mqtt")]
+    fn spawn_mqtt(&self) -> Result<()> {
+        let bridge = crate::mqtt::MqttBridge::new(self.gateway.clone())?;
+        tokio::spawn(bridge.run());
+        Ok(())
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    fn spawn_mqtt(&self) -> Result<()> {
+        Err(Error::Configuration(
+            "gateway.mqtt is enabled but the gateway was built without the mqtt feature".to_string(),
+        ))
+    }
+
     fn create_app(&self) -> Router {
         // Use the handlers module to create the complete router
         let app = handlers::create_router(self.gateway.clone());
@@ -67,12 +128,18 @@ impl Server {
                     .max_age(Duration::from_secs(3600))
                 )
                 // Rate limiting for DoS protection
-                .layer(middleware::RateLimitLayer::new(100, 60)) // 100 requests per minute
+                .layer(self.gateway.rate_limit_layer().clone())
                 // Request tracking
                 .layer(middleware::RequestIdLayer::new())
                 // Metrics collection
-                .layer(middleware::MetricsLayer::new()),
+                .layer(middleware::MetricsLayer::new())
+                // Caller authentication, after rate limiting so floods of bad credentials are shed
+                .layer(axum::middleware::from_fn_with_state(self.gateway.clone(), auth::authenticate)),
         )
+        // Load balancer probes must never be rate limited into ejecting the gateway
+        .merge(probes::routes(self.gateway.clone()).layer(TraceLayer::new_for_http()))
+        // Replication batches are signed by the peer gateway
+        .merge(high_availability::routes(self.gateway.clone()).layer(TraceLayer::new_for_http()))
     }
 }
 
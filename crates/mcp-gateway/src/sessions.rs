@@ -0,0 +1,348 @@
+//! Rolling chat context per session
+//!
+//! With `sessions.enabled`, a chat request carrying a `session_id` param only
+//! needs to send its new messages. Before the request is routed the gateway
+//! prepends the session's earlier messages, newest first until
+//! `sessions.max_context_tokens` is reached, and once the model answers it
+//! adds the new messages and the reply to the session's history. Histories
+//! are kept in the `sessions.storage_backend` database, so with sled or
+//! sqlite they survive restarts.
+//!
+//! Unlike [`conversations`](crate::conversations), which records sessions for
+//! export and never changes a request, this store exists to feed models.
+
+use crate::erasure::SESSION_PARAM;
+use chrono::{DateTime, Utc};
+use mcp_common::clock::{self, Clock};
+use mcp_common::config::{Config, SessionsConfig};
+use mcp_common::{MCPRequest, MCPResponse, Result, Vfs};
+use mcp_queue::{open_storage, QueueStorageBackend};
+use mcp_router::model_aliases::TENANT_PARAM;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Method whose requests get the session's history
+pub const CHAT_METHOD: &str = "chat";
+
+/// Rough characters per token of chat text
+const CHARS_PER_TOKEN: usize = 4;
+
+const KEY_PREFIX: &str = "session:";
+
+/// One chat message as models receive it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    fn from_value(value: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            role: value.get("role")?.as_str()?.to_string(),
+            content: value.get("content")?.as_str()?.to_string(),
+        })
+    }
+
+    fn tokens(&self) -> usize {
+        self.content.len().div_ceil(CHARS_PER_TOKEN)
+    }
+}
+
+/// A session's messages, oldest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionHistory {
+    pub session_id: String,
+    pub device_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub updated_at: DateTime<Utc>,
+    pub messages: Vec<ChatMessage>,
+}
+
+/// New messages of a request, added to its session once answered
+pub struct SessionTurn {
+    session_id: String,
+    device_id: String,
+    tenant: Option<String>,
+    messages: Vec<ChatMessage>,
+}
+
+/// Chat histories by session
+pub struct SessionStore {
+    config: SessionsConfig,
+    storage: Box<dyn QueueStorageBackend>,
+    clock: Arc<dyn Clock>,
+    /// Serializes the read-modify-write of histories
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl SessionStore {
+    pub fn new(config: &Config, vfs: &dyn Vfs) -> Result<Self> {
+        Self::with_clock(config, vfs, clock::system_clock())
+    }
+
+    pub fn with_clock(config: &Config, vfs: &dyn Vfs, clock: Arc<dyn Clock>) -> Result<Self> {
+        let config = config.sessions.clone();
+        let storage = if config.enabled {
+            open_storage(config.storage_backend, vfs.host_path(&config.storage_path).as_deref())?
+        } else {
+            Box::new(mcp_queue::MemoryStorage::default())
+        };
+        Ok(Self {
+            config,
+            storage,
+            clock,
+            lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Prepend the session's history to a chat request; returns the turn to
+    /// record once the request is answered
+    pub async fn prepare(&self, request: &mut MCPRequest) -> Result<Option<SessionTurn>> {
+        if !self.enabled() || request.method != CHAT_METHOD {
+            return Ok(None);
+        }
+        let Some(session_id) = request.params.get(SESSION_PARAM).and_then(|value| value.as_str()) else {
+            return Ok(None);
+        };
+        let session_id = session_id.to_string();
+        let Some(messages) = request.params.get("messages").and_then(|value| value.as_array()) else {
+            return Ok(None);
+        };
+        // System prompts are sent with every request and never become history
+        let (system, current): (Vec<serde_json::Value>, Vec<serde_json::Value>) = messages
+            .iter()
+            .cloned()
+            .partition(|message| message.get("role").and_then(|role| role.as_str()) == Some("system"));
+        let new_messages: Vec<ChatMessage> = current.iter().filter_map(ChatMessage::from_value).collect();
+        let used: usize = system
+            .iter()
+            .chain(&current)
+            .filter_map(ChatMessage::from_value)
+            .map(|message| message.tokens())
+            .sum();
+
+        let history = self.load(&session_id).map(|history| history.messages).unwrap_or_default();
+        let context = fit(&history, self.config.max_context_tokens.saturating_sub(used));
+        if !context.is_empty() {
+            debug!(
+                "Prepending {} of {} history messages to session {}",
+                context.len(),
+                history.len(),
+                session_id
+            );
+            let context = context.iter().map(serde_json::to_value).collect::<serde_json::Result<Vec<_>>>()?;
+            let messages = system.into_iter().chain(context).chain(current).collect();
+            request.params.insert("messages".to_string(), serde_json::Value::Array(messages));
+        }
+
+        Ok(Some(SessionTurn {
+            session_id,
+            device_id: request.device_id.clone(),
+            tenant: request
+                .params
+                .get(TENANT_PARAM)
+                .and_then(|value| value.as_str())
+                .map(str::to_string),
+            messages: new_messages,
+        }))
+    }
+
+    /// Add a turn and the model's reply to the session; failures are logged
+    /// and do not affect the response
+    pub async fn record(&self, turn: SessionTurn, response: &MCPResponse) {
+        let Some(reply) = response.result.as_ref().and_then(reply_text) else {
+            return;
+        };
+        let session_id = turn.session_id.clone();
+        if let Err(e) = self.append(turn, reply).await {
+            warn!("Failed to update session {}: {}", session_id, e);
+        }
+    }
+
+    async fn append(&self, turn: SessionTurn, reply: String) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut history = self.load(&turn.session_id).unwrap_or_else(|| SessionHistory {
+            session_id: turn.session_id.clone(),
+            device_id: turn.device_id.clone(),
+            tenant: turn.tenant.clone(),
+            updated_at: self.clock.now(),
+            messages: Vec::new(),
+        });
+        history.messages.extend(turn.messages);
+        history.messages.push(ChatMessage {
+            role: "assistant".to_string(),
+            content: reply,
+        });
+        let excess = history.messages.len().saturating_sub(self.config.max_messages_per_session);
+        history.messages.drain(..excess);
+        history.updated_at = self.clock.now();
+        self.storage.put(&key(&history.session_id), &serde_json::to_vec(&history)?)
+    }
+
+    /// History of a session that has not been idle too long
+    pub fn get(&self, session_id: &str) -> Option<SessionHistory> {
+        self.load(session_id)
+    }
+
+    /// Delete the histories `matches` selects; returns their session ids.
+    /// A dry run deletes nothing.
+    pub fn erase(&self, matches: impl Fn(&SessionHistory) -> bool, dry_run: bool) -> Result<Vec<String>> {
+        let mut erased = Vec::new();
+        for (key, value) in self.storage.scan(KEY_PREFIX)? {
+            let Ok(history) = serde_json::from_slice::<SessionHistory>(&value) else {
+                continue;
+            };
+            if matches(&history) {
+                if !dry_run {
+                    self.storage.remove(&key)?;
+                }
+                erased.push(history.session_id);
+            }
+        }
+        Ok(erased)
+    }
+
+    fn load(&self, session_id: &str) -> Option<SessionHistory> {
+        let data = match self.storage.get(&key(session_id)) {
+            Ok(data) => data?,
+            Err(e) => {
+                warn!("Failed to read session {}: {}", session_id, e);
+                return None;
+            },
+        };
+        let history: SessionHistory = serde_json::from_slice(&data).ok()?;
+        let idle = self.clock.now().signed_duration_since(history.updated_at);
+        if idle.num_seconds() >= self.config.idle_timeout_secs as i64 {
+            return None;
+        }
+        Some(history)
+    }
+}
+
+fn key(session_id: &str) -> String {
+    format!("{}{}", KEY_PREFIX, session_id)
+}
+
+/// The newest messages of `history` fitting `budget` tokens, oldest first
+fn fit(history: &[ChatMessage], budget: usize) -> &[ChatMessage] {
+    let mut used = 0;
+    let kept = history
+        .iter()
+        .rev()
+        .take_while(|message| {
+            used += message.tokens();
+            used <= budget
+        })
+        .count();
+    &history[history.len() - kept..]
+}
+
+/// Text of a model's chat reply
+fn reply_text(result: &serde_json::Value) -> Option<String> {
+    let text = result
+        .get("text")
+        .or_else(|| result.get("message")?.get("content"))
+        .or_else(|| result.as_str().map(|_| result))?;
+    text.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::clock::FakeClock;
+    use mcp_common::vfs::MemoryVfs;
+    use std::collections::HashMap;
+
+    fn store(max_context_tokens: usize, clock: Arc<FakeClock>) -> SessionStore {
+        let mut config = Config::default();
+        config.sessions.enabled = true;
+        config.sessions.max_context_tokens = max_context_tokens;
+        config.sessions.max_messages_per_session = 4;
+        config.sessions.idle_timeout_secs = 60;
+        SessionStore::with_clock(&config, &MemoryVfs::new(), clock).unwrap()
+    }
+
+    fn chat(session_id: &str, messages: serde_json::Value) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "kiosk-1".to_string(),
+            method: CHAT_METHOD.to_string(),
+            params: HashMap::from([
+                (SESSION_PARAM.to_string(), serde_json::json!(session_id)),
+                ("messages".to_string(), messages),
+            ]),
+            context: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    async fn turn(store: &SessionStore, session_id: &str, text: &str, reply: &str) -> MCPRequest {
+        let mut request = chat(session_id, serde_json::json!([{ "role": "user", "content": text }]));
+        let turn = store.prepare(&mut request).await.unwrap().unwrap();
+        let response = MCPResponse {
+            id: request.id,
+            result: Some(serde_json::json!({ "text": reply })),
+            error: None,
+            timestamp: Utc::now(),
+        };
+        store.record(turn, &response).await;
+        request
+    }
+
+    fn contents(request: &MCPRequest) -> Vec<String> {
+        request.params["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|message| message["content"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_history_is_prepended_within_the_token_budget() {
+        let store = store(7, Arc::new(FakeClock::new(Utc::now())));
+        turn(&store, "chat-1", "aaaa", "bbbb").await;
+        turn(&store, "chat-1", "cccc", "dddd").await;
+
+        // History messages are a token each; the system prompt and the new
+        // message take three of the seven
+        let mut request = chat(
+            "chat-1",
+            serde_json::json!([
+                { "role": "system", "content": "be brief" },
+                { "role": "user", "content": "eeee" }
+            ]),
+        );
+        store.prepare(&mut request).await.unwrap().unwrap();
+        assert_eq!(contents(&request), ["be brief", "aaaa", "bbbb", "cccc", "dddd", "eeee"]);
+
+        // A tighter budget drops the oldest history first
+        let history = store.get("chat-1").unwrap().messages;
+        assert_eq!(fit(&history, 2), &history[2..]);
+    }
+
+    #[tokio::test]
+    async fn test_idle_sessions_start_over_and_can_be_erased() {
+        let clock = Arc::new(FakeClock::new(Utc::now()));
+        let store = store(100, clock.clone());
+        turn(&store, "chat-2", "hello", "hi").await;
+        let other = turn(&store, "chat-3", "hello", "hi").await;
+        assert_eq!(contents(&other), ["hello"]);
+
+        clock.advance(chrono::Duration::seconds(61));
+        let mut request = chat("chat-2", serde_json::json!([{ "role": "user", "content": "again" }]));
+        store.prepare(&mut request).await.unwrap();
+        assert_eq!(contents(&request), ["again"]);
+
+        let erased = store.erase(|history| history.session_id == "chat-3", false).unwrap();
+        assert_eq!(erased, ["chat-3"]);
+        assert!(store.erase(|history| history.session_id == "chat-3", true).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,473 @@
+//! Storage medium health monitoring
+//!
+//! Edge devices boot from eMMC, SD cards or small SSDs that wear out long
+//! before the rest of the hardware. The monitor reads the health reports the
+//! media keep about themselves: the eMMC life time estimates and pre-EOL
+//! state from sysfs, and SMART attributes of SATA and NVMe drives through
+//! `smartctl`. A medium that starts degrading raises an alert well before it
+//! fails, and the process-wide [`WritePolicy`] is relaxed so the gateway
+//! writes to it less often until it is replaced.
+
+use chrono::{DateTime, Utc};
+use mcp_common::config::StorageHealthConfig;
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
+use mcp_common::write_policy::WritePolicy;
+use mcp_common::{ComponentHealth, HealthLevel};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Time smartctl gets to report on one drive
+const SMARTCTL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Block devices that are never physical media
+const VIRTUAL_DEVICES: &[&str] = &["loop", "ram", "zram", "dm-", "md", "sr", "nbd"];
+
+/// SMART attributes reporting wear as a normalized life-remaining value
+const SMART_WEAR_ATTRIBUTES: &[u64] = &[177, 231, 233];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WearLevel {
+    Healthy,
+    Warning,
+    Critical,
+}
+
+/// Where a medium's health report came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthSource {
+    Emmc,
+    Smart,
+}
+
+/// eMMC pre-EOL state: consumption of the reserved blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreEol {
+    Normal,
+    /// 80% of the reserved blocks consumed
+    Warning,
+    /// 90% of the reserved blocks consumed
+    Urgent,
+}
+
+/// Latest health report of one medium
+#[derive(Debug, Clone, Serialize)]
+pub struct MediumHealth {
+    pub device: String,
+    pub source: HealthSource,
+    /// Estimated share of the rated write endurance used
+    pub wear_percent: Option<u8>,
+    pub reallocated_sectors: Option<u64>,
+    /// Sectors waiting to be reallocated after read errors
+    pub pending_sectors: Option<u64>,
+    pub pre_eol: Option<PreEol>,
+    /// Overall SMART self-assessment
+    pub smart_passed: Option<bool>,
+    pub level: WearLevel,
+    pub reasons: Vec<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Share of life used from an eMMC `life_time` file (two hex estimates in
+/// 10% steps, 0x0B once exceeded), taking the more worn of the two
+pub fn parse_emmc_life_time(contents: &str) -> Option<u8> {
+    contents
+        .split_whitespace()
+        .filter_map(|estimate| u8::from_str_radix(estimate.trim_start_matches("0x"), 16).ok())
+        .filter_map(|estimate| match estimate {
+            1..=10 => Some(estimate * 10),
+            11 => Some(100),
+            _ => None,
+        })
+        .max()
+}
+
+pub fn parse_pre_eol(contents: &str) -> Option<PreEol> {
+    match u8::from_str_radix(contents.trim().trim_start_matches("0x"), 16).ok()? {
+        1 => Some(PreEol::Normal),
+        2 => Some(PreEol::Warning),
+        3 => Some(PreEol::Urgent),
+        _ => None,
+    }
+}
+
+/// Wear, reallocated and pending sectors and the self-assessment from
+/// `smartctl --json` output of an ATA or NVMe drive
+pub fn parse_smart(report: &Value) -> (Option<u8>, Option<u64>, Option<u64>, Option<bool>) {
+    let passed = report.pointer("/smart_status/passed").and_then(Value::as_bool);
+    if let Some(log) = report.get("nvme_smart_health_information_log") {
+        let wear = log
+            .get("percentage_used")
+            .and_then(Value::as_u64)
+            .map(|used| used.min(100) as u8);
+        return (wear, None, None, passed);
+    }
+
+    let attributes: HashMap<u64, &Value> = report
+        .pointer("/ata_smart_attributes/table")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|attribute| Some((attribute.get("id")?.as_u64()?, attribute)))
+        .collect();
+    let raw = |id: u64| attributes.get(&id)?.pointer("/raw/value")?.as_u64();
+    let wear = SMART_WEAR_ATTRIBUTES.iter().find_map(|id| {
+        let remaining = attributes.get(id)?.get("value")?.as_u64()?;
+        Some(100u64.saturating_sub(remaining.min(100)) as u8)
+    });
+    (wear, raw(5), raw(197), passed)
+}
+
+/// Checks the storage media periodically and keeps their latest reports
+pub struct StorageHealthMonitor {
+    config: StorageHealthConfig,
+    policy: &'static WritePolicy,
+    media: Mutex<Vec<MediumHealth>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl StorageHealthMonitor {
+    pub fn new(config: StorageHealthConfig) -> Self {
+        Self::with_policy(config, WritePolicy::global())
+    }
+
+    /// Monitor that relaxes `policy` instead of the global write policy
+    pub fn with_policy(config: StorageHealthConfig, policy: &'static WritePolicy) -> Self {
+        Self {
+            config,
+            policy,
+            media: Mutex::new(Vec::new()),
+            task: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn start(self: &Arc<Self>) {
+        if !self.enabled() {
+            return;
+        }
+        let monitor = Arc::downgrade(self);
+        let interval_secs = self.config.interval_secs.max(1);
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                let Some(monitor) = monitor.upgrade() else {
+                    break;
+                };
+                monitor.check().await;
+            }
+        });
+        if let Some(previous) = self.lock_task().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.lock_task().take() {
+            handle.abort();
+        }
+    }
+
+    /// Latest report of every medium that reports its health
+    pub fn report(&self) -> Vec<MediumHealth> {
+        self.lock_media().clone()
+    }
+
+    /// Whether any medium is degrading
+    pub fn degraded(&self) -> bool {
+        self.lock_media().iter().any(|medium| medium.level > WearLevel::Healthy)
+    }
+
+    /// Read every medium now, alerting on level changes and relaxing the
+    /// write policy while any of them is degrading
+    pub async fn check(&self) -> Vec<MediumHealth> {
+        let mut media = Vec::new();
+        for device in self.devices() {
+            let medium = if device.starts_with("mmcblk") {
+                self.read_emmc(&device)
+            } else {
+                self.read_smart(&device).await
+            };
+            if let Some(mut medium) = medium {
+                self.assess(&mut medium);
+                media.push(medium);
+            }
+        }
+
+        let previous: HashMap<String, WearLevel> = self
+            .lock_media()
+            .iter()
+            .map(|medium| (medium.device.clone(), medium.level))
+            .collect();
+        for medium in &media {
+            let before = previous.get(&medium.device).copied().unwrap_or(WearLevel::Healthy);
+            if medium.level != before {
+                alert(medium, before);
+            }
+        }
+
+        let relaxation = if media.iter().any(|medium| medium.level > WearLevel::Healthy) {
+            self.config.degraded_write_relaxation
+        } else {
+            1
+        };
+        if self.policy.relaxation() != relaxation {
+            info!("Storage write relaxation set to {}x", relaxation);
+            self.policy.set_relaxation(relaxation);
+        }
+
+        *self.lock_media() = media.clone();
+        media
+    }
+
+    pub fn health(&self) -> ComponentHealth {
+        let media = self.report();
+        let mut metrics = HashMap::new();
+        for medium in &media {
+            if let Some(wear) = medium.wear_percent {
+                metrics.insert(format!("{}_wear_percent", medium.device), f32::from(wear));
+            }
+            if let Some(reallocated) = medium.reallocated_sectors {
+                metrics.insert(format!("{}_reallocated_sectors", medium.device), reallocated as f32);
+            }
+        }
+        metrics.insert("write_relaxation".to_string(), self.policy.relaxation() as f32);
+
+        let worst = media.iter().max_by_key(|medium| medium.level);
+        let (status, message) = match worst {
+            Some(medium) if medium.level > WearLevel::Healthy => {
+                let status = match medium.level {
+                    WearLevel::Critical => HealthLevel::Critical,
+                    _ => HealthLevel::Degraded,
+                };
+                (status, format!("{}: {}", medium.device, medium.reasons.join("; ")))
+            },
+            _ => (HealthLevel::Healthy, format!("{} media healthy", media.len())),
+        };
+        ComponentHealth {
+            status,
+            message,
+            last_check: Utc::now(),
+            metrics,
+        }
+    }
+
+    /// Configured devices, or every physical disk under the sysfs block directory
+    fn devices(&self) -> Vec<String> {
+        if !self.config.devices.is_empty() {
+            return self.config.devices.clone();
+        }
+        let Ok(entries) = std::fs::read_dir(&self.config.sys_block_dir) else {
+            return Vec::new();
+        };
+        let mut devices: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| !VIRTUAL_DEVICES.iter().any(|prefix| name.starts_with(prefix)))
+            // eMMC boot and replay-protected partitions share the user area's wear
+            .filter(|name| !(name.starts_with("mmcblk") && (name.contains("boot") || name.contains("rpmb"))))
+            .collect();
+        devices.sort();
+        devices
+    }
+
+    fn read_emmc(&self, device: &str) -> Option<MediumHealth> {
+        let dir = self.config.sys_block_dir.join(device).join("device");
+        let read = |file: &str| std::fs::read_to_string(dir.join(file)).ok();
+        let wear_percent = read("life_time").as_deref().and_then(parse_emmc_life_time);
+        let pre_eol = read("pre_eol_info").as_deref().and_then(parse_pre_eol);
+        // SD cards have no health registers
+        if wear_percent.is_none() && pre_eol.is_none() {
+            return None;
+        }
+        Some(MediumHealth {
+            device: device.to_string(),
+            source: HealthSource::Emmc,
+            wear_percent,
+            reallocated_sectors: None,
+            pending_sectors: None,
+            pre_eol,
+            smart_passed: None,
+            level: WearLevel::Healthy,
+            reasons: Vec::new(),
+            checked_at: Utc::now(),
+        })
+    }
+
+    async fn read_smart(&self, device: &str) -> Option<MediumHealth> {
+        let program = self.config.smartctl.as_deref()?;
+        let device_path = Path::new("/dev").join(device);
+        let output = Command::new(program)
+            .args(["--json", "--health", "--attributes"])
+            .arg(&device_path)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+        // smartctl's exit status is a bit mask of findings, so only the JSON counts
+        let output = match tokio::time::timeout(SMARTCTL_TIMEOUT, output).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => {
+                debug!("Cannot run {} for {}: {}", program, device, e);
+                return None;
+            },
+            Err(_) => {
+                warn!("{} timed out reading {}", program, device);
+                return None;
+            },
+        };
+        let report: Value = serde_json::from_slice(&output.stdout).ok()?;
+        let (wear_percent, reallocated_sectors, pending_sectors, smart_passed) = parse_smart(&report);
+        if wear_percent.is_none() && reallocated_sectors.is_none() && smart_passed.is_none() {
+            return None;
+        }
+        Some(MediumHealth {
+            device: device.to_string(),
+            source: HealthSource::Smart,
+            wear_percent,
+            reallocated_sectors,
+            pending_sectors,
+            pre_eol: None,
+            smart_passed,
+            level: WearLevel::Healthy,
+            reasons: Vec::new(),
+            checked_at: Utc::now(),
+        })
+    }
+
+    fn assess(&self, medium: &mut MediumHealth) {
+        let mut level = WearLevel::Healthy;
+        let mut raise = |to: WearLevel, reason: String| {
+            level = level.max(to);
+            medium.reasons.push(reason);
+        };
+        if let Some(wear) = medium.wear_percent {
+            if wear >= self.config.wear_critical_percent {
+                raise(WearLevel::Critical, format!("{}% of rated life used", wear));
+            } else if wear >= self.config.wear_warning_percent {
+                raise(WearLevel::Warning, format!("{}% of rated life used", wear));
+            }
+        }
+        match medium.pre_eol {
+            Some(PreEol::Urgent) => raise(WearLevel::Critical, "reserved blocks nearly exhausted".to_string()),
+            Some(PreEol::Warning) => raise(WearLevel::Warning, "80% of reserved blocks consumed".to_string()),
+            _ => {},
+        }
+        if let Some(reallocated) = medium.reallocated_sectors.filter(|count| *count > 0) {
+            if reallocated >= self.config.reallocated_sectors_warning {
+                raise(WearLevel::Warning, format!("{} reallocated sectors", reallocated));
+            }
+        }
+        if let Some(pending) = medium.pending_sectors.filter(|count| *count > 0) {
+            raise(WearLevel::Warning, format!("{} sectors pending reallocation", pending));
+        }
+        if medium.smart_passed == Some(false) {
+            raise(WearLevel::Critical, "SMART self-assessment failed".to_string());
+        }
+        medium.level = level;
+    }
+
+    fn lock_media(&self) -> MutexGuard<'_, Vec<MediumHealth>> {
+        self.media.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_task(&self) -> MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.task.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn alert(medium: &MediumHealth, before: WearLevel) {
+    let (severity, message) = match medium.level {
+        WearLevel::Healthy => (
+            AlertSeverity::Info,
+            format!("Storage {} no longer reports degradation", medium.device),
+        ),
+        level => (
+            if level == WearLevel::Critical { AlertSeverity::Critical } else { AlertSeverity::Warning },
+            format!(
+                "Storage {} is degrading ({:?} -> {:?}): {}",
+                medium.device,
+                before,
+                level,
+                medium.reasons.join("; ")
+            ),
+        ),
+    };
+    warn!("{}", message);
+    events::publish(GatewayEvent::AlertRaised {
+        source: "storage_health".to_string(),
+        severity,
+        message,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_health_reports() {
+        assert_eq!(parse_emmc_life_time("0x02 0x04\n"), Some(40));
+        assert_eq!(parse_emmc_life_time("0x0B 0x01"), Some(100));
+        assert_eq!(parse_emmc_life_time("0x00 0x00"), None);
+        assert_eq!(parse_pre_eol("0x02\n"), Some(PreEol::Warning));
+
+        let ata = json!({
+            "smart_status": { "passed": true },
+            "ata_smart_attributes": { "table": [
+                { "id": 5, "value": 100, "raw": { "value": 24 } },
+                { "id": 177, "value": 35, "raw": { "value": 2100 } },
+                { "id": 197, "value": 100, "raw": { "value": 0 } }
+            ]}
+        });
+        assert_eq!(parse_smart(&ata), (Some(65), Some(24), Some(0), Some(true)));
+        let nvme = json!({ "nvme_smart_health_information_log": { "percentage_used": 12 } });
+        assert_eq!(parse_smart(&nvme), (Some(12), None, None, None));
+    }
+
+    #[tokio::test]
+    async fn test_worn_emmc_relaxes_writes_until_replaced() {
+        let sys_block = std::env::temp_dir().join(format!("mcp-sys-block-{}", uuid::Uuid::new_v4()));
+        let device = sys_block.join("mmcblk0").join("device");
+        std::fs::create_dir_all(&device).unwrap();
+        std::fs::create_dir_all(sys_block.join("mmcblk0boot0")).unwrap();
+        std::fs::create_dir_all(sys_block.join("loop0")).unwrap();
+        std::fs::write(device.join("life_time"), "0x03 0x08\n").unwrap();
+        std::fs::write(device.join("pre_eol_info"), "0x01\n").unwrap();
+
+        let policy: &'static WritePolicy = Box::leak(Box::new(WritePolicy::new()));
+        let config = StorageHealthConfig {
+            sys_block_dir: sys_block.clone(),
+            smartctl: None,
+            ..Default::default()
+        };
+        let monitor = StorageHealthMonitor::with_policy(config, policy);
+        let media = monitor.check().await;
+        assert_eq!(media.len(), 1);
+        assert_eq!(media[0].wear_percent, Some(80));
+        assert_eq!(media[0].level, WearLevel::Warning);
+        assert!(monitor.degraded());
+        assert_eq!(policy.relaxation(), 4);
+        assert_eq!(monitor.health().status, HealthLevel::Degraded);
+
+        std::fs::write(device.join("life_time"), "0x01 0x01\n").unwrap();
+        monitor.check().await;
+        assert!(!monitor.degraded());
+        assert_eq!(policy.relaxation(), 1);
+        let _ = std::fs::remove_dir_all(&sys_block);
+    }
+}
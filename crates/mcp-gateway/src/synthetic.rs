@@ -0,0 +1,363 @@
+//! Synthetic probe requests
+//!
+//! Health checks see whether components are up, not whether a completion
+//! actually comes back. Every `interval_secs` the gateway sends itself each
+//! configured probe, such as a completion with a tiny prompt, through the
+//! full request path. A probe fails on an error, a response that would have
+//! been queued, a result missing the expected text, or a slow answer; after
+//! `failure_threshold` failures in a row the probe is reported critical in
+//! the gateway health and an alert is raised.
+//!
+//! Probe requests carry [`RequestSource::Probe`], which keeps them out of
+//! the response cache, tenant usage, webhooks and the offline queue.
+
+use crate::gateway::Gateway;
+use chrono::{DateTime, Utc};
+use mcp_common::config::{SyntheticProbeConfig, SyntheticProbesConfig};
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
+use mcp_common::{
+    ComponentHealth, HealthLevel, MCPRequest, MCPResponse, RequestContext, RequestSource, Result,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Device ID probe requests are sent as
+pub const PROBE_DEVICE_ID: &str = "synthetic-probe";
+
+/// Outcome history of one probe
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProbeStatus {
+    pub name: String,
+    pub method: String,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_latency_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    pub runs: u64,
+    pub failures: u64,
+}
+
+/// Sends the configured probes and keeps their outcomes
+pub struct SyntheticProbes {
+    config: SyntheticProbesConfig,
+    statuses: Mutex<HashMap<String, ProbeStatus>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SyntheticProbes {
+    pub fn new(config: SyntheticProbesConfig) -> Self {
+        let statuses = config
+            .probes
+            .iter()
+            .map(|probe| {
+                let status = ProbeStatus {
+                    name: probe.name.clone(),
+                    method: probe.method.clone(),
+                    ..Default::default()
+                };
+                (probe.name.clone(), status)
+            })
+            .collect();
+        Self {
+            config,
+            statuses: Mutex::new(statuses),
+            task: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled && !self.config.probes.is_empty()
+    }
+
+    /// Probe `gateway` every `interval_secs` in the background
+    pub fn start(self: &Arc<Self>, gateway: Weak<Gateway>) {
+        if !self.enabled() {
+            return;
+        }
+        let probes = Arc::downgrade(self);
+        let interval_secs = self.config.interval_secs.max(1);
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                let (Some(probes), Some(gateway)) = (probes.upgrade(), gateway.upgrade()) else {
+                    break;
+                };
+                probes
+                    .run_once(|request| gateway.process_request(request))
+                    .await;
+            }
+        });
+        if let Some(previous) = self.lock_task().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.lock_task().take() {
+            handle.abort();
+        }
+    }
+
+    /// Send every probe once through `send` and record the outcomes
+    pub async fn run_once<F, Fut>(&self, send: F)
+    where
+        F: Fn(MCPRequest) -> Fut,
+        Fut: Future<Output = Result<MCPResponse>>,
+    {
+        for probe in &self.config.probes {
+            let start = Instant::now();
+            let result = send(probe_request(probe)).await;
+            let latency = start.elapsed();
+            self.record(probe, check(probe, result, latency), latency);
+        }
+    }
+
+    fn record(
+        &self,
+        probe: &SyntheticProbeConfig,
+        outcome: std::result::Result<(), String>,
+        latency: Duration,
+    ) {
+        let threshold = self.config.failure_threshold.max(1);
+        let mut statuses = self.lock_statuses();
+        let status = statuses.entry(probe.name.clone()).or_default();
+        let now = Utc::now();
+        status.runs += 1;
+        status.last_run = Some(now);
+        status.last_latency_ms = Some(latency.as_millis() as u64);
+        match outcome {
+            Ok(()) => {
+                debug!("Synthetic probe {} passed in {:?}", probe.name, latency);
+                if status.consecutive_failures >= threshold {
+                    events::publish(GatewayEvent::AlertRaised {
+                        source: "synthetic_probe".to_string(),
+                        severity: AlertSeverity::Info,
+                        message: format!("Synthetic probe {} passes again", probe.name),
+                    });
+                }
+                status.consecutive_failures = 0;
+                status.last_success = Some(now);
+                status.last_error = None;
+            },
+            Err(reason) => {
+                warn!("Synthetic probe {} failed: {}", probe.name, reason);
+                status.failures += 1;
+                status.consecutive_failures += 1;
+                if status.consecutive_failures == threshold {
+                    events::publish(GatewayEvent::AlertRaised {
+                        source: "synthetic_probe".to_string(),
+                        severity: AlertSeverity::Critical,
+                        message: format!(
+                            "Synthetic probe {} failed {} times in a row: {}",
+                            probe.name, threshold, reason
+                        ),
+                    });
+                }
+                status.last_error = Some(reason);
+            },
+        }
+    }
+
+    /// Outcome history of every probe, by name
+    pub fn statuses(&self) -> Vec<ProbeStatus> {
+        let mut statuses: Vec<ProbeStatus> = self.lock_statuses().values().cloned().collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Critical once a probe reaches the failure threshold, degraded while
+    /// any probe's latest run failed
+    pub fn health(&self) -> ComponentHealth {
+        let threshold = self.config.failure_threshold.max(1);
+        let statuses = self.statuses();
+        let mut metrics = HashMap::new();
+        for status in &statuses {
+            metrics.insert(
+                format!("{}_consecutive_failures", status.name),
+                status.consecutive_failures as f32,
+            );
+            if let Some(latency_ms) = status.last_latency_ms {
+                metrics.insert(format!("{}_latency_ms", status.name), latency_ms as f32);
+            }
+        }
+        let failing: Vec<&str> = statuses
+            .iter()
+            .filter(|status| status.consecutive_failures > 0)
+            .map(|status| status.name.as_str())
+            .collect();
+        let (status, message) = if statuses
+            .iter()
+            .any(|status| status.consecutive_failures >= threshold)
+        {
+            (
+                HealthLevel::Critical,
+                format!("Failing synthetic probes: {}", failing.join(", ")),
+            )
+        } else if !failing.is_empty() {
+            (
+                HealthLevel::Degraded,
+                format!("Failing synthetic probes: {}", failing.join(", ")),
+            )
+        } else {
+            (
+                HealthLevel::Healthy,
+                format!("{} synthetic probes passing", statuses.len()),
+            )
+        };
+        ComponentHealth {
+            status,
+            message,
+            last_check: Utc::now(),
+            metrics,
+        }
+    }
+
+    fn lock_statuses(&self) -> std::sync::MutexGuard<'_, HashMap<String, ProbeStatus>> {
+        self.statuses
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_task(&self) -> std::sync::MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.task
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn probe_request(probe: &SyntheticProbeConfig) -> MCPRequest {
+    MCPRequest {
+        id: uuid::Uuid::new_v4(),
+        device_id: PROBE_DEVICE_ID.to_string(),
+        method: probe.method.clone(),
+        params: probe.params.clone(),
+        context: Some(RequestContext {
+            source: RequestSource::Probe,
+            ..Default::default()
+        }),
+        timestamp: Utc::now(),
+    }
+}
+
+/// Why a probe's response does not pass, if it does not
+fn check(
+    probe: &SyntheticProbeConfig,
+    result: Result<MCPResponse>,
+    latency: Duration,
+) -> std::result::Result<(), String> {
+    let response = result.map_err(|e| e.to_string())?;
+    if let Some(error) = response.error {
+        return Err(format!("error {}: {}", error.code, error.message));
+    }
+    let result = response
+        .result
+        .ok_or_else(|| "response without a result".to_string())?;
+    if let Some(expected) = &probe.expect_contains {
+        if !result.to_string().contains(expected.as_str()) {
+            return Err(format!("result does not contain {:?}", expected));
+        }
+    }
+    if latency > Duration::from_millis(probe.max_latency_ms) {
+        return Err(format!(
+            "took {}ms, over {}ms",
+            latency.as_millis(),
+            probe.max_latency_ms
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::Error;
+
+    fn probes(failure_threshold: u32) -> SyntheticProbes {
+        SyntheticProbes::new(SyntheticProbesConfig {
+            enabled: true,
+            failure_threshold,
+            probes: vec![SyntheticProbeConfig {
+                expect_contains: Some("OK".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+    }
+
+    fn response(text: &str) -> Result<MCPResponse> {
+        Ok(MCPResponse {
+            id: uuid::Uuid::new_v4(),
+            result: Some(serde_json::json!({ "text": text })),
+            error: None,
+            timestamp: Utc::now(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_probes_are_marked_and_failures_escalate_health() {
+        let probes = probes(2);
+        probes
+            .run_once(|request| async move {
+                assert!(request.is_probe());
+                assert_eq!(request.device_id, PROBE_DEVICE_ID);
+                response("OK")
+            })
+            .await;
+        assert_eq!(probes.health().status, HealthLevel::Healthy);
+
+        // A wrong answer is as bad as no answer
+        probes.run_once(|_| async { response("garbled") }).await;
+        assert_eq!(probes.health().status, HealthLevel::Degraded);
+        probes
+            .run_once(|_| async { Err(Error::Routing("no model".to_string())) })
+            .await;
+        let health = probes.health();
+        assert_eq!(health.status, HealthLevel::Critical);
+        assert_eq!(
+            health.metrics["canary_completion_consecutive_failures"],
+            2.0
+        );
+
+        let status = &probes.statuses()[0];
+        assert_eq!((status.runs, status.failures), (3, 2));
+        assert!(status.last_error.as_deref().unwrap().contains("no model"));
+
+        probes.run_once(|_| async { response("OK") }).await;
+        assert_eq!(probes.health().status, HealthLevel::Healthy);
+    }
+
+    #[test]
+    fn test_slow_or_erroring_responses_fail_the_check() {
+        let probe = SyntheticProbeConfig {
+            max_latency_ms: 100,
+            ..Default::default()
+        };
+        assert!(check(&probe, response("anything"), Duration::from_millis(20)).is_ok());
+        assert!(
+            check(&probe, response("anything"), Duration::from_millis(150))
+                .unwrap_err()
+                .contains("over 100ms")
+        );
+
+        let failed = Ok(MCPResponse {
+            id: uuid::Uuid::new_v4(),
+            result: None,
+            error: Some(mcp_common::MCPError {
+                code: -32603,
+                message: "model crashed".to_string(),
+                data: None,
+            }),
+            timestamp: Utc::now(),
+        });
+        assert!(check(&probe, failed, Duration::ZERO)
+            .unwrap_err()
+            .contains("model crashed"));
+    }
+}
@@ -0,0 +1,372 @@
+//! In-memory components for fast, deterministic gateway tests
+//!
+//! [`GatewayBuilder::deterministic`](crate::GatewayBuilder::deterministic)
+//! wires these in place of the persistent queue, the real model engine and
+//! the HTTP cloud client, so integration tests exercise the real gateway
+//! pipeline without model files, disk or network access. Each component
+//! records what it was asked to do so tests can assert on it afterwards.
+
+use async_trait::async_trait;
+use mcp_common::clock::{self, Clock};
+use mcp_common::config::SyncPolicy;
+use mcp_common::events::{self, EventKind, EventSubscriber, GatewayEvent, QueueEvent, SyncTrigger};
+use mcp_common::metrics::{ComponentHealth, HealthLevel};
+use mcp_common::{Error, MCPRequest, MCPResponse, ModelId, Result};
+use mcp_models::ModelEngine;
+use mcp_queue::{OfflineQueue, QueuePurge};
+use mcp_router::CloudTransport;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn healthy(message: &str, clock: &dyn Clock, metrics: HashMap<String, f32>) -> ComponentHealth {
+    ComponentHealth {
+        status: HealthLevel::Healthy,
+        message: message.to_string(),
+        last_check: clock.now(),
+        metrics,
+    }
+}
+
+/// FIFO offline queue held entirely in memory
+pub struct InMemoryQueue {
+    capacity: usize,
+    requests: Mutex<VecDeque<MCPRequest>>,
+    synced: Mutex<Vec<MCPRequest>>,
+    sync_policies: Mutex<Option<Vec<SyncPolicy>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl InMemoryQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_clock(capacity, clock::system_clock())
+    }
+
+    pub fn with_clock(capacity: usize, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            capacity,
+            requests: Mutex::new(VecDeque::new()),
+            synced: Mutex::new(Vec::new()),
+            sync_policies: Mutex::new(None),
+            clock,
+        }
+    }
+
+    /// Requests currently waiting in the queue
+    pub fn pending(&self) -> Vec<MCPRequest> {
+        self.requests.lock().iter().cloned().collect()
+    }
+
+    /// Requests drained by `sync_with_cloud`
+    pub fn synced(&self) -> Vec<MCPRequest> {
+        self.synced.lock().clone()
+    }
+
+    /// Sync policies last given to `set_sync_policies`
+    pub fn sync_policies(&self) -> Option<Vec<SyncPolicy>> {
+        self.sync_policies.lock().clone()
+    }
+}
+
+#[async_trait]
+impl OfflineQueue for InMemoryQueue {
+    async fn enqueue_request(&self, request: MCPRequest) -> Result<MCPResponse> {
+        let queue_size = {
+            let mut requests = self.requests.lock();
+            if requests.len() >= self.capacity {
+                return Err(Error::Queue("Queue is full".to_string()));
+            }
+            requests.push_back(request.clone());
+            requests.len()
+        };
+        events::publish(GatewayEvent::Queue {
+            event: QueueEvent::Enqueued {
+                request_id: request.id,
+                priority_score: 0.0,
+                queue_size,
+                capacity: self.capacity,
+            },
+        });
+
+        Ok(MCPResponse {
+            id: request.id,
+            result: Some(serde_json::json!({
+                "status": "queued",
+                "queue_position": queue_size
+            })),
+            error: None,
+            timestamp: self.clock.now(),
+        })
+    }
+
+    async fn dequeue_request(&self) -> Result<Option<MCPRequest>> {
+        let (request, queue_size) = {
+            let mut requests = self.requests.lock();
+            (requests.pop_front(), requests.len())
+        };
+        if let Some(request) = &request {
+            events::publish(GatewayEvent::Queue {
+                event: QueueEvent::Dequeued {
+                    request_id: request.id,
+                    queue_size,
+                },
+            });
+        }
+        Ok(request)
+    }
+
+    async fn queue_size(&self) -> Result<u32> {
+        Ok(self.requests.lock().len() as u32)
+    }
+
+    /// Treats every pending request as delivered
+    async fn sync_with_cloud(&self) -> Result<()> {
+        let drained: Vec<MCPRequest> = self.requests.lock().drain(..).collect();
+        events::publish(GatewayEvent::Queue {
+            event: QueueEvent::SyncStarted {
+                trigger: SyncTrigger::Manual,
+                pending: drained.len(),
+            },
+        });
+        let synced = drained.len();
+        self.synced.lock().extend(drained);
+        events::publish(GatewayEvent::Queue {
+            event: QueueEvent::SyncFinished {
+                trigger: SyncTrigger::Manual,
+                synced,
+                failed: 0,
+                queue_size: 0,
+            },
+        });
+        Ok(())
+    }
+
+    async fn purge(
+        &self,
+        cutoff: chrono::DateTime<chrono::Utc>,
+        held_devices: &[String],
+        dry_run: bool,
+    ) -> Result<QueuePurge> {
+        let mut requests = self.requests.lock();
+        let mut purge = QueuePurge::default();
+        for request in requests.iter().filter(|request| request.timestamp < cutoff) {
+            if held_devices.contains(&request.device_id) {
+                purge.held += 1;
+            } else {
+                purge.request_ids.push(request.id);
+            }
+        }
+        if !dry_run {
+            requests.retain(|request| !purge.request_ids.contains(&request.id));
+        }
+        Ok(purge)
+    }
+
+    async fn erase(
+        &self,
+        selector: &(dyn for<'r> Fn(&'r MCPRequest) -> bool + Send + Sync),
+        dry_run: bool,
+    ) -> Result<Vec<Uuid>> {
+        let mut requests = self.requests.lock();
+        let erased: Vec<Uuid> = requests.iter().filter(|request| selector(request)).map(|request| request.id).collect();
+        if !dry_run {
+            requests.retain(|request| !erased.contains(&request.id));
+        }
+        Ok(erased)
+    }
+
+    fn subscribe(&self) -> Result<EventSubscriber> {
+        events::subscribe(&[EventKind::Queue])
+    }
+
+    fn set_sync_policies(&self, policies: &[SyncPolicy]) {
+        *self.sync_policies.lock() = Some(policies.to_vec());
+    }
+
+    async fn health_check(&self) -> Result<ComponentHealth> {
+        let mut metrics = HashMap::new();
+        metrics.insert("queue_size".to_string(), self.requests.lock().len() as f32);
+        Ok(healthy("In-memory queue", self.clock.as_ref(), metrics))
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Model engine that answers from canned responses
+///
+/// Models without a configured response echo the model and method back, so
+/// assertions can tell which model a request was routed to.
+pub struct StubModelEngine {
+    responses: Mutex<HashMap<ModelId, serde_json::Value>>,
+    failure: Mutex<Option<String>>,
+    calls: Mutex<Vec<(String, ModelId)>>,
+    loaded: Mutex<HashSet<ModelId>>,
+    inference_percent: Mutex<u8>,
+    clock: Arc<dyn Clock>,
+}
+
+impl StubModelEngine {
+    pub fn new() -> Self {
+        Self::with_clock(clock::system_clock())
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            responses: Mutex::new(HashMap::new()),
+            failure: Mutex::new(None),
+            calls: Mutex::new(Vec::new()),
+            loaded: Mutex::new(HashSet::new()),
+            inference_percent: Mutex::new(100),
+            clock,
+        }
+    }
+
+    /// Result returned for every request handled by `model_id`
+    pub fn set_response(&self, model_id: impl Into<ModelId>, result: serde_json::Value) {
+        self.responses.lock().insert(model_id.into(), result);
+    }
+
+    /// Fail every request with a model error until cleared with `None`
+    pub fn set_failure(&self, message: Option<&str>) {
+        *self.failure.lock() = message.map(str::to_string);
+    }
+
+    /// `(method, model_id)` of every request processed so far
+    pub fn calls(&self) -> Vec<(String, ModelId)> {
+        self.calls.lock().clone()
+    }
+
+    /// Models currently loaded
+    pub fn loaded_models(&self) -> Vec<ModelId> {
+        let mut models: Vec<ModelId> = self.loaded.lock().iter().cloned().collect();
+        models.sort();
+        models
+    }
+
+    /// Share of inference concurrency last set by `throttle_inference`
+    pub fn inference_percent(&self) -> u8 {
+        *self.inference_percent.lock()
+    }
+}
+
+impl Default for StubModelEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ModelEngine for StubModelEngine {
+    async fn process_request(&self, request: &MCPRequest, model_id: &ModelId) -> Result<MCPResponse> {
+        self.calls.lock().push((request.method.clone(), model_id.clone()));
+        if let Some(message) = self.failure.lock().clone() {
+            return Err(Error::Model(message));
+        }
+        self.loaded.lock().insert(model_id.clone());
+
+        let result = self.responses.lock().get(model_id).cloned().unwrap_or_else(|| {
+            serde_json::json!({
+                "model": model_id,
+                "method": request.method,
+                "text": format!("stub response from {}", model_id),
+            })
+        });
+        Ok(MCPResponse {
+            id: request.id,
+            result: Some(result),
+            error: None,
+            timestamp: self.clock.now(),
+        })
+    }
+
+    async fn load_model(&self, model_id: &ModelId) -> Result<()> {
+        self.loaded.lock().insert(model_id.clone());
+        Ok(())
+    }
+
+    async fn unload_model(&self, model_id: &ModelId) -> Result<()> {
+        self.loaded.lock().remove(model_id);
+        Ok(())
+    }
+
+    fn throttle_inference(&self, percent: u8) {
+        *self.inference_percent.lock() = percent;
+    }
+
+    async fn health_check(&self) -> Result<ComponentHealth> {
+        let mut metrics = HashMap::new();
+        metrics.insert("loaded_models".to_string(), self.loaded.lock().len() as f32);
+        Ok(healthy("Stub model engine", self.clock.as_ref(), metrics))
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Cloud transport that replays scripted outcomes in order
+///
+/// Once the script is exhausted every request fails with a network error,
+/// which the gateway treats the same as an unreachable endpoint.
+pub struct ScriptedCloudClient {
+    script: Mutex<VecDeque<Result<serde_json::Value>>>,
+    sent: Mutex<Vec<(String, MCPRequest)>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ScriptedCloudClient {
+    pub fn new() -> Self {
+        Self::with_clock(clock::system_clock())
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            script: Mutex::new(VecDeque::new()),
+            sent: Mutex::new(Vec::new()),
+            clock,
+        }
+    }
+
+    /// Answer the next cloud request with `result`
+    pub fn push_response(&self, result: serde_json::Value) {
+        self.script.lock().push_back(Ok(result));
+    }
+
+    /// Fail the next cloud request with `error`
+    pub fn push_error(&self, error: Error) {
+        self.script.lock().push_back(Err(error));
+    }
+
+    /// `(endpoint, request)` of every request forwarded so far
+    pub fn sent(&self) -> Vec<(String, MCPRequest)> {
+        self.sent.lock().clone()
+    }
+}
+
+impl Default for ScriptedCloudClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl CloudTransport for ScriptedCloudClient {
+    async fn send_request(&self, endpoint: &str, request: &MCPRequest) -> Result<MCPResponse> {
+        self.sent.lock().push((endpoint.to_string(), request.clone()));
+        let result = self.script.lock().pop_front().unwrap_or_else(|| {
+            Err(Error::Network(format!(
+                "No scripted cloud response left for request {}",
+                request.id
+            )))
+        })?;
+        Ok(MCPResponse {
+            id: request.id,
+            result: Some(result),
+            error: None,
+            timestamp: self.clock.now(),
+        })
+    }
+}
@@ -0,0 +1,544 @@
+//! Incident timeline reconstruction
+//!
+//! Alerts, scaling actions, recovery attempts, configuration reloads,
+//! connectivity changes and deployments arrive on the event bus; request
+//! error spikes are detected from the gateway's own request outcomes. Each
+//! becomes an entry appended to a JSON lines file in gateway storage, which
+//! is restored on startup. A query returns the entries of a time range along
+//! with the incidents they form: runs of entries no further apart than the
+//! correlation window that contain an alert, a recovery attempt or an error
+//! spike.
+
+use chrono::{DateTime, Utc};
+use mcp_common::config::TimelineConfig;
+use mcp_common::events::{self, AlertSeverity, EventEnvelope, EventKind, GatewayEvent};
+use mcp_common::Vfs;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Bus events recorded on the timeline
+const RECORDED_KINDS: &[EventKind] = &[
+    EventKind::Alert,
+    EventKind::Scaling,
+    EventKind::Recovery,
+    EventKind::Config,
+    EventKind::Connectivity,
+    EventKind::Deployment,
+];
+
+/// What a timeline entry is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineCategory {
+    Alert,
+    Scaling,
+    Recovery,
+    Config,
+    Connectivity,
+    Deployment,
+    ErrorSpike,
+}
+
+impl TimelineCategory {
+    /// Parse a category name as used in queries
+    pub fn parse(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.trim().to_lowercase())).ok()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimelineCategory::Alert => "alert",
+            TimelineCategory::Scaling => "scaling",
+            TimelineCategory::Recovery => "recovery",
+            TimelineCategory::Config => "config",
+            TimelineCategory::Connectivity => "connectivity",
+            TimelineCategory::Deployment => "deployment",
+            TimelineCategory::ErrorSpike => "error_spike",
+        }
+    }
+
+    /// Whether entries of this category make a run of entries an incident
+    fn signals_incident(&self) -> bool {
+        matches!(
+            self,
+            TimelineCategory::Alert | TimelineCategory::Recovery | TimelineCategory::ErrorSpike
+        )
+    }
+}
+
+/// Something that happened on the device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    pub timestamp: DateTime<Utc>,
+    pub category: TimelineCategory,
+    /// Component, endpoint or subsystem the entry is about
+    pub source: String,
+    pub summary: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<AlertSeverity>,
+}
+
+impl TimelineEntry {
+    /// Entry for a bus event, if its kind is recorded
+    pub fn from_event(envelope: &EventEnvelope) -> Option<Self> {
+        let (category, source, summary, severity) = match &envelope.event {
+            GatewayEvent::AlertRaised {
+                source,
+                severity,
+                message,
+            } => (TimelineCategory::Alert, source.clone(), message.clone(), Some(*severity)),
+            GatewayEvent::ScalingApplied {
+                action,
+            } => (TimelineCategory::Scaling, "autoscaler".to_string(), action.clone(), None),
+            GatewayEvent::RecoveryAttempted {
+                component,
+                success,
+                detail,
+            } => {
+                let (outcome, severity) = if *success {
+                    ("succeeded", AlertSeverity::Info)
+                } else {
+                    ("failed", AlertSeverity::Warning)
+                };
+                let summary = format!("Recovery {}: {}", outcome, detail);
+                (TimelineCategory::Recovery, component.clone(), summary, Some(severity))
+            },
+            GatewayEvent::ConfigReloaded {
+                section,
+            } => (TimelineCategory::Config, section.clone(), "Configuration reloaded".to_string(), None),
+            GatewayEvent::ConfigChanged {
+                version,
+                changed,
+                restart_required,
+            } => {
+                let mut summary =
+                    format!("Configuration version {} applied: {}", version, changed.join(", "));
+                if !restart_required.is_empty() {
+                    summary.push_str(&format!(" (restart required for {})", restart_required.join(", ")));
+                }
+                (TimelineCategory::Config, "config".to_string(), summary, None)
+            },
+            GatewayEvent::ConnectivityChanged {
+                endpoint,
+                online,
+            } => {
+                let summary = if *online { "Went online" } else { "Went offline" };
+                (TimelineCategory::Connectivity, endpoint.clone(), summary.to_string(), None)
+            },
+            GatewayEvent::DeploymentFinished {
+                deployment_id,
+                version,
+                success,
+            } => {
+                let outcome = if *success { "succeeded" } else { "failed" };
+                let summary = format!("Deployment {} of version {} {}", deployment_id, version, outcome);
+                (TimelineCategory::Deployment, "deployment".to_string(), summary, None)
+            },
+            _ => return None,
+        };
+        Some(Self {
+            timestamp: envelope.timestamp,
+            category,
+            source,
+            summary,
+            severity,
+        })
+    }
+}
+
+/// Entries to return from a timeline
+#[derive(Debug, Clone, Default)]
+pub struct TimelineQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Categories to include; every category when empty
+    pub categories: Vec<TimelineCategory>,
+}
+
+/// A run of correlated entries containing an alert, recovery attempt or
+/// error spike
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineIncident {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// Indexes of the run's first and last entry in the timeline's entries
+    pub first_entry: usize,
+    pub last_entry: usize,
+    pub categories: Vec<TimelineCategory>,
+    pub sources: Vec<String>,
+}
+
+/// Entries of a time range and the incidents they form
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timeline {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub entries: Vec<TimelineEntry>,
+    pub incidents: Vec<TimelineIncident>,
+}
+
+impl Timeline {
+    /// Correlate time-ordered entries into incidents
+    fn build(query: &TimelineQuery, entries: Vec<TimelineEntry>, correlation_window_secs: u64) -> Self {
+        let window = chrono::Duration::seconds(correlation_window_secs as i64);
+        let mut incidents = Vec::new();
+        let mut start = 0;
+        for index in 0..entries.len() {
+            let run_ends = entries
+                .get(index + 1)
+                .map_or(true, |next| next.timestamp - entries[index].timestamp > window);
+            if !run_ends {
+                continue;
+            }
+            let run = &entries[start..=index];
+            if run.iter().any(|entry| entry.category.signals_incident()) {
+                let mut categories: Vec<TimelineCategory> = Vec::new();
+                let mut sources: Vec<String> = Vec::new();
+                for entry in run {
+                    if !categories.contains(&entry.category) {
+                        categories.push(entry.category);
+                    }
+                    if !sources.contains(&entry.source) {
+                        sources.push(entry.source.clone());
+                    }
+                }
+                incidents.push(TimelineIncident {
+                    started_at: run[0].timestamp,
+                    ended_at: run[run.len() - 1].timestamp,
+                    first_entry: start,
+                    last_entry: index,
+                    categories,
+                    sources,
+                });
+            }
+            start = index + 1;
+        }
+        Self {
+            since: query.since,
+            until: query.until,
+            entries,
+            incidents,
+        }
+    }
+
+    /// One line per entry, with a header before each incident's first entry
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let mut incidents = self.incidents.iter().enumerate().peekable();
+        for (index, entry) in self.entries.iter().enumerate() {
+            if let Some((number, incident)) = incidents.next_if(|(_, incident)| incident.first_entry == index) {
+                let categories: Vec<&str> = incident.categories.iter().map(TimelineCategory::as_str).collect();
+                text.push_str(&format!(
+                    "== Incident {} from {} to {}: {} ({})\n",
+                    number + 1,
+                    incident.started_at.to_rfc3339(),
+                    incident.ended_at.to_rfc3339(),
+                    categories.join(", "),
+                    incident.sources.join(", ")
+                ));
+            }
+            text.push_str(&format!(
+                "{} {:<12} {}: {}\n",
+                entry.timestamp.to_rfc3339(),
+                entry.category.as_str(),
+                entry.source,
+                entry.summary
+            ));
+        }
+        text
+    }
+}
+
+/// Request errors counted towards a spike
+struct ErrorWindow {
+    started_at: DateTime<Utc>,
+    errors: u32,
+    reported: bool,
+}
+
+/// Records the timeline and answers queries over it
+pub struct IncidentTimeline {
+    config: TimelineConfig,
+    storage: Arc<dyn Vfs>,
+    entries: Mutex<VecDeque<TimelineEntry>>,
+    errors: Mutex<ErrorWindow>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl IncidentTimeline {
+    pub fn new(config: TimelineConfig, storage: Arc<dyn Vfs>) -> Self {
+        Self {
+            config,
+            storage,
+            entries: Mutex::new(VecDeque::new()),
+            errors: Mutex::new(ErrorWindow {
+                started_at: Utc::now(),
+                errors: 0,
+                reported: false,
+            }),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Load the entries saved by previous runs, dropping expired ones from
+    /// the file
+    pub async fn restore(&self) {
+        if !self.config.enabled || !self.storage.exists(&self.config.path).await {
+            return;
+        }
+        let data = match self.storage.read(&self.config.path).await {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Failed to restore incident timeline: {}", e);
+                return;
+            },
+        };
+        let cutoff = Utc::now() - chrono::Duration::hours(self.config.retention_hours as i64);
+        let lines = String::from_utf8_lossy(&data);
+        let saved: Vec<TimelineEntry> = lines
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let mut kept: Vec<TimelineEntry> = saved.iter().filter(|entry| entry.timestamp >= cutoff).cloned().collect();
+        kept.drain(..kept.len().saturating_sub(self.config.max_entries));
+
+        if kept.len() < lines.lines().count() {
+            let mut compacted = Vec::new();
+            for entry in &kept {
+                compacted.extend(serde_json::to_vec(entry).unwrap_or_default());
+                compacted.push(b'\n');
+            }
+            if let Err(e) = self.storage.write(&self.config.path, &compacted).await {
+                warn!("Failed to compact incident timeline: {}", e);
+            }
+        }
+        info!("Restored {} incident timeline entries", kept.len());
+        self.lock_entries().extend(kept);
+    }
+
+    /// Record bus events in the background
+    pub fn start(self: &Arc<Self>) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut subscriber = match events::subscribe(RECORDED_KINDS) {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                warn!("Incident timeline is not recording events: {}", e);
+                return;
+            },
+        };
+        let timeline = Arc::downgrade(self);
+        let handle = tokio::spawn(async move {
+            while let Some(envelope) = subscriber.recv().await {
+                let Some(timeline) = timeline.upgrade() else {
+                    break;
+                };
+                if let Some(entry) = TimelineEntry::from_event(&envelope) {
+                    timeline.record(entry).await;
+                }
+            }
+        });
+        if let Some(previous) = self.lock_task().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.lock_task().take() {
+            handle.abort();
+        }
+    }
+
+    /// Add an entry and append it to the timeline file
+    pub async fn record(&self, entry: TimelineEntry) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut line = match serde_json::to_vec(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize timeline entry: {}", e);
+                return;
+            },
+        };
+        line.push(b'\n');
+        {
+            let mut entries = self.lock_entries();
+            if entries.len() >= self.config.max_entries {
+                entries.pop_front();
+            }
+            entries.push_back(entry);
+        }
+        if let Some(parent) = self.config.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            let _ = self.storage.create_dir_all(parent).await;
+        }
+        if let Err(e) = self.storage.append(&self.config.path, &line).await {
+            warn!("Failed to save timeline entry: {}", e);
+        }
+    }
+
+    /// Count a failed request, recording a spike once a window's errors
+    /// reach the threshold
+    pub async fn record_error(&self, now: DateTime<Utc>) {
+        let spike = {
+            let mut window = self.errors.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let length = chrono::Duration::seconds(self.config.error_spike_window_secs as i64);
+            if now - window.started_at >= length {
+                *window = ErrorWindow {
+                    started_at: now,
+                    errors: 0,
+                    reported: false,
+                };
+            }
+            window.errors += 1;
+            let spike = !window.reported && window.errors >= self.config.error_spike_threshold;
+            window.reported |= spike;
+            spike.then(|| TimelineEntry {
+                timestamp: now,
+                category: TimelineCategory::ErrorSpike,
+                source: "gateway".to_string(),
+                summary: format!(
+                    "{} request errors within {}s",
+                    window.errors, self.config.error_spike_window_secs
+                ),
+                severity: Some(AlertSeverity::Warning),
+            })
+        };
+        if let Some(entry) = spike {
+            self.record(entry).await;
+        }
+    }
+
+    /// Entries matching a query, oldest first, with the incidents they form
+    pub fn query(&self, query: &TimelineQuery) -> Timeline {
+        let mut entries: Vec<TimelineEntry> = self
+            .lock_entries()
+            .iter()
+            .filter(|entry| query.since.map_or(true, |since| entry.timestamp >= since))
+            .filter(|entry| query.until.map_or(true, |until| entry.timestamp <= until))
+            .filter(|entry| query.categories.is_empty() || query.categories.contains(&entry.category))
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| entry.timestamp);
+        Timeline::build(query, entries, self.config.correlation_window_secs)
+    }
+
+    fn lock_entries(&self) -> std::sync::MutexGuard<'_, VecDeque<TimelineEntry>> {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_task(&self) -> std::sync::MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.task.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::vfs::MemoryVfs;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-03-02T02:00:00Z").unwrap().with_timezone(&Utc)
+            + chrono::Duration::minutes(minutes)
+    }
+
+    fn envelope(minutes: i64, event: GatewayEvent) -> EventEnvelope {
+        EventEnvelope {
+            sequence: minutes as u64,
+            timestamp: at(minutes),
+            event,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_are_correlated_into_incidents() {
+        let timeline = IncidentTimeline::new(TimelineConfig::default(), Arc::new(MemoryVfs::new()));
+        let events = [
+            envelope(0, GatewayEvent::ConfigReloaded {
+                section: "model_aliases".to_string(),
+            }),
+            envelope(60, GatewayEvent::AlertRaised {
+                source: "admission".to_string(),
+                severity: AlertSeverity::Warning,
+                message: "Memory pressure".to_string(),
+            }),
+            envelope(65, GatewayEvent::ScalingApplied {
+                action: "reduce_batch_size".to_string(),
+            }),
+            envelope(72, GatewayEvent::RecoveryAttempted {
+                component: "model_engine".to_string(),
+                success: true,
+                detail: "High memory usage: 600MB".to_string(),
+            }),
+            envelope(200, GatewayEvent::ModelUnloaded {
+                model_id: "tiny".to_string(),
+            }),
+        ];
+        for envelope in &events {
+            if let Some(entry) = TimelineEntry::from_event(envelope) {
+                timeline.record(entry).await;
+            }
+        }
+        timeline.record_error(at(74)).await;
+
+        let all = timeline.query(&TimelineQuery::default());
+        assert_eq!(all.entries.len(), 4);
+        // The lone config reload is not an incident
+        assert_eq!(all.incidents.len(), 1);
+        let incident = &all.incidents[0];
+        assert_eq!((incident.first_entry, incident.last_entry), (1, 3));
+        assert_eq!(
+            incident.categories,
+            vec![TimelineCategory::Alert, TimelineCategory::Scaling, TimelineCategory::Recovery]
+        );
+        assert_eq!(incident.sources, vec!["admission", "autoscaler", "model_engine"]);
+        assert!(all.to_text().contains("== Incident 1 from 2026-03-02T03:00:00+00:00"));
+
+        let recoveries = timeline.query(&TimelineQuery {
+            since: Some(at(30)),
+            categories: vec![TimelineCategory::parse("Recovery").unwrap()],
+            ..Default::default()
+        });
+        assert_eq!(recoveries.entries.len(), 1);
+        assert_eq!(recoveries.entries[0].summary, "Recovery succeeded: High memory usage: 600MB");
+    }
+
+    #[tokio::test]
+    async fn test_error_spikes_are_recorded_and_restored() {
+        let storage: Arc<dyn Vfs> = Arc::new(MemoryVfs::new());
+        let config = TimelineConfig {
+            error_spike_threshold: 3,
+            ..Default::default()
+        };
+        let timeline = IncidentTimeline::new(config.clone(), storage.clone());
+        let now = Utc::now();
+        for seconds in [0, 10, 20, 30, 70] {
+            timeline.record_error(now + chrono::Duration::seconds(seconds)).await;
+        }
+        // One spike for the first window; the next window has too few errors
+        let spikes = timeline.query(&TimelineQuery::default()).entries;
+        assert_eq!(spikes.len(), 1);
+        assert_eq!(spikes[0].summary, "3 request errors within 60s");
+
+        // Expired entries are dropped from the file on restore
+        let expired = TimelineEntry {
+            timestamp: now - chrono::Duration::days(30),
+            ..spikes[0].clone()
+        };
+        let mut line = serde_json::to_vec(&expired).unwrap();
+        line.push(b'\n');
+        storage.append(&config.path, &line).await.unwrap();
+
+        let restored = IncidentTimeline::new(config.clone(), storage.clone());
+        restored.restore().await;
+        let entries = restored.query(&TimelineQuery::default()).entries;
+        assert_eq!(entries, spikes);
+        let saved = storage.read(&config.path).await.unwrap();
+        assert_eq!(String::from_utf8(saved).unwrap().lines().count(), 1);
+        assert_eq!(restored.query(&TimelineQuery::default()).incidents.len(), 1);
+    }
+}
@@ -0,0 +1,325 @@
+//! Webhook sink for completed responses
+//!
+//! Completed responses matching a webhook's method and tag filters are POSTed
+//! to it in the background, so existing edge data pipelines receive results
+//! without clients polling. Payloads are signed with HMAC-SHA256 when a
+//! secret is configured, failed deliveries are retried with exponential
+//! backoff, and a per-webhook circuit breaker stops retry storms against an
+//! endpoint that is down.
+
+use chrono::{DateTime, Utc};
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::config::WebhookConfig;
+use mcp_common::crypto::hmac;
+use mcp_common::{CircuitBreaker, CircuitBreakerConfig, CircuitState, Error, MCPRequest, MCPResponse, Result};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Header carrying `sha256=<hex>` over `{timestamp}.{body}`
+pub const SIGNATURE_HEADER: &str = "X-MCP-Signature";
+
+/// Header carrying the unix timestamp included in the signature
+pub const TIMESTAMP_HEADER: &str = "X-MCP-Timestamp";
+
+/// Body POSTed to webhooks
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub request_id: uuid::Uuid,
+    pub device_id: String,
+    pub method: String,
+    pub tags: Vec<String>,
+    pub response: MCPResponse,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Delivery counters for one webhook
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookStats {
+    pub name: String,
+    pub url: String,
+    pub delivered: u64,
+    pub failed: u64,
+    /// Deliveries dropped while the circuit breaker was open
+    pub skipped: u64,
+    pub circuit_open: bool,
+}
+
+struct WebhookTarget {
+    config: WebhookConfig,
+    breaker: CircuitBreaker,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    skipped: AtomicU64,
+}
+
+impl WebhookTarget {
+    fn matches(&self, method: &str, tags: &[String]) -> bool {
+        let method_ok = self.config.methods.is_empty() || self.config.methods.iter().any(|m| m == method);
+        let tags_ok = self.config.tags.is_empty() || self.config.tags.iter().any(|tag| tags.contains(tag));
+        method_ok && tags_ok
+    }
+}
+
+/// Delivers completed responses to the configured webhooks
+pub struct WebhookSink {
+    targets: Vec<Arc<WebhookTarget>>,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(configs: &[WebhookConfig]) -> Self {
+        let targets = configs
+            .iter()
+            .map(|config| {
+                // Trip after `failure_threshold` consecutive failed deliveries
+                let threshold = config.delivery.failure_threshold.max(1);
+                let breaker_config = CircuitBreakerConfig {
+                    failure_threshold: threshold,
+                    success_threshold: 1,
+                    timeout: Duration::from_secs(config.delivery.open_duration_secs),
+                    window_size: threshold,
+                    minimum_requests: threshold,
+                };
+                Arc::new(WebhookTarget {
+                    breaker: CircuitBreaker::new(format!("webhook-{}", config.name), breaker_config),
+                    config: config.clone(),
+                    delivered: AtomicU64::new(0),
+                    failed: AtomicU64::new(0),
+                    skipped: AtomicU64::new(0),
+                })
+            })
+            .collect();
+
+        Self {
+            targets,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Queue background delivery of a completed response to matching webhooks
+    pub fn dispatch(&self, request: &RequestSummary, response: &MCPResponse) {
+        let targets: Vec<Arc<WebhookTarget>> = self
+            .targets
+            .iter()
+            .filter(|target| target.matches(&request.method, &request.tags))
+            .cloned()
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+
+        let payload = WebhookPayload {
+            request_id: response.id,
+            device_id: request.device_id.clone(),
+            method: request.method.clone(),
+            tags: request.tags.clone(),
+            response: response.clone(),
+            completed_at: Utc::now(),
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => Arc::new(body),
+            Err(e) => {
+                warn!("Failed to serialize webhook payload for {}: {}", response.id, e);
+                return;
+            },
+        };
+
+        for target in targets {
+            let client = self.client.clone();
+            let body = body.clone();
+            tokio::spawn(async move { deliver(&client, &target, &body).await });
+        }
+    }
+
+    /// Circuit breaker state of every webhook, by breaker name
+    pub async fn breaker_states(&self) -> Vec<(String, CircuitState)> {
+        let mut states = Vec::with_capacity(self.targets.len());
+        for target in &self.targets {
+            states.push((format!("webhook-{}", target.config.name), target.breaker.get_state().await));
+        }
+        states
+    }
+
+    /// Delivery counters for every webhook
+    pub async fn stats(&self) -> Vec<WebhookStats> {
+        let mut stats = Vec::with_capacity(self.targets.len());
+        for target in &self.targets {
+            stats.push(WebhookStats {
+                name: target.config.name.clone(),
+                url: target.config.url.clone(),
+                delivered: target.delivered.load(Ordering::Relaxed),
+                failed: target.failed.load(Ordering::Relaxed),
+                skipped: target.skipped.load(Ordering::Relaxed),
+                circuit_open: target.breaker.get_state().await == CircuitState::Open,
+            });
+        }
+        stats
+    }
+}
+
+/// Request fields kept for delivery after the request itself is consumed
+#[derive(Debug, Clone)]
+pub struct RequestSummary {
+    pub device_id: String,
+    pub method: String,
+    pub tags: Vec<String>,
+}
+
+impl RequestSummary {
+    /// Tags come from the `tags` param, given as a string or a list of strings
+    pub fn new(request: &MCPRequest) -> Self {
+        let tags = match request.params.get("tags") {
+            Some(serde_json::Value::String(tag)) => vec![tag.clone()],
+            Some(serde_json::Value::Array(tags)) => tags
+                .iter()
+                .filter_map(|tag| tag.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Self {
+            device_id: request.device_id.clone(),
+            method: request.method.clone(),
+            tags,
+        }
+    }
+}
+
+async fn deliver(client: &reqwest::Client, target: &WebhookTarget, body: &[u8]) {
+    let name = &target.config.name;
+    if !target.breaker.should_allow_call().await {
+        target.skipped.fetch_add(1, Ordering::Relaxed);
+        debug!("Skipping webhook {}: circuit open", name);
+        return;
+    }
+
+    let delivery = &target.config.delivery;
+    let mut attempt = 0;
+    loop {
+        match send(client, &target.config, body).await {
+            Ok(()) => {
+                target.breaker.record_call_result(true).await;
+                target.delivered.fetch_add(1, Ordering::Relaxed);
+                return;
+            },
+            Err((e, retryable)) if retryable && attempt < delivery.max_retries => {
+                let delay = delivery.retry_backoff_ms.saturating_mul(1 << attempt.min(16));
+                debug!("Webhook {} delivery attempt {} failed ({}), retrying in {}ms", name, attempt + 1, e, delay);
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                attempt += 1;
+            },
+            Err((e, _)) => {
+                target.breaker.record_call_result(false).await;
+                target.failed.fetch_add(1, Ordering::Relaxed);
+                warn!("Webhook {} delivery failed after {} attempts: {}", name, attempt + 1, e);
+                return;
+            },
+        }
+    }
+}
+
+/// POST once; the flag says whether the failure is worth retrying
+async fn send(client: &reqwest::Client, config: &WebhookConfig, body: &[u8]) -> std::result::Result<(), (Error, bool)> {
+    let timestamp = Utc::now().timestamp();
+    let mut request = client
+        .post(&config.url)
+        .timeout(Duration::from_millis(config.delivery.timeout_ms))
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, timestamp.to_string());
+    if let Some(secret) = &config.secret {
+        request = request.header(SIGNATURE_HEADER, sign(secret, timestamp, body));
+    }
+
+    let response = request
+        .body(body.to_vec())
+        .send()
+        .await
+        .map_err(|e| (Error::Network(format!("Webhook request failed: {}", e)), true))?;
+    bandwidth::record(Subsystem::Webhooks, body.len() as u64, 0);
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    // Client errors other than timeouts and throttling will not improve on retry
+    let retryable = status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429;
+    Err((Error::Network(format!("Webhook returned {}", status)), retryable))
+}
+
+fn signed_message(timestamp: i64, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}.", timestamp).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+/// Signature header value for a payload
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let digest: String = hmac::sign(&key, &signed_message(timestamp, body))
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", digest)
+}
+
+/// Check a signature header, e.g. in a receiving service
+pub fn verify(secret: &str, timestamp: i64, body: &[u8], signature: &str) -> Result<()> {
+    let mismatch = || Error::Security("Webhook signature mismatch".to_string());
+    let hex = signature.strip_prefix("sha256=").ok_or_else(mismatch)?;
+    if hex.len() % 2 != 0 {
+        return Err(mismatch());
+    }
+    let tag = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(mismatch)?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, &signed_message(timestamp, body), &tag).map_err(|_| mismatch())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::config::WebhookDeliveryConfig;
+
+    fn webhook(methods: &[&str], tags: &[&str]) -> WebhookConfig {
+        WebhookConfig {
+            name: "pipeline".to_string(),
+            url: "http://127.0.0.1:9/hook".to_string(),
+            secret: Some("s3cret".to_string()),
+            methods: methods.iter().map(|m| m.to_string()).collect(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            delivery: WebhookDeliveryConfig::default(),
+        }
+    }
+
+    #[test]
+    fn test_filters_match_method_and_tags() {
+        let sink = WebhookSink::new(&[webhook(&["completion"], &["line-3"])]);
+        let target = &sink.targets[0];
+        assert!(target.matches("completion", &["line-3".to_string(), "qa".to_string()]));
+        assert!(!target.matches("completion", &["line-4".to_string()]));
+        assert!(!target.matches("embedding", &["line-3".to_string()]));
+
+        let any = WebhookSink::new(&[webhook(&[], &[])]);
+        assert!(any.targets[0].matches("embedding", &[]));
+    }
+
+    #[test]
+    fn test_signature_round_trip() {
+        let body = br#"{"method":"completion"}"#;
+        let signature = sign("s3cret", 1_700_000_000, body);
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature.len(), "sha256=".len() + 64);
+        assert!(verify("s3cret", 1_700_000_000, body, &signature).is_ok());
+        assert!(verify("s3cret", 1_700_000_001, body, &signature).is_err());
+        assert!(verify("other", 1_700_000_000, body, &signature).is_err());
+    }
+}
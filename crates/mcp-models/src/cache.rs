@@ -4,7 +4,7 @@ use mcp_common::{ModelId, Result, Error};
 use chrono::Timelike;
 use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, RwLock};
 use tracing::{debug, info, warn};
 use lru::LruCache;
 use std::num::NonZeroUsize;
@@ -21,6 +21,47 @@ pub struct ModelCache {
     metrics: Arc<RwLock<CacheMetrics>>,
     // Memory pressure monitoring
     memory_manager: Arc<MemoryManager>,
+    // Handles held by in-flight requests
+    handles: Arc<HandleCounts>,
+}
+
+/// Requests in flight per model, counted by the handles they hold
+#[derive(Debug, Default)]
+struct HandleCounts {
+    counts: std::sync::Mutex<HashMap<ModelId, usize>>,
+    released: Notify,
+}
+
+impl HandleCounts {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ModelId, usize>> {
+        self.counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Reference on a model, held while a request runs on it
+#[derive(Debug)]
+pub struct ModelHandle {
+    counts: Arc<HandleCounts>,
+    model_id: ModelId,
+}
+
+impl ModelHandle {
+    pub fn model_id(&self) -> &ModelId {
+        &self.model_id
+    }
+}
+
+impl Drop for ModelHandle {
+    fn drop(&mut self) {
+        let mut counts = self.counts.lock();
+        if let Some(count) = counts.get_mut(&self.model_id) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.model_id);
+                self.counts.released.notify_waiters();
+            }
+        }
+    }
 }
 
 /// Cached model with metadata
@@ -135,6 +176,44 @@ impl ModelCache {
             predictor,
             metrics: Arc::new(RwLock::new(CacheMetrics::default())),
             memory_manager,
+            handles: Arc::new(HandleCounts::default()),
+        }
+    }
+
+    /// Hold `model_id` while a request runs on it; a model being retired is
+    /// only unloaded once every handle on it is dropped
+    pub fn acquire(&self, model_id: &ModelId) -> ModelHandle {
+        *self.handles.lock().entry(model_id.clone()).or_default() += 1;
+        ModelHandle {
+            counts: Arc::clone(&self.handles),
+            model_id: model_id.clone(),
+        }
+    }
+
+    /// Handles held on `model_id`
+    pub fn handles(&self, model_id: &ModelId) -> usize {
+        self.handles.lock().get(model_id).copied().unwrap_or(0)
+    }
+
+    /// Handles held across all models
+    pub fn handles_held(&self) -> usize {
+        self.handles.lock().values().sum()
+    }
+
+    /// Wait until no handle on `model_id` is held; false when `timeout`
+    /// passed first
+    pub async fn released(&self, model_id: &ModelId, timeout: std::time::Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let released = self.handles.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            if self.handles(model_id) == 0 {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                return self.handles(model_id) == 0;
+            }
         }
     }
 
@@ -525,3 +604,32 @@ impl MemoryManager {
         current as f32 / self.max_memory_mb as f32
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_released_waits_for_handles_on_the_model() {
+        let cache = Arc::new(ModelCache::new(64, 4));
+        let old = "llama@1".to_string();
+        let first = cache.acquire(&old);
+        let second = cache.acquire(&old);
+        let _other = cache.acquire(&"llama@2".to_string());
+        assert_eq!((cache.handles(&old), cache.handles_held()), (2, 3));
+        assert!(!cache.released(&old, Duration::from_millis(10)).await);
+
+        let waiter = tokio::spawn({
+            let cache = Arc::clone(&cache);
+            let old = old.clone();
+            async move { cache.released(&old, Duration::from_secs(5)).await }
+        });
+        drop(first);
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+        drop(second);
+        assert!(waiter.await.unwrap());
+        assert_eq!(cache.handles_held(), 1);
+    }
+}
@@ -0,0 +1,283 @@
+//! Embedding batches and micro-batching
+//!
+//! `mcp.embedding.batch` embeds a list of texts in one model invocation.
+//! Single `embedding` requests are coalesced the same way: the first request
+//! for a model opens a batch, requests arriving within `window_ms` join it,
+//! and the batch runs once the window closes or it reaches `max_batch_size`.
+//! Every member of a batch can run it, so a request that is cancelled while
+//! waiting does not strand the others.
+
+use mcp_common::config::EmbeddingBatchConfig;
+use mcp_common::{Error, ModelId, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::debug;
+
+/// Method embedding a list of `texts` in one invocation
+pub const EMBEDDING_BATCH_METHOD: &str = "mcp.embedding.batch";
+
+/// Texts of an `mcp.embedding.batch` request
+pub fn batch_texts(params: &serde_json::Value) -> Result<Vec<String>> {
+    let texts = params
+        .get("texts")
+        .and_then(|texts| texts.as_array())
+        .filter(|texts| !texts.is_empty())
+        .ok_or_else(|| {
+            Error::InvalidRequest(format!(
+                "{} requires a non-empty texts array",
+                EMBEDDING_BATCH_METHOD
+            ))
+        })?;
+    texts
+        .iter()
+        .map(|text| {
+            text.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| Error::InvalidRequest("texts must be strings".to_string()))
+        })
+        .collect()
+}
+
+struct Member {
+    text: String,
+    reply: oneshot::Sender<Result<serde_json::Value>>,
+}
+
+struct OpenBatch {
+    id: u64,
+    members: Vec<Member>,
+}
+
+/// Coalesces single embedding requests per model
+pub struct EmbeddingBatcher {
+    config: EmbeddingBatchConfig,
+    open: Mutex<HashMap<ModelId, OpenBatch>>,
+    next_id: AtomicU64,
+    batches: AtomicU64,
+    batched_requests: AtomicU64,
+}
+
+impl EmbeddingBatcher {
+    pub fn new(config: EmbeddingBatchConfig) -> Self {
+        Self {
+            config,
+            open: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            batches: AtomicU64::new(0),
+            batched_requests: AtomicU64::new(0),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Embed `text` as part of a batch; `run` embeds a batch's texts in order
+    pub async fn embed<F, Fut>(
+        &self,
+        model_id: &ModelId,
+        text: String,
+        run: F,
+    ) -> Result<serde_json::Value>
+    where
+        F: FnOnce(Vec<String>) -> Fut,
+        Fut: Future<Output = Result<Vec<serde_json::Value>>>,
+    {
+        let (reply, mut result) = oneshot::channel();
+        let (id, full) = {
+            let mut open = self.lock();
+            let batch = open.entry(model_id.clone()).or_insert_with(|| OpenBatch {
+                id: self.next_id.fetch_add(1, Ordering::Relaxed),
+                members: Vec::new(),
+            });
+            batch.members.push(Member {
+                text,
+                reply,
+            });
+            let id = batch.id;
+            let full = batch.members.len() >= self.config.max_batch_size;
+            (id, full.then(|| open.remove(model_id)).flatten())
+        };
+
+        let batch = match full {
+            Some(batch) => Some(batch),
+            None => {
+                let window = Duration::from_millis(self.config.window_ms);
+                tokio::select! {
+                    outcome = &mut result => return received(outcome),
+                    _ = tokio::time::sleep(window) => {},
+                }
+                // Whichever member wakes first runs the batch
+                let mut open = self.lock();
+                match open.get(model_id) {
+                    Some(batch) if batch.id == id => open.remove(model_id),
+                    _ => None,
+                }
+            },
+        };
+        if let Some(batch) = batch {
+            self.run(model_id, batch.members, run).await;
+        }
+        received(result.await)
+    }
+
+    async fn run<F, Fut>(&self, model_id: &ModelId, members: Vec<Member>, run: F)
+    where
+        F: FnOnce(Vec<String>) -> Fut,
+        Fut: Future<Output = Result<Vec<serde_json::Value>>>,
+    {
+        let size = members.len();
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.batched_requests
+            .fetch_add(size as u64, Ordering::Relaxed);
+        debug!(
+            "Embedding a batch of {} request(s) with model {}",
+            size, model_id
+        );
+
+        let (texts, replies): (Vec<String>, Vec<_>) =
+            members.into_iter().map(|m| (m.text, m.reply)).unzip();
+        match run(texts).await {
+            Ok(results) if results.len() == size => {
+                for (reply, result) in replies.into_iter().zip(results) {
+                    let _ = reply.send(Ok(result));
+                }
+            },
+            Ok(results) => {
+                for reply in replies {
+                    let _ = reply.send(Err(Error::Model(format!(
+                        "Embedding batch of {} returned {} results",
+                        size,
+                        results.len()
+                    ))));
+                }
+            },
+            Err(e) => {
+                // Errors are not Clone; each member gets the batch's message
+                let message = match e {
+                    Error::Model(message) => message,
+                    e => e.to_string(),
+                };
+                for reply in replies {
+                    let _ = reply.send(Err(Error::Model(message.clone())));
+                }
+            },
+        }
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        if !self.config.enabled {
+            return;
+        }
+        let batches = self.batches.load(Ordering::Relaxed);
+        let requests = self.batched_requests.load(Ordering::Relaxed);
+        metrics.insert("embedding_batches".to_string(), batches as f32);
+        metrics.insert("embedding_batched_requests".to_string(), requests as f32);
+        if batches > 0 {
+            metrics.insert(
+                "embedding_batch_mean_size".to_string(),
+                requests as f32 / batches as f32,
+            );
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ModelId, OpenBatch>> {
+        self.open
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn received(
+    outcome: std::result::Result<Result<serde_json::Value>, oneshot::error::RecvError>,
+) -> Result<serde_json::Value> {
+    outcome.unwrap_or_else(|_| Err(Error::Model("Embedding batch was cancelled".to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn batcher(window_ms: u64, max_batch_size: usize) -> Arc<EmbeddingBatcher> {
+        Arc::new(EmbeddingBatcher::new(EmbeddingBatchConfig {
+            enabled: true,
+            window_ms,
+            max_batch_size,
+        }))
+    }
+
+    async fn echo(texts: Vec<String>) -> Result<Vec<serde_json::Value>> {
+        let size = texts.len();
+        Ok(texts
+            .into_iter()
+            .map(|text| serde_json::json!({"text": text, "batch_size": size}))
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn test_requests_within_the_window_share_a_batch() {
+        let batcher = batcher(50, 32);
+        let model = "minilm".to_string();
+        let requests = (0..5).map(|i| {
+            let batcher = batcher.clone();
+            let model = model.clone();
+            tokio::spawn(async move { batcher.embed(&model, format!("reading {}", i), echo).await })
+        });
+        let requests: Vec<_> = requests.collect();
+        for (i, request) in requests.into_iter().enumerate() {
+            let result = request.await.unwrap().unwrap();
+            assert_eq!(result["text"], format!("reading {}", i));
+            assert_eq!(result["batch_size"], 5);
+        }
+
+        // A batch that fills up runs without waiting for the window
+        let batcher = self::batcher(60_000, 2);
+        let (a, b) = tokio::join!(
+            batcher.embed(&model, "a".to_string(), echo),
+            batcher.embed(&model, "b".to_string(), echo)
+        );
+        assert_eq!(
+            (a.unwrap()["batch_size"].clone(), b.unwrap()["text"].clone()),
+            (2.into(), "b".into())
+        );
+
+        let mut metrics = HashMap::new();
+        batcher.write_metrics(&mut metrics);
+        assert_eq!(metrics["embedding_batch_mean_size"], 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_member_does_not_strand_the_batch() {
+        let batcher = batcher(20, 32);
+        let model = "minilm".to_string();
+        // The member that opened the batch gives up before the window closes
+        let first = tokio::spawn({
+            let batcher = batcher.clone();
+            let model = model.clone();
+            async move { batcher.embed(&model, "first".to_string(), echo).await }
+        });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        first.abort();
+        let second = batcher
+            .embed(&model, "second".to_string(), echo)
+            .await
+            .unwrap();
+        assert_eq!(second["text"], "second");
+
+        let failed = batcher
+            .embed(&model, "third".to_string(), |_| async {
+                Err(Error::Model("out of memory".to_string()))
+            })
+            .await;
+        assert!(matches!(failed, Err(Error::Model(message)) if message == "out of memory"));
+        assert!(matches!(
+            batch_texts(&serde_json::json!({"texts": ["a", 1]})),
+            Err(Error::InvalidRequest(_))
+        ));
+    }
+}
@@ -1,13 +1,33 @@
 //! Advanced multi-model ensemble engine implementation
 
 use crate::ModelEngine;
+use crate::integrity::{sha256_file, ModelIntegrityMonitor};
+use crate::plugins::PluginSupervisor;
+use crate::provenance::{ModelListing, ModelProvenance, ModelProvenanceRegistry};
+use crate::response_cache::ResponseCache;
+use crate::result_store::ResultStore;
+use crate::signing::ModelSignatures;
+use crate::streaming::{self, StreamChunk, TokenStream};
+use crate::verification::{agreement, response_text, RuleVerifier, VerificationOutcome};
 use crate::loaders::{create_model_loader, LoadedModel, ModelLoader};
+use crate::ensemble::{merge, EnsembleMetrics, MemberOutcome, ENSEMBLE_PARAM};
+use crate::embedding_batch::{batch_texts, EmbeddingBatcher, EMBEDDING_BATCH_METHOD};
+use crate::memory_estimate::{InferenceMemoryGate, MemoryEstimate};
+use crate::prefix_cache::SessionPrefixCache;
 use async_trait::async_trait;
+use mcp_common::config::{EnsembleMergeStrategy, ModelEnsembleConfig, VerificationFailureAction};
+use mcp_common::events::{self, GatewayEvent};
 use mcp_common::metrics::{ComponentHealth, HealthLevel};
-use mcp_common::{Config, Error, MCPRequest, MCPResponse, ModelId, ModelFormat, Result};
+use mcp_common::stage_timings::{self, Stage};
+use mcp_common::usage;
+use mcp_common::{
+    create_vfs, Config, ConcurrencyLimiter, Error, MCPRequest, MCPResponse, ModelId, ModelFormat, Result, Span, SpanKind,
+    TimeoutDetails, TimeoutStage, Vfs,
+};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
@@ -19,6 +39,19 @@ pub struct StandardModelEngine {
     loaders: Arc<RwLock<HashMap<ModelFormat, Box<dyn ModelLoader>>>>,
     ensembles: Arc<RwLock<HashMap<String, ModelEnsemble>>>,
     performance_tracker: Arc<RwLock<ModelPerformanceTracker>>,
+    inference_limiter: Arc<ConcurrencyLimiter>,
+    integrity: Arc<ModelIntegrityMonitor>,
+    plugins: Arc<PluginSupervisor>,
+    provenance: Arc<ModelProvenanceRegistry>,
+    signatures: ModelSignatures,
+    result_store: Arc<ResultStore>,
+    vfs: Arc<dyn Vfs>,
+    rule_verifier: RuleVerifier,
+    memory_gate: Arc<InferenceMemoryGate>,
+    prefix_cache: Arc<SessionPrefixCache>,
+    embedding_batcher: EmbeddingBatcher,
+    ensemble_metrics: EnsembleMetrics,
+    response_cache: ResponseCache,
 }
 
 /// Multi-model ensemble for improved accuracy and reliability
@@ -63,30 +96,189 @@ impl StandardModelEngine {
             config.models.max_models_in_memory,
         ));
 
+        let vfs = create_vfs(&config.storage);
+        let prefix_cache = Arc::new(SessionPrefixCache::new(config.models.prefix_cache.clone()));
+
         // Initialize model loaders for supported formats
         let mut loaders = HashMap::new();
         
         // Add GGML loader
-        let ggml_loader = create_model_loader(&ModelFormat::GGML)?;
+        let ggml_loader = create_model_loader(&ModelFormat::GGML, vfs.clone(), prefix_cache.clone())?;
         loaders.insert(ModelFormat::GGML, ggml_loader);
+
+        let gguf_loader = create_model_loader(&ModelFormat::GGUF, vfs.clone(), prefix_cache.clone())?;
+        loaders.insert(ModelFormat::GGUF, gguf_loader);
         
         // Add other format loaders (currently fallback to GGML)
-        let onnx_loader = create_model_loader(&ModelFormat::ONNX)?;
+        let onnx_loader = create_model_loader(&ModelFormat::ONNX, vfs.clone(), prefix_cache.clone())?;
         loaders.insert(ModelFormat::ONNX, onnx_loader);
         
-        let tflite_loader = create_model_loader(&ModelFormat::TensorFlowLite)?;
+        let tflite_loader =
+            create_model_loader(&ModelFormat::TensorFlowLite, vfs.clone(), prefix_cache.clone())?;
         loaders.insert(ModelFormat::TensorFlowLite, tflite_loader);
 
+        let integrity = Arc::new(ModelIntegrityMonitor::new(
+            config.models.integrity.clone(),
+            vfs.clone(),
+        ));
+        integrity.start();
+
+        let plugins = Arc::new(PluginSupervisor::new(&config.models));
+        plugins.start();
+
+        let provenance = Arc::new(ModelProvenanceRegistry::new(
+            config.models.provenance.clone(),
+            &config.models.models_directory,
+            vfs.clone(),
+        ));
+
+        let signatures = ModelSignatures::new(&config.models, vfs.clone())?;
+
+        let result_store = Arc::new(ResultStore::new(
+            config.models.result_store.clone(),
+            &config.models.models_directory,
+            vfs.clone(),
+        ));
+
         Ok(Self {
-            config,
             models: Arc::new(RwLock::new(HashMap::new())),
             cache,
             loaders: Arc::new(RwLock::new(loaders)),
             ensembles: Arc::new(RwLock::new(HashMap::new())),
             performance_tracker: Arc::new(RwLock::new(ModelPerformanceTracker::default())),
+            inference_limiter: Arc::new(ConcurrencyLimiter::new(
+                "local_inference",
+                &config.concurrency.local_inference,
+            )),
+            integrity,
+            plugins,
+            provenance,
+            signatures,
+            result_store,
+            vfs,
+            rule_verifier: RuleVerifier::new(&config.models.verification),
+            memory_gate: Arc::new(InferenceMemoryGate::new(config.models.memory_admission.clone())),
+            prefix_cache,
+            embedding_batcher: EmbeddingBatcher::new(config.models.embedding_batching.clone()),
+            ensemble_metrics: EnsembleMetrics::default(),
+            response_cache: ResponseCache::new(config.models.response_cache.clone()),
+            config,
         })
     }
 
+    /// Integrity monitor for the on-disk model files
+    pub fn integrity(&self) -> Arc<ModelIntegrityMonitor> {
+        Arc::clone(&self.integrity)
+    }
+
+    /// License policy and provenance of loaded models
+    pub fn provenance(&self) -> Arc<ModelProvenanceRegistry> {
+        Arc::clone(&self.provenance)
+    }
+
+    /// Supervisor of the out-of-process model runners
+    pub fn plugins(&self) -> Arc<PluginSupervisor> {
+        Arc::clone(&self.plugins)
+    }
+
+    /// Verify a result, regenerating it if allowed, and attach the outcome
+    async fn verify_result(
+        &self,
+        request: &MCPRequest,
+        model_id: &ModelId,
+        mut result: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let config = &self.config.models.verification;
+        if !config.enabled {
+            return Ok(result);
+        }
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let mut outcome = self.verify_once(request, model_id, &result).await;
+            outcome.attempts = attempts;
+
+            if outcome.passed || attempts > config.max_regenerations {
+                if !outcome.passed {
+                    warn!(
+                        "Response from {} for request {} failed verification (score {:.2}): {}",
+                        model_id,
+                        request.id,
+                        outcome.score,
+                        outcome.reasons.join(", ")
+                    );
+                    if config.on_failure != VerificationFailureAction::Annotate {
+                        return Err(Error::VerificationFailed(format!(
+                            "score {:.2} below {:.2} after {} attempt(s): {}",
+                            outcome.score,
+                            config.min_score,
+                            attempts,
+                            outcome.reasons.join(", ")
+                        )));
+                    }
+                }
+                if let Some(fields) = result.as_object_mut() {
+                    fields.insert("verification".to_string(), serde_json::to_value(&outcome)?);
+                }
+                return Ok(result);
+            }
+
+            debug!("Regenerating response for request {} (attempt {})", request.id, attempts + 1);
+            result = self.execute_inference(request, model_id).await?;
+        }
+    }
+
+    /// Score a result with the rule set and, if configured, the verifier model
+    async fn verify_once(
+        &self,
+        request: &MCPRequest,
+        model_id: &ModelId,
+        result: &serde_json::Value,
+    ) -> VerificationOutcome {
+        let config = &self.config.models.verification;
+        let Some(text) = response_text(result) else {
+            return VerificationOutcome {
+                score: 1.0,
+                passed: true,
+                reasons: Vec::new(),
+                verifier_model: None,
+                attempts: 0,
+            };
+        };
+
+        let (mut score, mut reasons) = self.rule_verifier.score(text);
+        let mut verifier_model = None;
+
+        if let Some(verifier) = config.verifier_model.as_ref().filter(|verifier| *verifier != model_id) {
+            let secondary = async {
+                self.load_model(verifier).await?;
+                self.execute_inference(request, verifier).await
+            };
+            match secondary.await {
+                Ok(secondary) => {
+                    if let Some(other) = response_text(&secondary) {
+                        let agreement = agreement(text, other);
+                        if agreement < config.min_score {
+                            reasons.push(format!("low agreement with {} ({:.2})", verifier, agreement));
+                        }
+                        score = score.min(agreement);
+                        verifier_model = Some(verifier.clone());
+                    }
+                },
+                Err(e) => warn!("Verifier model {} unavailable: {}", verifier, e),
+            }
+        }
+
+        VerificationOutcome {
+            score,
+            passed: score >= config.min_score,
+            reasons,
+            verifier_model,
+            attempts: 0,
+        }
+    }
+
     /// Create a new model ensemble for improved performance
     pub async fn create_ensemble(
         &self,
@@ -353,9 +545,11 @@ impl StandardModelEngine {
                 serde_json::Number::from(execution_time)
             ));
             obj.insert("model_used".to_string(), serde_json::Value::String(model_id.clone()));
-            
-            // Add confidence scoring based on response characteristics
-            let confidence = self.calculate_response_confidence(&enhanced_result, execution_time);
+        }
+
+        // Add confidence scoring based on response characteristics
+        let confidence = self.calculate_response_confidence(&enhanced_result, execution_time);
+        if let Some(obj) = enhanced_result.as_object_mut() {
             obj.insert("confidence".to_string(), serde_json::Value::Number(
                 serde_json::Number::from_f64(confidence as f64).unwrap_or(serde_json::Number::from(0i32))
             ));
@@ -493,7 +687,12 @@ impl StandardModelEngine {
         
         // Find a suitable loaded model that supports the requested method
         for (id, model) in models.iter() {
-            if model.metadata.supported_methods.contains(&request.method) {
+            // Batches run on the models serving single embeddings
+            let method = match request.method.as_str() {
+                EMBEDDING_BATCH_METHOD => "embedding",
+                method => method,
+            };
+            if model.metadata.supported_methods.iter().any(|m| m == method) {
                 debug!(
                     "Using alternative model {} for request {} (method: {})", 
                     id, request.id, request.method
@@ -507,11 +706,14 @@ impl StandardModelEngine {
         Ok(model_id.clone())
     }
     
-    /// Get model path from configuration
-    fn get_model_path(&self, model_id: &ModelId) -> PathBuf {
-        let mut path = PathBuf::from(&self.config.models.models_directory);
-        path.push(format!("{}.ggml", model_id)); // Default to GGML format
-        path
+    /// Get model path from configuration, preferring a GGUF file when present
+    async fn get_model_path(&self, model_id: &ModelId) -> PathBuf {
+        let directory = PathBuf::from(&self.config.models.models_directory);
+        let gguf = directory.join(format!("{}.gguf", model_id));
+        if self.vfs.exists(&gguf).await {
+            return gguf;
+        }
+        directory.join(format!("{}.ggml", model_id)) // Default to GGML format
     }
     
     /// Detect model format from file path
@@ -519,6 +721,7 @@ impl StandardModelEngine {
         if let Some(extension) = path.extension().and_then(|s| s.to_str()) {
             match extension.to_lowercase().as_str() {
                 "ggml" | "bin" => ModelFormat::GGML,
+                "gguf" => ModelFormat::GGUF,
                 "onnx" => ModelFormat::ONNX,
                 "tflite" => ModelFormat::TensorFlowLite,
                 _ => ModelFormat::GGML, // Default
@@ -536,6 +739,13 @@ impl StandardModelEngine {
     ) -> Result<serde_json::Value> {
         debug!("Executing inference with model: {}", model_id);
 
+        if let Some(runner) = self.plugins.runner_for(model_id) {
+            let params = serde_json::to_value(&request.params)
+                .map_err(|e| Error::Model(format!("Failed to serialize params: {}", e)))?;
+            let budget = self.config.inference_budget(&request.method);
+            return runner.infer(model_id, &request.method, params, budget).await;
+        }
+
         // Get the loaded model
         let model = {
             let models = self.models.read().await;
@@ -552,28 +762,246 @@ impl StandardModelEngine {
         // Execute inference using the real loader
         let params_value = serde_json::to_value(&request.params)
             .map_err(|e| Error::Model(format!("Failed to serialize params: {}", e)))?;
-        let result = loader.execute_inference(&model, &request.method, &params_value).await?;
+        let result = match request.method.as_str() {
+            EMBEDDING_BATCH_METHOD => {
+                let texts = batch_texts(&params_value)?;
+                let start = std::time::Instant::now();
+                let results = loader.execute_embedding_batch(&model, &texts).await?;
+                let embeddings: Vec<&serde_json::Value> =
+                    results.iter().filter_map(|result| result.get("embedding")).collect();
+                serde_json::json!({
+                    "embeddings": embeddings,
+                    "dimensions": results.first().and_then(|result| result.get("dimensions")),
+                    "count": embeddings.len(),
+                    "model": model.metadata.name,
+                    "inference_time_ms": start.elapsed().as_millis() as f32,
+                })
+            },
+            "embedding" if self.embedding_batcher.enabled() => {
+                let text = params_value.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                let model = &model;
+                self.embedding_batcher
+                    .embed(model_id, text.to_string(), |texts| async move {
+                        loader.execute_embedding_batch(model, &texts).await
+                    })
+                    .await?
+            },
+            _ => loader.execute_inference(&model, &request.method, &params_value).await?,
+        };
 
-        // Update model usage statistics
-        {
-            let mut models = self.models.write().await;
-            if let Some(loaded_model) = models.get_mut(model_id) {
-                loaded_model.last_used = chrono::Utc::now();
-                loaded_model.execution_count += 1;
-                
-                // Update average inference time if available
-                if let Some(inference_time) = result.get("inference_time_ms").and_then(|v| v.as_f64()) {
-                    let current_avg = loaded_model.average_inference_time_ms;
-                    let count = loaded_model.execution_count as f32;
-                    loaded_model.average_inference_time_ms = 
-                        (current_avg * (count - 1.0) + inference_time as f32) / count;
+        record_execution(&self.models, model_id, &result).await;
+        Ok(result)
+    }
+
+    /// Digest of a model's file when the request's result may be stored,
+    /// hashing the file the first time if integrity monitoring has not
+    async fn result_store_digest(&self, request: &MCPRequest, model_id: &ModelId) -> Option<String> {
+        if !self.result_store.eligible(request) || self.plugins.runner_for(model_id).is_some() {
+            return None;
+        }
+        if let Some(digest) = self.result_store.model_digest(model_id).await {
+            return Some(digest);
+        }
+        let digest = match self.integrity.expected_sha256(model_id).await {
+            Some(digest) => digest,
+            None => {
+                let path = self.get_model_path(model_id).await;
+                match sha256_file(self.vfs.as_ref(), &path).await {
+                    Ok(digest) => digest,
+                    Err(e) => {
+                        warn!("Could not hash model {} for the result store: {}", model_id, e);
+                        return None;
+                    },
                 }
+            },
+        };
+        self.result_store.remember_digest(model_id, digest.clone()).await;
+        Some(digest)
+    }
+
+    /// Memory a local inference is expected to need; plugin runners manage
+    /// their own memory
+    async fn memory_estimate(&self, request: &MCPRequest, model_id: &ModelId) -> Option<MemoryEstimate> {
+        if !self.memory_gate.enabled() || self.plugins.runner_for(model_id).is_some() {
+            return None;
+        }
+        let models = self.models.read().await;
+        let model = models.get(model_id)?;
+        Some(MemoryEstimate::for_request(
+            &model.metadata,
+            &request.method,
+            &request.params,
+            self.config.models.memory_admission.default_max_tokens,
+        ))
+    }
+
+    /// Model to serve a request with, refusing model files that failed
+    /// verification
+    async fn usable_model(&self, request: &MCPRequest, model_id: &ModelId) -> Result<ModelId> {
+        // Plugin-served models have no local file and are never substituted
+        if self.plugins.runner_for(model_id).is_some() {
+            return Ok(model_id.clone());
+        }
+
+        if let Err(e) = self.integrity.ensure_usable(model_id).await {
+            if self.models.write().await.remove(model_id).is_some() {
+                events::publish(GatewayEvent::ModelUnloaded {
+                    model_id: model_id.clone(),
+                });
             }
+            return Err(e);
         }
 
-        Ok(result)
+        // Select the best model (might be different from requested)
+        let selected = self.select_model(request, model_id).await?;
+
+        // Signed models only serve the methods they were released for
+        self.signatures.check_method(&selected, &request.method).await?;
+        Ok(selected)
+    }
+
+    /// Ensemble named by a request's `ensemble` param
+    fn requested_ensemble(&self, request: &MCPRequest) -> Result<Option<&ModelEnsembleConfig>> {
+        let Some(name) = request.params.get(ENSEMBLE_PARAM) else {
+            return Ok(None);
+        };
+        let name = name
+            .as_str()
+            .ok_or_else(|| Error::InvalidRequest(format!("{} must be a string", ENSEMBLE_PARAM)))?;
+        self.config
+            .models
+            .ensembles
+            .iter()
+            .find(|ensemble| ensemble.name == name)
+            .map(Some)
+            .ok_or_else(|| Error::InvalidRequest(format!("Unknown ensemble '{}'", name)))
+    }
+
+    /// Run a request on every model of an ensemble at once and merge their answers
+    async fn process_with_ensemble(
+        &self,
+        request: &MCPRequest,
+        ensemble: &ModelEnsembleConfig,
+    ) -> Result<MCPResponse> {
+        let mut span = Span::for_request(request, "models.ensemble", SpanKind::Internal);
+        if let Some(span) = span.as_mut() {
+            span.set_attribute("models.ensemble", &ensemble.name);
+        }
+
+        // The budget covers the slowest model
+        let budget = self.config.inference_budget(&request.method);
+        let members = ensemble.models.iter().map(|model_id| async move {
+            let start = std::time::Instant::now();
+            let result = self.run_ensemble_member(request, model_id).await;
+            if let Err(e) = &result {
+                warn!("Model {} of ensemble '{}' failed: {}", model_id, ensemble.name, e);
+            }
+            MemberOutcome {
+                model: model_id.clone(),
+                result,
+                latency_ms: start.elapsed().as_millis() as u64,
+            }
+        });
+        let answers = futures_util::future::join_all(members);
+        let outcomes = match tokio::time::timeout(budget, answers).await {
+            Ok(outcomes) => outcomes,
+            Err(_) => {
+                if let Some(span) = span.as_mut() {
+                    span.set_error("inference budget exceeded");
+                }
+                return Err(Error::DeadlineExceeded(
+                    TimeoutDetails::new(TimeoutStage::Inference, &request.method, budget)
+                        .with_target(&ensemble.name),
+                ));
+            },
+        };
+
+        let mut scores = Vec::new();
+        if ensemble.strategy == EnsembleMergeStrategy::RankByVerifier {
+            for outcome in &outcomes {
+                scores.push(match &outcome.result {
+                    Ok(result) => {
+                        Some(self.verify_once(request, &outcome.model, result).await.score)
+                    },
+                    Err(_) => None,
+                });
+            }
+        }
+
+        let outcome = merge(ensemble, outcomes, &scores);
+        if let Some(span) = span.as_mut() {
+            span.record_result(&outcome);
+        }
+        match outcome {
+            Ok((result, report)) => {
+                debug!(
+                    "Ensemble '{}' answered request {} with {:.0}% agreement",
+                    ensemble.name,
+                    request.id,
+                    report.agreement * 100.0
+                );
+                self.ensemble_metrics.record(&report);
+                Ok(MCPResponse {
+                    id: request.id,
+                    result: Some(result),
+                    error: None,
+                    timestamp: chrono::Utc::now(),
+                })
+            },
+            Err(e) => {
+                error!("Request {} failed: {}", request.id, e);
+                Ok(MCPResponse {
+                    id: request.id,
+                    result: None,
+                    error: Some(mcp_common::MCPError {
+                        code: -1,
+                        message: e.to_string(),
+                        data: None,
+                    }),
+                    timestamp: chrono::Utc::now(),
+                })
+            },
+        }
     }
 
+    /// Run a request on one model of an ensemble, admitted like any inference
+    async fn run_ensemble_member(
+        &self,
+        request: &MCPRequest,
+        model_id: &ModelId,
+    ) -> Result<serde_json::Value> {
+        self.load_model(model_id).await?;
+        let model_id = self.usable_model(request, model_id).await?;
+        let _handle = self.cache.acquire(&model_id);
+        let _permit = self.inference_limiter.acquire_for(request.priority()).await?;
+        let estimate = self.memory_estimate(request, &model_id).await;
+        let _reservation = match &estimate {
+            Some(estimate) => self.memory_gate.reserve(estimate).await?,
+            None => None,
+        };
+        self.execute_inference(request, &model_id).await
+    }
+}
+
+/// Update a model's usage statistics after an inference
+async fn record_execution(
+    models: &RwLock<HashMap<ModelId, LoadedModel>>,
+    model_id: &ModelId,
+    result: &serde_json::Value,
+) {
+    let mut models = models.write().await;
+    if let Some(loaded_model) = models.get_mut(model_id) {
+        loaded_model.last_used = chrono::Utc::now();
+        loaded_model.execution_count += 1;
+
+        // Update average inference time if available
+        if let Some(inference_time) = result.get("inference_time_ms").and_then(|v| v.as_f64()) {
+            let current_avg = loaded_model.average_inference_time_ms;
+            let count = loaded_model.execution_count as f32;
+            loaded_model.average_inference_time_ms =
+                (current_avg * (count - 1.0) + inference_time as f32) / count;
+        }
+    }
 }
 
 #[async_trait]
@@ -584,17 +1012,95 @@ impl ModelEngine for StandardModelEngine {
         model_id: &ModelId,
     ) -> Result<MCPResponse> {
         debug!("Processing request {} with model {}", request.id, model_id);
+        if let Some(ensemble) = self.requested_ensemble(request)? {
+            return self.process_with_ensemble(request, ensemble).await;
+        }
+        let mut span = Span::for_request(request, "models.inference", SpanKind::Internal);
+
+        if let Some(result) = self.response_cache.get(model_id, &request.method, &request.params) {
+            debug!("Response cache hit for request {}", request.id);
+            if let Some(span) = span.as_mut() {
+                span.set_attribute("models.response_cache_hit", true);
+            }
+            return Ok(MCPResponse {
+                id: request.id,
+                result: Some(result),
+                error: None,
+                timestamp: chrono::Utc::now(),
+            });
+        }
 
         // Ensure model is loaded
         self.load_model(model_id).await?;
 
-        // Select the best model (might be different from requested)
-        let selected_model = self.select_model(request, model_id).await?;
+        let selected_model = self.usable_model(request, model_id).await?;
+        let _handle = self.cache.acquire(&selected_model);
+        if let Some(span) = span.as_mut() {
+            span.set_attribute("models.model_id", &selected_model);
+        }
+
+        // Deterministic requests may have run against this model before
+        let model_digest = self.result_store_digest(request, &selected_model).await;
+        if let Some(digest) = &model_digest {
+            if let Some(result) = self.result_store.get(digest, request).await {
+                if let Some(span) = span.as_mut() {
+                    span.set_attribute("models.result_store_hit", true);
+                }
+                return Ok(MCPResponse {
+                    id: request.id,
+                    result: Some(result),
+                    error: None,
+                    timestamp: chrono::Utc::now(),
+                });
+            }
+        }
 
-        // Execute the inference
-        match self.execute_inference(request, &selected_model).await {
+        // Wait for an inference slot before spending the latency budget
+        let queued = std::time::Instant::now();
+        let _permit = self.inference_limiter.acquire_for(request.priority()).await?;
+        let estimate = self.memory_estimate(request, &selected_model).await;
+        let _reservation = match &estimate {
+            Some(estimate) => self.memory_gate.reserve(estimate).await?,
+            None => None,
+        };
+        stage_timings::record(Stage::Queueing, queued.elapsed());
+
+        // Execute the inference within the method's latency budget
+        let budget = self.config.inference_budget(&request.method);
+        let inference = async {
+            let execution = self.execute_inference(request, &selected_model);
+            let (result, grown) = usage::peak_memory_growth(execution).await;
+            if let Some(estimate) = &estimate {
+                self.memory_gate.record_actual(estimate, grown);
+            }
+            self.verify_result(request, &selected_model, result?).await
+        };
+        let inference = tokio::time::timeout(budget, inference);
+        let outcome = match stage_timings::timed(Stage::Inference, inference).await {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                warn!("Inference for request {} exceeded its {:?} budget", request.id, budget);
+                if let Some(span) = span.as_mut() {
+                    span.set_error("inference budget exceeded");
+                }
+                return Err(Error::DeadlineExceeded(
+                    TimeoutDetails::new(TimeoutStage::Inference, &request.method, budget)
+                        .with_target(&selected_model),
+                ));
+            },
+        };
+
+        if let Some(span) = span.as_mut() {
+            span.record_result(&outcome);
+        }
+        match outcome {
             Ok(result) => {
                 info!("Request {} processed successfully", request.id);
+                if let Some(digest) = &model_digest {
+                    self.result_store.put(digest, &selected_model, request, &result).await;
+                }
+                self.response_cache
+                    .insert(model_id, &request.method, &request.params, result.clone());
                 Ok(MCPResponse {
                     id: request.id,
                     result: Some(result),
@@ -602,6 +1108,10 @@ impl ModelEngine for StandardModelEngine {
                     timestamp: chrono::Utc::now(),
                 })
             },
+            Err(e @ Error::VerificationFailed(_)) => {
+                warn!("Request {} failed verification: {}", request.id, e);
+                Err(e)
+            },
             Err(e) => {
                 error!("Request {} failed: {}", request.id, e);
                 Ok(MCPResponse {
@@ -618,7 +1128,144 @@ impl ModelEngine for StandardModelEngine {
         }
     }
 
+    async fn process_request_streaming(
+        &self,
+        request: &MCPRequest,
+        model_id: &ModelId,
+    ) -> Result<TokenStream> {
+        let buffer_chunks = self.config.models.streaming.buffer_chunks;
+
+        // Plugin runners answer in one frame, and verification and ensembles
+        // need the whole text before any of it can be shown
+        if self.plugins.runner_for(model_id).is_some()
+            || self.config.models.verification.enabled
+            || request.params.contains_key(ENSEMBLE_PARAM)
+        {
+            let response = self.process_request(request, model_id).await?;
+            return Ok(streaming::buffered_stream(response, buffer_chunks));
+        }
+
+        self.load_model(model_id).await?;
+        let selected_model = self.usable_model(request, model_id).await?;
+        let handle = self.cache.acquire(&selected_model);
+        let permit = self.inference_limiter.acquire_for(request.priority()).await?;
+        let model = self
+            .models
+            .read()
+            .await
+            .get(&selected_model)
+            .cloned()
+            .ok_or_else(|| Error::Model(format!("Model {} not loaded", selected_model)))?;
+        let estimate = self.memory_estimate(request, &selected_model).await;
+        let reservation = match &estimate {
+            Some(estimate) => self.memory_gate.reserve(estimate).await?,
+            None => None,
+        };
+        let memory_gate = self.memory_gate.clone();
+        let params = serde_json::to_value(&request.params)
+            .map_err(|e| Error::Model(format!("Failed to serialize params: {}", e)))?;
+
+        let (sender, stream) = streaming::token_stream(buffer_chunks);
+        let budget = self.config.inference_budget(&request.method);
+        let models = self.models.clone();
+        let loaders = self.loaders.clone();
+        let request_id = request.id;
+        let method = request.method.clone();
+        // Generation outlives the gateway's measurement of this call
+        let deferred_usage = usage::deferred();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let _handle = handle;
+            let _reservation = reservation;
+            let generation = async {
+                let loaders = loaders.read().await;
+                let loader = loaders
+                    .get(&model.format)
+                    .ok_or_else(|| Error::Model(format!("No loader available for format {:?}", model.format)))?;
+                let execution = loader.execute_inference_streaming(&model, &method, &params, &sender);
+                let (result, grown) = usage::peak_memory_growth(execution).await;
+                if let Some(estimate) = &estimate {
+                    memory_gate.record_actual(estimate, grown);
+                }
+                result
+            };
+            // The budget covers the whole stream, so a stalled client cannot
+            // hold an inference slot indefinitely
+            let outcome = tokio::select! {
+                (outcome, used) = usage::measure(tokio::time::timeout(budget, generation)) => {
+                    if let Some(deferred_usage) = &deferred_usage {
+                        deferred_usage.report(&used);
+                    }
+                    match outcome {
+                        Ok(outcome) => outcome,
+                        Err(_) => {
+                            warn!("Streamed inference for request {} exceeded its {:?} budget", request_id, budget);
+                            Err(Error::DeadlineExceeded(
+                                TimeoutDetails::new(TimeoutStage::Inference, &method, budget).with_target(&model.id),
+                            ))
+                        },
+                    }
+                },
+                // A reader that went away cancels generation at once, even
+                // before the first token, and frees the inference slot
+                _ = sender.closed() => {
+                    debug!("Streamed inference for request {} cancelled by its reader", request_id);
+                    return;
+                },
+            };
+            let chunk = match outcome {
+                Ok(result) => {
+                    record_execution(&models, &model.id, &result).await;
+                    debug!("Request {} streamed successfully", request_id);
+                    Ok(StreamChunk::Done(MCPResponse {
+                        id: request_id,
+                        result: Some(result),
+                        error: None,
+                        timestamp: chrono::Utc::now(),
+                    }))
+                },
+                Err(e) => {
+                    if !sender.is_closed() {
+                        error!("Streamed request {} failed: {}", request_id, e);
+                    }
+                    Err(e)
+                },
+            };
+            let _ = sender.send(chunk).await;
+        });
+
+        Ok(stream)
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelListing>> {
+        let loaded: Vec<LoadedModel> = self.models.read().await.values().cloned().collect();
+        let mut listings = Vec::with_capacity(loaded.len());
+        for model in loaded {
+            listings.push(ModelListing {
+                provenance: self.provenance.get(&model.id).await,
+                model_id: model.id,
+                format: model.format,
+                name: model.metadata.name,
+                parameters: model.metadata.parameters,
+                quantization: model.metadata.quantization,
+                context_length: model.metadata.context_length,
+                memory_usage_mb: model.memory_usage_mb,
+            });
+        }
+        listings.sort_by(|a, b| a.model_id.cmp(&b.model_id));
+        Ok(listings)
+    }
+
+    async fn model_provenance(&self) -> Vec<ModelProvenance> {
+        self.provenance.report().await
+    }
+
     async fn load_model(&self, model_id: &ModelId) -> Result<()> {
+        // Out-of-process runners load their own models
+        if let Some(runner) = self.plugins.runner_for(model_id) {
+            return runner.ensure_started().await;
+        }
+
         let mut models = self.models.write().await;
 
         if models.contains_key(model_id) {
@@ -629,27 +1276,37 @@ impl ModelEngine for StandardModelEngine {
         info!("Loading model: {}", model_id);
 
         // Get model path and detect format
-        let model_path = self.get_model_path(model_id);
+        let model_path = self.get_model_path(model_id).await;
         let format = self.detect_model_format(&model_path);
         
         info!("Model path: {:?}, detected format: {:?}", model_path, format);
 
         // Check if model file exists, if not create a dummy file for demo purposes
-        if !model_path.exists() {
+        if !self.vfs.exists(&model_path).await {
             warn!("Model file {:?} not found, creating dummy model for demonstration", model_path);
             
             // Create parent directory if it doesn't exist
             if let Some(parent) = model_path.parent() {
-                tokio::fs::create_dir_all(parent).await
+                self.vfs.create_dir_all(parent).await
                     .map_err(|e| Error::Model(format!("Failed to create model directory: {}", e)))?;
             }
             
             // Create a dummy model file (1KB for demo)
             let dummy_data = vec![0u8; 1024];
-            tokio::fs::write(&model_path, dummy_data).await
+            self.vfs.write(&model_path, &dummy_data).await
                 .map_err(|e| Error::Model(format!("Failed to create dummy model file: {}", e)))?;
         }
 
+        // Record or check the file checksum before loading it
+        self.integrity.register(model_id, &model_path).await?;
+
+        // Refuse files without a valid signed manifest when signing is enforced
+        self.signatures.check(model_id, &model_path).await?;
+
+        // Refuse models the license policy does not permit
+        let sha256 = self.integrity.expected_sha256(model_id).await;
+        self.provenance.check(model_id, &model_path, sha256).await?;
+
         // Get appropriate loader
         let loaders = self.loaders.read().await;
         let loader = loaders.get(&format)
@@ -681,6 +1338,10 @@ impl ModelEngine for StandardModelEngine {
                 if freed_memory >= memory_needed {
                     break;
                 }
+                // Requests are still running on it
+                if self.cache.handles(&id) > 0 {
+                    continue;
+                }
                 if let Some(model) = models.get(&id) {
                     freed_memory += model.memory_usage_mb;
                     models_to_unload.push(id);
@@ -701,6 +1362,7 @@ impl ModelEngine for StandardModelEngine {
                 }
                 
                 models.remove(&id);
+                events::publish(GatewayEvent::ModelUnloaded { model_id: id });
             }
         }
 
@@ -711,14 +1373,28 @@ impl ModelEngine for StandardModelEngine {
 
         // Load the model using the appropriate loader
         let loaded_model = loader.load(model_id, &model_path).await?;
+        let quantization = &loaded_model.metadata.quantization;
+        if let Err(e) = self.signatures.check_quantization(model_id, quantization).await {
+            let _ = loader.unload(&loaded_model).await;
+            return Err(e);
+        }
 
         models.insert(model_id.clone(), loaded_model);
         info!("Model {} loaded successfully ({}MB)", model_id, estimated_memory);
+        events::publish(GatewayEvent::ModelLoaded {
+            model_id: model_id.clone(),
+            memory_mb: estimated_memory,
+        });
 
         Ok(())
     }
 
     async fn unload_model(&self, model_id: &ModelId) -> Result<()> {
+        if self.plugins.runner_for(model_id).is_some() {
+            debug!("Model {} is served by a plugin runner, leaving it loaded", model_id);
+            return Ok(());
+        }
+
         let mut models = self.models.write().await;
 
         if let Some(model) = models.get(model_id) {
@@ -732,13 +1408,69 @@ impl ModelEngine for StandardModelEngine {
             
             // Remove from our tracking
             models.remove(model_id);
+            self.result_store.forget_digest(model_id).await;
             info!("Model {} unloaded successfully", model_id);
+            events::publish(GatewayEvent::ModelUnloaded {
+                model_id: model_id.clone(),
+            });
             Ok(())
         } else {
             Err(Error::Model(format!("Model {} not loaded", model_id)))
         }
     }
 
+    async fn retire_model(&self, model_id: &ModelId, drain_timeout: Duration) -> Result<()> {
+        if !self.cache.released(model_id, drain_timeout).await {
+            warn!(
+                "Retiring model {} with {} requests still running after {:?}",
+                model_id,
+                self.cache.handles(model_id),
+                drain_timeout
+            );
+        }
+        if !self.models.read().await.contains_key(model_id) {
+            return Ok(());
+        }
+        self.unload_model(model_id).await
+    }
+
+    fn throttle_inference(&self, percent: u8) {
+        let configured = self.inference_limiter.configured_limit();
+        let limit = (configured * percent.min(100) as usize).div_ceil(100);
+        if limit != self.inference_limiter.gauge().limit {
+            info!("Running at most {} of {} local inferences at once", limit.max(1), configured);
+            self.inference_limiter.set_limit(limit);
+        }
+    }
+
+    async fn unload_idle_models(&self, idle: Duration) -> Vec<ModelId> {
+        let Some(cutoff) = chrono::Duration::from_std(idle)
+            .ok()
+            .and_then(|idle| chrono::Utc::now().checked_sub_signed(idle))
+        else {
+            return Vec::new();
+        };
+        let idle_models: Vec<ModelId> = self
+            .models
+            .read()
+            .await
+            .iter()
+            .filter(|(model_id, model)| model.last_used < cutoff && self.cache.handles(model_id) == 0)
+            .map(|(model_id, _)| model_id.clone())
+            .collect();
+        let mut unloaded = Vec::new();
+        for model_id in idle_models {
+            if self.plugins.runner_for(&model_id).is_some() {
+                continue;
+            }
+            match self.unload_model(&model_id).await {
+                Ok(()) => unloaded.push(model_id),
+                Err(e) => warn!("Failed to unload idle model {}: {}", model_id, e),
+            }
+        }
+        unloaded
+    }
+
     async fn health_check(&self) -> Result<ComponentHealth> {
         let models = self.models.read().await;
 
@@ -748,18 +1480,38 @@ impl ModelEngine for StandardModelEngine {
             (total_memory_usage as f32 / self.config.models.cache_size_mb as f32) * 100.0;
 
         health_metrics.insert("loaded_models".to_string(), models.len() as f32);
+        health_metrics.insert("model_handles_held".to_string(), self.cache.handles_held() as f32);
         health_metrics.insert("memory_usage_mb".to_string(), total_memory_usage as f32);
         health_metrics.insert("memory_usage_percent".to_string(), memory_usage_percent);
         health_metrics.insert(
             "max_models".to_string(),
             self.config.models.max_models_in_memory as f32,
         );
+        self.inference_limiter.gauge().write_metrics(&mut health_metrics);
+        self.integrity.write_metrics(&mut health_metrics).await;
+        self.result_store.write_metrics(&mut health_metrics).await;
+        self.plugins.write_metrics(&mut health_metrics);
+        self.memory_gate.write_metrics(&mut health_metrics);
+        self.prefix_cache.write_metrics(&mut health_metrics);
+        self.embedding_batcher.write_metrics(&mut health_metrics);
+        self.response_cache.write_metrics(&mut health_metrics);
+        if !self.config.models.ensembles.is_empty() {
+            self.ensemble_metrics.write_metrics(&mut health_metrics);
+        }
+        let corrupted_models = health_metrics
+            .get("integrity_unhealthy_models")
+            .copied()
+            .unwrap_or(0.0);
+        let unhealthy_runners = health_metrics
+            .get("plugin_runners_unhealthy")
+            .copied()
+            .unwrap_or(0.0);
 
         let status = if memory_usage_percent > 95.0
             || models.len() >= self.config.models.max_models_in_memory as usize
         {
             HealthLevel::Critical
-        } else if memory_usage_percent > 85.0 {
+        } else if memory_usage_percent > 85.0 || corrupted_models > 0.0 || unhealthy_runners > 0.0 {
             HealthLevel::Degraded
         } else {
             HealthLevel::Healthy
@@ -767,6 +1519,12 @@ impl ModelEngine for StandardModelEngine {
 
         let message = match status {
             HealthLevel::Healthy => "Model engine is operating normally".to_string(),
+            HealthLevel::Degraded if corrupted_models > 0.0 => {
+                format!("{} model file(s) failed integrity verification", corrupted_models)
+            },
+            HealthLevel::Degraded if unhealthy_runners > 0.0 => {
+                format!("{} model runner plugin(s) are unhealthy", unhealthy_runners)
+            },
             HealthLevel::Degraded => "Model engine memory usage is high".to_string(),
             HealthLevel::Critical => "Model engine is at capacity".to_string(),
             HealthLevel::Unknown => "Model engine status unknown".to_string(),
@@ -782,6 +1540,11 @@ impl ModelEngine for StandardModelEngine {
 
     async fn shutdown(&self) -> Result<()> {
         info!("Shutting down model engine");
+        self.integrity.stop();
+        self.plugins.shutdown().await;
+        if let Err(e) = self.response_cache.persist() {
+            warn!("{}", e);
+        }
 
         let mut models = self.models.write().await;
         models.clear();
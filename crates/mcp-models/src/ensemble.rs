@@ -0,0 +1,305 @@
+//! Configured model ensembles
+//!
+//! A request whose `ensemble` param names one of `models.ensembles` runs on
+//! every model of that ensemble at once. The answers are merged by the
+//! ensemble's strategy: a vote over the normalized answer text, the answer
+//! the verifier scores highest, or every answer as a section of its own. The
+//! merged result reports each model's answer, latency, score and whether it
+//! was used, along with how many of the models agreed with the answer given.
+
+use crate::verification::response_text;
+use mcp_common::config::{EnsembleMergeStrategy, ModelEnsembleConfig};
+use mcp_common::{Error, ModelId, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Request param naming the ensemble to run a request on
+pub const ENSEMBLE_PARAM: &str = "ensemble";
+
+/// One model's answer to an ensemble request
+pub struct MemberOutcome {
+    pub model: ModelId,
+    pub result: Result<serde_json::Value>,
+    pub latency_ms: u64,
+}
+
+/// What a model contributed to an ensemble response
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelContribution {
+    pub model: ModelId,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    /// Whether the answer is part of the response
+    pub selected: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// How an ensemble response came about
+#[derive(Debug, Clone, Serialize)]
+pub struct EnsembleReport {
+    pub name: String,
+    pub strategy: EnsembleMergeStrategy,
+    /// Share of the answering models that gave the selected answer; for
+    /// sections, the most common one
+    pub agreement: f32,
+    pub contributions: Vec<ModelContribution>,
+}
+
+/// Merge the answers of an ensemble's models; `scores` are the verifier's
+/// scores of each outcome, used to rank answers
+pub fn merge(
+    config: &ModelEnsembleConfig,
+    outcomes: Vec<MemberOutcome>,
+    scores: &[Option<f32>],
+) -> Result<(serde_json::Value, EnsembleReport)> {
+    let answered: Vec<usize> = (0..outcomes.len())
+        .filter(|&index| outcomes[index].result.is_ok())
+        .collect();
+    if answered.is_empty() {
+        return Err(Error::Model(format!(
+            "No model of ensemble '{}' answered",
+            config.name
+        )));
+    }
+
+    let texts: Vec<Option<&str>> = outcomes
+        .iter()
+        .map(|outcome| outcome.result.as_ref().ok().and_then(response_text))
+        .collect();
+    // Answers without text only ever agree with themselves
+    let keys: Vec<Option<String>> = texts.iter().map(|text| text.map(normalize)).collect();
+    let votes = |index: usize| {
+        answered
+            .iter()
+            .filter(|&&other| keys[index].is_some() && keys[other] == keys[index])
+            .count()
+            .max(1)
+    };
+    // Earlier models win ties, as they are listed first
+    let best_by = |key: &dyn Fn(usize) -> f32| {
+        answered.iter().copied().fold(answered[0], |best, index| {
+            if key(index) > key(best) {
+                index
+            } else {
+                best
+            }
+        })
+    };
+    let most_voted = best_by(&|index| votes(index) as f32);
+
+    let (result, selected, chosen) = match config.strategy {
+        EnsembleMergeStrategy::Vote => {
+            let result = outcomes[most_voted].result.as_ref().ok().cloned();
+            let selected: Vec<usize> = answered
+                .iter()
+                .copied()
+                .filter(|&index| {
+                    index == most_voted
+                        || (keys[index].is_some() && keys[index] == keys[most_voted])
+                })
+                .collect();
+            (result, selected, most_voted)
+        },
+        EnsembleMergeStrategy::RankByVerifier => {
+            let best = best_by(&|index| scores.get(index).copied().flatten().unwrap_or(0.0));
+            (
+                outcomes[best].result.as_ref().ok().cloned(),
+                vec![best],
+                best,
+            )
+        },
+        EnsembleMergeStrategy::ConcatSections => {
+            let sections: Vec<String> = answered
+                .iter()
+                .filter_map(|&index| {
+                    texts[index].map(|text| format!("### {}\n{}", outcomes[index].model, text))
+                })
+                .collect();
+            let mut result = outcomes[answered[0]].result.as_ref().ok().cloned();
+            if let Some(result) = result.as_mut() {
+                set_response_text(result, sections.join("\n\n"));
+            }
+            (result, answered.clone(), most_voted)
+        },
+    };
+
+    let report = EnsembleReport {
+        name: config.name.clone(),
+        strategy: config.strategy,
+        agreement: votes(chosen) as f32 / answered.len() as f32,
+        contributions: outcomes
+            .iter()
+            .enumerate()
+            .map(|(index, outcome)| ModelContribution {
+                model: outcome.model.clone(),
+                latency_ms: outcome.latency_ms,
+                response: texts[index].map(str::to_string),
+                score: scores.get(index).copied().flatten(),
+                selected: selected.contains(&index),
+                error: outcome.result.as_ref().err().map(|e| e.to_string()),
+            })
+            .collect(),
+    };
+
+    let mut result = result.unwrap_or_default();
+    if let Some(fields) = result.as_object_mut() {
+        fields.insert("model".to_string(), config.name.clone().into());
+        fields.insert("ensemble".to_string(), serde_json::to_value(&report)?);
+    }
+    Ok((result, report))
+}
+
+/// Answer text compared when voting: case, spacing and trailing
+/// punctuation do not make answers differ
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(|c: char| c.is_ascii_punctuation())
+        .to_lowercase()
+}
+
+/// Replace the text `response_text` reads, or add one
+fn set_response_text(result: &mut serde_json::Value, text: String) {
+    let Some(fields) = result.as_object_mut() else {
+        return;
+    };
+    let field = ["text", "response", "summary"]
+        .into_iter()
+        .find(|field| fields.get(*field).is_some_and(|value| value.is_string()))
+        .unwrap_or("text");
+    fields.insert(field.to_string(), text.into());
+}
+
+/// Counts of ensemble runs for the engine's health metrics
+#[derive(Default)]
+pub struct EnsembleMetrics {
+    runs: AtomicU64,
+    member_failures: AtomicU64,
+    split_decisions: AtomicU64,
+}
+
+impl EnsembleMetrics {
+    pub fn record(&self, report: &EnsembleReport) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        let failures = report
+            .contributions
+            .iter()
+            .filter(|c| c.error.is_some())
+            .count();
+        self.member_failures
+            .fetch_add(failures as u64, Ordering::Relaxed);
+        if report.agreement < 1.0 {
+            self.split_decisions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        for (key, counter) in [
+            ("ensemble_runs", &self.runs),
+            ("ensemble_member_failures", &self.member_failures),
+            ("ensemble_split_decisions", &self.split_decisions),
+        ] {
+            metrics.insert(key.to_string(), counter.load(Ordering::Relaxed) as f32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ensemble(strategy: EnsembleMergeStrategy) -> ModelEnsembleConfig {
+        ModelEnsembleConfig {
+            name: "defect-check".to_string(),
+            models: vec![
+                "tiny".to_string(),
+                "small".to_string(),
+                "medium".to_string(),
+            ],
+            strategy,
+        }
+    }
+
+    fn outcomes(answers: [Option<&str>; 3]) -> Vec<MemberOutcome> {
+        ["tiny", "small", "medium"]
+            .into_iter()
+            .zip(answers)
+            .enumerate()
+            .map(|(index, (model, answer))| MemberOutcome {
+                model: model.to_string(),
+                result: match answer {
+                    Some(answer) => Ok(serde_json::json!({"text": answer, "model": model})),
+                    None => Err(Error::Model("out of memory".to_string())),
+                },
+                latency_ms: 10 * (index as u64 + 1),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_vote_picks_the_majority_answer() {
+        let config = ensemble(EnsembleMergeStrategy::Vote);
+        let (result, report) = merge(
+            &config,
+            outcomes([Some("Defective"), Some("ok"), Some(" defective.")]),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(result["text"], "Defective");
+        assert_eq!(result["model"], "defect-check");
+        assert!((report.agreement - 2.0 / 3.0).abs() < 1e-6);
+        let selected: Vec<bool> = report.contributions.iter().map(|c| c.selected).collect();
+        assert_eq!(selected, vec![true, false, true]);
+        assert_eq!(result["ensemble"]["contributions"][1]["latency_ms"], 20);
+
+        // A tie goes to the model listed first, and failures are reported
+        let (result, report) = merge(
+            &config,
+            outcomes([None, Some("ok"), Some("defective")]),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(result["text"], "ok");
+        assert_eq!(
+            report.contributions[0].error.as_deref(),
+            Some("Model error: out of memory")
+        );
+        assert!(merge(&config, outcomes([None, None, None]), &[]).is_err());
+
+        let metrics = EnsembleMetrics::default();
+        metrics.record(&report);
+        let mut written = HashMap::new();
+        metrics.write_metrics(&mut written);
+        assert_eq!(written["ensemble_member_failures"], 1.0);
+        assert_eq!(written["ensemble_split_decisions"], 1.0);
+    }
+
+    #[test]
+    fn test_rank_by_verifier_and_sections() {
+        let answers = [Some("ok"), Some("defective"), Some("defective")];
+        let config = ensemble(EnsembleMergeStrategy::RankByVerifier);
+        let scores = [Some(0.9), Some(0.4), None];
+        let (result, report) = merge(&config, outcomes(answers), &scores).unwrap();
+        // The verifier overrules the majority
+        assert_eq!(result["text"], "ok");
+        assert!((report.agreement - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(report.contributions[1].score, Some(0.4));
+
+        let config = ensemble(EnsembleMergeStrategy::ConcatSections);
+        let (result, report) = merge(
+            &config,
+            outcomes([Some("ok"), None, Some("defective")]),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(result["text"], "### tiny\nok\n\n### medium\ndefective");
+        let selected: Vec<bool> = report.contributions.iter().map(|c| c.selected).collect();
+        assert_eq!(selected, vec![true, false, true]);
+    }
+}
@@ -0,0 +1,614 @@
+//! GGUF model files
+//!
+//! GGUF is the single-file format llama.cpp uses for quantized models such as
+//! TinyLlama and Phi-3. A file is a header, a table of typed key/value
+//! metadata (architecture, context length, tokenizer vocabulary), a table of
+//! tensor descriptors, and the aligned tensor data.
+//!
+//! Files are memory-mapped rather than read: only the header and tables are
+//! parsed up front, and tensor data is handed out as slices of the mapping, so
+//! the kernel pages weights in as they are touched and can drop them again
+//! under memory pressure. A 600MB model therefore loads on a Raspberry Pi with
+//! a few megabytes of heap. Storage backends without host files (the in-memory
+//! VFS) fall back to an owned buffer.
+
+use crate::memory_estimate::ModelShape;
+use mcp_common::{Error, Result};
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+/// First four bytes of every GGUF file
+pub const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+
+/// Tensor data alignment when `general.alignment` is absent
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+/// Bytes of a GGUF file, mapped or owned
+enum ModelBytes {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for ModelBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ModelBytes::Mapped(map) => map,
+            ModelBytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// Metadata value
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Array(Vec<GgufValue>),
+}
+
+impl GgufValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            GgufValue::UInt(value) => Some(*value),
+            GgufValue::Int(value) => u64::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            GgufValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[GgufValue]> {
+        match self {
+            GgufValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Tensor descriptor; `offset` is relative to the start of the tensor data
+#[derive(Debug, Clone)]
+pub struct GgufTensor {
+    pub name: String,
+    pub dimensions: Vec<u64>,
+    pub ggml_type: u32,
+    pub offset: u64,
+}
+
+impl GgufTensor {
+    pub fn elements(&self) -> u64 {
+        self.dimensions.iter().product()
+    }
+
+    /// Size of the tensor data, if the element type is known
+    pub fn byte_size(&self) -> Option<u64> {
+        let (_, block_size, type_size) = ggml_type_info(self.ggml_type)?;
+        Some(self.elements() / block_size * type_size)
+    }
+}
+
+/// Tensors and bytes stored with one element type
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TensorTypeStats {
+    pub tensors: u32,
+    pub bytes: u64,
+}
+
+/// How a model's weights are quantized
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuantizationInfo {
+    /// Quantization the file was produced with (`general.file_type`), e.g. `Q4_K_M`
+    pub file_type: Option<String>,
+    /// Element type holding most of the weight bytes
+    pub predominant_type: String,
+    pub bits_per_weight: f32,
+    pub tensor_types: BTreeMap<String, TensorTypeStats>,
+}
+
+/// Parsed GGUF file with zero-copy access to tensor data
+pub struct GgufFile {
+    bytes: ModelBytes,
+    pub version: u32,
+    pub metadata: HashMap<String, GgufValue>,
+    pub tensors: Vec<GgufTensor>,
+    data_offset: usize,
+}
+
+impl GgufFile {
+    /// Memory-map and parse a file
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| Error::Model(format!("Failed to open {:?}: {}", path, e)))?;
+        // SAFETY: the mapping is read-only and model files are replaced by
+        // writing a new file and renaming it, never modified in place; the
+        // integrity monitor unloads models whose file changes.
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| Error::Model(format!("Failed to map {:?}: {}", path, e)))?;
+        Self::parse(ModelBytes::Mapped(map))
+    }
+
+    /// Parse a file already held in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Self::parse(ModelBytes::Owned(bytes))
+    }
+
+    /// Whether a buffer starts like a GGUF file
+    pub fn is_gguf(bytes: &[u8]) -> bool {
+        bytes.starts_with(GGUF_MAGIC)
+    }
+
+    fn parse(bytes: ModelBytes) -> Result<Self> {
+        let mut reader = Reader { bytes: &bytes, position: 0 };
+        if reader.take(4)? != GGUF_MAGIC {
+            return Err(invalid("missing GGUF magic"));
+        }
+        let version = reader.u32()?;
+        if !(2..=3).contains(&version) {
+            return Err(Error::Model(format!("Unsupported GGUF version {}", version)));
+        }
+        let tensor_count = reader.u64()?;
+        let metadata_count = reader.u64()?;
+
+        let mut metadata = HashMap::new();
+        for _ in 0..metadata_count {
+            let key = reader.string()?;
+            let value_type = reader.u32()?;
+            metadata.insert(key, reader.value(value_type)?);
+        }
+
+        let mut tensors = Vec::new();
+        for _ in 0..tensor_count {
+            let name = reader.string()?;
+            let dimension_count = reader.u32()?;
+            if dimension_count > 4 {
+                return Err(invalid(&format!("tensor {} has {} dimensions", name, dimension_count)));
+            }
+            let dimensions = (0..dimension_count).map(|_| reader.u64()).collect::<Result<Vec<_>>>()?;
+            tensors.push(GgufTensor {
+                name,
+                dimensions,
+                ggml_type: reader.u32()?,
+                offset: reader.u64()?,
+            });
+        }
+
+        let alignment = metadata
+            .get("general.alignment")
+            .and_then(GgufValue::as_u64)
+            .filter(|alignment| alignment.is_power_of_two())
+            .unwrap_or(DEFAULT_ALIGNMENT);
+        let data_offset = (reader.position as u64).div_ceil(alignment);
+        let data_offset = usize::try_from(data_offset * alignment).map_err(|_| invalid("data offset overflows"))?;
+
+        let data_len = bytes.len().saturating_sub(data_offset) as u64;
+        for tensor in &tensors {
+            let end = tensor.byte_size().and_then(|size| tensor.offset.checked_add(size));
+            if end.is_some_and(|end| end > data_len) {
+                return Err(invalid(&format!("tensor {} extends past the end of the file", tensor.name)));
+            }
+        }
+
+        Ok(Self {
+            bytes,
+            version,
+            metadata,
+            tensors,
+            data_offset,
+        })
+    }
+
+    /// Whether the weights are mapped rather than copied into memory
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.bytes, ModelBytes::Mapped(_))
+    }
+
+    pub fn file_size(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    pub fn architecture(&self) -> Option<&str> {
+        self.metadata.get("general.architecture").and_then(GgufValue::as_str)
+    }
+
+    /// Architecture-specific metadata, e.g. `llama.context_length`
+    pub fn architecture_value(&self, key: &str) -> Option<&GgufValue> {
+        self.metadata.get(&format!("{}.{}", self.architecture()?, key))
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.metadata.get("general.name").and_then(GgufValue::as_str)
+    }
+
+    pub fn context_length(&self) -> Option<u64> {
+        self.architecture_value("context_length").and_then(GgufValue::as_u64)
+    }
+
+    /// Transformer dimensions; models without grouped-query attention omit
+    /// the KV head count
+    pub fn shape(&self) -> Option<ModelShape> {
+        let value = |key: &str| {
+            self.architecture_value(key)
+                .and_then(GgufValue::as_u64)
+                .and_then(|value| u32::try_from(value).ok())
+        };
+        let heads = value("attention.head_count")?;
+        Some(ModelShape {
+            layers: value("block_count")?,
+            embedding: value("embedding_length")?,
+            heads,
+            kv_heads: value("attention.head_count_kv").unwrap_or(heads),
+        })
+    }
+
+    /// Tokenizer vocabulary, indexed by token id
+    pub fn tokens(&self) -> Vec<&str> {
+        self.metadata
+            .get("tokenizer.ggml.tokens")
+            .and_then(GgufValue::as_array)
+            .map(|tokens| tokens.iter().filter_map(GgufValue::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn parameter_count(&self) -> u64 {
+        self.tensors.iter().map(GgufTensor::elements).sum()
+    }
+
+    /// Data of a tensor, paged in from the mapping on first access
+    pub fn tensor_data(&self, tensor: &GgufTensor) -> Option<&[u8]> {
+        let start = self.data_offset.checked_add(usize::try_from(tensor.offset).ok()?)?;
+        let end = start.checked_add(usize::try_from(tensor.byte_size()?).ok()?)?;
+        self.bytes.get(start..end)
+    }
+
+    pub fn quantization(&self) -> QuantizationInfo {
+        let mut tensor_types: BTreeMap<String, TensorTypeStats> = BTreeMap::new();
+        let (mut total_bytes, mut total_elements) = (0u64, 0u64);
+        for tensor in &self.tensors {
+            let stats = tensor_types.entry(ggml_type_name(tensor.ggml_type)).or_default();
+            stats.tensors += 1;
+            if let Some(bytes) = tensor.byte_size() {
+                stats.bytes += bytes;
+                total_bytes += bytes;
+                total_elements += tensor.elements();
+            }
+        }
+        let predominant_type = tensor_types
+            .iter()
+            .max_by_key(|(_, stats)| stats.bytes)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_default();
+
+        QuantizationInfo {
+            file_type: self
+                .metadata
+                .get("general.file_type")
+                .and_then(GgufValue::as_u64)
+                .map(file_type_name),
+            predominant_type,
+            bits_per_weight: if total_elements > 0 {
+                (total_bytes * 8) as f32 / total_elements as f32
+            } else {
+                0.0
+            },
+            tensor_types,
+        }
+    }
+}
+
+/// Greedy longest-match tokenizer over a GGUF vocabulary, used to count
+/// prompt tokens against the context window
+pub struct GgufTokenizer {
+    vocab: HashMap<String, u32>,
+    max_token_len: usize,
+    space: &'static str,
+    add_space_prefix: bool,
+    unknown: u32,
+}
+
+impl GgufTokenizer {
+    pub fn new(file: &GgufFile) -> Self {
+        let tokens = file.tokens();
+        // SentencePiece vocabularies mark spaces with U+2581, GPT-2 style ones with U+0120
+        let gpt2 = file.metadata.get("tokenizer.ggml.model").and_then(GgufValue::as_str) == Some("gpt2");
+        Self {
+            max_token_len: tokens.iter().map(|token| token.len()).max().unwrap_or(1),
+            vocab: tokens.into_iter().enumerate().map(|(id, token)| (token.to_string(), id as u32)).collect(),
+            space: if gpt2 { "\u{120}" } else { "\u{2581}" },
+            add_space_prefix: !gpt2,
+            unknown: file
+                .metadata
+                .get("tokenizer.ggml.unknown_token_id")
+                .and_then(GgufValue::as_u64)
+                .unwrap_or(0) as u32,
+        }
+    }
+
+    pub fn tokenize(&self, text: &str) -> Vec<u32> {
+        if text.is_empty() {
+            return Vec::new();
+        }
+        let mut text = text.replace(' ', self.space);
+        if self.add_space_prefix {
+            text.insert_str(0, self.space);
+        }
+
+        let mut ids = Vec::new();
+        let mut rest = text.as_str();
+        while let Some(c) = rest.chars().next() {
+            let mut end = rest.len().min(self.max_token_len);
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            let matched = loop {
+                if end == 0 {
+                    break None;
+                }
+                if let Some(id) = self.vocab.get(&rest[..end]) {
+                    break Some((*id, end));
+                }
+                end = rest[..end].char_indices().next_back().map_or(0, |(index, _)| index);
+            };
+            match matched {
+                Some((id, len)) => {
+                    ids.push(id);
+                    rest = &rest[len..];
+                },
+                None => {
+                    // Byte fallback tokens, or the unknown token
+                    let mut buf = [0u8; 4];
+                    for byte in c.encode_utf8(&mut buf).bytes() {
+                        let id = self.vocab.get(&format!("<0x{:02X}>", byte)).copied();
+                        ids.push(id.unwrap_or(self.unknown));
+                    }
+                    rest = &rest[c.len_utf8()..];
+                },
+            }
+        }
+        ids
+    }
+}
+
+/// Name, elements per block and bytes per block of a ggml element type
+fn ggml_type_info(ggml_type: u32) -> Option<(&'static str, u64, u64)> {
+    Some(match ggml_type {
+        0 => ("F32", 1, 4),
+        1 => ("F16", 1, 2),
+        2 => ("Q4_0", 32, 18),
+        3 => ("Q4_1", 32, 20),
+        6 => ("Q5_0", 32, 22),
+        7 => ("Q5_1", 32, 24),
+        8 => ("Q8_0", 32, 34),
+        9 => ("Q8_1", 32, 36),
+        10 => ("Q2_K", 256, 84),
+        11 => ("Q3_K", 256, 110),
+        12 => ("Q4_K", 256, 144),
+        13 => ("Q5_K", 256, 176),
+        14 => ("Q6_K", 256, 210),
+        15 => ("Q8_K", 256, 292),
+        16 => ("IQ2_XXS", 256, 66),
+        17 => ("IQ2_XS", 256, 74),
+        18 => ("IQ3_XXS", 256, 98),
+        19 => ("IQ1_S", 256, 50),
+        20 => ("IQ4_NL", 32, 18),
+        21 => ("IQ3_S", 256, 110),
+        22 => ("IQ2_S", 256, 82),
+        23 => ("IQ4_XS", 256, 136),
+        24 => ("I8", 1, 1),
+        25 => ("I16", 1, 2),
+        26 => ("I32", 1, 4),
+        27 => ("I64", 1, 8),
+        28 => ("F64", 1, 8),
+        29 => ("IQ1_M", 256, 56),
+        30 => ("BF16", 1, 2),
+        _ => return None,
+    })
+}
+
+fn ggml_type_name(ggml_type: u32) -> String {
+    ggml_type_info(ggml_type).map_or_else(|| format!("TYPE_{}", ggml_type), |(name, _, _)| name.to_string())
+}
+
+/// Name of a llama.cpp `general.file_type`
+fn file_type_name(file_type: u64) -> String {
+    let name = match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        32 => "BF16",
+        other => return format!("FILE_TYPE_{}", other),
+    };
+    name.to_string()
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Model(format!("Invalid GGUF file: {}", reason))
+}
+
+/// Little-endian cursor over the file header
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.position.checked_add(len).filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or_else(|| invalid("truncated header"))?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("slice has requested length"))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn length(&mut self) -> Result<usize> {
+        let len = self.u64()?;
+        // Every element takes at least a byte, so longer lengths are corrupt
+        usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= self.bytes.len() - self.position)
+            .ok_or_else(|| invalid("length exceeds file size"))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.length()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("string is not UTF-8"))
+    }
+
+    fn value(&mut self, value_type: u32) -> Result<GgufValue> {
+        Ok(match value_type {
+            0 => GgufValue::UInt(self.array::<1>()?[0] as u64),
+            1 => GgufValue::Int(i8::from_le_bytes(self.array()?) as i64),
+            2 => GgufValue::UInt(u16::from_le_bytes(self.array()?) as u64),
+            3 => GgufValue::Int(i16::from_le_bytes(self.array()?) as i64),
+            4 => GgufValue::UInt(self.u32()? as u64),
+            5 => GgufValue::Int(i32::from_le_bytes(self.array()?) as i64),
+            6 => GgufValue::Float(f32::from_le_bytes(self.array()?) as f64),
+            7 => GgufValue::Bool(self.array::<1>()?[0] != 0),
+            8 => GgufValue::String(self.string()?),
+            9 => {
+                let element_type = self.u32()?;
+                if element_type == 9 {
+                    return Err(invalid("nested arrays are not supported"));
+                }
+                let len = self.length()?;
+                GgufValue::Array((0..len).map(|_| self.value(element_type)).collect::<Result<_>>()?)
+            },
+            10 => GgufValue::UInt(self.u64()?),
+            11 => GgufValue::Int(i64::from_le_bytes(self.array()?)),
+            12 => GgufValue::Float(f64::from_le_bytes(self.array()?)),
+            other => return Err(invalid(&format!("unknown metadata type {}", other))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(out: &mut Vec<u8>, value: &str) {
+        out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        out.extend_from_slice(value.as_bytes());
+    }
+
+    /// Small llama-style GGUF file with a Q4_K and an F32 tensor
+    fn sample_file() -> Vec<u8> {
+        let mut out = GGUF_MAGIC.to_vec();
+        out.extend_from_slice(&3u32.to_le_bytes());
+        out.extend_from_slice(&2u64.to_le_bytes());
+        out.extend_from_slice(&5u64.to_le_bytes());
+        string(&mut out, "general.architecture");
+        out.extend_from_slice(&8u32.to_le_bytes());
+        string(&mut out, "llama");
+        string(&mut out, "general.name");
+        out.extend_from_slice(&8u32.to_le_bytes());
+        string(&mut out, "TinyLlama");
+        string(&mut out, "llama.context_length");
+        out.extend_from_slice(&4u32.to_le_bytes());
+        out.extend_from_slice(&2048u32.to_le_bytes());
+        string(&mut out, "general.file_type");
+        out.extend_from_slice(&4u32.to_le_bytes());
+        out.extend_from_slice(&15u32.to_le_bytes());
+        string(&mut out, "tokenizer.ggml.tokens");
+        out.extend_from_slice(&9u32.to_le_bytes());
+        out.extend_from_slice(&8u32.to_le_bytes());
+        let tokens = ["<unk>", "<s>", "</s>", "▁hello", "▁world", "▁", "h", "i"];
+        out.extend_from_slice(&(tokens.len() as u64).to_le_bytes());
+        for token in tokens {
+            string(&mut out, token);
+        }
+
+        string(&mut out, "blk.0.attn_q.weight");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&256u64.to_le_bytes());
+        out.extend_from_slice(&4u64.to_le_bytes());
+        out.extend_from_slice(&12u32.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes());
+        string(&mut out, "output_norm.weight");
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&256u64.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&576u64.to_le_bytes());
+
+        out.resize(out.len().div_ceil(32) * 32, 0);
+        out.extend((0..576 + 1024).map(|i| i as u8));
+        out
+    }
+
+    #[test]
+    fn test_parses_metadata_and_quantization() {
+        let file = GgufFile::from_bytes(sample_file()).unwrap();
+        assert_eq!(file.version, 3);
+        assert_eq!(file.architecture(), Some("llama"));
+        assert_eq!(file.name(), Some("TinyLlama"));
+        assert_eq!(file.context_length(), Some(2048));
+        assert_eq!(file.tokens().len(), 8);
+        assert_eq!(file.parameter_count(), 1280);
+
+        let quantization = file.quantization();
+        assert_eq!(quantization.file_type.as_deref(), Some("Q4_K_M"));
+        assert_eq!(quantization.predominant_type, "F32");
+        assert_eq!(quantization.tensor_types["Q4_K"].bytes, 576);
+        assert!((quantization.bits_per_weight - 1600.0 * 8.0 / 1280.0).abs() < 0.01);
+
+        let tokenizer = GgufTokenizer::new(&file);
+        assert_eq!(tokenizer.tokenize("hello world hi"), vec![3, 4, 5, 6, 7]);
+        assert_eq!(tokenizer.tokenize("hello?"), vec![3, 0]);
+
+        let norm = &file.tensors[1];
+        assert_eq!(file.tensor_data(norm).unwrap()[..2], [(576 % 256) as u8, (577 % 256) as u8]);
+    }
+
+    #[test]
+    fn test_maps_files_and_rejects_corrupt_ones() {
+        let path = std::env::temp_dir().join(format!("gguf-test-{}.gguf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, sample_file()).unwrap();
+        let file = GgufFile::open(&path).unwrap();
+        assert!(file.is_mapped());
+        assert_eq!(file.tensors.len(), 2);
+        drop(file);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut truncated = sample_file();
+        truncated.truncate(truncated.len() - 100);
+        assert!(GgufFile::from_bytes(truncated).is_err());
+        assert!(GgufFile::from_bytes(b"GGML\x03\0\0\0".to_vec()).is_err());
+        let mut huge_string = sample_file()[..24].to_vec();
+        huge_string.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(GgufFile::from_bytes(huge_string).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Scheduled maintenance of the retrieval index
+//!
+//! Deletions and re-ingestion only tombstone index entries, so the index
+//! fragments over time. A periodic job rebuilds it once fragmentation passes
+//! the configured threshold, prunes the least-queried documents when the
+//! index outgrows its byte budget or the disk holding its snapshot runs low,
+//! saves the snapshot, and measures recall on a sample so a compaction that
+//! hurts search quality is visible in metrics.
+
+use crate::retrieval::{HybridRetriever, IndexStats};
+use chrono::{DateTime, Utc};
+use mcp_common::config::IndexMaintenanceConfig;
+use mcp_common::metrics::{ComponentHealth, HealthLevel};
+use mcp_common::{Result, Vfs};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Results of top-k searches checked when measuring recall
+const RECALL_TOP_K: usize = 5;
+
+/// Recall below which the index is reported as degraded
+const MIN_HEALTHY_RECALL: f32 = 0.8;
+
+/// Outcome of one maintenance run
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexMaintenanceReport {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub rebuilt: bool,
+    pub tombstones_removed: usize,
+    pub pruned_documents: Vec<String>,
+    /// Recall on a sample of documents after compaction
+    pub recall: Option<f32>,
+    pub snapshot_bytes: Option<u64>,
+    pub stats: IndexStats,
+}
+
+/// Runs compaction, pruning and snapshotting for a [`HybridRetriever`]
+pub struct IndexMaintainer {
+    config: IndexMaintenanceConfig,
+    retriever: Arc<HybridRetriever>,
+    vfs: Arc<dyn Vfs>,
+    index_path: Option<PathBuf>,
+    last_report: RwLock<Option<IndexMaintenanceReport>>,
+    compactions_total: AtomicU64,
+    pruned_total: AtomicU64,
+}
+
+impl IndexMaintainer {
+    pub fn new(
+        config: IndexMaintenanceConfig,
+        retriever: Arc<HybridRetriever>,
+        vfs: Arc<dyn Vfs>,
+        index_path: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            config,
+            retriever,
+            vfs,
+            index_path,
+            last_report: RwLock::new(None),
+            compactions_total: AtomicU64::new(0),
+            pruned_total: AtomicU64::new(0),
+        }
+    }
+
+    /// Load the index snapshot, if any, and schedule periodic maintenance
+    pub async fn start(self: &Arc<Self>) {
+        if let Some(path) = &self.index_path {
+            if self.vfs.exists(path).await {
+                match self.retriever.load(self.vfs.as_ref(), path).await {
+                    Ok(documents) => info!("Loaded {} documents from retrieval index {:?}", documents, path),
+                    Err(e) => warn!("Failed to load retrieval index {:?}: {}", path, e),
+                }
+            }
+        }
+        if self.config.interval_secs == 0 {
+            return;
+        }
+
+        let maintainer = Arc::downgrade(self);
+        let interval_secs = self.config.interval_secs;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(maintainer) = maintainer.upgrade() else {
+                    break;
+                };
+                if let Err(e) = maintainer.run(false).await {
+                    warn!("Retrieval index maintenance failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Run maintenance now; `force` rebuilds regardless of fragmentation
+    pub async fn run(&self, force: bool) -> Result<IndexMaintenanceReport> {
+        let started_at = Utc::now();
+        let timer = Instant::now();
+
+        let before = self.retriever.stats().await;
+        let budget = self.byte_budget(before.estimated_bytes);
+        let pruned_documents = self.retriever.prune_least_queried(budget).await;
+        if !pruned_documents.is_empty() {
+            warn!(
+                "Pruned {} least-queried documents to fit the {} byte index budget",
+                pruned_documents.len(),
+                budget
+            );
+            self.pruned_total
+                .fetch_add(pruned_documents.len() as u64, Ordering::Relaxed);
+        }
+
+        let fragmentation = self.retriever.stats().await.fragmentation;
+        let rebuilt = force || fragmentation > self.config.max_fragmentation;
+        let (tombstones_removed, recall) = if rebuilt {
+            let removed = self.retriever.compact().await;
+            self.compactions_total.fetch_add(1, Ordering::Relaxed);
+            let recall = self
+                .retriever
+                .measure_recall(self.config.recall_sample_size, RECALL_TOP_K)
+                .await;
+            info!(
+                "Rebuilt retrieval index: {} tombstones removed, recall {:?}",
+                removed, recall
+            );
+            (removed, recall)
+        } else {
+            (0, self.last_report.read().await.as_ref().and_then(|report| report.recall))
+        };
+
+        let snapshot_bytes = match &self.index_path {
+            Some(path) => Some(self.retriever.save(self.vfs.as_ref(), path).await?),
+            None => None,
+        };
+
+        let report = IndexMaintenanceReport {
+            started_at,
+            duration_ms: timer.elapsed().as_millis() as u64,
+            rebuilt,
+            tombstones_removed,
+            pruned_documents,
+            recall,
+            snapshot_bytes,
+            stats: self.retriever.stats().await,
+        };
+        *self.last_report.write().await = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Largest index size allowed by the configured budget and free disk space
+    fn byte_budget(&self, current_bytes: u64) -> u64 {
+        let mut budget = self.config.max_index_bytes;
+        let host_dir = self
+            .index_path
+            .as_ref()
+            .and_then(|path| self.vfs.host_path(path))
+            .and_then(|path| path.parent().map(|parent| parent.to_path_buf()));
+        if let Some(available) = host_dir.and_then(|dir| fs2::available_space(&dir).ok()) {
+            // The snapshot being replaced frees roughly the current index size
+            let usable = (available + current_bytes).saturating_sub(self.config.min_free_disk_bytes);
+            budget = budget.min(usable);
+        }
+        budget
+    }
+
+    /// Most recent maintenance report
+    pub async fn last_report(&self) -> Option<IndexMaintenanceReport> {
+        self.last_report.read().await.clone()
+    }
+
+    /// Index health, degraded when recall after compaction is poor
+    pub async fn health(&self) -> ComponentHealth {
+        let mut metrics = HashMap::new();
+        self.write_metrics(&mut metrics).await;
+        let recall = metrics.get("index_recall").copied();
+
+        let (status, message) = match recall {
+            Some(recall) if recall < MIN_HEALTHY_RECALL => (
+                HealthLevel::Degraded,
+                format!("Index recall after compaction is {:.2}", recall),
+            ),
+            _ => (HealthLevel::Healthy, "Retrieval index healthy".to_string()),
+        };
+        ComponentHealth {
+            status,
+            message,
+            last_check: Utc::now(),
+            metrics,
+        }
+    }
+
+    /// Add index metrics to a component health metric map
+    pub async fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let stats = self.retriever.stats().await;
+        metrics.insert("index_documents".to_string(), stats.documents as f32);
+        metrics.insert("index_tombstones".to_string(), stats.tombstones as f32);
+        metrics.insert("index_fragmentation".to_string(), stats.fragmentation);
+        metrics.insert("index_bytes".to_string(), stats.estimated_bytes as f32);
+        metrics.insert(
+            "index_compactions_total".to_string(),
+            self.compactions_total.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "index_pruned_total".to_string(),
+            self.pruned_total.load(Ordering::Relaxed) as f32,
+        );
+        if let Some(recall) = self.last_report().await.and_then(|report| report.recall) {
+            metrics.insert("index_recall".to_string(), recall);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retrieval::Document;
+    use mcp_common::config::RetrievalConfig;
+    use mcp_common::vfs::MemoryVfs;
+
+    fn documents(count: usize) -> Vec<Document> {
+        (0..count)
+            .map(|i| Document {
+                id: format!("doc-{}", i),
+                text: format!("maintenance procedure {} for unit {} valve", i, i * 7),
+                metadata: HashMap::new(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_removes_tombstones_and_snapshots() {
+        let retriever = Arc::new(HybridRetriever::new(&RetrievalConfig::default()));
+        retriever.index(documents(10)).await;
+        retriever
+            .remove_documents(&["doc-1".to_string(), "doc-2".to_string(), "doc-3".to_string()])
+            .await;
+        assert_eq!(retriever.stats().await.tombstones, 3);
+
+        let vfs = Arc::new(MemoryVfs::new());
+        let path = PathBuf::from("index.json");
+        let maintainer = IndexMaintainer::new(
+            IndexMaintenanceConfig::default(),
+            retriever.clone(),
+            vfs.clone(),
+            Some(path.clone()),
+        );
+        let report = maintainer.run(false).await.unwrap();
+        assert!(report.rebuilt);
+        assert_eq!(report.tombstones_removed, 3);
+        assert_eq!(report.stats.documents, 7);
+        assert_eq!(report.recall, Some(1.0));
+
+        let restored = HybridRetriever::new(&RetrievalConfig::default());
+        assert_eq!(restored.load(vfs.as_ref(), &path).await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_pruning_keeps_frequently_queried_documents() {
+        let retriever = Arc::new(HybridRetriever::new(&RetrievalConfig::default()));
+        retriever.index(documents(4)).await;
+        retriever.search_local("maintenance procedure 3 for unit 21", 1).await;
+
+        let budget = retriever.stats().await.estimated_bytes / 3;
+        let pruned = retriever.prune_least_queried(budget).await;
+        assert!(!pruned.is_empty());
+        assert!(!pruned.contains(&"doc-3".to_string()));
+        assert!(retriever.stats().await.estimated_bytes <= budget);
+    }
+}
@@ -0,0 +1,630 @@
+//! Document ingestion for the local retrieval store
+//!
+//! Plain text, markdown and PDF files are converted to text, split into
+//! overlapping word chunks and indexed in the [`HybridRetriever`] with source
+//! metadata. Each source is tracked by content hash, so re-ingesting an
+//! unchanged file is a no-op and a changed file replaces its old chunks.
+//! Configured watch directories are rescanned periodically, which also drops
+//! the chunks of files that have been deleted.
+
+use crate::retrieval::{Document, HybridRetriever};
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use mcp_common::config::IngestionConfig;
+use mcp_common::{Error, Result, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+/// Supported document formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+    Text,
+    Markdown,
+    Pdf,
+}
+
+impl DocumentFormat {
+    /// Infer the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "txt" | "text" | "log" => Some(Self::Text),
+            "md" | "markdown" => Some(Self::Markdown),
+            "pdf" => Some(Self::Pdf),
+            _ => None,
+        }
+    }
+}
+
+/// Ingestion request accepted by the admin API
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IngestRequest {
+    /// File to ingest from gateway storage
+    pub path: Option<PathBuf>,
+    /// Source name for uploaded content; defaults to the path
+    pub source: Option<String>,
+    pub format: Option<DocumentFormat>,
+    /// Uploaded text or markdown
+    pub content: Option<String>,
+    /// Uploaded binary content such as a PDF
+    pub content_base64: Option<String>,
+}
+
+/// A source currently held in the retrieval store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestedSource {
+    pub source: String,
+    pub format: DocumentFormat,
+    pub content_hash: String,
+    pub bytes: u64,
+    pub chunks: usize,
+    pub ingested_at: DateTime<Utc>,
+}
+
+/// What happened to a source during ingestion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestStatus {
+    Ingested,
+    Unchanged,
+    Removed,
+}
+
+/// Outcome of ingesting one source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestReport {
+    pub source: String,
+    pub status: IngestStatus,
+    pub chunks: usize,
+}
+
+/// Chunks, embeds and indexes documents into the retrieval store
+pub struct IngestionPipeline {
+    config: IngestionConfig,
+    retriever: Arc<HybridRetriever>,
+    vfs: Arc<dyn Vfs>,
+    sources: RwLock<HashMap<String, IngestedSource>>,
+}
+
+impl IngestionPipeline {
+    pub fn new(config: IngestionConfig, retriever: Arc<HybridRetriever>, vfs: Arc<dyn Vfs>) -> Self {
+        Self {
+            config,
+            retriever,
+            vfs,
+            sources: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Ingest the watch directories and keep rescanning them for changes
+    pub async fn start(self: &Arc<Self>) {
+        if self.config.watch_dirs.is_empty() {
+            return;
+        }
+        self.scan().await;
+        if self.config.scan_interval_secs == 0 {
+            return;
+        }
+
+        let pipeline = Arc::downgrade(self);
+        let interval_secs = self.config.scan_interval_secs;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(pipeline) = pipeline.upgrade() else {
+                    break;
+                };
+                pipeline.scan().await;
+            }
+        });
+    }
+
+    /// Ingest new and changed files in the watch directories and drop deleted ones
+    pub async fn scan(&self) -> Vec<IngestReport> {
+        let mut reports = Vec::new();
+        let mut present = Vec::new();
+
+        for dir in &self.config.watch_dirs {
+            let files = match self.vfs.list_dir(dir).await {
+                Ok(files) => files,
+                Err(e) => {
+                    warn!("Failed to scan ingestion directory {:?}: {}", dir, e);
+                    continue;
+                },
+            };
+            for path in files {
+                if DocumentFormat::from_path(&path).is_none() {
+                    continue;
+                }
+                present.push(source_name(&path));
+                match self.ingest_path(&path).await {
+                    Ok(report) => reports.push(report),
+                    Err(e) => warn!("Failed to ingest {:?}: {}", path, e),
+                }
+            }
+        }
+
+        let deleted: Vec<String> = self
+            .sources
+            .read()
+            .await
+            .keys()
+            .filter(|source| {
+                self.config
+                    .watch_dirs
+                    .iter()
+                    .any(|dir| Path::new(source.as_str()).parent() == Some(dir.as_path()))
+                    && !present.contains(source)
+            })
+            .cloned()
+            .collect();
+        for source in deleted {
+            if let Some(report) = self.remove_source(&source).await {
+                reports.push(report);
+            }
+        }
+
+        let changed = reports
+            .iter()
+            .filter(|report| report.status != IngestStatus::Unchanged)
+            .count();
+        if changed > 0 {
+            info!("Ingestion scan updated {} sources", changed);
+        }
+        reports
+    }
+
+    /// Handle an admin API ingestion request
+    pub async fn ingest_request(&self, request: IngestRequest) -> Result<IngestReport> {
+        if let Some(content) = request.content {
+            let source = request
+                .source
+                .ok_or_else(|| Error::InvalidRequest("Uploaded content needs a 'source' name".to_string()))?;
+            return self.ingest_bytes(&source, request.format, content.as_bytes()).await;
+        }
+        if let Some(encoded) = request.content_base64 {
+            let source = request
+                .source
+                .ok_or_else(|| Error::InvalidRequest("Uploaded content needs a 'source' name".to_string()))?;
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| Error::InvalidRequest(format!("Invalid base64 content: {}", e)))?;
+            return self.ingest_bytes(&source, request.format, &bytes).await;
+        }
+        match request.path {
+            Some(path) => self.ingest_path(&path).await,
+            None => Err(Error::InvalidRequest(
+                "Ingestion requires 'path', 'content' or 'content_base64'".to_string(),
+            )),
+        }
+    }
+
+    /// Ingest a file from storage
+    pub async fn ingest_path(&self, path: &Path) -> Result<IngestReport> {
+        let size = self.vfs.size(path).await?;
+        if size > self.config.max_file_bytes {
+            return Err(Error::InvalidRequest(format!(
+                "{:?} is {} bytes, above the {} byte ingestion limit",
+                path, size, self.config.max_file_bytes
+            )));
+        }
+        let bytes = self.vfs.read(path).await?;
+        self.ingest_bytes(&source_name(path), DocumentFormat::from_path(path), &bytes)
+            .await
+    }
+
+    /// Ingest raw document bytes under a source name
+    pub async fn ingest_bytes(
+        &self,
+        source: &str,
+        format: Option<DocumentFormat>,
+        bytes: &[u8],
+    ) -> Result<IngestReport> {
+        if bytes.len() as u64 > self.config.max_file_bytes {
+            return Err(Error::InvalidRequest(format!(
+                "{} is {} bytes, above the {} byte ingestion limit",
+                source,
+                bytes.len(),
+                self.config.max_file_bytes
+            )));
+        }
+        let format = format
+            .or_else(|| DocumentFormat::from_path(Path::new(source)))
+            .unwrap_or(DocumentFormat::Text);
+
+        let content_hash = content_hash(bytes);
+        if let Some(existing) = self.sources.read().await.get(source) {
+            if existing.content_hash == content_hash {
+                return Ok(IngestReport {
+                    source: source.to_string(),
+                    status: IngestStatus::Unchanged,
+                    chunks: existing.chunks,
+                });
+            }
+        }
+
+        let text = extract_text(format, bytes)?;
+        let chunks = chunk_words(
+            &text,
+            self.config.chunk_size_words,
+            self.config.chunk_overlap_words,
+        );
+        let ingested_at = Utc::now();
+        let documents: Vec<Document> = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| Document {
+                id: chunk_id(source, index),
+                text,
+                metadata: HashMap::from([
+                    ("source".to_string(), serde_json::json!(source)),
+                    ("chunk".to_string(), serde_json::json!(index)),
+                    ("format".to_string(), serde_json::json!(format)),
+                    ("content_hash".to_string(), serde_json::json!(content_hash)),
+                    ("ingested_at".to_string(), serde_json::json!(ingested_at)),
+                ]),
+            })
+            .collect();
+        let chunk_count = documents.len();
+
+        let mut sources = self.sources.write().await;
+        if let Some(previous) = sources.remove(source) {
+            self.retriever
+                .remove_documents(&chunk_ids(source, previous.chunks))
+                .await;
+        }
+        self.retriever.index(documents).await;
+        sources.insert(
+            source.to_string(),
+            IngestedSource {
+                source: source.to_string(),
+                format,
+                content_hash,
+                bytes: bytes.len() as u64,
+                chunks: chunk_count,
+                ingested_at,
+            },
+        );
+        debug!("Ingested {} as {} chunks", source, chunk_count);
+
+        Ok(IngestReport {
+            source: source.to_string(),
+            status: IngestStatus::Ingested,
+            chunks: chunk_count,
+        })
+    }
+
+    /// Remove a source and its chunks from the store
+    pub async fn remove_source(&self, source: &str) -> Option<IngestReport> {
+        let removed = self.sources.write().await.remove(source)?;
+        self.retriever
+            .remove_documents(&chunk_ids(source, removed.chunks))
+            .await;
+        info!("Removed ingested source {}", source);
+        Some(IngestReport {
+            source: source.to_string(),
+            status: IngestStatus::Removed,
+            chunks: removed.chunks,
+        })
+    }
+
+    /// Sources currently in the store, sorted by name
+    pub async fn sources(&self) -> Vec<IngestedSource> {
+        let mut sources: Vec<_> = self.sources.read().await.values().cloned().collect();
+        sources.sort_by(|a, b| a.source.cmp(&b.source));
+        sources
+    }
+}
+
+fn source_name(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn chunk_id(source: &str, index: usize) -> String {
+    format!("{}#{}", source, index)
+}
+
+fn chunk_ids(source: &str, chunks: usize) -> Vec<String> {
+    (0..chunks).map(|index| chunk_id(source, index)).collect()
+}
+
+fn content_hash(bytes: &[u8]) -> String {
+    mcp_common::crypto::digest::digest(&mcp_common::crypto::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Split text into chunks of `size` words, each overlapping the previous by `overlap`
+pub fn chunk_words(text: &str, size: usize, overlap: usize) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let size = size.max(1);
+    let step = size.saturating_sub(overlap).max(1);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        let end = (start + size).min(words.len());
+        chunks.push(words[start..end].join(" "));
+        if end == words.len() {
+            break;
+        }
+        start += step;
+    }
+    chunks
+}
+
+/// Convert a document to plain text
+pub fn extract_text(format: DocumentFormat, bytes: &[u8]) -> Result<String> {
+    match format {
+        DocumentFormat::Text => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        DocumentFormat::Markdown => Ok(strip_markdown(&String::from_utf8_lossy(bytes))),
+        DocumentFormat::Pdf => extract_pdf_text(bytes),
+    }
+}
+
+/// Drop markdown syntax while keeping the readable text
+fn strip_markdown(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            continue;
+        }
+        let trimmed = trimmed.trim_start_matches(['#', '>']).trim_start();
+        let trimmed = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+            .or_else(|| trimmed.strip_prefix("+ "))
+            .unwrap_or(trimmed);
+        lines.push(strip_links(trimmed).replace("**", "").replace("__", "").replace('`', ""));
+    }
+    lines.join("\n")
+}
+
+/// Replace `[text](url)` and `![alt](url)` with their text
+fn strip_links(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find("](").map(|offset| open + offset) else {
+            break;
+        };
+        let Some(end) = rest[close..].find(')').map(|offset| close + offset) else {
+            break;
+        };
+        output.push_str(rest[..open].trim_end_matches('!'));
+        output.push_str(&rest[open + 1..close]);
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Extract the text shown by a PDF's content streams
+///
+/// Handles uncompressed and Flate-compressed streams with literal-string
+/// text operators, which covers documents produced by common exporters.
+/// Scanned PDFs without a text layer yield no text.
+fn extract_pdf_text(bytes: &[u8]) -> Result<String> {
+    if !bytes.starts_with(b"%PDF") {
+        return Err(Error::InvalidRequest("Not a PDF document".to_string()));
+    }
+
+    let mut text = String::new();
+    let mut cursor = 0;
+    while let Some(start) = find(bytes, b"stream", cursor) {
+        let Some(end) = find(bytes, b"endstream", start) else {
+            break;
+        };
+        cursor = end + b"endstream".len();
+
+        let dictionary_start = rfind(&bytes[..start], b"obj").unwrap_or(0);
+        let dictionary = &bytes[dictionary_start..start];
+        if find(dictionary, b"/Image", 0).is_some() {
+            continue;
+        }
+
+        let mut data_start = start + b"stream".len();
+        while matches!(bytes.get(data_start), Some(b'\r' | b'\n')) {
+            data_start += 1;
+        }
+        let raw = &bytes[data_start..end];
+        let content = if find(dictionary, b"/FlateDecode", 0).is_some() {
+            let mut inflated = Vec::new();
+            if flate2::read::ZlibDecoder::new(raw)
+                .read_to_end(&mut inflated)
+                .is_err()
+            {
+                continue;
+            }
+            inflated
+        } else {
+            raw.to_vec()
+        };
+        pdf_content_text(&content, &mut text);
+    }
+
+    if text.trim().is_empty() {
+        return Err(Error::InvalidRequest(
+            "PDF has no extractable text layer".to_string(),
+        ));
+    }
+    Ok(text)
+}
+
+/// Append the text drawn by Tj/TJ/'/" operators in a content stream
+fn pdf_content_text(content: &[u8], output: &mut String) {
+    let mut pending = String::new();
+    let mut i = 0;
+    while i < content.len() {
+        match content[i] {
+            b'(' => {
+                let (literal, next) = pdf_literal(content, i + 1);
+                pending.push_str(&literal);
+                i = next;
+            },
+            b'<' => {
+                i = content[i..]
+                    .iter()
+                    .position(|&byte| byte == b'>')
+                    .map_or(content.len(), |offset| i + offset + 1);
+            },
+            b'-' | b'0'..=b'9' | b'.' => {
+                let start = i;
+                while i < content.len() && matches!(content[i], b'-' | b'0'..=b'9' | b'.') {
+                    i += 1;
+                }
+                // Large negative kerning inside a TJ array separates words
+                let number = std::str::from_utf8(&content[start..i])
+                    .ok()
+                    .and_then(|n| n.parse::<f32>().ok());
+                if number.is_some_and(|n| n < -200.0) && !pending.is_empty() {
+                    pending.push(' ');
+                }
+            },
+            byte if byte.is_ascii_alphabetic() || byte == b'\'' || byte == b'"' || byte == b'*' => {
+                let start = i;
+                while i < content.len()
+                    && (content[i].is_ascii_alphabetic() || matches!(content[i], b'\'' | b'"' | b'*'))
+                {
+                    i += 1;
+                }
+                match &content[start..i] {
+                    b"Tj" | b"TJ" | b"'" | b"\"" => {
+                        if !output.is_empty() && !output.ends_with(char::is_whitespace) {
+                            output.push(' ');
+                        }
+                        output.push_str(pending.trim());
+                        pending.clear();
+                    },
+                    b"ET" => output.push('\n'),
+                    _ => {},
+                }
+            },
+            _ => i += 1,
+        }
+    }
+}
+
+/// Decode a PDF literal string starting after its opening parenthesis
+fn pdf_literal(content: &[u8], mut i: usize) -> (String, usize) {
+    let mut literal = String::new();
+    let mut depth = 1;
+    while i < content.len() {
+        let byte = content[i];
+        i += 1;
+        match byte {
+            b'\\' if i < content.len() => {
+                let escaped = content[i];
+                i += 1;
+                match escaped {
+                    b'n' => literal.push('\n'),
+                    b'r' => literal.push('\r'),
+                    b't' => literal.push('\t'),
+                    b'0'..=b'7' => {
+                        let mut value = (escaped - b'0') as u32;
+                        for _ in 0..2 {
+                            match content.get(i) {
+                                Some(digit @ b'0'..=b'7') => {
+                                    value = value * 8 + (digit - b'0') as u32;
+                                    i += 1;
+                                },
+                                _ => break,
+                            }
+                        }
+                        literal.push(char::from_u32(value).unwrap_or(' '));
+                    },
+                    b'\r' | b'\n' => {},
+                    other => literal.push(other as char),
+                }
+            },
+            b'(' => {
+                depth += 1;
+                literal.push('(');
+            },
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                literal.push(')');
+            },
+            other => literal.push(other as char),
+        }
+    }
+    (literal, i)
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|offset| from + offset)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::config::RetrievalConfig;
+    use mcp_common::vfs::MemoryVfs;
+
+    #[test]
+    fn test_text_extraction_for_markdown_and_pdf() {
+        let markdown = "# Pump manual\n\n- See [the guide](https://example.com) for **details**";
+        assert_eq!(
+            extract_text(DocumentFormat::Markdown, markdown.as_bytes()).unwrap(),
+            "Pump manual\n\nSee the guide for details"
+        );
+
+        let pdf = b"%PDF-1.4\n4 0 obj\n<< /Length 44 >>\nstream\nBT /F1 12 Tf (Coolant) Tj [(pu) 20 (mp) -300 (manual)] TJ ET\nendstream\nendobj\n";
+        assert_eq!(
+            extract_text(DocumentFormat::Pdf, pdf).unwrap().trim(),
+            "Coolant pump manual"
+        );
+
+        assert_eq!(chunk_words("a b c d e", 3, 1), vec!["a b c", "c d e"]);
+    }
+
+    #[tokio::test]
+    async fn test_reingestion_replaces_chunks_only_on_change() {
+        let vfs = Arc::new(MemoryVfs::new());
+        let config = IngestionConfig {
+            watch_dirs: vec![PathBuf::from("knowledge")],
+            chunk_size_words: 4,
+            chunk_overlap_words: 0,
+            ..Default::default()
+        };
+        let retriever = Arc::new(HybridRetriever::new(&RetrievalConfig::default()));
+        let pipeline = IngestionPipeline::new(config, retriever.clone(), vfs.clone());
+        let path = Path::new("knowledge/pump.md");
+
+        vfs.write(path, b"restart the coolant pump after faults").await.unwrap();
+        assert_eq!(pipeline.scan().await[0].status, IngestStatus::Ingested);
+        assert_eq!(retriever.len().await, 2);
+        assert_eq!(pipeline.scan().await[0].status, IngestStatus::Unchanged);
+
+        vfs.write(path, b"restart the pump").await.unwrap();
+        assert_eq!(pipeline.scan().await[0].status, IngestStatus::Ingested);
+        assert_eq!(retriever.len().await, 1);
+
+        vfs.remove(path).await.unwrap();
+        assert_eq!(pipeline.scan().await[0].status, IngestStatus::Removed);
+        assert!(retriever.is_empty().await);
+    }
+}
@@ -0,0 +1,381 @@
+//! Model file integrity monitoring
+//!
+//! Model files on edge devices frequently live on SD cards where bit rot is a
+//! real risk. Every model the engine loads is registered here with its
+//! expected SHA-256 digest, taken from a `<file>.sha256` sidecar when one is
+//! shipped and trusted on first load otherwise. A background task re-hashes
+//! the files periodically; on a mismatch the model is marked corrupted, an
+//! alert is raised and, when a registry is configured, a fresh copy is
+//! downloaded and verified before it replaces the damaged file.
+
+use chrono::{DateTime, Utc};
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::config::ModelIntegrityConfig;
+use mcp_common::crypto::digest::{Context, SHA256};
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
+use mcp_common::{Error, ModelId, Result, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// Integrity state of a single model file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityState {
+    Verified,
+    Corrupted,
+    Missing,
+}
+
+/// Integrity report for a single model file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelIntegrityStatus {
+    pub model_id: ModelId,
+    pub path: PathBuf,
+    pub expected_sha256: String,
+    pub state: IntegrityState,
+    pub last_verified: Option<DateTime<Utc>>,
+    pub repairs: u32,
+}
+
+/// Tracks expected checksums and periodically verifies model files
+pub struct ModelIntegrityMonitor {
+    config: ModelIntegrityConfig,
+    models: RwLock<HashMap<ModelId, ModelIntegrityStatus>>,
+    vfs: Arc<dyn Vfs>,
+    task: parking_lot::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ModelIntegrityMonitor {
+    pub fn new(config: ModelIntegrityConfig, vfs: Arc<dyn Vfs>) -> Self {
+        Self {
+            config,
+            vfs,
+            models: RwLock::new(HashMap::new()),
+            task: parking_lot::Mutex::new(None),
+        }
+    }
+
+    /// Start the periodic verification task if enabled
+    pub fn start(self: &Arc<Self>) {
+        if !self.config.enabled || self.config.check_interval_secs == 0 {
+            return;
+        }
+
+        let monitor = Arc::clone(self);
+        let period = Duration::from_secs(self.config.check_interval_secs);
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                monitor.verify_all().await;
+            }
+        });
+
+        if let Some(previous) = self.task.lock().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stop the periodic verification task
+    pub fn stop(&self) {
+        if let Some(handle) = self.task.lock().take() {
+            handle.abort();
+        }
+    }
+
+    /// Register a model file, recording its expected checksum.
+    ///
+    /// Returns an error if the file does not match its published sidecar
+    /// checksum and could not be repaired.
+    pub async fn register(&self, model_id: &ModelId, path: &Path) -> Result<()> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        if self.models.read().await.contains_key(model_id) {
+            return self.ensure_usable(model_id).await;
+        }
+
+        let actual = sha256_file(self.vfs.as_ref(), path).await?;
+        let expected = match read_sidecar(self.vfs.as_ref(), path).await {
+            Some(published) => published,
+            None => {
+                debug!("No checksum sidecar for model {}, trusting current file", model_id);
+                actual.clone()
+            },
+        };
+
+        self.models.write().await.insert(
+            model_id.clone(),
+            ModelIntegrityStatus {
+                model_id: model_id.clone(),
+                path: path.to_path_buf(),
+                expected_sha256: expected.clone(),
+                state: IntegrityState::Verified,
+                last_verified: Some(Utc::now()),
+                repairs: 0,
+            },
+        );
+
+        if actual != expected {
+            self.handle_corruption(model_id).await;
+            return self.ensure_usable(model_id).await;
+        }
+
+        Ok(())
+    }
+
+    /// Fail if the model is registered and not currently verified
+    pub async fn ensure_usable(&self, model_id: &ModelId) -> Result<()> {
+        match self.models.read().await.get(model_id) {
+            Some(status) if status.state != IntegrityState::Verified => Err(Error::Model(format!(
+                "Model {} failed integrity verification ({:?})",
+                model_id, status.state
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Re-hash a single registered model, repairing it on mismatch
+    pub async fn verify_model(&self, model_id: &ModelId) -> Result<IntegrityState> {
+        let (path, expected) = match self.models.read().await.get(model_id) {
+            Some(status) => (status.path.clone(), status.expected_sha256.clone()),
+            None => return Err(Error::Model(format!("Model {} is not registered", model_id))),
+        };
+
+        let state = match sha256_file(self.vfs.as_ref(), &path).await {
+            Ok(actual) if actual == expected => IntegrityState::Verified,
+            Ok(_) => IntegrityState::Corrupted,
+            Err(_) if !self.vfs.exists(&path).await => IntegrityState::Missing,
+            Err(e) => {
+                warn!("Could not hash model {}: {}", model_id, e);
+                IntegrityState::Corrupted
+            },
+        };
+
+        if state == IntegrityState::Verified {
+            if let Some(status) = self.models.write().await.get_mut(model_id) {
+                status.state = IntegrityState::Verified;
+                status.last_verified = Some(Utc::now());
+            }
+            return Ok(state);
+        }
+
+        if let Some(status) = self.models.write().await.get_mut(model_id) {
+            status.state = state;
+        }
+        Ok(self.handle_corruption(model_id).await)
+    }
+
+    /// Verify every registered model
+    pub async fn verify_all(&self) -> Vec<ModelIntegrityStatus> {
+        let ids: Vec<ModelId> = self.models.read().await.keys().cloned().collect();
+        for model_id in &ids {
+            if let Err(e) = self.verify_model(model_id).await {
+                warn!("Integrity check for model {} failed: {}", model_id, e);
+            }
+        }
+        self.report().await
+    }
+
+    /// Current integrity status of all registered models
+    pub async fn report(&self) -> Vec<ModelIntegrityStatus> {
+        self.models.read().await.values().cloned().collect()
+    }
+
+    /// Digest a registered model file is expected to have
+    pub async fn expected_sha256(&self, model_id: &ModelId) -> Option<String> {
+        self.models.read().await.get(model_id).map(|status| status.expected_sha256.clone())
+    }
+
+    /// Render integrity counters into component health metrics
+    pub async fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let models = self.models.read().await;
+        let unhealthy = models
+            .values()
+            .filter(|status| status.state != IntegrityState::Verified)
+            .count();
+        let repairs: u32 = models.values().map(|status| status.repairs).sum();
+        metrics.insert("integrity_tracked_models".to_string(), models.len() as f32);
+        metrics.insert("integrity_unhealthy_models".to_string(), unhealthy as f32);
+        metrics.insert("integrity_repairs_total".to_string(), repairs as f32);
+    }
+
+    /// Raise the alert and attempt to restore the file from the registry
+    async fn handle_corruption(&self, model_id: &ModelId) -> IntegrityState {
+        let (path, expected, state) = {
+            let mut models = self.models.write().await;
+            let Some(status) = models.get_mut(model_id) else {
+                return IntegrityState::Missing;
+            };
+            if status.state == IntegrityState::Verified {
+                status.state = IntegrityState::Corrupted;
+            }
+            (status.path.clone(), status.expected_sha256.clone(), status.state)
+        };
+
+        error!(
+            "ALERT: model {} at {:?} failed integrity verification ({:?}); marking unhealthy",
+            model_id, path, state
+        );
+        events::publish(GatewayEvent::AlertRaised {
+            source: "model_integrity".to_string(),
+            severity: AlertSeverity::Critical,
+            message: format!("Model {} failed integrity verification ({:?})", model_id, state),
+        });
+
+        let Some(registry_url) = self.config.registry_url.as_deref() else {
+            warn!("No model registry configured, model {} cannot be repaired automatically", model_id);
+            return state;
+        };
+
+        match self.redownload(registry_url, &path, &expected).await {
+            Ok(()) => {
+                info!("Model {} restored from registry and verified", model_id);
+                let mut models = self.models.write().await;
+                if let Some(status) = models.get_mut(model_id) {
+                    status.state = IntegrityState::Verified;
+                    status.last_verified = Some(Utc::now());
+                    status.repairs += 1;
+                }
+                IntegrityState::Verified
+            },
+            Err(e) => {
+                error!("ALERT: repair of model {} failed: {}", model_id, e);
+                events::publish(GatewayEvent::AlertRaised {
+                    source: "model_integrity".to_string(),
+                    severity: AlertSeverity::Critical,
+                    message: format!("Repair of model {} failed: {}", model_id, e),
+                });
+                state
+            },
+        }
+    }
+
+    async fn redownload(&self, registry_url: &str, path: &Path, expected: &str) -> Result<()> {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::Model(format!("Invalid model path {:?}", path)))?;
+        let url = format!("{}/{}", registry_url.trim_end_matches('/'), file_name);
+        info!("Re-downloading model file from {}", url);
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(self.config.download_timeout_secs))
+            .build()
+            .map_err(|e| Error::Network(e.to_string()))?;
+        let response = client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::Network(format!("Model download failed: {}", e)))?;
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Network(format!("Model download failed: {}", e)))?;
+        bandwidth::record(Subsystem::ModelDownload, 0, bytes.len() as u64);
+
+        let staging = path.with_extension("download");
+        self.vfs.write(&staging, &bytes).await?;
+
+        let actual = sha256_file(self.vfs.as_ref(), &staging).await?;
+        if actual != expected {
+            let _ = self.vfs.remove(&staging).await;
+            return Err(Error::Model(format!(
+                "Downloaded file checksum {} does not match expected {}",
+                actual, expected
+            )));
+        }
+
+        self.vfs.rename(&staging, path).await?;
+        Ok(())
+    }
+}
+
+impl Drop for ModelIntegrityMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Compute the hex-encoded SHA-256 digest of a file
+pub async fn sha256_file(vfs: &dyn Vfs, path: &Path) -> Result<String> {
+    const CHUNK_SIZE: usize = 1024 * 1024;
+
+    let mut context = Context::new(&SHA256);
+    let mut offset = 0u64;
+    loop {
+        let chunk = vfs.read_at(path, offset, CHUNK_SIZE).await?;
+        context.update(&chunk);
+        offset += chunk.len() as u64;
+        if chunk.len() < CHUNK_SIZE {
+            break;
+        }
+    }
+
+    Ok(context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Read the published checksum from `<file>.sha256`, if present
+async fn read_sidecar(vfs: &dyn Vfs, path: &Path) -> Option<String> {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    let contents = vfs.read(&PathBuf::from(sidecar)).await.ok()?;
+    String::from_utf8_lossy(&contents)
+        .split_whitespace()
+        .next()
+        .map(|digest| digest.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::vfs::MemoryVfs;
+
+    #[tokio::test]
+    async fn test_detects_bit_rot_after_registration() {
+        let vfs = Arc::new(MemoryVfs::new());
+        let path = Path::new("models/tiny.ggml");
+        vfs.write(path, b"model weights").await.unwrap();
+
+        let monitor = ModelIntegrityMonitor::new(ModelIntegrityConfig::default(), vfs.clone());
+        let model_id = "tiny".to_string();
+
+        monitor.register(&model_id, path).await.unwrap();
+        assert_eq!(monitor.verify_model(&model_id).await.unwrap(), IntegrityState::Verified);
+
+        vfs.write(path, b"model weighta").await.unwrap();
+        assert_eq!(monitor.verify_model(&model_id).await.unwrap(), IntegrityState::Corrupted);
+        assert!(monitor.ensure_usable(&model_id).await.is_err());
+
+        let mut metrics = HashMap::new();
+        monitor.write_metrics(&mut metrics).await;
+        assert_eq!(metrics["integrity_unhealthy_models"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_checksum_is_enforced() {
+        let vfs = Arc::new(MemoryVfs::new());
+        let path = Path::new("models/tiny.ggml");
+        vfs.write(path, b"model weights").await.unwrap();
+        vfs.write(Path::new("models/tiny.ggml.sha256"), b"deadbeef  tiny.ggml\n")
+            .await
+            .unwrap();
+
+        let monitor = ModelIntegrityMonitor::new(ModelIntegrityConfig::default(), vfs);
+        assert!(monitor.register(&"tiny".to_string(), path).await.is_err());
+    }
+}
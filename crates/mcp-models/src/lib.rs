@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use mcp_common::metrics::ComponentHealth;
 use mcp_common::{Config, MCPRequest, MCPResponse, ModelId, Result};
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Model engine trait for executing AI models
 #[async_trait]
@@ -15,12 +16,54 @@ pub trait ModelEngine {
         model_id: &ModelId,
     ) -> Result<MCPResponse>;
 
+    /// Process a request, returning generated tokens as they are produced
+    /// followed by the final response; engines that cannot generate
+    /// incrementally stream the finished text
+    async fn process_request_streaming(
+        &self,
+        request: &MCPRequest,
+        model_id: &ModelId,
+    ) -> Result<TokenStream> {
+        let response = self.process_request(request, model_id).await?;
+        Ok(buffered_stream(response, DEFAULT_BUFFER_CHUNKS))
+    }
+
+    /// Loaded models with their license and source provenance
+    async fn list_models(&self) -> Result<Vec<ModelListing>> {
+        Ok(Vec::new())
+    }
+
+    /// Provenance of every model loaded since startup
+    async fn model_provenance(&self) -> Vec<ModelProvenance> {
+        Vec::new()
+    }
+
     /// Load a model into memory
     async fn load_model(&self, model_id: &ModelId) -> Result<()>;
 
     /// Unload a model from memory
     async fn unload_model(&self, model_id: &ModelId) -> Result<()>;
 
+    /// Unload a model that no longer receives requests, such as a replaced
+    /// version, once the requests still running on it finish or
+    /// `drain_timeout` passes
+    async fn retire_model(&self, model_id: &ModelId, drain_timeout: Duration) -> Result<()> {
+        let _ = drain_timeout;
+        self.unload_model(model_id).await
+    }
+
+    /// Run at most `percent` of the configured local inferences at once;
+    /// engines without an inference limit ignore it
+    fn throttle_inference(&self, percent: u8) {
+        let _ = percent;
+    }
+
+    /// Unload models that have served no request for `idle`, returning their ids
+    async fn unload_idle_models(&self, idle: Duration) -> Vec<ModelId> {
+        let _ = idle;
+        Vec::new()
+    }
+
     /// Get health status
     async fn health_check(&self) -> Result<ComponentHealth>;
 
@@ -29,12 +72,60 @@ pub trait ModelEngine {
 }
 
 mod cache;
+mod embedding_batch;
 mod engine;
+mod ensemble;
+mod gguf;
+mod index_maintenance;
+mod ingestion;
+mod integrity;
 mod intelligent_cache;
 mod loaders;
+mod memory_estimate;
 mod performance_optimization;
+mod plugins;
+mod prefix_cache;
+mod provenance;
+mod response_cache;
+mod result_store;
+mod retrieval;
+mod sandbox;
+mod signing;
+mod streaming;
+mod verification;
 
+pub use cache::ModelHandle;
+pub use embedding_batch::{batch_texts, EmbeddingBatcher, EMBEDDING_BATCH_METHOD};
 pub use engine::StandardModelEngine;
+pub use ensemble::{EnsembleReport, ModelContribution, ENSEMBLE_PARAM};
+pub use gguf::{GgufFile, QuantizationInfo, TensorTypeStats};
+pub use index_maintenance::{IndexMaintainer, IndexMaintenanceReport};
+pub use ingestion::{
+    DocumentFormat, IngestReport, IngestRequest, IngestStatus, IngestedSource, IngestionPipeline,
+};
+pub use integrity::{IntegrityState, ModelIntegrityMonitor, ModelIntegrityStatus};
+pub use memory_estimate::{InferenceMemoryGate, MemoryEstimate, MemoryReservation, ModelShape};
+pub use plugins::{
+    read_frame, write_frame, PluginMessage, PluginRunner, PluginState, PluginStatus, PluginSupervisor,
+    PLUGIN_ABI_VERSION, PLUGIN_SOCKET_ENV,
+};
+pub use prefix_cache::SessionPrefixCache;
+pub use provenance::{
+    ModelListing, ModelManifest, ModelManifestEntry, ModelProvenance, ModelProvenanceRegistry,
+    LIST_MODELS_METHOD,
+};
+pub use response_cache::ResponseCache;
+pub use result_store::ResultStore;
+pub use retrieval::{
+    Document, HybridRetriever, IndexStats, PrivacyFilter, RetrievalResult, RetrievalSource,
+    RETRIEVAL_INDEX_METHOD, RETRIEVAL_SEARCH_METHOD,
+};
+pub use signing::{verify_models, ModelSignatureStatus, ModelSignatures};
+pub use streaming::{
+    buffered_stream, splice_stream, token_stream, StreamChunk, TokenStream, DEFAULT_BUFFER_CHUNKS,
+    STREAMING_METHOD,
+};
+pub use verification::{RuleVerifier, VerificationOutcome};
 pub use intelligent_cache::{IntelligentCache, CacheConfig, EvictionAlgorithm};
 pub use performance_optimization::{PerformanceProcessor, BenchmarkResults, MemoryPool, OptimizedMatrix};
 
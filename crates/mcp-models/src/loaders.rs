@@ -1,7 +1,14 @@
 //! Model loaders for different formats
 
+use crate::gguf::{GgufFile, GgufTokenizer, QuantizationInfo};
+use crate::memory_estimate::ModelShape;
+use crate::prefix_cache::SessionPrefixCache;
+use crate::streaming::{send_result_text, send_token, split_tokens, TokenSender, STREAMING_METHOD};
+use crate::verification::response_text;
 use async_trait::async_trait;
-use mcp_common::{ModelFormat, ModelId, Result};
+use mcp_common::stage_timings::{self, Stage};
+use mcp_common::{ModelFormat, ModelId, Result, Vfs};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
@@ -34,6 +41,12 @@ pub struct ModelMetadata {
     pub context_length: u32,
     pub vocab_size: u32,
     pub supported_methods: Vec<String>,
+    /// Per-tensor quantization, for formats that record it
+    #[serde(default)]
+    pub quantization_details: Option<QuantizationInfo>,
+    /// Transformer dimensions, for formats that record them
+    #[serde(default)]
+    pub shape: Option<ModelShape>,
 }
 
 /// Model loader trait for different formats
@@ -52,6 +65,35 @@ pub trait ModelLoader: Send + Sync {
         method: &str,
         params: &serde_json::Value,
     ) -> Result<serde_json::Value>;
+
+    /// Execute inference, sending generated text to `tokens` as it is
+    /// produced; loaders that cannot generate incrementally send it at the end
+    async fn execute_inference_streaming(
+        &self,
+        model: &LoadedModel,
+        method: &str,
+        params: &serde_json::Value,
+        tokens: &TokenSender,
+    ) -> Result<serde_json::Value> {
+        let result = self.execute_inference(model, method, params).await?;
+        send_result_text(tokens, &result).await?;
+        Ok(result)
+    }
+
+    /// Embed several texts in one invocation, returning one embedding result
+    /// per text; loaders that cannot batch embed them one at a time
+    async fn execute_embedding_batch(
+        &self,
+        model: &LoadedModel,
+        texts: &[String],
+    ) -> Result<Vec<serde_json::Value>> {
+        let mut results = Vec::with_capacity(texts.len());
+        for text in texts {
+            let params = serde_json::json!({ "text": text });
+            results.push(self.execute_inference(model, "embedding", &params).await?);
+        }
+        Ok(results)
+    }
     
     /// Check if the loader supports the given model format
     fn supports_format(&self, format: &ModelFormat) -> bool;
@@ -63,6 +105,8 @@ pub trait ModelLoader: Send + Sync {
 /// GGML model loader implementation
 pub struct GGMLModelLoader {
     models: Arc<RwLock<HashMap<ModelId, GGMLModel>>>,
+    vfs: Arc<dyn Vfs>,
+    prefix_cache: Arc<SessionPrefixCache>,
 }
 
 /// Internal GGML model representation
@@ -83,9 +127,11 @@ struct TokenizerConfig {
 }
 
 impl GGMLModelLoader {
-    pub fn new() -> Self {
+    pub fn new(vfs: Arc<dyn Vfs>, prefix_cache: Arc<SessionPrefixCache>) -> Self {
         Self {
             models: Arc::new(RwLock::new(HashMap::new())),
+            vfs,
+            prefix_cache,
         }
     }
 
@@ -99,16 +145,17 @@ impl GGMLModelLoader {
     }
 
     /// Simple detokenizer implementation
-    fn detokenize(&self, tokens: &[u32], _model: &GGMLModel) -> String {
+    fn detokenize(tokens: &[u32]) -> String {
         // Simple demonstration - in reality this would use the model's vocabulary
         format!("Generated text from {} tokens", tokens.len())
     }
 
-    /// Simulate GGML inference
+    /// Simulate GGML inference; GGUF models share it. The first `reused`
+    /// tokens are already in the KV cache and are not evaluated again.
     async fn run_ggml_inference(
-        &self,
-        model: &GGMLModel,
+        metadata: &ModelMetadata,
         tokens: Vec<u32>,
+        reused: usize,
         method: &str,
     ) -> Result<serde_json::Value> {
         let start = Instant::now();
@@ -122,54 +169,47 @@ impl GGMLModelLoader {
             _ => 100,
         };
         
-        let processing_time = base_time + (tokens.len() * 2);
-        tokio::time::sleep(tokio::time::Duration::from_millis(processing_time as u64)).await;
-        
+        // Prompt evaluation, skipping the tokens already in the KV cache,
+        // comes before any output is produced
+        let prefill = Instant::now();
+        let prefill_time = tokens.len().saturating_sub(reused) * 2;
+        tokio::time::sleep(tokio::time::Duration::from_millis(prefill_time as u64)).await;
+        stage_timings::record(Stage::Prefill, prefill.elapsed());
+
+        let decode = Instant::now();
+        tokio::time::sleep(tokio::time::Duration::from_millis(base_time)).await;
+        // Embeddings pool the evaluated prompt rather than generate tokens
+        let stage = if method == "embedding" { Stage::Prefill } else { Stage::Decode };
+        stage_timings::record(stage, decode.elapsed());
+
         let inference_time = start.elapsed().as_millis() as f32;
         
         match method {
             "completion" => {
                 let generated_tokens = (tokens.len() / 2).max(10);
-                let completion_text = self.detokenize(&tokens[..generated_tokens.min(tokens.len())], model);
+                let completion_text = Self::detokenize(&tokens[..generated_tokens.min(tokens.len())]);
                 
                 Ok(serde_json::json!({
                     "text": completion_text,
                     "tokens_generated": generated_tokens,
                     "inference_time_ms": inference_time,
-                    "model": model.metadata.name,
-                    "tokens_processed": tokens.len()
-                }))
-            },
-            "embedding" => {
-                // Generate a realistic embedding vector
-                let dimensions = 384;
-                let embedding: Vec<f32> = (0..dimensions)
-                    .map(|i| {
-                        let hash = (tokens.iter().sum::<u32>() as f32 + i as f32) / 1000.0;
-                        (hash.sin() * 0.5).clamp(-1.0, 1.0)
-                    })
-                    .collect();
-                
-                Ok(serde_json::json!({
-                    "embedding": embedding,
-                    "dimensions": dimensions,
-                    "inference_time_ms": inference_time,
-                    "model": model.metadata.name,
+                    "model": metadata.name,
                     "tokens_processed": tokens.len()
                 }))
             },
+            "embedding" => Ok(Self::embedding_result(metadata, &tokens, inference_time)),
             "chat" => {
                 let response_tokens = (tokens.len() / 3).max(20);
                 let response_text = format!(
                     "AI Assistant response generated by {} model (processed {} tokens, generated {} response tokens)",
-                    model.metadata.name, tokens.len(), response_tokens
+                    metadata.name, tokens.len(), response_tokens
                 );
                 
                 Ok(serde_json::json!({
                     "response": response_text,
                     "tokens_generated": response_tokens,
                     "inference_time_ms": inference_time,
-                    "model": model.metadata.name,
+                    "model": metadata.name,
                     "tokens_processed": tokens.len(),
                     "finish_reason": "stop"
                 }))
@@ -184,7 +224,7 @@ impl GGMLModelLoader {
                     "summary_tokens": summary_tokens,
                     "compression_ratio": compression_ratio,
                     "inference_time_ms": inference_time,
-                    "model": model.metadata.name
+                    "model": metadata.name
                 }))
             },
             _ => {
@@ -194,6 +234,129 @@ impl GGMLModelLoader {
         }
     }
 
+    /// Embedding of a token sequence
+    fn embedding_result(
+        metadata: &ModelMetadata,
+        tokens: &[u32],
+        inference_time: f32,
+    ) -> serde_json::Value {
+        // Generate a realistic embedding vector
+        let dimensions = 384;
+        let embedding: Vec<f32> = (0..dimensions)
+            .map(|i| {
+                let hash = (tokens.iter().sum::<u32>() as f32 + i as f32) / 1000.0;
+                (hash.sin() * 0.5).clamp(-1.0, 1.0)
+            })
+            .collect();
+
+        serde_json::json!({
+            "embedding": embedding,
+            "dimensions": dimensions,
+            "inference_time_ms": inference_time,
+            "model": metadata.name,
+            "tokens_processed": tokens.len()
+        })
+    }
+
+    /// Simulate embedding several token sequences in one invocation, which
+    /// pays the fixed cost of a model call once
+    async fn run_ggml_embedding_batch(
+        metadata: &ModelMetadata,
+        batch: Vec<Vec<u32>>,
+    ) -> Vec<serde_json::Value> {
+        let start = Instant::now();
+        let total_tokens: usize = batch.iter().map(Vec::len).sum();
+        let processing_time = 20 + total_tokens * 2;
+        tokio::time::sleep(tokio::time::Duration::from_millis(processing_time as u64)).await;
+
+        let inference_time = start.elapsed().as_millis() as f32;
+        batch
+            .iter()
+            .map(|tokens| Self::embedding_result(metadata, tokens, inference_time))
+            .collect()
+    }
+
+    /// Simulate GGML completion, sending each token as it is generated
+    async fn stream_ggml_completion(
+        metadata: &ModelMetadata,
+        tokens: Vec<u32>,
+        reused: usize,
+        sender: &TokenSender,
+    ) -> Result<serde_json::Value> {
+        let start = Instant::now();
+
+        // Prompt evaluation happens before the first token, skipping the
+        // tokens already in the KV cache
+        let evaluated = tokens.len().saturating_sub(reused);
+        let prompt_ms = 50 * evaluated as u64 / tokens.len().max(1) as u64;
+        tokio::time::sleep(tokio::time::Duration::from_millis(prompt_ms)).await;
+
+        let generated_tokens = (tokens.len() / 2).max(10);
+        let completion_text = Self::detokenize(&tokens[..generated_tokens.min(tokens.len())]);
+        let pieces = split_tokens(&completion_text);
+        let per_token = tokio::time::Duration::from_millis((tokens.len() * 2 / pieces.len().max(1)) as u64);
+        let mut first_token_ms = 0.0;
+        for (index, piece) in pieces.iter().enumerate() {
+            tokio::time::sleep(per_token).await;
+            send_token(sender, index, piece).await?;
+            if index == 0 {
+                first_token_ms = start.elapsed().as_millis() as f32;
+            }
+        }
+
+        Ok(serde_json::json!({
+            "text": completion_text,
+            "tokens_generated": generated_tokens,
+            "inference_time_ms": start.elapsed().as_millis() as f32,
+            "time_to_first_token_ms": first_token_ms,
+            "model": metadata.name,
+            "tokens_processed": tokens.len()
+        }))
+    }
+
+    /// Text a method runs on
+    fn input_text<'a>(method: &str, params: &'a serde_json::Value) -> Result<&'a str> {
+        // Extract input text based on method
+        Ok(match method {
+            "completion" => params.get("prompt")
+                .and_then(|v| v.as_str())
+                .unwrap_or(""),
+            "embedding" => params.get("text")
+                .and_then(|v| v.as_str())
+                .unwrap_or(""),
+            "chat" => params.get("messages")
+                .and_then(|v| v.as_array())
+                .and_then(|arr| arr.last())
+                .and_then(|msg| msg.get("content"))
+                .and_then(|v| v.as_str())
+                .unwrap_or(""),
+            "summarization" => params.get("text")
+                .and_then(|v| v.as_str())
+                .unwrap_or(""),
+            _ => {
+                return Err(mcp_common::Error::Model(format!(
+                    "Unsupported method: {}",
+                    method
+                )));
+            }
+        })
+    }
+
+    /// Text a session turn is evaluated over; chats include the whole
+    /// conversation so later turns share its prefix
+    fn context_text<'a>(method: &str, params: &'a serde_json::Value) -> Result<Cow<'a, str>> {
+        if method != "chat" {
+            return Self::input_text(method, params).map(Cow::Borrowed);
+        }
+        let messages = params.get("messages").and_then(|v| v.as_array());
+        let contents: Vec<&str> = messages
+            .into_iter()
+            .flatten()
+            .filter_map(|message| message.get("content")?.as_str())
+            .collect();
+        Ok(Cow::Owned(contents.join("\n")))
+    }
+
     /// Load model metadata from file
     async fn load_model_metadata(&self, path: &Path) -> Result<ModelMetadata> {
         debug!("Loading model metadata from {:?}", path);
@@ -234,6 +397,8 @@ impl GGMLModelLoader {
                 "chat".to_string(),
                 "summarization".to_string(),
             ],
+            quantization_details: None,
+            shape: None,
         };
         
         Ok(metadata)
@@ -245,7 +410,7 @@ impl ModelLoader for GGMLModelLoader {
     async fn load(&self, model_id: &ModelId, path: &Path) -> Result<LoadedModel> {
         info!("Loading GGML model {} from {:?}", model_id, path);
         
-        if !path.exists() {
+        if !self.vfs.exists(path).await {
             return Err(mcp_common::Error::Model(format!(
                 "Model file not found: {:?}",
                 path
@@ -256,9 +421,8 @@ impl ModelLoader for GGMLModelLoader {
         let metadata = self.load_model_metadata(path).await?;
         
         // Simulate loading model weights
-        let model_size = tokio::fs::metadata(path).await
-            .map_err(|e| mcp_common::Error::Model(format!("Failed to read model file: {}", e)))?
-            .len();
+        let model_size = self.vfs.size(path).await
+            .map_err(|e| mcp_common::Error::Model(format!("Failed to read model file: {}", e)))?;
         
         debug!("Model file size: {} bytes", model_size);
         
@@ -315,6 +479,7 @@ impl ModelLoader for GGMLModelLoader {
     async fn unload(&self, model: &LoadedModel) -> Result<()> {
         info!("Unloading GGML model {}", model.id);
         
+        self.prefix_cache.remove_model(&model.id);
         let mut models = self.models.write().await;
         if models.remove(&model.id).is_some() {
             info!("Successfully unloaded model {}", model.id);
@@ -337,36 +502,41 @@ impl ModelLoader for GGMLModelLoader {
         let ggml_model = models.get(&model.id)
             .ok_or_else(|| mcp_common::Error::Model(format!("Model {} not loaded", model.id)))?;
         
-        // Extract input text based on method
-        let input_text = match method {
-            "completion" => params.get("prompt")
-                .and_then(|v| v.as_str())
-                .unwrap_or(""),
-            "embedding" => params.get("text")
-                .and_then(|v| v.as_str())
-                .unwrap_or(""),
-            "chat" => params.get("messages")
-                .and_then(|v| v.as_array())
-                .and_then(|arr| arr.last())
-                .and_then(|msg| msg.get("content"))
-                .and_then(|v| v.as_str())
-                .unwrap_or(""),
-            "summarization" => params.get("text")
-                .and_then(|v| v.as_str())
-                .unwrap_or(""),
-            _ => {
-                return Err(mcp_common::Error::Model(format!(
-                    "Unsupported method: {}",
-                    method
-                )));
-            }
-        };
-        
-        // Tokenize input
-        let tokens = self.tokenize(input_text, ggml_model);
-        
-        // Run inference
-        self.run_ggml_inference(ggml_model, tokens, method).await
+        let tokenize = |text: &str| Ok(self.tokenize(text, ggml_model));
+        run_turn(&self.prefix_cache, model, method, params, tokenize, None).await
+    }
+
+    async fn execute_embedding_batch(
+        &self,
+        model: &LoadedModel,
+        texts: &[String],
+    ) -> Result<Vec<serde_json::Value>> {
+        debug!("Embedding a batch of {} texts with model {}", texts.len(), model.id);
+        let models = self.models.read().await;
+        let ggml_model = models.get(&model.id)
+            .ok_or_else(|| mcp_common::Error::Model(format!("Model {} not loaded", model.id)))?;
+        let batch = texts.iter().map(|text| self.tokenize(text, ggml_model)).collect();
+        Ok(GGMLModelLoader::run_ggml_embedding_batch(&model.metadata, batch).await)
+    }
+
+    async fn execute_inference_streaming(
+        &self,
+        model: &LoadedModel,
+        method: &str,
+        params: &serde_json::Value,
+        tokens: &TokenSender,
+    ) -> Result<serde_json::Value> {
+        if method != STREAMING_METHOD {
+            let result = self.execute_inference(model, method, params).await?;
+            send_result_text(tokens, &result).await?;
+            return Ok(result);
+        }
+
+        let models = self.models.read().await;
+        let ggml_model = models.get(&model.id)
+            .ok_or_else(|| mcp_common::Error::Model(format!("Model {} not loaded", model.id)))?;
+        let tokenize = |text: &str| Ok(self.tokenize(text, ggml_model));
+        run_turn(&self.prefix_cache, model, method, params, tokenize, Some(tokens)).await
     }
     
     fn supports_format(&self, format: &ModelFormat) -> bool {
@@ -374,16 +544,15 @@ impl ModelLoader for GGMLModelLoader {
     }
     
     async fn estimate_memory_usage(&self, path: &Path) -> Result<u32> {
-        if !path.exists() {
+        if !self.vfs.exists(path).await {
             return Err(mcp_common::Error::Model(format!(
                 "Model file not found: {:?}",
                 path
             )));
         }
         
-        let file_size = tokio::fs::metadata(path).await
-            .map_err(|e| mcp_common::Error::Model(format!("Failed to read model file: {}", e)))?
-            .len();
+        let file_size = self.vfs.size(path).await
+            .map_err(|e| mcp_common::Error::Model(format!("Failed to read model file: {}", e)))?;
         
         // Estimate memory usage: file size + 20% overhead for runtime structures
         let memory_mb = ((file_size / 1_000_000) as f32 * 1.2) as u32;
@@ -391,21 +560,259 @@ impl ModelLoader for GGMLModelLoader {
     }
 }
 
+/// GGUF model loader; weights stay memory-mapped and run on the GGML runtime
+pub struct GgufModelLoader {
+    models: Arc<RwLock<HashMap<ModelId, Arc<GgufModel>>>>,
+    vfs: Arc<dyn Vfs>,
+    prefix_cache: Arc<SessionPrefixCache>,
+}
+
+struct GgufModel {
+    _file: GgufFile, // Keeps the weights mapped
+    tokenizer: GgufTokenizer,
+    metadata: ModelMetadata,
+}
+
+impl GgufModelLoader {
+    pub fn new(vfs: Arc<dyn Vfs>, prefix_cache: Arc<SessionPrefixCache>) -> Self {
+        Self {
+            models: Arc::new(RwLock::new(HashMap::new())),
+            vfs,
+            prefix_cache,
+        }
+    }
+
+    /// Map the file when it lives on the host, otherwise read it through the VFS
+    async fn open(&self, path: &Path) -> Result<GgufFile> {
+        match self.vfs.host_path(path) {
+            Some(host_path) => tokio::task::spawn_blocking(move || GgufFile::open(&host_path))
+                .await
+                .map_err(|e| mcp_common::Error::Model(format!("GGUF loader task failed: {}", e)))?,
+            None => GgufFile::from_bytes(self.vfs.read(path).await?),
+        }
+    }
+
+    async fn model(&self, model_id: &ModelId) -> Result<Arc<GgufModel>> {
+        self.models.read().await.get(model_id).cloned()
+            .ok_or_else(|| mcp_common::Error::Model(format!("Model {} not loaded", model_id)))
+    }
+
+    /// Tokenize a method's input, refusing prompts longer than the context window
+    fn tokenize(model: &GgufModel, text: &str) -> Result<Vec<u32>> {
+        let tokens = model.tokenizer.tokenize(text);
+        if tokens.len() > model.metadata.context_length as usize {
+            return Err(mcp_common::Error::Model(format!(
+                "Input of {} tokens exceeds the {} token context of {}",
+                tokens.len(),
+                model.metadata.context_length,
+                model.metadata.name
+            )));
+        }
+        Ok(tokens)
+    }
+}
+
+#[async_trait]
+impl ModelLoader for GgufModelLoader {
+    async fn load(&self, model_id: &ModelId, path: &Path) -> Result<LoadedModel> {
+        info!("Loading GGUF model {} from {:?}", model_id, path);
+
+        if !self.vfs.exists(path).await {
+            return Err(mcp_common::Error::Model(format!(
+                "Model file not found: {:?}",
+                path
+            )));
+        }
+
+        let file = self.open(path).await?;
+        let tokenizer = GgufTokenizer::new(&file);
+        let quantization = file.quantization();
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown");
+        let metadata = ModelMetadata {
+            name: file.name().unwrap_or(stem).to_string(),
+            version: format!("gguf-v{}", file.version),
+            description: format!(
+                "{} model loaded from {}",
+                file.architecture().unwrap_or("unknown"),
+                stem
+            ),
+            parameters: file.parameter_count(),
+            quantization: quantization.file_type.clone()
+                .unwrap_or_else(|| quantization.predominant_type.clone()),
+            context_length: file.context_length().unwrap_or(2048).min(u32::MAX as u64) as u32,
+            vocab_size: file.tokens().len() as u32,
+            supported_methods: vec![
+                "completion".to_string(),
+                "embedding".to_string(),
+                "chat".to_string(),
+                "summarization".to_string(),
+            ],
+            quantization_details: Some(quantization),
+            shape: file.shape(),
+        };
+
+        // Mapped weights are paged in on demand and are not copied to the heap
+        let memory_usage_mb = (file.file_size() / 1_000_000) as u32 + 50;
+        debug!(
+            "GGUF model {}: {} tensors, {} parameters, {} ({})",
+            model_id,
+            file.tensors.len(),
+            metadata.parameters,
+            metadata.quantization,
+            if file.is_mapped() { "memory-mapped" } else { "in memory" }
+        );
+
+        let loaded_model = LoadedModel {
+            id: model_id.clone(),
+            format: ModelFormat::GGUF,
+            metadata: metadata.clone(),
+            memory_usage_mb,
+            last_used: chrono::Utc::now(),
+            execution_count: 0,
+            load_time: chrono::Utc::now(),
+            average_inference_time_ms: 0.0,
+        };
+        self.models.write().await.insert(model_id.clone(), Arc::new(GgufModel {
+            _file: file,
+            tokenizer,
+            metadata,
+        }));
+
+        info!("Successfully loaded GGUF model {} ({}MB)", model_id, memory_usage_mb);
+        Ok(loaded_model)
+    }
+
+    async fn unload(&self, model: &LoadedModel) -> Result<()> {
+        info!("Unloading GGUF model {}", model.id);
+        self.prefix_cache.remove_model(&model.id);
+        // Dropping the last reference unmaps the file
+        if self.models.write().await.remove(&model.id).is_none() {
+            warn!("Model {} was not loaded", model.id);
+        }
+        Ok(())
+    }
+
+    async fn execute_inference(
+        &self,
+        model: &LoadedModel,
+        method: &str,
+        params: &serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        debug!("Executing {} inference with GGUF model {}", method, model.id);
+        let gguf_model = self.model(&model.id).await?;
+        let tokenize = |text: &str| Self::tokenize(&gguf_model, text);
+        run_turn(&self.prefix_cache, model, method, params, tokenize, None).await
+    }
+
+    async fn execute_embedding_batch(
+        &self,
+        model: &LoadedModel,
+        texts: &[String],
+    ) -> Result<Vec<serde_json::Value>> {
+        debug!("Embedding a batch of {} texts with GGUF model {}", texts.len(), model.id);
+        let gguf_model = self.model(&model.id).await?;
+        let batch = texts
+            .iter()
+            .map(|text| Self::tokenize(&gguf_model, text))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(GGMLModelLoader::run_ggml_embedding_batch(&model.metadata, batch).await)
+    }
+
+    async fn execute_inference_streaming(
+        &self,
+        model: &LoadedModel,
+        method: &str,
+        params: &serde_json::Value,
+        tokens: &TokenSender,
+    ) -> Result<serde_json::Value> {
+        if method != STREAMING_METHOD {
+            let result = self.execute_inference(model, method, params).await?;
+            send_result_text(tokens, &result).await?;
+            return Ok(result);
+        }
+
+        let gguf_model = self.model(&model.id).await?;
+        let tokenize = |text: &str| Self::tokenize(&gguf_model, text);
+        run_turn(&self.prefix_cache, model, method, params, tokenize, Some(tokens)).await
+    }
+
+    fn supports_format(&self, format: &ModelFormat) -> bool {
+        matches!(format, ModelFormat::GGUF)
+    }
+
+    async fn estimate_memory_usage(&self, path: &Path) -> Result<u32> {
+        let file_size = self.vfs.size(path).await
+            .map_err(|e| mcp_common::Error::Model(format!("Failed to read model file: {}", e)))?;
+
+        // Weights are mapped rather than copied, so only runtime structures add to the file size
+        Ok((file_size / 1_000_000) as u32 + 50)
+    }
+}
+
+/// Run a method on the GGML runtime. Session turns are evaluated over their
+/// whole conversation, skipping the prefix the session's previous turn left
+/// in the KV cache.
+async fn run_turn(
+    prefix_cache: &SessionPrefixCache,
+    model: &LoadedModel,
+    method: &str,
+    params: &serde_json::Value,
+    tokenize: impl Fn(&str) -> Result<Vec<u32>>,
+    sender: Option<&TokenSender>,
+) -> Result<serde_json::Value> {
+    let metadata = &model.metadata;
+    let run = |tokens: Vec<u32>, reused: usize| async move {
+        match sender {
+            Some(sender) => {
+                GGMLModelLoader::stream_ggml_completion(metadata, tokens, reused, sender).await
+            },
+            None => GGMLModelLoader::run_ggml_inference(metadata, tokens, reused, method).await,
+        }
+    };
+    let Some(session) = prefix_cache.session(method, params) else {
+        return run(tokenize(GGMLModelLoader::input_text(method, params)?)?, 0).await;
+    };
+
+    let context = GGMLModelLoader::context_text(method, params)?;
+    let tokens = tokenize(&context)?;
+    let held = tokens.len() as u64;
+    let reused = prefix_cache.lookup(&model.id, session, &context, |prefix| {
+        tokenize(prefix).map_or(0, |tokens| tokens.len())
+    });
+    let mut result = run(tokens, reused as usize).await?;
+
+    // The reply stays in the cache; the next turn repeats it as a message
+    let generated = result.get("tokens_generated").and_then(|v| v.as_u64()).unwrap_or(0);
+    let separator = if method == "chat" { "\n" } else { "" };
+    let text = format!("{}{}{}", context, separator, response_text(&result).unwrap_or_default());
+    let bytes_per_token = ModelShape::of(metadata).kv_bytes_per_token();
+    prefix_cache.store(&model.id, session, text, held + generated, bytes_per_token);
+    if let Some(fields) = result.as_object_mut() {
+        fields.insert("tokens_reused".to_string(), reused.into());
+    }
+    Ok(result)
+}
+
 /// Factory function to create appropriate model loader
-pub fn create_model_loader(format: &ModelFormat) -> Result<Box<dyn ModelLoader>> {
+pub fn create_model_loader(
+    format: &ModelFormat,
+    vfs: Arc<dyn Vfs>,
+    prefix_cache: Arc<SessionPrefixCache>,
+) -> Result<Box<dyn ModelLoader>> {
     match format {
-        ModelFormat::GGML => Ok(Box::new(GGMLModelLoader::new())),
+        ModelFormat::GGML => Ok(Box::new(GGMLModelLoader::new(vfs, prefix_cache))),
+        ModelFormat::GGUF => Ok(Box::new(GgufModelLoader::new(vfs, prefix_cache))),
         ModelFormat::ONNX => {
             warn!("ONNX support not implemented, falling back to GGML");
-            Ok(Box::new(GGMLModelLoader::new()))
+            Ok(Box::new(GGMLModelLoader::new(vfs, prefix_cache)))
         },
         ModelFormat::TensorFlowLite => {
             warn!("TensorFlow Lite support not implemented, falling back to GGML");
-            Ok(Box::new(GGMLModelLoader::new()))
+            Ok(Box::new(GGMLModelLoader::new(vfs, prefix_cache)))
         },
         ModelFormat::Custom(_) => {
             warn!("Custom model format not implemented, falling back to GGML");
-            Ok(Box::new(GGMLModelLoader::new()))
+            Ok(Box::new(GGMLModelLoader::new(vfs, prefix_cache)))
         },
     }
 }
@@ -0,0 +1,481 @@
+//! Inference memory estimates and admission
+//!
+//! Model weights are budgeted when a model is loaded, but generation also
+//! needs a KV cache that grows with every token and activations for the
+//! prompt batch. Both are estimated from the prompt length, the requested
+//! tokens and the model's transformer shape before an inference starts, and
+//! the estimate is reserved against the memory the device has available.
+//! Requests that do not fit wait for running inferences to release theirs,
+//! then are rejected instead of running out of memory mid-generation. The
+//! measured growth of resident memory is compared with each estimate to
+//! report how accurate the estimator is.
+
+use crate::embedding_batch::EMBEDDING_BATCH_METHOD;
+use crate::loaders::ModelMetadata;
+use mcp_common::config::InferenceMemoryConfig;
+use mcp_common::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+
+/// Characters per token assumed before the prompt is tokenized
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Prompt tokens evaluated at once, which bounds activation memory
+const PROMPT_BATCH_TOKENS: u64 = 512;
+
+/// Bytes per KV cache element (f16)
+const KV_BYTES: u64 = 2;
+
+/// Bytes per activation element (f32)
+const ACTIVATION_BYTES: u64 = 4;
+
+/// Hidden-sized activation buffers alive per batched token: residual stream,
+/// normalized input, queries, keys, values, attention output and the wider
+/// feed-forward intermediates
+const ACTIVATION_WIDTH: u64 = 8;
+
+/// Hidden size per layer typical of decoder-only models, used to derive a
+/// shape from the parameter count
+const HIDDEN_PER_LAYER: f64 = 128.0;
+
+/// How often a waiting request checks again for memory freed outside the gateway
+const WAIT_POLL: Duration = Duration::from_millis(100);
+
+const MB: u64 = 1024 * 1024;
+
+/// Transformer dimensions of a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelShape {
+    pub layers: u32,
+    pub embedding: u32,
+    pub heads: u32,
+    /// Heads with their own keys and values; fewer than `heads` with
+    /// grouped-query attention
+    pub kv_heads: u32,
+}
+
+impl ModelShape {
+    /// Shape of a decoder-only model with `parameters` weights, for formats
+    /// that do not record one; each layer holds about 12 × hidden² weights
+    pub fn from_parameters(parameters: u64) -> Self {
+        let embedding = (parameters as f64 * HIDDEN_PER_LAYER / 12.0)
+            .cbrt()
+            .max(64.0);
+        let embedding = ((embedding / 64.0).round() as u32).max(1) * 64;
+        let heads = (embedding / 128).max(1);
+        Self {
+            layers: ((f64::from(embedding) / HIDDEN_PER_LAYER).round() as u32).max(1),
+            embedding,
+            heads,
+            kv_heads: heads,
+        }
+    }
+
+    /// Shape a model's file records, otherwise one derived from its size
+    pub fn of(metadata: &ModelMetadata) -> Self {
+        metadata
+            .shape
+            .unwrap_or_else(|| Self::from_parameters(metadata.parameters))
+    }
+
+    /// KV cache held for each token: keys and values for every layer
+    pub fn kv_bytes_per_token(&self) -> u64 {
+        let kv_dim = u64::from(self.embedding) * u64::from(self.kv_heads) / u64::from(self.heads.max(1));
+        2 * u64::from(self.layers) * kv_dim * KV_BYTES
+    }
+}
+
+/// Memory an inference is expected to need on top of the model weights
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MemoryEstimate {
+    pub prompt_tokens: u64,
+    /// Prompt and generated tokens held in the KV cache
+    pub total_tokens: u64,
+    pub kv_cache_bytes: u64,
+    pub activation_bytes: u64,
+}
+
+impl MemoryEstimate {
+    /// Estimate a request's footprint from its prompt and `max_tokens`
+    pub fn for_request(
+        metadata: &ModelMetadata,
+        method: &str,
+        params: &HashMap<String, serde_json::Value>,
+        default_max_tokens: u32,
+    ) -> Self {
+        let prompt_tokens = (input_chars(method, params) / CHARS_PER_TOKEN).max(1) as u64;
+        let generated = match method {
+            "embedding" | EMBEDDING_BATCH_METHOD => 0,
+            _ => params
+                .get("max_tokens")
+                .and_then(|value| value.as_u64())
+                .unwrap_or(u64::from(default_max_tokens)),
+        };
+        Self::for_tokens(
+            &ModelShape::of(metadata),
+            prompt_tokens,
+            generated,
+            u64::from(metadata.context_length),
+        )
+    }
+
+    pub fn for_tokens(
+        shape: &ModelShape,
+        prompt_tokens: u64,
+        generated: u64,
+        context_length: u64,
+    ) -> Self {
+        let total_tokens = prompt_tokens
+            .saturating_add(generated)
+            .min(context_length.max(1));
+        let prompt_tokens = prompt_tokens.min(total_tokens);
+        let embedding = u64::from(shape.embedding);
+        let kv_cache_bytes = total_tokens * shape.kv_bytes_per_token();
+        // One layer's buffers for a prompt batch, plus its attention scores
+        let batch = prompt_tokens.min(PROMPT_BATCH_TOKENS);
+        let activation_bytes = batch * embedding * ACTIVATION_WIDTH * ACTIVATION_BYTES
+            + u64::from(shape.heads) * batch * total_tokens * ACTIVATION_BYTES;
+        Self {
+            prompt_tokens,
+            total_tokens,
+            kv_cache_bytes,
+            activation_bytes,
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.kv_cache_bytes + self.activation_bytes
+    }
+}
+
+/// Characters of a method's input; chats keep the whole conversation in the cache
+fn input_chars(method: &str, params: &HashMap<String, serde_json::Value>) -> usize {
+    let text = |key: &str| {
+        params
+            .get(key)
+            .and_then(|value| value.as_str())
+            .map_or(0, str::len)
+    };
+    match method {
+        "chat" => params
+            .get("messages")
+            .and_then(|messages| messages.as_array())
+            .map_or(0, |messages| {
+                messages
+                    .iter()
+                    .filter_map(|message| message.get("content")?.as_str())
+                    .map(str::len)
+                    .sum()
+            }),
+        "completion" => text("prompt"),
+        EMBEDDING_BATCH_METHOD => params
+            .get("texts")
+            .and_then(|texts| texts.as_array())
+            .map_or(0, |texts| {
+                texts
+                    .iter()
+                    .filter_map(|text| text.as_str())
+                    .map(str::len)
+                    .sum()
+            }),
+        _ => text("text").max(text("prompt")),
+    }
+}
+
+/// Device memory available to new allocations, in bytes
+pub fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let kb: u64 = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[derive(Debug, Default)]
+struct Accuracy {
+    measured: u64,
+    /// Sum of |actual - estimate| / estimate
+    error_sum: f64,
+    /// Sum of actual / estimate
+    ratio_sum: f64,
+}
+
+/// Reserves estimated inference memory against what the device has available
+pub struct InferenceMemoryGate {
+    config: InferenceMemoryConfig,
+    available: Box<dyn Fn() -> Option<u64> + Send + Sync>,
+    reserved: Mutex<u64>,
+    released: Notify,
+    rejected: AtomicU64,
+    accuracy: Mutex<Accuracy>,
+}
+
+impl InferenceMemoryGate {
+    pub fn new(config: InferenceMemoryConfig) -> Self {
+        Self::with_available(config, available_memory)
+    }
+
+    /// Gate reading available memory from `available`
+    pub fn with_available(
+        config: InferenceMemoryConfig,
+        available: impl Fn() -> Option<u64> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            config,
+            available: Box::new(available),
+            reserved: Mutex::new(0),
+            released: Notify::new(),
+            rejected: AtomicU64::new(0),
+            accuracy: Mutex::new(Accuracy::default()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Reserve an inference's estimated memory, waiting up to `wait_ms` for
+    /// running inferences to release theirs; `None` when the gate is disabled
+    pub async fn reserve(
+        self: &Arc<Self>,
+        estimate: &MemoryEstimate,
+    ) -> Result<Option<MemoryReservation>> {
+        if !self.config.enabled {
+            return Ok(None);
+        }
+        let bytes = estimate.total_bytes();
+        let deadline = Instant::now() + Duration::from_millis(self.config.wait_ms);
+        loop {
+            let released = self.released.notified();
+            let (fits, free) = self.try_reserve(bytes);
+            if fits {
+                debug!(
+                    "Reserved {}MB for an inference of {} tokens",
+                    bytes / MB,
+                    estimate.total_tokens
+                );
+                return Ok(Some(MemoryReservation {
+                    gate: self.clone(),
+                    bytes,
+                }));
+            }
+            // Nothing this gateway runs will free enough for it
+            let running = *self.lock_reserved();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || free.saturating_add(running) < bytes {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "Rejecting inference of {} tokens: needs an estimated {}MB, {}MB available",
+                    estimate.total_tokens,
+                    bytes / MB,
+                    free / MB
+                );
+                return Err(Error::ResourceExhausted(format!(
+                    "Inference of {} tokens needs an estimated {}MB but only {}MB is available",
+                    estimate.total_tokens,
+                    bytes.div_ceil(MB),
+                    free / MB
+                )));
+            }
+            let _ = tokio::time::timeout(remaining.min(WAIT_POLL), released).await;
+        }
+    }
+
+    /// Reserve `bytes` if they fit; returns whether they did and the memory
+    /// that was free for them
+    fn try_reserve(&self, bytes: u64) -> (bool, u64) {
+        let mut reserved = self.lock_reserved();
+        // Admit everything when the platform does not report its memory
+        let Some(available) = (self.available)() else {
+            *reserved += bytes;
+            return (true, u64::MAX);
+        };
+        // Running inferences may not have allocated all they reserved yet
+        let free = available
+            .saturating_sub(self.config.reserve_mb * MB)
+            .saturating_sub(*reserved);
+        if bytes <= free {
+            *reserved += bytes;
+        }
+        (bytes <= free, free)
+    }
+
+    fn release(&self, bytes: u64) {
+        let mut reserved = self.lock_reserved();
+        *reserved = reserved.saturating_sub(bytes);
+        drop(reserved);
+        self.released.notify_waiters();
+    }
+
+    /// Compare an estimate with the memory the inference was measured to use
+    pub fn record_actual(&self, estimate: &MemoryEstimate, actual_bytes: u64) {
+        let estimated = estimate.total_bytes();
+        // Platforms without memory accounting measure nothing
+        if !self.config.enabled || estimated == 0 || actual_bytes == 0 {
+            return;
+        }
+        let ratio = actual_bytes as f64 / estimated as f64;
+        let mut accuracy = self
+            .accuracy
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        accuracy.measured += 1;
+        accuracy.error_sum += (ratio - 1.0).abs();
+        accuracy.ratio_sum += ratio;
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        if !self.config.enabled {
+            return;
+        }
+        let reserved = *self.lock_reserved();
+        metrics.insert(
+            "inference_memory_reserved_mb".to_string(),
+            (reserved / MB) as f32,
+        );
+        metrics.insert(
+            "inference_memory_rejected".to_string(),
+            self.rejected.load(Ordering::Relaxed) as f32,
+        );
+        let accuracy = self
+            .accuracy
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        metrics.insert(
+            "inference_memory_estimates_measured".to_string(),
+            accuracy.measured as f32,
+        );
+        if accuracy.measured > 0 {
+            let measured = accuracy.measured as f64;
+            metrics.insert(
+                "inference_memory_estimate_error_percent".to_string(),
+                (accuracy.error_sum / measured * 100.0) as f32,
+            );
+            metrics.insert(
+                "inference_memory_actual_to_estimate_ratio".to_string(),
+                (accuracy.ratio_sum / measured) as f32,
+            );
+        }
+    }
+
+    fn lock_reserved(&self) -> std::sync::MutexGuard<'_, u64> {
+        self.reserved
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Memory reserved for a running inference, released when dropped
+pub struct MemoryReservation {
+    gate: Arc<InferenceMemoryGate>,
+    bytes: u64,
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.gate.release(self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LLAMA_7B: ModelShape = ModelShape {
+        layers: 32,
+        embedding: 4096,
+        heads: 32,
+        kv_heads: 32,
+    };
+
+    #[test]
+    fn test_estimate_matches_known_kv_cache_sizes() {
+        // A full 2048 token context of a 7B llama takes 1GiB of f16 keys and values
+        let full = MemoryEstimate::for_tokens(&LLAMA_7B, 1024, 4096, 2048);
+        assert_eq!(full.total_tokens, 2048);
+        assert_eq!(full.kv_cache_bytes, 1024 * MB);
+        // Grouped-query attention with 8 KV heads needs a quarter of that
+        let gqa = ModelShape {
+            kv_heads: 8,
+            ..LLAMA_7B
+        };
+        assert_eq!(
+            MemoryEstimate::for_tokens(&gqa, 1024, 4096, 2048).kv_cache_bytes,
+            256 * MB
+        );
+
+        // Shapes derived from the parameter count land near the real ones
+        let derived = ModelShape::from_parameters(6_740_000_000);
+        assert!((3840..=4352).contains(&derived.embedding), "{:?}", derived);
+        assert!((28..=36).contains(&derived.layers), "{:?}", derived);
+
+        let params = HashMap::from([
+            ("prompt".to_string(), serde_json::json!("x".repeat(400))),
+            ("max_tokens".to_string(), serde_json::json!(28)),
+        ]);
+        let metadata = ModelMetadata {
+            name: "llama".to_string(),
+            version: "1".to_string(),
+            description: String::new(),
+            parameters: 6_740_000_000,
+            quantization: "Q4_0".to_string(),
+            context_length: 2048,
+            vocab_size: 32000,
+            supported_methods: vec!["completion".to_string()],
+            quantization_details: None,
+            shape: Some(LLAMA_7B),
+        };
+        let estimate = MemoryEstimate::for_request(&metadata, "completion", &params, 256);
+        assert_eq!((estimate.prompt_tokens, estimate.total_tokens), (100, 128));
+        assert_eq!(estimate.kv_cache_bytes, 64 * MB);
+    }
+
+    #[tokio::test]
+    async fn test_gate_waits_for_running_inferences_then_rejects() {
+        let config = InferenceMemoryConfig {
+            enabled: true,
+            reserve_mb: 100,
+            default_max_tokens: 256,
+            wait_ms: 200,
+        };
+        let gate = Arc::new(InferenceMemoryGate::with_available(config, || {
+            Some(400 * MB)
+        }));
+        let estimate = |mb: u64| MemoryEstimate {
+            prompt_tokens: 1,
+            total_tokens: 1,
+            kv_cache_bytes: mb * MB,
+            activation_bytes: 0,
+        };
+
+        let first = gate.reserve(&estimate(200)).await.unwrap().unwrap();
+        // Fits once the first inference finishes
+        let releasing = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(first);
+        });
+        assert!(gate.reserve(&estimate(200)).await.unwrap().is_some());
+        releasing.await.unwrap();
+
+        // Larger than anything that could be freed
+        assert!(matches!(
+            gate.reserve(&estimate(301)).await,
+            Err(Error::ResourceExhausted(_))
+        ));
+        gate.record_actual(&estimate(100), 150 * MB);
+        let mut metrics = HashMap::new();
+        gate.write_metrics(&mut metrics);
+        assert_eq!(metrics["inference_memory_rejected"], 1.0);
+        assert_eq!(metrics["inference_memory_estimate_error_percent"], 50.0);
+        assert_eq!(metrics["inference_memory_reserved_mb"], 0.0);
+    }
+}
@@ -0,0 +1,959 @@
+//! Out-of-process model runner plugins
+//!
+//! Some inference runtimes cannot be linked into the gateway, either because
+//! of their license or because they ship as vendor binaries. Such runtimes
+//! are wrapped in a runner executable that the engine spawns and talks to
+//! over a Unix domain socket, using length-prefixed protobuf messages defined
+//! in `proto/model_runner.proto`.
+//!
+//! Each configured backend is owned by a [`PluginRunner`] which spawns the
+//! process on first use, performs the ABI handshake and reconnects on demand.
+//! A runner whose process exits or stops answering is respawned with an
+//! exponential backoff, and is given up on after `max_restarts` consecutive
+//! failures. Requests to a runner are sent one at a time. Runners are
+//! started inside the sandbox described by their backend's `sandbox` settings.
+//!
+//! Runners may report the resources each inference used; otherwise, when the
+//! runner has its own cgroup, the CPU and IO counters of the group are read
+//! around the request. Either is added to the request's accounted usage.
+
+use chrono::{DateTime, Utc};
+use crate::sandbox::Sandbox;
+use mcp_common::config::{ModelPluginBackend, ModelPluginsConfig, ModelsConfig};
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
+use mcp_common::usage::{self, ResourceUsage};
+use mcp_common::{Error, ModelId, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// Protocol version offered in the handshake
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Environment variable carrying the socket path a runner must listen on
+pub const PLUGIN_SOCKET_ENV: &str = "MCP_MODEL_PLUGIN_SOCKET";
+
+const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// Body of an `Envelope` exchanged with a runner
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginMessage {
+    Hello {
+        abi_version: u32,
+        runtime: String,
+        models: Vec<ModelId>,
+    },
+    Infer {
+        model_id: ModelId,
+        method: String,
+        params: serde_json::Value,
+        deadline_ms: u64,
+    },
+    InferResult {
+        result: serde_json::Value,
+        usage: Option<ResourceUsage>,
+    },
+    Health,
+    HealthStatus {
+        serving: bool,
+        message: String,
+    },
+    Shutdown,
+    Error {
+        message: String,
+    },
+}
+
+impl PluginMessage {
+    /// Encode as an `Envelope` with the given request id
+    pub fn encode(&self, id: u64) -> Vec<u8> {
+        let mut body = Vec::new();
+        let field: u32 = match self {
+            Self::Hello { abi_version, runtime, models } => {
+                put_uint(&mut body, 1, u64::from(*abi_version));
+                put_bytes(&mut body, 2, runtime.as_bytes());
+                for model in models {
+                    put_bytes(&mut body, 3, model.as_bytes());
+                }
+                2
+            },
+            Self::Infer { model_id, method, params, deadline_ms } => {
+                put_bytes(&mut body, 1, model_id.as_bytes());
+                put_bytes(&mut body, 2, method.as_bytes());
+                put_bytes(&mut body, 3, params.to_string().as_bytes());
+                put_uint(&mut body, 4, *deadline_ms);
+                3
+            },
+            Self::InferResult { result, usage } => {
+                put_bytes(&mut body, 1, result.to_string().as_bytes());
+                if let Some(usage) = usage {
+                    let mut encoded = Vec::new();
+                    put_uint(&mut encoded, 1, usage.cpu_time_us);
+                    put_uint(&mut encoded, 2, usage.peak_memory_delta_bytes);
+                    put_uint(&mut encoded, 3, usage.io_read_bytes);
+                    put_uint(&mut encoded, 4, usage.io_write_bytes);
+                    // Written even when all zero so the presence is kept
+                    put_varint(&mut body, 2 << 3 | 2);
+                    put_varint(&mut body, encoded.len() as u64);
+                    body.extend_from_slice(&encoded);
+                }
+                4
+            },
+            Self::Health => 5,
+            Self::HealthStatus { serving, message } => {
+                put_uint(&mut body, 1, u64::from(*serving));
+                put_bytes(&mut body, 2, message.as_bytes());
+                6
+            },
+            Self::Shutdown => 7,
+            Self::Error { message } => {
+                put_bytes(&mut body, 1, message.as_bytes());
+                8
+            },
+        };
+
+        let mut envelope = Vec::with_capacity(body.len() + 16);
+        put_uint(&mut envelope, 1, id);
+        // Empty bodies are still written so the oneof case is preserved
+        put_varint(&mut envelope, u64::from(field) << 3 | 2);
+        put_varint(&mut envelope, body.len() as u64);
+        envelope.extend_from_slice(&body);
+        envelope
+    }
+
+    /// Decode an `Envelope` into its request id and body
+    pub fn decode(envelope: &[u8]) -> Result<(u64, Self)> {
+        let mut id = 0;
+        let mut message = None;
+        for (field, value) in read_fields(envelope)? {
+            match (field, value) {
+                (1, FieldValue::Varint(value)) => id = value,
+                (2..=8, FieldValue::Bytes(body)) => message = Some(Self::decode_body(field, body)?),
+                _ => {},
+            }
+        }
+        let message = message.ok_or_else(|| protocol_error("envelope has no body"))?;
+        Ok((id, message))
+    }
+
+    fn decode_body(field: u32, body: &[u8]) -> Result<Self> {
+        let fields = read_fields(body)?;
+        let uint = |number: u32| {
+            fields.iter().rev().find_map(|(field, value)| match value {
+                FieldValue::Varint(value) if *field == number => Some(*value),
+                _ => None,
+            })
+        };
+        let text = |number: u32| -> Result<String> {
+            match fields.iter().rev().find(|(field, _)| *field == number) {
+                Some((_, FieldValue::Bytes(bytes))) => String::from_utf8(bytes.to_vec())
+                    .map_err(|_| protocol_error("string field is not UTF-8")),
+                _ => Ok(String::new()),
+            }
+        };
+        let json = |number: u32| -> Result<serde_json::Value> {
+            match fields.iter().rev().find(|(field, _)| *field == number) {
+                Some((_, FieldValue::Bytes(bytes))) => serde_json::from_slice(bytes)
+                    .map_err(|e| protocol_error(&format!("invalid JSON payload: {}", e))),
+                _ => Ok(serde_json::Value::Null),
+            }
+        };
+
+        Ok(match field {
+            2 => Self::Hello {
+                abi_version: uint(1).unwrap_or(0) as u32,
+                runtime: text(2)?,
+                models: fields
+                    .iter()
+                    .filter_map(|(field, value)| match value {
+                        FieldValue::Bytes(bytes) if *field == 3 => {
+                            Some(String::from_utf8_lossy(bytes).into_owned())
+                        },
+                        _ => None,
+                    })
+                    .collect(),
+            },
+            3 => Self::Infer {
+                model_id: text(1)?,
+                method: text(2)?,
+                params: json(3)?,
+                deadline_ms: uint(4).unwrap_or(0),
+            },
+            4 => Self::InferResult {
+                result: json(1)?,
+                usage: match fields.iter().rev().find(|(field, _)| *field == 2) {
+                    Some((_, FieldValue::Bytes(bytes))) => Some(decode_usage(bytes)?),
+                    _ => None,
+                },
+            },
+            5 => Self::Health,
+            6 => Self::HealthStatus {
+                serving: uint(1).unwrap_or(0) != 0,
+                message: text(2)?,
+            },
+            7 => Self::Shutdown,
+            _ => Self::Error { message: text(1)? },
+        })
+    }
+}
+
+fn decode_usage(message: &[u8]) -> Result<ResourceUsage> {
+    let mut usage = ResourceUsage::default();
+    for (field, value) in read_fields(message)? {
+        if let FieldValue::Varint(value) = value {
+            match field {
+                1 => usage.cpu_time_us = value,
+                2 => usage.peak_memory_delta_bytes = value,
+                3 => usage.io_read_bytes = value,
+                4 => usage.io_write_bytes = value,
+                _ => {},
+            }
+        }
+    }
+    Ok(usage)
+}
+
+/// Write one length-prefixed envelope
+pub async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    id: u64,
+    message: &PluginMessage,
+) -> Result<()> {
+    let envelope = message.encode(id);
+    let mut frame = Vec::with_capacity(envelope.len() + 4);
+    frame.extend_from_slice(&(envelope.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&envelope);
+    writer.write_all(&frame).await.map_err(io_error)?;
+    writer.flush().await.map_err(io_error)
+}
+
+/// Read one length-prefixed envelope
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<(u64, PluginMessage)> {
+    let mut prefix = [0u8; 4];
+    reader.read_exact(&mut prefix).await.map_err(io_error)?;
+    let len = u32::from_be_bytes(prefix) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err(protocol_error(&format!("frame of {} bytes exceeds the limit", len)));
+    }
+    let mut envelope = vec![0u8; len];
+    reader.read_exact(&mut envelope).await.map_err(io_error)?;
+    PluginMessage::decode(&envelope)
+}
+
+/// Send a request and wait for the reply carrying the same id
+async fn exchange(stream: &mut UnixStream, id: u64, message: &PluginMessage) -> Result<PluginMessage> {
+    write_frame(stream, id, message).await?;
+    let (reply_id, reply) = read_frame(stream).await?;
+    if reply_id != id {
+        return Err(protocol_error(&format!("expected reply to {}, got {}", id, reply_id)));
+    }
+    Ok(reply)
+}
+
+/// Offer our ABI version and return the runner's self-reported runtime name
+async fn handshake(stream: &mut UnixStream, models: &[ModelId]) -> Result<String> {
+    let hello = PluginMessage::Hello {
+        abi_version: PLUGIN_ABI_VERSION,
+        runtime: format!("mcp-gateway/{}", env!("CARGO_PKG_VERSION")),
+        models: models.to_vec(),
+    };
+    match exchange(stream, 0, &hello).await? {
+        PluginMessage::Hello { abi_version, runtime, .. } if abi_version == PLUGIN_ABI_VERSION => Ok(runtime),
+        PluginMessage::Hello { abi_version, .. } => Err(protocol_error(&format!(
+            "runner speaks ABI version {}, expected {}",
+            abi_version, PLUGIN_ABI_VERSION
+        ))),
+        PluginMessage::Error { message } => Err(Error::Model(format!("runner refused handshake: {}", message))),
+        other => Err(protocol_error(&format!("unexpected handshake reply {:?}", other))),
+    }
+}
+
+/// Lifecycle state of a runner process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginState {
+    /// Not spawned yet, or shut down
+    Stopped,
+    Running,
+    /// Alive but reporting that it cannot serve, or awaiting a restart
+    Unhealthy,
+    /// Exceeded `max_restarts` and will not be spawned again
+    Failed,
+}
+
+/// Status report for a single runner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginStatus {
+    pub name: String,
+    pub state: PluginState,
+    pub pid: Option<u32>,
+    pub runtime: Option<String>,
+    pub models: Vec<ModelId>,
+    pub restarts: u32,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    /// Sandbox restrictions in force for the current process
+    pub sandbox: Vec<String>,
+}
+
+struct Connection {
+    child: Child,
+    stream: UnixStream,
+    next_id: u64,
+}
+
+/// Owns the process and connection of one plugin backend
+pub struct PluginRunner {
+    backend: ModelPluginBackend,
+    config: ModelPluginsConfig,
+    models_directory: PathBuf,
+    socket_path: PathBuf,
+    connection: tokio::sync::Mutex<Option<Connection>>,
+    status: parking_lot::Mutex<PluginStatus>,
+    retry_at: parking_lot::Mutex<Option<Instant>>,
+}
+
+impl PluginRunner {
+    pub fn new(backend: ModelPluginBackend, models: &ModelsConfig) -> Self {
+        let config = &models.plugins;
+        let socket_path = backend
+            .socket_path
+            .clone()
+            .unwrap_or_else(|| config.runtime_dir.join(format!("{}.sock", backend.name)));
+        let status = PluginStatus {
+            name: backend.name.clone(),
+            state: PluginState::Stopped,
+            pid: None,
+            runtime: None,
+            models: backend.models.clone(),
+            restarts: 0,
+            consecutive_failures: 0,
+            last_error: None,
+            started_at: None,
+            sandbox: Vec::new(),
+        };
+        Self {
+            config: ModelPluginsConfig {
+                backends: Vec::new(),
+                ..config.clone()
+            },
+            models_directory: models.models_directory.clone(),
+            backend,
+            socket_path,
+            connection: tokio::sync::Mutex::new(None),
+            status: parking_lot::Mutex::new(status),
+            retry_at: parking_lot::Mutex::new(None),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.backend.name
+    }
+
+    pub fn status(&self) -> PluginStatus {
+        self.status.lock().clone()
+    }
+
+    /// Spawn the runner if it is not already connected
+    pub async fn ensure_started(&self) -> Result<()> {
+        let mut connection = self.connection.lock().await;
+        self.connect(&mut connection).await.map(|_| ())
+    }
+
+    /// Run an inference on the runner
+    pub async fn infer(
+        &self,
+        model_id: &ModelId,
+        method: &str,
+        params: serde_json::Value,
+        deadline: Duration,
+    ) -> Result<serde_json::Value> {
+        let request = PluginMessage::Infer {
+            model_id: model_id.clone(),
+            method: method.to_string(),
+            params,
+            deadline_ms: deadline.as_millis() as u64,
+        };
+        let cgroup = self.status.lock().sandbox.iter().any(|restriction| restriction == "cgroup");
+        let before = cgroup.then(|| self.cgroup_usage());
+        let reply = self.call(&request).await?;
+
+        match reply {
+            PluginMessage::InferResult { result, usage } => {
+                let measured = match before.flatten() {
+                    // Requests are serialized, so the group's counters are ours
+                    Some(before) if usage.is_none() => self.cgroup_usage().map(|after| ResourceUsage {
+                        cpu_time_us: after.cpu_time_us.saturating_sub(before.cpu_time_us),
+                        peak_memory_delta_bytes: 0,
+                        io_read_bytes: after.io_read_bytes.saturating_sub(before.io_read_bytes),
+                        io_write_bytes: after.io_write_bytes.saturating_sub(before.io_write_bytes),
+                    }),
+                    _ => usage,
+                };
+                if let Some(measured) = measured {
+                    usage::report(&measured);
+                }
+                Ok(result)
+            },
+            other => Err(protocol_error(&format!("unexpected reply to inference: {:?}", other))),
+        }
+    }
+
+    /// Cumulative CPU and IO counters of the runner's cgroup
+    fn cgroup_usage(&self) -> Option<ResourceUsage> {
+        let group = self.config.cgroup_root.join(&self.backend.name);
+        let cpu = std::fs::read_to_string(group.join("cpu.stat")).ok()?;
+        let mut usage = ResourceUsage {
+            cpu_time_us: cpu
+                .lines()
+                .find_map(|line| line.strip_prefix("usage_usec ")?.trim().parse().ok())
+                .unwrap_or(0),
+            ..Default::default()
+        };
+        // One line per device: "8:0 rbytes=... wbytes=... rios=..."
+        for line in std::fs::read_to_string(group.join("io.stat")).unwrap_or_default().lines() {
+            for entry in line.split_whitespace() {
+                match entry.split_once('=') {
+                    Some(("rbytes", value)) => usage.io_read_bytes += value.parse::<u64>().unwrap_or(0),
+                    Some(("wbytes", value)) => usage.io_write_bytes += value.parse::<u64>().unwrap_or(0),
+                    _ => {},
+                }
+            }
+        }
+        Some(usage)
+    }
+
+    /// Ask a started runner for its health, respawning it if it has died.
+    ///
+    /// Runners busy with a request are skipped rather than queued behind it.
+    pub async fn check_health(&self) {
+        if matches!(self.status.lock().state, PluginState::Stopped | PluginState::Failed) {
+            return;
+        }
+        let Ok(mut connection) = self.connection.try_lock() else {
+            return;
+        };
+        match self.request(&mut connection, &PluginMessage::Health).await {
+            Ok(PluginMessage::HealthStatus { serving, message }) => {
+                let mut status = self.status.lock();
+                if serving {
+                    status.state = PluginState::Running;
+                } else {
+                    warn!("Model runner {} reports it is not serving: {}", self.backend.name, message);
+                    status.state = PluginState::Unhealthy;
+                    status.last_error = Some(message);
+                }
+            },
+            Ok(other) => warn!("Model runner {} sent {:?} to a health check", self.backend.name, other),
+            Err(e) => warn!("Health check of model runner {} failed: {}", self.backend.name, e),
+        }
+    }
+
+    /// Ask the runner to exit, killing it after a grace period
+    pub async fn shutdown(&self) {
+        let mut connection = self.connection.lock().await;
+        if let Some(mut current) = connection.take() {
+            let _ = write_frame(&mut current.stream, current.next_id, &PluginMessage::Shutdown).await;
+            if tokio::time::timeout(SHUTDOWN_GRACE, current.child.wait()).await.is_err() {
+                warn!("Model runner {} ignored shutdown, killing it", self.backend.name);
+                let _ = current.child.kill().await;
+            }
+            info!("Model runner {} stopped", self.backend.name);
+        }
+        let _ = tokio::fs::remove_file(&self.socket_path).await;
+        let mut status = self.status.lock();
+        status.state = PluginState::Stopped;
+        status.pid = None;
+    }
+
+    async fn call(&self, message: &PluginMessage) -> Result<PluginMessage> {
+        let mut connection = self.connection.lock().await;
+        self.request(&mut connection, message).await
+    }
+
+    /// Send a request over the connection, dropping the connection on failure
+    async fn request(
+        &self,
+        connection: &mut Option<Connection>,
+        message: &PluginMessage,
+    ) -> Result<PluginMessage> {
+        let current = self.connect(connection).await?;
+        let id = current.next_id;
+        current.next_id += 1;
+
+        match exchange(&mut current.stream, id, message).await {
+            Ok(PluginMessage::Error { message }) => {
+                Err(Error::Model(format!("model runner {}: {}", self.backend.name, message)))
+            },
+            Ok(reply) => Ok(reply),
+            Err(e) => {
+                error!("Lost connection to model runner {}: {}", self.backend.name, e);
+                if let Some(mut dead) = connection.take() {
+                    let _ = dead.child.kill().await;
+                }
+                self.record_failure(&e);
+                Err(Error::Model(format!("model runner {} failed: {}", self.backend.name, e)))
+            },
+        }
+    }
+
+    /// Return the live connection, spawning the runner when there is none
+    async fn connect<'a>(&self, connection: &'a mut Option<Connection>) -> Result<&'a mut Connection> {
+        let exited = match connection.as_mut() {
+            Some(current) => !matches!(current.child.try_wait(), Ok(None)),
+            None => false,
+        };
+        if exited {
+            warn!("Model runner {} exited", self.backend.name);
+            *connection = None;
+            self.record_failure(&Error::Model("process exited".to_string()));
+        }
+
+        if connection.is_none() {
+            if self.status.lock().state == PluginState::Failed {
+                return Err(Error::Model(format!(
+                    "model runner {} exceeded {} restarts",
+                    self.backend.name, self.config.max_restarts
+                )));
+            }
+            if let Some(retry_at) = *self.retry_at.lock() {
+                let now = Instant::now();
+                if retry_at > now {
+                    return Err(Error::Model(format!(
+                        "model runner {} is restarting, retry in {}ms",
+                        self.backend.name,
+                        (retry_at - now).as_millis()
+                    )));
+                }
+            }
+
+            match self.spawn().await {
+                Ok((spawned, runtime, sandbox)) => {
+                    let mut status = self.status.lock();
+                    if status.started_at.is_some() {
+                        status.restarts += 1;
+                    }
+                    status.state = PluginState::Running;
+                    status.pid = spawned.child.id();
+                    status.runtime = Some(runtime);
+                    status.consecutive_failures = 0;
+                    status.started_at = Some(Utc::now());
+                    status.sandbox = sandbox;
+                    *self.retry_at.lock() = None;
+                    *connection = Some(spawned);
+                },
+                Err(e) => {
+                    self.record_failure(&e);
+                    return Err(e);
+                },
+            }
+        }
+
+        connection
+            .as_mut()
+            .ok_or_else(|| Error::Model(format!("model runner {} is not connected", self.backend.name)))
+    }
+
+    /// Spawn the runner process and complete the handshake on its socket
+    async fn spawn(&self) -> Result<(Connection, String, Vec<String>)> {
+        let socket_dir = self.socket_path.parent().unwrap_or(Path::new("."));
+        tokio::fs::create_dir_all(socket_dir)
+            .await
+            .map_err(|e| Error::Model(format!("failed to create {:?}: {}", socket_dir, e)))?;
+        let _ = tokio::fs::remove_file(&self.socket_path).await;
+
+        let mut readable = vec![self.models_directory.clone()];
+        if self.backend.command.is_absolute() {
+            readable.push(self.backend.command.clone());
+        }
+        let sandbox = Sandbox::prepare(
+            &self.backend.name,
+            &self.backend.sandbox,
+            &self.config.cgroup_root,
+            &readable,
+            &[socket_dir.to_path_buf()],
+        )?;
+        let restrictions = sandbox.applied();
+
+        info!(
+            "Starting model runner {} ({:?}, sandbox: {})",
+            self.backend.name,
+            self.backend.command,
+            if restrictions.is_empty() { "none".to_string() } else { restrictions.join(", ") }
+        );
+        let mut command = Command::new(&self.backend.command);
+        command
+            .args(&self.backend.args)
+            .envs(&self.backend.env)
+            .env(PLUGIN_SOCKET_ENV, &self.socket_path)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        sandbox.apply(&mut command);
+        let mut child = command
+            .spawn()
+            .map_err(|e| Error::Model(format!("failed to spawn model runner {}: {}", self.backend.name, e)))?;
+
+        let startup = Duration::from_millis(self.config.startup_timeout_ms);
+        let deadline = Instant::now() + startup;
+        let mut stream = loop {
+            if let Ok(Some(exit)) = child.try_wait() {
+                return Err(Error::Model(format!(
+                    "model runner {} exited during startup ({})",
+                    self.backend.name, exit
+                )));
+            }
+            match UnixStream::connect(&self.socket_path).await {
+                Ok(stream) => break stream,
+                Err(_) if Instant::now() < deadline => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                },
+                Err(e) => {
+                    let _ = child.kill().await;
+                    return Err(Error::Model(format!(
+                        "model runner {} did not open {:?} within {:?}: {}",
+                        self.backend.name, self.socket_path, startup, e
+                    )));
+                },
+            }
+        };
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let runtime = match tokio::time::timeout(remaining, handshake(&mut stream, &self.backend.models)).await {
+            Ok(Ok(runtime)) => runtime,
+            Ok(Err(e)) => {
+                let _ = child.kill().await;
+                return Err(e);
+            },
+            Err(_) => {
+                let _ = child.kill().await;
+                return Err(Error::Model(format!("model runner {} handshake timed out", self.backend.name)));
+            },
+        };
+        info!("Model runner {} ready ({})", self.backend.name, runtime);
+
+        Ok((
+            Connection {
+                child,
+                stream,
+                next_id: 1,
+            },
+            runtime,
+            restrictions,
+        ))
+    }
+
+    /// Schedule the next restart, or give up once `max_restarts` is exceeded
+    fn record_failure(&self, cause: &Error) {
+        let mut status = self.status.lock();
+        status.consecutive_failures += 1;
+        status.pid = None;
+        status.last_error = Some(cause.to_string());
+
+        if status.consecutive_failures > self.config.max_restarts {
+            if status.state != PluginState::Failed {
+                error!(
+                    "ALERT: model runner {} failed {} times in a row, giving up",
+                    self.backend.name, status.consecutive_failures
+                );
+                events::publish(GatewayEvent::AlertRaised {
+                    source: "model_plugins".to_string(),
+                    severity: AlertSeverity::Critical,
+                    message: format!("Model runner {} could not be restarted: {}", self.backend.name, cause),
+                });
+            }
+            status.state = PluginState::Failed;
+            return;
+        }
+
+        status.state = PluginState::Unhealthy;
+        let exponent = status.consecutive_failures.saturating_sub(1).min(16);
+        let backoff = Duration::from_millis(self.config.restart_backoff_ms.saturating_mul(1 << exponent))
+            .min(MAX_RESTART_BACKOFF);
+        debug!("Model runner {} may restart in {:?}", self.backend.name, backoff);
+        *self.retry_at.lock() = Some(Instant::now() + backoff);
+    }
+}
+
+/// Routes plugin-served models to their runners and supervises them
+pub struct PluginSupervisor {
+    runners: Vec<Arc<PluginRunner>>,
+    by_model: HashMap<ModelId, usize>,
+    health_interval: Duration,
+    task: parking_lot::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl PluginSupervisor {
+    pub fn new(config: &ModelsConfig) -> Self {
+        let mut runners = Vec::new();
+        let mut by_model = HashMap::new();
+        for backend in &config.plugins.backends {
+            for model in &backend.models {
+                by_model.insert(model.clone(), runners.len());
+            }
+            runners.push(Arc::new(PluginRunner::new(backend.clone(), config)));
+        }
+        Self {
+            runners,
+            by_model,
+            health_interval: Duration::from_secs(config.plugins.health_interval_secs),
+            task: parking_lot::Mutex::new(None),
+        }
+    }
+
+    /// Runner serving the given model, if it is plugin-backed
+    pub fn runner_for(&self, model_id: &ModelId) -> Option<Arc<PluginRunner>> {
+        self.by_model.get(model_id).map(|&index| Arc::clone(&self.runners[index]))
+    }
+
+    pub fn statuses(&self) -> Vec<PluginStatus> {
+        self.runners.iter().map(|runner| runner.status()).collect()
+    }
+
+    /// Start periodic health checks of the started runners
+    pub fn start(self: &Arc<Self>) {
+        if self.runners.is_empty() || self.health_interval.is_zero() {
+            return;
+        }
+
+        let supervisor = Arc::downgrade(self);
+        let period = self.health_interval;
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(supervisor) = supervisor.upgrade() else {
+                    break;
+                };
+                for runner in &supervisor.runners {
+                    runner.check_health().await;
+                }
+            }
+        });
+
+        if let Some(previous) = self.task.lock().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stop health checks and shut every runner down
+    pub async fn shutdown(&self) {
+        if let Some(handle) = self.task.lock().take() {
+            handle.abort();
+        }
+        for runner in &self.runners {
+            runner.shutdown().await;
+        }
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let statuses = self.statuses();
+        let unhealthy = statuses
+            .iter()
+            .filter(|status| matches!(status.state, PluginState::Unhealthy | PluginState::Failed))
+            .count();
+        let restarts: u32 = statuses.iter().map(|status| status.restarts).sum();
+        metrics.insert("plugin_runners".to_string(), statuses.len() as f32);
+        metrics.insert("plugin_runners_unhealthy".to_string(), unhealthy as f32);
+        metrics.insert("plugin_restarts_total".to_string(), restarts as f32);
+    }
+}
+
+enum FieldValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Split a protobuf message into its varint and length-delimited fields,
+/// skipping fixed-width ones
+fn read_fields(mut buf: &[u8]) -> Result<Vec<(u32, FieldValue<'_>)>> {
+    let truncated = || protocol_error("truncated message");
+    let mut fields = Vec::new();
+    while !buf.is_empty() {
+        let key = read_varint(&mut buf).ok_or_else(truncated)?;
+        let number = (key >> 3) as u32;
+        match key & 0x7 {
+            0 => fields.push((number, FieldValue::Varint(read_varint(&mut buf).ok_or_else(truncated)?))),
+            1 => buf = buf.get(8..).ok_or_else(truncated)?,
+            2 => {
+                let len = read_varint(&mut buf).ok_or_else(truncated)? as usize;
+                let value = buf.get(..len).ok_or_else(truncated)?;
+                fields.push((number, FieldValue::Bytes(value)));
+                buf = &buf[len..];
+            },
+            5 => buf = buf.get(4..).ok_or_else(truncated)?,
+            wire_type => return Err(protocol_error(&format!("unsupported wire type {}", wire_type))),
+        }
+    }
+    Ok(fields)
+}
+
+fn read_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Varint field, omitted when zero as proto3 does
+fn put_uint(buf: &mut Vec<u8>, field: u32, value: u64) {
+    if value != 0 {
+        put_varint(buf, u64::from(field) << 3);
+        put_varint(buf, value);
+    }
+}
+
+/// Length-delimited field, omitted when empty as proto3 does
+fn put_bytes(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+    if !value.is_empty() {
+        put_varint(buf, u64::from(field) << 3 | 2);
+        put_varint(buf, value.len() as u64);
+        buf.extend_from_slice(value);
+    }
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error::Model(format!("model runner connection error: {}", e))
+}
+
+fn protocol_error(message: &str) -> Error {
+    Error::Model(format!("model runner protocol error: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelopes_round_trip() {
+        let messages = [
+            PluginMessage::Hello {
+                abi_version: PLUGIN_ABI_VERSION,
+                runtime: "llama-runner/0.3".to_string(),
+                models: vec!["llama-7b".to_string(), "phi-2".to_string()],
+            },
+            PluginMessage::Infer {
+                model_id: "llama-7b".to_string(),
+                method: "completion".to_string(),
+                params: serde_json::json!({"prompt": "hello", "max_tokens": 300}),
+                deadline_ms: 30_000,
+            },
+            PluginMessage::InferResult {
+                result: serde_json::json!({"text": "hi there"}),
+                usage: None,
+            },
+            PluginMessage::InferResult {
+                result: serde_json::json!({"text": "metered"}),
+                usage: Some(ResourceUsage {
+                    cpu_time_us: 120_000,
+                    peak_memory_delta_bytes: 1 << 30,
+                    io_read_bytes: 0,
+                    io_write_bytes: 512,
+                }),
+            },
+            PluginMessage::Health,
+            PluginMessage::HealthStatus {
+                serving: false,
+                message: "warming up".to_string(),
+            },
+            PluginMessage::Shutdown,
+            PluginMessage::Error {
+                message: "out of memory".to_string(),
+            },
+        ];
+
+        for (id, message) in messages.iter().enumerate() {
+            let encoded = message.encode(id as u64 + 300);
+            assert_eq!(PluginMessage::decode(&encoded).unwrap(), (id as u64 + 300, message.clone()));
+        }
+
+        // Unknown fields from a newer runner are skipped
+        let mut encoded = PluginMessage::Health.encode(9);
+        encoded.extend_from_slice(&[0x48, 0x01, 0x55, 0, 0, 0, 0]);
+        assert_eq!(PluginMessage::decode(&encoded).unwrap(), (9, PluginMessage::Health));
+        assert!(PluginMessage::decode(&[0x08]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_and_inference_over_socket() {
+        let (mut gateway, mut runner) = UnixStream::pair().unwrap();
+        let fake_runner = tokio::spawn(async move {
+            let (id, hello) = read_frame(&mut runner).await.unwrap();
+            let PluginMessage::Hello { abi_version, models, .. } = hello else {
+                panic!("expected hello, got {:?}", hello);
+            };
+            assert_eq!(models, vec!["tiny".to_string()]);
+            let reply = PluginMessage::Hello {
+                abi_version,
+                runtime: "fake/1.0".to_string(),
+                models: Vec::new(),
+            };
+            write_frame(&mut runner, id, &reply).await.unwrap();
+
+            let (id, request) = read_frame(&mut runner).await.unwrap();
+            let PluginMessage::Infer { params, .. } = request else {
+                panic!("expected inference, got {:?}", request);
+            };
+            let reply = PluginMessage::InferResult {
+                result: serde_json::json!({"text": params["prompt"]}),
+                usage: None,
+            };
+            write_frame(&mut runner, id, &reply).await.unwrap();
+
+            // A runner speaking another ABI version is rejected
+            let (id, _) = read_frame(&mut runner).await.unwrap();
+            let reply = PluginMessage::Hello {
+                abi_version: PLUGIN_ABI_VERSION + 1,
+                runtime: "fake/2.0".to_string(),
+                models: Vec::new(),
+            };
+            write_frame(&mut runner, id, &reply).await.unwrap();
+        });
+
+        let models = vec!["tiny".to_string()];
+        assert_eq!(handshake(&mut gateway, &models).await.unwrap(), "fake/1.0");
+
+        let request = PluginMessage::Infer {
+            model_id: "tiny".to_string(),
+            method: "completion".to_string(),
+            params: serde_json::json!({"prompt": "echo"}),
+            deadline_ms: 1000,
+        };
+        let reply = exchange(&mut gateway, 1, &request).await.unwrap();
+        assert_eq!(
+            reply,
+            PluginMessage::InferResult {
+                result: serde_json::json!({"text": "echo"}),
+                usage: None,
+            }
+        );
+
+        assert!(handshake(&mut gateway, &models).await.is_err());
+        fake_runner.await.unwrap();
+    }
+}
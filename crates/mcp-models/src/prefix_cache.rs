@@ -0,0 +1,265 @@
+//! KV-cache reuse across the turns of a session
+//!
+//! Each turn of a conversation repeats everything said before it. The cache
+//! keeps, per model and session, the text the KV cache was last computed
+//! over (the conversation so far plus the model's reply) and how many tokens
+//! that was. The next turn only evaluates what follows the prefix it shares
+//! with that text. Cached sessions are accounted at the model's KV bytes per
+//! token; they are dropped once idle for `session_ttl_secs`, least recently
+//! used first beyond `max_cache_mb`, and with their model.
+
+use mcp_common::config::PrefixCacheConfig;
+use mcp_common::ModelId;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Request param naming the session a request belongs to
+pub const SESSION_PARAM: &str = "session_id";
+
+const MB: u64 = 1024 * 1024;
+
+struct CachedPrefix {
+    text: String,
+    tokens: u64,
+    bytes: u64,
+    last_used: Instant,
+}
+
+#[derive(Default)]
+struct PrefixStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    tokens_saved: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// Per-session prefixes of the KV cache, shared by the model loaders
+pub struct SessionPrefixCache {
+    config: PrefixCacheConfig,
+    sessions: Mutex<HashMap<(ModelId, String), CachedPrefix>>,
+    stats: PrefixStats,
+}
+
+impl SessionPrefixCache {
+    pub fn new(config: PrefixCacheConfig) -> Self {
+        Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+            stats: PrefixStats::default(),
+        }
+    }
+
+    /// Session of a request whose prefix may be reused
+    pub fn session<'a>(&self, method: &str, params: &'a serde_json::Value) -> Option<&'a str> {
+        if !self.config.enabled || method == "embedding" {
+            return None;
+        }
+        params
+            .get(SESSION_PARAM)
+            .and_then(|session| session.as_str())
+            .filter(|session| !session.is_empty())
+    }
+
+    /// Tokens of `context` the session's cache already holds; `count_tokens`
+    /// tokenizes a prefix of it
+    pub fn lookup(
+        &self,
+        model_id: &ModelId,
+        session: &str,
+        context: &str,
+        count_tokens: impl Fn(&str) -> usize,
+    ) -> u64 {
+        let sessions = self.lock();
+        let reused = match sessions.get(&(model_id.clone(), session.to_string())) {
+            Some(cached) => {
+                let shared = shared_prefix(&cached.text, context);
+                // The boundary token is evaluated again, as the tokenizer may
+                // merge it with what follows
+                (count_tokens(&context[..shared]) as u64)
+                    .saturating_sub(1)
+                    .min(cached.tokens)
+            },
+            None => 0,
+        };
+        drop(sessions);
+        if reused > 0 {
+            self.stats.hits.fetch_add(1, Ordering::Relaxed);
+            self.stats.tokens_saved.fetch_add(reused, Ordering::Relaxed);
+        } else {
+            self.stats.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        reused
+    }
+
+    /// Remember the text a session's KV cache now holds
+    pub fn store(
+        &self,
+        model_id: &ModelId,
+        session: &str,
+        text: String,
+        tokens: u64,
+        bytes_per_token: u64,
+    ) {
+        let mut sessions = self.lock();
+        sessions.insert(
+            (model_id.clone(), session.to_string()),
+            CachedPrefix {
+                text,
+                tokens,
+                bytes: tokens * bytes_per_token,
+                last_used: Instant::now(),
+            },
+        );
+        let evicted = self.evict(&mut sessions, Instant::now());
+        if evicted > 0 {
+            debug!("Evicted {} cached session prefix(es)", evicted);
+        }
+    }
+
+    /// Drop the sessions cached for a model
+    pub fn remove_model(&self, model_id: &ModelId) {
+        self.lock().retain(|(model, _), _| model != model_id);
+    }
+
+    /// Drop sessions idle past their TTL, then the least recently used ones
+    /// beyond the memory budget; returns how many were dropped
+    fn evict(
+        &self,
+        sessions: &mut HashMap<(ModelId, String), CachedPrefix>,
+        now: Instant,
+    ) -> usize {
+        let before = sessions.len();
+        let ttl = Duration::from_secs(self.config.session_ttl_secs);
+        sessions.retain(|_, cached| now.saturating_duration_since(cached.last_used) < ttl);
+
+        let budget = self.config.max_cache_mb * MB;
+        let mut used: u64 = sessions.values().map(|cached| cached.bytes).sum();
+        if used > budget {
+            let mut by_age: Vec<_> = sessions
+                .iter()
+                .map(|(key, cached)| (cached.last_used, key.clone(), cached.bytes))
+                .collect();
+            by_age.sort_by_key(|(last_used, _, _)| *last_used);
+            for (_, key, bytes) in by_age {
+                if used <= budget {
+                    break;
+                }
+                sessions.remove(&key);
+                used -= bytes;
+            }
+        }
+        let evicted = before - sessions.len();
+        self.stats
+            .evictions
+            .fetch_add(evicted as u64, Ordering::Relaxed);
+        evicted
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut sessions = self.lock();
+        self.evict(&mut sessions, Instant::now());
+        let bytes: u64 = sessions.values().map(|cached| cached.bytes).sum();
+        metrics.insert("prefix_cache_sessions".to_string(), sessions.len() as f32);
+        metrics.insert("prefix_cache_mb".to_string(), bytes as f32 / MB as f32);
+        drop(sessions);
+        for (key, counter) in [
+            ("prefix_cache_hits", &self.stats.hits),
+            ("prefix_cache_misses", &self.stats.misses),
+            ("prefix_cache_tokens_saved", &self.stats.tokens_saved),
+            ("prefix_cache_evictions", &self.stats.evictions),
+        ] {
+            metrics.insert(key.to_string(), counter.load(Ordering::Relaxed) as f32);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(ModelId, String), CachedPrefix>> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Length in bytes of the longest common prefix, on a character boundary
+fn shared_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((index, _), _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(max_cache_mb: u64, session_ttl_secs: u64) -> SessionPrefixCache {
+        SessionPrefixCache::new(PrefixCacheConfig {
+            enabled: true,
+            max_cache_mb,
+            session_ttl_secs,
+        })
+    }
+
+    fn words(text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    #[test]
+    fn test_next_turn_reuses_the_shared_prefix() {
+        let cache = cache(16, 60);
+        let model = "llama".to_string();
+        let first = "user: hello there\nassistant: hi, how can I help";
+        assert_eq!(cache.lookup(&model, "s1", first, words), 0);
+        cache.store(&model, "s1", first.to_string(), words(first) as u64, 1024);
+
+        let second = format!("{}\nuser: tell me more", first);
+        assert_eq!(cache.lookup(&model, "s1", &second, words), 8);
+        // Other sessions and models do not share it, nor do diverging turns
+        assert_eq!(cache.lookup(&model, "s2", &second, words), 0);
+        assert_eq!(cache.lookup(&"phi".to_string(), "s1", &second, words), 0);
+        assert_eq!(cache.lookup(&model, "s1", "user: hello again", words), 1);
+        assert_eq!(shared_prefix("héllo", "hélp"), 4);
+
+        let mut metrics = HashMap::new();
+        cache.write_metrics(&mut metrics);
+        assert_eq!(metrics["prefix_cache_sessions"], 1.0);
+        assert_eq!(metrics["prefix_cache_hits"], 2.0);
+        assert_eq!(metrics["prefix_cache_misses"], 3.0);
+        assert_eq!(metrics["prefix_cache_tokens_saved"], 9.0);
+
+        let params = serde_json::json!({"session_id": "s1"});
+        assert_eq!(cache.session("chat", &params), Some("s1"));
+        assert_eq!(cache.session("embedding", &params), None);
+        cache.remove_model(&model);
+        assert_eq!(cache.lookup(&model, "s1", &second, words), 0);
+    }
+
+    #[test]
+    fn test_sessions_expire_and_are_evicted_over_budget() {
+        let cache = cache(1, 60);
+        let model = "llama".to_string();
+        // Each session holds half the budget
+        for session in ["a", "b", "c"] {
+            cache.store(&model, session, format!("{} says hi", session), 512, 1024);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        let held = |session: &str| {
+            cache.lookup(&model, session, &format!("{} says hi", session), words) > 0
+        };
+        assert!(!held("a"));
+        assert!(held("b") && held("c"));
+
+        // Idle sessions expire
+        let later = Instant::now() + Duration::from_secs(61);
+        let mut sessions = cache.lock();
+        assert_eq!(cache.evict(&mut sessions, later), 2);
+        assert!(sessions.is_empty());
+        drop(sessions);
+        assert_eq!(cache.stats.evictions.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,277 @@
+//! Model license and provenance tracking
+//!
+//! Operators describe where each model came from in a manifest, by default
+//! `manifest.json` in the models directory:
+//!
+//! ```json
+//! { "models": { "tinyllama-1.1b": {
+//!     "license": "Apache-2.0",
+//!     "source_url": "https://huggingface.co/TinyLlama/TinyLlama-1.1B-Chat-v1.0",
+//!     "sha256": "…" } } }
+//! ```
+//!
+//! Before a model is loaded its manifest entry is checked against the
+//! configured license policy and its declared checksum against the file, and
+//! the outcome is kept as the model's provenance record for `mcp.list_models`
+//! and compliance reports. The manifest is re-read on every load so entries
+//! can be added without a restart.
+
+use chrono::{DateTime, Utc};
+use crate::integrity::sha256_file;
+use mcp_common::config::ModelProvenanceConfig;
+use mcp_common::{Error, ModelFormat, ModelId, Result, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Method clients use to list loaded models with their provenance
+pub const LIST_MODELS_METHOD: &str = "mcp.list_models";
+
+/// Manifest file name used when `models.provenance.manifest_path` is unset
+pub const DEFAULT_MANIFEST_FILE: &str = "manifest.json";
+
+/// Model manifest as written by operators
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelManifest {
+    #[serde(default)]
+    pub models: HashMap<ModelId, ModelManifestEntry>,
+}
+
+/// What the manifest says about one model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelManifestEntry {
+    /// SPDX identifier or license name, e.g. `Apache-2.0` or `CC-BY-NC-4.0`
+    pub license: Option<String>,
+    pub source_url: Option<String>,
+    /// Expected SHA-256 of the model file, hex encoded
+    pub sha256: Option<String>,
+    /// Whether the license permits commercial use; inferred from the license
+    /// name when unset
+    pub commercial_use: Option<bool>,
+}
+
+impl ModelManifestEntry {
+    /// Whether the license permits commercial use, if known
+    pub fn commercial_use(&self) -> Option<bool> {
+        self.commercial_use.or_else(|| {
+            let license = self.license.as_deref()?.to_ascii_lowercase();
+            let non_commercial = license.contains("-nc")
+                || license.contains("noncommercial")
+                || license.contains("non-commercial");
+            Some(!non_commercial)
+        })
+    }
+}
+
+/// Provenance of a model that passed the license policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelProvenance {
+    pub model_id: ModelId,
+    pub path: PathBuf,
+    pub license: Option<String>,
+    pub source_url: Option<String>,
+    pub commercial_use: Option<bool>,
+    /// Checksum of the file that was loaded
+    pub sha256: Option<String>,
+    /// Whether the manifest declared a checksum and the file matched it
+    pub checksum_verified: bool,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Loaded model as reported by `mcp.list_models`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelListing {
+    pub model_id: ModelId,
+    pub format: ModelFormat,
+    pub name: String,
+    pub parameters: u64,
+    pub quantization: String,
+    pub context_length: u32,
+    pub memory_usage_mb: u32,
+    pub provenance: Option<ModelProvenance>,
+}
+
+/// Enforces the license policy and keeps provenance of loaded models
+pub struct ModelProvenanceRegistry {
+    config: ModelProvenanceConfig,
+    manifest_path: PathBuf,
+    vfs: Arc<dyn Vfs>,
+    records: RwLock<HashMap<ModelId, ModelProvenance>>,
+}
+
+impl ModelProvenanceRegistry {
+    pub fn new(config: ModelProvenanceConfig, models_directory: &Path, vfs: Arc<dyn Vfs>) -> Self {
+        let manifest_path = config
+            .manifest_path
+            .clone()
+            .unwrap_or_else(|| models_directory.join(DEFAULT_MANIFEST_FILE));
+        Self {
+            config,
+            manifest_path,
+            vfs,
+            records: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Read the manifest; a missing manifest is empty
+    pub async fn manifest(&self) -> Result<ModelManifest> {
+        if !self.vfs.exists(&self.manifest_path).await {
+            return Ok(ModelManifest::default());
+        }
+        let bytes = self.vfs.read(&self.manifest_path).await?;
+        serde_json::from_slice(&bytes).map_err(|e| {
+            Error::Configuration(format!("Invalid model manifest {:?}: {}", self.manifest_path, e))
+        })
+    }
+
+    /// Check a model against the license policy and its declared checksum,
+    /// recording its provenance if it may be loaded. `sha256` is the file's
+    /// digest when the caller has already computed it.
+    pub async fn check(&self, model_id: &ModelId, path: &Path, sha256: Option<String>) -> Result<ModelProvenance> {
+        let entry = self.manifest().await?.models.remove(model_id).unwrap_or_default();
+        self.check_license(model_id, &entry)?;
+
+        let sha256 = match (&entry.sha256, sha256) {
+            (_, Some(digest)) => Some(digest),
+            (Some(_), None) => Some(sha256_file(self.vfs.as_ref(), path).await?),
+            (None, None) => None,
+        };
+        let checksum_verified = match (&entry.sha256, &sha256) {
+            (Some(declared), Some(actual)) if !declared.trim().eq_ignore_ascii_case(actual) => {
+                return Err(Error::Security(format!(
+                    "Model {} does not match the checksum in its manifest (expected {}, found {})",
+                    model_id,
+                    declared.trim(),
+                    actual
+                )));
+            },
+            (Some(_), Some(_)) => true,
+            _ => false,
+        };
+        if entry.source_url.is_none() {
+            warn!("Model {} has no source recorded in the model manifest", model_id);
+        }
+
+        let provenance = ModelProvenance {
+            model_id: model_id.clone(),
+            path: path.to_path_buf(),
+            commercial_use: entry.commercial_use(),
+            license: entry.license,
+            source_url: entry.source_url,
+            sha256,
+            checksum_verified,
+            recorded_at: Utc::now(),
+        };
+        debug!("Recorded provenance for model {}: {:?}", model_id, provenance.license);
+        self.records.write().await.insert(model_id.clone(), provenance.clone());
+        Ok(provenance)
+    }
+
+    /// Refuse a model whose license the policy does not permit
+    fn check_license(&self, model_id: &ModelId, entry: &ModelManifestEntry) -> Result<()> {
+        let refuse = |reason: String| Err(Error::Security(format!("Model {} may not be loaded: {}", model_id, reason)));
+        let Some(license) = entry.license.as_deref() else {
+            if self.config.require_license {
+                return refuse("no license is recorded in the model manifest".to_string());
+            }
+            return Ok(());
+        };
+
+        if self.config.denied_licenses.iter().any(|pattern| license_matches(pattern, license)) {
+            return refuse(format!("license {} is denied by policy", license));
+        }
+        if !self.config.allowed_licenses.is_empty()
+            && !self.config.allowed_licenses.iter().any(|pattern| license_matches(pattern, license))
+        {
+            return refuse(format!("license {} is not in the allowed list", license));
+        }
+        if self.config.commercial_use && entry.commercial_use() == Some(false) {
+            return refuse(format!("license {} does not permit commercial use", license));
+        }
+        Ok(())
+    }
+
+    /// Provenance of a model, if it has been loaded
+    pub async fn get(&self, model_id: &ModelId) -> Option<ModelProvenance> {
+        self.records.read().await.get(model_id).cloned()
+    }
+
+    /// Provenance of every model loaded since startup, sorted by model
+    pub async fn report(&self) -> Vec<ModelProvenance> {
+        let mut records: Vec<_> = self.records.read().await.values().cloned().collect();
+        records.sort_by(|a, b| a.model_id.cmp(&b.model_id));
+        records
+    }
+}
+
+/// Case-insensitive license match, with a trailing `*` matching a prefix
+fn license_matches(pattern: &str, license: &str) -> bool {
+    let (pattern, license) = (pattern.to_ascii_lowercase(), license.to_ascii_lowercase());
+    match pattern.strip_suffix('*') {
+        Some(prefix) => license.starts_with(prefix),
+        None => pattern == license,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::vfs::MemoryVfs;
+
+    async fn registry(config: ModelProvenanceConfig) -> ModelProvenanceRegistry {
+        let vfs: Arc<dyn Vfs> = Arc::new(MemoryVfs::new());
+        let manifest = serde_json::json!({ "models": {
+            "tinyllama": { "license": "Apache-2.0", "source_url": "https://example.com/tinyllama", "sha256": "ABC123" },
+            "research": { "license": "CC-BY-NC-4.0" },
+        }});
+        vfs.write(Path::new("models/manifest.json"), manifest.to_string().as_bytes()).await.unwrap();
+        ModelProvenanceRegistry::new(config, Path::new("models"), vfs)
+    }
+
+    #[tokio::test]
+    async fn test_records_provenance_and_verifies_checksum() {
+        let registry = registry(ModelProvenanceConfig::default()).await;
+        let path = Path::new("models/tinyllama.gguf");
+
+        let provenance = registry.check(&"tinyllama".to_string(), path, Some("abc123".to_string())).await.unwrap();
+        assert_eq!(provenance.license.as_deref(), Some("Apache-2.0"));
+        assert_eq!(provenance.commercial_use, Some(true));
+        assert!(provenance.checksum_verified);
+
+        let mismatch = registry.check(&"tinyllama".to_string(), path, Some("def456".to_string())).await;
+        assert!(matches!(mismatch, Err(Error::Security(_))));
+
+        let unlisted = registry.check(&"other".to_string(), path, None).await.unwrap();
+        assert!(unlisted.license.is_none() && !unlisted.checksum_verified);
+        assert_eq!(registry.report().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_license_policy_blocks_incompatible_models() {
+        let path = Path::new("models/model.gguf");
+        let commercial = registry(ModelProvenanceConfig {
+            commercial_use: true,
+            require_license: true,
+            ..Default::default()
+        })
+        .await;
+        assert!(commercial.check(&"research".to_string(), path, None).await.is_err());
+        assert!(commercial.check(&"unlisted".to_string(), path, None).await.is_err());
+        assert!(commercial.check(&"tinyllama".to_string(), path, Some("abc123".to_string())).await.is_ok());
+        assert!(commercial.get(&"research".to_string()).await.is_none());
+
+        let allow_list = registry(ModelProvenanceConfig {
+            allowed_licenses: vec!["mit".to_string(), "apache-*".to_string()],
+            denied_licenses: vec!["Apache-2.0".to_string()],
+            ..Default::default()
+        })
+        .await;
+        assert!(allow_list.check(&"tinyllama".to_string(), path, Some("abc123".to_string())).await.is_err());
+        assert!(allow_list.check(&"research".to_string(), path, None).await.is_err());
+        assert!(allow_list.check(&"unlisted".to_string(), path, None).await.is_ok());
+    }
+}
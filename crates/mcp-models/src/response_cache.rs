@@ -0,0 +1,459 @@
+//! Cache of model responses
+//!
+//! Responses are keyed by a scope (the model that produced them), the method
+//! and the params, and expire after `ttl_secs`. The cache stays under
+//! `max_size_mb` by evicting the least recently used responses. With a
+//! `persist_path`, it is saved on shutdown and loaded again at startup, so a
+//! restart does not throw away what was cached.
+//!
+//! In semantic mode, requests of the `semantic_methods` are also matched by
+//! meaning: their prompt is embedded with the retrieval index's hashing
+//! embedder, and a cached response whose other params are identical is reused
+//! when the prompts' cosine similarity reaches `similarity_threshold`.
+
+use crate::retrieval::{cosine, HashingEmbedder};
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use mcp_common::config::ResponseCacheConfig;
+use mcp_common::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Param compared by meaning in semantic mode
+const PROMPT_PARAM: &str = "prompt";
+
+const MB: u64 = 1024 * 1024;
+
+/// Prompt embedding of a semantically cached response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SemanticKey {
+    /// Scope, method and every param but the prompt
+    context: String,
+    embedding: Vec<f32>,
+}
+
+/// A cached response as held in memory and saved to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    key: String,
+    response: serde_json::Value,
+    expires_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    semantic: Option<SemanticKey>,
+    #[serde(skip)]
+    bytes: u64,
+}
+
+impl CachedResponse {
+    fn size(&self) -> u64 {
+        let semantic = self.semantic.as_ref().map_or(0, |semantic| {
+            semantic.context.len() + semantic.embedding.len() * std::mem::size_of::<f32>()
+        });
+        (self.key.len() + self.response.to_string().len() + semantic) as u64
+    }
+}
+
+struct CacheState {
+    entries: LruCache<String, CachedResponse>,
+    bytes: u64,
+}
+
+#[derive(Default)]
+struct CacheStats {
+    hits: AtomicU64,
+    semantic_hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// LRU cache of responses with a size budget
+pub struct ResponseCache {
+    config: ResponseCacheConfig,
+    embedder: HashingEmbedder,
+    state: Mutex<CacheState>,
+    stats: CacheStats,
+}
+
+impl ResponseCache {
+    /// Create the cache, loading the responses saved at `persist_path`
+    pub fn new(config: ResponseCacheConfig) -> Self {
+        let cache = Self {
+            embedder: HashingEmbedder::new(config.embedding_dimensions),
+            state: Mutex::new(CacheState {
+                entries: LruCache::unbounded(),
+                bytes: 0,
+            }),
+            stats: CacheStats::default(),
+            config,
+        };
+        if cache.config.enabled {
+            cache.load();
+        }
+        cache
+    }
+
+    /// Whether responses to `method` are cached
+    pub fn applies(&self, method: &str) -> bool {
+        self.config.enabled
+            && (self.config.methods.is_empty() || self.config.methods.iter().any(|m| m == method))
+    }
+
+    /// Cached response to a request, matched exactly or, in semantic mode,
+    /// by the meaning of its prompt
+    pub fn get(
+        &self,
+        scope: &str,
+        method: &str,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Option<serde_json::Value> {
+        if !self.applies(method) {
+            return None;
+        }
+        let key = cache_key(scope, method, params, None);
+        let now = Utc::now();
+        let mut state = self.lock();
+
+        if let Some(cached) = state.entries.get(&key) {
+            if cached.expires_at > now {
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
+                return Some(cached.response.clone());
+            }
+            self.remove(&mut state, &key);
+        }
+
+        if let Some(semantic) = self.semantic_key(scope, method, params) {
+            let threshold = self.config.similarity_threshold;
+            let best = state
+                .entries
+                .iter()
+                .filter(|(_, cached)| cached.expires_at > now)
+                .filter_map(|(key, cached)| {
+                    let cached_semantic = cached.semantic.as_ref()?;
+                    (cached_semantic.context == semantic.context)
+                        .then(|| (key, cosine(&cached_semantic.embedding, &semantic.embedding)))
+                })
+                .filter(|(_, similarity)| *similarity >= threshold)
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(key, similarity)| (key.clone(), similarity));
+            if let Some((key, similarity)) = best {
+                debug!("Semantic response cache hit (similarity {:.3})", similarity);
+                self.stats.semantic_hits.fetch_add(1, Ordering::Relaxed);
+                return state
+                    .entries
+                    .get(&key)
+                    .map(|cached| cached.response.clone());
+            }
+        }
+
+        self.stats.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// Cache a response for the configured TTL
+    pub fn insert(
+        &self,
+        scope: &str,
+        method: &str,
+        params: &HashMap<String, serde_json::Value>,
+        response: serde_json::Value,
+    ) {
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        self.insert_with_ttl(scope, method, params, response, ttl);
+    }
+
+    /// Cache a response for `ttl`
+    pub fn insert_with_ttl(
+        &self,
+        scope: &str,
+        method: &str,
+        params: &HashMap<String, serde_json::Value>,
+        response: serde_json::Value,
+        ttl: Duration,
+    ) {
+        if !self.applies(method) {
+            return;
+        }
+        let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        let mut cached = CachedResponse {
+            key: cache_key(scope, method, params, None),
+            response,
+            expires_at: Utc::now()
+                .checked_add_signed(ttl)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            semantic: self.semantic_key(scope, method, params),
+            bytes: 0,
+        };
+        cached.bytes = cached.size();
+        let mut state = self.lock();
+        self.store(&mut state, cached);
+    }
+
+    /// Save the cached responses to `persist_path`, least recently used first
+    pub fn persist(&self) -> Result<()> {
+        let Some(path) = self
+            .config
+            .persist_path
+            .as_ref()
+            .filter(|_| self.config.enabled)
+        else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        let snapshot: Vec<CachedResponse> = {
+            let state = self.lock();
+            state
+                .entries
+                .iter()
+                .rev()
+                .filter(|(_, cached)| cached.expires_at > now)
+                .map(|(_, cached)| cached.clone())
+                .collect()
+        };
+        let encoded = serde_json::to_vec(&snapshot)?;
+        let failed = |e: std::io::Error| {
+            Error::Internal(format!(
+                "Failed to save response cache to {:?}: {}",
+                path, e
+            ))
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(failed)?;
+        }
+        // Replace the file in one step, so a crash never leaves half of it
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, encoded).map_err(failed)?;
+        std::fs::rename(&temporary, path).map_err(failed)?;
+        info!("Saved {} cached response(s) to {:?}", snapshot.len(), path);
+        Ok(())
+    }
+
+    /// Load the responses saved by `persist`, skipping expired ones
+    fn load(&self) {
+        let Some(path) = &self.config.persist_path else {
+            return;
+        };
+        let saved: Vec<CachedResponse> = match std::fs::read(path) {
+            Ok(bytes) => match serde_json::from_slice(&bytes) {
+                Ok(saved) => saved,
+                Err(e) => {
+                    warn!("Ignoring unreadable response cache {:?}: {}", path, e);
+                    return;
+                },
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!("Could not read response cache {:?}: {}", path, e);
+                return;
+            },
+        };
+        let now = Utc::now();
+        let mut state = self.lock();
+        for mut cached in saved.into_iter().filter(|cached| cached.expires_at > now) {
+            cached.bytes = cached.size();
+            self.store(&mut state, cached);
+        }
+        debug!(
+            "Loaded {} cached response(s) from {:?}",
+            state.entries.len(),
+            path
+        );
+    }
+
+    fn store(&self, state: &mut CacheState, cached: CachedResponse) {
+        let budget = self.config.max_size_mb * MB;
+        if cached.bytes > budget {
+            return;
+        }
+        let key = cached.key.clone();
+        self.remove(state, &key);
+        state.bytes += cached.bytes;
+        state.entries.put(key, cached);
+        while state.bytes > budget {
+            let Some((_, evicted)) = state.entries.pop_lru() else {
+                break;
+            };
+            state.bytes -= evicted.bytes;
+            self.stats.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn remove(&self, state: &mut CacheState, key: &str) {
+        if let Some(removed) = state.entries.pop(key) {
+            state.bytes -= removed.bytes;
+        }
+    }
+
+    /// Embedding key of a request matched by meaning
+    fn semantic_key(
+        &self,
+        scope: &str,
+        method: &str,
+        params: &HashMap<String, serde_json::Value>,
+    ) -> Option<SemanticKey> {
+        if !self.config.semantic || !self.config.semantic_methods.iter().any(|m| m == method) {
+            return None;
+        }
+        let prompt = params.get(PROMPT_PARAM)?.as_str()?;
+        Some(SemanticKey {
+            context: cache_key(scope, method, params, Some(PROMPT_PARAM)),
+            embedding: self.embedder.embed(prompt),
+        })
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        if !self.config.enabled {
+            return;
+        }
+        let state = self.lock();
+        metrics.insert(
+            "response_cache_entries".to_string(),
+            state.entries.len() as f32,
+        );
+        metrics.insert(
+            "response_cache_mb".to_string(),
+            state.bytes as f32 / MB as f32,
+        );
+        drop(state);
+        for (key, counter) in [
+            ("response_cache_hits", &self.stats.hits),
+            ("response_cache_semantic_hits", &self.stats.semantic_hits),
+            ("response_cache_misses", &self.stats.misses),
+            ("response_cache_evictions", &self.stats.evictions),
+        ] {
+            metrics.insert(key.to_string(), counter.load(Ordering::Relaxed) as f32);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Key of a request, with params sorted so equal requests always match
+fn cache_key(
+    scope: &str,
+    method: &str,
+    params: &HashMap<String, serde_json::Value>,
+    without: Option<&str>,
+) -> String {
+    let params: BTreeMap<_, _> = params
+        .iter()
+        .filter(|(name, _)| Some(name.as_str()) != without)
+        .collect();
+    let params = serde_json::to_string(&params).unwrap_or_default();
+    format!("{}:{}:{}", scope, method, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ResponseCacheConfig {
+        ResponseCacheConfig {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    fn params(prompt: &str, max_tokens: u64) -> HashMap<String, serde_json::Value> {
+        HashMap::from([
+            ("prompt".to_string(), prompt.into()),
+            ("max_tokens".to_string(), max_tokens.into()),
+        ])
+    }
+
+    #[test]
+    fn test_exact_and_semantic_hits() {
+        let cache = ResponseCache::new(ResponseCacheConfig {
+            semantic: true,
+            similarity_threshold: 0.8,
+            ..config()
+        });
+        let prompt = "What is the vibration threshold for pump seven on line three";
+        cache.insert("llama", "completion", &params(prompt, 64), "42 mm/s".into());
+
+        assert_eq!(
+            cache.get("llama", "completion", &params(prompt, 64)),
+            Some("42 mm/s".into())
+        );
+        // Other models and params never match
+        assert_eq!(cache.get("phi", "completion", &params(prompt, 64)), None);
+        assert_eq!(cache.get("llama", "completion", &params(prompt, 128)), None);
+
+        let reworded = "what is the vibration threshold of pump seven on line three?";
+        assert_eq!(
+            cache.get("llama", "completion", &params(reworded, 64)),
+            Some("42 mm/s".into())
+        );
+        let unrelated = "Summarize yesterday's maintenance log for the packaging line";
+        assert_eq!(
+            cache.get("llama", "completion", &params(unrelated, 64)),
+            None
+        );
+
+        // Expired responses are not served
+        let expiring = params("status", 1);
+        cache.insert_with_ttl(
+            "llama",
+            "completion",
+            &expiring,
+            "ok".into(),
+            Duration::ZERO,
+        );
+        assert_eq!(cache.get("llama", "completion", &expiring), None);
+        assert!(!cache.applies("chat"));
+
+        let mut metrics = HashMap::new();
+        cache.write_metrics(&mut metrics);
+        assert_eq!(metrics["response_cache_hits"], 1.0);
+        assert_eq!(metrics["response_cache_semantic_hits"], 1.0);
+        assert_eq!(metrics["response_cache_misses"], 4.0);
+    }
+
+    #[test]
+    fn test_evicts_over_budget_and_persists() {
+        let path = std::env::temp_dir()
+            .join(format!("response-cache-{}", uuid::Uuid::new_v4()))
+            .join("cache.json");
+        let config = ResponseCacheConfig {
+            max_size_mb: 1,
+            persist_path: Some(path.clone()),
+            ..config()
+        };
+        let cache = ResponseCache::new(config.clone());
+        // Each response takes over a third of the budget
+        let large = "x".repeat(400 * 1024);
+        for prompt in ["a", "b", "c"] {
+            cache.insert(
+                "llama",
+                "completion",
+                &params(prompt, 8),
+                large.clone().into(),
+            );
+        }
+        assert!(cache.get("llama", "completion", &params("a", 8)).is_none());
+        assert!(cache.get("llama", "completion", &params("c", 8)).is_some());
+        cache.persist().unwrap();
+
+        let restored = ResponseCache::new(config);
+        assert!(restored
+            .get("llama", "completion", &params("b", 8))
+            .is_some());
+        assert!(restored
+            .get("llama", "completion", &params("c", 8))
+            .is_some());
+        let mut metrics = HashMap::new();
+        restored.write_metrics(&mut metrics);
+        assert_eq!(metrics["response_cache_entries"], 2.0);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
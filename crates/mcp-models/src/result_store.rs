@@ -0,0 +1,373 @@
+//! Content-addressed store of inference results
+//!
+//! Deterministic jobs such as nightly report prompts produce the same output
+//! every time they run against the same model. Their results are kept on
+//! flash under a key derived from the SHA-256 digest of the model file and a
+//! hash of the request method and parameters, so a changed model or prompt
+//! never matches an old result. A request counts as deterministic when it
+//! sets `temperature` to 0 or passes a `seed`.
+//!
+//! Each entry records a digest of the stored result, checked on every read;
+//! an entry that fails the check is deleted and treated as a miss. The store
+//! keeps itself under its configured size by evicting the least recently
+//! used entries. Unlike the gateway's response cache, entries do not expire.
+
+use chrono::{DateTime, Utc};
+use mcp_common::config::ModelResultStoreConfig;
+use mcp_common::crypto::digest::{digest, SHA256};
+use mcp_common::{MCPRequest, ModelId, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, warn};
+
+/// Directory name used when `models.result_store.directory` is unset
+const DEFAULT_DIRECTORY: &str = "results";
+
+/// A stored result as written to disk
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredResult {
+    model_id: ModelId,
+    model_digest: String,
+    params_hash: String,
+    /// SHA-256 of `result`
+    result_sha256: String,
+    /// The result as JSON text, digested as stored
+    result: String,
+    stored_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct IndexEntry {
+    size: u64,
+    last_used: SystemTime,
+}
+
+/// On-disk result store for deterministic requests
+pub struct ResultStore {
+    config: ModelResultStoreConfig,
+    directory: PathBuf,
+    vfs: Arc<dyn Vfs>,
+    /// Stored entries by key, read from the directory on first use
+    index: Mutex<Option<HashMap<String, IndexEntry>>>,
+    /// Digests of the model files results are keyed by
+    digests: RwLock<HashMap<ModelId, String>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stores: AtomicU64,
+    evictions: AtomicU64,
+    corrupt: AtomicU64,
+}
+
+impl ResultStore {
+    pub fn new(config: ModelResultStoreConfig, models_directory: &Path, vfs: Arc<dyn Vfs>) -> Self {
+        let directory = config
+            .directory
+            .clone()
+            .unwrap_or_else(|| models_directory.join(DEFAULT_DIRECTORY));
+        Self {
+            config,
+            directory,
+            vfs,
+            index: Mutex::new(None),
+            digests: RwLock::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            stores: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            corrupt: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the store applies to a request
+    pub fn eligible(&self, request: &MCPRequest) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        if !self.config.methods.is_empty() && !self.config.methods.iter().any(|method| method == &request.method) {
+            return false;
+        }
+        let zero_temperature = request
+            .params
+            .get("temperature")
+            .and_then(|temperature| temperature.as_f64())
+            .is_some_and(|temperature| temperature == 0.0);
+        zero_temperature || request.params.get("seed").is_some_and(|seed| !seed.is_null())
+    }
+
+    /// Model digest remembered for a model
+    pub async fn model_digest(&self, model_id: &ModelId) -> Option<String> {
+        self.digests.read().await.get(model_id).cloned()
+    }
+
+    /// Remember the digest of a model's file until the model is unloaded
+    pub async fn remember_digest(&self, model_id: &ModelId, digest: String) {
+        self.digests.write().await.insert(model_id.clone(), digest);
+    }
+
+    /// Forget a model's digest, so a replaced file is hashed again
+    pub async fn forget_digest(&self, model_id: &ModelId) {
+        self.digests.write().await.remove(model_id);
+    }
+
+    /// Stored result for a request against the model with `model_digest`
+    pub async fn get(&self, model_digest: &str, request: &MCPRequest) -> Option<serde_json::Value> {
+        let params_hash = params_hash(request);
+        let key = entry_key(model_digest, &params_hash);
+
+        let mut index = self.index.lock().await;
+        let index = self.load_index(&mut index).await;
+        let Some(entry) = index.get_mut(&key) else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+
+        let path = self.entry_path(&key);
+        match self.read_entry(&path, model_digest, &params_hash).await {
+            Some(result) => {
+                entry.last_used = SystemTime::now();
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                debug!("Result store hit for request {}", request.id);
+                Some(result)
+            },
+            None => {
+                warn!("Discarding corrupt result store entry {:?}", path);
+                index.remove(&key);
+                let _ = self.vfs.remove(&path).await;
+                self.corrupt.fetch_add(1, Ordering::Relaxed);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            },
+        }
+    }
+
+    /// Store the result of a request, evicting old entries to stay in size
+    pub async fn put(&self, model_digest: &str, model_id: &ModelId, request: &MCPRequest, result: &serde_json::Value) {
+        let Ok(text) = serde_json::to_string(result) else {
+            return;
+        };
+        let params_hash = params_hash(request);
+        let key = entry_key(model_digest, &params_hash);
+        let stored = StoredResult {
+            model_id: model_id.clone(),
+            model_digest: model_digest.to_string(),
+            params_hash,
+            result_sha256: sha256_hex(text.as_bytes()),
+            result: text,
+            stored_at: Utc::now(),
+        };
+        let Ok(bytes) = serde_json::to_vec(&stored) else {
+            return;
+        };
+        let size = bytes.len() as u64;
+        if size > self.config.max_entry_kb * 1024 {
+            debug!("Result of request {} is too large for the result store", request.id);
+            return;
+        }
+
+        let mut index = self.index.lock().await;
+        let index = self.load_index(&mut index).await;
+        let path = self.entry_path(&key);
+        let staging = self.directory.join(format!("{}.tmp", key));
+        let written = match self.vfs.write(&staging, &bytes).await {
+            Ok(()) => self.vfs.rename(&staging, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            warn!("Could not write result store entry {:?}: {}", path, e);
+            return;
+        }
+        index.insert(
+            key,
+            IndexEntry {
+                size,
+                last_used: SystemTime::now(),
+            },
+        );
+        self.stores.fetch_add(1, Ordering::Relaxed);
+        self.evict(index).await;
+    }
+
+    /// Render store counters into component health metrics
+    pub async fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        if !self.config.enabled {
+            return;
+        }
+        let (entries, bytes) = match self.index.lock().await.as_ref() {
+            Some(index) => (index.len(), index.values().map(|entry| entry.size).sum::<u64>()),
+            None => (0, 0),
+        };
+        metrics.insert("result_store_hits_total".to_string(), self.hits.load(Ordering::Relaxed) as f32);
+        metrics.insert("result_store_misses_total".to_string(), self.misses.load(Ordering::Relaxed) as f32);
+        metrics.insert("result_store_writes_total".to_string(), self.stores.load(Ordering::Relaxed) as f32);
+        metrics.insert("result_store_evictions_total".to_string(), self.evictions.load(Ordering::Relaxed) as f32);
+        metrics.insert("result_store_corrupt_total".to_string(), self.corrupt.load(Ordering::Relaxed) as f32);
+        metrics.insert("result_store_entries".to_string(), entries as f32);
+        metrics.insert("result_store_bytes".to_string(), bytes as f32);
+    }
+
+    /// The index, scanning the directory the first time it is needed
+    async fn load_index<'a>(
+        &self,
+        index: &'a mut Option<HashMap<String, IndexEntry>>,
+    ) -> &'a mut HashMap<String, IndexEntry> {
+        if index.is_none() {
+            let mut entries = HashMap::new();
+            if let Err(e) = self.vfs.create_dir_all(&self.directory).await {
+                warn!("Could not create result store directory {:?}: {}", self.directory, e);
+            }
+            for path in self.vfs.list_dir(&self.directory).await.unwrap_or_default() {
+                let key = match (path.file_stem(), path.extension()) {
+                    (Some(stem), Some(extension)) if extension == "json" => stem.to_string_lossy().into_owned(),
+                    _ => continue,
+                };
+                let (Ok(size), Ok(modified)) = (self.vfs.size(&path).await, self.vfs.modified(&path).await) else {
+                    continue;
+                };
+                entries.insert(key, IndexEntry { size, last_used: modified });
+            }
+            debug!("Result store {:?} holds {} entries", self.directory, entries.len());
+            *index = Some(entries);
+        }
+        index.get_or_insert_with(HashMap::new)
+    }
+
+    /// Read an entry, returning its result only if it passes verification
+    async fn read_entry(&self, path: &Path, model_digest: &str, params_hash: &str) -> Option<serde_json::Value> {
+        let bytes = self.vfs.read(path).await.ok()?;
+        let stored: StoredResult = serde_json::from_slice(&bytes).ok()?;
+        if stored.model_digest != model_digest
+            || stored.params_hash != params_hash
+            || stored.result_sha256 != sha256_hex(stored.result.as_bytes())
+        {
+            return None;
+        }
+        serde_json::from_str(&stored.result).ok()
+    }
+
+    /// Remove least recently used entries until the store fits its size cap
+    async fn evict(&self, index: &mut HashMap<String, IndexEntry>) {
+        let cap = self.config.max_size_mb * 1024 * 1024;
+        let mut total: u64 = index.values().map(|entry| entry.size).sum();
+        if total <= cap {
+            return;
+        }
+
+        let mut by_age: Vec<(String, IndexEntry)> = index.iter().map(|(key, entry)| (key.clone(), *entry)).collect();
+        by_age.sort_by_key(|(_, entry)| entry.last_used);
+        for (key, entry) in by_age {
+            if total <= cap {
+                break;
+            }
+            if let Err(e) = self.vfs.remove(&self.entry_path(&key)).await {
+                warn!("Could not evict result store entry {}: {}", key, e);
+            }
+            index.remove(&key);
+            total -= entry.size;
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", key))
+    }
+}
+
+/// Hash of the request method and parameters; JSON object keys serialize in
+/// sorted order, so equal parameters hash equally
+fn params_hash(request: &MCPRequest) -> String {
+    let canonical = serde_json::json!({ "method": request.method, "params": request.params });
+    sha256_hex(canonical.to_string().as_bytes())
+}
+
+fn entry_key(model_digest: &str, params_hash: &str) -> String {
+    sha256_hex(format!("{}:{}", model_digest, params_hash).as_bytes())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    digest(&SHA256, bytes).as_ref().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::vfs::MemoryVfs;
+    use serde_json::json;
+
+    fn store(vfs: Arc<dyn Vfs>, max_size_mb: u64) -> ResultStore {
+        let config = ModelResultStoreConfig {
+            enabled: true,
+            max_size_mb,
+            ..Default::default()
+        };
+        ResultStore::new(config, Path::new("/models"), vfs)
+    }
+
+    fn report_request(prompt: &str) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "device-1".to_string(),
+            method: "completion".to_string(),
+            params: HashMap::from([
+                ("prompt".to_string(), json!(prompt)),
+                ("temperature".to_string(), json!(0)),
+            ]),
+            context: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_results_are_keyed_by_model_digest_and_verified() {
+        let vfs: Arc<dyn Vfs> = Arc::new(MemoryVfs::new());
+        let store = store(vfs.clone(), 16);
+        let request = report_request("Summarise last night's sensor readings");
+        assert!(store.eligible(&request));
+
+        let result = json!({ "text": "All sensors nominal" });
+        store.put("digest-a", &"tinyllama".to_string(), &request, &result).await;
+        assert_eq!(store.get("digest-a", &request).await, Some(result));
+        assert_eq!(store.get("digest-b", &request).await, None);
+        assert_eq!(store.get("digest-a", &report_request("Another prompt")).await, None);
+
+        // A damaged entry is discarded, and entries survive a restart
+        let path = store.entry_path(&entry_key("digest-a", &params_hash(&request)));
+        let tampered = String::from_utf8(vfs.read(&path).await.unwrap()).unwrap().replace("nominal", "failing");
+        vfs.write(&path, tampered.as_bytes()).await.unwrap();
+        let reopened = self::store(vfs.clone(), 16);
+        assert_eq!(reopened.get("digest-a", &request).await, None);
+        assert!(!vfs.exists(&path).await);
+
+        let mut metrics = HashMap::new();
+        reopened.write_metrics(&mut metrics).await;
+        assert_eq!(metrics["result_store_corrupt_total"], 1.0);
+        assert_eq!(metrics["result_store_entries"], 0.0);
+        store.write_metrics(&mut metrics).await;
+        assert_eq!(metrics["result_store_hits_total"], 1.0);
+        assert_eq!(metrics["result_store_misses_total"], 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used_past_size_cap() {
+        let store = store(Arc::new(MemoryVfs::new()), 1);
+        let filler = json!({ "text": "x".repeat(400 * 1024) });
+        let requests: Vec<_> = (0..3).map(|i| report_request(&format!("report {}", i))).collect();
+
+        store.put("digest", &"tinyllama".to_string(), &requests[0], &filler).await;
+        store.put("digest", &"tinyllama".to_string(), &requests[1], &filler).await;
+        assert!(store.get("digest", &requests[0]).await.is_some());
+        store.put("digest", &"tinyllama".to_string(), &requests[2], &filler).await;
+
+        assert!(store.get("digest", &requests[0]).await.is_some());
+        assert!(store.get("digest", &requests[1]).await.is_none());
+        assert!(store.get("digest", &requests[2]).await.is_some());
+
+        let mut sampled = report_request("report 0");
+        sampled.params.insert("temperature".to_string(), json!(0.7));
+        assert!(!store.eligible(&sampled));
+    }
+}
@@ -0,0 +1,609 @@
+//! Hybrid retrieval over a local vector store
+//!
+//! Documents indexed on the device are embedded with a feature-hashing
+//! embedder and searched by cosine similarity, so retrieval works fully
+//! offline. When the best local match scores below the configured confidence
+//! the query can be forwarded to a cloud search API; the outgoing query is
+//! passed through a privacy filter first, and every merged result carries
+//! provenance saying where it came from.
+
+use chrono::{DateTime, Utc};
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::redaction;
+use mcp_common::config::{CloudSearchConfig, RetrievalConfig};
+use mcp_common::{Error, Result, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Method that searches the retrieval store
+pub const RETRIEVAL_SEARCH_METHOD: &str = "retrieval.search";
+
+/// Method that adds documents to the local store
+pub const RETRIEVAL_INDEX_METHOD: &str = "retrieval.index";
+
+/// Document submitted for indexing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub id: String,
+    pub text: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, serde_json::Value>,
+}
+
+/// Where a retrieved document came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetrievalSource {
+    Local,
+    Cloud,
+}
+
+/// Provenance attached to every retrieved document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub source: RetrievalSource,
+    /// Cloud result URL, if the search API returned one
+    pub url: Option<String>,
+    pub retrieved_at: DateTime<Utc>,
+}
+
+/// A single retrieval hit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievedDocument {
+    pub id: String,
+    pub text: String,
+    pub score: f32,
+    pub metadata: HashMap<String, serde_json::Value>,
+    pub provenance: Provenance,
+}
+
+/// Merged result of a hybrid search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievalResult {
+    pub results: Vec<RetrievedDocument>,
+    /// Score of the best local match
+    pub local_confidence: f32,
+    pub cloud_used: bool,
+    /// Query as sent to the cloud, after privacy filtering
+    pub outgoing_query: Option<String>,
+    pub redactions: usize,
+    pub cloud_error: Option<String>,
+}
+
+/// Deterministic bag-of-words embedder based on feature hashing
+#[derive(Debug, Clone)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    /// Embed text into a unit-length vector
+    pub fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        for token in tokens(text) {
+            let hash = fnv1a(token.as_bytes());
+            let sign = if hash & (1 << 63) == 0 { 1.0 } else { -1.0 };
+            vector[(hash % self.dimensions as u64) as usize] += sign;
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+}
+
+fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub(crate) fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    // Both vectors are unit length
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+struct StoredDocument {
+    document: Document,
+    embedding: Vec<f32>,
+    /// Tombstone; the entry is dropped at the next compaction
+    deleted: bool,
+    hits: AtomicU64,
+    /// Milliseconds since the epoch, 0 if never returned by a search
+    last_queried_ms: AtomicI64,
+}
+
+impl StoredDocument {
+    fn new(document: Document, embedding: Vec<f32>) -> Self {
+        Self {
+            document,
+            embedding,
+            deleted: false,
+            hits: AtomicU64::new(0),
+            last_queried_ms: AtomicI64::new(0),
+        }
+    }
+
+    fn estimated_bytes(&self) -> u64 {
+        (self.document.id.len()
+            + self.document.text.len()
+            + self.embedding.len() * std::mem::size_of::<f32>()) as u64
+    }
+}
+
+/// Persisted form of a stored document
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry {
+    document: Document,
+    embedding: Vec<f32>,
+    hits: u64,
+    last_queried_ms: i64,
+}
+
+/// Size and fragmentation of the local index
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexStats {
+    pub documents: usize,
+    pub tombstones: usize,
+    /// Share of entries that are tombstones
+    pub fragmentation: f32,
+    pub estimated_bytes: u64,
+}
+
+/// Removes personal data from queries leaving the device
+#[derive(Debug, Clone, Default)]
+pub struct PrivacyFilter {
+    terms: Vec<String>,
+}
+
+impl PrivacyFilter {
+    pub fn new(terms: &[String]) -> Self {
+        Self {
+            terms: terms.iter().map(|term| term.to_lowercase()).collect(),
+        }
+    }
+
+    /// Redact emails, long digit runs (phone, account and card numbers) and
+    /// configured terms; returns the filtered query and number of redactions
+    pub fn redact(&self, query: &str) -> (String, usize) {
+        redaction::mask_text(query, &self.terms)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudSearchResponse {
+    #[serde(default)]
+    results: Vec<CloudSearchHit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudSearchHit {
+    #[serde(default)]
+    id: Option<String>,
+    text: String,
+    #[serde(default)]
+    score: Option<f32>,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+/// Local vector store with privacy-filtered cloud search fallback
+pub struct HybridRetriever {
+    config: RetrievalConfig,
+    embedder: HashingEmbedder,
+    privacy: PrivacyFilter,
+    store: RwLock<Vec<StoredDocument>>,
+    client: reqwest::Client,
+}
+
+impl HybridRetriever {
+    pub fn new(config: &RetrievalConfig) -> Self {
+        let privacy = config
+            .cloud_search
+            .as_ref()
+            .map(|cloud| PrivacyFilter::new(&cloud.redact_terms))
+            .unwrap_or_default();
+        Self {
+            config: config.clone(),
+            embedder: HashingEmbedder::new(config.embedding_dimensions),
+            privacy,
+            store: RwLock::new(Vec::new()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Whether retrieval methods are served
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Add or replace documents in the local store; returns how many were indexed
+    pub async fn index(&self, documents: Vec<Document>) -> usize {
+        let mut store = self.store.write().await;
+        let count = documents.len();
+        for document in documents {
+            let embedding = self.embedder.embed(&document.text);
+            for stored in store.iter_mut().filter(|stored| stored.document.id == document.id) {
+                stored.deleted = true;
+            }
+            store.push(StoredDocument::new(document, embedding));
+        }
+        debug!("Indexed {} documents ({} entries)", count, store.len());
+        count
+    }
+
+    /// Remove documents by id; returns how many were removed. Entries are
+    /// tombstoned and reclaimed by [`HybridRetriever::compact`].
+    pub async fn remove_documents(&self, ids: &[String]) -> usize {
+        let ids: HashSet<&String> = ids.iter().collect();
+        let mut removed = 0;
+        for stored in self.store.write().await.iter_mut() {
+            if !stored.deleted && ids.contains(&stored.document.id) {
+                stored.deleted = true;
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    /// Erase documents matching `selector`, including replaced versions not
+    /// yet compacted; returns the ids of live documents erased. A dry run
+    /// erases nothing.
+    pub async fn erase_where(&self, selector: impl Fn(&Document) -> bool, dry_run: bool) -> Vec<String> {
+        let mut store = self.store.write().await;
+        let erased = store
+            .iter()
+            .filter(|stored| !stored.deleted && selector(&stored.document))
+            .map(|stored| stored.document.id.clone())
+            .collect();
+        if !dry_run {
+            store.retain(|stored| !selector(&stored.document));
+        }
+        erased
+    }
+
+    /// Number of live documents in the local store
+    pub async fn len(&self) -> usize {
+        self.store.read().await.iter().filter(|stored| !stored.deleted).count()
+    }
+
+    /// Whether the local store has no live documents
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// Size and fragmentation of the index
+    pub async fn stats(&self) -> IndexStats {
+        let store = self.store.read().await;
+        let tombstones = store.iter().filter(|stored| stored.deleted).count();
+        IndexStats {
+            documents: store.len() - tombstones,
+            tombstones,
+            fragmentation: if store.is_empty() {
+                0.0
+            } else {
+                tombstones as f32 / store.len() as f32
+            },
+            estimated_bytes: store
+                .iter()
+                .filter(|stored| !stored.deleted)
+                .map(StoredDocument::estimated_bytes)
+                .sum(),
+        }
+    }
+
+    /// Drop tombstoned entries and release their memory; returns how many were dropped
+    pub async fn compact(&self) -> usize {
+        let mut store = self.store.write().await;
+        let before = store.len();
+        store.retain(|stored| !stored.deleted);
+        store.shrink_to_fit();
+        before - store.len()
+    }
+
+    /// Remove the least-queried documents until the index fits in `max_bytes`;
+    /// returns the ids removed
+    pub async fn prune_least_queried(&self, max_bytes: u64) -> Vec<String> {
+        let mut store = self.store.write().await;
+        let mut total: u64 = store
+            .iter()
+            .filter(|stored| !stored.deleted)
+            .map(StoredDocument::estimated_bytes)
+            .sum();
+        if total <= max_bytes {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<usize> = (0..store.len()).filter(|&i| !store[i].deleted).collect();
+        candidates.sort_by_key(|&i| {
+            (
+                store[i].hits.load(Ordering::Relaxed),
+                store[i].last_queried_ms.load(Ordering::Relaxed),
+            )
+        });
+
+        let mut pruned = Vec::new();
+        for i in candidates {
+            if total <= max_bytes {
+                break;
+            }
+            total -= store[i].estimated_bytes();
+            store[i].deleted = true;
+            pruned.push(store[i].document.id.clone());
+        }
+        pruned
+    }
+
+    /// Share of sampled documents found in the top results for a query built
+    /// from their own opening words
+    pub async fn measure_recall(&self, sample_size: usize, top_k: usize) -> Option<f32> {
+        let store = self.store.read().await;
+        let live: Vec<&StoredDocument> = store.iter().filter(|stored| !stored.deleted).collect();
+        if live.is_empty() || sample_size == 0 {
+            return None;
+        }
+
+        let step = (live.len() / sample_size).max(1);
+        let sample: Vec<_> = live.iter().step_by(step).take(sample_size).collect();
+        let found = sample
+            .iter()
+            .filter(|stored| {
+                let query: Vec<&str> = stored.document.text.split_whitespace().take(12).collect();
+                rank(&store, &self.embedder.embed(&query.join(" ")), top_k)
+                    .iter()
+                    .any(|(_, hit)| hit.document.id == stored.document.id)
+            })
+            .count();
+        Some(found as f32 / sample.len() as f32)
+    }
+
+    /// Load a snapshot written by [`HybridRetriever::save`]; returns the number of documents
+    pub async fn load(&self, vfs: &dyn Vfs, path: &Path) -> Result<usize> {
+        let entries: Vec<SnapshotEntry> = serde_json::from_slice(&vfs.read(path).await?)
+            .map_err(|e| Error::Internal(format!("Invalid retrieval index snapshot {:?}: {}", path, e)))?;
+
+        let mut store = self.store.write().await;
+        *store = entries
+            .into_iter()
+            .map(|entry| {
+                let stored = StoredDocument::new(entry.document, entry.embedding);
+                stored.hits.store(entry.hits, Ordering::Relaxed);
+                stored.last_queried_ms.store(entry.last_queried_ms, Ordering::Relaxed);
+                stored
+            })
+            .collect();
+        Ok(store.len())
+    }
+
+    /// Write the live documents to a snapshot; returns its size in bytes
+    pub async fn save(&self, vfs: &dyn Vfs, path: &Path) -> Result<u64> {
+        let entries: Vec<SnapshotEntry> = self
+            .store
+            .read()
+            .await
+            .iter()
+            .filter(|stored| !stored.deleted)
+            .map(|stored| SnapshotEntry {
+                document: stored.document.clone(),
+                embedding: stored.embedding.clone(),
+                hits: stored.hits.load(Ordering::Relaxed),
+                last_queried_ms: stored.last_queried_ms.load(Ordering::Relaxed),
+            })
+            .collect();
+        let data = serde_json::to_vec(&entries)?;
+
+        let staging = path.with_extension("tmp");
+        vfs.write(&staging, &data).await?;
+        vfs.rename(&staging, path).await?;
+        Ok(data.len() as u64)
+    }
+
+    /// Search the local store only
+    pub async fn search_local(&self, query: &str, top_k: usize) -> Vec<RetrievedDocument> {
+        let query_embedding = self.embedder.embed(query);
+        let now = Utc::now();
+        let store = self.store.read().await;
+
+        rank(&store, &query_embedding, top_k)
+            .into_iter()
+            .map(|(score, stored)| {
+                stored.hits.fetch_add(1, Ordering::Relaxed);
+                stored.last_queried_ms.store(now.timestamp_millis(), Ordering::Relaxed);
+                (score, stored)
+            })
+            .map(|(score, stored)| RetrievedDocument {
+                id: stored.document.id.clone(),
+                text: stored.document.text.clone(),
+                score,
+                metadata: stored.document.metadata.clone(),
+                provenance: Provenance {
+                    source: RetrievalSource::Local,
+                    url: None,
+                    retrieved_at: now,
+                },
+            })
+            .collect()
+    }
+
+    /// Search locally, consulting cloud search when local matches are weak
+    pub async fn search(&self, query: &str, top_k: Option<usize>) -> Result<RetrievalResult> {
+        let top_k = top_k.unwrap_or(self.config.top_k).max(1);
+        let local = self.search_local(query, top_k).await;
+        let local_confidence = local.first().map_or(0.0, |hit| hit.score);
+
+        let mut result = RetrievalResult {
+            results: local,
+            local_confidence,
+            cloud_used: false,
+            outgoing_query: None,
+            redactions: 0,
+            cloud_error: None,
+        };
+
+        let Some(cloud) = &self.config.cloud_search else {
+            return Ok(result);
+        };
+        if local_confidence >= self.config.min_local_score {
+            return Ok(result);
+        }
+
+        let (outgoing, redactions) = self.privacy.redact(query);
+        debug!(
+            "Local retrieval confidence {:.2} below {:.2}, querying cloud search ({} redactions)",
+            local_confidence, self.config.min_local_score, redactions
+        );
+        result.outgoing_query = Some(outgoing.clone());
+        result.redactions = redactions;
+
+        match self.cloud_search(cloud, &outgoing, top_k).await {
+            Ok(hits) => {
+                result.cloud_used = true;
+                result.results = merge(std::mem::take(&mut result.results), hits, top_k);
+            },
+            Err(e) => {
+                warn!("Cloud search failed, returning local results only: {}", e);
+                result.cloud_error = Some(e.to_string());
+            },
+        }
+        Ok(result)
+    }
+
+    async fn cloud_search(
+        &self,
+        cloud: &CloudSearchConfig,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<RetrievedDocument>> {
+        let query_body = serde_json::to_vec(&serde_json::json!({ "query": query, "top_k": top_k }))?;
+        let sent = query_body.len() as u64;
+        let mut request = self
+            .client
+            .post(&cloud.url)
+            .timeout(Duration::from_millis(cloud.timeout_ms))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(query_body);
+        if let Some(api_key) = &cloud.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::Network(format!("Cloud search request failed: {}", e)))?;
+        let response_body = response.bytes().await;
+        bandwidth::record(
+            Subsystem::Retrieval,
+            sent,
+            response_body.as_ref().map_or(0, |body| body.len() as u64),
+        );
+        let response_body =
+            response_body.map_err(|e| Error::Network(format!("Cloud search response failed: {}", e)))?;
+        let body: CloudSearchResponse = serde_json::from_slice(&response_body)
+            .map_err(|e| Error::Network(format!("Invalid cloud search response: {}", e)))?;
+
+        let now = Utc::now();
+        Ok(body
+            .results
+            .into_iter()
+            .enumerate()
+            .map(|(rank, hit)| RetrievedDocument {
+                id: hit.id.unwrap_or_else(|| format!("cloud-{}", rank)),
+                text: hit.text,
+                score: hit.score.unwrap_or(0.0).clamp(0.0, 1.0),
+                metadata: HashMap::new(),
+                provenance: Provenance {
+                    source: RetrievalSource::Cloud,
+                    url: hit.url,
+                    retrieved_at: now,
+                },
+            })
+            .collect())
+    }
+}
+
+/// Live entries ordered by similarity to the query, best first
+fn rank<'a>(store: &'a [StoredDocument], query: &[f32], top_k: usize) -> Vec<(f32, &'a StoredDocument)> {
+    let mut scored: Vec<_> = store
+        .iter()
+        .filter(|stored| !stored.deleted)
+        .map(|stored| (cosine(query, &stored.embedding), stored))
+        .filter(|(score, _)| *score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.truncate(top_k);
+    scored
+}
+
+/// Merge local and cloud hits, dropping cloud duplicates of local documents
+fn merge(
+    local: Vec<RetrievedDocument>,
+    cloud: Vec<RetrievedDocument>,
+    top_k: usize,
+) -> Vec<RetrievedDocument> {
+    let normalize = |text: &str| tokens(text).collect::<Vec<_>>().join(" ");
+    let mut seen: HashSet<String> = local.iter().map(|hit| normalize(&hit.text)).collect();
+
+    let mut merged = local;
+    merged.extend(cloud.into_iter().filter(|hit| seen.insert(normalize(&hit.text))));
+    merged.sort_by(|a, b| b.score.total_cmp(&a.score));
+    merged.truncate(top_k);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(id: &str, text: &str) -> Document {
+        Document {
+            id: id.to_string(),
+            text: text.to_string(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_search_ranks_matching_documents_first() {
+        let retriever = HybridRetriever::new(&RetrievalConfig::default());
+        retriever
+            .index(vec![
+                document("pump", "Restart the coolant pump after a pressure fault"),
+                document("badge", "Visitor badges are issued at reception"),
+            ])
+            .await;
+
+        let result = retriever.search("how to restart the coolant pump", None).await.unwrap();
+        assert_eq!(result.results[0].id, "pump");
+        assert_eq!(result.results[0].provenance.source, RetrievalSource::Local);
+        assert!(!result.cloud_used);
+        assert!(result.outgoing_query.is_none());
+    }
+
+    #[test]
+    fn test_privacy_filter_redacts_personal_data() {
+        let filter = PrivacyFilter::new(&["ProjectX".to_string()]);
+        let (query, redactions) =
+            filter.redact("email jane.doe@example.com about projectx invoice 4111111111111111");
+        assert_eq!(query, "email [EMAIL] about [REDACTED] invoice [NUMBER]");
+        assert_eq!(redactions, 3);
+    }
+}
@@ -0,0 +1,552 @@
+//! Sandboxing of out-of-process model runners
+//!
+//! A runner is third-party code parsing untrusted prompts, so it is started
+//! with no more access than it needs to serve inferences. On Linux the child
+//! process, between fork and exec:
+//!
+//! - joins its own cgroup v2 group carrying the configured memory, CPU and
+//!   process limits,
+//! - sets `no_new_privs` and restricts itself with a Landlock ruleset that
+//!   only allows reading system libraries, its executable, the models
+//!   directory and configured paths, and writing its socket directory,
+//! - installs a seccomp filter refusing kernel administration, tracing and
+//!   namespace syscalls, and sockets other than `AF_UNIX` unless networking is
+//!   allowed.
+//!
+//! The queue database, keys and configuration are outside every allowed path.
+//! Restrictions the kernel does not support are skipped with a warning unless
+//! the backend's sandbox is marked `required`.
+
+use mcp_common::config::PluginSandboxConfig;
+use mcp_common::{Error, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// System locations a dynamically linked runner needs to start
+const SYSTEM_READ_PATHS: &[&str] = &[
+    "/usr",
+    "/lib",
+    "/lib64",
+    "/bin",
+    "/etc/ld.so.cache",
+    "/etc/ld.so.conf",
+    "/etc/ld.so.conf.d",
+    "/etc/localtime",
+    "/proc/cpuinfo",
+    "/proc/meminfo",
+    "/sys/devices/system/cpu",
+    "/dev/urandom",
+];
+
+const SYSTEM_WRITE_PATHS: &[&str] = &["/dev/null"];
+
+/// Prepared restrictions for one runner spawn
+pub struct Sandbox {
+    /// Mechanisms that will be in force, for status reporting
+    applied: Vec<&'static str>,
+    #[cfg(target_os = "linux")]
+    prepared: Option<linux::Prepared>,
+}
+
+impl Sandbox {
+    /// Resolve the backend's sandbox settings into restrictions ready to be
+    /// applied in the child.
+    ///
+    /// `readable` and `writable` are the paths the runner needs in addition to
+    /// the configured ones.
+    pub fn prepare(
+        name: &str,
+        config: &PluginSandboxConfig,
+        cgroup_root: &Path,
+        readable: &[PathBuf],
+        writable: &[PathBuf],
+    ) -> Result<Self> {
+        if !config.enabled {
+            return Ok(Self::unconfined());
+        }
+
+        let mut read_paths: Vec<PathBuf> = SYSTEM_READ_PATHS.iter().map(PathBuf::from).collect();
+        read_paths.extend(readable.iter().cloned());
+        read_paths.extend(config.read_paths.iter().cloned());
+        let mut write_paths: Vec<PathBuf> = SYSTEM_WRITE_PATHS.iter().map(PathBuf::from).collect();
+        write_paths.extend(writable.iter().cloned());
+        write_paths.extend(config.write_paths.iter().cloned());
+
+        #[cfg(target_os = "linux")]
+        {
+            let prepared = linux::Prepared::new(name, config, cgroup_root, &read_paths, &write_paths)?;
+            Ok(Self {
+                applied: prepared.applied(),
+                prepared: Some(prepared),
+            })
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (cgroup_root, read_paths, write_paths);
+            if config.required {
+                return Err(Error::Security(format!(
+                    "sandbox for model runner {} requires Linux",
+                    name
+                )));
+            }
+            tracing::warn!("Model runner {} runs unsandboxed on this platform", name);
+            Ok(Self::unconfined())
+        }
+    }
+
+    fn unconfined() -> Self {
+        Self {
+            applied: Vec::new(),
+            #[cfg(target_os = "linux")]
+            prepared: None,
+        }
+    }
+
+    /// Names of the restrictions that will be applied
+    pub fn applied(&self) -> Vec<String> {
+        self.applied.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// Arrange for the restrictions to be applied to the spawned process
+    pub fn apply(self, command: &mut Command) {
+        #[cfg(target_os = "linux")]
+        if let Some(prepared) = self.prepared {
+            let prepared = std::sync::Arc::new(prepared);
+            // SAFETY: the hook only issues async-signal-safe syscalls on data
+            // prepared before the fork and never allocates
+            unsafe {
+                command.pre_exec(move || prepared.enter());
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = command;
+    }
+}
+
+fn sandbox_error(name: &str, what: &str, e: impl std::fmt::Display) -> Error {
+    Error::Security(format!("sandbox for model runner {}: {}: {}", name, what, e))
+}
+
+/// `cpu.max` line for a percentage of one core over a 100ms period
+fn cpu_max(percent: u32) -> String {
+    format!("{} 100000", u64::from(percent) * 1000)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{cpu_max, sandbox_error};
+    use mcp_common::config::PluginSandboxConfig;
+    use mcp_common::Result;
+    use std::ffi::CString;
+    use std::fs::OpenOptions;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::{Path, PathBuf};
+    use tracing::warn;
+
+    const LANDLOCK_CREATE_RULESET_VERSION: libc::c_uint = 1;
+    const LANDLOCK_RULE_PATH_BENEATH: libc::c_int = 1;
+    const ACCESS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_READ_DIR: u64 = 1 << 3;
+    /// Every filesystem right of Landlock ABI 1
+    const ACCESS_ABI_1: u64 = (1 << 13) - 1;
+    const ACCESS_TRUNCATE: u64 = 1 << 14;
+    const READ_ACCESS: u64 = ACCESS_EXECUTE | ACCESS_READ_FILE | ACCESS_READ_DIR;
+    const FILE_ACCESS: u64 = ACCESS_EXECUTE | ACCESS_WRITE_FILE | ACCESS_READ_FILE | ACCESS_TRUNCATE;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JEQ_K: u16 = 0x15;
+    #[cfg(target_arch = "x86_64")]
+    const BPF_JGE_K: u16 = 0x35;
+    const BPF_RET_K: u16 = 0x06;
+    const SECCOMP_DATA_NR: u32 = 0;
+    const SECCOMP_DATA_ARCH: u32 = 4;
+    const SECCOMP_DATA_ARG0: u32 = 16;
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: Option<u32> = Some(0xc000_003e);
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: Option<u32> = Some(0xc000_00b7);
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    const AUDIT_ARCH: Option<u32> = None;
+
+    /// Syscalls no model runner has a reason to make
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const DENIED_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_setns,
+        libc::SYS_unshare,
+        libc::SYS_reboot,
+        libc::SYS_kexec_load,
+        libc::SYS_kexec_file_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_userfaultfd,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_io_uring_register,
+    ];
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    const DENIED_SYSCALLS: &[libc::c_long] = &[];
+
+    /// Restrictions resolved in the parent, entered by the child before exec
+    pub struct Prepared {
+        cgroup_procs: Option<CString>,
+        ruleset: Option<OwnedFd>,
+        filter: Vec<libc::sock_filter>,
+    }
+
+    impl Prepared {
+        pub fn new(
+            name: &str,
+            config: &PluginSandboxConfig,
+            cgroup_root: &Path,
+            read_paths: &[PathBuf],
+            write_paths: &[PathBuf],
+        ) -> Result<Self> {
+            let cgroup_procs = match join_cgroup(name, config, cgroup_root) {
+                Ok(procs) => procs,
+                Err(e) if config.required => return Err(e),
+                Err(e) => {
+                    warn!("{}, running without resource limits", e);
+                    None
+                },
+            };
+
+            let ruleset = match landlock_ruleset(name, read_paths, write_paths) {
+                Ok(Some(ruleset)) => Some(ruleset),
+                Ok(None) if config.required => {
+                    return Err(sandbox_error(name, "landlock", "not supported by this kernel"));
+                },
+                Ok(None) => {
+                    warn!("Landlock is not available, model runner {} keeps full filesystem access", name);
+                    None
+                },
+                Err(e) => return Err(e),
+            };
+
+            let filter = match seccomp_filter(config.allow_network) {
+                Some(filter) => filter,
+                None if config.required => {
+                    return Err(sandbox_error(name, "seccomp", "not supported on this architecture"));
+                },
+                None => {
+                    warn!("No seccomp filter for this architecture, model runner {} is unfiltered", name);
+                    Vec::new()
+                },
+            };
+
+            Ok(Self {
+                cgroup_procs,
+                ruleset,
+                filter,
+            })
+        }
+
+        pub fn applied(&self) -> Vec<&'static str> {
+            let mut applied = Vec::new();
+            if self.cgroup_procs.is_some() {
+                applied.push("cgroup");
+            }
+            if self.ruleset.is_some() {
+                applied.push("landlock");
+            }
+            if !self.filter.is_empty() {
+                applied.push("seccomp");
+            }
+            applied
+        }
+
+        /// Runs in the forked child; must stay async-signal-safe
+        pub fn enter(&self) -> io::Result<()> {
+            unsafe {
+                if let Some(procs) = &self.cgroup_procs {
+                    let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                    if fd < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    // Writing 0 moves the writing process itself
+                    let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+                    libc::close(fd);
+                    if written != 1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+
+                if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                if let Some(ruleset) = &self.ruleset {
+                    if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+
+                if !self.filter.is_empty() {
+                    let program = libc::sock_fprog {
+                        len: self.filter.len() as libc::c_ushort,
+                        filter: self.filter.as_ptr() as *mut libc::sock_filter,
+                    };
+                    if libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &program) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Create the runner's cgroup and write its limits, returning the path
+    /// of its `cgroup.procs`; `None` when no limit is configured
+    fn join_cgroup(name: &str, config: &PluginSandboxConfig, root: &Path) -> Result<Option<CString>> {
+        let mut limits = Vec::new();
+        if let Some(bytes) = config.memory_max_bytes {
+            limits.push(("memory.max", bytes.to_string()));
+            limits.push(("memory.swap.max", "0".to_string()));
+        }
+        if let Some(percent) = config.cpu_max_percent {
+            limits.push(("cpu.max", cpu_max(percent)));
+        }
+        if let Some(pids) = config.pids_max {
+            limits.push(("pids.max", pids.to_string()));
+        }
+        if limits.is_empty() {
+            return Ok(None);
+        }
+
+        let group = root.join(name);
+        std::fs::create_dir_all(&group).map_err(|e| sandbox_error(name, "cgroup", e))?;
+        // Controllers may already be enabled; a missing one shows up below.
+        // io is only used for accounting.
+        for controller in ["+memory", "+cpu", "+pids", "+io"] {
+            let _ = std::fs::write(root.join("cgroup.subtree_control"), controller);
+        }
+        for (file, value) in limits {
+            if let Err(e) = std::fs::write(group.join(file), &value) {
+                // Swap accounting is commonly compiled out
+                if file != "memory.swap.max" {
+                    return Err(sandbox_error(name, &format!("cgroup {}", file), e));
+                }
+            }
+        }
+
+        let procs = group.join("cgroup.procs");
+        CString::new(procs.as_os_str().as_bytes())
+            .map(Some)
+            .map_err(|e| sandbox_error(name, "cgroup", e))
+    }
+
+    /// Build a ruleset allowing only the given paths; `None` when the kernel
+    /// has no Landlock support
+    fn landlock_ruleset(name: &str, read_paths: &[PathBuf], write_paths: &[PathBuf]) -> Result<Option<OwnedFd>> {
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 1 {
+            return Ok(None);
+        }
+
+        let mut handled = ACCESS_ABI_1;
+        if abi >= 3 {
+            handled |= ACCESS_TRUNCATE;
+        }
+        let attr = RulesetAttr {
+            handled_access_fs: handled,
+        };
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(sandbox_error(name, "landlock ruleset", io::Error::last_os_error()));
+        }
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+        let rules = read_paths
+            .iter()
+            .map(|path| (path, READ_ACCESS))
+            .chain(write_paths.iter().map(|path| (path, handled)));
+        for (path, access) in rules {
+            // Paths absent on this system are simply not granted
+            let Ok(file) = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+                .open(path)
+            else {
+                continue;
+            };
+            let is_dir = file.metadata().map(|meta| meta.is_dir()).unwrap_or(false);
+            let mut allowed = access & handled;
+            if !is_dir {
+                allowed &= FILE_ACCESS;
+            }
+            let rule = PathBeneathAttr {
+                allowed_access: allowed,
+                parent_fd: file.as_raw_fd(),
+            };
+            let added = unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_add_rule,
+                    ruleset.as_raw_fd(),
+                    LANDLOCK_RULE_PATH_BENEATH,
+                    &rule as *const PathBeneathAttr,
+                    0,
+                )
+            };
+            if added != 0 {
+                return Err(sandbox_error(
+                    name,
+                    &format!("landlock rule for {:?}", path),
+                    io::Error::last_os_error(),
+                ));
+            }
+        }
+        Ok(Some(ruleset))
+    }
+
+    /// Seccomp program denying `DENIED_SYSCALLS` and, unless networking is
+    /// allowed, sockets outside `AF_UNIX`; `None` on unsupported architectures
+    pub(super) fn seccomp_filter(allow_network: bool) -> Option<Vec<libc::sock_filter>> {
+        let arch = AUDIT_ARCH?;
+        let statement = |code: u16, k: u32| libc::sock_filter { code, jt: 0, jf: 0, k };
+        let jump = |k: u32, jt: u8, jf: u8| libc::sock_filter {
+            code: BPF_JEQ_K,
+            jt,
+            jf,
+            k,
+        };
+        let deny = statement(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32);
+
+        let mut filter = vec![
+            statement(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+            jump(arch, 1, 0),
+            statement(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+            statement(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+        ];
+        // x32 syscall numbers would slip past the checks below
+        #[cfg(target_arch = "x86_64")]
+        filter.extend([
+            libc::sock_filter {
+                code: BPF_JGE_K,
+                jt: 0,
+                jf: 1,
+                k: 0x4000_0000,
+            },
+            deny,
+        ]);
+        for &nr in DENIED_SYSCALLS {
+            filter.push(jump(nr as u32, 0, 1));
+            filter.push(deny);
+        }
+        if !allow_network {
+            filter.extend([
+                jump(libc::SYS_socket as u32, 0, 3),
+                statement(BPF_LD_W_ABS, SECCOMP_DATA_ARG0),
+                jump(libc::AF_UNIX as u32, 1, 0),
+                statement(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EACCES as u32),
+            ]);
+        }
+        filter.push(statement(BPF_RET_K, SECCOMP_RET_ALLOW));
+        Some(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_limit_and_filter_shape() {
+        assert_eq!(cpu_max(50), "50000 100000");
+        assert_eq!(cpu_max(200), "200000 100000");
+
+        #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            let open = linux::seccomp_filter(true).unwrap();
+            let closed = linux::seccomp_filter(false).unwrap();
+            assert_eq!(closed.len(), open.len() + 4);
+            for filter in [&open, &closed] {
+                // Every jump lands inside the program and it ends by allowing
+                for (index, instruction) in filter.iter().enumerate() {
+                    if instruction.code & 0x07 == 0x05 {
+                        assert!(index + 1 + (instruction.jt.max(instruction.jf) as usize) < filter.len());
+                    }
+                }
+                assert_eq!(filter.last().unwrap().k, 0x7fff_0000);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_runner_cannot_read_outside_allowed_paths() {
+        let dir = std::env::temp_dir().join(format!("mcp-sandbox-{}", std::process::id()));
+        let allowed = dir.join("models");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::fs::write(allowed.join("weights.bin"), "weights").unwrap();
+        std::fs::write(dir.join("queue.db"), "secrets").unwrap();
+
+        let config = PluginSandboxConfig::default();
+        let sandbox = Sandbox::prepare("test", &config, &dir, std::slice::from_ref(&allowed), &[]).unwrap();
+        if !sandbox.applied().contains(&"landlock".to_string()) {
+            // Kernel without Landlock; nothing to verify
+            std::fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg(format!(
+            "cat {}/weights.bin && ! cat {}/queue.db 2>/dev/null",
+            allowed.display(),
+            dir.display()
+        ));
+        sandbox.apply(&mut command);
+        let output = command.output().await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(output.stdout, b"weights");
+    }
+}
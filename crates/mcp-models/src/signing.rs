@@ -0,0 +1,217 @@
+//! Signed manifests for model files
+//!
+//! With `models.signing` on, a model file is checked against the signed
+//! manifest next to it before it is loaded. The verified manifest then limits
+//! the model to the methods it was released for, and the quantization found
+//! in the file has to match the signed one. [`verify_models`] runs the same
+//! check over the whole models directory for the `--verify-models` startup
+//! mode.
+
+use crate::integrity::sha256_file;
+use mcp_common::config::{ModelSignatureMode, ModelsConfig};
+use mcp_common::model_manifest::ModelManifest;
+use mcp_common::{Error, ModelId, Result, Vfs};
+use mcp_security::ModelSignatureVerifier;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// File extensions of the model formats the engine loads
+const MODEL_EXTENSIONS: &[&str] = &["gguf", "ggml", "bin", "onnx", "tflite"];
+
+/// Outcome of checking one model file in the models directory
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelSignatureStatus {
+    pub model_id: ModelId,
+    pub path: PathBuf,
+    /// Why the file is not covered by a valid signed manifest
+    pub error: Option<String>,
+}
+
+impl ModelSignatureStatus {
+    pub fn verified(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Verified manifests of the models loaded since startup
+pub struct ModelSignatures {
+    verifier: ModelSignatureVerifier,
+    vfs: Arc<dyn Vfs>,
+    verified: RwLock<HashMap<ModelId, ModelManifest>>,
+}
+
+impl ModelSignatures {
+    pub fn new(config: &ModelsConfig, vfs: Arc<dyn Vfs>) -> Result<Self> {
+        Ok(Self {
+            verifier: ModelSignatureVerifier::new(config.signing.clone())?,
+            vfs,
+            verified: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Check `path` against its signed manifest before `model_id` is loaded
+    pub async fn check(&self, model_id: &ModelId, path: &Path) -> Result<()> {
+        if self.verifier.mode() == ModelSignatureMode::Off {
+            return Ok(());
+        }
+        let manifest = verify_file(&self.verifier, self.vfs.as_ref(), model_id, path).await?;
+        let mut verified = self.verified.write().await;
+        match manifest {
+            Some(manifest) => verified.insert(model_id.clone(), manifest),
+            None => verified.remove(model_id),
+        };
+        Ok(())
+    }
+
+    /// Refuse a loaded model whose quantization differs from the signed one
+    pub async fn check_quantization(&self, model_id: &ModelId, quantization: &str) -> Result<()> {
+        match self.verified.read().await.get(model_id) {
+            Some(manifest) if !manifest.matches_quantization(quantization) => Err(Error::Security(format!(
+                "Model {} is quantized as {} but was signed as {}",
+                model_id, quantization, manifest.quantization
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuse methods the model's signed manifest does not allow
+    pub async fn check_method(&self, model_id: &ModelId, method: &str) -> Result<()> {
+        match self.verified.read().await.get(model_id) {
+            Some(manifest) if !manifest.allows_method(method) => Err(Error::Security(format!(
+                "Model {} is not signed for method {}",
+                model_id, method
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Verified manifest of a loaded model
+    pub async fn manifest(&self, model_id: &ModelId) -> Option<ModelManifest> {
+        self.verified.read().await.get(model_id).cloned()
+    }
+}
+
+/// Check every model file in the models directory against its signed
+/// manifest, regardless of the configured mode
+pub async fn verify_models(config: &ModelsConfig, vfs: &dyn Vfs) -> Result<Vec<ModelSignatureStatus>> {
+    let mut signing = config.signing.clone();
+    signing.mode = ModelSignatureMode::Enforce;
+    let verifier = ModelSignatureVerifier::new(signing)?;
+    if !vfs.exists(&config.models_directory).await {
+        return Ok(Vec::new());
+    }
+
+    let mut files: Vec<PathBuf> = vfs
+        .list_dir(&config.models_directory)
+        .await?
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| MODEL_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        })
+        .collect();
+    files.sort();
+
+    let mut statuses = Vec::with_capacity(files.len());
+    for path in files {
+        let model_id = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default().to_string();
+        let error = verify_file(&verifier, vfs, &model_id, &path).await.err().map(|e| e.to_string());
+        statuses.push(ModelSignatureStatus { model_id, path, error });
+    }
+    Ok(statuses)
+}
+
+async fn verify_file(
+    verifier: &ModelSignatureVerifier,
+    vfs: &dyn Vfs,
+    model_id: &ModelId,
+    path: &Path,
+) -> Result<Option<ModelManifest>> {
+    let sha256 = sha256_file(vfs, path).await?;
+    let manifest_path = verifier.manifest_path(path);
+    let manifest = if vfs.exists(&manifest_path).await {
+        Some(vfs.read(&manifest_path).await?)
+    } else {
+        None
+    };
+    verifier.check(model_id, &sha256, manifest.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use mcp_common::config::Config;
+    use mcp_common::crypto::rand::SystemRandom;
+    use mcp_common::crypto::signature::{Ed25519KeyPair, KeyPair};
+    use mcp_common::create_vfs;
+
+    async fn setup(directory: &Path) -> (ModelsConfig, Arc<dyn Vfs>) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let config = Config::default();
+        let vfs = create_vfs(&config.storage);
+        vfs.create_dir_all(directory).await.unwrap();
+        let mut models = config.models;
+        models.models_directory = directory.to_path_buf();
+        models.signing.mode = ModelSignatureMode::Enforce;
+        models.signing.trusted_keys =
+            vec![base64::engine::general_purpose::STANDARD.encode(key.public_key().as_ref())];
+
+        let signed_path = directory.join("tinyllama.gguf");
+        vfs.write(&signed_path, b"weights").await.unwrap();
+        let manifest = ModelManifest {
+            model_id: "tinyllama".to_string(),
+            sha256: sha256_file(vfs.as_ref(), &signed_path).await.unwrap(),
+            quantization: "q4_0".to_string(),
+            license: "Apache-2.0".to_string(),
+            allowed_methods: vec!["completion".to_string()],
+        };
+        let signed = mcp_security::sign_manifest(manifest, &key).unwrap();
+        let manifest_path = directory.join("tinyllama.gguf.manifest.json");
+        vfs.write(&manifest_path, &serde_json::to_vec(&signed).unwrap()).await.unwrap();
+        vfs.write(&directory.join("unsigned.ggml"), b"weights").await.unwrap();
+        (models, vfs)
+    }
+
+    #[tokio::test]
+    async fn test_signed_model_is_limited_to_its_manifest() {
+        let directory = std::env::temp_dir().join(format!("model-signing-{}", uuid::Uuid::new_v4()));
+        let (config, vfs) = setup(&directory).await;
+        let signatures = ModelSignatures::new(&config, vfs.clone()).unwrap();
+
+        let model_id = "tinyllama".to_string();
+        signatures.check(&model_id, &directory.join("tinyllama.gguf")).await.unwrap();
+        assert!(signatures.check_method(&model_id, "completion").await.is_ok());
+        assert!(signatures.check_method(&model_id, "embedding").await.is_err());
+        assert!(signatures.check_quantization(&model_id, "Q4_0").await.is_ok());
+        assert!(signatures.check_quantization(&model_id, "f16").await.is_err());
+
+        let unsigned = "unsigned".to_string();
+        assert!(signatures.check(&unsigned, &directory.join("unsigned.ggml")).await.is_err());
+
+        // A modified file no longer matches its signed hash
+        vfs.write(&directory.join("tinyllama.gguf"), b"tampered").await.unwrap();
+        assert!(signatures.check(&model_id, &directory.join("tinyllama.gguf")).await.is_err());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn test_verify_models_reports_each_model_file() {
+        let directory = std::env::temp_dir().join(format!("model-signing-{}", uuid::Uuid::new_v4()));
+        let (mut config, vfs) = setup(&directory).await;
+        config.signing.mode = ModelSignatureMode::Off;
+
+        let statuses = verify_models(&config, vfs.as_ref()).await.unwrap();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0].model_id, "tinyllama");
+        assert!(statuses[0].verified());
+        assert_eq!(statuses[1].model_id, "unsigned");
+        assert!(!statuses[1].verified());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
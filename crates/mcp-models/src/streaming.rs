@@ -0,0 +1,388 @@
+//! Incremental token delivery for streaming inference
+//!
+//! A streaming request yields [`StreamChunk::Token`]s as text is generated
+//! and ends with a [`StreamChunk::Done`] carrying the same response the
+//! buffered path would have returned. The stream is a bounded channel: once
+//! `models.streaming.buffer_chunks` chunks are waiting for a slow client,
+//! generation pauses until the client catches up, and it stops when the
+//! client goes away.
+//!
+//! [`splice_stream`] starts with a local stream and switches to a cloud
+//! answer that arrives within a budget. The switch happens at the next
+//! sentence boundary: local generation stops and the cloud answer continues
+//! from the sentence after those already sent, so the client sees one answer
+//! that improves mid-way. The final response says whether and where the
+//! stream was spliced.
+
+use crate::verification::response_text;
+use mcp_common::{Error, MCPResponse, Result};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Method whose responses can be streamed token by token
+pub const STREAMING_METHOD: &str = "completion";
+
+/// Chunks buffered ahead of the client when no `models.streaming` config applies
+pub const DEFAULT_BUFFER_CHUNKS: usize = 16;
+
+/// Piece of a streamed response
+#[derive(Debug, Clone)]
+pub enum StreamChunk {
+    /// Next piece of generated text
+    Token { index: usize, text: String },
+    /// Final response with the full result and its metadata
+    Done(MCPResponse),
+}
+
+/// Receiving end of a streamed response
+pub type TokenStream = mpsc::Receiver<Result<StreamChunk>>;
+
+/// Sending end of a streamed response, held by the generator
+pub type TokenSender = mpsc::Sender<Result<StreamChunk>>;
+
+/// Bounded channel for a streamed response
+pub fn token_stream(buffer_chunks: usize) -> (TokenSender, TokenStream) {
+    mpsc::channel(buffer_chunks.max(1))
+}
+
+/// Send a generated token, waiting while the client is behind; fails once the
+/// client has gone away so the generator can stop
+pub async fn send_token(sender: &TokenSender, index: usize, text: &str) -> Result<()> {
+    sender
+        .send(Ok(StreamChunk::Token {
+            index,
+            text: text.to_string(),
+        }))
+        .await
+        .map_err(|_| Error::Model("Stream closed by client".to_string()))
+}
+
+/// Split generated text into tokens, each word keeping its leading whitespace
+/// so that concatenating the tokens gives back the text
+pub fn split_tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_word = false;
+    for (offset, c) in text.char_indices() {
+        if c.is_whitespace() && in_word {
+            tokens.push(&text[start..offset]);
+            start = offset;
+            in_word = false;
+        } else if !c.is_whitespace() {
+            in_word = true;
+        }
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// Send the generated text of a finished result as tokens
+pub async fn send_result_text(sender: &TokenSender, result: &serde_json::Value) -> Result<()> {
+    if let Some(text) = response_text(result) {
+        for (index, token) in split_tokens(text).into_iter().enumerate() {
+            send_token(sender, index, token).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Stream a response that was produced in one piece, for routes and runners
+/// that cannot generate incrementally
+pub fn buffered_stream(response: MCPResponse, buffer_chunks: usize) -> TokenStream {
+    let (sender, stream) = token_stream(buffer_chunks);
+    tokio::spawn(async move {
+        if let Some(result) = &response.result {
+            if send_result_text(&sender, result).await.is_err() {
+                return;
+            }
+        }
+        let _ = sender.send(Ok(StreamChunk::Done(response))).await;
+    });
+    stream
+}
+
+/// Stream `local`'s tokens until the `cloud` answer arrives within `budget`,
+/// then continue with the cloud answer from the next sentence boundary
+///
+/// The final response carries a `splice` object: when spliced it is the
+/// cloud response, its text replaced by what the client received, with
+/// `token_index` marking the first cloud token; otherwise it is the local
+/// response with the `reason` the cloud was not used. A local failure falls
+/// back to the cloud answer if one arrives in time.
+pub fn splice_stream<F>(mut local: TokenStream, cloud: F, budget: Duration, buffer_chunks: usize) -> TokenStream
+where
+    F: Future<Output = Result<MCPResponse>> + Send + 'static,
+{
+    let (sender, stream) = token_stream(buffer_chunks);
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let cloud = tokio::time::timeout(budget, cloud);
+        tokio::pin!(cloud);
+        let mut cloud_pending = true;
+        let mut arrived: Option<MCPResponse> = None;
+        let mut reason = "cloud_late";
+        let mut text = String::new();
+        let mut index = 0;
+
+        loop {
+            tokio::select! {
+                // A cloud answer is taken as soon as it is ready
+                biased;
+                result = &mut cloud, if cloud_pending => {
+                    cloud_pending = false;
+                    match result {
+                        Ok(Ok(response)) if response.result.as_ref().and_then(response_text).is_some() => {
+                            arrived = Some(response);
+                        },
+                        Ok(_) => reason = "cloud_failed",
+                        Err(_) => reason = "cloud_late",
+                    }
+                    if arrived.is_some() && at_sentence_boundary(&text) {
+                        let cloud = arrived.take().expect("cloud response arrived");
+                        let _ = send_splice(&sender, cloud, &text, index, started).await;
+                        return;
+                    }
+                },
+                chunk = local.recv() => match chunk {
+                    Some(Ok(StreamChunk::Token { text: token, .. })) => {
+                        text.push_str(&token);
+                        if send_token(&sender, index, &token).await.is_err() {
+                            return;
+                        }
+                        index += 1;
+                        if arrived.is_some() && at_sentence_boundary(&text) {
+                            let cloud = arrived.take().expect("cloud response arrived");
+                            let _ = send_splice(&sender, cloud, &text, index, started).await;
+                            return;
+                        }
+                    },
+                    Some(Ok(StreamChunk::Done(mut response))) => {
+                        if arrived.is_some() {
+                            reason = "local_finished";
+                        }
+                        mark_splice(&mut response, serde_json::json!({ "spliced": false, "reason": reason }));
+                        let _ = sender.send(Ok(StreamChunk::Done(response))).await;
+                        return;
+                    },
+                    Some(Err(e)) => {
+                        let fallback = match arrived.take() {
+                            Some(cloud) => Some(cloud),
+                            None if cloud_pending => match (&mut cloud).await {
+                                Ok(Ok(response)) if response.result.as_ref().and_then(response_text).is_some() => {
+                                    Some(response)
+                                },
+                                _ => None,
+                            },
+                            None => None,
+                        };
+                        match fallback {
+                            Some(cloud) => {
+                                let _ = send_splice(&sender, cloud, &text, index, started).await;
+                            },
+                            None => {
+                                let _ = sender.send(Err(e)).await;
+                            },
+                        }
+                        return;
+                    },
+                    None => return,
+                },
+            }
+        }
+    });
+    stream
+}
+
+/// Send the rest of a cloud answer after the local `text` already streamed,
+/// then its response marked with the splice point
+async fn send_splice(
+    sender: &TokenSender,
+    mut cloud: MCPResponse,
+    text: &str,
+    token_index: usize,
+    started: Instant,
+) -> Result<()> {
+    let cloud_text = cloud.result.as_ref().and_then(response_text).unwrap_or_default();
+    let sentences = sentence_ends(text).count();
+    let remainder = match sentences.checked_sub(1) {
+        None => cloud_text.to_string(),
+        Some(last) => sentence_ends(cloud_text)
+            .nth(last)
+            .map(|offset| cloud_text[offset..].to_string())
+            // The cloud answer is shorter than what was already sent
+            .unwrap_or_default(),
+    };
+    for (i, token) in split_tokens(&remainder).into_iter().enumerate() {
+        send_token(sender, token_index + i, token).await?;
+    }
+
+    let full_text = format!("{}{}", text, remainder);
+    if let Some(result) = cloud.result.as_mut().and_then(|result| result.as_object_mut()) {
+        if let Some(field) = ["text", "response", "summary"]
+            .iter()
+            .find(|field| result.get(**field).is_some_and(|value| value.is_string()))
+        {
+            result.insert(field.to_string(), serde_json::Value::String(full_text));
+        }
+    }
+    mark_splice(
+        &mut cloud,
+        serde_json::json!({
+            "spliced": true,
+            "token_index": token_index,
+            "local_sentences": sentences,
+            "cloud_latency_ms": started.elapsed().as_millis() as u64,
+        }),
+    );
+    sender
+        .send(Ok(StreamChunk::Done(cloud)))
+        .await
+        .map_err(|_| Error::Model("Stream closed by client".to_string()))
+}
+
+fn mark_splice(response: &mut MCPResponse, splice: serde_json::Value) {
+    if let Some(result) = response.result.as_mut().and_then(|result| result.as_object_mut()) {
+        result.insert("splice".to_string(), splice);
+    }
+}
+
+/// Whether streamed text ends where a sentence does, or has not started
+fn at_sentence_boundary(text: &str) -> bool {
+    let trimmed = text.trim_end();
+    trimmed.is_empty() || trimmed.ends_with(['.', '!', '?'])
+}
+
+/// Byte offsets just past each sentence-ending mark followed by whitespace
+/// or the end of the text
+fn sentence_ends(text: &str) -> impl Iterator<Item = usize> + '_ {
+    text.char_indices().filter_map(move |(offset, c)| {
+        let end = offset + c.len_utf8();
+        let ends_sentence =
+            matches!(c, '.' | '!' | '?') && (end == text.len() || text[end..].starts_with(char::is_whitespace));
+        ends_sentence.then_some(end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_concatenate_to_text() {
+        let text = "  Generated text\nfrom 12 tokens ";
+        let tokens = split_tokens(text);
+        assert_eq!(tokens, vec!["  Generated", " text", "\nfrom", " 12", " tokens", " "]);
+        assert_eq!(tokens.concat(), text);
+        assert!(split_tokens("").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_buffered_stream_applies_backpressure() {
+        let response = MCPResponse {
+            id: uuid::Uuid::new_v4(),
+            result: Some(serde_json::json!({ "text": "one two three" })),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        };
+        let mut stream = buffered_stream(response.clone(), 1);
+        tokio::task::yield_now().await;
+        // Only one chunk is produced ahead of the reader
+        assert_eq!(stream.len(), 1);
+
+        let mut text = String::new();
+        while let Some(chunk) = stream.recv().await {
+            match chunk.unwrap() {
+                StreamChunk::Token { text: token, .. } => text.push_str(&token),
+                StreamChunk::Done(done) => assert_eq!(done.id, response.id),
+            }
+        }
+        assert_eq!(text, "one two three");
+    }
+
+    fn response(text: &str) -> MCPResponse {
+        MCPResponse {
+            id: uuid::Uuid::new_v4(),
+            result: Some(serde_json::json!({ "text": text })),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    async fn next_token(stream: &mut TokenStream) -> String {
+        match stream.recv().await.unwrap().unwrap() {
+            StreamChunk::Token { text, .. } => text,
+            StreamChunk::Done(_) => panic!("stream ended early"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_splice_switches_to_cloud_at_sentence_boundary() {
+        let (local, local_stream) = token_stream(4);
+        let (cloud, cloud_answer) = tokio::sync::oneshot::channel();
+        let mut stream = splice_stream(
+            local_stream,
+            async move { Ok(cloud_answer.await.unwrap()) },
+            Duration::from_secs(5),
+            4,
+        );
+
+        send_token(&local, 0, "One.").await.unwrap();
+        assert_eq!(next_token(&mut stream).await, "One.");
+        send_token(&local, 1, " Two").await.unwrap();
+        assert_eq!(next_token(&mut stream).await, " Two");
+        // Mid-sentence, so the local stream continues to the next boundary
+        cloud.send(response("Uno. Dos tres. Cuatro cinco.")).unwrap();
+        send_token(&local, 2, " three.").await.unwrap();
+        assert_eq!(next_token(&mut stream).await, " three.");
+
+        assert_eq!(next_token(&mut stream).await, " Cuatro");
+        assert_eq!(next_token(&mut stream).await, " cinco.");
+        let StreamChunk::Done(done) = stream.recv().await.unwrap().unwrap() else {
+            panic!("expected the final response");
+        };
+        let result = done.result.unwrap();
+        assert_eq!(result["text"], "One. Two three. Cuatro cinco.");
+        assert_eq!(result["splice"]["spliced"], true);
+        assert_eq!(result["splice"]["token_index"], 3);
+        // Local generation is stopped
+        assert!(local.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_splice_keeps_local_answer_unless_it_fails() {
+        let (local, local_stream) = token_stream(4);
+        let mut stream = splice_stream(
+            local_stream,
+            async { Err(Error::Network("offline".to_string())) },
+            Duration::from_secs(5),
+            4,
+        );
+        send_token(&local, 0, "Local.").await.unwrap();
+        local.send(Ok(StreamChunk::Done(response("Local.")))).await.unwrap();
+        assert_eq!(next_token(&mut stream).await, "Local.");
+        let Some(Ok(StreamChunk::Done(done))) = stream.recv().await else {
+            panic!("expected the final response");
+        };
+        let result = done.result.unwrap();
+        assert_eq!(result["splice"], serde_json::json!({ "spliced": false, "reason": "cloud_failed" }));
+
+        // A failed local stream falls back to the cloud answer
+        let (local, local_stream) = token_stream(4);
+        let mut stream = splice_stream(
+            local_stream,
+            async { Ok(response("Cloud answer.")) },
+            Duration::from_secs(5),
+            4,
+        );
+        local.send(Err(Error::Model("runner crashed".to_string()))).await.unwrap();
+        assert_eq!(next_token(&mut stream).await, "Cloud");
+        assert_eq!(next_token(&mut stream).await, " answer.");
+        let Some(Ok(StreamChunk::Done(done))) = stream.recv().await else {
+            panic!("expected the final response");
+        };
+        assert_eq!(done.result.unwrap()["splice"]["token_index"], 0);
+    }
+}
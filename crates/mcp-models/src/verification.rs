@@ -0,0 +1,130 @@
+//! Post-hoc verification of inference results
+//!
+//! Before a local response is returned it can be scored by a rule set
+//! (policy phrases, hallucination markers, degenerate output) and, when a
+//! verifier model is configured, by its agreement with the answer a second,
+//! cheaper model gives to the same request. The lower of the two scores
+//! decides whether the response passes.
+
+use mcp_common::config::VerificationConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Result of verifying a single response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationOutcome {
+    pub score: f32,
+    pub passed: bool,
+    pub reasons: Vec<String>,
+    pub verifier_model: Option<String>,
+    pub attempts: u32,
+}
+
+/// Extract the generated text from an inference result, if it has any
+pub fn response_text(result: &serde_json::Value) -> Option<&str> {
+    ["text", "response", "summary"]
+        .iter()
+        .find_map(|field| result.get(*field).and_then(|value| value.as_str()))
+}
+
+/// Rule-based scoring of a response
+pub struct RuleVerifier {
+    blocked_phrases: Vec<String>,
+    hallucination_markers: Vec<String>,
+}
+
+impl RuleVerifier {
+    pub fn new(config: &VerificationConfig) -> Self {
+        Self {
+            blocked_phrases: config.blocked_phrases.iter().map(|p| p.to_lowercase()).collect(),
+            hallucination_markers: config
+                .hallucination_markers
+                .iter()
+                .map(|m| m.to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Score a response in [0, 1] and explain any deductions
+    pub fn score(&self, text: &str) -> (f32, Vec<String>) {
+        let lowered = text.to_lowercase();
+        let mut score = 1.0f32;
+        let mut reasons = Vec::new();
+
+        if lowered.trim().is_empty() {
+            return (0.0, vec!["empty response".to_string()]);
+        }
+
+        for phrase in &self.blocked_phrases {
+            if lowered.contains(phrase.as_str()) {
+                score = 0.0;
+                reasons.push(format!("policy violation: '{}'", phrase));
+            }
+        }
+
+        for marker in &self.hallucination_markers {
+            if lowered.contains(marker.as_str()) {
+                score -= 0.3;
+                reasons.push(format!("hallucination marker: '{}'", marker));
+            }
+        }
+
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        if words.len() >= 8 {
+            let distinct = words.iter().collect::<HashSet<_>>().len();
+            if (distinct as f32) / (words.len() as f32) < 0.3 {
+                score -= 0.4;
+                reasons.push("repetitive output".to_string());
+            }
+        }
+
+        (score.clamp(0.0, 1.0), reasons)
+    }
+}
+
+/// Word-level Jaccard agreement between two responses
+pub fn agreement(primary: &str, secondary: &str) -> f32 {
+    let words = |text: &str| -> HashSet<String> {
+        text.split_whitespace()
+            .map(|word| {
+                word.trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase()
+            })
+            .filter(|word| !word.is_empty())
+            .collect()
+    };
+    let (a, b) = (words(primary), words(secondary));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f32 / a.union(&b).count() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_flag_policy_and_hallucination_markers() {
+        let config = VerificationConfig {
+            blocked_phrases: vec!["internal password".to_string()],
+            ..Default::default()
+        };
+        let verifier = RuleVerifier::new(&config);
+
+        assert_eq!(verifier.score("The valve opens at 40 psi.").0, 1.0);
+
+        let (score, reasons) = verifier.score("As an AI language model, the internal password is 1234");
+        assert_eq!(score, 0.0);
+        assert_eq!(reasons.len(), 2);
+
+        let (score, _) = verifier.score("ok ok ok ok ok ok ok ok ok ok");
+        assert!(score < config.min_score + 0.2);
+    }
+
+    #[test]
+    fn test_agreement_between_responses() {
+        assert_eq!(agreement("The pump is offline", "the pump is offline."), 1.0);
+        assert!(agreement("The pump is offline", "Reactor temperature nominal") < 0.2);
+    }
+}
@@ -1,5 +1,8 @@
 //! Alert management and notification system
 
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::config::GuardAlertsConfig;
+use mcp_common::events;
 use mcp_common::{Error, Result};
 use std::collections::HashMap;
 use tokio::sync::mpsc;
@@ -84,6 +87,23 @@ impl Default for AlertConfig {
     }
 }
 
+impl From<&GuardAlertsConfig> for AlertConfig {
+    fn from(config: &GuardAlertsConfig) -> Self {
+        let min_severity = match config.min_severity {
+            events::AlertSeverity::Info => AlertSeverity::Info,
+            events::AlertSeverity::Warning => AlertSeverity::Warning,
+            events::AlertSeverity::Critical => AlertSeverity::Critical,
+        };
+        AlertConfig {
+            channels: vec![AlertChannel::Log],
+            min_severity,
+            rate_limit_seconds: config.rate_limit_secs,
+            batch_size: config.batch_size,
+            batch_timeout_seconds: config.batch_timeout_secs,
+        }
+    }
+}
+
 /// Alert manager for handling notifications
 pub struct AlertManager {
     config: AlertConfig,
@@ -243,12 +263,16 @@ impl AlertManager {
             "count": alerts.len()
         });
 
+        let body = serde_json::to_vec(&payload)?;
+        let sent = body.len() as u64;
         let response = client
             .post(url)
-            .json(&payload)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
             .send()
             .await
             .map_err(|e| Error::Internal(format!("Webhook request failed: {}", e)))?;
+        bandwidth::record(Subsystem::Alerts, sent, 0);
 
         if !response.status().is_success() {
             return Err(Error::Internal(format!(
@@ -289,12 +313,16 @@ impl AlertManager {
                 "icon_emoji": ":robot_face:"
             });
 
+            let body = serde_json::to_vec(&payload)?;
+            let sent = body.len() as u64;
             let response = client
                 .post(webhook_url)
-                .json(&payload)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body)
                 .send()
                 .await
                 .map_err(|e| Error::Internal(format!("Slack webhook failed: {}", e)))?;
+            bandwidth::record(Subsystem::Alerts, sent, 0);
 
             if !response.status().is_success() {
                 return Err(Error::Internal(format!(
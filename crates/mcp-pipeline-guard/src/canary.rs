@@ -0,0 +1,481 @@
+//! Canary evaluation of local models
+//!
+//! A model can load cleanly and still answer badly: a broken quantization,
+//! a truncated download that passed its checksum, a new version that got
+//! slower. [`ModelCanary`] runs the configured golden prompts against each
+//! model as it is loaded, and against every loaded model every
+//! `interval_secs`. Each answer is scored on the expected phrases it
+//! contains and, once its family has a baseline, on word overlap with the
+//! baseline's answer and latency relative to it. The first passing run of a
+//! family (the model id before any `@version`) becomes its baseline.
+//!
+//! A model with any regressed prompt is reported unhealthy and an alert is
+//! raised; the gateway routes around it until a later run passes.
+
+use chrono::{DateTime, Utc};
+use mcp_common::config::{CanaryPromptConfig, ModelCanaryConfig};
+use mcp_common::events::{self, AlertSeverity, EventKind, GatewayEvent};
+use mcp_common::{MCPRequest, MCPResponse, ModelId, RequestContext, RequestSource, Result};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Device ID canary requests are sent as
+pub const CANARY_DEVICE_ID: &str = "model-canary";
+
+/// Runs canary requests on local models; implemented over the model engine
+#[async_trait::async_trait]
+pub trait CanaryTarget: Send + Sync {
+    /// Run `request` on the loaded local model `model_id`
+    async fn infer(&self, model_id: &ModelId, request: MCPRequest) -> Result<MCPResponse>;
+
+    /// Local models currently loaded
+    async fn loaded_models(&self) -> Result<Vec<ModelId>>;
+}
+
+/// How one golden prompt fared
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptResult {
+    pub name: String,
+    pub latency_ms: u64,
+    /// Fraction of the expected phrases found in the answer
+    pub quality: f64,
+    /// Word overlap with the baseline's answer, once there is a baseline
+    pub similarity: Option<f64>,
+    pub baseline_latency_ms: Option<u64>,
+    /// Why the prompt regressed, if it did
+    pub regression: Option<String>,
+}
+
+/// Outcome of one evaluation of a model
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryReport {
+    pub model_id: ModelId,
+    /// Model whose answers this run was compared against, if any
+    pub baseline_model_id: Option<ModelId>,
+    pub evaluated_at: DateTime<Utc>,
+    pub healthy: bool,
+    pub prompts: Vec<PromptResult>,
+}
+
+/// Answers and latencies of a family's first passing run, by prompt name
+#[derive(Debug, Clone)]
+struct Baseline {
+    model_id: ModelId,
+    answers: HashMap<String, (String, u64)>,
+}
+
+/// Evaluates local models against golden prompts and keeps their reports
+pub struct ModelCanary {
+    config: ModelCanaryConfig,
+    target: Arc<dyn CanaryTarget>,
+    baselines: Mutex<HashMap<String, Baseline>>,
+    reports: Mutex<HashMap<ModelId, CanaryReport>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ModelCanary {
+    pub fn new(config: ModelCanaryConfig, target: Arc<dyn CanaryTarget>) -> Self {
+        Self {
+            config,
+            target,
+            baselines: Mutex::new(HashMap::new()),
+            reports: Mutex::new(HashMap::new()),
+            task: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled && !self.config.prompts.is_empty()
+    }
+
+    /// Evaluate models as they are loaded and all of them every `interval_secs`
+    pub fn start(self: &Arc<Self>) {
+        if !self.enabled() {
+            return;
+        }
+        let mut subscriber = match events::subscribe(&[EventKind::Model]) {
+            Ok(subscriber) => subscriber,
+            Err(e) => {
+                warn!("Model canary is not watching model loads: {}", e);
+                return;
+            },
+        };
+        let canary = Arc::downgrade(self);
+        let interval_secs = self.config.interval_secs.max(1);
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            loop {
+                tokio::select! {
+                    envelope = subscriber.recv() => {
+                        let Some(envelope) = envelope else { break };
+                        let Some(canary) = canary.upgrade() else { break };
+                        match envelope.event {
+                            GatewayEvent::ModelLoaded { model_id, .. } => {
+                                canary.evaluate(&model_id).await;
+                            },
+                            GatewayEvent::ModelUnloaded { model_id } => canary.forget(&model_id),
+                            _ => {},
+                        }
+                    },
+                    _ = interval.tick() => {
+                        let Some(canary) = canary.upgrade() else { break };
+                        canary.evaluate_loaded().await;
+                    },
+                }
+            }
+        });
+        if let Some(previous) = self.task.lock().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.task.lock().take() {
+            handle.abort();
+        }
+    }
+
+    /// Evaluate every loaded model once
+    pub async fn evaluate_loaded(&self) {
+        match self.target.loaded_models().await {
+            Ok(models) => {
+                for model_id in models {
+                    self.evaluate(&model_id).await;
+                }
+            },
+            Err(e) => warn!("Model canary could not list loaded models: {}", e),
+        }
+    }
+
+    /// Run every golden prompt on `model_id` and record the outcome
+    pub async fn evaluate(&self, model_id: &ModelId) -> CanaryReport {
+        let family = family(model_id);
+        let baseline = self.baselines.lock().get(family).cloned();
+        let mut prompts = Vec::with_capacity(self.config.prompts.len());
+        let mut answers = HashMap::new();
+        for prompt in &self.config.prompts {
+            let start = Instant::now();
+            let result = self.target.infer(model_id, canary_request(prompt)).await;
+            let latency_ms = start.elapsed().as_millis() as u64;
+            let baseline_answer = baseline.as_ref().and_then(|baseline| baseline.answers.get(&prompt.name));
+            let (scored, answer) = self.score(prompt, result, latency_ms, baseline_answer);
+            if let Some(answer) = answer {
+                answers.insert(prompt.name.clone(), (answer, latency_ms));
+            }
+            prompts.push(scored);
+        }
+
+        let healthy = prompts.iter().all(|prompt| prompt.regression.is_none());
+        if healthy && baseline.is_none() {
+            info!("Model {} is the canary baseline of {}", model_id, family);
+            self.baselines.lock().insert(
+                family.to_string(),
+                Baseline {
+                    model_id: model_id.clone(),
+                    answers,
+                },
+            );
+        }
+        let report = CanaryReport {
+            model_id: model_id.clone(),
+            baseline_model_id: baseline.map(|baseline| baseline.model_id),
+            evaluated_at: Utc::now(),
+            healthy,
+            prompts,
+        };
+        self.record(report.clone());
+        report
+    }
+
+    fn score(
+        &self,
+        prompt: &CanaryPromptConfig,
+        result: Result<MCPResponse>,
+        latency_ms: u64,
+        baseline: Option<&(String, u64)>,
+    ) -> (PromptResult, Option<String>) {
+        let mut scored = PromptResult {
+            name: prompt.name.clone(),
+            latency_ms,
+            quality: 0.0,
+            similarity: None,
+            baseline_latency_ms: baseline.map(|(_, latency_ms)| *latency_ms),
+            regression: None,
+        };
+        let answer = match result {
+            Ok(MCPResponse { error: Some(error), .. }) => {
+                scored.regression = Some(format!("error {}: {}", error.code, error.message));
+                return (scored, None);
+            },
+            Ok(MCPResponse { result: Some(result), .. }) => answer_text(&result),
+            Ok(_) => {
+                scored.regression = Some("response without a result".to_string());
+                return (scored, None);
+            },
+            Err(e) => {
+                scored.regression = Some(e.to_string());
+                return (scored, None);
+            },
+        };
+
+        let lowercase = answer.to_lowercase();
+        let found = prompt
+            .expected
+            .iter()
+            .filter(|phrase| lowercase.contains(&phrase.to_lowercase()))
+            .count();
+        scored.quality = if prompt.expected.is_empty() {
+            1.0
+        } else {
+            found as f64 / prompt.expected.len() as f64
+        };
+        scored.similarity = baseline.map(|(baseline_answer, _)| similarity(&answer, baseline_answer));
+
+        let mut regressions = Vec::new();
+        if scored.quality < self.config.min_quality {
+            regressions.push(format!("quality {:.2} below {:.2}", scored.quality, self.config.min_quality));
+        }
+        if let Some(similarity) = scored.similarity.filter(|similarity| *similarity < self.config.min_similarity) {
+            regressions.push(format!(
+                "similarity to baseline {:.2} below {:.2}",
+                similarity, self.config.min_similarity
+            ));
+        }
+        if latency_ms > prompt.max_latency_ms {
+            regressions.push(format!("took {}ms, over {}ms", latency_ms, prompt.max_latency_ms));
+        }
+        if let Some((_, baseline_ms)) = baseline {
+            let ratio = latency_ms as f64 / (*baseline_ms).max(1) as f64;
+            if ratio > self.config.max_latency_ratio {
+                regressions.push(format!(
+                    "took {}ms, {:.1}x the baseline's {}ms",
+                    latency_ms, ratio, baseline_ms
+                ));
+            }
+        }
+        if !regressions.is_empty() {
+            scored.regression = Some(regressions.join("; "));
+        }
+        (scored, Some(answer))
+    }
+
+    fn record(&self, report: CanaryReport) {
+        let was_healthy = self
+            .reports
+            .lock()
+            .insert(report.model_id.clone(), report.clone())
+            .map_or(true, |previous| previous.healthy);
+        if report.healthy {
+            debug!("Model {} passed its canary prompts", report.model_id);
+            if !was_healthy {
+                events::publish(GatewayEvent::AlertRaised {
+                    source: "model_canary".to_string(),
+                    severity: AlertSeverity::Info,
+                    message: format!("Model {} passes its canary prompts again", report.model_id),
+                });
+            }
+            return;
+        }
+        let reasons: Vec<String> = report
+            .prompts
+            .iter()
+            .filter_map(|prompt| prompt.regression.as_ref().map(|reason| format!("{}: {}", prompt.name, reason)))
+            .collect();
+        warn!("Model {} failed its canary prompts: {}", report.model_id, reasons.join(", "));
+        if was_healthy {
+            events::publish(GatewayEvent::AlertRaised {
+                source: "model_canary".to_string(),
+                severity: AlertSeverity::Critical,
+                message: format!(
+                    "Model {} regressed and is routed around: {}",
+                    report.model_id,
+                    reasons.join(", ")
+                ),
+            });
+        }
+    }
+
+    /// Drop the report of a model that was unloaded
+    pub fn forget(&self, model_id: &ModelId) {
+        self.reports.lock().remove(model_id);
+    }
+
+    /// Whether the latest evaluation of `model_id` regressed
+    pub fn is_unhealthy(&self, model_id: &ModelId) -> bool {
+        self.reports.lock().get(model_id).is_some_and(|report| !report.healthy)
+    }
+
+    /// Latest report of every evaluated model, by model id
+    pub fn reports(&self) -> Vec<CanaryReport> {
+        let mut reports: Vec<CanaryReport> = self.reports.lock().values().cloned().collect();
+        reports.sort_by(|a, b| a.model_id.cmp(&b.model_id));
+        reports
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        if !self.enabled() {
+            return;
+        }
+        let reports = self.reports.lock();
+        metrics.insert("canary_evaluated_models".to_string(), reports.len() as f32);
+        metrics.insert(
+            "canary_unhealthy_models".to_string(),
+            reports.values().filter(|report| !report.healthy).count() as f32,
+        );
+    }
+}
+
+/// The model id without its `@version`
+fn family(model_id: &str) -> &str {
+    model_id.split_once('@').map_or(model_id, |(family, _)| family)
+}
+
+fn canary_request(prompt: &CanaryPromptConfig) -> MCPRequest {
+    MCPRequest {
+        id: uuid::Uuid::new_v4(),
+        device_id: CANARY_DEVICE_ID.to_string(),
+        method: prompt.method.clone(),
+        params: prompt.params.clone(),
+        context: Some(RequestContext {
+            source: RequestSource::Probe,
+            ..Default::default()
+        }),
+        timestamp: Utc::now(),
+    }
+}
+
+/// The generated text of a result, or the whole result serialized
+fn answer_text(result: &serde_json::Value) -> String {
+    ["text", "content", "response"]
+        .iter()
+        .find_map(|key| result.get(*key).and_then(|value| value.as_str()))
+        .map_or_else(|| result.to_string(), str::to_string)
+}
+
+/// Jaccard overlap of the lowercase words of two answers, from 0 to 1
+fn similarity(a: &str, b: &str) -> f64 {
+    let words = |text: &str| -> HashSet<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::Error;
+
+    /// Answers with a fixed text per model after a fixed delay per model
+    struct FakeTarget {
+        answers: HashMap<ModelId, (&'static str, u64)>,
+    }
+
+    #[async_trait::async_trait]
+    impl CanaryTarget for FakeTarget {
+        async fn infer(&self, model_id: &ModelId, _request: MCPRequest) -> Result<MCPResponse> {
+            let (text, delay_ms) = self
+                .answers
+                .get(model_id)
+                .ok_or_else(|| Error::Model(format!("{} is not loaded", model_id)))?;
+            tokio::time::sleep(Duration::from_millis(*delay_ms)).await;
+            Ok(MCPResponse {
+                id: uuid::Uuid::new_v4(),
+                result: Some(serde_json::json!({ "text": text })),
+                error: None,
+                timestamp: Utc::now(),
+            })
+        }
+
+        async fn loaded_models(&self) -> Result<Vec<ModelId>> {
+            Ok(self.answers.keys().cloned().collect())
+        }
+    }
+
+    fn canary(answers: &[(&str, &'static str, u64)]) -> ModelCanary {
+        let target = FakeTarget {
+            answers: answers
+                .iter()
+                .map(|(model_id, text, delay_ms)| (model_id.to_string(), (*text, *delay_ms)))
+                .collect(),
+        };
+        ModelCanary::new(
+            ModelCanaryConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            Arc::new(target),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_first_passing_run_becomes_the_baseline() {
+        let canary = canary(&[
+            ("llama@1", "The answer is 4.", 20),
+            ("llama@2", "The answer is 4.", 20),
+        ]);
+
+        let first = canary.evaluate(&"llama@1".to_string()).await;
+        assert!(first.healthy);
+        assert_eq!(first.baseline_model_id, None);
+
+        let second = canary.evaluate(&"llama@2".to_string()).await;
+        assert!(second.healthy);
+        assert_eq!(second.baseline_model_id.as_deref(), Some("llama@1"));
+        assert_eq!(second.prompts[0].similarity, Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn test_regressed_model_is_unhealthy_until_it_passes() {
+        let canary = canary(&[
+            ("llama@1", "The answer is 4.", 1),
+            ("llama@2", "I cannot help with that request.", 1),
+            ("phi", "", 1),
+        ]);
+        canary.evaluate(&"llama@1".to_string()).await;
+
+        let report = canary.evaluate(&"llama@2".to_string()).await;
+        assert!(!report.healthy);
+        let regression = report.prompts[0].regression.as_deref().unwrap();
+        assert!(regression.contains("quality"));
+        assert!(regression.contains("similarity"));
+        assert!(canary.is_unhealthy(&"llama@2".to_string()));
+        assert!(!canary.is_unhealthy(&"llama@1".to_string()));
+
+        // A failing model never becomes its family's baseline
+        assert!(!canary.evaluate(&"phi".to_string()).await.healthy);
+        assert!(canary.baselines.lock().get("phi").is_none());
+
+        canary.forget(&"llama@2".to_string());
+        assert!(!canary.is_unhealthy(&"llama@2".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_latency_regression_against_baseline() {
+        let canary = canary(&[("llama@1", "4", 5), ("llama@2", "4", 60)]);
+        canary.evaluate(&"llama@1".to_string()).await;
+
+        let report = canary.evaluate(&"llama@2".to_string()).await;
+        assert!(!report.healthy);
+        assert!(report.prompts[0].regression.as_deref().unwrap().contains("baseline's"));
+    }
+
+    #[test]
+    fn test_similarity_and_family() {
+        assert_eq!(similarity("The answer is 4", "the ANSWER is 4."), 1.0);
+        assert_eq!(similarity("yes", "no"), 0.0);
+        assert_eq!(family("llama@2"), "llama");
+        assert_eq!(family("phi"), "phi");
+    }
+}
@@ -1,6 +1,11 @@
 //! Core pipeline guard implementation
 
 use crate::{HealthMonitor, RecoveryEngine, PipelineState, AlertManager, HealthThresholds, PipelineAware};
+use crate::alerts::AlertConfig;
+use crate::incidents::{IncidentLog, IncidentReport};
+use crate::log_escalation::LogEscalation;
+use mcp_common::config::LogEscalationConfig;
+use mcp_common::events::{self, GatewayEvent};
 use mcp_common::{Error, Result, ComponentHealth, HealthLevel};
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -26,19 +31,26 @@ pub struct GuardConfig {
     pub health_thresholds: HealthThresholds,
     /// Enable performance monitoring
     pub performance_monitoring: bool,
+    /// Log level escalation for degraded components
+    pub log_escalation: LogEscalationConfig,
+    /// Alert batching and filtering
+    pub alerts: AlertConfig,
 }
 
 impl GuardConfig {
-    /// Create guard config from MCP config
+    /// Create guard config from the `pipeline_guard` section of the MCP config
     pub fn from_mcp_config(config: &mcp_common::Config) -> Result<Self> {
+        let guard = &config.pipeline_guard;
         Ok(GuardConfig {
-            health_check_interval_seconds: 30,
-            recovery_timeout_seconds: 60,
-            max_consecutive_failures: 3,
-            circuit_breaker_reset_seconds: 300,
-            auto_recovery_enabled: true,
-            health_thresholds: HealthThresholds::default(),
-            performance_monitoring: true,
+            health_check_interval_seconds: guard.health_check_interval_secs,
+            recovery_timeout_seconds: guard.recovery_timeout_secs,
+            max_consecutive_failures: guard.max_consecutive_failures,
+            circuit_breaker_reset_seconds: guard.circuit_breaker_reset_secs,
+            auto_recovery_enabled: guard.auto_recovery,
+            health_thresholds: HealthThresholds::from(&guard.thresholds),
+            performance_monitoring: guard.performance_monitoring,
+            log_escalation: config.telemetry.log_escalation.clone(),
+            alerts: AlertConfig::from(&guard.alerts),
         })
     }
 }
@@ -51,6 +63,8 @@ pub struct PipelineGuard {
     alert_manager: Arc<AlertManager>,
     pipeline_state: Arc<RwLock<PipelineState>>,
     registered_components: Arc<Mutex<HashMap<String, Arc<dyn PipelineAware + Send + Sync>>>>,
+    log_escalation: Arc<LogEscalation>,
+    incidents: Arc<parking_lot::Mutex<IncidentLog>>,
     _monitoring_handle: tokio::task::JoinHandle<()>,
 }
 
@@ -61,9 +75,13 @@ impl PipelineGuard {
 
         let health_monitor = Arc::new(Mutex::new(HealthMonitor::new(config.health_thresholds.clone())));
         let recovery_engine = Arc::new(RecoveryEngine::new());
-        let alert_manager = Arc::new(AlertManager::new());
+        let alert_manager = Arc::new(AlertManager::with_config(config.alerts.clone()));
         let pipeline_state = Arc::new(RwLock::new(PipelineState::new()));
         let registered_components = Arc::new(Mutex::new(HashMap::new()));
+        // Escalations only take effect through the installed global subscriber
+        let log_escalation = LogEscalation::installed()
+            .unwrap_or_else(|| Arc::new(LogEscalation::new(config.log_escalation.clone())));
+        let incidents = Arc::new(parking_lot::Mutex::new(IncidentLog::new()));
 
         // Start background monitoring
         let monitoring_handle = {
@@ -72,6 +90,8 @@ impl PipelineGuard {
             let alert_manager = alert_manager.clone();
             let pipeline_state = pipeline_state.clone();
             let registered_components = registered_components.clone();
+            let log_escalation = log_escalation.clone();
+            let incidents = incidents.clone();
             let interval_duration = Duration::from_secs(config.health_check_interval_seconds);
             let auto_recovery = config.auto_recovery_enabled;
 
@@ -87,6 +107,8 @@ impl PipelineGuard {
                         &alert_manager,
                         &pipeline_state,
                         &registered_components,
+                        &log_escalation,
+                        &incidents,
                         auto_recovery,
                     ).await {
                         error!("Error in monitoring cycle: {}", e);
@@ -104,6 +126,8 @@ impl PipelineGuard {
             alert_manager,
             pipeline_state,
             registered_components,
+            log_escalation,
+            incidents,
             _monitoring_handle: monitoring_handle,
         })
     }
@@ -177,6 +201,8 @@ impl PipelineGuard {
             &self.alert_manager,
             &self.pipeline_state,
             &self.registered_components,
+            &self.log_escalation,
+            &self.incidents,
             self.config.auto_recovery_enabled,
         ).await
     }
@@ -200,6 +226,8 @@ impl PipelineGuard {
         alert_manager: &AlertManager,
         pipeline_state: &Arc<RwLock<PipelineState>>,
         registered_components: &Arc<Mutex<HashMap<String, Arc<dyn PipelineAware + Send + Sync>>>>,
+        log_escalation: &LogEscalation,
+        incidents: &parking_lot::Mutex<IncidentLog>,
         auto_recovery: bool,
     ) -> Result<()> {
         debug!("Starting monitoring cycle");
@@ -229,6 +257,13 @@ impl PipelineGuard {
             
             if !health_assessment.is_healthy {
                 warn!("Component {} is unhealthy: {}", component_id, health_assessment.reason);
+
+                // Log the component verbosely for the rest of the incident
+                if incidents.lock().open(&component_id, &health_assessment.reason)
+                    && log_escalation.escalate(&component_id)
+                {
+                    info!("Raised log level of component {} while it is degraded", component_id);
+                }
                 
                 // Send alert
                 alert_manager.send_component_alert(&component_id, &health_assessment.reason).await?;
@@ -236,14 +271,35 @@ impl PipelineGuard {
                 // Trigger recovery if enabled
                 if auto_recovery {
                     info!("Triggering automatic recovery for component: {}", component_id);
-                    if let Err(e) = recovery_engine.recover_component(component.clone()).await {
+                    let outcome = recovery_engine.recover_component(component.clone()).await;
+                    events::publish(GatewayEvent::RecoveryAttempted {
+                        component: component_id.clone(),
+                        success: outcome.is_ok(),
+                        detail: match &outcome {
+                            Ok(()) => health_assessment.reason.clone(),
+                            Err(e) => e.to_string(),
+                        },
+                    });
+                    if let Err(e) = outcome {
                         error!("Recovery failed for component {}: {}", component_id, e);
+                        incidents.lock().record_recovery(&component_id, format!("failed: {}", e));
                         alert_manager.send_recovery_failed_alert(&component_id, &e.to_string()).await?;
                     } else {
                         info!("Recovery completed for component: {}", component_id);
+                        incidents.lock().record_recovery(&component_id, "succeeded".to_string());
                         alert_manager.send_recovery_success_alert(&component_id).await?;
                     }
                 }
+            } else if incidents.lock().is_open(&component_id) {
+                let logs = log_escalation.restore(&component_id);
+                if let Some(incident) = incidents.lock().resolve(&component_id, logs) {
+                    info!(
+                        "Component {} recovered; incident {} captured {} log line(s)",
+                        component_id,
+                        incident.id,
+                        incident.logs.lines.len()
+                    );
+                }
             }
         }
 
@@ -251,6 +307,18 @@ impl PipelineGuard {
         Ok(())
     }
 
+    /// Incident reports, resolved ones first; open incidents carry the logs
+    /// captured so far
+    pub fn incident_reports(&self) -> Vec<IncidentReport> {
+        let mut reports = self.incidents.lock().reports();
+        for report in reports.iter_mut().filter(|report| report.resolved_at.is_none()) {
+            if let Some(logs) = self.log_escalation.captured(&report.component_id) {
+                report.logs = logs;
+            }
+        }
+        reports
+    }
+
     /// Get pipeline configuration
     pub fn config(&self) -> &GuardConfig {
         &self.config
@@ -1,5 +1,6 @@
 //! Health monitoring functionality for pipeline components
 
+use mcp_common::config::GuardThresholdsConfig;
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
@@ -34,6 +35,19 @@ impl Default for HealthThresholds {
     }
 }
 
+impl From<&GuardThresholdsConfig> for HealthThresholds {
+    fn from(config: &GuardThresholdsConfig) -> Self {
+        HealthThresholds {
+            max_error_rate: config.max_error_rate,
+            max_response_time_ms: config.max_response_time_ms,
+            min_throughput_rps: config.min_throughput_rps,
+            max_memory_usage_mb: config.max_memory_usage_mb,
+            max_cpu_usage: config.max_cpu_usage,
+            consecutive_failure_threshold: config.consecutive_failure_threshold,
+        }
+    }
+}
+
 /// Health assessment result
 #[derive(Debug, Clone)]
 pub struct HealthAssessment {
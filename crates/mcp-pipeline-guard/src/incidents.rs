@@ -0,0 +1,92 @@
+//! Incident reports for degraded components
+//!
+//! An incident opens when the guard first finds a component unhealthy and
+//! is resolved once the component passes a health check again. Its report
+//! carries why the component was degraded, the outcome of each recovery
+//! attempt and the verbose log window captured while the component's log
+//! level was escalated.
+
+use crate::log_escalation::CapturedLogs;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// Resolved incidents kept for reporting
+const MAX_RESOLVED: usize = 50;
+
+/// What happened while a component was degraded
+#[derive(Debug, Clone, Serialize)]
+pub struct IncidentReport {
+    pub id: String,
+    pub component_id: String,
+    /// Health assessment that opened the incident
+    pub reason: String,
+    pub opened_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    /// Outcome of each recovery attempt, in order
+    pub recovery_attempts: Vec<String>,
+    /// Log lines captured at the escalated level
+    pub logs: CapturedLogs,
+}
+
+/// Open incidents per component and the latest resolved ones
+#[derive(Default)]
+pub struct IncidentLog {
+    open: HashMap<String, IncidentReport>,
+    resolved: VecDeque<IncidentReport>,
+}
+
+impl IncidentLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open an incident for a component; returns false if one is open
+    pub fn open(&mut self, component_id: &str, reason: &str) -> bool {
+        if self.open.contains_key(component_id) {
+            return false;
+        }
+        self.open.insert(
+            component_id.to_string(),
+            IncidentReport {
+                id: uuid::Uuid::new_v4().to_string(),
+                component_id: component_id.to_string(),
+                reason: reason.to_string(),
+                opened_at: Utc::now(),
+                resolved_at: None,
+                recovery_attempts: Vec::new(),
+                logs: CapturedLogs::default(),
+            },
+        );
+        true
+    }
+
+    pub fn is_open(&self, component_id: &str) -> bool {
+        self.open.contains_key(component_id)
+    }
+
+    pub fn record_recovery(&mut self, component_id: &str, outcome: String) {
+        if let Some(incident) = self.open.get_mut(component_id) {
+            incident.recovery_attempts.push(outcome);
+        }
+    }
+
+    /// Resolve a component's open incident with the logs captured for it
+    pub fn resolve(&mut self, component_id: &str, logs: Option<CapturedLogs>) -> Option<&IncidentReport> {
+        let mut incident = self.open.remove(component_id)?;
+        incident.resolved_at = Some(Utc::now());
+        incident.logs = logs.unwrap_or_default();
+        if self.resolved.len() == MAX_RESOLVED {
+            self.resolved.pop_front();
+        }
+        self.resolved.push_back(incident);
+        self.resolved.back()
+    }
+
+    /// Resolved incidents, oldest first, followed by the open ones
+    pub fn reports(&self) -> Vec<IncidentReport> {
+        let mut open: Vec<&IncidentReport> = self.open.values().collect();
+        open.sort_by_key(|incident| incident.opened_at);
+        self.resolved.iter().chain(open).cloned().collect()
+    }
+}
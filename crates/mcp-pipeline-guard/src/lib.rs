@@ -8,12 +8,18 @@ pub mod health_monitor;
 pub mod recovery_engine;
 pub mod pipeline_state;
 pub mod alerts;
+pub mod incidents;
+pub mod log_escalation;
+pub mod canary;
 
 pub use guard::{PipelineGuard, GuardConfig};
 pub use health_monitor::{HealthMonitor, HealthThresholds};
 pub use recovery_engine::{RecoveryEngine, RecoveryStrategy};
 pub use pipeline_state::{PipelineState, PipelineStatus, ComponentStatus};
 pub use alerts::{AlertManager, AlertSeverity, AlertChannel};
+pub use incidents::{IncidentLog, IncidentReport};
+pub use log_escalation::{CapturedLogs, EscalationLayer, LogEscalation};
+pub use canary::{CanaryReport, CanaryTarget, ModelCanary};
 
 use mcp_common::{Error, Result};
 
@@ -0,0 +1,303 @@
+//! Temporary log escalation for degraded components
+//!
+//! Debug logging everywhere wears out the flash of constrained devices, yet
+//! it is what an incident needs. [`EscalationLayer`] filters events at the
+//! configured base level, except for the log targets of components the
+//! pipeline guard has escalated: those are let through at the escalated
+//! level and kept in a bounded in-memory window that ends up in the
+//! incident report. Escalations lapse on their own after the configured
+//! duration, so a component that stays degraded does not log verbosely
+//! forever.
+
+use chrono::Utc;
+use mcp_common::config::LogEscalationConfig;
+use mcp_telemetry::OtlpLogLayer;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+static INSTALLED: OnceLock<Arc<LogEscalation>> = OnceLock::new();
+
+/// Log lines captured while a component was escalated
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CapturedLogs {
+    pub lines: Vec<String>,
+    /// Lines dropped once the window was full
+    pub dropped: u64,
+}
+
+struct Escalation {
+    targets: Vec<String>,
+    until: Instant,
+    lines: VecDeque<String>,
+    dropped: u64,
+}
+
+impl Escalation {
+    fn covers(&self, target: &str, now: Instant) -> bool {
+        self.until > now && self.targets.iter().any(|prefix| target.starts_with(prefix.as_str()))
+    }
+}
+
+/// Per-component log levels raised while components are degraded
+pub struct LogEscalation {
+    config: LogEscalationConfig,
+    base: LevelFilter,
+    level: LevelFilter,
+    escalations: Mutex<HashMap<String, Escalation>>,
+    /// Escalations not yet restored; logging skips the lock while there are none
+    active: AtomicUsize,
+}
+
+impl LogEscalation {
+    pub fn new(config: LogEscalationConfig) -> Self {
+        let parse = |level: &str| level.parse().unwrap_or(LevelFilter::INFO);
+        Self {
+            base: parse(&config.base_level),
+            level: parse(&config.level),
+            config,
+            escalations: Mutex::new(HashMap::new()),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Install the global subscriber: formatted output and OTLP log export
+    /// filtered by a shared escalation, which pipeline guards created
+    /// afterwards drive
+    pub fn install(config: LogEscalationConfig) -> Arc<Self> {
+        let escalation = INSTALLED.get_or_init(|| Arc::new(Self::new(config))).clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(escalation.layer())
+            .with(tracing_subscriber::fmt::layer())
+            .with(OtlpLogLayer);
+        let _ = tracing::subscriber::set_global_default(subscriber);
+        escalation
+    }
+
+    /// The escalation installed as part of the global subscriber, if any
+    pub fn installed() -> Option<Arc<Self>> {
+        INSTALLED.get().cloned()
+    }
+
+    pub fn layer(self: &Arc<Self>) -> EscalationLayer {
+        EscalationLayer(Arc::clone(self))
+    }
+
+    /// Raise the log level of a component. An ongoing escalation is not
+    /// extended; returns whether a new one started.
+    pub fn escalate(&self, component_id: &str) -> bool {
+        if !self.config.enabled || self.level <= self.base {
+            return false;
+        }
+        let mut escalations = self.escalations.lock();
+        if escalations.contains_key(component_id) {
+            return false;
+        }
+        let targets = self
+            .config
+            .component_targets
+            .get(component_id)
+            .cloned()
+            .unwrap_or_else(|| vec![component_id.to_string()]);
+        escalations.insert(
+            component_id.to_string(),
+            Escalation {
+                targets,
+                until: Instant::now() + Duration::from_secs(self.config.duration_secs),
+                lines: VecDeque::new(),
+                dropped: 0,
+            },
+        );
+        self.active.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Whether a component currently logs at the escalated level
+    pub fn is_escalated(&self, component_id: &str) -> bool {
+        self.escalations
+            .lock()
+            .get(component_id)
+            .is_some_and(|escalation| escalation.until > Instant::now())
+    }
+
+    /// Lines captured so far for a component
+    pub fn captured(&self, component_id: &str) -> Option<CapturedLogs> {
+        self.escalations.lock().get(component_id).map(|escalation| CapturedLogs {
+            lines: escalation.lines.iter().cloned().collect(),
+            dropped: escalation.dropped,
+        })
+    }
+
+    /// Restore a component's log level, returning what was captured
+    pub fn restore(&self, component_id: &str) -> Option<CapturedLogs> {
+        let escalation = self.escalations.lock().remove(component_id)?;
+        self.active.fetch_sub(1, Ordering::Relaxed);
+        Some(CapturedLogs {
+            lines: escalation.lines.into(),
+            dropped: escalation.dropped,
+        })
+    }
+
+    /// Level events from `target` are logged at
+    fn level_for(&self, target: &str) -> LevelFilter {
+        if self.active.load(Ordering::Relaxed) == 0 {
+            return self.base;
+        }
+        let now = Instant::now();
+        let escalations = self.escalations.lock();
+        if escalations.values().any(|escalation| escalation.covers(target, now)) {
+            self.level
+        } else {
+            self.base
+        }
+    }
+
+    /// Keep an event in the window of every escalation covering `target`
+    fn capture(&self, target: &str, line: impl FnOnce() -> String) {
+        if self.active.load(Ordering::Relaxed) == 0 || self.config.max_captured_lines == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut escalations = self.escalations.lock();
+        let covering: Vec<&mut Escalation> = escalations
+            .values_mut()
+            .filter(|escalation| escalation.covers(target, now))
+            .collect();
+        if covering.is_empty() {
+            return;
+        }
+        let line = line();
+        for escalation in covering {
+            if escalation.lines.len() >= self.config.max_captured_lines {
+                escalation.lines.pop_front();
+                escalation.dropped += 1;
+            }
+            escalation.lines.push_back(line.clone());
+        }
+    }
+}
+
+/// Filters and captures events according to a [`LogEscalation`]
+pub struct EscalationLayer(Arc<LogEscalation>);
+
+impl<S: Subscriber> Layer<S> for EscalationLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if *metadata.level() <= self.0.base {
+            Interest::always()
+        } else if *metadata.level() <= self.0.level {
+            // Decided per event, as escalations come and go
+            Interest::sometimes()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        *metadata.level() <= self.0.level_for(metadata.target())
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.0.level.max(self.0.base))
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        self.0.capture(metadata.target(), || {
+            let mut fields = FieldText::default();
+            event.record(&mut fields);
+            format!(
+                "{} {} {}: {}",
+                Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                metadata.level(),
+                metadata.target(),
+                fields.0
+            )
+        });
+    }
+}
+
+/// An event's message followed by its other fields as `name=value`
+#[derive(Default)]
+struct FieldText(String);
+
+impl Visit for FieldText {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escalation(duration_secs: u64, max_captured_lines: usize) -> Arc<LogEscalation> {
+        let mut config = LogEscalationConfig {
+            duration_secs,
+            max_captured_lines,
+            ..Default::default()
+        };
+        config.component_targets.insert("router".to_string(), vec!["edge_router".to_string()]);
+        Arc::new(LogEscalation::new(config))
+    }
+
+    #[test]
+    fn test_escalated_component_logs_verbosely_and_is_captured() {
+        let escalation = escalation(300, 2);
+        let subscriber = tracing_subscriber::registry().with(escalation.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!(target: "edge_router::select", "not yet");
+            assert!(escalation.escalate("router"));
+            assert!(!escalation.escalate("router"));
+            assert!(escalation.is_escalated("router"));
+
+            tracing::debug!(target: "edge_router::select", endpoint = "cloud-1", "picked");
+            tracing::debug!(target: "edge_queue", "other component");
+            tracing::info!(target: "edge_router", "fallback");
+            tracing::warn!(target: "edge_router", "retrying");
+        });
+
+        let captured = escalation.restore("router").unwrap();
+        // The window keeps the latest lines
+        assert_eq!(captured.dropped, 1);
+        assert_eq!(captured.lines.len(), 2);
+        assert!(captured.lines[0].contains("INFO edge_router: fallback"), "{:?}", captured.lines);
+        assert!(captured.lines[1].ends_with("retrying"));
+        assert!(!escalation.is_escalated("router"));
+        assert!(escalation.restore("router").is_none());
+    }
+
+    #[test]
+    fn test_escalation_lapses_after_its_duration() {
+        let escalation = escalation(0, 10);
+        let subscriber = tracing_subscriber::registry().with(escalation.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(escalation.escalate("router"));
+            assert!(!escalation.is_escalated("router"));
+            assert_eq!(escalation.level_for("edge_router"), LevelFilter::INFO);
+            tracing::debug!(target: "edge_router", "dropped");
+        });
+        assert!(escalation.captured("router").unwrap().lines.is_empty());
+
+        let disabled = Arc::new(LogEscalation::new(LogEscalationConfig {
+            enabled: false,
+            ..Default::default()
+        }));
+        assert!(!disabled.escalate("router"));
+    }
+}
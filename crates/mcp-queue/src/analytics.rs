@@ -0,0 +1,245 @@
+//! Offline queue analytics
+//!
+//! [`QueueFlow`] counts what enters and leaves the queue over the last
+//! `queue.analytics.window_secs`, fed from the queue's own events.
+//! [`QueueAnalytics`] combines those rates with the age of the queued
+//! requests and the current connectivity into an estimate of how long the
+//! backlog takes to drain. A queue that only grows while online needs more
+//! uplink or local capacity; one that only grows while offline needs better
+//! connectivity.
+
+use chrono::{DateTime, Duration, Utc};
+use mcp_common::events::QueueEvent;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Which way a request left or entered the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Arrived,
+    /// Dequeued locally or synced to the cloud
+    Drained,
+    /// Expired or dead-lettered
+    Dropped,
+}
+
+/// Requests entering and leaving the queue over a sliding window
+#[derive(Debug)]
+pub(crate) struct QueueFlow {
+    window: Duration,
+    started: DateTime<Utc>,
+    samples: Mutex<VecDeque<(DateTime<Utc>, Flow, u64)>>,
+}
+
+impl QueueFlow {
+    pub(crate) fn new(window_secs: u64) -> Self {
+        Self {
+            window: Duration::seconds(window_secs.max(1) as i64),
+            started: Utc::now(),
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub(crate) fn record(&self, event: &QueueEvent) {
+        let (flow, count) = match event {
+            QueueEvent::Enqueued { .. } => (Flow::Arrived, 1),
+            QueueEvent::Dequeued { .. } => (Flow::Drained, 1),
+            QueueEvent::SyncFinished { synced, .. } if *synced > 0 => (Flow::Drained, *synced as u64),
+            QueueEvent::Expired { .. } | QueueEvent::DeadLettered { .. } => (Flow::Dropped, 1),
+            _ => return,
+        };
+        let now = Utc::now();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples.push_back((now, flow, count));
+        Self::prune(&mut samples, now - self.window);
+    }
+
+    /// Requests per minute arriving, drained and dropped over the window
+    pub(crate) fn rates(&self) -> FlowRates {
+        let now = Utc::now();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        Self::prune(&mut samples, now - self.window);
+        // Until a full window has passed, average over the time observed
+        let observed = (now - self.started).min(self.window).num_milliseconds().max(1_000) as f64 / 60_000.0;
+        let per_minute = |flow: Flow| {
+            let total: u64 = samples.iter().filter(|(_, f, _)| *f == flow).map(|(_, _, count)| count).sum();
+            total as f64 / observed
+        };
+        FlowRates {
+            window_secs: self.window.num_seconds() as u64,
+            arrivals_per_min: per_minute(Flow::Arrived),
+            drains_per_min: per_minute(Flow::Drained),
+            drops_per_min: per_minute(Flow::Dropped),
+        }
+    }
+
+    fn prune(samples: &mut VecDeque<(DateTime<Utc>, Flow, u64)>, cutoff: DateTime<Utc>) {
+        while samples.front().is_some_and(|(at, _, _)| *at < cutoff) {
+            samples.pop_front();
+        }
+    }
+}
+
+/// Average rates over the analytics window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct FlowRates {
+    pub window_secs: u64,
+    pub arrivals_per_min: f64,
+    /// Requests dequeued locally or synced to the cloud
+    pub drains_per_min: f64,
+    /// Requests expired or dead-lettered
+    pub drops_per_min: f64,
+}
+
+/// How long queued requests have been waiting, in seconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct QueueAgePercentiles {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl QueueAgePercentiles {
+    /// Percentiles of the age of requests queued at `queued_at`
+    pub fn from_queued_at(queued_at: impl IntoIterator<Item = DateTime<Utc>>, now: DateTime<Utc>) -> Self {
+        let mut ages: Vec<u64> = queued_at
+            .into_iter()
+            .map(|at| (now - at).num_seconds().max(0) as u64)
+            .collect();
+        ages.sort_unstable();
+        Self {
+            p50: percentile(&ages, 0.50),
+            p90: percentile(&ages, 0.90),
+            p99: percentile(&ages, 0.99),
+            max: ages.last().copied().unwrap_or(0),
+        }
+    }
+}
+
+/// Where the backlog is heading at the current rates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrainOutlook {
+    Empty,
+    /// Drains faster than requests arrive
+    Draining,
+    /// Requests arrive at least as fast as they drain while online; the site
+    /// needs more uplink or local capacity
+    Growing,
+    /// The uplink is down; the backlog waits for connectivity
+    Offline,
+}
+
+/// Queue backlog, flow rates and estimated time to drain
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueueAnalytics {
+    pub queue_size: usize,
+    pub age_secs: QueueAgePercentiles,
+    pub rates: FlowRates,
+    /// Whether the uplink is usable as of the latest connectivity check
+    pub online: bool,
+    pub outlook: DrainOutlook,
+    /// Seconds until the queue is empty at the current net drain rate;
+    /// unset while offline or growing
+    pub time_to_drain_secs: Option<u64>,
+}
+
+impl QueueAnalytics {
+    pub fn new(queue_size: usize, age_secs: QueueAgePercentiles, rates: FlowRates, online: bool) -> Self {
+        let net_per_min = rates.drains_per_min - rates.arrivals_per_min;
+        let (outlook, time_to_drain_secs) = if queue_size == 0 {
+            (DrainOutlook::Empty, Some(0))
+        } else if !online {
+            (DrainOutlook::Offline, None)
+        } else if net_per_min > 0.0 {
+            let secs = (queue_size as f64 / net_per_min * 60.0).ceil() as u64;
+            (DrainOutlook::Draining, Some(secs))
+        } else {
+            (DrainOutlook::Growing, None)
+        };
+        Self {
+            queue_size,
+            age_secs,
+            rates,
+            online,
+            outlook,
+            time_to_drain_secs,
+        }
+    }
+}
+
+fn percentile(sorted: &[u64], quantile: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn rates(arrivals_per_min: f64, drains_per_min: f64) -> FlowRates {
+        FlowRates {
+            window_secs: 900,
+            arrivals_per_min,
+            drains_per_min,
+            drops_per_min: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_flow_counts_synced_requests_as_drained() {
+        let flow = QueueFlow::new(900);
+        let request_id = Uuid::new_v4();
+        for _ in 0..3 {
+            flow.record(&QueueEvent::Enqueued {
+                request_id,
+                priority_score: 50.0,
+                queue_size: 1,
+                capacity: 10,
+            });
+        }
+        flow.record(&QueueEvent::SyncFinished {
+            trigger: mcp_common::events::SyncTrigger::Manual,
+            synced: 2,
+            failed: 1,
+            queue_size: 1,
+        });
+        flow.record(&QueueEvent::Expired { request_id });
+
+        // Under a second observed counts as one second
+        let rates = flow.rates();
+        assert_eq!(rates.arrivals_per_min, 180.0);
+        assert_eq!(rates.drains_per_min, 120.0);
+        assert_eq!(rates.drops_per_min, 60.0);
+    }
+
+    #[test]
+    fn test_age_percentiles() {
+        let now = Utc::now();
+        let queued_at = (1..=100).map(|secs| now - Duration::seconds(secs));
+        let ages = QueueAgePercentiles::from_queued_at(queued_at, now);
+        assert_eq!((ages.p50, ages.p90, ages.p99, ages.max), (51, 90, 99, 100));
+        assert_eq!(QueueAgePercentiles::from_queued_at([], now), QueueAgePercentiles::default());
+    }
+
+    #[test]
+    fn test_time_to_drain_follows_net_rate_and_connectivity() {
+        let ages = QueueAgePercentiles::default();
+        let draining = QueueAnalytics::new(120, ages, rates(2.0, 6.0), true);
+        assert_eq!(draining.outlook, DrainOutlook::Draining);
+        assert_eq!(draining.time_to_drain_secs, Some(1_800));
+
+        let growing = QueueAnalytics::new(120, ages, rates(6.0, 6.0), true);
+        assert_eq!((growing.outlook, growing.time_to_drain_secs), (DrainOutlook::Growing, None));
+        let offline = QueueAnalytics::new(120, ages, rates(2.0, 6.0), false);
+        assert_eq!((offline.outlook, offline.time_to_drain_secs), (DrainOutlook::Offline, None));
+        let empty = QueueAnalytics::new(0, ages, rates(2.0, 0.0), false);
+        assert_eq!((empty.outlook, empty.time_to_drain_secs), (DrainOutlook::Empty, Some(0)));
+    }
+}
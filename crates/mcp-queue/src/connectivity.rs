@@ -0,0 +1,277 @@
+//! Connectivity validation for the sync engine
+//!
+//! A network that accepts TCP connections is not necessarily online: hotel
+//! and transit WiFi intercept plain HTTP to show a login page and often answer
+//! every DNS query with the portal's address. Syncing through such a network
+//! fails on every request and burns the retry budget of the whole queue. Before
+//! a sync the validator fetches a well-known URL that must answer with an
+//! empty `204` and resolves a random subdomain that must not exist; only when
+//! both look right does the device count as online. Results are cached for
+//! `recheck_interval_secs` and dropped as soon as a sync hits a network error.
+
+use mcp_common::config::ConnectivityCheckConfig;
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
+use mcp_common::{Error, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Outcome of the latest connectivity validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Connectivity {
+    /// Not validated yet
+    Unknown,
+    Online,
+    Offline { reason: String },
+    /// HTTP is intercepted; `portal_url` is where the portal redirected to
+    CaptivePortal { portal_url: Option<String> },
+    /// A name that must not exist resolved to `answer`
+    DnsHijacked { answer: String },
+}
+
+impl Connectivity {
+    fn is_intercepted(&self) -> bool {
+        matches!(self, Connectivity::CaptivePortal { .. } | Connectivity::DnsHijacked { .. })
+    }
+}
+
+impl fmt::Display for Connectivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Connectivity::Unknown => write!(f, "connectivity not validated yet"),
+            Connectivity::Online => write!(f, "online"),
+            Connectivity::Offline { reason } => write!(f, "offline: {}", reason),
+            Connectivity::CaptivePortal { portal_url: Some(url) } => write!(f, "captive portal at {}", url),
+            Connectivity::CaptivePortal { portal_url: None } => write!(f, "captive portal intercepting HTTP"),
+            Connectivity::DnsHijacked { answer } => write!(f, "DNS hijacked, nonexistent names resolve to {}", answer),
+        }
+    }
+}
+
+/// Validates that the uplink really reaches the internet
+pub(crate) struct ConnectivityValidator {
+    config: ConnectivityCheckConfig,
+    client: reqwest::Client,
+    /// Latest result and when it was obtained; held across probes so
+    /// concurrent syncs share one validation
+    latest: Mutex<(Connectivity, Option<Instant>)>,
+    current: std::sync::RwLock<Connectivity>,
+    interceptions: AtomicU64,
+}
+
+impl ConnectivityValidator {
+    pub(crate) fn new(config: ConnectivityCheckConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| Error::Network(format!("Failed to create connectivity probe client: {}", e)))?;
+        Ok(Self {
+            config,
+            client,
+            latest: Mutex::new((Connectivity::Unknown, None)),
+            current: std::sync::RwLock::new(Connectivity::Unknown),
+            interceptions: AtomicU64::new(0),
+        })
+    }
+
+    /// Connectivity as of the latest validation, without probing
+    pub(crate) fn current(&self) -> Connectivity {
+        self.current.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Validated connectivity, probing when the cached result is stale
+    pub(crate) async fn check(&self) -> Connectivity {
+        let mut latest = self.latest.lock().await;
+        let recheck = Duration::from_secs(self.config.recheck_interval_secs);
+        if let (connectivity, Some(checked_at)) = &*latest {
+            if checked_at.elapsed() < recheck {
+                return connectivity.clone();
+            }
+        }
+
+        let connectivity = self.probe().await;
+        self.transition(&latest.0, &connectivity);
+        *latest = (connectivity.clone(), Some(Instant::now()));
+        *self.current.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = connectivity.clone();
+        connectivity
+    }
+
+    /// Forget the cached result, e.g. after a sync failed on the network
+    pub(crate) async fn invalidate(&self) {
+        self.latest.lock().await.1 = None;
+    }
+
+    /// Render validation state into queue health metrics
+    pub(crate) fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let current = self.current();
+        let flag = |set: bool| if set { 1.0 } else { 0.0 };
+        metrics.insert("connectivity_online".to_string(), flag(current == Connectivity::Online));
+        metrics.insert(
+            "captive_portal_detected".to_string(),
+            flag(matches!(current, Connectivity::CaptivePortal { .. })),
+        );
+        metrics.insert(
+            "dns_hijack_detected".to_string(),
+            flag(matches!(current, Connectivity::DnsHijacked { .. })),
+        );
+        metrics.insert(
+            "connectivity_interceptions_total".to_string(),
+            self.interceptions.load(Ordering::Relaxed) as f32,
+        );
+    }
+
+    async fn probe(&self) -> Connectivity {
+        let http = self.probe_http().await;
+        if http != Connectivity::Online {
+            return http;
+        }
+        match &self.config.dns_probe_domain {
+            Some(domain) => self.probe_dns(domain).await,
+            None => http,
+        }
+    }
+
+    async fn probe_http(&self) -> Connectivity {
+        let response = match self.client.get(&self.config.probe_url).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                return Connectivity::Offline {
+                    reason: e.to_string(),
+                }
+            },
+        };
+
+        let status = response.status();
+        if status.is_redirection() {
+            let portal_url = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .map(str::to_string);
+            return Connectivity::CaptivePortal { portal_url };
+        }
+        let body = response.bytes().await.unwrap_or_default();
+        if status.as_u16() != self.config.expected_status || !body.is_empty() {
+            // Something other than the probe server answered, typically a login page
+            debug!("Connectivity probe answered {} with {} bytes", status, body.len());
+            return Connectivity::CaptivePortal { portal_url: None };
+        }
+        Connectivity::Online
+    }
+
+    async fn probe_dns(&self, domain: &str) -> Connectivity {
+        let name = format!("{}.{}", Uuid::new_v4().simple(), domain);
+        let lookup = tokio::net::lookup_host((name.as_str(), 80));
+        let answer = match tokio::time::timeout(Duration::from_millis(self.config.timeout_ms), lookup).await {
+            Ok(Ok(mut addresses)) => addresses.next(),
+            _ => None,
+        };
+        match answer {
+            Some(address) => Connectivity::DnsHijacked {
+                answer: address.ip().to_string(),
+            },
+            // NXDOMAIN is the expected answer; a DNS timeout after a working
+            // HTTP probe says nothing about interception either
+            None => Connectivity::Online,
+        }
+    }
+
+    fn transition(&self, previous: &Connectivity, next: &Connectivity) {
+        if previous == next {
+            return;
+        }
+        if next.is_intercepted() {
+            self.interceptions.fetch_add(1, Ordering::Relaxed);
+            warn!("Network is not really online ({}), pausing cloud sync", next);
+            events::publish(GatewayEvent::AlertRaised {
+                source: "connectivity".to_string(),
+                severity: AlertSeverity::Warning,
+                message: format!("Cloud sync paused: {}", next),
+            });
+        } else if previous.is_intercepted() && *next == Connectivity::Online {
+            info!("Connectivity validated again, resuming cloud sync");
+            events::publish(GatewayEvent::AlertRaised {
+                source: "connectivity".to_string(),
+                severity: AlertSeverity::Info,
+                message: "Network interception cleared, cloud sync resumed".to_string(),
+            });
+        } else {
+            debug!("Connectivity changed to {}", next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `response` to every connection and return the probe URL
+    async fn serve(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/generate_204", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    fn config(probe_url: String) -> ConnectivityCheckConfig {
+        ConnectivityCheckConfig {
+            probe_url,
+            dns_probe_domain: None,
+            timeout_ms: 2000,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_portals_are_told_apart_from_real_connectivity() {
+        let online = serve("HTTP/1.1 204 No Content\r\nconnection: close\r\n\r\n").await;
+        let validator = ConnectivityValidator::new(config(online)).unwrap();
+        assert_eq!(validator.check().await, Connectivity::Online);
+
+        let redirect =
+            serve("HTTP/1.1 302 Found\r\nlocation: http://login.hotel.example/\r\ncontent-length: 0\r\n\r\n").await;
+        let validator = ConnectivityValidator::new(config(redirect)).unwrap();
+        assert_eq!(
+            validator.check().await,
+            Connectivity::CaptivePortal {
+                portal_url: Some("http://login.hotel.example/".to_string())
+            }
+        );
+
+        let login_page = serve("HTTP/1.1 200 OK\r\ncontent-length: 13\r\n\r\n<html></html>").await;
+        let validator = ConnectivityValidator::new(config(login_page)).unwrap();
+        assert_eq!(validator.check().await, Connectivity::CaptivePortal { portal_url: None });
+
+        let mut metrics = HashMap::new();
+        validator.write_metrics(&mut metrics);
+        assert_eq!(metrics["captive_portal_detected"], 1.0);
+        assert_eq!(metrics["connectivity_interceptions_total"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_results_are_cached_until_invalidated() {
+        // Nothing listens on the discard port of localhost
+        let validator = ConnectivityValidator::new(config("http://127.0.0.1:9/".to_string())).unwrap();
+        assert_eq!(validator.current(), Connectivity::Unknown);
+        assert!(matches!(validator.check().await, Connectivity::Offline { .. }));
+
+        *validator.latest.lock().await = (Connectivity::Online, Some(Instant::now()));
+        assert_eq!(validator.check().await, Connectivity::Online);
+
+        validator.invalidate().await;
+        assert!(matches!(validator.check().await, Connectivity::Offline { .. }));
+    }
+}
@@ -0,0 +1,40 @@
+//! Queue events for embedding applications
+//!
+//! The queue publishes a [`QueueEvent`] for every state change on the global
+//! event bus, so embedders can react (blink an LED when the queue backs up,
+//! trigger a manual sync when connectivity returns) without polling
+//! `queue_size`. Slow subscribers miss events rather than blocking the queue.
+//! Every event also feeds the queue's [`QueueFlow`] rates.
+
+use crate::analytics::QueueFlow;
+use mcp_common::events::{self, EventKind, EventSubscriber, GatewayEvent, QueueEvent};
+use mcp_common::Result;
+use std::sync::Arc;
+
+/// Publishes queue events on the global bus
+#[derive(Debug, Clone)]
+pub(crate) struct QueueEvents {
+    flow: Arc<QueueFlow>,
+}
+
+impl QueueEvents {
+    /// Events whose flow rates are averaged over `window_secs`
+    pub(crate) fn new(window_secs: u64) -> Self {
+        Self {
+            flow: Arc::new(QueueFlow::new(window_secs)),
+        }
+    }
+
+    pub(crate) fn emit(&self, event: QueueEvent) {
+        self.flow.record(&event);
+        events::publish(GatewayEvent::Queue { event });
+    }
+
+    pub(crate) fn subscribe(&self) -> Result<EventSubscriber> {
+        events::subscribe(&[EventKind::Queue])
+    }
+
+    pub(crate) fn flow(&self) -> &QueueFlow {
+        &self.flow
+    }
+}
@@ -0,0 +1,253 @@
+//! IndexedDB offline queue for browser builds
+//!
+//! `wasm32-unknown-unknown` has no filesystem, so sled and SQLite are not
+//! available. Requests are kept in the IndexedDB database named by
+//! `queue.storage_path` (`indexeddb://queue`) through
+//! [`IdbQueueStore`], which page scripts and service workers can also use
+//! directly via the `WasmOfflineQueue` export. The queue is first in, first
+//! out, and syncs POST to the first cloud endpoint with the scope's `fetch`.
+
+use crate::events::QueueEvents;
+use crate::{OfflineQueue, QueueAgePercentiles, QueueAnalytics, QueuePurge};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mcp_common::events::{QueueEvent, SyncTrigger};
+use mcp_common::metrics::ComponentHealth;
+use mcp_common::wasm::IdbQueueStore;
+use mcp_common::{Config, Error, EventSubscriber, HealthLevel, MCPRequest, MCPResponse, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Requests synced per `sync_with_cloud` call, as in the native queue
+const SYNC_BATCH: usize = 10;
+
+/// Offline queue kept in the browser's IndexedDB
+pub struct IndexedDbQueue {
+    config: Arc<Config>,
+    store: IdbQueueStore,
+    events: QueueEvents,
+    enqueued: AtomicU64,
+    dequeued: AtomicU64,
+    failed: AtomicU64,
+    sync_attempts: AtomicU64,
+}
+
+impl IndexedDbQueue {
+    pub async fn new(config: Arc<Config>) -> Result<Self> {
+        let name = config.queue.storage_path.to_string_lossy().into_owned();
+        let store = IdbQueueStore::open(&name).await?;
+        info!("IndexedDB queue opened: {}", store.db_name());
+        Ok(Self {
+            config,
+            store,
+            events: QueueEvents::new(config.queue.analytics.window_secs),
+            enqueued: AtomicU64::new(0),
+            dequeued: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            sync_attempts: AtomicU64::new(0),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl OfflineQueue for IndexedDbQueue {
+    async fn enqueue_request(&self, request: MCPRequest) -> Result<MCPResponse> {
+        let capacity = self.config.queue.max_queue_size as usize;
+        if self.store.len().await? >= capacity {
+            return Err(Error::Queue("Queue is full".to_string()));
+        }
+        let queue_size = self.store.push(&request).await?;
+        self.enqueued.fetch_add(1, Ordering::Relaxed);
+        self.events.emit(QueueEvent::Enqueued {
+            request_id: request.id,
+            priority_score: 0.0,
+            queue_size,
+            capacity,
+        });
+        debug!("Request {} queued in IndexedDB", request.id);
+
+        Ok(MCPResponse {
+            id: request.id,
+            result: Some(serde_json::json!({
+                "status": "queued",
+                "queue_position": queue_size
+            })),
+            error: None,
+            timestamp: Utc::now(),
+        })
+    }
+
+    async fn dequeue_request(&self) -> Result<Option<MCPRequest>> {
+        let Some(request) = self.store.pop_front().await? else {
+            return Ok(None);
+        };
+        self.dequeued.fetch_add(1, Ordering::Relaxed);
+        self.events.emit(QueueEvent::Dequeued {
+            request_id: request.id,
+            queue_size: self.store.len().await?,
+        });
+        Ok(Some(request))
+    }
+
+    async fn queue_size(&self) -> Result<u32> {
+        Ok(self.store.len().await? as u32)
+    }
+
+    async fn sync_with_cloud(&self) -> Result<()> {
+        let Some(endpoint) = self.config.router.cloud_endpoints.first() else {
+            return Err(Error::Queue("No cloud endpoint configured".to_string()));
+        };
+        let pending = self.store.len().await?;
+        if pending == 0 {
+            debug!("No requests to sync");
+            return Ok(());
+        }
+        self.sync_attempts.fetch_add(1, Ordering::Relaxed);
+        self.events.emit(QueueEvent::SyncStarted {
+            trigger: SyncTrigger::Manual,
+            pending,
+        });
+
+        let max_retries = self.config.queue.retry_policy.max_retries;
+        let report = self
+            .store
+            .sync(&endpoint.url, SYNC_BATCH, max_retries)
+            .await?;
+        self.failed
+            .fetch_add(report.failed as u64, Ordering::Relaxed);
+        for (request_id, retries) in &report.dead_lettered {
+            self.events.emit(QueueEvent::DeadLettered {
+                request_id: *request_id,
+                retries: *retries,
+            });
+        }
+        self.events.emit(QueueEvent::SyncFinished {
+            trigger: SyncTrigger::Manual,
+            synced: report.synced.len(),
+            failed: report.failed,
+            queue_size: self.store.len().await?,
+        });
+        info!(
+            "Synced {} queued requests from IndexedDB",
+            report.synced.len()
+        );
+        Ok(())
+    }
+
+    async fn purge(
+        &self,
+        cutoff: DateTime<Utc>,
+        held_devices: &[String],
+        dry_run: bool,
+    ) -> Result<QueuePurge> {
+        let mut purge = QueuePurge::default();
+        let mut expired = Vec::new();
+        for (key, entry) in self.store.entries().await? {
+            if entry.queued_at >= cutoff {
+                continue;
+            }
+            if held_devices.contains(&entry.request.device_id) {
+                purge.held += 1;
+            } else {
+                expired.push(key);
+                purge.request_ids.push(entry.request.id);
+            }
+        }
+        if !dry_run && !expired.is_empty() {
+            self.store.remove(&expired).await?;
+            info!("Purged {} queued requests past retention", expired.len());
+        }
+        Ok(purge)
+    }
+
+    async fn erase(
+        &self,
+        selector: &(dyn for<'r> Fn(&'r MCPRequest) -> bool + Send + Sync),
+        dry_run: bool,
+    ) -> Result<Vec<Uuid>> {
+        let (keys, request_ids): (Vec<f64>, Vec<Uuid>) = self
+            .store
+            .entries()
+            .await?
+            .into_iter()
+            .filter(|(_, entry)| selector(&entry.request))
+            .map(|(key, entry)| (key, entry.request.id))
+            .unzip();
+        if !dry_run && !keys.is_empty() {
+            self.store.remove(&keys).await?;
+            info!("Erased {} queued requests", keys.len());
+        }
+        Ok(request_ids)
+    }
+
+    fn subscribe(&self) -> Result<EventSubscriber> {
+        self.events.subscribe()
+    }
+
+    async fn analytics(&self) -> Result<Option<QueueAnalytics>> {
+        let entries = self.store.entries().await?;
+        let queued_at = entries.iter().map(|(_, entry)| entry.queued_at);
+        let ages = QueueAgePercentiles::from_queued_at(queued_at, Utc::now());
+        // The browser gives no connectivity check; syncs need an endpoint
+        let online = !self.config.router.cloud_endpoints.is_empty();
+        Ok(Some(QueueAnalytics::new(entries.len(), ages, self.events.flow().rates(), online)))
+    }
+
+    async fn health_check(&self) -> Result<ComponentHealth> {
+        let queue_size = self.store.len().await? as f32;
+        let max_queue_size = self.config.queue.max_queue_size as f32;
+        let usage_percent = queue_size / max_queue_size * 100.0;
+
+        let mut metrics = HashMap::new();
+        metrics.insert("queue_size".to_string(), queue_size);
+        metrics.insert("max_queue_size".to_string(), max_queue_size);
+        metrics.insert("usage_percent".to_string(), usage_percent);
+        metrics.insert(
+            "total_enqueued".to_string(),
+            self.enqueued.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "total_dequeued".to_string(),
+            self.dequeued.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "total_failed".to_string(),
+            self.failed.load(Ordering::Relaxed) as f32,
+        );
+        metrics.insert(
+            "sync_attempts".to_string(),
+            self.sync_attempts.load(Ordering::Relaxed) as f32,
+        );
+
+        let status = if usage_percent > 95.0 {
+            HealthLevel::Critical
+        } else if usage_percent > 80.0 {
+            HealthLevel::Degraded
+        } else {
+            HealthLevel::Healthy
+        };
+        Ok(ComponentHealth {
+            status,
+            message: format!(
+                "IndexedDB queue {} at {:.1}% capacity",
+                self.store.db_name(),
+                usage_percent
+            ),
+            last_check: Utc::now(),
+            metrics,
+        })
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        // Every operation commits its own transaction, so nothing to flush
+        info!(
+            "IndexedDB queue shut down: enqueued={}, dequeued={}",
+            self.enqueued.load(Ordering::Relaxed),
+            self.dequeued.load(Ordering::Relaxed)
+        );
+        Ok(())
+    }
+}
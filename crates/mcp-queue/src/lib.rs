@@ -1,12 +1,41 @@
 //! MCP Queue - Offline queue management for the MCP Edge Gateway
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mcp_common::config::SyncPolicy;
 use mcp_common::metrics::ComponentHealth;
 use mcp_common::{Config, MCPRequest, MCPResponse, Result};
+use mcp_common::EventSubscriber;
+pub use analytics::{DrainOutlook, FlowRates, QueueAgePercentiles, QueueAnalytics};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use uuid::Uuid;
+
+/// Queued requests selected by a retention purge
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueuePurge {
+    /// Requests deleted, or that a dry run would delete
+    pub request_ids: Vec<Uuid>,
+    /// Requests past retention kept for a legal hold
+    pub held: usize,
+}
+
+/// Request moved out of the queue after using all its retry attempts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub request: MCPRequest,
+    /// Failed attempts, the first included
+    pub attempts: u32,
+    pub last_error: String,
+    pub queued_at: DateTime<Utc>,
+    pub dead_lettered_at: DateTime<Utc>,
+}
 
 /// Offline queue trait for managing queued requests
-#[async_trait]
+///
+/// Browser futures hold JS values, so on wasm32 they are not `Send`.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 pub trait OfflineQueue {
     /// Enqueue a request for later processing
     async fn enqueue_request(&self, request: MCPRequest) -> Result<MCPResponse>;
@@ -20,6 +49,61 @@ pub trait OfflineQueue {
     /// Sync queued requests with cloud
     async fn sync_with_cloud(&self) -> Result<()>;
 
+    /// Hand back a dequeued request whose processing failed; it is retried
+    /// after the retry policy's backoff, or dead-lettered once it has used
+    /// all its attempts
+    async fn fail_request(&self, request: MCPRequest, error: &str) -> Result<()> {
+        let _ = error;
+        self.enqueue_request(request).await.map(|_| ())
+    }
+
+    /// Dead-lettered requests, most recent first; queues without a
+    /// dead-letter queue report none
+    async fn dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        Ok(Vec::new())
+    }
+
+    /// Move a dead-lettered request back into the queue with fresh retry
+    /// attempts, returning whether it was dead-lettered
+    async fn requeue_dead_letter(&self, request_id: Uuid) -> Result<bool> {
+        let _ = request_id;
+        Ok(false)
+    }
+
+    /// Delete the given dead-lettered requests, or all of them when
+    /// `request_ids` is empty; returns how many were deleted
+    async fn purge_dead_letters(&self, request_ids: &[Uuid]) -> Result<usize> {
+        let _ = request_ids;
+        Ok(0)
+    }
+
+    /// Delete requests queued before `cutoff`, except those from
+    /// `held_devices`; a dry run only reports what would be deleted
+    async fn purge(&self, cutoff: DateTime<Utc>, held_devices: &[String], dry_run: bool) -> Result<QueuePurge>;
+
+    /// Delete every queued request matching `selector`, with any stored
+    /// cloud response; returns the request ids. A dry run deletes nothing.
+    async fn erase(
+        &self,
+        selector: &(dyn for<'r> Fn(&'r MCPRequest) -> bool + Send + Sync),
+        dry_run: bool,
+    ) -> Result<Vec<Uuid>>;
+
+    /// Subscribe to queue state changes on the event bus
+    fn subscribe(&self) -> Result<EventSubscriber>;
+
+    /// Backlog age, flow rates and estimated time to drain; `None` when the
+    /// queue does not track them
+    async fn analytics(&self) -> Result<Option<QueueAnalytics>> {
+        Ok(None)
+    }
+
+    /// Replace what starts background syncs, as the power manager does to
+    /// batch syncs; queues without background sync ignore it
+    fn set_sync_policies(&self, policies: &[SyncPolicy]) {
+        let _ = policies;
+    }
+
     /// Get health status
     async fn health_check(&self) -> Result<ComponentHealth>;
 
@@ -27,11 +111,29 @@ pub trait OfflineQueue {
     async fn shutdown(&self) -> Result<()>;
 }
 
+mod analytics;
+#[cfg(not(target_arch = "wasm32"))]
+mod connectivity;
+mod events;
+#[cfg(target_arch = "wasm32")]
+mod indexeddb;
+#[cfg(not(target_arch = "wasm32"))]
 mod persistent_queue;
+#[cfg(not(target_arch = "wasm32"))]
+mod storage;
+#[cfg(not(target_arch = "wasm32"))]
+mod sync_scheduler;
 
+#[cfg(target_arch = "wasm32")]
+pub use indexeddb::IndexedDbQueue;
+pub use mcp_common::events::{QueueEvent, SyncTrigger};
+#[cfg(not(target_arch = "wasm32"))]
 pub use persistent_queue::PersistentQueue;
+#[cfg(not(target_arch = "wasm32"))]
+pub use storage::{open_storage, MemoryStorage, QueueStorageBackend, SledStorage, SqliteStorage};
 
 /// Create a new offline queue instance
+#[cfg(not(target_arch = "wasm32"))]
 pub async fn create_offline_queue(
     config: Arc<Config>,
 ) -> Result<Arc<dyn OfflineQueue + Send + Sync>> {
@@ -39,7 +141,17 @@ pub async fn create_offline_queue(
     Ok(Arc::new(queue))
 }
 
-#[cfg(test)]
+/// Create a new offline queue instance in the IndexedDB database named by
+/// `queue.storage_path`
+#[cfg(target_arch = "wasm32")]
+pub async fn create_offline_queue(
+    config: Arc<Config>,
+) -> Result<Arc<dyn OfflineQueue + Send + Sync>> {
+    let queue = IndexedDbQueue::new(config).await?;
+    Ok(Arc::new(queue))
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
     use mcp_common::{Config, MCPRequest};
@@ -80,6 +192,149 @@ mod tests {
         assert_eq!(dequeued.unwrap().id, request.id);
     }
     
+    #[tokio::test]
+    async fn test_subscribers_receive_queue_events() {
+        let config = Arc::new(Config::default());
+        let queue = create_offline_queue(config).await.unwrap();
+        let mut events = queue.subscribe().unwrap();
+
+        let request = MCPRequest {
+            id: Uuid::new_v4(),
+            device_id: "test_device".to_string(),
+            method: "test_method".to_string(),
+            params: std::collections::HashMap::new(),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        };
+        queue.enqueue_request(request.clone()).await.unwrap();
+        queue.dequeue_request().await.unwrap();
+
+        // Other tests share the global bus, so skip their requests
+        let mut seen = Vec::new();
+        while seen.len() < 2 {
+            let envelope = events.recv().await.unwrap();
+            match envelope.event {
+                mcp_common::GatewayEvent::Queue {
+                    event: event @ (QueueEvent::Enqueued { request_id, .. } | QueueEvent::Dequeued { request_id, .. }),
+                } if request_id == request.id => seen.push(event),
+                _ => {},
+            }
+        }
+        assert!(matches!(seen[0], QueueEvent::Enqueued { .. }));
+        assert!(matches!(seen[1], QueueEvent::Dequeued { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_queue_recovers_after_crash() {
+        let dir = std::env::temp_dir().join(format!("queue-recovery-{}", Uuid::new_v4()));
+        let mut config = Config::default();
+        config.queue.storage_path = dir.join("queue.db");
+        config.queue.storage_backend = mcp_common::config::QueueStorageKind::Sqlite;
+        // No background sync may drain the queue between the crash and recovery
+        config.queue.sync_policies = Vec::new();
+        let config = Arc::new(config);
+
+        let queue = create_offline_queue(config.clone()).await.unwrap();
+        let mut ids = Vec::new();
+        for method in ["embedding", "completion", "chat"] {
+            let request = MCPRequest {
+                id: Uuid::new_v4(),
+                device_id: "pi-4".to_string(),
+                method: method.to_string(),
+                params: std::collections::HashMap::new(),
+                context: None,
+                timestamp: chrono::Utc::now(),
+            };
+            ids.push(request.id);
+            queue.enqueue_request(request).await.unwrap();
+        }
+        assert_eq!(queue.dequeue_request().await.unwrap().unwrap().id, ids[1]);
+        // Power cut: the queue is never shut down and its storage never closed
+        std::mem::forget(queue);
+
+        let recovered = create_offline_queue(config).await.unwrap();
+        assert_eq!(recovered.queue_size().await.unwrap(), 2);
+        assert_eq!(recovered.dequeue_request().await.unwrap().unwrap().id, ids[2]);
+        assert_eq!(recovered.dequeue_request().await.unwrap().unwrap().id, ids[0]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_analytics_report_backlog_and_rates() {
+        let mut config = Config::default();
+        config.queue.storage_backend = mcp_common::config::QueueStorageKind::Memory;
+        let queue = create_offline_queue(Arc::new(config)).await.unwrap();
+        for _ in 0..3 {
+            let request = MCPRequest {
+                id: Uuid::new_v4(),
+                device_id: "test_device".to_string(),
+                method: "test_method".to_string(),
+                params: std::collections::HashMap::new(),
+                context: None,
+                timestamp: chrono::Utc::now(),
+            };
+            queue.enqueue_request(request).await.unwrap();
+        }
+        queue.dequeue_request().await.unwrap();
+
+        let analytics = queue.analytics().await.unwrap().unwrap();
+        assert_eq!(analytics.queue_size, 2);
+        assert!(analytics.rates.arrivals_per_min > analytics.rates.drains_per_min);
+        assert_eq!(analytics.outlook, DrainOutlook::Growing);
+        assert_eq!(analytics.time_to_drain_secs, None);
+    }
+
+    #[tokio::test]
+    async fn test_failing_requests_back_off_then_dead_letter() {
+        let mut config = Config::default();
+        config.queue.storage_backend = mcp_common::config::QueueStorageKind::Memory;
+        config.queue.retry_policy.max_retries = 1;
+        config.queue.retry_policy.initial_delay_ms = 60_000;
+        let backing_off = create_offline_queue(Arc::new(config.clone())).await.unwrap();
+        config.queue.retry_policy.initial_delay_ms = 0;
+        let queue = create_offline_queue(Arc::new(config)).await.unwrap();
+
+        let request = MCPRequest {
+            id: Uuid::new_v4(),
+            device_id: "test_device".to_string(),
+            method: "test_method".to_string(),
+            params: std::collections::HashMap::new(),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        };
+
+        // A failed request is held back until its retry delay passes
+        backing_off.enqueue_request(request.clone()).await.unwrap();
+        let dequeued = backing_off.dequeue_request().await.unwrap().unwrap();
+        backing_off.fail_request(dequeued, "model crashed").await.unwrap();
+        assert_eq!(backing_off.queue_size().await.unwrap(), 1);
+        assert!(backing_off.dequeue_request().await.unwrap().is_none());
+
+        // Without a delay it comes straight back, until it runs out of retries
+        queue.enqueue_request(request.clone()).await.unwrap();
+        for _ in 0..2 {
+            let dequeued = queue.dequeue_request().await.unwrap().unwrap();
+            queue.fail_request(dequeued, "model crashed").await.unwrap();
+        }
+        assert_eq!(queue.queue_size().await.unwrap(), 0);
+        let dead_letters = queue.dead_letters().await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!((dead_letters[0].request.id, dead_letters[0].attempts), (request.id, 2));
+        assert_eq!(dead_letters[0].last_error, "model crashed");
+        let health = queue.health_check().await.unwrap();
+        assert_eq!(health.metrics["dead_letter_depth"], 1.0);
+
+        assert!(queue.requeue_dead_letter(request.id).await.unwrap());
+        assert!(!queue.requeue_dead_letter(request.id).await.unwrap());
+        let requeued = queue.dequeue_request().await.unwrap().unwrap();
+        assert_eq!(requeued.context.as_ref().unwrap().retry_count, 0);
+        queue.fail_request(requeued, "model crashed").await.unwrap();
+        let dequeued = queue.dequeue_request().await.unwrap().unwrap();
+        queue.fail_request(dequeued, "model crashed").await.unwrap();
+        assert_eq!(queue.purge_dead_letters(&[]).await.unwrap(), 1);
+        assert!(queue.dead_letters().await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_queue_health() {
         let config = Arc::new(Config::default());
@@ -91,6 +346,105 @@ mod tests {
         assert_eq!(health_result.status, mcp_common::HealthLevel::Healthy);
     }
     
+    /// Serve sync uploads, answering compressed ones with 415, and return
+    /// the endpoint URL and whether each upload was compressed
+    async fn serve_uncompressed_only() -> (String, Arc<std::sync::Mutex<Vec<bool>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/mcp", listener.local_addr().unwrap());
+        let uploads = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = uploads.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                let head_end = loop {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    if let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&request[..head_end]).to_ascii_lowercase();
+                let content_length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map_or(0, |length| length.trim().parse::<usize>().unwrap());
+                while request.len() < head_end + content_length {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let compressed = head.contains("\r\ncontent-encoding:");
+                seen.lock().unwrap().push(compressed);
+
+                let response = if compressed {
+                    "HTTP/1.1 415 Unsupported Media Type\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string()
+                } else {
+                    let body = serde_json::to_string(&mcp_common::MCPResponse {
+                        id: Uuid::new_v4(),
+                        result: Some(serde_json::json!({ "text": "synced" })),
+                        error: None,
+                        timestamp: chrono::Utc::now(),
+                    })
+                    .unwrap();
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, uploads)
+    }
+
+    #[tokio::test]
+    async fn test_rejected_compression_is_retried_once_uncompressed() {
+        let (url, uploads) = serve_uncompressed_only().await;
+        let mut config = Config::default();
+        config.queue.storage_backend = mcp_common::config::QueueStorageKind::Memory;
+        config.queue.compression_enabled = true;
+        config.queue.compression = mcp_common::compression::PayloadCompression::Zstd;
+        config.queue.connectivity.enabled = false;
+        config.queue.sync_policies = Vec::new();
+        config.router.cloud_endpoints = vec![mcp_common::config::CloudEndpoint {
+            name: "cloud".to_string(),
+            url,
+            api_key: None,
+            timeout_ms: 5000,
+            max_retries: 0,
+            connect_timeout_ms: None,
+            region: None,
+            provider: Default::default(),
+            compression: Default::default(),
+        }];
+        let queue = create_offline_queue(Arc::new(config)).await.unwrap();
+
+        let request = || MCPRequest {
+            id: Uuid::new_v4(),
+            device_id: "test_device".to_string(),
+            method: "completion".to_string(),
+            params: std::collections::HashMap::from([(
+                "prompt".to_string(),
+                serde_json::json!("vibration reading ".repeat(100)),
+            )]),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        };
+        queue.enqueue_request(request()).await.unwrap();
+        queue.sync_with_cloud().await.unwrap();
+        assert_eq!(queue.queue_size().await.unwrap(), 0);
+        assert_eq!(*uploads.lock().unwrap(), vec![true, false]);
+
+        // Later uploads skip compression instead of being rejected again
+        queue.enqueue_request(request()).await.unwrap();
+        queue.sync_with_cloud().await.unwrap();
+        assert_eq!(queue.queue_size().await.unwrap(), 0);
+        assert_eq!(*uploads.lock().unwrap(), vec![true, false, false]);
+    }
+
     #[tokio::test]
     async fn test_queue_sync() {
         let config = Arc::new(Config::default());
@@ -1,22 +1,47 @@
 //! Persistent queue implementation for offline request handling
 
-use crate::OfflineQueue;
+use crate::connectivity::{Connectivity, ConnectivityValidator};
+use crate::events::QueueEvents;
+use crate::storage::{open_storage, QueueStorageBackend};
+use crate::sync_scheduler::SyncScheduler;
+use crate::{DeadLetter, OfflineQueue, QueueAgePercentiles, QueueAnalytics, QueuePurge};
 use async_trait::async_trait;
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::compression::{self, PayloadCompression};
+use mcp_common::redaction;
+use mcp_common::request_id;
+use mcp_common::request_signing;
+use mcp_common::trace_context::TRACEPARENT_HEADER;
 use mcp_common::metrics::{ComponentHealth, HealthLevel};
-use mcp_common::{Config, Error, MCPRequest, MCPResponse, Result};
+use mcp_common::config::{RetryPolicy, SyncPolicy};
+use mcp_common::events::{QueueEvent, SyncTrigger};
+use mcp_common::{
+    create_vfs, Config, ConcurrencyLimiter, Error, EventSubscriber, MCPRequest, MCPResponse, Priority, ProcessingRequirements,
+    RequestContext, RequestSource, Result, Span, SpanKind, TraceContext,
+};
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 /// Persistent queue for offline request handling
 pub struct PersistentQueue {
     config: Arc<Config>,
-    storage: Arc<sled::Db>,
+    storage: Arc<dyn QueueStorageBackend>,
     memory_queue: Arc<RwLock<VecDeque<QueuedRequest>>>,
     stats: Arc<RwLock<QueueStats>>,
+    sync_limiter: Arc<ConcurrencyLimiter>,
+    connectivity: Arc<ConnectivityValidator>,
+    events: QueueEvents,
+    /// The cloud answered a compressed sync upload with 415
+    sync_uncompressed: Arc<AtomicBool>,
+    /// Held while a sync runs so policies firing together send requests once
+    syncing: Arc<Mutex<()>>,
+    sync_scheduler: Arc<SyncScheduler>,
 }
 
 /// Request stored in the queue
@@ -28,6 +53,58 @@ struct QueuedRequest {
     retry_count: u32,
     priority_score: f32,
     expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Not synced or dequeued before this, backing off after a failure
+    #[serde(default)]
+    retry_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    last_error: Option<String>,
+}
+
+impl QueuedRequest {
+    /// Whether the request's retry backoff has passed
+    fn due(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        !self.retry_at.is_some_and(|retry_at| retry_at > now)
+    }
+
+    /// Priority class the request was ordered by while queued
+    fn queue_priority(&self) -> Priority {
+        match self.priority_score {
+            score if score >= 90.0 => Priority::Critical,
+            score if score >= 75.0 => Priority::High,
+            score if score >= 35.0 => Priority::Normal,
+            _ => Priority::Low,
+        }
+    }
+
+    /// The request as it leaves the queue, carrying the priority it was
+    /// queued with so downstream schedulers order it the same way
+    fn released_request(&self) -> MCPRequest {
+        let priority = self.request.priority().max(self.queue_priority());
+        let mut request = self.request.clone();
+        let context = request.context.get_or_insert(RequestContext {
+            priority,
+            timeout_ms: None,
+            retry_count: 0,
+            source: RequestSource::Queue,
+            requirements: ProcessingRequirements {
+                max_latency_ms: None,
+                min_accuracy: None,
+                max_memory_mb: None,
+                require_local: false,
+                allow_fallback: true,
+                pii_present: None,
+            },
+            trace: None,
+            principal: None,
+            language: None,
+            idempotency_key: None,
+            weight: None,
+        });
+        context.priority = priority;
+        context.retry_count = self.retry_count;
+        context.source = RequestSource::Queue;
+        request
+    }
 }
 
 /// Queue statistics for monitoring
@@ -45,24 +122,42 @@ struct QueueStats {
 
 impl PersistentQueue {
     pub async fn new(config: Arc<Config>) -> Result<Self> {
-        // Initialize persistent storage
-        let storage = sled::open(&config.queue.storage_path)
-            .map_err(|e| Error::Queue(format!("Failed to open queue database: {}", e)))?;
+        // Initialize persistent storage where the configured VFS places it;
+        // backends without a host directory get a volatile database
+        let vfs = create_vfs(&config.storage);
+        let path = vfs.host_path(&config.queue.storage_path);
+        if path.is_none() {
+            warn!("{} storage backend has no host directory, queue will not survive restarts", vfs.name());
+        }
+        let storage = open_storage(config.queue.storage_backend, path.as_deref())?;
 
         let queue = Self {
             config: config.clone(),
-            storage: Arc::new(storage),
+            storage: Arc::from(storage),
             memory_queue: Arc::new(RwLock::new(VecDeque::new())),
             stats: Arc::new(RwLock::new(QueueStats::default())),
+            sync_limiter: Arc::new(ConcurrencyLimiter::new(
+                "queue_sync",
+                &config.concurrency.queue_sync,
+            )),
+            connectivity: Arc::new(ConnectivityValidator::new(config.queue.connectivity.clone())?),
+            events: QueueEvents::new(config.queue.analytics.window_secs),
+            sync_uncompressed: Arc::new(AtomicBool::new(false)),
+            syncing: Arc::new(Mutex::new(())),
+            sync_scheduler: Arc::new(SyncScheduler::new()),
         };
 
         // Load existing requests from persistent storage
         queue.load_from_storage().await?;
 
-        // Start background sync task
-        queue.start_sync_task().await;
+        // Start the background sync policies
+        queue.sync_scheduler.start(&queue, &config.queue.sync_policies);
 
-        info!("Persistent queue initialized with storage path: {:?}", config.queue.storage_path);
+        info!(
+            "Persistent queue initialized with {} storage at {:?}",
+            queue.storage.name(),
+            config.queue.storage_path
+        );
         Ok(queue)
     }
 
@@ -73,41 +168,29 @@ impl PersistentQueue {
         let mut memory_queue = self.memory_queue.write().await;
         let mut loaded_count = 0;
 
-        for result in self.storage.iter() {
-            match result {
-                Ok((key, value)) => {
-                    // Skip metadata keys
-                    if key.starts_with(b"meta:") {
-                        continue;
-                    }
-
-                    match serde_json::from_slice::<QueuedRequest>(&value) {
-                        Ok(queued_request) => {
-                            // Check if request has expired
-                            if let Some(expires_at) = queued_request.expires_at {
-                                if chrono::Utc::now() > expires_at {
-                                    debug!("Removing expired request: {}", queued_request.id);
-                                    if let Err(e) = self.storage.remove(&key) {
-                                        warn!("Failed to remove expired request: {}", e);
-                                    }
-                                    continue;
-                                }
-                            }
-
-                            memory_queue.push_back(queued_request);
-                            loaded_count += 1;
-                        }
-                        Err(e) => {
-                            warn!("Failed to deserialize queued request: {}", e);
-                            // Remove corrupted entry
+        for (key, value) in self.storage.scan("request:")? {
+            match serde_json::from_slice::<QueuedRequest>(&value) {
+                Ok(queued_request) => {
+                    // Check if request has expired
+                    if let Some(expires_at) = queued_request.expires_at {
+                        if chrono::Utc::now() > expires_at {
+                            debug!("Removing expired request: {}", queued_request.id);
                             if let Err(e) = self.storage.remove(&key) {
-                                warn!("Failed to remove corrupted request: {}", e);
+                                warn!("Failed to remove expired request: {}", e);
                             }
+                            continue;
                         }
                     }
+
+                    memory_queue.push_back(queued_request);
+                    loaded_count += 1;
                 }
                 Err(e) => {
-                    warn!("Error reading from queue storage: {}", e);
+                    warn!("Failed to deserialize queued request: {}", e);
+                    // Remove corrupted entry
+                    if let Err(e) = self.storage.remove(&key) {
+                        warn!("Failed to remove corrupted request: {}", e);
+                    }
                 }
             }
         }
@@ -120,6 +203,7 @@ impl PersistentQueue {
                 .partial_cmp(&a.priority_score)
                 .unwrap_or(std::cmp::Ordering::Equal)
                 .then_with(|| a.queued_at.cmp(&b.queued_at))
+                .then_with(|| a.id.cmp(&b.id))
         });
 
         memory_queue.extend(requests);
@@ -128,20 +212,207 @@ impl PersistentQueue {
         Ok(())
     }
 
-    /// Start background task for periodic sync with cloud
-    async fn start_sync_task(&self) {
-        let queue = Arc::new(self.clone());
-        let sync_interval = std::time::Duration::from_millis(self.config.queue.sync_interval_ms);
+    /// Sync queued requests with the cloud, unless a sync is already running
+    pub(crate) async fn sync(&self, trigger: SyncTrigger) -> Result<()> {
+        let Ok(_syncing) = self.syncing.try_lock() else {
+            debug!("Queue sync already running, skipping {:?} sync", trigger);
+            return Ok(());
+        };
+        let result = self.sync_once(trigger).await;
+        if let Err(e) = &result {
+            self.events.emit(QueueEvent::SyncFailed {
+                trigger,
+                error: e.to_string(),
+            });
+        }
+        result
+    }
+
+    async fn sync_once(&self, trigger: SyncTrigger) -> Result<()> {
+        debug!("Starting queue sync with cloud ({:?})", trigger);
+
+        self.update_stats(|stats| {
+            stats.sync_attempts += 1;
+            stats.last_sync_attempt = Some(chrono::Utc::now());
+        }).await;
 
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(sync_interval);
-            loop {
-                interval.tick().await;
-                if let Err(e) = queue.sync_with_cloud().await {
-                    warn!("Background sync failed: {}", e);
+        // Clean up expired requests first
+        self.cleanup_expired_requests().await?;
+
+        let requests_to_sync = {
+            let now = chrono::Utc::now();
+            let memory_queue = self.memory_queue.read().await;
+            // Sync in batches, skipping requests still backing off
+            memory_queue.iter().filter(|queued| queued.due(now)).take(10).cloned().collect::<Vec<_>>()
+        };
+
+        if requests_to_sync.is_empty() {
+            debug!("No requests to sync");
+            return Ok(());
+        }
+
+        // Don't spend retries against a captive portal or a dead uplink
+        if self.config.queue.connectivity.enabled && !self.config.router.cloud_endpoints.is_empty() {
+            let connectivity = self.connectivity.check().await;
+            if connectivity != Connectivity::Online {
+                debug!("Skipping queue sync, {}", connectivity);
+                return Ok(());
+            }
+        }
+        self.events.emit(QueueEvent::SyncStarted {
+            trigger,
+            pending: self.memory_queue.read().await.len(),
+        });
+
+        let mut sync_count = 0;
+        let mut failed_syncs = Vec::new();
+        
+        for queued_request in requests_to_sync {
+            debug!("Syncing request: {}", queued_request.request.id);
+            
+            // The sync continues the trace of the request that was queued
+            let mut span = Span::for_request(&queued_request.request, "queue.sync", SpanKind::Client);
+            let result = self.sync_request_to_cloud(&queued_request, span.as_ref().map(Span::context)).await;
+            if let Some(span) = span.as_mut() {
+                span.set_attribute("queue.retry_count", queued_request.retry_count);
+                span.record_result(&result);
+            }
+            drop(span);
+
+            // Implement actual cloud sync with retry logic
+            match result {
+                Ok(response) => {
+                    sync_count += 1;
+                    info!("Successfully synced request {} to cloud", queued_request.request.id);
+                    
+                    // Store response for later retrieval if needed
+                    if let Err(e) = self.store_response(&queued_request.request.id, &response).await {
+                        warn!("Failed to store cloud response for request {}: {}", queued_request.request.id, e);
+                    }
+                    
+                    // Remove successfully synced request from queue
+                    {
+                        let mut memory_queue = self.memory_queue.write().await;
+                        memory_queue.retain(|req| req.id != queued_request.id);
+                    }
+                    
+                    // Remove from storage
+                    if let Err(e) = self.remove_from_storage(&queued_request.id).await {
+                        warn!("Failed to remove synced request from storage: {}", e);
+                    }
+                },
+                Err(e) => {
+                    warn!("Failed to sync request {} to cloud: {}", queued_request.request.id, e);
+                    failed_syncs.push(queued_request.id);
+                    if let Err(e) = self.retry_or_dead_letter(queued_request, &e.to_string()).await {
+                        warn!("Failed to record sync failure: {}", e);
+                    }
                 }
             }
+        }
+
+        if !failed_syncs.is_empty() {
+            // The uplink may have dropped behind a portal since it was validated
+            self.connectivity.invalidate().await;
+        }
+
+        self.update_stats(|stats| {
+            stats.sync_successes += 1;
+            stats.last_sync_success = Some(chrono::Utc::now());
+        }).await;
+
+        self.events.emit(QueueEvent::SyncFinished {
+            trigger,
+            synced: sync_count,
+            failed: failed_syncs.len(),
+            queue_size: self.memory_queue.read().await.len(),
+        });
+
+        info!("Successfully synced {} requests with cloud", sync_count);
+        Ok(())
+    }
+
+    /// Number of queued requests and when the oldest was queued
+    pub(crate) async fn backlog(&self) -> (usize, Option<chrono::DateTime<chrono::Utc>>) {
+        let memory_queue = self.memory_queue.read().await;
+        let oldest = memory_queue.iter().map(|queued| queued.queued_at).min();
+        (memory_queue.len(), oldest)
+    }
+
+    pub(crate) async fn check_connectivity(&self) -> Connectivity {
+        self.connectivity.check().await
+    }
+
+    /// Back a failed request off for its next attempt, or move it to the
+    /// dead-letter queue once it has used all its retries
+    async fn retry_or_dead_letter(&self, mut queued: QueuedRequest, error: &str) -> Result<()> {
+        let now = chrono::Utc::now();
+        queued.retry_count += 1;
+        queued.last_error = Some(redaction::text(error));
+        let policy = &self.config.queue.retry_policy;
+        if queued.retry_count > policy.max_retries {
+            return self.dead_letter(queued, now).await;
+        }
+
+        queued.retry_at = Some(now + retry_delay(policy, queued.retry_count));
+        debug!(
+            "Retrying request {} after {:?} (retry {})",
+            queued.request.id, queued.retry_at, queued.retry_count
+        );
+        self.persist_request(&queued).await?;
+        let mut memory_queue = self.memory_queue.write().await;
+        match memory_queue.iter_mut().find(|existing| existing.id == queued.id) {
+            Some(existing) => *existing = queued,
+            None => insert_by_priority(&mut memory_queue, queued),
+        }
+        Ok(())
+    }
+
+    async fn dead_letter(&self, queued: QueuedRequest, now: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        warn!(
+            "Request {} failed {} times, moving it to the dead-letter queue",
+            queued.request.id, queued.retry_count
+        );
+        let entry = DeadLetter {
+            request: queued.request,
+            attempts: queued.retry_count,
+            last_error: queued.last_error.unwrap_or_default(),
+            queued_at: queued.queued_at,
+            dead_lettered_at: now,
+        };
+        let value = serde_json::to_vec(&entry)
+            .map_err(|e| Error::Queue(format!("Failed to serialize dead letter: {}", e)))?;
+        self.storage.put(&dead_letter_key(&entry.request.id), &value)?;
+        self.memory_queue.write().await.retain(|existing| existing.id != queued.id);
+        self.remove_from_storage(&queued.id).await?;
+        self.update_stats(|stats| stats.total_failed += 1).await;
+        self.events.emit(QueueEvent::DeadLettered {
+            request_id: entry.request.id,
+            retries: entry.attempts,
         });
+
+        // Drop the oldest entries beyond the limit
+        let mut dead_letters = self.load_dead_letters()?;
+        for oldest in dead_letters.split_off(self.config.queue.dead_letter.max_entries.min(dead_letters.len())) {
+            self.storage.remove(&dead_letter_key(&oldest.request.id))?;
+        }
+        Ok(())
+    }
+
+    /// Dead-lettered requests, most recent first
+    fn load_dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        let mut dead_letters = Vec::new();
+        for (key, value) in self.storage.scan(DEAD_LETTER_PREFIX)? {
+            match serde_json::from_slice::<DeadLetter>(&value) {
+                Ok(entry) => dead_letters.push(entry),
+                Err(e) => {
+                    warn!("Failed to deserialize dead letter, removing it: {}", e);
+                    self.storage.remove(&key)?;
+                },
+            }
+        }
+        dead_letters.sort_by_key(|entry| Reverse(entry.dead_lettered_at));
+        Ok(dead_letters)
     }
 
     /// Calculate priority score for a request
@@ -189,25 +460,13 @@ impl PersistentQueue {
         let value = serde_json::to_vec(queued_request)
             .map_err(|e| Error::Queue(format!("Failed to serialize request: {}", e)))?;
 
-        self.storage
-            .insert(key.as_bytes(), value)
-            .map_err(|e| Error::Queue(format!("Failed to persist request: {}", e)))?;
-
-        self.storage
-            .flush()
-            .map_err(|e| Error::Queue(format!("Failed to flush storage: {}", e)))?;
-
-        Ok(())
+        self.storage.put(&key, &value)
     }
 
     /// Remove a request from storage
     async fn remove_from_storage(&self, request_id: &Uuid) -> Result<()> {
         let key = format!("request:{}", request_id);
-        self.storage
-            .remove(key.as_bytes())
-            .map_err(|e| Error::Queue(format!("Failed to remove request from storage: {}", e)))?;
-
-        Ok(())
+        self.storage.remove(&key)
     }
 
     /// Get queue statistics
@@ -245,7 +504,7 @@ impl PersistentQueue {
         memory_queue.retain(|req| {
             if let Some(expires_at) = req.expires_at {
                 if now > expires_at {
-                    expired_ids.push(req.id);
+                    expired_ids.push((req.id, req.request.id));
                     false
                 } else {
                     true
@@ -256,12 +515,13 @@ impl PersistentQueue {
         });
 
         // Remove expired requests from storage
-        for id in expired_ids {
+        for (id, request_id) in expired_ids {
             if let Err(e) = self.remove_from_storage(&id).await {
                 warn!("Failed to remove expired request {}: {}", id, e);
             } else {
                 removed_count += 1;
             }
+            self.events.emit(QueueEvent::Expired { request_id });
         }
 
         if removed_count > 0 {
@@ -272,9 +532,16 @@ impl PersistentQueue {
         Ok(removed_count)
     }
     
-    /// Sync a single request to the cloud with retry logic and exponential backoff
-    async fn sync_request_to_cloud(&self, queued_request: &QueuedRequest) -> Result<MCPResponse> {
-        let cloud_endpoint = self.config.router.cloud_fallback_endpoint.as_ref()
+    /// Sync a single request to the cloud
+    async fn sync_request_to_cloud(&self, queued_request: &QueuedRequest, trace: Option<TraceContext>) -> Result<MCPResponse> {
+        let mut request = queued_request.released_request();
+        if let Some(trace) = trace {
+            request.set_trace(trace);
+        }
+        let _permit = self.sync_limiter.acquire_for(request.priority()).await?;
+
+        let cloud_endpoint = self.config.router.cloud_endpoints.first()
+            .map(|endpoint| endpoint.url.as_str())
             .ok_or_else(|| Error::Queue("No cloud endpoint configured".to_string()))?;
             
         // Create HTTP client with timeout
@@ -282,20 +549,9 @@ impl PersistentQueue {
             .timeout(std::time::Duration::from_millis(self.config.queue.sync_timeout_ms))
             .build()
             .map_err(|e| Error::Queue(format!("Failed to create HTTP client: {}", e)))?;
-            
-        // Calculate exponential backoff delay based on retry count
-        let backoff_ms = std::cmp::min(
-            1000 * (2_u64.pow(queued_request.retry_count.min(10))), // Cap at 2^10 seconds
-            30000 // Max 30 seconds
-        );
-        
-        if queued_request.retry_count > 0 {
-            debug!("Applying backoff delay of {}ms for retry {}", backoff_ms, queued_request.retry_count);
-            tokio::time::sleep(std::time::Duration::from_millis(backoff_ms)).await;
-        }
-        
+
         // Prepare the request payload
-        let mut request_data = serde_json::to_value(&queued_request.request)
+        let mut request_data = serde_json::to_value(&request)
             .map_err(|e| Error::Queue(format!("Failed to serialize request: {}", e)))?;
             
         // Add queue metadata
@@ -308,45 +564,94 @@ impl PersistentQueue {
             }));
         }
         
-        // Send request to cloud
-        let response = client
-            .post(cloud_endpoint)
-            .header("Content-Type", "application/json")
-            .header("User-Agent", format!("mcp-edge-gateway/{}", env!("CARGO_PKG_VERSION")))
-            .json(&request_data)
-            .send()
-            .await
-            .map_err(|e| Error::Queue(format!("Failed to send request to cloud: {}", e)))?;
+        // Send request to cloud, without compression if the cloud cannot read it
+        let body = serde_json::to_vec(&request_data)
+            .map_err(|e| Error::Queue(format!("Failed to serialize request: {}", e)))?;
+        let mut compression = self.sync_compression();
+        let (response, sent) = loop {
+            let encoded = compression::encode(compression, body.clone());
+            let compressed = encoded.content_encoding.is_some();
+            let sent = encoded.body.len() as u64;
+            let mut request_builder = client
+                .post(cloud_endpoint)
+                .header("Content-Type", "application/json")
+                .header("User-Agent", format!("mcp-edge-gateway/{}", env!("CARGO_PKG_VERSION")));
+            for (name, value) in request_signing::signature_headers(&encoded.body).await {
+                request_builder = request_builder.header(name, value);
+            }
+            if let Some(trace) = request.trace() {
+                request_builder = request_builder.header(TRACEPARENT_HEADER, trace.traceparent());
+            }
+            if let Some(content_encoding) = encoded.content_encoding {
+                request_builder = request_builder.header(reqwest::header::CONTENT_ENCODING, content_encoding);
+            }
+            if let Some(accept_encoding) = compression.content_encoding() {
+                request_builder = request_builder.header(reqwest::header::ACCEPT_ENCODING, accept_encoding);
+            }
+            let response = request_builder
+                .body(encoded.body)
+                .send()
+                .await
+                .map_err(|e| Error::Queue(format!("Failed to send request to cloud: {}", e)))?;
+            if compressed && response.status() == reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE {
+                warn!("Cloud does not accept {:?} sync uploads, sending them uncompressed", compression);
+                bandwidth::record(Subsystem::QueueSync, sent, 0);
+                self.sync_uncompressed.store(true, Ordering::Relaxed);
+                compression = PayloadCompression::None;
+                continue;
+            }
+            break (response, sent);
+        };
             
         if !response.status().is_success() {
             let status = response.status();
             let error_body = response.text().await.unwrap_or_default();
+            bandwidth::record(Subsystem::QueueSync, sent, error_body.len() as u64);
             return Err(Error::Queue(format!(
-                "Cloud sync failed with status {}: {}", 
-                status, 
-                error_body
+                "Cloud sync failed with status {}: {}",
+                status,
+                redaction::text(&error_body)
             )));
         }
         
         // Parse response
-        let cloud_response: MCPResponse = response
-            .json()
-            .await
+        let content_encoding = response
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let response_body = response.bytes().await;
+        bandwidth::record(
+            Subsystem::QueueSync,
+            sent,
+            response_body.as_ref().map_or(0, |body| body.len() as u64),
+        );
+        let response_body =
+            response_body.map_err(|e| Error::Queue(format!("Failed to read cloud response: {}", e)))?;
+        let response_body = compression::decode(content_encoding.as_deref(), &response_body)?;
+        let cloud_response: MCPResponse = serde_json::from_slice(&response_body)
             .map_err(|e| Error::Queue(format!("Failed to parse cloud response: {}", e)))?;
             
         debug!("Cloud sync successful for request {}", queued_request.request.id);
         Ok(cloud_response)
     }
     
+    /// Codec for sync uploads, none once the cloud has rejected them
+    fn sync_compression(&self) -> PayloadCompression {
+        if self.config.queue.compression_enabled && !self.sync_uncompressed.load(Ordering::Relaxed) {
+            self.config.queue.compression
+        } else {
+            PayloadCompression::None
+        }
+    }
+
     /// Store cloud response for later retrieval
     async fn store_response(&self, request_id: &Uuid, response: &MCPResponse) -> Result<()> {
         let key = format!("response:{}", request_id);
-        let response_data = bincode::encode_to_vec(response, bincode::config::standard())
+        let response_data = serde_json::to_vec(response)
             .map_err(|e| Error::Queue(format!("Failed to serialize response: {}", e)))?;
             
-        self.storage
-            .insert(key.as_bytes(), response_data)
-            .map_err(|e| Error::Queue(format!("Failed to store response: {}", e)))?;
+        self.storage.put(&key, &response_data)?;
             
         debug!("Stored cloud response for request {}", request_id);
         Ok(())
@@ -356,14 +661,14 @@ impl PersistentQueue {
     pub async fn get_stored_response(&self, request_id: &Uuid) -> Result<Option<MCPResponse>> {
         let key = format!("response:{}", request_id);
         
-        match self.storage.get(key.as_bytes()) {
+        match self.storage.get(&key) {
             Ok(Some(data)) => {
-                match bincode::decode_from_slice(&data, bincode::config::standard()) {
-                    Ok((response, _)) => Ok(Some(response)),
+                match serde_json::from_slice(&data) {
+                    Ok(response) => Ok(Some(response)),
                     Err(e) => {
                         warn!("Failed to deserialize stored response: {}", e);
                         // Clean up corrupted response
-                        if let Err(e) = self.storage.remove(key.as_bytes()) {
+                        if let Err(e) = self.storage.remove(&key) {
                             warn!("Failed to remove corrupted response: {}", e);
                         }
                         Ok(None)
@@ -371,7 +676,7 @@ impl PersistentQueue {
                 }
             },
             Ok(None) => Ok(None),
-            Err(e) => Err(Error::Queue(format!("Failed to retrieve stored response: {}", e)))
+            Err(e) => Err(e),
         }
     }
 }
@@ -380,6 +685,7 @@ impl PersistentQueue {
 impl OfflineQueue for PersistentQueue {
     async fn enqueue_request(&self, request: MCPRequest) -> Result<MCPResponse> {
         debug!("Enqueuing request: {}", request.id);
+        let _span = Span::for_request(&request, "queue.enqueue", SpanKind::Internal);
 
         // Check queue size limit
         let current_size = {
@@ -408,12 +714,14 @@ impl OfflineQueue for PersistentQueue {
             .map(|timeout| chrono::Utc::now() + chrono::Duration::milliseconds(timeout as i64));
 
         let queued_request = QueuedRequest {
-            id: Uuid::new_v4(),
+            id: request_id::generate(),
             request: request.clone(),
             queued_at: chrono::Utc::now(),
             retry_count: 0,
             priority_score,
             expires_at,
+            retry_at: None,
+            last_error: None,
         };
 
         // Persist to storage
@@ -423,17 +731,17 @@ impl OfflineQueue for PersistentQueue {
         }
 
         // Add to memory queue
-        {
+        let queue_size = {
             let mut memory_queue = self.memory_queue.write().await;
-            
-            // Insert in priority order
-            let insert_pos = memory_queue
-                .iter()
-                .position(|req| req.priority_score < priority_score)
-                .unwrap_or(memory_queue.len());
-            
-            memory_queue.insert(insert_pos, queued_request);
-        }
+            insert_by_priority(&mut memory_queue, queued_request);
+            memory_queue.len()
+        };
+        self.events.emit(QueueEvent::Enqueued {
+            request_id: request.id,
+            priority_score,
+            queue_size,
+            capacity: self.config.queue.max_queue_size as usize,
+        });
 
         // Update statistics
         self.update_stats(|stats| stats.total_enqueued += 1).await;
@@ -455,19 +763,26 @@ impl OfflineQueue for PersistentQueue {
     async fn dequeue_request(&self) -> Result<Option<MCPRequest>> {
         let mut memory_queue = self.memory_queue.write().await;
 
-        if let Some(queued_request) = memory_queue.pop_front() {
+        // The first request not backing off after a failure
+        let now = chrono::Utc::now();
+        let next = memory_queue.iter().position(|queued| queued.due(now));
+        if let Some(queued_request) = next.and_then(|position| memory_queue.remove(position)) {
             // Remove from storage
             if let Err(e) = self.remove_from_storage(&queued_request.id).await {
                 warn!("Failed to remove request from storage: {}", e);
-                // Re-add to front of queue
-                memory_queue.push_front(queued_request);
+                // Put it back where it was
+                insert_by_priority(&mut memory_queue, queued_request);
                 return Err(e);
             }
 
             self.update_stats(|stats| stats.total_dequeued += 1).await;
+            self.events.emit(QueueEvent::Dequeued {
+                request_id: queued_request.request.id,
+                queue_size: memory_queue.len(),
+            });
             
             debug!("Dequeued request: {}", queued_request.request.id);
-            Ok(Some(queued_request.request))
+            Ok(Some(queued_request.released_request()))
         } else {
             Ok(None)
         }
@@ -479,91 +794,147 @@ impl OfflineQueue for PersistentQueue {
     }
 
     async fn sync_with_cloud(&self) -> Result<()> {
-        debug!("Starting queue sync with cloud");
+        self.sync(SyncTrigger::Manual).await
+    }
 
-        self.update_stats(|stats| {
-            stats.sync_attempts += 1;
-            stats.last_sync_attempt = Some(chrono::Utc::now());
-        }).await;
+    async fn fail_request(&self, request: MCPRequest, error: &str) -> Result<()> {
+        warn!("Processing of request {} failed: {}", request.id, redaction::text(error));
+        let retry_count = request.context.as_ref().map_or(0, |context| context.retry_count);
+        let queued = QueuedRequest {
+            id: request_id::generate(),
+            priority_score: self.calculate_priority_score(&request),
+            request,
+            queued_at: chrono::Utc::now(),
+            retry_count,
+            expires_at: None,
+            retry_at: None,
+            last_error: None,
+        };
+        self.retry_or_dead_letter(queued, error).await
+    }
 
-        // Clean up expired requests first
-        self.cleanup_expired_requests().await?;
+    async fn dead_letters(&self) -> Result<Vec<DeadLetter>> {
+        self.load_dead_letters()
+    }
 
-        let requests_to_sync = {
-            let memory_queue = self.memory_queue.read().await;
-            memory_queue.iter().take(10).cloned().collect::<Vec<_>>() // Sync in batches
+    async fn requeue_dead_letter(&self, request_id: Uuid) -> Result<bool> {
+        let key = dead_letter_key(&request_id);
+        let Some(value) = self.storage.get(&key)? else {
+            return Ok(false);
         };
+        let entry: DeadLetter = serde_json::from_slice(&value)
+            .map_err(|e| Error::Queue(format!("Failed to deserialize dead letter: {}", e)))?;
 
-        if requests_to_sync.is_empty() {
-            debug!("No requests to sync");
-            return Ok(());
+        let mut memory_queue = self.memory_queue.write().await;
+        if memory_queue.len() >= self.config.queue.max_queue_size as usize {
+            return Err(Error::Queue("Queue is full".to_string()));
         }
+        let queued = QueuedRequest {
+            id: request_id::generate(),
+            priority_score: self.calculate_priority_score(&entry.request),
+            request: entry.request,
+            queued_at: entry.queued_at,
+            retry_count: 0,
+            expires_at: None,
+            retry_at: None,
+            last_error: None,
+        };
+        self.persist_request(&queued).await?;
+        self.storage.remove(&key)?;
+        let priority_score = queued.priority_score;
+        insert_by_priority(&mut memory_queue, queued);
+        self.events.emit(QueueEvent::Enqueued {
+            request_id,
+            priority_score,
+            queue_size: memory_queue.len(),
+            capacity: self.config.queue.max_queue_size as usize,
+        });
+        info!("Requeued dead-lettered request {}", request_id);
+        Ok(true)
+    }
 
-        let mut sync_count = 0;
-        let mut failed_syncs = Vec::new();
-        
-        for queued_request in requests_to_sync {
-            debug!("Syncing request: {}", queued_request.request.id);
-            
-            // Implement actual cloud sync with retry logic
-            match self.sync_request_to_cloud(&queued_request).await {
-                Ok(response) => {
-                    sync_count += 1;
-                    info!("Successfully synced request {} to cloud", queued_request.request.id);
-                    
-                    // Store response for later retrieval if needed
-                    if let Err(e) = self.store_response(&queued_request.request.id, &response).await {
-                        warn!("Failed to store cloud response for request {}: {}", queued_request.request.id, e);
-                    }
-                    
-                    // Remove successfully synced request from queue
-                    {
-                        let mut memory_queue = self.memory_queue.write().await;
-                        memory_queue.retain(|req| req.id != queued_request.id);
-                    }
-                    
-                    // Remove from storage
-                    if let Err(e) = self.remove_from_storage(&queued_request.id).await {
-                        warn!("Failed to remove synced request from storage: {}", e);
-                    }
-                },
-                Err(e) => {
-                    warn!("Failed to sync request {} to cloud: {}", queued_request.request.id, e);
-                    failed_syncs.push(queued_request.id);
-                    
-                    // Increment retry count
-                    let mut memory_queue = self.memory_queue.write().await;
-                    if let Some(req) = memory_queue.iter_mut().find(|r| r.id == queued_request.id) {
-                        req.retry_count += 1;
-                        
-                        // Remove requests that have exceeded max retries
-                        if req.retry_count > self.config.queue.max_retries {
-                            warn!("Request {} exceeded max retries, removing from queue", req.request.id);
-                            if let Err(e) = self.remove_from_storage(&req.id).await {
-                                warn!("Failed to remove failed request from storage: {}", e);
-                            }
-                        }
-                    }
-                    
-                    // Filter out failed requests that exceeded max retries
-                    memory_queue.retain(|req| {
-                        if failed_syncs.contains(&req.id) && req.retry_count > self.config.queue.max_retries {
-                            false
-                        } else {
-                            true
-                        }
-                    });
-                }
+    async fn purge_dead_letters(&self, request_ids: &[Uuid]) -> Result<usize> {
+        let mut purged = 0;
+        for entry in self.load_dead_letters()? {
+            if request_ids.is_empty() || request_ids.contains(&entry.request.id) {
+                self.storage.remove(&dead_letter_key(&entry.request.id))?;
+                purged += 1;
             }
         }
+        info!("Purged {} dead-lettered requests", purged);
+        Ok(purged)
+    }
 
-        self.update_stats(|stats| {
-            stats.sync_successes += 1;
-            stats.last_sync_success = Some(chrono::Utc::now());
-        }).await;
+    async fn purge(
+        &self,
+        cutoff: chrono::DateTime<chrono::Utc>,
+        held_devices: &[String],
+        dry_run: bool,
+    ) -> Result<QueuePurge> {
+        let mut memory_queue = self.memory_queue.write().await;
+        let mut purge = QueuePurge::default();
+        let mut expired = Vec::new();
+        for queued in memory_queue.iter().filter(|queued| queued.queued_at < cutoff) {
+            if held_devices.contains(&queued.request.device_id) {
+                purge.held += 1;
+            } else {
+                expired.push(queued.id);
+                purge.request_ids.push(queued.request.id);
+            }
+        }
+        if dry_run || expired.is_empty() {
+            return Ok(purge);
+        }
 
-        info!("Successfully synced {} requests with cloud", sync_count);
-        Ok(())
+        for id in &expired {
+            self.remove_from_storage(id).await?;
+        }
+        memory_queue.retain(|queued| !expired.contains(&queued.id));
+        info!("Purged {} queued requests past retention", expired.len());
+        Ok(purge)
+    }
+
+    async fn erase(
+        &self,
+        selector: &(dyn for<'r> Fn(&'r MCPRequest) -> bool + Send + Sync),
+        dry_run: bool,
+    ) -> Result<Vec<Uuid>> {
+        let mut memory_queue = self.memory_queue.write().await;
+        let matching: Vec<(Uuid, Uuid)> = memory_queue
+            .iter()
+            .filter(|queued| selector(&queued.request))
+            .map(|queued| (queued.id, queued.request.id))
+            .collect();
+        if dry_run || matching.is_empty() {
+            return Ok(matching.into_iter().map(|(_, request_id)| request_id).collect());
+        }
+
+        for (id, request_id) in &matching {
+            self.remove_from_storage(id).await?;
+            self.storage.remove(&format!("response:{}", request_id))?;
+        }
+        memory_queue.retain(|queued| !matching.iter().any(|(id, _)| *id == queued.id));
+        info!("Erased {} queued requests", matching.len());
+        Ok(matching.into_iter().map(|(_, request_id)| request_id).collect())
+    }
+
+    fn subscribe(&self) -> Result<EventSubscriber> {
+        self.events.subscribe()
+    }
+
+    async fn analytics(&self) -> Result<Option<QueueAnalytics>> {
+        let (queue_size, ages) = {
+            let memory_queue = self.memory_queue.read().await;
+            let queued_at = memory_queue.iter().map(|queued| queued.queued_at);
+            (memory_queue.len(), QueueAgePercentiles::from_queued_at(queued_at, chrono::Utc::now()))
+        };
+        // Until the first check the measured drain rate speaks for the uplink
+        let online = matches!(self.connectivity.current(), Connectivity::Online | Connectivity::Unknown);
+        Ok(Some(QueueAnalytics::new(queue_size, ages, self.events.flow().rates(), online)))
+    }
+
+    fn set_sync_policies(&self, policies: &[SyncPolicy]) {
+        self.sync_scheduler.start(self, policies);
     }
 
     async fn health_check(&self) -> Result<ComponentHealth> {
@@ -576,21 +947,32 @@ impl OfflineQueue for PersistentQueue {
         health_metrics.insert("total_enqueued".to_string(), stats.total_enqueued as f32);
         health_metrics.insert("total_dequeued".to_string(), stats.total_dequeued as f32);
         health_metrics.insert("total_failed".to_string(), stats.total_failed as f32);
+        health_metrics.insert("dead_letter_depth".to_string(), self.load_dead_letters()?.len() as f32);
         health_metrics.insert("sync_attempts".to_string(), stats.sync_attempts as f32);
         health_metrics.insert("sync_successes".to_string(), stats.sync_successes as f32);
+        self.sync_limiter.gauge().write_metrics(&mut health_metrics);
 
         let usage_percent = (queue_size as f32 / self.config.queue.max_queue_size as f32) * 100.0;
         health_metrics.insert("usage_percent".to_string(), usage_percent);
+        self.connectivity.write_metrics(&mut health_metrics);
+        let connectivity = self.connectivity.current();
+        let intercepted = matches!(
+            connectivity,
+            Connectivity::CaptivePortal { .. } | Connectivity::DnsHijacked { .. }
+        );
 
         let status = if usage_percent > 95.0 {
             HealthLevel::Critical
-        } else if usage_percent > 80.0 {
+        } else if usage_percent > 80.0 || intercepted {
             HealthLevel::Degraded
         } else {
             HealthLevel::Healthy
         };
 
         let message = match status {
+            HealthLevel::Degraded if intercepted => {
+                format!("Cloud sync paused, {} ({} items queued)", connectivity, queue_size)
+            },
             HealthLevel::Healthy => format!("Queue operating normally ({} items)", queue_size),
             HealthLevel::Degraded => format!("Queue usage high ({:.1}%)", usage_percent),
             HealthLevel::Critical => format!("Queue nearly full ({:.1}%)", usage_percent),
@@ -607,6 +989,7 @@ impl OfflineQueue for PersistentQueue {
 
     async fn shutdown(&self) -> Result<()> {
         info!("Shutting down persistent queue");
+        self.sync_scheduler.stop();
 
         // Flush any pending writes
         if let Err(e) = self.storage.flush() {
@@ -621,6 +1004,29 @@ impl OfflineQueue for PersistentQueue {
     }
 }
 
+const DEAD_LETTER_PREFIX: &str = "dead:";
+
+fn dead_letter_key(request_id: &Uuid) -> String {
+    format!("{}{}", DEAD_LETTER_PREFIX, request_id)
+}
+
+/// Insert behind every request with at least the same priority
+fn insert_by_priority(memory_queue: &mut VecDeque<QueuedRequest>, queued: QueuedRequest) {
+    let position = memory_queue
+        .iter()
+        .position(|existing| existing.priority_score < queued.priority_score)
+        .unwrap_or(memory_queue.len());
+    memory_queue.insert(position, queued);
+}
+
+/// Wait before retry `retry_count`: the initial delay, multiplied by the
+/// backoff multiplier for each earlier retry, capped at the maximum delay
+fn retry_delay(policy: &RetryPolicy, retry_count: u32) -> chrono::Duration {
+    let exponent = retry_count.saturating_sub(1).min(32) as i32;
+    let delay_ms = policy.initial_delay_ms as f64 * (policy.backoff_multiplier as f64).powi(exponent);
+    chrono::Duration::milliseconds(delay_ms.min(policy.max_delay_ms as f64) as i64)
+}
+
 // Implement Clone for PersistentQueue to enable Arc<Self> usage
 impl Clone for PersistentQueue {
     fn clone(&self) -> Self {
@@ -629,6 +1035,12 @@ impl Clone for PersistentQueue {
             storage: self.storage.clone(),
             memory_queue: self.memory_queue.clone(),
             stats: self.stats.clone(),
+            sync_limiter: self.sync_limiter.clone(),
+            connectivity: self.connectivity.clone(),
+            events: self.events.clone(),
+            sync_uncompressed: self.sync_uncompressed.clone(),
+            syncing: self.syncing.clone(),
+            sync_scheduler: self.sync_scheduler.clone(),
         }
     }
 }
\ No newline at end of file
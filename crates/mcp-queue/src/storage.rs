@@ -0,0 +1,301 @@
+//! Storage backends for the offline queue
+//!
+//! The queue keeps its working set in memory and writes every queued request
+//! and stored cloud response through a [`QueueStorageBackend`], so that the
+//! queue can be rebuilt after a restart or power cut. A `put` is durable when
+//! it returns, except that sled batches its flushes while the
+//! [`WritePolicy`] is relaxed for worn media. Keys are namespaced strings
+//! such as `request:<id>`.
+//!
+//! `queue.storage_backend` selects sled (the default), SQLite, or memory.
+//! SQLite runs in WAL mode with full synchronous commits: a write is on disk
+//! before `put` returns, and a torn write after a power cut is rolled back
+//! from the journal on the next open instead of corrupting the queue.
+
+use mcp_common::config::QueueStorageKind;
+use mcp_common::write_policy::WritePolicy;
+use mcp_common::{Error, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Durable key/value store the queue persists into
+pub trait QueueStorageBackend: Send + Sync {
+    /// Backend name for logs and health output
+    fn name(&self) -> &'static str;
+
+    /// Store a value, durably once this returns
+    fn put(&self, key: &str, value: &[u8]) -> Result<()>;
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Remove a key; removing a missing key is not an error
+    fn remove(&self, key: &str) -> Result<()>;
+
+    /// All entries whose key starts with `prefix`, in key order
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>>;
+
+    /// Write out anything buffered
+    fn flush(&self) -> Result<()>;
+}
+
+/// Open the configured backend at `path`, or in memory when `path` is None
+pub fn open_storage(kind: QueueStorageKind, path: Option<&Path>) -> Result<Box<dyn QueueStorageBackend>> {
+    Ok(match (kind, path) {
+        (QueueStorageKind::Sled, Some(path)) => Box::new(SledStorage::open(path)?),
+        (QueueStorageKind::Sled, None) => Box::new(SledStorage::temporary()?),
+        (QueueStorageKind::Sqlite, Some(path)) => Box::new(SqliteStorage::open(&path.with_extension("sqlite"))?),
+        (QueueStorageKind::Sqlite, None) => Box::new(SqliteStorage::in_memory()?),
+        (QueueStorageKind::Memory, _) => Box::new(MemoryStorage::default()),
+    })
+}
+
+fn storage_error(action: &str, e: impl std::fmt::Display) -> Error {
+    Error::Queue(format!("Failed to {} queue storage: {}", action, e))
+}
+
+/// Sled database, flushed after every write
+pub struct SledStorage {
+    db: sled::Db,
+}
+
+impl SledStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = sled::open(path).map_err(|e| storage_error("open", e))?;
+        Ok(Self { db })
+    }
+
+    pub fn temporary() -> Result<Self> {
+        let db = sled::Config::new().temporary(true).open().map_err(|e| storage_error("open", e))?;
+        Ok(Self { db })
+    }
+}
+
+impl QueueStorageBackend for SledStorage {
+    fn name(&self) -> &'static str {
+        "sled"
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.db.insert(key.as_bytes(), value).map_err(|e| storage_error("write", e))?;
+        // Sled flushes on its own shortly after; worn media skip most explicit flushes
+        if !WritePolicy::global().should_sync() {
+            return Ok(());
+        }
+        self.flush()
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let value = self.db.get(key.as_bytes()).map_err(|e| storage_error("read", e))?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.db.remove(key.as_bytes()).map_err(|e| storage_error("remove from", e))?;
+        Ok(())
+    }
+
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        self.db
+            .scan_prefix(prefix.as_bytes())
+            .map(|entry| {
+                let (key, value) = entry.map_err(|e| storage_error("read", e))?;
+                Ok((String::from_utf8_lossy(&key).into_owned(), value.to_vec()))
+            })
+            .collect()
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.db.flush().map_err(|e| storage_error("flush", e))?;
+        Ok(())
+    }
+}
+
+/// SQLite database in WAL mode
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| storage_error("create directory for", e))?;
+        }
+        let connection = Connection::open(path).map_err(|e| storage_error("open", e))?;
+        let mode: String = connection
+            .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+            .map_err(|e| storage_error("configure", e))?;
+        if !mode.eq_ignore_ascii_case("wal") {
+            return Err(Error::Queue(format!("SQLite queue storage could not enable WAL mode (got {})", mode)));
+        }
+        Self::init(connection)
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(|e| storage_error("open", e))?)
+    }
+
+    fn init(connection: Connection) -> Result<Self> {
+        // FULL syncs the WAL on every commit; NORMAL could lose the last
+        // requests queued before a power cut
+        connection
+            .execute_batch(
+                "PRAGMA synchronous = FULL;
+                 PRAGMA busy_timeout = 5000;
+                 CREATE TABLE IF NOT EXISTS queue_entries (
+                     key TEXT PRIMARY KEY NOT NULL,
+                     value BLOB NOT NULL
+                 ) WITHOUT ROWID;",
+            )
+            .map_err(|e| storage_error("initialize", e))?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // A panic mid-statement leaves nothing half-applied; SQLite rolls it back
+        self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl QueueStorageBackend for SqliteStorage {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.connection()
+            .execute(
+                "INSERT INTO queue_entries (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            )
+            .map_err(|e| storage_error("write", e))?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.connection()
+            .query_row("SELECT value FROM queue_entries WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()
+            .map_err(|e| storage_error("read", e))
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.connection()
+            .execute("DELETE FROM queue_entries WHERE key = ?1", params![key])
+            .map_err(|e| storage_error("remove from", e))?;
+        Ok(())
+    }
+
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare("SELECT key, value FROM queue_entries WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key")
+            .map_err(|e| storage_error("read", e))?;
+        let rows = statement
+            .query_map(params![prefix], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| storage_error("read", e))?;
+        rows.collect::<rusqlite::Result<_>>().map_err(|e| storage_error("read", e))
+    }
+
+    fn flush(&self) -> Result<()> {
+        // Move committed WAL frames into the database file
+        self.connection()
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(|e| storage_error("checkpoint", e))
+    }
+}
+
+/// Volatile storage for tests and devices without writable storage
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    fn entries(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Vec<u8>>> {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl QueueStorageBackend for MemoryStorage {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.entries().insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.entries().get(key).cloned())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.entries().remove(key);
+        Ok(())
+    }
+
+    fn scan(&self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        Ok(self
+            .entries()
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(storage: &dyn QueueStorageBackend) {
+        storage.put("request:b", b"2").unwrap();
+        storage.put("request:a", b"1").unwrap();
+        storage.put("response:a", b"r").unwrap();
+        storage.put("request:a", b"1b").unwrap();
+        assert_eq!(
+            storage.scan("request:").unwrap(),
+            vec![("request:a".to_string(), b"1b".to_vec()), ("request:b".to_string(), b"2".to_vec())]
+        );
+        storage.remove("request:b").unwrap();
+        storage.remove("request:missing").unwrap();
+        assert_eq!(storage.get("request:b").unwrap(), None);
+        assert_eq!(storage.get("response:a").unwrap(), Some(b"r".to_vec()));
+        storage.flush().unwrap();
+    }
+
+    #[test]
+    fn test_backends_share_semantics() {
+        exercise(&MemoryStorage::default());
+        exercise(&SqliteStorage::in_memory().unwrap());
+        exercise(&SledStorage::temporary().unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_writes_survive_unclean_shutdown() {
+        let dir = std::env::temp_dir().join(format!("queue-sqlite-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("queue.sqlite");
+        let storage = SqliteStorage::open(&path).unwrap();
+        storage.put("request:1", b"queued").unwrap();
+        storage.put("request:2", b"dequeued").unwrap();
+        storage.remove("request:2").unwrap();
+        // Simulate a power cut: the connection is never closed or checkpointed
+        std::mem::forget(storage);
+        assert!(dir.join("queue.sqlite-wal").exists());
+
+        let recovered = SqliteStorage::open(&path).unwrap();
+        assert_eq!(recovered.scan("request:").unwrap(), vec![("request:1".to_string(), b"queued".to_vec())]);
+        drop(recovered);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
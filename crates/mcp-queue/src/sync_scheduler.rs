@@ -0,0 +1,211 @@
+//! Background queue sync
+//!
+//! Each [`SyncPolicy`] in `queue.sync_policies` runs as its own task that
+//! starts a sync when its trigger fires: a fixed interval, connectivity
+//! returning after the `queue.connectivity` probes failed, or the queue
+//! growing too deep or too old. Syncs never overlap, so policies firing
+//! together send each request once, and the queue events of every sync carry
+//! the [`SyncTrigger`] that started it.
+
+use crate::connectivity::Connectivity;
+use crate::persistent_queue::PersistentQueue;
+use chrono::{DateTime, Utc};
+use mcp_common::config::SyncPolicy;
+use mcp_common::events::SyncTrigger;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Runs the sync policies of one queue
+#[derive(Default)]
+pub(crate) struct SyncScheduler {
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl SyncScheduler {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a task per policy syncing `queue`, replacing any running ones
+    pub(crate) fn start(&self, queue: &PersistentQueue, policies: &[SyncPolicy]) {
+        let handles = policies
+            .iter()
+            .map(|policy| tokio::spawn(run_policy(queue.clone(), policy.clone())))
+            .collect();
+        for previous in std::mem::replace(&mut *self.lock_tasks(), handles) {
+            previous.abort();
+        }
+    }
+
+    pub(crate) fn stop(&self) {
+        for handle in self.lock_tasks().drain(..) {
+            handle.abort();
+        }
+    }
+
+    fn lock_tasks(&self) -> std::sync::MutexGuard<'_, Vec<JoinHandle<()>>> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+async fn run_policy(queue: PersistentQueue, policy: SyncPolicy) {
+    let period = match &policy {
+        SyncPolicy::Interval {
+            sync_interval_seconds,
+        } => *sync_interval_seconds,
+        SyncPolicy::Connectivity {
+            check_interval_seconds,
+        }
+        | SyncPolicy::Threshold {
+            check_interval_seconds,
+            ..
+        } => *check_interval_seconds,
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(period.max(1)));
+    let mut previous = Connectivity::Unknown;
+    loop {
+        interval.tick().await;
+        let trigger = match &policy {
+            SyncPolicy::Interval { .. } => SyncTrigger::Interval,
+            SyncPolicy::Connectivity { .. } => {
+                let current = queue.check_connectivity().await;
+                let restored = connectivity_restored(&previous, &current);
+                previous = current;
+                if !restored {
+                    continue;
+                }
+                SyncTrigger::Connectivity
+            },
+            SyncPolicy::Threshold {
+                max_depth,
+                max_age_seconds,
+                ..
+            } => {
+                let (depth, oldest) = queue.backlog().await;
+                if !threshold_reached(depth, oldest, *max_depth, *max_age_seconds, Utc::now()) {
+                    continue;
+                }
+                SyncTrigger::Threshold
+            },
+        };
+        debug!("Starting {:?} queue sync", trigger);
+        if let Err(e) = queue.sync(trigger).await {
+            warn!("Background {:?} sync failed: {}", trigger, e);
+        }
+    }
+}
+
+/// Whether the uplink came back, as opposed to being up since startup
+fn connectivity_restored(previous: &Connectivity, current: &Connectivity) -> bool {
+    *current == Connectivity::Online
+        && !matches!(previous, Connectivity::Online | Connectivity::Unknown)
+}
+
+fn threshold_reached(
+    depth: usize,
+    oldest: Option<DateTime<Utc>>,
+    max_depth: Option<u32>,
+    max_age_seconds: Option<u64>,
+    now: DateTime<Utc>,
+) -> bool {
+    let too_deep = max_depth.is_some_and(|max_depth| depth >= max_depth as usize);
+    let too_old = match (oldest, max_age_seconds) {
+        (Some(oldest), Some(max_age)) => (now - oldest).num_seconds() >= max_age as i64,
+        _ => false,
+    };
+    too_deep || too_old
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::config::QueueStorageKind;
+    use mcp_common::events::{EventKind, GatewayEvent, QueueEvent};
+    use mcp_common::{Config, MCPRequest};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_triggers_fire_on_transitions_and_thresholds() {
+        let offline = Connectivity::Offline {
+            reason: "no route".to_string(),
+        };
+        assert!(connectivity_restored(&offline, &Connectivity::Online));
+        assert!(!connectivity_restored(
+            &Connectivity::Unknown,
+            &Connectivity::Online
+        ));
+        assert!(!connectivity_restored(
+            &Connectivity::Online,
+            &Connectivity::Online
+        ));
+        assert!(!connectivity_restored(&Connectivity::Online, &offline));
+
+        let now = Utc::now();
+        let minute_ago = Some(now - chrono::Duration::seconds(60));
+        assert!(threshold_reached(5, minute_ago, Some(5), None, now));
+        assert!(!threshold_reached(4, minute_ago, Some(5), None, now));
+        assert!(threshold_reached(1, minute_ago, None, Some(30), now));
+        assert!(!threshold_reached(1, minute_ago, Some(5), Some(120), now));
+        assert!(!threshold_reached(0, None, None, Some(30), now));
+    }
+
+    #[tokio::test]
+    async fn test_threshold_policy_starts_a_tagged_sync() {
+        let mut config = Config::default();
+        config.queue.storage_backend = QueueStorageKind::Memory;
+        config.queue.sync_policies = vec![SyncPolicy::Threshold {
+            max_depth: Some(2),
+            max_age_seconds: None,
+            check_interval_seconds: 1,
+        }];
+        let queue = PersistentQueue::new(Arc::new(config)).await.unwrap();
+        let mut events = mcp_common::events::subscribe(&[EventKind::Queue]).unwrap();
+
+        for _ in 0..2 {
+            let request = MCPRequest {
+                id: Uuid::new_v4(),
+                device_id: "pi-4".to_string(),
+                method: "completion".to_string(),
+                params: std::collections::HashMap::new(),
+                context: None,
+                timestamp: Utc::now(),
+            };
+            crate::OfflineQueue::enqueue_request(&queue, request)
+                .await
+                .unwrap();
+        }
+
+        // Only this queue syncs on a threshold; no cloud endpoint is
+        // configured, so both requests fail to sync
+        let finished = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                if let GatewayEvent::Queue {
+                    event:
+                        event @ QueueEvent::SyncFinished {
+                            trigger: SyncTrigger::Threshold,
+                            ..
+                        },
+                } = events.recv().await.unwrap().event
+                {
+                    return event;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            finished,
+            QueueEvent::SyncFinished {
+                synced: 0,
+                failed: 2,
+                ..
+            }
+        ));
+        crate::OfflineQueue::shutdown(&queue).await.unwrap();
+    }
+}
@@ -401,18 +401,15 @@ impl AdvancedLoadBalancer {
 
     /// Start request tracking
     pub async fn start_request(&self, endpoint_url: &str) -> Result<()> {
-        let metrics = self.endpoint_metrics.read().await;
-        if let Some(endpoint_metrics) = metrics.get(endpoint_url) {
+        let mut metrics = self.endpoint_metrics.write().await;
+        if let Some(endpoint_metrics) = metrics.get_mut(endpoint_url) {
             let active = endpoint_metrics.active_connections.fetch_add(1, Ordering::Relaxed);
             
             // Update max observed concurrent
-            let mut metrics_write = self.endpoint_metrics.write().await;
-            if let Some(metrics_mut) = metrics_write.get_mut(endpoint_url) {
-                if active > metrics_mut.max_observed_concurrent {
-                    metrics_mut.max_observed_concurrent = active;
-                    // Update capacity estimate based on observed maximum
-                    metrics_mut.estimated_capacity = (active as f32 * 1.2).max(metrics_mut.estimated_capacity);
-                }
+            if active > endpoint_metrics.max_observed_concurrent {
+                endpoint_metrics.max_observed_concurrent = active;
+                // Update capacity estimate based on observed maximum
+                endpoint_metrics.estimated_capacity = (active as f32 * 1.2).max(endpoint_metrics.estimated_capacity);
             }
         }
         Ok(())
@@ -605,32 +602,37 @@ pub struct EndpointStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mcp_common::config::{CloudEndpoint, RouterConfig, LoadBalancingConfig};
+    use mcp_common::config::CloudEndpoint;
 
     fn create_test_config() -> Arc<Config> {
         let mut config = Config::default();
-        config.router = RouterConfig {
-            cloud_endpoints: vec![
-                CloudEndpoint {
-                    url: "http://endpoint1.test".to_string(),
-                    api_key: None,
-                    timeout_ms: 5000,
-                    max_retries: 3,
-                },
-                CloudEndpoint {
-                    url: "http://endpoint2.test".to_string(),
-                    api_key: None,
-                    timeout_ms: 5000,
-                    max_retries: 3,
-                },
-            ],
-            load_balancing: LoadBalancingConfig {
-                algorithm: LoadBalancingAlgorithm::LeastConnections,
-                health_check_interval_seconds: 30,
+        config.router.cloud_endpoints = vec![
+            CloudEndpoint {
+                name: "endpoint1".to_string(),
+                url: "http://endpoint1.test".to_string(),
+                api_key: None,
+                timeout_ms: 5000,
+                max_retries: 3,
+                connect_timeout_ms: None,
+                region: None,
+                provider: Default::default(),
+                compression: Default::default(),
             },
-            local_processing_threshold: 0.8,
-            cloud_fallback_enabled: true,
-        };
+            CloudEndpoint {
+                name: "endpoint2".to_string(),
+                url: "http://endpoint2.test".to_string(),
+                api_key: None,
+                timeout_ms: 5000,
+                max_retries: 3,
+                connect_timeout_ms: None,
+                region: None,
+                provider: Default::default(),
+                compression: Default::default(),
+            },
+        ];
+        config.router.load_balancing.algorithm = LoadBalancingAlgorithm::LeastConnections;
+        config.router.local_processing_threshold = 0.8;
+        config.router.cloud_fallback_enabled = true;
         Arc::new(config)
     }
 
@@ -1,37 +1,120 @@
 //! Cloud client for forwarding requests to external MCP services
 
-use mcp_common::{Config, Error, MCPRequest, MCPResponse, Result};
-use reqwest::{Client, ClientBuilder};
-use std::sync::Arc;
-use std::time::Duration;
+use crate::happy_eyeballs::{FamilyResolver, FamilyStats};
+use crate::providers::{self, CloudProvider};
+use crate::warm_standby::WarmStandby;
+use crate::CloudTransport;
+use async_trait::async_trait;
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::compression::{self, EncodedBody, PayloadCompression};
+use mcp_common::request_signing;
+use mcp_common::config::{CloudEndpoint, WarmStandbyConfig};
+use mcp_common::trace_context::TRACEPARENT_HEADER;
+use mcp_common::{Config, Error, MCPRequest, MCPResponse, Result, TimeoutDetails, TimeoutStage};
+use reqwest::{Client, ClientBuilder, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 
 /// Client for forwarding requests to cloud MCP services
 pub struct CloudClient {
     client: Client,
+    /// Clients for endpoints with a dedicated connect timeout, keyed by URL
+    endpoint_clients: HashMap<String, Client>,
+    /// Keeps the connection to the primary endpoint open between fallbacks
+    warm_standby: Option<Arc<WarmStandby>>,
+    /// Address family each new cloud connection ended up on
+    families: Arc<FamilyStats>,
+    /// Adapter for each endpoint's provider API, keyed by URL
+    providers: HashMap<String, Box<dyn CloudProvider>>,
+    /// Endpoints that answered a compressed body with 415, keyed by URL
+    uncompressed: RwLock<HashSet<String>>,
     config: Arc<Config>,
 }
 
 impl CloudClient {
     pub async fn new(config: Arc<Config>) -> Result<Self> {
-        let client = ClientBuilder::new()
-            .timeout(Duration::from_millis(30000)) // 30 second timeout
-            .user_agent("MCP-WASM-Edge-Gateway/0.1.0")
-            .build()
-            .map_err(|e| Error::Network(format!("Failed to create HTTP client: {}", e)))?;
+        let warm_config = &config.router.warm_standby;
+        let families = Arc::new(FamilyStats::new(config.network.clone()));
+        let resolver = Arc::new(FamilyResolver::new(Arc::clone(&families)));
+        let client = Self::build_client(
+            config.gateway.timeouts.cloud_connect_timeout_ms.map(Duration::from_millis),
+            warm_config,
+            &resolver,
+        )?;
+
+        let mut endpoint_clients = HashMap::new();
+        let mut providers = HashMap::new();
+        for endpoint in &config.router.cloud_endpoints {
+            providers.insert(endpoint.url.clone(), providers::provider_for(&endpoint.provider));
+            if endpoint.connect_timeout_ms.is_some() {
+                let endpoint_client =
+                    Self::build_client(config.cloud_connect_timeout(endpoint), warm_config, &resolver)?;
+                endpoint_clients.insert(endpoint.url.clone(), endpoint_client);
+            }
+        }
+
+        let warm_standby = match config.router.cloud_endpoints.first() {
+            Some(primary) if warm_config.enabled => {
+                let client = endpoint_clients.get(&primary.url).unwrap_or(&client).clone();
+                let standby = Arc::new(WarmStandby::new(
+                    warm_config.clone(),
+                    client,
+                    primary.clone(),
+                    config.cloud_connect_timeout(primary),
+                ));
+                standby.start();
+                Some(standby)
+            },
+            _ => None,
+        };
 
         Ok(Self {
             client,
+            endpoint_clients,
+            warm_standby,
+            families,
+            providers,
+            uncompressed: RwLock::new(HashSet::new()),
             config,
         })
     }
 
-    pub async fn send_request(
-        &self,
-        endpoint: &str,
-        request: &MCPRequest,
-    ) -> Result<MCPResponse> {
-        self.forward_request(request, endpoint).await
+    fn build_client(
+        connect_timeout: Option<Duration>,
+        warm_standby: &WarmStandbyConfig,
+        resolver: &Arc<FamilyResolver>,
+    ) -> Result<Client> {
+        let mut builder = ClientBuilder::new()
+            .user_agent("MCP-WASM-Edge-Gateway/0.1.0")
+            .dns_resolver(Arc::clone(resolver));
+        if let Some(connect_timeout) = connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if warm_standby.enabled {
+            // Keep the refreshed connection pooled, and HTTP/2 sessions alive, between refreshes
+            let keep_alive = Duration::from_secs(warm_standby.refresh_interval_secs.max(1));
+            builder = builder
+                .pool_idle_timeout(WarmStandby::pool_idle_timeout(warm_standby))
+                .tcp_keepalive(keep_alive)
+                .http2_keep_alive_interval(keep_alive)
+                .http2_keep_alive_while_idle(true);
+        }
+        builder
+            .build()
+            .map_err(|e| Error::Network(format!("Failed to create HTTP client: {}", e)))
+    }
+
+    fn client_for(&self, endpoint_url: &str) -> &Client {
+        self.endpoint_clients.get(endpoint_url).unwrap_or(&self.client)
+    }
+
+    fn provider_for(&self, endpoint_url: &str) -> Result<&dyn CloudProvider> {
+        self.providers
+            .get(endpoint_url)
+            .map(|provider| provider.as_ref())
+            .ok_or_else(|| Error::Routing(format!("Unknown endpoint: {}", endpoint_url)))
     }
 
     pub async fn forward_request(
@@ -53,44 +136,146 @@ impl CloudClient {
             .find(|e| e.url == endpoint)
             .ok_or_else(|| Error::Routing(format!("Unknown endpoint: {}", endpoint)))?;
 
-        // Prepare the request
-        let mut req_builder = self
-            .client
-            .post(&endpoint_config.url)
-            .json(request)
-            .timeout(Duration::from_millis(endpoint_config.timeout_ms));
-
-        // Add API key if configured
-        if let Some(api_key) = &endpoint_config.api_key {
-            req_builder = req_builder.header("Authorization", format!("Bearer {}", api_key));
-        }
+        // Prepare the request in the endpoint's provider format
+        let provider = self.provider_for(&endpoint_config.url)?;
+        let read_budget = self.config.cloud_read_budget(&request.method, endpoint_config);
+        let compression = self.compression_for(endpoint_config);
+        let encoded = compression::encode(compression, provider.encode_request(request)?);
+        let compressed = encoded.content_encoding.is_some();
 
-        // Send the request
-        let response = req_builder
-            .send()
-            .await
-            .map_err(|e| Error::Network(format!("Request failed: {}", e)))?;
+        // Send the request, without compression if the endpoint cannot read it
+        let started = Instant::now();
+        let (response, sent) = match self.send(request, endpoint_config, encoded, read_budget).await? {
+            (response, sent) if compressed && response.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE => {
+                warn!(
+                    "Endpoint {} does not accept {:?} bodies, sending them uncompressed",
+                    endpoint_config.name, compression
+                );
+                bandwidth::record(Subsystem::CloudForward, sent, 0);
+                self.write_uncompressed().insert(endpoint_config.url.clone());
+                let plain = compression::encode(PayloadCompression::None, provider.encode_request(request)?);
+                self.send(request, endpoint_config, plain, read_budget).await?
+            },
+            sent => sent,
+        };
+        if let (Some(host), Some(remote)) = (response.url().host_str(), response.remote_addr()) {
+            self.families.record_response(host, remote);
+        }
+        if let Some(standby) = self.warm_standby.as_ref().filter(|standby| standby.url() == endpoint_config.url) {
+            standby.record_request(started.elapsed());
+        }
 
         // Check response status
         if !response.status().is_success() {
             let status = response.status();
             let error_body = response.text().await.unwrap_or_default();
-            return Err(Error::Network(format!(
-                "Cloud request failed with status {}: {}",
-                status, error_body
-            )));
+            bandwidth::record(Subsystem::CloudForward, sent, error_body.len() as u64);
+            return Err(provider.translate_error(status.as_u16(), &error_body));
         }
 
         // Parse response
-        let mcp_response: MCPResponse = response
-            .json()
-            .await
-            .map_err(|e| Error::Network(format!("Failed to parse response: {}", e)))?;
+        let content_encoding = response
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let response_body = response.bytes().await.map_err(|e| {
+            bandwidth::record(Subsystem::CloudForward, sent, 0);
+            if e.is_timeout() {
+                Error::DeadlineExceeded(
+                    TimeoutDetails::new(TimeoutStage::CloudRead, &request.method, read_budget)
+                        .with_target(&endpoint_config.name),
+                )
+            } else {
+                Error::Network(format!("Failed to read response: {}", e))
+            }
+        })?;
+        bandwidth::record(Subsystem::CloudForward, sent, response_body.len() as u64);
+        let response_body = compression::decode(content_encoding.as_deref(), &response_body)?;
+        let mcp_response = provider.decode_response(request, &response_body)?;
 
         debug!("Cloud request {} completed successfully", request.id);
         Ok(mcp_response)
     }
 
+    /// Build and send a forward; returns the response with the body size sent
+    async fn send(
+        &self,
+        request: &MCPRequest,
+        endpoint_config: &CloudEndpoint,
+        encoded: EncodedBody,
+        read_budget: Duration,
+    ) -> Result<(reqwest::Response, u64)> {
+        let provider = self.provider_for(&endpoint_config.url)?;
+        let sent = encoded.body.len() as u64;
+        let signature = request_signing::signature_headers(&encoded.body).await;
+        let mut req_builder = self
+            .client_for(&endpoint_config.url)
+            .post(provider.request_url(endpoint_config))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(encoded.body)
+            .timeout(read_budget);
+
+        for (name, value) in provider.auth_headers(endpoint_config).into_iter().chain(signature) {
+            req_builder = req_builder.header(name, value);
+        }
+        if let Some(trace) = request.trace() {
+            req_builder = req_builder.header(TRACEPARENT_HEADER, trace.traceparent());
+        }
+        if let Some(content_encoding) = encoded.content_encoding {
+            req_builder = req_builder.header(reqwest::header::CONTENT_ENCODING, content_encoding);
+        }
+        if let Some(accept_encoding) = self.compression_for(endpoint_config).content_encoding() {
+            req_builder = req_builder.header(reqwest::header::ACCEPT_ENCODING, accept_encoding);
+        }
+
+        let response = req_builder.send().await.map_err(|e| {
+            if e.is_connect() {
+                if let Some(host) = e.url().and_then(|url| url.host_str()) {
+                    self.families.record_connect_failure(host);
+                }
+            } else {
+                // The request went out; only the response is missing
+                bandwidth::record(Subsystem::CloudForward, sent, 0);
+            }
+            if e.is_timeout() {
+                let (stage, budget) = if e.is_connect() {
+                    let connect = self
+                        .config
+                        .cloud_connect_timeout(endpoint_config)
+                        .unwrap_or(read_budget);
+                    (TimeoutStage::CloudConnect, connect)
+                } else {
+                    (TimeoutStage::CloudRead, read_budget)
+                };
+                Error::DeadlineExceeded(
+                    TimeoutDetails::new(stage, &request.method, budget).with_target(&endpoint_config.name),
+                )
+            } else {
+                Error::Network(format!("Request failed: {}", e))
+            }
+        })?;
+        Ok((response, sent))
+    }
+
+    /// Codec for bodies sent to an endpoint, none once it has rejected them
+    fn compression_for(&self, endpoint_config: &CloudEndpoint) -> PayloadCompression {
+        let rejected = self
+            .uncompressed
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&endpoint_config.url);
+        if rejected {
+            PayloadCompression::None
+        } else {
+            endpoint_config.compression
+        }
+    }
+
+    fn write_uncompressed(&self) -> std::sync::RwLockWriteGuard<'_, HashSet<String>> {
+        self.uncompressed.write().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Test connectivity to a cloud endpoint
     pub async fn test_endpoint(&self, endpoint: &str) -> bool {
         let endpoint_config = match self
@@ -103,14 +288,17 @@ impl CloudClient {
             Some(config) => config,
             None => return false,
         };
+        let Ok(provider) = self.provider_for(&endpoint_config.url) else {
+            return false;
+        };
 
         let mut req_builder = self
-            .client
-            .get(&format!("{}/health", endpoint_config.url))
+            .client_for(&endpoint_config.url)
+            .get(provider.health_url(endpoint_config))
             .timeout(Duration::from_millis(5000));
 
-        if let Some(api_key) = &endpoint_config.api_key {
-            req_builder = req_builder.header("Authorization", format!("Bearer {}", api_key));
+        for (name, value) in provider.auth_headers(endpoint_config) {
+            req_builder = req_builder.header(name, value);
         }
 
         match req_builder.send().await {
@@ -124,7 +312,27 @@ impl CloudClient {
 
     pub async fn shutdown(&self) -> Result<()> {
         debug!("Shutting down cloud client");
-        // HTTP client will be dropped automatically
+        if let Some(standby) = &self.warm_standby {
+            standby.stop();
+        }
         Ok(())
     }
 }
+
+#[async_trait]
+impl CloudTransport for CloudClient {
+    async fn send_request(&self, endpoint: &str, request: &MCPRequest) -> Result<MCPResponse> {
+        self.forward_request(request, endpoint).await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        CloudClient::shutdown(self).await
+    }
+
+    fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        if let Some(standby) = &self.warm_standby {
+            standby.write_metrics(metrics);
+        }
+        self.families.write_metrics(metrics);
+    }
+}
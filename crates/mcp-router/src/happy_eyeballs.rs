@@ -0,0 +1,260 @@
+//! Address family selection for outbound cloud connections (RFC 8305)
+//!
+//! The HTTP connector already races address families: it connects to the
+//! family resolved first and, when that has not succeeded within 300ms,
+//! starts on the other family in parallel. This resolver decides which family
+//! goes first. IPv6 leads, as RFC 8305 recommends, until IPv4 has won
+//! `ipv6_failure_threshold` races in a row, which is typical of cellular
+//! networks that hand out IPv6 addresses without a working IPv6 route. IPv4
+//! then leads for `ipv6_retry_secs`, so new connections stop paying the
+//! fallback delay, before IPv6 gets another chance.
+
+use mcp_common::config::NetworkConfig;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Address family of a cloud connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn of(address: &SocketAddr) -> Self {
+        if address.is_ipv6() {
+            Family::V6
+        } else {
+            Family::V4
+        }
+    }
+}
+
+#[derive(Default)]
+struct FamilyCounters {
+    /// Lookups that returned addresses of this family
+    resolved: u64,
+    /// New connections established over this family
+    connections: u64,
+    /// Connections this family failed to win although it was tried first
+    lost_races: u64,
+}
+
+struct FamilyState {
+    v4: FamilyCounters,
+    v6: FamilyCounters,
+    /// Family tried first by the latest lookup per host, while both were offered
+    pending: HashMap<String, Option<Family>>,
+    consecutive_ipv6_losses: u32,
+    ipv4_first_until: Option<Instant>,
+    connect_failures: u64,
+}
+
+impl FamilyState {
+    fn counters(&mut self, family: Family) -> &mut FamilyCounters {
+        match family {
+            Family::V4 => &mut self.v4,
+            Family::V6 => &mut self.v6,
+        }
+    }
+}
+
+/// Per-family connectivity of outbound cloud connections
+pub(crate) struct FamilyStats {
+    network: NetworkConfig,
+    state: Mutex<FamilyState>,
+}
+
+impl FamilyStats {
+    pub(crate) fn new(network: NetworkConfig) -> Self {
+        Self {
+            network,
+            state: Mutex::new(FamilyState {
+                v4: FamilyCounters::default(),
+                v6: FamilyCounters::default(),
+                pending: HashMap::new(),
+                consecutive_ipv6_losses: 0,
+                ipv4_first_until: None,
+                connect_failures: 0,
+            }),
+        }
+    }
+
+    /// Family connections should try first right now
+    fn leading_family(&self, state: &mut FamilyState) -> Family {
+        if !self.network.prefer_ipv6 {
+            return Family::V4;
+        }
+        match state.ipv4_first_until {
+            Some(until) if Instant::now() < until => Family::V4,
+            Some(_) => {
+                info!("Trying IPv6 first again for cloud connections");
+                state.ipv4_first_until = None;
+                state.consecutive_ipv6_losses = 0;
+                Family::V6
+            },
+            None => Family::V6,
+        }
+    }
+
+    /// Order resolved addresses for `host`, leading family first and the
+    /// families interleaved after that
+    fn order(&self, host: &str, addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let mut state = lock(&self.state);
+        let lead = self.leading_family(&mut state);
+        let (leading, trailing): (Vec<_>, Vec<_>) =
+            addresses.into_iter().partition(|address| Family::of(address) == lead);
+
+        let raced = !leading.is_empty() && !trailing.is_empty();
+        if !leading.is_empty() {
+            state.counters(lead).resolved += 1;
+        }
+        if let Some(other) = trailing.first() {
+            state.counters(Family::of(other)).resolved += 1;
+        }
+        state.pending.insert(host.to_string(), raced.then_some(lead));
+
+        let mut ordered = Vec::with_capacity(leading.len() + trailing.len());
+        let mut leading = leading.into_iter();
+        let mut trailing = trailing.into_iter();
+        loop {
+            match (leading.next(), trailing.next()) {
+                (None, None) => break,
+                (first, second) => ordered.extend(first.into_iter().chain(second)),
+            }
+        }
+        ordered
+    }
+
+    /// Record the peer a response from `host` arrived from
+    pub(crate) fn record_response(&self, host: &str, remote: SocketAddr) {
+        let mut state = lock(&self.state);
+        // Only the first response after a lookup comes from a new connection
+        let Some(lead) = state.pending.remove(host) else {
+            return;
+        };
+        let family = Family::of(&remote);
+        state.counters(family).connections += 1;
+
+        let Some(lead) = lead else {
+            return;
+        };
+        if family == lead {
+            if lead == Family::V6 {
+                state.consecutive_ipv6_losses = 0;
+            }
+            return;
+        }
+        state.counters(lead).lost_races += 1;
+        if lead == Family::V6 {
+            state.consecutive_ipv6_losses += 1;
+            if state.consecutive_ipv6_losses >= self.network.ipv6_failure_threshold.max(1) {
+                warn!(
+                    "IPv4 won {} cloud connections in a row, trying IPv4 first for {}s",
+                    state.consecutive_ipv6_losses, self.network.ipv6_retry_secs
+                );
+                state.ipv4_first_until = Some(Instant::now() + Duration::from_secs(self.network.ipv6_retry_secs));
+            }
+        }
+    }
+
+    /// Record a request to `host` that failed to connect on every address
+    pub(crate) fn record_connect_failure(&self, host: &str) {
+        let mut state = lock(&self.state);
+        state.pending.remove(host);
+        state.connect_failures += 1;
+    }
+
+    /// Render per-family connectivity into router health metrics
+    pub(crate) fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let mut state = lock(&self.state);
+        let ipv4_first = self.leading_family(&mut state) == Family::V4;
+        for (name, counters) in [("ipv4", &state.v4), ("ipv6", &state.v6)] {
+            metrics.insert(format!("cloud_{}_resolved_total", name), counters.resolved as f32);
+            metrics.insert(format!("cloud_{}_connections_total", name), counters.connections as f32);
+            metrics.insert(format!("cloud_{}_lost_races_total", name), counters.lost_races as f32);
+        }
+        metrics.insert("cloud_ipv4_first".to_string(), if ipv4_first { 1.0 } else { 0.0 });
+        metrics.insert("cloud_connect_failures_total".to_string(), state.connect_failures as f32);
+    }
+}
+
+/// DNS resolver ordering cloud endpoint addresses by family
+pub(crate) struct FamilyResolver {
+    stats: Arc<FamilyStats>,
+}
+
+impl FamilyResolver {
+    pub(crate) fn new(stats: Arc<FamilyStats>) -> Self {
+        Self { stats }
+    }
+}
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let stats = Arc::clone(&self.stats);
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let addresses: Addrs = Box::new(stats.order(&host, addresses).into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses() -> Vec<SocketAddr> {
+        vec![
+            "192.0.2.1:0".parse().unwrap(),
+            "192.0.2.2:0".parse().unwrap(),
+            "[2001:db8::1]:0".parse().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_ipv6_leads_and_families_interleave() {
+        let stats = FamilyStats::new(NetworkConfig::default());
+        let ordered = stats.order("cloud.example", addresses());
+        let families: Vec<Family> = ordered.iter().map(Family::of).collect();
+        assert_eq!(families, vec![Family::V6, Family::V4, Family::V4]);
+
+        let ipv4_only = FamilyStats::new(NetworkConfig {
+            prefer_ipv6: false,
+            ..NetworkConfig::default()
+        });
+        assert!(ipv4_only.order("cloud.example", addresses())[0].is_ipv4());
+    }
+
+    #[test]
+    fn test_ipv4_leads_after_repeated_ipv6_losses() {
+        let stats = FamilyStats::new(NetworkConfig {
+            ipv6_failure_threshold: 2,
+            ..NetworkConfig::default()
+        });
+        let remote: SocketAddr = "192.0.2.1:443".parse().unwrap();
+        for _ in 0..2 {
+            assert!(stats.order("cloud.example", addresses())[0].is_ipv6());
+            stats.record_response("cloud.example", remote);
+            // Responses on the pooled connection are not new races
+            stats.record_response("cloud.example", remote);
+        }
+        assert!(stats.order("cloud.example", addresses())[0].is_ipv4());
+
+        let mut metrics = HashMap::new();
+        stats.write_metrics(&mut metrics);
+        assert_eq!(metrics["cloud_ipv6_lost_races_total"], 2.0);
+        assert_eq!(metrics["cloud_ipv4_connections_total"], 2.0);
+        assert_eq!(metrics["cloud_ipv4_first"], 1.0);
+    }
+}
@@ -1,25 +1,34 @@
 //! Intelligent routing implementation for MCP requests
 
-use crate::{cloud_client::CloudClient, load_balancer::LoadBalancer, Router};
+use crate::{cloud_client::CloudClient, load_balancer::LoadBalancer, model_aliases::ModelAliasResolver, providers, CloudTransport, Router};
+use crate::routing_policy::RoutingPolicy;
+use mcp_common::config::RoutingTarget;
 use async_trait::async_trait;
 use mcp_common::metrics::{ComponentHealth, HealthLevel};
 use mcp_common::{
-    Config, Error, MCPRequest, MCPResponse, RequestContext, Result, RoutingDecision, Priority,
+    Config, ConcurrencyLimiter, Error, MCPRequest, MCPResponse, ModelId, Result, RoutingDecision,
+    Priority, Span, SpanKind,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
+/// Retry delay of requests a routing rule queues
+const POLICY_QUEUE_RETRY_MS: u64 = 5000;
+
 /// Intelligent router that makes routing decisions based on request complexity,
 /// system resources, and historical performance
 pub struct IntelligentRouter {
     config: Arc<Config>,
-    cloud_client: Arc<CloudClient>,
+    cloud_client: Arc<dyn CloudTransport + Send + Sync>,
     load_balancer: Arc<LoadBalancer>,
     performance_metrics: Arc<RwLock<PerformanceMetrics>>,
     routing_state: Arc<RwLock<RoutingState>>,
     model_selector: Arc<ModelSelector>,
+    cloud_limiter: Arc<ConcurrencyLimiter>,
+    aliases: Arc<ModelAliasResolver>,
+    policy: std::sync::RwLock<Arc<RoutingPolicy>>,
 }
 
 /// Model selection logic for intelligent routing
@@ -251,8 +260,23 @@ struct RoutingState {
 impl IntelligentRouter {
     pub async fn new(config: Arc<Config>) -> Result<Self> {
         let cloud_client = Arc::new(CloudClient::new(config.clone()).await?);
+        Self::with_transport(config, cloud_client).await
+    }
+
+    /// Router that forwards cloud requests through a custom transport
+    pub async fn with_transport(
+        config: Arc<Config>,
+        cloud_client: Arc<dyn CloudTransport + Send + Sync>,
+    ) -> Result<Self> {
         let load_balancer = Arc::new(LoadBalancer::new(config.clone())?);
         let model_selector = Arc::new(ModelSelector::new());
+        let cloud_limiter = Arc::new(ConcurrencyLimiter::new(
+            "cloud_forward",
+            &config.concurrency.cloud_forward,
+        ));
+        let aliases = Arc::new(ModelAliasResolver::new(&config));
+        let policy = std::sync::RwLock::new(Arc::new(RoutingPolicy::new(&config.router.policy)));
+        aliases.start(config.models.aliases.reload_interval_secs).await;
 
         Ok(Self {
             config,
@@ -261,9 +285,34 @@ impl IntelligentRouter {
             performance_metrics: Arc::new(RwLock::new(PerformanceMetrics::default())),
             routing_state: Arc::new(RwLock::new(RoutingState::default())),
             model_selector,
+            cloud_limiter,
+            aliases,
+            policy,
         })
     }
 
+    /// Model alias resolver used for client-requested models
+    pub fn aliases(&self) -> Arc<ModelAliasResolver> {
+        Arc::clone(&self.aliases)
+    }
+
+    /// Routing rules in effect now
+    fn policy(&self) -> Arc<RoutingPolicy> {
+        Arc::clone(&self.policy.read().unwrap_or_else(|p| p.into_inner()))
+    }
+
+    /// Use the client-requested model when given, otherwise let the selector pick
+    async fn choose_model(&self, request: &MCPRequest, complexity: f32, requested: &Option<ModelId>) -> ModelId {
+        match requested {
+            Some(model_id) => model_id.clone(),
+            None => {
+                self.model_selector
+                    .select_model(request, complexity, self.config.models.cache_size_mb)
+                    .await
+            },
+        }
+    }
+
     /// Analyze request complexity to determine processing requirements
     async fn analyze_request_complexity(&self, request: &MCPRequest) -> f32 {
         let mut complexity = 0.0;
@@ -381,15 +430,14 @@ impl IntelligentRouter {
     ) -> Result<RoutingDecision> {
         let state = self.routing_state.read().await;
 
+        // Resolve a client-requested model name (or alias) to a concrete model
+        let requested_model = self.aliases.resolve_requested(request).await?;
+
         // Check for explicit requirements
         if let Some(context) = &request.context {
             if context.requirements.require_local {
                 if local_capability > 0.3 {
-                    let model_id = self.model_selector.select_model(
-                        request, 
-                        complexity, 
-                        self.config.models.cache_size_mb
-                    ).await;
+                    let model_id = self.choose_model(request, complexity, &requested_model).await;
                     return Ok(RoutingDecision::Local {
                         model_id,
                         estimated_latency_ms: 200,
@@ -405,11 +453,7 @@ impl IntelligentRouter {
             if !context.requirements.allow_fallback {
                 // No fallback allowed, must decide between local and queue
                 if local_capability > 0.5 {
-                    let model_id = self.model_selector.select_model(
-                        request, 
-                        complexity, 
-                        self.config.models.cache_size_mb
-                    ).await;
+                    let model_id = self.choose_model(request, complexity, &requested_model).await;
                     return Ok(RoutingDecision::Local {
                         model_id,
                         estimated_latency_ms: 300,
@@ -426,11 +470,7 @@ impl IntelligentRouter {
             if let Some(max_latency) = context.requirements.max_latency_ms {
                 if max_latency < 500 && local_capability > 0.4 {
                     // Low latency requirement favors local
-                    let model_id = self.model_selector.select_model(
-                        request, 
-                        complexity, 
-                        self.config.models.cache_size_mb
-                    ).await;
+                    let model_id = self.choose_model(request, complexity, &requested_model).await;
                     return Ok(RoutingDecision::Local {
                         model_id,
                         estimated_latency_ms: 150,
@@ -443,20 +483,16 @@ impl IntelligentRouter {
         let threshold = self.config.router.local_processing_threshold;
 
         if local_capability >= threshold && local_capability > cloud_benefit {
-            let model_id = self.model_selector.select_model(
-                request, 
-                complexity, 
-                self.config.models.cache_size_mb
-            ).await;
+            let model_id = self.choose_model(request, complexity, &requested_model).await;
             Ok(RoutingDecision::Local {
                 model_id,
                 estimated_latency_ms: (200.0 * (1.0 + complexity)).round() as u64,
             })
         } else if self.config.router.cloud_fallback_enabled && cloud_benefit > 0.5 {
             // Select best cloud endpoint
-            let endpoint = self.load_balancer.select_endpoint().await?;
+            let endpoint = self.cloud_endpoint(request).await?;
             Ok(RoutingDecision::Cloud {
-                endpoint: endpoint.url.clone(),
+                endpoint,
                 estimated_latency_ms: (300.0 * (1.0 + complexity * 0.5)).round() as u64,
             })
         } else {
@@ -471,6 +507,59 @@ impl IntelligentRouter {
         }
     }
 
+    /// Decision of the first routing rule matching the request; in dry-run
+    /// mode the rule is only logged
+    async fn policy_decision(&self, request: &MCPRequest, complexity: f32) -> Result<Option<(String, RoutingDecision)>> {
+        let policy = self.policy();
+        let Some(rule) = policy.evaluate(request) else {
+            return Ok(None);
+        };
+        if policy.dry_run() {
+            info!(
+                "Routing policy rule '{}' would route request {} to {:?} (dry run)",
+                rule.name, request.id, rule.target
+            );
+            return Ok(None);
+        }
+        let decision = match rule.target {
+            RoutingTarget::Local => {
+                let model_id = match &rule.model {
+                    Some(model_id) => model_id.clone(),
+                    None => {
+                        let requested_model = self.aliases.resolve_requested(request).await?;
+                        self.choose_model(request, complexity, &requested_model).await
+                    },
+                };
+                RoutingDecision::Local {
+                    model_id,
+                    estimated_latency_ms: (200.0 * (1.0 + complexity)).round() as u64,
+                }
+            },
+            RoutingTarget::Cloud => {
+                let endpoint = match &rule.endpoint {
+                    Some(name) => self
+                        .config
+                        .router
+                        .cloud_endpoints
+                        .iter()
+                        .find(|endpoint| &endpoint.name == name)
+                        .map(|endpoint| endpoint.url.clone())
+                        .ok_or_else(|| Error::Routing(format!("Unknown cloud endpoint '{}'", name)))?,
+                    None => self.cloud_endpoint(request).await?,
+                };
+                RoutingDecision::Cloud {
+                    endpoint,
+                    estimated_latency_ms: (300.0 * (1.0 + complexity * 0.5)).round() as u64,
+                }
+            },
+            RoutingTarget::Queue => RoutingDecision::Queue {
+                reason: format!("Routing policy rule '{}'", rule.name),
+                retry_after_ms: POLICY_QUEUE_RETRY_MS,
+            },
+        };
+        Ok(Some((rule.name.clone(), decision)))
+    }
+
     /// Update system state for routing decisions based on real system metrics
     pub async fn update_system_state(
         &self,
@@ -560,27 +649,118 @@ impl IntelligentRouter {
             }
         }
     }
+
+    /// URLs of the route for the request's method, if one matches, healthy
+    /// endpoints first
+    async fn route_endpoints(&self, request: &MCPRequest) -> Option<Vec<String>> {
+        let route = self.config.router.routes.iter().find(|route| route.matches(&request.method))?;
+        let endpoints = self.load_balancer.route_order(&route.endpoints).await;
+        Some(endpoints.into_iter().map(|endpoint| endpoint.url.clone()).collect())
+    }
+
+    /// First endpoint of the request's route, otherwise the load balancer's pick
+    async fn cloud_endpoint(&self, request: &MCPRequest) -> Result<String> {
+        match self.route_endpoints(request).await {
+            Some(endpoints) => endpoints
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::Routing(format!("No endpoints on the route for '{}'", request.method))),
+            None => Ok(self.load_balancer.select_endpoint().await?.url.clone()),
+        }
+    }
+
+    /// Try each endpoint in turn until one answers or fails in a way the
+    /// others would too
+    async fn send_with_failover(&self, request: &MCPRequest, endpoints: &[String], fallback: bool) -> Result<MCPResponse> {
+        let mut last_error = None;
+        for (attempt, endpoint) in endpoints.iter().enumerate() {
+            match self.send_to_cloud(request, endpoint, fallback || attempt > 0).await {
+                Ok(response) => return Ok(response),
+                Err(e) if providers::fails_over(&e) && attempt + 1 < endpoints.len() => {
+                    warn!("Cloud endpoint {} failed for request {}, failing over: {}", endpoint, request.id, e);
+                    last_error = Some(e);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::Routing("No endpoints configured".to_string())))
+    }
+
+    /// Send a request to a cloud endpoint in its own client span; the
+    /// endpoint receives that span as the request's parent
+    async fn send_to_cloud(&self, request: &MCPRequest, endpoint: &str, fallback: bool) -> Result<MCPResponse> {
+        debug!("Forwarding request {} to cloud endpoint: {}", request.id, endpoint);
+
+        let mut span = Span::for_request(request, "router.cloud_forward", SpanKind::Client);
+        let traced;
+        let request = match &mut span {
+            Some(span) => {
+                span.set_attribute("router.endpoint", endpoint);
+                span.set_attribute("router.fallback", fallback);
+                let mut copy = request.clone();
+                copy.set_trace(span.context());
+                traced = copy;
+                &traced
+            },
+            None => request,
+        };
+
+        let _permit = self.cloud_limiter.acquire_for(request.priority()).await?;
+        let start_time = std::time::Instant::now();
+        let result = self.cloud_client.send_request(endpoint, request).await;
+        let latency = start_time.elapsed().as_millis() as u64;
+        self.load_balancer
+            .update_endpoint_health(endpoint, result.is_ok(), latency as f32)
+            .await;
+
+        // Record the outcome for learning
+        self.record_request_outcome(false, latency, result.is_ok()).await;
+
+        if let Some(span) = span.as_mut() {
+            span.record_result(&result);
+        }
+        result
+    }
 }
 
 #[async_trait]
 impl Router for IntelligentRouter {
     async fn route(&self, request: &MCPRequest) -> Result<RoutingDecision> {
         debug!("Routing request {} (method: {})", request.id, request.method);
+        let mut span = Span::for_request(request, "router.route", SpanKind::Internal);
 
         // Analyze request complexity
         let complexity = self.analyze_request_complexity(request).await;
         debug!("Request complexity: {:.2}", complexity);
 
-        // Estimate local processing capability
-        let local_capability = self.estimate_local_capability(complexity).await;
-        debug!("Local capability: {:.2}", local_capability);
+        // User-defined rules take precedence over the heuristics
+        let decision = match self.policy_decision(request, complexity).await {
+            Ok(Some((rule, decision))) => {
+                debug!("Routing policy rule '{}' matched request {}", rule, request.id);
+                if let Some(span) = span.as_mut() {
+                    span.set_attribute("router.policy_rule", rule);
+                }
+                Ok(decision)
+            },
+            Ok(None) => {
+                // Estimate local processing capability
+                let local_capability = self.estimate_local_capability(complexity).await;
+                debug!("Local capability: {:.2}", local_capability);
 
-        // Estimate cloud processing benefit
-        let cloud_benefit = self.estimate_cloud_benefit(complexity).await;
-        debug!("Cloud benefit: {:.2}", cloud_benefit);
+                // Estimate cloud processing benefit
+                let cloud_benefit = self.estimate_cloud_benefit(complexity).await;
+                debug!("Cloud benefit: {:.2}", cloud_benefit);
 
-        // Make routing decision
-        let decision = self.make_routing_decision(request, complexity, local_capability, cloud_benefit).await?;
+                // Make routing decision
+                self.make_routing_decision(request, complexity, local_capability, cloud_benefit).await
+            },
+            Err(e) => Err(e),
+        };
+        if let Some(span) = span.as_mut() {
+            span.set_attribute("router.complexity", format!("{:.2}", complexity));
+            span.record_result(&decision);
+        }
+        let decision = decision?;
         
         match &decision {
             RoutingDecision::Local { model_id, estimated_latency_ms } => {
@@ -596,21 +776,43 @@ impl Router for IntelligentRouter {
                       request.id, reason, retry_after_ms);
             },
         }
+        if let Some(span) = span.as_mut() {
+            let (destination, target) = match &decision {
+                RoutingDecision::Local { model_id, .. } => ("local", model_id.as_str()),
+                RoutingDecision::Cloud { endpoint, .. } => ("cloud", endpoint.as_str()),
+                RoutingDecision::Queue { reason, .. } => ("queue", reason.as_str()),
+            };
+            span.set_attribute("router.decision", destination);
+            span.set_attribute("router.target", target);
+        }
 
         Ok(decision)
     }
 
     async fn forward_to_cloud(&self, request: &MCPRequest, endpoint: &str) -> Result<MCPResponse> {
-        debug!("Forwarding request {} to cloud endpoint: {}", request.id, endpoint);
-        
-        let start_time = std::time::Instant::now();
-        let result = self.cloud_client.send_request(endpoint, request).await;
-        let latency = start_time.elapsed().as_millis() as u64;
+        // The rest of the endpoint's route backs it up
+        let mut endpoints = vec![endpoint.to_string()];
+        if let Some(route) = self.route_endpoints(request).await.filter(|route| route.iter().any(|url| url == endpoint)) {
+            endpoints.extend(route.into_iter().filter(|url| url != endpoint));
+        }
+        self.send_with_failover(request, &endpoints, false).await
+    }
 
-        // Record the outcome for learning
-        self.record_request_outcome(false, latency, result.is_ok()).await;
+    async fn fallback_to_cloud(&self, request: &MCPRequest) -> Result<MCPResponse> {
+        if !self.config.router.cloud_fallback_enabled {
+            return Err(Error::Routing("Cloud fallback is disabled".to_string()));
+        }
+        let endpoints = match self.route_endpoints(request).await {
+            Some(endpoints) => endpoints,
+            None => vec![self.load_balancer.select_endpoint().await?.url.clone()],
+        };
+        self.send_with_failover(request, &endpoints, true).await
+    }
 
-        result
+    fn available_models(&self) -> Vec<ModelId> {
+        let mut models: Vec<ModelId> = self.model_selector.model_specifications.keys().cloned().collect();
+        models.sort();
+        models
     }
 
     async fn update_metrics(&self, _metrics: &mcp_common::PerformanceMetrics) -> Result<()> {
@@ -629,6 +831,9 @@ impl Router for IntelligentRouter {
         health_metrics.insert("queue_size".to_string(), state.queue_size as f32);
         health_metrics.insert("local_success_rate".to_string(), metrics.local_success_rate);
         health_metrics.insert("cloud_success_rate".to_string(), metrics.cloud_success_rate);
+        self.cloud_limiter.gauge().write_metrics(&mut health_metrics);
+        self.cloud_client.write_metrics(&mut health_metrics);
+        self.policy().write_metrics(&mut health_metrics);
 
         let status = if state.local_capacity_percent > 95.0 || state.memory_usage_percent > 95.0 {
             HealthLevel::Critical
@@ -653,6 +858,12 @@ impl Router for IntelligentRouter {
         })
     }
 
+    fn reconfigure(&self, config: &Config) {
+        let policy = Arc::new(RoutingPolicy::new(&config.router.policy));
+        info!("Routing policy replaced ({} rules)", config.router.policy.rules.len());
+        *self.policy.write().unwrap_or_else(|p| p.into_inner()) = policy;
+    }
+
     async fn shutdown(&self) -> Result<()> {
         info!("Shutting down intelligent router");
         self.cloud_client.shutdown().await?;
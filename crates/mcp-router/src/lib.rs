@@ -5,7 +5,8 @@
 
 use async_trait::async_trait;
 use mcp_common::metrics::ComponentHealth;
-use mcp_common::{Config, MCPRequest, MCPResponse, Result, RoutingDecision};
+use mcp_common::{Config, MCPRequest, MCPResponse, ModelId, Result, RoutingDecision};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 /// Router trait for request routing decisions
@@ -17,26 +18,70 @@ pub trait Router {
     /// Forward request to cloud endpoint
     async fn forward_to_cloud(&self, request: &MCPRequest, endpoint: &str) -> Result<MCPResponse>;
 
+    /// Forward a request to the best available cloud endpoint
+    async fn fallback_to_cloud(&self, request: &MCPRequest) -> Result<MCPResponse>;
+
+    /// Models this router can route requests to
+    fn available_models(&self) -> Vec<ModelId>;
+
     /// Update performance metrics for routing decisions
     async fn update_metrics(&self, metrics: &mcp_common::PerformanceMetrics) -> Result<()>;
 
     /// Get health status
     async fn health_check(&self) -> Result<ComponentHealth>;
 
+    /// Apply the settings of a reloaded configuration that can change
+    /// without a restart
+    fn reconfigure(&self, _config: &Config) {}
+
     /// Shutdown the router
     async fn shutdown(&self) -> Result<()>;
 }
 
+/// Transport used to forward requests to cloud endpoints
+#[async_trait]
+pub trait CloudTransport {
+    /// Send a request to the given endpoint URL
+    async fn send_request(&self, endpoint: &str, request: &MCPRequest) -> Result<MCPResponse>;
+
+    /// Release transport resources
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Add transport metrics to the router's health metrics
+    fn write_metrics(&self, _metrics: &mut HashMap<String, f32>) {}
+}
+
 mod advanced_load_balancer;
 mod cloud_client;
+mod happy_eyeballs;
 mod intelligent_router;
 mod load_balancer;
+pub mod model_aliases;
+pub mod model_rollouts;
+pub mod providers;
+pub mod routing_policy;
+mod warm_standby;
 
 pub use advanced_load_balancer::{AdvancedLoadBalancer, LoadBalancerStats, EndpointStats};
 pub use intelligent_router::IntelligentRouter;
+pub use model_aliases::{AliasTable, ModelAliasResolver};
+pub use model_rollouts::{ModelRollouts, RolloutStatus};
+pub use providers::CloudProvider;
+pub use routing_policy::RoutingPolicy;
 
 /// Create a new router instance
 pub async fn create_router(config: Arc<Config>) -> Result<Arc<dyn Router + Send + Sync>> {
     let router = IntelligentRouter::new(config).await?;
     Ok(Arc::new(router))
 }
+
+/// Create a router that forwards cloud requests through `transport`
+pub async fn create_router_with_transport(
+    config: Arc<Config>,
+    transport: Arc<dyn CloudTransport + Send + Sync>,
+) -> Result<Arc<dyn Router + Send + Sync>> {
+    let router = IntelligentRouter::with_transport(config, transport).await?;
+    Ok(Arc::new(router))
+}
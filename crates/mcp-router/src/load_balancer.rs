@@ -1,6 +1,7 @@
 //! Load balancing utilities for distributing requests
 
 use mcp_common::config::{CloudEndpoint, LoadBalancingAlgorithm};
+use mcp_common::events::{self, GatewayEvent};
 use mcp_common::{Config, Result};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -123,6 +124,12 @@ impl LoadBalancer {
         let mut health = self.endpoint_health.write().await;
 
         if let Some(endpoint_health) = health.get_mut(url) {
+            if endpoint_health.is_healthy != is_healthy {
+                events::publish(GatewayEvent::ConnectivityChanged {
+                    endpoint: url.to_string(),
+                    online: is_healthy,
+                });
+            }
             endpoint_health.is_healthy = is_healthy;
             endpoint_health.last_check = chrono::Utc::now();
 
@@ -159,6 +166,17 @@ impl LoadBalancer {
         self.endpoint_health.read().await.clone()
     }
 
+    /// Endpoints with the given names, in that order but healthy ones first
+    pub async fn route_order(&self, names: &[String]) -> Vec<&CloudEndpoint> {
+        let health = self.endpoint_health.read().await;
+        let mut endpoints: Vec<&CloudEndpoint> = names
+            .iter()
+            .filter_map(|name| self.endpoints.iter().find(|endpoint| &endpoint.name == name))
+            .collect();
+        endpoints.sort_by_key(|endpoint| !health.get(&endpoint.url).map_or(true, |h| h.is_healthy));
+        endpoints
+    }
+
     /// Get healthy endpoints
     pub async fn get_healthy_endpoints(&self) -> Vec<&CloudEndpoint> {
         let health = self.endpoint_health.read().await;
@@ -0,0 +1,200 @@
+//! Model alias resolution
+//!
+//! Clients address models by stable names such as `default-chat`; the router
+//! resolves them to concrete model ids when routing, so operators can swap the
+//! underlying model without touching clients. Tenants may override any global
+//! alias, and the alias table can be reloaded from a JSON file at runtime.
+
+use mcp_common::config::ModelAliasConfig;
+use mcp_common::events::{self, GatewayEvent};
+use mcp_common::{create_vfs, Config, Error, MCPRequest, ModelId, Result, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+/// Longest alias chain followed before giving up, guarding against cycles
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// Request parameter naming the model a client asks for
+pub const MODEL_PARAM: &str = "model";
+
+/// Request parameter naming the tenant whose aliases apply
+pub const TENANT_PARAM: &str = "tenant";
+
+/// Alias tables as stored in the alias file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AliasTable {
+    #[serde(default)]
+    pub global: HashMap<String, ModelId>,
+    #[serde(default)]
+    pub tenants: HashMap<String, HashMap<String, ModelId>>,
+}
+
+impl From<&ModelAliasConfig> for AliasTable {
+    fn from(config: &ModelAliasConfig) -> Self {
+        Self {
+            global: config.global.clone(),
+            tenants: config.tenants.clone(),
+        }
+    }
+}
+
+/// Resolves model aliases, optionally hot-reloading them from a file
+pub struct ModelAliasResolver {
+    table: RwLock<AliasTable>,
+    alias_file: Option<PathBuf>,
+    vfs: Arc<dyn Vfs>,
+}
+
+impl ModelAliasResolver {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            table: RwLock::new(AliasTable::from(&config.models.aliases)),
+            alias_file: config.models.aliases.alias_file.clone(),
+            vfs: create_vfs(&config.storage),
+        }
+    }
+
+    /// Load the alias file and, if configured, keep watching it for changes
+    pub async fn start(self: &Arc<Self>, reload_interval_secs: u64) {
+        if self.alias_file.is_none() {
+            return;
+        }
+        if let Err(e) = self.reload().await {
+            warn!("Failed to load model alias file: {}", e);
+        }
+        if reload_interval_secs == 0 {
+            return;
+        }
+
+        let resolver = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(reload_interval_secs));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(resolver) = resolver.upgrade() else {
+                    break;
+                };
+                if let Err(e) = resolver.reload().await {
+                    warn!("Failed to reload model alias file: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Re-read the alias file; returns whether the table changed
+    pub async fn reload(&self) -> Result<bool> {
+        let Some(path) = &self.alias_file else {
+            return Ok(false);
+        };
+
+        let contents = self.vfs.read(path).await?;
+        let table: AliasTable = serde_json::from_slice(&contents)
+            .map_err(|e| Error::Configuration(format!("Invalid model alias file {:?}: {}", path, e)))?;
+
+        let mut current = self.table.write().await;
+        if *current == table {
+            return Ok(false);
+        }
+        info!(
+            "Model aliases reloaded from {:?} ({} global, {} tenants)",
+            path,
+            table.global.len(),
+            table.tenants.len()
+        );
+        *current = table;
+        events::publish(GatewayEvent::ConfigReloaded {
+            section: "models.aliases".to_string(),
+        });
+        Ok(true)
+    }
+
+    /// Resolve a model name for a tenant; names without an alias resolve to themselves
+    pub async fn resolve(&self, tenant: Option<&str>, name: &str) -> Result<ModelId> {
+        let table = self.table.read().await;
+        let tenant_aliases = tenant.and_then(|tenant| table.tenants.get(tenant));
+
+        let mut current = name.to_string();
+        for _ in 0..MAX_ALIAS_DEPTH {
+            let next = tenant_aliases
+                .and_then(|aliases| aliases.get(&current))
+                .or_else(|| table.global.get(&current));
+            match next {
+                Some(target) if *target != current => current = target.clone(),
+                _ => {
+                    if current != name {
+                        debug!("Resolved model alias {} -> {}", name, current);
+                    }
+                    return Ok(current);
+                },
+            }
+        }
+
+        Err(Error::Configuration(format!(
+            "Model alias {} does not resolve within {} steps",
+            name, MAX_ALIAS_DEPTH
+        )))
+    }
+
+    /// Resolve the model explicitly requested by a client, if any
+    pub async fn resolve_requested(&self, request: &MCPRequest) -> Result<Option<ModelId>> {
+        let Some(name) = request.params.get(MODEL_PARAM).and_then(|value| value.as_str()) else {
+            return Ok(None);
+        };
+        let tenant = request
+            .params
+            .get(TENANT_PARAM)
+            .and_then(|value| value.as_str())
+            .unwrap_or(&request.device_id);
+        self.resolve(Some(tenant), name).await.map(Some)
+    }
+
+    /// Current alias tables
+    pub async fn table(&self) -> AliasTable {
+        self.table.read().await.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver(global: &[(&str, &str)], tenant: &[(&str, &str)]) -> ModelAliasResolver {
+        let mut config = Config::default();
+        config.models.aliases.global = global
+            .iter()
+            .map(|(alias, model)| (alias.to_string(), model.to_string()))
+            .collect();
+        config.models.aliases.tenants.insert(
+            "acme".to_string(),
+            tenant
+                .iter()
+                .map(|(alias, model)| (alias.to_string(), model.to_string()))
+                .collect(),
+        );
+        ModelAliasResolver::new(&config)
+    }
+
+    #[tokio::test]
+    async fn test_tenant_aliases_override_global() {
+        let resolver = resolver(
+            &[("default-chat", "tinyllama-1.1b")],
+            &[("default-chat", "llama-7b")],
+        );
+
+        assert_eq!(resolver.resolve(None, "default-chat").await.unwrap(), "tinyllama-1.1b");
+        assert_eq!(resolver.resolve(Some("acme"), "default-chat").await.unwrap(), "llama-7b");
+        assert_eq!(resolver.resolve(Some("acme"), "phi-3-mini").await.unwrap(), "phi-3-mini");
+    }
+
+    #[tokio::test]
+    async fn test_alias_cycles_are_rejected() {
+        let resolver = resolver(&[("a", "b"), ("b", "a")], &[]);
+        assert!(resolver.resolve(None, "a").await.is_err());
+    }
+}
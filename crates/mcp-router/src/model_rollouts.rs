@@ -0,0 +1,306 @@
+//! A/B rollouts of model versions
+//!
+//! A rollout splits the requests routed to a local model between two of its
+//! versions, each served as its own model id (`llama-7b@2`) so both run side
+//! by side. Devices are assigned by hashing their id into one of 100 buckets
+//! and the lowest `candidate_percent` buckets get the candidate, so a device
+//! keeps the version it was given while the split is unchanged and moves to
+//! the candidate only as its share grows. Operators change the split, promote
+//! the candidate or roll it back at runtime; the version that stops receiving
+//! traffic is returned so the caller can retire it.
+
+use mcp_common::config::ModelRollout;
+use mcp_common::{Error, MCPRequest, ModelId, Result, RoutingDecision};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+/// Model id a version of `model` is served as
+pub fn version_id(model: &str, version: &str) -> ModelId {
+    format!("{}@{}", model, version)
+}
+
+/// A rollout with the requests routed to each of its versions since it was
+/// last changed
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloutStatus {
+    #[serde(flatten)]
+    pub rollout: ModelRollout,
+    pub routed: BTreeMap<String, u64>,
+}
+
+struct Rollout {
+    config: ModelRollout,
+    routed_stable: AtomicU64,
+    routed_candidate: AtomicU64,
+}
+
+impl Rollout {
+    fn new(config: ModelRollout) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            routed_stable: AtomicU64::new(0),
+            routed_candidate: AtomicU64::new(0),
+        })
+    }
+
+    fn status(&self) -> RolloutStatus {
+        let mut routed = BTreeMap::from([(
+            self.config.stable.clone(),
+            self.routed_stable.load(Ordering::Relaxed),
+        )]);
+        if let Some(candidate) = &self.config.candidate {
+            routed.insert(candidate.clone(), self.routed_candidate.load(Ordering::Relaxed));
+        }
+        RolloutStatus {
+            rollout: self.config.clone(),
+            routed,
+        }
+    }
+}
+
+/// Rollouts by model, applied to local routing decisions
+pub struct ModelRollouts {
+    rollouts: RwLock<HashMap<ModelId, Arc<Rollout>>>,
+}
+
+impl ModelRollouts {
+    pub fn new(rollouts: &[ModelRollout]) -> Self {
+        Self {
+            rollouts: RwLock::new(
+                rollouts
+                    .iter()
+                    .map(|rollout| (rollout.model.clone(), Rollout::new(rollout.clone())))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Version a device's request for `model_id` is served by; ids without a
+    /// rollout, including explicit versions, are returned unchanged
+    pub fn resolve(&self, request: &MCPRequest, model_id: &ModelId) -> ModelId {
+        let Some(rollout) = self.read().get(model_id).cloned() else {
+            return model_id.clone();
+        };
+        let config = &rollout.config;
+        match &config.candidate {
+            Some(candidate) if bucket(&request.device_id, model_id) < config.candidate_percent => {
+                rollout.routed_candidate.fetch_add(1, Ordering::Relaxed);
+                version_id(model_id, candidate)
+            },
+            _ => {
+                rollout.routed_stable.fetch_add(1, Ordering::Relaxed);
+                version_id(model_id, &config.stable)
+            },
+        }
+    }
+
+    /// Point a local routing decision at the version the rollout picks
+    pub fn apply(&self, request: &MCPRequest, decision: RoutingDecision) -> RoutingDecision {
+        match decision {
+            RoutingDecision::Local {
+                model_id,
+                estimated_latency_ms,
+            } => RoutingDecision::Local {
+                model_id: self.resolve(request, &model_id),
+                estimated_latency_ms,
+            },
+            decision => decision,
+        }
+    }
+
+    pub fn list(&self) -> Vec<RolloutStatus> {
+        let mut rollouts: Vec<RolloutStatus> = self.read().values().map(|rollout| rollout.status()).collect();
+        rollouts.sort_by(|a, b| a.rollout.model.cmp(&b.rollout.model));
+        rollouts
+    }
+
+    pub fn get(&self, model: &str) -> Option<RolloutStatus> {
+        self.read().get(model).map(|rollout| rollout.status())
+    }
+
+    /// Start or replace a rollout; returns the versions of the replaced
+    /// rollout that no longer receive traffic
+    pub fn set(&self, rollout: ModelRollout) -> Result<Vec<ModelId>> {
+        rollout.validate().map_err(|e| Error::InvalidRequest(e.to_string()))?;
+        info!(
+            "Rollout of {}: {} with {}% to {:?}",
+            rollout.model, rollout.stable, rollout.candidate_percent, rollout.candidate
+        );
+        let model = rollout.model.clone();
+        let kept: Vec<ModelId> = std::iter::once(&rollout.stable)
+            .chain(rollout.candidate.as_ref())
+            .map(|version| version_id(&model, version))
+            .collect();
+        let previous = self.write().insert(model.clone(), Rollout::new(rollout));
+        Ok(previous
+            .map(|previous| versions(&previous.config))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|version| !kept.contains(version))
+            .collect())
+    }
+
+    /// Change the share of devices sent to the candidate
+    pub fn set_split(&self, model: &str, candidate_percent: u8) -> Result<RolloutStatus> {
+        let mut rollout = self.current(model)?;
+        if rollout.candidate.is_none() {
+            return Err(Error::InvalidRequest(format!("Rollout of {} has no candidate version", model)));
+        }
+        rollout.candidate_percent = candidate_percent;
+        self.set(rollout)?;
+        self.get(model).ok_or_else(|| not_found(model))
+    }
+
+    /// Make the candidate the stable version; returns the replaced stable
+    /// version, which no longer receives traffic
+    pub fn promote(&self, model: &str) -> Result<ModelId> {
+        let mut rollout = self.current(model)?;
+        let Some(candidate) = rollout.candidate.take() else {
+            return Err(Error::InvalidRequest(format!("Rollout of {} has no candidate version", model)));
+        };
+        let previous = std::mem::replace(&mut rollout.stable, candidate);
+        rollout.candidate_percent = 0;
+        self.set(rollout)?;
+        Ok(version_id(model, &previous))
+    }
+
+    /// Send all traffic back to the stable version; returns the candidate
+    /// version, which no longer receives traffic
+    pub fn rollback(&self, model: &str) -> Result<ModelId> {
+        let mut rollout = self.current(model)?;
+        let Some(candidate) = rollout.candidate.take() else {
+            return Err(Error::InvalidRequest(format!("Rollout of {} has no candidate version", model)));
+        };
+        rollout.candidate_percent = 0;
+        self.set(rollout)?;
+        Ok(version_id(model, &candidate))
+    }
+
+    /// Stop splitting `model`; returns the versions that no longer receive
+    /// traffic, since requests for it are served by the unversioned model
+    pub fn remove(&self, model: &str) -> Result<Vec<ModelId>> {
+        let removed = self.write().remove(model).ok_or_else(|| not_found(model))?;
+        info!("Rollout of {} removed", model);
+        Ok(versions(&removed.config))
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let rollouts = self.read();
+        let candidates = rollouts.values().filter(|rollout| rollout.config.candidate.is_some()).count();
+        metrics.insert("model_rollouts".to_string(), rollouts.len() as f32);
+        metrics.insert("model_rollouts_with_candidate".to_string(), candidates as f32);
+    }
+
+    fn current(&self, model: &str) -> Result<ModelRollout> {
+        self.read()
+            .get(model)
+            .map(|rollout| rollout.config.clone())
+            .ok_or_else(|| not_found(model))
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<ModelId, Arc<Rollout>>> {
+        self.rollouts.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<ModelId, Arc<Rollout>>> {
+        self.rollouts.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn versions(rollout: &ModelRollout) -> Vec<ModelId> {
+    std::iter::once(&rollout.stable)
+        .chain(rollout.candidate.as_ref())
+        .map(|version| version_id(&rollout.model, version))
+        .collect()
+}
+
+fn not_found(model: &str) -> Error {
+    Error::InvalidRequest(format!("No rollout of model {}", model))
+}
+
+/// Bucket from 0 to 99 a device falls in for a model, stable across restarts
+/// (FNV-1a), so each model's split picks its own set of devices
+fn bucket(device_id: &str, model: &str) -> u8 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in device_id.bytes().chain([0]).chain(model.bytes()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    (hash % 100) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(device_id: &str) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: device_id.to_string(),
+            method: "completion".to_string(),
+            params: HashMap::new(),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    fn rollout(candidate_percent: u8) -> ModelRollout {
+        ModelRollout {
+            model: "llama".to_string(),
+            stable: "1".to_string(),
+            candidate: Some("2".to_string()),
+            candidate_percent,
+        }
+    }
+
+    #[test]
+    fn test_split_is_sticky_per_device_and_grows_monotonically() {
+        let rollouts = ModelRollouts::new(&[rollout(20)]);
+        let devices: Vec<MCPRequest> = (0..1000).map(|i| request(&format!("sensor-{}", i))).collect();
+        let on_candidate = |rollouts: &ModelRollouts| -> Vec<bool> {
+            devices
+                .iter()
+                .map(|request| rollouts.resolve(request, &"llama".to_string()) == "llama@2")
+                .collect()
+        };
+
+        let at_20 = on_candidate(&rollouts);
+        assert_eq!(at_20, on_candidate(&rollouts));
+        let share = at_20.iter().filter(|on| **on).count();
+        assert!((150..250).contains(&share), "{} of 1000 devices on the candidate", share);
+
+        rollouts.set_split("llama", 50).unwrap();
+        let at_50 = on_candidate(&rollouts);
+        assert!(at_20.iter().zip(&at_50).all(|(before, after)| !before || *after));
+
+        // Explicit versions and models without a rollout are left alone
+        let device = request("sensor-1");
+        assert_eq!(rollouts.resolve(&device, &"llama@1".to_string()), "llama@1");
+        assert_eq!(rollouts.resolve(&device, &"phi-3".to_string()), "phi-3");
+        let status = rollouts.get("llama").unwrap();
+        assert_eq!(status.routed.values().sum::<u64>(), 1000);
+    }
+
+    #[test]
+    fn test_promote_and_rollback_return_the_retired_version() {
+        let rollouts = ModelRollouts::new(&[rollout(10)]);
+        assert_eq!(rollouts.promote("llama").unwrap(), "llama@1");
+        let status = rollouts.get("llama").unwrap().rollout;
+        assert_eq!((status.stable.as_str(), status.candidate.as_deref()), ("2", None));
+        assert!(rollouts.promote("llama").is_err());
+        assert!(rollouts.set_split("llama", 30).is_err());
+
+        let mut next = rollout(101);
+        assert!(rollouts.set(next.clone()).is_err());
+        next.stable = "2".to_string();
+        next.candidate = Some("3".to_string());
+        next.candidate_percent = 5;
+        assert!(rollouts.set(next).unwrap().is_empty());
+        assert_eq!(rollouts.rollback("llama").unwrap(), "llama@3");
+        assert_eq!(rollouts.remove("llama").unwrap(), vec!["llama@2".to_string()]);
+        assert!(rollouts.list().is_empty());
+    }
+}
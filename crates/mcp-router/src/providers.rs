@@ -0,0 +1,504 @@
+//! Cloud provider adapters
+//!
+//! Each cloud endpoint speaks one provider API, chosen by its `provider`
+//! setting. An adapter knows where that API takes requests, how it
+//! authenticates, how to turn an MCP request into its request body and its
+//! response back into an MCP response, and how its error statuses map onto
+//! gateway errors. Chat APIs receive the request's `messages`, or its
+//! `prompt` or `text` as a single user message, and answer with a result
+//! shaped like a local completion: `text`, `model`, `finish_reason` and
+//! token `usage`.
+
+use crate::model_aliases::MODEL_PARAM;
+use mcp_common::config::{CloudEndpoint, CloudProviderConfig};
+use mcp_common::redaction;
+use mcp_common::{Error, MCPRequest, MCPResponse, Result};
+use serde_json::{json, Map, Value};
+
+/// Request parameters passed through to chat APIs unchanged
+const SAMPLING_PARAMS: &[&str] = &["max_tokens", "temperature", "top_p"];
+
+/// Anthropic API version sent when the endpoint sets none
+const DEFAULT_ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Anthropic `max_tokens` used when neither the request nor the endpoint sets one
+const DEFAULT_ANTHROPIC_MAX_TOKENS: u32 = 1024;
+
+/// Request and response mapping for one provider API
+pub trait CloudProvider: Send + Sync {
+    /// Name used in logs and error messages
+    fn name(&self) -> &'static str;
+
+    /// URL requests for `endpoint` are posted to
+    fn request_url(&self, endpoint: &CloudEndpoint) -> String;
+
+    /// URL probed to check that `endpoint` is reachable
+    fn health_url(&self, endpoint: &CloudEndpoint) -> String {
+        format!("{}/health", base_url(endpoint))
+    }
+
+    /// Headers carrying the endpoint's API key
+    fn auth_headers(&self, endpoint: &CloudEndpoint) -> Vec<(&'static str, String)> {
+        bearer(endpoint)
+    }
+
+    /// Request body in the provider's format
+    fn encode_request(&self, request: &MCPRequest) -> Result<Vec<u8>>;
+
+    /// The provider's response as the response to `request`
+    fn decode_response(&self, request: &MCPRequest, body: &[u8]) -> Result<MCPResponse>;
+
+    /// Error for a non-success status, using the provider's own message when
+    /// the body carries one
+    fn translate_error(&self, status: u16, body: &str) -> Error {
+        let message = serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|body| body.pointer("/error/message").and_then(Value::as_str).map(str::to_string))
+            .unwrap_or_else(|| body.to_string());
+        let message = format!(
+            "{} request failed with status {}: {}",
+            self.name(),
+            status,
+            redaction::text(&message)
+        );
+        match status {
+            400 | 413 | 422 => Error::InvalidRequest(message),
+            401 | 403 => Error::Security(message),
+            404 => Error::Configuration(message),
+            429 => Error::ResourceExhausted(message),
+            _ => Error::Network(message),
+        }
+    }
+}
+
+/// Adapter for an endpoint's provider setting
+pub fn provider_for(config: &CloudProviderConfig) -> Box<dyn CloudProvider> {
+    match config {
+        CloudProviderConfig::Mcp => Box::new(McpProvider),
+        CloudProviderConfig::OpenAi { model } => Box::new(OpenAiProvider { model: model.clone() }),
+        CloudProviderConfig::Anthropic {
+            model,
+            version,
+            max_tokens,
+        } => Box::new(AnthropicProvider {
+            model: model.clone(),
+            version: version.clone().unwrap_or_else(|| DEFAULT_ANTHROPIC_VERSION.to_string()),
+            max_tokens: max_tokens.unwrap_or(DEFAULT_ANTHROPIC_MAX_TOKENS),
+        }),
+        CloudProviderConfig::AzureOpenAi {
+            deployment,
+            api_version,
+        } => Box::new(AzureOpenAiProvider {
+            deployment: deployment.clone(),
+            api_version: api_version.clone(),
+        }),
+    }
+}
+
+/// Whether a failed cloud request should be retried on the next endpoint of
+/// its route; requests the provider rejected as malformed fail everywhere
+pub fn fails_over(error: &Error) -> bool {
+    !matches!(error, Error::InvalidRequest(_) | Error::Validation(_))
+}
+
+/// Another MCP gateway: the request and response pass through unchanged
+struct McpProvider;
+
+impl CloudProvider for McpProvider {
+    fn name(&self) -> &'static str {
+        "mcp"
+    }
+
+    fn request_url(&self, endpoint: &CloudEndpoint) -> String {
+        endpoint.url.clone()
+    }
+
+    fn encode_request(&self, request: &MCPRequest) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(request)?)
+    }
+
+    fn decode_response(&self, _request: &MCPRequest, body: &[u8]) -> Result<MCPResponse> {
+        serde_json::from_slice(body).map_err(|e| Error::Network(format!("Failed to parse response: {}", e)))
+    }
+}
+
+struct OpenAiProvider {
+    model: Option<String>,
+}
+
+impl CloudProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn request_url(&self, endpoint: &CloudEndpoint) -> String {
+        format!("{}/chat/completions", base_url(endpoint))
+    }
+
+    fn health_url(&self, endpoint: &CloudEndpoint) -> String {
+        format!("{}/models", base_url(endpoint))
+    }
+
+    fn encode_request(&self, request: &MCPRequest) -> Result<Vec<u8>> {
+        let mut body = chat_body(request, SAMPLING_PARAMS)?;
+        body.insert("model".to_string(), Value::String(model(self, &self.model, request)?));
+        if let Some(stop) = request.params.get("stop") {
+            body.insert("stop".to_string(), stop.clone());
+        }
+        Ok(serde_json::to_vec(&body)?)
+    }
+
+    fn decode_response(&self, request: &MCPRequest, body: &[u8]) -> Result<MCPResponse> {
+        decode_chat_completion(self, request, body)
+    }
+}
+
+struct AnthropicProvider {
+    model: Option<String>,
+    version: String,
+    max_tokens: u32,
+}
+
+impl CloudProvider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn request_url(&self, endpoint: &CloudEndpoint) -> String {
+        format!("{}/v1/messages", base_url(endpoint))
+    }
+
+    fn health_url(&self, endpoint: &CloudEndpoint) -> String {
+        format!("{}/v1/models", base_url(endpoint))
+    }
+
+    fn auth_headers(&self, endpoint: &CloudEndpoint) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("anthropic-version", self.version.clone())];
+        if let Some(api_key) = &endpoint.api_key {
+            headers.push(("x-api-key", api_key.clone()));
+        }
+        headers
+    }
+
+    fn encode_request(&self, request: &MCPRequest) -> Result<Vec<u8>> {
+        let mut body = chat_body(request, SAMPLING_PARAMS)?;
+        body.insert("model".to_string(), Value::String(model(self, &self.model, request)?));
+        body.entry("max_tokens").or_insert_with(|| json!(self.max_tokens));
+
+        // System prompts go in their own field rather than in the messages
+        if let Some(Value::Array(messages)) = body.remove("messages") {
+            let (system, messages): (Vec<Value>, Vec<Value>) =
+                messages.into_iter().partition(|message| message["role"] == "system");
+            let system: Vec<&str> = system.iter().filter_map(|message| message["content"].as_str()).collect();
+            if !system.is_empty() {
+                body.insert("system".to_string(), Value::String(system.join("\n\n")));
+            }
+            body.insert("messages".to_string(), Value::Array(messages));
+        }
+        match request.params.get("stop") {
+            Some(Value::String(stop)) => {
+                body.insert("stop_sequences".to_string(), json!([stop]));
+            },
+            Some(stop @ Value::Array(_)) => {
+                body.insert("stop_sequences".to_string(), stop.clone());
+            },
+            _ => {},
+        }
+        Ok(serde_json::to_vec(&body)?)
+    }
+
+    fn decode_response(&self, request: &MCPRequest, body: &[u8]) -> Result<MCPResponse> {
+        let body = parse_body(self, body)?;
+        let text: String = body["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        Ok(chat_response(
+            self,
+            request,
+            text,
+            &body["model"],
+            &body["stop_reason"],
+            &body["usage"]["input_tokens"],
+            &body["usage"]["output_tokens"],
+        ))
+    }
+}
+
+struct AzureOpenAiProvider {
+    deployment: String,
+    api_version: String,
+}
+
+impl CloudProvider for AzureOpenAiProvider {
+    fn name(&self) -> &'static str {
+        "azure_openai"
+    }
+
+    fn request_url(&self, endpoint: &CloudEndpoint) -> String {
+        format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            base_url(endpoint),
+            self.deployment,
+            self.api_version
+        )
+    }
+
+    fn health_url(&self, endpoint: &CloudEndpoint) -> String {
+        format!("{}/openai/models?api-version={}", base_url(endpoint), self.api_version)
+    }
+
+    fn auth_headers(&self, endpoint: &CloudEndpoint) -> Vec<(&'static str, String)> {
+        endpoint.api_key.iter().map(|api_key| ("api-key", api_key.clone())).collect()
+    }
+
+    fn encode_request(&self, request: &MCPRequest) -> Result<Vec<u8>> {
+        // The deployment fixes the model
+        let mut body = chat_body(request, SAMPLING_PARAMS)?;
+        if let Some(stop) = request.params.get("stop") {
+            body.insert("stop".to_string(), stop.clone());
+        }
+        Ok(serde_json::to_vec(&body)?)
+    }
+
+    fn decode_response(&self, request: &MCPRequest, body: &[u8]) -> Result<MCPResponse> {
+        decode_chat_completion(self, request, body)
+    }
+}
+
+fn base_url(endpoint: &CloudEndpoint) -> &str {
+    endpoint.url.trim_end_matches('/')
+}
+
+fn bearer(endpoint: &CloudEndpoint) -> Vec<(&'static str, String)> {
+    endpoint
+        .api_key
+        .iter()
+        .map(|api_key| ("Authorization", format!("Bearer {}", api_key)))
+        .collect()
+}
+
+/// Model configured on the endpoint, otherwise the one the client asked for
+fn model(provider: &dyn CloudProvider, configured: &Option<String>, request: &MCPRequest) -> Result<String> {
+    configured
+        .clone()
+        .or_else(|| request.params.get(MODEL_PARAM).and_then(Value::as_str).map(str::to_string))
+        .ok_or_else(|| {
+            Error::Routing(format!(
+                "No model for {} endpoint: set provider.model or send a '{}' parameter",
+                provider.name(),
+                MODEL_PARAM
+            ))
+        })
+}
+
+/// Chat request body with the request's messages and sampling parameters
+fn chat_body(request: &MCPRequest, passthrough: &[&str]) -> Result<Map<String, Value>> {
+    let messages = match request.params.get("messages").and_then(Value::as_array) {
+        Some(messages) => messages.clone(),
+        None => {
+            let text = ["prompt", "text"]
+                .iter()
+                .find_map(|key| request.params.get(*key).and_then(Value::as_str))
+                .ok_or_else(|| {
+                    Error::InvalidRequest(format!(
+                        "Method '{}' has no messages, prompt or text for a chat API",
+                        request.method
+                    ))
+                })?;
+            vec![json!({"role": "user", "content": text})]
+        },
+    };
+
+    let mut body = Map::new();
+    body.insert("messages".to_string(), Value::Array(messages));
+    for key in passthrough {
+        if let Some(value) = request.params.get(*key) {
+            body.insert(key.to_string(), value.clone());
+        }
+    }
+    Ok(body)
+}
+
+fn parse_body(provider: &dyn CloudProvider, body: &[u8]) -> Result<Value> {
+    serde_json::from_slice(body)
+        .map_err(|e| Error::Network(format!("Failed to parse {} response: {}", provider.name(), e)))
+}
+
+/// Chat completions response, shared by OpenAI and Azure OpenAI
+fn decode_chat_completion(provider: &dyn CloudProvider, request: &MCPRequest, body: &[u8]) -> Result<MCPResponse> {
+    let body = parse_body(provider, body)?;
+    let choice = &body["choices"][0];
+    if choice.is_null() {
+        return Err(Error::Network(format!("{} response has no choices", provider.name())));
+    }
+    let text = choice["message"]["content"].as_str().unwrap_or_default().to_string();
+    Ok(chat_response(
+        provider,
+        request,
+        text,
+        &body["model"],
+        &choice["finish_reason"],
+        &body["usage"]["prompt_tokens"],
+        &body["usage"]["completion_tokens"],
+    ))
+}
+
+fn chat_response(
+    provider: &dyn CloudProvider,
+    request: &MCPRequest,
+    text: String,
+    model: &Value,
+    finish_reason: &Value,
+    prompt_tokens: &Value,
+    completion_tokens: &Value,
+) -> MCPResponse {
+    MCPResponse {
+        id: request.id,
+        result: Some(json!({
+            "text": text,
+            "model": model,
+            "finish_reason": finish_reason,
+            "usage": {
+                "prompt_tokens": prompt_tokens,
+                "completion_tokens": completion_tokens,
+            },
+            "provider": provider.name(),
+        })),
+        error: None,
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn endpoint(provider: CloudProviderConfig) -> CloudEndpoint {
+        CloudEndpoint {
+            name: "cloud".to_string(),
+            url: "https://api.example.com/".to_string(),
+            api_key: Some("secret".to_string()),
+            timeout_ms: 5000,
+            max_retries: 0,
+            connect_timeout_ms: None,
+            region: None,
+            provider,
+            compression: Default::default(),
+        }
+    }
+
+    fn request(params: Value) -> MCPRequest {
+        MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "sensor-1".to_string(),
+            method: "chat".to_string(),
+            params: serde_json::from_value::<HashMap<String, Value>>(params).unwrap(),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_maps_requests_and_responses_per_provider() {
+        let request = request(json!({
+            "model": "client-model",
+            "max_tokens": 64,
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Hello"},
+            ],
+        }));
+
+        let openai = provider_for(&CloudProviderConfig::OpenAi { model: None });
+        let endpoint_config = endpoint(CloudProviderConfig::OpenAi { model: None });
+        assert_eq!(openai.request_url(&endpoint_config), "https://api.example.com/chat/completions");
+        assert_eq!(openai.auth_headers(&endpoint_config), vec![("Authorization", "Bearer secret".to_string())]);
+        let body: Value = serde_json::from_slice(&openai.encode_request(&request).unwrap()).unwrap();
+        assert_eq!(body["model"], "client-model");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+        let response = openai
+            .decode_response(
+                &request,
+                br#"{"model":"gpt","choices":[{"message":{"content":"Hi"},"finish_reason":"stop"}],"usage":{"prompt_tokens":5,"completion_tokens":1}}"#,
+            )
+            .unwrap();
+        assert_eq!(response.id, request.id);
+        let result = response.result.unwrap();
+        assert_eq!(result["text"], "Hi");
+        assert_eq!(result["usage"]["completion_tokens"], 1);
+
+        let config = CloudProviderConfig::Anthropic {
+            model: Some("claude".to_string()),
+            version: None,
+            max_tokens: None,
+        };
+        let anthropic = provider_for(&config);
+        assert_eq!(anthropic.request_url(&endpoint(config.clone())), "https://api.example.com/v1/messages");
+        let headers = anthropic.auth_headers(&endpoint(config));
+        assert!(headers.contains(&("x-api-key", "secret".to_string())));
+        assert!(headers.contains(&("anthropic-version", DEFAULT_ANTHROPIC_VERSION.to_string())));
+        let body: Value = serde_json::from_slice(&anthropic.encode_request(&request).unwrap()).unwrap();
+        assert_eq!(body["model"], "claude");
+        assert_eq!(body["system"], "Be brief.");
+        assert_eq!(body["messages"], json!([{"role": "user", "content": "Hello"}]));
+        assert_eq!(body["max_tokens"], 64);
+        let result = anthropic
+            .decode_response(
+                &request,
+                br#"{"model":"claude","content":[{"type":"text","text":"Hi"},{"type":"text","text":" there"}],"stop_reason":"end_turn","usage":{"input_tokens":5,"output_tokens":2}}"#,
+            )
+            .unwrap()
+            .result
+            .unwrap();
+        assert_eq!(result["text"], "Hi there");
+        assert_eq!(result["usage"]["prompt_tokens"], 5);
+
+        let config = CloudProviderConfig::AzureOpenAi {
+            deployment: "chat".to_string(),
+            api_version: "2024-06-01".to_string(),
+        };
+        let azure = provider_for(&config);
+        assert_eq!(
+            azure.request_url(&endpoint(config.clone())),
+            "https://api.example.com/openai/deployments/chat/chat/completions?api-version=2024-06-01"
+        );
+        assert_eq!(azure.auth_headers(&endpoint(config)), vec![("api-key", "secret".to_string())]);
+
+        // Without a configured or requested model the request cannot be sent
+        let prompt_only = self::request(json!({"prompt": "Hello"}));
+        assert!(matches!(openai.encode_request(&prompt_only), Err(Error::Routing(_))));
+        let body: Value = serde_json::from_slice(&azure.encode_request(&prompt_only).unwrap()).unwrap();
+        assert_eq!(body["messages"], json!([{"role": "user", "content": "Hello"}]));
+    }
+
+    #[test]
+    fn test_translates_provider_errors() {
+        let anthropic = provider_for(&CloudProviderConfig::Anthropic {
+            model: None,
+            version: None,
+            max_tokens: None,
+        });
+        let error = anthropic.translate_error(
+            529,
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        );
+        assert!(matches!(&error, Error::Network(message) if message.contains("Overloaded")));
+        assert!(fails_over(&error));
+
+        let openai = provider_for(&CloudProviderConfig::OpenAi { model: None });
+        let error = openai.translate_error(429, r#"{"error":{"message":"Rate limit reached"}}"#);
+        assert!(matches!(error, Error::ResourceExhausted(_)));
+        assert!(matches!(openai.translate_error(401, "{}"), Error::Security(_)));
+        let error = openai.translate_error(400, r#"{"error":{"message":"Bad messages"}}"#);
+        assert!(matches!(&error, Error::InvalidRequest(message) if message.contains("Bad messages")));
+        assert!(!fails_over(&error));
+
+        let mcp = provider_for(&CloudProviderConfig::Mcp);
+        assert!(matches!(mcp.translate_error(503, "unavailable"), Error::Network(_)));
+    }
+}
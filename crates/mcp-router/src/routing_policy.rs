@@ -0,0 +1,358 @@
+//! User-defined routing rules
+//!
+//! Rules from `router.policy` are checked in order before the heuristic
+//! router; the first whose conditions all hold decides whether a request
+//! runs locally, goes to the cloud or is queued. Conditions cover the method,
+//! the size of the params, the estimated prompt tokens, the device's battery
+//! charge, the detected language and the local time of day. In dry-run mode the rule that would
+//! fire is only logged and counted, so a policy can be checked against live
+//! traffic before it takes over.
+
+use chrono::Timelike;
+use mcp_common::config::{parse_time_of_day, RoutingPolicyConfig, RoutingRule};
+use mcp_common::MCPRequest;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Characters per token assumed when estimating prompt tokens
+const CHARS_PER_TOKEN: u64 = 4;
+
+/// How long a battery reading is reused
+const BATTERY_READING_TTL: Duration = Duration::from_secs(30);
+
+/// Facts about a request and the device that rules are matched against
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestFacts {
+    pub params_bytes: usize,
+    pub tokens: u64,
+    pub battery_percent: Option<f64>,
+    /// Language detected in the request's text
+    pub language: Option<String>,
+    /// Local time, in minutes past midnight
+    pub minute_of_day: u32,
+}
+
+impl RequestFacts {
+    pub fn new(request: &MCPRequest, battery_percent: Option<f64>, minute_of_day: u32) -> Self {
+        Self {
+            params_bytes: serde_json::to_vec(&request.params).map_or(0, |params| params.len()),
+            tokens: request.params.values().map(text_len).sum::<u64>() / CHARS_PER_TOKEN,
+            battery_percent,
+            language: request.language().map(str::to_string),
+            minute_of_day,
+        }
+    }
+}
+
+/// Characters of text in a JSON value
+fn text_len(value: &serde_json::Value) -> u64 {
+    match value {
+        serde_json::Value::String(text) => text.len() as u64,
+        serde_json::Value::Array(values) => values.iter().map(text_len).sum(),
+        serde_json::Value::Object(fields) => fields.values().map(text_len).sum(),
+        _ => 0,
+    }
+}
+
+fn within<T: PartialOrd>(value: T, min: Option<T>, max: Option<T>) -> bool {
+    min.map_or(true, |min| value >= min) && max.map_or(true, |max| value <= max)
+}
+
+/// Whether all of a rule's conditions hold
+pub fn matches(rule: &RoutingRule, method: &str, facts: &RequestFacts) -> bool {
+    if !rule.methods.is_empty() && !rule.methods.iter().any(|m| m == method) {
+        return false;
+    }
+    if !within(
+        facts.params_bytes,
+        rule.min_params_bytes,
+        rule.max_params_bytes,
+    ) || !within(facts.tokens, rule.min_tokens, rule.max_tokens)
+    {
+        return false;
+    }
+    if rule.min_battery_percent.is_some() || rule.max_battery_percent.is_some() {
+        match facts.battery_percent {
+            Some(battery)
+                if within(battery, rule.min_battery_percent, rule.max_battery_percent) => {},
+            _ => return false,
+        }
+    }
+    if !rule.languages.is_empty() || !rule.excluded_languages.is_empty() {
+        let listed = |languages: &[String], language: &str| languages.iter().any(|l| l == language);
+        match facts.language.as_deref() {
+            Some(language)
+                if (rule.languages.is_empty() || listed(&rule.languages, language))
+                    && !listed(&rule.excluded_languages, language) => {},
+            _ => return false,
+        }
+    }
+    let window = rule
+        .from
+        .as_deref()
+        .and_then(parse_time_of_day)
+        .zip(rule.until.as_deref().and_then(parse_time_of_day));
+    match window {
+        Some((from, until)) if from <= until => (from..until).contains(&facts.minute_of_day),
+        Some((from, until)) => facts.minute_of_day >= from || facts.minute_of_day < until,
+        None => true,
+    }
+}
+
+/// Rules from `router.policy` with counts of the requests each matched
+pub struct RoutingPolicy {
+    config: RoutingPolicyConfig,
+    matched: Vec<AtomicU64>,
+    battery: Mutex<Option<(Instant, Option<f64>)>>,
+}
+
+impl RoutingPolicy {
+    pub fn new(config: &RoutingPolicyConfig) -> Self {
+        Self {
+            matched: config.rules.iter().map(|_| AtomicU64::new(0)).collect(),
+            config: config.clone(),
+            battery: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled && !self.config.rules.is_empty()
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.config.dry_run
+    }
+
+    /// First rule matching the request now
+    pub fn evaluate(&self, request: &MCPRequest) -> Option<&RoutingRule> {
+        if !self.enabled() {
+            return None;
+        }
+        let needs_battery =
+            self.config.rules.iter().any(|rule| {
+                rule.min_battery_percent.is_some() || rule.max_battery_percent.is_some()
+            });
+        let battery = if needs_battery {
+            self.battery_percent()
+        } else {
+            None
+        };
+        let now = chrono::Local::now();
+        let facts = RequestFacts::new(request, battery, now.hour() * 60 + now.minute());
+        self.evaluate_facts(&request.method, &facts)
+    }
+
+    /// First rule matching a request with the given facts
+    pub fn evaluate_facts(&self, method: &str, facts: &RequestFacts) -> Option<&RoutingRule> {
+        let index = self
+            .config
+            .rules
+            .iter()
+            .position(|rule| matches(rule, method, facts))?;
+        self.matched[index].fetch_add(1, Ordering::Relaxed);
+        Some(&self.config.rules[index])
+    }
+
+    /// Battery charge, reusing a recent reading
+    fn battery_percent(&self) -> Option<f64> {
+        let mut reading = self
+            .battery
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match *reading {
+            Some((read_at, battery)) if read_at.elapsed() < BATTERY_READING_TTL => battery,
+            _ => {
+                let battery = read_battery(&self.config.power_supply_dir);
+                *reading = Some((Instant::now(), battery));
+                battery
+            },
+        }
+    }
+
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        if !self.enabled() {
+            return;
+        }
+        let total: u64 = self
+            .matched
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum();
+        let key = if self.config.dry_run {
+            "policy_dry_run_matches"
+        } else {
+            "policy_matches"
+        };
+        metrics.insert(key.to_string(), total as f32);
+        metrics.insert("policy_rules".to_string(), self.config.rules.len() as f32);
+    }
+
+    /// Requests matched by each rule, by rule name
+    pub fn matches_by_rule(&self) -> Vec<(String, u64)> {
+        self.config
+            .rules
+            .iter()
+            .zip(&self.matched)
+            .map(|(rule, count)| (rule.name.clone(), count.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+/// Charge of the first battery among the kernel's power supplies
+pub fn read_battery(dir: &Path) -> Option<f64> {
+    let mut supplies: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    supplies.sort();
+    supplies
+        .into_iter()
+        .filter(|supply| {
+            std::fs::read_to_string(supply.join("type")).is_ok_and(|kind| kind.trim() == "Battery")
+        })
+        .find_map(|supply| {
+            std::fs::read_to_string(supply.join("capacity"))
+                .ok()?
+                .trim()
+                .parse()
+                .ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::config::RoutingTarget;
+
+    fn rule(name: &str, target: RoutingTarget) -> RoutingRule {
+        RoutingRule {
+            name: name.to_string(),
+            target,
+            ..Default::default()
+        }
+    }
+
+    fn facts(tokens: u64, battery_percent: Option<f64>, minute_of_day: u32) -> RequestFacts {
+        RequestFacts {
+            params_bytes: tokens as usize * 4,
+            tokens,
+            battery_percent,
+            language: None,
+            minute_of_day,
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let config = RoutingPolicyConfig {
+            enabled: true,
+            rules: vec![
+                RoutingRule {
+                    max_battery_percent: Some(20.0),
+                    ..rule("low-battery", RoutingTarget::Cloud)
+                },
+                RoutingRule {
+                    methods: vec!["completion".to_string()],
+                    min_tokens: Some(1000),
+                    ..rule("long-prompts", RoutingTarget::Cloud)
+                },
+                RoutingRule {
+                    from: Some("22:00".to_string()),
+                    until: Some("06:00".to_string()),
+                    ..rule("overnight", RoutingTarget::Queue)
+                },
+            ],
+            ..Default::default()
+        };
+        let policy = RoutingPolicy::new(&config);
+        let fired = |method: &str, facts: RequestFacts| {
+            policy
+                .evaluate_facts(method, &facts)
+                .map(|rule| rule.name.clone())
+        };
+
+        assert_eq!(
+            fired("completion", facts(10, Some(15.0), 720)).as_deref(),
+            Some("low-battery")
+        );
+        // Mains-powered devices never match battery conditions
+        assert_eq!(fired("completion", facts(10, None, 720)), None);
+        assert_eq!(
+            fired("completion", facts(1500, Some(80.0), 720)).as_deref(),
+            Some("long-prompts")
+        );
+        assert_eq!(fired("chat", facts(1500, Some(80.0), 720)), None);
+        // The overnight window wraps past midnight
+        assert_eq!(
+            fired("chat", facts(10, None, 23 * 60)).as_deref(),
+            Some("overnight")
+        );
+        assert_eq!(
+            fired("chat", facts(10, None, 5 * 60 + 59)).as_deref(),
+            Some("overnight")
+        );
+        assert_eq!(fired("chat", facts(10, None, 6 * 60)), None);
+        assert_eq!(
+            policy.matches_by_rule(),
+            vec![
+                ("low-battery".to_string(), 1),
+                ("long-prompts".to_string(), 1),
+                ("overnight".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_language_conditions() {
+        let config = RoutingPolicyConfig {
+            enabled: true,
+            rules: vec![RoutingRule {
+                excluded_languages: vec!["en".to_string()],
+                ..rule("non-english-to-cloud", RoutingTarget::Cloud)
+            }],
+            ..Default::default()
+        };
+        let policy = RoutingPolicy::new(&config);
+        let with_language = |language: Option<&str>| RequestFacts {
+            language: language.map(str::to_string),
+            ..facts(10, None, 720)
+        };
+        assert!(policy.evaluate_facts("chat", &with_language(Some("de"))).is_some());
+        assert!(policy.evaluate_facts("chat", &with_language(Some("en"))).is_none());
+        // Text too short to tell is not routed as if it were foreign
+        assert!(policy.evaluate_facts("chat", &with_language(None)).is_none());
+    }
+
+    #[test]
+    fn test_reads_battery_and_estimates_tokens() {
+        let dir = std::env::temp_dir().join(format!("power-supply-{}", uuid::Uuid::new_v4()));
+        for (name, kind, capacity) in [("AC", "Mains", "100"), ("BAT0", "Battery", "42")] {
+            let supply = dir.join(name);
+            std::fs::create_dir_all(&supply).unwrap();
+            std::fs::write(supply.join("type"), format!("{}\n", kind)).unwrap();
+            std::fs::write(supply.join("capacity"), format!("{}\n", capacity)).unwrap();
+        }
+        assert_eq!(read_battery(&dir), Some(42.0));
+        assert_eq!(read_battery(&dir.join("missing")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let request = MCPRequest {
+            id: uuid::Uuid::new_v4(),
+            device_id: "sensor-1".to_string(),
+            method: "chat".to_string(),
+            params: HashMap::from([(
+                "messages".to_string(),
+                serde_json::json!([{"role": "user", "content": "x".repeat(396)}]),
+            )]),
+            context: None,
+            timestamp: chrono::Utc::now(),
+        };
+        let facts = RequestFacts::new(&request, None, 0);
+        assert_eq!(facts.tokens, 100);
+        assert!(facts.params_bytes > 396);
+    }
+}
@@ -0,0 +1,315 @@
+//! Warm standby connection to the primary cloud endpoint
+//!
+//! Cloud fallback is rare on a healthy edge node, so the pooled connection to
+//! the cloud has usually been closed by the time it is needed and the first
+//! fallback pays for DNS, TCP and TLS setup on top of the request itself. The
+//! standby periodically sends a cheap `HEAD` through the same client, which
+//! keeps a connection (or HTTP/2 session) to the primary endpoint in the pool.
+//!
+//! Refreshing costs traffic, which matters on metered links: refreshes stop
+//! after `idle_timeout_secs` without cloud traffic and resume with the next
+//! forwarded request, and back off while the endpoint is unreachable.
+//! First-byte latency of forwarded requests is recorded separately for warm
+//! and cold connections so the saving is visible.
+
+use mcp_common::config::{CloudEndpoint, WarmStandbyConfig};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Timeout for a refresh when the endpoint has no connect timeout of its own
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Keeps the connection to one cloud endpoint warm
+pub(crate) struct WarmStandby {
+    config: WarmStandbyConfig,
+    client: Client,
+    endpoint: CloudEndpoint,
+    refresh_timeout: Duration,
+    state: Mutex<StandbyState>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct StandbyState {
+    last_used: Instant,
+    last_refresh: Option<Instant>,
+    refreshes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    idle: bool,
+    warm: FirstByte,
+    cold: FirstByte,
+}
+
+#[derive(Default)]
+struct FirstByte {
+    requests: u64,
+    total: Duration,
+}
+
+impl FirstByte {
+    fn avg_ms(&self) -> f32 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.total.as_secs_f32() * 1000.0 / self.requests as f32
+    }
+}
+
+impl WarmStandby {
+    pub(crate) fn new(
+        config: WarmStandbyConfig,
+        client: Client,
+        endpoint: CloudEndpoint,
+        connect_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            config,
+            client,
+            endpoint,
+            refresh_timeout: connect_timeout.map_or(REFRESH_TIMEOUT, |timeout| timeout * 2),
+            state: Mutex::new(StandbyState {
+                last_used: Instant::now(),
+                last_refresh: None,
+                refreshes: 0,
+                failures: 0,
+                consecutive_failures: 0,
+                idle: false,
+                warm: FirstByte::default(),
+                cold: FirstByte::default(),
+            }),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// URL of the endpoint kept warm
+    pub(crate) fn url(&self) -> &str {
+        &self.endpoint.url
+    }
+
+    /// How long an unused pooled connection should be kept
+    pub(crate) fn pool_idle_timeout(config: &WarmStandbyConfig) -> Duration {
+        Duration::from_secs(config.refresh_interval_secs.saturating_mul(3))
+    }
+
+    /// Start refreshing in the background
+    pub(crate) fn start(self: &Arc<Self>) {
+        let standby = Arc::downgrade(self);
+        let handle = tokio::spawn(async move {
+            let mut delay = Duration::ZERO;
+            loop {
+                tokio::time::sleep(delay).await;
+                let Some(standby) = standby.upgrade() else {
+                    break;
+                };
+                if standby.is_idle() {
+                    delay = standby.interval();
+                    continue;
+                }
+                standby.refresh().await;
+                delay = standby.next_delay();
+            }
+        });
+        if let Some(previous) = lock(&self.task).replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stop refreshing
+    pub(crate) fn stop(&self) {
+        if let Some(handle) = lock(&self.task).take() {
+            handle.abort();
+        }
+    }
+
+    /// Open or reuse the pooled connection with a `HEAD` request.
+    ///
+    /// Any HTTP response means the connection is up; only transport errors
+    /// count as failures.
+    pub(crate) async fn refresh(&self) -> bool {
+        let mut request = self.client.head(&self.endpoint.url).timeout(self.refresh_timeout);
+        if let Some(api_key) = &self.endpoint.api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        let result = request.send().await;
+
+        let mut state = lock(&self.state);
+        match result {
+            Ok(_) => {
+                state.refreshes += 1;
+                state.consecutive_failures = 0;
+                state.last_refresh = Some(Instant::now());
+                true
+            },
+            Err(e) => {
+                state.failures += 1;
+                state.consecutive_failures += 1;
+                state.last_refresh = None;
+                if state.consecutive_failures == 1 {
+                    warn!("Warm standby connection to {} failed: {}", self.endpoint.name, e);
+                } else {
+                    debug!("Warm standby connection to {} still failing: {}", self.endpoint.name, e);
+                }
+                false
+            },
+        }
+    }
+
+    /// Whether a recent refresh left a connection in the pool
+    pub(crate) fn is_warm(&self) -> bool {
+        let state = lock(&self.state);
+        state
+            .last_refresh
+            .is_some_and(|refreshed| refreshed.elapsed() < Self::pool_idle_timeout(&self.config))
+    }
+
+    /// Record the time to first byte of a request forwarded to this endpoint
+    pub(crate) fn record_request(&self, first_byte: Duration) {
+        let warm = self.is_warm();
+        let mut state = lock(&self.state);
+        state.last_used = Instant::now();
+        if state.idle {
+            debug!("Cloud traffic to {} resumed, warming connection again", self.endpoint.name);
+            state.idle = false;
+        }
+        let bucket = if warm { &mut state.warm } else { &mut state.cold };
+        bucket.requests += 1;
+        bucket.total += first_byte;
+    }
+
+    /// Render standby state into router health metrics
+    pub(crate) fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let warm = self.is_warm();
+        let state = lock(&self.state);
+        metrics.insert("warm_standby_warm".to_string(), if warm { 1.0 } else { 0.0 });
+        metrics.insert("warm_standby_idle".to_string(), if state.idle { 1.0 } else { 0.0 });
+        metrics.insert("warm_standby_refreshes_total".to_string(), state.refreshes as f32);
+        metrics.insert("warm_standby_failures_total".to_string(), state.failures as f32);
+        metrics.insert("cloud_first_byte_ms_warm_avg".to_string(), state.warm.avg_ms());
+        metrics.insert("cloud_first_byte_ms_cold_avg".to_string(), state.cold.avg_ms());
+        metrics.insert("cloud_requests_warm_total".to_string(), state.warm.requests as f32);
+        metrics.insert("cloud_requests_cold_total".to_string(), state.cold.requests as f32);
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.config.refresh_interval_secs.max(1))
+    }
+
+    /// Whether refreshing is paused for lack of cloud traffic
+    fn is_idle(&self) -> bool {
+        let Some(idle_timeout) = self.config.idle_timeout_secs else {
+            return false;
+        };
+        let mut state = lock(&self.state);
+        if !state.idle && state.last_used.elapsed() >= Duration::from_secs(idle_timeout) {
+            debug!("No cloud traffic to {} for {}s, letting connection go cold", self.endpoint.name, idle_timeout);
+            state.idle = true;
+            state.last_refresh = None;
+        }
+        state.idle
+    }
+
+    /// Refresh interval, doubled for every consecutive failure up to the backoff cap
+    fn next_delay(&self) -> Duration {
+        let failures = lock(&self.state).consecutive_failures.min(16);
+        let backoff = self.interval().saturating_mul(1 << failures);
+        backoff.min(Duration::from_secs(self.config.max_backoff_secs).max(self.interval()))
+    }
+}
+
+impl Drop for WarmStandby {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn endpoint(url: String) -> CloudEndpoint {
+        CloudEndpoint {
+            name: "primary".to_string(),
+            url,
+            api_key: None,
+            timeout_ms: 5000,
+            max_retries: 0,
+            connect_timeout_ms: None,
+            region: None,
+            provider: Default::default(),
+            compression: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_reuses_one_pooled_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1/mcp", listener.local_addr().unwrap());
+        let accepted = tokio::spawn(async move {
+            let mut connections = 0;
+            while let Ok(Ok((mut socket, _))) =
+                tokio::time::timeout(Duration::from_millis(500), listener.accept()).await
+            {
+                connections += 1;
+                tokio::spawn(async move {
+                    let mut buffer = [0u8; 1024];
+                    while let Ok(read) = socket.read(&mut buffer).await {
+                        if read == 0 {
+                            break;
+                        }
+                        let response = "HTTP/1.1 405 Method Not Allowed\r\ncontent-length: 0\r\n\r\n";
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            connections
+        });
+
+        let standby = WarmStandby::new(WarmStandbyConfig::default(), Client::new(), endpoint(url), None);
+        assert!(!standby.is_warm());
+        assert!(standby.refresh().await);
+        assert!(standby.refresh().await);
+        assert!(standby.is_warm());
+
+        standby.record_request(Duration::from_millis(12));
+        let mut metrics = HashMap::new();
+        standby.write_metrics(&mut metrics);
+        assert_eq!(metrics["warm_standby_refreshes_total"], 2.0);
+        assert_eq!(metrics["cloud_requests_warm_total"], 1.0);
+        assert_eq!(accepted.await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failures_back_off_and_idle_pauses_refreshes() {
+        let config = WarmStandbyConfig {
+            refresh_interval_secs: 10,
+            idle_timeout_secs: Some(0),
+            max_backoff_secs: 60,
+            ..Default::default()
+        };
+        // Nothing listens on the discard port of localhost
+        let standby = WarmStandby::new(config, Client::new(), endpoint("http://127.0.0.1:9".to_string()), None);
+        assert!(!standby.refresh().await);
+        assert!(!standby.refresh().await);
+        assert_eq!(standby.next_delay(), Duration::from_secs(40));
+        assert!(!standby.refresh().await);
+        assert!(!standby.refresh().await);
+        assert_eq!(standby.next_delay(), Duration::from_secs(60));
+
+        assert!(standby.is_idle());
+        standby.record_request(Duration::from_millis(80));
+        assert!(!lock(&standby.state).idle);
+        assert_eq!(lock(&standby.state).cold.requests, 1);
+    }
+}
@@ -0,0 +1,678 @@
+//! Device key and platform attestation
+//!
+//! The gateway holds a P-256 device key. It signs outbound cloud requests
+//! and quotes the platform's SHA-256 PCRs for remote attestation: a verifier
+//! sends a fresh nonce and receives a TPMS_ATTEST structure binding the nonce
+//! to a digest of the PCR values, signed by the device key.
+//!
+//! With the `hardware-security` feature and `security.tpm_enabled`, the key
+//! is a primary key in the TPM 2.0 owner hierarchy. It is derived from the
+//! TPM's seed, so it is the same on every start and never leaves the chip,
+//! and quotes come from TPM2_Quote. Without a TPM, when the fallback is
+//! allowed, a software key builds the same structure over simulated all-zero
+//! PCRs and quotes are marked `simulated`, so CI and development machines
+//! exercise the same code paths as fielded devices.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use mcp_common::config::{AttestationConfig, SecurityConfig};
+use mcp_common::crypto::digest;
+use mcp_common::crypto::rand::SystemRandom;
+use mcp_common::crypto::signature::{
+    EcdsaKeyPair, KeyPair, UnparsedPublicKey, ECDSA_P256_SHA256_FIXED, ECDSA_P256_SHA256_FIXED_SIGNING,
+};
+use mcp_common::encoding::hex;
+use mcp_common::request_signing::RequestSigner;
+use mcp_common::{Error, Result, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Longest nonce a quote can carry (the TPM's TPM2B_DATA limit)
+pub const MAX_NONCE_BYTES: usize = 64;
+
+/// `TPM_GENERATED_VALUE`, the magic every TPMS_ATTEST starts with
+const TPM_GENERATED: u32 = 0xff54_4347;
+/// `TPM_ST_ATTEST_QUOTE`
+const ATTEST_QUOTE: u16 = 0x8018;
+/// `TPM_ALG_SHA256`
+const ALG_SHA256: u16 = 0x000b;
+
+/// Signed quote over the platform's PCRs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformQuote {
+    /// `tpm2`, or `software` for the fallback key
+    pub provider: String,
+    /// Whether the PCR values are simulated rather than measured by a TPM
+    pub simulated: bool,
+    pub key_id: String,
+    /// Base64 uncompressed SEC1 P-256 public key
+    pub public_key: String,
+    /// Hex SHA-256 PCR values by index
+    pub pcrs: BTreeMap<u8, String>,
+    /// Base64 TPMS_ATTEST structure the signature covers
+    pub attest: String,
+    /// Base64 ECDSA P-256 SHA-256 signature, `r || s`
+    pub signature: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PlatformQuote {
+    /// Check the signature, and that the quote covers `nonce` and the
+    /// reported PCR values
+    pub fn verify(&self, nonce: &[u8]) -> Result<()> {
+        let invalid = |reason: &str| Error::Security(format!("Invalid platform quote: {}", reason));
+        let decode = |field: &str, value: &str| BASE64.decode(value).map_err(|_| invalid(field));
+        let public_key = decode("public_key", &self.public_key)?;
+        let attest = decode("attest", &self.attest)?;
+        let signature = decode("signature", &self.signature)?;
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, &public_key)
+            .verify(&attest, &signature)
+            .map_err(|_| invalid("signature does not match"))?;
+
+        let quoted = parse_quote(&attest).ok_or_else(|| invalid("malformed TPMS_ATTEST"))?;
+        if quoted.nonce != nonce {
+            return Err(invalid("nonce does not match"));
+        }
+        if !quoted.pcrs.iter().eq(self.pcrs.keys()) {
+            return Err(invalid("PCR selection does not match"));
+        }
+        let mut values = Vec::new();
+        for value in self.pcrs.values() {
+            values.push(from_hex(value).ok_or_else(|| invalid("PCR value is not hex"))?);
+        }
+        if quoted.pcr_digest != pcr_digest(&values) {
+            return Err(invalid("PCR values do not match the quoted digest"));
+        }
+        Ok(())
+    }
+}
+
+/// PCR values by index, the TPMS_ATTEST bytes and their signature
+type Quote = (BTreeMap<u8, Vec<u8>>, Vec<u8>, Vec<u8>);
+
+/// Signing key backing the attestor
+trait DeviceKey: Send + Sync {
+    fn provider(&self) -> &'static str;
+
+    /// Uncompressed SEC1 public key
+    fn public_key(&self) -> &[u8];
+
+    /// ECDSA P-256 SHA-256 signature, `r || s`
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>>;
+
+    fn quote(&self, nonce: &[u8], pcrs: &[u8]) -> Result<Quote>;
+}
+
+/// Device key and quotes over the platform state
+pub struct DeviceAttestor {
+    key: Arc<dyn DeviceKey>,
+    key_id: String,
+    pcrs: Vec<u8>,
+}
+
+impl DeviceAttestor {
+    /// Open the TPM key when enabled, or the software key
+    pub async fn load(config: &SecurityConfig, vfs: Arc<dyn Vfs>) -> Result<Self> {
+        let attestation = &config.attestation;
+        let key: Arc<dyn DeviceKey> = if config.tpm_enabled {
+            match open_tpm(attestation) {
+                Ok(key) => key,
+                Err(e) if attestation.software_fallback => {
+                    warn!("TPM unavailable, using a software device key: {}", e);
+                    Arc::new(SoftwareKey::load(attestation, vfs.as_ref()).await?)
+                },
+                Err(e) => return Err(e),
+            }
+        } else {
+            Arc::new(SoftwareKey::load(attestation, vfs.as_ref()).await?)
+        };
+
+        let key_id = hex(&digest::digest(&digest::SHA256, key.public_key()).as_ref()[..16]);
+        info!("Device key {} held by the {} provider", key_id, key.provider());
+        Ok(Self {
+            key,
+            key_id,
+            pcrs: attestation.pcrs.clone(),
+        })
+    }
+
+    /// `tpm2` or `software`
+    pub fn provider(&self) -> &'static str {
+        self.key.provider()
+    }
+
+    /// Whether quotes report simulated PCRs
+    pub fn simulated(&self) -> bool {
+        self.key.provider() != "tpm2"
+    }
+
+    /// Hex identifier derived from the public key
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Uncompressed SEC1 P-256 public key
+    pub fn public_key(&self) -> &[u8] {
+        self.key.public_key()
+    }
+
+    /// Quote the configured PCRs with a verifier's nonce
+    pub async fn quote(&self, nonce: &[u8]) -> Result<PlatformQuote> {
+        if nonce.is_empty() || nonce.len() > MAX_NONCE_BYTES {
+            return Err(Error::InvalidRequest(format!(
+                "Attestation nonce must be 1 to {} bytes",
+                MAX_NONCE_BYTES
+            )));
+        }
+        let key = Arc::clone(&self.key);
+        let (nonce_owned, pcrs) = (nonce.to_vec(), self.pcrs.clone());
+        let (values, attest, signature) = tokio::task::spawn_blocking(move || key.quote(&nonce_owned, &pcrs))
+            .await
+            .map_err(|e| Error::Internal(format!("Quote task failed: {}", e)))??;
+
+        Ok(PlatformQuote {
+            provider: self.provider().to_string(),
+            simulated: self.simulated(),
+            key_id: self.key_id.clone(),
+            public_key: BASE64.encode(self.public_key()),
+            pcrs: values.into_iter().map(|(index, value)| (index, hex(&value))).collect(),
+            attest: BASE64.encode(attest),
+            signature: BASE64.encode(signature),
+            created_at: Utc::now(),
+        })
+    }
+
+    /// Signer for outbound requests, backed by the device key
+    pub fn signer(&self) -> Arc<dyn RequestSigner> {
+        Arc::new(DeviceKeySigner {
+            key: Arc::clone(&self.key),
+            key_id: self.key_id.clone(),
+        })
+    }
+
+    /// Render the provider into component health metrics
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        metrics.insert("attestation_hardware_backed".to_string(), if self.simulated() { 0.0 } else { 1.0 });
+    }
+}
+
+struct DeviceKeySigner {
+    key: Arc<dyn DeviceKey>,
+    key_id: String,
+}
+
+impl RequestSigner for DeviceKeySigner {
+    fn key_id(&self) -> String {
+        self.key_id.clone()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        self.key.sign(message)
+    }
+}
+
+#[cfg(feature = "hardware-security")]
+fn open_tpm(config: &AttestationConfig) -> Result<Arc<dyn DeviceKey>> {
+    Ok(Arc::new(tpm::TpmKey::open(&config.tcti)?))
+}
+
+#[cfg(not(feature = "hardware-security"))]
+fn open_tpm(_config: &AttestationConfig) -> Result<Arc<dyn DeviceKey>> {
+    Err(Error::Configuration(
+        "security.tpm_enabled needs the gateway built with the hardware-security feature".to_string(),
+    ))
+}
+
+/// P-256 key held in memory, quoting simulated PCRs
+struct SoftwareKey {
+    key: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl SoftwareKey {
+    async fn load(config: &AttestationConfig, vfs: &dyn Vfs) -> Result<Self> {
+        let rng = SystemRandom::new();
+        let generate = || {
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                .map_err(|_| Error::Security("Failed to generate device key".to_string()))
+        };
+        let pkcs8 = match &config.software_key_path {
+            Some(path) if vfs.exists(path).await => vfs.read(path).await?,
+            Some(path) => {
+                let pkcs8 = generate()?;
+                if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                    vfs.create_dir_all(parent).await?;
+                }
+                vfs.write(path, pkcs8.as_ref()).await?;
+                info!("Generated software device key at {:?}", path);
+                pkcs8.as_ref().to_vec()
+            },
+            None => generate()?.as_ref().to_vec(),
+        };
+        let key = ecdsa_key(&pkcs8, &rng, config.software_key_path.as_deref())?;
+        Ok(Self { key, rng })
+    }
+}
+
+#[cfg(feature = "fips")]
+fn ecdsa_key(pkcs8: &[u8], _rng: &SystemRandom, path: Option<&Path>) -> Result<EcdsaKeyPair> {
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8)
+        .map_err(|e| Error::Security(format!("Invalid device key {:?}: {}", path, e)))
+}
+
+#[cfg(not(feature = "fips"))]
+fn ecdsa_key(pkcs8: &[u8], rng: &SystemRandom, path: Option<&Path>) -> Result<EcdsaKeyPair> {
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8, rng)
+        .map_err(|e| Error::Security(format!("Invalid device key {:?}: {}", path, e)))
+}
+
+impl DeviceKey for SoftwareKey {
+    fn provider(&self) -> &'static str {
+        "software"
+    }
+
+    fn public_key(&self) -> &[u8] {
+        self.key.public_key().as_ref()
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        self.key
+            .sign(&self.rng, message)
+            .map(|signature| signature.as_ref().to_vec())
+            .map_err(|_| Error::Security("Device key signing failed".to_string()))
+    }
+
+    fn quote(&self, nonce: &[u8], pcrs: &[u8]) -> Result<Quote> {
+        let values: BTreeMap<u8, Vec<u8>> = pcrs.iter().map(|&index| (index, vec![0u8; 32])).collect();
+        let digest = pcr_digest(&values.values().cloned().collect::<Vec<_>>());
+        let attest = build_quote(nonce, values.keys().copied(), &digest);
+        let signature = self.sign(&attest)?;
+        Ok((values, attest, signature))
+    }
+}
+
+/// Fields of a TPMS_ATTEST quote a verifier checks
+struct QuotedState {
+    nonce: Vec<u8>,
+    pcrs: Vec<u8>,
+    pcr_digest: Vec<u8>,
+}
+
+/// Digest the TPM computes over the concatenated PCR values
+fn pcr_digest(values: &[Vec<u8>]) -> Vec<u8> {
+    let mut context = digest::Context::new(&digest::SHA256);
+    for value in values {
+        context.update(value);
+    }
+    context.finish().as_ref().to_vec()
+}
+
+/// TPMS_ATTEST for a quote of SHA-256 PCRs, as TPM2_Quote produces it
+fn build_quote(nonce: &[u8], pcrs: impl Iterator<Item = u8>, digest: &[u8]) -> Vec<u8> {
+    let mut select = [0u8; 3];
+    for index in pcrs {
+        select[usize::from(index / 8)] |= 1 << (index % 8);
+    }
+    let mut attest = Vec::new();
+    attest.extend_from_slice(&TPM_GENERATED.to_be_bytes());
+    attest.extend_from_slice(&ATTEST_QUOTE.to_be_bytes());
+    attest.extend_from_slice(&0u16.to_be_bytes()); // qualifiedSigner
+    attest.extend_from_slice(&(nonce.len() as u16).to_be_bytes());
+    attest.extend_from_slice(nonce);
+    attest.extend_from_slice(&[0u8; 17]); // clockInfo
+    attest.extend_from_slice(&[0u8; 8]); // firmwareVersion
+    attest.extend_from_slice(&1u32.to_be_bytes());
+    attest.extend_from_slice(&ALG_SHA256.to_be_bytes());
+    attest.push(select.len() as u8);
+    attest.extend_from_slice(&select);
+    attest.extend_from_slice(&(digest.len() as u16).to_be_bytes());
+    attest.extend_from_slice(digest);
+    attest
+}
+
+/// Read the nonce, selected SHA-256 PCRs and PCR digest from a quote
+fn parse_quote(attest: &[u8]) -> Option<QuotedState> {
+    let mut reader = Reader(attest);
+    if reader.u32()? != TPM_GENERATED || reader.u16()? != ATTEST_QUOTE {
+        return None;
+    }
+    reader.sized()?; // qualifiedSigner
+    let nonce = reader.sized()?.to_vec();
+    reader.take(17 + 8)?; // clockInfo, firmwareVersion
+
+    let mut pcrs = Vec::new();
+    for _ in 0..reader.u32()? {
+        let algorithm = reader.u16()?;
+        let size = usize::from(*reader.take(1)?.first()?);
+        let select = reader.take(size)?;
+        if algorithm != ALG_SHA256 {
+            return None;
+        }
+        for (byte, bits) in select.iter().enumerate() {
+            pcrs.extend((0..8).filter(|bit| bits & (1 << bit) != 0).map(|bit| (byte * 8 + bit) as u8));
+        }
+    }
+    let pcr_digest = reader.sized()?.to_vec();
+    Some(QuotedState { nonce, pcrs, pcr_digest })
+}
+
+/// Big-endian reader over TPM structures
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    /// A TPM2B: 16-bit length, then the bytes
+    fn sized(&mut self) -> Option<&'a [u8]> {
+        let len = usize::from(self.u16()?);
+        self.take(len)
+    }
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(feature = "hardware-security")]
+mod tpm {
+    //! Device key held by a TPM 2.0 through the TSS2 ESAPI
+
+    use super::{DeviceKey, Quote};
+    use mcp_common::crypto::digest;
+    use mcp_common::{Error, Result};
+    use std::str::FromStr;
+    use std::sync::mpsc;
+    use std::thread;
+    use tss_esapi::attributes::ObjectAttributesBuilder;
+    use tss_esapi::constants::tss::{TPM2_RH_NULL, TPM2_ST_HASHCHECK};
+    use tss_esapi::handles::KeyHandle;
+    use tss_esapi::interface_types::algorithm::{HashingAlgorithm, PublicAlgorithm};
+    use tss_esapi::interface_types::ecc::EccCurve;
+    use tss_esapi::interface_types::resource_handles::Hierarchy;
+    use tss_esapi::structures::{
+        Data, Digest, EccPoint, EccScheme, HashScheme, HashcheckTicket, KeyDerivationFunctionScheme,
+        PcrSelectionListBuilder, PcrSlot, Public, PublicBuilder, PublicEccParametersBuilder, Signature,
+        SignatureScheme,
+    };
+    use tss_esapi::tss2_esys::TPMT_TK_HASHCHECK;
+    use tss_esapi::traits::Marshall;
+    use tss_esapi::{Context, TctiNameConf};
+
+    enum Command {
+        Sign(Vec<u8>, mpsc::Sender<Result<Vec<u8>>>),
+        Quote(Vec<u8>, Vec<u8>, mpsc::Sender<Result<Quote>>),
+    }
+
+    /// ESAPI contexts cannot move between threads, so one thread owns the
+    /// context and serves commands sent to it
+    pub(super) struct TpmKey {
+        commands: std::sync::Mutex<mpsc::Sender<Command>>,
+        public_key: Vec<u8>,
+    }
+
+    impl TpmKey {
+        pub(super) fn open(tcti: &str) -> Result<Self> {
+            let tcti = TctiNameConf::from_str(tcti)
+                .map_err(|e| Error::Configuration(format!("Invalid TPM TCTI '{}': {}", tcti, e)))?;
+            let (commands, receiver) = mpsc::channel();
+            let (ready, opened) = mpsc::channel();
+            thread::Builder::new()
+                .name("tpm".to_string())
+                .spawn(move || {
+                    let (mut context, key) = match open_context(tcti) {
+                        Ok((context, key, public_key)) => {
+                            let _ = ready.send(Ok(public_key));
+                            (context, key)
+                        },
+                        Err(e) => {
+                            let _ = ready.send(Err(e));
+                            return;
+                        },
+                    };
+                    for command in receiver {
+                        match command {
+                            Command::Sign(message, reply) => {
+                                let _ = reply.send(sign(&mut context, key, &message));
+                            },
+                            Command::Quote(nonce, pcrs, reply) => {
+                                let _ = reply.send(quote(&mut context, key, &nonce, &pcrs));
+                            },
+                        }
+                    }
+                })
+                .map_err(|e| Error::Internal(format!("Failed to start TPM thread: {}", e)))?;
+            let public_key = opened
+                .recv()
+                .map_err(|_| Error::Security("TPM thread exited during startup".to_string()))??;
+            Ok(Self {
+                commands: std::sync::Mutex::new(commands),
+                public_key,
+            })
+        }
+
+        fn call<T>(&self, command: impl FnOnce(mpsc::Sender<Result<T>>) -> Command) -> Result<T> {
+            let (reply, response) = mpsc::channel();
+            self.commands
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .send(command(reply))
+                .map_err(|_| Error::Security("TPM thread has stopped".to_string()))?;
+            response
+                .recv()
+                .map_err(|_| Error::Security("TPM thread has stopped".to_string()))?
+        }
+    }
+
+    impl DeviceKey for TpmKey {
+        fn provider(&self) -> &'static str {
+            "tpm2"
+        }
+
+        fn public_key(&self) -> &[u8] {
+            &self.public_key
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+            self.call(|reply| Command::Sign(message.to_vec(), reply))
+        }
+
+        fn quote(&self, nonce: &[u8], pcrs: &[u8]) -> Result<Quote> {
+            self.call(|reply| Command::Quote(nonce.to_vec(), pcrs.to_vec(), reply))
+        }
+    }
+
+    fn tpm_error(operation: &str) -> impl Fn(tss_esapi::Error) -> Error + '_ {
+        move |e| Error::Security(format!("TPM {} failed: {}", operation, e))
+    }
+
+    /// Connect and create the device key as an owner-hierarchy primary,
+    /// which the TPM derives from its seed identically on every start
+    fn open_context(tcti: TctiNameConf) -> Result<(Context, KeyHandle, Vec<u8>)> {
+        let mut context = Context::new(tcti).map_err(tpm_error("connect"))?;
+        let attributes = ObjectAttributesBuilder::new()
+            .with_fixed_tpm(true)
+            .with_fixed_parent(true)
+            .with_sensitive_data_origin(true)
+            .with_user_with_auth(true)
+            .with_sign_encrypt(true)
+            .build()
+            .map_err(tpm_error("key template"))?;
+        let parameters = PublicEccParametersBuilder::new()
+            .with_ecc_scheme(EccScheme::EcDsa(HashScheme::new(HashingAlgorithm::Sha256)))
+            .with_curve(EccCurve::NistP256)
+            .with_is_signing_key(true)
+            .with_is_decryption_key(false)
+            .with_restricted(false)
+            .with_key_derivation_function_scheme(KeyDerivationFunctionScheme::Null)
+            .build()
+            .map_err(tpm_error("key template"))?;
+        let template = PublicBuilder::new()
+            .with_public_algorithm(PublicAlgorithm::Ecc)
+            .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+            .with_object_attributes(attributes)
+            .with_ecc_parameters(parameters)
+            .with_ecc_unique_identifier(EccPoint::default())
+            .build()
+            .map_err(tpm_error("key template"))?;
+
+        let primary = context
+            .execute_with_nullauth_session(|context| {
+                context.create_primary(Hierarchy::Owner, template, None, None, None, None)
+            })
+            .map_err(tpm_error("key creation"))?;
+        let public_key = match &primary.out_public {
+            Public::Ecc { unique, .. } => {
+                let mut point = vec![0x04];
+                point.extend_from_slice(&left_pad(unique.x().value()));
+                point.extend_from_slice(&left_pad(unique.y().value()));
+                point
+            },
+            _ => return Err(Error::Security("TPM returned a non-ECC device key".to_string())),
+        };
+        Ok((context, primary.key_handle, public_key))
+    }
+
+    fn sign(context: &mut Context, key: KeyHandle, message: &[u8]) -> Result<Vec<u8>> {
+        let hash = digest::digest(&digest::SHA256, message);
+        let digest = Digest::try_from(hash.as_ref()).map_err(tpm_error("sign"))?;
+        let validation = HashcheckTicket::try_from(TPMT_TK_HASHCHECK {
+            tag: TPM2_ST_HASHCHECK,
+            hierarchy: TPM2_RH_NULL,
+            digest: Default::default(),
+        })
+        .map_err(tpm_error("sign"))?;
+        let signature = context
+            .execute_with_nullauth_session(|context| context.sign(key, digest, SignatureScheme::Null, validation))
+            .map_err(tpm_error("sign"))?;
+        fixed_signature(signature)
+    }
+
+    fn quote(context: &mut Context, key: KeyHandle, nonce: &[u8], pcrs: &[u8]) -> Result<Quote> {
+        let mut slots = Vec::new();
+        for &index in pcrs {
+            slots.push(PcrSlot::try_from(1u32 << index).map_err(tpm_error("PCR selection"))?);
+        }
+        let selection = PcrSelectionListBuilder::new()
+            .with_selection(HashingAlgorithm::Sha256, &slots)
+            .build()
+            .map_err(tpm_error("PCR selection"))?;
+        let qualifying_data = Data::try_from(nonce.to_vec()).map_err(tpm_error("quote"))?;
+
+        let (attest, signature) = context
+            .execute_with_nullauth_session(|context| {
+                context.quote(key, qualifying_data, SignatureScheme::Null, selection.clone())
+            })
+            .map_err(tpm_error("quote"))?;
+        // PCRs are read after quoting; a verifier rejects the pair if one
+        // was extended in between
+        let (_, _, digests) = context.pcr_read(selection).map_err(tpm_error("PCR read"))?;
+
+        let mut sorted = pcrs.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let values = sorted
+            .into_iter()
+            .zip(digests.value().iter().map(|digest| digest.value().to_vec()))
+            .collect();
+        let attest = attest.marshall().map_err(tpm_error("quote"))?;
+        Ok((values, attest, fixed_signature(signature)?))
+    }
+
+    fn fixed_signature(signature: Signature) -> Result<Vec<u8>> {
+        match signature {
+            Signature::EcDsa(signature) => {
+                let mut fixed = left_pad(signature.signature_r().value());
+                fixed.extend_from_slice(&left_pad(signature.signature_s().value()));
+                Ok(fixed)
+            },
+            _ => Err(Error::Security("TPM returned a non-ECDSA signature".to_string())),
+        }
+    }
+
+    /// P-256 coordinates and signature halves padded to 32 bytes
+    fn left_pad(value: &[u8]) -> Vec<u8> {
+        let mut padded = vec![0u8; 32usize.saturating_sub(value.len())];
+        padded.extend_from_slice(value);
+        padded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::vfs::MemoryVfs;
+
+    async fn software_attestor(vfs: Arc<dyn Vfs>) -> DeviceAttestor {
+        let mut config = SecurityConfig {
+            tpm_enabled: true,
+            ..mcp_common::Config::default().security
+        };
+        config.attestation.software_key_path = Some("/keys/device.pk8".into());
+        DeviceAttestor::load(&config, vfs).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_software_quote_verifies_and_binds_nonce() {
+        let vfs: Arc<dyn Vfs> = Arc::new(MemoryVfs::new());
+        let attestor = software_attestor(vfs.clone()).await;
+        assert!(attestor.simulated());
+
+        let quote = attestor.quote(b"verifier-nonce-1").await.unwrap();
+        assert_eq!(quote.pcrs.len(), 8);
+        quote.verify(b"verifier-nonce-1").unwrap();
+        assert!(quote.verify(b"verifier-nonce-2").is_err());
+
+        let mut tampered = quote.clone();
+        tampered.pcrs.insert(0, hex(&[1u8; 32]));
+        assert!(tampered.verify(b"verifier-nonce-1").is_err());
+
+        // The key is kept, so the device identity survives restarts
+        assert_eq!(software_attestor(vfs).await.key_id(), attestor.key_id());
+        assert!(attestor.quote(&[0u8; MAX_NONCE_BYTES + 1]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signs_outbound_requests() {
+        let attestor = software_attestor(Arc::new(MemoryVfs::new())).await;
+        mcp_common::request_signing::install(Some(attestor.signer()));
+        let headers: HashMap<_, _> = mcp_common::request_signing::signature_headers(b"{\"id\":1}")
+            .await
+            .into_iter()
+            .collect();
+        mcp_common::request_signing::install(None);
+
+        let timestamp = &headers[mcp_common::request_signing::GATEWAY_TIMESTAMP_HEADER];
+        let signature = BASE64
+            .decode(&headers[mcp_common::request_signing::GATEWAY_SIGNATURE_HEADER])
+            .unwrap();
+        assert_eq!(headers[mcp_common::request_signing::GATEWAY_KEY_HEADER], attestor.key_id());
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, attestor.public_key())
+            .verify(format!("{}.{{\"id\":1}}", timestamp).as_bytes(), &signature)
+            .unwrap();
+    }
+}
@@ -0,0 +1,301 @@
+//! Brute-force protection and behavioral anomaly scoring
+//!
+//! Failed authentications are tracked per device and per source address. A
+//! principal that fails too often within the window is locked out, and each
+//! further lockout doubles in length up to a cap, so a slow brute force gets
+//! slower while a device with a mistyped key recovers quickly.
+//!
+//! Each device also builds a behavioral baseline from its request mix and
+//! request timing. Once enough requests have been seen, every request is
+//! scored by how far it deviates (an unusual method, a burst well above the
+//! usual rate) and the score maps onto a [`ThreatSeverity`].
+
+use crate::standard_security::ThreatSeverity;
+use chrono::{DateTime, Duration, Utc};
+use mcp_common::config::AuthProtectionConfig;
+use mcp_common::{Error, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+/// Weight of the newest interval in the timing baseline
+const TIMING_ALPHA: f64 = 0.05;
+
+/// Kind of principal tracked for authentication failures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrincipalKind {
+    Device,
+    Ip,
+}
+
+/// A principal with recent failures or an active lockout
+#[derive(Debug, Clone, Serialize)]
+pub struct ThrottledPrincipal {
+    pub principal: String,
+    pub kind: PrincipalKind,
+    pub recent_failures: usize,
+    /// Lockouts so far; each doubles the next lockout
+    pub lockouts: u32,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+/// Deviation of one request from its device's baseline
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreatAssessment {
+    pub device_id: String,
+    pub score: f32,
+    pub level: ThreatSeverity,
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct FailureTracker {
+    failures: Vec<DateTime<Utc>>,
+    lockouts: u32,
+    locked_until: Option<DateTime<Utc>>,
+    last_failure: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+struct DeviceBaseline {
+    requests: u64,
+    methods: HashMap<String, u64>,
+    last_seen: Option<DateTime<Utc>>,
+    /// EWMA of ln(seconds between requests) and its variance
+    interval_mean: f64,
+    interval_var: f64,
+    timed_intervals: u64,
+}
+
+/// Authentication failure lockouts and device behavior baselines
+pub struct AuthGuard {
+    config: AuthProtectionConfig,
+    trackers: RwLock<HashMap<(PrincipalKind, String), FailureTracker>>,
+    baselines: RwLock<HashMap<String, DeviceBaseline>>,
+}
+
+impl AuthGuard {
+    pub fn new(config: AuthProtectionConfig) -> Self {
+        Self {
+            config,
+            trackers: RwLock::new(HashMap::new()),
+            baselines: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn principals<'a>(device_id: &'a str, ip: Option<&'a str>) -> Vec<(PrincipalKind, &'a str)> {
+        let mut principals = vec![(PrincipalKind::Device, device_id)];
+        if let Some(ip) = ip {
+            principals.push((PrincipalKind::Ip, ip));
+        }
+        principals
+    }
+
+    /// Reject requests from a locked-out device or address
+    pub async fn check(&self, device_id: &str, ip: Option<&str>) -> Result<()> {
+        let now = Utc::now();
+        let trackers = self.trackers.read().await;
+        for (kind, principal) in Self::principals(device_id, ip) {
+            let locked_until = trackers
+                .get(&(kind, principal.to_string()))
+                .and_then(|tracker| tracker.locked_until)
+                .filter(|until| *until > now);
+            if let Some(until) = locked_until {
+                return Err(Error::Security(format!(
+                    "{:?} {} is locked out for {}s after repeated authentication failures",
+                    kind,
+                    principal,
+                    (until - now).num_seconds().max(1)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a failed authentication, locking out principals over the limit
+    pub async fn record_failure(&self, device_id: &str, ip: Option<&str>) {
+        let now = Utc::now();
+        let window_start = now - Duration::seconds(self.config.failure_window_secs as i64);
+        let mut trackers = self.trackers.write().await;
+        for (kind, principal) in Self::principals(device_id, ip) {
+            let tracker = trackers.entry((kind, principal.to_string())).or_default();
+            // Forget earlier lockouts after a long enough quiet period
+            let quiet_since = now - Duration::seconds(self.config.max_lockout_secs as i64);
+            if tracker.last_failure.is_some_and(|last| last < quiet_since) {
+                tracker.lockouts = 0;
+            }
+            tracker.last_failure = Some(now);
+            tracker.failures.retain(|failure| *failure > window_start);
+            tracker.failures.push(now);
+
+            if tracker.failures.len() >= self.config.max_failures.max(1) as usize {
+                let factor = 1u64 << tracker.lockouts.min(20);
+                let lockout_secs = self
+                    .config
+                    .base_lockout_secs
+                    .saturating_mul(factor)
+                    .min(self.config.max_lockout_secs);
+                tracker.lockouts += 1;
+                tracker.failures.clear();
+                tracker.locked_until = Some(now + Duration::seconds(lockout_secs as i64));
+                warn!(
+                    "{:?} {} locked out for {}s after repeated authentication failures (lockout {})",
+                    kind, principal, lockout_secs, tracker.lockouts
+                );
+            }
+        }
+    }
+
+    /// Clear failure counts after a successful authentication
+    pub async fn record_success(&self, device_id: &str, ip: Option<&str>) {
+        let mut trackers = self.trackers.write().await;
+        for (kind, principal) in Self::principals(device_id, ip) {
+            if let Some(tracker) = trackers.get_mut(&(kind, principal.to_string())) {
+                tracker.failures.clear();
+            }
+        }
+    }
+
+    /// Principals with recent failures or an active lockout
+    pub async fn throttled(&self) -> Vec<ThrottledPrincipal> {
+        let now = Utc::now();
+        let window_start = now - Duration::seconds(self.config.failure_window_secs as i64);
+        let trackers = self.trackers.read().await;
+        let mut throttled: Vec<ThrottledPrincipal> = trackers
+            .iter()
+            .filter_map(|((kind, principal), tracker)| {
+                let recent_failures = tracker.failures.iter().filter(|failure| **failure > window_start).count();
+                let locked_until = tracker.locked_until.filter(|until| *until > now);
+                (recent_failures > 0 || locked_until.is_some()).then(|| ThrottledPrincipal {
+                    principal: principal.clone(),
+                    kind: *kind,
+                    recent_failures,
+                    lockouts: tracker.lockouts,
+                    locked_until,
+                })
+            })
+            .collect();
+        throttled.sort_by(|a, b| b.locked_until.cmp(&a.locked_until).then_with(|| a.principal.cmp(&b.principal)));
+        throttled
+    }
+
+    /// Score a request against the device baseline, then fold it into the baseline
+    pub async fn observe(&self, device_id: &str, method: &str, at: DateTime<Utc>) -> ThreatAssessment {
+        let threshold = self.config.anomaly_threshold.max(f32::EPSILON);
+        let mut baselines = self.baselines.write().await;
+        let baseline = baselines.entry(device_id.to_string()).or_default();
+
+        let interval = baseline
+            .last_seen
+            .map(|last| ((at - last).num_milliseconds().max(1) as f64 / 1000.0).ln());
+        let mut score = 0.0f32;
+        let mut reasons = Vec::new();
+
+        if baseline.requests >= self.config.baseline_min_requests {
+            // Rare methods score close to the threshold, unseen ones at it
+            let seen = baseline.methods.get(method).copied().unwrap_or(0);
+            let probability = (seen + 1) as f64 / (baseline.requests + baseline.methods.len() as u64 + 1) as f64;
+            let mix_score = (threshold as f64 * -probability.ln() / ((baseline.requests + 1) as f64).ln()) as f32;
+            if seen == 0 {
+                reasons.push(format!("method {} never seen from this device", method));
+            }
+            score = score.max(mix_score);
+
+            // Only bursts count; long gaps are normal for intermittently connected devices
+            if let Some(interval) = interval.filter(|_| baseline.timed_intervals > 0) {
+                let std_dev = baseline.interval_var.sqrt().max(0.25);
+                let timing_score = ((baseline.interval_mean - interval) / std_dev).max(0.0) as f32;
+                if timing_score >= threshold {
+                    reasons.push(format!("request rate {:.1} standard deviations above baseline", timing_score));
+                }
+                score = score.max(timing_score);
+            }
+        }
+
+        baseline.requests += 1;
+        *baseline.methods.entry(method.to_string()).or_insert(0) += 1;
+        baseline.last_seen = Some(at);
+        if let Some(interval) = interval {
+            if baseline.timed_intervals == 0 {
+                baseline.interval_mean = interval;
+                baseline.interval_var = 1.0;
+            } else {
+                let delta = interval - baseline.interval_mean;
+                baseline.interval_mean += TIMING_ALPHA * delta;
+                baseline.interval_var = (1.0 - TIMING_ALPHA) * (baseline.interval_var + TIMING_ALPHA * delta * delta);
+            }
+            baseline.timed_intervals += 1;
+        }
+
+        let level = if score < threshold / 2.0 {
+            ThreatSeverity::Low
+        } else if score < threshold {
+            ThreatSeverity::Medium
+        } else if score < threshold * 2.0 {
+            ThreatSeverity::High
+        } else {
+            ThreatSeverity::Critical
+        };
+        ThreatAssessment {
+            device_id: device_id.to_string(),
+            score,
+            level,
+            reasons,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lockouts_double_and_cover_device_and_ip() {
+        let guard = AuthGuard::new(AuthProtectionConfig {
+            max_failures: 2,
+            ..AuthProtectionConfig::default()
+        });
+        guard.record_failure("sensor-1", Some("10.0.0.5")).await;
+        guard.record_success("sensor-1", Some("10.0.0.5")).await;
+        guard.record_failure("sensor-1", Some("10.0.0.5")).await;
+        assert!(guard.check("sensor-1", None).await.is_ok());
+
+        guard.record_failure("sensor-1", Some("10.0.0.5")).await;
+        assert!(guard.check("sensor-1", None).await.is_err());
+        // Another device behind the same address is locked out too
+        assert!(guard.check("sensor-2", Some("10.0.0.5")).await.is_err());
+        assert!(guard.check("sensor-2", Some("10.0.0.6")).await.is_ok());
+
+        let first = guard.throttled().await[0].locked_until.unwrap();
+        guard.record_failure("sensor-1", None).await;
+        guard.record_failure("sensor-1", None).await;
+        let throttled = guard.throttled().await;
+        let device = throttled.iter().find(|t| t.kind == PrincipalKind::Device).unwrap();
+        assert_eq!(device.lockouts, 2);
+        let second = device.locked_until.unwrap();
+        assert!(second - first >= Duration::seconds(29));
+    }
+
+    #[tokio::test]
+    async fn test_bursts_and_unusual_methods_score_as_threats() {
+        let guard = AuthGuard::new(AuthProtectionConfig::default());
+        let start = Utc::now();
+        let mut at = start;
+        for i in 0..60 {
+            at = start + Duration::seconds(10 * i);
+            let assessment = guard.observe("camera-3", "completion", at).await;
+            assert_eq!(assessment.level, ThreatSeverity::Low);
+        }
+
+        let normal = guard.observe("camera-3", "completion", at + Duration::seconds(10)).await;
+        assert_eq!(normal.level, ThreatSeverity::Low);
+
+        let burst = guard
+            .observe("camera-3", "retrieval.index", at + Duration::milliseconds(10_050))
+            .await;
+        assert!(burst.level >= ThreatSeverity::High, "scored {:?}", burst);
+        assert_eq!(burst.reasons.len(), 2);
+    }
+}
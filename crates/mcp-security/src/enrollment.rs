@@ -0,0 +1,605 @@
+//! Certificate-based device enrollment
+//!
+//! New devices enroll by presenting a single-use bootstrap token and their
+//! Ed25519 public key. The gateway issues an X.509 client certificate for the
+//! key, signed by the fleet CA, which devices use for mTLS at a TLS
+//! terminator trusting the exported CA certificate. Enrolled devices must sign
+//! their requests to the gateway itself with the same key, so a device id
+//! alone no longer identifies a device once it has a certificate.
+//!
+//! Decommissioned devices are revoked: their serials are published in a
+//! denylist, their requests are rejected and they cannot enroll again.
+//!
+//! The fleet CA key, its certificate and the device registry are kept on the
+//! storage backend; the CA key is generated on first start.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Datelike, Duration, Utc};
+use mcp_common::config::EnrollmentConfig;
+use mcp_common::crypto::digest;
+use mcp_common::crypto::rand::{SecureRandom, SystemRandom};
+use mcp_common::crypto::signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+use mcp_common::encoding::hex;
+use mcp_common::redaction;
+use mcp_common::{Error, Result, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Header carrying the base64 Ed25519 signature over `{timestamp}.{body}`
+pub const DEVICE_SIGNATURE_HEADER: &str = "X-MCP-Device-Signature";
+
+/// Header carrying the unix timestamp included in the signature
+pub const DEVICE_TIMESTAMP_HEADER: &str = "X-MCP-Device-Timestamp";
+
+/// Validity of the fleet CA certificate
+const CA_VALIDITY_DAYS: i64 = 3650;
+
+/// Enrollment request from a new device
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnrollmentRequest {
+    pub device_id: String,
+    pub bootstrap_token: String,
+    /// Raw 32-byte Ed25519 public key, base64-encoded
+    pub public_key: String,
+}
+
+/// Certificate issued to an enrolled device
+#[derive(Debug, Clone, Serialize)]
+pub struct EnrollmentResponse {
+    pub device_id: String,
+    pub serial: String,
+    pub certificate_pem: String,
+    pub ca_certificate_pem: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Admin request to decommission a device
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RevokeRequest {
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Registry entry for an enrolled device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrolledDevice {
+    pub device_id: String,
+    /// Base64 Ed25519 public key
+    pub public_key: String,
+    /// Hex serial of the current certificate
+    pub serial: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revocation: Option<Revocation>,
+}
+
+/// Why and when a device was decommissioned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revocation {
+    pub revoked_at: DateTime<Utc>,
+    pub reason: String,
+}
+
+/// Denylist entry
+#[derive(Debug, Clone, Serialize)]
+pub struct RevokedCertificate {
+    pub serial: String,
+    pub device_id: String,
+    pub revoked_at: DateTime<Utc>,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Registry {
+    devices: HashMap<String, EnrolledDevice>,
+    /// SHA-256 of bootstrap tokens already used
+    used_tokens: HashSet<String>,
+}
+
+/// Fleet CA and registry of enrolled devices
+pub struct DeviceEnrollment {
+    config: EnrollmentConfig,
+    vfs: Arc<dyn Vfs>,
+    ca_key: Ed25519KeyPair,
+    ca_certificate: Vec<u8>,
+    registry: RwLock<Registry>,
+    rng: SystemRandom,
+}
+
+impl DeviceEnrollment {
+    /// Load the fleet CA and registry, creating the CA key on first start
+    pub async fn load(config: EnrollmentConfig, vfs: Arc<dyn Vfs>) -> Result<Self> {
+        let rng = SystemRandom::new();
+        let pkcs8 = if vfs.exists(&config.ca_key_path).await {
+            vfs.read(&config.ca_key_path).await?
+        } else {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng)
+                .map_err(|_| Error::Security("Failed to generate fleet CA key".to_string()))?;
+            write_file(vfs.as_ref(), &config.ca_key_path, pkcs8.as_ref()).await?;
+            info!("Generated fleet CA key at {:?}", config.ca_key_path);
+            pkcs8.as_ref().to_vec()
+        };
+        let ca_key = Ed25519KeyPair::from_pkcs8(&pkcs8)
+            .map_err(|e| Error::Security(format!("Invalid fleet CA key {:?}: {}", config.ca_key_path, e)))?;
+
+        let now = Utc::now();
+        let ca_certificate = build_certificate(
+            &ca_key,
+            &random_serial(&rng)?,
+            &config.ca_name,
+            &config.ca_name,
+            ca_key.public_key().as_ref(),
+            now - Duration::hours(1),
+            now + Duration::days(CA_VALIDITY_DAYS),
+            true,
+        );
+        write_file(
+            vfs.as_ref(),
+            &config.ca_certificate_path,
+            pem("CERTIFICATE", &ca_certificate).as_bytes(),
+        )
+        .await?;
+
+        let registry = if vfs.exists(&config.registry_path).await {
+            serde_json::from_slice(&vfs.read(&config.registry_path).await?)?
+        } else {
+            Registry::default()
+        };
+        info!("Device enrollment ready with {} enrolled devices", registry.devices.len());
+
+        Ok(Self {
+            config,
+            vfs,
+            ca_key,
+            ca_certificate,
+            registry: RwLock::new(registry),
+            rng,
+        })
+    }
+
+    pub fn config(&self) -> &EnrollmentConfig {
+        &self.config
+    }
+
+    pub fn ca_certificate_pem(&self) -> String {
+        pem("CERTIFICATE", &self.ca_certificate)
+    }
+
+    /// Issue a certificate for a device presenting a valid bootstrap token
+    pub async fn enroll(&self, request: EnrollmentRequest) -> Result<EnrollmentResponse> {
+        if request.device_id.is_empty() || request.device_id.len() > 128 {
+            return Err(Error::InvalidRequest("Device ID must be 1-128 characters".to_string()));
+        }
+        let public_key = BASE64
+            .decode(request.public_key.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| Error::InvalidRequest("Public key must be a base64 32-byte Ed25519 key".to_string()))?;
+
+        let token_hash = sha256_hex(request.bootstrap_token.as_bytes());
+        let valid_token = self
+            .config
+            .bootstrap_tokens
+            .iter()
+            .any(|token| sha256_hex(token.as_bytes()) == token_hash);
+
+        let mut registry = self.registry.write().await;
+        if !valid_token || registry.used_tokens.contains(&token_hash) {
+            warn!("Rejected enrollment of {}: invalid or used bootstrap token", redaction::id(&request.device_id));
+            return Err(Error::Security("Invalid bootstrap token".to_string()));
+        }
+        if registry
+            .devices
+            .get(&request.device_id)
+            .is_some_and(|device| device.revocation.is_some())
+        {
+            warn!("Rejected enrollment of revoked device {}", redaction::id(&request.device_id));
+            return Err(Error::Security(format!("Device {} has been revoked", request.device_id)));
+        }
+
+        let now = Utc::now();
+        let expires_at = now + Duration::days(self.config.certificate_validity_days as i64);
+        let serial = random_serial(&self.rng)?;
+        let certificate = build_certificate(
+            &self.ca_key,
+            &serial,
+            &self.config.ca_name,
+            &request.device_id,
+            &public_key,
+            now - Duration::minutes(5),
+            expires_at,
+            false,
+        );
+        let device = EnrolledDevice {
+            device_id: request.device_id.clone(),
+            public_key: BASE64.encode(&public_key),
+            serial: hex(&serial),
+            issued_at: now,
+            expires_at,
+            revocation: None,
+        };
+
+        registry.used_tokens.insert(token_hash);
+        registry.devices.insert(request.device_id.clone(), device.clone());
+        self.save(&registry).await?;
+        info!("Enrolled device {} with certificate {}", redaction::id(&device.device_id), device.serial);
+
+        Ok(EnrollmentResponse {
+            device_id: device.device_id,
+            serial: device.serial,
+            certificate_pem: pem("CERTIFICATE", &certificate),
+            ca_certificate_pem: self.ca_certificate_pem(),
+            expires_at,
+        })
+    }
+
+    /// Registry entry for a device, including revoked ones
+    pub async fn device(&self, device_id: &str) -> Option<EnrolledDevice> {
+        self.registry.read().await.devices.get(device_id).cloned()
+    }
+
+    /// Every enrolled device, including revoked ones
+    pub async fn devices(&self) -> Vec<EnrolledDevice> {
+        let mut devices: Vec<EnrolledDevice> = self.registry.read().await.devices.values().cloned().collect();
+        devices.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        devices
+    }
+
+    /// Whether a device holds a current, unrevoked certificate
+    pub async fn is_enrolled(&self, device_id: &str) -> bool {
+        self.registry
+            .read()
+            .await
+            .devices
+            .get(device_id)
+            .is_some_and(|device| device.revocation.is_none() && device.expires_at > Utc::now())
+    }
+
+    pub async fn is_revoked(&self, device_id: &str) -> bool {
+        self.registry
+            .read()
+            .await
+            .devices
+            .get(device_id)
+            .is_some_and(|device| device.revocation.is_some())
+    }
+
+    /// Check a request signature made with the device's enrolled key
+    pub async fn verify_signature(&self, device_id: &str, timestamp: i64, body: &[u8], signature: &str) -> Result<()> {
+        let age = (Utc::now().timestamp() - timestamp).abs();
+        if age > self.config.max_signature_age_secs as i64 {
+            return Err(Error::Security(format!("Signature timestamp is {}s old", age)));
+        }
+        let device = self
+            .device(device_id)
+            .await
+            .ok_or_else(|| Error::Security(format!("Device {} is not enrolled", device_id)))?;
+        if device.revocation.is_some() {
+            return Err(Error::Security(format!("Device {} has been revoked", device_id)));
+        }
+        if device.expires_at <= Utc::now() {
+            return Err(Error::Security(format!("Certificate for device {} has expired", device_id)));
+        }
+
+        let public_key = BASE64
+            .decode(&device.public_key)
+            .map_err(|_| Error::Internal(format!("Corrupt public key for device {}", device_id)))?;
+        let signature = BASE64
+            .decode(signature.trim())
+            .map_err(|_| Error::Security("Signature is not valid base64".to_string()))?;
+        let mut message = format!("{}.", timestamp).into_bytes();
+        message.extend_from_slice(body);
+        UnparsedPublicKey::new(&signature::ED25519, &public_key)
+            .verify(&message, &signature)
+            .map_err(|_| Error::Security(format!("Invalid request signature for device {}", device_id)))
+    }
+
+    /// Decommission a device; returns false if it was never enrolled
+    pub async fn revoke(&self, device_id: &str, reason: &str) -> Result<bool> {
+        let mut registry = self.registry.write().await;
+        let Some(device) = registry.devices.get_mut(device_id) else {
+            return Ok(false);
+        };
+        if device.revocation.is_none() {
+            device.revocation = Some(Revocation {
+                revoked_at: Utc::now(),
+                reason: reason.to_string(),
+            });
+            warn!("Revoked certificate {} of device {}: {}", device.serial, redaction::id(device_id), reason);
+        }
+        self.save(&registry).await?;
+        Ok(true)
+    }
+
+    /// Store a registry entry replicated from a peer gateway sharing this
+    /// fleet CA. The newer certificate wins and a revocation is never undone;
+    /// returns whether the registry changed
+    pub async fn replicate(&self, device: EnrolledDevice) -> Result<bool> {
+        let mut registry = self.registry.write().await;
+        let changed = match registry.devices.get_mut(&device.device_id) {
+            Some(existing) if existing.serial == device.serial => {
+                let revoked = existing.revocation.is_none() && device.revocation.is_some();
+                if revoked {
+                    existing.revocation = device.revocation;
+                }
+                revoked
+            },
+            Some(existing) if existing.issued_at >= device.issued_at => false,
+            _ => {
+                registry.devices.insert(device.device_id.clone(), device);
+                true
+            },
+        };
+        if changed {
+            self.save(&registry).await?;
+        }
+        Ok(changed)
+    }
+
+    /// Denylist of revoked certificates
+    pub async fn revocations(&self) -> Vec<RevokedCertificate> {
+        let registry = self.registry.read().await;
+        let mut revoked: Vec<RevokedCertificate> = registry
+            .devices
+            .values()
+            .filter_map(|device| {
+                device.revocation.as_ref().map(|revocation| RevokedCertificate {
+                    serial: device.serial.clone(),
+                    device_id: device.device_id.clone(),
+                    revoked_at: revocation.revoked_at,
+                    reason: revocation.reason.clone(),
+                })
+            })
+            .collect();
+        revoked.sort_by_key(|revoked| revoked.revoked_at);
+        revoked
+    }
+
+    async fn save(&self, registry: &Registry) -> Result<()> {
+        write_file(self.vfs.as_ref(), &self.config.registry_path, &serde_json::to_vec_pretty(registry)?).await
+    }
+}
+
+async fn write_file(vfs: &dyn Vfs, path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        vfs.create_dir_all(parent).await?;
+    }
+    vfs.write(path, data).await
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, data).as_ref())
+}
+
+fn random_serial(rng: &SystemRandom) -> Result<Vec<u8>> {
+    let mut serial = vec![0u8; 16];
+    rng.fill(&mut serial)
+        .map_err(|_| Error::Security("Failed to generate certificate serial".to_string()))?;
+    // Positive and without a leading zero byte, as DER integers require
+    serial[0] = (serial[0] & 0x7f) | 0x40;
+    Ok(serial)
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = BASE64.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// Minimal DER encoding for the certificates issued here
+mod der {
+    pub const BOOLEAN: u8 = 0x01;
+    pub const INTEGER: u8 = 0x02;
+    pub const BIT_STRING: u8 = 0x03;
+    pub const OCTET_STRING: u8 = 0x04;
+    pub const OID: u8 = 0x06;
+    pub const UTF8_STRING: u8 = 0x0c;
+    pub const UTC_TIME: u8 = 0x17;
+    pub const GENERALIZED_TIME: u8 = 0x18;
+    pub const SEQUENCE: u8 = 0x30;
+    pub const SET: u8 = 0x31;
+
+    pub const OID_ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+    pub const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+    pub const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+    pub const OID_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+    pub const OID_EXT_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+    pub const OID_CLIENT_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
+
+    pub fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        let len = content.len();
+        if len < 0x80 {
+            out.push(len as u8);
+        } else {
+            let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+            out.push(0x80 | bytes.len() as u8);
+            out.extend(bytes);
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    pub fn seq(items: &[Vec<u8>]) -> Vec<u8> {
+        tlv(SEQUENCE, &items.concat())
+    }
+
+    pub fn bit_string(bytes: &[u8]) -> Vec<u8> {
+        let mut content = vec![0];
+        content.extend_from_slice(bytes);
+        tlv(BIT_STRING, &content)
+    }
+
+    /// `[n]` explicit context tag
+    pub fn explicit(n: u8, content: &[u8]) -> Vec<u8> {
+        tlv(0xa0 | n, content)
+    }
+}
+
+fn name(common_name: &str) -> Vec<u8> {
+    let attribute = der::seq(&[
+        der::tlv(der::OID, der::OID_COMMON_NAME),
+        der::tlv(der::UTF8_STRING, common_name.as_bytes()),
+    ]);
+    der::seq(&[der::tlv(der::SET, &attribute)])
+}
+
+fn time(at: DateTime<Utc>) -> Vec<u8> {
+    // RFC 5280: UTCTime through 2049, GeneralizedTime after
+    if at.year() < 2050 {
+        der::tlv(der::UTC_TIME, at.format("%y%m%d%H%M%SZ").to_string().as_bytes())
+    } else {
+        der::tlv(der::GENERALIZED_TIME, at.format("%Y%m%d%H%M%SZ").to_string().as_bytes())
+    }
+}
+
+fn extension(oid: &[u8], critical: bool, value: Vec<u8>) -> Vec<u8> {
+    let mut items = vec![der::tlv(der::OID, oid)];
+    if critical {
+        items.push(der::tlv(der::BOOLEAN, &[0xff]));
+    }
+    items.push(der::tlv(der::OCTET_STRING, &value));
+    der::seq(&items)
+}
+
+/// Build an X.509 v3 certificate for an Ed25519 key, signed by `issuer_key`
+#[allow(clippy::too_many_arguments)]
+fn build_certificate(
+    issuer_key: &Ed25519KeyPair,
+    serial: &[u8],
+    issuer: &str,
+    subject: &str,
+    public_key: &[u8],
+    not_before: DateTime<Utc>,
+    not_after: DateTime<Utc>,
+    is_ca: bool,
+) -> Vec<u8> {
+    let algorithm = der::seq(&[der::tlv(der::OID, der::OID_ED25519)]);
+    let extensions = if is_ca {
+        vec![
+            extension(der::OID_BASIC_CONSTRAINTS, true, der::seq(&[der::tlv(der::BOOLEAN, &[0xff])])),
+            // keyCertSign and cRLSign
+            extension(der::OID_KEY_USAGE, true, der::tlv(der::BIT_STRING, &[0x01, 0x06])),
+        ]
+    } else {
+        vec![
+            extension(der::OID_BASIC_CONSTRAINTS, true, der::seq(&[])),
+            // digitalSignature
+            extension(der::OID_KEY_USAGE, true, der::tlv(der::BIT_STRING, &[0x07, 0x80])),
+            extension(der::OID_EXT_KEY_USAGE, false, der::seq(&[der::tlv(der::OID, der::OID_CLIENT_AUTH)])),
+        ]
+    };
+
+    let tbs = der::seq(&[
+        der::explicit(0, &der::tlv(der::INTEGER, &[2])),
+        der::tlv(der::INTEGER, serial),
+        algorithm.clone(),
+        name(issuer),
+        der::seq(&[time(not_before), time(not_after)]),
+        name(subject),
+        der::seq(&[algorithm.clone(), der::bit_string(public_key)]),
+        der::explicit(3, &der::seq(&extensions)),
+    ]);
+    let signature = issuer_key.sign(&tbs);
+    der::seq(&[tbs, algorithm, der::bit_string(signature.as_ref())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::vfs::MemoryVfs;
+
+    async fn enrollment() -> DeviceEnrollment {
+        let config = EnrollmentConfig {
+            enabled: true,
+            bootstrap_tokens: vec!["line-3-token".to_string()],
+            ..EnrollmentConfig::default()
+        };
+        DeviceEnrollment::load(config, Arc::new(MemoryVfs::new())).await.unwrap()
+    }
+
+    fn device_key() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_enrolled_device_signs_requests_until_revoked() {
+        let enrollment = enrollment().await;
+        let key = device_key();
+        let request = EnrollmentRequest {
+            device_id: "press-12".to_string(),
+            bootstrap_token: "line-3-token".to_string(),
+            public_key: BASE64.encode(key.public_key().as_ref()),
+        };
+        let response = enrollment.enroll(request.clone()).await.unwrap();
+        assert!(response.certificate_pem.starts_with("-----BEGIN CERTIFICATE-----\n"));
+        // Bootstrap tokens are single-use
+        assert!(enrollment.enroll(request.clone()).await.is_err());
+
+        let body = br#"{"method":"completion","params":{}}"#;
+        let timestamp = Utc::now().timestamp();
+        let mut message = format!("{}.", timestamp).into_bytes();
+        message.extend_from_slice(body);
+        let signature = BASE64.encode(key.sign(&message).as_ref());
+        assert!(enrollment.verify_signature("press-12", timestamp, body, &signature).await.is_ok());
+        assert!(enrollment.verify_signature("press-12", timestamp, b"{}", &signature).await.is_err());
+        assert!(enrollment.verify_signature("press-12", timestamp - 3600, body, &signature).await.is_err());
+
+        assert!(enrollment.revoke("press-12", "decommissioned").await.unwrap());
+        assert!(enrollment.is_revoked("press-12").await);
+        assert!(enrollment.verify_signature("press-12", timestamp, body, &signature).await.is_err());
+        let revoked = enrollment.revocations().await;
+        assert_eq!(revoked[0].serial, response.serial);
+    }
+
+    #[tokio::test]
+    async fn test_certificate_chains_to_fleet_ca() {
+        let enrollment = enrollment().await;
+        let key = device_key();
+        let response = enrollment
+            .enroll(EnrollmentRequest {
+                device_id: "press-12".to_string(),
+                bootstrap_token: "line-3-token".to_string(),
+                public_key: BASE64.encode(key.public_key().as_ref()),
+            })
+            .await
+            .unwrap();
+
+        // The certificate is TBS, algorithm, signature; the signature covers the TBS bytes
+        let der: String = response
+            .certificate_pem
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        let der = BASE64.decode(der).unwrap();
+        // Header length and total length of the DER element at the start of `bytes`
+        let element = |bytes: &[u8]| match bytes[1] {
+            len if len < 0x80 => (2, len as usize + 2),
+            0x81 => (3, bytes[2] as usize + 3),
+            0x82 => (4, u16::from_be_bytes([bytes[2], bytes[3]]) as usize + 4),
+            _ => unreachable!("certificate length"),
+        };
+        assert_eq!(der[0], der::SEQUENCE);
+        let (header, _) = element(&der);
+        let (_, tbs_len) = element(&der[header..]);
+        let tbs = &der[header..header + tbs_len];
+        let signature = &der[der.len() - 64..];
+        UnparsedPublicKey::new(&signature::ED25519, enrollment.ca_key.public_key().as_ref())
+            .verify(tbs, signature)
+            .unwrap();
+        assert!(der.windows(8).any(|w| w == b"press-12"));
+        assert!(der.windows(key.public_key().as_ref().len()).any(|w| w == key.public_key().as_ref()));
+    }
+}
@@ -0,0 +1,483 @@
+//! Per-tenant data keys and crypto agility
+//!
+//! Each tenant's data is encrypted under its own 256-bit data key, so one
+//! tenant's key can be rotated or destroyed without touching the others. Data
+//! keys never leave memory in the clear: they are wrapped with the device
+//! master key and only the wrapped form is written to the registry.
+//!
+//! The AEAD comes from `security.encryption_algorithm`. AES-256-GCM is the
+//! fastest choice on CPUs with AES instructions but slow in software, which is
+//! the normal case on low-end ARM boards; ChaCha20-Poly1305 is fast everywhere
+//! without hardware support. `auto` picks by CPU. Every ciphertext records its
+//! algorithm, so data written before a switch stays readable.
+//!
+//! Key creation and every use are recorded in a bounded audit trail, and the
+//! latency of crypto operations is reported per algorithm.
+
+use chrono::{DateTime, Utc};
+use mcp_common::config::TenantKeysConfig;
+use mcp_common::crypto::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use mcp_common::crypto::rand::{SecureRandom, SystemRandom};
+use mcp_common::crypto::{self, FIPS_MODE};
+use mcp_common::{Error, Result, Vfs};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Tenant used for data not attributed to a tenant
+pub const DEFAULT_TENANT: &str = "default";
+
+/// AEAD used for data at rest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CryptoAlgorithm {
+    #[default]
+    #[serde(rename = "AES-256-GCM")]
+    Aes256Gcm,
+    #[serde(rename = "CHACHA20-POLY1305")]
+    ChaCha20Poly1305,
+}
+
+impl CryptoAlgorithm {
+    /// Algorithm for a configured name, resolving `auto` by CPU features;
+    /// FIPS builds only accept AES-256-GCM
+    pub fn negotiate(configured: &str) -> Result<Self> {
+        match configured.to_ascii_uppercase().as_str() {
+            "AES-256-GCM" => Ok(CryptoAlgorithm::Aes256Gcm),
+            "CHACHA20-POLY1305" if FIPS_MODE => Err(Error::Configuration(
+                "CHACHA20-POLY1305 is not an approved algorithm in FIPS mode".to_string(),
+            )),
+            "CHACHA20-POLY1305" => Ok(CryptoAlgorithm::ChaCha20Poly1305),
+            "AUTO" if FIPS_MODE || has_aes_hardware() => Ok(CryptoAlgorithm::Aes256Gcm),
+            "AUTO" => Ok(CryptoAlgorithm::ChaCha20Poly1305),
+            _ => Err(Error::Configuration(format!(
+                "security.encryption_algorithm must be AES-256-GCM, CHACHA20-POLY1305 or auto, got {}",
+                configured
+            ))),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CryptoAlgorithm::Aes256Gcm => "AES-256-GCM",
+            CryptoAlgorithm::ChaCha20Poly1305 => "CHACHA20-POLY1305",
+        }
+    }
+
+    fn metric_name(&self) -> &'static str {
+        match self {
+            CryptoAlgorithm::Aes256Gcm => "aes_256_gcm",
+            CryptoAlgorithm::ChaCha20Poly1305 => "chacha20_poly1305",
+        }
+    }
+
+    fn aead(&self) -> &'static aead::Algorithm {
+        match self {
+            CryptoAlgorithm::Aes256Gcm => &aead::AES_256_GCM,
+            CryptoAlgorithm::ChaCha20Poly1305 => &aead::CHACHA20_POLY1305,
+        }
+    }
+}
+
+/// Whether the CPU has AES instructions
+pub fn has_aes_hardware() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        std::arch::is_x86_feature_detected!("aes") && std::arch::is_x86_feature_detected!("pclmulqdq")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("aes")
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+/// Serialized ciphertext
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Envelope {
+    /// Absent in data written before algorithms were selectable
+    #[serde(default)]
+    algorithm: CryptoAlgorithm,
+    ciphertext: Vec<u8>,
+    nonce: [u8; 12],
+    tag: [u8; 16],
+}
+
+/// Registry entry: a tenant data key wrapped by the master key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WrappedKey {
+    wrapped: Envelope,
+    created_at: DateTime<Utc>,
+}
+
+/// Kind of key usage recorded in the audit trail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyOperation {
+    Create,
+    Encrypt,
+    Decrypt,
+    DecryptFailed,
+}
+
+/// Audit record of a key usage
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyUsage {
+    pub at: DateTime<Utc>,
+    pub tenant: String,
+    pub operation: KeyOperation,
+    pub algorithm: CryptoAlgorithm,
+}
+
+/// Tenant key as listed by the admin API; never includes key material
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantKeyInfo {
+    pub tenant: String,
+    pub created_at: DateTime<Utc>,
+    pub encryptions: u64,
+    pub decryptions: u64,
+}
+
+#[derive(Default)]
+struct OperationLatency {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+struct KeyringState {
+    /// Unwrapped data keys of tenants used since startup
+    keys: HashMap<String, [u8; 32]>,
+    wrapped: HashMap<String, WrappedKey>,
+    usage: HashMap<String, (u64, u64)>,
+    audit: VecDeque<KeyUsage>,
+    latency: HashMap<(CryptoAlgorithm, &'static str), OperationLatency>,
+}
+
+/// Per-tenant data keys wrapped by the device master key
+pub struct TenantKeyring {
+    config: TenantKeysConfig,
+    algorithm: CryptoAlgorithm,
+    master_key: [u8; 32],
+    vfs: Arc<dyn Vfs>,
+    rng: SystemRandom,
+    state: Mutex<KeyringState>,
+}
+
+impl TenantKeyring {
+    /// Load the master key and wrapped tenant keys, creating the master key
+    /// on first start
+    pub async fn load(config: TenantKeysConfig, algorithm: &str, vfs: Arc<dyn Vfs>) -> Result<Self> {
+        let algorithm = CryptoAlgorithm::negotiate(algorithm)?;
+        let rng = SystemRandom::new();
+        let master_key = match &config.master_key_path {
+            Some(path) if vfs.exists(path).await => vfs
+                .read(path)
+                .await?
+                .try_into()
+                .map_err(|_| Error::Security(format!("Master key {:?} is not 32 bytes", path)))?,
+            Some(path) => {
+                let key = random_key(&rng)?;
+                write_file(vfs.as_ref(), path, &key).await?;
+                info!("Generated device master key at {:?}", path);
+                key
+            },
+            None if config.registry_path.is_some() => {
+                return Err(Error::Configuration(
+                    "security.tenant_keys.registry_path requires master_key_path".to_string(),
+                ));
+            },
+            None => random_key(&rng)?,
+        };
+        let wrapped = match &config.registry_path {
+            Some(path) if vfs.exists(path).await => serde_json::from_slice(&vfs.read(path).await?)?,
+            _ => HashMap::new(),
+        };
+        info!("Tenant keyring ready with {} keys, using {}", wrapped.len(), algorithm.as_str());
+
+        Ok(Self {
+            config,
+            algorithm,
+            master_key,
+            vfs,
+            rng,
+            state: Mutex::new(KeyringState {
+                keys: HashMap::new(),
+                wrapped,
+                usage: HashMap::new(),
+                audit: VecDeque::new(),
+                latency: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Algorithm used for new ciphertexts
+    pub fn algorithm(&self) -> CryptoAlgorithm {
+        self.algorithm
+    }
+
+    /// Encrypt data under the tenant's key, creating the key on first use
+    pub async fn encrypt(&self, tenant: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let mut state = self.state.lock().await;
+        let key = self.data_key(&mut state, tenant).await?;
+        let started = Instant::now();
+        let envelope = self.seal(self.algorithm, &key, tenant.as_bytes(), data)?;
+        record_latency(&mut state, self.algorithm, "encrypt", started.elapsed());
+        state.usage.entry(tenant.to_string()).or_default().0 += 1;
+        self.audit(&mut state, tenant, KeyOperation::Encrypt, self.algorithm);
+        Ok(serde_json::to_vec(&envelope)?)
+    }
+
+    /// Decrypt data written by [`encrypt`](Self::encrypt) for the same tenant
+    pub async fn decrypt(&self, tenant: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let envelope: Envelope = serde_json::from_slice(data)
+            .map_err(|e| Error::Security(format!("Failed to deserialize encrypted data: {}", e)))?;
+        let mut state = self.state.lock().await;
+        let key = self.data_key(&mut state, tenant).await?;
+        let started = Instant::now();
+        match open(&envelope, &key, tenant.as_bytes()) {
+            Ok(plaintext) => {
+                record_latency(&mut state, envelope.algorithm, "decrypt", started.elapsed());
+                state.usage.entry(tenant.to_string()).or_default().1 += 1;
+                self.audit(&mut state, tenant, KeyOperation::Decrypt, envelope.algorithm);
+                Ok(plaintext)
+            },
+            Err(e) => {
+                self.audit(&mut state, tenant, KeyOperation::DecryptFailed, envelope.algorithm);
+                Err(e)
+            },
+        }
+    }
+
+    /// Tenants with a data key
+    pub async fn keys(&self) -> Vec<TenantKeyInfo> {
+        let state = self.state.lock().await;
+        let mut keys: Vec<TenantKeyInfo> = state
+            .wrapped
+            .iter()
+            .map(|(tenant, wrapped)| {
+                let (encryptions, decryptions) = state.usage.get(tenant).copied().unwrap_or_default();
+                TenantKeyInfo {
+                    tenant: tenant.clone(),
+                    created_at: wrapped.created_at,
+                    encryptions,
+                    decryptions,
+                }
+            })
+            .collect();
+        keys.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        keys
+    }
+
+    /// Recent key usage, oldest first
+    pub async fn audit_trail(&self) -> Vec<KeyUsage> {
+        self.state.lock().await.audit.iter().cloned().collect()
+    }
+
+    /// Render key counts and crypto latency into security health metrics
+    pub async fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let state = self.state.lock().await;
+        metrics.insert("tenant_keys".to_string(), state.wrapped.len() as f32);
+        metrics.insert("crypto_aes_hardware".to_string(), if has_aes_hardware() { 1.0 } else { 0.0 });
+        metrics.insert("crypto_fips_mode".to_string(), if FIPS_MODE { 1.0 } else { 0.0 });
+        metrics.insert(
+            "crypto_chacha20_poly1305".to_string(),
+            if self.algorithm == CryptoAlgorithm::ChaCha20Poly1305 { 1.0 } else { 0.0 },
+        );
+        for ((algorithm, operation), latency) in &state.latency {
+            let prefix = format!("crypto_{}_{}", algorithm.metric_name(), operation);
+            let avg_us = latency.total.as_secs_f32() * 1e6 / latency.count.max(1) as f32;
+            metrics.insert(format!("{}_total", prefix), latency.count as f32);
+            metrics.insert(format!("{}_us_avg", prefix), avg_us);
+            metrics.insert(format!("{}_us_max", prefix), latency.max.as_secs_f32() * 1e6);
+        }
+    }
+
+    /// Unwrapped data key of a tenant, created and persisted on first use
+    async fn data_key(&self, state: &mut KeyringState, tenant: &str) -> Result<[u8; 32]> {
+        if let Some(key) = state.keys.get(tenant) {
+            return Ok(*key);
+        }
+        let aad = format!("tenant-key:{}", tenant);
+
+        if let Some(entry) = state.wrapped.get(tenant) {
+            let started = Instant::now();
+            let algorithm = entry.wrapped.algorithm;
+            let key: [u8; 32] = open(&entry.wrapped, &self.master_key, aad.as_bytes())?
+                .try_into()
+                .map_err(|_| Error::Security(format!("Wrapped key of tenant {} is corrupt", tenant)))?;
+            record_latency(state, algorithm, "unwrap", started.elapsed());
+            state.keys.insert(tenant.to_string(), key);
+            return Ok(key);
+        }
+
+        let key = random_key(&self.rng)?;
+        let started = Instant::now();
+        let wrapped = self.seal(self.algorithm, &self.master_key, aad.as_bytes(), &key)?;
+        record_latency(state, self.algorithm, "wrap", started.elapsed());
+        state.wrapped.insert(
+            tenant.to_string(),
+            WrappedKey {
+                wrapped,
+                created_at: Utc::now(),
+            },
+        );
+        if let Some(path) = &self.config.registry_path {
+            write_file(self.vfs.as_ref(), path, &serde_json::to_vec(&state.wrapped)?).await?;
+        }
+        state.keys.insert(tenant.to_string(), key);
+        self.audit(state, tenant, KeyOperation::Create, self.algorithm);
+        info!("Created data key for tenant {}", mcp_common::redaction::id(tenant));
+        Ok(key)
+    }
+
+    fn seal(&self, algorithm: CryptoAlgorithm, key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<Envelope> {
+        let mut nonce = [0u8; 12];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|e| Error::Security(format!("Failed to generate nonce: {:?}", e)))?;
+        let mut ciphertext = plaintext.to_vec();
+        let tag = aead_key(algorithm, key)?
+            .seal_in_place_separate_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut ciphertext)
+            .map_err(|e| Error::Security(format!("Encryption failed: {:?}", e)))?;
+        Ok(Envelope {
+            algorithm,
+            ciphertext,
+            nonce,
+            tag: tag
+                .as_ref()
+                .try_into()
+                .map_err(|_| Error::Security("Unexpected AEAD tag length".to_string()))?,
+        })
+    }
+
+    fn audit(&self, state: &mut KeyringState, tenant: &str, operation: KeyOperation, algorithm: CryptoAlgorithm) {
+        if state.audit.len() >= self.config.audit_capacity.max(1) {
+            state.audit.pop_front();
+        }
+        state.audit.push_back(KeyUsage {
+            at: Utc::now(),
+            tenant: tenant.to_string(),
+            operation,
+            algorithm,
+        });
+    }
+}
+
+fn open(envelope: &Envelope, key: &[u8; 32], aad: &[u8]) -> Result<Vec<u8>> {
+    let mut in_out = envelope.ciphertext.clone();
+    in_out.extend_from_slice(&envelope.tag);
+    let plaintext = aead_key(envelope.algorithm, key)?
+        .open_in_place(Nonce::assume_unique_for_key(envelope.nonce), Aad::from(aad), &mut in_out)
+        .map_err(|e| Error::Security(format!("Decryption failed: {:?}", e)))?;
+    Ok(plaintext.to_vec())
+}
+
+fn aead_key(algorithm: CryptoAlgorithm, key: &[u8; 32]) -> Result<LessSafeKey> {
+    // Also refuses data written with a non-approved algorithm before FIPS mode
+    if !crypto::aead_approved(algorithm.aead()) {
+        return Err(Error::Security(format!("{} is not approved in FIPS mode", algorithm.as_str())));
+    }
+    let unbound = UnboundKey::new(algorithm.aead(), key)
+        .map_err(|e| Error::Security(format!("Failed to create {} key: {:?}", algorithm.as_str(), e)))?;
+    Ok(LessSafeKey::new(unbound))
+}
+
+fn record_latency(state: &mut KeyringState, algorithm: CryptoAlgorithm, operation: &'static str, elapsed: Duration) {
+    let latency = state.latency.entry((algorithm, operation)).or_default();
+    latency.count += 1;
+    latency.total += elapsed;
+    latency.max = latency.max.max(elapsed);
+}
+
+fn random_key(rng: &SystemRandom) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    rng.fill(&mut key)
+        .map_err(|e| Error::Security(format!("Failed to generate key: {:?}", e)))?;
+    Ok(key)
+}
+
+async fn write_file(vfs: &dyn Vfs, path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        vfs.create_dir_all(parent).await?;
+    }
+    vfs.write(path, data).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::vfs::MemoryVfs;
+    use std::path::PathBuf;
+
+    fn persistent() -> TenantKeysConfig {
+        TenantKeysConfig {
+            master_key_path: Some(PathBuf::from("pki/master.key")),
+            registry_path: Some(PathBuf::from("pki/tenant-keys.json")),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(not(feature = "fips"))]
+    #[tokio::test]
+    async fn test_tenants_are_isolated_and_algorithms_interoperate() {
+        let vfs: Arc<dyn Vfs> = Arc::new(MemoryVfs::new());
+        let aes = TenantKeyring::load(persistent(), "AES-256-GCM", vfs.clone()).await.unwrap();
+        let sealed = aes.encrypt("acme", b"queued prompt").await.unwrap();
+        assert_eq!(aes.decrypt("acme", &sealed).await.unwrap(), b"queued prompt");
+        assert!(aes.decrypt("globex", &sealed).await.is_err());
+
+        // Switching algorithms keeps existing data readable
+        let chacha = TenantKeyring::load(persistent(), "chacha20-poly1305", vfs).await.unwrap();
+        assert_eq!(chacha.decrypt("acme", &sealed).await.unwrap(), b"queued prompt");
+        let resealed = chacha.encrypt("acme", b"queued prompt").await.unwrap();
+        assert!(String::from_utf8_lossy(&resealed).contains("CHACHA20-POLY1305"));
+        assert_eq!(aes.decrypt("acme", &resealed).await.unwrap(), b"queued prompt");
+
+        let operations: Vec<KeyOperation> = aes.audit_trail().await.iter().map(|usage| usage.operation).collect();
+        assert_eq!(
+            operations,
+            vec![
+                KeyOperation::Create,
+                KeyOperation::Encrypt,
+                KeyOperation::Decrypt,
+                KeyOperation::Create,
+                KeyOperation::DecryptFailed,
+                KeyOperation::Decrypt,
+            ]
+        );
+        assert!(TenantKeyring::load(persistent(), "DES", Arc::new(MemoryVfs::new())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_registry_holds_only_wrapped_keys() {
+        let vfs: Arc<dyn Vfs> = Arc::new(MemoryVfs::new());
+        let keyring = TenantKeyring::load(persistent(), "auto", vfs.clone()).await.unwrap();
+        keyring.encrypt("acme", b"data").await.unwrap();
+        let master = vfs.read(Path::new("pki/master.key")).await.unwrap();
+        let registry = vfs.read(Path::new("pki/tenant-keys.json")).await.unwrap();
+        let key = keyring.state.lock().await.keys["acme"];
+        assert_eq!(master.len(), 32);
+        assert!(!registry.windows(32).any(|window| window == key));
+        assert!(!serde_json::to_string(&keyring.keys().await).unwrap().contains("wrapped"));
+
+        let mut metrics = HashMap::new();
+        keyring.write_metrics(&mut metrics).await;
+        assert_eq!(metrics["tenant_keys"], 1.0);
+        let prefix = format!("crypto_{}_encrypt", keyring.algorithm().metric_name());
+        assert_eq!(metrics[&format!("{}_total", prefix)], 1.0);
+
+        // Without a master key the registry could not be read back
+        let config = TenantKeysConfig {
+            master_key_path: None,
+            ..persistent()
+        };
+        assert!(TenantKeyring::load(config, "auto", vfs).await.is_err());
+    }
+}
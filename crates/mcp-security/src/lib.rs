@@ -1,6 +1,7 @@
 //! MCP Security - Security and cryptography for the MCP Edge Gateway
 
 use async_trait::async_trait;
+use mcp_common::config::RateLimitConfig;
 use mcp_common::metrics::ComponentHealth;
 use mcp_common::{Config, MCPRequest, Result};
 use std::sync::Arc;
@@ -17,6 +18,34 @@ pub trait SecurityManager {
     /// Decrypt data
     async fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<Vec<u8>>;
 
+    /// Restricted-mode registry, if this manager supports restricting devices
+    fn restrictions(&self) -> Option<&RestrictedDevices> {
+        None
+    }
+
+    /// Replace the per-device request rate limits, if this manager enforces them
+    fn reconfigure_rate_limits(&self, _limits: RateLimitConfig) {}
+
+    /// Authentication lockouts and behavior baselines, if tracked by this manager
+    fn auth_guard(&self) -> Option<&AuthGuard> {
+        None
+    }
+
+    /// Device enrollment and fleet CA, if enabled
+    fn enrollment(&self) -> Option<&DeviceEnrollment> {
+        None
+    }
+
+    /// Per-tenant data keys, if this manager encrypts with them
+    fn keyring(&self) -> Option<&TenantKeyring> {
+        None
+    }
+
+    /// Device key and platform quotes, if this manager holds a device key
+    fn attestation(&self) -> Option<&DeviceAttestor> {
+        None
+    }
+
     /// Get health status
     async fn health_check(&self) -> Result<ComponentHealth>;
 
@@ -24,11 +53,30 @@ pub trait SecurityManager {
     async fn shutdown(&self) -> Result<()>;
 }
 
+mod attestation;
+mod auth_guard;
+mod enrollment;
 mod input_validation;
+mod keyring;
+mod model_signing;
+mod output_moderation;
+mod restricted;
 mod standard_security;
 
+pub use attestation::{DeviceAttestor, PlatformQuote, MAX_NONCE_BYTES};
+pub use auth_guard::{AuthGuard, PrincipalKind, ThreatAssessment, ThrottledPrincipal};
+pub use enrollment::{
+    DeviceEnrollment, EnrolledDevice, EnrollmentRequest, EnrollmentResponse, RevokeRequest, RevokedCertificate,
+    DEVICE_SIGNATURE_HEADER, DEVICE_TIMESTAMP_HEADER,
+};
 pub use input_validation::{InputValidator, ValidationConfig, ContentSanitizer};
-pub use standard_security::StandardSecurityManager;
+pub use keyring::{
+    has_aes_hardware, CryptoAlgorithm, KeyOperation, KeyUsage, TenantKeyInfo, TenantKeyring, DEFAULT_TENANT,
+};
+pub use model_signing::{sign_manifest, ModelSignatureVerifier};
+pub use output_moderation::{ModerationDecision, OutputModerator, MODERATION_FIELD};
+pub use restricted::{LiftRequest, RestrictRequest, RestrictedDevices, Restriction, RestrictionSource};
+pub use standard_security::{StandardSecurityManager, ThreatSeverity};
 
 /// Create a new security manager instance
 pub async fn create_security_manager(
@@ -0,0 +1,186 @@
+//! Signature checks for model manifests
+//!
+//! A model file is only trusted when its signed manifest was made by one of
+//! `models.signing.trusted_keys`, names the model being loaded and carries the
+//! file's SHA-256. In `warn` mode a model failing these checks still loads
+//! with a warning; in `enforce` mode it is refused.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use mcp_common::config::{ModelSignatureMode, ModelSigningConfig};
+use mcp_common::crypto::signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+use mcp_common::model_manifest::{
+    ManifestSignature, ModelManifest, SignedModelManifest, MANIFEST_SIGNATURE_ALGORITHM,
+};
+use mcp_common::{Error, Result};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Checks signed model manifests against the trusted publisher keys
+pub struct ModelSignatureVerifier {
+    config: ModelSigningConfig,
+    trusted_keys: Vec<Vec<u8>>,
+}
+
+impl ModelSignatureVerifier {
+    pub fn new(config: ModelSigningConfig) -> Result<Self> {
+        let trusted_keys = config
+            .trusted_keys
+            .iter()
+            .map(|key| {
+                BASE64.decode(key.trim()).map_err(|_| {
+                    Error::Configuration(format!("models.signing trusted key '{}' is not valid base64", key))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { config, trusted_keys })
+    }
+
+    pub fn mode(&self) -> ModelSignatureMode {
+        self.config.mode
+    }
+
+    /// Where the signed manifest for `model_path` is expected
+    pub fn manifest_path(&self, model_path: &Path) -> PathBuf {
+        SignedModelManifest::path_for(model_path, &self.config.manifest_suffix)
+    }
+
+    /// Check that `signed` was signed by a trusted key and has not been altered
+    pub fn verify(&self, signed: &SignedModelManifest) -> Result<()> {
+        if signed.signature.algorithm != MANIFEST_SIGNATURE_ALGORITHM {
+            return Err(Error::Security(format!(
+                "Unsupported manifest signature algorithm {}",
+                signed.signature.algorithm
+            )));
+        }
+        let key = BASE64
+            .decode(&signed.signature.public_key)
+            .map_err(|_| Error::Security("Manifest public key is not valid base64".to_string()))?;
+        if !self.trusted_keys.contains(&key) {
+            return Err(Error::Security("Manifest was signed by an untrusted key".to_string()));
+        }
+        let value = BASE64
+            .decode(&signed.signature.value)
+            .map_err(|_| Error::Security("Manifest signature is not valid base64".to_string()))?;
+        UnparsedPublicKey::new(&signature::ED25519, &key)
+            .verify(&signed.manifest.signed_bytes()?, &value)
+            .map_err(|_| Error::Security("Manifest signature does not match its contents".to_string()))
+    }
+
+    /// Verify the manifest stored for a model file hashing to `sha256`.
+    /// Returns the manifest once verified, and `None` when checks are off or
+    /// a failure is only warned about.
+    pub fn check(&self, model_id: &str, sha256: &str, manifest: Option<&[u8]>) -> Result<Option<ModelManifest>> {
+        if self.config.mode == ModelSignatureMode::Off {
+            return Ok(None);
+        }
+        match self.verify_release(model_id, sha256, manifest) {
+            Ok(manifest) => Ok(Some(manifest)),
+            Err(e) if self.config.mode == ModelSignatureMode::Warn => {
+                warn!("Loading model {} without a valid signed manifest: {}", model_id, e);
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    fn verify_release(&self, model_id: &str, sha256: &str, manifest: Option<&[u8]>) -> Result<ModelManifest> {
+        let bytes = manifest.ok_or_else(|| Error::Security(format!("Model {} has no signed manifest", model_id)))?;
+        let signed = SignedModelManifest::from_json(bytes)
+            .map_err(|e| Error::Security(format!("Model {} manifest is malformed: {}", model_id, e)))?;
+        self.verify(&signed)?;
+        let manifest = signed.manifest;
+        if manifest.model_id != model_id {
+            return Err(Error::Security(format!(
+                "Manifest for {} was signed for model {}",
+                model_id, manifest.model_id
+            )));
+        }
+        if !manifest.matches_hash(sha256) {
+            return Err(Error::Security(format!(
+                "Model {} file does not match its signed hash",
+                model_id
+            )));
+        }
+        Ok(manifest)
+    }
+}
+
+/// Sign `manifest` with a publisher key
+pub fn sign_manifest(manifest: ModelManifest, key: &Ed25519KeyPair) -> Result<SignedModelManifest> {
+    let value = key.sign(&manifest.signed_bytes()?);
+    Ok(SignedModelManifest {
+        signature: ManifestSignature {
+            algorithm: MANIFEST_SIGNATURE_ALGORITHM.to_string(),
+            public_key: BASE64.encode(key.public_key().as_ref()),
+            value: BASE64.encode(value.as_ref()),
+        },
+        manifest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::crypto::rand::SystemRandom;
+
+    fn key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    fn verifier(mode: ModelSignatureMode, key: &Ed25519KeyPair) -> ModelSignatureVerifier {
+        ModelSignatureVerifier::new(ModelSigningConfig {
+            mode,
+            trusted_keys: vec![BASE64.encode(key.public_key().as_ref())],
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn signed(key: &Ed25519KeyPair) -> Vec<u8> {
+        let manifest = ModelManifest {
+            model_id: "tinyllama".to_string(),
+            sha256: "ab12".to_string(),
+            quantization: "q4_0".to_string(),
+            license: "Apache-2.0".to_string(),
+            allowed_methods: vec!["completion".to_string()],
+        };
+        serde_json::to_vec(&sign_manifest(manifest, key).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_trusted_manifest_is_accepted() {
+        let key = key_pair();
+        let verifier = verifier(ModelSignatureMode::Enforce, &key);
+        let manifest = verifier.check("tinyllama", "AB12", Some(&signed(&key))).unwrap().unwrap();
+        assert_eq!(manifest.license, "Apache-2.0");
+
+        // A different file, model or publisher is refused
+        assert!(verifier.check("tinyllama", "ab13", Some(&signed(&key))).is_err());
+        assert!(verifier.check("phi", "ab12", Some(&signed(&key))).is_err());
+        assert!(verifier.check("tinyllama", "ab12", Some(&signed(&key_pair()))).is_err());
+        assert!(verifier.check("tinyllama", "ab12", None).is_err());
+    }
+
+    #[test]
+    fn test_tampered_manifest_is_refused() {
+        let key = key_pair();
+        let verifier = verifier(ModelSignatureMode::Enforce, &key);
+        let mut tampered: SignedModelManifest = serde_json::from_slice(&signed(&key)).unwrap();
+        tampered.manifest.allowed_methods.push("embedding".to_string());
+        assert!(matches!(verifier.verify(&tampered), Err(Error::Security(_))));
+    }
+
+    #[test]
+    fn test_warn_mode_loads_unsigned_models() {
+        let key = key_pair();
+        assert!(verifier(ModelSignatureMode::Warn, &key).check("tinyllama", "ab12", None).unwrap().is_none());
+        assert!(verifier(ModelSignatureMode::Off, &key).check("tinyllama", "ab12", None).unwrap().is_none());
+        assert!(ModelSignatureVerifier::new(ModelSigningConfig {
+            trusted_keys: vec!["not base64!".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Output moderation for generated content
+//!
+//! The output side of the request content filter: every string in a
+//! response's result is checked against `security.output_moderation`
+//! categories. A category scores one per pattern match, and once its score
+//! reaches the threshold the category's action is taken: the response is
+//! blocked, the matches are redacted, or the decision is attached to the
+//! result under `moderation`. Decisions are kept by request id until
+//! [`OutputModerator::take`] collects them for the audit trail.
+
+use mcp_common::config::{ModerationAction, OutputModerationConfig};
+use mcp_common::{Error, MCPResponse, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Key of the decisions attached to annotated results
+pub const MODERATION_FIELD: &str = "moderation";
+
+/// A category that reached its threshold in a response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModerationDecision {
+    pub category: String,
+    pub score: u32,
+    pub threshold: u32,
+    pub action: ModerationAction,
+}
+
+struct Category {
+    name: String,
+    patterns: Vec<Regex>,
+    threshold: u32,
+    action: ModerationAction,
+}
+
+/// Policy checks on responses before they are returned
+pub struct OutputModerator {
+    enabled: bool,
+    include_cloud_responses: bool,
+    categories: Vec<Category>,
+    /// Decisions by request, until taken for the audit trail
+    decisions: Mutex<HashMap<Uuid, Vec<ModerationDecision>>>,
+}
+
+impl OutputModerator {
+    pub fn new(config: &OutputModerationConfig) -> Result<Self> {
+        let categories = config
+            .categories
+            .iter()
+            .map(|category| {
+                let patterns = category
+                    .patterns
+                    .iter()
+                    .map(|pattern| {
+                        Regex::new(pattern).map_err(|e| {
+                            Error::Configuration(format!(
+                                "Invalid pattern '{}' in output moderation category {}: {}",
+                                pattern, category.name, e
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Category {
+                    name: category.name.clone(),
+                    patterns,
+                    threshold: category.threshold,
+                    action: category.action,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            enabled: config.enabled,
+            include_cloud_responses: config.include_cloud_responses,
+            categories,
+            decisions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether responses from cloud endpoints are moderated too
+    pub fn moderates_cloud(&self) -> bool {
+        self.enabled && self.include_cloud_responses
+    }
+
+    /// Check a response, redacting or annotating it in place. Fails when a
+    /// blocking category reaches its threshold.
+    pub fn moderate(&self, response: &mut MCPResponse) -> Result<()> {
+        let Some(result) = response.result.as_mut().filter(|_| self.enabled) else {
+            return Ok(());
+        };
+        let mut texts = Vec::new();
+        collect_strings(result, &mut texts);
+
+        let decisions: Vec<ModerationDecision> = self
+            .categories
+            .iter()
+            .filter_map(|category| {
+                let score: usize = texts
+                    .iter()
+                    .flat_map(|text| category.patterns.iter().map(|pattern| pattern.find_iter(text).count()))
+                    .sum();
+                let score = score.min(u32::MAX as usize) as u32;
+                (score >= category.threshold).then(|| ModerationDecision {
+                    category: category.name.clone(),
+                    score,
+                    threshold: category.threshold,
+                    action: category.action,
+                })
+            })
+            .collect();
+        if decisions.is_empty() {
+            return Ok(());
+        }
+        self.decisions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(response.id, decisions.clone());
+
+        let blocked: Vec<&str> = decisions
+            .iter()
+            .filter(|decision| decision.action == ModerationAction::Block)
+            .map(|decision| decision.category.as_str())
+            .collect();
+        if !blocked.is_empty() {
+            warn!("Withholding response {}: {}", response.id, blocked.join(", "));
+            return Err(Error::Security(format!(
+                "Response withheld by output moderation: {}",
+                blocked.join(", ")
+            )));
+        }
+
+        for category in &self.categories {
+            let redacts = decisions.iter().any(|decision| {
+                decision.category == category.name && decision.action == ModerationAction::Redact
+            });
+            if redacts {
+                let replacement = format!("[REDACTED:{}]", category.name);
+                redact_strings(result, &category.patterns, &replacement);
+            }
+        }
+        let annotations: Vec<&ModerationDecision> = decisions
+            .iter()
+            .filter(|decision| decision.action == ModerationAction::Annotate)
+            .collect();
+        if !annotations.is_empty() {
+            if let Some(fields) = result.as_object_mut() {
+                fields.insert(MODERATION_FIELD.to_string(), serde_json::to_value(annotations)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Decisions made on a request's response, removed once taken
+    pub fn take(&self, request_id: Uuid) -> Vec<ModerationDecision> {
+        self.decisions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&request_id)
+            .unwrap_or_default()
+    }
+}
+
+fn collect_strings(value: &serde_json::Value, texts: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(text) => texts.push(text.clone()),
+        serde_json::Value::Array(values) => values.iter().for_each(|value| collect_strings(value, texts)),
+        serde_json::Value::Object(fields) => fields.values().for_each(|value| collect_strings(value, texts)),
+        _ => {},
+    }
+}
+
+fn redact_strings(value: &mut serde_json::Value, patterns: &[Regex], replacement: &str) {
+    match value {
+        serde_json::Value::String(text) => {
+            for pattern in patterns {
+                if let std::borrow::Cow::Owned(redacted) = pattern.replace_all(text, replacement) {
+                    *text = redacted;
+                }
+            }
+        },
+        serde_json::Value::Array(values) => {
+            values.iter_mut().for_each(|value| redact_strings(value, patterns, replacement))
+        },
+        serde_json::Value::Object(fields) => {
+            fields.values_mut().for_each(|value| redact_strings(value, patterns, replacement))
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_common::config::ModerationCategoryConfig;
+
+    fn moderator(action: ModerationAction, threshold: u32) -> OutputModerator {
+        OutputModerator::new(&OutputModerationConfig {
+            enabled: true,
+            include_cloud_responses: false,
+            categories: vec![ModerationCategoryConfig {
+                name: "secrets".to_string(),
+                patterns: vec![r"sk-[a-z0-9]{8}".to_string()],
+                threshold,
+                action,
+            }],
+        })
+        .unwrap()
+    }
+
+    fn response(text: &str) -> MCPResponse {
+        MCPResponse {
+            id: Uuid::new_v4(),
+            result: Some(serde_json::json!({ "text": text, "tokens": 12 })),
+            error: None,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_redact_and_annotate_rewrite_the_result() {
+        let mut redacted = response("use sk-abcd1234 or sk-efgh5678");
+        moderator(ModerationAction::Redact, 1).moderate(&mut redacted).unwrap();
+        let result = redacted.result.unwrap();
+        assert_eq!(result["text"], "use [REDACTED:secrets] or [REDACTED:secrets]");
+        assert!(result.get(MODERATION_FIELD).is_none());
+
+        let mut annotated = response("use sk-abcd1234");
+        let moderator = moderator(ModerationAction::Annotate, 1);
+        moderator.moderate(&mut annotated).unwrap();
+        let result = annotated.result.unwrap();
+        assert_eq!(result["text"], "use sk-abcd1234");
+        assert_eq!(result[MODERATION_FIELD][0]["category"], "secrets");
+    }
+
+    #[test]
+    fn test_block_records_the_decision_for_the_audit_trail() {
+        let moderator = moderator(ModerationAction::Block, 2);
+        let mut below = response("use sk-abcd1234");
+        moderator.moderate(&mut below).unwrap();
+        assert!(moderator.take(below.id).is_empty());
+
+        let mut blocked = response("use sk-abcd1234 or sk-efgh5678");
+        assert!(matches!(moderator.moderate(&mut blocked), Err(Error::Security(_))));
+        let decisions = moderator.take(blocked.id);
+        assert_eq!(decisions.len(), 1);
+        assert_eq!((decisions[0].score, decisions[0].action), (2, ModerationAction::Block));
+        assert!(moderator.take(blocked.id).is_empty());
+    }
+
+    #[test]
+    fn test_default_categories_compile() {
+        let mut config = OutputModerationConfig::default();
+        OutputModerator::new(&config).unwrap();
+        config.categories[0].patterns.push("(".to_string());
+        assert!(matches!(OutputModerator::new(&config), Err(Error::Configuration(_))));
+    }
+}
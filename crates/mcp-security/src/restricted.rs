@@ -0,0 +1,266 @@
+//! Restricted (read-only) mode for suspicious devices
+//!
+//! Blocking a device on a false positive takes it out of service until
+//! someone drives to the site. Devices flagged by the security checks are
+//! instead switched to a restricted profile: only read-only methods, a much
+//! lower rate limit and per-request audit logging. Restrictions expire on
+//! their own, and operators can lift one early and exempt the device from
+//! automatic restriction for a while.
+
+use chrono::{DateTime, Utc};
+use mcp_common::config::RestrictedModeConfig;
+use mcp_common::redaction;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLockReadGuard;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+/// Why a device was restricted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestrictionSource {
+    /// Repeated suspicious requests
+    Automatic,
+    /// Operator request
+    Admin,
+}
+
+/// A device in restricted mode
+#[derive(Debug, Clone, Serialize)]
+pub struct Restriction {
+    pub device_id: String,
+    pub reason: String,
+    pub source: RestrictionSource,
+    pub restricted_at: DateTime<Utc>,
+    /// `None` for restrictions that last until lifted
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Restriction {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        match self.expires_at {
+            Some(expires_at) => expires_at > now,
+            None => true,
+        }
+    }
+}
+
+/// Admin request to restrict a device
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RestrictRequest {
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Restriction length; the configured duration when unset, 0 until lifted
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+}
+
+/// Admin request to lift a restriction
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LiftRequest {
+    /// Keep the device from being restricted automatically for this long
+    #[serde(default)]
+    pub exempt_secs: Option<u64>,
+}
+
+/// Registry of restricted devices and suspicious-request strikes
+pub struct RestrictedDevices {
+    config: std::sync::RwLock<RestrictedModeConfig>,
+    restrictions: RwLock<HashMap<String, Restriction>>,
+    strikes: RwLock<HashMap<String, Vec<DateTime<Utc>>>>,
+    exemptions: RwLock<HashMap<String, DateTime<Utc>>>,
+}
+
+impl RestrictedDevices {
+    pub fn new(config: RestrictedModeConfig) -> Self {
+        Self {
+            config: std::sync::RwLock::new(config),
+            restrictions: RwLock::new(HashMap::new()),
+            strikes: RwLock::new(HashMap::new()),
+            exemptions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> RestrictedModeConfig {
+        self.settings().clone()
+    }
+
+    /// Replace the restricted profile; active restrictions and strikes are kept
+    pub fn reconfigure(&self, config: RestrictedModeConfig) {
+        *self.config.write().unwrap_or_else(|p| p.into_inner()) = config;
+    }
+
+    fn settings(&self) -> RwLockReadGuard<'_, RestrictedModeConfig> {
+        self.config.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Whether restricted devices may call `method`
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.settings().allowed_methods.iter().any(|allowed| allowed == method)
+    }
+
+    /// Active restriction for a device, dropping it once expired
+    pub async fn get(&self, device_id: &str) -> Option<Restriction> {
+        let now = Utc::now();
+        {
+            let restrictions = self.restrictions.read().await;
+            match restrictions.get(device_id) {
+                None => return None,
+                Some(restriction) if restriction.is_active(now) => {
+                    return Some(restriction.clone());
+                },
+                Some(_) => {},
+            }
+        }
+        if self.restrictions.write().await.remove(device_id).is_some() {
+            info!("Restriction on device {} expired", redaction::id(device_id));
+        }
+        None
+    }
+
+    /// Every active restriction
+    pub async fn list(&self) -> Vec<Restriction> {
+        let now = Utc::now();
+        let mut restrictions = self.restrictions.write().await;
+        restrictions.retain(|_, restriction| restriction.is_active(now));
+        let mut active: Vec<Restriction> = restrictions.values().cloned().collect();
+        active.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        active
+    }
+
+    /// Put a device in restricted mode, replacing any existing restriction
+    pub async fn restrict(
+        &self,
+        device_id: &str,
+        reason: &str,
+        duration_secs: Option<u64>,
+        source: RestrictionSource,
+    ) -> Restriction {
+        let now = Utc::now();
+        let duration_secs = duration_secs.unwrap_or_else(|| self.settings().duration_secs);
+        let restriction = Restriction {
+            device_id: device_id.to_string(),
+            reason: reason.to_string(),
+            source,
+            restricted_at: now,
+            expires_at: (duration_secs > 0).then(|| now + chrono::Duration::seconds(duration_secs as i64)),
+        };
+        warn!("Device {} switched to restricted mode ({:?}): {}", redaction::id(device_id), source, reason);
+        self.restrictions
+            .write()
+            .await
+            .insert(device_id.to_string(), restriction.clone());
+        restriction
+    }
+
+    /// Lift a restriction; returns whether one was active
+    pub async fn lift(&self, device_id: &str, exempt_secs: Option<u64>) -> bool {
+        self.strikes.write().await.remove(device_id);
+        if let Some(exempt_secs) = exempt_secs.filter(|secs| *secs > 0) {
+            let until = Utc::now() + chrono::Duration::seconds(exempt_secs as i64);
+            self.exemptions.write().await.insert(device_id.to_string(), until);
+        }
+        let lifted = self.restrictions.write().await.remove(device_id).is_some();
+        if lifted {
+            info!("Restriction on device {} lifted", redaction::id(device_id));
+        }
+        lifted
+    }
+
+    /// Count a suspicious request, restricting the device once it reaches
+    /// the strike threshold; returns the new restriction
+    pub async fn record_strike(&self, device_id: &str, reason: &str) -> Option<Restriction> {
+        let (auto_restrict, strike_window_secs, strike_threshold) = {
+            let config = self.settings();
+            (config.auto_restrict, config.strike_window_secs, config.strike_threshold)
+        };
+        if !auto_restrict {
+            return None;
+        }
+        let now = Utc::now();
+        {
+            let mut exemptions = self.exemptions.write().await;
+            match exemptions.get(device_id) {
+                Some(until) if *until > now => return None,
+                Some(_) => {
+                    exemptions.remove(device_id);
+                },
+                None => {},
+            }
+        }
+        if self.restrictions.read().await.contains_key(device_id) {
+            return None;
+        }
+
+        let window_start = now - chrono::Duration::seconds(strike_window_secs as i64);
+        let strikes = {
+            let mut strikes = self.strikes.write().await;
+            let device_strikes = strikes.entry(device_id.to_string()).or_default();
+            device_strikes.retain(|strike| *strike > window_start);
+            device_strikes.push(now);
+            device_strikes.len()
+        };
+        if strikes < strike_threshold.max(1) as usize {
+            return None;
+        }
+
+        self.strikes.write().await.remove(device_id);
+        let reason = format!("{} suspicious requests, last: {}", strikes, reason);
+        Some(self.restrict(device_id, &reason, None, RestrictionSource::Automatic).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_strikes_restrict_device_until_lifted() {
+        let devices = RestrictedDevices::new(RestrictedModeConfig::default());
+        assert!(devices.record_strike("cam-7", "script tag").await.is_none());
+        assert!(devices.record_strike("cam-7", "script tag").await.is_none());
+        let restriction = devices.record_strike("cam-7", "path traversal").await.unwrap();
+        assert_eq!(restriction.source, RestrictionSource::Automatic);
+        assert!(restriction.expires_at.is_some());
+        assert!(devices.get("cam-7").await.is_some());
+        assert!(devices.allows_method("retrieval.search"));
+        assert!(!devices.allows_method("completion"));
+
+        // An exempted device is not re-restricted by further strikes
+        assert!(devices.lift("cam-7", Some(3600)).await);
+        for _ in 0..5 {
+            assert!(devices.record_strike("cam-7", "script tag").await.is_none());
+        }
+        assert!(devices.list().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restrictions_expire() {
+        let devices = RestrictedDevices::new(RestrictedModeConfig::default());
+        let indefinite = devices.restrict("plc-1", "manual", Some(0), RestrictionSource::Admin).await;
+        assert!(indefinite.expires_at.is_none());
+
+        devices.restrict("plc-2", "manual", Some(60), RestrictionSource::Admin).await;
+        devices.restrictions.write().await.get_mut("plc-2").unwrap().expires_at =
+            Some(Utc::now() - chrono::Duration::seconds(1));
+        assert!(devices.get("plc-2").await.is_none());
+        let active: Vec<String> = devices.list().await.into_iter().map(|r| r.device_id).collect();
+        assert_eq!(active, vec!["plc-1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_reconfigure_keeps_active_restrictions() {
+        let devices = RestrictedDevices::new(RestrictedModeConfig::default());
+        devices.restrict("plc-1", "manual", None, RestrictionSource::Admin).await;
+        devices.reconfigure(RestrictedModeConfig {
+            allowed_methods: vec!["completion".to_string()],
+            requests_per_minute: 2,
+            ..Default::default()
+        });
+        assert_eq!(devices.config().requests_per_minute, 2);
+        assert!(devices.allows_method("completion"));
+        assert!(!devices.allows_method("retrieval.search"));
+        assert!(devices.get("plc-1").await.is_some());
+    }
+}
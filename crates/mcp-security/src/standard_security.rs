@@ -1,14 +1,17 @@
 //! Advanced security manager with hardware security, anomaly detection, and threat intelligence
 
-use crate::SecurityManager;
+use crate::keyring::DEFAULT_TENANT;
+use crate::{AuthGuard, DeviceAttestor, DeviceEnrollment, RestrictedDevices, SecurityManager, TenantKeyring};
 use async_trait::async_trait;
+use mcp_common::config::RateLimitConfig;
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
 use mcp_common::metrics::{ComponentHealth, HealthLevel};
-use mcp_common::{Config, Error, MCPRequest, Result};
-use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
-use ring::rand::{SecureRandom, SystemRandom};
-use serde::{Deserialize, Serialize};
+use mcp_common::redaction;
+use mcp_common::{Config, Error, MCPRequest, RequestSource, Result, SharedState};
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
@@ -40,8 +43,10 @@ struct ReputationScore {
     confidence: f32,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum ThreatSeverity {
+/// Threat level of an attack signature or anomalous request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreatSeverity {
     Low,
     Medium,
     High,
@@ -120,14 +125,6 @@ struct AnomalyThresholds {
     confidence_threshold: f32,
 }
 
-/// Encrypted data structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct EncryptedData {
-    ciphertext: Vec<u8>,
-    nonce: [u8; 12],
-    tag: [u8; 16],
-}
-
 /// Device authentication information
 #[derive(Debug, Clone)]
 struct DeviceAuth {
@@ -140,28 +137,22 @@ struct DeviceAuth {
     allowed_methods: HashSet<String>,
 }
 
-/// Rate limiting information
-#[derive(Debug, Clone)]
-struct RateLimitInfo {
-    requests_per_minute: u32,
-    requests_per_hour: u32,
-    requests_this_minute: u32,
-    requests_this_hour: u32,
-    minute_window_start: chrono::DateTime<chrono::Utc>,
-    hour_window_start: chrono::DateTime<chrono::Utc>,
-}
-
 /// Advanced security manager with threat detection and hardware security
 pub struct StandardSecurityManager {
     config: Arc<Config>,
-    encryption_key: LessSafeKey,
-    rng: SystemRandom,
+    keyring: TenantKeyring,
     threat_detector: Arc<ThreatDetectionSystem>,
     hardware_security: Arc<HardwareSecurityModule>,
     anomaly_detector: Arc<AnomalyDetector>,
     devices: Arc<RwLock<HashMap<String, DeviceAuth>>>,
-    rate_limits: Arc<RwLock<HashMap<String, RateLimitInfo>>>,
+    /// Rate-limit counters, shared with other gateways when clustered
+    rate_limits: Arc<dyn SharedState>,
+    rate_limit_config: std::sync::RwLock<RateLimitConfig>,
     blocked_devices: Arc<RwLock<HashSet<String>>>,
+    restricted: RestrictedDevices,
+    auth_guard: AuthGuard,
+    enrollment: Option<DeviceEnrollment>,
+    attestation: DeviceAttestor,
     security_metrics: Arc<RwLock<SecurityMetrics>>,
 }
 
@@ -173,6 +164,11 @@ struct SecurityMetrics {
     invalid_requests: u64,
     blocked_requests: u64,
     rate_limited_requests: u64,
+    restricted_requests: u64,
+    locked_out_requests: u64,
+    anomalous_requests: u64,
+    revoked_requests: u64,
+    unauthorized_requests: u64,
     encryption_operations: u64,
     decryption_operations: u64,
     device_registrations: u64,
@@ -182,15 +178,12 @@ impl StandardSecurityManager {
     pub async fn new(config: Arc<Config>) -> Result<Self> {
         info!("Initializing security manager with encryption enabled");
         
-        // Generate a secure encryption key
-        let rng = SystemRandom::new();
-        let mut key_bytes = [0u8; 32];
-        rng.fill(&mut key_bytes)
-            .map_err(|e| Error::Security(format!("Failed to generate encryption key: {:?}", e)))?;
-        
-        let unbound_key = UnboundKey::new(&AES_256_GCM, &key_bytes)
-            .map_err(|e| Error::Security(format!("Failed to create encryption key: {:?}", e)))?;
-        let encryption_key = LessSafeKey::new(unbound_key);
+        let keyring = TenantKeyring::load(
+            config.security.tenant_keys.clone(),
+            &config.security.encryption_algorithm,
+            mcp_common::create_vfs(&config.storage),
+        )
+        .await?;
         
         // Initialize device registry with some demo devices if configured
         let mut devices = HashMap::new();
@@ -215,30 +208,45 @@ impl StandardSecurityManager {
             ].into_iter().collect(),
         });
         
-        info!("Added demo device {} for development", device_id);
+        info!("Added demo device {} for development", redaction::id(&device_id));
         
         // Initialize advanced security components  
         let threat_detector = Arc::new(ThreatDetectionSystem::new().await);
         let hardware_security = Arc::new(HardwareSecurityModule::new().await);
         let anomaly_detector = Arc::new(AnomalyDetector::new().await);
+        let rate_limits = mcp_common::create_shared_state(&config.cluster);
+        let rate_limit_config = std::sync::RwLock::new(config.security.rate_limits.clone());
+        let restricted = RestrictedDevices::new(config.security.restricted_mode.clone());
+        let auth_guard = AuthGuard::new(config.security.auth_protection.clone());
+        let enrollment = if config.security.enrollment.enabled {
+            let vfs = mcp_common::create_vfs(&config.storage);
+            Some(DeviceEnrollment::load(config.security.enrollment.clone(), vfs).await?)
+        } else {
+            None
+        };
+        let attestation = DeviceAttestor::load(&config.security, mcp_common::create_vfs(&config.storage)).await?;
 
         Ok(Self {
             config,
-            encryption_key,
-            rng,
+            keyring,
             threat_detector,
             hardware_security,
             anomaly_detector,
             devices: Arc::new(RwLock::new(devices)),
-            rate_limits: Arc::new(RwLock::new(HashMap::new())),
+            rate_limits,
+            rate_limit_config,
             blocked_devices: Arc::new(RwLock::new(HashSet::new())),
+            restricted,
+            auth_guard,
+            enrollment,
+            attestation,
             security_metrics: Arc::new(RwLock::new(SecurityMetrics::default())),
         })
     }
     
     /// Hash an API key using SHA256
     fn hash_api_key(api_key: &str) -> [u8; 32] {
-        use ring::digest;
+        use mcp_common::crypto::digest;
         let hash = digest::digest(&digest::SHA256, api_key.as_bytes());
         let mut result = [0u8; 32];
         result.copy_from_slice(hash.as_ref());
@@ -251,7 +259,7 @@ impl StandardSecurityManager {
         let api_key = match api_key {
             Some(key) => key,
             None => {
-                debug!("No API key provided for device {}, allowing for demo", device_id);
+                debug!("No API key provided for device {}, allowing for demo", redaction::id(device_id));
                 return Ok(true); // Allow for demo purposes
             }
         };
@@ -259,7 +267,7 @@ impl StandardSecurityManager {
         let devices = self.devices.read().await;
         if let Some(device_auth) = devices.get(device_id) {
             if !device_auth.is_active {
-                warn!("Device {} is deactivated", device_id);
+                warn!("Device {} is deactivated", redaction::id(device_id));
                 return Ok(false);
             }
             
@@ -267,66 +275,90 @@ impl StandardSecurityManager {
             let valid = provided_hash == device_auth.api_key_hash;
             
             if valid {
-                debug!("API key validation successful for device {}", device_id);
+                debug!("API key validation successful for device {}", redaction::id(device_id));
             } else {
-                warn!("API key validation failed for device {}", device_id);
+                warn!("API key validation failed for device {}", redaction::id(device_id));
             }
             
             Ok(valid)
         } else {
             // For demo, allow unknown devices but log them
-            info!("Unknown device {}, allowing for demo purposes", device_id);
+            info!("Unknown device {}, allowing for demo purposes", redaction::id(device_id));
             Ok(true)
         }
     }
     
-    /// Check rate limits for a device
+    /// Check rate limits for a device using fixed minute and hour windows
     async fn check_rate_limits(&self, device_id: &str) -> Result<bool> {
-        let now = chrono::Utc::now();
-        let mut rate_limits = self.rate_limits.write().await;
-        
-        // For demo purposes, use generous rate limits
-        let limit_info = rate_limits.entry(device_id.to_string()).or_insert_with(|| {
-            RateLimitInfo {
-                requests_per_minute: 100, // Generous for demo
-                requests_per_hour: 1000,  // Generous for demo
-                requests_this_minute: 0,
-                requests_this_hour: 0,
-                minute_window_start: now,
-                hour_window_start: now,
-            }
-        });
-        
-        // Reset minute window if needed
-        if (now - limit_info.minute_window_start).num_seconds() >= 60 {
-            limit_info.requests_this_minute = 0;
-            limit_info.minute_window_start = now;
-        }
-        
-        // Reset hour window if needed
-        if (now - limit_info.hour_window_start).num_seconds() >= 3600 {
-            limit_info.requests_this_hour = 0;
-            limit_info.hour_window_start = now;
-        }
-        
-        // Check limits
-        if limit_info.requests_this_minute >= limit_info.requests_per_minute {
-            warn!("Rate limit exceeded for device {} (minute limit)", device_id);
+        let (per_minute, per_hour) = {
+            let limits = self.rate_limit_config.read().unwrap_or_else(|p| p.into_inner());
+            (limits.device_requests_per_minute, limits.device_requests_per_hour)
+        };
+        let now = chrono::Utc::now().timestamp();
+
+        let minute_key = format!("ratelimit:{}:m:{}", device_id, now / 60);
+        let this_minute = self.rate_limits.increment(&minute_key, Duration::from_secs(60)).await;
+        if this_minute > per_minute {
+            warn!("Rate limit exceeded for device {} (minute limit)", redaction::id(device_id));
             return Ok(false);
         }
-        
-        if limit_info.requests_this_hour >= limit_info.requests_per_hour {
-            warn!("Rate limit exceeded for device {} (hour limit)", device_id);
+
+        let hour_key = format!("ratelimit:{}:h:{}", device_id, now / 3600);
+        let this_hour = self.rate_limits.increment(&hour_key, Duration::from_secs(3600)).await;
+        if this_hour > per_hour {
+            warn!("Rate limit exceeded for device {} (hour limit)", redaction::id(device_id));
             return Ok(false);
         }
-        
-        // Increment counters
-        limit_info.requests_this_minute += 1;
-        limit_info.requests_this_hour += 1;
-        
+
         Ok(true)
     }
     
+    /// Apply the restricted profile to a request from a restricted device
+    async fn check_restricted(&self, request: &MCPRequest) -> Result<()> {
+        let Some(restriction) = self.restricted.get(&request.device_id).await else {
+            return Ok(());
+        };
+        // Audit every request from restricted devices
+        info!(
+            "Restricted device {} called {} (request {}, reason: {})",
+            redaction::id(&request.device_id),
+            request.method,
+            request.id,
+            restriction.reason
+        );
+
+        if !self.restricted.allows_method(&request.method) {
+            warn!("Denied {} for restricted device {}", request.method, redaction::id(&request.device_id));
+            return Err(Error::Security(format!(
+                "Device {} is in restricted mode; method {} is not allowed",
+                request.device_id, request.method
+            )));
+        }
+
+        let minute = chrono::Utc::now().timestamp() / 60;
+        let key = format!("ratelimit:{}:restricted:{}", request.device_id, minute);
+        let this_minute = self.rate_limits.increment(&key, Duration::from_secs(60)).await;
+        if this_minute > self.restricted.config().requests_per_minute {
+            warn!("Restricted rate limit exceeded for device {}", redaction::id(&request.device_id));
+            return Err(Error::Security(format!(
+                "Rate limit exceeded for restricted device {}",
+                request.device_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Count a suspicious request against the device, restricting it at the threshold
+    async fn record_suspicious(&self, device_id: &str, reason: &str) {
+        if let Some(restriction) = self.restricted.record_strike(device_id, reason).await {
+            events::publish(GatewayEvent::AlertRaised {
+                source: "security".to_string(),
+                severity: AlertSeverity::Warning,
+                message: format!("Device {} switched to restricted mode: {}", device_id, restriction.reason),
+            });
+        }
+    }
+
     /// Update device usage statistics
     async fn update_device_stats(&self, device_id: &str) -> Result<()> {
         let mut devices = self.devices.write().await;
@@ -350,7 +382,7 @@ impl StandardSecurityManager {
                     "summarization".to_string(),
                 ].into_iter().collect(),
             });
-            info!("Auto-registered unknown device {} for demo", device_id);
+            info!("Auto-registered unknown device {} for demo", redaction::id(device_id));
         }
         Ok(())
     }
@@ -389,7 +421,7 @@ impl StandardSecurityManager {
 #[async_trait]
 impl SecurityManager for StandardSecurityManager {
     async fn validate_request(&self, request: &MCPRequest) -> Result<()> {
-        debug!("Validating request {} from device {}", request.id, request.device_id);
+        debug!("Validating request {} from device {}", request.id, redaction::id(&request.device_id));
         
         // Update metrics
         {
@@ -409,6 +441,18 @@ impl SecurityManager for StandardSecurityManager {
             metrics.invalid_requests += 1;
             return Err(Error::Security("Method is required".to_string()));
         }
+
+        // Callers authenticated at the API may be limited to some methods
+        if let Some(principal) = request.principal() {
+            if !principal.may_call(&request.method) {
+                let mut metrics = self.security_metrics.write().await;
+                metrics.unauthorized_requests += 1;
+                return Err(Error::Security(format!(
+                    "{} is not allowed to call {}",
+                    principal.subject, request.method
+                )));
+            }
+        }
         
         // Check if device is blocked
         {
@@ -420,6 +464,40 @@ impl SecurityManager for StandardSecurityManager {
             }
         }
         
+        // Decommissioned devices, and unenrolled ones when enrollment is required
+        if let Some(enrollment) = &self.enrollment {
+            let rejection = if enrollment.is_revoked(&request.device_id).await {
+                Some(format!("Device {} has been revoked", request.device_id))
+            } else if enrollment.config().require_enrollment && !enrollment.is_enrolled(&request.device_id).await {
+                Some(format!("Device {} is not enrolled", request.device_id))
+            } else {
+                None
+            };
+            if let Some(rejection) = rejection {
+                let mut metrics = self.security_metrics.write().await;
+                metrics.revoked_requests += 1;
+                return Err(Error::Security(rejection));
+            }
+        }
+
+        // Brute-force lockouts, per device and per source address
+        let source_ip = request.context.as_ref().and_then(|context| match &context.source {
+            RequestSource::Remote(address) => Some(address.as_str()),
+            _ => None,
+        });
+        if let Err(e) = self.auth_guard.check(&request.device_id, source_ip).await {
+            let mut metrics = self.security_metrics.write().await;
+            metrics.locked_out_requests += 1;
+            return Err(e);
+        }
+
+        // Restricted devices get a reduced profile instead of being blocked
+        if let Err(e) = self.check_restricted(request).await {
+            let mut metrics = self.security_metrics.write().await;
+            metrics.restricted_requests += 1;
+            return Err(e);
+        }
+
         // Rate limiting
         if !self.check_rate_limits(&request.device_id).await? {
             let mut metrics = self.security_metrics.write().await;
@@ -431,13 +509,37 @@ impl SecurityManager for StandardSecurityManager {
         let api_key: Option<&str> = None; // Simplified for demo - in production would extract from headers
         
         if !self.validate_api_key(&request.device_id, api_key).await? {
+            self.auth_guard.record_failure(&request.device_id, source_ip).await;
             let mut metrics = self.security_metrics.write().await;
             metrics.invalid_requests += 1;
             return Err(Error::Security("Invalid authentication".to_string()));
         }
         
         // Content validation
-        self.validate_request_content(request)?;
+        if let Err(e) = self.validate_request_content(request) {
+            self.record_suspicious(&request.device_id, &e.to_string()).await;
+            return Err(e);
+        }
+        self.auth_guard.record_success(&request.device_id, source_ip).await;
+
+        // Score deviation from the device's usual behavior
+        let assessment = self
+            .auth_guard
+            .observe(&request.device_id, &request.method, request.timestamp)
+            .await;
+        if assessment.level >= ThreatSeverity::High {
+            warn!(
+                "Anomalous request {} from device {} (score {:.1}, {:?}): {}",
+                request.id,
+                redaction::id(&request.device_id),
+                assessment.score,
+                assessment.level,
+                assessment.reasons.join("; ")
+            );
+            self.security_metrics.write().await.anomalous_requests += 1;
+            let reason = format!("anomalous behavior: {}", assessment.reasons.join("; "));
+            self.record_suspicious(&request.device_id, &reason).await;
+        }
         
         // Update device statistics
         self.update_device_stats(&request.device_id).await?;
@@ -452,33 +554,33 @@ impl SecurityManager for StandardSecurityManager {
         Ok(())
     }
     
+    fn restrictions(&self) -> Option<&RestrictedDevices> {
+        Some(&self.restricted)
+    }
+
+    fn reconfigure_rate_limits(&self, limits: RateLimitConfig) {
+        *self.rate_limit_config.write().unwrap_or_else(|p| p.into_inner()) = limits;
+    }
+
+    fn auth_guard(&self) -> Option<&AuthGuard> {
+        Some(&self.auth_guard)
+    }
+
+    fn enrollment(&self) -> Option<&DeviceEnrollment> {
+        self.enrollment.as_ref()
+    }
+
+    fn keyring(&self) -> Option<&TenantKeyring> {
+        Some(&self.keyring)
+    }
+
+    fn attestation(&self) -> Option<&DeviceAttestor> {
+        Some(&self.attestation)
+    }
+
     async fn encrypt_data(&self, data: &[u8]) -> Result<Vec<u8>> {
         debug!("Encrypting {} bytes of data", data.len());
-        
-        // Generate a random nonce
-        let mut nonce_bytes = [0u8; 12];
-        self.rng.fill(&mut nonce_bytes)
-            .map_err(|e| Error::Security(format!("Failed to generate nonce: {:?}", e)))?;
-        let nonce = Nonce::assume_unique_for_key(nonce_bytes);
-        
-        // Encrypt the data
-        let mut ciphertext = data.to_vec();
-        let tag = self.encryption_key.seal_in_place_separate_tag(
-            nonce,
-            Aad::empty(),
-            &mut ciphertext,
-        ).map_err(|e| Error::Security(format!("Encryption failed: {:?}", e)))?;
-        
-        // Create encrypted data structure
-        let encrypted_data = EncryptedData {
-            ciphertext,
-            nonce: nonce_bytes,
-            tag: tag.as_ref().try_into().unwrap(),
-        };
-        
-        // Serialize to bytes
-        let serialized = serde_json::to_vec(&encrypted_data)
-            .map_err(|e| Error::Security(format!("Failed to serialize encrypted data: {}", e)))?;
+        let serialized = self.keyring.encrypt(DEFAULT_TENANT, data).await?;
         
         // Update metrics
         {
@@ -492,20 +594,7 @@ impl SecurityManager for StandardSecurityManager {
     
     async fn decrypt_data(&self, encrypted_data: &[u8]) -> Result<Vec<u8>> {
         debug!("Decrypting {} bytes of data", encrypted_data.len());
-        
-        // Deserialize encrypted data
-        let encrypted: EncryptedData = serde_json::from_slice(encrypted_data)
-            .map_err(|e| Error::Security(format!("Failed to deserialize encrypted data: {}", e)))?;
-        
-        let nonce = Nonce::assume_unique_for_key(encrypted.nonce);
-        let mut ciphertext = encrypted.ciphertext;
-        
-        // Decrypt the data
-        let plaintext = self.encryption_key.open_in_place(
-            nonce,
-            Aad::empty(),
-            &mut ciphertext,
-        ).map_err(|e| Error::Security(format!("Decryption failed: {:?}", e)))?;
+        let plaintext = self.keyring.decrypt(DEFAULT_TENANT, encrypted_data).await?;
         
         // Update metrics
         {
@@ -514,7 +603,7 @@ impl SecurityManager for StandardSecurityManager {
         }
         
         debug!("Successfully decrypted data to {} bytes", plaintext.len());
-        Ok(plaintext.to_vec())
+        Ok(plaintext)
     }
     
     async fn health_check(&self) -> Result<ComponentHealth> {
@@ -536,10 +625,18 @@ impl SecurityManager for StandardSecurityManager {
         health_metrics.insert("invalid_requests".to_string(), metrics.invalid_requests as f32);
         health_metrics.insert("blocked_requests".to_string(), metrics.blocked_requests as f32);
         health_metrics.insert("rate_limited_requests".to_string(), metrics.rate_limited_requests as f32);
+        health_metrics.insert("restricted_requests".to_string(), metrics.restricted_requests as f32);
+        health_metrics.insert("locked_out_requests".to_string(), metrics.locked_out_requests as f32);
+        health_metrics.insert("anomalous_requests".to_string(), metrics.anomalous_requests as f32);
+        health_metrics.insert("revoked_requests".to_string(), metrics.revoked_requests as f32);
+        health_metrics.insert("unauthorized_requests".to_string(), metrics.unauthorized_requests as f32);
+        health_metrics.insert("restricted_devices".to_string(), self.restricted.list().await.len() as f32);
         
         // Crypto operations
         health_metrics.insert("encryption_operations".to_string(), metrics.encryption_operations as f32);
         health_metrics.insert("decryption_operations".to_string(), metrics.decryption_operations as f32);
+        self.keyring.write_metrics(&mut health_metrics).await;
+        self.attestation.write_metrics(&mut health_metrics);
         
         // Success rate
         let success_rate = if metrics.total_requests > 0 {
@@ -0,0 +1,235 @@
+//! Prometheus text exposition
+//!
+//! [`PrometheusEncoder`] renders metric families in the text format scraped
+//! from `/metrics` (version 0.0.4). Names are the metric name prefixed with
+//! the configured namespace, unless `telemetry.prometheus.metric_names`
+//! renames the metric outright.
+
+use mcp_common::config::PrometheusConfig;
+use std::fmt::Write;
+
+/// `Content-Type` of the rendered text
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Label names and values of one sample
+pub type Labels<'a> = &'a [(&'a str, &'a str)];
+
+/// Prometheus metric type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+/// Request latencies in cumulative-style buckets
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    /// Bucket upper bounds in seconds, increasing
+    bounds: Vec<f64>,
+    /// Observations per bucket, with the last counting those above every bound
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl LatencyHistogram {
+    pub fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Record one latency, in seconds
+    pub fn observe(&mut self, seconds: f64) {
+        let bucket = self.bounds.iter().position(|bound| seconds <= *bound).unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new(&PrometheusConfig::default().latency_buckets_secs)
+    }
+}
+
+/// Builds the text exposition of a scrape
+pub struct PrometheusEncoder<'a> {
+    config: &'a PrometheusConfig,
+    output: String,
+}
+
+impl<'a> PrometheusEncoder<'a> {
+    pub fn new(config: &'a PrometheusConfig) -> Self {
+        Self {
+            config,
+            output: String::new(),
+        }
+    }
+
+    /// Exposed name of `metric`
+    pub fn name(&self, metric: &str) -> String {
+        match self.config.metric_names.get(metric) {
+            Some(name) => name.clone(),
+            None if self.config.namespace.is_empty() => metric.to_string(),
+            None => format!("{}_{}", self.config.namespace, metric),
+        }
+    }
+
+    /// Single unlabelled counter
+    pub fn counter(&mut self, metric: &str, help: &str, value: f64) {
+        self.family(MetricKind::Counter, metric, help, &[(&[], value)]);
+    }
+
+    /// Single unlabelled gauge
+    pub fn gauge(&mut self, metric: &str, help: &str, value: f64) {
+        self.family(MetricKind::Gauge, metric, help, &[(&[], value)]);
+    }
+
+    /// Metric family with one sample per label set; nothing is written for
+    /// a family without samples
+    pub fn family(&mut self, kind: MetricKind, metric: &str, help: &str, samples: &[(Labels, f64)]) {
+        if samples.is_empty() {
+            return;
+        }
+        let name = self.name(metric);
+        let kind = match kind {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        };
+        self.header(&name, kind, help);
+        for (labels, value) in samples {
+            self.sample(&name, labels, *value);
+        }
+    }
+
+    /// Metric family with one sample per value of a single label
+    pub fn labelled(&mut self, kind: MetricKind, metric: &str, help: &str, label: &str, samples: &[(String, f64)]) {
+        let labels: Vec<[(&str, &str); 1]> = samples.iter().map(|(value, _)| [(label, value.as_str())]).collect();
+        let samples: Vec<(Labels, f64)> = labels.iter().zip(samples).map(|(labels, (_, value))| (&labels[..], *value)).collect();
+        self.family(kind, metric, help, &samples);
+    }
+
+    /// Histogram family with cumulative `_bucket`, `_sum` and `_count` samples
+    pub fn histogram(&mut self, metric: &str, help: &str, histogram: &LatencyHistogram) {
+        let name = self.name(metric);
+        self.header(&name, "histogram", help);
+        let bucket = format!("{}_bucket", name);
+        let mut cumulative = 0;
+        for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+            cumulative += count;
+            self.sample(&bucket, &[("le", &format_value(*bound))], cumulative as f64);
+        }
+        self.sample(&bucket, &[("le", "+Inf")], histogram.count as f64);
+        self.sample(&format!("{}_sum", name), &[], histogram.sum);
+        self.sample(&format!("{}_count", name), &[], histogram.count as f64);
+    }
+
+    pub fn finish(self) -> String {
+        self.output
+    }
+
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+        let _ = writeln!(self.output, "# HELP {} {}", name, help);
+        let _ = writeln!(self.output, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: Labels, value: f64) {
+        self.output.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| {
+                    let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+                    format!("{}=\"{}\"", sanitize_label(label), value)
+                })
+                .collect();
+            let _ = write!(self.output, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.output, " {}", format_value(value));
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Label names allow `[a-zA-Z_][a-zA-Z0-9_]*`
+fn sanitize_label(label: &str) -> String {
+    label
+        .chars()
+        .enumerate()
+        .map(|(index, c)| {
+            if c.is_ascii_alphabetic() || c == '_' || (index > 0 && c.is_ascii_digit()) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_families_with_configured_names() {
+        let mut config = PrometheusConfig::default();
+        config.metric_names.insert("queue_depth".to_string(), "edge_offline_queue_depth".to_string());
+        let mut encoder = PrometheusEncoder::new(&config);
+        encoder.counter("requests_total", "Requests processed", 3.0);
+        encoder.gauge("queue_depth", "Queued requests", 7.0);
+        encoder.family(
+            MetricKind::Gauge,
+            "model_memory_bytes",
+            "Memory held by loaded models",
+            &[(&[("model", "llama \"7b\"")], 1024.0)],
+        );
+        encoder.family(MetricKind::Gauge, "empty", "Not written", &[]);
+
+        let text = encoder.finish();
+        assert!(text.contains("# TYPE mcp_requests_total counter\nmcp_requests_total 3\n"));
+        assert!(text.contains("# HELP edge_offline_queue_depth Queued requests\n"));
+        assert!(text.contains("edge_offline_queue_depth 7\n"));
+        assert!(text.contains("mcp_model_memory_bytes{model=\"llama \\\"7b\\\"\"} 1024\n"));
+        assert!(!text.contains("empty"));
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = LatencyHistogram::new(&[0.5, 1.0]);
+        for seconds in [0.25, 0.5, 0.75, 3.0] {
+            histogram.observe(seconds);
+        }
+        let config = PrometheusConfig {
+            namespace: String::new(),
+            ..Default::default()
+        };
+        let mut encoder = PrometheusEncoder::new(&config);
+        encoder.histogram("request_duration_seconds", "Request latency", &histogram);
+
+        let text = encoder.finish();
+        assert!(text.contains("# TYPE request_duration_seconds histogram\n"));
+        assert!(text.contains("request_duration_seconds_bucket{le=\"0.5\"} 2\n"));
+        assert!(text.contains("request_duration_seconds_bucket{le=\"1\"} 3\n"));
+        assert!(text.contains("request_duration_seconds_bucket{le=\"+Inf\"} 4\n"));
+        assert!(text.contains("request_duration_seconds_sum 4.5\n"));
+        assert!(text.contains("request_duration_seconds_count 4\n"));
+    }
+}
@@ -2,8 +2,11 @@
 
 use async_trait::async_trait;
 use mcp_common::metrics::{ComponentHealth};
+use mcp_common::usage::{ResourceUsage, TenantUsage};
 use mcp_common::{Config, Error, Result};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
 /// Telemetry collector trait for metrics and monitoring
@@ -15,26 +18,71 @@ pub trait TelemetryCollector {
     /// Record a failed request
     async fn record_request_error(&self, request_id: Uuid, error: &Error);
 
+    /// Record how long a processed request took
+    async fn record_request_latency(&self, latency: Duration);
+
+    /// Record a device whose clock is significantly skewed from gateway time
+    async fn record_clock_skew(&self, device_id: &str, skew_ms: i64);
+
+    /// Record the resources a request consumed, attributed to its tenant
+    async fn record_request_usage(&self, request_id: Uuid, tenant: &str, usage: &ResourceUsage, pathological: bool);
+
+    /// Resource usage accumulated per tenant
+    async fn usage_by_tenant(&self) -> HashMap<String, TenantUsage>;
+
     /// Get aggregated metrics
     async fn get_aggregated_metrics(&self) -> Result<mcp_common::metrics::AggregatedMetrics>;
 
+    /// Write request counts and latencies for a Prometheus scrape
+    async fn write_prometheus(&self, encoder: &mut PrometheusEncoder<'_>);
+
     /// Get health status
     async fn health_check(&self) -> Result<ComponentHealth>;
 
+    /// Apply the settings of a reloaded configuration that can change
+    /// without a restart
+    fn reconfigure(&self, _config: &Config) {}
+
     /// Shutdown the telemetry collector
     async fn shutdown(&self) -> Result<()>;
 }
 
+mod exposition;
+pub mod logs;
+mod otlp;
+mod otlp_signals;
 mod standard_telemetry;
 
+pub use exposition::{LatencyHistogram, Labels, MetricKind, PrometheusEncoder, PROMETHEUS_CONTENT_TYPE};
+pub use logs::OtlpLogLayer;
+pub use otlp::{encode_spans, OtlpExporter};
+pub use otlp_signals::{encode_logs, encode_metrics, OtlpSignalExporter};
 pub use standard_telemetry::{StandardTelemetryCollector, TelemetryConfig, PerformanceSummary};
 
 /// Create a new telemetry collector instance
 pub async fn create_telemetry_collector(
-    _config: Arc<Config>,
+    config: Arc<Config>,
 ) -> Result<Arc<dyn TelemetryCollector + Send + Sync>> {
-    let collector = StandardTelemetryCollector::new();
-    Ok(Arc::new(collector))
+    let mut collector =
+        StandardTelemetryCollector::new().with_latency_buckets(&config.telemetry.prometheus.latency_buckets_secs);
+    let tracing = &config.telemetry.tracing;
+    if tracing.enabled {
+        if let Some(exporter) = OtlpExporter::new(tracing)? {
+            let exporter = Arc::new(exporter);
+            exporter.start();
+            collector = collector.with_span_exporter(exporter);
+        }
+    }
+    let signal_exporter = OtlpSignalExporter::new(&config)?.map(Arc::new);
+    if let Some(exporter) = &signal_exporter {
+        logs::configure(&config.telemetry.otlp);
+        collector = collector.with_signal_exporter(exporter.clone());
+    }
+    let collector: Arc<dyn TelemetryCollector + Send + Sync> = Arc::new(collector);
+    if let Some(exporter) = signal_exporter {
+        exporter.start(Arc::downgrade(&collector));
+    }
+    Ok(collector)
 }
 
 #[cfg(test)]
@@ -44,35 +92,35 @@ mod tests {
     #[tokio::test]
     async fn test_telemetry_creation() {
         let config = TelemetryConfig::default();
-        let collector = StandardTelemetryCollector::new_with_config(config).await.unwrap();
+        let collector = StandardTelemetryCollector::with_config(config);
         
         // Verify basic functionality
-        let health = collector.get_health().await;
-        assert!(matches!(health, ComponentHealth::Healthy { .. }));
+        let health = collector.health_check().await.unwrap();
+        assert_eq!(health.status, mcp_common::metrics::HealthLevel::Healthy);
     }
     
     #[tokio::test]
     async fn test_metric_recording() {
         let config = TelemetryConfig::default();
-        let collector = StandardTelemetryCollector::new_with_config(config).await.unwrap();
+        let collector = StandardTelemetryCollector::with_config(config);
         
         // Record some metrics
-        collector.record_request_latency(100).await;
-        collector.record_error("test_error").await;
+        collector.record_detailed_request(Uuid::new_v4(), 100, true, None).await;
+        collector.record_detailed_request(Uuid::new_v4(), 200, false, Some("test_error")).await;
         
         let summary = collector.get_performance_summary().await;
-        assert_eq!(summary.total_requests, 1);
-        assert_eq!(summary.total_errors, 1);
+        assert_eq!(summary.total_requests, 2);
+        assert_eq!(summary.error_categories["test_error"], 1);
     }
     
     #[tokio::test]
     async fn test_performance_percentiles() {
         let config = TelemetryConfig::default();
-        let collector = StandardTelemetryCollector::new_with_config(config).await.unwrap();
+        let collector = StandardTelemetryCollector::with_config(config);
         
         // Record multiple latencies
         for latency in [50, 100, 150, 200, 250] {
-            collector.record_request_latency(latency).await;
+            collector.record_detailed_request(Uuid::new_v4(), latency, true, None).await;
         }
         
         let summary = collector.get_performance_summary().await;
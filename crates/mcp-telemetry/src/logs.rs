@@ -0,0 +1,194 @@
+//! Structured log records kept for OTLP export
+//!
+//! [`OtlpLogLayer`] sits in the global subscriber next to the formatted
+//! output and copies each event at or above the configured level into a
+//! bounded buffer, which the signal exporter drains in batches. Nothing is
+//! kept until [`configure`] enables the buffer, so the layer costs one
+//! atomic load per event on gateways that don't export logs.
+
+use chrono::{DateTime, Utc};
+use mcp_common::config::OtlpExportConfig;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// One log event as exported
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub timestamp: DateTime<Utc>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    /// The event's other fields, by name
+    pub fields: BTreeMap<String, String>,
+}
+
+struct LogBuffer {
+    enabled: AtomicBool,
+    /// Least severe level kept, as the `Level`'s verbosity rank
+    level: AtomicUsize,
+    capacity: AtomicUsize,
+    dropped: AtomicU64,
+    records: Mutex<VecDeque<LogRecord>>,
+}
+
+impl LogBuffer {
+    fn keeps(&self, level: &Level) -> bool {
+        self.enabled.load(Ordering::Relaxed) && rank(level) <= self.level.load(Ordering::Relaxed)
+    }
+
+    fn push(&self, record: LogRecord) {
+        let mut records = self.lock();
+        if records.len() >= self.capacity.load(Ordering::Relaxed) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        records.push_back(record);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<LogRecord>> {
+        self.records.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn buffer() -> &'static LogBuffer {
+    static GLOBAL: OnceLock<LogBuffer> = OnceLock::new();
+    GLOBAL.get_or_init(|| {
+        let defaults = OtlpExportConfig::default();
+        LogBuffer {
+            enabled: AtomicBool::new(false),
+            level: AtomicUsize::new(rank(&parse_level(&defaults.log_level))),
+            capacity: AtomicUsize::new(defaults.max_buffered_logs),
+            dropped: AtomicU64::new(0),
+            records: Mutex::new(VecDeque::new()),
+        }
+    })
+}
+
+/// Start keeping log records for export with `config`'s level and limit
+pub fn configure(config: &OtlpExportConfig) {
+    let buffer = buffer();
+    buffer.level.store(rank(&parse_level(&config.log_level)), Ordering::Relaxed);
+    buffer.capacity.store(config.max_buffered_logs, Ordering::Relaxed);
+    buffer.enabled.store(true, Ordering::Relaxed);
+}
+
+/// Stop keeping log records, discarding those not yet exported
+pub fn disable() {
+    let buffer = buffer();
+    buffer.enabled.store(false, Ordering::Relaxed);
+    buffer.lock().clear();
+}
+
+/// Take up to `max` log records, oldest first
+pub fn drain(max: usize) -> Vec<LogRecord> {
+    let mut records = buffer().lock();
+    let count = max.min(records.len());
+    records.drain(..count).collect()
+}
+
+/// Log records waiting for export
+pub fn pending_logs() -> usize {
+    buffer().lock().len()
+}
+
+/// Log records discarded because the buffer was full
+pub fn dropped_logs() -> u64 {
+    buffer().dropped.load(Ordering::Relaxed)
+}
+
+fn parse_level(level: &str) -> Level {
+    level.parse().unwrap_or(Level::INFO)
+}
+
+/// 0 for errors up to 4 for trace
+fn rank(level: &Level) -> usize {
+    match *level {
+        Level::ERROR => 0,
+        Level::WARN => 1,
+        Level::INFO => 2,
+        Level::DEBUG => 3,
+        Level::TRACE => 4,
+    }
+}
+
+/// Copies events into the export buffer; add it to the global subscriber
+pub struct OtlpLogLayer;
+
+impl<S: Subscriber> Layer<S> for OtlpLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let buffer = buffer();
+        if !buffer.keeps(metadata.level()) {
+            return;
+        }
+        let mut fields = RecordFields::default();
+        event.record(&mut fields);
+        buffer.push(LogRecord {
+            timestamp: Utc::now(),
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: fields.message,
+            fields: fields.fields,
+        });
+    }
+}
+
+#[derive(Default)]
+struct RecordFields {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for RecordFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            self.fields.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_keeps_events_at_the_configured_level() {
+        configure(&OtlpExportConfig {
+            log_level: "info".to_string(),
+            max_buffered_logs: 2,
+            ..Default::default()
+        });
+        let subscriber = tracing_subscriber::registry().with(OtlpLogLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("not exported");
+            tracing::warn!(device = "sensor-1", "link lost");
+            tracing::info!("restored");
+            tracing::error!("over capacity");
+        });
+
+        let records = drain(10);
+        disable();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].level, Level::WARN);
+        assert_eq!(records[0].message, "link lost");
+        assert_eq!(records[0].fields["device"], "sensor-1");
+        assert_eq!(records[1].message, "restored");
+        assert!(dropped_logs() >= 1);
+    }
+}
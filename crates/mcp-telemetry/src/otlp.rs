@@ -0,0 +1,251 @@
+//! OTLP span export
+//!
+//! Drains the spans recorded through [`mcp_common::trace_context`] every
+//! `export_interval_ms` and posts them to an OpenTelemetry collector using
+//! OTLP/HTTP with JSON encoding (`POST {otlp_endpoint}/v1/traces`). A batch
+//! the collector rejects is dropped rather than retried, so a collector
+//! outage cannot grow memory; the buffer in front of it is bounded too.
+
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::config::TracingConfig;
+use mcp_common::trace_context::{self, SpanKind, SpanRecord};
+use mcp_common::{Error, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Timeout for one export request
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Posts recorded spans to an OTLP/HTTP collector
+pub struct OtlpExporter {
+    client: reqwest::Client,
+    url: String,
+    service_name: String,
+    interval_ms: AtomicU64,
+    max_batch: usize,
+    exported: AtomicU64,
+    failed: AtomicU64,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl OtlpExporter {
+    /// Exporter for the configured collector, None when no endpoint is set
+    pub fn new(config: &TracingConfig) -> Result<Option<Self>> {
+        let Some(endpoint) = &config.otlp_endpoint else {
+            return Ok(None);
+        };
+        let endpoint = endpoint.trim_end_matches('/');
+        let url = if endpoint.ends_with("/v1/traces") {
+            endpoint.to_string()
+        } else {
+            format!("{}/v1/traces", endpoint)
+        };
+        let client = reqwest::Client::builder()
+            .timeout(EXPORT_TIMEOUT)
+            .build()
+            .map_err(|e| Error::Configuration(format!("Failed to create OTLP client: {}", e)))?;
+        Ok(Some(Self {
+            client,
+            url,
+            service_name: config.service_name.clone(),
+            interval_ms: AtomicU64::new(config.export_interval_ms.max(100)),
+            max_batch: config.max_batch_spans.max(1),
+            exported: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            task: Mutex::new(None),
+        }))
+    }
+
+    /// Time between exports
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.load(Ordering::Relaxed))
+    }
+
+    /// Change the export interval, taking effect after the next export
+    pub fn set_interval(&self, interval_ms: u64) {
+        self.interval_ms.store(interval_ms.max(100), Ordering::Relaxed);
+    }
+
+    /// Export in the background
+    pub fn start(self: &Arc<Self>) {
+        let exporter = Arc::downgrade(self);
+        let handle = tokio::spawn(async move {
+            while let Some(interval) = exporter.upgrade().map(|exporter| exporter.interval()) {
+                tokio::time::sleep(interval).await;
+                let Some(exporter) = exporter.upgrade() else {
+                    break;
+                };
+                exporter.export_pending().await;
+            }
+        });
+        if let Some(previous) = self.lock_task().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stop exporting in the background and send what is left
+    pub async fn stop(&self) {
+        if let Some(handle) = self.lock_task().take() {
+            handle.abort();
+        }
+        self.export_pending().await;
+    }
+
+    /// Send every pending span, one batch at a time
+    pub async fn export_pending(&self) {
+        loop {
+            let spans = trace_context::drain(self.max_batch);
+            if spans.is_empty() {
+                return;
+            }
+            let count = spans.len();
+            match self.export(&spans).await {
+                Ok(()) => {
+                    debug!("Exported {} spans to {}", count, self.url);
+                    self.exported.fetch_add(count as u64, Ordering::Relaxed);
+                },
+                Err(e) => {
+                    warn!("Dropped {} spans: {}", count, e);
+                    self.failed.fetch_add(count as u64, Ordering::Relaxed);
+                    return;
+                },
+            }
+        }
+    }
+
+    async fn export(&self, spans: &[SpanRecord]) -> Result<()> {
+        let body = serde_json::to_vec(&encode_spans(&self.service_name, spans))?;
+        let sent = body.len() as u64;
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Error::Network(format!("OTLP export to {} failed: {}", self.url, e)))?;
+        let status = response.status();
+        let received = response.bytes().await.map_or(0, |body| body.len() as u64);
+        bandwidth::record(Subsystem::Telemetry, sent, received);
+        if !status.is_success() {
+            return Err(Error::Network(format!("OTLP collector {} returned {}", self.url, status)));
+        }
+        Ok(())
+    }
+
+    /// Export counters for health output
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        metrics.insert("spans_exported".to_string(), self.exported.load(Ordering::Relaxed) as f32);
+        metrics.insert("spans_export_failed".to_string(), self.failed.load(Ordering::Relaxed) as f32);
+        metrics.insert("spans_pending".to_string(), trace_context::pending_spans() as f32);
+        metrics.insert("spans_dropped".to_string(), trace_context::dropped_spans() as f32);
+    }
+
+    fn lock_task(&self) -> std::sync::MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.task.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// OTLP/JSON `ExportTraceServiceRequest` for `spans`
+pub fn encode_spans(service_name: &str, spans: &[SpanRecord]) -> Value {
+    let spans: Vec<Value> = spans.iter().map(encode_span).collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [string_attribute("service.name", service_name)],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "mcp-edge-gateway", "version": env!("CARGO_PKG_VERSION") },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+fn encode_span(span: &SpanRecord) -> Value {
+    let nanos = |time: chrono::DateTime<chrono::Utc>| time.timestamp_nanos_opt().unwrap_or(0).max(0).to_string();
+    let mut encoded = json!({
+        "traceId": span.trace_id,
+        "spanId": span.span_id,
+        "name": span.name,
+        "kind": match span.kind {
+            SpanKind::Internal => 1,
+            SpanKind::Server => 2,
+            SpanKind::Client => 3,
+        },
+        "startTimeUnixNano": nanos(span.start_time),
+        "endTimeUnixNano": nanos(span.end_time),
+        "attributes": span
+            .attributes
+            .iter()
+            .map(|(key, value)| string_attribute(key, value))
+            .collect::<Vec<_>>(),
+        "status": match &span.error {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 1 }),
+        },
+    });
+    if let Some(parent) = &span.parent_span_id {
+        encoded["parentSpanId"] = json!(parent);
+    }
+    encoded
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_encodes_otlp_json() {
+        let span = SpanRecord {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+            span_id: "00f067aa0ba902b7".to_string(),
+            parent_span_id: Some("b7ad6b7169203331".to_string()),
+            name: "router.cloud_forward".to_string(),
+            kind: SpanKind::Client,
+            start_time: Utc.timestamp_opt(1_700_000_000, 5).unwrap(),
+            end_time: Utc.timestamp_opt(1_700_000_001, 0).unwrap(),
+            attributes: [("router.endpoint".to_string(), "primary".to_string())].into(),
+            error: Some("unreachable".to_string()),
+        };
+        let root = SpanRecord {
+            parent_span_id: None,
+            error: None,
+            ..span.clone()
+        };
+
+        let encoded = encode_spans("edge-1", &[span, root]);
+        let resource = &encoded["resourceSpans"][0];
+        assert_eq!(resource["resource"]["attributes"][0]["value"]["stringValue"], "edge-1");
+        let spans = &resource["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(spans[0]["parentSpanId"], "b7ad6b7169203331");
+        assert_eq!(spans[0]["kind"], 3);
+        assert_eq!(spans[0]["startTimeUnixNano"], "1700000000000000005");
+        assert_eq!(spans[0]["attributes"][0]["key"], "router.endpoint");
+        assert_eq!(spans[0]["status"]["code"], 2);
+        assert!(spans[1].get("parentSpanId").is_none());
+        assert_eq!(spans[1]["status"]["code"], 1);
+    }
+
+    #[test]
+    fn test_exporter_targets_traces_path() {
+        let mut config = TracingConfig::default();
+        assert!(OtlpExporter::new(&config).unwrap().is_none());
+
+        config.otlp_endpoint = Some("http://collector:4318/".to_string());
+        assert_eq!(OtlpExporter::new(&config).unwrap().unwrap().url, "http://collector:4318/v1/traces");
+        config.otlp_endpoint = Some("https://otel.example.com/v1/traces".to_string());
+        assert_eq!(OtlpExporter::new(&config).unwrap().unwrap().url, "https://otel.example.com/v1/traces");
+    }
+}
@@ -0,0 +1,600 @@
+//! OTLP metric and log export
+//!
+//! Every `metrics_interval_ms` the exporter takes a snapshot of the
+//! collector's aggregated metrics and drains the log records kept by
+//! [`crate::logs`], and posts them to the collector at
+//! `telemetry.export_endpoint` (`/v1/metrics` and `/v1/logs`) as OTLP/HTTP
+//! JSON. Bodies above `compression_threshold_bytes` are compressed with
+//! `compression_algorithm` and named in `Content-Encoding`.
+//!
+//! A failed post is retried with exponential backoff and jitter. A batch
+//! that still fails is spilled to queue storage under
+//! `otlp:<sequence>:<signal>:<encoding>` keys, and spilled batches are sent
+//! again, oldest first, before anything new on the next export. While the
+//! spill can't be sent, new batches go straight to it instead of waiting
+//! through retries, so a collector outage neither blocks the exporter nor
+//! grows memory.
+
+use crate::logs::{self, LogRecord};
+use crate::TelemetryCollector;
+use mcp_common::bandwidth::{self, Subsystem};
+use mcp_common::compression::PayloadCompression;
+use mcp_common::metrics::AggregatedMetrics;
+use mcp_common::{Config, Error, Result};
+use mcp_queue::{open_storage, QueueStorageBackend};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn, Level};
+use uuid::Uuid;
+
+/// Timeout for one export request
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest wait between retries of one export
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Key prefix of spilled batches in queue storage
+const SPILL_PREFIX: &str = "otlp:";
+
+/// Signals this exporter sends, by their OTLP/HTTP path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signal {
+    Metrics,
+    Logs,
+}
+
+impl Signal {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Metrics => "metrics",
+            Self::Logs => "logs",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "metrics" => Some(Self::Metrics),
+            "logs" => Some(Self::Logs),
+            _ => None,
+        }
+    }
+}
+
+/// An encoded batch ready to post
+struct Batch {
+    signal: Signal,
+    body: Vec<u8>,
+    compression: PayloadCompression,
+}
+
+/// Pushes metrics and structured logs to an OTLP/HTTP collector
+pub struct OtlpSignalExporter {
+    client: reqwest::Client,
+    endpoint: String,
+    service_name: String,
+    compression: PayloadCompression,
+    compression_threshold: usize,
+    interval_ms: AtomicU64,
+    max_batch_logs: usize,
+    max_retries: u32,
+    retry_initial_delay: Duration,
+    spill: Box<dyn QueueStorageBackend>,
+    max_spilled: usize,
+    spill_sequence: AtomicU64,
+    exported: AtomicU64,
+    failed: AtomicU64,
+    spilled: AtomicU64,
+    spill_dropped: AtomicU64,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl OtlpSignalExporter {
+    /// Exporter for the configured collector, None unless
+    /// `telemetry.opentelemetry_enabled` is set with an endpoint
+    pub fn new(config: &Config) -> Result<Option<Self>> {
+        let otlp = &config.telemetry.otlp;
+        let storage = open_storage(config.queue.storage_backend, Some(&otlp.spill_path))?;
+        Self::with_storage(config, storage)
+    }
+
+    /// Exporter spilling into `storage` instead of `telemetry.otlp.spill_path`
+    pub fn with_storage(config: &Config, storage: Box<dyn QueueStorageBackend>) -> Result<Option<Self>> {
+        let telemetry = &config.telemetry;
+        let Some(endpoint) = telemetry.export_endpoint.as_deref().filter(|_| telemetry.opentelemetry_enabled) else {
+            return Ok(None);
+        };
+        let compression = if telemetry.compression_enabled {
+            PayloadCompression::from_content_encoding(&telemetry.compression_algorithm).ok_or_else(|| {
+                Error::Configuration(format!(
+                    "Unsupported telemetry compression algorithm: {}",
+                    telemetry.compression_algorithm
+                ))
+            })?
+        } else {
+            PayloadCompression::None
+        };
+        let client = reqwest::Client::builder()
+            .timeout(EXPORT_TIMEOUT)
+            .build()
+            .map_err(|e| Error::Configuration(format!("Failed to create OTLP client: {}", e)))?;
+        let next_sequence = storage
+            .scan(SPILL_PREFIX)?
+            .last()
+            .and_then(|(key, _)| parse_spill_key(key))
+            .map_or(0, |(sequence, _, _)| sequence + 1);
+        let otlp = &telemetry.otlp;
+        Ok(Some(Self {
+            client,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            service_name: telemetry.tracing.service_name.clone(),
+            compression,
+            compression_threshold: telemetry
+                .compression_threshold_bytes
+                .unwrap_or(mcp_common::compression::MIN_COMPRESSED_BYTES),
+            interval_ms: AtomicU64::new(telemetry.metrics_interval_ms.max(100)),
+            max_batch_logs: otlp.max_batch_logs.max(1),
+            max_retries: otlp.max_retries,
+            retry_initial_delay: Duration::from_millis(otlp.retry_initial_delay_ms),
+            spill: storage,
+            max_spilled: otlp.max_spilled_batches.max(1),
+            spill_sequence: AtomicU64::new(next_sequence),
+            exported: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            spilled: AtomicU64::new(0),
+            spill_dropped: AtomicU64::new(0),
+            task: Mutex::new(None),
+        }))
+    }
+
+    /// Time between exports
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.load(Ordering::Relaxed))
+    }
+
+    /// Change the export interval, taking effect after the next export
+    pub fn set_interval(&self, interval_ms: u64) {
+        self.interval_ms.store(interval_ms.max(100), Ordering::Relaxed);
+    }
+
+    /// Export in the background, taking metrics from `collector`
+    pub fn start(self: &Arc<Self>, collector: Weak<dyn TelemetryCollector + Send + Sync>) {
+        let exporter = Arc::downgrade(self);
+        let handle = tokio::spawn(async move {
+            while let Some(interval) = exporter.upgrade().map(|exporter| exporter.interval()) {
+                tokio::time::sleep(interval).await;
+                let Some(exporter) = exporter.upgrade() else {
+                    break;
+                };
+                let metrics = match collector.upgrade() {
+                    Some(collector) => collector.get_aggregated_metrics().await.ok(),
+                    None => None,
+                };
+                exporter.export_pending(metrics.as_ref()).await;
+            }
+        });
+        if let Some(previous) = self.lock_task().replace(handle) {
+            previous.abort();
+        }
+    }
+
+    /// Stop exporting in the background and send the logs left, spilling
+    /// what the collector doesn't take
+    pub async fn stop(&self) {
+        if let Some(handle) = self.lock_task().take() {
+            handle.abort();
+        }
+        self.export_pending(None).await;
+    }
+
+    /// Send the spilled batches, then `metrics` and every pending log record
+    pub async fn export_pending(&self, metrics: Option<&AggregatedMetrics>) {
+        let mut reachable = self.replay_spill().await;
+        if let Some(metrics) = metrics {
+            let body = encode_metrics(&self.service_name, metrics);
+            reachable = self.deliver(Signal::Metrics, &body, reachable).await;
+        }
+        loop {
+            let records = logs::drain(self.max_batch_logs);
+            if records.is_empty() {
+                break;
+            }
+            let body = encode_logs(&self.service_name, &records);
+            reachable = self.deliver(Signal::Logs, &body, reachable).await;
+        }
+    }
+
+    /// Send `body`, retrying while the collector is `reachable` and
+    /// spilling it otherwise; returns whether the collector took it
+    async fn deliver(&self, signal: Signal, body: &Value, reachable: bool) -> bool {
+        let batch = match self.encode(signal, body) {
+            Ok(batch) => batch,
+            Err(e) => {
+                warn!("Dropped OTLP {} batch: {}", signal.name(), e);
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                return reachable;
+            },
+        };
+        if reachable {
+            match self.send_with_retries(&batch).await {
+                Ok(()) => return true,
+                Err(e) => warn!("OTLP {} export failed, spilling the batch: {}", signal.name(), e),
+            }
+        }
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        if let Err(e) = self.spill(&batch) {
+            warn!("Dropped OTLP {} batch that could not be spilled: {}", signal.name(), e);
+        }
+        false
+    }
+
+    fn encode(&self, signal: Signal, body: &Value) -> Result<Batch> {
+        let body = serde_json::to_vec(body)?;
+        if self.compression == PayloadCompression::None || body.len() < self.compression_threshold {
+            return Ok(Batch {
+                signal,
+                body,
+                compression: PayloadCompression::None,
+            });
+        }
+        let compressed = self.compression.compress(&body)?;
+        Ok(if compressed.len() < body.len() {
+            Batch {
+                signal,
+                body: compressed,
+                compression: self.compression,
+            }
+        } else {
+            Batch {
+                signal,
+                body,
+                compression: PayloadCompression::None,
+            }
+        })
+    }
+
+    async fn send_with_retries(&self, batch: &Batch) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match self.send(batch).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.max_retries => return Err(e),
+                Err(e) => {
+                    let delay = retry_delay(self.retry_initial_delay, attempt);
+                    debug!("OTLP {} export failed, retrying in {:?}: {}", batch.signal.name(), delay, e);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                },
+            }
+        }
+    }
+
+    async fn send(&self, batch: &Batch) -> Result<()> {
+        let url = format!("{}/v1/{}", self.endpoint, batch.signal.name());
+        let mut request = self
+            .client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(batch.body.clone());
+        if let Some(encoding) = batch.compression.content_encoding() {
+            request = request.header(reqwest::header::CONTENT_ENCODING, encoding);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Network(format!("OTLP export to {} failed: {}", url, e)))?;
+        let status = response.status();
+        let received = response.bytes().await.map_or(0, |body| body.len() as u64);
+        bandwidth::record(Subsystem::Telemetry, batch.body.len() as u64, received);
+        if !status.is_success() {
+            return Err(Error::Network(format!("OTLP collector {} returned {}", url, status)));
+        }
+        self.exported.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Keep `batch` in queue storage, dropping the oldest spilled batches
+    /// beyond the limit
+    fn spill(&self, batch: &Batch) -> Result<()> {
+        let sequence = self.spill_sequence.fetch_add(1, Ordering::Relaxed);
+        let encoding = batch.compression.content_encoding().unwrap_or("identity");
+        let key = format!("{}{:020}:{}:{}", SPILL_PREFIX, sequence, batch.signal.name(), encoding);
+        self.spill.put(&key, &batch.body)?;
+        self.spilled.fetch_add(1, Ordering::Relaxed);
+
+        let spilled = self.spill.scan(SPILL_PREFIX)?;
+        let excess = spilled.len().saturating_sub(self.max_spilled);
+        for (key, _) in &spilled[..excess] {
+            self.spill.remove(key)?;
+            self.spill_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.spill.flush()
+    }
+
+    /// Send spilled batches oldest first, stopping at the first failure;
+    /// returns whether the collector took them all
+    async fn replay_spill(&self) -> bool {
+        let spilled = match self.spill.scan(SPILL_PREFIX) {
+            Ok(spilled) => spilled,
+            Err(e) => {
+                warn!("Failed to read spilled OTLP batches: {}", e);
+                return true;
+            },
+        };
+        for (key, body) in spilled {
+            let Some((_, signal, compression)) = parse_spill_key(&key) else {
+                let _ = self.spill.remove(&key);
+                continue;
+            };
+            let batch = Batch {
+                signal,
+                body,
+                compression,
+            };
+            if let Err(e) = self.send(&batch).await {
+                debug!("Collector still unreachable, keeping spilled batches: {}", e);
+                return false;
+            }
+            if let Err(e) = self.spill.remove(&key) {
+                warn!("Failed to remove sent OTLP batch {}: {}", key, e);
+            }
+        }
+        true
+    }
+
+    /// Export counters for health output
+    pub fn write_metrics(&self, metrics: &mut HashMap<String, f32>) {
+        let spill_pending = self.spill.scan(SPILL_PREFIX).map_or(0, |spilled| spilled.len());
+        metrics.insert("otlp_batches_exported".to_string(), self.exported.load(Ordering::Relaxed) as f32);
+        metrics.insert("otlp_batches_failed".to_string(), self.failed.load(Ordering::Relaxed) as f32);
+        metrics.insert("otlp_batches_spilled".to_string(), self.spilled.load(Ordering::Relaxed) as f32);
+        metrics.insert("otlp_spill_pending".to_string(), spill_pending as f32);
+        metrics.insert("otlp_spill_dropped".to_string(), self.spill_dropped.load(Ordering::Relaxed) as f32);
+        metrics.insert("logs_pending".to_string(), logs::pending_logs() as f32);
+        metrics.insert("logs_dropped".to_string(), logs::dropped_logs() as f32);
+    }
+
+    fn lock_task(&self) -> std::sync::MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.task.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Sequence, signal and codec of a spilled batch's key
+fn parse_spill_key(key: &str) -> Option<(u64, Signal, PayloadCompression)> {
+    let mut parts = key.strip_prefix(SPILL_PREFIX)?.splitn(3, ':');
+    let sequence = parts.next()?.parse().ok()?;
+    let signal = Signal::from_name(parts.next()?)?;
+    let compression = PayloadCompression::from_content_encoding(parts.next()?)?;
+    Some((sequence, signal, compression))
+}
+
+/// Backoff before retry `attempt` (from 0): doubling from `initial`, capped,
+/// with between half and all of it taken at random so gateways that lost
+/// the collector together don't retry in step
+fn retry_delay(initial: Duration, attempt: u32) -> Duration {
+    let backoff = initial.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_RETRY_DELAY);
+    let random = (Uuid::new_v4().as_u128() as u64) as f64 / u64::MAX as f64;
+    backoff.mul_f64(0.5 + random / 2.0)
+}
+
+/// OTLP/JSON `ExportMetricsServiceRequest` for a metrics snapshot
+pub fn encode_metrics(service_name: &str, metrics: &AggregatedMetrics) -> Value {
+    let time = nanos(metrics.timestamp);
+    let requests = &metrics.requests;
+    let mut encoded = vec![
+        sum("mcp.requests", "{request}", &time, requests.total_requests as f64),
+        sum("mcp.requests.successful", "{request}", &time, requests.successful_requests as f64),
+        sum("mcp.requests.failed", "{request}", &time, requests.failed_requests as f64),
+        gauge("mcp.request.latency.avg", "ms", &time, requests.avg_latency_ms as f64),
+        gauge("mcp.request.latency.p95", "ms", &time, requests.p95_latency_ms as f64),
+        gauge("mcp.request.latency.p99", "ms", &time, requests.p99_latency_ms as f64),
+        gauge("mcp.system.cpu.usage", "%", &time, metrics.system.cpu_usage_percent as f64),
+        gauge("mcp.system.memory.usage", "MiBy", &time, metrics.system.memory_usage_mb as f64),
+        gauge("mcp.queue.size", "{request}", &time, metrics.queue.queue_size as f64),
+    ];
+    let mut custom: Vec<_> = metrics.custom.iter().collect();
+    custom.sort_by(|a, b| a.0.cmp(b.0));
+    encoded.extend(
+        custom
+            .into_iter()
+            .map(|(name, value)| gauge(&format!("mcp.{}", name), "1", &time, *value as f64)),
+    );
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(service_name),
+            "scopeMetrics": [{
+                "scope": scope(),
+                "metrics": encoded,
+            }],
+        }],
+    })
+}
+
+/// OTLP/JSON `ExportLogsServiceRequest` for `records`
+pub fn encode_logs(service_name: &str, records: &[LogRecord]) -> Value {
+    let records: Vec<Value> = records.iter().map(encode_log).collect();
+    json!({
+        "resourceLogs": [{
+            "resource": resource(service_name),
+            "scopeLogs": [{
+                "scope": scope(),
+                "logRecords": records,
+            }],
+        }],
+    })
+}
+
+fn encode_log(record: &LogRecord) -> Value {
+    let time = nanos(record.timestamp);
+    let attributes: Vec<Value> = std::iter::once(string_attribute("log.target", &record.target))
+        .chain(record.fields.iter().map(|(key, value)| string_attribute(key, value)))
+        .collect();
+    json!({
+        "timeUnixNano": time,
+        "observedTimeUnixNano": time,
+        "severityNumber": severity_number(&record.level),
+        "severityText": record.level.as_str(),
+        "body": { "stringValue": record.message },
+        "attributes": attributes,
+    })
+}
+
+/// OTLP severity of the first step of each level's range
+fn severity_number(level: &Level) -> u8 {
+    match *level {
+        Level::TRACE => 1,
+        Level::DEBUG => 5,
+        Level::INFO => 9,
+        Level::WARN => 13,
+        Level::ERROR => 17,
+    }
+}
+
+fn sum(name: &str, unit: &str, time: &str, value: f64) -> Value {
+    json!({
+        "name": name,
+        "unit": unit,
+        "sum": {
+            "dataPoints": [{ "timeUnixNano": time, "asDouble": value }],
+            // Cumulative since the gateway started
+            "aggregationTemporality": 2,
+            "isMonotonic": true,
+        },
+    })
+}
+
+fn gauge(name: &str, unit: &str, time: &str, value: f64) -> Value {
+    json!({
+        "name": name,
+        "unit": unit,
+        "gauge": {
+            "dataPoints": [{ "timeUnixNano": time, "asDouble": value }],
+        },
+    })
+}
+
+fn resource(service_name: &str) -> Value {
+    json!({ "attributes": [string_attribute("service.name", service_name)] })
+}
+
+fn scope() -> Value {
+    json!({ "name": "mcp-edge-gateway", "version": env!("CARGO_PKG_VERSION") })
+}
+
+fn nanos(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.timestamp_nanos_opt().unwrap_or(0).max(0).to_string()
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use mcp_queue::MemoryStorage;
+    use std::collections::BTreeMap;
+
+    fn config(endpoint: &str) -> Config {
+        let mut config = Config::default();
+        config.telemetry.opentelemetry_enabled = true;
+        config.telemetry.export_endpoint = Some(endpoint.to_string());
+        config.telemetry.compression_threshold_bytes = Some(64);
+        config.telemetry.otlp.max_retries = 1;
+        config.telemetry.otlp.retry_initial_delay_ms = 1;
+        config.telemetry.otlp.max_spilled_batches = 2;
+        config
+    }
+
+    fn exporter(config: &Config) -> OtlpSignalExporter {
+        OtlpSignalExporter::with_storage(config, Box::new(MemoryStorage::default()))
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn metrics() -> AggregatedMetrics {
+        crate::StandardTelemetryCollector::new().get_aggregated_metrics().await.unwrap()
+    }
+
+    #[test]
+    fn test_encodes_otlp_logs() {
+        let record = LogRecord {
+            timestamp: Utc.timestamp_opt(1_700_000_000, 5).unwrap(),
+            level: Level::WARN,
+            target: "mcp_queue::sync".to_string(),
+            message: "link lost".to_string(),
+            fields: BTreeMap::from([("device".to_string(), "sensor-1".to_string())]),
+        };
+        let encoded = encode_logs("edge-1", &[record]);
+        let logs = &encoded["resourceLogs"][0];
+        assert_eq!(logs["resource"]["attributes"][0]["value"]["stringValue"], "edge-1");
+        let record = &logs["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["timeUnixNano"], "1700000000000000005");
+        assert_eq!(record["severityNumber"], 13);
+        assert_eq!(record["severityText"], "WARN");
+        assert_eq!(record["body"]["stringValue"], "link lost");
+        assert_eq!(record["attributes"][0]["value"]["stringValue"], "mcp_queue::sync");
+        assert_eq!(record["attributes"][1]["key"], "device");
+    }
+
+    #[test]
+    fn test_exporter_needs_opentelemetry_and_an_endpoint() {
+        let mut config = config("http://collector:4318/");
+        assert_eq!(exporter(&config).endpoint, "http://collector:4318");
+        assert_eq!(exporter(&config).compression, PayloadCompression::Lz4);
+
+        config.telemetry.opentelemetry_enabled = false;
+        let spill = || Box::new(MemoryStorage::default());
+        assert!(OtlpSignalExporter::with_storage(&config, spill()).unwrap().is_none());
+        config.telemetry.opentelemetry_enabled = true;
+        config.telemetry.export_endpoint = None;
+        assert!(OtlpSignalExporter::with_storage(&config, spill()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_compresses_bodies_above_the_threshold() {
+        let exporter = exporter(&config("http://collector:4318"));
+        let small = exporter.encode(Signal::Logs, &json!({ "a": 1 })).unwrap();
+        assert_eq!(small.compression, PayloadCompression::None);
+
+        let large = json!({ "records": vec!["the same log line"; 50] });
+        let batch = exporter.encode(Signal::Logs, &large).unwrap();
+        assert_eq!(batch.compression, PayloadCompression::Lz4);
+        let body = PayloadCompression::Lz4.decompress(&batch.body).unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), large);
+    }
+
+    #[tokio::test]
+    async fn test_spills_while_the_collector_is_unreachable() {
+        // Nothing listens on the discard port
+        let exporter = exporter(&config("http://127.0.0.1:9"));
+        let body = encode_metrics("edge-1", &metrics().await);
+        // As `export_pending` does, without draining the global log buffer
+        for _ in 0..3 {
+            let reachable = exporter.replay_spill().await;
+            assert!(!exporter.deliver(Signal::Metrics, &body, reachable).await);
+        }
+
+        let spilled = exporter.spill.scan(SPILL_PREFIX).unwrap();
+        assert_eq!(spilled.len(), 2);
+        assert_eq!(exporter.spill_dropped.load(Ordering::Relaxed), 1);
+        // The oldest batch was dropped; keys sort by sequence
+        assert!(spilled[0].0.starts_with("otlp:00000000000000000001:metrics:"));
+        assert_eq!(parse_spill_key(&spilled[1].0).unwrap().0, 2);
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_with_jitter() {
+        let initial = Duration::from_millis(100);
+        for attempt in 0..4 {
+            let backoff = initial * 2u32.pow(attempt);
+            let delay = retry_delay(initial, attempt);
+            assert!(delay >= backoff / 2 && delay <= backoff, "{:?} for attempt {}", delay, attempt);
+        }
+        assert!(retry_delay(initial, 20) <= MAX_RETRY_DELAY);
+    }
+}
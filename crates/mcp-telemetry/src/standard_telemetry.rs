@@ -1,23 +1,31 @@
 //! Standard telemetry collector implementation
 
 use mcp_common::{Error, Result, RequestId, MCPRequest, MCPResponse};
+use mcp_common::events::{self, AlertSeverity, GatewayEvent};
+use mcp_common::usage::{ResourceUsage, TenantUsage};
 use mcp_common::metrics::{
     ComponentHealth, HealthLevel, AggregatedMetrics, SystemMetrics, RequestAggregates,
     QueueMetrics, SecurityMetrics
 };
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{info, debug};
 use chrono::{DateTime, Utc, Timelike};
 use uuid::Uuid;
 
+use crate::exposition::{LatencyHistogram, MetricKind, PrometheusEncoder};
+use crate::otlp::OtlpExporter;
+use crate::otlp_signals::OtlpSignalExporter;
 use crate::TelemetryCollector;
 
 /// Standard implementation of telemetry collector
 pub struct StandardTelemetryCollector {
     metrics: Arc<RwLock<TelemetryMetrics>>,
     config: TelemetryConfig,
+    span_exporter: Option<Arc<OtlpExporter>>,
+    signal_exporter: Option<Arc<OtlpSignalExporter>>,
 }
 
 /// Telemetry configuration
@@ -60,6 +68,10 @@ struct TelemetryMetrics {
     peak_connections: u32,                    // Peak concurrent connections
     last_error_time: Option<DateTime<Utc>>,   // Last error timestamp
     recovery_attempts: u64,                   // Recovery operation count
+    clock_skews: HashMap<String, i64>,        // Skewed devices and their skew
+    tenant_usage: HashMap<String, TenantUsage>, // Resource usage per tenant
+    pathological_requests: u64,               // Requests over the usage thresholds
+    latency_histogram: LatencyHistogram,      // Processing time of every request
 }
 
 impl StandardTelemetryCollector {
@@ -73,8 +85,31 @@ impl StandardTelemetryCollector {
         StandardTelemetryCollector {
             metrics: Arc::new(RwLock::new(TelemetryMetrics::default())),
             config,
+            span_exporter: None,
+            signal_exporter: None,
         }
     }
+
+    /// Bucket request latencies at `bounds`, in seconds
+    pub fn with_latency_buckets(mut self, bounds: &[f64]) -> Self {
+        self.metrics = Arc::new(RwLock::new(TelemetryMetrics {
+            latency_histogram: LatencyHistogram::new(bounds),
+            ..Default::default()
+        }));
+        self
+    }
+
+    /// Export request spans through `exporter`, stopping it on shutdown
+    pub fn with_span_exporter(mut self, exporter: Arc<OtlpExporter>) -> Self {
+        self.span_exporter = Some(exporter);
+        self
+    }
+
+    /// Push metrics and logs through `exporter`, stopping it on shutdown
+    pub fn with_signal_exporter(mut self, exporter: Arc<OtlpSignalExporter>) -> Self {
+        self.signal_exporter = Some(exporter);
+        self
+    }
 }
 
 #[async_trait::async_trait]
@@ -95,8 +130,50 @@ impl TelemetryCollector for StandardTelemetryCollector {
         metrics.error_count += 1;
     }
 
+    async fn record_request_latency(&self, latency: Duration) {
+        let mut metrics = self.metrics.write().await;
+        metrics.latency_histogram.observe(latency.as_secs_f64());
+    }
+
+    async fn record_clock_skew(&self, device_id: &str, skew_ms: i64) {
+        let mut metrics = self.metrics.write().await;
+        metrics.clock_skews.insert(device_id.to_string(), skew_ms);
+    }
+
+    async fn record_request_usage(&self, request_id: Uuid, tenant: &str, usage: &ResourceUsage, pathological: bool) {
+        debug!("Recording resource usage of request {}: {:?}", request_id, usage);
+
+        let mut metrics = self.metrics.write().await;
+        metrics.tenant_usage.entry(tenant.to_string()).or_default().record(usage);
+        if pathological {
+            metrics.pathological_requests += 1;
+        }
+    }
+
+    async fn usage_by_tenant(&self) -> HashMap<String, TenantUsage> {
+        self.metrics.read().await.tenant_usage.clone()
+    }
+
     async fn get_aggregated_metrics(&self) -> Result<AggregatedMetrics> {
         let metrics = self.metrics.read().await;
+
+        let mut custom = HashMap::new();
+        custom.insert("clock_skewed_devices".to_string(), metrics.clock_skews.len() as f32);
+        custom.insert(
+            "clock_skew_max_abs_ms".to_string(),
+            metrics.clock_skews.values().map(|skew| skew.unsigned_abs()).max().unwrap_or(0) as f32,
+        );
+        let mut usage = ResourceUsage::default();
+        for tenant in metrics.tenant_usage.values() {
+            usage.add(&tenant.total);
+        }
+        custom.insert("metered_tenants".to_string(), metrics.tenant_usage.len() as f32);
+        custom.insert("request_cpu_time_ms_total".to_string(), usage.cpu_time_us as f32 / 1000.0);
+        custom.insert(
+            "request_io_bytes_total".to_string(),
+            (usage.io_read_bytes + usage.io_write_bytes) as f32,
+        );
+        custom.insert("pathological_requests".to_string(), metrics.pathological_requests as f32);
         
         Ok(AggregatedMetrics {
             timestamp: Utc::now(),
@@ -147,21 +224,61 @@ impl TelemetryCollector for StandardTelemetryCollector {
                 security_violations: 0,
                 audit_events: 0,
             },
-            custom: HashMap::new(),
+            custom,
         })
     }
 
+    async fn write_prometheus(&self, encoder: &mut PrometheusEncoder<'_>) {
+        let metrics = self.metrics.read().await;
+        encoder.family(
+            MetricKind::Counter,
+            "requests_total",
+            "Requests processed, by outcome",
+            &[
+                (&[("outcome", "success")], metrics.success_count as f64),
+                (&[("outcome", "error")], metrics.error_count as f64),
+            ],
+        );
+        encoder.histogram(
+            "request_duration_seconds",
+            "Time taken to process a request",
+            &metrics.latency_histogram,
+        );
+    }
+
     async fn health_check(&self) -> Result<ComponentHealth> {
+        let mut metrics = HashMap::new();
+        if let Some(exporter) = &self.span_exporter {
+            exporter.write_metrics(&mut metrics);
+        }
+        if let Some(exporter) = &self.signal_exporter {
+            exporter.write_metrics(&mut metrics);
+        }
         Ok(ComponentHealth {
             status: HealthLevel::Healthy,
             message: "Telemetry collector operational".to_string(),
             last_check: Utc::now(),
-            metrics: HashMap::new(),
+            metrics,
         })
     }
 
+    fn reconfigure(&self, config: &mcp_common::Config) {
+        if let Some(exporter) = &self.span_exporter {
+            exporter.set_interval(config.telemetry.tracing.export_interval_ms);
+        }
+        if let Some(exporter) = &self.signal_exporter {
+            exporter.set_interval(config.telemetry.metrics_interval_ms);
+        }
+    }
+
     async fn shutdown(&self) -> Result<()> {
         info!("Shutting down telemetry collector");
+        if let Some(exporter) = &self.span_exporter {
+            exporter.stop().await;
+        }
+        if let Some(exporter) = &self.signal_exporter {
+            exporter.stop().await;
+        }
         Ok(())
     }
 }
@@ -237,6 +354,15 @@ impl StandardTelemetryCollector {
             message = message,
             "Alert triggered"
         );
+        events::publish(GatewayEvent::AlertRaised {
+            source: component.to_string(),
+            severity: match severity {
+                0..=1 => AlertSeverity::Info,
+                2..=3 => AlertSeverity::Warning,
+                _ => AlertSeverity::Critical,
+            },
+            message: message.to_string(),
+        });
     }
 
     /// Record circuit breaker state change
@@ -1,214 +1,73 @@
-#!/usr/bin/env cargo
-
 //! MCP WASM Edge Gateway - Working Demo
-//! 
+//!
 //! This demonstrates the core functionality without complex dependencies
 
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use serde_json::{json, Value};
+use mcp_wasm_edge_gateway::compat::{demo_gateway, demo_request};
+use serde_json::json;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("🚀 MCP WASM Edge Gateway Demo v0.1.0");
+async fn main() -> anyhow::Result<()> {
+    println!(
+        "🚀 MCP WASM Edge Gateway Demo v{}",
+        env!("CARGO_PKG_VERSION")
+    );
     println!("=====================================");
-    
+
     // Initialize core components
-    let gateway = EdgeGateway::new().await?;
-    
-    // Simulate some MCP requests
+    println!("🔧 Initializing gateway components...");
+    let gateway = demo_gateway().await?;
+    println!("   ✅ Model engine loaded");
+    println!("   ✅ Security manager active");
+    println!("   ✅ Telemetry collector ready");
+    println!("   ✅ Request router initialized");
+
     println!("\n📋 Processing MCP Requests:");
-    
+
     // Request 1: Text completion
-    let completion_request = json!({
-        "id": "req-001",
-        "method": "completion",
-        "params": {
-            "prompt": "What is edge computing?",
-            "max_tokens": 50
-        }
-    });
-    
+    let completion_request = demo_request(
+        "edge-demo",
+        "completion",
+        json!({ "prompt": "What is edge computing?", "max_tokens": 50 }),
+    );
+    println!(
+        "   🔄 Processing completion request: {}",
+        completion_request.id
+    );
     let response1 = gateway.process_request(completion_request).await?;
-    println!("✅ Completion: {}", response1["result"]["text"]);
-    
+    println!(
+        "✅ Completion: {}",
+        response1.result.unwrap_or_default()["text"]
+    );
+
     // Request 2: Embedding
-    let embedding_request = json!({
-        "id": "req-002", 
-        "method": "embedding",
-        "params": {
-            "text": "Edge computing brings AI to the edge"
-        }
-    });
-    
+    let embedding_request = demo_request(
+        "edge-demo",
+        "embedding",
+        json!({ "text": "Edge computing brings AI to the edge" }),
+    );
+    println!(
+        "   🔄 Processing embedding request: {}",
+        embedding_request.id
+    );
     let response2 = gateway.process_request(embedding_request).await?;
-    println!("✅ Embedding: {} dimensions", response2["result"]["embedding"].as_array().unwrap().len());
-    
+    println!("✅ Embedding: {}", response2.result.unwrap_or_default());
+
     // Show system stats
     println!("\n📊 Gateway Statistics:");
-    let stats = gateway.get_stats().await;
-    println!("   • Total requests: {}", stats["total_requests"]);
-    println!("   • Success rate: {:.1}%", stats["success_rate"]);
-    println!("   • Avg latency: {}ms", stats["avg_latency_ms"]);
-    println!("   • Memory usage: {}MB", stats["memory_usage_mb"]);
-    
+    for report in gateway.priority_latency() {
+        println!(
+            "   • {:?} priority requests: {}",
+            report.priority, report.requests
+        );
+        println!("   • Avg latency: {:.0}ms", report.avg_ms);
+        println!("   • p95 latency: {}ms", report.p95_ms);
+    }
+
     println!("\n🎯 Demo completed successfully!");
     println!("   ✨ Ultra-lightweight edge AI gateway is operational");
     println!("   🔒 Security validation: PASSED");
     println!("   📈 Performance metrics: HEALTHY");
     println!("   🌐 Ready for edge deployment");
-    
-    Ok(())
-}
 
-/// Simplified Edge Gateway implementation
-pub struct EdgeGateway {
-    stats: Arc<Mutex<GatewayStats>>,
-    model_engine: Arc<MockModelEngine>,
-}
-
-#[derive(Default, Clone)]
-pub struct GatewayStats {
-    pub total_requests: u32,
-    pub successful_requests: u32,
-    pub total_latency_ms: u32,
-}
-
-impl EdgeGateway {
-    pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        println!("🔧 Initializing gateway components...");
-        
-        let stats = Arc::new(Mutex::new(GatewayStats::default()));
-        let model_engine = Arc::new(MockModelEngine::new());
-        
-        println!("   ✅ Model engine loaded");
-        println!("   ✅ Security manager active");
-        println!("   ✅ Telemetry collector ready");
-        println!("   ✅ Request router initialized");
-        
-        Ok(Self {
-            stats,
-            model_engine,
-        })
-    }
-    
-    pub async fn process_request(&self, request: Value) -> Result<Value, Box<dyn std::error::Error>> {
-        let start_time = std::time::Instant::now();
-        
-        // Extract request details
-        let id = request["id"].as_str().unwrap_or("unknown");
-        let method = request["method"].as_str().unwrap_or("unknown");
-        
-        println!("   🔄 Processing {} request: {}", method, id);
-        
-        // Route to appropriate handler
-        let result = match method {
-            "completion" => {
-                let prompt = request["params"]["prompt"].as_str().unwrap_or("");
-                self.model_engine.generate_completion(prompt).await
-            },
-            "embedding" => {
-                let text = request["params"]["text"].as_str().unwrap_or("");
-                self.model_engine.generate_embedding(text).await
-            },
-            _ => {
-                json!({
-                    "error": "Unsupported method",
-                    "method": method
-                })
-            }
-        };
-        
-        // Update stats
-        let latency_ms = start_time.elapsed().as_millis() as u32;
-        {
-            let mut stats = self.stats.lock().await;
-            stats.total_requests += 1;
-            stats.successful_requests += 1;
-            stats.total_latency_ms += latency_ms;
-        }
-        
-        Ok(json!({
-            "id": id,
-            "result": result,
-            "timestamp": chrono::Utc::now().to_rfc3339(),
-            "latency_ms": latency_ms
-        }))
-    }
-    
-    pub async fn get_stats(&self) -> Value {
-        let stats = self.stats.lock().await;
-        let avg_latency = if stats.total_requests > 0 {
-            stats.total_latency_ms / stats.total_requests
-        } else {
-            0
-        };
-        
-        let success_rate = if stats.total_requests > 0 {
-            (stats.successful_requests as f32 / stats.total_requests as f32) * 100.0
-        } else {
-            100.0
-        };
-        
-        json!({
-            "total_requests": stats.total_requests,
-            "success_rate": success_rate,
-            "avg_latency_ms": avg_latency,
-            "memory_usage_mb": 64, // Mock value
-            "models_loaded": 2,
-            "queue_size": 0
-        })
-    }
-}
-
-/// Mock model engine for demonstration
-pub struct MockModelEngine;
-
-impl MockModelEngine {
-    pub fn new() -> Self {
-        Self
-    }
-    
-    pub async fn generate_completion(&self, prompt: &str) -> Value {
-        // Simulate processing time
-        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
-        
-        let response = match prompt.to_lowercase().as_str() {
-            p if p.contains("edge computing") => 
-                "Edge computing brings computation closer to data sources, reducing latency and improving efficiency.",
-            p if p.contains("ai") => 
-                "Artificial Intelligence enables machines to perform tasks that typically require human intelligence.",
-            _ => "I'm an edge AI model running on a resource-constrained device. I provide fast, local responses."
-        };
-        
-        json!({
-            "text": response,
-            "model": "edge-llm-v1",
-            "tokens_used": response.split_whitespace().count()
-        })
-    }
-    
-    pub async fn generate_embedding(&self, text: &str) -> Value {
-        // Simulate processing time
-        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
-        
-        // Generate mock 384-dimensional embedding
-        let embedding: Vec<f32> = (0..384)
-            .map(|i| ((i as f32 * 0.1) + text.len() as f32 * 0.01) % 2.0 - 1.0)
-            .collect();
-        
-        json!({
-            "embedding": embedding,
-            "model": "edge-embedding-v1",
-            "dimensions": 384,
-            "input_text": text
-        })
-    }
+    Ok(())
 }
-
-// Dependencies would be:
-// [dependencies]
-// tokio = { version = "1", features = ["full"] }
-// serde_json = "1.0"
-// chrono = { version = "0.4", features = ["serde"] }
\ No newline at end of file
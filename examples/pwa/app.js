@@ -0,0 +1,82 @@
+// Offline-capable page around the browser build of the gateway.
+//
+// Requests go through EdgeGateway.processRequest: the gateway's router picks
+// a target, cloud requests are sent with fetch, and anything that can't be
+// sent while offline is kept in IndexedDB. The queue is synced when the
+// browser comes back online, or by the service worker's background sync.
+
+import init, { EdgeGateway } from './pkg/mcp_gateway.js';
+import { CONFIG, SYNC_TAG } from './config.js';
+
+const $ = (id) => document.getElementById(id);
+
+await init();
+const gateway = await EdgeGateway.create(JSON.stringify(CONFIG));
+
+// No model runs in the page; requests routed to a local model get a canned
+// answer so the demo works without a cloud endpoint
+gateway.setLocalHandler(async (requestJson) => {
+  const request = JSON.parse(requestJson);
+  return { text: `Handled on device: ${request.params.prompt}` };
+});
+
+const telemetry = [];
+
+async function refresh() {
+  $('network').textContent = navigator.onLine ? 'online' : 'offline';
+  $('queue-size').textContent = await gateway.queueSize();
+  telemetry.push(...JSON.parse(gateway.drainTelemetry()));
+  const totals = JSON.parse(gateway.telemetryTotals());
+  $('telemetry').textContent = JSON.stringify({ totals, recent: telemetry.slice(-5) }, null, 2);
+}
+
+async function sync() {
+  try {
+    const synced = await gateway.syncQueue();
+    console.info(`Synced ${synced} queued requests`);
+  } catch (error) {
+    console.warn('Sync failed', error);
+  }
+  await refresh();
+}
+
+$('request-form').addEventListener('submit', async (event) => {
+  event.preventDefault();
+  const request = {
+    id: crypto.randomUUID(),
+    device_id: 'pwa-demo',
+    method: 'completion',
+    params: { prompt: $('prompt').value },
+    context: null,
+    timestamp: new Date().toISOString(),
+  };
+  try {
+    const response = JSON.parse(await gateway.processRequest(JSON.stringify(request)));
+    $('response').textContent = JSON.stringify(response, null, 2);
+    if (response.result?.status === 'queued') {
+      await requestBackgroundSync();
+    }
+  } catch (error) {
+    $('response').textContent = String(error);
+  }
+  await refresh();
+});
+
+$('sync').addEventListener('click', sync);
+window.addEventListener('online', sync);
+window.addEventListener('offline', refresh);
+
+// Let the service worker deliver the queue even if the page is closed
+// before the connection returns
+async function requestBackgroundSync() {
+  const registration = await navigator.serviceWorker?.ready;
+  if (registration && 'sync' in registration) {
+    await registration.sync.register(SYNC_TAG);
+  }
+}
+
+if ('serviceWorker' in navigator) {
+  navigator.serviceWorker.register('sw.js', { type: 'module' });
+}
+
+await refresh();
@@ -0,0 +1,21 @@
+// Gateway settings shared by the page and the service worker, laid over
+// the defaults like a configuration file. Both must name the same queue.
+
+export const CONFIG = {
+  router: {
+    cloud_endpoints: [
+      {
+        name: 'primary',
+        url: 'https://api.example.com/v1/mcp',
+        api_key: null,
+        timeout_ms: 10000,
+        max_retries: 3,
+      },
+    ],
+  },
+  queue: {
+    storage_path: 'indexeddb://mcp-pwa-queue',
+  },
+};
+
+export const SYNC_TAG = 'mcp-queue-sync';
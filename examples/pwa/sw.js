@@ -0,0 +1,62 @@
+// Service worker: serves the app shell offline and syncs the IndexedDB
+// queue when background sync fires.
+
+import init, { EdgeGateway } from './pkg/mcp_gateway.js';
+import { CONFIG, SYNC_TAG } from './config.js';
+
+const CACHE = 'mcp-pwa-v1';
+const SHELL = [
+  './',
+  'index.html',
+  'app.js',
+  'config.js',
+  'manifest.json',
+  'pkg/mcp_gateway.js',
+  'pkg/mcp_gateway_bg.wasm',
+];
+
+self.addEventListener('install', (event) => {
+  event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(SHELL)));
+  self.skipWaiting();
+});
+
+self.addEventListener('activate', (event) => {
+  event.waitUntil(
+    caches.keys().then((keys) =>
+      Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))),
+    ),
+  );
+  self.clients.claim();
+});
+
+self.addEventListener('fetch', (event) => {
+  if (event.request.method !== 'GET') {
+    return;
+  }
+  event.respondWith(
+    caches.match(event.request).then((cached) => cached ?? fetch(event.request)),
+  );
+});
+
+self.addEventListener('sync', (event) => {
+  if (event.tag === SYNC_TAG) {
+    event.waitUntil(syncQueue());
+  }
+});
+
+// The worker may be restarted between events, so each sync opens the
+// gateway again; the queue itself lives in IndexedDB
+async function syncQueue() {
+  await init();
+  const gateway = await EdgeGateway.create(JSON.stringify(CONFIG));
+  let remaining = await gateway.queueSize();
+  while (remaining > 0) {
+    await gateway.syncQueue();
+    const after = await gateway.queueSize();
+    if (after >= remaining) {
+      // Nothing was delivered; rejecting makes the browser retry later
+      throw new Error(`${after} requests still queued`);
+    }
+    remaining = after;
+  }
+}
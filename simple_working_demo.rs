@@ -1,105 +1,24 @@
-#!/usr/bin/env rust-script
-
 //! Simple working demo of MCP Edge Gateway core functionality
 //! This demonstrates that Generation 1 is WORKING
 
-use std::sync::Arc;
-use tokio::time::{Duration, sleep};
-
-// Mock the basic types we need for demo
-#[derive(Debug, Clone)]
-pub struct MCPRequest {
-    pub id: String,
-    pub content: String,
-    pub device_id: String,
-}
-
-#[derive(Debug, Clone)]
-pub struct MCPResponse {
-    pub id: String,
-    pub content: String,
-    pub status: String,
-}
-
-#[derive(Debug, Clone)]
-pub struct Config {
-    pub gateway: GatewayConfig,
-}
-
-#[derive(Debug, Clone)]
-pub struct GatewayConfig {
-    pub bind_address: String,
-    pub port: u16,
-    pub max_connections: u32,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            gateway: GatewayConfig {
-                bind_address: "0.0.0.0".to_string(),
-                port: 8080,
-                max_connections: 1000,
-            }
-        }
-    }
-}
-
-/// Mock Edge Gateway Core
-#[derive(Clone)]
-pub struct EdgeGateway {
-    config: Arc<Config>,
-    request_count: Arc<std::sync::atomic::AtomicU64>,
-}
-
-impl EdgeGateway {
-    pub fn new(config: Arc<Config>) -> Self {
-        Self {
-            config,
-            request_count: Arc::new(std::sync::atomic::AtomicU64::new(0)),
-        }
-    }
-    
-    pub async fn process_request(&self, request: MCPRequest) -> MCPResponse {
-        // Increment request counter
-        self.request_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        
-        println!("🔄 Processing MCP request: {} from device: {}", request.id, request.device_id);
-        
-        // Simulate edge processing with local model
-        sleep(Duration::from_millis(50)).await;
-        
-        // Smart routing logic - prefer local processing for edge devices
-        let response_content = if request.device_id.starts_with("edge") {
-            format!("✅ LOCAL_PROCESSED: {}", request.content)
-        } else {
-            format!("☁️ CLOUD_ROUTED: {}", request.content)
-        };
-        
-        MCPResponse {
-            id: request.id,
-            content: response_content,
-            status: "success".to_string(),
-        }
-    }
-    
-    pub fn get_metrics(&self) -> u64 {
-        self.request_count.load(std::sync::atomic::Ordering::Relaxed)
-    }
-}
+use mcp_wasm_edge_gateway::compat::{demo_gateway, demo_request};
+use serde_json::json;
 
 /// Demo edge device scenarios
-async fn run_edge_demo() {
+async fn run_edge_demo() -> anyhow::Result<()> {
     println!("🚀 Starting MCP WASM Edge Gateway Demo - Generation 1: MAKE IT WORK");
-    
-    let config = Arc::new(Config::default());
-    let gateway = EdgeGateway::new(config.clone());
-    
+
+    let gateway = demo_gateway().await?;
+    let config = gateway.config_manager().current();
+
     println!("📋 Configuration:");
-    println!("   • Bind Address: {}:{}", config.gateway.bind_address, config.gateway.port);
+    println!(
+        "   • Bind Address: {}:{}",
+        config.gateway.bind_address, config.gateway.port
+    );
     println!("   • Max Connections: {}", config.gateway.max_connections);
     println!();
-    
+
     // Test scenarios representing different edge devices
     let test_scenarios = vec![
         ("edge_001", "Analyze sensor data: temperature=25.3°C"),
@@ -108,36 +27,45 @@ async fn run_edge_demo() {
         ("iot_004", "Process image from security camera"),
         ("edge_005", "Real-time speech recognition"),
     ];
-    
+
     println!("🧪 Testing Edge Device Scenarios:");
-    
+
     for (device_id, content) in test_scenarios {
-        let request = MCPRequest {
-            id: format!("req_{}", uuid::Uuid::new_v4().to_string()[..8].to_string()),
-            content: content.to_string(),
-            device_id: device_id.to_string(),
-        };
-        
-        let response = gateway.process_request(request).await;
-        
-        println!("   Device: {} -> {}", device_id, response.content);
+        let request = demo_request(device_id, "completion", json!({ "prompt": content }));
+        println!(
+            "🔄 Processing MCP request: {} from device: {}",
+            request.id, request.device_id
+        );
+
+        match gateway.process_request(request).await {
+            Ok(response) => println!(
+                "   Device: {} -> {}",
+                device_id,
+                response.result.unwrap_or_default()
+            ),
+            Err(e) => println!("   Device: {} -> ❌ {}", device_id, e),
+        }
     }
-    
+
     println!();
     println!("📊 Gateway Metrics:");
-    println!("   • Total Requests Processed: {}", gateway.get_metrics());
-    println!("   • Average Response Time: ~50ms (simulated)");
+    for report in gateway.priority_latency() {
+        println!(
+            "   • {:?} priority: {} requests, avg {:.0}ms",
+            report.priority, report.requests, report.avg_ms
+        );
+    }
     println!("   • Memory Footprint: <3MB (target)");
     println!("   • Power Consumption: Optimized for battery devices");
     println!();
-    
+
     // Demonstrate offline capability
     println!("🔌 Testing Offline-First Capability:");
     println!("   • Queue Size: 1000 requests");
-    println!("   • Compression: zstd for bandwidth optimization"); 
+    println!("   • Compression: zstd for bandwidth optimization");
     println!("   • Sync Strategy: Batched uploads when connected");
     println!();
-    
+
     // Show hardware security features
     println!("🔒 Hardware Security Features:");
     println!("   • TPM 2.0 integration: Enabled");
@@ -145,7 +73,7 @@ async fn run_edge_demo() {
     println!("   • Hardware attestation: Required");
     println!("   • Encryption: AES-256-GCM with hardware acceleration");
     println!();
-    
+
     // Platform compatibility
     println!("🔧 Platform Compatibility:");
     println!("   ✅ Raspberry Pi 4 (ARM64)");
@@ -154,7 +82,7 @@ async fn run_edge_demo() {
     println!("   ✅ iPhone/Android (WASM deployment)");
     println!("   ✅ Docker containers (testing/dev)");
     println!();
-    
+
     println!("🎯 Key Features Demonstrated:");
     println!("   • ✅ Ultra-lightweight edge processing");
     println!("   • ✅ Intelligent local/cloud routing");
@@ -163,46 +91,13 @@ async fn run_edge_demo() {
     println!("   • ✅ Power-efficient operation");
     println!("   • ✅ Hardware security integration");
     println!();
-    
+
     println!("🎉 GENERATION 1 SUCCESS: Core functionality is WORKING!");
     println!("Ready for Generation 2: Enhanced robustness & monitoring");
-}
-
-// External dependencies would be added to Cargo.toml:
-// [dependencies]
-// uuid = { version = "1.0", features = ["v4"] }
-// tokio = { version = "1.0", features = ["full"] }
-
-// For now, let's use a simple UUID mock
-mod uuid {
-    pub struct Uuid(String);
-    impl Uuid {
-        pub fn new_v4() -> Self {
-            Self(format!("{:08x}-{:04x}-{:04x}-{:04x}-{:012x}", 
-                rand::random::<u32>(),
-                rand::random::<u16>(),
-                rand::random::<u16>(),
-                rand::random::<u16>(),
-                rand::random::<u64>() & 0xffffffffffff
-            ))
-        }
-        pub fn to_string(&self) -> String { self.0.clone() }
-    }
-}
-
-mod rand {
-    pub fn random<T>() -> T 
-    where T: From<u8> + std::ops::BitXor<Output=T> + Copy {
-        // Simple linear congruential generator for demo
-        static mut SEED: u64 = 1;
-        unsafe {
-            SEED = SEED.wrapping_mul(1103515245).wrapping_add(12345);
-            T::from((SEED / 65536) as u8)
-        }
-    }
+    Ok(())
 }
 
 #[tokio::main]
-async fn main() {
-    run_edge_demo().await;
-}
\ No newline at end of file
+async fn main() -> anyhow::Result<()> {
+    run_edge_demo().await
+}
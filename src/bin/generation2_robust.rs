@@ -1,7 +1,9 @@
 //! Generation 2: MAKE IT ROBUST (Reliable)
 //! Enhanced MCP Edge Gateway with comprehensive error handling, monitoring, and security
 
+use mcp_common::config::ResponseCacheConfig;
 use mcp_common::{Config, MCPRequest, MCPResponse, MCPError};
+use mcp_models::ResponseCache;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{info, error, debug, warn, instrument};
@@ -16,7 +18,7 @@ use std::time::{Duration, Instant};
 pub struct RobustGateway {
     _config: Arc<Config>,
     state: Arc<RwLock<GatewayState>>,
-    request_cache: Arc<RwLock<HashMap<String, CachedResponse>>>,
+    request_cache: Arc<ResponseCache>,
     metrics: Arc<RwLock<RobustMetrics>>,
     circuit_breakers: Arc<RwLock<HashMap<String, CircuitBreaker>>>,
     security_monitor: Arc<RwLock<SecurityMonitor>>,
@@ -34,14 +36,6 @@ pub struct GatewayState {
     pub uptime_seconds: u64,
 }
 
-#[derive(Debug, Clone)]
-pub struct CachedResponse {
-    pub response: MCPResponse,
-    pub cached_at: DateTime<Utc>,
-    pub ttl_seconds: u64,
-    pub hit_count: u64,
-}
-
 #[derive(Debug, Clone)]
 pub struct RobustMetrics {
     // Request metrics
@@ -213,7 +207,15 @@ impl RobustGateway {
             uptime_seconds: 0,
         }));
         
-        let request_cache = Arc::new(RwLock::new(HashMap::new()));
+        let request_cache = Arc::new(ResponseCache::new(ResponseCacheConfig {
+            enabled: true,
+            methods: vec![
+                "mcp.list_models".to_string(),
+                "mcp.ping".to_string(),
+                "mcp.health".to_string(),
+            ],
+            ..Default::default()
+        }));
         let metrics = Arc::new(RwLock::new(RobustMetrics {
             successful_requests: 0,
             failed_requests: 0,
@@ -488,21 +490,16 @@ impl RobustGateway {
 
     /// Internal request processing with comprehensive error handling
     async fn process_request_internal(&self, request: MCPRequest) -> anyhow::Result<MCPResponse> {
-        // Check cache first
-        let cache_key = format!("{}:{:?}:{}", request.method, request.params, request.device_id);
-        {
-            let mut cache = self.request_cache.write().await;
-            if let Some(cached) = cache.get_mut(&cache_key) {
-                if self.is_cache_valid(cached) {
-                    debug!("Cache hit for request: {}", request.id);
-                    cached.hit_count += 1;
-                    self.metrics.write().await.cache_hits += 1;
-                    return Ok(cached.response.clone());
-                } else {
-                    // Remove expired entry
-                    cache.remove(&cache_key);
-                }
-            }
+        // Check cache first; responses are cached per device
+        let cached = self
+            .request_cache
+            .get(&request.device_id, &request.method, &request.params)
+            .and_then(|cached| serde_json::from_value::<MCPResponse>(cached).ok());
+        if let Some(mut response) = cached {
+            debug!("Cache hit for request: {}", request.id);
+            self.metrics.write().await.cache_hits += 1;
+            response.id = request.id;
+            return Ok(response);
         }
         
         self.metrics.write().await.cache_misses += 1;
@@ -513,14 +510,14 @@ impl RobustGateway {
             match self.route_request(&request).await {
                 Ok(response) => {
                     // Cache successful responses
-                    if self.is_cacheable_method(&request.method) {
-                        let cached_response = CachedResponse {
-                            response: response.clone(),
-                            cached_at: Utc::now(),
-                            ttl_seconds: self.get_cache_ttl(&request.method),
-                            hit_count: 0,
-                        };
-                        self.request_cache.write().await.insert(cache_key, cached_response);
+                    if let Ok(cached) = serde_json::to_value(&response) {
+                        self.request_cache.insert_with_ttl(
+                            &request.device_id,
+                            &request.method,
+                            &request.params,
+                            cached,
+                            Duration::from_secs(self.get_cache_ttl(&request.method)),
+                        );
                     }
                     
                     if attempt > 1 {
@@ -1033,15 +1030,6 @@ impl RobustGateway {
         harmful_patterns.iter().any(|&pattern| content_lower.contains(pattern))
     }
 
-    fn is_cache_valid(&self, cached: &CachedResponse) -> bool {
-        let age = Utc::now().signed_duration_since(cached.cached_at).num_seconds() as u64;
-        age < cached.ttl_seconds
-    }
-
-    fn is_cacheable_method(&self, method: &str) -> bool {
-        matches!(method, "mcp.list_models" | "mcp.ping" | "mcp.health")
-    }
-
     fn get_cache_ttl(&self, method: &str) -> u64 {
         match method {
             "mcp.list_models" => 300,  // 5 minutes
@@ -0,0 +1,98 @@
+//! Migrate code from the skeleton types to the workspace crates
+//!
+//! Lists every skeleton type defined or used under the given paths. With
+//! `--write`, uses are replaced with the workspace types in place; local
+//! copies of the skeleton structs are left for a manual migration, guided by
+//! the notes printed for each. Exits with status 1 while findings remain.
+
+use mcp_wasm_edge_gateway::compat::{rewrite, scan, FindingKind};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage: migrate_skeleton [--write] <path>...";
+
+fn main() -> anyhow::Result<()> {
+    let mut write = false;
+    let mut roots = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--write" => write = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            },
+            _ => roots.push(PathBuf::from(arg)),
+        }
+    }
+    if roots.is_empty() {
+        anyhow::bail!(USAGE);
+    }
+
+    let mut files = Vec::new();
+    for root in &roots {
+        collect_sources(root, &mut files)?;
+    }
+
+    let mut remaining = 0;
+    let mut rewritten_files = 0;
+    for file in &files {
+        let mut source = std::fs::read_to_string(file)?;
+        if write {
+            let (rewritten, replaced) = rewrite(&source);
+            if replaced > 0 {
+                std::fs::write(file, &rewritten)?;
+                println!("{}: replaced {} uses", file.display(), replaced);
+                rewritten_files += 1;
+                source = rewritten;
+            }
+        }
+        for finding in scan(&source) {
+            let skeleton = finding.skeleton;
+            let action = match finding.kind {
+                FindingKind::Definition => format!("remove local struct {}", skeleton.name),
+                FindingKind::Use => format!("replace {}", skeleton.name),
+            };
+            println!(
+                "{}:{}: {} with mcp_wasm_edge_gateway::{} ({})",
+                file.display(),
+                finding.line,
+                action,
+                skeleton.replacement,
+                skeleton.note
+            );
+            remaining += 1;
+        }
+    }
+
+    println!(
+        "{} files scanned, {} rewritten, {} findings remaining",
+        files.len(),
+        rewritten_files,
+        remaining
+    );
+    if remaining > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Rust sources under `path`, skipping build output and hidden directories
+fn collect_sources(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if path.is_file() {
+        if path.extension().is_some_and(|extension| extension == "rs") {
+            files.push(path.to_path_buf());
+        }
+        return Ok(());
+    }
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+    for entry in entries {
+        let name = entry.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if entry.is_dir() && (name == "target" || name.starts_with('.')) {
+            continue;
+        }
+        collect_sources(&entry, files)?;
+    }
+    Ok(())
+}
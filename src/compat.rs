@@ -0,0 +1,326 @@
+//! Compatibility shim for code written against the skeleton types
+//!
+//! The first single-file versions of the gateway defined their own mock
+//! request, response, config and gateway structs, which have since drifted
+//! from the workspace crates. The deprecated aliases below point those names
+//! at the real types, so downstream code keeps compiling with a warning that
+//! names the replacement. The real types are re-exported here as well, so
+//! imports from this module still resolve once the names are replaced.
+//!
+//! [`scan`] and [`rewrite`] back the `migrate_skeleton` tool, which lists the
+//! skeleton names left in a source tree and replaces the ones it safely can.
+//! Demos that drove one of the mock gateways can run on [`demo_gateway`].
+
+pub use mcp_common::{Config, MCPRequest, MCPResponse};
+pub use mcp_gateway::Gateway;
+
+use mcp_common::config::{CloudEndpoint, QueueStorageKind};
+use mcp_common::Result;
+use mcp_gateway::testing::ScriptedCloudClient;
+use std::sync::Arc;
+
+/// Cloud requests the [`demo_gateway`] endpoint answers before it fails
+pub const DEMO_CLOUD_ANSWERS: usize = 64;
+
+#[deprecated(
+    since = "0.1.0",
+    note = "use `mcp_wasm_edge_gateway::MCPRequest`; `params` is a map of named values"
+)]
+pub type SimpleMCPRequest = MCPRequest;
+
+#[deprecated(
+    since = "0.1.0",
+    note = "use `mcp_wasm_edge_gateway::MCPResponse`; `result` is optional and `metadata` is gone"
+)]
+pub type SimpleMCPResponse = MCPResponse;
+
+#[deprecated(
+    since = "0.1.0",
+    note = "use `mcp_wasm_edge_gateway::Config`; gateway settings live under `gateway`"
+)]
+pub type SimpleGatewayConfig = Config;
+
+#[deprecated(
+    since = "0.1.0",
+    note = "use `mcp_wasm_edge_gateway::Config`; gateway settings live under `gateway`"
+)]
+pub type EdgeConfig = Config;
+
+#[deprecated(since = "0.1.0", note = "use `mcp_wasm_edge_gateway::Gateway`")]
+pub type SimpleEdgeGateway = Gateway;
+
+#[deprecated(since = "0.1.0", note = "use `mcp_wasm_edge_gateway::Gateway`")]
+pub type MCPEdgeGateway = Gateway;
+
+#[deprecated(since = "0.1.0", note = "use `mcp_wasm_edge_gateway::Gateway`")]
+pub type EdgeGateway = Gateway;
+
+/// Gateway standing in for the skeleton's mock gateways: in-memory storage,
+/// a stub model engine and a scripted cloud endpoint answering the first
+/// [`DEMO_CLOUD_ANSWERS`] requests, so demos run without model files or a
+/// network
+pub async fn demo_gateway() -> Result<Gateway> {
+    let mut config = Config::default();
+    config.queue.storage_backend = QueueStorageKind::Memory;
+    config.router.cloud_endpoints = vec![CloudEndpoint {
+        name: "cloud-fallback".to_string(),
+        url: "https://cloud.invalid".to_string(),
+        api_key: None,
+        timeout_ms: 1000,
+        max_retries: 0,
+        connect_timeout_ms: None,
+        region: None,
+        provider: Default::default(),
+        compression: Default::default(),
+    }];
+    let cloud = Arc::new(ScriptedCloudClient::new());
+    for _ in 0..DEMO_CLOUD_ANSWERS {
+        cloud.push_response(serde_json::json!({ "text": "answered by the demo cloud endpoint" }));
+    }
+    Gateway::builder(config)
+        .with_cloud_transport(cloud)
+        .deterministic()
+        .build()
+        .await
+}
+
+/// Request from `device_id` with the fields of the `params` object
+pub fn demo_request(device_id: &str, method: &str, params: serde_json::Value) -> MCPRequest {
+    let params = match params {
+        serde_json::Value::Object(params) => params.into_iter().collect(),
+        _ => Default::default(),
+    };
+    MCPRequest {
+        id: uuid::Uuid::new_v4(),
+        device_id: device_id.to_string(),
+        method: method.to_string(),
+        params,
+        context: None,
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+/// Skeleton type and the workspace type that replaces it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SkeletonType {
+    pub name: &'static str,
+    pub replacement: &'static str,
+    /// What changed beyond the name
+    pub note: &'static str,
+}
+
+/// Every type the skeleton defined, including local copies of the real names
+pub const SKELETON_TYPES: &[SkeletonType] = &[
+    SkeletonType {
+        name: "SimpleMCPRequest",
+        replacement: "MCPRequest",
+        note: "`params` is a map of named values and requests carry a `device_id`",
+    },
+    SkeletonType {
+        name: "SimpleMCPResponse",
+        replacement: "MCPResponse",
+        note: "`result` is optional, `error` is an `MCPError` and `metadata` belongs in the result",
+    },
+    SkeletonType {
+        name: "SimpleGatewayConfig",
+        replacement: "Config",
+        note: "gateway settings live under `gateway`, fallback under `router`",
+    },
+    SkeletonType {
+        name: "EdgeConfig",
+        replacement: "Config",
+        note: "gateway settings live under `gateway`, fallback under `router`",
+    },
+    SkeletonType {
+        name: "SimpleEdgeGateway",
+        replacement: "Gateway",
+        note: "build it with `Gateway::new(config).await`",
+    },
+    SkeletonType {
+        name: "MCPEdgeGateway",
+        replacement: "Gateway",
+        note: "build it with `Gateway::new(config).await`",
+    },
+    SkeletonType {
+        name: "EdgeGateway",
+        replacement: "Gateway",
+        note: "build it with `Gateway::new(config).await`",
+    },
+    SkeletonType {
+        name: "MCPRequest",
+        replacement: "MCPRequest",
+        note: "the local copy shadows the real type",
+    },
+    SkeletonType {
+        name: "MCPResponse",
+        replacement: "MCPResponse",
+        note: "the local copy shadows the real type",
+    },
+];
+
+/// How a skeleton type appears in a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    /// The file defines its own copy, which has to be removed by hand
+    Definition,
+    /// The file uses the name, which [`rewrite`] replaces
+    Use,
+}
+
+/// Skeleton type found in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// 1-based line number
+    pub line: usize,
+    pub kind: FindingKind,
+    pub skeleton: SkeletonType,
+}
+
+/// Skeleton types defined or used in `source`; uses of a name the file
+/// defines itself are covered by the definition
+pub fn scan(source: &str) -> Vec<Finding> {
+    let defined = defined_types(source);
+    let mut findings = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let words = identifiers(line);
+        for (position, (_, word)) in words.iter().enumerate() {
+            let Some(skeleton) = SKELETON_TYPES
+                .iter()
+                .find(|skeleton| skeleton.name == *word)
+            else {
+                continue;
+            };
+            let kind = if position > 0 && words[position - 1].1 == "struct" {
+                FindingKind::Definition
+            } else if skeleton.name != skeleton.replacement && !defined.contains(&skeleton.name) {
+                FindingKind::Use
+            } else {
+                continue;
+            };
+            findings.push(Finding {
+                line: index + 1,
+                kind,
+                skeleton: *skeleton,
+            });
+        }
+    }
+    findings
+}
+
+/// Replace uses of skeleton names with their workspace types, leaving names
+/// the file defines itself for a manual migration; returns the new source and
+/// the number of replacements
+pub fn rewrite(source: &str) -> (String, usize) {
+    let defined = defined_types(source);
+    let mut rewritten = String::with_capacity(source.len());
+    let mut replaced = 0;
+    let mut copied = 0;
+    for (start, word) in identifiers(source) {
+        let replacement = SKELETON_TYPES
+            .iter()
+            .find(|skeleton| skeleton.name == word && skeleton.name != skeleton.replacement)
+            .filter(|skeleton| !defined.contains(&skeleton.name));
+        if let Some(skeleton) = replacement {
+            rewritten.push_str(&source[copied..start]);
+            rewritten.push_str(skeleton.replacement);
+            copied = start + word.len();
+            replaced += 1;
+        }
+    }
+    rewritten.push_str(&source[copied..]);
+    (rewritten, replaced)
+}
+
+/// Skeleton type names `source` defines a struct for
+fn defined_types(source: &str) -> Vec<&'static str> {
+    let words = identifiers(source);
+    words
+        .windows(2)
+        .filter(|pair| pair[0].1 == "struct")
+        .filter_map(|pair| {
+            SKELETON_TYPES
+                .iter()
+                .find(|skeleton| skeleton.name == pair[1].1)
+        })
+        .map(|skeleton| skeleton.name)
+        .collect()
+}
+
+/// Identifiers in `text` with their byte offsets
+fn identifiers(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (offset, c) in text.char_indices() {
+        let part_of_word = c.is_alphanumeric() || c == '_';
+        match (part_of_word, start) {
+            (true, None) => start = Some(offset),
+            (false, Some(begin)) => {
+                words.push((begin, &text[begin..offset]));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    if let Some(begin) = start {
+        words.push((begin, &text[begin..]));
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uses_are_rewritten_and_imports_still_resolve() {
+        let source = "use mcp_wasm_edge_gateway::compat::{SimpleMCPRequest, EdgeConfig};\n\
+                      fn handle(request: SimpleMCPRequest, config: &EdgeConfig) {}\n\
+                      struct SimpleMCPRequestQueue;\n";
+        let findings = scan(source);
+        assert_eq!(findings.len(), 4);
+        assert!(findings
+            .iter()
+            .all(|finding| finding.kind == FindingKind::Use));
+
+        let (rewritten, replaced) = rewrite(source);
+        assert_eq!(replaced, 4);
+        assert!(rewritten.starts_with("use mcp_wasm_edge_gateway::compat::{MCPRequest, Config};"));
+        assert!(rewritten.contains("fn handle(request: MCPRequest, config: &Config)"));
+        // Longer identifiers that merely start with a skeleton name are kept
+        assert!(rewritten.contains("struct SimpleMCPRequestQueue;"));
+        assert!(scan(&rewritten).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_demo_gateway_answers_local_and_cloud_requests() {
+        let gateway = demo_gateway().await.unwrap();
+        for method in ["embedding", "completion"] {
+            let request = demo_request(
+                "demo-device",
+                method,
+                serde_json::json!({ "text": "valve 3" }),
+            );
+            let request_id = request.id;
+            let response = gateway.process_request(request).await.unwrap();
+            assert_eq!(response.id, request_id);
+            assert!(response.result.is_some());
+        }
+    }
+
+    #[test]
+    fn test_local_definitions_are_reported_and_left_alone() {
+        let source = "pub struct SimpleMCPResponse { pub metadata: String }\n\
+                      pub struct MCPRequest { pub params: String }\n\
+                      fn reply() -> SimpleMCPResponse { todo!() }\n";
+        let findings = scan(source);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].line, 1);
+        assert_eq!(findings[0].kind, FindingKind::Definition);
+        assert_eq!(findings[0].skeleton.replacement, "MCPResponse");
+        assert_eq!(findings[1].skeleton.name, "MCPRequest");
+
+        let (rewritten, replaced) = rewrite(source);
+        assert_eq!(replaced, 0);
+        assert_eq!(rewritten, source);
+    }
+}
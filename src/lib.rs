@@ -1,5 +1,6 @@
 pub use mcp_gateway::*;
 pub use mcp_common::*;
 
+pub mod compat;
 pub mod performance_optimizer;
-pub mod security_hardening;
\ No newline at end of file
+pub mod security_hardening;
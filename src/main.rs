@@ -1,5 +1,4 @@
-use mcp_common::Config;
-use mcp_gateway::Gateway;
+use mcp_gateway::{config_reload, Gateway};
 use std::sync::Arc;
 use tracing::{error, info};
 
@@ -11,7 +10,7 @@ async fn main() -> anyhow::Result<()> {
     info!("Starting MCP WASM Edge Gateway v0.1.0");
 
     // Load configuration
-    let config = Arc::new(Config::default());
+    let config = Arc::new(config_reload::load_from_env()?);
     
     info!("Loaded configuration: bind_address={}:{}", 
           config.gateway.bind_address, config.gateway.port);
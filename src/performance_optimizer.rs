@@ -161,7 +161,9 @@ impl PerformanceOptimizer {
     where 
         T: serde::de::DeserializeOwned,
     {
-        let mut cache = self.cache.write().await;
+        let mut guard = self.cache.write().await;
+        // Borrow the fields separately rather than through the guard
+        let cache = &mut *guard;
         
         if let Some(entry) = cache.entries.get_mut(key) {
             // Update access pattern
@@ -182,9 +184,9 @@ impl PerformanceOptimizer {
 
             // Check TTL
             if entry.created_at.elapsed() < entry.ttl {
-                if let Ok(data) = bincode::decode_from_slice(&entry.data, bincode::config::standard()) {
+                if let Ok(data) = serde_json::from_slice(&entry.data) {
                     debug!("Cache hit for key: {}", key);
-                    return Some(data.0);
+                    return Some(data);
                 }
             }
         }
@@ -199,7 +201,7 @@ impl PerformanceOptimizer {
     where 
         T: serde::Serialize,
     {
-        let serialized = match bincode::encode_to_vec(data, bincode::config::standard()) {
+        let serialized = match serde_json::to_vec(data) {
             Ok(data) => data,
             Err(e) => {
                 warn!("Failed to serialize cache data for key {}: {}", key, e);
@@ -580,7 +582,6 @@ mod tests {
         let metrics = optimizer.get_performance_metrics().await;
         
         assert!(metrics.cache_entries > 0);
-        assert!(metrics.cache_size_mb >= 0);
         assert!(metrics.cache_hit_ratio >= 0.0 && metrics.cache_hit_ratio <= 1.0);
     }
 }
\ No newline at end of file
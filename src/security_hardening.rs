@@ -580,31 +580,34 @@ impl InputValidator {
         }
     }
 
-    async fn validate_json_recursive(
-        &self,
-        value: &serde_json::Value,
-        path: &str,
-        violations: &mut Vec<SecurityViolation>,
-        threat_level: &mut ThreatSeverity,
-    ) {
-        match value {
-            serde_json::Value::String(s) => {
-                self.validate_string_field(s, path, violations, threat_level).await;
-            }
-            serde_json::Value::Object(obj) => {
-                for (key, val) in obj {
-                    let new_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
-                    self.validate_json_recursive(val, &new_path, violations, threat_level).await;
+    /// Boxed, as async recursion needs an indirection
+    fn validate_json_recursive<'a>(
+        &'a self,
+        value: &'a serde_json::Value,
+        path: &'a str,
+        violations: &'a mut Vec<SecurityViolation>,
+        threat_level: &'a mut ThreatSeverity,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send + 'a>> {
+        Box::pin(async move {
+            match value {
+                serde_json::Value::String(s) => {
+                    self.validate_string_field(s, path, violations, threat_level).await;
                 }
-            }
-            serde_json::Value::Array(arr) => {
-                for (i, val) in arr.iter().enumerate() {
-                    let new_path = format!("{}[{}]", path, i);
-                    self.validate_json_recursive(val, &new_path, violations, threat_level).await;
+                serde_json::Value::Object(obj) => {
+                    for (key, val) in obj {
+                        let new_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                        self.validate_json_recursive(val, &new_path, violations, threat_level).await;
+                    }
                 }
+                serde_json::Value::Array(arr) => {
+                    for (i, val) in arr.iter().enumerate() {
+                        let new_path = format!("{}[{}]", path, i);
+                        self.validate_json_recursive(val, &new_path, violations, threat_level).await;
+                    }
+                }
+                _ => {} // Numbers, booleans, null are generally safe
             }
-            _ => {} // Numbers, booleans, null are generally safe
-        }
+        })
     }
 
     async fn validate_string_field(
@@ -897,7 +900,7 @@ impl AuditLogger {
         let sensitive_patterns = vec![
             regex::Regex::new(r"\b\d{4}[-\s]?\d{4}[-\s]?\d{4}[-\s]?\d{4}\b").unwrap(), // Credit card
             regex::Regex::new(r"\b\d{3}-\d{2}-\d{4}\b").unwrap(), // SSN
-            regex::Regex::new(r"password[\s]*[:=][\s]*['\"]?([^'\"\\s]+)").unwrap(), // Password
+            regex::Regex::new(r#"password[\s]*[:=][\s]*['"]?([^'"\s]+)"#).unwrap(), // Password
         ];
 
         Self {